@@ -0,0 +1,10 @@
+CREATE TABLE job_usage (
+    job_id VARCHAR REFERENCES job_configs(id) ON DELETE CASCADE NOT NULL,
+    day VARCHAR(10) NOT NULL,
+    cpu_seconds DOUBLE PRECISION NOT NULL DEFAULT 0,
+    max_memory_bytes BIGINT NOT NULL DEFAULT 0,
+    state_bytes BIGINT NOT NULL DEFAULT 0,
+    network_bytes BIGINT NOT NULL DEFAULT 0,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (job_id, day)
+);
@@ -0,0 +1,18 @@
+CREATE TABLE savepoints (
+    pub_id VARCHAR PRIMARY KEY,
+    organization_id VARCHAR NOT NULL,
+    created_by VARCHAR NOT NULL,
+    created_at TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    updated_at TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    name TEXT NOT NULL,
+    -- savepoints outlive the pipelines and jobs they were taken from
+    pipeline_id VARCHAR NOT NULL,
+    job_id VARCHAR NOT NULL,
+    state TEXT DEFAULT 'pending' NOT NULL,
+    epoch INTEGER,
+    failure_message TEXT,
+
+    UNIQUE(organization_id, name)
+);
+
+ALTER TABLE job_configs ADD COLUMN restore_savepoint_id VARCHAR;
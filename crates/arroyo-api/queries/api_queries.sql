@@ -185,10 +185,18 @@ SET
    restart_mode = :mode
 WHERE id = :job_id AND organization_id = :organization_id;
 
---! create_job(ttl_micros?)
+--! update_source_offset_overrides(source_offset_overrides?)
+UPDATE job_configs
+SET
+   updated_at = :updated_at,
+   updated_by = :updated_by,
+   source_offset_overrides = :source_offset_overrides
+WHERE id = :job_id AND organization_id = :organization_id;
+
+--! create_job(ttl_micros?, restore_savepoint_id?)
 INSERT INTO job_configs
-(id, organization_id, pipeline_name, created_by, pipeline_id, checkpoint_interval_micros, ttl_micros)
-VALUES (:id, :organization_id, :pipeline_name, :created_by, :pipeline_id, :checkpoint_interval_micros, :ttl_micros);
+(id, organization_id, pipeline_name, created_by, pipeline_id, checkpoint_interval_micros, ttl_micros, restore_savepoint_id)
+VALUES (:id, :organization_id, :pipeline_name, :created_by, :pipeline_id, :checkpoint_interval_micros, :ttl_micros, :restore_savepoint_id);
 
 --! create_job_status
 INSERT INTO job_statuses (pub_id, id, organization_id) VALUES (:pub_id, :id, :organization_id);
@@ -244,6 +252,28 @@ WHERE job_configs.id = :job_id
     AND state != 'failed'
 ORDER BY epoch;
 
+--! get_finished_job_checkpoints_after: DbCheckpoint
+SELECT epoch, state_backend, start_time, finish_time, operators FROM checkpoints
+JOIN job_configs ON checkpoints.job_id = job_configs.id
+WHERE job_configs.id = :job_id
+    AND checkpoints.organization_id = :organization_id
+    AND epoch > :after_epoch
+    AND finish_time IS NOT NULL
+    AND state != 'compacted'
+    AND state != 'failed'
+ORDER BY epoch;
+
+--: DbJobCheckpoint (finish_time?, operators?)
+
+--! get_all_job_checkpoints: DbJobCheckpoint
+SELECT job_configs.id AS job_id, epoch, state_backend, start_time, finish_time, operators FROM checkpoints
+JOIN job_configs ON checkpoints.job_id = job_configs.id
+WHERE job_configs.organization_id = :organization_id
+    AND job_configs.ttl_micros IS NULL
+    AND state != 'compacted'
+    AND state != 'failed'
+ORDER BY job_configs.id, epoch;
+
 --! get_job_checkpoint: DbCheckpoint
 SELECT epoch, state_backend, start_time, finish_time, operators FROM checkpoints
 JOIN job_configs ON checkpoints.job_id = job_configs.id
@@ -253,6 +283,14 @@ WHERE job_configs.id = :job_id
     AND state != 'failed'
     AND checkpoints.pub_id = :checkpoint_pub_id;
 
+--! get_last_successful_checkpoint
+SELECT pub_id, epoch FROM checkpoints
+WHERE job_id = :job_id
+    AND organization_id = :organization_id
+    AND (state = 'ready' OR state = 'committing')
+ORDER BY epoch DESC
+LIMIT 1;
+
 --! get_checkpoint_details: (finish_time?, operators?)
 SELECT epoch, state_backend, start_time, finish_time, operators FROM checkpoints
 WHERE job_id = :job_id
@@ -269,7 +307,7 @@ DELETE FROM pipelines WHERE pipelines.id = (
 --: DbLogMessage (operator_id?, task_index?)
 
 --! get_operator_errors : DbLogMessage
-SELECT jlm.pub_id, jlm.job_id, jlm.operator_id, jlm.task_index, jlm.created_at, jlm.log_level, jlm.message, jlm.details
+SELECT jlm.id, jlm.pub_id, jlm.job_id, jlm.operator_id, jlm.task_index, jlm.created_at, jlm.log_level, jlm.message, jlm.details
 FROM job_log_messages jlm
 JOIN job_configs ON job_configs.id = jlm.job_id
 WHERE job_configs.organization_id = :organization_id AND job_configs.id = :job_id
@@ -281,30 +319,81 @@ WHERE job_configs.organization_id = :organization_id AND job_configs.id = :job_i
 ORDER BY jlm.created_at DESC
 LIMIT cast(:limit as integer);
 
+--! get_operator_errors_after : DbLogMessage
+SELECT jlm.id, jlm.pub_id, jlm.job_id, jlm.operator_id, jlm.task_index, jlm.created_at, jlm.log_level, jlm.message, jlm.details
+FROM job_log_messages jlm
+JOIN job_configs ON job_configs.id = jlm.job_id
+WHERE job_configs.organization_id = :organization_id AND job_configs.id = :job_id
+  AND jlm.log_level = 'error'
+  AND jlm.id > :after_id
+ORDER BY jlm.id
+LIMIT cast(:limit as integer);
+
+--! get_pipeline_usage : DbPipelineUsage
+SELECT job_usage.day,
+    SUM(job_usage.cpu_seconds) as cpu_seconds,
+    MAX(job_usage.max_memory_bytes) as max_memory_bytes,
+    MAX(job_usage.state_bytes) as state_bytes,
+    CAST(SUM(job_usage.network_bytes) AS BIGINT) as network_bytes
+FROM job_usage
+JOIN job_configs ON job_configs.id = job_usage.job_id
+JOIN pipelines ON pipelines.id = job_configs.pipeline_id
+WHERE job_configs.organization_id = :organization_id AND pipelines.pub_id = :pub_id
+GROUP BY job_usage.day
+ORDER BY job_usage.day;
+
 
 ----------- udfs -----------------------
 
---: DbUdf (description?, dylib_url?)
+--: DbUdf (description?, dylib_url?, cargo_lock?)
 
---! create_udf (dylib_url?)
-INSERT INTO udfs (pub_id, organization_id, created_by, prefix, name, language, definition, description, dylib_url)
-VALUES (:pub_id, :organization_id, :created_by, :prefix, :name,  :language, :definition, :description, :dylib_url);
+--! create_udf (dylib_url?, cargo_lock?)
+INSERT INTO udfs (pub_id, organization_id, created_by, prefix, name, language, definition, description, dylib_url, cargo_lock)
+VALUES (:pub_id, :organization_id, :created_by, :prefix, :name,  :language, :definition, :description, :dylib_url, :cargo_lock);
     
 --! get_udf: DbUdf
-SELECT pub_id, prefix, name, language, definition, created_at, updated_at, description, dylib_url
+SELECT pub_id, prefix, name, language, definition, created_at, updated_at, description, dylib_url, cargo_lock
 FROM udfs
 WHERE organization_id = :organization_id AND pub_id = :pub_id;
 
 --! get_udf_by_name: DbUdf
-SELECT pub_id, prefix, name, language, definition, created_at, updated_at, description, dylib_url
+SELECT pub_id, prefix, name, language, definition, created_at, updated_at, description, dylib_url, cargo_lock
 FROM udfs
 WHERE organization_id = :organization_id AND name = :name;
 
 --! get_udfs: DbUdf
-SELECT pub_id, prefix, name, language, definition, created_at, updated_at, description, dylib_url
+SELECT pub_id, prefix, name, language, definition, created_at, updated_at, description, dylib_url, cargo_lock
 FROM udfs
 WHERE organization_id = :organization_id;
 
 --! delete_udf
 DELETE FROM udfs
 WHERE organization_id = :organization_id AND pub_id = :pub_id;
+
+
+----------- savepoints -----------------------
+
+--: DbSavepoint (epoch?, failure_message?)
+
+--! create_savepoint
+INSERT INTO savepoints (pub_id, organization_id, created_by, name, pipeline_id, job_id)
+VALUES (:pub_id, :organization_id, :created_by, :name, :pipeline_id, :job_id);
+
+--! get_savepoint: DbSavepoint
+SELECT pub_id, name, pipeline_id, job_id, state, epoch, created_at, failure_message
+FROM savepoints
+WHERE organization_id = :organization_id AND pub_id = :pub_id;
+
+--! get_pipeline_savepoints: DbSavepoint
+SELECT pub_id, name, pipeline_id, job_id, state, epoch, created_at, failure_message
+FROM savepoints
+WHERE organization_id = :organization_id AND pipeline_id = :pipeline_id
+ORDER BY created_at DESC;
+
+--! fail_savepoint
+UPDATE savepoints
+SET
+    state = 'failed',
+    failure_message = :failure_message,
+    updated_at = CURRENT_TIMESTAMP
+WHERE pub_id = :pub_id;
@@ -0,0 +1,11 @@
+CREATE TABLE job_usage (
+    job_id TEXT NOT NULL,
+    day TEXT NOT NULL,
+    cpu_seconds REAL NOT NULL DEFAULT 0,
+    max_memory_bytes INTEGER NOT NULL DEFAULT 0,
+    state_bytes INTEGER NOT NULL DEFAULT 0,
+    network_bytes INTEGER NOT NULL DEFAULT 0,
+    updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    PRIMARY KEY (job_id, day),
+    FOREIGN KEY (job_id) REFERENCES job_configs(id) ON DELETE CASCADE
+);
@@ -0,0 +1 @@
+ALTER TABLE job_configs ADD COLUMN source_offset_overrides TEXT;
@@ -0,0 +1,17 @@
+CREATE TABLE savepoints (
+    pub_id TEXT PRIMARY KEY,
+    organization_id TEXT NOT NULL,
+    created_by TEXT NOT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    name TEXT NOT NULL,
+    -- savepoints outlive the pipelines and jobs they were taken from
+    pipeline_id TEXT NOT NULL,
+    job_id TEXT NOT NULL,
+    state TEXT DEFAULT 'pending' NOT NULL,
+    epoch INTEGER,
+    failure_message TEXT,
+    UNIQUE (organization_id, name)
+);
+
+ALTER TABLE job_configs ADD COLUMN restore_savepoint_id TEXT;
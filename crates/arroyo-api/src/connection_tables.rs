@@ -16,14 +16,15 @@ use tracing::debug;
 use arroyo_connectors::confluent::ConfluentProfile;
 use arroyo_connectors::connector_for_type;
 use arroyo_connectors::kafka::{KafkaConfig, KafkaTable, SchemaRegistry};
+use arroyo_connectors::postgres_cdc::{self, PostgresCdcTable, PostgresConfig};
 use arroyo_formats::{avro, json, proto};
 use arroyo_operator::connector::ErasedConnector;
 use arroyo_rpc::api_types::connections::{
     ConnectionProfile, ConnectionSchema, ConnectionTable, ConnectionTablePost, ConnectionType,
-    SchemaDefinition, SourceField,
+    SchemaDefinition, SchemaInferenceQueryParams, SourceField,
 };
 use arroyo_rpc::api_types::{ConnectionTableCollection, PaginationQueryParams};
-use arroyo_rpc::formats::{AvroFormat, Format, JsonFormat, ProtobufFormat};
+use arroyo_rpc::formats::{AvroFormat, Format, JsonFormat, ProtobufFormat, XmlPath};
 use arroyo_rpc::public_ids::{generate_id, IdTypes};
 use arroyo_rpc::schema_resolver::{
     ConfluentSchemaRegistry, ConfluentSchemaSubjectResponse, ConfluentSchemaType,
@@ -434,6 +435,10 @@ pub(crate) async fn expand_schema(
     profile_config: &Value,
     table_config: &Value,
 ) -> Result<ConnectionSchema, ErrorResp> {
+    if connector == "postgres_cdc" && schema.fields.is_empty() {
+        return expand_postgres_cdc_schema(schema, profile_config, table_config).await;
+    }
+
     let Some(format) = schema.format.as_ref() else {
         return Ok(schema);
     };
@@ -463,6 +468,17 @@ pub(crate) async fn expand_schema(
         Format::Parquet(_) => Ok(schema),
         Format::RawString(_) => Ok(schema),
         Format::RawBytes(_) => Ok(schema),
+        Format::ArrowIpc(_) => Ok(schema),
+        Format::Csv(_) => Ok(schema),
+        Format::Xml(xml) => {
+            if connection_type != ConnectionType::Source {
+                return Err(bad_request("The XML format can only be used for sources"));
+            }
+            for path in std::iter::once(&xml.record_path).chain(xml.fields.values()) {
+                XmlPath::parse(path).map_err(bad_request)?;
+            }
+            Ok(schema)
+        }
         Format::Protobuf(_) => {
             expand_proto_schema(
                 connector,
@@ -476,6 +492,28 @@ pub(crate) async fn expand_schema(
     }
 }
 
+async fn expand_postgres_cdc_schema(
+    mut schema: ConnectionSchema,
+    profile_config: &Value,
+    table_config: &Value,
+) -> Result<ConnectionSchema, ErrorResp> {
+    let profile: PostgresConfig = serde_json::from_value(profile_config.clone())
+        .map_err(|e| bad_request(format!("Invalid Postgres connection profile: {}", e)))?;
+    let table: PostgresCdcTable = serde_json::from_value(table_config.clone())
+        .map_err(|e| bad_request(format!("Invalid Postgres CDC table config: {}", e)))?;
+
+    schema.fields = postgres_cdc::infer_fields(&profile, &table)
+        .await
+        .map_err(|e| {
+            bad_request(format!(
+                "failed to read the schema of {}.{} from Postgres: {:#}",
+                table.schema_name, table.table_name, e
+            ))
+        })?;
+
+    Ok(schema)
+}
+
 async fn expand_avro_schema(
     connector: &str,
     connection_type: ConnectionType,
@@ -794,6 +832,74 @@ async fn get_schema(
     Ok(Some((resp, references)))
 }
 
+const DEFAULT_INFERENCE_SAMPLE_SIZE: u32 = 100;
+const MAX_INFERENCE_SAMPLE_SIZE: u32 = 1000;
+
+/// Infer the schema of a JSON source by sampling messages from it
+#[utoipa::path(
+    post,
+    path = "/v1/connection_tables/schemas/infer",
+    tag = "connection_tables",
+    params(
+        SchemaInferenceQueryParams
+    ),
+    request_body = ConnectionTablePost,
+    responses(
+        (status = 200, description = "Inferred schema", body = ConnectionSchema),
+    ),
+)]
+pub(crate) async fn infer_schema(
+    State(state): State<AppState>,
+    bearer_auth: BearerAuth,
+    query_params: Query<SchemaInferenceQueryParams>,
+    WithRejection(Json(req), _): WithRejection<Json<ConnectionTablePost>, ApiError>,
+) -> Result<Json<ConnectionSchema>, ErrorResp> {
+    let auth_data = authenticate(&state.database, bearer_auth).await?;
+
+    let (connector, _, profile, schema) =
+        get_and_validate_connector(&req, &auth_data, &state.database).await?;
+
+    let schema = schema.ok_or_else(|| required_field("schema"))?;
+    let Some(Format::Json(format)) = &schema.format else {
+        return Err(bad_request(
+            "Schemas can only be inferred for the JSON format",
+        ));
+    };
+
+    if connector.table_type(&profile, &req.config).unwrap() != ConnectionType::Source {
+        return Err(bad_request("Schemas can only be inferred for sources"));
+    }
+
+    let sample_size = query_params
+        .sample_size
+        .unwrap_or(DEFAULT_INFERENCE_SAMPLE_SIZE)
+        .clamp(1, MAX_INFERENCE_SAMPLE_SIZE);
+
+    let messages = connector
+        .sample(&profile, &req.config, sample_size as usize)
+        .map_err(|e| bad_request(format!("Failed to parse config: {:?}", e)))?
+        .await
+        .map_err(log_and_map)?
+        .map_err(|e| bad_request(format!("Failed to sample messages: {:#}", e)))?;
+
+    let arrow = json::infer_schema(format, schema.framing.as_ref(), &messages)
+        .map_err(|e| bad_request(format!("Failed to infer schema: {:#}", e)))?;
+
+    let fields: Vec<SourceField> = arrow
+        .fields
+        .iter()
+        .map(|f| (**f).clone().try_into())
+        .collect::<Result<_, String>>()
+        .map_err(|e| bad_request(format!("Inferred schema is not supported: {}", e)))?;
+
+    Ok(Json(ConnectionSchema {
+        fields,
+        definition: None,
+        inferred: None,
+        ..schema
+    }))
+}
+
 /// Test a Connection Schema
 #[utoipa::path(
     post,
@@ -841,3 +947,40 @@ pub(crate) async fn test_schema(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::StatusCode;
+
+    #[tokio::test]
+    async fn test_expand_postgres_cdc_schema_invalid_config() {
+        let schema = ConnectionSchema {
+            format: None,
+            bad_data: None,
+            framing: None,
+            struct_name: None,
+            fields: vec![],
+            definition: None,
+            inferred: None,
+        };
+
+        let err = expand_schema(
+            "orders",
+            "postgres_cdc",
+            ConnectionType::Source,
+            schema,
+            &json!({"host": "localhost"}),
+            &json!({"tableName": "orders"}),
+        )
+        .await
+        .unwrap_err();
+
+        assert_eq!(err.status_code, StatusCode::BAD_REQUEST);
+        assert!(
+            err.message.contains("Invalid Postgres connection profile"),
+            "{}",
+            err.message
+        );
+    }
+}
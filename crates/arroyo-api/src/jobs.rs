@@ -1,47 +1,62 @@
 use crate::queries::api_queries::{DbCheckpoint, DbLogMessage, DbPipelineJob};
+use arroyo_datastream::logical::{LogicalProgram, OperatorName};
 use arroyo_rpc::api_types::checkpoints::{
-    Checkpoint, CheckpointEventSpan, CheckpointSpanType, OperatorCheckpointGroup,
-    SubtaskCheckpointGroup,
+    Checkpoint, CheckpointEventSpan, CheckpointSpanType, OperatorCheckpointGroup, StateEntry,
+    StateQueryParams, SubtaskCheckpointGroup,
+};
+use arroyo_rpc::api_types::pipelines::{
+    Job, JobEvent, JobLogLevel, JobLogMessage, OutputData, SourceOffsetOverrides,
+    SourceOffsetsPost, StopType,
 };
-use arroyo_rpc::api_types::pipelines::{JobLogLevel, JobLogMessage, OutputData, StopType};
 use arroyo_rpc::api_types::{
     CheckpointCollection, JobCollection, JobLogMessageCollection,
-    OperatorCheckpointGroupCollection, PaginationQueryParams,
+    OperatorCheckpointGroupCollection, PaginationQueryParams, StateEntryCollection,
 };
+use arroyo_rpc::config::config;
 use arroyo_rpc::grpc;
 use arroyo_rpc::grpc::api::{
-    OperatorCheckpointDetail, TaskCheckpointDetail, TaskCheckpointEventType,
+    ArrowProgram, ConnectorOp, OperatorCheckpointDetail, TaskCheckpointDetail,
+    TaskCheckpointEventType,
 };
 use arroyo_rpc::grpc::rpc::controller_grpc_client::ControllerGrpcClient;
 use arroyo_rpc::public_ids::{generate_id, IdTypes};
 use axum::extract::{Path, Query, State};
-use axum::response::sse::{Event, Sse};
+use axum::http::StatusCode;
+use axum::response::sse::{Event, KeepAlive, Sse};
 use axum::Json;
+use axum_extra::extract::WithRejection;
 use futures_util::stream::Stream;
+use prost::Message;
 use std::convert::Infallible;
 use std::{collections::HashMap, time::Duration};
+use time::OffsetDateTime;
 use tokio_stream::wrappers::ReceiverStream;
 use tokio_stream::StreamExt as _;
 use tonic::{Code, Request};
-use tracing::info;
-
-const PREVIEW_TTL: Duration = Duration::from_secs(60);
+use tracing::{info, warn};
 
 use crate::pipelines::{query_job_by_pub_id, query_pipeline_by_pub_id};
 use crate::rest::AppState;
 use crate::rest_utils::{
-    authenticate, bad_request, log_and_map, not_found, paginate_results,
-    validate_pagination_params, BearerAuth, ErrorResp,
+    authenticate, bad_request, log_and_map, not_found, paginate_results, service_unavailable,
+    validate_pagination_params, ApiError, BearerAuth, ErrorResp,
 };
 use crate::types::public::LogLevel;
 use crate::{queries::api_queries, to_micros, types::public, AuthData};
 use cornucopia_async::DatabaseSource;
 
+/// How often the watch endpoint checks the database for changes to the job
+const WATCH_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The most errors the watch endpoint sends in a single poll
+const WATCH_MAX_ERRORS: usize = 100;
+
 pub(crate) async fn create_job<'a>(
     pipeline_name: &str,
     pipeline_id: i64,
     checkpoint_interval: Duration,
     preview: bool,
+    restore_savepoint_id: Option<String>,
     auth: &AuthData,
     db: &DatabaseSource,
 ) -> Result<String, ErrorResp> {
@@ -92,10 +107,11 @@ pub(crate) async fn create_job<'a>(
         &pipeline_id,
         &(checkpoint_interval.as_micros() as i64),
         &(if preview {
-            Some(PREVIEW_TTL.as_micros() as i64)
+            Some(config().pipeline.preview.max_runtime.as_micros() as i64)
         } else {
             None
         }),
+        &restore_savepoint_id,
     )
     .await?;
 
@@ -418,6 +434,239 @@ pub async fn get_checkpoint_details(
     Ok(Json(OperatorCheckpointGroupCollection { data: operators }))
 }
 
+/// Checks that each override targets a Kafka or Kinesis source in the program, with a valid
+/// partition (or shard) and offset
+fn validate_offset_overrides(
+    program: &LogicalProgram,
+    overrides: &[SourceOffsetOverride],
+) -> Result<(), ErrorResp> {
+    for o in overrides {
+        let node = program
+            .graph
+            .node_weights()
+            .find(|n| n.operator_id == o.node_id)
+            .ok_or_else(|| bad_request(format!("Pipeline has no node '{}'", o.node_id)))?;
+
+        if node.operator_name != OperatorName::ConnectorSource {
+            return Err(bad_request(format!("Node '{}' is not a source", o.node_id)));
+        }
+
+        let connector = ConnectorOp::decode(&node.operator_config[..])
+            .map_err(log_and_map)?
+            .connector;
+
+        match connector.as_str() {
+            "kafka" => {
+                if o.partition.parse::<i32>().is_err() {
+                    return Err(bad_request(format!(
+                        "Invalid Kafka partition '{}'",
+                        o.partition
+                    )));
+                }
+                if !matches!(o.offset.as_str(), "earliest" | "latest")
+                    && o.offset.parse::<u64>().is_err()
+                {
+                    return Err(bad_request(format!(
+                        "Invalid Kafka offset '{}'; expected a number, 'earliest', or 'latest'",
+                        o.offset
+                    )));
+                }
+            }
+            "kinesis" => {
+                // shards are named `shardId-` followed by a 12-digit number
+                let valid_shard = o
+                    .partition
+                    .strip_prefix("shardId-")
+                    .is_some_and(|n| !n.is_empty() && n.chars().all(|c| c.is_ascii_digit()));
+                if !valid_shard {
+                    return Err(bad_request(format!(
+                        "Invalid Kinesis shard id '{}'; expected an id like 'shardId-000000000000'",
+                        o.partition
+                    )));
+                }
+                if !matches!(o.offset.as_str(), "earliest" | "latest")
+                    && (o.offset.is_empty() || !o.offset.chars().all(|c| c.is_ascii_digit()))
+                {
+                    return Err(bad_request(format!(
+                        "Invalid Kinesis sequence number '{}'; expected a number, 'earliest', or 'latest'",
+                        o.offset
+                    )));
+                }
+            }
+            _ => {
+                return Err(bad_request(format!(
+                    "The {} connector for node '{}' does not support offset overrides",
+                    connector, o.node_id
+                )));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Override where a stopped job's sources start reading when it's restarted
+#[utoipa::path(
+    post,
+    path = "/v1/pipelines/{pipeline_id}/jobs/{job_id}/source_offsets",
+    tag = "jobs",
+    params(
+        ("pipeline_id" = String, Path, description = "Pipeline id"),
+        ("job_id" = String, Path, description = "Job id")
+    ),
+    request_body = SourceOffsetsPost,
+    responses(
+        (status = 200, description = "Set source offset overrides", body = SourceOffsetOverrides),
+    ),
+)]
+pub async fn set_source_offsets(
+    State(state): State<AppState>,
+    bearer_auth: BearerAuth,
+    Path((pipeline_pub_id, job_pub_id)): Path<(String, String)>,
+    WithRejection(Json(req), _): WithRejection<Json<SourceOffsetsPost>, ApiError>,
+) -> Result<Json<SourceOffsetOverrides>, ErrorResp> {
+    let db = state.database.client().await?;
+    let auth_data = authenticate(&state.database, bearer_auth).await?;
+
+    let job = query_job_by_pub_id(&pipeline_pub_id, &job_pub_id, &db, &auth_data).await?;
+    if !matches!(job.state.as_str(), "Stopped" | "Failed" | "Finished") {
+        return Err(bad_request(format!(
+            "Source offsets can only be overridden for stopped jobs, but the job is {}",
+            job.state
+        )));
+    }
+
+    let details = api_queries::fetch_get_job_details(&db, &auth_data.organization_id, &job_pub_id)
+        .await?
+        .into_iter()
+        .next()
+        .ok_or_else(|| not_found("Job"))?;
+
+    let program: LogicalProgram = ArrowProgram::decode(&details.program[..])
+        .map_err(log_and_map)?
+        .try_into()
+        .map_err(log_and_map)?;
+
+    validate_offset_overrides(&program, &req.overrides)?;
+
+    // the overrides replace the positions stored in the checkpoint the job will restore from
+    let epoch = api_queries::fetch_get_last_successful_checkpoint(
+        &db,
+        &job_pub_id,
+        &auth_data.organization_id,
+    )
+    .await?
+    .into_iter()
+    .next()
+    .map(|c| c.epoch as u32);
+
+    let overrides = SourceOffsetOverrides {
+        epoch,
+        overrides: req.overrides,
+    };
+
+    let value = if overrides.overrides.is_empty() {
+        None
+    } else {
+        Some(serde_json::to_value(&overrides).map_err(log_and_map)?)
+    };
+
+    api_queries::execute_update_source_offset_overrides(
+        &db,
+        &OffsetDateTime::now_utc(),
+        &auth_data.user_id,
+        &value,
+        &job_pub_id,
+        &auth_data.organization_id,
+    )
+    .await?;
+
+    Ok(Json(overrides))
+}
+
+const DEFAULT_STATE_QUERY_LIMIT: u32 = 100;
+const MAX_STATE_QUERY_LIMIT: u32 = 1000;
+
+/// Query the live state of one of a running job's operators
+///
+/// Returns the entries of the operator's state table across all of its subtasks, optionally
+/// filtered to a single key. Tables are only queryable once the operator has read them.
+#[utoipa::path(
+    get,
+    path = "/v1/pipelines/{pipeline_id}/jobs/{job_id}/state",
+    tag = "jobs",
+    params(
+        ("pipeline_id" = String, Path, description = "Pipeline id"),
+        ("job_id" = String, Path, description = "Job id"),
+        StateQueryParams,
+    ),
+    responses(
+        (status = 200, description = "Got the operator's state", body = StateEntryCollection),
+        (status = 400, description = "Bad request", body = ErrorResp),
+    ),
+)]
+pub async fn query_job_state(
+    State(state): State<AppState>,
+    bearer_auth: BearerAuth,
+    Path((pipeline_pub_id, job_pub_id)): Path<(String, String)>,
+    query_params: Query<StateQueryParams>,
+) -> Result<Json<StateEntryCollection>, ErrorResp> {
+    let db = state.database.client().await?;
+    let auth_data = authenticate(&state.database, bearer_auth).await?;
+
+    let job = query_job_by_pub_id(&pipeline_pub_id, &job_pub_id, &db, &auth_data).await?;
+    if job.state != "Running" {
+        return Err(bad_request(format!(
+            "State can only be queried for running jobs, but the job is {}",
+            job.state
+        )));
+    }
+
+    let limit = query_params.limit.unwrap_or(DEFAULT_STATE_QUERY_LIMIT);
+    if limit == 0 || limit > MAX_STATE_QUERY_LIMIT {
+        return Err(bad_request(format!(
+            "Limit must be between 1 and {}",
+            MAX_STATE_QUERY_LIMIT
+        )));
+    }
+
+    let mut controller = ControllerGrpcClient::connect(state.controller_addr.clone())
+        .await
+        .map_err(|e| {
+            warn!("failed to connect to controller: {:?}", e);
+            service_unavailable("Controller")
+        })?;
+
+    let entries = controller
+        .query_state(Request::new(grpc::rpc::QueryStateReq {
+            job_id: job_pub_id,
+            operator_id: query_params.operator_id.clone(),
+            table: query_params.table.clone(),
+            key: query_params.key.clone(),
+            limit,
+        }))
+        .await
+        .map_err(|e| match e.code() {
+            Code::FailedPrecondition | Code::NotFound | Code::InvalidArgument => {
+                bad_request(e.message().to_string())
+            }
+            _ => log_and_map(e),
+        })?
+        .into_inner()
+        .entries;
+
+    Ok(Json(StateEntryCollection {
+        data: entries
+            .into_iter()
+            .map(|e| StateEntry {
+                subtask_index: e.subtask_index,
+                key: e.key,
+                value: e.value,
+            })
+            .collect(),
+    }))
+}
+
 /// Subscribe to a job's output
 #[utoipa::path(
     get,
@@ -505,6 +754,205 @@ pub async fn get_job_output(
     Ok(Sse::new(ReceiverStream::new(rx)))
 }
 
+/// Watch a job's state transitions, completed checkpoints and errors
+#[utoipa::path(
+    get,
+    path = "/v1/pipelines/{pipeline_id}/jobs/{job_id}/watch",
+    tag = "jobs",
+    params(
+        ("pipeline_id" = String, Path, description = "Pipeline id"),
+        ("job_id" = String, Path, description = "Job id")
+    ),
+    responses(
+        (status = 200, description = "Job events as 'text/event-stream'; each event's data is a JobEvent"),
+    ),
+)]
+pub async fn watch_job(
+    State(state): State<AppState>,
+    bearer_auth: BearerAuth,
+    Path((pipeline_pub_id, job_pub_id)): Path<(String, String)>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, ErrorResp> {
+    let auth_data = authenticate(&state.database, bearer_auth).await?;
+    let db = state.database.client().await?;
+
+    let job = query_job_by_pub_id(&pipeline_pub_id, &job_pub_id, &db, &auth_data).await?;
+
+    // only events that happen after the watch starts are sent, other than the current state
+    let epoch =
+        api_queries::fetch_get_job_checkpoints(&db, &job_pub_id, &auth_data.organization_id)
+            .await
+            .map_err(log_and_map)?
+            .into_iter()
+            .filter(|c| c.finish_time.is_some())
+            .map(|c| c.epoch)
+            .max()
+            .unwrap_or_default();
+
+    let error_id = api_queries::fetch_get_operator_errors(
+        &db,
+        &auth_data.organization_id,
+        &job_pub_id,
+        &String::new(),
+        &1,
+    )
+    .await
+    .map_err(log_and_map)?
+    .into_iter()
+    .next()
+    .map(|e| e.id)
+    .unwrap_or_default();
+
+    drop(db);
+
+    let mut cursor = WatchCursor {
+        job: job.clone(),
+        epoch,
+        error_id,
+    };
+
+    let (tx, rx) = tokio::sync::mpsc::channel(32);
+    let database = state.database.clone();
+
+    tokio::spawn(async move {
+        let mut event_count: u64 = 0;
+        let mut to_sse = |event: JobEvent| {
+            let e = Event::default()
+                .event(event.name())
+                .json_data(event)
+                .unwrap()
+                .id(event_count.to_string());
+            event_count += 1;
+            e
+        };
+
+        if tx
+            .send(Ok(to_sse(JobEvent::StateChanged { job })))
+            .await
+            .is_err()
+        {
+            return;
+        }
+
+        let mut interval = tokio::time::interval(WATCH_POLL_INTERVAL);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+        while !tx.is_closed() {
+            interval.tick().await;
+
+            let db = match database.client().await {
+                Ok(db) => db,
+                Err(e) => {
+                    warn!("failed to connect to database while watching job: {:?}", e);
+                    continue;
+                }
+            };
+
+            let job =
+                match query_job_by_pub_id(&pipeline_pub_id, &job_pub_id, &db, &auth_data).await {
+                    Ok(job) => job,
+                    Err(e) if e.status_code == StatusCode::NOT_FOUND => {
+                        info!("Job {} was deleted; closing watch stream", job_pub_id);
+                        break;
+                    }
+                    Err(e) => {
+                        warn!("failed to fetch job while watching: {}", e.message);
+                        continue;
+                    }
+                };
+
+            let checkpoints = match api_queries::fetch_get_finished_job_checkpoints_after(
+                &db,
+                &job_pub_id,
+                &auth_data.organization_id,
+                &cursor.epoch,
+            )
+            .await
+            {
+                Ok(checkpoints) => checkpoints.into_iter().map(|c| c.into()).collect(),
+                Err(e) => {
+                    warn!("failed to fetch checkpoints while watching job: {:?}", e);
+                    vec![]
+                }
+            };
+
+            let errors = match api_queries::fetch_get_operator_errors_after(
+                &db,
+                &auth_data.organization_id,
+                &job_pub_id,
+                &cursor.error_id,
+                &(WATCH_MAX_ERRORS as i32),
+            )
+            .await
+            {
+                Ok(errors) => errors.into_iter().map(|e| (e.id, e.into())).collect(),
+                Err(e) => {
+                    warn!("failed to fetch errors while watching job: {:?}", e);
+                    vec![]
+                }
+            };
+
+            let events = cursor.advance(job, checkpoints, errors);
+            for event in events {
+                if tx.send(Ok(to_sse(event))).await.is_err() {
+                    break;
+                }
+            }
+        }
+
+        info!("Closing watch stream for {}", job_pub_id);
+    });
+
+    Ok(Sse::new(ReceiverStream::new(rx)).keep_alive(KeepAlive::default()))
+}
+
+/// What a watch stream has already sent, so that each poll only fetches what's new
+struct WatchCursor {
+    job: Job,
+    /// the epoch of the last finished checkpoint
+    epoch: i32,
+    /// the id of the last error; errors are followed by id rather than by time, as several may
+    /// be logged with the same timestamp
+    error_id: i64,
+}
+
+impl WatchCursor {
+    /// Returns the events for whatever has changed since the last poll, in the order they
+    /// happened, and moves the cursor past them
+    fn advance(
+        &mut self,
+        job: Job,
+        checkpoints: Vec<Checkpoint>,
+        errors: Vec<(i64, JobLogMessage)>,
+    ) -> Vec<JobEvent> {
+        let mut events = vec![];
+
+        if job.state != self.job.state
+            || job.running_desired != self.job.running_desired
+            || job.run_id != self.job.run_id
+            || job.failure_message != self.job.failure_message
+        {
+            self.job = job.clone();
+            events.push(JobEvent::StateChanged { job });
+        }
+
+        for checkpoint in checkpoints {
+            if checkpoint.finish_time.is_some() && checkpoint.epoch as i32 > self.epoch {
+                self.epoch = checkpoint.epoch as i32;
+                events.push(JobEvent::CheckpointCompleted { checkpoint });
+            }
+        }
+
+        for (id, error) in errors {
+            if id > self.error_id {
+                self.error_id = id;
+                events.push(JobEvent::Error { error });
+            }
+        }
+
+        events
+    }
+}
+
 /// Get all jobs
 #[utoipa::path(
     get,
@@ -541,3 +989,173 @@ impl From<DbCheckpoint> for Checkpoint {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arroyo_datastream::logical::{LogicalGraph, LogicalNode, ProgramConfig};
+
+    fn program() -> LogicalProgram {
+        let mut graph = LogicalGraph::new();
+        for (id, name, connector) in [
+            ("source_0", OperatorName::ConnectorSource, "kafka"),
+            ("source_1", OperatorName::ConnectorSource, "kinesis"),
+            ("source_2", OperatorName::ConnectorSource, "sse"),
+            ("sink_3", OperatorName::ConnectorSink, "kafka"),
+        ] {
+            graph.add_node(LogicalNode {
+                operator_id: id.to_string(),
+                description: id.to_string(),
+                operator_name: name,
+                operator_config: ConnectorOp {
+                    connector: connector.to_string(),
+                    config: "{}".to_string(),
+                    description: connector.to_string(),
+                    timestamp_field: None,
+                    start_at: None,
+                    end_at: None,
+                }
+                .encode_to_vec(),
+                parallelism: 1,
+            });
+        }
+        LogicalProgram::new(graph, ProgramConfig::default())
+    }
+
+    fn validate(node_id: &str, partition: &str, offset: &str) -> Result<(), String> {
+        validate_offset_overrides(
+            &program(),
+            &[SourceOffsetOverride {
+                node_id: node_id.to_string(),
+                partition: partition.to_string(),
+                offset: offset.to_string(),
+            }],
+        )
+        .map_err(|e| {
+            assert_eq!(e.status_code, StatusCode::BAD_REQUEST);
+            e.message
+        })
+    }
+
+    #[test]
+    fn test_validate_offset_overrides() {
+        assert!(validate("source_0", "3", "150").is_ok());
+        assert!(validate("source_0", "0", "earliest").is_ok());
+        assert!(validate("source_1", "shardId-000000000001", "latest").is_ok());
+        assert!(validate(
+            "source_1",
+            "shardId-000000000001",
+            "49590338271490256608559692538361571095921575989136588898"
+        )
+        .is_ok());
+
+        let err = |node_id, partition, offset| validate(node_id, partition, offset).unwrap_err();
+
+        assert!(err("source_9", "0", "1").contains("no node 'source_9'"));
+        assert!(err("sink_3", "0", "1").contains("is not a source"));
+        assert!(err("source_2", "0", "1").contains("does not support offset overrides"));
+        assert!(err("source_0", "shardId-000000000001", "1").contains("Invalid Kafka partition"));
+        assert!(err("source_0", "1", "next").contains("Invalid Kafka offset"));
+        assert!(err("source_1", "3", "latest").contains("Invalid Kinesis shard id"));
+        assert!(err("source_1", "shardId-000000000001", "-1")
+            .contains("Invalid Kinesis sequence number"));
+    }
+
+    fn job(state: &str) -> Job {
+        Job {
+            id: "job_1".to_string(),
+            running_desired: true,
+            state: state.to_string(),
+            run_id: 1,
+            start_time: None,
+            finish_time: None,
+            tasks: None,
+            failure_message: None,
+            created_at: 0,
+        }
+    }
+
+    fn checkpoint(epoch: u32, finished: bool) -> Checkpoint {
+        Checkpoint {
+            epoch,
+            backend: "parquet".to_string(),
+            start_time: 0,
+            finish_time: finished.then_some(1),
+        }
+    }
+
+    fn error(id: i64, created_at: u64) -> (i64, JobLogMessage) {
+        (
+            id,
+            JobLogMessage {
+                id: format!("error_{}", id),
+                created_at,
+                operator_id: None,
+                task_index: None,
+                level: JobLogLevel::Error,
+                message: "failed".to_string(),
+                details: String::new(),
+            },
+        )
+    }
+
+    fn names(events: &[JobEvent]) -> Vec<String> {
+        events
+            .iter()
+            .map(|e| match e {
+                JobEvent::StateChanged { job } => format!("state:{}", job.state),
+                JobEvent::CheckpointCompleted { checkpoint } => {
+                    format!("checkpoint:{}", checkpoint.epoch)
+                }
+                JobEvent::Error { error } => format!("error:{}", error.id),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_watch_cursor() {
+        let mut cursor = WatchCursor {
+            job: job("Running"),
+            epoch: 3,
+            error_id: 10,
+        };
+
+        // nothing is sent again for what the stream started after
+        assert!(cursor
+            .advance(
+                job("Running"),
+                vec![checkpoint(3, true)],
+                vec![error(10, 100)]
+            )
+            .is_empty());
+
+        let events = cursor.advance(
+            job("Failing"),
+            vec![checkpoint(4, true), checkpoint(5, false)],
+            // errors logged at the same time are all sent
+            vec![error(11, 200), error(12, 200), error(13, 200)],
+        );
+        assert_eq!(
+            names(&events),
+            vec![
+                "state:Failing",
+                "checkpoint:4",
+                "error:error_11",
+                "error:error_12",
+                "error:error_13"
+            ]
+        );
+        assert_eq!(cursor.epoch, 4);
+        assert_eq!(cursor.error_id, 13);
+
+        // the unfinished checkpoint is sent once it finishes
+        let events = cursor.advance(job("Failing"), vec![checkpoint(5, true)], vec![]);
+        assert_eq!(names(&events), vec!["checkpoint:5"]);
+
+        let mut failed = job("Failed");
+        failed.failure_message = Some("out of memory".to_string());
+        let events = cursor.advance(failed.clone(), vec![], vec![error(14, 150)]);
+        assert_eq!(names(&events), vec!["state:Failed", "error:error_14"]);
+        assert!(cursor.advance(failed, vec![], vec![]).is_empty());
+    }
+}
@@ -18,22 +18,25 @@ use crate::connection_profiles::{
 };
 use crate::connection_tables::{
     __path_create_connection_table, __path_delete_connection_table, __path_get_connection_tables,
-    __path_test_connection_table, __path_test_schema,
+    __path_infer_schema, __path_test_connection_table, __path_test_schema,
 };
 use crate::connectors::__path_get_connectors;
 use crate::jobs::{
     __path_get_checkpoint_details, __path_get_job_checkpoints, __path_get_job_errors,
-    __path_get_job_output, __path_get_jobs,
+    __path_get_job_output, __path_get_jobs, __path_query_job_state, __path_set_source_offsets,
+    __path_watch_job,
 };
 use crate::metrics::__path_get_operator_metric_groups;
 use crate::pipelines::__path_get_pipelines;
 use crate::pipelines::{
     __path_create_pipeline, __path_create_preview_pipeline, __path_delete_pipeline,
-    __path_get_pipeline, __path_get_pipeline_jobs, __path_patch_pipeline, __path_restart_pipeline,
-    __path_validate_query,
+    __path_diff_pipeline, __path_get_pipeline, __path_get_pipeline_jobs, __path_get_pipeline_usage,
+    __path_patch_pipeline, __path_restart_pipeline, __path_validate_query,
 };
 use crate::rest::__path_ping;
 use crate::rest_utils::{service_unavailable, ErrorResp};
+use crate::savepoints::{__path_create_savepoint, __path_get_savepoints};
+use crate::sql::__path_query_system_tables;
 use crate::udfs::{__path_create_udf, __path_delete_udf, __path_get_udfs, __path_validate_udf};
 use arroyo_rpc::api_types::{checkpoints::*, connections::*, metrics::*, pipelines::*, udfs::*, *};
 use arroyo_rpc::config::config;
@@ -51,6 +54,7 @@ mod metrics;
 mod pipelines;
 pub mod rest;
 mod rest_utils;
+mod savepoints;
 pub mod sql;
 mod udfs;
 
@@ -220,14 +224,21 @@ impl IntoResponse for HttpError {
         create_preview_pipeline,
         patch_pipeline,
         restart_pipeline,
+        diff_pipeline,
         get_pipeline,
         delete_pipeline,
         get_pipelines,
         get_jobs,
         get_pipeline_jobs,
+        get_pipeline_usage,
         get_job_errors,
         get_job_checkpoints,
         get_job_output,
+        watch_job,
+        set_source_offsets,
+        query_job_state,
+        create_savepoint,
+        get_savepoints,
         get_operator_metric_groups,
         get_connectors,
         get_connection_profiles,
@@ -240,10 +251,12 @@ impl IntoResponse for HttpError {
         delete_connection_table,
         test_connection_table,
         test_schema,
+        infer_schema,
         get_checkpoint_details,
         create_udf,
         get_udfs,
-        delete_udf
+        delete_udf,
+        query_system_tables
     ),
     components(schemas(
         ErrorResp,
@@ -251,20 +264,40 @@ impl IntoResponse for HttpError {
         PreviewPost,
         PipelinePatch,
         PipelineRestart,
+        PipelineDiffPost,
+        PipelineDiff,
+        OperatorDiff,
+        OperatorChange,
+        StateCompatibility,
+        SourceOffsetsPost,
+        SourceOffsetOverride,
+        SourceOffsetOverrides,
         Pipeline,
         PipelineGraph,
         PipelineNode,
         PipelineEdge,
+        SinkLineage,
+        ColumnLineage,
+        SourceColumn,
         Job,
         StopType,
         PipelineCollection,
         JobCollection,
+        PipelineUsage,
+        PipelineUsageCollection,
         JobLogMessage,
         JobLogMessageCollection,
         JobLogLevel,
         Checkpoint,
         CheckpointCollection,
+        SavepointPost,
+        SavepointState,
+        Savepoint,
+        SavepointCollection,
+        StateEntry,
+        StateEntryCollection,
         OutputData,
+        JobEvent,
         MetricName,
         Metric,
         SubtaskMetrics,
@@ -295,11 +328,16 @@ impl IntoResponse for HttpError {
         ParquetFormat,
         RawStringFormat,
         RawBytesFormat,
+        ArrowIpcFormat,
+        CsvFormat,
+        XmlFormat,
         TimestampFormat,
         Framing,
         FramingMethod,
         NewlineDelimitedFraming,
+        PayloadCompression,
         PaginationQueryParams,
+        SchemaInferenceQueryParams,
         CheckpointEventSpan,
         CheckpointSpanType,
         OperatorCheckpointGroupCollection,
@@ -307,6 +345,8 @@ impl IntoResponse for HttpError {
         OperatorCheckpointGroup,
         ValidateQueryPost,
         QueryValidationResult,
+        SystemQueryPost,
+        SystemQueryResult,
         ValidateUdfPost,
         UdfValidationResult,
         Udf,
@@ -323,6 +363,7 @@ impl IntoResponse for HttpError {
         (name = "pipelines", description = "Pipeline management endpoints"),
         (name = "jobs", description = "Job management endpoints"),
         (name = "connectors", description = "Connector management endpoints"),
+        (name = "system", description = "System table endpoints"),
     )
 )]
 pub struct ApiDoc;
@@ -1,3 +1,5 @@
+use std::collections::HashMap;
+
 use axum::extract::{Path, State};
 use axum::Json;
 
@@ -7,7 +9,7 @@ use crate::rest_utils::{authenticate, log_and_map, BearerAuth, ErrorResp};
 use arroyo_rpc::api_types::metrics::OperatorMetricGroup;
 use arroyo_rpc::api_types::OperatorMetricGroupCollection;
 use arroyo_rpc::grpc::rpc::controller_grpc_client::ControllerGrpcClient;
-use arroyo_rpc::grpc::rpc::JobMetricsReq;
+use arroyo_rpc::grpc::rpc::{JobMetricsReq, JobsMetricsReq};
 use tonic::codec::CompressionEncoding;
 use tonic::transport::Channel;
 use tonic::Code;
@@ -40,17 +42,23 @@ pub async fn get_operator_metric_groups(
     )
     .await?;
 
-    let channel = Channel::builder(state.controller_addr.parse().unwrap())
-        .connect()
-        .await
-        .map_err(log_and_map)?;
+    let data = fetch_job_metrics(&state, &job.id).await?;
 
-    let mut controller = ControllerGrpcClient::new(channel)
-        .accept_compressed(CompressionEncoding::Zstd)
-        .send_compressed(CompressionEncoding::Zstd);
+    Ok(Json(OperatorMetricGroupCollection { data }))
+}
+
+/// Fetches the current metrics for a job from the controller, which are empty if it is not
+/// running
+pub(crate) async fn fetch_job_metrics(
+    state: &AppState,
+    job_id: &str,
+) -> Result<Vec<OperatorMetricGroup>, ErrorResp> {
+    let mut controller = controller_client(state).await?;
 
-    let data = match controller
-        .job_metrics(JobMetricsReq { job_id: job.id })
+    let metrics = match controller
+        .job_metrics(JobMetricsReq {
+            job_id: job_id.to_string(),
+        })
         .await
     {
         Ok(resp) => {
@@ -68,5 +76,39 @@ pub async fn get_operator_metric_groups(
         }
     };
 
-    Ok(Json(OperatorMetricGroupCollection { data }))
+    Ok(metrics)
+}
+
+/// Fetches the current metrics for many jobs from the controller in a single request; jobs
+/// that aren't running are omitted
+pub(crate) async fn fetch_jobs_metrics(
+    state: &AppState,
+    job_ids: Vec<String>,
+) -> Result<HashMap<String, Vec<OperatorMetricGroup>>, ErrorResp> {
+    if job_ids.is_empty() {
+        return Ok(HashMap::new());
+    }
+
+    let mut controller = controller_client(state).await?;
+
+    controller
+        .jobs_metrics(JobsMetricsReq { job_ids })
+        .await
+        .map_err(log_and_map)?
+        .into_inner()
+        .metrics
+        .into_iter()
+        .map(|(job_id, metrics)| Ok((job_id, serde_json::from_str(&metrics).map_err(log_and_map)?)))
+        .collect()
+}
+
+async fn controller_client(state: &AppState) -> Result<ControllerGrpcClient<Channel>, ErrorResp> {
+    let channel = Channel::builder(state.controller_addr.parse().unwrap())
+        .connect()
+        .await
+        .map_err(log_and_map)?;
+
+    Ok(ControllerGrpcClient::new(channel)
+        .accept_compressed(CompressionEncoding::Zstd)
+        .send_compressed(CompressionEncoding::Zstd))
 }
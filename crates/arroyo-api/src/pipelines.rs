@@ -15,16 +15,18 @@ use std::time::{Duration, SystemTime};
 use crate::{compiler_service, connection_profiles, jobs, types};
 use arroyo_datastream::default_sink;
 use arroyo_rpc::api_types::pipelines::{
-    Job, Pipeline, PipelinePatch, PipelinePost, PipelineRestart, PreviewPost,
-    QueryValidationResult, StopType, ValidateQueryPost,
+    Job, Pipeline, PipelineDiff, PipelineDiffPost, PipelinePatch, PipelinePost, PipelineRestart,
+    PipelineUsage, PreviewPost, QueryValidationResult, StopType, ValidateQueryPost,
 };
 use arroyo_rpc::api_types::udfs::{GlobalUdf, Udf, UdfLanguage};
-use arroyo_rpc::api_types::{JobCollection, PaginationQueryParams, PipelineCollection};
+use arroyo_rpc::api_types::{
+    JobCollection, PaginationQueryParams, PipelineCollection, PipelineUsageCollection,
+};
 use arroyo_rpc::grpc::api::{ArrowProgram, ConnectorOp};
 
 use arroyo_connectors::kafka::{KafkaConfig, KafkaTable, SchemaRegistry};
 use arroyo_datastream::logical::{LogicalNode, LogicalProgram, OperatorName};
-use arroyo_df::{ArroyoSchemaProvider, CompiledSql, SqlConfig};
+use arroyo_df::{ArroyoSchemaProvider, CompiledSql, PlannedSql, SqlConfig};
 use arroyo_formats::ser::ArrowSerializer;
 use arroyo_rpc::formats::Format;
 use arroyo_rpc::grpc::rpc::compiler_grpc_client::CompilerGrpcClient;
@@ -32,6 +34,7 @@ use arroyo_rpc::public_ids::{generate_id, IdTypes};
 use arroyo_rpc::schema_resolver::{ConfluentSchemaRegistry, ConfluentSchemaType};
 use arroyo_rpc::{error_chain, OperatorConfig};
 use arroyo_server_common::log_event;
+use arroyo_state::compatibility::diff_programs;
 use arroyo_udf_host::ParsedUdfFile;
 use prost::Message;
 use serde_json::json;
@@ -40,29 +43,58 @@ use tracing::warn;
 
 use crate::jobs::get_action;
 use crate::queries::api_queries;
-use crate::queries::api_queries::{fetch_get_udfs, DbPipeline, DbPipelineJob};
+use crate::queries::api_queries::{fetch_get_udfs, DbPipeline, DbPipelineJob, DbPipelineUsage};
 use crate::rest::AppState;
 use crate::rest_utils::{
     authenticate, bad_request, log_and_map, not_found, paginate_results, required_field,
     validate_pagination_params, ApiError, BearerAuth, ErrorResp,
 };
+use crate::sql::query_result;
 use crate::types::public::{PipelineType, RestartMode, StopMode};
 use crate::udfs::build_udf;
 use crate::AuthData;
 use crate::{connection_tables, to_micros};
 use arroyo_rpc::config::config;
-use arroyo_types::to_millis;
+use arroyo_types::{to_millis, KEY_GROUPS};
 use cornucopia_async::{Database, DatabaseSource};
 use petgraph::prelude::EdgeRef;
 
 async fn compile_sql<'a>(
     query: String,
-    local_udfs: &Vec<Udf>,
+    local_udfs: &mut [Udf],
     parallelism: usize,
     auth_data: &AuthData,
     validate_only: bool,
+    preview: bool,
     db: &DatabaseSource,
 ) -> Result<CompiledSql, ErrorResp> {
+    plan_sql(
+        query,
+        local_udfs,
+        parallelism,
+        auth_data,
+        validate_only,
+        preview,
+        db,
+    )
+    .await?
+    .into_pipeline()
+    .map_err(|err| bad_request(err.to_string()))
+}
+
+async fn plan_sql(
+    query: String,
+    local_udfs: &mut [Udf],
+    parallelism: usize,
+    auth_data: &AuthData,
+    validate_only: bool,
+    preview: bool,
+    db: &DatabaseSource,
+) -> Result<PlannedSql, ErrorResp> {
+    if query.trim().is_empty() {
+        return Err(bad_request("Query is empty"));
+    }
+
     let mut schema_provider = ArroyoSchemaProvider::new();
 
     let global_udfs = fetch_get_udfs(&db.client().await?, &auth_data.organization_id)
@@ -111,6 +143,7 @@ async fn compile_sql<'a>(
                             &mut compiler_service,
                             &udf.definition,
                             UdfLanguage::Rust,
+                            udf.cargo_lock.as_deref(),
                             true,
                         )
                         .await?;
@@ -122,6 +155,10 @@ async fn compile_sql<'a>(
                             )));
                         }
 
+                        // record the resolved dependencies so that the pipeline keeps building
+                        // against them, even as new versions are published
+                        udf.cargo_lock = res.cargo_lock;
+
                         res.url.expect("valid UDF does not have a URL in response")
                     } else {
                         "".to_string()
@@ -171,23 +208,19 @@ async fn compile_sql<'a>(
         schema_provider.add_connection_profile(profile);
     }
 
-    arroyo_df::parse_and_get_program(
-        &query,
+    arroyo_df::parse_and_get_arrow_program(
+        query,
         schema_provider,
         SqlConfig {
             default_parallelism: parallelism,
+            preview: preview.then(|| config().pipeline.preview.clone()),
+            ..Default::default()
         },
     )
     .await
     .map_err(|err| bad_request(err.to_string()))
 }
 
-fn set_parallelism(program: &mut LogicalProgram, parallelism: usize) {
-    for node in program.graph.node_weights_mut() {
-        node.parallelism = parallelism;
-    }
-}
-
 #[allow(unused)]
 async fn try_register_confluent_schema(
     sink: &mut ConnectorOp,
@@ -289,14 +322,67 @@ async fn register_schemas(compiled_sql: &mut CompiledSql) -> anyhow::Result<()>
 pub(crate) async fn create_pipeline_int<'a>(
     name: String,
     query: String,
-    udfs: Vec<Udf>,
+    mut udfs: Vec<Udf>,
     parallelism: u64,
     checkpoint_interval: Duration,
     is_preview: bool,
     enable_sinks: bool,
+    preview_node: Option<String>,
+    savepoint_id: Option<String>,
     auth: AuthData,
     db: &DatabaseSource,
 ) -> Result<String, ErrorResp> {
+    let pub_id = generate_id(IdTypes::Pipeline);
+
+    if is_preview && enable_sinks && !config().pipeline.preview.allow_sinks {
+        return Err(bad_request(
+            "Previews are not allowed to write to sinks; set `pipeline.preview.allow-sinks` \
+            to enable this"
+                .to_string(),
+        ));
+    }
+
+    // previews are planned with the limits of the preview configuration, like a single
+    // subtask per operator and bounded queues
+    let mut compiled = compile_sql(
+        query.clone(),
+        &mut udfs,
+        parallelism as usize,
+        &auth,
+        false,
+        is_preview,
+        db,
+    )
+    .await?;
+
+    // `SET` statements in the query take precedence over the pipeline configuration
+    let checkpoint_interval = compiled
+        .program
+        .program_config
+        .checkpoint_interval
+        .unwrap_or(checkpoint_interval);
+
+    // sources can't usefully run with more subtasks than they have partitions to read, nor be
+    // autoscaled beyond them
+    let source_limits = arroyo_df::limit_to_source_partitions(&mut compiled.program.graph).await;
+    if let Some(autoscaling) = &mut compiled.program.program_config.autoscaling {
+        for (operator_id, limit) in source_limits {
+            let max = autoscaling
+                .operator_max_parallelism
+                .entry(operator_id)
+                .or_insert(limit as u64);
+            *max = (*max).min(limit as u64);
+        }
+    }
+
+    let parallelism = compiled
+        .program
+        .graph
+        .node_weights()
+        .map(|n| n.parallelism as u64)
+        .max()
+        .unwrap_or(parallelism);
+
     if parallelism > auth.org_metadata.max_parallelism as u64 {
         return Err(bad_request(format!(
             "Your plan allows you to run pipelines up to parallelism {};
@@ -305,10 +391,24 @@ pub(crate) async fn create_pipeline_int<'a>(
         )));
     }
 
-    let pub_id = generate_id(IdTypes::Pipeline);
+    // the controller never autoscales past the maximum, so it's bounded by the plan as well
+    if let Some(autoscaling) = &compiled.program.program_config.autoscaling {
+        if autoscaling.max_parallelism > auth.org_metadata.max_parallelism as u64 {
+            return Err(bad_request(format!(
+                "Your plan allows you to run pipelines up to parallelism {}, which \
+                `autoscaling.max_parallelism` can't exceed; contact support@arroyo.systems for \
+                an increase",
+                auth.org_metadata.max_parallelism
+            )));
+        }
+    }
 
-    let mut compiled =
-        compile_sql(query.clone(), &udfs, parallelism as usize, &auth, false, db).await?;
+    if parallelism > KEY_GROUPS as u64 {
+        return Err(bad_request(format!(
+            "parallelism can be at most {}",
+            KEY_GROUPS
+        )));
+    }
 
     if compiled.program.graph.node_count() > auth.org_metadata.max_operators as usize {
         return Err(bad_request(
@@ -316,9 +416,23 @@ pub(crate) async fn create_pipeline_int<'a>(
                 contact support@arroyo.systems for an increase", auth.org_metadata.max_operators)));
     }
 
-    set_parallelism(&mut compiled.program, parallelism as usize);
-
-    if is_preview {
+    if let Some(node_id) = preview_node.filter(|_| is_preview) {
+        // previewing an intermediate operator; drop everything downstream of it and send its
+        // output to a preview sink
+        compiled.program = compiled
+            .program
+            .truncate_to(
+                &node_id,
+                LogicalNode {
+                    operator_id: format!("{}_preview", node_id),
+                    description: "Preview sink".to_string(),
+                    operator_name: OperatorName::ConnectorSink,
+                    operator_config: default_sink().encode_to_vec(),
+                    parallelism: 1,
+                },
+            )
+            .map_err(|e| bad_request(format!("Cannot preview operator: {}", e)))?;
+    } else if is_preview {
         // in Preview, we either replace sinks with a preview sink, or add a preview sink
         // next to them depending on the `enable_sinks` option
         let g = &mut compiled.program.graph;
@@ -410,6 +524,7 @@ pub(crate) async fn create_pipeline_int<'a>(
         pipeline_id,
         checkpoint_interval,
         is_preview,
+        savepoint_id,
         &auth,
         db,
     )
@@ -497,6 +612,18 @@ impl From<DbPipelineJob> for Job {
     }
 }
 
+impl From<DbPipelineUsage> for PipelineUsage {
+    fn from(val: DbPipelineUsage) -> Self {
+        PipelineUsage {
+            day: val.day,
+            cpu_seconds: val.cpu_seconds,
+            max_memory_bytes: val.max_memory_bytes as u64,
+            state_bytes: val.state_bytes as u64,
+            network_bytes: val.network_bytes as u64,
+        }
+    }
+}
+
 /// Validate a query and return pipeline graph
 #[utoipa::path(
     post,
@@ -514,25 +641,41 @@ pub async fn validate_query(
 ) -> Result<Json<QueryValidationResult>, ErrorResp> {
     let auth_data = authenticate(&state.database, bearer_auth).await?;
 
-    let udfs = validate_query_post.udfs.unwrap_or(vec![]);
+    let mut udfs = validate_query_post.udfs.unwrap_or(vec![]);
 
-    let pipeline_graph_validation_result = match compile_sql(
+    let pipeline_graph_validation_result = match plan_sql(
         validate_query_post.query,
-        &udfs,
+        &mut udfs,
         1,
         &auth_data,
         true,
+        false,
         &state.database,
     )
     .await
     {
-        Ok(CompiledSql { program, .. }) => QueryValidationResult {
+        Ok(PlannedSql::Pipeline(CompiledSql { program, .. })) => QueryValidationResult {
             graph: Some(program.try_into().map_err(log_and_map)?),
             errors: vec![],
+            result: None,
+        },
+        Ok(PlannedSql::ResultSet(batch)) => QueryValidationResult {
+            graph: None,
+            errors: vec![],
+            result: Some(query_result(
+                batch
+                    .schema()
+                    .fields()
+                    .iter()
+                    .map(|f| f.name().clone())
+                    .collect(),
+                &[batch],
+            )?),
         },
         Err(e) => QueryValidationResult {
             graph: None,
             errors: vec![e.message],
+            result: None,
         },
     };
 
@@ -565,6 +708,25 @@ pub async fn create_pipeline(
         .map(Duration::from_micros)
         .unwrap_or(*config().default_checkpoint_interval);
 
+    if let Some(savepoint_id) = &pipeline_post.savepoint_id {
+        let savepoint = api_queries::fetch_get_savepoint(
+            &state.database.client().await?,
+            &auth_data.organization_id,
+            savepoint_id,
+        )
+        .await?
+        .into_iter()
+        .next()
+        .ok_or_else(|| not_found("Savepoint"))?;
+
+        if savepoint.state != "ready" {
+            return Err(bad_request(format!(
+                "Savepoint '{}' is {}; only ready savepoints can be restored from",
+                savepoint.name, savepoint.state
+            )));
+        }
+    }
+
     let pipeline_id = create_pipeline_int(
         pipeline_post.name,
         pipeline_post.query,
@@ -573,6 +735,8 @@ pub async fn create_pipeline(
         checkpoint_interval,
         false,
         true,
+        None,
+        pipeline_post.savepoint_id,
         auth_data.clone(),
         &state.database,
     )
@@ -612,6 +776,8 @@ pub async fn create_preview_pipeline(
         Duration::MAX,
         true,
         req.enable_sinks,
+        req.node_id,
+        None,
         auth_data.clone(),
         &state.database,
     )
@@ -624,6 +790,11 @@ pub async fn create_preview_pipeline(
 }
 
 /// Update a pipeline
+///
+/// Setting `parallelism` rescales a running pipeline: the job takes a checkpoint, stops, and is
+/// restarted from it with the new parallelism, with its state redistributed across the new
+/// subtasks. When its workers have enough slots, it's restarted on them rather than on new ones.
+/// Pipelines with autoscaling bounds may later be rescaled again by the controller.
 #[utoipa::path(
     patch,
     path = "/v1/pipelines/{id}",
@@ -675,6 +846,13 @@ pub async fn patch_pipeline(
     }
 
     let parallelism_overrides = if let Some(parallelism) = pipeline_patch.parallelism {
+        if parallelism == 0 || parallelism > KEY_GROUPS as u64 {
+            return Err(bad_request(format!(
+                "parallelism must be between 1 and {}",
+                KEY_GROUPS
+            )));
+        }
+
         let res = api_queries::fetch_get_job_details(&db, &auth_data.organization_id, &job_id)
             .await?
             .into_iter()
@@ -713,6 +891,81 @@ pub async fn patch_pipeline(
     Ok(Json(pipeline))
 }
 
+/// Compare a pipeline with an updated query
+///
+/// Compiles the query and compares its plan with the plan the pipeline is running, reporting
+/// which operators would be added, removed or changed and whether the state of each can be
+/// restored from the pipeline's checkpoints.
+#[utoipa::path(
+    post,
+    path = "/v1/pipelines/{id}/diff",
+    tag = "pipelines",
+    params(
+        ("id" = String, Path, description = "Pipeline id")
+    ),
+    request_body = PipelineDiffPost,
+    responses(
+        (status = 200, description = "Differences between the plans", body = PipelineDiff),
+        (status = 400, description = "Bad request", body = ErrorResp),
+    ),
+)]
+pub async fn diff_pipeline(
+    State(state): State<AppState>,
+    bearer_auth: BearerAuth,
+    Path(pipeline_pub_id): Path<String>,
+    WithRejection(Json(req), _): WithRejection<Json<PipelineDiffPost>, ApiError>,
+) -> Result<Json<PipelineDiff>, ErrorResp> {
+    let auth_data = authenticate(&state.database, bearer_auth).await?;
+
+    let pipeline = api_queries::fetch_get_pipeline(
+        &state.database.client().await?,
+        &pipeline_pub_id,
+        &auth_data.organization_id,
+    )
+    .await?
+    .into_iter()
+    .next()
+    .ok_or_else(|| not_found("Pipeline"))?;
+
+    let mut old: LogicalProgram = ArrowProgram::decode(&pipeline.program[..])
+        .map_err(log_and_map)?
+        .try_into()
+        .map_err(log_and_map)?;
+
+    old.update_parallelism(
+        &pipeline
+            .parallelism_overrides
+            .as_object()
+            .unwrap()
+            .into_iter()
+            .map(|(k, v)| (k.clone(), v.as_u64().unwrap() as usize))
+            .collect(),
+    );
+
+    // plan the update at the pipeline's current parallelism, so that only real changes show up
+    let parallelism = old
+        .graph
+        .node_weights()
+        .map(|n| n.parallelism)
+        .max()
+        .unwrap_or(1);
+
+    let mut udfs = req.udfs.unwrap_or_default();
+    let new = compile_sql(
+        req.query,
+        &mut udfs,
+        parallelism,
+        &auth_data,
+        true,
+        false,
+        &state.database,
+    )
+    .await?
+    .program;
+
+    Ok(Json(diff_programs(&old, &new)))
+}
+
 /// Restart a pipeline
 #[utoipa::path(
     post,
@@ -927,6 +1180,37 @@ pub async fn get_pipeline_jobs(
     }))
 }
 
+/// Get a pipeline's daily resource usage
+#[utoipa::path(
+    get,
+    path = "/v1/pipelines/{id}/usage",
+    tag = "pipelines",
+    params(
+        ("id" = String, Path, description = "Pipeline id")
+    ),
+    responses(
+        (status = 200, description = "Got pipeline usage", body = PipelineUsageCollection),
+    ),
+)]
+pub async fn get_pipeline_usage(
+    State(state): State<AppState>,
+    bearer_auth: BearerAuth,
+    Path(pipeline_pub_id): Path<String>,
+) -> Result<Json<PipelineUsageCollection>, ErrorResp> {
+    let db = state.database.client().await?;
+    let auth_data = authenticate(&state.database, bearer_auth).await?;
+
+    query_pipeline_by_pub_id(&pipeline_pub_id, &db, &auth_data).await?;
+
+    let usage: Vec<DbPipelineUsage> =
+        api_queries::fetch_get_pipeline_usage(&db, &auth_data.organization_id, &pipeline_pub_id)
+            .await?;
+
+    Ok(Json(PipelineUsageCollection {
+        data: usage.into_iter().map(|u| u.into()).collect(),
+    }))
+}
+
 pub async fn query_pipeline_by_pub_id<'a>(
     pipeline_pub_id: &String,
     db: &Database<'a>,
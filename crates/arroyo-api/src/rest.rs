@@ -16,19 +16,23 @@ use crate::connection_profiles::{
     get_connection_profiles, test_connection_profile,
 };
 use crate::connection_tables::{
-    create_connection_table, delete_connection_table, get_connection_tables, test_connection_table,
-    test_schema,
+    create_connection_table, delete_connection_table, get_connection_tables, infer_schema,
+    test_connection_table, test_schema,
 };
 use crate::connectors::get_connectors;
 use crate::jobs::{
     get_checkpoint_details, get_job_checkpoints, get_job_errors, get_job_output, get_jobs,
+    query_job_state, set_source_offsets, watch_job,
 };
 use crate::metrics::get_operator_metric_groups;
 use crate::pipelines::{
-    create_pipeline, create_preview_pipeline, delete_pipeline, get_pipeline, get_pipeline_jobs,
-    get_pipelines, patch_pipeline, restart_pipeline, validate_query,
+    create_pipeline, create_preview_pipeline, delete_pipeline, diff_pipeline, get_pipeline,
+    get_pipeline_jobs, get_pipeline_usage, get_pipelines, patch_pipeline, restart_pipeline,
+    validate_query,
 };
 use crate::rest_utils::not_found;
+use crate::savepoints::{create_savepoint, get_savepoints};
+use crate::sql::query_system_tables;
 use crate::udfs::{create_udf, delete_udf, get_udfs, validate_udf};
 use crate::ApiDoc;
 use arroyo_rpc::config::config;
@@ -133,6 +137,9 @@ pub fn create_rest_app(database: DatabaseSource, controller_addr: &str) -> Route
             get(get_checkpoint_details),
         )
         .route("/:job_id/output", get(get_job_output))
+        .route("/:job_id/watch", get(watch_job))
+        .route("/:job_id/source_offsets", post(set_source_offsets))
+        .route("/:job_id/state", get(query_job_state))
         .route(
             "/:job_id/operator_metric_groups",
             get(get_operator_metric_groups),
@@ -156,6 +163,7 @@ pub fn create_rest_app(database: DatabaseSource, controller_addr: &str) -> Route
         .route("/connection_tables", post(create_connection_table))
         .route("/connection_tables/test", post(test_connection_table))
         .route("/connection_tables/schemas/test", post(test_schema))
+        .route("/connection_tables/schemas/infer", post(infer_schema))
         .route("/connection_tables/:id", delete(delete_connection_table))
         .route("/udfs", post(create_udf))
         .route("/udfs", get(get_udfs))
@@ -166,9 +174,14 @@ pub fn create_rest_app(database: DatabaseSource, controller_addr: &str) -> Route
         .route("/pipelines", get(get_pipelines))
         .route("/jobs", get(get_jobs))
         .route("/pipelines/validate_query", post(validate_query))
+        .route("/system/query", post(query_system_tables))
         .route("/pipelines/:id", patch(patch_pipeline))
         .route("/pipelines/:id", get(get_pipeline))
         .route("/pipelines/:id/restart", post(restart_pipeline))
+        .route("/pipelines/:id/diff", post(diff_pipeline))
+        .route("/pipelines/:id/usage", get(get_pipeline_usage))
+        .route("/pipelines/:id/savepoints", post(create_savepoint))
+        .route("/pipelines/:id/savepoints", get(get_savepoints))
         .route("/pipelines/:id", delete(delete_pipeline))
         .nest("/pipelines/:id/jobs", jobs_routes)
         .fallback(api_fallback);
@@ -0,0 +1,156 @@
+use crate::queries::api_queries;
+use crate::queries::api_queries::DbSavepoint;
+use crate::rest::AppState;
+use crate::rest_utils::{
+    authenticate, bad_request, internal_server_error, log_and_map, map_insert_err, not_found,
+    required_field, service_unavailable, ApiError, BearerAuth, ErrorResp,
+};
+use crate::to_micros;
+use arroyo_rpc::api_types::checkpoints::{Savepoint, SavepointPost, SavepointState};
+use arroyo_rpc::api_types::SavepointCollection;
+use arroyo_rpc::grpc::rpc::controller_grpc_client::ControllerGrpcClient;
+use arroyo_rpc::grpc::rpc::TakeSavepointReq;
+use arroyo_rpc::public_ids::{generate_id, IdTypes};
+use axum::extract::{Path, State};
+use axum::Json;
+use axum_extra::extract::WithRejection;
+use tonic::{Code, Request};
+use tracing::warn;
+
+impl From<DbSavepoint> for Savepoint {
+    fn from(val: DbSavepoint) -> Self {
+        Savepoint {
+            id: val.pub_id,
+            name: val.name,
+            pipeline_id: val.pipeline_id,
+            job_id: val.job_id,
+            state: match val.state.as_str() {
+                "ready" => SavepointState::Ready,
+                "failed" => SavepointState::Failed,
+                _ => SavepointState::Pending,
+            },
+            epoch: val.epoch.map(|e| e as u32),
+            created_at: to_micros(val.created_at),
+            failure_message: val.failure_message,
+        }
+    }
+}
+
+/// Take a savepoint of a pipeline's running job
+///
+/// The savepoint is written from the job's next checkpoint, after which its state becomes
+/// `ready` and new pipelines can be started from it.
+#[utoipa::path(
+    post,
+    path = "/v1/pipelines/{id}/savepoints",
+    tag = "pipelines",
+    params(
+        ("id" = String, Path, description = "Pipeline id")
+    ),
+    request_body = SavepointPost,
+    responses(
+        (status = 200, description = "Created savepoint", body = Savepoint),
+        (status = 400, description = "Bad request", body = ErrorResp),
+    ),
+)]
+pub async fn create_savepoint(
+    State(state): State<AppState>,
+    bearer_auth: BearerAuth,
+    Path(pipeline_pub_id): Path<String>,
+    WithRejection(Json(req), _): WithRejection<Json<SavepointPost>, ApiError>,
+) -> Result<Json<Savepoint>, ErrorResp> {
+    let auth_data = authenticate(&state.database, bearer_auth).await?;
+    let db = state.database.client().await?;
+
+    if req.name.is_empty() {
+        return Err(required_field("name"));
+    }
+
+    let job =
+        api_queries::fetch_get_pipeline_jobs(&db, &auth_data.organization_id, &pipeline_pub_id)
+            .await?
+            .into_iter()
+            .next()
+            .ok_or_else(|| not_found("Pipeline"))?;
+
+    if job.state.as_deref() != Some("Running") {
+        return Err(bad_request(format!(
+            "Savepoints can only be taken of running pipelines, but the job is {}",
+            job.state.as_deref().unwrap_or("Created")
+        )));
+    }
+
+    let pub_id = generate_id(IdTypes::Savepoint);
+    api_queries::execute_create_savepoint(
+        &db,
+        &pub_id,
+        &auth_data.organization_id,
+        &auth_data.user_id,
+        &req.name,
+        &pipeline_pub_id,
+        &job.id,
+    )
+    .await
+    .map_err(|e| map_insert_err("savepoint", e))?;
+
+    let result = match ControllerGrpcClient::connect(state.controller_addr.clone()).await {
+        Ok(mut controller) => controller
+            .take_savepoint(Request::new(TakeSavepointReq {
+                job_id: job.id.clone(),
+                savepoint_id: pub_id.clone(),
+            }))
+            .await
+            .map_err(|e| match e.code() {
+                Code::FailedPrecondition | Code::NotFound => bad_request(e.message().to_string()),
+                _ => log_and_map(e),
+            }),
+        Err(e) => {
+            warn!("failed to connect to controller: {:?}", e);
+            Err(service_unavailable("Controller"))
+        }
+    };
+
+    if let Err(e) = result {
+        api_queries::execute_fail_savepoint(&db, &e.message, &pub_id).await?;
+        return Err(e);
+    }
+
+    let savepoint = api_queries::fetch_get_savepoint(&db, &auth_data.organization_id, &pub_id)
+        .await?
+        .into_iter()
+        .next()
+        .ok_or_else(|| internal_server_error("Failed to fetch created savepoint"))?;
+
+    Ok(Json(savepoint.into()))
+}
+
+/// List the savepoints taken of a pipeline
+#[utoipa::path(
+    get,
+    path = "/v1/pipelines/{id}/savepoints",
+    tag = "pipelines",
+    params(
+        ("id" = String, Path, description = "Pipeline id")
+    ),
+    responses(
+        (status = 200, description = "The pipeline's savepoints", body = SavepointCollection),
+    ),
+)]
+pub async fn get_savepoints(
+    State(state): State<AppState>,
+    bearer_auth: BearerAuth,
+    Path(pipeline_pub_id): Path<String>,
+) -> Result<Json<SavepointCollection>, ErrorResp> {
+    let auth_data = authenticate(&state.database, bearer_auth).await?;
+
+    let savepoints = api_queries::fetch_get_pipeline_savepoints(
+        &state.database.client().await?,
+        &auth_data.organization_id,
+        &pipeline_pub_id,
+    )
+    .await?;
+
+    Ok(Json(SavepointCollection {
+        data: savepoints.into_iter().map(|s| s.into()).collect(),
+    }))
+}
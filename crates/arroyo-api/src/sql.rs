@@ -1 +1,482 @@
+//! System tables, which expose Arroyo's own jobs, checkpoints, and metrics to ad-hoc SQL
+//! queries for debugging. Unlike pipeline queries, these are run once as batch queries over a
+//! snapshot of the tables taken when the query is submitted.
 
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use arrow::array::{
+    ArrayRef, BooleanArray, Float64Array, RecordBatch, StringArray, TimestampMicrosecondArray,
+    UInt32Array, UInt64Array,
+};
+use arrow_schema::{DataType, Field, Schema, TimeUnit};
+use arroyo_rpc::api_types::checkpoints::Checkpoint;
+use arroyo_rpc::api_types::metrics::OperatorMetricGroup;
+use arroyo_rpc::api_types::pipelines::{Job, SystemQueryPost, SystemQueryResult};
+use axum::extract::State;
+use axum::Json;
+use axum_extra::extract::WithRejection;
+use datafusion::datasource::MemTable;
+use datafusion::execution::context::{SQLOptions, SessionContext};
+use datafusion::prelude::SessionConfig;
+
+use crate::metrics::fetch_jobs_metrics;
+use crate::queries::api_queries;
+use crate::rest::AppState;
+use crate::rest_utils::{authenticate, bad_request, log_and_map, ApiError, BearerAuth, ErrorResp};
+use crate::{to_micros, AuthData};
+
+const SYSTEM_CATALOG: &str = "arroyo";
+const SYSTEM_SCHEMA: &str = "system";
+
+fn timestamp_type() -> DataType {
+    DataType::Timestamp(TimeUnit::Microsecond, None)
+}
+
+fn batch(fields: Vec<Field>, columns: Vec<ArrayRef>) -> Result<RecordBatch, ErrorResp> {
+    RecordBatch::try_new(Arc::new(Schema::new(fields)), columns).map_err(log_and_map)
+}
+
+fn jobs_table(jobs: &[Job]) -> Result<RecordBatch, ErrorResp> {
+    batch(
+        vec![
+            Field::new("job_id", DataType::Utf8, false),
+            Field::new("state", DataType::Utf8, false),
+            Field::new("running_desired", DataType::Boolean, false),
+            Field::new("run_id", DataType::UInt64, false),
+            Field::new("tasks", DataType::UInt64, true),
+            Field::new("start_time", timestamp_type(), true),
+            Field::new("finish_time", timestamp_type(), true),
+            Field::new("failure_message", DataType::Utf8, true),
+            Field::new("created_at", timestamp_type(), false),
+        ],
+        vec![
+            Arc::new(StringArray::from_iter_values(jobs.iter().map(|j| &j.id))),
+            Arc::new(StringArray::from_iter_values(jobs.iter().map(|j| &j.state))),
+            Arc::new(BooleanArray::from_iter(
+                jobs.iter().map(|j| Some(j.running_desired)),
+            )),
+            Arc::new(UInt64Array::from_iter_values(jobs.iter().map(|j| j.run_id))),
+            Arc::new(UInt64Array::from_iter(jobs.iter().map(|j| j.tasks))),
+            Arc::new(TimestampMicrosecondArray::from_iter(
+                jobs.iter().map(|j| j.start_time.map(|t| t as i64)),
+            )),
+            Arc::new(TimestampMicrosecondArray::from_iter(
+                jobs.iter().map(|j| j.finish_time.map(|t| t as i64)),
+            )),
+            Arc::new(StringArray::from_iter(
+                jobs.iter().map(|j| j.failure_message.as_ref()),
+            )),
+            Arc::new(TimestampMicrosecondArray::from_iter_values(
+                jobs.iter().map(|j| j.created_at as i64),
+            )),
+        ],
+    )
+}
+
+fn checkpoints_table(checkpoints: &[(String, Checkpoint)]) -> Result<RecordBatch, ErrorResp> {
+    batch(
+        vec![
+            Field::new("job_id", DataType::Utf8, false),
+            Field::new("epoch", DataType::UInt32, false),
+            Field::new("backend", DataType::Utf8, false),
+            Field::new("start_time", timestamp_type(), false),
+            Field::new("finish_time", timestamp_type(), true),
+        ],
+        vec![
+            Arc::new(StringArray::from_iter_values(
+                checkpoints.iter().map(|(job_id, _)| job_id),
+            )),
+            Arc::new(UInt32Array::from_iter_values(
+                checkpoints.iter().map(|(_, c)| c.epoch),
+            )),
+            Arc::new(StringArray::from_iter_values(
+                checkpoints.iter().map(|(_, c)| &c.backend),
+            )),
+            Arc::new(TimestampMicrosecondArray::from_iter_values(
+                checkpoints.iter().map(|(_, c)| c.start_time as i64),
+            )),
+            Arc::new(TimestampMicrosecondArray::from_iter(
+                checkpoints
+                    .iter()
+                    .map(|(_, c)| c.finish_time.map(|t| t as i64)),
+            )),
+        ],
+    )
+}
+
+fn operators_table(
+    metrics: &HashMap<String, Vec<OperatorMetricGroup>>,
+) -> Result<RecordBatch, ErrorResp> {
+    let mut job_ids = vec![];
+    let mut operator_ids = vec![];
+    let mut subtasks = vec![];
+    let mut metric_names = vec![];
+    let mut times = vec![];
+    let mut values = vec![];
+
+    let mut jobs: Vec<_> = metrics.iter().collect();
+    jobs.sort_by_key(|(job_id, _)| *job_id);
+
+    for (job_id, operators) in jobs {
+        for operator in operators {
+            for group in &operator.metric_groups {
+                for subtask in &group.subtasks {
+                    for metric in &subtask.metrics {
+                        job_ids.push(job_id.clone());
+                        operator_ids.push(operator.operator_id.clone());
+                        subtasks.push(subtask.index);
+                        metric_names.push(group.name.as_ref().to_string());
+                        times.push(metric.time as i64);
+                        values.push(metric.value);
+                    }
+                }
+            }
+        }
+    }
+
+    batch(
+        vec![
+            Field::new("job_id", DataType::Utf8, false),
+            Field::new("operator_id", DataType::Utf8, false),
+            Field::new("subtask_index", DataType::UInt32, false),
+            Field::new("metric", DataType::Utf8, false),
+            Field::new("time", timestamp_type(), false),
+            Field::new("value", DataType::Float64, false),
+        ],
+        vec![
+            Arc::new(StringArray::from(job_ids)),
+            Arc::new(StringArray::from(operator_ids)),
+            Arc::new(UInt32Array::from(subtasks)),
+            Arc::new(StringArray::from(metric_names)),
+            Arc::new(TimestampMicrosecondArray::from(times)),
+            Arc::new(Float64Array::from(values)),
+        ],
+    )
+}
+
+fn new_session() -> SessionContext {
+    SessionContext::new_with_config(
+        SessionConfig::new().with_default_catalog_and_schema(SYSTEM_CATALOG, SYSTEM_SCHEMA),
+    )
+}
+
+/// The names of the system tables that a query reads, so that only those are loaded
+fn referenced_tables(ctx: &SessionContext, query: &str) -> Result<HashSet<String>, ErrorResp> {
+    let state = ctx.state();
+    let statement = state
+        .sql_to_statement(query, "generic")
+        .map_err(|e| bad_request(e.to_string()))?;
+
+    Ok(state
+        .resolve_table_references(&statement)
+        .map_err(|e| bad_request(e.to_string()))?
+        .into_iter()
+        .map(|table| table.table().to_string())
+        .collect())
+}
+
+fn register_tables(
+    ctx: &SessionContext,
+    tables: Vec<(&str, RecordBatch)>,
+) -> Result<(), ErrorResp> {
+    for (name, table) in tables {
+        let table = MemTable::try_new(table.schema(), vec![vec![table]]).map_err(log_and_map)?;
+        ctx.register_table(name, Arc::new(table))
+            .map_err(log_and_map)?;
+    }
+
+    Ok(())
+}
+
+/// Builds a session with the system tables that the query reads for the authenticated
+/// organization, registered as `system.jobs`, `system.checkpoints`, and `system.operators`.
+/// Metrics are only fetched from the controller for queries over `system.operators`.
+async fn system_session(
+    state: &AppState,
+    auth_data: &AuthData,
+    query: &str,
+) -> Result<SessionContext, ErrorResp> {
+    let ctx = new_session();
+    let referenced = referenced_tables(&ctx, query)?;
+
+    let db = state.database.client().await?;
+
+    let jobs: Vec<Job> = api_queries::fetch_get_all_jobs(&db, &auth_data.organization_id)
+        .await?
+        .into_iter()
+        .map(|j| j.into())
+        .collect();
+
+    let mut tables = vec![];
+
+    if referenced.contains("checkpoints") {
+        let checkpoints: Vec<_> =
+            api_queries::fetch_get_all_job_checkpoints(&db, &auth_data.organization_id)
+                .await
+                .map_err(log_and_map)?
+                .into_iter()
+                .map(|c| {
+                    (
+                        c.job_id,
+                        Checkpoint {
+                            epoch: c.epoch as u32,
+                            backend: c.state_backend,
+                            start_time: to_micros(c.start_time),
+                            finish_time: c.finish_time.map(to_micros),
+                        },
+                    )
+                })
+                .collect();
+        tables.push(("checkpoints", checkpoints_table(&checkpoints)?));
+    }
+
+    if referenced.contains("operators") {
+        // only running jobs have metrics
+        let running = jobs
+            .iter()
+            .filter(|j| j.state == "Running")
+            .map(|j| j.id.clone())
+            .collect();
+        let metrics = fetch_jobs_metrics(state, running).await?;
+        tables.push(("operators", operators_table(&metrics)?));
+    }
+
+    tables.push(("jobs", jobs_table(&jobs)?));
+    register_tables(&ctx, tables)?;
+
+    Ok(ctx)
+}
+
+/// Runs a read-only query in the session
+async fn run_query(ctx: &SessionContext, query: &str) -> Result<SystemQueryResult, ErrorResp> {
+    // system tables are read-only
+    let options = SQLOptions::new()
+        .with_allow_ddl(false)
+        .with_allow_dml(false)
+        .with_allow_statements(false);
+
+    let df = ctx
+        .sql_with_options(query, options)
+        .await
+        .map_err(|e| bad_request(e.to_string()))?;
+
+    let columns = df
+        .schema()
+        .fields()
+        .iter()
+        .map(|f| f.name().clone())
+        .collect();
+
+    let batches = df.collect().await.map_err(|e| bad_request(e.to_string()))?;
+
+    query_result(columns, &batches)
+}
+
+/// Run a query over the system tables
+///
+/// The system tables (`system.jobs`, `system.checkpoints`, and `system.operators`) expose the
+/// state and metrics of the organization's jobs for ad-hoc debugging.
+#[utoipa::path(
+    post,
+    path = "/v1/system/query",
+    tag = "system",
+    request_body = SystemQueryPost,
+    responses(
+        (status = 200, description = "Query results", body = SystemQueryResult),
+        (status = 400, description = "Invalid query", body = ErrorResp),
+    ),
+)]
+pub async fn query_system_tables(
+    State(state): State<AppState>,
+    bearer_auth: BearerAuth,
+    WithRejection(Json(query_post), _): WithRejection<Json<SystemQueryPost>, ApiError>,
+) -> Result<Json<SystemQueryResult>, ErrorResp> {
+    let auth_data = authenticate(&state.database, bearer_auth).await?;
+
+    let ctx = system_session(&state, &auth_data, &query_post.query).await?;
+
+    Ok(Json(run_query(&ctx, &query_post.query).await?))
+}
+
+/// Converts the batches returned by a query into rows of JSON
+pub(crate) fn query_result(
+    columns: Vec<String>,
+    batches: &[RecordBatch],
+) -> Result<SystemQueryResult, ErrorResp> {
+    let mut writer = arrow_json::ArrayWriter::new(vec![]);
+    for batch in batches {
+        writer.write(batch).map_err(log_and_map)?;
+    }
+    writer.finish().map_err(log_and_map)?;
+
+    let buf = writer.into_inner();
+    let rows = if buf.is_empty() {
+        vec![]
+    } else {
+        serde_json::from_slice(&buf).map_err(log_and_map)?
+    };
+
+    Ok(SystemQueryResult { columns, rows })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arroyo_rpc::api_types::metrics::{Metric, MetricGroup, MetricName, SubtaskMetrics};
+    use axum::http::StatusCode;
+    use serde_json::json;
+
+    fn job(id: &str, state: &str) -> Job {
+        Job {
+            id: id.to_string(),
+            running_desired: true,
+            state: state.to_string(),
+            run_id: 1,
+            start_time: Some(1_000),
+            finish_time: None,
+            tasks: Some(2),
+            failure_message: None,
+            created_at: 500,
+        }
+    }
+
+    fn checkpoint(epoch: u32) -> Checkpoint {
+        Checkpoint {
+            epoch,
+            backend: "parquet".to_string(),
+            start_time: epoch as u64 * 1_000,
+            finish_time: Some(epoch as u64 * 1_000 + 100),
+        }
+    }
+
+    fn session() -> SessionContext {
+        let jobs = vec![job("job_a", "Running"), job("job_b", "Stopped")];
+        let checkpoints = vec![
+            ("job_a".to_string(), checkpoint(1)),
+            ("job_a".to_string(), checkpoint(2)),
+            ("job_b".to_string(), checkpoint(1)),
+        ];
+        let metrics = HashMap::from([(
+            "job_a".to_string(),
+            vec![OperatorMetricGroup {
+                operator_id: "source_0".to_string(),
+                metric_groups: vec![MetricGroup {
+                    name: MetricName::MessagesSent,
+                    subtasks: (0..2)
+                        .map(|index| SubtaskMetrics {
+                            index,
+                            metrics: vec![Metric {
+                                time: 2_000,
+                                value: 10.0 * (index + 1) as f64,
+                            }],
+                        })
+                        .collect(),
+                }],
+            }],
+        )]);
+
+        let ctx = new_session();
+        register_tables(
+            &ctx,
+            vec![
+                ("jobs", jobs_table(&jobs).unwrap()),
+                ("checkpoints", checkpoints_table(&checkpoints).unwrap()),
+                ("operators", operators_table(&metrics).unwrap()),
+            ],
+        )
+        .unwrap();
+        ctx
+    }
+
+    #[tokio::test]
+    async fn test_query_system_tables() {
+        let ctx = session();
+
+        let result = run_query(
+            &ctx,
+            "SELECT j.job_id, j.state, count(c.epoch) AS checkpoints \
+            FROM jobs j JOIN system.checkpoints c ON j.job_id = c.job_id \
+            GROUP BY j.job_id, j.state ORDER BY j.job_id",
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(result.columns, vec!["job_id", "state", "checkpoints"]);
+        assert_eq!(
+            result.rows,
+            vec![
+                json!({"job_id": "job_a", "state": "Running", "checkpoints": 2}),
+                json!({"job_id": "job_b", "state": "Stopped", "checkpoints": 1}),
+            ]
+        );
+
+        let result = run_query(
+            &ctx,
+            "SELECT subtask_index, value FROM operators \
+            WHERE job_id = 'job_a' AND metric = 'messages_sent' ORDER BY subtask_index",
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            result.rows,
+            vec![
+                json!({"subtask_index": 0, "value": 10.0}),
+                json!({"subtask_index": 1, "value": 20.0}),
+            ]
+        );
+
+        let result = run_query(&ctx, "SELECT job_id FROM jobs WHERE state = 'Failed'")
+            .await
+            .unwrap();
+        assert_eq!(result.columns, vec!["job_id"]);
+        assert!(result.rows.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_system_tables_are_read_only() {
+        let ctx = session();
+
+        for query in [
+            "CREATE TABLE foo (x INT)",
+            "INSERT INTO jobs (job_id) VALUES ('job_c')",
+            "DROP TABLE jobs",
+            "SET datafusion.execution.batch_size = 1",
+        ] {
+            let err = run_query(&ctx, query).await.unwrap_err();
+            assert_eq!(err.status_code, StatusCode::BAD_REQUEST, "{}", query);
+        }
+    }
+
+    #[test]
+    fn test_referenced_tables() {
+        let ctx = new_session();
+
+        assert_eq!(
+            referenced_tables(&ctx, "SELECT * FROM jobs").unwrap(),
+            HashSet::from(["jobs".to_string()])
+        );
+
+        assert_eq!(
+            referenced_tables(
+                &ctx,
+                "SELECT * FROM system.jobs JOIN arroyo.system.operators USING (job_id)"
+            )
+            .unwrap(),
+            HashSet::from(["jobs".to_string(), "operators".to_string()])
+        );
+
+        // queries that don't read the operators table don't need the controller
+        assert!(!referenced_tables(&ctx, "SELECT count(*) FROM checkpoints")
+            .unwrap()
+            .contains("operators"));
+
+        assert_eq!(
+            referenced_tables(&ctx, "SELEC * FROM jobs")
+                .unwrap_err()
+                .status_code,
+            StatusCode::BAD_REQUEST
+        );
+    }
+}
@@ -42,6 +42,7 @@ impl From<DbUdf> for GlobalUdf {
             updated_at: to_micros(val.updated_at),
             description: val.description,
             dylib_url: val.dylib_url,
+            cargo_lock: val.cargo_lock,
             language: UdfLanguage::from_str(&val.language).unwrap_or_default(),
         }
     }
@@ -70,11 +71,13 @@ pub async fn create_udf(
     //     .await
     //     .map_err(log_and_map)?;
 
-    // build udf
+    // build udf, recording the dependencies it was resolved to so that every pipeline that uses
+    // it runs against the same versions
     let build_udf_resp = build_udf(
         &mut compiler_service().await?,
         &req.definition,
         req.language,
+        req.cargo_lock.as_deref(),
         true,
     )
     .await?;
@@ -100,6 +103,7 @@ pub async fn create_udf(
         &req.definition,
         &req.description.unwrap_or_default(),
         &build_udf_resp.url,
+        &build_udf_resp.cargo_lock,
     )
     .await
     .map_err(|e| map_insert_err("udf", e))?;
@@ -177,6 +181,7 @@ pub struct UdfResp {
     pub errors: Vec<String>,
     pub name: Option<String>,
     pub url: Option<String>,
+    pub cargo_lock: Option<String>,
 }
 
 impl From<anyhow::Error> for UdfResp {
@@ -185,6 +190,7 @@ impl From<anyhow::Error> for UdfResp {
             errors: vec![value.to_string()],
             name: None,
             url: None,
+            cargo_lock: None,
         }
     }
 }
@@ -193,6 +199,7 @@ pub async fn build_udf(
     compiler_service: &mut CompilerGrpcClient<Channel>,
     udf_definition: &str,
     language: UdfLanguage,
+    cargo_lock: Option<&str>,
     save: bool,
 ) -> Result<UdfResp, ErrorResp> {
     match language {
@@ -201,11 +208,13 @@ pub async fn build_udf(
                 errors: vec![],
                 name: Some(Arc::unwrap_or_clone(udf.name)),
                 url: None,
+                cargo_lock: None,
             }),
             Err(e) => Ok(UdfResp {
                 errors: vec![e.to_string()],
                 name: None,
                 url: None,
+                cargo_lock: None,
             }),
         },
         UdfLanguage::Rust => {
@@ -237,6 +246,7 @@ pub async fn build_udf(
                         name: file.udf.name.clone(),
                         definition: udf_definition.to_string(),
                         dependencies: dependencies.to_string(),
+                        cargo_lock: cargo_lock.map(|s| s.to_string()),
                     }),
                     save,
                 })
@@ -256,6 +266,7 @@ pub async fn build_udf(
                 errors: check_udfs_resp.errors,
                 name: Some(file.udf.name),
                 url: check_udfs_resp.udf_path,
+                cargo_lock: check_udfs_resp.cargo_lock,
             })
         }
     }
@@ -278,6 +289,7 @@ pub async fn validate_udf(
         &mut compiler_service().await?,
         &req.definition,
         req.language,
+        None,
         false,
     )
     .await?;
@@ -0,0 +1,371 @@
+//! A harness for verifying that pipelines process each event exactly once in the face of
+//! failures.
+//!
+//! The harness runs a pipeline that reads a fixed number of events from a deterministic
+//! source (the impulse connector) and writes them to a filesystem sink, which commits its
+//! output transactionally. While the pipeline runs, faults are induced by force-restarting it
+//! through the API, and (when the cluster's workers are configured with `worker.chaos`) by
+//! the workers themselves, which kill themselves and drop their data connections. Once the
+//! source has been exhausted and the pipeline finishes, the committed output is checked to
+//! contain every event exactly once.
+
+use anyhow::{anyhow, bail};
+use arroyo_openapi::types::{PipelinePost, PipelineRestart};
+use arroyo_openapi::Client;
+use rand::random;
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+// the filesystem sink writes files here until they're committed
+const IN_PROGRESS_DIR: &str = "__in_progress";
+
+#[derive(Debug, Clone)]
+pub struct ChaosTestConfig {
+    /// Number of events produced by each subtask of the source
+    pub events: u64,
+
+    /// Number of events produced by the source per second
+    pub event_rate: u64,
+
+    /// Parallelism of the pipeline
+    pub parallelism: u32,
+
+    /// Local directory the sink writes its output to; it should be empty
+    pub output_dir: PathBuf,
+
+    /// Average time between forced restarts of the pipeline, if set; zero disables them
+    pub restart_interval: Option<Duration>,
+
+    /// How long to wait for the pipeline to finish before giving up
+    pub timeout: Duration,
+}
+
+impl ChaosTestConfig {
+    pub fn new(output_dir: impl Into<PathBuf>) -> Self {
+        Self {
+            events: 100_000,
+            event_rate: 1_000,
+            parallelism: 2,
+            output_dir: output_dir.into(),
+            restart_interval: Some(Duration::from_secs(30)),
+            timeout: Duration::from_secs(30 * 60),
+        }
+    }
+
+    /// The query run by the test
+    pub fn query(&self) -> String {
+        format!(
+            "CREATE TABLE impulse WITH (
+                connector = 'impulse',
+                event_rate = '{}',
+                message_count = '{}',
+                parallelism = '{}'
+            );
+
+            CREATE TABLE output (
+                subtask_index BIGINT,
+                counter BIGINT
+            ) WITH (
+                connector = 'filesystem',
+                type = 'sink',
+                path = '{}',
+                format = 'json',
+                rollover_seconds = '5'
+            );
+
+            INSERT INTO output
+            SELECT CAST(subtask_index AS BIGINT), CAST(counter AS BIGINT)
+            FROM impulse;",
+            self.event_rate,
+            self.events,
+            self.parallelism,
+            self.output_dir.to_string_lossy()
+        )
+    }
+}
+
+/// The result of checking the output of a chaos test
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ChaosReport {
+    /// Number of distinct events the source produced
+    pub expected: u64,
+
+    /// Number of events found in the output, including duplicates
+    pub received: u64,
+
+    /// Events (as subtask index and counter) that were not found in the output
+    pub missing: Vec<(u64, u64)>,
+
+    /// Events that were found in the output more than once
+    pub duplicated: Vec<(u64, u64)>,
+
+    /// Events in the output that the source should never have produced
+    pub unexpected: Vec<(u64, u64)>,
+
+    /// Number of times the pipeline was force-restarted by the harness
+    pub restarts: u32,
+}
+
+impl ChaosReport {
+    /// Builds a report from the events found in the output of a source with `subtasks`
+    /// subtasks that each produced `events` events
+    pub fn from_events(
+        subtasks: u64,
+        events: u64,
+        received: impl IntoIterator<Item = (u64, u64)>,
+    ) -> Self {
+        let mut counts: HashMap<(u64, u64), u64> = HashMap::new();
+        let mut report = Self {
+            expected: subtasks * events,
+            ..Default::default()
+        };
+
+        for event in received {
+            report.received += 1;
+            *counts.entry(event).or_default() += 1;
+        }
+
+        for subtask in 0..subtasks {
+            for counter in 0..events {
+                match counts.remove(&(subtask, counter)) {
+                    None => report.missing.push((subtask, counter)),
+                    Some(1) => {}
+                    Some(_) => report.duplicated.push((subtask, counter)),
+                }
+            }
+        }
+
+        report.unexpected = counts.into_keys().collect();
+        report.unexpected.sort();
+
+        report
+    }
+
+    pub fn is_exactly_once(&self) -> bool {
+        self.missing.is_empty() && self.duplicated.is_empty() && self.unexpected.is_empty()
+    }
+}
+
+impl Display for ChaosReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        fn sample(events: &[(u64, u64)]) -> String {
+            let mut s = events
+                .iter()
+                .take(10)
+                .map(|(subtask, counter)| format!("{}/{}", subtask, counter))
+                .collect::<Vec<_>>()
+                .join(", ");
+            if events.len() > 10 {
+                s.push_str(", ...");
+            }
+            s
+        }
+
+        writeln!(
+            f,
+            "expected {} events, received {} after {} forced restarts",
+            self.expected, self.received, self.restarts
+        )?;
+
+        for (name, events) in [
+            ("missing", &self.missing),
+            ("duplicated", &self.duplicated),
+            ("unexpected", &self.unexpected),
+        ] {
+            if !events.is_empty() {
+                writeln!(f, "  {} {} events: {}", events.len(), name, sample(events))?;
+            }
+        }
+
+        if self.is_exactly_once() {
+            write!(f, "every event was received exactly once")
+        } else {
+            write!(f, "output is not exactly-once")
+        }
+    }
+}
+
+fn read_output_events(dir: &Path, events: &mut Vec<(u64, u64)>) -> anyhow::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        if name == IN_PROGRESS_DIR || name.starts_with('.') {
+            continue;
+        }
+
+        if path.is_dir() {
+            read_output_events(&path, events)?;
+            continue;
+        }
+
+        for line in fs::read_to_string(&path)?.lines() {
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let value: serde_json::Value = serde_json::from_str(line)
+                .map_err(|e| anyhow!("invalid output record in {:?}: {}", path, e))?;
+            let field = |name: &str| {
+                value.get(name).and_then(|v| v.as_u64()).ok_or_else(|| {
+                    anyhow!(
+                        "output record in {:?} is missing '{}': {}",
+                        path,
+                        name,
+                        line
+                    )
+                })
+            };
+
+            events.push((field("subtask_index")?, field("counter")?));
+        }
+    }
+
+    Ok(())
+}
+
+/// Checks the committed output written to `config.output_dir` by the test pipeline
+pub fn verify_output(config: &ChaosTestConfig) -> anyhow::Result<ChaosReport> {
+    let mut events = vec![];
+    read_output_events(&config.output_dir, &mut events)?;
+    Ok(ChaosReport::from_events(
+        config.parallelism as u64,
+        config.events,
+        events,
+    ))
+}
+
+fn next_restart(config: &ChaosTestConfig) -> Option<Instant> {
+    // restart at exponentially-distributed intervals
+    config
+        .restart_interval
+        .filter(|i| !i.is_zero())
+        .map(|interval| {
+            let u: f64 = random();
+            Instant::now() + Duration::from_secs_f64(interval.as_secs_f64() * -(1.0 - u).ln())
+        })
+}
+
+async fn job_state(client: &Client, pipeline_id: &str) -> anyhow::Result<String> {
+    let jobs = client
+        .get_pipeline_jobs()
+        .id(pipeline_id)
+        .send()
+        .await?
+        .into_inner();
+
+    Ok(jobs
+        .data
+        .into_iter()
+        .next()
+        .ok_or_else(|| anyhow!("pipeline {} has no jobs", pipeline_id))?
+        .state)
+}
+
+/// Runs the chaos test pipeline on the cluster behind `client`, inducing faults until its
+/// source is exhausted, then checks its output
+pub async fn run_chaos_test(
+    client: &Client,
+    config: &ChaosTestConfig,
+) -> anyhow::Result<ChaosReport> {
+    fs::create_dir_all(&config.output_dir)?;
+    if fs::read_dir(&config.output_dir)?.next().is_some() {
+        bail!(
+            "output directory {:?} must be empty",
+            config.output_dir.to_string_lossy()
+        );
+    }
+
+    let pipeline_id = client
+        .create_pipeline()
+        .body(
+            PipelinePost::builder()
+                .name(format!("chaos_test_{}", random::<u32>()))
+                .parallelism(config.parallelism as i64)
+                .checkpoint_interval_micros(1_000_000)
+                .query(config.query()),
+        )
+        .send()
+        .await?
+        .into_inner()
+        .id;
+
+    info!("Started chaos test pipeline {}", pipeline_id);
+
+    let deadline = Instant::now() + config.timeout;
+    let mut restart_at = next_restart(config);
+    let mut restarts = 0;
+    let mut last_state = String::new();
+
+    loop {
+        if Instant::now() > deadline {
+            bail!(
+                "pipeline {} did not finish within {:?}",
+                pipeline_id,
+                config.timeout
+            );
+        }
+
+        let state = job_state(client, &pipeline_id).await?;
+        if state != last_state {
+            info!("Job transitioned to {}", state);
+            last_state = state.clone();
+        }
+
+        match state.as_str() {
+            "Finished" => break,
+            "Failed" => bail!("pipeline {} failed", pipeline_id),
+            "Running" if restart_at.is_some_and(|t| Instant::now() > t) => {
+                info!("Force-restarting pipeline");
+                if let Err(e) = client
+                    .restart_pipeline()
+                    .id(&pipeline_id)
+                    .body(PipelineRestart::builder().force(Some(true)))
+                    .send()
+                    .await
+                {
+                    warn!("Failed to restart pipeline: {}", e);
+                } else {
+                    restarts += 1;
+                }
+                restart_at = next_restart(config);
+            }
+            _ => {}
+        }
+
+        tokio::time::sleep(Duration::from_millis(500)).await;
+    }
+
+    let mut report = verify_output(config)?;
+    report.restarts = restarts;
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report() {
+        let events = (0..2).flat_map(|s| (0..5).map(move |c| (s, c)));
+
+        let report = ChaosReport::from_events(2, 5, events.clone());
+        assert!(report.is_exactly_once());
+        assert_eq!(report.received, 10);
+
+        let report = ChaosReport::from_events(
+            2,
+            5,
+            events
+                .filter(|e| *e != (0, 3))
+                .chain([(1, 4), (2, 0)].into_iter()),
+        );
+        assert!(!report.is_exactly_once());
+        assert_eq!(report.missing, vec![(0, 3)]);
+        assert_eq!(report.duplicated, vec![(1, 4)]);
+        assert_eq!(report.unexpected, vec![(2, 0)]);
+    }
+}
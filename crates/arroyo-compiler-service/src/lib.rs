@@ -110,11 +110,46 @@ impl CompileService {
 
         tokio::fs::write(self.build_dir.join("Cargo.toml"), &cargo_toml.to_string()).await?;
 
+        // the build dir is shared between UDFs, so a lock file left over from a previous build
+        // must not leak into this one
+        let lock_file = self.build_dir.join("Cargo.lock");
+        match &udf_crate.cargo_lock {
+            Some(cargo_lock) => tokio::fs::write(&lock_file, cargo_lock).await?,
+            None => match tokio::fs::remove_file(&lock_file).await {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+                _ => {}
+            },
+        }
+
         tokio::fs::create_dir_all(&self.build_dir.join("src")).await?;
         tokio::fs::write(self.build_dir.join("src/lib.rs"), &udf_crate.definition).await?;
         Ok(())
     }
 
+    async fn stored_cargo_lock(&self, path: &str) -> Option<String> {
+        let bytes = self.storage.get(cargo_lock_path(path)).await.ok()?;
+        String::from_utf8(bytes.to_vec()).ok()
+    }
+
+    /// An artifact built without a pinned lock file is reused for a pinned build if it was
+    /// resolved to that same lock file; otherwise the pinned build gets its own artifact
+    async fn resolve_dylib_path(
+        &self,
+        name: &str,
+        definition: &str,
+        cargo_lock: Option<&str>,
+    ) -> String {
+        let path = dylib_path(name, definition, None);
+        match cargo_lock {
+            Some(cargo_lock)
+                if self.stored_cargo_lock(&path).await.as_deref() != Some(cargo_lock) =>
+            {
+                dylib_path(name, definition, Some(cargo_lock))
+            }
+            _ => path,
+        }
+    }
+
     async fn check_cargo(&self) -> anyhow::Result<()> {
         if binary_present(&self.cargo_path.lock().await).await {
             return Ok(());
@@ -224,14 +259,25 @@ impl CompileService {
     }
 }
 
-fn dylib_path(name: &str, definition: &str) -> String {
+fn dylib_path(name: &str, definition: &str, cargo_lock: Option<&str>) -> String {
     let mut hasher = DefaultHasher::new();
     definition.hash(&mut hasher);
+    if let Some(cargo_lock) = cargo_lock {
+        cargo_lock.hash(&mut hasher);
+    }
     let hash = BASE64_STANDARD_NO_PAD.encode(hasher.finish().to_le_bytes());
 
     format!("udfs/{}_{}.{}", name, hash, PLATFORM_FILE_EXTENSION)
 }
 
+/// The Cargo.lock an artifact was built with is stored next to it
+fn cargo_lock_path(dylib_path: &str) -> String {
+    let base = dylib_path
+        .strip_suffix(&format!(".{}", PLATFORM_FILE_EXTENSION))
+        .unwrap_or(dylib_path);
+    format!("{}.Cargo.lock", base)
+}
+
 #[tonic::async_trait]
 impl CompilerGrpc for CompileService {
     async fn build_udf(
@@ -253,7 +299,13 @@ impl CompilerGrpc for CompileService {
             .udf_crate
             .ok_or_else(|| Status::failed_precondition("missing udf_crate field"))?;
 
-        let path = dylib_path(&udf_crate.name, &udf_crate.definition);
+        let path = self
+            .resolve_dylib_path(
+                &udf_crate.name,
+                &udf_crate.definition,
+                udf_crate.cargo_lock.as_deref(),
+            )
+            .await;
         let canonical_url = self.storage.canonical_url_for(&path);
 
         // exit early if udf is already compiled
@@ -262,12 +314,14 @@ impl CompilerGrpc for CompileService {
             return Ok(Response::new(BuildUdfResp {
                 errors: vec![],
                 udf_path: Some(canonical_url),
+                cargo_lock: self.stored_cargo_lock(&path).await,
             }));
         }
 
         let start = Instant::now();
 
         let name = udf_crate.name.clone();
+        let locked = udf_crate.cargo_lock.is_some();
         self.write_udf_crate(udf_crate)
             .await
             .map_err(|e| Status::internal(format!("Writing UDFs failed: {}", e)))?;
@@ -275,18 +329,23 @@ impl CompilerGrpc for CompileService {
         let cargo_command = if req.save { "build" } else { "check" };
 
         info!("{}ing udf", cargo_command);
-        let output = Command::new(&*self.cargo_path.lock().await)
+        let mut command = Command::new(&*self.cargo_path.lock().await);
+        command
             .current_dir(&self.build_dir)
             .arg(cargo_command)
             .arg("--release")
-            .arg("--message-format=json")
-            .output()
-            .await
-            .map_err(|e| {
-                Status::internal(format!(
-                    "Failed to run cargo, will not be able to compile UDFs: {e}"
-                ))
-            })?;
+            .arg("--message-format=json");
+
+        if locked {
+            // fail instead of silently resolving different versions than the ones pinned
+            command.arg("--locked");
+        }
+
+        let output = command.output().await.map_err(|e| {
+            Status::internal(format!(
+                "Failed to run cargo, will not be able to compile UDFs: {e}"
+            ))
+        })?;
 
         info!(
             "Finished running cargo {} on udfs crate {} after {:.2}s, exit code: {:?}",
@@ -297,6 +356,10 @@ impl CompilerGrpc for CompileService {
         );
 
         if output.status.success() {
+            let cargo_lock = tokio::fs::read_to_string(self.build_dir.join("Cargo.lock"))
+                .await
+                .map_err(|e| Status::internal(format!("Failed to read Cargo.lock: {}", e)))?;
+
             let udf_path = if req.save {
                 // save dylib to storage
                 let dylib = tokio::fs::read(
@@ -314,6 +377,16 @@ impl CompilerGrpc for CompileService {
                     ))
                 })?;
 
+                self.storage
+                    .put(cargo_lock_path(&path), cargo_lock.clone().into_bytes())
+                    .await
+                    .map_err(|e| {
+                        Status::internal(format!(
+                            "Failed to write UDF Cargo.lock to artifact storage: {}",
+                            e
+                        ))
+                    })?;
+
                 info!("Wrote UDF dylib to {}", canonical_url);
                 Some(canonical_url)
             } else {
@@ -323,6 +396,7 @@ impl CompilerGrpc for CompileService {
             return Ok(Response::new(BuildUdfResp {
                 errors: vec![],
                 udf_path,
+                cargo_lock: Some(cargo_lock),
             }));
         }
 
@@ -359,6 +433,7 @@ impl CompilerGrpc for CompileService {
         return Ok(Response::new(BuildUdfResp {
             errors,
             udf_path: None,
+            cargo_lock: None,
         }));
     }
 
@@ -368,7 +443,7 @@ impl CompilerGrpc for CompileService {
     ) -> Result<Response<GetUdfPathResp>, Status> {
         let req = request.into_inner();
 
-        let path = dylib_path(&req.name, &req.definition);
+        let path = dylib_path(&req.name, &req.definition, None);
         let canonical_url = self.storage.canonical_url_for(&path);
 
         let exists =
@@ -381,3 +456,107 @@ impl CompilerGrpc for CompileService {
         }))
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    async fn service(name: &str) -> CompileService {
+        let dir = std::env::temp_dir().join(format!(
+            "arroyo-compiler-test-{}-{}",
+            name,
+            to_millis(SystemTime::now())
+        ));
+        let artifacts = dir.join("artifacts");
+        tokio::fs::create_dir_all(&artifacts).await.unwrap();
+
+        CompileService {
+            build_dir: dir.join("build"),
+            lock: Arc::new(Mutex::new(())),
+            storage: StorageProvider::for_url(&format!("file://{}", artifacts.to_str().unwrap()))
+                .await
+                .unwrap(),
+            cargo_path: Arc::new(Mutex::new("cargo".to_string())),
+        }
+    }
+
+    fn udf_crate(cargo_lock: Option<&str>) -> UdfCrate {
+        UdfCrate {
+            name: "my_udf".to_string(),
+            definition: "fn my_udf(x: i64) -> i64 { x }".to_string(),
+            dependencies: "".to_string(),
+            cargo_lock: cargo_lock.map(|s| s.to_string()),
+        }
+    }
+
+    #[test]
+    fn test_dylib_path() {
+        let unpinned = dylib_path("my_udf", "def", None);
+        assert_eq!(unpinned, dylib_path("my_udf", "def", None));
+        assert_ne!(unpinned, dylib_path("my_udf", "def", Some("lock a")));
+        assert_ne!(
+            dylib_path("my_udf", "def", Some("lock a")),
+            dylib_path("my_udf", "def", Some("lock b"))
+        );
+
+        assert!(cargo_lock_path(&unpinned).ends_with(".Cargo.lock"));
+        assert!(!cargo_lock_path(&unpinned).contains(PLATFORM_FILE_EXTENSION));
+    }
+
+    #[tokio::test]
+    async fn test_write_udf_crate_lock_file() {
+        let service = service("lock-file").await;
+        let lock_file = service.build_dir.join("Cargo.lock");
+
+        service
+            .write_udf_crate(udf_crate(Some("pinned")))
+            .await
+            .unwrap();
+        assert_eq!(
+            tokio::fs::read_to_string(&lock_file).await.unwrap(),
+            "pinned"
+        );
+
+        // a lock file left over from a pinned build isn't used for an unpinned one
+        service.write_udf_crate(udf_crate(None)).await.unwrap();
+        assert!(!lock_file.exists());
+    }
+
+    #[tokio::test]
+    async fn test_resolve_dylib_path() {
+        let service = service("resolve").await;
+        let unpinned = dylib_path("my_udf", "def", None);
+
+        // nothing has been built yet, so a pinned build gets its own artifact
+        assert_eq!(
+            service
+                .resolve_dylib_path("my_udf", "def", Some("lock a"))
+                .await,
+            dylib_path("my_udf", "def", Some("lock a"))
+        );
+
+        service
+            .storage
+            .put(cargo_lock_path(&unpinned), b"lock a".to_vec())
+            .await
+            .unwrap();
+
+        // the unpinned artifact was resolved to the same lock file, so it's reused
+        assert_eq!(
+            service
+                .resolve_dylib_path("my_udf", "def", Some("lock a"))
+                .await,
+            unpinned
+        );
+        assert_eq!(
+            service
+                .resolve_dylib_path("my_udf", "def", Some("lock b"))
+                .await,
+            dylib_path("my_udf", "def", Some("lock b"))
+        );
+        assert_eq!(
+            service.resolve_dylib_path("my_udf", "def", None).await,
+            unpinned
+        );
+    }
+}
@@ -18,7 +18,8 @@ impl ArrowOperator for BlackholeSinkFunc {
         "BlackholeSink".to_string()
     }
 
-    async fn process_batch(&mut self, _: RecordBatch, _: &mut ArrowContext) {
+    async fn process_batch(&mut self, _: RecordBatch, _: &mut ArrowContext) -> anyhow::Result<()> {
         // no-op
+        Ok(())
     }
 }
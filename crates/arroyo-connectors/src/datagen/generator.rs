@@ -0,0 +1,437 @@
+use anyhow::{anyhow, bail};
+use arrow::array::{
+    ArrayRef, BooleanArray, Float64Array, Int64Array, StringArray, TimestampNanosecondArray,
+};
+use arrow::compute::cast;
+use arrow::datatypes::{DataType, Field};
+use arroyo_types::to_nanos;
+use rand::distributions::{Alphanumeric, DistString, WeightedIndex};
+use rand::rngs::SmallRng;
+use rand::Rng;
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+const MAX_REGEX_REPEAT: u32 = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ColumnType {
+    Int { unsigned: bool },
+    Float,
+    String,
+    Bool,
+    Timestamp,
+}
+
+impl ColumnType {
+    fn of(data_type: &DataType) -> Option<Self> {
+        Some(match data_type {
+            DataType::Int8 | DataType::Int16 | DataType::Int32 | DataType::Int64 => {
+                ColumnType::Int { unsigned: false }
+            }
+            DataType::UInt8 | DataType::UInt16 | DataType::UInt32 | DataType::UInt64 => {
+                ColumnType::Int { unsigned: true }
+            }
+            DataType::Float32 | DataType::Float64 => ColumnType::Float,
+            DataType::Utf8 | DataType::LargeUtf8 => ColumnType::String,
+            DataType::Boolean => ColumnType::Bool,
+            DataType::Timestamp(_, _) => ColumnType::Timestamp,
+            _ => return None,
+        })
+    }
+}
+
+#[derive(Debug)]
+enum Generator {
+    /// `start`, `start + step`, ..., interleaved across subtasks
+    Sequence {
+        start: i64,
+        step: i64,
+    },
+    RandomInt {
+        min: i64,
+        max: i64,
+    },
+    RandomFloat {
+        min: f64,
+        max: f64,
+    },
+    RandomString {
+        length: usize,
+    },
+    RandomBool,
+    Regex(rand_regex::Regex),
+    Enum {
+        values: Vec<String>,
+        weights: WeightedIndex<u64>,
+    },
+    /// The current time, less up to `max_past`
+    Timestamp {
+        max_past: Duration,
+    },
+}
+
+/// Generates the values of a single column
+#[derive(Debug)]
+pub struct FieldGenerator {
+    column_type: ColumnType,
+    data_type: DataType,
+    generator: Generator,
+    null_rate: f64,
+}
+
+fn option<T: FromStr>(
+    options: &mut HashMap<String, String>,
+    column: &str,
+    name: &str,
+) -> anyhow::Result<Option<T>> {
+    options
+        .remove(name)
+        .map(|value| {
+            value
+                .parse()
+                .map_err(|_| anyhow!("invalid value '{}' for fields.{}.{}", value, column, name))
+        })
+        .transpose()
+}
+
+/// Parses a comma separated list of values, each optionally followed by `:weight`
+fn parse_enum(values: &str) -> anyhow::Result<(Vec<String>, WeightedIndex<u64>)> {
+    let (values, weights): (Vec<_>, Vec<_>) = values
+        .split(',')
+        .map(|v| match v.rsplit_once(':') {
+            Some((value, weight)) if weight.trim().parse::<u64>().is_ok() => {
+                (value.trim().to_string(), weight.trim().parse().unwrap())
+            }
+            _ => (v.trim().to_string(), 1),
+        })
+        .unzip();
+
+    let weights = WeightedIndex::new(&weights)
+        .map_err(|e| anyhow!("invalid weights for enum values: {}", e))?;
+
+    Ok((values, weights))
+}
+
+impl FieldGenerator {
+    pub fn new(field: &Field, mut options: HashMap<String, String>) -> anyhow::Result<Self> {
+        let column = field.name().as_str();
+        let column_type = ColumnType::of(field.data_type()).ok_or_else(|| {
+            anyhow!(
+                "column '{}' has type {}, which the datagen source can't generate",
+                column,
+                field.data_type()
+            )
+        })?;
+
+        let kind = options.remove("kind").unwrap_or_else(|| {
+            if column_type == ColumnType::Timestamp {
+                "timestamp".to_string()
+            } else {
+                "random".to_string()
+            }
+        });
+
+        let generator = match (kind.as_str(), column_type) {
+            ("sequence", ColumnType::Int { .. } | ColumnType::Float | ColumnType::String) => {
+                Generator::Sequence {
+                    start: option(&mut options, column, "start")?.unwrap_or(0),
+                    step: option(&mut options, column, "step")?.unwrap_or(1),
+                }
+            }
+            ("random", ColumnType::Int { unsigned }) => {
+                let min = option(&mut options, column, "min")?.unwrap_or(0);
+                let max = option(&mut options, column, "max")?.unwrap_or(1_000_000);
+                if min > max {
+                    bail!("fields.{column}.min must not be greater than fields.{column}.max");
+                }
+                if unsigned && min < 0 {
+                    bail!("fields.{column}.min must not be negative for an unsigned column");
+                }
+                Generator::RandomInt { min, max }
+            }
+            ("random", ColumnType::Float) => {
+                let min = option(&mut options, column, "min")?.unwrap_or(0.0);
+                let max = option(&mut options, column, "max")?.unwrap_or(1.0);
+                if min >= max {
+                    bail!("fields.{column}.min must be less than fields.{column}.max");
+                }
+                Generator::RandomFloat { min, max }
+            }
+            ("random", ColumnType::String) => Generator::RandomString {
+                length: option(&mut options, column, "length")?.unwrap_or(10),
+            },
+            ("random", ColumnType::Bool) => Generator::RandomBool,
+            ("regex", ColumnType::String) => {
+                let pattern: String = option(&mut options, column, "regex")?
+                    .ok_or_else(|| anyhow!("fields.{column}.regex must be set"))?;
+                Generator::Regex(
+                    rand_regex::Regex::compile(&pattern, MAX_REGEX_REPEAT)
+                        .map_err(|e| anyhow!("invalid regex for fields.{column}.regex: {}", e))?,
+                )
+            }
+            ("enum", ColumnType::String) => {
+                let values: String = option(&mut options, column, "values")?
+                    .ok_or_else(|| anyhow!("fields.{column}.values must be set"))?;
+                let (values, weights) = parse_enum(&values)?;
+                Generator::Enum { values, weights }
+            }
+            ("timestamp", ColumnType::Timestamp) => Generator::Timestamp {
+                max_past: Duration::from_millis(
+                    option(&mut options, column, "max_past_ms")?.unwrap_or(0),
+                ),
+            },
+            (kind, _) => {
+                bail!(
+                    "'{}' generator can't be used for column '{}' of type {}",
+                    kind,
+                    column,
+                    field.data_type()
+                );
+            }
+        };
+
+        let null_rate = option(&mut options, column, "null_rate")?.unwrap_or(0.0);
+        if !(0.0..=1.0).contains(&null_rate) {
+            bail!("fields.{column}.null_rate must be between 0 and 1");
+        }
+        if null_rate > 0.0 && !field.is_nullable() {
+            bail!("fields.{column}.null_rate is set, but column '{column}' is not nullable");
+        }
+
+        if let Some(option) = options.keys().next() {
+            bail!(
+                "unknown option fields.{}.{} for '{}' generator",
+                column,
+                option,
+                kind
+            );
+        }
+
+        Ok(Self {
+            column_type,
+            data_type: field.data_type().clone(),
+            generator,
+            null_rate,
+        })
+    }
+
+    fn values<T>(
+        &self,
+        rng: &mut SmallRng,
+        n: usize,
+        mut f: impl FnMut(&mut SmallRng, usize) -> T,
+    ) -> Vec<Option<T>> {
+        (0..n)
+            .map(|i| {
+                if self.null_rate > 0.0 && rng.gen_bool(self.null_rate) {
+                    None
+                } else {
+                    Some(f(rng, i))
+                }
+            })
+            .collect()
+    }
+
+    /// Generates a column for rows with the given (global) sequence numbers
+    pub fn generate(&self, rng: &mut SmallRng, sequence: &[u64], now: SystemTime) -> ArrayRef {
+        let n = sequence.len();
+
+        let array: ArrayRef = match &self.generator {
+            Generator::Sequence { start, step } => {
+                let value = |i: usize| start.wrapping_add(step.wrapping_mul(sequence[i] as i64));
+                match self.column_type {
+                    ColumnType::Float => {
+                        Arc::new(Float64Array::from(
+                            self.values(rng, n, |_, i| value(i) as f64),
+                        ))
+                    }
+                    ColumnType::String => {
+                        Arc::new(StringArray::from(
+                            self.values(rng, n, |_, i| value(i).to_string()),
+                        ))
+                    }
+                    _ => Arc::new(Int64Array::from(self.values(rng, n, |_, i| value(i)))),
+                }
+            }
+            Generator::RandomInt { min, max } => {
+                Arc::new(Int64Array::from(
+                    self.values(rng, n, |r, _| r.gen_range(*min..=*max)),
+                ))
+            }
+            Generator::RandomFloat { min, max } => {
+                Arc::new(Float64Array::from(
+                    self.values(rng, n, |r, _| r.gen_range(*min..*max)),
+                ))
+            }
+            Generator::RandomString { length } => {
+                Arc::new(StringArray::from(
+                    self.values(rng, n, |r, _| Alphanumeric.sample_string(r, *length)),
+                ))
+            }
+            Generator::RandomBool => Arc::new(BooleanArray::from(
+                self.values(rng, n, |r, _| r.gen_bool(0.5)),
+            )),
+            Generator::Regex(regex) => Arc::new(StringArray::from(
+                self.values(rng, n, |r, _| r.sample::<String, _>(regex)),
+            )),
+            Generator::Enum { values, weights } => {
+                Arc::new(StringArray::from(
+                    self.values(rng, n, |r, _| values[r.sample(weights)].clone()),
+                ))
+            }
+            Generator::Timestamp { max_past } => {
+                let now = to_nanos(now) as i64;
+                let max_past = max_past.as_nanos() as i64;
+                Arc::new(TimestampNanosecondArray::from(self.values(
+                    rng,
+                    n,
+                    |r, _| now - r.gen_range(0..=max_past),
+                )))
+            }
+        };
+
+        cast(&array, &self.data_type).expect("generated column must be castable to its type")
+    }
+}
+
+/// Creates the generators for `fields` from the `fields.column.option` table options (with the
+/// `fields.` prefix removed)
+pub fn field_generators<'a>(
+    fields: impl IntoIterator<Item = &'a Field>,
+    options: &HashMap<String, String>,
+) -> anyhow::Result<Vec<FieldGenerator>> {
+    let mut by_column: HashMap<&str, HashMap<String, String>> = HashMap::new();
+    for (key, value) in options {
+        let (column, option) = key.rsplit_once('.').ok_or_else(|| {
+            anyhow!(
+                "invalid option 'fields.{}'; expected fields.column.option",
+                key
+            )
+        })?;
+        by_column
+            .entry(column)
+            .or_default()
+            .insert(option.to_string(), value.clone());
+    }
+
+    let generators = fields
+        .into_iter()
+        .map(|f| FieldGenerator::new(f, by_column.remove(f.name().as_str()).unwrap_or_default()))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    if let Some(column) = by_column.keys().next() {
+        bail!(
+            "fields.{} options are set, but there is no column named '{}'",
+            column,
+            column
+        );
+    }
+
+    Ok(generators)
+}
+
+#[cfg(test)]
+mod test {
+    use super::field_generators;
+    use arrow::array::{AsArray, Int32Array};
+    use arrow::datatypes::{DataType, Field, Float64Type, TimeUnit};
+    use rand::rngs::SmallRng;
+    use rand::SeedableRng;
+    use std::collections::HashMap;
+    use std::time::SystemTime;
+
+    fn options(options: &[(&str, &str)]) -> HashMap<String, String> {
+        options
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_generators() {
+        let fields = [
+            Field::new("id", DataType::Int32, false),
+            Field::new("status", DataType::Utf8, true),
+            Field::new("sku", DataType::Utf8, false),
+            Field::new("price", DataType::Float64, false),
+            Field::new(
+                "at",
+                DataType::Timestamp(TimeUnit::Millisecond, None),
+                false,
+            ),
+        ];
+
+        let generators = field_generators(
+            &fields,
+            &options(&[
+                ("id.kind", "sequence"),
+                ("id.start", "10"),
+                ("status.kind", "enum"),
+                ("status.values", "active:3,banned:0"),
+                ("status.null_rate", "0.5"),
+                ("sku.kind", "regex"),
+                ("sku.regex", "[A-Z]{3}-[0-9]{4}"),
+                ("price.min", "1"),
+                ("price.max", "2"),
+            ]),
+        )
+        .unwrap();
+
+        let mut rng = SmallRng::seed_from_u64(1);
+        let now = SystemTime::now();
+        let columns: Vec<_> = generators
+            .iter()
+            .map(|g| g.generate(&mut rng, &[0, 2, 4], now))
+            .collect();
+
+        assert_eq!(
+            columns[0].as_any().downcast_ref::<Int32Array>().unwrap(),
+            &Int32Array::from(vec![10, 12, 14])
+        );
+
+        for status in columns[1].as_string::<i32>().iter().flatten() {
+            assert_eq!(status, "active");
+        }
+
+        for sku in columns[2].as_string::<i32>().iter() {
+            let sku = sku.unwrap();
+            assert_eq!(sku.len(), 8);
+            assert!(sku.starts_with(|c: char| c.is_ascii_uppercase()));
+        }
+
+        for price in columns[3].as_primitive::<Float64Type>().values() {
+            assert!((1.0..2.0).contains(price));
+        }
+
+        assert_eq!(
+            columns[4].data_type(),
+            &DataType::Timestamp(TimeUnit::Millisecond, None)
+        );
+    }
+
+    #[test]
+    fn test_invalid_options() {
+        let fields = [
+            Field::new("id", DataType::Int64, false),
+            Field::new("name", DataType::Utf8, false),
+        ];
+
+        for invalid in [
+            vec![("id.kind", "regex")],
+            vec![("id.min", "10"), ("id.max", "1")],
+            vec![("id.null_rate", "0.1")],
+            vec![("id.unknown", "1")],
+            vec![("missing.kind", "random")],
+            vec![("name.kind", "enum")],
+            vec![("name.kind", "regex"), ("name.regex", "[")],
+        ] {
+            assert!(
+                field_generators(&fields, &options(&invalid)).is_err(),
+                "{:?}",
+                invalid
+            );
+        }
+    }
+}
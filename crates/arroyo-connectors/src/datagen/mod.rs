@@ -0,0 +1,195 @@
+mod generator;
+mod operator;
+
+use anyhow::{anyhow, bail};
+use arrow::datatypes::Field;
+use arroyo_operator::connector::{Connection, Connector};
+use arroyo_operator::operator::OperatorNode;
+use arroyo_rpc::api_types::connections::{
+    ConnectionProfile, ConnectionSchema, ConnectionType, TestSourceMessage,
+};
+use arroyo_rpc::OperatorConfig;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::str::FromStr;
+use typify::import_types;
+
+use crate::datagen::generator::field_generators;
+use crate::datagen::operator::{DatagenSourceFunc, DatagenSourceState};
+use crate::{pull_opt, pull_option_to_u64, EmptyConfig};
+
+const TABLE_SCHEMA: &str = include_str!("./table.json");
+const ICON: &str = include_str!("./datagen.svg");
+
+import_types!(schema = "src/datagen/table.json");
+
+pub struct DatagenConnector {}
+
+impl Connector for DatagenConnector {
+    type ProfileT = EmptyConfig;
+    type TableT = DatagenTable;
+
+    fn name(&self) -> &'static str {
+        "datagen"
+    }
+
+    fn metadata(&self) -> arroyo_rpc::api_types::connections::Connector {
+        arroyo_rpc::api_types::connections::Connector {
+            id: "datagen".to_string(),
+            name: "Datagen".to_string(),
+            icon: ICON.to_string(),
+            description: "Generates fake data matching the table's schema".to_string(),
+            enabled: true,
+            source: true,
+            sink: false,
+            testing: false,
+            hidden: false,
+            custom_schemas: true,
+            connection_config: None,
+            table_config: TABLE_SCHEMA.to_string(),
+        }
+    }
+
+    fn table_type(&self, _: Self::ProfileT, _: Self::TableT) -> ConnectionType {
+        ConnectionType::Source
+    }
+
+    fn is_bounded(&self, _: Self::ProfileT, table: Self::TableT) -> bool {
+        table.message_count.is_some()
+    }
+
+    fn get_schema(
+        &self,
+        _: Self::ProfileT,
+        _: Self::TableT,
+        s: Option<&ConnectionSchema>,
+    ) -> Option<ConnectionSchema> {
+        s.cloned()
+    }
+
+    fn test(
+        &self,
+        _: &str,
+        _: Self::ProfileT,
+        _: Self::TableT,
+        _: Option<&ConnectionSchema>,
+        tx: tokio::sync::mpsc::Sender<TestSourceMessage>,
+    ) {
+        tokio::task::spawn(async move {
+            let message = TestSourceMessage {
+                error: false,
+                done: true,
+                message: "Successfully validated connection".to_string(),
+            };
+            tx.send(message).await.unwrap();
+        });
+    }
+
+    fn from_options(
+        &self,
+        name: &str,
+        options: &mut HashMap<String, String>,
+        schema: Option<&ConnectionSchema>,
+        _profile: Option<&ConnectionProfile>,
+    ) -> anyhow::Result<Connection> {
+        let event_rate = f64::from_str(&pull_opt("event_rate", options)?)
+            .map_err(|_| anyhow!("invalid value for event_rate; expected float"))?;
+
+        let message_count = pull_option_to_u64("message_count", options)?
+            .map(|n| n.try_into())
+            .transpose()
+            .map_err(|_| anyhow!("message_count must be greater than 0"))?;
+
+        let seed = pull_option_to_u64("seed", options)?;
+
+        let fields: HashMap<String, String> = options
+            .iter()
+            .filter(|(k, _)| k.starts_with("fields."))
+            .map(|(k, v)| (k.trim_start_matches("fields.").to_string(), v.to_string()))
+            .collect();
+        options.retain(|k, _| !k.starts_with("fields."));
+
+        self.from_config(
+            None,
+            name,
+            EmptyConfig {},
+            DatagenTable {
+                event_rate,
+                message_count,
+                seed,
+                fields,
+            },
+            schema,
+        )
+    }
+
+    fn from_config(
+        &self,
+        id: Option<i64>,
+        name: &str,
+        config: Self::ProfileT,
+        table: Self::TableT,
+        schema: Option<&ConnectionSchema>,
+    ) -> anyhow::Result<Connection> {
+        let schema = schema
+            .map(|s| s.to_owned())
+            .ok_or_else(|| anyhow!("no schema defined for datagen source"))?;
+
+        if schema.fields.is_empty() {
+            bail!("datagen source must define the columns to generate");
+        }
+
+        if table.event_rate <= 0.0 {
+            bail!("event_rate for datagen source must be greater than 0");
+        }
+
+        let fields: Vec<Field> = schema.fields.iter().map(|f| f.clone().into()).collect();
+        field_generators(&fields, &table.fields)?;
+
+        let description = format!(
+            "{}Datagen<{} eps>",
+            if table.message_count.is_some() {
+                "Bounded"
+            } else {
+                ""
+            },
+            table.event_rate
+        );
+
+        let config = OperatorConfig {
+            connection: serde_json::to_value(config).unwrap(),
+            table: serde_json::to_value(table).unwrap(),
+            rate_limit: None,
+            format: None,
+            bad_data: None,
+            framing: None,
+            metadata_fields: vec![],
+        };
+
+        Ok(Connection {
+            id,
+            connector: self.name(),
+            name: name.to_string(),
+            connection_type: ConnectionType::Source,
+            schema,
+            config: serde_json::to_string(&config).unwrap(),
+            description,
+        })
+    }
+
+    fn make_operator(
+        &self,
+        _: Self::ProfileT,
+        table: Self::TableT,
+        _: OperatorConfig,
+    ) -> anyhow::Result<OperatorNode> {
+        Ok(OperatorNode::from_source(Box::new(DatagenSourceFunc {
+            event_rate: table.event_rate,
+            message_count: table.message_count.map(|n| n.get()),
+            seed: table.seed,
+            fields: table.fields,
+            generators: vec![],
+            state: DatagenSourceState { counter: 0 },
+        })))
+    }
+}
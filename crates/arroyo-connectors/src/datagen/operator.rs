@@ -0,0 +1,188 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+
+use arrow::array::{RecordBatch, TimestampNanosecondArray};
+use arroyo_operator::context::ArrowContext;
+use arroyo_operator::operator::SourceOperator;
+use arroyo_operator::SourceFinishType;
+use arroyo_rpc::grpc::rpc::{StopMode, TableConfig};
+use arroyo_rpc::ControlMessage;
+use arroyo_types::to_nanos;
+use async_trait::async_trait;
+use bincode::{Decode, Encode};
+use rand::rngs::SmallRng;
+use rand::SeedableRng;
+use tracing::{debug, info};
+
+use crate::datagen::generator::{field_generators, FieldGenerator};
+
+#[derive(Encode, Decode, Debug, Copy, Clone, Eq, PartialEq)]
+pub struct DatagenSourceState {
+    pub counter: u64,
+}
+
+pub struct DatagenSourceFunc {
+    pub event_rate: f64,
+    pub message_count: Option<u64>,
+    pub seed: Option<u64>,
+    pub fields: HashMap<String, String>,
+    pub generators: Vec<FieldGenerator>,
+    pub state: DatagenSourceState,
+}
+
+impl DatagenSourceFunc {
+    fn rng(&self, ctx: &ArrowContext) -> SmallRng {
+        match self.seed {
+            // include the position so that a restored source doesn't repeat the rows it generated
+            // before the checkpoint
+            Some(seed) => SmallRng::seed_from_u64(
+                seed.wrapping_mul(31)
+                    .wrapping_add(ctx.task_info.task_index as u64)
+                    .wrapping_mul(31)
+                    .wrapping_add(self.state.counter),
+            ),
+            None => SmallRng::from_entropy(),
+        }
+    }
+
+    async fn run_int(&mut self, ctx: &mut ArrowContext) -> SourceFinishType {
+        let parallelism = ctx.task_info.parallelism as u64;
+        let task_index = ctx.task_info.task_index as u64;
+
+        // split the rows evenly across subtasks
+        let limit = self
+            .message_count
+            .map(|n| n / parallelism + u64::from(task_index < n % parallelism))
+            .unwrap_or(u64::MAX);
+
+        let rate = self.event_rate / parallelism as f64;
+        // generate about 100ms of rows at a time
+        let batch_size = ((rate / 10.0).ceil() as u64).clamp(1, 8192);
+
+        let schema = ctx.out_schema.as_ref().unwrap().schema.clone();
+        let timestamp_index = ctx.out_schema.as_ref().unwrap().timestamp_index;
+
+        let mut rng = self.rng(ctx);
+        let start = Instant::now();
+        let start_counter = self.state.counter;
+
+        info!(
+            "Starting datagen source at row {} with rate {} and limit {}",
+            self.state.counter, rate, limit
+        );
+
+        while self.state.counter < limit {
+            let n = batch_size.min(limit - self.state.counter);
+            let now = SystemTime::now();
+            let sequence: Vec<u64> = (self.state.counter..self.state.counter + n)
+                .map(|i| i * parallelism + task_index)
+                .collect();
+
+            let mut columns: Vec<_> = self
+                .generators
+                .iter()
+                .map(|g| g.generate(&mut rng, &sequence, now))
+                .collect();
+            columns.insert(
+                timestamp_index,
+                Arc::new(TimestampNanosecondArray::from(vec![
+                    to_nanos(now) as i64;
+                    n as usize
+                ])),
+            );
+
+            ctx.collect(RecordBatch::try_new(schema.clone(), columns).unwrap())
+                .await;
+            self.state.counter += n;
+
+            match ctx.control_rx.try_recv() {
+                Ok(ControlMessage::Checkpoint(c)) => {
+                    debug!("starting checkpointing {}", ctx.task_info.task_index);
+                    ctx.table_manager
+                        .get_global_keyed_state("d")
+                        .await
+                        .unwrap()
+                        .insert(ctx.task_info.task_index, self.state)
+                        .await;
+                    if self.start_checkpoint(c, ctx).await {
+                        return SourceFinishType::Immediate;
+                    }
+                }
+                Ok(ControlMessage::Stop { mode }) => {
+                    info!("Stopping datagen source {:?}", mode);
+
+                    match mode {
+                        StopMode::Graceful => {
+                            return SourceFinishType::Graceful;
+                        }
+                        StopMode::Immediate => {
+                            return SourceFinishType::Immediate;
+                        }
+                    }
+                }
+                Ok(ControlMessage::Commit { .. }) => {
+                    unreachable!("sources shouldn't receive commit messages");
+                }
+                Ok(ControlMessage::LoadCompacted { compacted }) => {
+                    ctx.load_compacted(compacted).await;
+                }
+                Ok(ControlMessage::QueryState { query }) => {
+                    ctx.query_state(query).await;
+                }
+                Ok(ControlMessage::NoOp) => {}
+                Err(_) => {
+                    // no messages
+                }
+            }
+
+            let next =
+                start + Duration::from_secs_f64((self.state.counter - start_counter) as f64 / rate);
+            if let Some(sleep_time) = next.checked_duration_since(Instant::now()) {
+                tokio::time::sleep(sleep_time).await;
+            }
+        }
+
+        SourceFinishType::Final
+    }
+}
+
+#[async_trait]
+impl SourceOperator for DatagenSourceFunc {
+    fn name(&self) -> String {
+        "datagen-source".to_string()
+    }
+
+    fn tables(&self) -> HashMap<String, TableConfig> {
+        arroyo_state::global_table_config("d", "datagen source state")
+    }
+
+    async fn on_start(&mut self, ctx: &mut ArrowContext) {
+        let s = ctx
+            .table_manager
+            .get_global_keyed_state("d")
+            .await
+            .expect("should have table d in datagen source");
+
+        if let Some(state) = s.get(&ctx.task_info.task_index) {
+            self.state = *state;
+        }
+
+        let out_schema = ctx.out_schema.as_ref().unwrap();
+        self.generators = field_generators(
+            out_schema
+                .schema
+                .fields()
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| *i != out_schema.timestamp_index)
+                .map(|(_, f)| f.as_ref()),
+            &self.fields,
+        )
+        .expect("datagen fields should have been validated");
+    }
+
+    async fn run(&mut self, ctx: &mut ArrowContext) -> SourceFinishType {
+        self.run_int(ctx).await
+    }
+}
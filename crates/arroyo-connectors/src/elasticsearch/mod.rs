@@ -0,0 +1,467 @@
+mod sink;
+
+use anyhow::{anyhow, bail};
+use arroyo_operator::connector::{Connection, Connector};
+use arroyo_operator::operator::OperatorNode;
+use arroyo_rpc::api_types::connections::{
+    ConnectionProfile, ConnectionSchema, ConnectionType, FieldType, PrimitiveType,
+    TestSourceMessage,
+};
+use arroyo_rpc::formats::{Format, JsonFormat};
+use arroyo_rpc::var_str::VarStr;
+use arroyo_rpc::OperatorConfig;
+use chrono::{DateTime, Utc};
+use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::sync::oneshot::Receiver;
+use typify::import_types;
+
+use crate::elasticsearch::sink::ElasticsearchSinkFunc;
+use crate::{pull_opt, pull_option_to_i64};
+
+const CONFIG_SCHEMA: &str = include_str!("./profile.json");
+const TABLE_SCHEMA: &str = include_str!("./table.json");
+
+import_types!(
+    schema = "src/elasticsearch/profile.json",
+    convert = {
+        {type = "string", format = "var-str"} = VarStr
+    }
+);
+
+import_types!(schema = "src/elasticsearch/table.json");
+
+pub struct ElasticsearchConnector {}
+
+/// Creates a client that authenticates every request with the credentials in the config
+fn create_client(config: &ElasticsearchConfig) -> anyhow::Result<Client> {
+    let mut headers = HeaderMap::new();
+
+    let authorization = match (&config.api_key, &config.username) {
+        (Some(key), _) => Some(format!("ApiKey {}", key.sub_env_vars()?)),
+        (None, Some(username)) => {
+            let password = config
+                .password
+                .as_ref()
+                .map(|p| p.sub_env_vars())
+                .transpose()?
+                .unwrap_or_default();
+            Some(format!(
+                "Basic {}",
+                base64::encode(format!("{}:{}", username.sub_env_vars()?, password))
+            ))
+        }
+        (None, None) => None,
+    };
+
+    if let Some(authorization) = authorization {
+        let mut value = HeaderValue::from_str(&authorization)
+            .map_err(|_| anyhow!("invalid credentials for Elasticsearch"))?;
+        value.set_sensitive(true);
+        headers.insert(AUTHORIZATION, value);
+    }
+
+    reqwest::ClientBuilder::new()
+        .default_headers(headers)
+        .timeout(Duration::from_secs(30))
+        .build()
+        .map_err(|e| anyhow!("could not construct HTTP client: {:?}", e))
+}
+
+/// A part of an index name, which is either fixed or formatted from the event time
+#[derive(Debug, Clone, PartialEq)]
+enum IndexPart {
+    Literal(String),
+    /// A chrono format string
+    Time(String),
+}
+
+/// An index name that may contain date patterns in braces, like `events-{yyyy-MM-dd}`, which are
+/// filled in from the event time of each document
+#[derive(Debug, Clone, PartialEq)]
+pub struct IndexPattern {
+    parts: Vec<IndexPart>,
+}
+
+impl IndexPattern {
+    pub fn parse(pattern: &str) -> anyhow::Result<Self> {
+        let mut parts = vec![];
+        let mut rest = pattern;
+
+        while let Some(start) = rest.find('{') {
+            if start > 0 {
+                parts.push(IndexPart::Literal(rest[..start].to_string()));
+            }
+
+            let end = rest[start..]
+                .find('}')
+                .ok_or_else(|| anyhow!("unclosed '{{' in index pattern '{}'", pattern))?
+                + start;
+
+            parts.push(IndexPart::Time(Self::time_format(
+                &rest[start + 1..end],
+                pattern,
+            )?));
+            rest = &rest[end + 1..];
+        }
+
+        if rest.contains('}') {
+            bail!("unmatched '}}' in index pattern '{}'", pattern);
+        }
+
+        if !rest.is_empty() {
+            parts.push(IndexPart::Literal(rest.to_string()));
+        }
+
+        if parts.is_empty() {
+            bail!("index must not be empty");
+        }
+
+        Ok(Self { parts })
+    }
+
+    /// Converts a date pattern like `yyyy-MM-dd` into a chrono format string
+    fn time_format(time_pattern: &str, pattern: &str) -> anyhow::Result<String> {
+        let mut format = String::new();
+        let mut chars = time_pattern.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            if !c.is_ascii_alphabetic() {
+                if c == '%' {
+                    format.push('%');
+                }
+                format.push(c);
+                continue;
+            }
+
+            let mut len = 1;
+            while chars.next_if_eq(&c).is_some() {
+                len += 1;
+            }
+
+            format.push_str(match (c, len) {
+                ('y', 4) => "%Y",
+                ('y', 2) => "%y",
+                ('M', 2) => "%m",
+                ('d', 2) => "%d",
+                ('H', 2) => "%H",
+                ('m', 2) => "%M",
+                ('s', 2) => "%S",
+                _ => bail!(
+                    "unsupported date pattern '{}' in index pattern '{}'; expected one of yyyy, yy, MM, dd, HH, mm or ss",
+                    c.to_string().repeat(len),
+                    pattern
+                ),
+            });
+        }
+
+        Ok(format)
+    }
+
+    /// Whether the index name depends on the event time
+    pub fn is_dynamic(&self) -> bool {
+        self.parts.iter().any(|p| matches!(p, IndexPart::Time(_)))
+    }
+
+    pub fn format(&self, time: DateTime<Utc>) -> String {
+        let mut index = String::new();
+        for part in &self.parts {
+            match part {
+                IndexPart::Literal(s) => index.push_str(s),
+                IndexPart::Time(f) => index.push_str(&time.format(f).to_string()),
+            }
+        }
+        index
+    }
+}
+
+async fn test_inner(
+    config: &ElasticsearchConfig,
+    table: Option<&ElasticsearchTable>,
+    schema: Option<&ConnectionSchema>,
+) -> anyhow::Result<String> {
+    if let Some(table) = table {
+        validate_table(table, schema)?;
+    }
+
+    let client = create_client(config)?;
+    let resp = client
+        .get(&config.endpoint)
+        .send()
+        .await
+        .map_err(|e| anyhow!("failed to connect to {}: {}", config.endpoint, e))?;
+
+    if !resp.status().is_success() {
+        bail!(
+            "{} responded with {}: {}",
+            config.endpoint,
+            resp.status(),
+            resp.text().await.unwrap_or_default()
+        );
+    }
+
+    let info: serde_json::Value = resp
+        .json()
+        .await
+        .map_err(|e| anyhow!("{} did not return cluster info: {}", config.endpoint, e))?;
+
+    Ok(format!(
+        "Successfully connected to cluster {} (version {})",
+        info["cluster_name"].as_str().unwrap_or("unknown"),
+        info["version"]["number"].as_str().unwrap_or("unknown")
+    ))
+}
+
+fn validate_table(
+    table: &ElasticsearchTable,
+    schema: Option<&ConnectionSchema>,
+) -> anyhow::Result<()> {
+    IndexPattern::parse(&table.index)?;
+
+    if table.batch_size <= 0 {
+        bail!("batch_size must be positive");
+    }
+
+    if table.flush_interval_millis <= 0 {
+        bail!("flush_interval_millis must be positive");
+    }
+
+    if let (Some(id_field), Some(schema)) = (&table.id_field, schema) {
+        let Some(field) = schema.fields.iter().find(|f| &f.field_name == id_field) else {
+            bail!("id_field '{}' is not a field of the table", id_field);
+        };
+
+        // document ids are the field's value formatted as a string, so they must be scalars
+        if !matches!(
+            field.field_type.r#type,
+            FieldType::Primitive(
+                PrimitiveType::Int32
+                    | PrimitiveType::Int64
+                    | PrimitiveType::UInt32
+                    | PrimitiveType::UInt64
+                    | PrimitiveType::String
+            )
+        ) {
+            bail!(
+                "id_field '{}' must be a string or integer field to be used as a document id",
+                id_field
+            );
+        }
+    }
+
+    Ok(())
+}
+
+impl Connector for ElasticsearchConnector {
+    type ProfileT = ElasticsearchConfig;
+    type TableT = ElasticsearchTable;
+
+    fn name(&self) -> &'static str {
+        "elasticsearch"
+    }
+
+    fn metadata(&self) -> arroyo_rpc::api_types::connections::Connector {
+        arroyo_rpc::api_types::connections::Connector {
+            id: "elasticsearch".to_string(),
+            name: "Elasticsearch".to_string(),
+            icon: "".to_string(),
+            description: "Index results into Elasticsearch or OpenSearch".to_string(),
+            enabled: true,
+            source: false,
+            sink: true,
+            testing: true,
+            hidden: false,
+            custom_schemas: true,
+            connection_config: Some(CONFIG_SCHEMA.to_owned()),
+            table_config: TABLE_SCHEMA.to_owned(),
+        }
+    }
+
+    fn config_description(&self, config: Self::ProfileT) -> String {
+        config.endpoint
+    }
+
+    fn table_type(&self, _: Self::ProfileT, _: Self::TableT) -> ConnectionType {
+        ConnectionType::Sink
+    }
+
+    fn get_schema(
+        &self,
+        _: Self::ProfileT,
+        _: Self::TableT,
+        s: Option<&ConnectionSchema>,
+    ) -> Option<ConnectionSchema> {
+        s.cloned()
+    }
+
+    fn test_profile(&self, profile: Self::ProfileT) -> Option<Receiver<TestSourceMessage>> {
+        let (tx, rx) = tokio::sync::oneshot::channel();
+
+        tokio::spawn(async move {
+            let message = match test_inner(&profile, None, None).await {
+                Ok(m) => TestSourceMessage::done(m),
+                Err(e) => {
+                    TestSourceMessage::fail(format!("Failed to connect to Elasticsearch: {}", e))
+                }
+            };
+
+            tx.send(message).unwrap();
+        });
+
+        Some(rx)
+    }
+
+    fn test(
+        &self,
+        _: &str,
+        config: Self::ProfileT,
+        table: Self::TableT,
+        schema: Option<&ConnectionSchema>,
+        tx: tokio::sync::mpsc::Sender<TestSourceMessage>,
+    ) {
+        let schema = schema.cloned();
+        tokio::task::spawn(async move {
+            let message = match test_inner(&config, Some(&table), schema.as_ref()).await {
+                Ok(m) => TestSourceMessage::done(m),
+                Err(e) => TestSourceMessage::fail(e.to_string()),
+            };
+
+            tx.send(message).await.unwrap();
+        });
+    }
+
+    fn from_options(
+        &self,
+        name: &str,
+        options: &mut HashMap<String, String>,
+        schema: Option<&ConnectionSchema>,
+        profile: Option<&ConnectionProfile>,
+    ) -> anyhow::Result<Connection> {
+        let config = match profile {
+            Some(p) => serde_json::from_value(p.config.clone())
+                .map_err(|e| anyhow!("invalid config for profile '{}' in database: {}", p.id, e))?,
+            None => ElasticsearchConfig {
+                endpoint: pull_opt("endpoint", options)?,
+                username: options.remove("username").map(VarStr::new),
+                password: options.remove("password").map(VarStr::new),
+                api_key: options.remove("api_key").map(VarStr::new),
+            },
+        };
+
+        let table = ElasticsearchTable {
+            index: pull_opt("index", options)?,
+            id_field: options.remove("id_field"),
+            batch_size: pull_option_to_i64("batch_size", options)?.unwrap_or(1000),
+            flush_interval_millis: pull_option_to_i64("flush_interval_millis", options)?
+                .unwrap_or(1000),
+        };
+
+        self.from_config(None, name, config, table, schema)
+    }
+
+    fn from_config(
+        &self,
+        id: Option<i64>,
+        name: &str,
+        config: Self::ProfileT,
+        table: Self::TableT,
+        schema: Option<&ConnectionSchema>,
+    ) -> anyhow::Result<Connection> {
+        if let Err(e) = reqwest::Url::parse(&config.endpoint) {
+            bail!("invalid endpoint '{}': {:?}", config.endpoint, e);
+        }
+
+        let mut schema = schema
+            .map(|s| s.to_owned())
+            .ok_or_else(|| anyhow!("no schema defined for Elasticsearch sink"))?;
+
+        validate_table(&table, Some(&schema))?;
+
+        let format = schema
+            .format
+            .clone()
+            .unwrap_or_else(|| Format::Json(JsonFormat::default()));
+
+        match &format {
+            Format::Json(JsonFormat {
+                debezium: false,
+                include_schema: false,
+                ..
+            }) => {}
+            _ => bail!("Elasticsearch sinks only support the json format"),
+        }
+
+        schema.format = Some(format.clone());
+
+        let description = format!("ElasticsearchSink<{}>", table.index);
+
+        let config = OperatorConfig {
+            connection: serde_json::to_value(config).unwrap(),
+            table: serde_json::to_value(table).unwrap(),
+            rate_limit: None,
+            format: Some(format),
+            bad_data: schema.bad_data.clone(),
+            framing: None,
+            metadata_fields: vec![],
+        };
+
+        Ok(Connection {
+            id,
+            connector: self.name(),
+            name: name.to_string(),
+            connection_type: ConnectionType::Sink,
+            schema,
+            config: serde_json::to_string(&config).unwrap(),
+            description,
+        })
+    }
+
+    fn make_operator(
+        &self,
+        profile: Self::ProfileT,
+        table: Self::TableT,
+        config: OperatorConfig,
+    ) -> anyhow::Result<OperatorNode> {
+        Ok(OperatorNode::from_operator(Box::new(
+            ElasticsearchSinkFunc::new(
+                create_client(&profile)?,
+                &profile.endpoint,
+                table,
+                config
+                    .format
+                    .expect("No format configured for Elasticsearch sink"),
+            )?,
+        )))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_index_pattern() {
+        let time = Utc.with_ymd_and_hms(2024, 3, 7, 14, 5, 9).unwrap();
+
+        let pattern = IndexPattern::parse("events").unwrap();
+        assert!(!pattern.is_dynamic());
+        assert_eq!(pattern.format(time), "events");
+
+        let pattern = IndexPattern::parse("events-{yyyy-MM-dd}").unwrap();
+        assert!(pattern.is_dynamic());
+        assert_eq!(pattern.format(time), "events-2024-03-07");
+
+        let pattern = IndexPattern::parse("logs-{yy.MM}-{HH:mm:ss}-v1").unwrap();
+        assert_eq!(pattern.format(time), "logs-24.03-14:05:09-v1");
+
+        assert!(IndexPattern::parse("events-{yyyy-MM-dd").is_err());
+        assert!(IndexPattern::parse("events-yyyy}").is_err());
+        assert!(IndexPattern::parse("events-{yyy}").is_err());
+        assert!(IndexPattern::parse("events-{YYYY}").is_err());
+        assert!(IndexPattern::parse("").is_err());
+    }
+}
@@ -0,0 +1,347 @@
+use anyhow::{anyhow, bail, Context};
+use arrow::array::{Array, AsArray, RecordBatch};
+use arrow::datatypes::TimestampNanosecondType;
+use arrow::util::display::{ArrayFormatter, FormatOptions};
+use arroyo_formats::ser::ArrowSerializer;
+use arroyo_operator::context::ArrowContext;
+use arroyo_operator::operator::ArrowOperator;
+use arroyo_rpc::formats::Format;
+use arroyo_types::{from_nanos, CheckpointBarrier, SignalMessage};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use reqwest::header::CONTENT_TYPE;
+use reqwest::{Client, StatusCode};
+use serde::Deserialize;
+use serde_json::json;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+use crate::elasticsearch::{ElasticsearchTable, IndexPattern};
+
+const MAX_WRITE_ATTEMPTS: u32 = 20;
+/// Throttling is expected when the cluster is under load, so throttled requests are retried
+/// for longer: about 25 minutes, once the backoff reaches [MAX_BACKOFF]
+const MAX_THROTTLED_ATTEMPTS: u32 = 60;
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+/// A document waiting to be sent, along with its bulk action line
+struct BulkItem {
+    action: String,
+    document: Vec<u8>,
+}
+
+#[derive(Deserialize)]
+struct BulkResponse {
+    errors: bool,
+    #[serde(default)]
+    items: Vec<HashMap<String, BulkItemResponse>>,
+}
+
+#[derive(Deserialize)]
+struct BulkItemResponse {
+    status: u16,
+    #[serde(default)]
+    error: Option<serde_json::Value>,
+}
+
+/// Why a bulk request, or an item within it, failed
+enum Failure {
+    /// The cluster is overloaded and asked us to slow down; retried up to
+    /// [MAX_THROTTLED_ATTEMPTS] times
+    Throttled(String),
+    /// A transient failure, retried up to [MAX_WRITE_ATTEMPTS] times
+    Retryable(String),
+}
+
+/// Whether a request or item that failed with this status may succeed if it's retried
+fn is_retryable(status: StatusCode) -> bool {
+    status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS
+}
+
+/// How long to wait before the given retry, doubling with each attempt up to [MAX_BACKOFF]
+fn backoff(attempt: u32) -> Duration {
+    Duration::from_millis(50 * (1 << attempt.min(16))).min(MAX_BACKOFF)
+}
+
+pub struct ElasticsearchSinkFunc {
+    client: Client,
+    bulk_url: String,
+    table: ElasticsearchTable,
+    index: IndexPattern,
+    serializer: ArrowSerializer,
+    pending: Vec<BulkItem>,
+    last_flushed: Instant,
+}
+
+impl ElasticsearchSinkFunc {
+    pub fn new(
+        client: Client,
+        endpoint: &str,
+        table: ElasticsearchTable,
+        format: Format,
+    ) -> anyhow::Result<Self> {
+        Ok(Self {
+            client,
+            bulk_url: format!("{}/_bulk", endpoint.trim_end_matches('/')),
+            index: IndexPattern::parse(&table.index)?,
+            table,
+            serializer: ArrowSerializer::new(format),
+            pending: vec![],
+            last_flushed: Instant::now(),
+        })
+    }
+
+    /// Sends the pending items in a single bulk request. Items that the cluster accepted, or
+    /// rejected for reasons that retrying won't fix, are removed from `pending`; the returned
+    /// failure describes why the remaining items need to be retried.
+    async fn send(&mut self, ctx: &mut ArrowContext) -> Result<(), Failure> {
+        let mut body = vec![];
+        for item in &self.pending {
+            body.extend_from_slice(item.action.as_bytes());
+            body.push(b'\n');
+            body.extend_from_slice(&item.document);
+            body.push(b'\n');
+        }
+
+        let resp = self
+            .client
+            .post(&self.bulk_url)
+            .header(CONTENT_TYPE, "application/x-ndjson")
+            .body(body)
+            .send()
+            .await
+            .map_err(|e| Failure::Retryable(e.to_string()))?;
+
+        let status = resp.status();
+        if !status.is_success() {
+            let message = format!(
+                "bulk request failed with {}: {}",
+                status,
+                resp.text().await.unwrap_or_default()
+            );
+
+            return Err(if status == StatusCode::TOO_MANY_REQUESTS {
+                Failure::Throttled(message)
+            } else {
+                Failure::Retryable(message)
+            });
+        }
+
+        let resp: BulkResponse = resp
+            .json()
+            .await
+            .map_err(|e| Failure::Retryable(format!("invalid bulk response: {}", e)))?;
+
+        if !resp.errors {
+            self.pending.clear();
+            return Ok(());
+        }
+
+        if resp.items.len() != self.pending.len() {
+            return Err(Failure::Retryable(format!(
+                "bulk response contained {} items, but {} were sent",
+                resp.items.len(),
+                self.pending.len()
+            )));
+        }
+
+        let mut retry = vec![];
+        let mut throttled = false;
+        let mut failure = None;
+
+        for (item, result) in self.pending.drain(..).zip(resp.items) {
+            let Some(result) = result.into_values().next() else {
+                continue;
+            };
+
+            let Some(error) = result.error else {
+                continue;
+            };
+
+            let status =
+                StatusCode::from_u16(result.status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+
+            if is_retryable(status) {
+                throttled |= status == StatusCode::TOO_MANY_REQUESTS;
+                failure.get_or_insert_with(|| error.to_string());
+                retry.push(item);
+            } else {
+                // the document itself is invalid, for example because it doesn't match the
+                // index's mapping, so it will never be accepted
+                warn!("Elasticsearch rejected document: {}", error);
+                ctx.report_error("Elasticsearch rejected document", error.to_string())
+                    .await;
+            }
+        }
+
+        self.pending = retry;
+
+        match failure {
+            None => Ok(()),
+            Some(message) if throttled => Err(Failure::Throttled(message)),
+            Some(message) => Err(Failure::Retryable(message)),
+        }
+    }
+
+    async fn flush(&mut self, ctx: &mut ArrowContext) -> anyhow::Result<()> {
+        let mut attempts: u32 = 0;
+        let mut throttled = 0;
+        let mut failures = 0;
+
+        while !self.pending.is_empty() {
+            let message = match self.send(ctx).await {
+                Ok(()) => continue,
+                Err(Failure::Throttled(message)) => {
+                    throttled += 1;
+                    if throttled >= MAX_THROTTLED_ATTEMPTS {
+                        bail!(
+                            "Elasticsearch is still throttling writes after {} attempts: {}",
+                            throttled,
+                            message
+                        );
+                    }
+                    message
+                }
+                Err(Failure::Retryable(message)) => {
+                    failures += 1;
+                    if failures >= MAX_WRITE_ATTEMPTS {
+                        bail!(
+                            "failed to write to Elasticsearch after {} attempts: {}",
+                            failures,
+                            message
+                        );
+                    }
+                    message
+                }
+            };
+
+            attempts += 1;
+            ctx.report_error("Failed to write to Elasticsearch", message)
+                .await;
+            tokio::time::sleep(backoff(attempts)).await;
+        }
+
+        self.last_flushed = Instant::now();
+        Ok(())
+    }
+
+    fn add_batch(&mut self, batch: &RecordBatch, timestamp_index: usize) -> anyhow::Result<()> {
+        let timestamps = batch
+            .column(timestamp_index)
+            .as_primitive::<TimestampNanosecondType>();
+
+        let ids = self
+            .table
+            .id_field
+            .as_ref()
+            .map(|field| {
+                batch
+                    .column_by_name(field)
+                    .ok_or_else(|| anyhow!("id_field '{}' is not in the input", field))
+            })
+            .transpose()?;
+
+        let id_formatter = ids
+            .map(|ids| ArrayFormatter::try_new(ids.as_ref(), &FormatOptions::default()))
+            .transpose()?;
+
+        for (i, document) in self.serializer.serialize(batch).enumerate() {
+            let mut action = json!({});
+
+            if self.index.is_dynamic() {
+                let time: DateTime<Utc> = from_nanos(timestamps.value(i) as u128).into();
+                action["_index"] = self.index.format(time).into();
+            } else {
+                action["_index"] = self.table.index.clone().into();
+            }
+
+            if let (Some(ids), Some(formatter)) = (ids, &id_formatter) {
+                // documents without an id are given one by the cluster
+                if ids.is_valid(i) {
+                    action["_id"] = formatter.value(i).to_string().into();
+                }
+            }
+
+            self.pending.push(BulkItem {
+                action: json!({ "index": action }).to_string(),
+                document,
+            });
+        }
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl ArrowOperator for ElasticsearchSinkFunc {
+    fn name(&self) -> String {
+        "ElasticsearchSink".to_string()
+    }
+
+    fn tick_interval(&self) -> Option<Duration> {
+        Some(Duration::from_millis(
+            self.table.flush_interval_millis as u64,
+        ))
+    }
+
+    async fn process_batch(
+        &mut self,
+        batch: RecordBatch,
+        ctx: &mut ArrowContext,
+    ) -> anyhow::Result<()> {
+        let timestamp_index = ctx
+            .in_schemas
+            .first()
+            .expect("no in-schema for Elasticsearch sink!")
+            .timestamp_index;
+
+        self.add_batch(&batch, timestamp_index)
+            .context("failed to prepare documents for Elasticsearch")?;
+
+        if self.pending.len() >= self.table.batch_size as usize {
+            self.flush(ctx).await?;
+        }
+        Ok(())
+    }
+
+    async fn handle_checkpoint(
+        &mut self,
+        _: CheckpointBarrier,
+        ctx: &mut ArrowContext,
+    ) -> anyhow::Result<()> {
+        // with an id_field, replayed documents overwrite their earlier writes, so flushing
+        // before the checkpoint completes gives effectively-once delivery without state
+        self.flush(ctx).await
+    }
+
+    async fn handle_tick(&mut self, _: u64, ctx: &mut ArrowContext) -> anyhow::Result<()> {
+        if self.last_flushed.elapsed()
+            >= Duration::from_millis(self.table.flush_interval_millis as u64)
+        {
+            self.flush(ctx).await?;
+        }
+        Ok(())
+    }
+
+    async fn on_close(
+        &mut self,
+        _: &Option<SignalMessage>,
+        ctx: &mut ArrowContext,
+    ) -> anyhow::Result<()> {
+        self.flush(ctx).await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_backoff() {
+        assert_eq!(backoff(1), Duration::from_millis(100));
+        assert_eq!(backoff(5), Duration::from_millis(1600));
+        assert_eq!(backoff(10), MAX_BACKOFF);
+        assert_eq!(backoff(u32::MAX), MAX_BACKOFF);
+    }
+}
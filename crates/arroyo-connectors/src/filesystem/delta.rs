@@ -9,7 +9,8 @@ use arroyo_rpc::api_types::connections::{
 use arroyo_rpc::OperatorConfig;
 
 use crate::filesystem::{
-    file_system_sink_from_options, CommitStyle, FileSystemTable, FormatSettings, TableType,
+    file_system_sink_from_options, test_table, CommitStyle, FileSystemTable, FormatSettings,
+    TableType,
 };
 use crate::EmptyConfig;
 
@@ -40,7 +41,7 @@ impl Connector for DeltaLakeConnector {
             enabled: true,
             source: false,
             sink: true,
-            testing: false,
+            testing: true,
             hidden: true,
             custom_schemas: true,
             connection_config: None,
@@ -52,15 +53,14 @@ impl Connector for DeltaLakeConnector {
         &self,
         _: &str,
         _: Self::ProfileT,
-        _: Self::TableT,
+        table: Self::TableT,
         _: Option<&ConnectionSchema>,
         tx: tokio::sync::mpsc::Sender<TestSourceMessage>,
     ) {
         tokio::task::spawn(async move {
-            let message = TestSourceMessage {
-                error: false,
-                done: true,
-                message: "Successfully validated connection".to_string(),
+            let message = match test_table(table, &tx).await {
+                Ok(message) => TestSourceMessage::done(message),
+                Err(e) => TestSourceMessage::fail(format!("{:#}", e)),
             };
             tx.send(message).await.unwrap();
         });
@@ -1,11 +1,17 @@
 pub mod delta;
-mod sink;
-mod source;
+pub(crate) mod sink;
+pub(crate) mod source;
 
-use anyhow::{anyhow, bail, Result};
-use arroyo_storage::BackendConfig;
+use anyhow::{anyhow, bail, Context, Result};
+use arroyo_storage::{BackendConfig, StorageProvider};
+use async_compression::tokio::bufread::{GzipDecoder, ZstdDecoder};
+use futures::StreamExt;
 use regex::Regex;
 use std::collections::HashMap;
+use tokio::io::AsyncReadExt;
+use tokio::sync::mpsc::Sender;
+use tokio::sync::oneshot;
+use uuid::Uuid;
 
 use typify::import_types;
 
@@ -14,23 +20,27 @@ use arroyo_rpc::api_types::connections::{
     ConnectionProfile, ConnectionSchema, ConnectionType, TestSourceMessage,
 };
 use arroyo_rpc::formats::Format;
+use arroyo_rpc::var_str::VarStr;
 use arroyo_rpc::OperatorConfig;
 use serde::{Deserialize, Serialize};
 
-use crate::{pull_opt, pull_option_to_i64, EmptyConfig};
+use crate::{pull_opt, pull_option_to_i64, pull_option_to_u64, EmptyConfig};
 
+use crate::filesystem::sink::manifest::is_internal_path;
 use crate::filesystem::source::FileSystemSourceFunc;
 use arroyo_operator::connector::Connector;
 use arroyo_operator::operator::OperatorNode;
 
 use self::sink::{
-    JsonFileSystemSink, LocalJsonFileSystemSink, LocalParquetFileSystemSink, ParquetFileSystemSink,
+    ArrowIpcFileSystemSink, AvroFileSystemSink, CsvFileSystemSink, JsonFileSystemSink,
+    LocalArrowIpcFileSystemSink, LocalAvroFileSystemSink, LocalCsvFileSystemSink,
+    LocalJsonFileSystemSink, LocalParquetFileSystemSink, ParquetFileSystemSink,
 };
 
 const TABLE_SCHEMA: &str = include_str!("./table.json");
 const ICON: &str = include_str!("./filesystem.svg");
 
-import_types!(schema = "src/filesystem/table.json");
+import_types!(schema = "src/filesystem/table.json", convert = { {type = "string", format = "var-str"} = VarStr });
 
 pub struct FileSystemConnector {}
 
@@ -68,38 +78,30 @@ impl Connector for FileSystemConnector {
         _: Option<&ConnectionSchema>,
         tx: tokio::sync::mpsc::Sender<TestSourceMessage>,
     ) {
-        let mut failed = false;
-        let mut message = "Successfully validated connection".to_string();
-        match table.table_type {
-            TableType::Source { regex_pattern, .. } => {
-                let r = regex_pattern
-                    .as_ref()
-                    .map(|pattern| Regex::new(pattern))
-                    .transpose();
-                if let Err(e) = r {
-                    failed = true;
-                    message = format!(
-                        "Invalid regex pattern: {}, {}",
-                        regex_pattern.as_ref().unwrap(),
-                        e
-                    );
-                }
-            }
-            TableType::Sink { .. } => {
-                // TODO: implement sink testing
-            }
-        }
-
         tokio::task::spawn(async move {
-            let message = TestSourceMessage {
-                error: failed,
-                done: true,
-                message,
+            let message = match test_table(table, &tx).await {
+                Ok(message) => TestSourceMessage::done(message),
+                Err(e) => TestSourceMessage::fail(format!("{:#}", e)),
             };
             tx.send(message).await.unwrap();
         });
     }
 
+    fn sample(
+        &self,
+        _: Self::ProfileT,
+        table: Self::TableT,
+        count: usize,
+    ) -> oneshot::Receiver<anyhow::Result<Vec<Vec<u8>>>> {
+        let (tx, rx) = oneshot::channel();
+
+        tokio::spawn(async move {
+            let _ = tx.send(sample_table(table, count).await);
+        });
+
+        rx
+    }
+
     fn table_type(&self, _: Self::ProfileT, table: Self::TableT) -> ConnectionType {
         match table.table_type {
             TableType::Source { .. } => ConnectionType::Source,
@@ -107,6 +109,18 @@ impl Connector for FileSystemConnector {
         }
     }
 
+    fn is_bounded(&self, _: Self::ProfileT, table: Self::TableT) -> bool {
+        // the source lists the files under its path when it starts, and finishes once it has
+        // read them unless it's watching for new files
+        matches!(
+            table.table_type,
+            TableType::Source {
+                watch_interval_ms: None | Some(0),
+                ..
+            }
+        )
+    }
+
     fn from_config(
         &self,
         id: Option<i64>,
@@ -123,15 +137,26 @@ impl Connector for FileSystemConnector {
                 ref file_settings,
                 ..
             } => {
-                // confirm commit style is Direct
-                let Some(CommitStyle::Direct) = file_settings
+                // confirm commit style is Direct or Manifest
+                let Some(CommitStyle::Direct | CommitStyle::Manifest) = file_settings
                     .as_ref()
                     .ok_or_else(|| anyhow!("no file_settings"))?
                     .commit_style
                 else {
-                    bail!("commit_style must be Direct");
+                    bail!("commit_style must be Direct or Manifest");
                 };
 
+                if let Some(settings) = file_settings {
+                    if settings.compaction_target_size.is_some() {
+                        if settings.commit_style != Some(CommitStyle::Direct) {
+                            bail!("compaction requires the Direct commit style");
+                        }
+                        if !matches!(format_settings, Some(FormatSettings::Parquet { .. })) {
+                            bail!("compaction is only supported for Parquet files");
+                        }
+                    }
+                }
+
                 let backend_config = BackendConfig::parse_url(write_path, true)?;
                 let is_local = backend_config.is_local();
                 let description = match (format_settings, is_local) {
@@ -145,6 +170,18 @@ impl Connector for FileSystemConnector {
                         "LocalFileSystem<JSON>".to_string()
                     }
                     (Some(FormatSettings::Json { .. }), false) => "FileSystem<JSON>".to_string(),
+                    (Some(FormatSettings::ArrowIpc { .. }), true) => {
+                        "LocalFileSystem<ArrowIpc>".to_string()
+                    }
+                    (Some(FormatSettings::ArrowIpc { .. }), false) => {
+                        "FileSystem<ArrowIpc>".to_string()
+                    }
+                    (Some(FormatSettings::Csv { .. }), true) => "LocalFileSystem<CSV>".to_string(),
+                    (Some(FormatSettings::Csv { .. }), false) => "FileSystem<CSV>".to_string(),
+                    (Some(FormatSettings::Avro { .. }), true) => {
+                        "LocalFileSystem<Avro>".to_string()
+                    }
+                    (Some(FormatSettings::Avro { .. }), false) => "FileSystem<Avro>".to_string(),
                     (None, _) => bail!("have to have some format settings"),
                 };
                 (description, ConnectionType::Sink)
@@ -197,7 +234,14 @@ impl Connector for FileSystemConnector {
                     .map(|format| format.as_str().try_into().map_err(|err: &str| anyhow!(err)))
                     .transpose()?
                     .unwrap_or(CompressionFormat::None);
+                if schema
+                    .and_then(|s| s.framing.as_ref())
+                    .is_some_and(|f| f.compression.is_some())
+                {
+                    bail!("filesystem sources decompress whole files; set compression_format instead of compression");
+                }
                 let matching_pattern = options.remove("source.regex-pattern");
+                let watch_interval_ms = pull_option_to_u64("source.watch-interval-ms", options)?;
                 self.from_config(
                     None,
                     name,
@@ -208,13 +252,26 @@ impl Connector for FileSystemConnector {
                             storage_options,
                             compression_format: Some(compression_format),
                             regex_pattern: matching_pattern,
+                            watch_interval_ms,
                         },
                     },
                     schema,
                 )
             }
             Some(t) if t == "sink" => {
-                let table = file_system_sink_from_options(options, schema, CommitStyle::Direct)?;
+                let commit_style = options
+                    .remove("commit_style")
+                    .map(|value| match value.as_str() {
+                        "direct" => Ok(CommitStyle::Direct),
+                        "manifest" => Ok(CommitStyle::Manifest),
+                        _ => bail!(
+                            "'{}' is not a valid commit_style; expected 'direct' or 'manifest'",
+                            value
+                        ),
+                    })
+                    .transpose()?
+                    .unwrap_or(CommitStyle::Direct);
+                let table = file_system_sink_from_options(options, schema, commit_style)?;
 
                 self.from_config(None, name, EmptyConfig {}, table, schema)
             }
@@ -267,6 +324,32 @@ impl Connector for FileSystemConnector {
                     (Some(FormatSettings::Json { .. }), false) => Ok(OperatorNode::from_operator(
                         Box::new(JsonFileSystemSink::new(table, config)),
                     )),
+                    (Some(FormatSettings::ArrowIpc { .. }), true) => {
+                        Ok(OperatorNode::from_operator(Box::new(
+                            LocalArrowIpcFileSystemSink::new(write_path.to_string(), table, config),
+                        )))
+                    }
+                    (Some(FormatSettings::ArrowIpc { .. }), false) => {
+                        Ok(OperatorNode::from_operator(Box::new(
+                            ArrowIpcFileSystemSink::new(table, config),
+                        )))
+                    }
+                    (Some(FormatSettings::Csv { .. }), true) => {
+                        Ok(OperatorNode::from_operator(Box::new(
+                            LocalCsvFileSystemSink::new(write_path.to_string(), table, config),
+                        )))
+                    }
+                    (Some(FormatSettings::Csv { .. }), false) => Ok(OperatorNode::from_operator(
+                        Box::new(CsvFileSystemSink::new(table, config)),
+                    )),
+                    (Some(FormatSettings::Avro { .. }), true) => {
+                        Ok(OperatorNode::from_operator(Box::new(
+                            LocalAvroFileSystemSink::new(write_path.to_string(), table, config),
+                        )))
+                    }
+                    (Some(FormatSettings::Avro { .. }), false) => Ok(OperatorNode::from_operator(
+                        Box::new(AvroFileSystemSink::new(table, config)),
+                    )),
                     (None, _) => bail!("have to have some format settings"),
                 }
             }
@@ -288,6 +371,155 @@ fn get_storage_url_and_options(
     Ok((storage_url, storage_options))
 }
 
+/// Checks that the table's path can be read from (for sources) or written to (for sinks),
+/// reporting progress over `tx`
+pub(crate) async fn test_table(
+    table: FileSystemTable,
+    tx: &Sender<TestSourceMessage>,
+) -> Result<String> {
+    match table.table_type {
+        TableType::Source {
+            path,
+            storage_options,
+            regex_pattern,
+            ..
+        } => {
+            if let Some(pattern) = &regex_pattern {
+                Regex::new(pattern)
+                    .map_err(|e| anyhow!("Invalid regex pattern: {}, {}", pattern, e))?;
+            }
+
+            let _ = tx
+                .send(TestSourceMessage::info(format!(
+                    "Listing files in {}",
+                    path
+                )))
+                .await;
+            let provider = StorageProvider::for_url_with_options(&path, storage_options)
+                .await
+                .context("Failed to create storage provider")?;
+
+            let mut files = Box::pin(provider.list(true).await?);
+            match files.next().await {
+                Some(Ok(_)) => Ok(format!("Successfully listed files in {}", path)),
+                Some(Err(e)) => bail!("Failed to list files in {}: {}", path, e),
+                None => Ok(format!(
+                    "Successfully connected, but there are no files in {}",
+                    path
+                )),
+            }
+        }
+        TableType::Sink {
+            write_path,
+            storage_options,
+            ..
+        } => {
+            let _ = tx
+                .send(TestSourceMessage::info(format!(
+                    "Writing a test file to {}",
+                    write_path
+                )))
+                .await;
+            let provider = StorageProvider::for_url_with_options(&write_path, storage_options)
+                .await
+                .context("Failed to create storage provider")?;
+
+            let key = format!(".arroyo-test-{}", Uuid::new_v4());
+            provider
+                .put(key.as_str(), b"arroyo connection test".to_vec())
+                .await
+                .with_context(|| format!("Failed to write to {}", write_path))?;
+
+            let _ = tx
+                .send(TestSourceMessage::info("Deleting the test file"))
+                .await;
+            provider
+                .delete_if_present(key.as_str())
+                .await
+                .with_context(|| {
+                    format!(
+                        "Wrote a test file to {}, but could not delete it",
+                        write_path
+                    )
+                })?;
+
+            Ok(format!("Successfully wrote to {}", write_path))
+        }
+    }
+}
+
+/// Reads up to `count` lines from the files that the source would read, as they'd be passed to
+/// the deserializer
+pub(crate) async fn sample_table(table: FileSystemTable, count: usize) -> Result<Vec<Vec<u8>>> {
+    let TableType::Source {
+        path,
+        storage_options,
+        compression_format,
+        regex_pattern,
+        ..
+    } = table.table_type
+    else {
+        bail!("only filesystem sources can be sampled");
+    };
+
+    let regex_pattern = regex_pattern
+        .as_ref()
+        .map(|pattern| {
+            Regex::new(pattern).map_err(|e| anyhow!("Invalid regex pattern: {}, {}", pattern, e))
+        })
+        .transpose()?;
+
+    let provider = StorageProvider::for_url_with_options(&path, storage_options)
+        .await
+        .context("Failed to create storage provider")?;
+
+    let mut files = Box::pin(provider.list(regex_pattern.is_some()).await?);
+    let mut lines = vec![];
+    while let Some(file) = files.next().await {
+        let file = file.with_context(|| format!("Failed to list files in {}", path))?;
+        if is_internal_path(&file)
+            || !regex_pattern
+                .as_ref()
+                .map_or(true, |r| r.is_match(file.as_ref()))
+        {
+            continue;
+        }
+
+        let bytes = provider
+            .get(file.clone())
+            .await
+            .with_context(|| format!("Failed to read {}", file))?;
+
+        let mut data = vec![];
+        match compression_format.unwrap_or(CompressionFormat::None) {
+            CompressionFormat::Zstd => {
+                ZstdDecoder::new(&bytes[..]).read_to_end(&mut data).await?;
+            }
+            CompressionFormat::Gzip => {
+                GzipDecoder::new(&bytes[..]).read_to_end(&mut data).await?;
+            }
+            CompressionFormat::None => data = bytes.to_vec(),
+        }
+
+        lines.extend(
+            data.split(|b| *b == b'\n')
+                .filter(|line| !line.is_empty())
+                .take(count - lines.len())
+                .map(|line| line.to_vec()),
+        );
+
+        if lines.len() >= count {
+            break;
+        }
+    }
+
+    if lines.is_empty() {
+        bail!("no data was found in {}", path);
+    }
+
+    Ok(lines)
+}
+
 pub fn file_system_sink_from_options(
     opts: &mut std::collections::HashMap<String, String>,
     schema: Option<&ConnectionSchema>,
@@ -300,6 +532,11 @@ pub fn file_system_sink_from_options(
     let rollover_seconds = pull_option_to_i64("rollover_seconds", opts)?;
     let target_file_size = pull_option_to_i64("target_file_size", opts)?;
     let target_part_size = pull_option_to_i64("target_part_size", opts)?;
+    let compaction_target_size = if matches!(commit_style, CommitStyle::Direct) {
+        pull_option_to_i64("compaction_target_size", opts)?
+    } else {
+        None
+    };
     let prefix = opts.remove("filename.prefix");
     let suffix = opts.remove("filename.suffix");
     let strategy = opts
@@ -316,10 +553,15 @@ pub fn file_system_sink_from_options(
         .unwrap_or_default();
 
     let time_partition_pattern = opts.remove("time_partition_pattern");
+    let partition_expression = opts.remove("partition_by");
 
-    let partitioning = if time_partition_pattern.is_some() || !partition_fields.is_empty() {
+    let partitioning = if time_partition_pattern.is_some()
+        || partition_expression.is_some()
+        || !partition_fields.is_empty()
+    {
         Some(Partitioning {
             time_partition_pattern,
+            partition_expression,
             partition_fields,
         })
     } else {
@@ -342,8 +584,10 @@ pub fn file_system_sink_from_options(
         rollover_seconds,
         target_file_size,
         target_part_size,
+        compaction_target_size,
         partitioning,
         commit_style: Some(commit_style),
+        iceberg: None,
         file_naming,
     });
 
@@ -370,6 +614,17 @@ pub fn file_system_sink_from_options(
         Format::Json(..) => Some(FormatSettings::Json {
             json_format: JsonFormat::Json,
         }),
+        Format::ArrowIpc(..) => Some(FormatSettings::ArrowIpc {
+            ipc_format: IpcFormat::Stream,
+        }),
+        Format::Csv(..) => Some(FormatSettings::Csv {
+            csv_format: CsvFormat::Csv,
+        }),
+        Format::Avro(avro) if !avro.confluent_schema_registry && !avro.raw_datums => {
+            Some(FormatSettings::Avro {
+                avro_format: AvroFormat::ObjectContainer,
+            })
+        }
         other => bail!("Unsupported format: {:?}", other),
     };
     Ok(FileSystemTable {
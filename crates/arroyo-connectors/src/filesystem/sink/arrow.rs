@@ -1 +1,197 @@
+use std::{fs::File, io::Write, time::Instant};
 
+use arrow::{ipc::writer::StreamWriter, record_batch::RecordBatch};
+use arroyo_rpc::{df::ArroyoSchemaRef, formats::Format};
+
+use super::{
+    local::{CurrentFileRecovery, FilePreCommit, LocalWriter},
+    parquet::{representitive_timestamp, SharedBuffer},
+    BatchBufferingWriter, FileSettings, FileSystemTable, MultiPartWriterStats, TableType,
+};
+
+/// The end-of-stream marker of the Arrow IPC streaming format: a continuation token followed
+/// by a zero length
+const END_OF_STREAM: [u8; 8] = [0xFF, 0xFF, 0xFF, 0xFF, 0, 0, 0, 0];
+
+fn new_stream_writer(
+    shared_buffer: &SharedBuffer,
+    schema: &ArroyoSchemaRef,
+) -> StreamWriter<SharedBuffer> {
+    StreamWriter::try_new(shared_buffer.clone(), &schema.schema_without_timestamp())
+        .expect("failed to create Arrow IPC writer")
+}
+
+/// Writes files in the Arrow IPC streaming format, with a single schema message followed by
+/// one record batch message per batch
+pub struct ArrowIpcWriter {
+    writer: Option<StreamWriter<SharedBuffer>>,
+    shared_buffer: SharedBuffer,
+    target_part_size: usize,
+    schema: ArroyoSchemaRef,
+}
+
+impl BatchBufferingWriter for ArrowIpcWriter {
+    fn new(config: &FileSystemTable, _format: Option<Format>, schema: ArroyoSchemaRef) -> Self {
+        let target_part_size = if let TableType::Sink {
+            file_settings:
+                Some(FileSettings {
+                    target_part_size: Some(target_part_size),
+                    ..
+                }),
+            ..
+        } = config.table_type
+        {
+            target_part_size as usize
+        } else {
+            5 * 1024 * 1024
+        };
+        let shared_buffer = SharedBuffer::new(target_part_size);
+        let writer = new_stream_writer(&shared_buffer, &schema);
+
+        Self {
+            writer: Some(writer),
+            shared_buffer,
+            target_part_size,
+            schema,
+        }
+    }
+
+    fn suffix() -> String {
+        "arrow".to_string()
+    }
+
+    fn add_batch_data(&mut self, mut data: RecordBatch) -> Option<Vec<u8>> {
+        let writer = self.writer.as_mut().unwrap();
+        // remove timestamp column
+        self.schema.remove_timestamp_column(&mut data);
+        writer.write(&data).unwrap();
+        writer.flush().unwrap();
+        if self.buffer_length() > self.target_part_size {
+            Some(self.evict_current_buffer())
+        } else {
+            None
+        }
+    }
+
+    fn buffer_length(&self) -> usize {
+        self.shared_buffer.buffer.try_lock().unwrap().len()
+    }
+
+    fn evict_current_buffer(&mut self) -> Vec<u8> {
+        let mut buffer = self.shared_buffer.buffer.try_lock().unwrap();
+        let current_buffer_data = buffer.to_vec();
+        buffer.clear();
+        current_buffer_data
+    }
+
+    fn get_trailing_bytes_for_checkpoint(&mut self) -> Option<Vec<u8>> {
+        // every message is written out in full, so the stream only needs its end marker
+        let mut copied_bytes = self.shared_buffer.buffer.try_lock().unwrap().to_vec();
+        copied_bytes.extend_from_slice(&END_OF_STREAM);
+        Some(copied_bytes)
+    }
+
+    fn close(&mut self, final_batch: Option<RecordBatch>) -> Option<Vec<u8>> {
+        let mut writer = self.writer.take().unwrap();
+        if let Some(mut batch) = final_batch {
+            self.schema.remove_timestamp_column(&mut batch);
+            writer.write(&batch).unwrap();
+        }
+        writer.finish().unwrap();
+        let buffer = self.shared_buffer.buffer.try_lock().unwrap();
+        Some(buffer.to_vec())
+    }
+}
+
+pub struct ArrowIpcLocalWriter {
+    writer: Option<StreamWriter<SharedBuffer>>,
+    tmp_path: String,
+    file: File,
+    destination_path: String,
+    shared_buffer: SharedBuffer,
+    stats: Option<MultiPartWriterStats>,
+    schema: ArroyoSchemaRef,
+}
+
+impl LocalWriter for ArrowIpcLocalWriter {
+    fn new(
+        tmp_path: String,
+        final_path: String,
+        _table_properties: &FileSystemTable,
+        _format: Option<Format>,
+        schema: ArroyoSchemaRef,
+    ) -> Self {
+        let shared_buffer = SharedBuffer::new(0);
+        let writer = new_stream_writer(&shared_buffer, &schema);
+        let file = File::create(tmp_path.clone()).unwrap();
+        Self {
+            writer: Some(writer),
+            tmp_path,
+            file,
+            destination_path: final_path,
+            shared_buffer,
+            stats: None,
+            schema,
+        }
+    }
+
+    fn file_suffix() -> &'static str {
+        "arrow"
+    }
+
+    fn write_batch(&mut self, mut batch: RecordBatch) -> anyhow::Result<()> {
+        if self.stats.is_none() {
+            self.stats = Some(MultiPartWriterStats {
+                bytes_written: 0,
+                parts_written: 0,
+                first_write_at: Instant::now(),
+                last_write_at: Instant::now(),
+                representative_timestamp: representitive_timestamp(
+                    batch.column(self.schema.timestamp_index),
+                )?,
+            });
+        } else {
+            self.stats.as_mut().unwrap().last_write_at = Instant::now();
+        }
+        self.schema.remove_timestamp_column(&mut batch);
+        self.writer.as_mut().unwrap().write(&batch)?;
+        Ok(())
+    }
+
+    fn sync(&mut self) -> anyhow::Result<usize> {
+        let mut buffer = self.shared_buffer.buffer.try_lock().unwrap();
+        self.file.write_all(&buffer)?;
+        self.file.sync_all()?;
+        // get size of the file
+        let metadata = self.file.metadata()?;
+        let size = metadata.len() as usize;
+        self.stats.as_mut().unwrap().bytes_written = size;
+        buffer.clear();
+        Ok(size)
+    }
+
+    fn close(&mut self) -> anyhow::Result<FilePreCommit> {
+        let mut writer = self.writer.take().unwrap();
+        writer.finish()?;
+        self.sync()?;
+        Ok(FilePreCommit {
+            tmp_file: self.tmp_path.clone(),
+            destination: self.destination_path.clone(),
+        })
+    }
+
+    fn checkpoint(&mut self) -> anyhow::Result<Option<CurrentFileRecovery>> {
+        self.writer.as_mut().unwrap().flush()?;
+        let bytes_written = self.sync()?;
+        Ok(Some(CurrentFileRecovery {
+            tmp_file: self.tmp_path.clone(),
+            bytes_written,
+            suffix: Some(END_OF_STREAM.to_vec()),
+            destination: self.destination_path.clone(),
+        }))
+    }
+
+    fn stats(&self) -> MultiPartWriterStats {
+        self.stats.as_ref().unwrap().clone()
+    }
+}
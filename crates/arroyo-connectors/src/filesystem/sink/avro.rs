@@ -0,0 +1,177 @@
+use std::{fs::File, io::Write, sync::Arc, time::Instant};
+
+use arrow::record_batch::RecordBatch;
+use arroyo_formats::avro::ser::ContainerFileWriter;
+use arroyo_formats::ser::ArrowSerializer;
+use arroyo_rpc::{df::ArroyoSchemaRef, formats::Format};
+
+use super::{
+    local::{CurrentFileRecovery, FilePreCommit, LocalWriter},
+    parquet::representitive_timestamp,
+    BatchBufferingWriter, FileSettings, FileSystemTable, MultiPartWriterStats, TableType,
+};
+
+fn container_file_writer(schema: &ArroyoSchemaRef) -> ContainerFileWriter {
+    ContainerFileWriter::new(Arc::new(ArrowSerializer::avro_schema(&schema.schema)))
+}
+
+/// Writes Avro object container files, with a block of records for each batch
+pub struct AvroWriter {
+    writer: ContainerFileWriter,
+    current_buffer: Vec<u8>,
+    target_part_size: usize,
+    schema: ArroyoSchemaRef,
+}
+
+impl BatchBufferingWriter for AvroWriter {
+    fn new(config: &FileSystemTable, _format: Option<Format>, schema: ArroyoSchemaRef) -> Self {
+        let target_part_size = if let TableType::Sink {
+            file_settings:
+                Some(FileSettings {
+                    target_part_size: Some(target_part_size),
+                    ..
+                }),
+            ..
+        } = config.table_type
+        {
+            target_part_size as usize
+        } else {
+            5 * 1024 * 1024
+        };
+        let writer = container_file_writer(&schema);
+
+        Self {
+            current_buffer: writer.header(),
+            writer,
+            target_part_size,
+            schema,
+        }
+    }
+
+    fn suffix() -> String {
+        "avro".to_string()
+    }
+
+    fn add_batch_data(&mut self, mut batch: RecordBatch) -> Option<Vec<u8>> {
+        self.schema.remove_timestamp_column(&mut batch);
+        self.current_buffer.extend(self.writer.block(&batch));
+        if self.buffer_length() > self.target_part_size {
+            Some(self.evict_current_buffer())
+        } else {
+            None
+        }
+    }
+
+    fn buffer_length(&self) -> usize {
+        self.current_buffer.len()
+    }
+
+    fn evict_current_buffer(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.current_buffer)
+    }
+
+    fn get_trailing_bytes_for_checkpoint(&mut self) -> Option<Vec<u8>> {
+        // every block ends with the sync marker, so the file is complete as it is
+        if self.current_buffer.is_empty() {
+            None
+        } else {
+            Some(self.current_buffer.clone())
+        }
+    }
+
+    fn close(&mut self, final_batch: Option<RecordBatch>) -> Option<Vec<u8>> {
+        if let Some(final_batch) = final_batch {
+            if let Some(final_batch) = self.add_batch_data(final_batch) {
+                return Some(final_batch);
+            }
+        }
+        if self.current_buffer.is_empty() {
+            None
+        } else {
+            Some(self.evict_current_buffer())
+        }
+    }
+}
+
+pub struct AvroLocalWriter {
+    writer: ContainerFileWriter,
+    tmp_path: String,
+    final_path: String,
+    file: File,
+    stats: Option<MultiPartWriterStats>,
+    schema: ArroyoSchemaRef,
+}
+
+impl LocalWriter for AvroLocalWriter {
+    fn new(
+        tmp_path: String,
+        final_path: String,
+        _table_properties: &FileSystemTable,
+        _format: Option<Format>,
+        schema: ArroyoSchemaRef,
+    ) -> Self {
+        let writer = container_file_writer(&schema);
+        let mut file = File::create(&tmp_path).unwrap();
+        file.write_all(&writer.header()).unwrap();
+        Self {
+            writer,
+            tmp_path,
+            final_path,
+            file,
+            stats: None,
+            schema,
+        }
+    }
+
+    fn file_suffix() -> &'static str {
+        "avro"
+    }
+
+    fn write_batch(&mut self, mut batch: RecordBatch) -> anyhow::Result<()> {
+        if self.stats.is_none() {
+            self.stats = Some(MultiPartWriterStats {
+                bytes_written: 0,
+                parts_written: 0,
+                first_write_at: Instant::now(),
+                last_write_at: Instant::now(),
+                representative_timestamp: representitive_timestamp(
+                    batch.column(self.schema.timestamp_index),
+                )?,
+            });
+        } else {
+            self.stats.as_mut().unwrap().last_write_at = Instant::now();
+        }
+        self.schema.remove_timestamp_column(&mut batch);
+        self.file.write_all(&self.writer.block(&batch))?;
+        Ok(())
+    }
+
+    fn sync(&mut self) -> anyhow::Result<usize> {
+        self.file.flush()?;
+        let size = self.file.metadata()?.len() as usize;
+        self.stats.as_mut().unwrap().bytes_written = size;
+        Ok(size)
+    }
+
+    fn close(&mut self) -> anyhow::Result<FilePreCommit> {
+        LocalWriter::sync(self)?;
+        Ok(FilePreCommit {
+            tmp_file: self.tmp_path.clone(),
+            destination: self.final_path.clone(),
+        })
+    }
+
+    fn checkpoint(&mut self) -> anyhow::Result<Option<CurrentFileRecovery>> {
+        let bytes_written = LocalWriter::sync(self)?;
+        Ok(Some(CurrentFileRecovery {
+            tmp_file: self.tmp_path.clone(),
+            bytes_written,
+            suffix: None,
+            destination: self.final_path.clone(),
+        }))
+    }
+
+    fn stats(&self) -> MultiPartWriterStats {
+        self.stats.clone().unwrap()
+    }
+}
@@ -0,0 +1,212 @@
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+use arroyo_storage::StorageProvider;
+use bincode::{Decode, Encode};
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use parquet::arrow::ArrowWriter;
+use parquet::file::properties::WriterProperties;
+use tracing::info;
+use uuid::Uuid;
+
+use super::FinishedFile;
+
+/// A merge of small files in a directory into a single file
+#[derive(Debug, Clone, Encode, Decode, PartialEq, Eq, PartialOrd)]
+pub struct CompactionPlan {
+    inputs: Vec<String>,
+    output: String,
+}
+
+impl CompactionPlan {
+    /// Writes the rows of the inputs to the output, then deletes the inputs. Inputs are only
+    /// deleted once the output has been written, so this can be run again after failing at any
+    /// point: if some of the inputs are gone, the output is already complete.
+    async fn run(&self, storage: &StorageProvider, properties: &WriterProperties) -> Result<()> {
+        let mut present = vec![];
+        for input in &self.inputs {
+            if storage.exists(input.as_str()).await? {
+                present.push(input);
+            }
+        }
+
+        if present.len() == self.inputs.len() {
+            let mut writer: Option<ArrowWriter<Vec<u8>>> = None;
+            for input in &self.inputs {
+                let bytes = storage.get(input.as_str()).await?;
+                for batch in ParquetRecordBatchReaderBuilder::try_new(bytes)?.build()? {
+                    let batch = batch?;
+                    let writer = match &mut writer {
+                        Some(writer) => writer,
+                        None => writer.insert(ArrowWriter::try_new(
+                            Vec::new(),
+                            batch.schema(),
+                            Some(properties.clone()),
+                        )?),
+                    };
+                    writer.write(&batch)?;
+                }
+            }
+
+            if let Some(writer) = writer {
+                storage
+                    .put(self.output.as_str(), writer.into_inner()?)
+                    .await?;
+            }
+            info!("compacted {} files into {}", self.inputs.len(), self.output);
+        }
+
+        for input in present {
+            storage.delete_if_present(input.as_str()).await?;
+        }
+        Ok(())
+    }
+}
+
+/// The state of compaction as of a checkpoint
+#[derive(Debug, Default, Clone, Encode, Decode, PartialEq, Eq, PartialOrd)]
+pub struct CompactionState {
+    small_files: Vec<FinishedFile>,
+    planned: Vec<CompactionPlan>,
+}
+
+impl CompactionState {
+    /// Combines the state of several subtasks, for the subtask that restores all of them
+    pub fn merge(&mut self, other: CompactionState) {
+        self.small_files.extend(other.small_files);
+        self.planned.extend(other.planned);
+    }
+}
+
+/// Merges the small files committed by the sink into files of about the target size. Merges
+/// are planned when files are committed, and only run once a checkpoint that includes the plan
+/// has been committed, so that after a failure they're finished from the restored state rather
+/// than lost or repeated.
+pub(crate) struct Compactor {
+    target_size: usize,
+    properties: WriterProperties,
+    /// committed files smaller than the target size that haven't been merged
+    small_files: Vec<FinishedFile>,
+    /// merges that have been included in a checkpoint
+    saved: Vec<CompactionPlan>,
+    /// merges planned since the last checkpoint
+    unsaved: Vec<CompactionPlan>,
+}
+
+impl Compactor {
+    pub fn new(target_size: usize, properties: WriterProperties) -> Self {
+        Self {
+            target_size,
+            properties,
+            small_files: vec![],
+            saved: vec![],
+            unsaved: vec![],
+        }
+    }
+
+    pub fn restore(&mut self, state: CompactionState) {
+        self.small_files.extend(state.small_files);
+        self.saved.extend(state.planned);
+    }
+
+    pub fn checkpoint(&mut self) -> CompactionState {
+        self.saved.append(&mut self.unsaved);
+        CompactionState {
+            small_files: self.small_files.clone(),
+            planned: self.saved.clone(),
+        }
+    }
+
+    /// Runs the merges that have been included in a checkpoint
+    pub async fn run_saved(&mut self, storage: &StorageProvider) -> Result<()> {
+        while let Some(plan) = self.saved.first() {
+            plan.run(storage, &self.properties).await?;
+            self.saved.remove(0);
+        }
+        Ok(())
+    }
+
+    /// Adds newly committed files, planning a merge of the small files in a directory once
+    /// together they reach the target size
+    pub fn add_files(&mut self, files: &[FinishedFile]) {
+        self.small_files
+            .extend(files.iter().filter(|f| f.size < self.target_size).cloned());
+
+        let mut by_dir: BTreeMap<String, Vec<FinishedFile>> = BTreeMap::new();
+        for file in self.small_files.drain(..) {
+            let dir = file
+                .filename
+                .rsplit_once('/')
+                .map(|(dir, _)| dir.to_string())
+                .unwrap_or_default();
+            by_dir.entry(dir).or_default().push(file);
+        }
+
+        for (dir, files) in by_dir {
+            if files.len() < 2 || files.iter().map(|f| f.size).sum::<usize>() < self.target_size {
+                self.small_files.extend(files);
+                continue;
+            }
+
+            let name = format!("compacted-{}.parquet", Uuid::new_v4());
+            self.unsaved.push(CompactionPlan {
+                inputs: files.into_iter().map(|f| f.filename).collect(),
+                output: if dir.is_empty() {
+                    name
+                } else {
+                    format!("{}/{}", dir, name)
+                },
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{CompactionState, Compactor};
+    use crate::filesystem::sink::FinishedFile;
+    use parquet::file::properties::WriterProperties;
+
+    fn file(filename: &str, size: usize) -> FinishedFile {
+        FinishedFile {
+            filename: filename.to_string(),
+            partition: None,
+            size,
+        }
+    }
+
+    #[test]
+    fn test_planning() {
+        let mut compactor = Compactor::new(100, WriterProperties::default());
+        compactor.add_files(&[
+            file("table/a/00000-000.parquet", 40),
+            file("table/b/00000-000.parquet", 40),
+            file("table/a/00001-000.parquet", 500),
+        ]);
+        assert!(compactor.unsaved.is_empty());
+        assert_eq!(compactor.small_files.len(), 2);
+
+        compactor.add_files(&[
+            file("table/a/00002-000.parquet", 70),
+            file("table/b/00002-000.parquet", 10),
+        ]);
+        assert_eq!(compactor.unsaved.len(), 1);
+        let plan = &compactor.unsaved[0];
+        assert_eq!(
+            plan.inputs,
+            vec!["table/a/00000-000.parquet", "table/a/00002-000.parquet"]
+        );
+        assert!(plan.output.starts_with("table/a/compacted-"));
+        assert_eq!(compactor.small_files.len(), 2);
+
+        // plans are only run once they've been checkpointed
+        assert!(compactor.saved.is_empty());
+        let state = compactor.checkpoint();
+        assert_eq!(compactor.saved.len(), 1);
+
+        let mut restored = Compactor::new(100, WriterProperties::default());
+        restored.restore(state.clone());
+        assert_eq!(restored.checkpoint(), state);
+        assert_ne!(state, CompactionState::default());
+    }
+}
@@ -0,0 +1,404 @@
+use super::FinishedFile;
+use crate::filesystem::IcebergCommit;
+use crate::iceberg::catalog::{CommitResult, RestCatalog, Snapshot, TableMetadata};
+use crate::iceberg::manifest::{
+    read_manifest_list, write_manifest, write_manifest_list, DataFile, ManifestFile,
+};
+use crate::sink_schema::{resolve_drift, schema_drift, Dialect, DriftMode, TableColumn};
+use anyhow::{bail, Context, Result};
+use arrow::datatypes::{DataType, Field, Schema};
+use arroyo_storage::StorageProvider;
+use arroyo_types::{to_millis, NonRetryableError};
+use object_store::path::Path;
+use parquet::arrow::async_reader::{AsyncFileReader, ParquetObjectReader};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::time::SystemTime;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+/// Snapshot summary property recording which files a snapshot committed, so that commits that
+/// are retried after a failure can be recognized
+const COMMIT_ID_PROPERTY: &str = "arroyo.commit-id";
+
+const NAME_MAPPING_PROPERTY: &str = "schema.name-mapping.default";
+
+const MAX_COMMIT_ATTEMPTS: usize = 5;
+
+/// Commits the finished files to an Iceberg table as a new append snapshot. Files are written
+/// under `data_url`, which corresponds to `relative_table_path` in the storage provider.
+pub(crate) async fn commit_files_to_iceberg(
+    finished_files: &[FinishedFile],
+    relative_table_path: &Path,
+    data_url: &str,
+    storage_provider: &StorageProvider,
+    storage_options: &HashMap<String, String>,
+    settings: &IcebergCommit,
+) -> Result<()> {
+    if finished_files.is_empty() {
+        return Ok(());
+    }
+
+    let mut data_files = vec![];
+    for file in finished_files {
+        data_files.push(data_file(file, relative_table_path, data_url, storage_provider).await?);
+    }
+
+    // file names are unique, so any of them identifies the commit
+    let commit_id = data_files
+        .iter()
+        .map(|f| f.file_path.as_str())
+        .min()
+        .unwrap()
+        .to_string();
+
+    let token = settings
+        .token
+        .as_ref()
+        .map(|t| t.sub_env_vars())
+        .transpose()?;
+    let catalog =
+        RestCatalog::connect(&settings.catalog_url, settings.warehouse.clone(), token).await?;
+
+    for attempt in 1..=MAX_COMMIT_ATTEMPTS {
+        let metadata = catalog
+            .load_table(&settings.namespace, &settings.table)
+            .await?;
+
+        if let Some(snapshot) = metadata
+            .snapshots
+            .iter()
+            .find(|s| s.summary.get(COMMIT_ID_PROPERTY) == Some(&commit_id))
+        {
+            info!(
+                "files were already committed to Iceberg in snapshot {}",
+                snapshot.snapshot_id
+            );
+            return Ok(());
+        }
+
+        let snapshot = write_snapshot(&metadata, &data_files, &commit_id, storage_options).await?;
+
+        let mut properties = HashMap::new();
+        if !metadata.properties.contains_key(NAME_MAPPING_PROPERTY) {
+            // the parquet files we write don't carry Iceberg field ids, so readers need a
+            // mapping from column names to ids
+            properties.insert(
+                NAME_MAPPING_PROPERTY.to_string(),
+                name_mapping(metadata.current_schema()?).to_string(),
+            );
+        }
+
+        match catalog
+            .commit_snapshot(
+                &settings.namespace,
+                &settings.table,
+                &metadata.table_uuid,
+                &snapshot,
+                properties,
+            )
+            .await?
+        {
+            CommitResult::Committed => {
+                info!(
+                    "committed {} files to Iceberg in snapshot {}",
+                    data_files.len(),
+                    snapshot.snapshot_id
+                );
+                return Ok(());
+            }
+            CommitResult::Conflict => {
+                warn!(
+                    "Iceberg table {}.{} changed during commit (attempt {}/{})",
+                    settings.namespace, settings.table, attempt, MAX_COMMIT_ATTEMPTS
+                );
+            }
+        }
+    }
+
+    bail!(
+        "failed to commit to Iceberg table {}.{} after {} attempts",
+        settings.namespace,
+        settings.table,
+        MAX_COMMIT_ATTEMPTS
+    )
+}
+
+/// Iceberg's name for the type of the column that a field is written as
+fn column_type(field: &Field) -> Result<String> {
+    Ok(match field.data_type() {
+        DataType::Boolean => "boolean".to_string(),
+        DataType::Int8 | DataType::Int16 | DataType::Int32 | DataType::UInt8 | DataType::UInt16 => {
+            "int".to_string()
+        }
+        DataType::Int64 | DataType::UInt32 | DataType::UInt64 => "long".to_string(),
+        DataType::Float16 | DataType::Float32 => "float".to_string(),
+        DataType::Float64 => "double".to_string(),
+        DataType::Decimal128(p, s) | DataType::Decimal256(p, s) => format!("decimal({}, {})", p, s),
+        DataType::Utf8 | DataType::LargeUtf8 | DataType::Utf8View => "string".to_string(),
+        DataType::Binary | DataType::LargeBinary | DataType::BinaryView => "binary".to_string(),
+        DataType::FixedSizeBinary(n) => format!("fixed[{}]", n),
+        DataType::Date32 | DataType::Date64 => "date".to_string(),
+        DataType::Time32(_) | DataType::Time64(_) => "time".to_string(),
+        DataType::Timestamp(_, None) => "timestamp".to_string(),
+        DataType::Timestamp(_, Some(_)) => "timestamptz".to_string(),
+        DataType::Struct(_) => "struct".to_string(),
+        DataType::List(_) | DataType::LargeList(_) | DataType::FixedSizeList(_, _) => {
+            "list".to_string()
+        }
+        DataType::Map(_, _) => "map".to_string(),
+        t => bail!(
+            "field '{}' has type {}, which cannot be written to Iceberg",
+            field.name(),
+            t
+        ),
+    })
+}
+
+/// The sink doesn't evolve Iceberg tables, so missing columns are always rejected; readers
+/// would otherwise silently drop them
+const ICEBERG: Dialect = Dialect {
+    column_type,
+    // the type promotions that Iceberg readers apply
+    coercible: |from, to| matches!((from, to), ("int", "long") | ("float", "double")),
+    add_column: None,
+};
+
+/// The top-level columns of an Iceberg schema; nested types are only compared by kind
+fn table_columns(schema: &Value) -> Vec<TableColumn> {
+    schema
+        .get("fields")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(|field| {
+            let field_type = field.get("type")?;
+            Some(TableColumn {
+                name: field.get("name")?.as_str()?.to_string(),
+                type_name: match field_type {
+                    Value::String(t) => t.clone(),
+                    t => t.get("type")?.as_str()?.to_string(),
+                },
+                not_null: field.get("required").and_then(Value::as_bool) == Some(true),
+                has_default: false,
+            })
+        })
+        .collect()
+}
+
+/// Loads the table from the catalog and checks that the sink can commit to it and that its
+/// columns match the fields of the sink, so that unsupported tables are rejected when the sink
+/// starts rather than once data has been written
+pub(crate) async fn check_iceberg_table(settings: &IcebergCommit, schema: &Schema) -> Result<()> {
+    let token = settings
+        .token
+        .as_ref()
+        .map(|t| t.sub_env_vars())
+        .transpose()?;
+    let metadata = RestCatalog::connect(&settings.catalog_url, settings.warehouse.clone(), token)
+        .await?
+        .load_table(&settings.namespace, &settings.table)
+        .await?;
+
+    check_supported(&metadata).context(format!(
+        "cannot write to Iceberg table {}.{}",
+        settings.namespace, settings.table
+    ))?;
+
+    let fields: Vec<_> = schema.fields().iter().map(|f| f.as_ref().clone()).collect();
+    let drift = schema_drift(
+        &ICEBERG,
+        &fields,
+        &table_columns(metadata.current_schema()?),
+    )?;
+    for d in &drift {
+        warn!(
+            "schema of Iceberg table {}.{} differs from sink: {}",
+            settings.namespace, settings.table, d
+        );
+    }
+
+    // the table won't change by itself, so restarting the pipeline wouldn't help
+    resolve_drift(
+        &ICEBERG,
+        &format!("{}.{}", settings.namespace, settings.table),
+        DriftMode::Coerce,
+        &drift,
+    )
+    .map_err(|e| NonRetryableError(e.to_string()))?;
+
+    Ok(())
+}
+
+pub(crate) fn check_supported(metadata: &TableMetadata) -> Result<()> {
+    if metadata.format_version != 2 {
+        bail!(
+            "the Iceberg sink only supports format version 2 tables, but the table has version {}",
+            metadata.format_version
+        );
+    }
+
+    if !metadata.default_spec()?.fields.is_empty() {
+        bail!("the Iceberg sink does not support partitioned tables");
+    }
+
+    Ok(())
+}
+
+async fn data_file(
+    file: &FinishedFile,
+    relative_table_path: &Path,
+    data_url: &str,
+    storage_provider: &StorageProvider,
+) -> Result<DataFile> {
+    let subpath = file
+        .filename
+        .strip_prefix(&relative_table_path.to_string())
+        .context(format!(
+            "File {} is not in table {}",
+            file.filename, relative_table_path
+        ))?
+        .trim_start_matches('/');
+
+    // manifests record the number of rows in each file, which we read from the parquet footer
+    let object_meta = storage_provider.head(file.filename.as_str()).await?;
+    let mut reader = ParquetObjectReader::new(storage_provider.get_backing_store(), object_meta);
+    let parquet_metadata = reader.get_metadata().await?;
+
+    Ok(DataFile {
+        file_path: format!("{}/{}", data_url.trim_end_matches('/'), subpath),
+        record_count: parquet_metadata.file_metadata().num_rows(),
+        file_size_in_bytes: file.size as i64,
+    })
+}
+
+/// Writes the manifest and manifest list for a snapshot that appends the data files to the
+/// current state of the table
+async fn write_snapshot(
+    metadata: &TableMetadata,
+    data_files: &[DataFile],
+    commit_id: &str,
+    storage_options: &HashMap<String, String>,
+) -> Result<Snapshot> {
+    check_supported(metadata)?;
+    let spec = metadata.default_spec()?;
+
+    let schema = metadata.current_schema()?;
+    let parent = metadata.current_snapshot();
+    let sequence_number = metadata.last_sequence_number + 1;
+    let snapshot_id = rand::random::<i64>() & i64::MAX;
+
+    let location = metadata.location.trim_end_matches('/');
+    let storage = StorageProvider::for_url_with_options(location, storage_options.clone()).await?;
+
+    let manifest = write_manifest(data_files, snapshot_id, schema, spec.spec_id)?;
+    let manifest_key = format!("metadata/{}-m0.avro", Uuid::new_v4());
+    let manifest_length = manifest.len() as i64;
+    storage.put(manifest_key.as_str(), manifest).await?;
+
+    let mut manifests = vec![ManifestFile {
+        manifest_path: format!("{}/{}", location, manifest_key),
+        manifest_length,
+        partition_spec_id: spec.spec_id,
+        content: 0,
+        sequence_number,
+        min_sequence_number: sequence_number,
+        added_snapshot_id: snapshot_id,
+        added_files_count: data_files.len() as i32,
+        existing_files_count: 0,
+        deleted_files_count: 0,
+        added_rows_count: data_files.iter().map(|f| f.record_count).sum(),
+        existing_rows_count: 0,
+        deleted_rows_count: 0,
+    }];
+
+    // an append snapshot includes all of the manifests of its parent
+    if let Some(parent) = parent {
+        let list =
+            StorageProvider::get_url_with_options(&parent.manifest_list, storage_options.clone())
+                .await?;
+        manifests.extend(read_manifest_list(&parent.manifest_list, &list)?);
+    }
+
+    let manifest_list = write_manifest_list(
+        &manifests,
+        snapshot_id,
+        parent.map(|p| p.snapshot_id),
+        sequence_number,
+    )?;
+    let manifest_list_key = format!("metadata/snap-{}-1-{}.avro", snapshot_id, Uuid::new_v4());
+    storage
+        .put(manifest_list_key.as_str(), manifest_list)
+        .await?;
+
+    let summary = [
+        ("operation", "append".to_string()),
+        ("added-data-files", data_files.len().to_string()),
+        ("added-records", manifests[0].added_rows_count.to_string()),
+        (
+            "added-files-size",
+            data_files
+                .iter()
+                .map(|f| f.file_size_in_bytes)
+                .sum::<i64>()
+                .to_string(),
+        ),
+        (COMMIT_ID_PROPERTY, commit_id.to_string()),
+    ]
+    .into_iter()
+    .map(|(k, v)| (k.to_string(), v))
+    .collect();
+
+    Ok(Snapshot {
+        snapshot_id,
+        parent_snapshot_id: parent.map(|p| p.snapshot_id),
+        sequence_number,
+        timestamp_ms: to_millis(SystemTime::now()) as i64,
+        manifest_list: format!("{}/{}", location, manifest_list_key),
+        summary,
+        schema_id: metadata.current_schema_id,
+    })
+}
+
+/// Builds a [name mapping](https://iceberg.apache.org/spec/#column-projection) for an Iceberg
+/// schema, which lets readers resolve columns in files that don't have field ids
+fn name_mapping(schema: &Value) -> Value {
+    fn nested(field_type: &Value) -> Option<Value> {
+        let fields = match field_type.get("type").and_then(Value::as_str)? {
+            "struct" => return Some(name_mapping(field_type)),
+            "list" => vec![("element-id", "element", "element")],
+            "map" => vec![("key-id", "key", "key"), ("value-id", "value", "value")],
+            _ => return None,
+        };
+
+        Some(Value::Array(
+            fields
+                .into_iter()
+                .filter_map(|(id, name, t)| {
+                    let mut mapping = json!({"field-id": field_type.get(id)?, "names": [name]});
+                    if let Some(fields) = field_type.get(t).and_then(nested) {
+                        mapping["fields"] = fields;
+                    }
+                    Some(mapping)
+                })
+                .collect(),
+        ))
+    }
+
+    Value::Array(
+        schema
+            .get("fields")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(|field| {
+                let mut mapping =
+                    json!({"field-id": field.get("id")?, "names": [field.get("name")?]});
+                if let Some(fields) = field.get("type").and_then(nested) {
+                    mapping["fields"] = fields;
+                }
+                Some(mapping)
+            })
+            .collect(),
+    )
+}
@@ -16,14 +16,17 @@ use tokio::{fs::OpenOptions, io::AsyncWriteExt};
 use tracing::info;
 use uuid::Uuid;
 
-use crate::filesystem::{sink::two_phase_committer::TwoPhaseCommitter, FileSettings};
+use crate::filesystem::FileSettings;
 use anyhow::{bail, Result};
+use arroyo_operator::two_phase_committer::{TwoPhaseCommitter, TwoPhaseCommitterOperator};
 
 use super::{
-    add_suffix_prefix, delta, get_partitioner_from_file_settings, parquet::batches_by_partition,
-    two_phase_committer::TwoPhaseCommitterOperator, CommitState, CommitStyle, FileNaming,
-    FileSystemTable, FilenameStrategy, FinishedFile, MultiPartWriterStats, RollingPolicy,
-    TableType,
+    add_suffix_prefix,
+    compaction::{CompactionState, Compactor},
+    delta, get_partitioner_from_file_settings, iceberg, manifest,
+    parquet::{batches_by_partition, writer_properties_from_table},
+    CommitState, CommitStyle, FileNaming, FileSystemTable, FilenameStrategy, FinishedFile,
+    MultiPartWriterStats, RollingPolicy, TableType,
 };
 
 pub struct LocalFileSystemWriter<V: LocalWriter> {
@@ -42,6 +45,7 @@ pub struct LocalFileSystemWriter<V: LocalWriter> {
     schema: Option<ArroyoSchemaRef>,
     commit_state: CommitState,
     filenaming: FileNaming,
+    compactor: Option<Compactor>,
 }
 
 impl<V: LocalWriter> LocalFileSystemWriter<V> {
@@ -53,11 +57,6 @@ impl<V: LocalWriter> LocalFileSystemWriter<V> {
         if final_dir.starts_with("file://") {
             final_dir = final_dir.trim_start_matches("file://").to_string();
         }
-        // TODO: explore configuration options here
-        let tmp_dir = format!("{}/__in_progress", final_dir);
-        // make sure final_dir and tmp_dir exists
-        create_dir_all(&tmp_dir).unwrap();
-
         let TableType::Sink {
             ref file_settings, ..
         } = table_properties.table_type
@@ -66,9 +65,20 @@ impl<V: LocalWriter> LocalFileSystemWriter<V> {
         };
         let commit_state = match file_settings.as_ref().unwrap().commit_style.unwrap() {
             CommitStyle::DeltaLake => CommitState::DeltaLake { last_version: -1 },
+            CommitStyle::Iceberg => CommitState::Iceberg,
+            CommitStyle::Manifest => CommitState::Manifest,
             CommitStyle::Direct => CommitState::VanillaParquet,
         };
 
+        // TODO: explore configuration options here
+        let tmp_dir = if commit_state == CommitState::Manifest {
+            format!("{}/{}", final_dir, manifest::STAGING_DIR)
+        } else {
+            format!("{}/__in_progress", final_dir)
+        };
+        // make sure final_dir and tmp_dir exists
+        create_dir_all(&tmp_dir).unwrap();
+
         let mut filenaming = file_settings
             .clone()
             .unwrap()
@@ -83,6 +93,17 @@ impl<V: LocalWriter> LocalFileSystemWriter<V> {
             filenaming.suffix = Some(V::file_suffix().to_string());
         }
 
+        let compactor = match (
+            &commit_state,
+            file_settings.as_ref().unwrap().compaction_target_size,
+        ) {
+            (CommitState::VanillaParquet, Some(target_size)) => Some(Compactor::new(
+                target_size as usize,
+                writer_properties_from_table(&table_properties),
+            )),
+            _ => None,
+        };
+
         let writer = Self {
             writers: HashMap::new(),
             tmp_dir,
@@ -98,6 +119,7 @@ impl<V: LocalWriter> LocalFileSystemWriter<V> {
             table_properties,
             commit_state,
             filenaming,
+            compactor,
         };
         TwoPhaseCommitterOperator::new(writer)
     }
@@ -117,7 +139,7 @@ impl<V: LocalWriter> LocalFileSystemWriter<V> {
             self.partitioner = get_partitioner_from_file_settings(
                 self.file_settings.clone(),
                 self.schema.as_ref().unwrap().clone(),
-            );
+            )?;
         }
         Ok(())
     }
@@ -165,6 +187,42 @@ impl<V: LocalWriter> LocalFileSystemWriter<V> {
         }
         self.writers.get_mut(partition).unwrap()
     }
+
+    /// Moves the files from the staging directory into place and publishes them in a manifest.
+    /// Files that a previous attempt at this commit already moved are included in the manifest.
+    async fn publish_manifest(
+        &self,
+        task_info: &TaskInfo,
+        epoch: u32,
+        pre_commit: Vec<FilePreCommit>,
+    ) -> Result<()> {
+        let mut finished_files = vec![];
+        for FilePreCommit {
+            tmp_file,
+            destination,
+        } in pre_commit
+        {
+            let size = match tokio::fs::metadata(&tmp_file).await {
+                Ok(metadata) => metadata.len(),
+                Err(_) => tokio::fs::metadata(&destination).await?.len(),
+            };
+            finished_files.push(FinishedFile {
+                filename: object_store::path::Path::parse(&tmp_file)?.to_string(),
+                partition: None,
+                size: size as usize,
+            });
+        }
+
+        let storage_provider = StorageProvider::for_url("/").await?;
+        manifest::publish_files(
+            &finished_files,
+            &object_store::path::Path::parse(&self.final_dir)?,
+            &storage_provider,
+            epoch,
+            task_info.task_index,
+        )
+        .await
+    }
 }
 
 pub trait LocalWriter: Send + 'static {
@@ -188,6 +246,7 @@ pub trait LocalWriter: Send + 'static {
 pub struct LocalFileDataRecovery {
     next_file_index: usize,
     current_files: Vec<CurrentFileRecovery>,
+    compaction: CompactionState,
 }
 
 #[derive(Debug, Clone, Decode, Encode, PartialEq, PartialOrd)]
@@ -218,11 +277,24 @@ impl<V: LocalWriter + Send + 'static> TwoPhaseCommitter for LocalFileSystemWrite
         ctx: &mut ArrowContext,
         data_recovery: Vec<Self::DataRecovery>,
     ) -> Result<()> {
+        if let CommitState::Iceberg = self.commit_state {
+            let Some(settings) = &self.file_settings.iceberg else {
+                bail!("iceberg commit style requires iceberg settings");
+            };
+            iceberg::check_iceberg_table(
+                settings,
+                &ctx.in_schemas.first().unwrap().schema_without_timestamp(),
+            )
+            .await?;
+        }
+
         let mut max_file_index = 0;
         let mut recovered_files = Vec::new();
+        let mut compaction_state = CompactionState::default();
         for LocalFileDataRecovery {
             next_file_index,
             current_files,
+            compaction,
         } in data_recovery
         {
             max_file_index = max_file_index.max(next_file_index);
@@ -232,6 +304,7 @@ impl<V: LocalWriter + Send + 'static> TwoPhaseCommitter for LocalFileSystemWrite
             if ctx.task_info.task_index > 0 {
                 continue;
             }
+            compaction_state.merge(compaction);
             for CurrentFileRecovery {
                 tmp_file,
                 bytes_written,
@@ -258,10 +331,16 @@ impl<V: LocalWriter + Send + 'static> TwoPhaseCommitter for LocalFileSystemWrite
         self.subtask_id = ctx.task_info.task_index;
         self.finished_files = recovered_files;
         self.next_file_index = max_file_index;
+        if let Some(compactor) = &mut self.compactor {
+            compactor.restore(compaction_state);
+            compactor
+                .run_saved(&StorageProvider::for_url("/").await?)
+                .await?;
+        }
         Ok(())
     }
 
-    async fn insert_batch(&mut self, batch: RecordBatch) -> Result<()> {
+    async fn insert_batch(&mut self, batch: RecordBatch, _ctx: &mut ArrowContext) -> Result<()> {
         if self.schema.is_none() {
             self.init_schema_and_partitioner(&batch)?;
         }
@@ -279,12 +358,16 @@ impl<V: LocalWriter + Send + 'static> TwoPhaseCommitter for LocalFileSystemWrite
 
     async fn commit(
         &mut self,
-        _task_info: &TaskInfo,
+        task_info: &TaskInfo,
+        epoch: u32,
         pre_commit: Vec<Self::PreCommit>,
     ) -> Result<()> {
         if pre_commit.is_empty() {
             return Ok(());
         }
+        if let CommitState::Manifest = self.commit_state {
+            return self.publish_manifest(task_info, epoch, pre_commit).await;
+        }
         let mut finished_files = vec![];
         for FilePreCommit {
             tmp_file,
@@ -327,12 +410,34 @@ impl<V: LocalWriter + Send + 'static> TwoPhaseCommitter for LocalFileSystemWrite
                 };
             }
         }
+        if let CommitState::Iceberg = self.commit_state {
+            let Some(settings) = &self.file_settings.iceberg else {
+                bail!("iceberg commit style requires iceberg settings");
+            };
+            let storage_provider = StorageProvider::for_url("/").await?;
+            iceberg::commit_files_to_iceberg(
+                &finished_files,
+                &object_store::path::Path::parse(&self.final_dir)?,
+                &format!("file://{}", self.final_dir),
+                &storage_provider,
+                &HashMap::new(),
+                settings,
+            )
+            .await?;
+        }
+        if let Some(compactor) = &mut self.compactor {
+            // merges planned before this checkpoint are now recorded in its state
+            compactor
+                .run_saved(&StorageProvider::for_url("/").await?)
+                .await?;
+            compactor.add_files(&finished_files);
+        }
         Ok(())
     }
 
     async fn checkpoint(
         &mut self,
-        _task_info: &TaskInfo,
+        _ctx: &mut ArrowContext,
         watermark: Option<SystemTime>,
         stopping: bool,
     ) -> Result<(Self::DataRecovery, HashMap<String, Self::PreCommit>)> {
@@ -360,6 +465,11 @@ impl<V: LocalWriter + Send + 'static> TwoPhaseCommitter for LocalFileSystemWrite
                 .iter_mut()
                 .filter_map(|(_partition, writer)| writer.checkpoint().transpose())
                 .collect::<Result<_>>()?,
+            compaction: self
+                .compactor
+                .as_mut()
+                .map(|compactor| compactor.checkpoint())
+                .unwrap_or_default(),
         };
         Ok((data_recovery, pre_commits))
     }
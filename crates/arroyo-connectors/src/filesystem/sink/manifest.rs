@@ -0,0 +1,193 @@
+use super::FinishedFile;
+use anyhow::{anyhow, bail, Result};
+use arroyo_storage::StorageProvider;
+use object_store::path::Path;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+/// Prefix, relative to the table path, that files are written under until their checkpoint
+/// commits
+pub(crate) const STAGING_DIR: &str = "_staging";
+
+/// Prefix, relative to the table path, that the manifests of committed checkpoints are
+/// published under
+pub(crate) const MANIFEST_DIR: &str = "_manifests";
+
+/// Lists the files published by a single commit. Paths are relative to the table path.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub(crate) struct Manifest {
+    pub epoch: u32,
+    pub subtask: usize,
+    pub files: Vec<ManifestEntry>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub(crate) struct ManifestEntry {
+    pub path: String,
+    pub partition: Option<String>,
+    pub size: usize,
+}
+
+/// Returns the path under which files for `table_path` are staged
+pub(crate) fn staging_path(table_path: &Path) -> Path {
+    table_path.child(STAGING_DIR)
+}
+
+/// Returns the path that a file staged at `staged` is published to
+pub(crate) fn published_path(table_path: &Path, staged: &Path) -> Result<Path> {
+    let relative = staged
+        .prefix_match(&staging_path(table_path))
+        .ok_or_else(|| anyhow!("{} is not a staged file of {}", staged, table_path))?;
+
+    Ok(table_path.parts().chain(relative).collect())
+}
+
+/// Returns true if `path` is one of the staged files or manifests of a table, which readers of
+/// the table should skip
+pub(crate) fn is_internal_path(path: &Path) -> bool {
+    path.parts()
+        .any(|p| p.as_ref() == STAGING_DIR || p.as_ref() == MANIFEST_DIR)
+}
+
+fn manifest_name(epoch: u32, subtask: usize) -> String {
+    format!("{:>010}-{:>03}.json", epoch, subtask)
+}
+
+/// Atomically publishes the files committed by `subtask` in `epoch`. The manifest is first
+/// written to the staging prefix, then each file is moved from the staging prefix to its final
+/// location, and finally the manifest is moved under `_manifests/`, which is the point at which
+/// the files become visible to readers that follow the manifests.
+///
+/// Every step is idempotent, so if a failure interrupts the commit it will be completed when the
+/// commit is retried after recovery, without duplicating any files.
+pub(crate) async fn publish_files(
+    finished_files: &[FinishedFile],
+    table_path: &Path,
+    storage_provider: &StorageProvider,
+    epoch: u32,
+    subtask: usize,
+) -> Result<()> {
+    if finished_files.is_empty() {
+        return Ok(());
+    }
+
+    let name = manifest_name(epoch, subtask);
+    let published_manifest = table_path.child(MANIFEST_DIR).child(name.as_str());
+    if storage_provider.exists(published_manifest.clone()).await? {
+        info!(
+            "manifest {} has already been published, skipping commit",
+            published_manifest
+        );
+        return Ok(());
+    }
+
+    let mut moves = vec![];
+    let mut files = vec![];
+    for file in finished_files {
+        let staged = Path::parse(&file.filename)?;
+        let published = published_path(table_path, &staged)?;
+        let relative: Path = published
+            .prefix_match(table_path)
+            .expect("published path is under the table path")
+            .collect();
+
+        files.push(ManifestEntry {
+            path: relative.to_string(),
+            partition: file.partition.clone(),
+            size: file.size,
+        });
+        moves.push((staged, published));
+    }
+
+    let manifest = Manifest {
+        epoch,
+        subtask,
+        files,
+    };
+
+    let staged_manifest = staging_path(table_path)
+        .child(MANIFEST_DIR)
+        .child(name.as_str());
+    storage_provider
+        .put(
+            staged_manifest.clone(),
+            serde_json::to_vec_pretty(&manifest)?,
+        )
+        .await?;
+
+    for (staged, published) in moves {
+        move_file(storage_provider, &staged, &published).await?;
+    }
+
+    storage_provider
+        .rename(staged_manifest, published_manifest.clone())
+        .await?;
+
+    info!(
+        "published {} files in manifest {}",
+        manifest.files.len(),
+        published_manifest
+    );
+
+    Ok(())
+}
+
+async fn move_file(storage_provider: &StorageProvider, from: &Path, to: &Path) -> Result<()> {
+    if !storage_provider.exists(from.clone()).await? {
+        // a previous attempt at this commit already moved the file
+        if storage_provider.exists(to.clone()).await? {
+            return Ok(());
+        }
+        bail!("staged file {} does not exist", from);
+    }
+
+    if let Err(e) = storage_provider.rename(from.clone(), to.clone()).await {
+        // renames that are implemented as a copy and a delete may fail after the copy
+        if storage_provider.exists(to.clone()).await? {
+            warn!("failed to remove staged file {}: {}", from, e);
+            storage_provider.delete_if_present(from.clone()).await?;
+        } else {
+            return Err(e.into());
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_published_path() {
+        let table = Path::parse("bucket/table").unwrap();
+
+        assert_eq!(
+            published_path(
+                &table,
+                &Path::parse("bucket/table/_staging/a=1/00001-000.parquet").unwrap()
+            )
+            .unwrap(),
+            Path::parse("bucket/table/a=1/00001-000.parquet").unwrap()
+        );
+
+        assert!(published_path(
+            &table,
+            &Path::parse("bucket/table/00001-000.parquet").unwrap()
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_internal_paths() {
+        assert!(is_internal_path(
+            &Path::parse("table/_staging/00001-000.parquet").unwrap()
+        ));
+        assert!(is_internal_path(
+            &Path::parse("table/_manifests/0000000001-000.json").unwrap()
+        ));
+        assert!(!is_internal_path(
+            &Path::parse("table/a=1/00001-000.parquet").unwrap()
+        ));
+    }
+}
@@ -14,7 +14,7 @@ use ::arrow::{
     record_batch::RecordBatch,
     util::display::{ArrayFormatter, FormatOptions},
 };
-use anyhow::{bail, Result};
+use anyhow::{anyhow, bail, Result};
 use arroyo_operator::context::ArrowContext;
 use arroyo_rpc::{df::ArroyoSchemaRef, formats::Format, OperatorConfig, TIMESTAMP_FIELD};
 use arroyo_storage::StorageProvider;
@@ -23,13 +23,14 @@ use bincode::{Decode, Encode};
 use chrono::{DateTime, Utc};
 use datafusion::prelude::concat;
 use datafusion::{
-    common::{Column, Result as DFResult},
+    common::{Column, DFSchema, Result as DFResult},
     execution::{
         context::{SessionConfig, SessionState},
         runtime_env::RuntimeEnv,
     },
     logical_expr::{
-        expr::ScalarFunction, Expr, ScalarUDF, ScalarUDFImpl, Signature, TypeSignature, Volatility,
+        expr::ScalarFunction, Expr, ExprSchemable, ScalarUDF, ScalarUDFImpl, Signature,
+        TypeSignature, Volatility,
     },
     physical_plan::{ColumnarValue, PhysicalExpr},
     physical_planner::{DefaultPhysicalPlanner, PhysicalPlanner},
@@ -44,25 +45,35 @@ use uuid::Uuid;
 
 use arroyo_types::*;
 pub mod arrow;
+pub mod avro;
+mod compaction;
+pub mod csv;
 mod delta;
+pub(crate) mod iceberg;
 pub mod json;
 pub mod local;
+pub(crate) mod manifest;
 pub mod parquet;
-mod two_phase_committer;
 
 use self::{
+    arrow::{ArrowIpcLocalWriter, ArrowIpcWriter},
+    avro::{AvroLocalWriter, AvroWriter},
+    compaction::{CompactionState, Compactor},
+    csv::{CsvLocalWriter, CsvWriter},
     json::{JsonLocalWriter, JsonWriter},
     local::LocalFileSystemWriter,
     parquet::{
-        batches_by_partition, representitive_timestamp, ParquetLocalWriter,
-        RecordBatchBufferingWriter,
+        batches_by_partition, representitive_timestamp, writer_properties_from_table,
+        ParquetLocalWriter, RecordBatchBufferingWriter,
     },
 };
 
 use crate::filesystem::{
     CommitStyle, FileNaming, FileSettings, FileSystemTable, FilenameStrategy, TableType,
 };
-use two_phase_committer::{CommitStrategy, TwoPhaseCommitter, TwoPhaseCommitterOperator};
+use arroyo_operator::two_phase_committer::{
+    CommitStrategy, TwoPhaseCommitter, TwoPhaseCommitterOperator,
+};
 
 pub struct FileSystemSink<R: MultiPartWriter + Send + 'static> {
     sender: Option<Sender<FileSystemMessages>>,
@@ -82,6 +93,18 @@ pub type LocalParquetFileSystemSink = LocalFileSystemWriter<ParquetLocalWriter>;
 
 pub type LocalJsonFileSystemSink = LocalFileSystemWriter<JsonLocalWriter>;
 
+pub type ArrowIpcFileSystemSink = FileSystemSink<BatchMultipartWriter<ArrowIpcWriter>>;
+
+pub type LocalArrowIpcFileSystemSink = LocalFileSystemWriter<ArrowIpcLocalWriter>;
+
+pub type CsvFileSystemSink = FileSystemSink<BatchMultipartWriter<CsvWriter>>;
+
+pub type LocalCsvFileSystemSink = LocalFileSystemWriter<CsvLocalWriter>;
+
+pub type AvroFileSystemSink = FileSystemSink<BatchMultipartWriter<AvroWriter>>;
+
+pub type LocalAvroFileSystemSink = LocalFileSystemWriter<AvroLocalWriter>;
+
 impl<R: MultiPartWriter + Send + 'static> FileSystemSink<R> {
     pub fn create_and_start(
         table: FileSystemTable,
@@ -92,7 +115,10 @@ impl<R: MultiPartWriter + Send + 'static> FileSystemSink<R> {
         };
         let commit_strategy = match file_settings.as_ref().unwrap().commit_style.unwrap() {
             CommitStyle::Direct => CommitStrategy::PerSubtask,
-            CommitStyle::DeltaLake => CommitStrategy::PerOperator,
+            // a single subtask commits each checkpoint, so that it is published by one manifest
+            CommitStyle::DeltaLake | CommitStyle::Iceberg | CommitStyle::Manifest => {
+                CommitStrategy::PerOperator
+            }
         };
 
         TwoPhaseCommitterOperator::new(Self {
@@ -131,7 +157,7 @@ impl<R: MultiPartWriter + Send + 'static> FileSystemSink<R> {
         let partition_func = get_partitioner_from_file_settings(
             file_settings.as_ref().unwrap().clone(),
             schema.clone(),
-        );
+        )?;
         self.partitioner = partition_func;
         let table = self.table.clone();
         let format = self.format.clone();
@@ -159,53 +185,40 @@ impl<R: MultiPartWriter + Send + 'static> FileSystemSink<R> {
 fn get_partitioner_from_file_settings(
     file_settings: FileSettings,
     schema: ArroyoSchemaRef,
-) -> Option<Arc<dyn PhysicalExpr>> {
-    let partitions = file_settings.partitioning?;
-    match (
-        partitions.time_partition_pattern,
-        partitions.partition_fields.is_empty(),
-    ) {
-        (None, false) => {
-            Some(partition_string_for_fields(schema, &partitions.partition_fields).unwrap())
-        }
-        (None, true) => None,
-        (Some(pattern), false) => Some(
-            partition_string_for_fields_and_time(schema, &partitions.partition_fields, pattern)
-                .unwrap(),
-        ),
-        (Some(pattern), true) => Some(partition_string_for_time(schema, pattern).unwrap()),
+) -> Result<Option<Arc<dyn PhysicalExpr>>> {
+    let Some(partitions) = file_settings.partitioning else {
+        return Ok(None);
+    };
+
+    // the partition is made of the formatted time, the value of the partition expression, and
+    // the partition fields, in that order
+    let mut parts = vec![];
+    if let Some(pattern) = partitions.time_partition_pattern {
+        parts.push(timestamp_logical_expression(pattern)?);
+    }
+    if let Some(expression) = &partitions.partition_expression {
+        parts.push(sql_logical_expression(schema.clone(), expression)?);
+    }
+    if !partitions.partition_fields.is_empty() {
+        parts.push(field_logical_expression(
+            schema.clone(),
+            &partitions.partition_fields,
+        )?);
     }
-}
 
-fn partition_string_for_fields(
-    schema: ArroyoSchemaRef,
-    partition_fields: &[String],
-) -> Result<Arc<dyn PhysicalExpr>> {
-    let function = field_logical_expression(schema.clone(), partition_fields)?;
-    compile_expression(&function, schema)
-}
+    if parts.is_empty() {
+        return Ok(None);
+    }
 
-fn partition_string_for_time(
-    schema: ArroyoSchemaRef,
-    time_partition_pattern: String,
-) -> Result<Arc<dyn PhysicalExpr>> {
-    let function = timestamp_logical_expression(time_partition_pattern)?;
-    compile_expression(&function, schema)
-}
+    let mut function = vec![];
+    for (i, part) in parts.into_iter().enumerate() {
+        if i > 0 {
+            function.push(Expr::Literal(ScalarValue::Utf8(Some("/".to_string()))));
+        }
+        function.push(part);
+    }
 
-fn partition_string_for_fields_and_time(
-    schema: ArroyoSchemaRef,
-    partition_fields: &[String],
-    time_partition_pattern: String,
-) -> Result<Arc<dyn PhysicalExpr>> {
-    let field_function = field_logical_expression(schema.clone(), partition_fields)?;
-    let time_function = timestamp_logical_expression(time_partition_pattern)?;
-    let function = concat(vec![
-        time_function,
-        Expr::Literal(ScalarValue::Utf8(Some("/".to_string()))),
-        field_function,
-    ]);
-    compile_expression(&function, schema)
+    Ok(Some(compile_expression(&concat(function), schema)?))
 }
 
 fn compile_expression(expr: &Expr, schema: ArroyoSchemaRef) -> Result<Arc<dyn PhysicalExpr>> {
@@ -253,6 +266,26 @@ fn field_logical_expression(schema: ArroyoSchemaRef, partition_fields: &[String]
     Ok(function)
 }
 
+/// Parses a SQL expression over the columns of the table, like `date_trunc('hour', _timestamp)`,
+/// whose value (as a string) is used as the partition of each row
+fn sql_logical_expression(schema: ArroyoSchemaRef, expression: &str) -> Result<Expr> {
+    let session_state =
+        SessionState::new_with_config_rt(SessionConfig::new(), Arc::new(RuntimeEnv::default()));
+    let df_schema = DFSchema::try_from(schema.schema.as_ref().clone())?;
+    let expr = session_state
+        .create_logical_expr(expression, &df_schema)
+        .map_err(|e| anyhow!("invalid partition expression '{}': {}", expression, e))?;
+
+    let (data_type, _) = expr.data_type_and_nullable(&df_schema)?;
+    Ok(match data_type {
+        DataType::Utf8 => expr,
+        _ => Expr::Cast(datafusion::logical_expr::Cast {
+            expr: Box::new(expr),
+            data_type: DataType::Utf8,
+        }),
+    })
+}
+
 fn timestamp_logical_expression(time_partition_pattern: String) -> Result<Expr> {
     let udf = TimestampFormattingUDF::new(time_partition_pattern);
     let scalar_function = ScalarFunction::new_udf(
@@ -273,18 +306,23 @@ enum FileSystemMessages {
         max_file_index: usize,
         subtask_id: usize,
         recovered_files: Vec<InProgressFileCheckpoint>,
+        compaction: CompactionState,
     },
     Checkpoint {
         subtask_id: usize,
         watermark: Option<SystemTime>,
         then_stop: bool,
     },
-    FilesToFinish(Vec<FileToFinish>),
+    FilesToFinish {
+        epoch: u32,
+        files: Vec<FileToFinish>,
+    },
 }
 
 #[derive(Debug)]
 enum CheckpointData {
     InProgressFileCheckpoint(InProgressFileCheckpoint),
+    Compaction(CompactionState),
     Finished {
         max_file_index: usize,
         delta_version: i64,
@@ -438,11 +476,14 @@ struct AsyncMultipartFileSystemWriter<R: MultiPartWriter> {
     file_naming: FileNaming,
     format: Option<Format>,
     schema: ArroyoSchemaRef,
+    compactor: Option<Compactor>,
 }
 
 #[derive(Debug, Clone, Encode, Decode, PartialEq, Eq)]
 pub enum CommitState {
     DeltaLake { last_version: i64 },
+    Iceberg,
+    Manifest,
     VanillaParquet,
 }
 
@@ -613,7 +654,7 @@ pub struct FileToFinish {
     size: usize,
 }
 
-#[derive(Debug, Clone, Encode, Decode, PartialEq, Eq)]
+#[derive(Debug, Clone, Encode, Decode, PartialEq, Eq, PartialOrd)]
 pub struct FinishedFile {
     filename: String,
     partition: Option<String>,
@@ -724,6 +765,8 @@ where
 
         let commit_state = match file_settings.commit_style.unwrap() {
             CommitStyle::DeltaLake => CommitState::DeltaLake { last_version: -1 },
+            CommitStyle::Iceberg => CommitState::Iceberg,
+            CommitStyle::Manifest => CommitState::Manifest,
             CommitStyle::Direct => CommitState::VanillaParquet,
         };
         let mut file_naming = file_settings.file_naming.clone().unwrap_or(FileNaming {
@@ -734,6 +777,13 @@ where
         if file_naming.suffix.is_none() {
             file_naming.suffix = Some(R::suffix());
         }
+        let compactor = match (&commit_state, file_settings.compaction_target_size) {
+            (CommitState::VanillaParquet, Some(target_size)) => Some(Compactor::new(
+                target_size as usize,
+                writer_properties_from_table(&writer_properties),
+            )),
+            _ => None,
+        };
 
         Self {
             path,
@@ -753,6 +803,7 @@ where
             file_naming,
             format,
             schema,
+            compactor,
         }
     }
 
@@ -771,9 +822,13 @@ where
                                 self.futures.push(future);
                             }
                         },
-                        FileSystemMessages::Init {max_file_index, subtask_id, recovered_files } => {
+                        FileSystemMessages::Init {max_file_index, subtask_id, recovered_files, compaction } => {
                             self.max_file_index = max_file_index;
                             self.subtask_id = subtask_id;
+                            if let Some(compactor) = &mut self.compactor {
+                                compactor.restore(compaction);
+                                compactor.run_saved(&self.object_store).await?;
+                            }
                             info!("recovered files: {:?}", recovered_files);
                             for recovered_file in recovered_files {
                                 if let Some(file_to_finish) = from_checkpoint(
@@ -790,12 +845,15 @@ where
                                 self.stop().await?;
                             }
                             self.take_checkpoint(subtask_id).await?;
+                            if let Some(compactor) = &mut self.compactor {
+                                self.checkpoint_sender.send(CheckpointData::Compaction(compactor.checkpoint())).await?;
+                            }
                             let delta_version = self.delta_version();
                             self.checkpoint_sender.send({CheckpointData::Finished {  max_file_index: self.max_file_index,
                             delta_version}}).await?;
                         },
-                        FileSystemMessages::FilesToFinish(files_to_finish) =>{
-                            self.finish_files(files_to_finish).await?;
+                        FileSystemMessages::FilesToFinish { epoch, files } =>{
+                            self.finish_files(epoch, files).await?;
                         }
                     }
                 }
@@ -869,9 +927,15 @@ where
             self.file_naming.suffix.as_ref().unwrap(),
         );
 
+        // with the manifest commit style, files are only moved under the table path once their
+        // checkpoint commits
+        let base_path = match self.commit_state {
+            CommitState::Manifest => manifest::staging_path(&self.path),
+            _ => self.path.clone(),
+        };
         let path = match partition {
-            Some(sub_bucket) => format!("{}/{}/{}", self.path, sub_bucket, filename),
-            None => format!("{}/{}", self.path, filename),
+            Some(sub_bucket) => format!("{}/{}/{}", base_path, sub_bucket, filename),
+            None => format!("{}/{}", base_path, filename),
         };
         R::new(
             self.object_store.clone(),
@@ -922,13 +986,23 @@ where
         }
     }
 
-    async fn finish_files(&mut self, files_to_finish: Vec<FileToFinish>) -> Result<()> {
+    async fn finish_files(&mut self, epoch: u32, files_to_finish: Vec<FileToFinish>) -> Result<()> {
         let mut finished_files: Vec<FinishedFile> = vec![];
         for file_to_finish in files_to_finish {
             if let Some(file) = self.finish_file(file_to_finish).await? {
                 finished_files.push(file);
             }
         }
+        if let CommitState::Manifest = self.commit_state {
+            manifest::publish_files(
+                &finished_files,
+                &self.path,
+                &self.object_store,
+                epoch,
+                self.subtask_id,
+            )
+            .await?;
+        }
         if let CommitState::DeltaLake { last_version } = self.commit_state {
             if let Some(new_version) = delta::commit_files_to_delta(
                 &finished_files,
@@ -944,6 +1018,35 @@ where
                 };
             }
         }
+        if let CommitState::Iceberg = self.commit_state {
+            let TableType::Sink {
+                write_path,
+                file_settings,
+                storage_options,
+                ..
+            } = &self.properties.table_type
+            else {
+                unreachable!("AsyncMultipartFileSystemWriter can only be used as a sink");
+            };
+            let settings = file_settings
+                .as_ref()
+                .and_then(|s| s.iceberg.as_ref())
+                .ok_or_else(|| anyhow::anyhow!("iceberg commit style requires iceberg settings"))?;
+            iceberg::commit_files_to_iceberg(
+                &finished_files,
+                &self.path,
+                write_path,
+                &self.object_store,
+                storage_options,
+                settings,
+            )
+            .await?;
+        }
+        if let Some(compactor) = &mut self.compactor {
+            // merges planned before this checkpoint are now recorded in its state
+            compactor.run_saved(&self.object_store).await?;
+            compactor.add_files(&finished_files);
+        }
         let finished_message = CheckpointData::Finished {
             max_file_index: self.max_file_index,
             delta_version: self.delta_version(),
@@ -955,7 +1058,7 @@ where
     fn delta_version(&mut self) -> i64 {
         match self.commit_state {
             CommitState::DeltaLake { last_version } => last_version,
-            CommitState::VanillaParquet => 0,
+            CommitState::Iceberg | CommitState::Manifest | CommitState::VanillaParquet => 0,
         }
     }
 
@@ -972,13 +1075,27 @@ where
             return Ok(None);
         }
 
+        let location = Path::parse(&filename)?;
+        if let CommitState::Manifest = self.commit_state {
+            // a previous attempt at this commit may have already published the staged file
+            let published = manifest::published_path(&self.path, &location)?;
+            if !self.object_store.exists(location.clone()).await?
+                && self.object_store.exists(published).await?
+            {
+                return Ok(Some(FinishedFile {
+                    filename,
+                    partition,
+                    size,
+                }));
+            }
+        }
+
         let parts: Vec<_> = completed_parts
             .into_iter()
             .map(|content_id| PartId {
                 content_id: content_id.clone(),
             })
             .collect();
-        let location = Path::parse(&filename)?;
         match self
             .object_store
             .close_multipart(&location, &multi_part_upload_id, parts)
@@ -1535,6 +1652,7 @@ pub struct FileSystemDataRecovery {
     next_file_index: usize,
     active_files: Vec<InProgressFileCheckpoint>,
     delta_version: i64,
+    compaction: CompactionState,
 }
 
 #[async_trait]
@@ -1556,9 +1674,26 @@ impl<R: MultiPartWriter + Send + 'static> TwoPhaseCommitter for FileSystemSink<R
         ctx: &mut ArrowContext,
         data_recovery: Vec<Self::DataRecovery>,
     ) -> Result<()> {
+        if let TableType::Sink {
+            file_settings:
+                Some(FileSettings {
+                    commit_style: Some(CommitStyle::Iceberg),
+                    iceberg: Some(settings),
+                    ..
+                }),
+            ..
+        } = &self.table.table_type
+        {
+            iceberg::check_iceberg_table(
+                settings,
+                &ctx.in_schemas.first().unwrap().schema_without_timestamp(),
+            )
+            .await?;
+        }
         self.start(Arc::new(ctx.in_schemas.first().unwrap().clone()))?;
         let mut max_file_index = 0;
         let mut recovered_files = Vec::new();
+        let mut compaction = CompactionState::default();
         for file_system_data_recovery in data_recovery {
             max_file_index = max_file_index.max(file_system_data_recovery.next_file_index);
             // task 0 is responsible for recovering all files.
@@ -1566,6 +1701,7 @@ impl<R: MultiPartWriter + Send + 'static> TwoPhaseCommitter for FileSystemSink<R
             // Recovering should be reasonably fast since it is just finishing in-flight uploads.
             if ctx.task_info.task_index == 0 {
                 recovered_files.extend(file_system_data_recovery.active_files.into_iter());
+                compaction.merge(file_system_data_recovery.compaction);
             }
         }
         self.sender
@@ -1575,12 +1711,13 @@ impl<R: MultiPartWriter + Send + 'static> TwoPhaseCommitter for FileSystemSink<R
                 max_file_index,
                 subtask_id: ctx.task_info.task_index,
                 recovered_files,
+                compaction,
             })
             .await?;
         Ok(())
     }
 
-    async fn insert_batch(&mut self, record: RecordBatch) -> Result<()> {
+    async fn insert_batch(&mut self, record: RecordBatch, _ctx: &mut ArrowContext) -> Result<()> {
         // TODO: implement partitioning
         match &self.partitioner {
             None => {
@@ -1612,12 +1749,16 @@ impl<R: MultiPartWriter + Send + 'static> TwoPhaseCommitter for FileSystemSink<R
     async fn commit(
         &mut self,
         _task_info: &TaskInfo,
+        epoch: u32,
         pre_commit: Vec<Self::PreCommit>,
     ) -> Result<()> {
         self.sender
             .as_ref()
             .unwrap()
-            .send(FileSystemMessages::FilesToFinish(pre_commit))
+            .send(FileSystemMessages::FilesToFinish {
+                epoch,
+                files: pre_commit,
+            })
             .await?;
         // loop over checkpoint receiver until finished received
         if let Some(checkpoint_message) = self.checkpoint_receiver.as_mut().unwrap().recv().await {
@@ -1636,7 +1777,7 @@ impl<R: MultiPartWriter + Send + 'static> TwoPhaseCommitter for FileSystemSink<R
 
     async fn checkpoint(
         &mut self,
-        task_info: &TaskInfo,
+        ctx: &mut ArrowContext,
         watermark: Option<SystemTime>,
         stopping: bool,
     ) -> Result<(Self::DataRecovery, HashMap<String, Self::PreCommit>)> {
@@ -1644,13 +1785,14 @@ impl<R: MultiPartWriter + Send + 'static> TwoPhaseCommitter for FileSystemSink<R
             .as_ref()
             .unwrap()
             .send(FileSystemMessages::Checkpoint {
-                subtask_id: task_info.task_index,
+                subtask_id: ctx.task_info.task_index,
                 watermark,
                 then_stop: stopping,
             })
             .await?;
         let mut pre_commit_messages = HashMap::new();
         let mut active_files = Vec::new();
+        let mut compaction = CompactionState::default();
         while let Some(checkpoint_message) = self.checkpoint_receiver.as_mut().unwrap().recv().await
         {
             match checkpoint_message {
@@ -1663,10 +1805,14 @@ impl<R: MultiPartWriter + Send + 'static> TwoPhaseCommitter for FileSystemSink<R
                             next_file_index: max_file_index + 1,
                             active_files,
                             delta_version,
+                            compaction,
                         },
                         pre_commit_messages,
                     ))
                 }
+                CheckpointData::Compaction(state) => {
+                    compaction = state;
+                }
                 CheckpointData::InProgressFileCheckpoint(InProgressFileCheckpoint {
                     filename,
                     partition,
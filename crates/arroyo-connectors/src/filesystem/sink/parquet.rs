@@ -25,7 +25,7 @@ use super::{
     BatchBufferingWriter, FileSettings, FileSystemTable, MultiPartWriterStats, TableType,
 };
 
-fn writer_properties_from_table(table: &FileSystemTable) -> WriterProperties {
+pub(crate) fn writer_properties_from_table(table: &FileSystemTable) -> WriterProperties {
     let mut parquet_writer_options = WriterProperties::builder();
     if let TableType::Sink {
         format_settings:
@@ -58,12 +58,12 @@ fn writer_properties_from_table(table: &FileSystemTable) -> WriterProperties {
 /// A buffer with interior mutability shared by the [`ArrowWriter`] and
 /// [`AsyncArrowWriter`]. From Arrow. This lets us write data from the buffer to S3.
 #[derive(Clone)]
-struct SharedBuffer {
+pub(crate) struct SharedBuffer {
     /// The inner buffer for reading and writing
     ///
     /// The lock is used to obtain internal mutability, so no worry about the
     /// lock contention.
-    buffer: Arc<futures::lock::Mutex<Vec<u8>>>,
+    pub(crate) buffer: Arc<futures::lock::Mutex<Vec<u8>>>,
 }
 
 impl SharedBuffer {
@@ -2,12 +2,14 @@ use std::collections::hash_map::DefaultHasher;
 use std::collections::HashMap;
 use std::future::ready;
 use std::hash::{Hash, Hasher};
-use std::time::SystemTime;
+use std::io::Cursor;
+use std::time::{Duration, SystemTime};
 
 use anyhow::Result;
 use arrow::array::RecordBatch;
 
 use arrow::datatypes::SchemaRef;
+use arrow::ipc::reader::{FileReader, StreamReader};
 use arroyo_state::global_table_config;
 use arroyo_state::tables::global_keyed_map::GlobalKeyedView;
 use async_compression::tokio::bufread::{GzipDecoder, ZstdDecoder};
@@ -20,12 +22,13 @@ use parquet::arrow::ParquetRecordBatchStreamBuilder;
 
 use arroyo_operator::context::ArrowContext;
 use regex::Regex;
-use tokio::io::{AsyncBufReadExt, AsyncRead, BufReader};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, BufReader};
 use tokio::select;
 use tokio_stream::wrappers::LinesStream;
 use tokio_stream::Stream;
 use tracing::info;
 
+use crate::filesystem::sink::manifest::is_internal_path;
 use crate::filesystem::{CompressionFormat, TableType};
 use arroyo_operator::operator::SourceOperator;
 use arroyo_operator::SourceFinishType;
@@ -84,12 +87,13 @@ impl FileSystemSourceFunc {
     }
 
     async fn run_int(&mut self, ctx: &mut ArrowContext) -> Result<SourceFinishType, UserError> {
-        let (storage_provider, regex_pattern) = match &self.table {
+        let (storage_provider, regex_pattern, watch_interval) = match &self.table {
             TableType::Source {
                 path,
                 storage_options,
                 compression_format: _,
                 regex_pattern,
+                watch_interval_ms,
             } => {
                 let storage_provider =
                     StorageProvider::for_url_with_options(path, storage_options.clone())
@@ -107,7 +111,10 @@ impl FileSystemSourceFunc {
                             err.to_string(),
                         )
                     })?;
-                (storage_provider, matcher)
+                let watch_interval = watch_interval_ms
+                    .filter(|ms| *ms > 0)
+                    .map(Duration::from_millis);
+                (storage_provider, matcher, watch_interval)
             }
             TableType::Sink { .. } => {
                 return Err(UserError::new(
@@ -124,29 +131,6 @@ impl FileSystemSourceFunc {
         let parallelism = ctx.task_info.parallelism;
         let task_index = ctx.task_info.task_index;
 
-        // TODO: sort by creation time
-        let mut file_paths = storage_provider
-            .list(regex_pattern.is_some())
-            .await
-            .map_err(|err| UserError::new("could not list files", err.to_string()))?
-            .filter(|path| {
-                let Ok(path) = path else {
-                    return ready(true);
-                };
-                // hash the path and modulo by the number of tasks
-                let mut hasher = DefaultHasher::new();
-                path.hash(&mut hasher);
-                if (hasher.finish() as usize) % parallelism != task_index {
-                    return ready(false);
-                }
-
-                if let Some(matcher) = &regex_pattern {
-                    ready(matcher.is_match(path.as_ref()))
-                } else {
-                    ready(true)
-                }
-            });
-
         let state: &mut GlobalKeyedView<String, (String, FileReadState)> = ctx
             .table_manager
             .get_global_keyed_state("a")
@@ -154,20 +138,72 @@ impl FileSystemSourceFunc {
             .expect("should have table");
         self.file_states = state.get_all().clone().into_values().collect();
 
-        while let Some(path) = file_paths.next().await {
-            let obj_key = path
-                .map_err(|err| UserError::new("could not get next path", err.to_string()))?
-                .to_string();
+        loop {
+            // TODO: sort by creation time
+            let mut file_paths = storage_provider
+                .list(regex_pattern.is_some())
+                .await
+                .map_err(|err| UserError::new("could not list files", err.to_string()))?
+                .filter(|path| {
+                    let Ok(path) = path else {
+                        return ready(true);
+                    };
+                    // skip the staged files and manifests of tables written with the manifest
+                    // commit style
+                    if is_internal_path(path) {
+                        return ready(false);
+                    }
+                    // hash the path and modulo by the number of tasks
+                    let mut hasher = DefaultHasher::new();
+                    path.hash(&mut hasher);
+                    if (hasher.finish() as usize) % parallelism != task_index {
+                        return ready(false);
+                    }
 
-            if let Some(FileReadState::Finished) = self.file_states.get(&obj_key) {
-                // already finished
-                continue;
+                    if let Some(matcher) = &regex_pattern {
+                        ready(matcher.is_match(path.as_ref()))
+                    } else {
+                        ready(true)
+                    }
+                });
+
+            while let Some(path) = file_paths.next().await {
+                let obj_key = path
+                    .map_err(|err| UserError::new("could not get next path", err.to_string()))?
+                    .to_string();
+
+                if let Some(FileReadState::Finished) = self.file_states.get(&obj_key) {
+                    // already finished
+                    continue;
+                }
+
+                if let Some(finish_type) = self.read_file(ctx, &storage_provider, &obj_key).await? {
+                    return Ok(finish_type);
+                }
             }
 
-            if let Some(finish_type) = self.read_file(ctx, &storage_provider, &obj_key).await? {
-                return Ok(finish_type);
+            let Some(watch_interval) = watch_interval else {
+                break;
+            };
+
+            // wait before listing the path again for new files, handling checkpoints and stops
+            // in the meantime
+            let next_listing = tokio::time::sleep(watch_interval);
+            tokio::pin!(next_listing);
+            loop {
+                select! {
+                    _ = &mut next_listing => break,
+                    msg_res = ctx.control_rx.recv() => {
+                        if let Some(control_message) = msg_res {
+                            if let Some(finish_type) = self.process_control_message(ctx, control_message).await {
+                                return Ok(finish_type);
+                            }
+                        }
+                    }
+                }
             }
         }
+
         info!("FileSystem source finished");
         Ok(SourceFinishType::Final)
     }
@@ -178,7 +214,7 @@ impl FileSystemSourceFunc {
         path: String,
     ) -> Result<Box<dyn Stream<Item = Result<String, UserError>> + Unpin + Send>, UserError> {
         match &self.format {
-            Format::Json(_) => {
+            Format::Json(_) | Format::RawString(_) | Format::Csv(_) => {
                 let stream_reader = storage_provider.get_as_stream(path).await.unwrap();
 
                 let compression_reader: Box<dyn AsyncRead + Unpin + Send> =
@@ -268,7 +304,59 @@ impl FileSystemSourceFunc {
                     as Box<dyn Stream<Item = Result<RecordBatch, UserError>> + Send + Unpin>;
                 Ok(result)
             }
-            _ => unreachable!("code path only for Parquet"),
+            Format::ArrowIpc(_) => {
+                let bytes = storage_provider.get(path).await.map_err(|err| {
+                    UserError::new(
+                        "could not read Arrow file",
+                        format!("path:{}, err:{:?}", path, err),
+                    )
+                })?;
+
+                // IPC files (Feather v2) start with a magic string; anything else is read as a stream
+                let reader: Box<
+                    dyn Iterator<Item = Result<RecordBatch, arrow::error::ArrowError>> + Send,
+                > = if bytes.starts_with(b"ARROW1") {
+                    Box::new(
+                        FileReader::try_new(Cursor::new(bytes), None).map_err(|err| {
+                            UserError::new(
+                                "could not read Arrow file",
+                                format!("path:{}, err:{}", path, err),
+                            )
+                        })?,
+                    )
+                } else {
+                    Box::new(
+                        StreamReader::try_new(Cursor::new(bytes), None).map_err(|err| {
+                            UserError::new(
+                                "could not read Arrow stream",
+                                format!("path:{}, err:{}", path, err),
+                            )
+                        })?,
+                    )
+                };
+
+                let batches: Vec<_> = reader.map(move |res| match res {
+                    Ok(record_batch) => {
+                        // add timestamp
+                        let mut columns = record_batch.columns().to_vec();
+                        let current_time = to_nanos(SystemTime::now());
+                        let time_column = ScalarValue::TimestampNanosecond(Some(current_time as i64), None)
+                            .to_array_of_size(record_batch.num_rows())
+                            .unwrap();
+                        columns.push(time_column);
+
+                        RecordBatch::try_new(out_schema.clone(), columns).map_err(|e| UserError::new("data does not match schema",
+                            format!("The Arrow file has a schema that does not match the table schema: {:?}", e)))
+                    }
+                    Err(err) => Err(UserError::new(
+                        "could not read record batch from Arrow file",
+                        err.to_string(),
+                    )),
+                }).collect();
+
+                Ok(Box::new(futures::stream::iter(batches)))
+            }
+            _ => unreachable!("code path only for Parquet and Arrow IPC"),
         }
     }
 
@@ -293,7 +381,7 @@ impl FileSystemSourceFunc {
         };
 
         match self.format {
-            Format::Json(_) => {
+            Format::Json(_) | Format::RawString(_) | Format::Csv(_) => {
                 let line_reader = self
                     .get_newline_separated_stream(storage_provider, obj_key.to_string())
                     .await?
@@ -302,7 +390,7 @@ impl FileSystemSourceFunc {
                     .await
             }
             Format::Avro(_) => todo!(),
-            Format::Parquet(_) => {
+            Format::Parquet(_) | Format::ArrowIpc(_) => {
                 let record_batch_stream = self
                     .get_record_batch_stream(
                         storage_provider,
@@ -315,12 +403,56 @@ impl FileSystemSourceFunc {
                 self.read_parquet_file(ctx, record_batch_stream, obj_key, records_read)
                     .await
             }
-            Format::RawString(_) => todo!(),
+            Format::Xml(_) => self.read_xml_file(ctx, storage_provider, obj_key).await,
             Format::RawBytes(_) => todo!(),
             Format::Protobuf(_) => todo!("Protobuf not supported"),
         }
     }
 
+    /// XML documents can't be split into records without parsing them, so each file is read
+    /// and deserialized as a single message
+    async fn read_xml_file(
+        &mut self,
+        ctx: &mut ArrowContext,
+        storage_provider: &StorageProvider,
+        obj_key: &String,
+    ) -> Result<Option<SourceFinishType>, UserError> {
+        let bytes = storage_provider
+            .get(obj_key.as_str())
+            .await
+            .map_err(|err| {
+                UserError::new(
+                    "could not read XML file",
+                    format!("path:{}, err:{:?}", obj_key, err),
+                )
+            })?;
+
+        let mut data = vec![];
+        let read = match self.get_compression_format() {
+            CompressionFormat::Zstd => ZstdDecoder::new(&bytes[..]).read_to_end(&mut data).await,
+            CompressionFormat::Gzip => GzipDecoder::new(&bytes[..]).read_to_end(&mut data).await,
+            CompressionFormat::None => {
+                data = bytes.to_vec();
+                Ok(data.len())
+            }
+        };
+        read.map_err(|err| {
+            UserError::new(
+                "could not decompress XML file",
+                format!("path:{}, err:{}", obj_key, err),
+            )
+        })?;
+
+        ctx.deserialize_slice(&data, SystemTime::now(), None)
+            .await?;
+        ctx.flush_buffer().await?;
+
+        info!("finished reading file {}", obj_key);
+        self.file_states
+            .insert(obj_key.to_string(), FileReadState::Finished);
+        Ok(None)
+    }
+
     async fn read_parquet_file(
         &mut self,
         ctx: &mut ArrowContext,
@@ -425,6 +557,10 @@ impl FileSystemSourceFunc {
             ControlMessage::Commit { .. } => {
                 unreachable!("sources shouldn't receive commit messages");
             }
+            ControlMessage::QueryState { query } => {
+                ctx.query_state(query).await;
+                None
+            }
             _ => None,
         }
     }
@@ -4,9 +4,15 @@ use arroyo_operator::connector::{Connection, Connector};
 use arroyo_operator::operator::OperatorNode;
 use arroyo_rpc::api_types::connections::{ConnectionProfile, ConnectionSchema, TestSourceMessage};
 use arroyo_rpc::OperatorConfig;
-use fluvio::Offset;
+use fluvio::metadata::objects::Metadata;
+use fluvio::metadata::topic::TopicSpec;
+use fluvio::{
+    Fluvio, FluvioConfig, Offset, SmartModuleContextData, SmartModuleInvocation,
+    SmartModuleInvocationWasm, SmartModuleKind,
+};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use tokio::sync::mpsc::Sender;
 use typify::import_types;
 
 use crate::fluvio::sink::FluvioSinkFunc;
@@ -23,6 +29,52 @@ const ICON: &str = include_str!("./fluvio.svg");
 
 import_types!(schema = "src/fluvio/table.json");
 
+/// Connects to the cluster and checks that the table's topic exists
+async fn test_inner(table: FluvioTable, tx: &Sender<TestSourceMessage>) -> anyhow::Result<String> {
+    let _ = tx
+        .send(TestSourceMessage::info(format!(
+            "Connecting to Fluvio{}",
+            table
+                .endpoint
+                .as_ref()
+                .map(|e| format!(" at {}", e))
+                .unwrap_or_default()
+        )))
+        .await;
+
+    let client = match &table.endpoint {
+        Some(endpoint) => Fluvio::connect_with_config(&FluvioConfig::new(endpoint)).await,
+        None => Fluvio::connect().await,
+    }
+    .map_err(|e| anyhow!("Failed to connect to Fluvio: {}", e))?;
+
+    let _ = tx
+        .send(TestSourceMessage::info(format!(
+            "Fetching metadata for topic '{}'",
+            table.topic
+        )))
+        .await;
+    let admin = client.admin().await;
+    let topics: Vec<Metadata<TopicSpec>> =
+        admin.list(vec![table.topic.clone()]).await.map_err(|e| {
+            anyhow!(
+                "Failed to fetch metadata for topic '{}': {}",
+                table.topic,
+                e
+            )
+        })?;
+
+    let Some(topic) = topics.into_iter().next() else {
+        bail!("Topic '{}' does not exist", table.topic);
+    };
+
+    Ok(format!(
+        "Successfully found topic '{}' with {} partitions",
+        table.topic,
+        topic.spec.partitions()
+    ))
+}
+
 impl Connector for FluvioConnector {
     type ProfileT = EmptyConfig;
     type TableT = FluvioTable;
@@ -40,7 +92,7 @@ impl Connector for FluvioConnector {
             enabled: true,
             source: true,
             sink: true,
-            testing: false,
+            testing: true,
             hidden: false,
             custom_schemas: true,
             connection_config: None,
@@ -68,15 +120,14 @@ impl Connector for FluvioConnector {
         &self,
         _: &str,
         _: Self::ProfileT,
-        _: Self::TableT,
+        table: Self::TableT,
         _: Option<&ConnectionSchema>,
         tx: tokio::sync::mpsc::Sender<TestSourceMessage>,
     ) {
         tokio::task::spawn(async move {
-            let message = TestSourceMessage {
-                error: false,
-                done: true,
-                message: "Successfully validated connection".to_string(),
+            let message = match test_inner(table, &tx).await {
+                Ok(message) => TestSourceMessage::done(message),
+                Err(e) => TestSourceMessage::fail(format!("{:#}", e)),
             };
             tx.send(message).await.unwrap();
         });
@@ -100,8 +151,12 @@ impl Connector for FluvioConnector {
                     offset: match offset.as_deref() {
                         Some("earliest") => SourceOffset::Earliest,
                         None | Some("latest") => SourceOffset::Latest,
+                        Some("group") => SourceOffset::Group,
                         Some(other) => bail!("invalid value for source.offset '{}'", other),
                     },
+                    consumer: options.remove("source.consumer"),
+                    smart_module: options.remove("source.smartmodule"),
+                    smart_module_params: options.remove("source.smartmodule.params"),
                 }
             }
             "sink" => TableType::Sink {},
@@ -127,6 +182,27 @@ impl Connector for FluvioConnector {
         table: FluvioTable,
         schema: Option<&ConnectionSchema>,
     ) -> anyhow::Result<Connection> {
+        if let TableType::Source {
+            offset,
+            consumer,
+            smart_module,
+            smart_module_params,
+        } = &table.type_
+        {
+            if *offset == SourceOffset::Group && consumer.is_none() {
+                bail!("source.consumer must be set to read from the `group` offset");
+            }
+
+            if smart_module.is_none() && smart_module_params.is_some() {
+                bail!("source.smartmodule.params is set, but not source.smartmodule");
+            }
+
+            smart_module_params
+                .as_deref()
+                .map(parse_smart_module_params)
+                .transpose()?;
+        }
+
         let (typ, desc) = match table.type_ {
             TableType::Source { .. } => (
                 ConnectionType::Source,
@@ -175,11 +251,33 @@ impl Connector for FluvioConnector {
         config: OperatorConfig,
     ) -> anyhow::Result<OperatorNode> {
         match table.type_ {
-            TableType::Source { offset } => {
+            TableType::Source {
+                offset,
+                consumer,
+                smart_module,
+                smart_module_params,
+            } => {
+                let smart_module = smart_module
+                    .map(|name| {
+                        Ok::<_, anyhow::Error>(SmartModuleInvocation {
+                            wasm: SmartModuleInvocationWasm::Predefined(name),
+                            kind: SmartModuleKind::Generic(SmartModuleContextData::None),
+                            params: smart_module_params
+                                .as_deref()
+                                .map(parse_smart_module_params)
+                                .transpose()?
+                                .unwrap_or_default()
+                                .into(),
+                        })
+                    })
+                    .transpose()?;
+
                 Ok(OperatorNode::from_source(Box::new(FluvioSourceFunc {
                     topic: table.topic,
                     endpoint: table.endpoint.clone(),
                     offset_mode: offset,
+                    consumer,
+                    smart_module,
                     format: config
                         .format
                         .ok_or_else(|| anyhow!("format required for fluvio source"))?,
@@ -201,11 +299,45 @@ impl Connector for FluvioConnector {
     }
 }
 
+fn parse_smart_module_params(params: &str) -> anyhow::Result<BTreeMap<String, String>> {
+    params
+        .split(',')
+        .map(|s| s.trim())
+        .filter(|s| !s.is_empty())
+        .map(|param| {
+            let (key, value) = param.split_once('=').ok_or_else(|| {
+                anyhow!(
+                    "invalid SmartModule param '{}'; params must be of the form key=value",
+                    param
+                )
+            })?;
+            Ok((key.trim().to_string(), value.trim().to_string()))
+        })
+        .collect()
+}
+
 impl SourceOffset {
-    pub fn offset(&self) -> Offset {
+    /// The offset to start reading a partition from, or None to resume from the offset
+    /// committed by the consumer
+    pub fn offset(&self) -> Option<Offset> {
         match self {
-            SourceOffset::Earliest => Offset::beginning(),
-            SourceOffset::Latest => Offset::end(),
+            SourceOffset::Earliest => Some(Offset::beginning()),
+            SourceOffset::Latest => Some(Offset::end()),
+            SourceOffset::Group => None,
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::parse_smart_module_params;
+
+    #[test]
+    fn test_parse_smart_module_params() {
+        let params = parse_smart_module_params("regex=^error, limit = 10,").unwrap();
+        assert_eq!(params.get("regex").unwrap(), "^error");
+        assert_eq!(params.get("limit").unwrap(), "10");
+
+        assert!(parse_smart_module_params("regex").is_err());
+    }
+}
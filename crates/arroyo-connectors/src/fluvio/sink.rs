@@ -48,7 +48,11 @@ impl ArrowOperator for FluvioSinkFunc {
         }
     }
 
-    async fn process_batch(&mut self, batch: RecordBatch, _: &mut ArrowContext) {
+    async fn process_batch(
+        &mut self,
+        batch: RecordBatch,
+        _: &mut ArrowContext,
+    ) -> anyhow::Result<()> {
         let values = self.serializer.serialize(&batch);
         for v in values {
             self.producer
@@ -58,10 +62,16 @@ impl ArrowOperator for FluvioSinkFunc {
                 .await
                 .unwrap();
         }
+        Ok(())
     }
 
-    async fn handle_checkpoint(&mut self, _: CheckpointBarrier, _: &mut ArrowContext) {
+    async fn handle_checkpoint(
+        &mut self,
+        _: CheckpointBarrier,
+        _: &mut ArrowContext,
+    ) -> anyhow::Result<()> {
         self.producer.as_mut().unwrap().flush().await.unwrap();
+        Ok(())
     }
 }
 
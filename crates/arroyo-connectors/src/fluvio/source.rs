@@ -10,15 +10,18 @@ use arroyo_state::tables::global_keyed_map::GlobalKeyedView;
 use arroyo_types::*;
 use async_trait::async_trait;
 use bincode::{Decode, Encode};
+use fluvio::consumer::{
+    ConsumerConfigExtBuilder, ConsumerStream, OffsetManagementStrategy, Record as ConsumerRecord,
+};
 use fluvio::dataplane::link::ErrorCode;
 use fluvio::metadata::objects::Metadata;
 use fluvio::metadata::topic::TopicSpec;
-use fluvio::{consumer::Record as ConsumerRecord, Fluvio, FluvioConfig, Offset};
+use fluvio::{Fluvio, FluvioConfig, Offset, SmartModuleInvocation};
 use std::collections::HashMap;
 use std::time::Duration;
 use tokio::select;
 use tokio::time::MissedTickBehavior;
-use tokio_stream::{Stream, StreamExt, StreamMap};
+use tokio_stream::{StreamExt, StreamMap};
 use tracing::{debug, error, info, warn};
 
 use super::SourceOffset;
@@ -27,6 +30,8 @@ pub struct FluvioSourceFunc {
     pub topic: String,
     pub endpoint: Option<String>,
     pub offset_mode: SourceOffset,
+    pub consumer: Option<String>,
+    pub smart_module: Option<SmartModuleInvocation>,
     pub format: Format,
     pub framing: Option<Framing>,
     pub bad_data: Option<BadData>,
@@ -72,7 +77,8 @@ impl FluvioSourceFunc {
     async fn get_consumer(
         &mut self,
         ctx: &mut ArrowContext,
-    ) -> anyhow::Result<StreamMap<u32, impl Stream<Item = Result<ConsumerRecord, ErrorCode>>>> {
+    ) -> anyhow::Result<StreamMap<u32, impl ConsumerStream<Item = Result<ConsumerRecord, ErrorCode>>>>
+    {
         info!("Creating Fluvio consumer for {:?}", self.endpoint);
 
         let config: Option<FluvioConfig> = self.endpoint.as_ref().map(FluvioConfig::new);
@@ -112,18 +118,13 @@ impl FluvioSourceFunc {
         let parts: Vec<_> = (0..partitions)
             .filter(|i| *i % ctx.task_info.parallelism == ctx.task_info.task_index)
             .map(|i| {
-                let offset = state
-                    .get(&(i as u32))
-                    .map(|s| Offset::absolute(s.offset).unwrap())
-                    .unwrap_or_else(|| {
-                        if has_state {
-                            // if we've restored partitions and we don't know about this one, that means it's
-                            // new, and we want to start from the beginning so we don't drop data
-                            Offset::beginning()
-                        } else {
-                            self.offset_mode.offset()
-                        }
-                    });
+                let offset = match state.get(&(i as u32)) {
+                    Some(s) => Some(Offset::absolute(s.offset).unwrap()),
+                    // if we've restored partitions and we don't know about this one, that means it's
+                    // new, and we want to start from the beginning so we don't drop data
+                    None if has_state => Some(Offset::beginning()),
+                    None => self.offset_mode.offset(),
+                };
 
                 (i as u32, offset)
             })
@@ -131,11 +132,35 @@ impl FluvioSourceFunc {
 
         let mut streams = StreamMap::new();
         for (p, offset) in parts {
-            #[allow(deprecated)]
-            let c = client.partition_consumer(self.topic.clone(), p).await?;
+            let mut config = ConsumerConfigExtBuilder::default();
+            config
+                .topic(self.topic.clone())
+                .partition(p)
+                .offset_start(offset.clone().unwrap_or_else(Offset::end))
+                .smartmodule(self.smart_module.iter().cloned().collect::<Vec<_>>());
+
+            if let Some(consumer) = &self.consumer {
+                if offset.is_some() {
+                    // Fluvio resumes from the consumer's committed offset if there is one, so it
+                    // needs to be cleared for us to start from the offset in our state
+                    if let Err(e) = client
+                        .delete_consumer_offset(consumer.clone(), (self.topic.clone(), p))
+                        .await
+                    {
+                        debug!(
+                            "no committed offset to clear for consumer {} on partition {}: {:?}",
+                            consumer, p, e
+                        );
+                    }
+                }
+
+                config
+                    .offset_consumer(consumer.clone())
+                    .offset_strategy(OffsetManagementStrategy::Manual);
+            }
+
             info!("Starting partition {} at offset {:?}", p, offset);
-            #[allow(deprecated)]
-            streams.insert(p, c.stream(offset).await?);
+            streams.insert(p, client.consumer_with_config(config.build()?).await?);
         }
 
         Ok(streams)
@@ -202,6 +227,22 @@ impl FluvioSourceFunc {
                                 }).await;
                             }
 
+                            if self.consumer.is_some() {
+                                for (partition, stream) in streams.iter_mut() {
+                                    if !offsets.contains_key(partition) {
+                                        continue;
+                                    }
+
+                                    // This is just used for progress tracking, so it's not a fatal error if it
+                                    // fails. The actual offset is stored in state.
+                                    if let Err(e) = stream.offset_commit().await {
+                                        warn!("Failed to commit offset for partition {} to Fluvio: {:?}", partition, e);
+                                    } else if let Err(e) = stream.offset_flush().await {
+                                        warn!("Failed to flush offset for partition {} to Fluvio: {:?}", partition, e);
+                                    }
+                                }
+                            }
+
                             if self.start_checkpoint(c, ctx).await {
                                 return Ok(SourceFinishType::Immediate);
                             }
@@ -224,6 +265,9 @@ impl FluvioSourceFunc {
                         Some(ControlMessage::LoadCompacted {compacted}) => {
                             ctx.load_compacted(compacted).await;
                         }
+                        Some(ControlMessage::QueryState { query }) => {
+                            ctx.query_state(query).await;
+                        }
                         Some(ControlMessage::NoOp ) => {}
                         None => {
 
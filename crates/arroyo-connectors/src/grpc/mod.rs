@@ -0,0 +1,350 @@
+mod operator;
+
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use anyhow::{anyhow, bail, Context};
+use arroyo_formats::ser::ArrowSerializer;
+use arroyo_operator::connector::{Connection, Connector};
+use arroyo_operator::operator::OperatorNode;
+use arroyo_rpc::api_types::connections::{
+    ConnectionProfile, ConnectionSchema, ConnectionType, TestSourceMessage,
+};
+use arroyo_rpc::formats::{Format, ProtobufFormat};
+use arroyo_rpc::var_str::VarStr;
+use arroyo_rpc::OperatorConfig;
+use arroyo_types::string_to_map;
+use prost_reflect::{DescriptorPool, Kind, MessageDescriptor};
+use serde::{Deserialize, Serialize};
+use tokio::sync::{Mutex, Semaphore};
+use tonic::codegen::http::uri::PathAndQuery;
+use tonic::metadata::{MetadataKey, MetadataMap, MetadataValue};
+use tonic::transport::{ClientTlsConfig, Endpoint};
+use typify::import_types;
+
+use crate::grpc::operator::GrpcSinkFunc;
+use crate::{pull_opt, pull_option_to_i64, EmptyConfig};
+
+const TABLE_SCHEMA: &str = include_str!("./table.json");
+
+import_types!(schema = "src/grpc/table.json", convert = { {type = "string", format = "var-str"} = VarStr });
+
+const DEFAULT_DEADLINE: Duration = Duration::from_secs(10);
+const DEFAULT_MAX_RETRIES: u32 = 10;
+const DEFAULT_MAX_CONCURRENCY: u32 = 8;
+
+pub struct GrpcConnector {}
+
+/// The messages that a table's rows are sent as
+struct RequestMessages {
+    /// the path of the method, as /package.Service/Method
+    path: String,
+    /// the message that each row is encoded as
+    row: MessageDescriptor,
+    /// the number of the repeated field of the request that rows are batched into, if any
+    rows_field: Option<u32>,
+}
+
+impl GrpcTable {
+    fn descriptor_set(&self) -> anyhow::Result<Vec<u8>> {
+        base64::decode(self.descriptor_set.trim()).context("descriptor_set is not valid base64")
+    }
+
+    fn request_messages(&self) -> anyhow::Result<RequestMessages> {
+        let pool = DescriptorPool::decode(self.descriptor_set()?.as_slice())
+            .context("descriptor_set is not a valid FileDescriptorSet")?;
+
+        let (service_name, method_name) = self
+            .method
+            .trim_start_matches('/')
+            .split_once('/')
+            .ok_or_else(|| {
+                anyhow!(
+                    "method '{}' should be of the form package.Service/Method",
+                    self.method
+                )
+            })?;
+
+        let service = pool
+            .get_service_by_name(service_name)
+            .ok_or_else(|| anyhow!("service '{}' not found in descriptor_set", service_name))?;
+
+        let method = service
+            .methods()
+            .find(|m| m.name() == method_name)
+            .ok_or_else(|| {
+                anyhow!(
+                    "method '{}' not found in service '{}'",
+                    method_name,
+                    service_name
+                )
+            })?;
+
+        if method.is_client_streaming() || method.is_server_streaming() {
+            bail!("method '{}' must be unary", self.method);
+        }
+
+        let request = method.input();
+
+        let (row, rows_field) = match &self.rows_field {
+            Some(name) => {
+                let field = request.get_field_by_name(name).ok_or_else(|| {
+                    anyhow!(
+                        "rows_field '{}' is not a field of {}",
+                        name,
+                        request.full_name()
+                    )
+                })?;
+
+                match field.kind() {
+                    Kind::Message(row) if field.is_list() && !field.is_map() => {
+                        (row, Some(field.number()))
+                    }
+                    _ => bail!(
+                        "rows_field '{}' must be a repeated message field of {}",
+                        name,
+                        request.full_name()
+                    ),
+                }
+            }
+            None => (request, None),
+        };
+
+        Ok(RequestMessages {
+            path: format!("/{}/{}", service.full_name(), method_name),
+            row,
+            rows_field,
+        })
+    }
+
+    fn metadata(&self) -> anyhow::Result<MetadataMap> {
+        let mut metadata = MetadataMap::new();
+
+        if let Some(entries) = &self.metadata {
+            let entries = string_to_map(&entries.sub_env_vars()?, ':').ok_or_else(|| {
+                anyhow!("metadata must be a comma separated list of 'key: value' entries")
+            })?;
+
+            for (k, v) in entries {
+                metadata.insert(
+                    MetadataKey::from_str(&k.to_lowercase())
+                        .map_err(|_| anyhow!("invalid metadata key '{}'", k))?,
+                    MetadataValue::from_str(&v)
+                        .map_err(|_| anyhow!("invalid value for metadata key '{}'", k))?,
+                );
+            }
+        }
+
+        Ok(metadata)
+    }
+
+    fn endpoint(&self) -> anyhow::Result<Endpoint> {
+        let url = self.endpoint.sub_env_vars()?;
+        let mut endpoint = Endpoint::from_shared(url.clone())
+            .map_err(|e| anyhow!("invalid endpoint '{}': {}", url, e))?
+            .connect_timeout(self.deadline());
+
+        if url.starts_with("https://") {
+            endpoint = endpoint.tls_config(ClientTlsConfig::new())?;
+        }
+
+        Ok(endpoint)
+    }
+
+    fn deadline(&self) -> Duration {
+        self.deadline_ms
+            .map(|ms| Duration::from_millis(ms as u64))
+            .unwrap_or(DEFAULT_DEADLINE)
+    }
+}
+
+async fn test_inner(table: &GrpcTable) -> anyhow::Result<String> {
+    table.request_messages()?;
+    table.metadata()?;
+
+    table
+        .endpoint()?
+        .connect()
+        .await
+        .map_err(|e| anyhow!("failed to connect to gRPC server: {}", e))?;
+
+    Ok("Successfully connected to gRPC server".to_string())
+}
+
+impl Connector for GrpcConnector {
+    type ProfileT = EmptyConfig;
+    type TableT = GrpcTable;
+
+    fn name(&self) -> &'static str {
+        "grpc"
+    }
+
+    fn metadata(&self) -> arroyo_rpc::api_types::connections::Connector {
+        arroyo_rpc::api_types::connections::Connector {
+            id: "grpc".to_string(),
+            name: "gRPC".to_string(),
+            icon: "".to_string(),
+            description: "Send rows as protobuf messages to a gRPC service".to_string(),
+            enabled: true,
+            source: false,
+            sink: true,
+            testing: true,
+            hidden: false,
+            custom_schemas: true,
+            connection_config: None,
+            table_config: TABLE_SCHEMA.to_owned(),
+        }
+    }
+
+    fn table_type(&self, _: Self::ProfileT, _: Self::TableT) -> ConnectionType {
+        ConnectionType::Sink
+    }
+
+    fn test(
+        &self,
+        _: &str,
+        _: Self::ProfileT,
+        table: Self::TableT,
+        _: Option<&ConnectionSchema>,
+        tx: tokio::sync::mpsc::Sender<TestSourceMessage>,
+    ) {
+        tokio::task::spawn(async move {
+            let message = match test_inner(&table).await {
+                Ok(m) => TestSourceMessage::done(m),
+                Err(e) => TestSourceMessage::fail(format!("{:#}", e)),
+            };
+
+            tx.send(message).await.unwrap();
+        });
+    }
+
+    fn from_options(
+        &self,
+        name: &str,
+        options: &mut HashMap<String, String>,
+        schema: Option<&ConnectionSchema>,
+        _: Option<&ConnectionProfile>,
+    ) -> anyhow::Result<Connection> {
+        let table = GrpcTable {
+            endpoint: VarStr::new(pull_opt("endpoint", options)?),
+            method: pull_opt("method", options)?,
+            descriptor_set: pull_opt("descriptor_set", options)?,
+            rows_field: options.remove("rows_field"),
+            metadata: options.remove("metadata").map(VarStr::new),
+            deadline_ms: pull_option_to_i64("deadline_ms", options)?,
+            max_retries: pull_option_to_i64("max_retries", options)?,
+            max_concurrency: pull_option_to_i64("max_concurrency", options)?,
+        };
+
+        self.from_config(None, name, EmptyConfig {}, table, schema)
+    }
+
+    fn from_config(
+        &self,
+        id: Option<i64>,
+        name: &str,
+        config: Self::ProfileT,
+        table: Self::TableT,
+        schema: Option<&ConnectionSchema>,
+    ) -> anyhow::Result<Connection> {
+        let mut schema = schema
+            .map(|s| s.to_owned())
+            .ok_or_else(|| anyhow!("no schema defined for gRPC sink"))?;
+
+        let messages = table.request_messages()?;
+        table.metadata()?;
+        table.endpoint()?;
+
+        if table.deadline_ms.is_some_and(|d| d <= 0) {
+            bail!("deadline_ms must be greater than 0");
+        }
+        if table.max_retries.is_some_and(|r| r < 0) {
+            bail!("max_retries must not be negative");
+        }
+        if table.max_concurrency.is_some_and(|c| c <= 0) {
+            bail!("max_concurrency must be greater than 0");
+        }
+
+        for field in &schema.fields {
+            if messages.row.get_field_by_name(&field.field_name).is_none() {
+                bail!(
+                    "field '{}' is not a field of {}",
+                    field.field_name,
+                    messages.row.full_name()
+                );
+            }
+        }
+
+        // rows are always encoded with the messages from the descriptor set
+        if !matches!(schema.format, None | Some(Format::Protobuf(_))) {
+            bail!("gRPC sinks only support the protobuf format");
+        }
+
+        let format = Format::Protobuf(ProtobufFormat {
+            into_unstructured_json: false,
+            message_name: Some(messages.row.full_name().to_string()),
+            compiled_schema: Some(table.descriptor_set()?),
+            confluent_schema_registry: false,
+        });
+        schema.format = Some(format.clone());
+
+        let description = format!("GrpcSink<{}>", table.method);
+
+        let config = OperatorConfig {
+            connection: serde_json::to_value(config).unwrap(),
+            table: serde_json::to_value(table).unwrap(),
+            rate_limit: None,
+            format: Some(format),
+            bad_data: schema.bad_data.clone(),
+            framing: None,
+            metadata_fields: vec![],
+        };
+
+        Ok(Connection {
+            id,
+            connector: self.name(),
+            name: name.to_string(),
+            connection_type: ConnectionType::Sink,
+            schema,
+            config: serde_json::to_string(&config).unwrap(),
+            description,
+        })
+    }
+
+    fn make_operator(
+        &self,
+        _: Self::ProfileT,
+        table: Self::TableT,
+        config: OperatorConfig,
+    ) -> anyhow::Result<OperatorNode> {
+        let messages = table.request_messages()?;
+        let max_concurrency = table
+            .max_concurrency
+            .map(|c| c as u32)
+            .unwrap_or(DEFAULT_MAX_CONCURRENCY);
+
+        Ok(OperatorNode::from_operator(Box::new(GrpcSinkFunc {
+            endpoint: table.endpoint()?,
+            channel: None,
+            path: PathAndQuery::from_str(&messages.path)?,
+            metadata: table.metadata()?,
+            rows_field: messages.rows_field,
+            deadline: table.deadline(),
+            max_retries: table
+                .max_retries
+                .map(|r| r as u32)
+                .unwrap_or(DEFAULT_MAX_RETRIES),
+            max_concurrency,
+            semaphore: Arc::new(Semaphore::new(max_concurrency as usize)),
+            serializer: ArrowSerializer::new(
+                config
+                    .format
+                    .ok_or_else(|| anyhow!("format required for gRPC sink"))?,
+            ),
+            last_reported_error_at: Arc::new(Mutex::new(SystemTime::UNIX_EPOCH)),
+            failure: Arc::new(std::sync::Mutex::new(None)),
+        })))
+    }
+}
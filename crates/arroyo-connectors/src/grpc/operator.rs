@@ -0,0 +1,270 @@
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use arrow::array::RecordBatch;
+use arroyo_formats::ser::ArrowSerializer;
+use arroyo_operator::context::ArrowContext;
+use arroyo_operator::operator::ArrowOperator;
+use arroyo_rpc::ControlResp;
+use arroyo_types::{CheckpointBarrier, SignalMessage};
+use async_trait::async_trait;
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use prost::encoding::{encode_key, encode_varint, WireType};
+use tokio::sync::{Mutex, Semaphore};
+use tonic::client::Grpc;
+use tonic::codec::{Codec, DecodeBuf, Decoder, EncodeBuf, Encoder};
+use tonic::codegen::http::uri::PathAndQuery;
+use tonic::metadata::MetadataMap;
+use tonic::transport::{Channel, Endpoint};
+use tonic::{Code, Status};
+use tracing::warn;
+
+pub struct GrpcSinkFunc {
+    pub endpoint: Endpoint,
+    pub channel: Option<Channel>,
+    pub path: PathAndQuery,
+    pub metadata: MetadataMap,
+    pub rows_field: Option<u32>,
+    pub deadline: Duration,
+    pub max_retries: u32,
+    pub max_concurrency: u32,
+    pub semaphore: Arc<Semaphore>,
+    pub serializer: ArrowSerializer,
+    pub last_reported_error_at: Arc<Mutex<SystemTime>>,
+    /// set by a request that failed permanently; the sink fails once it sees it
+    pub failure: Arc<std::sync::Mutex<Option<String>>>,
+}
+
+/// Sends already-encoded messages, and ignores the contents of responses
+#[derive(Clone, Copy, Default)]
+struct EncodedCodec;
+
+impl Codec for EncodedCodec {
+    type Encode = Bytes;
+    type Decode = ();
+    type Encoder = EncodedCodec;
+    type Decoder = EncodedCodec;
+
+    fn encoder(&mut self) -> Self::Encoder {
+        *self
+    }
+
+    fn decoder(&mut self) -> Self::Decoder {
+        *self
+    }
+}
+
+impl Encoder for EncodedCodec {
+    type Item = Bytes;
+    type Error = Status;
+
+    fn encode(&mut self, item: Bytes, dst: &mut EncodeBuf<'_>) -> Result<(), Status> {
+        dst.put(item);
+        Ok(())
+    }
+}
+
+impl Decoder for EncodedCodec {
+    type Item = ();
+    type Error = Status;
+
+    fn decode(&mut self, src: &mut DecodeBuf<'_>) -> Result<Option<()>, Status> {
+        src.advance(src.remaining());
+        Ok(Some(()))
+    }
+}
+
+fn is_retryable(code: Code) -> bool {
+    matches!(
+        code,
+        Code::Unavailable
+            | Code::DeadlineExceeded
+            | Code::ResourceExhausted
+            | Code::Aborted
+            | Code::Internal
+            | Code::Unknown
+            | Code::Cancelled
+    )
+}
+
+/// Encodes the rows as the repeated message field `field` of a request. A repeated field on the
+/// wire is just the concatenation of its elements, so this doesn't need to decode the rows.
+fn batch_request(field: u32, rows: impl Iterator<Item = Vec<u8>>) -> Bytes {
+    let mut buf = BytesMut::new();
+    for row in rows {
+        encode_key(field, WireType::LengthDelimited, &mut buf);
+        encode_varint(row.len() as u64, &mut buf);
+        buf.put_slice(&row);
+    }
+    buf.freeze()
+}
+
+async fn send_request(
+    channel: Channel,
+    path: PathAndQuery,
+    metadata: MetadataMap,
+    deadline: Duration,
+    body: Bytes,
+) -> Result<(), Status> {
+    let mut client = Grpc::new(channel);
+    client
+        .ready()
+        .await
+        .map_err(|e| Status::unavailable(format!("gRPC service was not ready: {}", e)))?;
+
+    let mut request = tonic::Request::new(body);
+    *request.metadata_mut() = metadata;
+    request.set_timeout(deadline);
+
+    client.unary(request, path, EncodedCodec).await?;
+    Ok(())
+}
+
+impl GrpcSinkFunc {
+    fn check_failure(&self) {
+        if let Some(failure) = self.failure.lock().unwrap().as_ref() {
+            panic!("gRPC sink failed: {}", failure);
+        }
+    }
+
+    /// Blocks until all in-flight requests have finished, failing if any of them failed
+    async fn flush(&self) {
+        let _permits = self
+            .semaphore
+            .acquire_many(self.max_concurrency)
+            .await
+            .expect("gRPC sink semaphore closed");
+
+        self.check_failure();
+    }
+}
+
+#[async_trait]
+impl ArrowOperator for GrpcSinkFunc {
+    fn name(&self) -> String {
+        "GrpcSink".to_string()
+    }
+
+    async fn on_start(&mut self, _: &mut ArrowContext) {
+        self.channel = Some(self.endpoint.connect_lazy());
+    }
+
+    async fn process_batch(
+        &mut self,
+        batch: RecordBatch,
+        ctx: &mut ArrowContext,
+    ) -> anyhow::Result<()> {
+        self.check_failure();
+
+        let rows = self.serializer.serialize(&batch);
+        let requests: Vec<Bytes> = match self.rows_field {
+            Some(field) => vec![batch_request(field, rows)],
+            None => rows.map(Bytes::from).collect(),
+        };
+
+        for body in requests {
+            let permit = self
+                .semaphore
+                .clone()
+                .acquire_owned()
+                .await
+                .expect("gRPC sink semaphore closed");
+
+            let channel = self.channel.clone().expect("gRPC sink was not started");
+            let path = self.path.clone();
+            let metadata = self.metadata.clone();
+            let deadline = self.deadline;
+            let max_retries = self.max_retries;
+            let control_tx = ctx.control_tx.clone();
+            let error_lock = self.last_reported_error_at.clone();
+            let failure = self.failure.clone();
+
+            // these are just used for (potential) error reporting and we don't need to clone them
+            let operator_id = ctx.task_info.operator_id.clone();
+            let task_index = ctx.task_info.task_index;
+
+            tokio::task::spawn(async move {
+                // move the permit into the task
+                let _permit = permit;
+                let mut retries = 0;
+                loop {
+                    let status = match send_request(
+                        channel.clone(),
+                        path.clone(),
+                        metadata.clone(),
+                        deadline,
+                        body.clone(),
+                    )
+                    .await
+                    {
+                        Ok(_) => break,
+                        Err(status) => status,
+                    };
+
+                    let details = format!("{}: {}", status.code(), status.message());
+
+                    if !is_retryable(status.code()) || retries >= max_retries {
+                        warn!("gRPC request failed permanently: {}", details);
+                        control_tx
+                            .send(ControlResp::Error {
+                                operator_id: operator_id.clone(),
+                                task_index,
+                                message: format!("gRPC request failed after {} retries", retries),
+                                details: details.clone(),
+                            })
+                            .await
+                            .unwrap();
+
+                        failure.lock().unwrap().get_or_insert(details);
+                        break;
+                    }
+
+                    if let Ok(mut last_reported) = error_lock.try_lock() {
+                        if last_reported.elapsed().unwrap_or_default() > Duration::from_secs(1) {
+                            warn!("gRPC request failed: {}", details);
+
+                            control_tx
+                                .send(ControlResp::Error {
+                                    operator_id: operator_id.clone(),
+                                    task_index,
+                                    message: format!("gRPC request failed (retry {})", retries),
+                                    details,
+                                })
+                                .await
+                                .unwrap();
+
+                            *last_reported = SystemTime::now();
+                        }
+                    }
+
+                    retries += 1;
+
+                    tokio::time::sleep(Duration::from_millis(
+                        (50 * (1 << retries.min(10))).min(5_000),
+                    ))
+                    .await
+                }
+            });
+        }
+        Ok(())
+    }
+
+    async fn handle_checkpoint(
+        &mut self,
+        _: CheckpointBarrier,
+        _: &mut ArrowContext,
+    ) -> anyhow::Result<()> {
+        // every row before the barrier must be delivered before the checkpoint completes
+        self.flush().await;
+        Ok(())
+    }
+
+    async fn on_close(
+        &mut self,
+        _: &Option<SignalMessage>,
+        _: &mut ArrowContext,
+    ) -> anyhow::Result<()> {
+        self.flush().await;
+        Ok(())
+    }
+}
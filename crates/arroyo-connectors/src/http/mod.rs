@@ -0,0 +1,397 @@
+mod sink;
+mod template;
+
+use anyhow::{anyhow, bail};
+use arroyo_operator::connector::{Connection, Connector};
+use arroyo_operator::operator::OperatorNode;
+use arroyo_rpc::api_types::connections::{
+    ConnectionProfile, ConnectionSchema, ConnectionType, TestSourceMessage,
+};
+use arroyo_rpc::formats::Format;
+use arroyo_rpc::var_str::VarStr;
+use arroyo_rpc::OperatorConfig;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, AUTHORIZATION};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::Duration;
+use typify::import_types;
+
+use crate::http::sink::HttpSinkFunc;
+use crate::http::template::Template;
+use crate::oauth::{oauth_config, with_oauth_schema, OAuthOptions, TokenProvider};
+use crate::{pull_opt, pull_option_to_i64};
+
+const CONFIG_SCHEMA: &str = include_str!("./profile.json");
+const TABLE_SCHEMA: &str = include_str!("./table.json");
+
+import_types!(
+    schema = "src/http/profile.json",
+    convert = {
+        {type = "string", format = "var-str"} = VarStr,
+        {type = "object", format = "oauth"} = OAuthOptions
+    }
+);
+
+import_types!(schema = "src/http/table.json");
+
+pub struct HttpSinkConnector {}
+
+/// Parses a comma-separated list of `name: value` headers, whose values may interpolate columns
+fn parse_headers(headers: Option<&str>) -> anyhow::Result<Vec<(String, Template)>> {
+    headers
+        .unwrap_or_default()
+        .split(',')
+        .filter(|h| !h.trim().is_empty())
+        .map(|header| {
+            let (name, value) = header
+                .split_once(':')
+                .ok_or_else(|| anyhow!("invalid header '{}'; expected 'name: value'", header))?;
+            let name = name.trim();
+            HeaderName::try_from(name).map_err(|_| anyhow!("invalid header name '{}'", name))?;
+            Ok((name.to_string(), Template::parse(value.trim())?))
+        })
+        .collect()
+}
+
+/// Creates a client that sends the profile's headers and static credentials with every request
+fn create_client(config: &HttpSinkConfig) -> anyhow::Result<Client> {
+    let mut headers = HeaderMap::new();
+
+    for (name, value) in parse_headers(
+        config
+            .headers
+            .as_ref()
+            .map(|h| h.sub_env_vars())
+            .transpose()?
+            .as_deref(),
+    )? {
+        let value = value
+            .as_static()
+            .ok_or_else(|| anyhow!("connection profile headers can't refer to columns"))?;
+        headers.insert(
+            HeaderName::try_from(&name)?,
+            HeaderValue::try_from(&value)
+                .map_err(|_| anyhow!("invalid value for header '{}'", name))?,
+        );
+    }
+
+    let authorization = match &config.authentication {
+        HttpSinkConfigAuthentication::Bearer { token } => {
+            Some(format!("Bearer {}", token.sub_env_vars()?))
+        }
+        HttpSinkConfigAuthentication::Basic { username, password } => Some(format!(
+            "Basic {}",
+            base64::encode(format!(
+                "{}:{}",
+                username.sub_env_vars()?,
+                password.sub_env_vars()?
+            ))
+        )),
+        HttpSinkConfigAuthentication::None {} => None,
+    };
+
+    if authorization.is_some() && config.oauth.is_some() {
+        bail!("the HTTP sink can't use OAuth along with bearer or basic authentication");
+    }
+
+    if let Some(authorization) = authorization {
+        let mut value = HeaderValue::from_str(&authorization)
+            .map_err(|_| anyhow!("invalid credentials for HTTP sink"))?;
+        value.set_sensitive(true);
+        headers.insert(AUTHORIZATION, value);
+    }
+
+    reqwest::ClientBuilder::new()
+        .default_headers(headers)
+        .timeout(Duration::from_secs(30))
+        .build()
+        .map_err(|e| anyhow!("could not construct HTTP client: {:?}", e))
+}
+
+fn validate_table(table: &HttpSinkTable, schema: Option<&ConnectionSchema>) -> anyhow::Result<()> {
+    let endpoint = Template::parse(&table.endpoint)?;
+    let headers = parse_headers(table.headers.as_deref())?;
+
+    if let Some(url) = endpoint.as_static() {
+        if let Err(e) = reqwest::Url::parse(&url) {
+            bail!("invalid endpoint '{}': {:?}", url, e);
+        }
+    }
+
+    if table.batch_size <= 0 {
+        bail!("batch_size must be positive");
+    }
+
+    if table.flush_interval_millis <= 0 {
+        bail!("flush_interval_millis must be positive");
+    }
+
+    if table.max_retries < 0 {
+        bail!("max_retries must not be negative");
+    }
+
+    if let Some(schema) = schema {
+        for column in endpoint
+            .columns()
+            .chain(headers.iter().flat_map(|(_, value)| value.columns()))
+        {
+            if !schema.fields.iter().any(|f| f.field_name == column) {
+                bail!("'{}' is not a field of the table", column);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+async fn test_inner(
+    config: &HttpSinkConfig,
+    table: Option<&HttpSinkTable>,
+    schema: Option<&ConnectionSchema>,
+) -> anyhow::Result<String> {
+    if let Some(table) = table {
+        validate_table(table, schema)?;
+    }
+
+    create_client(config)?;
+
+    // requests may have side effects on the receiving end, so the only credentials we can check
+    // without sending data are OAuth ones
+    if let Some(oauth) = oauth_config(config.oauth.as_ref())? {
+        TokenProvider::new(oauth)
+            .token()
+            .await
+            .map_err(|e| anyhow!("failed to fetch OAuth token: {}", e))?;
+        return Ok("Successfully fetched an OAuth token".to_string());
+    }
+
+    Ok("Configuration is valid".to_string())
+}
+
+impl Connector for HttpSinkConnector {
+    type ProfileT = HttpSinkConfig;
+    type TableT = HttpSinkTable;
+
+    fn name(&self) -> &'static str {
+        "http"
+    }
+
+    fn metadata(&self) -> arroyo_rpc::api_types::connections::Connector {
+        arroyo_rpc::api_types::connections::Connector {
+            id: "http".to_string(),
+            name: "HTTP".to_string(),
+            icon: "".to_string(),
+            description: "Send batches of results to an HTTP endpoint".to_string(),
+            enabled: true,
+            source: false,
+            sink: true,
+            testing: true,
+            hidden: false,
+            custom_schemas: true,
+            connection_config: Some(with_oauth_schema(CONFIG_SCHEMA)),
+            table_config: TABLE_SCHEMA.to_owned(),
+        }
+    }
+
+    fn config_description(&self, config: Self::ProfileT) -> String {
+        match (config.authentication, config.oauth) {
+            (_, Some(_)) => "OAuth2",
+            (HttpSinkConfigAuthentication::None {}, _) => "no authentication",
+            (HttpSinkConfigAuthentication::Bearer { .. }, _) => "bearer token",
+            (HttpSinkConfigAuthentication::Basic { .. }, _) => "basic authentication",
+        }
+        .to_string()
+    }
+
+    fn table_type(&self, _: Self::ProfileT, _: Self::TableT) -> ConnectionType {
+        ConnectionType::Sink
+    }
+
+    fn get_schema(
+        &self,
+        _: Self::ProfileT,
+        _: Self::TableT,
+        s: Option<&ConnectionSchema>,
+    ) -> Option<ConnectionSchema> {
+        s.cloned()
+    }
+
+    fn test_profile(
+        &self,
+        profile: Self::ProfileT,
+    ) -> Option<tokio::sync::oneshot::Receiver<TestSourceMessage>> {
+        let (tx, rx) = tokio::sync::oneshot::channel();
+
+        tokio::spawn(async move {
+            let message = match test_inner(&profile, None, None).await {
+                Ok(m) => TestSourceMessage::done(m),
+                Err(e) => TestSourceMessage::fail(e.to_string()),
+            };
+
+            tx.send(message).unwrap();
+        });
+
+        Some(rx)
+    }
+
+    fn test(
+        &self,
+        _: &str,
+        config: Self::ProfileT,
+        table: Self::TableT,
+        schema: Option<&ConnectionSchema>,
+        tx: tokio::sync::mpsc::Sender<TestSourceMessage>,
+    ) {
+        let schema = schema.cloned();
+        tokio::task::spawn(async move {
+            let message = match test_inner(&config, Some(&table), schema.as_ref()).await {
+                Ok(m) => TestSourceMessage::done(m),
+                Err(e) => TestSourceMessage::fail(e.to_string()),
+            };
+
+            tx.send(message).await.unwrap();
+        });
+    }
+
+    fn from_options(
+        &self,
+        name: &str,
+        options: &mut HashMap<String, String>,
+        schema: Option<&ConnectionSchema>,
+        profile: Option<&ConnectionProfile>,
+    ) -> anyhow::Result<Connection> {
+        let config = match profile {
+            Some(p) => serde_json::from_value(p.config.clone())
+                .map_err(|e| anyhow!("invalid config for profile '{}' in database: {}", p.id, e))?,
+            None => {
+                let authentication = match options.remove("auth.type").as_deref() {
+                    Some("none") | None => HttpSinkConfigAuthentication::None {},
+                    Some("bearer") => HttpSinkConfigAuthentication::Bearer {
+                        token: VarStr::new(pull_opt("auth.token", options)?),
+                    },
+                    Some("basic") => HttpSinkConfigAuthentication::Basic {
+                        username: VarStr::new(pull_opt("auth.username", options)?),
+                        password: VarStr::new(pull_opt("auth.password", options)?),
+                    },
+                    Some(other) => bail!(
+                        "unknown auth.type '{}'; expected one of none, bearer or basic",
+                        other
+                    ),
+                };
+
+                HttpSinkConfig {
+                    authentication,
+                    headers: options.remove("profile_headers").map(VarStr::new),
+                    oauth: OAuthOptions::from_options(options)?,
+                }
+            }
+        };
+
+        let method = options
+            .remove("method")
+            .map(|m| {
+                HttpSinkTableMethod::try_from(m.to_uppercase().as_str())
+                    .map_err(|_| anyhow!("invalid method '{}'; expected POST, PUT or PATCH", m))
+            })
+            .transpose()?
+            .unwrap_or(HttpSinkTableMethod::Post);
+
+        let batch_encoding = options
+            .remove("batch_encoding")
+            .map(|e| {
+                HttpSinkTableBatchEncoding::try_from(e.as_str()).map_err(|_| {
+                    anyhow!(
+                        "invalid batch_encoding '{}'; expected newline_delimited or json_array",
+                        e
+                    )
+                })
+            })
+            .transpose()?
+            .unwrap_or(HttpSinkTableBatchEncoding::NewlineDelimited);
+
+        let table = HttpSinkTable {
+            endpoint: pull_opt("endpoint", options)?,
+            method,
+            headers: options.remove("headers"),
+            batch_size: pull_option_to_i64("batch_size", options)?.unwrap_or(1),
+            flush_interval_millis: pull_option_to_i64("flush_interval_millis", options)?
+                .unwrap_or(1000),
+            batch_encoding,
+            max_retries: pull_option_to_i64("max_retries", options)?.unwrap_or(10),
+        };
+
+        self.from_config(None, name, config, table, schema)
+    }
+
+    fn from_config(
+        &self,
+        id: Option<i64>,
+        name: &str,
+        config: Self::ProfileT,
+        table: Self::TableT,
+        schema: Option<&ConnectionSchema>,
+    ) -> anyhow::Result<Connection> {
+        let schema = schema
+            .map(|s| s.to_owned())
+            .ok_or_else(|| anyhow!("no schema defined for HTTP sink"))?;
+
+        validate_table(&table, Some(&schema))?;
+        create_client(&config)?;
+        oauth_config(config.oauth.as_ref())?;
+
+        let format = schema
+            .format
+            .as_ref()
+            .map(|t| t.to_owned())
+            .ok_or_else(|| anyhow!("'format' must be set for HTTP sink"))?;
+
+        match &format {
+            Format::Parquet(_) | Format::ArrowIpc(_) => {
+                bail!("HTTP sinks only support formats that serialize each record individually")
+            }
+            Format::Json(_) => {}
+            _ if table.batch_encoding == HttpSinkTableBatchEncoding::JsonArray => {
+                bail!("batch_encoding 'json_array' requires the json format")
+            }
+            _ => {}
+        }
+
+        let description = format!("HttpSink<{}>", table.endpoint);
+
+        let config = OperatorConfig {
+            connection: serde_json::to_value(config).unwrap(),
+            table: serde_json::to_value(table).unwrap(),
+            rate_limit: None,
+            format: Some(format),
+            bad_data: schema.bad_data.clone(),
+            framing: schema.framing.clone(),
+            metadata_fields: vec![],
+        };
+
+        Ok(Connection {
+            id,
+            connector: self.name(),
+            name: name.to_string(),
+            connection_type: ConnectionType::Sink,
+            schema,
+            config: serde_json::to_string(&config).unwrap(),
+            description,
+        })
+    }
+
+    fn make_operator(
+        &self,
+        profile: Self::ProfileT,
+        table: Self::TableT,
+        config: OperatorConfig,
+    ) -> anyhow::Result<OperatorNode> {
+        Ok(OperatorNode::from_operator(Box::new(HttpSinkFunc::new(
+            create_client(&profile)?,
+            table,
+            oauth_config(profile.oauth.as_ref())?.map(TokenProvider::new),
+            config.format.expect("No format configured for HTTP sink"),
+            config.bad_data,
+        )?)))
+    }
+}
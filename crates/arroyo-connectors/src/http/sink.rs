@@ -0,0 +1,325 @@
+use std::collections::{BTreeMap, HashMap};
+use std::time::{Duration, Instant};
+
+use arrow::array::RecordBatch;
+use arroyo_formats::ser::ArrowSerializer;
+use arroyo_operator::context::ArrowContext;
+use arroyo_operator::operator::ArrowOperator;
+use arroyo_rpc::formats::{BadData, Format};
+use arroyo_types::{CheckpointBarrier, SignalMessage};
+use async_trait::async_trait;
+use reqwest::header::AUTHORIZATION;
+use reqwest::{Client, Method, StatusCode};
+use tracing::warn;
+
+use crate::http::template::Template;
+use crate::http::{HttpSinkTable, HttpSinkTableBatchEncoding, HttpSinkTableMethod};
+use crate::oauth::TokenProvider;
+
+/// Where a record is sent: its rendered URL and headers
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+struct Target {
+    url: String,
+    headers: Vec<(String, String)>,
+}
+
+/// Why a request failed
+enum Failure {
+    /// A network error or a response that may succeed if the request is retried
+    Retryable(String),
+    /// The server rejected the request itself, so retrying it won't help
+    Permanent(String),
+}
+
+/// Whether a request that failed with this status may succeed if it's retried
+fn is_retryable(status: StatusCode) -> bool {
+    status.is_server_error()
+        || status == StatusCode::TOO_MANY_REQUESTS
+        || status == StatusCode::REQUEST_TIMEOUT
+}
+
+pub struct HttpSinkFunc {
+    client: Client,
+    method: Method,
+    endpoint: Template,
+    headers: Vec<(String, Template)>,
+    table: HttpSinkTable,
+    oauth: Option<TokenProvider>,
+    serializer: ArrowSerializer,
+    bad_data: BadData,
+    pending: BTreeMap<Target, Vec<Vec<u8>>>,
+    pending_records: usize,
+    last_flushed: Instant,
+}
+
+impl HttpSinkFunc {
+    pub fn new(
+        client: Client,
+        table: HttpSinkTable,
+        oauth: Option<TokenProvider>,
+        format: Format,
+        bad_data: Option<BadData>,
+    ) -> anyhow::Result<Self> {
+        Ok(Self {
+            client,
+            method: match table.method {
+                HttpSinkTableMethod::Post => Method::POST,
+                HttpSinkTableMethod::Put => Method::PUT,
+                HttpSinkTableMethod::Patch => Method::PATCH,
+            },
+            endpoint: Template::parse(&table.endpoint)?,
+            headers: super::parse_headers(table.headers.as_deref())?,
+            table,
+            oauth,
+            serializer: ArrowSerializer::new(format),
+            bad_data: bad_data.unwrap_or_default(),
+            pending: BTreeMap::new(),
+            pending_records: 0,
+            last_flushed: Instant::now(),
+        })
+    }
+
+    /// Handles records that can never be sent, according to the bad data policy
+    async fn bad_data(&self, ctx: &mut ArrowContext, message: &str, details: String) {
+        match self.bad_data {
+            BadData::Drop {} => {
+                warn!("{}: {}", message, details);
+                ctx.report_error(message, details).await;
+            }
+            BadData::Fail {} => {
+                ctx.report_error(message, details.clone()).await;
+                panic!("{}: {}", message, details);
+            }
+        }
+    }
+
+    fn body(&self, records: &[Vec<u8>]) -> Vec<u8> {
+        let (start, separator, end): (&[u8], &[u8], &[u8]) = match self.table.batch_encoding {
+            HttpSinkTableBatchEncoding::NewlineDelimited => (b"", b"\n", b""),
+            HttpSinkTableBatchEncoding::JsonArray => (b"[", b",", b"]"),
+        };
+
+        let mut body = start.to_vec();
+        for (i, record) in records.iter().enumerate() {
+            if i > 0 {
+                body.extend_from_slice(separator);
+            }
+            body.extend_from_slice(record);
+        }
+        body.extend_from_slice(end);
+        body
+    }
+
+    async fn send(&self, target: &Target, body: Vec<u8>) -> Result<(), Failure> {
+        let mut req = self.client.request(self.method.clone(), &target.url);
+        for (name, value) in &target.headers {
+            req = req.header(name, value);
+        }
+
+        if let Some(oauth) = &self.oauth {
+            let authorization = oauth
+                .authorization()
+                .await
+                .map_err(|e| Failure::Retryable(format!("failed to fetch OAuth token: {}", e)))?;
+            req = req.header(AUTHORIZATION, authorization);
+        }
+
+        let resp = req
+            .body(body)
+            .send()
+            .await
+            .map_err(|e| Failure::Retryable(e.to_string()))?;
+
+        let status = resp.status();
+        if status.is_success() {
+            return Ok(());
+        }
+
+        let message = format!(
+            "{} responded with {}: {}",
+            target.url,
+            status,
+            resp.text().await.unwrap_or_default()
+        );
+
+        if status == StatusCode::UNAUTHORIZED {
+            if let Some(oauth) = &self.oauth {
+                // the token may have been revoked before it expired; fetch a new one on retry
+                oauth.invalidate().await;
+                return Err(Failure::Retryable(message));
+            }
+        }
+
+        Err(if is_retryable(status) {
+            Failure::Retryable(message)
+        } else {
+            Failure::Permanent(message)
+        })
+    }
+
+    /// Sends a request, retrying it with exponential backoff while it fails with retryable
+    /// errors
+    async fn send_with_retries(&self, ctx: &mut ArrowContext, target: &Target, body: Vec<u8>) {
+        let mut retries: u32 = 0;
+        loop {
+            let message = match self.send(target, body.clone()).await {
+                Ok(()) => return,
+                Err(Failure::Permanent(message)) => {
+                    self.bad_data(ctx, "HTTP request rejected", message).await;
+                    return;
+                }
+                Err(Failure::Retryable(message)) => message,
+            };
+
+            if retries >= self.table.max_retries.max(0) as u32 {
+                ctx.report_error("HTTP request failed", message.clone())
+                    .await;
+                panic!(
+                    "HTTP request to {} failed after {} retries: {}",
+                    target.url, retries, message
+                );
+            }
+
+            retries += 1;
+            warn!("HTTP request failed (retry {}): {}", retries, message);
+            ctx.report_error(format!("HTTP request failed (retry {})", retries), message)
+                .await;
+            tokio::time::sleep(Duration::from_millis(
+                (100 * (1 << retries.min(10))).min(30_000),
+            ))
+            .await;
+        }
+    }
+
+    async fn flush(&mut self, ctx: &mut ArrowContext) {
+        let pending = std::mem::take(&mut self.pending);
+        self.pending_records = 0;
+
+        let batch_size = self.table.batch_size.max(1) as usize;
+        for (target, records) in pending {
+            for chunk in records.chunks(batch_size) {
+                let body = self.body(chunk);
+                self.send_with_retries(ctx, &target, body).await;
+            }
+        }
+
+        self.last_flushed = Instant::now();
+    }
+
+    async fn add_batch(&mut self, batch: &RecordBatch, ctx: &mut ArrowContext) {
+        let rendered = self.endpoint.render(batch, true).and_then(|urls| {
+            let headers = self
+                .headers
+                .iter()
+                .map(|(name, value)| Ok((name, value.render(batch, false)?)))
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            Ok((urls, headers))
+        });
+
+        let (urls, headers) = match rendered {
+            Ok(rendered) => rendered,
+            Err(e) => {
+                ctx.report_error("Failed to render request", e.to_string())
+                    .await;
+                panic!("failed to render HTTP request: {:?}", e);
+            }
+        };
+
+        for (i, (url, record)) in urls
+            .into_iter()
+            .zip(self.serializer.serialize(batch))
+            .enumerate()
+        {
+            let target = url.and_then(|url| {
+                Ok(Target {
+                    url,
+                    headers: headers
+                        .iter()
+                        .map(|(name, values)| Ok((name.to_string(), values[i].clone()?)))
+                        .collect::<Result<_, String>>()?,
+                })
+            });
+
+            match target {
+                Ok(target) => {
+                    self.pending.entry(target).or_default().push(record);
+                    self.pending_records += 1;
+                }
+                Err(e) => {
+                    self.bad_data(ctx, "Could not render request for record", e)
+                        .await;
+                }
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl ArrowOperator for HttpSinkFunc {
+    fn name(&self) -> String {
+        "HttpSink".to_string()
+    }
+
+    fn tick_interval(&self) -> Option<Duration> {
+        Some(Duration::from_millis(
+            self.table.flush_interval_millis.max(1) as u64,
+        ))
+    }
+
+    async fn process_batch(
+        &mut self,
+        batch: RecordBatch,
+        ctx: &mut ArrowContext,
+    ) -> anyhow::Result<()> {
+        self.add_batch(&batch, ctx).await;
+
+        if self.pending_records >= self.table.batch_size.max(1) as usize {
+            self.flush(ctx).await;
+        }
+        Ok(())
+    }
+
+    async fn handle_checkpoint(
+        &mut self,
+        _: CheckpointBarrier,
+        ctx: &mut ArrowContext,
+    ) -> anyhow::Result<()> {
+        // records are only considered written once they've been sent, so none are lost if the
+        // pipeline restarts from this checkpoint
+        self.flush(ctx).await;
+        Ok(())
+    }
+
+    async fn handle_tick(&mut self, _: u64, ctx: &mut ArrowContext) -> anyhow::Result<()> {
+        if self.last_flushed.elapsed()
+            >= Duration::from_millis(self.table.flush_interval_millis.max(1) as u64)
+        {
+            self.flush(ctx).await;
+        }
+        Ok(())
+    }
+
+    async fn on_close(
+        &mut self,
+        _: &Option<SignalMessage>,
+        ctx: &mut ArrowContext,
+    ) -> anyhow::Result<()> {
+        self.flush(ctx).await;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::is_retryable;
+    use reqwest::StatusCode;
+
+    #[test]
+    fn test_retryable() {
+        assert!(is_retryable(StatusCode::SERVICE_UNAVAILABLE));
+        assert!(is_retryable(StatusCode::TOO_MANY_REQUESTS));
+        assert!(is_retryable(StatusCode::REQUEST_TIMEOUT));
+        assert!(!is_retryable(StatusCode::BAD_REQUEST));
+        assert!(!is_retryable(StatusCode::NOT_FOUND));
+    }
+}
@@ -0,0 +1,203 @@
+use anyhow::{anyhow, bail};
+use arrow::array::{Array, RecordBatch};
+use arrow::util::display::{ArrayFormatter, FormatOptions};
+
+#[derive(Debug, Clone, PartialEq)]
+enum TemplatePart {
+    Literal(String),
+    Column(String),
+}
+
+/// A string that may interpolate the values of columns of each row, like
+/// `https://example.com/users/{user_id}/events`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Template {
+    parts: Vec<TemplatePart>,
+}
+
+/// Percent-encodes everything but the unreserved characters of RFC 3986, so that an
+/// interpolated value is always a single path segment or query value
+fn percent_encode(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for b in value.bytes() {
+        if b.is_ascii_alphanumeric() || matches!(b, b'-' | b'.' | b'_' | b'~') {
+            encoded.push(b as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", b));
+        }
+    }
+    encoded
+}
+
+impl Template {
+    pub fn parse(template: &str) -> anyhow::Result<Self> {
+        let mut parts = vec![];
+        let mut rest = template;
+
+        while let Some(start) = rest.find('{') {
+            if rest[..start].contains('}') {
+                bail!("unmatched '}}' in '{}'", template);
+            }
+            if start > 0 {
+                parts.push(TemplatePart::Literal(rest[..start].to_string()));
+            }
+
+            let end = start
+                + rest[start..]
+                    .find('}')
+                    .ok_or_else(|| anyhow!("unclosed '{{' in '{}'", template))?;
+
+            let column = rest[start + 1..end].trim();
+            if column.is_empty() || column.contains('{') {
+                bail!("invalid column reference in '{}'", template);
+            }
+
+            parts.push(TemplatePart::Column(column.to_string()));
+            rest = &rest[end + 1..];
+        }
+
+        if rest.contains('}') {
+            bail!("unmatched '}}' in '{}'", template);
+        }
+        if !rest.is_empty() {
+            parts.push(TemplatePart::Literal(rest.to_string()));
+        }
+
+        Ok(Self { parts })
+    }
+
+    /// The columns whose values are interpolated
+    pub fn columns(&self) -> impl Iterator<Item = &str> {
+        self.parts.iter().filter_map(|p| match p {
+            TemplatePart::Column(c) => Some(c.as_str()),
+            TemplatePart::Literal(_) => None,
+        })
+    }
+
+    /// Returns the rendered string if it doesn't depend on the rows
+    pub fn as_static(&self) -> Option<String> {
+        self.columns()
+            .next()
+            .is_none()
+            .then(|| self.render_with(|_| unreachable!()))
+    }
+
+    fn render_with(&self, mut value: impl FnMut(&str) -> String) -> String {
+        let mut s = String::new();
+        for part in &self.parts {
+            match part {
+                TemplatePart::Literal(literal) => s.push_str(literal),
+                TemplatePart::Column(name) => s.push_str(&value(name)),
+            }
+        }
+        s
+    }
+
+    /// Renders the template for each row of `batch`, or the reason that a row can't be rendered
+    /// (because a column it interpolates is null). With `encode`, interpolated values are
+    /// percent-encoded, as they must be in URLs.
+    pub fn render(
+        &self,
+        batch: &RecordBatch,
+        encode: bool,
+    ) -> anyhow::Result<Vec<Result<String, String>>> {
+        let options = FormatOptions::default();
+        let columns = self
+            .columns()
+            .map(|name| {
+                let array = batch
+                    .column_by_name(name)
+                    .ok_or_else(|| anyhow!("column '{}' is not in the table", name))?;
+                Ok((
+                    name,
+                    (
+                        array.as_ref(),
+                        ArrayFormatter::try_new(array.as_ref(), &options)?,
+                    ),
+                ))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        Ok((0..batch.num_rows())
+            .map(|i| {
+                if let Some((name, _)) = columns.iter().find(|(_, (array, _))| array.is_null(i)) {
+                    return Err(format!("column '{}' is null", name));
+                }
+
+                Ok(self.render_with(|name| {
+                    let (_, (_, formatter)) = columns.iter().find(|(n, _)| *n == name).unwrap();
+                    let value = formatter.value(i).to_string();
+                    if encode {
+                        percent_encode(&value)
+                    } else {
+                        value
+                    }
+                }))
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Template;
+    use arrow::array::{Int64Array, RecordBatch, StringArray};
+    use arrow::datatypes::{DataType, Field, Schema};
+    use std::sync::Arc;
+
+    #[test]
+    fn test_parse() {
+        let template = Template::parse("https://example.com/events").unwrap();
+        assert_eq!(
+            template.as_static(),
+            Some("https://example.com/events".to_string())
+        );
+
+        let template = Template::parse("https://example.com/{ tenant }/users/{user_id}").unwrap();
+        assert_eq!(template.as_static(), None);
+        assert_eq!(
+            template.columns().collect::<Vec<_>>(),
+            vec!["tenant", "user_id"]
+        );
+
+        for invalid in ["/users/{id", "/users/id}", "/users/{}", "/users/}{id}"] {
+            assert!(Template::parse(invalid).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_render() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("tenant", DataType::Utf8, true),
+            Field::new("user_id", DataType::Int64, false),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(StringArray::from(vec![Some("acme"), None, Some("a/b c")])),
+                Arc::new(Int64Array::from(vec![1, 2, 3])),
+            ],
+        )
+        .unwrap();
+
+        let template = Template::parse("https://example.com/{tenant}/users/{user_id}").unwrap();
+        let urls = template.render(&batch, true).unwrap();
+        assert_eq!(urls[0], Ok("https://example.com/acme/users/1".to_string()));
+        assert!(urls[1].is_err());
+        assert_eq!(
+            urls[2],
+            Ok("https://example.com/a%2Fb%20c/users/3".to_string())
+        );
+
+        let headers = Template::parse("{tenant}").unwrap();
+        assert_eq!(
+            headers.render(&batch, false).unwrap()[2],
+            Ok("a/b c".to_string())
+        );
+
+        assert!(Template::parse("{missing}")
+            .unwrap()
+            .render(&batch, false)
+            .is_err());
+    }
+}
@@ -0,0 +1,253 @@
+//! A client for the [Iceberg REST catalog](https://iceberg.apache.org/spec/#iceberg-rest-catalog)
+//! API, covering the calls needed to load a table and to commit new snapshots to it.
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Context, Result};
+use reqwest::{Client, StatusCode};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+/// The subset of Iceberg's table metadata that Arroyo reads
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct TableMetadata {
+    pub format_version: i32,
+    pub table_uuid: String,
+    pub location: String,
+    #[serde(default)]
+    pub last_sequence_number: i64,
+    pub current_schema_id: Option<i32>,
+    #[serde(default)]
+    pub schemas: Vec<Value>,
+    pub default_spec_id: Option<i32>,
+    #[serde(default)]
+    pub partition_specs: Vec<PartitionSpec>,
+    pub current_snapshot_id: Option<i64>,
+    #[serde(default)]
+    pub snapshots: Vec<Snapshot>,
+    #[serde(default)]
+    pub properties: HashMap<String, String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct PartitionSpec {
+    pub spec_id: i32,
+    #[serde(default)]
+    pub fields: Vec<Value>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Snapshot {
+    pub snapshot_id: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parent_snapshot_id: Option<i64>,
+    #[serde(default)]
+    pub sequence_number: i64,
+    pub timestamp_ms: i64,
+    pub manifest_list: String,
+    #[serde(default)]
+    pub summary: HashMap<String, String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub schema_id: Option<i32>,
+}
+
+impl TableMetadata {
+    /// The current snapshot of the table, or None if nothing has been written to it
+    pub fn current_snapshot(&self) -> Option<&Snapshot> {
+        // some catalogs use -1 rather than null for tables without snapshots
+        let id = self.current_snapshot_id.filter(|id| *id >= 0)?;
+        self.snapshot(id)
+    }
+
+    pub fn snapshot(&self, id: i64) -> Option<&Snapshot> {
+        self.snapshots.iter().find(|s| s.snapshot_id == id)
+    }
+
+    pub fn current_schema(&self) -> Result<&Value> {
+        let id = self.current_schema_id.unwrap_or_default();
+        self.schemas
+            .iter()
+            .find(|s| s.get("schema-id").and_then(Value::as_i64) == Some(id as i64))
+            .ok_or_else(|| anyhow!("table metadata is missing current schema {}", id))
+    }
+
+    pub fn default_spec(&self) -> Result<&PartitionSpec> {
+        let id = self.default_spec_id.unwrap_or_default();
+        self.partition_specs
+            .iter()
+            .find(|s| s.spec_id == id)
+            .ok_or_else(|| anyhow!("table metadata is missing partition spec {}", id))
+    }
+}
+
+#[derive(Deserialize)]
+struct LoadTableResponse {
+    metadata: TableMetadata,
+}
+
+#[derive(Deserialize)]
+struct CatalogConfig {
+    #[serde(default)]
+    defaults: HashMap<String, String>,
+    #[serde(default)]
+    overrides: HashMap<String, String>,
+}
+
+pub enum CommitResult {
+    Committed,
+    /// The table changed since it was loaded, so the commit's requirements were not met
+    Conflict,
+}
+
+pub struct RestCatalog {
+    client: Client,
+    url: String,
+    warehouse: Option<String>,
+    token: Option<String>,
+    prefix: Option<String>,
+}
+
+impl RestCatalog {
+    pub async fn connect(
+        url: &str,
+        warehouse: Option<String>,
+        token: Option<String>,
+    ) -> Result<Self> {
+        let client = reqwest::ClientBuilder::new()
+            .timeout(Duration::from_secs(30))
+            .build()
+            .map_err(|e| anyhow!("could not construct HTTP client: {:?}", e))?;
+
+        let mut catalog = Self {
+            client,
+            url: url.trim_end_matches('/').to_string(),
+            warehouse,
+            token,
+            prefix: None,
+        };
+
+        let mut request = catalog.client.get(format!("{}/v1/config", catalog.url));
+        if let Some(warehouse) = &catalog.warehouse {
+            request = request.query(&[("warehouse", warehouse)]);
+        }
+        let config: CatalogConfig = catalog.send(request).await?.json().await?;
+
+        catalog.prefix = config
+            .overrides
+            .get("prefix")
+            .or_else(|| config.defaults.get("prefix"))
+            .cloned();
+
+        Ok(catalog)
+    }
+
+    fn table_url(&self, namespace: &str, table: &str) -> String {
+        // multi-level namespaces are separated by the unit separator character
+        let namespace = namespace.split('.').collect::<Vec<_>>().join("\u{1f}");
+        let prefix = self
+            .prefix
+            .as_ref()
+            .map(|p| format!("/{}", p))
+            .unwrap_or_default();
+
+        format!(
+            "{}/v1{}/namespaces/{}/tables/{}",
+            self.url,
+            prefix,
+            url::form_urlencoded::byte_serialize(namespace.as_bytes()).collect::<String>(),
+            url::form_urlencoded::byte_serialize(table.as_bytes()).collect::<String>()
+        )
+    }
+
+    async fn send(&self, mut request: reqwest::RequestBuilder) -> Result<reqwest::Response> {
+        if let Some(token) = &self.token {
+            request = request.bearer_auth(token);
+        }
+
+        let response = request
+            .send()
+            .await
+            .context("Iceberg catalog request failed")?;
+        if !response.status().is_success() && response.status() != StatusCode::CONFLICT {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            bail!("Iceberg catalog returned {}: {}", status, body);
+        }
+
+        Ok(response)
+    }
+
+    pub async fn load_table(&self, namespace: &str, table: &str) -> Result<TableMetadata> {
+        let response = self
+            .send(self.client.get(self.table_url(namespace, table)))
+            .await?;
+
+        if response.status() == StatusCode::CONFLICT {
+            bail!(
+                "Iceberg catalog returned a conflict when loading {}.{}",
+                namespace,
+                table
+            );
+        }
+
+        let response: LoadTableResponse = response
+            .json()
+            .await
+            .context("invalid response when loading Iceberg table")?;
+
+        Ok(response.metadata)
+    }
+
+    /// Adds a snapshot to the table and makes it the current snapshot of the main branch, as
+    /// long as the main branch still points at the new snapshot's parent, setting the given
+    /// table properties in the same commit
+    pub async fn commit_snapshot(
+        &self,
+        namespace: &str,
+        table: &str,
+        table_uuid: &str,
+        snapshot: &Snapshot,
+        properties: HashMap<String, String>,
+    ) -> Result<CommitResult> {
+        let mut request = json!({
+            "requirements": [
+                {"type": "assert-table-uuid", "uuid": table_uuid},
+                {"type": "assert-ref-snapshot-id", "ref": "main", "snapshot-id": snapshot.parent_snapshot_id},
+            ],
+            "updates": [
+                {"action": "add-snapshot", "snapshot": snapshot},
+                {
+                    "action": "set-snapshot-ref",
+                    "ref-name": "main",
+                    "type": "branch",
+                    "snapshot-id": snapshot.snapshot_id,
+                },
+            ],
+        });
+
+        if !properties.is_empty() {
+            request["updates"]
+                .as_array_mut()
+                .unwrap()
+                .push(json!({"action": "set-properties", "updates": properties}));
+        }
+
+        let response = self
+            .send(
+                self.client
+                    .post(self.table_url(namespace, table))
+                    .json(&request),
+            )
+            .await?;
+
+        if response.status() == StatusCode::CONFLICT {
+            return Ok(CommitResult::Conflict);
+        }
+
+        Ok(CommitResult::Committed)
+    }
+}
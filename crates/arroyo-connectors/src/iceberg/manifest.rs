@@ -0,0 +1,398 @@
+//! Reading and writing Iceberg manifests and manifest lists, which are Avro files that record
+//! the data files that make up each snapshot of a table. Only the fields that Arroyo needs are
+//! read, and only unpartitioned data manifests (format version 2) are written.
+
+use anyhow::{anyhow, bail, Result};
+use apache_avro::types::Value;
+use apache_avro::{Reader, Schema, Writer};
+
+const MANIFEST_ENTRY_SCHEMA: &str = r#"{
+  "type": "record",
+  "name": "manifest_entry",
+  "fields": [
+    {"name": "status", "type": "int", "field-id": 0},
+    {"name": "snapshot_id", "type": ["null", "long"], "default": null, "field-id": 1},
+    {"name": "sequence_number", "type": ["null", "long"], "default": null, "field-id": 3},
+    {"name": "file_sequence_number", "type": ["null", "long"], "default": null, "field-id": 4},
+    {"name": "data_file", "field-id": 2, "type": {
+      "type": "record",
+      "name": "r2",
+      "fields": [
+        {"name": "content", "type": "int", "field-id": 134},
+        {"name": "file_path", "type": "string", "field-id": 100},
+        {"name": "file_format", "type": "string", "field-id": 101},
+        {"name": "partition", "field-id": 102, "type": {"type": "record", "name": "r102", "fields": []}},
+        {"name": "record_count", "type": "long", "field-id": 103},
+        {"name": "file_size_in_bytes", "type": "long", "field-id": 104}
+      ]
+    }}
+  ]
+}"#;
+
+const MANIFEST_FILE_SCHEMA: &str = r#"{
+  "type": "record",
+  "name": "manifest_file",
+  "fields": [
+    {"name": "manifest_path", "type": "string", "field-id": 500},
+    {"name": "manifest_length", "type": "long", "field-id": 501},
+    {"name": "partition_spec_id", "type": "int", "field-id": 502},
+    {"name": "content", "type": "int", "field-id": 517},
+    {"name": "sequence_number", "type": "long", "field-id": 515},
+    {"name": "min_sequence_number", "type": "long", "field-id": 516},
+    {"name": "added_snapshot_id", "type": "long", "field-id": 503},
+    {"name": "added_files_count", "type": "int", "field-id": 504},
+    {"name": "existing_files_count", "type": "int", "field-id": 505},
+    {"name": "deleted_files_count", "type": "int", "field-id": 506},
+    {"name": "added_rows_count", "type": "long", "field-id": 512},
+    {"name": "existing_rows_count", "type": "long", "field-id": 513},
+    {"name": "deleted_rows_count", "type": "long", "field-id": 514}
+  ]
+}"#;
+
+/// Manifest entries with this status have been removed from the table
+const STATUS_DELETED: i32 = 2;
+const STATUS_ADDED: i32 = 1;
+
+const CONTENT_DATA: i32 = 0;
+
+/// An entry in a manifest list
+#[derive(Debug, Clone)]
+pub struct ManifestFile {
+    pub manifest_path: String,
+    pub manifest_length: i64,
+    pub partition_spec_id: i32,
+    pub content: i32,
+    pub sequence_number: i64,
+    pub min_sequence_number: i64,
+    pub added_snapshot_id: i64,
+    pub added_files_count: i32,
+    pub existing_files_count: i32,
+    pub deleted_files_count: i32,
+    pub added_rows_count: i64,
+    pub existing_rows_count: i64,
+    pub deleted_rows_count: i64,
+}
+
+#[derive(Debug, Clone)]
+pub struct DataFile {
+    pub file_path: String,
+    pub record_count: i64,
+    pub file_size_in_bytes: i64,
+}
+
+fn unwrap_union(value: &Value) -> &Value {
+    match value {
+        Value::Union(_, v) => v,
+        v => v,
+    }
+}
+
+/// Looks up a field of a record by name, trying each of the names in turn (field names have
+/// changed between format versions)
+fn field<'a>(record: &'a Value, names: &[&str]) -> Option<&'a Value> {
+    let Value::Record(fields) = unwrap_union(record) else {
+        return None;
+    };
+
+    names.iter().find_map(|name| {
+        fields
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| unwrap_union(v))
+            .filter(|v| !matches!(v, Value::Null))
+    })
+}
+
+fn long_field(record: &Value, names: &[&str]) -> Option<i64> {
+    match field(record, names)? {
+        Value::Long(v) => Some(*v),
+        Value::Int(v) => Some(*v as i64),
+        _ => None,
+    }
+}
+
+fn int_field(record: &Value, names: &[&str]) -> Option<i32> {
+    match field(record, names)? {
+        Value::Int(v) => Some(*v),
+        _ => None,
+    }
+}
+
+fn string_field(record: &Value, names: &[&str]) -> Option<String> {
+    match field(record, names)? {
+        Value::String(v) => Some(v.clone()),
+        _ => None,
+    }
+}
+
+fn required<T>(value: Option<T>, name: &str, file: &str) -> Result<T> {
+    value.ok_or_else(|| anyhow!("Iceberg metadata file {} is missing '{}'", file, name))
+}
+
+/// Reads the manifests listed in a manifest list
+pub fn read_manifest_list(path: &str, bytes: &[u8]) -> Result<Vec<ManifestFile>> {
+    Reader::new(bytes)?
+        .map(|record| {
+            let record = record?;
+            Ok(ManifestFile {
+                manifest_path: required(
+                    string_field(&record, &["manifest_path"]),
+                    "manifest_path",
+                    path,
+                )?,
+                manifest_length: required(
+                    long_field(&record, &["manifest_length"]),
+                    "manifest_length",
+                    path,
+                )?,
+                partition_spec_id: required(
+                    int_field(&record, &["partition_spec_id"]),
+                    "partition_spec_id",
+                    path,
+                )?,
+                // fields that were added in format version 2 default to their v1 values
+                content: int_field(&record, &["content"]).unwrap_or(CONTENT_DATA),
+                sequence_number: long_field(&record, &["sequence_number"]).unwrap_or_default(),
+                min_sequence_number: long_field(&record, &["min_sequence_number"])
+                    .unwrap_or_default(),
+                added_snapshot_id: required(
+                    long_field(&record, &["added_snapshot_id"]),
+                    "added_snapshot_id",
+                    path,
+                )?,
+                added_files_count: int_field(
+                    &record,
+                    &["added_files_count", "added_data_files_count"],
+                )
+                .unwrap_or_default(),
+                existing_files_count: int_field(
+                    &record,
+                    &["existing_files_count", "existing_data_files_count"],
+                )
+                .unwrap_or_default(),
+                deleted_files_count: int_field(
+                    &record,
+                    &["deleted_files_count", "deleted_data_files_count"],
+                )
+                .unwrap_or_default(),
+                added_rows_count: long_field(&record, &["added_rows_count"]).unwrap_or_default(),
+                existing_rows_count: long_field(&record, &["existing_rows_count"])
+                    .unwrap_or_default(),
+                deleted_rows_count: long_field(&record, &["deleted_rows_count"])
+                    .unwrap_or_default(),
+            })
+        })
+        .collect()
+}
+
+/// Reads the live data files from a manifest, failing if it contains delete files, which are
+/// not supported
+pub fn read_manifest(path: &str, bytes: &[u8]) -> Result<Vec<DataFile>> {
+    let mut files = vec![];
+    for entry in Reader::new(bytes)? {
+        let entry = entry?;
+        if required(int_field(&entry, &["status"]), "status", path)? == STATUS_DELETED {
+            continue;
+        }
+
+        let data_file = required(field(&entry, &["data_file"]), "data_file", path)?;
+        if int_field(data_file, &["content"]).unwrap_or(CONTENT_DATA) != CONTENT_DATA {
+            bail!(
+                "Iceberg manifest {} contains delete files, which are not supported",
+                path
+            );
+        }
+
+        files.push(DataFile {
+            file_path: required(string_field(data_file, &["file_path"]), "file_path", path)?,
+            record_count: required(
+                long_field(data_file, &["record_count"]),
+                "record_count",
+                path,
+            )?,
+            file_size_in_bytes: required(
+                long_field(data_file, &["file_size_in_bytes"]),
+                "file_size_in_bytes",
+                path,
+            )?,
+        });
+    }
+
+    Ok(files)
+}
+
+fn record(fields: Vec<(&str, Value)>) -> Value {
+    Value::Record(
+        fields
+            .into_iter()
+            .map(|(name, value)| (name.to_string(), value))
+            .collect(),
+    )
+}
+
+/// Writes a manifest that adds the given data files to an unpartitioned table
+pub fn write_manifest(
+    files: &[DataFile],
+    snapshot_id: i64,
+    schema: &serde_json::Value,
+    partition_spec_id: i32,
+) -> Result<Vec<u8>> {
+    let avro_schema = Schema::parse_str(MANIFEST_ENTRY_SCHEMA)?;
+    let mut writer = Writer::new(&avro_schema, vec![]);
+
+    let schema_id = schema
+        .get("schema-id")
+        .and_then(|id| id.as_i64())
+        .unwrap_or_default();
+
+    writer.add_user_metadata("schema".to_string(), serde_json::to_string(schema)?)?;
+    writer.add_user_metadata("schema-id".to_string(), schema_id.to_string())?;
+    writer.add_user_metadata("partition-spec".to_string(), "[]")?;
+    writer.add_user_metadata(
+        "partition-spec-id".to_string(),
+        partition_spec_id.to_string(),
+    )?;
+    writer.add_user_metadata("format-version".to_string(), "2")?;
+    writer.add_user_metadata("content".to_string(), "data")?;
+
+    for file in files {
+        writer.append(record(vec![
+            ("status", Value::Int(STATUS_ADDED)),
+            (
+                "snapshot_id",
+                Value::Union(1, Box::new(Value::Long(snapshot_id))),
+            ),
+            // added files inherit their sequence numbers from the manifest list
+            ("sequence_number", Value::Union(0, Box::new(Value::Null))),
+            (
+                "file_sequence_number",
+                Value::Union(0, Box::new(Value::Null)),
+            ),
+            (
+                "data_file",
+                record(vec![
+                    ("content", Value::Int(CONTENT_DATA)),
+                    ("file_path", Value::String(file.file_path.clone())),
+                    ("file_format", Value::String("PARQUET".to_string())),
+                    ("partition", record(vec![])),
+                    ("record_count", Value::Long(file.record_count)),
+                    ("file_size_in_bytes", Value::Long(file.file_size_in_bytes)),
+                ]),
+            ),
+        ]))?;
+    }
+
+    Ok(writer.into_inner()?)
+}
+
+/// Writes the manifest list for a new snapshot
+pub fn write_manifest_list(
+    manifests: &[ManifestFile],
+    snapshot_id: i64,
+    parent_snapshot_id: Option<i64>,
+    sequence_number: i64,
+) -> Result<Vec<u8>> {
+    let avro_schema = Schema::parse_str(MANIFEST_FILE_SCHEMA)?;
+    let mut writer = Writer::new(&avro_schema, vec![]);
+
+    writer.add_user_metadata("snapshot-id".to_string(), snapshot_id.to_string())?;
+    writer.add_user_metadata(
+        "parent-snapshot-id".to_string(),
+        parent_snapshot_id
+            .map(|id| id.to_string())
+            .unwrap_or_else(|| "null".to_string()),
+    )?;
+    writer.add_user_metadata("sequence-number".to_string(), sequence_number.to_string())?;
+    writer.add_user_metadata("format-version".to_string(), "2")?;
+
+    for manifest in manifests {
+        writer.append(record(vec![
+            (
+                "manifest_path",
+                Value::String(manifest.manifest_path.clone()),
+            ),
+            ("manifest_length", Value::Long(manifest.manifest_length)),
+            ("partition_spec_id", Value::Int(manifest.partition_spec_id)),
+            ("content", Value::Int(manifest.content)),
+            ("sequence_number", Value::Long(manifest.sequence_number)),
+            (
+                "min_sequence_number",
+                Value::Long(manifest.min_sequence_number),
+            ),
+            ("added_snapshot_id", Value::Long(manifest.added_snapshot_id)),
+            ("added_files_count", Value::Int(manifest.added_files_count)),
+            (
+                "existing_files_count",
+                Value::Int(manifest.existing_files_count),
+            ),
+            (
+                "deleted_files_count",
+                Value::Int(manifest.deleted_files_count),
+            ),
+            ("added_rows_count", Value::Long(manifest.added_rows_count)),
+            (
+                "existing_rows_count",
+                Value::Long(manifest.existing_rows_count),
+            ),
+            (
+                "deleted_rows_count",
+                Value::Long(manifest.deleted_rows_count),
+            ),
+        ]))?;
+    }
+
+    Ok(writer.into_inner()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_manifest_round_trip() {
+        let files = vec![
+            DataFile {
+                file_path: "s3://bucket/table/data/00000-000.parquet".to_string(),
+                record_count: 10,
+                file_size_in_bytes: 1024,
+            },
+            DataFile {
+                file_path: "s3://bucket/table/data/00000-001.parquet".to_string(),
+                record_count: 20,
+                file_size_in_bytes: 2048,
+            },
+        ];
+
+        let schema = serde_json::json!({"type": "struct", "schema-id": 0, "fields": []});
+        let manifest = write_manifest(&files, 5, &schema, 0).unwrap();
+        let read = read_manifest("manifest.avro", &manifest).unwrap();
+
+        assert_eq!(read.len(), 2);
+        assert_eq!(read[1].file_path, files[1].file_path);
+        assert_eq!(read[1].record_count, 20);
+        assert_eq!(read[1].file_size_in_bytes, 2048);
+
+        let manifests = vec![ManifestFile {
+            manifest_path: "s3://bucket/table/metadata/m0.avro".to_string(),
+            manifest_length: manifest.len() as i64,
+            partition_spec_id: 0,
+            content: 0,
+            sequence_number: 3,
+            min_sequence_number: 3,
+            added_snapshot_id: 5,
+            added_files_count: 2,
+            existing_files_count: 0,
+            deleted_files_count: 0,
+            added_rows_count: 30,
+            existing_rows_count: 0,
+            deleted_rows_count: 0,
+        }];
+
+        let list = write_manifest_list(&manifests, 5, Some(4), 3).unwrap();
+        let read = read_manifest_list("snap.avro", &list).unwrap();
+        assert_eq!(read.len(), 1);
+        assert_eq!(read[0].manifest_path, manifests[0].manifest_path);
+        assert_eq!(read[0].sequence_number, 3);
+        assert_eq!(read[0].added_rows_count, 30);
+    }
+}
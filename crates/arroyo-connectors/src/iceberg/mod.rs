@@ -0,0 +1,304 @@
+pub(crate) mod catalog;
+pub(crate) mod manifest;
+mod source;
+
+use anyhow::{anyhow, bail};
+use std::collections::HashMap;
+use typify::import_types;
+
+use arroyo_operator::connector::{Connection, Connector};
+use arroyo_operator::operator::OperatorNode;
+use arroyo_rpc::api_types::connections::{
+    ConnectionProfile, ConnectionSchema, ConnectionType, TestSourceMessage,
+};
+use arroyo_rpc::formats::{Format, ParquetFormat};
+use arroyo_rpc::var_str::VarStr;
+use arroyo_rpc::OperatorConfig;
+use arroyo_storage::BackendConfig;
+use serde::{Deserialize, Serialize};
+
+use crate::filesystem::sink::iceberg::check_supported;
+use crate::filesystem::sink::{LocalParquetFileSystemSink, ParquetFileSystemSink};
+use crate::filesystem::{self, CommitStyle, FileSettings, FileSystemTable, IcebergCommit};
+use crate::iceberg::catalog::RestCatalog;
+use crate::iceberg::source::IcebergSourceFunc;
+use crate::{pull_opt, pull_option_to_i64, EmptyConfig};
+
+const TABLE_SCHEMA: &str = include_str!("./table.json");
+
+import_types!(schema = "src/iceberg/table.json", convert = { {type = "string", format = "var-str"} = VarStr });
+
+pub struct IcebergConnector {}
+
+impl IcebergConnector {
+    /// The filesystem sink table that writes parquet files for an Iceberg table and commits
+    /// them to its catalog
+    fn sink_table(table: &IcebergTable) -> anyhow::Result<FileSystemTable> {
+        let TableType::Sink {
+            write_path,
+            rollover_seconds,
+            target_file_size,
+        } = &table.table_type
+        else {
+            bail!("not an Iceberg sink table");
+        };
+
+        Ok(FileSystemTable {
+            table_type: filesystem::TableType::Sink {
+                write_path: write_path.clone(),
+                storage_options: table.storage_options.clone(),
+                format_settings: Some(filesystem::FormatSettings::Parquet {
+                    compression: None,
+                    row_batch_size: None,
+                    row_group_size: None,
+                }),
+                file_settings: Some(FileSettings {
+                    inactivity_rollover_seconds: None,
+                    max_parts: None,
+                    rollover_seconds: *rollover_seconds,
+                    target_file_size: *target_file_size,
+                    target_part_size: None,
+                    compaction_target_size: None,
+                    partitioning: None,
+                    commit_style: Some(CommitStyle::Iceberg),
+                    iceberg: Some(IcebergCommit {
+                        catalog_url: table.catalog.url.clone(),
+                        warehouse: table.catalog.warehouse.clone(),
+                        token: table.catalog.token.clone(),
+                        namespace: table.namespace.clone(),
+                        table: table.table.clone(),
+                    }),
+                    file_naming: None,
+                }),
+            },
+        })
+    }
+}
+
+impl Connector for IcebergConnector {
+    type ProfileT = EmptyConfig;
+
+    type TableT = IcebergTable;
+
+    fn name(&self) -> &'static str {
+        "iceberg"
+    }
+
+    fn metadata(&self) -> arroyo_rpc::api_types::connections::Connector {
+        arroyo_rpc::api_types::connections::Connector {
+            id: "iceberg".to_string(),
+            name: "Apache Iceberg".to_string(),
+            icon: "".to_string(),
+            description: "Read snapshots of or append to an Iceberg table".to_string(),
+            enabled: true,
+            source: true,
+            sink: true,
+            testing: true,
+            hidden: false,
+            custom_schemas: true,
+            connection_config: None,
+            table_config: TABLE_SCHEMA.to_owned(),
+        }
+    }
+
+    fn test(
+        &self,
+        _: &str,
+        _: Self::ProfileT,
+        table: Self::TableT,
+        _: Option<&ConnectionSchema>,
+        tx: tokio::sync::mpsc::Sender<TestSourceMessage>,
+    ) {
+        tokio::task::spawn(async move {
+            let result = async {
+                let token = table
+                    .catalog
+                    .token
+                    .as_ref()
+                    .map(|t| t.sub_env_vars())
+                    .transpose()?;
+                let metadata = RestCatalog::connect(
+                    &table.catalog.url,
+                    table.catalog.warehouse.clone(),
+                    token,
+                )
+                .await?
+                .load_table(&table.namespace, &table.table)
+                .await?;
+
+                if let TableType::Sink { .. } = table.table_type {
+                    check_supported(&metadata)?;
+                }
+                anyhow::Ok(())
+            }
+            .await;
+
+            let message = match result {
+                Ok(_) => TestSourceMessage {
+                    error: false,
+                    done: true,
+                    message: "Successfully validated connection".to_string(),
+                },
+                Err(e) => TestSourceMessage {
+                    error: true,
+                    done: true,
+                    message: format!("Failed to validate table: {:?}", e),
+                },
+            };
+            tx.send(message).await.unwrap();
+        });
+    }
+
+    fn table_type(&self, _: Self::ProfileT, table: Self::TableT) -> ConnectionType {
+        match table.table_type {
+            TableType::Source { .. } => ConnectionType::Source,
+            TableType::Sink { .. } => ConnectionType::Sink,
+        }
+    }
+
+    fn is_bounded(&self, _: Self::ProfileT, table: Self::TableT) -> bool {
+        // the source reads a single snapshot of the table
+        matches!(table.table_type, TableType::Source { .. })
+    }
+
+    fn from_config(
+        &self,
+        id: Option<i64>,
+        name: &str,
+        config: Self::ProfileT,
+        table: Self::TableT,
+        schema: Option<&ConnectionSchema>,
+    ) -> anyhow::Result<Connection> {
+        let connection_type = match &table.table_type {
+            TableType::Source { .. } => ConnectionType::Source,
+            TableType::Sink { write_path, .. } => {
+                BackendConfig::parse_url(write_path, true)?;
+                ConnectionType::Sink
+            }
+        };
+
+        let schema = schema
+            .map(|s| s.to_owned())
+            .ok_or_else(|| anyhow!("no schema defined for Iceberg connection"))?;
+
+        let format = schema
+            .format
+            .as_ref()
+            .map(|t| t.to_owned())
+            .unwrap_or_else(|| Format::Parquet(ParquetFormat {}));
+
+        if !matches!(format, Format::Parquet(..)) {
+            bail!("Iceberg tables only support the Parquet format");
+        }
+
+        let description = format!("Iceberg<{}.{}>", table.namespace, table.table);
+
+        let config = OperatorConfig {
+            connection: serde_json::to_value(config).unwrap(),
+            table: serde_json::to_value(table).unwrap(),
+            rate_limit: None,
+            format: Some(format),
+            bad_data: schema.bad_data.clone(),
+            framing: schema.framing.clone(),
+            metadata_fields: schema.metadata_fields(),
+        };
+
+        Ok(Connection {
+            id,
+            connector: self.name(),
+            name: name.to_string(),
+            connection_type,
+            schema,
+            config: serde_json::to_string(&config).unwrap(),
+            description,
+        })
+    }
+
+    fn from_options(
+        &self,
+        name: &str,
+        options: &mut HashMap<String, String>,
+        schema: Option<&ConnectionSchema>,
+        _profile: Option<&ConnectionProfile>,
+    ) -> anyhow::Result<Connection> {
+        // only REST catalogs are supported; Glue and Hive metastores can be used through a REST
+        // catalog that fronts them
+        match options.remove("catalog.type").as_deref() {
+            None | Some("rest") => {}
+            Some(t @ ("glue" | "hive")) => bail!(
+                "'{}' catalogs are not supported by the Iceberg connector; use a REST catalog \
+                 in front of the metastore and set 'catalog.url' to its endpoint",
+                t
+            ),
+            Some(t) => bail!("unknown catalog.type '{}'; only 'rest' is supported", t),
+        }
+
+        let catalog = Catalog {
+            url: pull_opt("catalog.url", options)?,
+            warehouse: options.remove("catalog.warehouse"),
+            token: options.remove("catalog.token").map(VarStr::new),
+        };
+        let namespace = pull_opt("namespace", options)?;
+        let table_name = pull_opt("table", options)?;
+
+        let storage_options: HashMap<String, String> = options
+            .iter()
+            .filter(|(k, _)| k.starts_with("storage."))
+            .map(|(k, v)| (k.trim_start_matches("storage.").to_string(), v.to_string()))
+            .collect();
+        options.retain(|k, _| !k.starts_with("storage."));
+
+        let table_type = match options.remove("type") {
+            Some(t) if t == "source" => TableType::Source {
+                snapshot_id: pull_option_to_i64("snapshot_id", options)?,
+            },
+            Some(t) if t == "sink" => TableType::Sink {
+                write_path: pull_opt("path", options)?,
+                rollover_seconds: pull_option_to_i64("rollover_seconds", options)?,
+                target_file_size: pull_option_to_i64("target_file_size", options)?,
+            },
+            Some(t) => bail!("unknown type: {}", t),
+            None => bail!("must have type set"),
+        };
+
+        let table = IcebergTable {
+            catalog,
+            namespace,
+            table: table_name,
+            storage_options,
+            table_type,
+        };
+
+        self.from_config(None, name, EmptyConfig {}, table, schema)
+    }
+
+    fn make_operator(
+        &self,
+        _: Self::ProfileT,
+        table: Self::TableT,
+        config: OperatorConfig,
+    ) -> anyhow::Result<OperatorNode> {
+        match &table.table_type {
+            TableType::Source { snapshot_id } => {
+                Ok(OperatorNode::from_source(Box::new(IcebergSourceFunc {
+                    snapshot_id: *snapshot_id,
+                    table,
+                    file_states: HashMap::new(),
+                })))
+            }
+            TableType::Sink { write_path, .. } => {
+                let write_path = write_path.clone();
+                let sink_table = Self::sink_table(&table)?;
+                if BackendConfig::parse_url(&write_path, true)?.is_local() {
+                    Ok(OperatorNode::from_operator(Box::new(
+                        LocalParquetFileSystemSink::new(write_path, sink_table, config),
+                    )))
+                } else {
+                    Ok(OperatorNode::from_operator(Box::new(
+                        ParquetFileSystemSink::new(sink_table, config),
+                    )))
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,358 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::time::SystemTime;
+
+use arrow::array::{new_null_array, RecordBatch};
+use arroyo_operator::context::ArrowContext;
+use arroyo_operator::operator::SourceOperator;
+use arroyo_operator::SourceFinishType;
+use arroyo_rpc::grpc::rpc::{StopMode, TableConfig};
+use arroyo_rpc::ControlMessage;
+use arroyo_state::global_table_config;
+use arroyo_state::tables::global_keyed_map::GlobalKeyedView;
+use arroyo_storage::StorageProvider;
+use arroyo_types::{to_nanos, UserError};
+use async_trait::async_trait;
+use datafusion::common::ScalarValue;
+use futures::StreamExt;
+use parquet::arrow::async_reader::ParquetObjectReader;
+use parquet::arrow::ParquetRecordBatchStreamBuilder;
+use tokio::select;
+use tracing::info;
+
+use crate::filesystem::source::FileReadState;
+use crate::iceberg::catalog::RestCatalog;
+use crate::iceberg::manifest::{read_manifest, read_manifest_list, DataFile};
+use crate::iceberg::IcebergTable;
+
+/// Reads the data files of a snapshot of an Iceberg table, then finishes
+pub struct IcebergSourceFunc {
+    pub table: IcebergTable,
+    pub snapshot_id: Option<i64>,
+    pub file_states: HashMap<String, FileReadState>,
+}
+
+#[async_trait]
+impl SourceOperator for IcebergSourceFunc {
+    fn tables(&self) -> HashMap<String, TableConfig> {
+        let mut tables = global_table_config("a", "iceberg");
+        tables.extend(global_table_config("s", "iceberg snapshot being read"));
+        tables
+    }
+
+    fn name(&self) -> String {
+        "Iceberg".to_string()
+    }
+
+    async fn run(&mut self, ctx: &mut ArrowContext) -> SourceFinishType {
+        match self.run_int(ctx).await {
+            Ok(s) => s,
+            Err(e) => {
+                ctx.report_error(e.name.clone(), e.details.clone()).await;
+
+                panic!("{}: {}", e.name, e.details);
+            }
+        }
+    }
+}
+
+impl IcebergSourceFunc {
+    /// Lists the live data files in the snapshot being read, returning the id of that snapshot
+    async fn data_files(&self) -> anyhow::Result<(String, Option<i64>, Vec<DataFile>)> {
+        let token = self
+            .table
+            .catalog
+            .token
+            .as_ref()
+            .map(|t| t.sub_env_vars())
+            .transpose()?;
+        let catalog = RestCatalog::connect(
+            &self.table.catalog.url,
+            self.table.catalog.warehouse.clone(),
+            token,
+        )
+        .await?;
+
+        let metadata = catalog
+            .load_table(&self.table.namespace, &self.table.table)
+            .await?;
+
+        let snapshot = match self.snapshot_id {
+            Some(id) => Some(metadata.snapshot(id).ok_or_else(|| {
+                anyhow::anyhow!(
+                    "snapshot {} does not exist in {}.{}",
+                    id,
+                    self.table.namespace,
+                    self.table.table
+                )
+            })?),
+            None => metadata.current_snapshot(),
+        };
+
+        let Some(snapshot) = snapshot else {
+            // nothing has been written to the table
+            return Ok((metadata.location, None, vec![]));
+        };
+
+        info!(
+            "reading snapshot {} of Iceberg table {}.{}",
+            snapshot.snapshot_id, self.table.namespace, self.table.table
+        );
+
+        let storage_options = self.table.storage_options.clone();
+        let list =
+            StorageProvider::get_url_with_options(&snapshot.manifest_list, storage_options.clone())
+                .await?;
+
+        let mut files = vec![];
+        for manifest in read_manifest_list(&snapshot.manifest_list, &list)? {
+            if manifest.content != 0 {
+                anyhow::bail!(
+                    "table {}.{} has delete files, which are not supported by the Iceberg source",
+                    self.table.namespace,
+                    self.table.table
+                );
+            }
+
+            let bytes = StorageProvider::get_url_with_options(
+                &manifest.manifest_path,
+                storage_options.clone(),
+            )
+            .await?;
+            files.extend(read_manifest(&manifest.manifest_path, &bytes)?);
+        }
+
+        Ok((metadata.location, Some(snapshot.snapshot_id), files))
+    }
+
+    async fn run_int(&mut self, ctx: &mut ArrowContext) -> Result<SourceFinishType, UserError> {
+        let task_index = ctx.task_info.task_index;
+
+        // on restore, keep reading the snapshot we started with, rather than whichever snapshot
+        // is current now, which may have a different set of data files
+        let snapshots: &mut GlobalKeyedView<usize, i64> = ctx
+            .table_manager
+            .get_global_keyed_state("s")
+            .await
+            .expect("should have table");
+        if let Some(id) = snapshots
+            .get(&task_index)
+            .or_else(|| snapshots.get_all().values().next())
+        {
+            info!("restoring Iceberg source at snapshot {}", id);
+            self.snapshot_id = Some(*id);
+        }
+
+        let (location, snapshot_id, files) = self
+            .data_files()
+            .await
+            .map_err(|err| UserError::new("failed to load Iceberg snapshot", err.to_string()))?;
+
+        if let Some(id) = snapshot_id {
+            self.snapshot_id = Some(id);
+            ctx.table_manager
+                .get_global_keyed_state("s")
+                .await
+                .expect("should have table")
+                .insert(task_index, id)
+                .await;
+        }
+
+        let location = location.trim_end_matches('/').to_string();
+        let storage_provider =
+            StorageProvider::for_url_with_options(&location, self.table.storage_options.clone())
+                .await
+                .map_err(|err| {
+                    UserError::new("failed to create storage provider", err.to_string())
+                })?;
+
+        let parallelism = ctx.task_info.parallelism;
+
+        let state: &mut GlobalKeyedView<String, (String, FileReadState)> = ctx
+            .table_manager
+            .get_global_keyed_state("a")
+            .await
+            .expect("should have table");
+        self.file_states = state.get_all().clone().into_values().collect();
+
+        for file in files {
+            // hash the path and modulo by the number of tasks
+            let mut hasher = DefaultHasher::new();
+            file.file_path.hash(&mut hasher);
+            if (hasher.finish() as usize) % parallelism != task_index {
+                continue;
+            }
+
+            if let Some(FileReadState::Finished) = self.file_states.get(&file.file_path) {
+                continue;
+            }
+
+            let Some(key) = file
+                .file_path
+                .strip_prefix(&location)
+                .map(|p| p.trim_start_matches('/'))
+            else {
+                return Err(UserError::new(
+                    "unsupported data file",
+                    format!(
+                        "data file {} is outside of the table location {}",
+                        file.file_path, location
+                    ),
+                ));
+            };
+
+            if let Some(finish_type) = self
+                .read_file(ctx, &storage_provider, key, &file.file_path)
+                .await?
+            {
+                return Ok(finish_type);
+            }
+        }
+
+        info!("Iceberg source finished");
+        Ok(SourceFinishType::Final)
+    }
+
+    async fn read_file(
+        &mut self,
+        ctx: &mut ArrowContext,
+        storage_provider: &StorageProvider,
+        key: &str,
+        file_path: &str,
+    ) -> Result<Option<SourceFinishType>, UserError> {
+        let mut records_read = match self.file_states.get(file_path) {
+            Some(FileReadState::RecordsRead(records_read)) => *records_read,
+            _ => 0,
+        };
+
+        let object_meta = storage_provider
+            .head(key)
+            .await
+            .map_err(|err| UserError::new("could not get object metadata", err.to_string()))?;
+        let object_reader =
+            ParquetObjectReader::new(storage_provider.get_backing_store(), object_meta);
+        let stream = ParquetRecordBatchStreamBuilder::new(object_reader)
+            .await
+            .map_err(|err| {
+                UserError::new(
+                    "could not create parquet record batch stream builder",
+                    format!("path:{}, err:{}", file_path, err),
+                )
+            })?
+            .with_batch_size(8192)
+            .build()
+            .map_err(|err| {
+                UserError::new(
+                    "could not build parquet record batch stream",
+                    err.to_string(),
+                )
+            })?;
+        let mut stream = Box::pin(stream.skip(records_read));
+
+        loop {
+            select! {
+                item = stream.next() => {
+                    match item {
+                        Some(batch) => {
+                            let batch = batch.map_err(|err| UserError::new(
+                                "could not read record batch from stream",
+                                err.to_string(),
+                            ))?;
+                            let batch = Self::to_output(ctx, batch)?;
+                            ctx.collect(batch).await;
+                            records_read += 1;
+                        }
+                        None => {
+                            info!("finished reading file {}", file_path);
+                            self.file_states.insert(file_path.to_string(), FileReadState::Finished);
+                            return Ok(None);
+                        }
+                    }
+                },
+                msg_res = ctx.control_rx.recv() => {
+                    if let Some(control_message) = msg_res {
+                        self.file_states.insert(file_path.to_string(), FileReadState::RecordsRead(records_read));
+                        if let Some(finish_type) = self.process_control_message(ctx, control_message).await {
+                            return Ok(Some(finish_type))
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Projects a batch read from a data file onto the output schema by column name, filling
+    /// in columns that are missing from older files with nulls and adding the timestamp
+    fn to_output(ctx: &ArrowContext, batch: RecordBatch) -> Result<RecordBatch, UserError> {
+        let out_schema = ctx.out_schema.as_ref().unwrap();
+        let current_time =
+            ScalarValue::TimestampNanosecond(Some(to_nanos(SystemTime::now()) as i64), None);
+
+        let columns = out_schema
+            .schema
+            .fields()
+            .iter()
+            .enumerate()
+            .map(|(i, field)| {
+                if i == out_schema.timestamp_index {
+                    current_time.to_array_of_size(batch.num_rows()).unwrap()
+                } else {
+                    batch
+                        .column_by_name(field.name())
+                        .cloned()
+                        .unwrap_or_else(|| new_null_array(field.data_type(), batch.num_rows()))
+                }
+            })
+            .collect();
+
+        RecordBatch::try_new(out_schema.schema.clone(), columns).map_err(|e| {
+            UserError::new(
+                "data does not match schema",
+                format!(
+                    "The Iceberg data file has a schema that does not match the table schema: {:?}",
+                    e
+                ),
+            )
+        })
+    }
+
+    async fn process_control_message(
+        &mut self,
+        ctx: &mut ArrowContext,
+        control_message: ControlMessage,
+    ) -> Option<SourceFinishType> {
+        match control_message {
+            ControlMessage::Checkpoint(c) => {
+                for (file, read_state) in &self.file_states {
+                    ctx.table_manager
+                        .get_global_keyed_state("a")
+                        .await
+                        .unwrap()
+                        .insert(file.clone(), (file.clone(), read_state.clone()))
+                        .await;
+                }
+                if self.start_checkpoint(c, ctx).await {
+                    Some(SourceFinishType::Immediate)
+                } else {
+                    None
+                }
+            }
+            ControlMessage::Stop { mode } => {
+                info!("Stopping Iceberg source {:?}", mode);
+                match mode {
+                    StopMode::Graceful => Some(SourceFinishType::Graceful),
+                    StopMode::Immediate => Some(SourceFinishType::Immediate),
+                }
+            }
+            ControlMessage::Commit { .. } => {
+                unreachable!("sources shouldn't receive commit messages");
+            }
+            ControlMessage::QueryState { query } => {
+                ctx.query_state(query).await;
+                None
+            }
+            _ => None,
+        }
+    }
+}
@@ -68,6 +68,10 @@ impl Connector for ImpulseConnector {
         ConnectionType::Source
     }
 
+    fn is_bounded(&self, _: Self::ProfileT, table: Self::TableT) -> bool {
+        table.message_count.is_some()
+    }
+
     fn get_schema(
         &self,
         _: Self::ProfileT,
@@ -16,6 +16,7 @@ use rdkafka::{
     consumer::{BaseConsumer, Consumer},
     ClientConfig, Message, Offset, TopicPartitionList,
 };
+use regex::Regex;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::borrow::Cow;
@@ -30,12 +31,13 @@ use tonic::Status;
 use tracing::{error, info, warn};
 use typify::import_types;
 
-use crate::{pull_opt, send, ConnectionType};
+use crate::{pull_opt, pull_option_to_u64, send, ConnectionType};
 
 use crate::kafka::sink::KafkaSinkFunc;
 use crate::kafka::source::KafkaSourceFunc;
 use arroyo_operator::connector::Connector;
 use arroyo_operator::operator::OperatorNode;
+use arroyo_operator::two_phase_committer::TwoPhaseCommitterOperator;
 
 mod sink;
 mod source;
@@ -53,6 +55,26 @@ import_types!(
 
 import_types!(schema = "src/kafka/table.json");
 
+/// How often sources check for new partitions to read, unless configured otherwise
+const DEFAULT_DISCOVERY_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Returns the regex that a topic is, if it starts with `^` (the same convention that librdkafka
+/// uses for subscriptions); sources with a topic pattern read every topic that matches it
+pub fn topic_pattern(topic: &str) -> anyhow::Result<Option<Regex>> {
+    if !topic.starts_with('^') {
+        return Ok(None);
+    }
+
+    Ok(Some(Regex::new(topic).map_err(|e| {
+        anyhow!("invalid topic pattern '{}': {}", topic, e)
+    })?))
+}
+
+/// Whether a topic is one of Kafka's own internal topics, which topic patterns never match
+pub fn is_internal_topic(topic: &str) -> bool {
+    matches!(topic, "__consumer_offsets" | "__transaction_state")
+}
+
 impl KafkaTable {
     pub fn subject(&self) -> Cow<str> {
         match &self.value_subject {
@@ -110,6 +132,11 @@ impl KafkaConnector {
                         None | Some("latest") => SourceOffset::Latest,
                         Some(other) => bail!("invalid value for source.offset '{}'", other),
                     },
+                    end_offset: match options.remove("source.end_offset").as_deref() {
+                        Some("latest") => Some(SourceEndOffset::Latest),
+                        None => None,
+                        Some(other) => bail!("invalid value for source.end_offset '{}'", other),
+                    },
                     read_mode: match options.remove("source.read_mode").as_deref() {
                         Some("read_committed") => Some(ReadMode::ReadCommitted),
                         Some("read_uncommitted") | None => Some(ReadMode::ReadUncommitted),
@@ -117,10 +144,15 @@ impl KafkaConnector {
                     },
                     group_id: options.remove("source.group_id"),
                     group_id_prefix: options.remove("source.group_id_prefix"),
+                    discovery_interval_ms: pull_option_to_u64(
+                        "source.discovery_interval_ms",
+                        options,
+                    )?,
                 }
             }
             "sink" => {
-                let commit_mode = options.remove("sink.commit_mode");
+                let commit_mode =
+                    pull_opt_with_alias("sink.commit_mode", "sink.semantics", options)?;
                 TableType::Sink {
                     commit_mode: match commit_mode.as_deref() {
                         Some("at_least_once") | None => SinkCommitMode::AtLeastOnce,
@@ -128,7 +160,13 @@ impl KafkaConnector {
                         Some(other) => bail!("invalid value for commit_mode '{}'", other),
                     },
                     timestamp_field: options.remove("sink.timestamp_field"),
-                    key_field: options.remove("sink.key_field"),
+                    key_field: pull_opt_with_alias("sink.key_field", "key.field", options)?,
+                    traceparent_field: options.remove("sink.traceparent_field"),
+                    header_fields: pull_opt_with_alias(
+                        "sink.header_fields",
+                        "headers.fields",
+                        options,
+                    )?,
                 }
             }
             _ => {
@@ -153,6 +191,31 @@ impl KafkaConnector {
     }
 }
 
+/// Removes an option that may also be set under another name, failing if both are set to
+/// different values
+fn pull_opt_with_alias(
+    name: &str,
+    alias: &str,
+    options: &mut HashMap<String, String>,
+) -> anyhow::Result<Option<String>> {
+    match (options.remove(name), options.remove(alias)) {
+        (Some(value), Some(aliased)) if value != aliased => {
+            bail!("{name} '{value}' conflicts with {alias} '{aliased}'")
+        }
+        (value, aliased) => Ok(value.or(aliased)),
+    }
+}
+
+/// The fields of a comma-separated `header_fields` option
+fn split_header_fields(header_fields: Option<&str>) -> Vec<String> {
+    header_fields
+        .iter()
+        .flat_map(|f| f.split(','))
+        .map(|f| f.trim().to_string())
+        .filter(|f| !f.is_empty())
+        .collect()
+}
+
 impl Connector for KafkaConnector {
     type ProfileT = KafkaConfig;
     type TableT = KafkaTable;
@@ -191,17 +254,40 @@ impl Connector for KafkaConnector {
         schema: Option<&ConnectionSchema>,
     ) -> anyhow::Result<Connection> {
         let (typ, desc) = match table.type_ {
-            TableType::Source { .. } => (
-                ConnectionType::Source,
-                format!("KafkaSource<{}>", table.topic),
-            ),
-            TableType::Sink { .. } => (ConnectionType::Sink, format!("KafkaSink<{}>", table.topic)),
+            TableType::Source { .. } => {
+                topic_pattern(&table.topic)?;
+                (
+                    ConnectionType::Source,
+                    format!("KafkaSource<{}>", table.topic),
+                )
+            }
+            TableType::Sink { .. } => {
+                if table.topic.starts_with('^') {
+                    bail!(
+                        "Kafka sinks must write to a single topic, not a pattern like '{}'",
+                        table.topic
+                    );
+                }
+                (ConnectionType::Sink, format!("KafkaSink<{}>", table.topic))
+            }
         };
 
         let schema = schema
             .map(|s| s.to_owned())
             .ok_or_else(|| anyhow!("No schema defined for Kafka connection"))?;
 
+        // the fields of inferred schemas aren't known until the sink's query is planned, in
+        // which case header fields are checked when the sink starts
+        if let TableType::Sink { header_fields, .. } = &table.type_ {
+            if schema.inferred != Some(true) {
+                for f in split_header_fields(header_fields.as_deref()) {
+                    if !schema.fields.iter().any(|sf| sf.field_name == f) {
+                        bail!("header field '{f}' of Kafka sink {name} is not in its schema");
+                    }
+                }
+            }
+        }
+
         let format = schema
             .format
             .as_ref()
@@ -288,6 +374,49 @@ impl Connector for KafkaConnector {
         Some(rx)
     }
 
+    fn sample(
+        &self,
+        profile: Self::ProfileT,
+        table: Self::TableT,
+        count: usize,
+    ) -> oneshot::Receiver<anyhow::Result<Vec<Vec<u8>>>> {
+        let (tx, rx) = oneshot::channel();
+
+        tokio::spawn(async move {
+            let tester = KafkaTester {
+                connection: profile,
+            };
+
+            let _ = tx.send(tester.sample(table, count).await);
+        });
+
+        rx
+    }
+
+    fn source_partitions(
+        &self,
+        profile: Self::ProfileT,
+        table: Self::TableT,
+    ) -> oneshot::Receiver<anyhow::Result<Option<usize>>> {
+        let (tx, rx) = oneshot::channel();
+
+        tokio::spawn(async move {
+            let tester = KafkaTester {
+                connection: profile,
+            };
+
+            let _ = tx.send(
+                tester
+                    .topic_metadata(&table.topic)
+                    .await
+                    .map(|metadata| Some(metadata.partitions))
+                    .map_err(|e| anyhow!("{}", e.message())),
+            );
+        });
+
+        rx
+    }
+
     fn test(
         &self,
         _: &str,
@@ -308,6 +437,21 @@ impl Connector for KafkaConnector {
         }
     }
 
+    fn is_bounded(&self, _: Self::ProfileT, table: Self::TableT) -> bool {
+        matches!(
+            table.type_,
+            TableType::Source {
+                end_offset: Some(_),
+                ..
+            }
+        )
+    }
+
+    fn seeks_to_time(&self, _: Self::ProfileT, table: Self::TableT) -> bool {
+        // sources find the offsets for a time from the timestamps of the messages
+        matches!(table.type_, TableType::Source { .. })
+    }
+
     fn metadata_defs(&self) -> &'static [MetadataDef] {
         &[
             MetadataDef {
@@ -326,6 +470,18 @@ impl Connector for KafkaConnector {
                 name: "timestamp",
                 data_type: DataType::Int64,
             },
+            MetadataDef {
+                name: "traceparent",
+                data_type: DataType::Utf8,
+            },
+            MetadataDef {
+                name: "key",
+                data_type: DataType::Utf8,
+            },
+            MetadataDef {
+                name: "headers",
+                data_type: DataType::Utf8,
+            },
         ]
     }
 
@@ -359,8 +515,10 @@ impl Connector for KafkaConnector {
             TableType::Source {
                 group_id,
                 offset,
+                end_offset,
                 read_mode,
                 group_id_prefix,
+                discovery_interval_ms,
             } => {
                 let mut client_configs = client_configs(&profile, &table);
                 if let Some(ReadMode::ReadCommitted) = read_mode {
@@ -394,6 +552,7 @@ impl Connector for KafkaConnector {
                     group_id: group_id.clone(),
                     group_id_prefix: group_id_prefix.clone(),
                     offset_mode: *offset,
+                    end_offset: *end_offset,
                     format: config.format.expect("Format must be set for Kafka source"),
                     framing: config.framing,
                     schema_resolver,
@@ -407,27 +566,40 @@ impl Connector for KafkaConnector {
                     )
                     .unwrap(),
                     metadata_fields: config.metadata_fields,
+                    discovery_interval: match discovery_interval_ms {
+                        Some(0) => None,
+                        Some(ms) => Some(Duration::from_millis(*ms)),
+                        None => Some(DEFAULT_DISCOVERY_INTERVAL),
+                    },
                 })))
             }
             TableType::Sink {
                 commit_mode,
                 key_field,
                 timestamp_field,
-            } => Ok(OperatorNode::from_operator(Box::new(KafkaSinkFunc {
-                bootstrap_servers: profile.bootstrap_servers.to_string(),
-                producer: None,
-                consistency_mode: (*commit_mode).into(),
-                timestamp_field: timestamp_field.clone(),
-                timestamp_col: None,
-                key_field: key_field.clone(),
-                key_col: None,
-                write_futures: vec![],
-                client_config: client_configs(&profile, &table),
-                topic: table.topic,
-                serializer: ArrowSerializer::new(
-                    config.format.expect("Format must be defined for KafkaSink"),
-                ),
-            }))),
+                traceparent_field,
+                header_fields,
+            } => Ok(OperatorNode::from_operator(Box::new(
+                TwoPhaseCommitterOperator::new(KafkaSinkFunc {
+                    bootstrap_servers: profile.bootstrap_servers.to_string(),
+                    producer: None,
+                    consistency_mode: (*commit_mode).into(),
+                    timestamp_field: timestamp_field.clone(),
+                    timestamp_col: None,
+                    key_field: key_field.clone(),
+                    key_col: None,
+                    traceparent_field: traceparent_field.clone(),
+                    traceparent_col: None,
+                    header_fields: split_header_fields(header_fields.as_deref()),
+                    header_cols: vec![],
+                    write_futures: vec![],
+                    client_config: client_configs(&profile, &table),
+                    topic: table.topic,
+                    serializer: ArrowSerializer::new(
+                        config.format.expect("Format must be defined for KafkaSink"),
+                    ),
+                }),
+            ))),
         }
     }
 }
@@ -510,7 +682,6 @@ impl KafkaTester {
         .map_err(|_| "unexpected error while connecting to kafka")?
     }
 
-    #[allow(unused)]
     pub async fn topic_metadata(&self, topic: &str) -> Result<TopicMetadata, Status> {
         let client = self
             .connect(None)
@@ -686,7 +857,26 @@ impl KafkaTester {
                 }
             }
             Format::Parquet(_) => {
-                unreachable!()
+                let aschema: ArroyoSchema = schema.clone().into();
+                let mut deserializer =
+                    ArrowDeserializer::new(format.clone(), aschema.clone(), None, BadData::Fail {});
+                let mut builders = aschema.builders();
+
+                let mut error = deserializer
+                    .deserialize_slice(&mut builders, &msg, SystemTime::now(), None)
+                    .await
+                    .into_iter()
+                    .next();
+                if let Some(Err(e)) = deserializer.flush_buffer() {
+                    error.replace(e);
+                }
+
+                if let Some(error) = error {
+                    bail!(
+                        "Failed to parse message as Parquet: {}. Ensure that the format and schema type are correct.",
+                        error.details()
+                    );
+                }
             }
             Format::RawString(_) => {
                 String::from_utf8(msg).map_err(|e|
@@ -717,45 +907,111 @@ impl KafkaTester {
                     );
                 }
             }
-        };
+            Format::ArrowIpc(_) => {
+                let aschema: ArroyoSchema = schema.clone().into();
+                let mut deserializer =
+                    ArrowDeserializer::new(format.clone(), aschema.clone(), None, BadData::Fail {});
+                let mut builders = aschema.builders();
 
-        Ok(())
-    }
+                let mut error = deserializer
+                    .deserialize_slice(&mut builders, &msg, SystemTime::now(), None)
+                    .await
+                    .into_iter()
+                    .next();
+                if let Some(Err(e)) = deserializer.flush_buffer() {
+                    error.replace(e);
+                }
 
-    async fn test(
-        &self,
-        table: KafkaTable,
-        schema: Option<ConnectionSchema>,
-        mut tx: Sender<TestSourceMessage>,
-    ) -> anyhow::Result<()> {
-        let format = schema
-            .as_ref()
-            .and_then(|s| s.format.clone())
-            .ok_or_else(|| anyhow!("No format defined for Kafka connection"))?;
+                if let Some(error) = error {
+                    bail!(
+                        "Failed to parse message as Arrow IPC: {}. Ensure that the format and schema type are correct.",
+                        error.details()
+                    );
+                }
+            }
+            Format::Csv(_) => {
+                let aschema: ArroyoSchema = schema.clone().into();
+                let mut deserializer =
+                    ArrowDeserializer::new(format.clone(), aschema.clone(), None, BadData::Fail {});
+                let mut builders = aschema.builders();
 
-        let client = self
-            .connect(Some(table.clone()))
-            .await
-            .map_err(|e| anyhow!("{}", e))?;
+                let mut error = deserializer
+                    .deserialize_slice(&mut builders, &msg, SystemTime::now(), None)
+                    .await
+                    .into_iter()
+                    .next();
+                if let Some(Err(e)) = deserializer.flush_buffer() {
+                    error.replace(e);
+                }
 
-        self.info(&mut tx, "Connected to Kafka").await;
+                if let Some(error) = error {
+                    bail!(
+                        "Failed to parse message as CSV: {}. Ensure that the format and schema type are correct.",
+                        error.details()
+                    );
+                }
+            }
+            Format::Xml(_) => {
+                let aschema: ArroyoSchema = schema.clone().into();
+                let mut deserializer =
+                    ArrowDeserializer::new(format.clone(), aschema.clone(), None, BadData::Fail {});
+                let mut builders = aschema.builders();
 
-        let topic = table.topic.clone();
+                let mut error = deserializer
+                    .deserialize_slice(&mut builders, &msg, SystemTime::now(), None)
+                    .await
+                    .into_iter()
+                    .next();
+                if let Some(Err(e)) = deserializer.flush_buffer() {
+                    error.replace(e);
+                }
+
+                if let Some(error) = error {
+                    bail!(
+                        "Failed to parse message as XML: {}. Ensure that the format, record path and schema type are correct.",
+                        error.details()
+                    );
+                }
+            }
+        };
+
+        Ok(())
+    }
+
+    /// Assigns all partitions of the topic (or the topics matching the pattern) to the client,
+    /// reading from the beginning
+    fn assign_partitions(&self, client: &BaseConsumer, topic: &str) -> anyhow::Result<()> {
+        let pattern = topic_pattern(topic)?;
 
         let metadata = client
-            .fetch_metadata(Some(&topic), Duration::from_secs(10))
+            .fetch_metadata(pattern.is_none().then_some(topic), Duration::from_secs(10))
             .map_err(|e| anyhow!("Failed to fetch metadata: {:?}", e))?;
 
-        self.info(&mut tx, "Fetched topic metadata").await;
+        let topics: Vec<_> = match &pattern {
+            Some(pattern) => metadata
+                .topics()
+                .iter()
+                .filter(|t| pattern.is_match(t.name()) && !is_internal_topic(t.name()))
+                .collect(),
+            None => metadata.topics().first().into_iter().collect(),
+        };
 
-        {
-            let topic_metadata = metadata.topics().first().ok_or_else(|| {
-                anyhow!(
-                    "Returned metadata was empty; unable to subscribe to topic '{}'",
+        if topics.is_empty() {
+            if pattern.is_some() {
+                bail!(
+                    "No topics in the Kafka cluster match the pattern '{}'",
                     topic
-                )
-            })?;
+                );
+            }
+            bail!(
+                "Returned metadata was empty; unable to subscribe to topic '{}'",
+                topic
+            );
+        }
 
+        let mut map = HashMap::new();
+        for topic_metadata in topics {
+            let topic = topic_metadata.name();
             if let Some(err) = topic_metadata.error() {
                 match err {
                     rdkafka::types::RDKafkaRespErr::RD_KAFKA_RESP_ERR__UNKNOWN_PARTITION
@@ -776,17 +1032,85 @@ impl KafkaTester {
                 }
             }
 
-            let map = topic_metadata
-                .partitions()
-                .iter()
-                .map(|p| ((topic.clone(), p.id()), Offset::Beginning))
-                .collect();
+            map.extend(
+                topic_metadata
+                    .partitions()
+                    .iter()
+                    .map(|p| ((topic.to_string(), p.id()), Offset::Beginning)),
+            );
+        }
 
-            client
-                .assign(&TopicPartitionList::from_topic_map(&map).unwrap())
-                .map_err(|e| anyhow!("Failed to subscribe to topic '{}': {:?}", topic, e))?;
+        client
+            .assign(&TopicPartitionList::from_topic_map(&map).unwrap())
+            .map_err(|e| anyhow!("Failed to subscribe to topic '{}': {:?}", topic, e))?;
+
+        Ok(())
+    }
+
+    /// Reads up to `count` messages from the start of the topic, waiting up to 30 seconds for
+    /// the first and stopping early once no more arrive
+    pub async fn sample(&self, table: KafkaTable, count: usize) -> anyhow::Result<Vec<Vec<u8>>> {
+        let client = self
+            .connect(Some(table.clone()))
+            .await
+            .map_err(|e| anyhow!("{}", e))?;
+        self.assign_partitions(&client, &table.topic)?;
+
+        let mut messages = vec![];
+        let start = Instant::now();
+        let mut last_message = start;
+        let timeout = Duration::from_secs(30);
+        while messages.len() < count && start.elapsed() < timeout {
+            match client.poll(Duration::ZERO) {
+                Some(Ok(message)) => {
+                    if let Some(payload) = message.payload() {
+                        messages.push(payload.to_vec());
+                    }
+                    last_message = Instant::now();
+                }
+                Some(Err(e)) => {
+                    return Err(anyhow!("Error while reading messages from Kafka: {}", e));
+                }
+                None if !messages.is_empty() && last_message.elapsed() > Duration::from_secs(2) => {
+                    break;
+                }
+                None => {
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                }
+            }
         }
 
+        if messages.is_empty() {
+            bail!(
+                "No messages received from Kafka within {} seconds",
+                timeout.as_secs()
+            );
+        }
+
+        Ok(messages)
+    }
+
+    async fn test(
+        &self,
+        table: KafkaTable,
+        schema: Option<ConnectionSchema>,
+        mut tx: Sender<TestSourceMessage>,
+    ) -> anyhow::Result<()> {
+        let format = schema
+            .as_ref()
+            .and_then(|s| s.format.clone())
+            .ok_or_else(|| anyhow!("No format defined for Kafka connection"))?;
+
+        let client = self
+            .connect(Some(table.clone()))
+            .await
+            .map_err(|e| anyhow!("{}", e))?;
+
+        self.info(&mut tx, "Connected to Kafka").await;
+
+        self.assign_partitions(&client, &table.topic)?;
+        self.info(&mut tx, "Fetched topic metadata").await;
+
         if let TableType::Source { .. } = table.type_ {
             self.info(&mut tx, "Waiting for messages").await;
 
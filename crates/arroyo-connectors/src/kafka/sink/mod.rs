@@ -1,28 +1,30 @@
-use anyhow::Result;
+use anyhow::{anyhow, bail, Result};
 use std::borrow::Cow;
 
-use arroyo_rpc::grpc::rpc::{GlobalKeyedTableConfig, TableConfig, TableEnum};
-use arroyo_rpc::{CheckpointEvent, ControlMessage, ControlResp};
 use arroyo_types::*;
 use std::collections::HashMap;
 use std::fmt::{Display, Formatter};
 use tracing::{error, warn};
 
+use rdkafka::message::{Header, OwnedHeaders};
 use rdkafka::producer::{DeliveryFuture, FutureProducer, FutureRecord, Producer};
 use rdkafka::util::Timeout;
 
 use rdkafka::ClientConfig;
 
 use super::SinkCommitMode;
+use crate::{parse_traceparent, TRACEPARENT_HEADER};
 use arrow::array::{Array, AsArray, RecordBatch};
 use arrow::datatypes::{DataType, TimeUnit};
+use arrow::util::display::{ArrayFormatter, FormatOptions};
 use arroyo_formats::ser::ArrowSerializer;
 use arroyo_operator::context::ArrowContext;
-use arroyo_operator::operator::{ArrowOperator, AsDisplayable, DisplayableOperator};
+use arroyo_operator::operator::{AsDisplayable, DisplayableOperator};
+use arroyo_operator::two_phase_committer::TwoPhaseCommitter;
 use arroyo_rpc::df::ArroyoSchema;
-use arroyo_types::CheckpointBarrier;
+use arroyo_rpc::TRACEPARENT_FIELD;
 use async_trait::async_trait;
-use prost::Message;
+use bincode::{Decode, Encode};
 use rdkafka::error::{KafkaError, RDKafkaErrorCode};
 use std::time::{Duration, SystemTime};
 
@@ -37,6 +39,10 @@ pub struct KafkaSinkFunc {
     pub timestamp_col: Option<usize>,
     pub key_field: Option<String>,
     pub key_col: Option<usize>,
+    pub traceparent_field: Option<String>,
+    pub traceparent_col: Option<usize>,
+    pub header_fields: Vec<String>,
+    pub header_cols: Vec<usize>,
     pub producer: Option<FutureProducer>,
     pub write_futures: Vec<DeliveryFuture>,
     pub client_config: HashMap<String, String>,
@@ -47,7 +53,8 @@ pub enum ConsistencyMode {
     AtLeastOnce,
     ExactlyOnce {
         next_transaction_index: usize,
-        producer_to_complete: Option<FutureProducer>,
+        /// the producer of the transaction flushed at the last checkpoint, with its id
+        producer_to_complete: Option<(String, FutureProducer)>,
     },
 }
 
@@ -72,6 +79,31 @@ impl From<SinkCommitMode> for ConsistencyMode {
     }
 }
 
+/// The transaction index of a subtask's producer, from which it resumes on restore
+#[derive(Debug, Clone, Encode, Decode, PartialEq, Eq)]
+pub struct KafkaDataRecovery {
+    task_index: usize,
+    next_transaction_index: usize,
+}
+
+/// A transaction that was flushed at a checkpoint, and is committed once it completes
+#[derive(Debug, Clone, Encode, Decode, PartialEq, Eq)]
+pub struct KafkaPreCommit {
+    transactional_id: String,
+}
+
+fn transactional_id(
+    task_info: &TaskInfo,
+    task_index: usize,
+    topic: &str,
+    transaction_index: usize,
+) -> String {
+    format!(
+        "arroyo-id-{}-{}-{}-{}-{}",
+        task_info.job_id, task_info.operator_id, topic, task_index, transaction_index
+    )
+}
+
 impl KafkaSinkFunc {
     fn is_committing(&self) -> bool {
         matches!(self.consistency_mode, ConsistencyMode::ExactlyOnce { .. })
@@ -125,12 +157,62 @@ impl KafkaSinkFunc {
         }
     }
 
-    fn init_producer(&mut self, task_info: &TaskInfo) -> Result<()> {
+    fn set_traceparent_col(&mut self, schema: &ArroyoSchema) {
+        // the hidden _traceparent column is written as a header whenever it's part of the
+        // input, unless another field is configured
+        let name = self
+            .traceparent_field
+            .as_deref()
+            .unwrap_or(TRACEPARENT_FIELD);
+
+        match schema.schema.field_with_name(name) {
+            Ok(f) if matches!(f.data_type(), DataType::Utf8) => {
+                self.traceparent_col = Some(schema.schema.index_of(f.name()).unwrap());
+            }
+            Ok(f) => {
+                warn!(
+                    "Kafka sink configured with traceparent_field '{name}', but it has type \
+                {}, not TEXT... ignoring",
+                    f.data_type()
+                );
+            }
+            Err(_) if self.traceparent_field.is_some() => {
+                warn!(
+                    "Kafka sink configured with traceparent_field '{name}', but that \
+                does not appear in the schema... ignoring"
+                );
+            }
+            Err(_) => {}
+        }
+    }
+
+    fn set_header_cols(&mut self, schema: &ArroyoSchema) -> Result<()> {
+        self.header_cols = self
+            .header_fields
+            .iter()
+            .map(|f| {
+                schema.schema.index_of(f).map_err(|_| {
+                    anyhow!(
+                        "Kafka sink configured with header field '{f}', but that does not \
+                        appear in the schema"
+                    )
+                })
+            })
+            .collect::<Result<_>>()?;
+        Ok(())
+    }
+
+    fn client_config(&self) -> ClientConfig {
         let mut client_config = ClientConfig::new();
         client_config.set("bootstrap.servers", &self.bootstrap_servers);
         for (key, value) in &self.client_config {
             client_config.set(key, value);
         }
+        client_config
+    }
+
+    fn init_producer(&mut self, task_info: &TaskInfo) -> Result<()> {
+        let mut client_config = self.client_config();
 
         match &mut self.consistency_mode {
             ConsistencyMode::AtLeastOnce => {
@@ -141,15 +223,15 @@ impl KafkaSinkFunc {
                 ..
             } => {
                 client_config.set("enable.idempotence", "true");
-                let transactional_id = format!(
-                    "arroyo-id-{}-{}-{}-{}-{}",
-                    task_info.job_id,
-                    task_info.operator_id,
-                    self.topic,
-                    task_info.task_index,
-                    next_transaction_index
+                client_config.set(
+                    "transactional.id",
+                    transactional_id(
+                        task_info,
+                        task_info.task_index,
+                        &self.topic,
+                        *next_transaction_index,
+                    ),
                 );
-                client_config.set("transactional.id", transactional_id);
                 let producer: FutureProducer = client_config.create()?;
                 producer.init_transactions(Timeout::After(Duration::from_secs(30)))?;
                 producer.begin_transaction()?;
@@ -160,6 +242,17 @@ impl KafkaSinkFunc {
         Ok(())
     }
 
+    /// Ends any transaction left open under the given id, by initializing a producer with it
+    /// which fences off the producer that opened it and aborts its uncommitted writes
+    fn fence(&self, transactional_id: &str) -> Result<()> {
+        let producer: FutureProducer = self
+            .client_config()
+            .set("transactional.id", transactional_id)
+            .create()?;
+        producer.init_transactions(Timeout::After(Duration::from_secs(30)))?;
+        Ok(())
+    }
+
     async fn flush(&mut self, ctx: &mut ArrowContext) {
         self.producer
             .as_ref()
@@ -185,6 +278,7 @@ impl KafkaSinkFunc {
         ts: Option<i64>,
         k: Option<Vec<u8>>,
         v: Vec<u8>,
+        headers: Option<OwnedHeaders>,
         ctx: &mut ArrowContext,
     ) {
         let mut rec = {
@@ -195,6 +289,9 @@ impl KafkaSinkFunc {
             if let Some(k) = k.as_ref() {
                 rec = rec.key(k);
             }
+            if let Some(headers) = headers {
+                rec = rec.headers(headers);
+            }
 
             rec.payload(&v)
         };
@@ -224,7 +321,10 @@ impl KafkaSinkFunc {
 }
 
 #[async_trait]
-impl ArrowOperator for KafkaSinkFunc {
+impl TwoPhaseCommitter for KafkaSinkFunc {
+    type DataRecovery = KafkaDataRecovery;
+    type PreCommit = KafkaPreCommit;
+
     fn name(&self) -> String {
         format!("kafka-producer-{}", self.topic)
     }
@@ -244,40 +344,110 @@ impl ArrowOperator for KafkaSinkFunc {
                     AsDisplayable::Debug(&self.timestamp_field),
                 ),
                 ("key_field", AsDisplayable::Debug(&self.key_field)),
+                (
+                    "traceparent_field",
+                    AsDisplayable::Debug(&self.traceparent_field),
+                ),
+                ("header_fields", AsDisplayable::Debug(&self.header_fields)),
                 ("client_config", AsDisplayable::Debug(&self.client_config)),
             ],
         }
     }
 
-    fn tables(&self) -> HashMap<String, TableConfig> {
-        if self.is_committing() {
-            single_item_hash_map(
-                "i".to_string(),
-                TableConfig {
-                    table_type: TableEnum::GlobalKeyValue.into(),
-                    config: GlobalKeyedTableConfig {
-                        table_name: "i".to_string(),
-                        description: "index for transactional ids".to_string(),
-                        uses_two_phase_commit: true,
-                    }
-                    .encode_to_vec(),
-                },
-            )
-        } else {
-            HashMap::new()
-        }
+    fn uses_two_phase_commit(&self) -> bool {
+        self.is_committing()
     }
 
-    async fn on_start(&mut self, ctx: &mut ArrowContext) {
+    async fn init(
+        &mut self,
+        ctx: &mut ArrowContext,
+        _data_recovery: Vec<Self::DataRecovery>,
+    ) -> Result<()> {
         self.set_timestamp_col(&ctx.in_schemas[0]);
         self.set_key_col(&ctx.in_schemas[0]);
+        self.set_traceparent_col(&ctx.in_schemas[0]);
+        self.set_header_cols(&ctx.in_schemas[0])?;
 
-        self.init_producer(&ctx.task_info)
-            .expect("Producer creation failed");
+        // when restoring, the producer has already been created by `abort`
+        if self.producer.is_none() {
+            self.init_producer(&ctx.task_info)?;
+        }
+        Ok(())
     }
 
-    async fn process_batch(&mut self, batch: RecordBatch, ctx: &mut ArrowContext) {
-        let values = self.serializer.serialize(&batch);
+    async fn abort(
+        &mut self,
+        ctx: &mut ArrowContext,
+        data_recovery: &[Self::DataRecovery],
+    ) -> Result<()> {
+        let ConsistencyMode::ExactlyOnce {
+            next_transaction_index,
+            ..
+        } = &mut self.consistency_mode
+        else {
+            return Ok(());
+        };
+
+        let task_index = ctx.task_info.task_index;
+        let parallelism = ctx.task_info.parallelism;
+
+        let mut to_fence = vec![];
+        for r in data_recovery
+            .iter()
+            .filter(|r| r.task_index % parallelism == task_index)
+        {
+            // the transaction flushed at the checkpoint may not have been committed before the
+            // job stopped. It can't be committed by a new producer (see `commit`), so it's ended
+            // now rather than left open until it times out, holding back read_committed
+            // consumers of the topic.
+            if let Some(precommitted) = r.next_transaction_index.checked_sub(1) {
+                to_fence.push(transactional_id(
+                    &ctx.task_info,
+                    r.task_index,
+                    &self.topic,
+                    precommitted,
+                ));
+            }
+
+            // as are the transactions opened after the checkpoint by subtasks that no longer
+            // exist; our own is fenced by resuming from its index below
+            if r.task_index != task_index {
+                to_fence.push(transactional_id(
+                    &ctx.task_info,
+                    r.task_index,
+                    &self.topic,
+                    r.next_transaction_index,
+                ));
+            }
+        }
+
+        // resume from the index stored in the last checkpoint, so that the first producer
+        // reuses the transactional id of the transaction that was open when the job stopped,
+        // fencing it off and aborting its uncommitted writes
+        if let Some(recovery) = data_recovery.iter().find(|r| r.task_index == task_index) {
+            *next_transaction_index = recovery.next_transaction_index;
+            self.init_producer(&ctx.task_info)?;
+        }
+
+        for id in to_fence {
+            self.fence(&id)?;
+        }
+
+        Ok(())
+    }
+
+    async fn insert_batch(&mut self, batch: RecordBatch, ctx: &mut ArrowContext) -> Result<()> {
+        // the trace context and header fields are written as headers rather than as part of
+        // the payload
+        let values = if self.traceparent_col.is_some() || !self.header_cols.is_empty() {
+            let projection: Vec<_> = (0..batch.num_columns())
+                .filter(|i| Some(*i) != self.traceparent_col && !self.header_cols.contains(i))
+                .collect();
+            self.serializer
+                .serialize(&batch.project(&projection).unwrap())
+        } else {
+            self.serializer.serialize(&batch)
+        };
         let timestamps = batch
             .column(
                 self.timestamp_col
@@ -287,6 +457,27 @@ impl ArrowOperator for KafkaSinkFunc {
             .downcast_ref::<arrow::array::TimestampNanosecondArray>();
 
         let keys = self.key_col.map(|i| batch.column(i).as_string::<i32>());
+        let traceparents = self
+            .traceparent_col
+            .map(|i| batch.column(i).as_string::<i32>());
+
+        let schema = batch.schema();
+        let options = FormatOptions::default();
+        let header_columns: Vec<_> = self
+            .header_cols
+            .iter()
+            .filter_map(|i| {
+                let column = batch.column(*i);
+                let name = schema.field(*i).name();
+                match ArrayFormatter::try_new(column.as_ref(), &options) {
+                    Ok(formatter) => Some((name.as_str(), column, formatter)),
+                    Err(e) => {
+                        warn!("Kafka sink can't write header field '{name}': {e}");
+                        None
+                    }
+                }
+            })
+            .collect();
 
         for (i, v) in values.enumerate() {
             // kafka timestamp as unix millis
@@ -299,84 +490,150 @@ impl ArrowOperator for KafkaSinkFunc {
             });
             // TODO: this copy should be unnecessary but likely needs a custom trait impl
             let key = keys.map(|k| k.value(i).as_bytes().to_vec());
-            self.publish(timestamp, key, v, ctx).await;
+            let mut headers = traceparents
+                .filter(|t| t.is_valid(i))
+                .and_then(|t| parse_traceparent(t.value(i).as_bytes()))
+                .map(|t| {
+                    OwnedHeaders::new().insert(Header {
+                        key: TRACEPARENT_HEADER,
+                        value: Some(t),
+                    })
+                });
+            for (name, column, formatter) in &header_columns {
+                let value = column.is_valid(i).then(|| formatter.value(i).to_string());
+                headers = Some(headers.unwrap_or_else(OwnedHeaders::new).insert(Header {
+                    key: name,
+                    value: value.as_deref(),
+                }));
+            }
+            self.publish(timestamp, key, v, headers, ctx).await;
         }
-    }
 
-    async fn handle_checkpoint(&mut self, _: CheckpointBarrier, ctx: &mut ArrowContext) {
-        self.flush(ctx).await;
-        if let ConsistencyMode::ExactlyOnce {
-            next_transaction_index,
-            producer_to_complete,
-        } = &mut self.consistency_mode
-        {
-            *producer_to_complete = self.producer.take();
-            ctx.table_manager
-                .get_global_keyed_state("i")
-                .await
-                .as_mut()
-                .unwrap()
-                .insert(ctx.task_info.task_index, *next_transaction_index)
-                .await;
-            self.init_producer(&ctx.task_info)
-                .expect("creating new producer during checkpointing");
-        }
+        Ok(())
     }
 
-    async fn handle_commit(
+    async fn commit(
         &mut self,
+        _task_info: &TaskInfo,
         epoch: u32,
-        _commit_data: &HashMap<String, HashMap<u32, Vec<u8>>>,
-        ctx: &mut ArrowContext,
-    ) {
+        pre_commit: Vec<Self::PreCommit>,
+    ) -> Result<()> {
         let ConsistencyMode::ExactlyOnce {
             next_transaction_index: _,
             producer_to_complete,
         } = &mut self.consistency_mode
         else {
             warn!("received commit but consistency mode is not exactly once");
-            return;
+            return Ok(());
         };
 
-        let Some(committing_producer) = producer_to_complete.take() else {
-            unimplemented!("received a commit message without a producer ready to commit. Restoring from commit phase not yet implemented");
+        let committing = producer_to_complete.take();
+
+        // pre-commits without a producer were restored from a checkpoint. Kafka only lets the
+        // producer that opened a transaction commit it, and initializing a new producer with its
+        // transactional id aborts it instead, so these can't be finished here; any that were
+        // still open when the job stopped were ended by `abort` when the sink was restored.
+        for p in &pre_commit {
+            if committing.as_ref().map(|(id, _)| id) != Some(&p.transactional_id) {
+                warn!(
+                    "transaction {} was flushed by a previous run of the sink and can't be \
+                    committed for epoch {}; if it wasn't committed before the sink restarted, \
+                    its writes were aborted",
+                    p.transactional_id, epoch
+                );
+            }
+        }
+
+        let Some((transactional_id, committing_producer)) = committing else {
+            return Ok(());
         };
+
         let mut commits_attempted = 0;
         loop {
-            if committing_producer
-                .commit_transaction(Timeout::After(Duration::from_secs(10)))
-                .is_ok()
-            {
-                break;
-            } else if commits_attempted == 5 {
-                panic!("failed to commit 5 times, giving up");
-            } else {
-                error!("failed to commit {} times, retrying", commits_attempted);
-                commits_attempted += 1;
+            match committing_producer.commit_transaction(Timeout::After(Duration::from_secs(10))) {
+                Ok(()) => break,
+                Err(e) if commits_attempted == 5 => {
+                    bail!(
+                        "failed to commit Kafka transaction {} after {} attempts: {:?}",
+                        transactional_id,
+                        commits_attempted + 1,
+                        e
+                    );
+                }
+                Err(e) => {
+                    error!(
+                        "failed to commit Kafka transaction {} {} times, retrying: {:?}",
+                        transactional_id,
+                        commits_attempted + 1,
+                        e
+                    );
+                    commits_attempted += 1;
+                }
             }
         }
-        let checkpoint_event = ControlResp::CheckpointEvent(CheckpointEvent {
-            checkpoint_epoch: epoch,
-            operator_id: ctx.task_info.operator_id.clone(),
-            subtask_index: ctx.task_info.task_index as u32,
-            time: SystemTime::now(),
-            event_type: arroyo_rpc::grpc::rpc::TaskCheckpointEventType::FinishedCommit,
-        });
-        ctx.control_tx
-            .send(checkpoint_event)
-            .await
-            .expect("sent commit event");
+        Ok(())
     }
 
-    async fn on_close(&mut self, _: &Option<SignalMessage>, ctx: &mut ArrowContext) {
+    async fn checkpoint(
+        &mut self,
+        ctx: &mut ArrowContext,
+        _watermark: Option<SystemTime>,
+        _stopping: bool,
+    ) -> Result<(Self::DataRecovery, HashMap<String, Self::PreCommit>)> {
         self.flush(ctx).await;
+        let task_index = ctx.task_info.task_index;
+        let ConsistencyMode::ExactlyOnce {
+            next_transaction_index,
+            producer_to_complete,
+        } = &mut self.consistency_mode
+        else {
+            return Ok((
+                KafkaDataRecovery {
+                    task_index,
+                    next_transaction_index: 0,
+                },
+                HashMap::new(),
+            ));
+        };
+
+        // the open transaction belongs to the producer created with the previous index
+        let transactional_id = transactional_id(
+            &ctx.task_info,
+            task_index,
+            &self.topic,
+            *next_transaction_index - 1,
+        );
+        let recovery = KafkaDataRecovery {
+            task_index,
+            next_transaction_index: *next_transaction_index,
+        };
+        *producer_to_complete = self.producer.take().map(|p| (transactional_id.clone(), p));
+        self.init_producer(&ctx.task_info)?;
+
+        Ok((
+            recovery,
+            HashMap::from([(
+                transactional_id.clone(),
+                KafkaPreCommit { transactional_id },
+            )]),
+        ))
+    }
+
+    async fn close(&mut self, ctx: &mut ArrowContext) -> Result<()> {
+        self.flush(ctx).await;
+        Ok(())
+    }
+
+    async fn abort_pending(&mut self, _ctx: &mut ArrowContext) -> Result<()> {
+        // only the open transaction is aborted; a pre-committed one is left for the restored
+        // subtask to end
         if !self.is_committing() {
-            return;
+            return Ok(());
         }
-        if let Some(ControlMessage::Commit { epoch, commit_data }) = ctx.control_rx.recv().await {
-            self.handle_commit(epoch, &commit_data, ctx).await;
-        } else {
-            warn!("no commit message received, not committing")
+
+        if let Some(producer) = self.producer.take() {
+            producer.abort_transaction(Timeout::After(Duration::from_secs(10)))?;
         }
+        Ok(())
     }
 }
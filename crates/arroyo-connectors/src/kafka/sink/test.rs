@@ -4,25 +4,31 @@ use std::collections::HashMap;
 use std::sync::Arc;
 use std::time::{Duration, SystemTime};
 
-use arrow::array::{RecordBatch, UInt32Array};
+use arrow::array::{RecordBatch, StringArray, UInt32Array};
 use arrow::datatypes::Field;
 use arrow::datatypes::{DataType, Schema, SchemaRef};
 use arroyo_formats::ser::ArrowSerializer;
+use arroyo_operator::connector::Connector;
 use arroyo_operator::context::ArrowContext;
 use arroyo_operator::operator::ArrowOperator;
+use arroyo_operator::two_phase_committer::{TwoPhaseCommitter, TwoPhaseCommitterOperator};
+use arroyo_rpc::api_types::connections::{ConnectionSchema, SourceField};
 use arroyo_rpc::df::ArroyoSchema;
 use arroyo_rpc::formats::{Format, JsonFormat};
+use arroyo_rpc::OperatorConfig;
 use arroyo_types::CheckpointBarrier;
 use arroyo_types::*;
 use itertools::Itertools;
 use rdkafka::admin::{AdminClient, AdminOptions, NewTopic};
 use rdkafka::consumer::{Consumer, StreamConsumer};
+use rdkafka::message::Headers;
 use rdkafka::producer::Producer;
 use rdkafka::{ClientConfig, Message};
 use serde::Deserialize;
 use tokio::sync::mpsc::channel;
 
 use super::{ConsistencyMode, KafkaSinkFunc};
+use crate::kafka::{KafkaConnector, KafkaTable, TableType};
 
 pub struct KafkaTopicTester {
     topic: String,
@@ -70,11 +76,19 @@ impl KafkaTopicTester {
     }
 
     async fn get_sink_with_writes(&self) -> KafkaSinkWithWrites {
-        let mut kafka = KafkaSinkFunc {
+        self.get_sink_with_headers(schema(), vec![]).await
+    }
+
+    fn sink_func(
+        &self,
+        consistency_mode: ConsistencyMode,
+        header_fields: Vec<String>,
+    ) -> KafkaSinkFunc {
+        KafkaSinkFunc {
             topic: self.topic.to_string(),
             bootstrap_servers: self.server.to_string(),
             producer: None,
-            consistency_mode: ConsistencyMode::AtLeastOnce,
+            consistency_mode,
             timestamp_field: None,
             timestamp_col: None,
             key_field: None,
@@ -82,37 +96,41 @@ impl KafkaTopicTester {
             client_config: HashMap::new(),
             serializer: ArrowSerializer::new(Format::Json(JsonFormat::default())),
             key_col: None,
-        };
-
-        let (_, control_rx) = channel(128);
-        let (command_tx, _) = channel(128);
-
-        let task_info = get_test_task_info();
-
-        let mut ctx = ArrowContext::new(
-            task_info,
-            None,
-            control_rx,
-            command_tx,
-            1,
-            vec![ArroyoSchema::new_unkeyed(schema(), 0)],
-            None,
-            None,
-            vec![vec![]],
-            HashMap::new(),
-        )
-        .await;
+            traceparent_field: None,
+            traceparent_col: None,
+            header_fields,
+            header_cols: vec![],
+        }
+    }
 
+    async fn get_sink_with_headers(
+        &self,
+        schema: SchemaRef,
+        header_fields: Vec<String>,
+    ) -> KafkaSinkWithWrites {
+        let mut kafka = TwoPhaseCommitterOperator::new(
+            self.sink_func(ConsistencyMode::AtLeastOnce, header_fields),
+        );
+        let mut ctx = context(schema, kafka.tables()).await;
         kafka.on_start(&mut ctx).await;
 
         KafkaSinkWithWrites { sink: kafka, ctx }
     }
 
     fn get_consumer(&mut self, job_id: &str) -> StreamConsumer {
+        self.get_consumer_with_isolation(job_id, "read_uncommitted")
+    }
+
+    fn get_consumer_with_isolation(
+        &mut self,
+        job_id: &str,
+        isolation_level: &str,
+    ) -> StreamConsumer {
         let base_consumer: StreamConsumer = ClientConfig::new()
             .set("bootstrap.servers", self.server.to_string())
             .set("enable.auto.commit", "false")
             .set("auto.offset.reset", "earliest")
+            .set("isolation.level", isolation_level)
             // TODO: parameterize group id
             .set("group.id", format!("{}-{}-consumer", job_id, "operator_id"))
             .set("group.instance.id", "0")
@@ -124,6 +142,28 @@ impl KafkaTopicTester {
     }
 }
 
+async fn context(
+    schema: SchemaRef,
+    tables: HashMap<String, arroyo_rpc::grpc::rpc::TableConfig>,
+) -> ArrowContext {
+    let (_, control_rx) = channel(128);
+    let (command_tx, _) = channel(128);
+
+    ArrowContext::new(
+        get_test_task_info(),
+        None,
+        control_rx,
+        command_tx,
+        1,
+        vec![ArroyoSchema::new_unkeyed(schema, 0)],
+        None,
+        None,
+        vec![vec![]],
+        tables,
+    )
+    .await
+}
+
 async fn get_data(consumer: &mut StreamConsumer) -> String {
     let owned_message = consumer
         .recv()
@@ -135,7 +175,7 @@ async fn get_data(consumer: &mut StreamConsumer) -> String {
 }
 
 struct KafkaSinkWithWrites {
-    sink: KafkaSinkFunc,
+    sink: TwoPhaseCommitterOperator<KafkaSinkFunc>,
     ctx: ArrowContext,
 }
 
@@ -157,7 +197,8 @@ async fn test_kafka_checkpoint_flushes() {
         sink_with_writes
             .sink
             .process_batch(batch, &mut sink_with_writes.ctx)
-            .await;
+            .await
+            .unwrap();
     }
     let barrier = CheckpointBarrier {
         epoch: 2,
@@ -168,7 +209,8 @@ async fn test_kafka_checkpoint_flushes() {
     sink_with_writes
         .sink
         .handle_checkpoint(barrier, &mut sink_with_writes.ctx)
-        .await;
+        .await
+        .unwrap();
 
     for message in 1u32..200 {
         let record = get_data(&mut consumer).await;
@@ -195,9 +237,11 @@ async fn test_kafka() {
         sink_with_writes
             .sink
             .process_batch(batch, &mut sink_with_writes.ctx)
-            .await;
+            .await
+            .unwrap();
         sink_with_writes
             .sink
+            .committer()
             .producer
             .as_ref()
             .unwrap()
@@ -208,3 +252,196 @@ async fn test_kafka() {
         assert_eq!(message, result.value);
     }
 }
+
+#[tokio::test]
+async fn test_kafka_restore_while_committing() {
+    let mut kafka_topic_tester = KafkaTopicTester {
+        topic: "arroyo-sink-restore".to_string(),
+        server: "0.0.0.0:9092".to_string(),
+    };
+
+    kafka_topic_tester.create_topic("restore", 1).await;
+    let mut consumer = kafka_topic_tester.get_consumer_with_isolation("3", "read_committed");
+
+    let exactly_once = || ConsistencyMode::ExactlyOnce {
+        next_transaction_index: 0,
+        producer_to_complete: None,
+    };
+    let batch = |values: std::ops::Range<u32>| {
+        RecordBatch::try_new(
+            schema(),
+            vec![Arc::new(UInt32Array::from_iter_values(values))],
+        )
+        .unwrap()
+    };
+
+    let mut ctx = context(schema(), HashMap::new()).await;
+    let mut sink = kafka_topic_tester.sink_func(exactly_once(), vec![]);
+    sink.init(&mut ctx, vec![]).await.unwrap();
+    sink.insert_batch(batch(0..5), &mut ctx).await.unwrap();
+    let (recovery, pre_commits) = sink.checkpoint(&mut ctx, None, false).await.unwrap();
+
+    // the job fails once the checkpoint has completed, but before its transaction is committed,
+    // leaving the transaction open
+    std::mem::forget(sink);
+
+    let mut restored = kafka_topic_tester.sink_func(exactly_once(), vec![]);
+    restored.abort(&mut ctx, &[recovery.clone()]).await.unwrap();
+    restored.init(&mut ctx, vec![recovery]).await.unwrap();
+
+    // the commit is sent again after the restore
+    restored
+        .commit(&ctx.task_info, 1, pre_commits.into_values().collect())
+        .await
+        .unwrap();
+
+    restored
+        .insert_batch(batch(10..15), &mut ctx)
+        .await
+        .unwrap();
+    let (_, pre_commits) = restored.checkpoint(&mut ctx, None, false).await.unwrap();
+    restored
+        .commit(&ctx.task_info, 2, pre_commits.into_values().collect())
+        .await
+        .unwrap();
+
+    // the old transaction was ended on restore, so read_committed consumers don't wait for it to
+    // time out before seeing what was committed after it
+    for value in 10..15 {
+        let record = tokio::time::timeout(Duration::from_secs(10), get_data(&mut consumer))
+            .await
+            .expect("committed rows should be readable");
+        let result: TestData = serde_json::from_str(&record).unwrap();
+        assert_eq!(value, result.value);
+    }
+}
+
+#[tokio::test]
+async fn test_kafka_headers() {
+    let mut kafka_topic_tester = KafkaTopicTester {
+        topic: "arroyo-sink-headers".to_string(),
+        server: "0.0.0.0:9092".to_string(),
+    };
+
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("value", DataType::UInt32, false),
+        Field::new("region", DataType::Utf8, true),
+    ]));
+
+    kafka_topic_tester.create_topic("headers", 1).await;
+    let mut sink_with_writes = kafka_topic_tester
+        .get_sink_with_headers(schema.clone(), vec!["region".to_string()])
+        .await;
+    let mut consumer = kafka_topic_tester.get_consumer("2");
+
+    let batch = RecordBatch::try_new(
+        schema,
+        vec![
+            Arc::new(UInt32Array::from(vec![1, 2])),
+            Arc::new(StringArray::from(vec![Some("us-east-1"), None])),
+        ],
+    )
+    .unwrap();
+
+    sink_with_writes
+        .sink
+        .process_batch(batch, &mut sink_with_writes.ctx)
+        .await
+        .unwrap();
+    sink_with_writes
+        .sink
+        .committer()
+        .producer
+        .as_ref()
+        .unwrap()
+        .flush(Duration::from_secs(3))
+        .unwrap();
+
+    for (value, region) in [(1, Some("us-east-1".as_bytes())), (2, None)] {
+        let message = consumer
+            .recv()
+            .await
+            .expect("shouldn't have errored")
+            .detach();
+
+        // the header field is written as a header rather than to the payload
+        let payload = String::from_utf8(message.payload().unwrap().to_vec()).unwrap();
+        assert_eq!(payload, format!("{{\"value\":{}}}", value));
+
+        let headers = message.headers().expect("message should have headers");
+        assert_eq!(headers.count(), 1);
+        let header = headers.get(0);
+        assert_eq!(header.key, "region");
+        assert_eq!(header.value, region);
+    }
+}
+
+#[test]
+fn test_sink_options() {
+    let connection_schema = |inferred| ConnectionSchema {
+        format: Some(Format::Json(JsonFormat::default())),
+        bad_data: None,
+        framing: None,
+        struct_name: None,
+        fields: if inferred {
+            vec![]
+        } else {
+            vec![
+                SourceField::try_from(Field::new("value", DataType::UInt32, false)).unwrap(),
+                SourceField::try_from(Field::new("region", DataType::Utf8, true)).unwrap(),
+                SourceField::try_from(Field::new("id", DataType::Utf8, false)).unwrap(),
+            ]
+        },
+        definition: None,
+        inferred: Some(inferred),
+    };
+
+    let from_options = |pairs: &[(&str, &str)], inferred| {
+        let mut options: HashMap<String, String> = [
+            ("bootstrap_servers", "localhost:9092"),
+            ("topic", "events"),
+            ("type", "sink"),
+        ]
+        .iter()
+        .chain(pairs)
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+
+        KafkaConnector {}
+            .from_options(
+                "events",
+                &mut options,
+                Some(&connection_schema(inferred)),
+                None,
+            )
+            .map(|connection| {
+                let config: OperatorConfig = serde_json::from_str(&connection.config).unwrap();
+                let table: KafkaTable = serde_json::from_value(config.table).unwrap();
+                table.type_
+            })
+    };
+
+    // the short names are accepted for the key and header fields
+    let TableType::Sink {
+        key_field,
+        header_fields,
+        ..
+    } = from_options(&[("key.field", "id"), ("headers.fields", "region")], false).unwrap()
+    else {
+        panic!("expected a sink");
+    };
+    assert_eq!(key_field.as_deref(), Some("id"));
+    assert_eq!(header_fields.as_deref(), Some("region"));
+
+    assert!(from_options(&[("sink.key_field", "id"), ("key.field", "value")], false).is_err());
+
+    // header fields that aren't in the schema are rejected when the table is created, unless the
+    // schema is inferred from the query
+    let err = from_options(&[("sink.header_fields", "region, missing")], false).unwrap_err();
+    assert!(
+        err.to_string().contains("header field 'missing'"),
+        "{}",
+        err
+    );
+    assert!(from_options(&[("sink.header_fields", "region, missing")], true).is_ok());
+}
@@ -1,6 +1,10 @@
+use crate::kafka::{is_internal_topic, topic_pattern};
+use crate::splits::assign_splits;
+use crate::{parse_traceparent, TRACEPARENT_HEADER};
+use anyhow::anyhow;
 use arroyo_formats::de::FieldValueType;
 use arroyo_rpc::formats::{BadData, Format, Framing};
-use arroyo_rpc::grpc::rpc::TableConfig;
+use arroyo_rpc::grpc::rpc::{SourceLagUnit, TableConfig};
 use arroyo_rpc::schema_resolver::SchemaResolver;
 use arroyo_rpc::{grpc::rpc::StopMode, ControlMessage, ControlResp, MetadataField};
 
@@ -12,11 +16,13 @@ use async_trait::async_trait;
 use bincode::{Decode, Encode};
 use governor::{Quota, RateLimiter as GovernorRateLimiter};
 use rdkafka::consumer::{CommitMode, Consumer, StreamConsumer};
+use rdkafka::message::Headers;
 use rdkafka::{ClientConfig, Message as KMessage, Offset, TopicPartitionList};
-use std::collections::HashMap;
+use regex::Regex;
+use std::collections::{HashMap, HashSet};
 use std::num::NonZeroU32;
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, SystemTime};
 use tokio::select;
 use tokio::time::MissedTickBehavior;
 use tracing::{debug, error, info, warn};
@@ -30,6 +36,7 @@ pub struct KafkaSourceFunc {
     pub group_id: Option<String>,
     pub group_id_prefix: Option<String>,
     pub offset_mode: super::SourceOffset,
+    pub end_offset: Option<super::SourceEndOffset>,
     pub format: Format,
     pub framing: Option<Framing>,
     pub bad_data: Option<BadData>,
@@ -37,6 +44,7 @@ pub struct KafkaSourceFunc {
     pub client_configs: HashMap<String, String>,
     pub messages_per_second: NonZeroU32,
     pub metadata_fields: Vec<MetadataField>,
+    pub discovery_interval: Option<Duration>,
 }
 
 #[derive(Copy, Clone, Debug, Encode, Decode, PartialEq, PartialOrd)]
@@ -45,8 +53,90 @@ pub struct KafkaState {
     offset: i64,
 }
 
+/// Parses an offset override for a partition, which is either the offset of the next message to
+/// read, or `earliest` or `latest`
+fn parse_offset_override(partition: &str, offset: &str) -> anyhow::Result<(i32, Offset)> {
+    let partition = partition
+        .parse()
+        .map_err(|_| anyhow!("invalid Kafka partition '{}' in offset override", partition))?;
+
+    let offset = match offset {
+        "earliest" => Offset::Beginning,
+        "latest" => Offset::End,
+        offset => Offset::Offset(offset.parse().map_err(|_| {
+            anyhow!(
+                "invalid offset '{}' for Kafka partition {} in offset override",
+                offset,
+                partition
+            )
+        })?),
+    };
+
+    Ok((partition, offset))
+}
+
+/// A hash of a topic name that's the same in every process (unlike the std hasher)
+fn topic_hash(topic: &str) -> usize {
+    // FNV-1a
+    topic.bytes().fold(0xcbf29ce484222325u64, |hash, b| {
+        (hash ^ b as u64).wrapping_mul(0x100000001b3)
+    }) as usize
+}
+
+/// The subtask that reads a partition when there's no lag to balance. The partitions of each
+/// topic are assigned round-robin; for topic patterns, each topic starts from a different subtask
+/// so that topics with few partitions aren't all read by the first ones. This only depends on the
+/// partition, so every subtask agrees on the owner of a partition whenever they discover it.
+fn partition_owner(topic: &str, partition: i32, pattern: bool, parallelism: usize) -> usize {
+    let start = if pattern { topic_hash(topic) } else { 0 };
+    start.wrapping_add(partition.max(0) as usize) % parallelism.max(1)
+}
+
+/// Records the offset of the latest message read from a partition
+fn record_offset(
+    offsets: &mut HashMap<String, HashMap<i32, i64>>,
+    topic: &str,
+    partition: i32,
+    offset: i64,
+) {
+    match offsets.get_mut(topic) {
+        Some(partitions) => {
+            partitions.insert(partition, offset);
+        }
+        None => {
+            offsets.insert(topic.to_string(), HashMap::from([(partition, offset)]));
+        }
+    }
+}
+
+/// Encodes the headers of a message as a JSON object of their names to their values, which are
+/// decoded as UTF-8 (or null for headers without a value). If a header appears more than once,
+/// its last value is kept.
+fn headers_json(headers: &impl Headers) -> String {
+    let headers: serde_json::Map<_, _> = headers
+        .iter()
+        .map(|h| {
+            (
+                h.key.to_string(),
+                h.value
+                    .map(|v| serde_json::Value::String(String::from_utf8_lossy(v).into_owned()))
+                    .unwrap_or_default(),
+            )
+        })
+        .collect();
+    serde_json::Value::Object(headers).to_string()
+}
+
 impl KafkaSourceFunc {
-    async fn get_consumer(&mut self, ctx: &mut ArrowContext) -> anyhow::Result<StreamConsumer> {
+    async fn get_consumer(
+        &mut self,
+        ctx: &mut ArrowContext,
+        pattern: Option<&Regex>,
+    ) -> anyhow::Result<(
+        StreamConsumer,
+        HashMap<(String, i32), Offset>,
+        HashSet<(String, i32)>,
+    )> {
         info!("Creating kafka consumer for {}", self.bootstrap_servers);
         let mut client_config = ClientConfig::new();
 
@@ -81,43 +171,106 @@ impl KafkaSourceFunc {
             .set("group.id", group_id)
             .create()?;
 
-        let state: Vec<_> = ctx
+        // the offset of the next message to read in each partition we've restored; sources
+        // with a topic pattern key their state by topic as well as partition
+        let state: HashMap<(String, i32), i64> = if pattern.is_some() {
+            ctx.table_manager
+                .get_global_keyed_state::<(String, i32), i64>("t")
+                .await?
+                .get_all()
+                .clone()
+        } else {
+            ctx.table_manager
+                .get_global_keyed_state::<i32, KafkaState>("k")
+                .await?
+                .get_all()
+                .values()
+                .map(|s| ((self.topic.clone(), s.partition), s.offset))
+                .collect()
+        };
+
+        // did we restore any partitions?
+        let has_state = !state.is_empty();
+
+        let lag: HashMap<(String, i32), u64> = ctx
             .table_manager
-            .get_global_keyed_state::<i32, KafkaState>("k")
+            .get_global_keyed_state::<i32, u64>("l")
             .await?
             .get_all()
-            .values()
+            .iter()
+            .map(|(partition, lag)| ((self.topic.clone(), *partition), *lag))
             .collect();
 
-        // did we restore any partitions?
-        let has_state = !state.is_empty();
-
-        let state: HashMap<i32, KafkaState> = state.iter().map(|s| (s.partition, **s)).collect();
-        let metadata = consumer.fetch_metadata(Some(&self.topic), Duration::from_secs(30))?;
+        // offsets overridden while the job was stopped take precedence over restored ones; for
+        // topic patterns, the partition is given as `{topic}:{partition}`
+        let overrides = ctx
+            .source_offset_overrides
+            .iter()
+            .map(|(key, offset)| {
+                let (topic, partition) = match key.rsplit_once(':') {
+                    Some((topic, partition)) => (topic.to_string(), partition),
+                    None => (self.topic.clone(), key.as_str()),
+                };
+                let (partition, offset) = parse_offset_override(partition, offset)?;
+                Ok(((topic, partition), offset))
+            })
+            .collect::<anyhow::Result<HashMap<(String, i32), Offset>>>()?;
+
+        let partitions = self.fetch_partitions(&consumer, pattern)?;
 
         info!("Fetched metadata for topic {}", self.topic);
 
+        // the stream of a hybrid table starts at the first message at or after the switch point,
+        // unless we've restored where it got to
+        let start_at_offsets = match ctx.source_start_at {
+            Some(start_at) if !has_state => {
+                Some(Self::offsets_for_time(&consumer, &partitions, start_at)?)
+            }
+            _ => None,
+        };
+
         let our_partitions: HashMap<_, _> = {
-            let partitions = metadata.topics()[0].partitions();
+            let parallelism = ctx.task_info.parallelism;
+
+            // if we know how far behind each partition was as of the checkpoint we restored from,
+            // we spread that backlog evenly across our subtasks
+            let assignment = if lag.is_empty() {
+                partitions
+                    .iter()
+                    .map(|(topic, partition)| {
+                        (
+                            (topic.clone(), *partition),
+                            partition_owner(topic, *partition, pattern.is_some(), parallelism),
+                        )
+                    })
+                    .collect()
+            } else {
+                assign_splits(partitions.iter().cloned(), &lag, parallelism)
+            };
+
             partitions
                 .iter()
-                .enumerate()
-                .filter(|(i, _)| i % ctx.task_info.parallelism == ctx.task_info.task_index)
-                .map(|(_, p)| {
-                    let offset = state
-                        .get(&p.id())
-                        .map(|s| Offset::Offset(s.offset))
+                .filter(|p| assignment.get(*p) == Some(&ctx.task_info.task_index))
+                .map(|p| {
+                    let offset = overrides
+                        .get(p)
+                        .copied()
+                        .or_else(|| state.get(p).map(|offset| Offset::Offset(*offset)))
                         .unwrap_or_else(|| {
                             if has_state {
                                 // if we've restored partitions and we don't know about this one, that means it's
                                 // new, and we want to start from the beginning so we don't drop data
                                 Offset::Beginning
+                            } else if let Some(offset) =
+                                start_at_offsets.as_ref().and_then(|o| o.get(p))
+                            {
+                                *offset
                             } else {
                                 self.offset_mode.get_offset()
                             }
                         });
 
-                    ((self.topic.clone(), p.id()), offset)
+                    (p.clone(), offset)
                 })
                 .collect()
         };
@@ -131,17 +284,218 @@ impl KafkaSourceFunc {
 
         consumer.assign(&topic_partitions)?;
 
-        Ok(consumer)
+        Ok((consumer, our_partitions, partitions.into_iter().collect()))
+    }
+
+    /// Fetches the partitions of the topic the source reads or, for a topic pattern, of every
+    /// topic that matches it
+    fn fetch_partitions(
+        &self,
+        consumer: &StreamConsumer,
+        pattern: Option<&Regex>,
+    ) -> anyhow::Result<Vec<(String, i32)>> {
+        let metadata = consumer.fetch_metadata(
+            pattern.is_none().then_some(self.topic.as_str()),
+            Duration::from_secs(30),
+        )?;
+
+        let mut partitions: Vec<_> = metadata
+            .topics()
+            .iter()
+            .filter(|t| match pattern {
+                Some(pattern) => pattern.is_match(t.name()) && !is_internal_topic(t.name()),
+                None => t.name() == self.topic,
+            })
+            .flat_map(|t| {
+                t.partitions()
+                    .iter()
+                    .map(|p| (t.name().to_string(), p.id()))
+            })
+            .collect();
+        partitions.sort();
+
+        Ok(partitions)
+    }
+
+    /// Finds the offset of the first message with a timestamp at or after `time` in each
+    /// partition, or the end of the partition if there isn't one yet
+    fn offsets_for_time(
+        consumer: &StreamConsumer,
+        partitions: &[(String, i32)],
+        time: SystemTime,
+    ) -> anyhow::Result<HashMap<(String, i32), Offset>> {
+        let mut tpl = TopicPartitionList::new();
+        for (topic, partition) in partitions {
+            tpl.add_partition_offset(topic, *partition, Offset::Offset(to_millis(time) as i64))?;
+        }
+
+        Ok(consumer
+            .offsets_for_times(tpl, Duration::from_secs(30))?
+            .elements()
+            .iter()
+            .map(|elem| {
+                let offset = match elem.offset() {
+                    Offset::Offset(offset) => Offset::Offset(offset),
+                    _ => Offset::End,
+                };
+                ((elem.topic().to_string(), elem.partition()), offset)
+            })
+            .collect())
+    }
+
+    /// Starts reading any partitions that have appeared since we last looked and that belong
+    /// to this subtask, from the configured offset (or for the stream of a hybrid table, from
+    /// its start time). Partitions that were already being read keep their subtask, so this
+    /// never needs a restart.
+    fn discover_partitions(
+        &self,
+        ctx: &ArrowContext,
+        consumer: &StreamConsumer,
+        pattern: Option<&Regex>,
+        known_partitions: &mut HashSet<(String, i32)>,
+    ) -> anyhow::Result<()> {
+        let mut new_partitions = TopicPartitionList::new();
+        for (topic, partition) in self.fetch_partitions(consumer, pattern)? {
+            if known_partitions.contains(&(topic.clone(), partition)) {
+                continue;
+            }
+
+            let owner = partition_owner(
+                &topic,
+                partition,
+                pattern.is_some(),
+                ctx.task_info.parallelism,
+            );
+            if owner == ctx.task_info.task_index {
+                // the stream of a hybrid table reads new partitions from the switch point too
+                let offset = match ctx.source_start_at {
+                    Some(start_at) => {
+                        Self::offsets_for_time(consumer, &[(topic.clone(), partition)], start_at)?
+                            .remove(&(topic.clone(), partition))
+                            .unwrap_or(Offset::End)
+                    }
+                    None => self.offset_mode.get_offset(),
+                };
+                new_partitions.add_partition_offset(&topic, partition, offset)?;
+            }
+            known_partitions.insert((topic, partition));
+        }
+
+        if new_partitions.count() > 0 {
+            info!(
+                "Kafka source {}-{} discovered new partitions: {:?}",
+                ctx.task_info.operator_id, ctx.task_info.task_index, new_partitions
+            );
+            consumer.incremental_assign(&new_partitions)?;
+        }
+
+        Ok(())
+    }
+
+    /// Finds the offset (exclusive) that each of our partitions should be read up to for a
+    /// bounded source, omitting partitions that have nothing left to read. The backfill of a
+    /// hybrid table reads up to the first message at or after `end_at`; if a partition has no
+    /// such message yet and `end_at` is still to come, it's read until one arrives.
+    fn fetch_end_offsets(
+        &self,
+        consumer: &StreamConsumer,
+        start_offsets: &HashMap<(String, i32), Offset>,
+        end_at: Option<SystemTime>,
+    ) -> anyhow::Result<HashMap<(String, i32), i64>> {
+        let mut end_offsets = HashMap::new();
+
+        let end_at_offsets = match end_at {
+            Some(end_at) => {
+                let partitions: Vec<_> = start_offsets.keys().cloned().collect();
+                Some(Self::offsets_for_time(consumer, &partitions, end_at)?)
+            }
+            None => None,
+        };
+
+        for ((topic, partition), start) in start_offsets {
+            let (low, high) =
+                consumer.fetch_watermarks(topic, *partition, Duration::from_secs(30))?;
+
+            let start = match start {
+                Offset::Offset(offset) => *offset,
+                Offset::Beginning => low,
+                Offset::Stored => {
+                    let mut tpl = TopicPartitionList::new();
+                    tpl.add_partition(topic, *partition);
+                    match consumer
+                        .committed_offsets(tpl, Duration::from_secs(30))?
+                        .find_partition(topic, *partition)
+                        .map(|p| p.offset())
+                    {
+                        Some(Offset::Offset(offset)) => offset,
+                        // without a committed offset the consumer starts from the end
+                        _ => high,
+                    }
+                }
+                _ => high,
+            };
+
+            let key = (topic.clone(), *partition);
+            let end = match (end_at, end_at_offsets.as_ref().and_then(|o| o.get(&key))) {
+                (Some(_), Some(Offset::Offset(offset))) => *offset,
+                (Some(end_at), _) if end_at > SystemTime::now() => i64::MAX,
+                _ => high,
+            };
+
+            // a source with both an end offset and an end time finishes at whichever is first
+            let end = if end_at.is_some() && self.end_offset.is_some() {
+                end.min(high)
+            } else {
+                end
+            };
+
+            if start < end {
+                end_offsets.insert(key, end);
+            }
+        }
+
+        Ok(end_offsets)
     }
 
     async fn run_int(&mut self, ctx: &mut ArrowContext) -> Result<SourceFinishType, UserError> {
-        let consumer = self
-            .get_consumer(ctx)
+        let pattern = topic_pattern(&self.topic)
+            .map_err(|e| UserError::new("Invalid Kafka topic pattern", e.to_string()))?;
+
+        let (consumer, start_offsets, mut known_partitions) = self
+            .get_consumer(ctx, pattern.as_ref())
             .await
             .map_err(|e| UserError::new("Could not create Kafka consumer", format!("{:?}", e)))?;
 
+        // for bounded sources (including the backfills of hybrid tables), the partitions we
+        // have yet to finish reading
+        let end_at = ctx.source_end_at;
+        let mut end_offsets = match (self.end_offset.as_ref(), end_at) {
+            (None, None) => None,
+            _ => {
+                let end_offsets = self
+                    .fetch_end_offsets(&consumer, &start_offsets, end_at)
+                    .map_err(|e| {
+                        UserError::new("Could not fetch Kafka end offsets", format!("{:?}", e))
+                    })?;
+                info!(
+                    "end offsets for {}-{}: {:?}",
+                    self.topic, ctx.task_info.task_index, end_offsets
+                );
+                Some(end_offsets)
+            }
+        };
+
+        if end_offsets.as_ref().is_some_and(|e| e.is_empty()) {
+            info!(
+                "Kafka source {}-{} has no messages to read before its end offsets",
+                ctx.task_info.operator_id, ctx.task_info.task_index
+            );
+            return Ok(SourceFinishType::Final);
+        }
+
         let rate_limiter = GovernorRateLimiter::direct(Quota::per_second(self.messages_per_second));
-        let mut offsets = HashMap::new();
+        // the offset of the latest message read from each partition, by topic
+        let mut offsets: HashMap<String, HashMap<i32, i64>> = HashMap::new();
 
         if consumer.assignment().unwrap().count() == 0 {
             warn!("Kafka Consumer {}-{} is subscribed to no partitions, as there are more subtasks than partitions... setting idle",
@@ -170,6 +524,13 @@ impl KafkaSourceFunc {
         let mut flush_ticker = tokio::time::interval(Duration::from_millis(50));
         flush_ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
 
+        // bounded sources only read the partitions that existed when they started
+        let discovery_interval = self.discovery_interval.filter(|_| end_offsets.is_none());
+        let period = discovery_interval.unwrap_or(Duration::from_secs(60));
+        let mut discovery_ticker =
+            tokio::time::interval_at(tokio::time::Instant::now() + period, period);
+        discovery_ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
         loop {
             select! {
                 message = consumer.recv() => {
@@ -182,6 +543,33 @@ impl KafkaSourceFunc {
 
                                 let topic = msg.topic();
 
+                                if let Some(end_offsets) = &mut end_offsets {
+                                    // a bounded source stops reading a partition once it gets to
+                                    // its end, which for a backfill is the first message at or
+                                    // after the switch point
+                                    let partition = (topic.to_string(), msg.partition());
+                                    let past_end = !end_offsets
+                                        .get(&partition)
+                                        .is_some_and(|end| msg.offset() < *end)
+                                        || end_at.is_some_and(|end_at| timestamp >= to_millis(end_at) as i64);
+                                    if past_end {
+                                        if end_offsets.remove(&partition).is_some() {
+                                            let mut tpl = TopicPartitionList::new();
+                                            tpl.add_partition(topic, msg.partition());
+                                            consumer.pause(&tpl).map_err(|e| {
+                                                UserError::new("Could not pause Kafka partition", e.to_string())
+                                            })?;
+                                        }
+                                        continue;
+                                    }
+                                }
+
+                                let key = msg.key().map(String::from_utf8_lossy);
+                                let headers = self.metadata_fields.iter()
+                                    .any(|f| f.key == "headers")
+                                    .then(|| msg.headers().map(headers_json))
+                                    .flatten();
+
                                 let connector_metadata = if !self.metadata_fields.is_empty() {
                                     let mut connector_metadata = HashMap::new();
                                     for f in &self.metadata_fields {
@@ -190,6 +578,12 @@ impl KafkaSourceFunc {
                                             "partition" => FieldValueType::Int32(msg.partition()),
                                             "topic" => FieldValueType::String(topic),
                                             "timestamp" => FieldValueType::Int64(timestamp),
+                                            "key" => FieldValueType::OptionalString(key.as_deref()),
+                                            "headers" => FieldValueType::OptionalString(headers.as_deref()),
+                                            "traceparent" => FieldValueType::OptionalString(msg.headers()
+                                                .and_then(|h| h.iter().find(|h| h.key == TRACEPARENT_HEADER))
+                                                .and_then(|h| h.value)
+                                                .and_then(parse_traceparent)),
                                             k => unreachable!("Invalid metadata key '{}'", k),
                                         });
                                     }
@@ -205,7 +599,7 @@ impl KafkaSourceFunc {
                                     ctx.flush_buffer().await?;
                                 }
 
-                                offsets.insert(msg.partition(), msg.offset());
+                                record_offset(&mut offsets, topic, msg.partition(), msg.offset());
                                 rate_limiter.until_ready().await;
                             }
                         },
@@ -218,22 +612,95 @@ impl KafkaSourceFunc {
                     if ctx.should_flush() {
                         ctx.flush_buffer().await?;
                     }
+
+                    if let Some(end_offsets) = &mut end_offsets {
+                        // the consumer's position skips over control records (like transaction
+                        // markers), which are never delivered as messages
+                        let position = consumer.position().map_err(|e| {
+                            UserError::new("Could not fetch Kafka consumer position", e.to_string())
+                        })?;
+                        for elem in position.elements() {
+                            let key = (elem.topic().to_string(), elem.partition());
+                            if let (Offset::Offset(offset), Some(end)) =
+                                (elem.offset(), end_offsets.get(&key))
+                            {
+                                if offset >= *end {
+                                    end_offsets.remove(&key);
+                                }
+                            }
+                        }
+
+                        if end_offsets.is_empty() {
+                            info!(
+                                "Kafka source {}-{} has read up to its end offsets",
+                                ctx.task_info.operator_id, ctx.task_info.task_index
+                            );
+                            ctx.flush_buffer().await?;
+                            return Ok(SourceFinishType::Final);
+                        }
+                    }
+                }
+                _ = discovery_ticker.tick(), if discovery_interval.is_some() => {
+                    if let Err(e) = self.discover_partitions(ctx, &consumer, pattern.as_ref(), &mut known_partitions) {
+                        warn!("Failed to discover new Kafka partitions: {:?}", e);
+                    }
                 }
                 control_message = ctx.control_rx.recv() => {
                     match control_message {
                         Some(ControlMessage::Checkpoint(c)) => {
                             debug!("starting checkpointing {}", ctx.task_info.task_index);
                             let mut topic_partitions = TopicPartitionList::new();
-                            let s = ctx.table_manager.get_global_keyed_state("k").await
+                            if pattern.is_some() {
+                                let s = ctx.table_manager.get_global_keyed_state("t").await
+                                    .map_err(|err| UserError::new("failed to get global key value", err.to_string()))?;
+                                for (topic, partitions) in &offsets {
+                                    for (partition, offset) in partitions {
+                                        s.insert((topic.clone(), *partition), *offset + 1).await;
+                                    }
+                                }
+                            } else {
+                                let s = ctx.table_manager.get_global_keyed_state("k").await
+                                    .map_err(|err| UserError::new("failed to get global key value", err.to_string()))?;
+                                for partitions in offsets.values() {
+                                    for (partition, offset) in partitions {
+                                        s.insert(*partition, KafkaState {
+                                            partition: *partition,
+                                            offset: *offset + 1,
+                                        }).await;
+                                    }
+                                }
+                            }
+                            for (topic, partitions) in &offsets {
+                                for (partition, offset) in partitions {
+                                    topic_partitions.add_partition_offset(
+                                        topic, *partition, Offset::Offset(*offset)).unwrap();
+                                }
+                            }
+
+                            // record how far behind each of our partitions is, so that they can
+                            // be rebalanced across subtasks if some of them fall behind (which
+                            // is only done for single topics)
+                            let lag_state = ctx.table_manager.get_global_keyed_state("l").await
                                 .map_err(|err| UserError::new("failed to get global key value", err.to_string()))?;
-                            for (partition, offset) in &offsets {
-                                s.insert(*partition, KafkaState {
-                                    partition: *partition,
-                                    offset: *offset + 1,
-                                }).await;
-                                topic_partitions.add_partition_offset(
-                                    &self.topic, *partition, Offset::Offset(*offset)).unwrap();
+                            let mut total_lag = 0u64;
+                            let mut split_lag = HashMap::new();
+                            for (topic, partitions) in &offsets {
+                                for (partition, offset) in partitions {
+                                    // this uses the high watermark from the most recent fetch, so it
+                                    // doesn't need to make a request to the broker
+                                    if let Ok((_, high)) = consumer.get_watermark_offsets(topic, *partition) {
+                                        let lag = (high - *offset - 1).max(0) as u64;
+                                        // only the partitions of a single topic are assigned
+                                        // by lag
+                                        if pattern.is_none() {
+                                            lag_state.insert(*partition, lag).await;
+                                            split_lag.insert(partition.to_string(), lag);
+                                        }
+                                        total_lag += lag;
+                                    }
+                                }
                             }
+                            ctx.report_source_lag(total_lag, split_lag, SourceLagUnit::Messages).await;
 
                             if let Err(e) = consumer.commit(&topic_partitions, CommitMode::Async) {
                                 // This is just used for progress tracking for metrics, so it's not a fatal error if it
@@ -262,6 +729,9 @@ impl KafkaSourceFunc {
                         Some(ControlMessage::LoadCompacted {compacted}) => {
                             ctx.load_compacted(compacted).await;
                         }
+                        Some(ControlMessage::QueryState { query }) => {
+                            ctx.query_state(query).await;
+                        }
                         Some(ControlMessage::NoOp) => {}
                         None => {
 
@@ -299,6 +769,15 @@ impl SourceOperator for KafkaSourceFunc {
     }
 
     fn tables(&self) -> HashMap<String, TableConfig> {
-        arroyo_state::global_table_config("k", "kafka offsets")
+        let mut tables = arroyo_state::global_table_config("k", "kafka offsets");
+        tables.extend(arroyo_state::global_table_config(
+            "l",
+            "kafka partition lag",
+        ));
+        tables.extend(arroyo_state::global_table_config(
+            "t",
+            "kafka offsets by topic",
+        ));
+        tables
     }
 }
@@ -15,18 +15,21 @@ use std::time::{Duration, SystemTime};
 use crate::kafka::SourceOffset;
 use arroyo_operator::context::{batch_bounded, ArrowContext, BatchReceiver};
 use arroyo_operator::operator::SourceOperator;
+use arroyo_operator::SourceFinishType;
 use arroyo_rpc::df::ArroyoSchema;
 use arroyo_rpc::formats::{Format, RawStringFormat};
 use arroyo_rpc::grpc::rpc::{CheckpointMetadata, OperatorCheckpointMetadata, OperatorMetadata};
 use arroyo_rpc::{CheckpointCompleted, ControlMessage, ControlResp, MetadataField};
 use arroyo_types::{
-    single_item_hash_map, to_micros, ArrowMessage, CheckpointBarrier, SignalMessage, TaskInfo,
+    single_item_hash_map, to_micros, to_millis, ArrowMessage, CheckpointBarrier, SignalMessage,
+    TaskInfo, KEY_GROUPS,
 };
 use rdkafka::admin::{AdminClient, AdminOptions, NewTopic};
 use rdkafka::producer::{BaseProducer, BaseRecord};
 use rdkafka::ClientConfig;
 use serde::{Deserialize, Serialize};
 use tokio::sync::mpsc::{channel, Receiver, Sender};
+use tokio::task::JoinHandle;
 
 use super::KafkaSourceFunc;
 
@@ -74,6 +77,8 @@ impl KafkaTopicTester {
         &self,
         task_info: TaskInfo,
         restore_from: Option<u32>,
+        start_at: Option<SystemTime>,
+        end_at: Option<SystemTime>,
     ) -> KafkaSourceWithReads {
         let mut kafka = Box::new(KafkaSourceFunc {
             bootstrap_servers: self.server.clone(),
@@ -81,6 +86,7 @@ impl KafkaTopicTester {
             group_id: self.group_id.clone(),
             group_id_prefix: None,
             offset_mode: SourceOffset::Earliest,
+            end_offset: None,
             format: Format::RawString(RawStringFormat {}),
             framing: None,
             bad_data: None,
@@ -88,6 +94,7 @@ impl KafkaTopicTester {
             client_configs: HashMap::new(),
             messages_per_second: NonZeroU32::new(100).unwrap(),
             metadata_fields: vec![],
+            discovery_interval: None,
         });
 
         let (to_control_tx, control_rx) = channel(128);
@@ -126,14 +133,15 @@ impl KafkaTopicTester {
             kafka.tables(),
         )
         .await;
+        ctx.source_start_at = start_at;
+        ctx.source_end_at = end_at;
 
-        tokio::spawn(async move {
-            kafka.run(&mut ctx).await;
-        });
+        let handle = tokio::spawn(async move { kafka.run(&mut ctx).await });
         KafkaSourceWithReads {
             to_control_tx,
             from_control_rx,
             data_recv: recv,
+            handle,
         }
     }
 
@@ -165,12 +173,24 @@ impl KafkaTopicProducer {
             .send(BaseRecord::<(), String>::to(&self.topic).payload(&json))
             .expect("could not send message")
     }
+
+    fn send_data_at(&mut self, data: TestData, time: SystemTime) {
+        let json = serde_json::to_string(&data).unwrap();
+        self.base_producer
+            .send(
+                BaseRecord::<(), String>::to(&self.topic)
+                    .payload(&json)
+                    .timestamp(to_millis(time) as i64),
+            )
+            .expect("could not send message")
+    }
 }
 
 struct KafkaSourceWithReads {
     to_control_tx: Sender<ControlMessage>,
     from_control_rx: Receiver<ControlResp>,
     data_recv: BatchReceiver,
+    handle: JoinHandle<SourceFinishType>,
 }
 
 impl KafkaSourceWithReads {
@@ -246,7 +266,7 @@ async fn test_kafka() {
 
     kafka_topic_tester.create_topic().await;
     let mut reader = kafka_topic_tester
-        .get_source_with_reader(task_info.clone(), None)
+        .get_source_with_reader(task_info.clone(), None, None, None)
         .await;
     let mut producer = kafka_topic_tester.get_producer();
 
@@ -295,6 +315,7 @@ async fn test_kafka() {
             min_watermark: Some(0),
             max_watermark: Some(0),
             parallelism: 1,
+            key_groups: KEY_GROUPS as u32,
         }),
     })
     .await
@@ -326,7 +347,7 @@ async fn test_kafka() {
         .unwrap();
 
     let mut reader = kafka_topic_tester
-        .get_source_with_reader(task_info, Some(1))
+        .get_source_with_reader(task_info, Some(1), None, None)
         .await;
 
     // leftover metric
@@ -370,6 +391,7 @@ async fn test_kafka_with_metadata_fields() {
         group_id: kafka_topic_tester.group_id.clone(),
         group_id_prefix: None,
         offset_mode: SourceOffset::Earliest,
+        end_offset: None,
         format: Format::RawString(RawStringFormat {}),
         framing: None,
         bad_data: None,
@@ -377,6 +399,7 @@ async fn test_kafka_with_metadata_fields() {
         client_configs: HashMap::new(),
         messages_per_second: NonZeroU32::new(100).unwrap(),
         metadata_fields,
+        discovery_interval: None,
     };
 
     let (_to_control_tx, control_rx) = channel(128);
@@ -415,7 +438,7 @@ async fn test_kafka_with_metadata_fields() {
     });
 
     let mut reader = kafka_topic_tester
-        .get_source_with_reader(task_info.clone(), None)
+        .get_source_with_reader(task_info.clone(), None, None, None)
         .await;
     let mut producer = kafka_topic_tester.get_producer();
 
@@ -441,3 +464,142 @@ async fn test_kafka_with_metadata_fields() {
         .await
         .unwrap();
 }
+
+#[tokio::test]
+async fn test_kafka_hybrid_handoff() {
+    let mut kafka_topic_tester = KafkaTopicTester {
+        topic: "__arroyo-source-test_hybrid".to_string(),
+        server: "0.0.0.0:9092".to_string(),
+        group_id: Some("test-consumer-group".to_string()),
+    };
+
+    kafka_topic_tester.create_topic().await;
+    let mut producer = kafka_topic_tester.get_producer();
+
+    let switch_at = SystemTime::now() - Duration::from_secs(3600);
+    let values = |range: std::ops::Range<u64>| -> VecDeque<String> {
+        range
+            .map(|i| serde_json::to_string(&TestData { i }).unwrap())
+            .collect()
+    };
+
+    // messages before the switch point, one exactly at it, and ones after it
+    for i in 0..5 {
+        producer.send_data_at(TestData { i }, switch_at - Duration::from_secs(60 - i));
+    }
+    producer.send_data_at(TestData { i: 5 }, switch_at);
+    for i in 6..10 {
+        producer.send_data_at(TestData { i }, switch_at + Duration::from_secs(i));
+    }
+
+    // the backfill reads everything before the switch point, then finishes
+    let mut task_info = arroyo_types::get_test_task_info();
+    task_info.job_id = format!("kafka-job-{}", random::<u64>());
+    let mut backfill = kafka_topic_tester
+        .get_source_with_reader(task_info, None, None, Some(switch_at))
+        .await;
+
+    backfill
+        .assert_next_message_record_values(values(0..5))
+        .await;
+    let finish = tokio::time::timeout(Duration::from_secs(10), &mut backfill.handle)
+        .await
+        .expect("backfill should finish at the switch point")
+        .unwrap();
+    assert!(matches!(finish, SourceFinishType::Final));
+    while let Some(item) = backfill.data_recv.recv().await {
+        assert!(
+            matches!(item, ArrowMessage::Signal(_)),
+            "backfill read past the switch point: {:?}",
+            item
+        );
+    }
+
+    // and the stream starts at the switch point, in place of its configured offset
+    let mut task_info = arroyo_types::get_test_task_info();
+    task_info.job_id = format!("kafka-job-{}", random::<u64>());
+    let mut stream = kafka_topic_tester
+        .get_source_with_reader(task_info, None, Some(switch_at), None)
+        .await;
+
+    stream
+        .assert_next_message_record_values(values(5..10))
+        .await;
+
+    producer.send_data(TestData { i: 10 });
+    stream
+        .assert_next_message_record_values(values(10..11))
+        .await;
+
+    stream
+        .to_control_tx
+        .send(ControlMessage::Stop {
+            mode: arroyo_rpc::grpc::rpc::StopMode::Graceful,
+        })
+        .await
+        .unwrap();
+}
+
+#[test]
+fn test_parse_offset_override() {
+    use rdkafka::Offset;
+
+    assert_eq!(
+        super::parse_offset_override("3", "150").unwrap(),
+        (3, Offset::Offset(150))
+    );
+    assert_eq!(
+        super::parse_offset_override("0", "earliest").unwrap(),
+        (0, Offset::Beginning)
+    );
+    assert_eq!(
+        super::parse_offset_override("1", "latest").unwrap(),
+        (1, Offset::End)
+    );
+    assert!(super::parse_offset_override("shard-0", "10").is_err());
+    assert!(super::parse_offset_override("0", "next").is_err());
+}
+
+#[test]
+fn test_headers_json() {
+    use rdkafka::message::{Header, OwnedHeaders};
+
+    let headers = OwnedHeaders::new()
+        .insert(Header {
+            key: "region",
+            value: Some("us-east-1"),
+        })
+        .insert(Header {
+            key: "empty",
+            value: None::<&str>,
+        });
+
+    let json: serde_json::Value = serde_json::from_str(&super::headers_json(&headers)).unwrap();
+    assert_eq!(
+        json,
+        serde_json::json!({"region": "us-east-1", "empty": null})
+    );
+}
+
+#[test]
+fn test_partition_owner() {
+    use super::partition_owner;
+
+    // partitions of a single topic are assigned round-robin
+    let owners: Vec<_> = (0..6)
+        .map(|p| partition_owner("orders", p, false, 3))
+        .collect();
+    assert_eq!(owners, vec![0, 1, 2, 0, 1, 2]);
+
+    // with a pattern, each topic's partitions are still spread evenly, but topics start from
+    // different subtasks
+    for topic in ["orders-us", "orders-eu"] {
+        let mut owners: Vec<_> = (0..3).map(|p| partition_owner(topic, p, true, 3)).collect();
+        owners.sort();
+        assert_eq!(owners, vec![0, 1, 2]);
+    }
+    assert_ne!(
+        partition_owner("orders-us", 0, true, 16),
+        partition_owner("orders-eu", 0, true, 16)
+    );
+}
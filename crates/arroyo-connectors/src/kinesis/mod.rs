@@ -1,5 +1,9 @@
-use anyhow::{anyhow, bail, Result};
+use anyhow::{anyhow, bail, Context, Result};
+use aws_config::{from_env, Region};
+use aws_sdk_kinesis::Client as KinesisClient;
 use std::collections::HashMap;
+use tokio::sync::mpsc::Sender;
+use tokio::sync::oneshot;
 use typify::import_types;
 
 use arroyo_formats::ser::ArrowSerializer;
@@ -25,6 +29,66 @@ mod source;
 
 pub struct KinesisConnector {}
 
+/// Checks that the stream exists and that we're allowed to read its shards
+async fn test_inner(table: KinesisTable, tx: &Sender<TestSourceMessage>) -> Result<String> {
+    let _ = tx
+        .send(TestSourceMessage::info("Connecting to Kinesis"))
+        .await;
+
+    let mut loader = from_env();
+    if let Some(region) = &table.aws_region {
+        loader = loader.region(Region::new(region.clone()));
+    }
+    let client = KinesisClient::new(&loader.load().await);
+
+    let _ = tx
+        .send(TestSourceMessage::info(format!(
+            "Listing shards for stream '{}'",
+            table.stream_name
+        )))
+        .await;
+    client
+        .list_shards()
+        .stream_name(&table.stream_name)
+        .send()
+        .await
+        .with_context(|| format!("Failed to list shards for stream '{}'", table.stream_name))?;
+
+    Ok(format!(
+        "Successfully connected to stream '{}'",
+        table.stream_name
+    ))
+}
+
+/// Counts the shards of the stream, following the pagination of `ListShards`
+async fn shard_count(table: KinesisTable) -> Result<usize> {
+    let mut loader = from_env();
+    if let Some(region) = &table.aws_region {
+        loader = loader.region(Region::new(region.clone()));
+    }
+    let client = KinesisClient::new(&loader.load().await);
+
+    let mut output = client
+        .list_shards()
+        .stream_name(&table.stream_name)
+        .send()
+        .await
+        .with_context(|| format!("Failed to list shards for stream '{}'", table.stream_name))?;
+    let mut count = output.shards().len();
+
+    while let Some(next_token) = output.next_token() {
+        output = client
+            .list_shards()
+            .set_next_token(Some(next_token.to_string()))
+            .send()
+            .await
+            .with_context(|| format!("Failed to list shards for stream '{}'", table.stream_name))?;
+        count += output.shards().len();
+    }
+
+    Ok(count)
+}
+
 impl Connector for KinesisConnector {
     type ProfileT = EmptyConfig;
 
@@ -43,7 +107,7 @@ impl Connector for KinesisConnector {
             enabled: true,
             source: true,
             sink: true,
-            testing: false,
+            testing: true,
             hidden: false,
             custom_schemas: true,
             connection_config: None,
@@ -55,20 +119,33 @@ impl Connector for KinesisConnector {
         &self,
         _: &str,
         _: Self::ProfileT,
-        _: Self::TableT,
+        table: Self::TableT,
         _: Option<&ConnectionSchema>,
         tx: tokio::sync::mpsc::Sender<TestSourceMessage>,
     ) {
         tokio::task::spawn(async move {
-            let message = TestSourceMessage {
-                error: false,
-                done: true,
-                message: "Successfully validated connection".to_string(),
+            let message = match test_inner(table, &tx).await {
+                Ok(message) => TestSourceMessage::done(message),
+                Err(e) => TestSourceMessage::fail(format!("{:#}", e)),
             };
             tx.send(message).await.unwrap();
         });
     }
 
+    fn source_partitions(
+        &self,
+        _: Self::ProfileT,
+        table: Self::TableT,
+    ) -> oneshot::Receiver<Result<Option<usize>>> {
+        let (tx, rx) = oneshot::channel();
+
+        tokio::task::spawn(async move {
+            let _ = tx.send(shard_count(table).await.map(Some));
+        });
+
+        rx
+    }
+
     fn table_type(&self, _: Self::ProfileT, table: Self::TableT) -> ConnectionType {
         match table.type_ {
             TableType::Source { .. } => ConnectionType::Source,
@@ -184,6 +261,8 @@ impl Connector for KinesisConnector {
                     aws_region: table.aws_region,
                     offset,
                     shards: HashMap::new(),
+                    shard_lag: HashMap::new(),
+                    shard_assignment: HashMap::new(),
                     format: config
                         .format
                         .ok_or_else(|| anyhow!("format required for kinesis source"))?,
@@ -44,7 +44,11 @@ impl ArrowOperator for KinesisSinkFunc {
         self.in_progress_batch = Some(BatchRecordPreparer::new(client, self.name.clone()));
     }
 
-    async fn process_batch(&mut self, batch: RecordBatch, ctx: &mut ArrowContext) {
+    async fn process_batch(
+        &mut self,
+        batch: RecordBatch,
+        ctx: &mut ArrowContext,
+    ) -> anyhow::Result<()> {
         for v in self.serializer.serialize(&batch) {
             self.in_progress_batch
                 .as_mut()
@@ -54,9 +58,14 @@ impl ArrowOperator for KinesisSinkFunc {
                 .await
                 .expect("failed to flush batch during processing");
         }
+        Ok(())
     }
 
-    async fn handle_checkpoint(&mut self, _: CheckpointBarrier, _: &mut ArrowContext) {
+    async fn handle_checkpoint(
+        &mut self,
+        _: CheckpointBarrier,
+        _: &mut ArrowContext,
+    ) -> anyhow::Result<()> {
         retry!(
             self.in_progress_batch.as_mut().unwrap().flush().await,
             30,
@@ -65,12 +74,14 @@ impl ArrowOperator for KinesisSinkFunc {
             |e| warn!("{}", e)
         )
         .expect("could not flush to Kinesis during checkpointing");
+        Ok(())
     }
 
-    async fn handle_tick(&mut self, _: u64, ctx: &mut ArrowContext) {
+    async fn handle_tick(&mut self, _: u64, ctx: &mut ArrowContext) -> anyhow::Result<()> {
         self.maybe_flush_with_retries(ctx)
             .await
             .expect("failed to flush batch during tick");
+        Ok(())
     }
 }
 
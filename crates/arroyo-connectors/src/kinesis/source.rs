@@ -1,5 +1,5 @@
 use std::{
-    collections::{hash_map::DefaultHasher, HashMap},
+    collections::{hash_map::DefaultHasher, HashMap, HashSet},
     fmt::Debug,
     hash::{Hash, Hasher},
     pin::Pin,
@@ -11,7 +11,7 @@ use arroyo_operator::context::ArrowContext;
 use arroyo_operator::operator::SourceOperator;
 use arroyo_operator::SourceFinishType;
 use arroyo_rpc::formats::{BadData, Format, Framing};
-use arroyo_rpc::grpc::rpc::TableConfig;
+use arroyo_rpc::grpc::rpc::{SourceLagUnit, TableConfig};
 use arroyo_rpc::{grpc::rpc::StopMode, ControlMessage};
 use arroyo_state::global_table_config;
 use arroyo_state::tables::global_keyed_map::GlobalKeyedView;
@@ -32,6 +32,7 @@ use tokio::{
 use tracing::{debug, info, warn};
 
 use super::SourceOffset;
+use crate::splits::assign_splits;
 
 #[derive(Clone, Debug, Encode, Decode, PartialEq, PartialOrd)]
 pub enum KinesisOffset {
@@ -50,6 +51,10 @@ pub struct KinesisSourceFunc {
     pub aws_region: Option<String>,
     pub shards: HashMap<String, ShardState>,
     pub offset: SourceOffset,
+    /// how far behind the latest record each of our shards is, in milliseconds
+    pub shard_lag: HashMap<String, u64>,
+    /// the subtask that owns each shard we restored, if we restored its lag
+    pub shard_assignment: HashMap<String, usize>,
 }
 
 #[derive(Clone, Debug, Encode, Decode, PartialEq, PartialOrd)]
@@ -80,6 +85,42 @@ impl TryFrom<(String, Shard, SourceOffset)> for ShardState {
 }
 type BoxedFuture<T> = Pin<Box<dyn Future<Output = T> + Send>>;
 
+/// The position a shard should start reading at if it was overridden while the job was stopped,
+/// which takes precedence over the restored one
+fn offset_override(shard_id: &str, ctx: &ArrowContext) -> Option<KinesisOffset> {
+    let offset = ctx.source_offset_overrides.get(shard_id)?;
+    info!("overriding offset for shard {} to {}", shard_id, offset);
+    Some(match offset.as_str() {
+        "earliest" => KinesisOffset::Earliest,
+        "latest" => KinesisOffset::Latest,
+        sequence_number => KinesisOffset::SequenceNumber(sequence_number.to_string()),
+    })
+}
+
+/// Checks that every overridden shard is one of the stream's shards, so that an override with a
+/// mistyped shard id fails the job instead of being ignored
+fn check_offset_overrides<'a>(
+    overrides: &HashMap<String, String>,
+    shard_ids: impl IntoIterator<Item = &'a str>,
+) -> anyhow::Result<()> {
+    let shard_ids: HashSet<_> = shard_ids.into_iter().collect();
+    let mut unknown: Vec<_> = overrides
+        .keys()
+        .filter(|shard_id| !shard_ids.contains(shard_id.as_str()))
+        .map(|shard_id| shard_id.as_str())
+        .collect();
+
+    if !unknown.is_empty() {
+        unknown.sort();
+        bail!(
+            "offset overrides were set for shards that are not in the stream: {}",
+            unknown.join(", ")
+        );
+    }
+
+    Ok(())
+}
+
 impl ShardState {
     fn new(stream_name: String, shard: Shard, source_offset: SourceOffset) -> Self {
         Self {
@@ -161,7 +202,9 @@ impl SourceOperator for KinesisSourceFunc {
     }
 
     fn tables(&self) -> HashMap<String, TableConfig> {
-        global_table_config("k", "kinesis source state")
+        let mut tables = global_table_config("k", "kinesis source state");
+        tables.extend(global_table_config("l", "kinesis shard lag"));
+        tables
     }
 
     async fn on_start(&mut self, ctx: &mut ArrowContext) {
@@ -197,17 +240,42 @@ impl KinesisSourceFunc {
             .get_global_keyed_state("k")
             .await
             .expect("failed to get state for kinesis source");
-        for (shard_id, shard_state) in s
-            .get_all()
-            .values()
-            .map(|shard_state| (shard_state.shard_id.clone(), shard_state.clone()))
-            .filter(|(shard_id, _shard_state)| {
-                let mut hasher = DefaultHasher::new();
-                shard_id.hash(&mut hasher);
-                let shard_hash = hasher.finish() as usize;
-                shard_hash % ctx.task_info.parallelism == ctx.task_info.task_index
-            })
-        {
+        let restored: Vec<ShardState> = s.get_all().values().cloned().collect();
+
+        if !ctx.source_offset_overrides.is_empty() {
+            let shards = self.get_splits().await?;
+            check_offset_overrides(
+                &ctx.source_offset_overrides,
+                shards.iter().map(|s| s.shard_id()),
+            )?;
+        }
+
+        let lag: &mut GlobalKeyedView<String, u64> = ctx
+            .table_manager
+            .get_global_keyed_state("l")
+            .await
+            .expect("failed to get state for kinesis source");
+
+        // if we know how far behind each shard was as of the checkpoint we restored from, we
+        // spread that backlog evenly across our subtasks
+        if !lag.get_all().is_empty() {
+            self.shard_assignment = assign_splits(
+                restored.iter().map(|s| s.shard_id.clone()),
+                lag.get_all(),
+                ctx.task_info.parallelism,
+            );
+        }
+
+        let ours: Vec<_> = restored
+            .into_iter()
+            .filter(|shard_state| self.owns_shard(&shard_state.shard_id, ctx))
+            .map(|shard_state| (shard_state.shard_id.clone(), shard_state))
+            .collect();
+
+        for (shard_id, mut shard_state) in ours {
+            if let Some(offset) = offset_override(&shard_id, ctx) {
+                shard_state.offset = offset;
+            }
             futures.push(
                 shard_state.get_update_shard_iterator_future(self.kinesis_client.as_ref().unwrap()),
             );
@@ -219,6 +287,20 @@ impl KinesisSourceFunc {
         Ok(futures)
     }
 
+    fn owns_shard(&self, shard_id: &str, ctx: &ArrowContext) -> bool {
+        let subtask = self
+            .shard_assignment
+            .get(shard_id)
+            .copied()
+            .unwrap_or_else(|| {
+                let mut hasher = DefaultHasher::new();
+                shard_id.hash(&mut hasher);
+                hasher.finish() as usize % ctx.task_info.parallelism
+            });
+
+        subtask == ctx.task_info.task_index
+    }
+
     async fn handle_async_result_split(
         &mut self,
         shard_id: String,
@@ -311,6 +393,11 @@ impl KinesisSourceFunc {
             .last()
             .map(|record| record.sequence_number().to_owned());
 
+        if let Some(millis_behind) = get_records.millis_behind_latest() {
+            self.shard_lag
+                .insert(shard_id.clone(), millis_behind.max(0) as u64);
+        }
+
         let next_shard_iterator = self.process_records(get_records, ctx).await?;
         let shard_state = self.shards.get_mut(&shard_id).unwrap();
 
@@ -394,6 +481,18 @@ impl KinesisSourceFunc {
                             for (shard_id, shard_state) in &self.shards {
                                 s.insert(shard_id.clone(), shard_state.clone()).await;
                             }
+
+                            // record how far behind each of our shards is, so that they can be
+                            // rebalanced across subtasks if some of them fall behind
+                            let lag_state = ctx.table_manager.get_global_keyed_state("l").await.unwrap();
+                            for (shard_id, lag) in &self.shard_lag {
+                                lag_state.insert(shard_id.clone(), *lag).await;
+                            }
+                            ctx.report_source_lag(
+                                self.shard_lag.values().sum(),
+                                self.shard_lag.clone(),
+                                SourceLagUnit::Milliseconds,
+                            ).await;
                             if self.start_checkpoint(c, ctx).await {
                                 return Ok(SourceFinishType::Immediate);
                             }
@@ -416,6 +515,9 @@ impl KinesisSourceFunc {
                         Some(ControlMessage::LoadCompacted { compacted }) => {
                             ctx.load_compacted(compacted).await;
                         },
+                        Some(ControlMessage::QueryState { query }) => {
+                            ctx.query_state(query).await;
+                        }
                         Some(ControlMessage::NoOp ) => {}
                         None => {
                         }
@@ -450,18 +552,15 @@ impl KinesisSourceFunc {
     ) -> Result<Vec<BoxedFuture<AsyncNamedResult<AsyncResult>>>> {
         let mut futures = Vec::new();
         for shard in self.get_splits().await? {
-            // check hash
             let shard_id = shard.shard_id().to_string();
-            let mut hasher = DefaultHasher::new();
-            shard_id.hash(&mut hasher);
-            let shard_hash = hasher.finish() as usize;
 
-            if self.shards.contains_key(&shard_id)
-                || shard_hash % ctx.task_info.parallelism != ctx.task_info.task_index
-            {
+            if self.shards.contains_key(&shard_id) || !self.owns_shard(&shard_id, ctx) {
                 continue;
             }
-            let shard_state = ShardState::new(self.stream_name.clone(), shard, self.offset);
+            let mut shard_state = ShardState::new(self.stream_name.clone(), shard, self.offset);
+            if let Some(offset) = offset_override(&shard_id, ctx) {
+                shard_state.offset = offset;
+            }
 
             futures.push(
                 shard_state.get_update_shard_iterator_future(self.kinesis_client.as_ref().unwrap()),
@@ -498,3 +597,32 @@ impl KinesisSourceFunc {
         Ok(shard_collect)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::check_offset_overrides;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_check_offset_overrides() {
+        let shards = ["shardId-000000000000", "shardId-000000000001"];
+        let overrides = |ids: &[&str]| -> HashMap<String, String> {
+            ids.iter()
+                .map(|id| (id.to_string(), "latest".to_string()))
+                .collect()
+        };
+
+        assert!(check_offset_overrides(&overrides(&[]), shards).is_ok());
+        assert!(check_offset_overrides(&overrides(&["shardId-000000000001"]), shards).is_ok());
+
+        let err = check_offset_overrides(
+            &overrides(&["shardId-000000000001", "shardId-000000000007", "shard-0"]),
+            shards,
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "offset overrides were set for shards that are not in the stream: shard-0, shardId-000000000007"
+        );
+    }
+}
@@ -1,12 +1,27 @@
 use crate::confluent::ConfluentConnector;
+use crate::datagen::DatagenConnector;
+use crate::elasticsearch::ElasticsearchConnector;
 use crate::filesystem::delta::DeltaLakeConnector;
 use crate::filesystem::FileSystemConnector;
+use crate::grpc::GrpcConnector;
+use crate::http::HttpSinkConnector;
+use crate::iceberg::IcebergConnector;
 use crate::kinesis::KinesisConnector;
 use crate::mqtt::MqttConnector;
+use crate::mysql::MySqlConnector;
+use crate::otlp::OtlpConnector;
 use crate::polling_http::PollingHTTPConnector;
+use crate::postgres::PostgresConnector;
+use crate::postgres_cdc::PostgresCdcConnector;
 use crate::preview::PreviewConnector;
 use crate::redis::RedisConnector;
+use crate::shared::SharedConnector;
+use crate::side_input::SideInputConnector;
 use crate::single_file::SingleFileConnector;
+use crate::snowflake::SnowflakeConnector;
+use crate::sns::SnsConnector;
+use crate::socket::SocketConnector;
+use crate::sqs::SqsConnector;
 use crate::stdout::StdoutConnector;
 use crate::webhook::WebhookConnector;
 use anyhow::{anyhow, bail, Context};
@@ -36,46 +51,87 @@ use self::kafka::KafkaConnector;
 
 pub mod blackhole;
 pub mod confluent;
+pub mod datagen;
+pub mod elasticsearch;
 pub mod filesystem;
 pub mod fluvio;
+pub mod grpc;
+pub mod http;
+pub mod iceberg;
 pub mod impulse;
 pub mod kafka;
 pub mod kinesis;
 pub mod mqtt;
+pub mod mysql;
 pub mod nats;
 pub mod nexmark;
+pub mod oauth;
+pub mod otlp;
+pub mod plugin;
 pub mod polling_http;
+pub mod postgres;
+pub mod postgres_cdc;
 pub mod preview;
 pub mod redis;
+pub mod shared;
+pub mod side_input;
 pub mod single_file;
+mod sink_schema;
+pub mod snowflake;
+pub mod sns;
+pub mod socket;
+pub mod splits;
+mod sql_sink;
+pub mod sqs;
 pub mod sse;
 pub mod stdout;
 pub mod webhook;
 pub mod websocket;
 
 pub fn connectors() -> HashMap<&'static str, Box<dyn ErasedConnector>> {
-    let connectors: Vec<Box<dyn ErasedConnector>> = vec![
+    let mut connectors: Vec<Box<dyn ErasedConnector>> = vec![
         Box::new(BlackholeConnector {}),
         Box::new(ConfluentConnector {}),
+        Box::new(DatagenConnector {}),
         Box::new(DeltaLakeConnector {}),
+        Box::new(ElasticsearchConnector {}),
         Box::new(FileSystemConnector {}),
         Box::new(FluvioConnector {}),
+        Box::new(GrpcConnector {}),
+        Box::new(HttpSinkConnector {}),
+        Box::new(IcebergConnector {}),
         Box::new(ImpulseConnector {}),
         Box::new(KafkaConnector {}),
         Box::new(KinesisConnector {}),
         Box::new(MqttConnector {}),
+        Box::new(MySqlConnector {}),
         Box::new(NatsConnector {}),
         Box::new(NexmarkConnector {}),
+        Box::new(OtlpConnector {}),
         Box::new(PollingHTTPConnector {}),
+        Box::new(PostgresConnector {}),
+        Box::new(PostgresCdcConnector {}),
         Box::new(PreviewConnector {}),
         Box::new(RedisConnector {}),
+        Box::new(SharedConnector {}),
+        Box::new(SideInputConnector {}),
         Box::new(SingleFileConnector {}),
+        Box::new(SnowflakeConnector {}),
+        Box::new(SnsConnector {}),
+        Box::new(SocketConnector {}),
+        Box::new(SqsConnector {}),
         Box::new(SSEConnector {}),
         Box::new(StdoutConnector {}),
         Box::new(WebhookConnector {}),
         Box::new(WebsocketConnector {}),
     ];
 
+    connectors.extend(
+        plugin::plugins()
+            .into_iter()
+            .map(|p| Box::new(p) as Box<dyn ErasedConnector>),
+    );
+
     connectors.into_iter().map(|c| (c.name(), c)).collect()
 }
 
@@ -88,6 +144,35 @@ pub(crate) async fn send(tx: &mut Sender<TestSourceMessage>, msg: TestSourceMess
     }
 }
 
+/// Message header used to propagate W3C trace context (https://www.w3.org/TR/trace-context/)
+pub(crate) const TRACEPARENT_HEADER: &str = "traceparent";
+
+/// Returns the value of a `traceparent` header if it is well-formed, in the form
+/// `{version}-{trace-id}-{parent-id}-{trace-flags}`, or None otherwise
+pub(crate) fn parse_traceparent(value: &[u8]) -> Option<&str> {
+    let value = std::str::from_utf8(value).ok()?.trim();
+    let parts: Vec<_> = value.split('-').collect();
+    let [version, trace_id, parent_id, flags] = parts[..] else {
+        return None;
+    };
+
+    let is_hex = |s: &str, len: usize| {
+        s.len() == len
+            && s.bytes()
+                .all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b))
+    };
+    let is_zero = |s: &str| s.bytes().all(|b| b == b'0');
+
+    (is_hex(version, 2)
+        && version != "ff"
+        && is_hex(trace_id, 32)
+        && !is_zero(trace_id)
+        && is_hex(parent_id, 16)
+        && !is_zero(parent_id)
+        && is_hex(flags, 2))
+    .then_some(value)
+}
+
 pub(crate) fn pull_opt(name: &str, opts: &mut HashMap<String, String>) -> anyhow::Result<String> {
     opts.remove(name)
         .ok_or_else(|| anyhow!("required option '{}' not set", name))
@@ -134,6 +219,7 @@ pub(crate) fn source_field(name: &str, field_type: FieldType) -> SourceField {
                 FieldType::Primitive(p) => Some(primitive_to_sql(p).to_string()),
                 FieldType::Struct(_) => None,
                 FieldType::List(_) => None,
+                FieldType::Map(_) => None,
             },
             r#type: field_type,
         },
@@ -178,3 +264,37 @@ pub fn header_map(headers: Option<VarStr>) -> HashMap<String, String> {
     )
     .expect("Invalid header map")
 }
+
+#[cfg(test)]
+mod test {
+    use super::parse_traceparent;
+
+    #[test]
+    fn test_parse_traceparent() {
+        let valid = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+        assert_eq!(parse_traceparent(valid.as_bytes()), Some(valid));
+        assert_eq!(
+            parse_traceparent(format!(" {valid}\n").as_bytes()),
+            Some(valid)
+        );
+
+        for invalid in [
+            "",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01-00",
+            // uppercase hex isn't allowed
+            "00-4BF92F3577B34DA6A3CE929D0E0E4736-00f067aa0ba902b7-01",
+            // version ff is invalid
+            "ff-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+            // all-zero trace and parent ids are invalid
+            "00-00000000000000000000000000000000-00f067aa0ba902b7-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-0000000000000000-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e473-00f067aa0ba902b7-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-0g",
+        ] {
+            assert_eq!(parse_traceparent(invalid.as_bytes()), None, "{invalid}");
+        }
+
+        assert_eq!(parse_traceparent(&[0xff, 0xfe]), None);
+    }
+}
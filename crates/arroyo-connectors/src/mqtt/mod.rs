@@ -4,12 +4,11 @@ use std::sync::atomic::AtomicBool;
 use std::sync::Arc;
 use std::time::Duration;
 
-use crate::mqtt::sink::MqttSinkFunc;
+use crate::mqtt::sink::{MqttSinkFunc, TopicTemplate};
 use crate::mqtt::source::MqttSourceFunc;
 use crate::pull_opt;
 use anyhow::{anyhow, bail};
 use arrow::datatypes::DataType;
-use arroyo_formats::ser::ArrowSerializer;
 use arroyo_operator::connector::{Connection, Connector, MetadataDef};
 use arroyo_operator::operator::OperatorNode;
 use arroyo_rpc::api_types::connections::{
@@ -54,6 +53,29 @@ impl MqttTable {
             })
             .unwrap_or(QoS::AtMostOnce)
     }
+
+    /// The shared subscription group the source subscribes as a member of, if any, which is set
+    /// either by the `shared_group` option or by a topic of the form `$share/<group>/<topic>`
+    pub fn shared_group(&self) -> Option<&str> {
+        if let Some(shared) = self.topic.strip_prefix("$share/") {
+            return shared.split_once('/').map(|(group, _)| group);
+        }
+
+        match &self.type_ {
+            TableType::Source { shared_group } => shared_group.as_deref(),
+            TableType::Sink { .. } => None,
+        }
+    }
+
+    /// The topic filter the source subscribes to
+    pub fn subscription(&self) -> String {
+        match self.shared_group() {
+            Some(group) if !self.topic.starts_with("$share/") => {
+                format!("$share/{}/{}", group, self.topic)
+            }
+            _ => self.topic.clone(),
+        }
+    }
 }
 
 impl MqttConnector {
@@ -98,7 +120,9 @@ impl MqttConnector {
             .transpose()?;
 
         let table_type = match typ.as_str() {
-            "source" => TableType::Source {},
+            "source" => TableType::Source {
+                shared_group: options.remove("source.shared_group"),
+            },
             "sink" => TableType::Sink {
                 retain: options
                     .remove("sink.retain")
@@ -171,6 +195,39 @@ impl Connector for MqttConnector {
             .map(|s| s.to_owned())
             .ok_or_else(|| anyhow!("No schema defined for Mqtt connection"))?;
 
+        match &table.type_ {
+            TableType::Source { shared_group } => {
+                if shared_group.is_some() && table.topic.starts_with("$share/") {
+                    bail!(
+                        "'source.shared_group' can't be set for a topic that starts with '$share/'"
+                    );
+                }
+
+                if table.topic.starts_with("$share/") || shared_group.is_some() {
+                    match table.shared_group() {
+                        Some(group) if !group.is_empty() && !group.contains(['/', '+', '#']) => {}
+                        _ => bail!(
+                            "invalid shared subscription '{}'; the group name must be non-empty \
+                            and may not contain '/', '+' or '#'",
+                            table.subscription()
+                        ),
+                    }
+                }
+            }
+            TableType::Sink { .. } => {
+                let template = TopicTemplate::parse(&table.topic)?;
+                for column in template.columns() {
+                    if !schema.fields.iter().any(|f| f.field_name == column) {
+                        bail!(
+                            "topic '{}' refers to column '{}', which is not in the table",
+                            table.topic,
+                            column
+                        );
+                    }
+                }
+            }
+        }
+
         let format = schema
             .format
             .as_ref()
@@ -239,11 +296,31 @@ impl Connector for MqttConnector {
         }
     }
 
+    fn max_source_parallelism(&self, _: Self::ProfileT, table: Self::TableT) -> Option<usize> {
+        if table.shared_group().is_some() {
+            // the broker divides messages among the members of the group
+            None
+        } else {
+            // only the first subtask subscribes to the topic
+            Some(1)
+        }
+    }
+
     fn metadata_defs(&self) -> &'static [MetadataDef] {
-        &[MetadataDef {
-            name: "topic",
-            data_type: DataType::Utf8,
-        }]
+        &[
+            MetadataDef {
+                name: "topic",
+                data_type: DataType::Utf8,
+            },
+            MetadataDef {
+                name: "qos",
+                data_type: DataType::Int32,
+            },
+            MetadataDef {
+                name: "retain",
+                data_type: DataType::Boolean,
+            },
+        ]
     }
 
     fn from_options(
@@ -274,9 +351,9 @@ impl Connector for MqttConnector {
     ) -> anyhow::Result<OperatorNode> {
         let qos = table.qos();
         Ok(match table.type_ {
-            TableType::Source {} => OperatorNode::from_source(Box::new(MqttSourceFunc {
+            TableType::Source { .. } => OperatorNode::from_source(Box::new(MqttSourceFunc {
                 config: profile,
-                topic: table.topic,
+                topic: table.subscription(),
                 qos,
                 format: config
                     .format
@@ -293,19 +370,15 @@ impl Connector for MqttConnector {
                 subscribed: Arc::new(AtomicBool::new(false)),
                 metadata_fields: config.metadata_fields,
             })),
-            TableType::Sink { retain } => OperatorNode::from_operator(Box::new(MqttSinkFunc {
-                config: profile,
+            TableType::Sink { retain } => OperatorNode::from_operator(Box::new(MqttSinkFunc::new(
+                profile,
                 qos,
-                topic: table.topic,
+                table.topic,
                 retain,
-                serializer: ArrowSerializer::new(
-                    config
-                        .format
-                        .ok_or_else(|| anyhow!("format is required for mqtt sink"))?,
-                ),
-                stopped: Arc::new(AtomicBool::new(false)),
-                client: None,
-            })),
+                config
+                    .format
+                    .ok_or_else(|| anyhow!("format is required for mqtt sink"))?,
+            )?)),
         })
     }
 }
@@ -323,7 +396,16 @@ async fn test_inner(
 
     let wait_for_incomming = match t {
         Some(t) => {
-            let topic = t.topic;
+            let subscription = t.subscription();
+            let topic = match t
+                .topic
+                .strip_prefix("$share/")
+                .and_then(|s| s.split_once('/'))
+            {
+                // messages are published to the topic itself, rather than the shared subscription
+                Some((_, topic)) => topic.to_string(),
+                None => t.topic,
+            };
             let qos = t
                 .qos
                 .map(|qos| match qos {
@@ -338,7 +420,7 @@ async fn test_inner(
                     .await?;
                 false
             } else {
-                client.subscribe(&topic, qos).await?;
+                client.subscribe(subscription, qos).await?;
                 client.publish(topic, qos, false, "test".as_bytes()).await?;
                 true
             }
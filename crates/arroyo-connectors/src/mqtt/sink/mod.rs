@@ -16,11 +16,15 @@ use rumqttc::v5::ConnectionError;
 
 #[cfg(test)]
 mod test;
+mod topic;
+
+pub use topic::TopicTemplate;
 
 pub struct MqttSinkFunc {
     pub config: MqttConfig,
     pub qos: QoS,
     pub topic: String,
+    pub topic_template: TopicTemplate,
     pub retain: bool,
     pub serializer: ArrowSerializer,
     pub client: Option<AsyncClient>,
@@ -28,16 +32,23 @@ pub struct MqttSinkFunc {
 }
 
 impl MqttSinkFunc {
-    pub fn new(config: MqttConfig, qos: QoS, topic: String, retain: bool, format: Format) -> Self {
-        Self {
+    pub fn new(
+        config: MqttConfig,
+        qos: QoS,
+        topic: String,
+        retain: bool,
+        format: Format,
+    ) -> anyhow::Result<Self> {
+        Ok(Self {
             config,
             qos,
+            topic_template: TopicTemplate::parse(&topic)?,
             topic,
             retain,
             serializer: ArrowSerializer::new(format),
             client: None,
             stopped: Arc::new(AtomicBool::new(false)),
-        }
+        })
     }
 }
 
@@ -91,13 +102,36 @@ impl ArrowOperator for MqttSinkFunc {
         panic!("Failed to establish connection to mqtt after 20 retries");
     }
 
-    async fn process_batch(&mut self, batch: RecordBatch, ctx: &mut ArrowContext) {
-        for v in self.serializer.serialize(&batch) {
+    async fn process_batch(
+        &mut self,
+        batch: RecordBatch,
+        ctx: &mut ArrowContext,
+    ) -> anyhow::Result<()> {
+        let topics = match self.topic_template.as_static() {
+            Some(topic) => vec![Ok(topic.to_string()); batch.num_rows()],
+            None => self
+                .topic_template
+                .render(&batch)
+                .expect("topic columns are validated when the table is created"),
+        };
+
+        let mut dropped = 0;
+        let mut reason = None;
+        for (v, topic) in self.serializer.serialize(&batch).zip(topics) {
+            let topic = match topic {
+                Ok(topic) => topic,
+                Err(e) => {
+                    dropped += 1;
+                    reason.get_or_insert(e);
+                    continue;
+                }
+            };
+
             match self
                 .client
                 .as_mut()
                 .unwrap()
-                .publish(&self.topic, self.qos, self.retain, v)
+                .publish(topic, self.qos, self.retain, v)
                 .await
             {
                 Ok(_) => (),
@@ -116,6 +150,18 @@ impl ArrowOperator for MqttSinkFunc {
                 }
             }
         }
+
+        if let Some(reason) = reason {
+            ctx.report_error(
+                "Dropped messages with invalid topics",
+                format!(
+                    "{} messages could not be published because their topic was invalid: {}",
+                    dropped, reason
+                ),
+            )
+            .await;
+        }
+        Ok(())
     }
 }
 
@@ -73,7 +73,8 @@ impl MqttTopicTester {
             self.topic.clone(),
             false,
             Format::Json(JsonFormat::default()),
-        );
+        )
+        .unwrap();
 
         let (_, control_rx) = channel(128);
         let (command_tx, _) = channel(128);
@@ -146,7 +147,8 @@ async fn test_mqtt() {
         sink_with_writes
             .sink
             .process_batch(batch, &mut sink_with_writes.ctx)
-            .await;
+            .await
+            .unwrap();
     }
 
     let mut message = 1u32;
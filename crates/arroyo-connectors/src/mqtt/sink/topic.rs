@@ -0,0 +1,192 @@
+use anyhow::{anyhow, bail};
+use arrow::array::{Array, RecordBatch};
+use arrow::util::display::{ArrayFormatter, FormatOptions};
+
+#[derive(Debug, Clone, PartialEq)]
+enum TopicPart {
+    Literal(String),
+    Column(String),
+}
+
+/// The topic a sink publishes to, which may interpolate the values of columns of each row,
+/// like `devices/{device_id}/alerts`
+#[derive(Debug, Clone, PartialEq)]
+pub struct TopicTemplate {
+    parts: Vec<TopicPart>,
+}
+
+/// Returns the first character of `s` that may not appear in a topic that's published to
+fn invalid_char(s: &str) -> Option<char> {
+    s.chars().find(|c| matches!(c, '+' | '#' | '\0'))
+}
+
+impl TopicTemplate {
+    pub fn parse(topic: &str) -> anyhow::Result<Self> {
+        let mut parts = vec![];
+        let push_literal = |parts: &mut Vec<TopicPart>, s: &str| {
+            if s.contains('}') {
+                bail!("unmatched '}}' in topic '{}'", topic);
+            }
+            if let Some(c) = invalid_char(s) {
+                bail!("topic '{}' may not contain '{}'", topic, c);
+            }
+            if !s.is_empty() {
+                parts.push(TopicPart::Literal(s.to_string()));
+            }
+            Ok(())
+        };
+
+        let mut rest = topic;
+        while let Some(start) = rest.find('{') {
+            push_literal(&mut parts, &rest[..start])?;
+
+            let end = start
+                + rest[start..]
+                    .find('}')
+                    .ok_or_else(|| anyhow!("unclosed '{{' in topic '{}'", topic))?;
+
+            let column = rest[start + 1..end].trim();
+            if column.is_empty() || column.contains('{') {
+                bail!("invalid column reference in topic '{}'", topic);
+            }
+
+            parts.push(TopicPart::Column(column.to_string()));
+            rest = &rest[end + 1..];
+        }
+        push_literal(&mut parts, rest)?;
+
+        if parts.is_empty() {
+            bail!("topic must not be empty");
+        }
+
+        Ok(Self { parts })
+    }
+
+    /// The columns whose values are interpolated into the topic
+    pub fn columns(&self) -> impl Iterator<Item = &str> {
+        self.parts.iter().filter_map(|p| match p {
+            TopicPart::Column(c) => Some(c.as_str()),
+            TopicPart::Literal(_) => None,
+        })
+    }
+
+    /// Returns the topic if it doesn't depend on the rows published to it
+    pub fn as_static(&self) -> Option<&str> {
+        match self.parts.as_slice() {
+            [TopicPart::Literal(topic)] => Some(topic),
+            _ => None,
+        }
+    }
+
+    /// Renders the topic for each row of `batch`, or the reason that a row has no valid topic
+    /// (because a column it interpolates is null, or has a value that can't appear in a topic)
+    pub fn render(&self, batch: &RecordBatch) -> anyhow::Result<Vec<Result<String, String>>> {
+        let options = FormatOptions::default();
+        // the column and its formatter for each part that interpolates one
+        let columns = self
+            .parts
+            .iter()
+            .map(|part| match part {
+                TopicPart::Literal(_) => Ok(None),
+                TopicPart::Column(name) => {
+                    let array = batch
+                        .column_by_name(name)
+                        .ok_or_else(|| anyhow!("topic column '{}' is not in the table", name))?;
+                    Ok(Some((
+                        array.as_ref(),
+                        ArrayFormatter::try_new(array.as_ref(), &options)?,
+                    )))
+                }
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        Ok((0..batch.num_rows())
+            .map(|i| {
+                let mut topic = String::new();
+                for (part, column) in self.parts.iter().zip(&columns) {
+                    match (part, column) {
+                        (TopicPart::Literal(literal), _) => topic.push_str(literal),
+                        (TopicPart::Column(name), Some((array, formatter))) => {
+                            if array.is_null(i) {
+                                return Err(format!("topic column '{}' is null", name));
+                            }
+
+                            let value = formatter.value(i).to_string();
+                            if let Some(c) = invalid_char(&value) {
+                                return Err(format!(
+                                    "value '{}' of topic column '{}' contains '{}', which may not \
+                                    appear in a topic",
+                                    value, name, c
+                                ));
+                            }
+                            topic.push_str(&value);
+                        }
+                        (TopicPart::Column(_), None) => unreachable!(),
+                    }
+                }
+                Ok(topic)
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::TopicTemplate;
+    use arrow::array::{Int64Array, RecordBatch, StringArray};
+    use arrow::datatypes::{DataType, Field, Schema};
+    use std::sync::Arc;
+
+    #[test]
+    fn test_parse() {
+        let template = TopicTemplate::parse("alerts").unwrap();
+        assert_eq!(template.as_static(), Some("alerts"));
+
+        let template = TopicTemplate::parse("sites/{site}/devices/{ device_id }").unwrap();
+        assert_eq!(template.as_static(), None);
+        assert_eq!(
+            template.columns().collect::<Vec<_>>(),
+            vec!["site", "device_id"]
+        );
+
+        for invalid in [
+            "devices/{id",
+            "devices/id}",
+            "devices/{}",
+            "devices/+/alerts",
+            "",
+        ] {
+            assert!(TopicTemplate::parse(invalid).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_render() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("site", DataType::Utf8, true),
+            Field::new("device_id", DataType::Int64, false),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(StringArray::from(vec![Some("nyc"), None, Some("sf#2")])),
+                Arc::new(Int64Array::from(vec![1, 2, 3])),
+            ],
+        )
+        .unwrap();
+
+        let topics = TopicTemplate::parse("sites/{site}/devices/{device_id}/alerts")
+            .unwrap()
+            .render(&batch)
+            .unwrap();
+
+        assert_eq!(topics[0], Ok("sites/nyc/devices/1/alerts".to_string()));
+        assert!(topics[1].is_err());
+        assert!(topics[2].is_err());
+
+        assert!(TopicTemplate::parse("{missing}")
+            .unwrap()
+            .render(&batch)
+            .is_err());
+    }
+}
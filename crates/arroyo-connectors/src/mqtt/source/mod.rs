@@ -96,6 +96,15 @@ impl MqttSourceFunc {
         self.subscribed.clone()
     }
 
+    /// The share of the source's rate limit for a subtask, when the rate is divided among the
+    /// subtasks of a shared subscription
+    fn subtask_rate(&self, task_index: usize, parallelism: usize) -> NonZeroU32 {
+        let parallelism = parallelism.max(1) as u32;
+        let rate = self.messages_per_second.get();
+        let extra = (task_index as u32) < rate % parallelism;
+        NonZeroU32::new(rate / parallelism + extra as u32).unwrap_or(NonZeroU32::MIN)
+    }
+
     async fn run_int(&mut self, ctx: &mut ArrowContext) -> Result<SourceFinishType, UserError> {
         ctx.initialize_deserializer(
             self.format.clone(),
@@ -103,7 +112,10 @@ impl MqttSourceFunc {
             self.bad_data.clone(),
         );
 
-        if ctx.task_info.task_index > 0 {
+        // with a shared subscription, the broker divides messages among all of the subtasks
+        let shared = self.topic.starts_with("$share/");
+
+        if !shared && ctx.task_info.task_index > 0 {
             tracing::warn!(
                 "Mqtt Consumer {}-{} can only be executed on a single worker... setting idle",
                 ctx.task_info.operator_id,
@@ -136,7 +148,11 @@ impl MqttSourceFunc {
             }
         }
 
-        let rate_limiter = GovernorRateLimiter::direct(Quota::per_second(self.messages_per_second));
+        let rate_limiter = GovernorRateLimiter::direct(Quota::per_second(if shared {
+            self.subtask_rate(ctx.task_info.task_index, ctx.task_info.parallelism)
+        } else {
+            self.messages_per_second
+        }));
 
         let topic = self.topic.clone();
         let qos = self.qos;
@@ -155,6 +171,8 @@ impl MqttSourceFunc {
                                 for mf in &self.metadata_fields {
                                     connector_metadata.insert(&mf.field_name, match mf.key.as_str() {
                                         "topic" => FieldValueType::String(&topic),
+                                        "qos" => FieldValueType::Int32(p.qos as i32),
+                                        "retain" => FieldValueType::Boolean(p.retain),
                                         k => unreachable!("invalid metadata key '{}' for mqtt", k)
                                     });
                                 }
@@ -220,6 +238,9 @@ impl MqttSourceFunc {
                         Some(ControlMessage::LoadCompacted {compacted}) => {
                             ctx.load_compacted(compacted).await;
                         }
+                        Some(ControlMessage::QueryState { query }) => {
+                            ctx.query_state(query).await;
+                        }
                         Some(ControlMessage::NoOp) => {}
                         None => {
 
@@ -221,3 +221,29 @@ async fn test_mqtt() {
         .await
         .unwrap();
 }
+
+#[test]
+fn test_subtask_rate() {
+    let source = MqttSourceFunc::new(
+        MqttConfig {
+            url: "tcp://localhost:1883".to_string(),
+            client_prefix: None,
+            username: None,
+            password: None,
+            tls: None,
+        },
+        "$share/arroyo/sensors".to_string(),
+        QoS::AtLeastOnce,
+        Format::Json(JsonFormat::default()),
+        None,
+        None,
+        10,
+        vec![],
+    );
+
+    let rates: Vec<_> = (0..4).map(|i| source.subtask_rate(i, 4).get()).collect();
+    assert_eq!(rates, vec![3, 3, 2, 2]);
+
+    // every subtask is allowed at least one message per second
+    assert_eq!(source.subtask_rate(15, 16).get(), 1);
+}
@@ -0,0 +1,434 @@
+mod sink;
+
+use anyhow::{anyhow, bail};
+use arrow::datatypes::{DataType, Field};
+use arroyo_operator::connector::{Connection, Connector};
+use arroyo_operator::operator::OperatorNode;
+use arroyo_rpc::api_types::connections::{
+    ConnectionProfile, ConnectionSchema, ConnectionType, TestSourceMessage,
+};
+use arroyo_rpc::formats::{Format, JsonFormat, TimestampFormat};
+use arroyo_rpc::var_str::VarStr;
+use arroyo_rpc::OperatorConfig;
+use mysql_async::prelude::Queryable;
+use mysql_async::{Conn, OptsBuilder, Pool, PoolConstraints, PoolOpts};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use tokio::sync::oneshot::Receiver;
+use typify::import_types;
+
+use crate::mysql::sink::MySqlSinkFunc;
+use crate::sink_schema::{resolve_drift, schema_drift, Dialect, DriftMode, TableColumn};
+use crate::sql_sink::check_schema;
+use crate::{pull_opt, pull_option_to_i64};
+
+const CONFIG_SCHEMA: &str = include_str!("./profile.json");
+const TABLE_SCHEMA: &str = include_str!("./table.json");
+
+import_types!(
+    schema = "src/mysql/profile.json",
+    convert = {
+        {type = "string", format = "var-str"} = VarStr
+    }
+);
+
+import_types!(schema = "src/mysql/table.json");
+
+pub struct MySqlConnector {}
+
+/// Creates a pool of connections to the database, which are opened lazily as they are needed
+fn create_pool(config: &MySqlConfig) -> anyhow::Result<Pool> {
+    let constraints = PoolConstraints::new(0, config.pool_size.max(1) as usize)
+        .ok_or_else(|| anyhow!("invalid pool size {}", config.pool_size))?;
+
+    let opts = OptsBuilder::default()
+        .ip_or_hostname(config.host.clone())
+        .tcp_port(config.port as u16)
+        .db_name(Some(config.database.clone()))
+        .user(Some(config.user.sub_env_vars()?))
+        .pass(
+            config
+                .password
+                .as_ref()
+                .map(|p| p.sub_env_vars())
+                .transpose()?,
+        )
+        .pool_opts(PoolOpts::default().with_constraints(constraints));
+
+    Ok(Pool::new(opts))
+}
+
+fn quote_ident(ident: &str) -> String {
+    format!("`{}`", ident.replace('`', "``"))
+}
+
+impl From<&SchemaDrift> for DriftMode {
+    fn from(mode: &SchemaDrift) -> Self {
+        match mode {
+            SchemaDrift::Fail => DriftMode::Fail,
+            SchemaDrift::Coerce => DriftMode::Coerce,
+            SchemaDrift::Evolve => DriftMode::Evolve,
+        }
+    }
+}
+
+/// The type of the column that MySQL stores a field in, which is what columns are created
+/// with when the table is evolved
+fn column_type(field: &Field) -> anyhow::Result<String> {
+    Ok(match field.data_type() {
+        DataType::Boolean => "tinyint(1)".to_string(),
+        DataType::Int8 | DataType::Int16 | DataType::Int32 | DataType::UInt8 | DataType::UInt16 => {
+            "int".to_string()
+        }
+        DataType::Int64 | DataType::UInt32 => "bigint".to_string(),
+        DataType::UInt64 => "bigint unsigned".to_string(),
+        DataType::Float16 | DataType::Float32 => "float".to_string(),
+        DataType::Float64 => "double".to_string(),
+        DataType::Decimal128(p, s) | DataType::Decimal256(p, s) => {
+            // MySQL decimals have at most 65 digits
+            format!("decimal({},{})", (*p).min(65), s)
+        }
+        DataType::Utf8 | DataType::LargeUtf8 => "text".to_string(),
+        DataType::Binary | DataType::LargeBinary => "blob".to_string(),
+        DataType::Timestamp(_, _) => "datetime(6)".to_string(),
+        DataType::Date32 | DataType::Date64 => "date".to_string(),
+        t => bail!(
+            "field '{}' has type {}, which cannot be written to MySQL",
+            field.name(),
+            t
+        ),
+    })
+}
+
+/// Whether values of the MySQL type `from` can be assigned to a column of type `to` without
+/// loss
+fn coercible(from: &str, to: &str) -> bool {
+    // every type can be written to a text column via its text representation
+    from == to
+        || matches!(
+            to,
+            "text" | "tinytext" | "mediumtext" | "longtext" | "varchar" | "char"
+        )
+        || matches!(
+            (from, to),
+            (
+                "tinyint",
+                "smallint" | "mediumint" | "int" | "bigint" | "decimal"
+            ) | ("int", "bigint" | "decimal" | "double")
+                | ("bigint", "decimal")
+                | ("float", "double" | "decimal")
+                | ("double", "decimal")
+                | ("text", "mediumtext" | "longtext" | "json")
+                | ("blob", "mediumblob" | "longblob")
+                | ("date", "datetime" | "timestamp")
+        )
+}
+
+/// Adds a column to the table; MySQL has no ADD COLUMN IF NOT EXISTS, so the sink ignores
+/// errors from columns that other tasks have already added
+fn add_column(table: &str, column: &str, type_name: &str) -> String {
+    format!(
+        "ALTER TABLE {} ADD COLUMN {} {}",
+        table,
+        quote_ident(column),
+        type_name
+    )
+}
+
+pub(crate) const MYSQL: Dialect = Dialect {
+    column_type,
+    coercible,
+    add_column: Some(add_column),
+};
+
+/// Reads the columns of the table from the database catalog, in order
+async fn table_columns(conn: &mut Conn, table: &MySqlTable) -> anyhow::Result<Vec<TableColumn>> {
+    let rows: Vec<(String, String, bool, bool)> = conn
+        .exec(
+            "SELECT COLUMN_NAME, DATA_TYPE, IS_NULLABLE = 'NO',
+                COLUMN_DEFAULT IS NOT NULL OR EXTRA <> ''
+            FROM information_schema.COLUMNS
+            WHERE TABLE_SCHEMA = DATABASE() AND TABLE_NAME = ?
+            ORDER BY ORDINAL_POSITION",
+            (&table.table_name,),
+        )
+        .await?;
+
+    if rows.is_empty() {
+        bail!("table {} does not exist", table.table_name);
+    }
+
+    Ok(rows
+        .into_iter()
+        .map(|(name, type_name, not_null, has_default)| TableColumn {
+            name,
+            type_name,
+            not_null,
+            has_default,
+        })
+        .collect())
+}
+
+/// Reads the columns of the primary key of the table from the database catalog; this is
+/// empty if the table has no primary key
+async fn primary_key_columns(conn: &mut Conn, table: &MySqlTable) -> anyhow::Result<Vec<String>> {
+    Ok(conn
+        .exec(
+            "SELECT COLUMN_NAME FROM information_schema.KEY_COLUMN_USAGE
+            WHERE TABLE_SCHEMA = DATABASE() AND TABLE_NAME = ? AND CONSTRAINT_NAME = 'PRIMARY'
+            ORDER BY ORDINAL_POSITION",
+            (&table.table_name,),
+        )
+        .await?)
+}
+
+async fn test_inner(
+    config: &MySqlConfig,
+    table: Option<&MySqlTable>,
+    schema: Option<&ConnectionSchema>,
+) -> anyhow::Result<String> {
+    let pool = create_pool(config)?;
+    let mut conn = pool.get_conn().await.map_err(|e| {
+        anyhow!(
+            "failed to connect to {}:{}: {}",
+            config.host,
+            config.port,
+            e
+        )
+    })?;
+
+    let Some(table) = table else {
+        return Ok("Successfully connected to MySQL".to_string());
+    };
+
+    let columns = table_columns(&mut conn, table).await?;
+
+    for key in &table.key_columns {
+        if !columns.iter().any(|c| &c.name == key) {
+            bail!(
+                "key column '{}' does not exist in table {}",
+                key,
+                table.table_name
+            );
+        }
+    }
+
+    let Some(schema) = schema else {
+        return Ok("Successfully validated connection".to_string());
+    };
+
+    let fields: Vec<Field> = schema.fields.iter().map(|f| f.clone().into()).collect();
+    let drift = schema_drift(&MYSQL, &fields, &columns)?;
+    let evolutions = resolve_drift(
+        &MYSQL,
+        &quote_ident(&table.table_name),
+        (&table.schema_drift).into(),
+        &drift,
+    )?;
+
+    if evolutions.is_empty() {
+        Ok("Successfully validated connection".to_string())
+    } else {
+        Ok(format!(
+            "Successfully validated connection; the table will be altered when the pipeline starts:\n{}",
+            evolutions.join(";\n")
+        ))
+    }
+}
+
+impl Connector for MySqlConnector {
+    type ProfileT = MySqlConfig;
+    type TableT = MySqlTable;
+
+    fn name(&self) -> &'static str {
+        "mysql"
+    }
+
+    fn metadata(&self) -> arroyo_rpc::api_types::connections::Connector {
+        arroyo_rpc::api_types::connections::Connector {
+            id: "mysql".to_string(),
+            name: "MySQL".to_string(),
+            icon: "".to_string(),
+            description: "Write results to a MySQL table, upserting rows by key".to_string(),
+            enabled: true,
+            source: false,
+            sink: true,
+            testing: true,
+            hidden: false,
+            custom_schemas: true,
+            connection_config: Some(CONFIG_SCHEMA.to_owned()),
+            table_config: TABLE_SCHEMA.to_owned(),
+        }
+    }
+
+    fn config_description(&self, config: Self::ProfileT) -> String {
+        format!("{}:{}/{}", config.host, config.port, config.database)
+    }
+
+    fn table_type(&self, _: Self::ProfileT, _: Self::TableT) -> ConnectionType {
+        ConnectionType::Sink
+    }
+
+    fn get_schema(
+        &self,
+        _: Self::ProfileT,
+        _: Self::TableT,
+        s: Option<&ConnectionSchema>,
+    ) -> Option<ConnectionSchema> {
+        s.cloned()
+    }
+
+    fn test_profile(&self, profile: Self::ProfileT) -> Option<Receiver<TestSourceMessage>> {
+        let (tx, rx) = tokio::sync::oneshot::channel();
+
+        tokio::spawn(async move {
+            let message = match test_inner(&profile, None, None).await {
+                Ok(m) => TestSourceMessage::done(m),
+                Err(e) => TestSourceMessage::fail(format!("Failed to connect to MySQL: {}", e)),
+            };
+
+            tx.send(message).unwrap();
+        });
+
+        Some(rx)
+    }
+
+    fn test(
+        &self,
+        _: &str,
+        config: Self::ProfileT,
+        table: Self::TableT,
+        schema: Option<&ConnectionSchema>,
+        tx: tokio::sync::mpsc::Sender<TestSourceMessage>,
+    ) {
+        let schema = schema.cloned();
+        tokio::task::spawn(async move {
+            let message = match test_inner(&config, Some(&table), schema.as_ref()).await {
+                Ok(m) => TestSourceMessage::done(m),
+                Err(e) => TestSourceMessage::fail(e.to_string()),
+            };
+
+            tx.send(message).await.unwrap();
+        });
+    }
+
+    fn from_options(
+        &self,
+        name: &str,
+        options: &mut HashMap<String, String>,
+        schema: Option<&ConnectionSchema>,
+        profile: Option<&ConnectionProfile>,
+    ) -> anyhow::Result<Connection> {
+        let config = match profile {
+            Some(p) => serde_json::from_value(p.config.clone())
+                .map_err(|e| anyhow!("invalid config for profile '{}' in database: {}", p.id, e))?,
+            None => MySqlConfig {
+                host: pull_opt("host", options)?,
+                port: pull_option_to_i64("port", options)?.unwrap_or(3306),
+                database: pull_opt("database", options)?,
+                user: VarStr::new(pull_opt("user", options)?),
+                password: options.remove("password").map(VarStr::new),
+                pool_size: pull_option_to_i64("pool_size", options)?.unwrap_or(4),
+            },
+        };
+
+        let table = MySqlTable {
+            table_name: pull_opt("table_name", options)?,
+            key_columns: options
+                .remove("key_columns")
+                .map(|keys| keys.split(',').map(|k| k.trim().to_string()).collect())
+                .unwrap_or_default(),
+            schema_drift: options
+                .remove("schema_drift")
+                .map(|mode| match mode.as_str() {
+                    "fail" => Ok(SchemaDrift::Fail),
+                    "coerce" => Ok(SchemaDrift::Coerce),
+                    "evolve" => Ok(SchemaDrift::Evolve),
+                    _ => Err(anyhow!(
+                        "invalid value for 'schema_drift': '{}'; expected one of 'fail', 'coerce' or 'evolve'",
+                        mode
+                    )),
+                })
+                .transpose()?
+                .unwrap_or(SchemaDrift::Coerce),
+            batch_size: pull_option_to_i64("batch_size", options)?.unwrap_or(1000),
+            flush_interval_millis: pull_option_to_i64("flush_interval_millis", options)?
+                .unwrap_or(1000),
+        };
+
+        self.from_config(None, name, config, table, schema)
+    }
+
+    fn from_config(
+        &self,
+        id: Option<i64>,
+        name: &str,
+        config: Self::ProfileT,
+        table: Self::TableT,
+        schema: Option<&ConnectionSchema>,
+    ) -> anyhow::Result<Connection> {
+        let mut schema = schema
+            .map(|s| s.to_owned())
+            .ok_or_else(|| anyhow!("no schema defined for MySQL sink"))?;
+
+        // like Postgres sinks, MySQL sinks always receive changes as debezium-style
+        // before/after rows
+        let format = schema.format.clone().unwrap_or_else(|| {
+            Format::Json(JsonFormat {
+                debezium: true,
+                timestamp_format: TimestampFormat::UnixMillis,
+                ..Default::default()
+            })
+        });
+
+        if !matches!(format, Format::Json(JsonFormat { debezium: true, .. })) {
+            bail!("MySQL sinks only support the debezium_json format");
+        }
+
+        if table.batch_size <= 0 {
+            bail!("batch_size must be positive");
+        }
+
+        if table.flush_interval_millis <= 0 {
+            bail!("flush_interval_millis must be positive");
+        }
+
+        check_schema(&schema, &table.key_columns, "MySQL")?;
+
+        schema.format = Some(format.clone());
+
+        let description = format!("MySqlSink<{}.{}>", config.database, table.table_name);
+
+        let config = OperatorConfig {
+            connection: serde_json::to_value(config).unwrap(),
+            table: serde_json::to_value(table).unwrap(),
+            rate_limit: None,
+            format: Some(format),
+            bad_data: schema.bad_data.clone(),
+            framing: None,
+            metadata_fields: vec![],
+        };
+
+        Ok(Connection {
+            id,
+            connector: self.name(),
+            name: name.to_string(),
+            connection_type: ConnectionType::Sink,
+            schema,
+            config: serde_json::to_string(&config).unwrap(),
+            description,
+        })
+    }
+
+    fn make_operator(
+        &self,
+        profile: Self::ProfileT,
+        table: Self::TableT,
+        _: OperatorConfig,
+    ) -> anyhow::Result<OperatorNode> {
+        Ok(OperatorNode::from_operator(Box::new(MySqlSinkFunc::new(
+            create_pool(&profile)?,
+            table,
+        ))))
+    }
+}
@@ -0,0 +1,417 @@
+use anyhow::bail;
+use arrow::array::RecordBatch;
+use arrow::datatypes::{DataType, Field};
+use arroyo_operator::context::ArrowContext;
+use arroyo_operator::operator::ArrowOperator;
+use arroyo_types::{CheckpointBarrier, NonRetryableError, SignalMessage};
+use async_trait::async_trait;
+use chrono::{DateTime, Datelike, Timelike, Utc};
+use mysql_async::prelude::Queryable;
+use mysql_async::{Conn, Params, Pool, TxOpts, Value};
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+use crate::mysql::{primary_key_columns, quote_ident, table_columns, MySqlTable, MYSQL};
+use crate::sink_schema::{resolve_drift, schema_drift};
+use crate::sql_sink::{PendingWrites, SqlValue};
+
+/// The maximum number of placeholders MySQL allows in a single prepared statement
+const MAX_PARAMS: usize = 65535;
+const MAX_WRITE_ATTEMPTS: u32 = 20;
+/// The error MySQL returns when adding a column that already exists
+const ER_DUP_FIELDNAME: u16 = 1060;
+
+fn mysql_value(value: &SqlValue) -> Value {
+    match value {
+        SqlValue::Null => Value::NULL,
+        SqlValue::Bool(v) => Value::Int(*v as i64),
+        SqlValue::Int4(v) => Value::Int(*v as i64),
+        SqlValue::Int8(v) => Value::Int(*v),
+        SqlValue::Float4(v) => Value::Float(*v),
+        SqlValue::Float8(v) => Value::Double(*v),
+        SqlValue::Text(v) => Value::Bytes(v.as_bytes().to_vec()),
+        SqlValue::Bytes(v) => Value::Bytes(v.clone()),
+        SqlValue::Timestamp(v) => {
+            // DATETIME and TIMESTAMP columns are written in UTC
+            let t: DateTime<Utc> = (*v).into();
+            Value::Date(
+                t.year() as u16,
+                t.month() as u8,
+                t.day() as u8,
+                t.hour() as u8,
+                t.minute() as u8,
+                t.second() as u8,
+                t.timestamp_subsec_micros(),
+            )
+        }
+    }
+}
+
+/// A list of `rows` parenthesized tuples of `columns` placeholders, like `(?, ?)`
+fn values_list(columns: usize, rows: usize) -> String {
+    let row = format!("({})", vec!["?"; columns].join(", "));
+    vec![row; rows].join(", ")
+}
+
+/// The statements used to write to the table, which are built once the table has been checked
+struct Statements {
+    table: String,
+    columns: Vec<String>,
+    key_indices: Vec<usize>,
+}
+
+impl Statements {
+    fn keyed(&self) -> bool {
+        !self.key_indices.is_empty()
+    }
+
+    /// Inserts `rows` rows, or upserts them if the table has key columns
+    fn insert(&self, rows: usize) -> String {
+        let columns: Vec<_> = self.columns.iter().map(|c| quote_ident(c)).collect();
+        let mut sql = format!(
+            "INSERT INTO {} ({}) VALUES {}",
+            self.table,
+            columns.join(", "),
+            values_list(columns.len(), rows)
+        );
+
+        if self.keyed() {
+            let updates: Vec<_> = columns
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| !self.key_indices.contains(i))
+                .map(|(_, c)| format!("{c} = VALUES({c})"))
+                .collect();
+
+            sql.push_str(" ON DUPLICATE KEY UPDATE ");
+            if updates.is_empty() {
+                // every column is part of the key, so there's nothing to update
+                let key = &columns[self.key_indices[0]];
+                sql.push_str(&format!("{key} = {key}"));
+            } else {
+                sql.push_str(&updates.join(", "));
+            }
+        }
+
+        sql
+    }
+
+    /// Deletes the rows with `rows` sets of key values
+    fn delete(&self, rows: usize) -> String {
+        let keys: Vec<_> = self
+            .key_indices
+            .iter()
+            .map(|i| quote_ident(&self.columns[*i]))
+            .collect();
+
+        format!(
+            "DELETE FROM {} WHERE ({}) IN ({})",
+            self.table,
+            keys.join(", "),
+            values_list(keys.len(), rows)
+        )
+    }
+}
+
+pub struct MySqlSinkFunc {
+    pool: Pool,
+    table: MySqlTable,
+    fields: Vec<Field>,
+    statements: Option<Statements>,
+    pending: PendingWrites,
+    last_flushed: Instant,
+}
+
+impl MySqlSinkFunc {
+    pub fn new(pool: Pool, table: MySqlTable) -> Self {
+        Self {
+            pool,
+            table,
+            fields: vec![],
+            statements: None,
+            pending: PendingWrites::default(),
+            last_flushed: Instant::now(),
+        }
+    }
+
+    async fn connect(&self, ctx: &mut ArrowContext) -> anyhow::Result<Conn> {
+        let mut attempts = 0;
+        loop {
+            match self.pool.get_conn().await {
+                Ok(conn) => return Ok(conn),
+                Err(e) => {
+                    attempts += 1;
+                    if attempts >= MAX_WRITE_ATTEMPTS {
+                        bail!(
+                            "failed to connect to MySQL after {} attempts: {}",
+                            attempts,
+                            e
+                        );
+                    }
+                    ctx.report_error("Failed to connect", e.to_string()).await;
+                    tokio::time::sleep(Duration::from_millis((50 * (1 << attempts)).min(5_000)))
+                        .await;
+                }
+            }
+        }
+    }
+
+    /// Checks the table against the fields of the sink, evolving it if the table allows it,
+    /// and returns the columns that identify its rows
+    async fn prepare_table(&self, conn: &mut Conn) -> anyhow::Result<Vec<String>> {
+        let columns = table_columns(conn, &self.table).await?;
+        let table = quote_ident(&self.table.table_name);
+
+        let drift = schema_drift(&MYSQL, &self.fields, &columns)?;
+        for d in &drift {
+            warn!("schema of MySQL table {} differs from sink: {}", table, d);
+        }
+
+        // the table won't change by itself, so restarting the pipeline wouldn't help
+        let evolutions = resolve_drift(&MYSQL, &table, (&self.table.schema_drift).into(), &drift)
+            .map_err(|e| NonRetryableError(e.to_string()))?;
+
+        for statement in evolutions {
+            info!("evolving MySQL table: {}", statement);
+            match conn.query_drop(statement.as_str()).await {
+                Err(mysql_async::Error::Server(e)) if e.code == ER_DUP_FIELDNAME => {}
+                r => r?,
+            }
+        }
+
+        if !self.table.key_columns.is_empty() {
+            return Ok(self.table.key_columns.clone());
+        }
+
+        primary_key_columns(conn, &self.table).await
+    }
+
+    /// Builds the statements to write with, once the table has been checked against the
+    /// fields of the sink
+    async fn prepare(&mut self, ctx: &mut ArrowContext) -> anyhow::Result<()> {
+        if self.statements.is_some() {
+            return Ok(());
+        }
+
+        let mut conn = self.connect(ctx).await?;
+        let key_columns = self.prepare_table(&mut conn).await?;
+
+        let columns: Vec<_> = self.fields.iter().map(|f| f.name().clone()).collect();
+        let key_indices = key_columns
+            .iter()
+            .map(|k| {
+                columns.iter().position(|c| c == k).ok_or_else(|| {
+                    NonRetryableError(format!(
+                        "key column '{}' is not a field of the MySQL sink",
+                        k
+                    ))
+                    .into()
+                })
+            })
+            .collect::<anyhow::Result<_>>()?;
+
+        info!(
+            "writing to MySQL table {} with key columns {:?}",
+            self.table.table_name, key_columns
+        );
+
+        self.statements = Some(Statements {
+            table: quote_ident(&self.table.table_name),
+            columns,
+            key_indices,
+        });
+
+        Ok(())
+    }
+
+    async fn write(&self) -> anyhow::Result<()> {
+        let statements = self.statements.as_ref().unwrap();
+
+        let mut conn = self.pool.get_conn().await?;
+        let mut transaction = conn.start_transaction(TxOpts::default()).await?;
+
+        let (upserts, deletes) = self.pending.writes();
+
+        for chunk in deletes.chunks(MAX_PARAMS / statements.key_indices.len().max(1)) {
+            let params: Vec<_> = chunk
+                .iter()
+                .flat_map(|row| row.iter().map(mysql_value))
+                .collect();
+            transaction
+                .exec_drop(statements.delete(chunk.len()), Params::Positional(params))
+                .await?;
+        }
+
+        for chunk in upserts.chunks(MAX_PARAMS / statements.columns.len()) {
+            let params: Vec<_> = chunk
+                .iter()
+                .flat_map(|row| row.iter().map(mysql_value))
+                .collect();
+            transaction
+                .exec_drop(statements.insert(chunk.len()), Params::Positional(params))
+                .await?;
+        }
+
+        transaction.commit().await?;
+        Ok(())
+    }
+
+    async fn flush(&mut self, ctx: &mut ArrowContext) -> anyhow::Result<()> {
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+
+        let mut attempts = 0;
+        while let Err(e) = self.write().await {
+            attempts += 1;
+            if attempts >= MAX_WRITE_ATTEMPTS {
+                bail!(
+                    "failed to write to MySQL after {} attempts: {}",
+                    attempts,
+                    e
+                );
+            }
+
+            ctx.report_error("Failed to write to MySQL", e.to_string())
+                .await;
+            tokio::time::sleep(Duration::from_millis((50 * (1 << attempts)).min(5_000))).await;
+        }
+
+        self.pending.clear();
+        self.last_flushed = Instant::now();
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl ArrowOperator for MySqlSinkFunc {
+    fn name(&self) -> String {
+        "MySqlSink".to_string()
+    }
+
+    fn tick_interval(&self) -> Option<Duration> {
+        Some(Duration::from_millis(
+            self.table.flush_interval_millis as u64,
+        ))
+    }
+
+    async fn on_start(&mut self, ctx: &mut ArrowContext) {
+        // the fields of the sink are checked when the pipeline is planned
+        let schema = &ctx
+            .in_schemas
+            .first()
+            .expect("no in-schema for MySQL sink!")
+            .schema;
+        let DataType::Struct(fields) = schema
+            .field_with_name("after")
+            .expect("MySQL sink input has no 'after' field")
+            .data_type()
+        else {
+            panic!("'after' field of MySQL sink input is not a struct");
+        };
+
+        self.fields = fields.iter().map(|f| f.as_ref().clone()).collect();
+    }
+
+    async fn process_batch(
+        &mut self,
+        batch: RecordBatch,
+        ctx: &mut ArrowContext,
+    ) -> anyhow::Result<()> {
+        self.prepare(ctx).await?;
+
+        let statements = self.statements.as_ref().unwrap();
+        self.pending.add_batch(
+            &batch,
+            statements.columns.len(),
+            &statements.key_indices,
+            &statements.table,
+        )?;
+
+        if self.pending.len() >= self.table.batch_size as usize {
+            self.flush(ctx).await?;
+        }
+        Ok(())
+    }
+
+    async fn handle_checkpoint(
+        &mut self,
+        _: CheckpointBarrier,
+        ctx: &mut ArrowContext,
+    ) -> anyhow::Result<()> {
+        // rows are written idempotently, so flushing before the checkpoint completes gives
+        // at-least-once delivery without needing any state
+        self.flush(ctx).await
+    }
+
+    async fn handle_tick(&mut self, _: u64, ctx: &mut ArrowContext) -> anyhow::Result<()> {
+        if self.last_flushed.elapsed()
+            >= Duration::from_millis(self.table.flush_interval_millis as u64)
+        {
+            self.flush(ctx).await?;
+        }
+        Ok(())
+    }
+
+    async fn on_close(
+        &mut self,
+        _: &Option<SignalMessage>,
+        ctx: &mut ArrowContext,
+    ) -> anyhow::Result<()> {
+        self.flush(ctx).await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::time::UNIX_EPOCH;
+
+    fn statements(keys: Vec<usize>) -> Statements {
+        Statements {
+            table: "`counts`".to_string(),
+            columns: vec!["id".to_string(), "name".to_string(), "count".to_string()],
+            key_indices: keys,
+        }
+    }
+
+    #[test]
+    fn test_statements() {
+        assert_eq!(
+            statements(vec![]).insert(2),
+            "INSERT INTO `counts` (`id`, `name`, `count`) VALUES (?, ?, ?), (?, ?, ?)"
+        );
+
+        let keyed = statements(vec![0, 1]);
+        assert_eq!(
+            keyed.insert(1),
+            "INSERT INTO `counts` (`id`, `name`, `count`) VALUES (?, ?, ?) \
+            ON DUPLICATE KEY UPDATE `count` = VALUES(`count`)"
+        );
+        assert_eq!(
+            keyed.delete(2),
+            "DELETE FROM `counts` WHERE (`id`, `name`) IN ((?, ?), (?, ?))"
+        );
+
+        assert_eq!(
+            statements(vec![0, 1, 2]).insert(1),
+            "INSERT INTO `counts` (`id`, `name`, `count`) VALUES (?, ?, ?) \
+            ON DUPLICATE KEY UPDATE `id` = `id`"
+        );
+    }
+
+    #[test]
+    fn test_mysql_value() {
+        assert_eq!(
+            mysql_value(&SqlValue::Timestamp(
+                UNIX_EPOCH + Duration::from_micros(86_400_000_001)
+            )),
+            Value::Date(1970, 1, 2, 0, 0, 0, 1)
+        );
+        assert_eq!(
+            mysql_value(&SqlValue::Text("a".to_string())),
+            Value::Bytes(b"a".to_vec())
+        );
+        assert_eq!(mysql_value(&SqlValue::Null), Value::NULL);
+    }
+}
@@ -221,19 +221,15 @@ impl Connector for NatsConnector {
     fn test(
         &self,
         _: &str,
-        _: Self::ProfileT,
-        _: Self::TableT,
+        config: Self::ProfileT,
+        table: Self::TableT,
         _: Option<&ConnectionSchema>,
         tx: Sender<TestSourceMessage>,
     ) {
-        // TODO: Implement a full-fledge `NatsTester` struct for testing the client connection,
-        // the stream or subject existence and access permissions, the deserialization of messages
-        // for the specified format, the authentication to the schema registry (if any), etc.
         tokio::spawn(async move {
-            let message = TestSourceMessage {
-                error: false,
-                done: true,
-                message: "Successfully validated connection".to_string(),
+            let message = match test_inner(config, table, &tx).await {
+                Ok(message) => TestSourceMessage::done(message),
+                Err(e) => TestSourceMessage::fail(format!("{:#}", e)),
             };
             tx.send(message).await.unwrap();
         });
@@ -382,6 +378,80 @@ impl Connector for NatsConnector {
     }
 }
 
+/// Connects to the servers and checks that the table's stream or subject can be used
+async fn test_inner(
+    config: NatsConfig,
+    table: NatsTable,
+    tx: &Sender<TestSourceMessage>,
+) -> anyhow::Result<String> {
+    let _ = tx.send(TestSourceMessage::info("Connecting to NATS")).await;
+    let client = get_nats_client(&config).await?;
+
+    match table.connector_type {
+        ConnectorType::Source {
+            source_type: Some(SourceType::Jetstream { stream, .. }),
+        } => {
+            let _ = tx
+                .send(TestSourceMessage::info(format!(
+                    "Fetching JetStream stream '{}'",
+                    stream
+                )))
+                .await;
+            let mut js_stream = async_nats::jetstream::new(client)
+                .get_stream(&stream)
+                .await
+                .map_err(|e| anyhow!("Failed to get stream '{}': {}", stream, e))?;
+            let info = js_stream
+                .info()
+                .await
+                .map_err(|e| anyhow!("Failed to get info for stream '{}': {}", stream, e))?;
+
+            Ok(format!(
+                "Successfully found stream '{}' with {} messages",
+                stream, info.state.messages
+            ))
+        }
+        ConnectorType::Source {
+            source_type: Some(SourceType::Core { subject, .. }),
+        } => {
+            let _ = tx
+                .send(TestSourceMessage::info(format!(
+                    "Subscribing to subject '{}'",
+                    subject
+                )))
+                .await;
+            let mut subscriber = client
+                .subscribe(subject.clone())
+                .await
+                .map_err(|e| anyhow!("Failed to subscribe to '{}': {}", subject, e))?;
+            // the server reports permission errors asynchronously, so wait for it to process
+            // the subscription
+            client
+                .flush()
+                .await
+                .map_err(|e| anyhow!("Failed to subscribe to '{}': {}", subject, e))?;
+            let _ = subscriber.unsubscribe().await;
+
+            Ok(format!("Successfully subscribed to subject '{}'", subject))
+        }
+        ConnectorType::Sink {
+            sink_type: Some(SinkType::Subject(subject)),
+        } => {
+            client
+                .flush()
+                .await
+                .map_err(|e| anyhow!("Failed to communicate with NATS: {}", e))?;
+
+            Ok(format!(
+                "Successfully connected to NATS to publish to '{}'",
+                subject
+            ))
+        }
+        ConnectorType::Source { source_type: None } => bail!("sourceType is required"),
+        ConnectorType::Sink { sink_type: None } => bail!("sinkType is required"),
+    }
+}
+
 async fn get_nats_client(connection: &NatsConfig) -> anyhow::Result<async_nats::Client> {
     let mut opts = async_nats::ConnectOptions::new();
 
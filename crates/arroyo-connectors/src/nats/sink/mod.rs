@@ -50,15 +50,25 @@ impl ArrowOperator for NatsSinkFunc {
         }
     }
 
-    async fn on_close(&mut self, _: &Option<SignalMessage>, ctx: &mut ArrowContext) {
+    async fn on_close(
+        &mut self,
+        _: &Option<SignalMessage>,
+        ctx: &mut ArrowContext,
+    ) -> anyhow::Result<()> {
         if let Some(ControlMessage::Commit { epoch, commit_data }) = ctx.control_rx.recv().await {
-            self.handle_commit(epoch, &commit_data, ctx).await;
+            self.handle_commit(epoch, &commit_data, ctx).await?;
         } else {
             warn!("No commit message received, not committing")
         }
+
+        Ok(())
     }
 
-    async fn handle_checkpoint(&mut self, _: CheckpointBarrier, _ctx: &mut ArrowContext) {
+    async fn handle_checkpoint(
+        &mut self,
+        _: CheckpointBarrier,
+        _ctx: &mut ArrowContext,
+    ) -> anyhow::Result<()> {
         // TODO: Implement checkpointing of in-progress data to avoid depending on
         // the downstream NATS availability to flush and checkpoint.
         let publisher = self
@@ -72,9 +82,14 @@ impl ArrowOperator for NatsSinkFunc {
                 panic!("Failed to flush NATS publisher: {:?}", e);
             }
         }
+        Ok(())
     }
 
-    async fn process_batch(&mut self, batch: RecordBatch, ctx: &mut ArrowContext) {
+    async fn process_batch(
+        &mut self,
+        batch: RecordBatch,
+        ctx: &mut ArrowContext,
+    ) -> anyhow::Result<()> {
         let SinkType::Subject(s) = &self.sink_type;
         let nats_subject = async_nats::Subject::from(s.clone());
         for msg in self.serializer.serialize(&batch) {
@@ -99,5 +114,6 @@ impl ArrowOperator for NatsSinkFunc {
                 }
             }
         }
+        Ok(())
     }
 }
@@ -472,6 +472,9 @@ impl NatsSourceFunc {
                                 Some(ControlMessage::LoadCompacted {compacted}) => {
                                     ctx.load_compacted(compacted).await;
                                 }
+                                Some(ControlMessage::QueryState { query }) => {
+                                    ctx.query_state(query).await;
+                                }
                                 Some(ControlMessage::NoOp) => {}
                                 None => {}
                             }
@@ -529,6 +532,9 @@ impl NatsSourceFunc {
                                 Some(ControlMessage::LoadCompacted {compacted}) => {
                                     ctx.load_compacted(compacted).await;
                                 }
+                                Some(ControlMessage::QueryState { query }) => {
+                                    ctx.query_state(query).await;
+                                }
                                 Some(ControlMessage::NoOp) => {}
                                 None => {}
                             }
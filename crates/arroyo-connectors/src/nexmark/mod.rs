@@ -12,11 +12,12 @@ use arroyo_rpc::api_types::connections::{
 use arroyo_rpc::OperatorConfig;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::num::NonZeroU64;
 use std::str::FromStr;
 use typify::import_types;
 
-use crate::nexmark::operator::NexmarkSourceFunc;
-use crate::{pull_opt, EmptyConfig};
+use crate::nexmark::operator::{NexmarkSourceFunc, RateSchedule};
+use crate::{pull_opt, pull_option_to_u64, EmptyConfig};
 
 const TABLE_SCHEMA: &str = include_str!("./table.json");
 const ICON: &str = include_str!("./nexmark.svg");
@@ -125,6 +126,10 @@ impl Connector for NexmarkConnector {
         ConnectionType::Source
     }
 
+    fn is_bounded(&self, _: Self::ProfileT, table: Self::TableT) -> bool {
+        table.runtime.is_some() || table.num_events.is_some()
+    }
+
     fn get_schema(
         &self,
         _: Self::ProfileT,
@@ -168,6 +173,24 @@ impl Connector for NexmarkConnector {
             .transpose()
             .map_err(|_| anyhow!("invalid value for runtime; expected float"))?;
 
+        let mut pull_non_zero = |name: &str| -> anyhow::Result<Option<NonZeroU64>> {
+            pull_option_to_u64(name, options)?
+                .map(|v| v.try_into())
+                .transpose()
+                .map_err(|_| anyhow!("{} must be greater than 0", name))
+        };
+
+        let num_events = pull_non_zero("num_events")?;
+        let person_proportion = pull_non_zero("person_proportion")?;
+        let auction_proportion = pull_non_zero("auction_proportion")?;
+        let bid_proportion = pull_non_zero("bid_proportion")?;
+        let hot_auction_ratio = pull_non_zero("hot_auction_ratio")?;
+        let hot_seller_ratio = pull_non_zero("hot_seller_ratio")?;
+        let hot_bidder_ratio = pull_non_zero("hot_bidder_ratio")?;
+
+        let seed = pull_option_to_u64("seed", options)?;
+        let rate_schedule = options.remove("rate_schedule");
+
         if let Some(schema) = schema {
             if !schema.fields.is_empty() && schema.fields != nexmark_schema().fields {
                 bail!("invalid schema for nexmark source; omit fields to rely on inference");
@@ -181,6 +204,15 @@ impl Connector for NexmarkConnector {
             NexmarkTable {
                 event_rate,
                 runtime,
+                num_events,
+                rate_schedule,
+                seed,
+                person_proportion,
+                auction_proportion,
+                bid_proportion,
+                hot_auction_ratio,
+                hot_seller_ratio,
+                hot_bidder_ratio,
             },
             None,
         )
@@ -194,9 +226,17 @@ impl Connector for NexmarkConnector {
         table: Self::TableT,
         _: Option<&ConnectionSchema>,
     ) -> anyhow::Result<Connection> {
+        if table.runtime.is_some() && table.num_events.is_some() {
+            bail!("only one of runtime and num_events may be set for a nexmark source");
+        }
+
+        if let Some(schedule) = &table.rate_schedule {
+            RateSchedule::parse(table.event_rate, schedule)?;
+        }
+
         let description = format!(
             "{}Nexmark<{} eps>",
-            if table.runtime.is_some() {
+            if table.runtime.is_some() || table.num_events.is_some() {
                 "Bounded"
             } else {
                 ""
@@ -232,7 +272,7 @@ impl Connector for NexmarkConnector {
         _: OperatorConfig,
     ) -> anyhow::Result<OperatorNode> {
         Ok(OperatorNode::from_source(Box::new(
-            NexmarkSourceFunc::from_config(&table),
+            NexmarkSourceFunc::from_config(&table)?,
         )))
     }
 }
@@ -1,4 +1,5 @@
 use crate::nexmark::{auction_fields, bid_fields, person_fields, NexmarkTable};
+use anyhow::{anyhow, bail};
 use arrow::array::{
     Int64Builder, RecordBatch, StringBuilder, StructBuilder, TimestampNanosecondBuilder,
 };
@@ -168,8 +169,7 @@ impl ArrowWriter for Option<&Bid> {
 }
 
 pub struct NexmarkSourceFunc {
-    first_event_rate: f64,
-    num_events: Option<u64>,
+    config: NexmarkConfig,
     state: Option<NexmarkSourceState>,
 }
 
@@ -183,20 +183,44 @@ impl NexmarkSourceFunc {
     #[allow(unused)]
     pub fn new(first_event_rate: u64, num_events: Option<u64>) -> Self {
         Self {
-            first_event_rate: first_event_rate as f64,
-            num_events,
+            config: NexmarkConfig::new(first_event_rate as f64, num_events, 1),
             state: None,
         }
     }
 
-    pub fn from_config(table: &NexmarkTable) -> Self {
-        Self {
-            first_event_rate: table.event_rate,
-            num_events: table
-                .runtime
-                .map(|time| (table.event_rate * time).floor() as u64),
-            state: None,
+    pub fn from_config(table: &NexmarkTable) -> anyhow::Result<Self> {
+        let rate_schedule = match &table.rate_schedule {
+            Some(schedule) => RateSchedule::parse(table.event_rate, schedule)?,
+            None => RateSchedule::constant(table.event_rate),
+        };
+
+        let num_events = match (table.num_events, table.runtime) {
+            (Some(num_events), _) => Some(num_events.get()),
+            (None, Some(runtime)) => Some(rate_schedule.events_in(runtime).floor() as u64),
+            (None, None) => None,
+        };
+
+        let mut config = NexmarkConfig::new(table.event_rate, num_events, 1);
+        config.rate_schedule = rate_schedule;
+        config.seed = table.seed;
+
+        for (field, value) in [
+            (&mut config.person_proportion, table.person_proportion),
+            (&mut config.auction_proportion, table.auction_proportion),
+            (&mut config.bid_proportion, table.bid_proportion),
+            (&mut config.hot_auction_ratio, table.hot_auction_ratio),
+            (&mut config.hot_seller_ratio, table.hot_seller_ratio),
+            (&mut config.hot_bidders_ratio, table.hot_bidder_ratio),
+        ] {
+            if let Some(value) = value {
+                *field = value.get();
+            }
         }
+
+        Ok(Self {
+            config,
+            state: None,
+        })
     }
 }
 
@@ -220,17 +244,12 @@ impl SourceOperator for NexmarkSourceFunc {
                 .expect("should be able to read state");
             let saved_states = ss.get_all().len();
             if saved_states != ctx.task_info.parallelism {
-                let config = GeneratorConfig::new(
-                    NexmarkConfig::new(
-                        self.first_event_rate,
-                        self.num_events,
-                        ctx.task_info.parallelism,
-                    ),
-                    SystemTime::now(),
-                    1,
-                    self.num_events,
-                    1,
-                );
+                let mut nexmark_config = self.config.clone();
+                nexmark_config.num_event_generators = ctx.task_info.parallelism as u64;
+                let num_events = nexmark_config.num_events;
+
+                let config =
+                    GeneratorConfig::new(nexmark_config, SystemTime::now(), 1, num_events, 1);
                 let splits = config.split(ctx.task_info.parallelism as u64);
                 NexmarkSourceState {
                     config: splits[ctx.task_info.task_index].clone(),
@@ -247,7 +266,15 @@ impl SourceOperator for NexmarkSourceFunc {
 
         let mut generator = NexmarkGenerator::from_config(&state.config, state.event_count as u64);
 
-        let mut random = SmallRng::seed_from_u64(ctx.task_info.task_index as u64);
+        let mut random = SmallRng::seed_from_u64(
+            state
+                .config
+                .configuration
+                .seed
+                .unwrap_or_default()
+                .wrapping_mul(31)
+                .wrapping_add(ctx.task_info.task_index as u64),
+        );
         let mut last_check = Instant::now();
 
         let mut records = 0;
@@ -335,6 +362,112 @@ impl SourceOperator for NexmarkSourceFunc {
     }
 }
 
+/// The rate events are generated at over time, which ramps linearly from the first rate to each
+/// of the points in turn and then holds at the rate of the last one
+#[derive(Clone, Encode, Decode, Debug, PartialEq)]
+pub struct RateSchedule {
+    first_rate: f64,
+    /// (seconds since the start, events per second)
+    points: Vec<(f64, f64)>,
+}
+
+impl RateSchedule {
+    pub fn constant(rate: f64) -> Self {
+        Self {
+            first_rate: rate,
+            points: vec![],
+        }
+    }
+
+    /// Parses a schedule of the form `60:10000,300:10000,360:100`
+    pub fn parse(first_rate: f64, schedule: &str) -> anyhow::Result<Self> {
+        let mut points: Vec<(f64, f64)> = vec![];
+        for point in schedule
+            .split(',')
+            .map(|p| p.trim())
+            .filter(|p| !p.is_empty())
+        {
+            let (seconds, rate) = point
+                .split_once(':')
+                .and_then(|(s, r)| Some((s.trim().parse().ok()?, r.trim().parse().ok()?)))
+                .ok_or_else(|| {
+                    anyhow!(
+                        "invalid rate_schedule point '{}'; expected seconds:rate",
+                        point
+                    )
+                })?;
+
+            if !(rate >= 0.0 && f64::is_finite(rate)) {
+                bail!("invalid rate in rate_schedule point '{}'", point);
+            }
+
+            if seconds <= points.last().map(|(s, _)| *s).unwrap_or(0.0) {
+                bail!("the times in rate_schedule must be positive and increasing");
+            }
+
+            points.push((seconds, rate));
+        }
+
+        if points.last().map(|(_, r)| *r).unwrap_or(first_rate) <= 0.0 {
+            bail!("the final rate in rate_schedule must be greater than 0");
+        }
+
+        Ok(Self { first_rate, points })
+    }
+
+    pub fn is_constant(&self) -> bool {
+        self.points.is_empty()
+    }
+
+    fn segments(&self) -> impl Iterator<Item = ((f64, f64), (f64, f64))> + '_ {
+        std::iter::once((0.0, self.first_rate))
+            .chain(self.points.iter().copied())
+            .zip(self.points.iter().copied())
+    }
+
+    fn last(&self) -> (f64, f64) {
+        self.points
+            .last()
+            .copied()
+            .unwrap_or((0.0, self.first_rate))
+    }
+
+    /// The number of events generated in the first `seconds`
+    pub fn events_in(&self, seconds: f64) -> f64 {
+        let mut events = 0.0;
+        for ((t0, r0), (t1, r1)) in self.segments() {
+            if seconds <= t1 {
+                let dt = seconds - t0;
+                return events + r0 * dt + (r1 - r0) / (2.0 * (t1 - t0)) * dt * dt;
+            }
+            events += (r0 + r1) / 2.0 * (t1 - t0);
+        }
+
+        let (t, r) = self.last();
+        events + r * (seconds - t)
+    }
+
+    /// The time, in seconds since the start, at which the `events`th event is generated
+    pub fn seconds_for(&self, mut events: f64) -> f64 {
+        if events <= 0.0 {
+            return 0.0;
+        }
+
+        for ((t0, r0), (t1, r1)) in self.segments() {
+            let segment_events = (r0 + r1) / 2.0 * (t1 - t0);
+            if events <= segment_events {
+                // solves events = r0 * dt + a * dt^2, in a form that is also stable for a = 0
+                let a = (r1 - r0) / (2.0 * (t1 - t0));
+                return t0 + 2.0 * events / (r0 + (r0 * r0 + 4.0 * a * events).sqrt());
+            }
+            events -= segment_events;
+        }
+
+        let (t, r) = self.last();
+        t + events / r
+    }
+}
+
 #[derive(Clone, Encode, Decode, Debug, PartialEq)]
 pub struct NexmarkConfig {
     num_events: Option<u64>,
@@ -363,6 +496,8 @@ pub struct NexmarkConfig {
     occasional_delay_seconds: u64,
     prob_delayed_event: f64,
     out_of_order_group_size: u64,
+    rate_schedule: RateSchedule,
+    seed: Option<u64>,
 }
 
 impl NexmarkConfig {
@@ -398,6 +533,8 @@ impl NexmarkConfig {
             occasional_delay_seconds: 3,
             prob_delayed_event: 0.1,
             out_of_order_group_size: 50,
+            rate_schedule: RateSchedule::constant(first_event_rate),
+            seed: None,
         }
     }
 
@@ -632,8 +769,18 @@ impl GeneratorConfig {
     }
 
     fn timestamp_for_event(&self, event_number: u64) -> SystemTime {
-        self.base_time
-            + Duration::from_nanos(self.inter_event_delay.as_nanos() as u64 * event_number)
+        if self.configuration.rate_schedule.is_constant() {
+            self.base_time
+                + Duration::from_nanos(self.inter_event_delay.as_nanos() as u64 * event_number)
+        } else {
+            // the schedule is for all generators, which take turns producing events
+            self.base_time
+                + Duration::from_secs_f64(
+                    self.configuration.rate_schedule.seconds_for(
+                        (event_number * self.configuration.num_event_generators) as f64,
+                    ),
+                )
+        }
     }
 
     fn next_price(random: &mut SmallRng) -> u64 {
@@ -829,7 +976,12 @@ impl NexmarkGenerator {
             generator_config: generator_config.clone(),
             channel_cache: ChannelCache {
                 cache: HashMap::new(),
-                random: SmallRng::seed_from_u64(to_millis(generator_config.base_time)),
+                random: SmallRng::seed_from_u64(
+                    generator_config
+                        .configuration
+                        .seed
+                        .unwrap_or_else(|| to_millis(generator_config.base_time)),
+                ),
             },
             events_count_so_far,
             wallclock_base_time,
@@ -1,4 +1,4 @@
-use crate::nexmark::operator::{GeneratorConfig, NexmarkConfig, NexmarkGenerator};
+use crate::nexmark::operator::{GeneratorConfig, NexmarkConfig, NexmarkGenerator, RateSchedule};
 use rand::{rngs::SmallRng, SeedableRng};
 use std::{borrow::BorrowMut, time::SystemTime};
 
@@ -36,3 +36,23 @@ fn test_exit() {
         generator.next_event(&mut random);
     }
 }
+
+#[test]
+fn test_rate_schedule() {
+    let schedule = RateSchedule::parse(100.0, "10:1100,20:1100,30:100").unwrap();
+
+    // ramps up from 100 to 1100 over the first 10 seconds
+    assert_eq!(schedule.events_in(10.0), 6000.0);
+    assert_eq!(schedule.events_in(20.0), 17000.0);
+    assert_eq!(schedule.events_in(30.0), 23000.0);
+    assert_eq!(schedule.events_in(40.0), 24000.0);
+
+    for seconds in [0.0, 0.5, 5.0, 10.0, 15.0, 25.0, 35.0] {
+        let events = schedule.events_in(seconds);
+        assert!((schedule.seconds_for(events) - seconds).abs() < 1e-6);
+    }
+
+    assert!(RateSchedule::parse(100.0, "10:1000,5:100").is_err());
+    assert!(RateSchedule::parse(100.0, "10:1000,20:0").is_err());
+    assert!(RateSchedule::parse(100.0, "10").is_err());
+}
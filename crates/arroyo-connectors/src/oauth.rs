@@ -0,0 +1,395 @@
+//! OAuth2 support for the HTTP-based connectors (polling HTTP, SSE, websocket, webhook and the
+//! HTTP sink).
+//!
+//! Tokens are fetched from the configured token endpoint using either the client-credentials
+//! or refresh-token grant, cached until shortly before they expire, and shared between all
+//! requests made by an operator.
+
+use crate::pull_opt;
+use anyhow::{anyhow, bail};
+use arroyo_rpc::var_str::VarStr;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+use tracing::{info, warn};
+
+// refresh tokens this long before they're due to expire, to account for clock skew and
+// request latency
+const EXPIRY_MARGIN: Duration = Duration::from_secs(30);
+const TOKEN_REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+const TOKEN_REQUEST_ATTEMPTS: u32 = 5;
+const INITIAL_RETRY_BACKOFF: Duration = Duration::from_millis(500);
+const MAX_RETRY_BACKOFF: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone)]
+pub struct OAuthConfig {
+    pub token_url: String,
+    pub client_id: String,
+    pub client_secret: Option<String>,
+    pub scopes: Vec<String>,
+    pub refresh_token: Option<String>,
+}
+
+/// The shared schema of the `oauth` option of the HTTP-based connectors' tables. Table schemas
+/// declare that option as `{"type": "object", "format": "oauth"}`, which typify maps to
+/// [`OAuthOptions`] and [`with_oauth_schema`] expands to this schema for the UI.
+const OAUTH_SCHEMA: &str = include_str!("./oauth.json");
+
+/// The `oauth` option of a table, as described by `oauth.json`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OAuthOptions {
+    pub token_url: String,
+    pub client_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_secret: Option<VarStr>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scopes: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub refresh_token: Option<VarStr>,
+}
+
+impl OAuthOptions {
+    /// Reads the `oauth_*` options of a SQL table, returning None if OAuth is not configured.
+    /// Their values are checked when the table's config is validated, by [`OAuthOptions::config`].
+    pub fn from_options(options: &mut HashMap<String, String>) -> anyhow::Result<Option<Self>> {
+        if !options.keys().any(|k| k.starts_with("oauth_")) {
+            return Ok(None);
+        }
+
+        Ok(Some(Self {
+            token_url: pull_opt("oauth_token_url", options)?,
+            client_id: pull_opt("oauth_client_id", options)?,
+            client_secret: options.remove("oauth_client_secret").map(VarStr::new),
+            scopes: options.remove("oauth_scopes"),
+            refresh_token: options.remove("oauth_refresh_token").map(VarStr::new),
+        }))
+    }
+
+    /// Validates the options, resolving any environment variables in the secrets, and builds the
+    /// config that tokens are fetched with
+    pub fn config(&self) -> anyhow::Result<OAuthConfig> {
+        if let Err(e) = reqwest::Url::parse(&self.token_url) {
+            bail!("invalid oauth token_url '{}': {:?}", self.token_url, e);
+        }
+
+        if self.client_id.is_empty() {
+            bail!("oauth client_id must not be empty");
+        }
+
+        Ok(OAuthConfig {
+            token_url: self.token_url.clone(),
+            client_id: self.client_id.clone(),
+            client_secret: self
+                .client_secret
+                .as_ref()
+                .map(|s| s.sub_env_vars())
+                .transpose()?,
+            scopes: self
+                .scopes
+                .as_deref()
+                .unwrap_or_default()
+                .split([',', ' '])
+                .filter(|s| !s.is_empty())
+                .map(|s| s.to_string())
+                .collect(),
+            refresh_token: self
+                .refresh_token
+                .as_ref()
+                .map(|s| s.sub_env_vars())
+                .transpose()?,
+        })
+    }
+}
+
+/// Builds the [`OAuthConfig`] for a table's `oauth` option, returning None if it isn't set
+pub fn oauth_config(options: Option<&OAuthOptions>) -> anyhow::Result<Option<OAuthConfig>> {
+    options.map(OAuthOptions::config).transpose()
+}
+
+/// Expands the properties of a table schema declared with `"format": "oauth"` to the shared
+/// OAuth schema, keeping their own title and description
+pub fn with_oauth_schema(table_schema: &str) -> String {
+    let oauth: serde_json::Value =
+        serde_json::from_str(OAUTH_SCHEMA).expect("oauth.json is not valid JSON");
+    let mut schema: serde_json::Value =
+        serde_json::from_str(table_schema).expect("table schema is not valid JSON");
+
+    if let Some(properties) = schema.get_mut("properties").and_then(|p| p.as_object_mut()) {
+        for property in properties.values_mut() {
+            if property.get("format").and_then(|f| f.as_str()) != Some("oauth") {
+                continue;
+            }
+
+            let mut shared = oauth.clone();
+            for key in ["title", "description"] {
+                if let Some(value) = property.get(key) {
+                    shared[key] = value.clone();
+                }
+            }
+            *property = shared;
+        }
+    }
+
+    schema.to_string()
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    expires_in: Option<u64>,
+    refresh_token: Option<String>,
+}
+
+struct CachedToken {
+    access_token: String,
+    expires_at: Option<Instant>,
+}
+
+struct TokenState {
+    token: Option<CachedToken>,
+    // servers may rotate refresh tokens on every use, so we track the most recent one
+    refresh_token: Option<String>,
+}
+
+/// Fetches and caches access tokens for an [`OAuthConfig`]. Cloning shares the cache.
+#[derive(Clone)]
+pub struct TokenProvider {
+    config: Arc<OAuthConfig>,
+    client: reqwest::Client,
+    state: Arc<Mutex<TokenState>>,
+}
+
+impl TokenProvider {
+    pub fn new(config: OAuthConfig) -> Self {
+        let refresh_token = config.refresh_token.clone();
+        Self {
+            config: Arc::new(config),
+            client: reqwest::ClientBuilder::new()
+                .timeout(TOKEN_REQUEST_TIMEOUT)
+                .build()
+                .expect("could not construct HTTP client"),
+            state: Arc::new(Mutex::new(TokenState {
+                token: None,
+                refresh_token,
+            })),
+        }
+    }
+
+    /// Returns a valid access token, fetching a new one if there is no cached token or it is
+    /// about to expire
+    pub async fn token(&self) -> anyhow::Result<String> {
+        let mut state = self.state.lock().await;
+
+        if let Some(token) = &state.token {
+            if token
+                .expires_at
+                .map(|t| Instant::now() + EXPIRY_MARGIN < t)
+                .unwrap_or(true)
+            {
+                return Ok(token.access_token.clone());
+            }
+        }
+
+        let resp = self.fetch(state.refresh_token.as_deref()).await?;
+
+        if resp.refresh_token.is_some() {
+            state.refresh_token = resp.refresh_token;
+        }
+
+        state.token = Some(CachedToken {
+            access_token: resp.access_token.clone(),
+            expires_at: resp
+                .expires_in
+                .map(|s| Instant::now() + Duration::from_secs(s)),
+        });
+
+        Ok(resp.access_token)
+    }
+
+    /// Drops the cached token, for example after the server has rejected it, so that the next
+    /// call to [`TokenProvider::token`] fetches a new one
+    pub async fn invalidate(&self) {
+        self.state.lock().await.token = None;
+    }
+
+    /// The value to send in the `Authorization` header
+    pub async fn authorization(&self) -> anyhow::Result<String> {
+        Ok(format!("Bearer {}", self.token().await?))
+    }
+
+    /// Like [`TokenProvider::authorization`], but retries failed token requests with
+    /// exponential backoff, so that a brief outage of the token endpoint doesn't fail the
+    /// operator
+    pub async fn authorization_with_retry(&self) -> anyhow::Result<String> {
+        let mut backoff = INITIAL_RETRY_BACKOFF;
+        let mut attempt = 1;
+        loop {
+            match self.authorization().await {
+                Ok(authorization) => return Ok(authorization),
+                Err(e) if attempt < TOKEN_REQUEST_ATTEMPTS => {
+                    warn!(
+                        message = "failed to fetch OAuth token, retrying",
+                        error = format!("{:#}", e),
+                        attempt,
+                        backoff = ?backoff
+                    );
+                    tokio::time::sleep(backoff).await;
+                    backoff = (backoff * 2).min(MAX_RETRY_BACKOFF);
+                    attempt += 1;
+                }
+                Err(e) => {
+                    return Err(e.context(format!("failed after {} attempts", attempt)));
+                }
+            }
+        }
+    }
+
+    async fn fetch(&self, refresh_token: Option<&str>) -> anyhow::Result<TokenResponse> {
+        let mut params = vec![("client_id", self.config.client_id.as_str())];
+
+        if let Some(secret) = &self.config.client_secret {
+            params.push(("client_secret", secret.as_str()));
+        }
+
+        match refresh_token {
+            Some(refresh_token) => {
+                params.push(("grant_type", "refresh_token"));
+                params.push(("refresh_token", refresh_token));
+            }
+            None => {
+                params.push(("grant_type", "client_credentials"));
+            }
+        }
+
+        let scopes = self.config.scopes.join(" ");
+        if !scopes.is_empty() {
+            params.push(("scope", scopes.as_str()));
+        }
+
+        let resp = self
+            .client
+            .post(&self.config.token_url)
+            .form(&params)
+            .send()
+            .await
+            .map_err(|e| anyhow!("failed to request OAuth token: {}", e))?;
+
+        let status = resp.status();
+        let body = resp
+            .bytes()
+            .await
+            .map_err(|e| anyhow!("failed to read OAuth token response: {}", e))?;
+
+        if !status.is_success() {
+            bail!(
+                "OAuth token endpoint responded with {}: {}",
+                status,
+                String::from_utf8_lossy(&body)
+            );
+        }
+
+        let token: TokenResponse = serde_json::from_slice(&body)
+            .map_err(|e| anyhow!("invalid OAuth token response: {}", e))?;
+
+        info!(
+            message = "fetched OAuth access token",
+            token_url = %self.config.token_url,
+            expires_in = token.expires_in
+        );
+
+        Ok(token)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(token_url: &str, client_id: &str) -> OAuthOptions {
+        OAuthOptions {
+            token_url: token_url.to_string(),
+            client_id: client_id.to_string(),
+            client_secret: None,
+            scopes: None,
+            refresh_token: None,
+        }
+    }
+
+    #[test]
+    fn test_oauth_config() {
+        assert!(oauth_config(None).unwrap().is_none());
+
+        assert!(options("not a url", "client").config().is_err());
+        assert!(options("https://auth.example.com/token", "")
+            .config()
+            .is_err());
+
+        let config = OAuthOptions {
+            client_secret: Some(VarStr::new("secret".to_string())),
+            scopes: Some("read:events, write:events".to_string()),
+            ..options("https://auth.example.com/token", "client")
+        }
+        .config()
+        .unwrap();
+
+        assert_eq!(config.client_secret.as_deref(), Some("secret"));
+        assert_eq!(config.scopes, vec!["read:events", "write:events"]);
+        assert!(config.refresh_token.is_none());
+    }
+
+    #[test]
+    fn test_oauth_options() {
+        let mut options = HashMap::new();
+        assert!(OAuthOptions::from_options(&mut options).unwrap().is_none());
+
+        // the token url and client id are required once any OAuth option is set
+        options.insert("oauth_client_id".to_string(), "client".to_string());
+        assert!(OAuthOptions::from_options(&mut options).is_err());
+
+        let mut options = HashMap::from([(
+            "oauth_token_url".to_string(),
+            "https://auth.example.com/token".to_string(),
+        )]);
+        assert!(OAuthOptions::from_options(&mut options).is_err());
+
+        options.insert("oauth_client_id".to_string(), "client".to_string());
+        options.insert(
+            "oauth_token_url".to_string(),
+            "https://auth.example.com/token".to_string(),
+        );
+        options.insert("oauth_scopes".to_string(), "read:events".to_string());
+        let oauth = OAuthOptions::from_options(&mut options).unwrap().unwrap();
+        assert!(options.is_empty());
+
+        let config = oauth_config(Some(&oauth)).unwrap().unwrap();
+        assert_eq!(config.client_id, "client");
+        assert_eq!(config.scopes, vec!["read:events"]);
+    }
+
+    #[test]
+    fn test_with_oauth_schema() {
+        let schema = with_oauth_schema(
+            r#"{
+                "type": "object",
+                "properties": {
+                    "endpoint": {"type": "string"},
+                    "oauth": {"title": "Auth", "type": "object", "format": "oauth"}
+                }
+            }"#,
+        );
+        let schema: serde_json::Value = serde_json::from_str(&schema).unwrap();
+
+        assert_eq!(schema["properties"]["endpoint"]["type"], "string");
+        let oauth = &schema["properties"]["oauth"];
+        assert_eq!(oauth["title"], "Auth");
+        assert!(oauth.get("format").is_none());
+        assert_eq!(oauth["properties"]["token_url"]["format"], "uri");
+        assert_eq!(
+            oauth["required"],
+            serde_json::json!(["token_url", "client_id"])
+        );
+    }
+}
@@ -0,0 +1,259 @@
+mod proto;
+mod source;
+
+use anyhow::{anyhow, bail};
+use arroyo_operator::connector::{Connection, Connector};
+use arroyo_operator::operator::OperatorNode;
+use arroyo_rpc::api_types::connections::FieldType::Primitive;
+use arroyo_rpc::api_types::connections::{
+    ConnectionProfile, ConnectionSchema, ConnectionType, PrimitiveType, SourceField,
+    TestSourceMessage,
+};
+use arroyo_rpc::formats::{Format, JsonFormat};
+use arroyo_rpc::OperatorConfig;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::str::FromStr;
+use typify::import_types;
+
+use crate::otlp::source::OtlpSourceFunc;
+use crate::{pull_opt, pull_option_to_i64, source_field, EmptyConfig};
+
+const TABLE_SCHEMA: &str = include_str!("./table.json");
+
+import_types!(schema = "src/otlp/table.json");
+
+/// Receives OpenTelemetry traces, metrics or logs over OTLP, so that telemetry can be sent
+/// directly from applications or collectors. Each table serves a single signal, and
+/// flattens it into one row per span, metric data point or log record.
+pub struct OtlpConnector {}
+
+fn field(name: &str, t: PrimitiveType, nullable: bool) -> SourceField {
+    SourceField {
+        nullable,
+        ..source_field(name, Primitive(t))
+    }
+}
+
+/// The columns describing the origin of the telemetry, which every signal has
+fn origin_fields() -> Vec<SourceField> {
+    vec![
+        field("service_name", PrimitiveType::String, true),
+        field("scope_name", PrimitiveType::String, true),
+        field("resource_attributes", PrimitiveType::Json, false),
+    ]
+}
+
+pub fn otlp_schema(signal: &Signal) -> ConnectionSchema {
+    let mut fields = match signal {
+        Signal::Traces => vec![
+            field("trace_id", PrimitiveType::String, false),
+            field("span_id", PrimitiveType::String, false),
+            field("parent_span_id", PrimitiveType::String, true),
+            field("trace_state", PrimitiveType::String, true),
+            field("name", PrimitiveType::String, false),
+            field("kind", PrimitiveType::String, false),
+            field("start_time", PrimitiveType::UnixNanos, true),
+            field("end_time", PrimitiveType::UnixNanos, true),
+            field("status_code", PrimitiveType::String, false),
+            field("status_message", PrimitiveType::String, true),
+            field("attributes", PrimitiveType::Json, false),
+            field("events", PrimitiveType::Json, false),
+        ],
+        Signal::Metrics => vec![
+            field("name", PrimitiveType::String, false),
+            field("description", PrimitiveType::String, true),
+            field("unit", PrimitiveType::String, true),
+            field("metric_type", PrimitiveType::String, false),
+            field("is_monotonic", PrimitiveType::Bool, true),
+            field("time", PrimitiveType::UnixNanos, true),
+            field("start_time", PrimitiveType::UnixNanos, true),
+            field("value", PrimitiveType::F64, true),
+            field("count", PrimitiveType::UInt64, true),
+            field("sum", PrimitiveType::F64, true),
+            field("min", PrimitiveType::F64, true),
+            field("max", PrimitiveType::F64, true),
+            field("attributes", PrimitiveType::Json, false),
+        ],
+        Signal::Logs => vec![
+            field("time", PrimitiveType::UnixNanos, true),
+            field("observed_time", PrimitiveType::UnixNanos, true),
+            field("severity_number", PrimitiveType::Int32, false),
+            field("severity_text", PrimitiveType::String, true),
+            field("body", PrimitiveType::String, true),
+            field("trace_id", PrimitiveType::String, true),
+            field("span_id", PrimitiveType::String, true),
+            field("attributes", PrimitiveType::Json, false),
+        ],
+    };
+
+    fields.extend(origin_fields());
+
+    ConnectionSchema {
+        format: Some(Format::Json(JsonFormat::default())),
+        framing: None,
+        bad_data: None,
+        struct_name: None,
+        fields,
+        definition: None,
+        inferred: None,
+    }
+}
+
+impl OtlpTable {
+    fn address(&self) -> anyhow::Result<SocketAddr> {
+        let ip = IpAddr::from_str(&self.bind_address)
+            .map_err(|_| anyhow!("invalid bind_address '{}'", self.bind_address))?;
+        let port = u16::try_from(self.port)
+            .ok()
+            .filter(|p| *p != 0)
+            .ok_or_else(|| anyhow!("invalid port {}; expected 1-65535", self.port))?;
+
+        Ok(SocketAddr::new(ip, port))
+    }
+}
+
+impl Connector for OtlpConnector {
+    type ProfileT = EmptyConfig;
+    type TableT = OtlpTable;
+
+    fn name(&self) -> &'static str {
+        "otlp"
+    }
+
+    fn metadata(&self) -> arroyo_rpc::api_types::connections::Connector {
+        arroyo_rpc::api_types::connections::Connector {
+            id: "otlp".to_string(),
+            name: "OpenTelemetry".to_string(),
+            icon: "".to_string(),
+            description: "Receive traces, metrics and logs over OTLP".to_string(),
+            enabled: true,
+            source: true,
+            sink: false,
+            testing: false,
+            hidden: false,
+            custom_schemas: false,
+            connection_config: None,
+            table_config: TABLE_SCHEMA.to_owned(),
+        }
+    }
+
+    fn table_type(&self, _: Self::ProfileT, _: Self::TableT) -> ConnectionType {
+        ConnectionType::Source
+    }
+
+    fn max_source_parallelism(&self, _: Self::ProfileT, _: Self::TableT) -> Option<usize> {
+        // only one subtask can listen on the port
+        Some(1)
+    }
+
+    fn get_schema(
+        &self,
+        _: Self::ProfileT,
+        table: Self::TableT,
+        _: Option<&ConnectionSchema>,
+    ) -> Option<ConnectionSchema> {
+        Some(otlp_schema(&table.signal))
+    }
+
+    fn test(
+        &self,
+        _: &str,
+        _: Self::ProfileT,
+        table: Self::TableT,
+        _: Option<&ConnectionSchema>,
+        tx: tokio::sync::mpsc::Sender<TestSourceMessage>,
+    ) {
+        tokio::task::spawn(async move {
+            let message = match table.address() {
+                Ok(_) => TestSourceMessage::done("Successfully validated connection"),
+                Err(e) => TestSourceMessage::fail(format!("{:#}", e)),
+            };
+            tx.send(message).await.unwrap();
+        });
+    }
+
+    fn from_options(
+        &self,
+        name: &str,
+        options: &mut HashMap<String, String>,
+        schema: Option<&ConnectionSchema>,
+        _: Option<&ConnectionProfile>,
+    ) -> anyhow::Result<Connection> {
+        let signal = pull_opt("signal", options)?;
+        let signal = Signal::try_from(&signal).map_err(|_| {
+            anyhow!(
+                "invalid signal '{}'; expected one of 'traces', 'metrics' or 'logs'",
+                signal
+            )
+        })?;
+
+        // the columns are determined by the signal, but may be declared to document them
+        if let Some(s) = schema {
+            if !s.fields.is_empty() && s.fields != otlp_schema(&signal).fields {
+                bail!(
+                    "invalid schema for OTLP {} source; omit the columns to use the schema of the signal",
+                    signal
+                );
+            }
+        }
+
+        let table = OtlpTable {
+            signal,
+            port: pull_option_to_i64("port", options)?.unwrap_or(4318),
+            bind_address: options
+                .remove("bind_address")
+                .unwrap_or_else(|| "0.0.0.0".to_string()),
+        };
+
+        self.from_config(None, name, EmptyConfig {}, table, None)
+    }
+
+    fn from_config(
+        &self,
+        id: Option<i64>,
+        name: &str,
+        config: Self::ProfileT,
+        table: Self::TableT,
+        _: Option<&ConnectionSchema>,
+    ) -> anyhow::Result<Connection> {
+        let address = table.address()?;
+        let schema = otlp_schema(&table.signal);
+
+        let config = OperatorConfig {
+            connection: serde_json::to_value(config).unwrap(),
+            table: serde_json::to_value(&table).unwrap(),
+            rate_limit: None,
+            format: schema.format.clone(),
+            bad_data: None,
+            framing: None,
+            metadata_fields: vec![],
+        };
+
+        Ok(Connection {
+            id,
+            connector: self.name(),
+            name: name.to_string(),
+            connection_type: ConnectionType::Source,
+            schema,
+            config: serde_json::to_string(&config).unwrap(),
+            description: format!("OtlpSource<{}, {}>", table.signal, address),
+        })
+    }
+
+    fn make_operator(
+        &self,
+        _: Self::ProfileT,
+        table: Self::TableT,
+        config: OperatorConfig,
+    ) -> anyhow::Result<OperatorNode> {
+        Ok(OperatorNode::from_source(Box::new(OtlpSourceFunc::new(
+            table.address()?,
+            table.signal,
+            config
+                .format
+                .ok_or_else(|| anyhow!("format required for OTLP source"))?,
+        ))))
+    }
+}
@@ -0,0 +1,821 @@
+//! The subset of the OpenTelemetry protocol (opentelemetry-proto v1) that the OTLP source
+//! reads, along with the conversion of each export request into rows of the signal's table.
+//! Fields the source doesn't use are left out, which prost skips when decoding.
+
+use serde_json::{json, Map, Value};
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct AnyValue {
+    #[prost(oneof = "any_value::Value", tags = "1, 2, 3, 4, 5, 6, 7")]
+    pub value: Option<any_value::Value>,
+}
+
+pub mod any_value {
+    #[derive(Clone, PartialEq, ::prost::Oneof)]
+    pub enum Value {
+        #[prost(string, tag = "1")]
+        StringValue(String),
+        #[prost(bool, tag = "2")]
+        BoolValue(bool),
+        #[prost(int64, tag = "3")]
+        IntValue(i64),
+        #[prost(double, tag = "4")]
+        DoubleValue(f64),
+        #[prost(message, tag = "5")]
+        ArrayValue(super::ArrayValue),
+        #[prost(message, tag = "6")]
+        KvlistValue(super::KeyValueList),
+        #[prost(bytes, tag = "7")]
+        BytesValue(Vec<u8>),
+    }
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ArrayValue {
+    #[prost(message, repeated, tag = "1")]
+    pub values: Vec<AnyValue>,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct KeyValueList {
+    #[prost(message, repeated, tag = "1")]
+    pub values: Vec<KeyValue>,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct KeyValue {
+    #[prost(string, tag = "1")]
+    pub key: String,
+    #[prost(message, optional, tag = "2")]
+    pub value: Option<AnyValue>,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct InstrumentationScope {
+    #[prost(string, tag = "1")]
+    pub name: String,
+    #[prost(string, tag = "2")]
+    pub version: String,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Resource {
+    #[prost(message, repeated, tag = "1")]
+    pub attributes: Vec<KeyValue>,
+}
+
+/// The response to an export request of any signal; we never report partial success
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ExportResponse {}
+
+// traces
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ExportTraceServiceRequest {
+    #[prost(message, repeated, tag = "1")]
+    pub resource_spans: Vec<ResourceSpans>,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ResourceSpans {
+    #[prost(message, optional, tag = "1")]
+    pub resource: Option<Resource>,
+    #[prost(message, repeated, tag = "2")]
+    pub scope_spans: Vec<ScopeSpans>,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ScopeSpans {
+    #[prost(message, optional, tag = "1")]
+    pub scope: Option<InstrumentationScope>,
+    #[prost(message, repeated, tag = "2")]
+    pub spans: Vec<Span>,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Span {
+    #[prost(bytes, tag = "1")]
+    pub trace_id: Vec<u8>,
+    #[prost(bytes, tag = "2")]
+    pub span_id: Vec<u8>,
+    #[prost(string, tag = "3")]
+    pub trace_state: String,
+    #[prost(bytes, tag = "4")]
+    pub parent_span_id: Vec<u8>,
+    #[prost(string, tag = "5")]
+    pub name: String,
+    #[prost(int32, tag = "6")]
+    pub kind: i32,
+    #[prost(fixed64, tag = "7")]
+    pub start_time_unix_nano: u64,
+    #[prost(fixed64, tag = "8")]
+    pub end_time_unix_nano: u64,
+    #[prost(message, repeated, tag = "9")]
+    pub attributes: Vec<KeyValue>,
+    #[prost(message, repeated, tag = "11")]
+    pub events: Vec<SpanEvent>,
+    #[prost(message, optional, tag = "15")]
+    pub status: Option<Status>,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct SpanEvent {
+    #[prost(fixed64, tag = "1")]
+    pub time_unix_nano: u64,
+    #[prost(string, tag = "2")]
+    pub name: String,
+    #[prost(message, repeated, tag = "3")]
+    pub attributes: Vec<KeyValue>,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Status {
+    #[prost(string, tag = "2")]
+    pub message: String,
+    #[prost(int32, tag = "3")]
+    pub code: i32,
+}
+
+// logs
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ExportLogsServiceRequest {
+    #[prost(message, repeated, tag = "1")]
+    pub resource_logs: Vec<ResourceLogs>,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ResourceLogs {
+    #[prost(message, optional, tag = "1")]
+    pub resource: Option<Resource>,
+    #[prost(message, repeated, tag = "2")]
+    pub scope_logs: Vec<ScopeLogs>,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ScopeLogs {
+    #[prost(message, optional, tag = "1")]
+    pub scope: Option<InstrumentationScope>,
+    #[prost(message, repeated, tag = "2")]
+    pub log_records: Vec<LogRecord>,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct LogRecord {
+    #[prost(fixed64, tag = "1")]
+    pub time_unix_nano: u64,
+    #[prost(fixed64, tag = "11")]
+    pub observed_time_unix_nano: u64,
+    #[prost(int32, tag = "2")]
+    pub severity_number: i32,
+    #[prost(string, tag = "3")]
+    pub severity_text: String,
+    #[prost(message, optional, tag = "5")]
+    pub body: Option<AnyValue>,
+    #[prost(message, repeated, tag = "6")]
+    pub attributes: Vec<KeyValue>,
+    #[prost(bytes, tag = "9")]
+    pub trace_id: Vec<u8>,
+    #[prost(bytes, tag = "10")]
+    pub span_id: Vec<u8>,
+}
+
+// metrics
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ExportMetricsServiceRequest {
+    #[prost(message, repeated, tag = "1")]
+    pub resource_metrics: Vec<ResourceMetrics>,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ResourceMetrics {
+    #[prost(message, optional, tag = "1")]
+    pub resource: Option<Resource>,
+    #[prost(message, repeated, tag = "2")]
+    pub scope_metrics: Vec<ScopeMetrics>,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ScopeMetrics {
+    #[prost(message, optional, tag = "1")]
+    pub scope: Option<InstrumentationScope>,
+    #[prost(message, repeated, tag = "2")]
+    pub metrics: Vec<Metric>,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Metric {
+    #[prost(string, tag = "1")]
+    pub name: String,
+    #[prost(string, tag = "2")]
+    pub description: String,
+    #[prost(string, tag = "3")]
+    pub unit: String,
+    #[prost(oneof = "metric::Data", tags = "5, 7, 9, 10, 11")]
+    pub data: Option<metric::Data>,
+}
+
+pub mod metric {
+    #[derive(Clone, PartialEq, ::prost::Oneof)]
+    pub enum Data {
+        #[prost(message, tag = "5")]
+        Gauge(super::Gauge),
+        #[prost(message, tag = "7")]
+        Sum(super::Sum),
+        #[prost(message, tag = "9")]
+        Histogram(super::Histogram),
+        #[prost(message, tag = "10")]
+        ExponentialHistogram(super::ExponentialHistogram),
+        #[prost(message, tag = "11")]
+        Summary(super::Summary),
+    }
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Gauge {
+    #[prost(message, repeated, tag = "1")]
+    pub data_points: Vec<NumberDataPoint>,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Sum {
+    #[prost(message, repeated, tag = "1")]
+    pub data_points: Vec<NumberDataPoint>,
+    #[prost(bool, tag = "3")]
+    pub is_monotonic: bool,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Histogram {
+    #[prost(message, repeated, tag = "1")]
+    pub data_points: Vec<HistogramDataPoint>,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ExponentialHistogram {
+    #[prost(message, repeated, tag = "1")]
+    pub data_points: Vec<ExponentialHistogramDataPoint>,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Summary {
+    #[prost(message, repeated, tag = "1")]
+    pub data_points: Vec<SummaryDataPoint>,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct NumberDataPoint {
+    #[prost(message, repeated, tag = "7")]
+    pub attributes: Vec<KeyValue>,
+    #[prost(fixed64, tag = "2")]
+    pub start_time_unix_nano: u64,
+    #[prost(fixed64, tag = "3")]
+    pub time_unix_nano: u64,
+    #[prost(oneof = "number_data_point::Value", tags = "4, 6")]
+    pub value: Option<number_data_point::Value>,
+}
+
+pub mod number_data_point {
+    #[derive(Clone, PartialEq, ::prost::Oneof)]
+    pub enum Value {
+        #[prost(double, tag = "4")]
+        AsDouble(f64),
+        #[prost(sfixed64, tag = "6")]
+        AsInt(i64),
+    }
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct HistogramDataPoint {
+    #[prost(message, repeated, tag = "9")]
+    pub attributes: Vec<KeyValue>,
+    #[prost(fixed64, tag = "2")]
+    pub start_time_unix_nano: u64,
+    #[prost(fixed64, tag = "3")]
+    pub time_unix_nano: u64,
+    #[prost(fixed64, tag = "4")]
+    pub count: u64,
+    #[prost(double, optional, tag = "5")]
+    pub sum: Option<f64>,
+    #[prost(double, optional, tag = "11")]
+    pub min: Option<f64>,
+    #[prost(double, optional, tag = "12")]
+    pub max: Option<f64>,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ExponentialHistogramDataPoint {
+    #[prost(message, repeated, tag = "1")]
+    pub attributes: Vec<KeyValue>,
+    #[prost(fixed64, tag = "2")]
+    pub start_time_unix_nano: u64,
+    #[prost(fixed64, tag = "3")]
+    pub time_unix_nano: u64,
+    #[prost(fixed64, tag = "4")]
+    pub count: u64,
+    #[prost(double, optional, tag = "5")]
+    pub sum: Option<f64>,
+    #[prost(double, optional, tag = "12")]
+    pub min: Option<f64>,
+    #[prost(double, optional, tag = "13")]
+    pub max: Option<f64>,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct SummaryDataPoint {
+    #[prost(message, repeated, tag = "7")]
+    pub attributes: Vec<KeyValue>,
+    #[prost(fixed64, tag = "2")]
+    pub start_time_unix_nano: u64,
+    #[prost(fixed64, tag = "3")]
+    pub time_unix_nano: u64,
+    #[prost(fixed64, tag = "4")]
+    pub count: u64,
+    #[prost(double, tag = "5")]
+    pub sum: f64,
+}
+
+/// An export request for one of the OTLP signals, which is served on the same paths as the
+/// OpenTelemetry collector's OTLP receiver
+pub trait ExportRequest: ::prost::Message + Default + Send + 'static {
+    /// path of the OTLP/HTTP endpoint
+    const HTTP_PATH: &'static str;
+    /// path of the Export method of the signal's gRPC service
+    const GRPC_PATH: &'static str;
+
+    /// Flattens the request into one JSON row per span, data point or log record
+    fn into_rows(self) -> Vec<Value>;
+}
+
+fn hex(bytes: &[u8]) -> Value {
+    if bytes.is_empty() {
+        return Value::Null;
+    }
+
+    Value::String(bytes.iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+fn non_empty(s: String) -> Value {
+    if s.is_empty() {
+        Value::Null
+    } else {
+        Value::String(s)
+    }
+}
+
+/// OTLP uses 0 for timestamps that are unset
+fn nanos(t: u64) -> Value {
+    if t == 0 {
+        Value::Null
+    } else {
+        Value::from(t)
+    }
+}
+
+fn any_value(v: &AnyValue) -> Value {
+    match &v.value {
+        Some(any_value::Value::StringValue(s)) => Value::String(s.clone()),
+        Some(any_value::Value::BoolValue(b)) => Value::Bool(*b),
+        Some(any_value::Value::IntValue(i)) => Value::from(*i),
+        Some(any_value::Value::DoubleValue(d)) => Value::from(*d),
+        Some(any_value::Value::ArrayValue(a)) => {
+            Value::Array(a.values.iter().map(any_value).collect())
+        }
+        Some(any_value::Value::KvlistValue(kvs)) => Value::Object(attribute_map(&kvs.values)),
+        Some(any_value::Value::BytesValue(b)) => hex(b),
+        None => Value::Null,
+    }
+}
+
+fn attribute_map(attributes: &[KeyValue]) -> Map<String, Value> {
+    attributes
+        .iter()
+        .map(|kv| {
+            (
+                kv.key.clone(),
+                kv.value.as_ref().map(any_value).unwrap_or(Value::Null),
+            )
+        })
+        .collect()
+}
+
+/// Attributes are emitted as the text of a JSON object, for the JSON columns of the table
+fn attributes(attributes: &[KeyValue]) -> Value {
+    Value::String(Value::Object(attribute_map(attributes)).to_string())
+}
+
+/// The columns shared by every signal, which describe where the telemetry came from
+fn origin_columns(
+    row: &mut Map<String, Value>,
+    resource: &Option<Resource>,
+    scope: &Option<InstrumentationScope>,
+) {
+    let resource_attributes = resource
+        .as_ref()
+        .map(|r| r.attributes.as_slice())
+        .unwrap_or_default();
+
+    let service_name = resource_attributes
+        .iter()
+        .find(|kv| kv.key == "service.name")
+        .and_then(|kv| kv.value.as_ref())
+        .map(any_value)
+        .unwrap_or(Value::Null);
+
+    row.insert("service_name".to_string(), service_name);
+    row.insert(
+        "scope_name".to_string(),
+        scope
+            .as_ref()
+            .map(|s| non_empty(s.name.clone()))
+            .unwrap_or(Value::Null),
+    );
+    row.insert(
+        "resource_attributes".to_string(),
+        attributes(resource_attributes),
+    );
+}
+
+fn span_kind(kind: i32) -> &'static str {
+    match kind {
+        1 => "internal",
+        2 => "server",
+        3 => "client",
+        4 => "producer",
+        5 => "consumer",
+        _ => "unspecified",
+    }
+}
+
+fn status_code(code: i32) -> &'static str {
+    match code {
+        1 => "ok",
+        2 => "error",
+        _ => "unset",
+    }
+}
+
+impl ExportRequest for ExportTraceServiceRequest {
+    const HTTP_PATH: &'static str = "/v1/traces";
+    const GRPC_PATH: &'static str = "/opentelemetry.proto.collector.trace.v1.TraceService/Export";
+
+    fn into_rows(self) -> Vec<Value> {
+        let mut rows = vec![];
+        for resource_spans in self.resource_spans {
+            for scope_spans in resource_spans.scope_spans {
+                for span in scope_spans.spans {
+                    let status = span.status.unwrap_or_default();
+                    let events: Vec<Value> = span
+                        .events
+                        .iter()
+                        .map(|e| {
+                            json!({
+                                "time": e.time_unix_nano,
+                                "name": e.name,
+                                "attributes": attribute_map(&e.attributes),
+                            })
+                        })
+                        .collect();
+
+                    let mut row = json!({
+                        "trace_id": hex(&span.trace_id),
+                        "span_id": hex(&span.span_id),
+                        "parent_span_id": hex(&span.parent_span_id),
+                        "trace_state": non_empty(span.trace_state),
+                        "name": span.name,
+                        "kind": span_kind(span.kind),
+                        "start_time": nanos(span.start_time_unix_nano),
+                        "end_time": nanos(span.end_time_unix_nano),
+                        "status_code": status_code(status.code),
+                        "status_message": non_empty(status.message),
+                        "attributes": attributes(&span.attributes),
+                        "events": Value::Array(events).to_string(),
+                    });
+
+                    origin_columns(
+                        row.as_object_mut().unwrap(),
+                        &resource_spans.resource,
+                        &scope_spans.scope,
+                    );
+                    rows.push(row);
+                }
+            }
+        }
+        rows
+    }
+}
+
+impl ExportRequest for ExportLogsServiceRequest {
+    const HTTP_PATH: &'static str = "/v1/logs";
+    const GRPC_PATH: &'static str = "/opentelemetry.proto.collector.logs.v1.LogsService/Export";
+
+    fn into_rows(self) -> Vec<Value> {
+        let mut rows = vec![];
+        for resource_logs in self.resource_logs {
+            for scope_logs in resource_logs.scope_logs {
+                for log in scope_logs.log_records {
+                    // string bodies are emitted as is, and structured bodies as JSON
+                    let body = match log.body.as_ref().map(any_value) {
+                        None | Some(Value::Null) => Value::Null,
+                        Some(Value::String(s)) => Value::String(s),
+                        Some(v) => Value::String(v.to_string()),
+                    };
+
+                    let mut row = json!({
+                        "time": nanos(log.time_unix_nano),
+                        "observed_time": nanos(log.observed_time_unix_nano),
+                        "severity_number": log.severity_number,
+                        "severity_text": non_empty(log.severity_text),
+                        "body": body,
+                        "trace_id": hex(&log.trace_id),
+                        "span_id": hex(&log.span_id),
+                        "attributes": attributes(&log.attributes),
+                    });
+
+                    origin_columns(
+                        row.as_object_mut().unwrap(),
+                        &resource_logs.resource,
+                        &scope_logs.scope,
+                    );
+                    rows.push(row);
+                }
+            }
+        }
+        rows
+    }
+}
+
+/// The columns of a metric data point, in addition to those of the metric itself
+struct DataPoint<'a> {
+    attributes: &'a [KeyValue],
+    start_time_unix_nano: u64,
+    time_unix_nano: u64,
+    value: Option<f64>,
+    count: Option<u64>,
+    sum: Option<f64>,
+    min: Option<f64>,
+    max: Option<f64>,
+}
+
+impl<'a> From<&'a NumberDataPoint> for DataPoint<'a> {
+    fn from(p: &'a NumberDataPoint) -> Self {
+        DataPoint {
+            attributes: &p.attributes,
+            start_time_unix_nano: p.start_time_unix_nano,
+            time_unix_nano: p.time_unix_nano,
+            value: p.value.as_ref().map(|v| match v {
+                number_data_point::Value::AsDouble(d) => *d,
+                number_data_point::Value::AsInt(i) => *i as f64,
+            }),
+            count: None,
+            sum: None,
+            min: None,
+            max: None,
+        }
+    }
+}
+
+impl<'a> From<&'a HistogramDataPoint> for DataPoint<'a> {
+    fn from(p: &'a HistogramDataPoint) -> Self {
+        DataPoint {
+            attributes: &p.attributes,
+            start_time_unix_nano: p.start_time_unix_nano,
+            time_unix_nano: p.time_unix_nano,
+            value: None,
+            count: Some(p.count),
+            sum: p.sum,
+            min: p.min,
+            max: p.max,
+        }
+    }
+}
+
+impl<'a> From<&'a ExponentialHistogramDataPoint> for DataPoint<'a> {
+    fn from(p: &'a ExponentialHistogramDataPoint) -> Self {
+        DataPoint {
+            attributes: &p.attributes,
+            start_time_unix_nano: p.start_time_unix_nano,
+            time_unix_nano: p.time_unix_nano,
+            value: None,
+            count: Some(p.count),
+            sum: p.sum,
+            min: p.min,
+            max: p.max,
+        }
+    }
+}
+
+impl<'a> From<&'a SummaryDataPoint> for DataPoint<'a> {
+    fn from(p: &'a SummaryDataPoint) -> Self {
+        DataPoint {
+            attributes: &p.attributes,
+            start_time_unix_nano: p.start_time_unix_nano,
+            time_unix_nano: p.time_unix_nano,
+            value: None,
+            count: Some(p.count),
+            sum: Some(p.sum),
+            min: None,
+            max: None,
+        }
+    }
+}
+
+impl ExportRequest for ExportMetricsServiceRequest {
+    const HTTP_PATH: &'static str = "/v1/metrics";
+    const GRPC_PATH: &'static str =
+        "/opentelemetry.proto.collector.metrics.v1.MetricsService/Export";
+
+    fn into_rows(self) -> Vec<Value> {
+        let mut rows = vec![];
+        for resource_metrics in self.resource_metrics {
+            for scope_metrics in resource_metrics.scope_metrics {
+                for metric in scope_metrics.metrics {
+                    let (metric_type, is_monotonic, points): (_, _, Vec<DataPoint>) =
+                        match &metric.data {
+                            Some(metric::Data::Gauge(g)) => (
+                                "gauge",
+                                None,
+                                g.data_points.iter().map(DataPoint::from).collect(),
+                            ),
+                            Some(metric::Data::Sum(s)) => (
+                                "sum",
+                                Some(s.is_monotonic),
+                                s.data_points.iter().map(DataPoint::from).collect(),
+                            ),
+                            Some(metric::Data::Histogram(h)) => (
+                                "histogram",
+                                None,
+                                h.data_points.iter().map(DataPoint::from).collect(),
+                            ),
+                            Some(metric::Data::ExponentialHistogram(h)) => (
+                                "exponential_histogram",
+                                None,
+                                h.data_points.iter().map(DataPoint::from).collect(),
+                            ),
+                            Some(metric::Data::Summary(s)) => (
+                                "summary",
+                                None,
+                                s.data_points.iter().map(DataPoint::from).collect(),
+                            ),
+                            None => continue,
+                        };
+
+                    for point in points {
+                        let mut row = json!({
+                            "name": metric.name,
+                            "description": non_empty(metric.description.clone()),
+                            "unit": non_empty(metric.unit.clone()),
+                            "metric_type": metric_type,
+                            "is_monotonic": is_monotonic,
+                            "time": nanos(point.time_unix_nano),
+                            "start_time": nanos(point.start_time_unix_nano),
+                            "value": point.value,
+                            "count": point.count,
+                            "sum": point.sum,
+                            "min": point.min,
+                            "max": point.max,
+                            "attributes": attributes(point.attributes),
+                        });
+
+                        origin_columns(
+                            row.as_object_mut().unwrap(),
+                            &resource_metrics.resource,
+                            &scope_metrics.scope,
+                        );
+                        rows.push(row);
+                    }
+                }
+            }
+        }
+        rows
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use prost::Message;
+
+    fn kv(key: &str, value: any_value::Value) -> KeyValue {
+        KeyValue {
+            key: key.to_string(),
+            value: Some(AnyValue { value: Some(value) }),
+        }
+    }
+
+    fn resource() -> Option<Resource> {
+        Some(Resource {
+            attributes: vec![kv(
+                "service.name",
+                any_value::Value::StringValue("checkout".to_string()),
+            )],
+        })
+    }
+
+    #[test]
+    fn test_trace_rows() {
+        let request = ExportTraceServiceRequest {
+            resource_spans: vec![ResourceSpans {
+                resource: resource(),
+                scope_spans: vec![ScopeSpans {
+                    scope: Some(InstrumentationScope {
+                        name: "http".to_string(),
+                        version: "1.0".to_string(),
+                    }),
+                    spans: vec![Span {
+                        trace_id: vec![0xab; 16],
+                        span_id: vec![0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08],
+                        name: "GET /cart".to_string(),
+                        kind: 2,
+                        start_time_unix_nano: 1_700_000_000_000_000_000,
+                        end_time_unix_nano: 1_700_000_000_250_000_000,
+                        attributes: vec![kv("http.status_code", any_value::Value::IntValue(200))],
+                        status: Some(Status {
+                            message: "".to_string(),
+                            code: 1,
+                        }),
+                        ..Default::default()
+                    }],
+                }],
+            }],
+        };
+
+        // round-trip through the wire format, as the source receives it
+        let request =
+            ExportTraceServiceRequest::decode(request.encode_to_vec().as_slice()).unwrap();
+        let rows = request.into_rows();
+
+        assert_eq!(rows.len(), 1);
+        let row = &rows[0];
+        assert_eq!(row["trace_id"], "ab".repeat(16));
+        assert_eq!(row["span_id"], "0102030405060708");
+        assert_eq!(row["parent_span_id"], Value::Null);
+        assert_eq!(row["kind"], "server");
+        assert_eq!(row["status_code"], "ok");
+        assert_eq!(row["status_message"], Value::Null);
+        assert_eq!(row["start_time"], 1_700_000_000_000_000_000u64);
+        assert_eq!(row["service_name"], "checkout");
+        assert_eq!(row["scope_name"], "http");
+        assert_eq!(row["attributes"], r#"{"http.status_code":200}"#);
+        assert_eq!(row["events"], "[]");
+    }
+
+    #[test]
+    fn test_metric_rows() {
+        let point = |value| NumberDataPoint {
+            time_unix_nano: 1_700_000_000_000_000_000,
+            value: Some(value),
+            ..Default::default()
+        };
+
+        let request = ExportMetricsServiceRequest {
+            resource_metrics: vec![ResourceMetrics {
+                resource: resource(),
+                scope_metrics: vec![ScopeMetrics {
+                    scope: None,
+                    metrics: vec![
+                        Metric {
+                            name: "requests".to_string(),
+                            data: Some(metric::Data::Sum(Sum {
+                                data_points: vec![
+                                    point(number_data_point::Value::AsInt(3)),
+                                    point(number_data_point::Value::AsInt(5)),
+                                ],
+                                is_monotonic: true,
+                            })),
+                            ..Default::default()
+                        },
+                        Metric {
+                            name: "latency".to_string(),
+                            unit: "ms".to_string(),
+                            data: Some(metric::Data::Histogram(Histogram {
+                                data_points: vec![HistogramDataPoint {
+                                    count: 10,
+                                    sum: Some(125.0),
+                                    ..Default::default()
+                                }],
+                            })),
+                            ..Default::default()
+                        },
+                    ],
+                }],
+            }],
+        };
+
+        let rows = request.into_rows();
+        assert_eq!(rows.len(), 3);
+
+        assert_eq!(rows[0]["metric_type"], "sum");
+        assert_eq!(rows[0]["is_monotonic"], true);
+        assert_eq!(rows[1]["value"], 5.0);
+        assert_eq!(rows[1]["count"], Value::Null);
+
+        assert_eq!(rows[2]["metric_type"], "histogram");
+        assert_eq!(rows[2]["unit"], "ms");
+        assert_eq!(rows[2]["value"], Value::Null);
+        assert_eq!(rows[2]["count"], 10);
+        assert_eq!(rows[2]["sum"], 125.0);
+        assert_eq!(rows[2]["time"], Value::Null);
+    }
+}
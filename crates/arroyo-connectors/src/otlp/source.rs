@@ -0,0 +1,303 @@
+use std::marker::PhantomData;
+use std::net::SocketAddr;
+use std::time::{Duration, SystemTime};
+
+use async_compression::tokio::bufread::GzipDecoder;
+use async_trait::async_trait;
+use axum::body::{Body, Bytes};
+use axum::extract::{DefaultBodyLimit, State};
+use axum::http::header::{CONTENT_ENCODING, CONTENT_TYPE};
+use axum::http::{HeaderMap, Request, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::post;
+use axum::Router;
+use futures::future::BoxFuture;
+use prost::Message;
+use serde_json::Value;
+use tokio::io::AsyncReadExt;
+use tokio::select;
+use tokio::sync::{mpsc, oneshot};
+use tokio::time::MissedTickBehavior;
+use tonic::codec::{CompressionEncoding, ProstCodec};
+use tonic::server::{Grpc, UnaryService};
+use tracing::{debug, error, info};
+
+use arroyo_operator::context::ArrowContext;
+use arroyo_operator::operator::SourceOperator;
+use arroyo_operator::SourceFinishType;
+use arroyo_rpc::formats::Format;
+use arroyo_rpc::grpc::rpc::StopMode;
+use arroyo_rpc::ControlMessage;
+use arroyo_types::UserError;
+
+use crate::otlp::proto::{
+    ExportLogsServiceRequest, ExportMetricsServiceRequest, ExportRequest, ExportResponse,
+    ExportTraceServiceRequest,
+};
+use crate::otlp::Signal;
+
+const MAX_BODY_SIZE: usize = 16 * 1024 * 1024;
+
+/// The rows of an export request, along with a channel to acknowledge them once they've been
+/// handed to the pipeline
+type Export = (Vec<Value>, oneshot::Sender<()>);
+
+/// Serves an OTLP receiver for a single signal, over both gRPC and HTTP/protobuf on the same
+/// port. Requests are only acknowledged once their rows have been read by the operator, so
+/// exporters see backpressure and retry while the pipeline is not running. Data received since
+/// the last checkpoint is not replayed on recovery.
+pub struct OtlpSourceFunc {
+    address: SocketAddr,
+    signal: Signal,
+    format: Format,
+}
+
+/// Hands the rows of a request to the operator and waits for them to be read
+async fn submit(tx: &mpsc::Sender<Export>, rows: Vec<Value>) -> anyhow::Result<()> {
+    if rows.is_empty() {
+        return Ok(());
+    }
+
+    let (ack_tx, ack_rx) = oneshot::channel();
+    tx.send((rows, ack_tx)).await?;
+    Ok(ack_rx.await?)
+}
+
+async fn export_http<R: ExportRequest>(
+    State(tx): State<mpsc::Sender<Export>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    let content_type = headers
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default();
+
+    if !content_type.starts_with("application/x-protobuf") {
+        return (
+            StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            "only the application/x-protobuf encoding of OTLP is supported",
+        )
+            .into_response();
+    }
+
+    let request = match headers.get(CONTENT_ENCODING).map(|v| v.as_bytes()) {
+        None | Some(b"identity") => R::decode(body),
+        Some(b"gzip") => {
+            let mut decompressed = vec![];
+            if let Err(e) = GzipDecoder::new(&body[..])
+                .read_to_end(&mut decompressed)
+                .await
+            {
+                return (StatusCode::BAD_REQUEST, format!("invalid gzip body: {}", e))
+                    .into_response();
+            }
+            R::decode(decompressed.as_slice())
+        }
+        Some(_) => {
+            return (
+                StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                "unsupported content encoding; expected gzip",
+            )
+                .into_response()
+        }
+    };
+
+    let request = match request {
+        Ok(request) => request,
+        Err(e) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                format!("invalid OTLP request: {}", e),
+            )
+                .into_response()
+        }
+    };
+
+    match submit(&tx, request.into_rows()).await {
+        Ok(()) => (
+            [(CONTENT_TYPE, "application/x-protobuf")],
+            ExportResponse {}.encode_to_vec(),
+        )
+            .into_response(),
+        Err(_) => (StatusCode::SERVICE_UNAVAILABLE, "pipeline is not running").into_response(),
+    }
+}
+
+struct ExportService<R> {
+    tx: mpsc::Sender<Export>,
+    _request: PhantomData<R>,
+}
+
+impl<R: ExportRequest> UnaryService<R> for ExportService<R> {
+    type Response = ExportResponse;
+    type Future = BoxFuture<'static, Result<tonic::Response<ExportResponse>, tonic::Status>>;
+
+    fn call(&mut self, request: tonic::Request<R>) -> Self::Future {
+        let tx = self.tx.clone();
+        Box::pin(async move {
+            submit(&tx, request.into_inner().into_rows())
+                .await
+                .map_err(|_| tonic::Status::unavailable("pipeline is not running"))?;
+
+            Ok(tonic::Response::new(ExportResponse {}))
+        })
+    }
+}
+
+async fn export_grpc<R: ExportRequest>(
+    State(tx): State<mpsc::Sender<Export>>,
+    request: Request<Body>,
+) -> Response {
+    let mut grpc = Grpc::new(ProstCodec::<ExportResponse, R>::default())
+        .accept_compressed(CompressionEncoding::Gzip)
+        .send_compressed(CompressionEncoding::Gzip);
+
+    grpc.unary(
+        ExportService {
+            tx,
+            _request: PhantomData,
+        },
+        request,
+    )
+    .await
+    .into_response()
+}
+
+fn router<R: ExportRequest>(tx: mpsc::Sender<Export>) -> Router {
+    Router::new()
+        .route(R::HTTP_PATH, post(export_http::<R>))
+        .route(R::GRPC_PATH, post(export_grpc::<R>))
+        .layer(DefaultBodyLimit::max(MAX_BODY_SIZE))
+        .with_state(tx)
+}
+
+impl OtlpSourceFunc {
+    pub fn new(address: SocketAddr, signal: Signal, format: Format) -> Self {
+        Self {
+            address,
+            signal,
+            format,
+        }
+    }
+
+    async fn handle_control_message(
+        &mut self,
+        ctx: &mut ArrowContext,
+        msg: Option<ControlMessage>,
+    ) -> Option<SourceFinishType> {
+        match msg? {
+            ControlMessage::Checkpoint(c) => {
+                debug!("starting checkpointing {}", ctx.task_info.task_index);
+                if self.start_checkpoint(c, ctx).await {
+                    return Some(SourceFinishType::Immediate);
+                }
+            }
+            ControlMessage::Stop { mode } => {
+                info!("Stopping OTLP source: {:?}", mode);
+
+                match mode {
+                    StopMode::Graceful => {
+                        return Some(SourceFinishType::Graceful);
+                    }
+                    StopMode::Immediate => {
+                        return Some(SourceFinishType::Immediate);
+                    }
+                }
+            }
+            ControlMessage::Commit { .. } => {
+                unreachable!("sources shouldn't receive commit messages");
+            }
+            ControlMessage::LoadCompacted { compacted } => {
+                ctx.load_compacted(compacted).await;
+            }
+            ControlMessage::QueryState { query } => {
+                ctx.query_state(query).await;
+            }
+            ControlMessage::NoOp => {}
+        }
+        None
+    }
+
+    async fn run_int(&mut self, ctx: &mut ArrowContext) -> Result<SourceFinishType, UserError> {
+        ctx.initialize_deserializer(self.format.clone(), None, None);
+
+        let (tx, mut rx) = mpsc::channel(64);
+        let router = match self.signal {
+            Signal::Traces => router::<ExportTraceServiceRequest>(tx),
+            Signal::Metrics => router::<ExportMetricsServiceRequest>(tx),
+            Signal::Logs => router::<ExportLogsServiceRequest>(tx),
+        };
+
+        let server = axum::Server::try_bind(&self.address).map_err(|e| {
+            UserError::new(
+                "failed to start OTLP receiver",
+                format!("Could not listen on {}: {}", self.address, e),
+            )
+        })?;
+
+        // the server shuts down when this is dropped, as the source finishes
+        let (_shutdown_tx, shutdown_rx) = oneshot::channel::<()>();
+        let address = self.address;
+        tokio::spawn(async move {
+            if let Err(e) = server
+                .serve(router.into_make_service())
+                .with_graceful_shutdown(async {
+                    let _ = shutdown_rx.await;
+                })
+                .await
+            {
+                error!("OTLP receiver on {} failed: {}", address, e);
+            }
+        });
+
+        info!("receiving OTLP {} on {}", self.signal, self.address);
+
+        let mut flush_ticker = tokio::time::interval(Duration::from_millis(50));
+        flush_ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        loop {
+            select! {
+                Some((rows, ack)) = rx.recv() => {
+                    let now = SystemTime::now();
+                    for row in rows {
+                        ctx.deserialize_slice(row.to_string().as_bytes(), now, None).await?;
+                    }
+                    let _ = ack.send(());
+
+                    if ctx.should_flush() {
+                        ctx.flush_buffer().await?;
+                    }
+                }
+                _ = flush_ticker.tick() => {
+                    if ctx.should_flush() {
+                        ctx.flush_buffer().await?;
+                    }
+                }
+                control_message = ctx.control_rx.recv() => {
+                    if let Some(r) = self.handle_control_message(ctx, control_message).await {
+                        return Ok(r);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl SourceOperator for OtlpSourceFunc {
+    fn name(&self) -> String {
+        "OtlpSource".to_string()
+    }
+
+    async fn run(&mut self, ctx: &mut ArrowContext) -> SourceFinishType {
+        match self.run_int(ctx).await {
+            Ok(r) => r,
+            Err(e) => {
+                ctx.report_error(e.name.clone(), e.details.clone()).await;
+
+                panic!("{}: {}", e.name, e.details);
+            }
+        }
+    }
+}
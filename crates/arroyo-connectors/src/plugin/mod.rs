@@ -0,0 +1,443 @@
+mod sink;
+mod source;
+
+use std::collections::HashMap;
+use std::io::Cursor;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Context};
+use arrow::array::RecordBatch;
+use arrow::datatypes::{Field, Schema};
+use arrow::ipc::reader::StreamReader;
+use arrow::ipc::writer::StreamWriter;
+use arroyo_operator::connector::{Connection, Connector};
+use arroyo_operator::operator::OperatorNode;
+use arroyo_rpc::api_types::connections::{
+    ConnectionProfile, ConnectionSchema, ConnectionType, TestSourceMessage,
+};
+use arroyo_rpc::config::config;
+use arroyo_rpc::grpc::connector::connector_plugin_client::ConnectorPluginClient;
+use arroyo_rpc::grpc::connector::{GetMetadataReq, GetMetadataResp, TestReq};
+use arroyo_rpc::OperatorConfig;
+use serde_json::{Map, Value};
+use tokio::sync::mpsc::Sender;
+use tonic::transport::{Channel, Endpoint};
+use tracing::{error, info};
+
+use crate::plugin::sink::PluginSinkFunc;
+use crate::plugin::source::PluginSourceFunc;
+
+/// The version of the connector plugin protocol (proto/connector.proto) that we speak
+const PROTOCOL_VERSION: u32 = 1;
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Options that are handled by the planner for every connector, and so aren't passed on to
+/// plugins
+const PLANNER_OPTIONS: &[&str] = &[
+    "event_time_field",
+    "watermark_field",
+    "watermark_udf",
+    "idle_micros",
+    "idle_time",
+    "parallelism",
+    "dedupe.keys",
+    "dedupe.window",
+];
+
+static PLUGINS: RwLock<Vec<PluginConnector>> = RwLock::new(vec![]);
+
+/// A connector served by an out-of-tree plugin process over gRPC
+#[derive(Clone)]
+pub struct PluginConnector {
+    id: &'static str,
+    endpoint: String,
+    metadata: Arc<GetMetadataResp>,
+}
+
+/// Loads the connector plugins from the config, making them available from
+/// [`crate::connectors`]; plugins that can't be loaded are logged and skipped
+pub async fn register_plugins() {
+    for plugin in &config().connector_plugins {
+        let endpoint = plugin.endpoint.to_string();
+        match PluginConnector::load(&endpoint).await {
+            Ok(connector) => {
+                info!(
+                    "Registered connector '{}' from plugin at {}",
+                    connector.id, endpoint
+                );
+                PLUGINS.write().unwrap().push(connector);
+            }
+            Err(e) => {
+                error!("Failed to load connector plugin at {}: {:#}", endpoint, e);
+            }
+        }
+    }
+}
+
+pub(crate) fn plugins() -> Vec<PluginConnector> {
+    PLUGINS.read().unwrap().clone()
+}
+
+async fn connect(endpoint: &str) -> anyhow::Result<ConnectorPluginClient<Channel>> {
+    let channel = Endpoint::from_shared(endpoint.to_string())?
+        .connect_timeout(CONNECT_TIMEOUT)
+        .connect()
+        .await
+        .with_context(|| format!("failed to connect to connector plugin at {}", endpoint))?;
+
+    Ok(ConnectorPluginClient::new(channel))
+}
+
+impl PluginConnector {
+    async fn load(endpoint: &str) -> anyhow::Result<Self> {
+        let metadata = connect(endpoint)
+            .await?
+            .get_metadata(GetMetadataReq {
+                protocol_version: PROTOCOL_VERSION,
+            })
+            .await
+            .map_err(|e| anyhow!("failed to fetch metadata: {}", e.message()))?
+            .into_inner();
+
+        if metadata.protocol_version != PROTOCOL_VERSION {
+            bail!(
+                "plugin speaks protocol version {}, but only version {} is supported",
+                metadata.protocol_version,
+                PROTOCOL_VERSION
+            );
+        }
+
+        if metadata.id.is_empty() {
+            bail!("plugin did not return a connector id");
+        }
+
+        if !metadata.source && !metadata.sink {
+            bail!("plugin connector must be a source, a sink, or both");
+        }
+
+        if crate::connectors().contains_key(metadata.id.as_str()) {
+            bail!("a connector named '{}' already exists", metadata.id);
+        }
+
+        serde_json::from_str::<Value>(&metadata.table_config)
+            .context("plugin's table_config is not valid JSON")?;
+
+        Ok(Self {
+            // plugins are registered once, when the process starts
+            id: Box::leak(metadata.id.clone().into_boxed_str()),
+            endpoint: endpoint.to_string(),
+            metadata: Arc::new(metadata),
+        })
+    }
+
+    fn connection_type(&self, table: &Value) -> anyhow::Result<ConnectionType> {
+        match (self.metadata.source, self.metadata.sink) {
+            (true, false) => Ok(ConnectionType::Source),
+            (false, true) => Ok(ConnectionType::Sink),
+            _ => match table.get("type").and_then(|t| t.as_str()) {
+                Some("source") => Ok(ConnectionType::Source),
+                Some("sink") => Ok(ConnectionType::Sink),
+                _ => bail!(
+                    "'type' must be set to 'source' or 'sink' for {} tables",
+                    self.id
+                ),
+            },
+        }
+    }
+}
+
+/// Converts the WITH options for a table into its JSON config; dotted keys become nested
+/// objects, and values that parse as numbers or booleans are converted to them
+fn options_to_json(options: &mut HashMap<String, String>) -> anyhow::Result<Value> {
+    let mut table = Map::new();
+
+    let keys: Vec<_> = options
+        .keys()
+        .filter(|k| !PLANNER_OPTIONS.contains(&k.as_str()))
+        .cloned()
+        .collect();
+
+    for key in keys {
+        let value = options.remove(&key).unwrap();
+        let value = match serde_json::from_str(&value) {
+            Ok(v @ (Value::Number(_) | Value::Bool(_))) => v,
+            _ => Value::String(value),
+        };
+
+        let mut path: Vec<_> = key.split('.').collect();
+        let last = path.pop().unwrap();
+        let mut obj = &mut table;
+        for part in path {
+            obj = obj
+                .entry(part)
+                .or_insert_with(|| Value::Object(Map::new()))
+                .as_object_mut()
+                .ok_or_else(|| anyhow!("option '{}' conflicts with option '{}'", key, part))?;
+        }
+
+        if obj.insert(last.to_string(), value).is_some() {
+            bail!("option '{}' conflicts with another option", key);
+        }
+    }
+
+    Ok(Value::Object(table))
+}
+
+fn arrow_schema(schema: &ConnectionSchema) -> Schema {
+    Schema::new(
+        schema
+            .fields
+            .iter()
+            .map(|f| f.clone().into())
+            .collect::<Vec<Field>>(),
+    )
+}
+
+/// Encodes a schema as an Arrow IPC stream with no batches
+fn encode_schema(schema: &Schema) -> Vec<u8> {
+    let mut buf = vec![];
+    StreamWriter::try_new(&mut buf, schema)
+        .and_then(|mut w| w.finish())
+        .expect("schema should be encodable");
+    buf
+}
+
+fn encode_batch(batch: &RecordBatch) -> anyhow::Result<Vec<u8>> {
+    let mut buf = vec![];
+    let mut writer = StreamWriter::try_new(&mut buf, &batch.schema())?;
+    writer.write(batch)?;
+    writer.finish()?;
+    drop(writer);
+    Ok(buf)
+}
+
+fn decode_batches(data: &[u8]) -> anyhow::Result<Vec<RecordBatch>> {
+    Ok(StreamReader::try_new(Cursor::new(data), None)?.collect::<Result<_, _>>()?)
+}
+
+async fn run_test(
+    endpoint: &str,
+    req: TestReq,
+    tx: &mut Sender<TestSourceMessage>,
+) -> anyhow::Result<()> {
+    let mut responses = connect(endpoint)
+        .await?
+        .test(req)
+        .await
+        .map_err(|e| anyhow!("{}", e.message()))?
+        .into_inner();
+
+    while let Some(resp) = responses
+        .message()
+        .await
+        .map_err(|e| anyhow!("{}", e.message()))?
+    {
+        let done = resp.done;
+        crate::send(
+            tx,
+            TestSourceMessage {
+                error: resp.error,
+                done,
+                message: resp.message,
+            },
+        )
+        .await;
+
+        if done {
+            return Ok(());
+        }
+    }
+
+    bail!("plugin closed the connection before finishing the test")
+}
+
+impl Connector for PluginConnector {
+    type ProfileT = Value;
+    type TableT = Value;
+
+    fn name(&self) -> &'static str {
+        self.id
+    }
+
+    fn metadata(&self) -> arroyo_rpc::api_types::connections::Connector {
+        arroyo_rpc::api_types::connections::Connector {
+            id: self.id.to_string(),
+            name: self.metadata.name.clone(),
+            icon: self.metadata.icon.clone(),
+            description: self.metadata.description.clone(),
+            enabled: true,
+            source: self.metadata.source,
+            sink: self.metadata.sink,
+            testing: true,
+            hidden: false,
+            custom_schemas: self.metadata.custom_schemas,
+            connection_config: self.metadata.connection_config.clone(),
+            table_config: self.metadata.table_config.clone(),
+        }
+    }
+
+    fn table_type(&self, _: Self::ProfileT, table: Self::TableT) -> ConnectionType {
+        // validated when the connection is created
+        self.connection_type(&table)
+            .unwrap_or(ConnectionType::Source)
+    }
+
+    fn test(
+        &self,
+        _: &str,
+        profile: Self::ProfileT,
+        table: Self::TableT,
+        schema: Option<&ConnectionSchema>,
+        mut tx: Sender<TestSourceMessage>,
+    ) {
+        let endpoint = self.endpoint.clone();
+        let req = TestReq {
+            profile: profile.to_string(),
+            table: table.to_string(),
+            schema: schema.map(|s| encode_schema(&arrow_schema(s))),
+        };
+
+        tokio::task::spawn(async move {
+            if let Err(e) = run_test(&endpoint, req, &mut tx).await {
+                crate::send(
+                    &mut tx,
+                    TestSourceMessage {
+                        error: true,
+                        done: true,
+                        message: format!("Failed to test connection: {:#}", e),
+                    },
+                )
+                .await;
+            }
+        });
+    }
+
+    fn from_options(
+        &self,
+        name: &str,
+        options: &mut HashMap<String, String>,
+        schema: Option<&ConnectionSchema>,
+        profile: Option<&ConnectionProfile>,
+    ) -> anyhow::Result<Connection> {
+        let profile = profile
+            .map(|p| p.config.clone())
+            .unwrap_or_else(|| Value::Object(Map::new()));
+
+        let table = options_to_json(options)?;
+
+        self.from_config(None, name, profile, table, schema)
+    }
+
+    fn from_config(
+        &self,
+        id: Option<i64>,
+        name: &str,
+        config: Self::ProfileT,
+        table: Self::TableT,
+        schema: Option<&ConnectionSchema>,
+    ) -> anyhow::Result<Connection> {
+        let connection_type = self.connection_type(&table)?;
+
+        let schema = schema
+            .map(|s| s.to_owned())
+            .ok_or_else(|| anyhow!("no schema defined for {} connection", self.id))?;
+
+        let config = OperatorConfig {
+            connection: config,
+            table,
+            rate_limit: None,
+            format: None,
+            bad_data: None,
+            framing: None,
+            metadata_fields: vec![],
+        };
+
+        Ok(Connection {
+            id,
+            connector: self.id,
+            name: name.to_string(),
+            connection_type,
+            schema,
+            config: serde_json::to_string(&config).unwrap(),
+            description: format!("{}<{}>", self.metadata.name, self.endpoint),
+        })
+    }
+
+    fn make_operator(
+        &self,
+        profile: Self::ProfileT,
+        table: Self::TableT,
+        _: OperatorConfig,
+    ) -> anyhow::Result<OperatorNode> {
+        Ok(match self.connection_type(&table)? {
+            ConnectionType::Source => OperatorNode::from_source(Box::new(PluginSourceFunc::new(
+                self.endpoint.clone(),
+                profile,
+                table,
+            ))),
+            ConnectionType::Sink => OperatorNode::from_operator(Box::new(PluginSinkFunc::new(
+                self.endpoint.clone(),
+                profile,
+                table,
+            ))),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use arrow::array::Int64Array;
+    use arrow::datatypes::DataType;
+
+    #[test]
+    fn test_options_to_json() {
+        let mut options: HashMap<String, String> = [
+            ("topic", "events"),
+            ("batch.size", "100"),
+            ("batch.compress", "true"),
+            ("parallelism", "2"),
+        ]
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+
+        assert_eq!(
+            options_to_json(&mut options).unwrap(),
+            serde_json::json!({
+                "topic": "events",
+                "batch": {
+                    "size": 100,
+                    "compress": true,
+                }
+            })
+        );
+
+        // options that the planner handles are left alone
+        assert_eq!(options.keys().collect::<Vec<_>>(), vec!["parallelism"]);
+
+        let mut options: HashMap<String, String> = [("batch", "10"), ("batch.size", "100")]
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        assert!(options_to_json(&mut options).is_err());
+    }
+
+    #[test]
+    fn test_ipc_roundtrip() {
+        let schema = Arc::new(Schema::new(vec![Field::new("n", DataType::Int64, false)]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![Arc::new(Int64Array::from(vec![1, 2, 3]))],
+        )
+        .unwrap();
+
+        assert_eq!(
+            decode_batches(&encode_batch(&batch).unwrap()).unwrap(),
+            vec![batch]
+        );
+        assert!(decode_batches(&encode_schema(&schema)).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,172 @@
+use arrow::array::RecordBatch;
+use arroyo_operator::context::ArrowContext;
+use arroyo_operator::operator::ArrowOperator;
+use arroyo_rpc::grpc::connector::{
+    sink_req, sink_resp, SinkCheckpoint, SinkReq, SinkResp, TaskContext,
+};
+use arroyo_types::{CheckpointBarrier, SignalMessage, UserError};
+use async_trait::async_trait;
+use serde_json::Value;
+use tokio::sync::mpsc::Sender;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::Streaming;
+
+use crate::plugin::{connect, encode_batch, encode_schema};
+
+pub struct PluginSinkFunc {
+    endpoint: String,
+    profile: Value,
+    table: Value,
+    tx: Option<Sender<SinkReq>>,
+    responses: Option<Streaming<SinkResp>>,
+}
+
+impl PluginSinkFunc {
+    pub fn new(endpoint: String, profile: Value, table: Value) -> Self {
+        Self {
+            endpoint,
+            profile,
+            table,
+            tx: None,
+            responses: None,
+        }
+    }
+
+    async fn start(&mut self, ctx: &mut ArrowContext) -> Result<(), UserError> {
+        let mut client = connect(&self.endpoint).await.map_err(|e| {
+            UserError::new("Failed to connect to connector plugin", format!("{:#}", e))
+        })?;
+
+        let (tx, rx) = tokio::sync::mpsc::channel(16);
+        self.tx = Some(tx);
+
+        self.send(sink_req::Msg::Start(TaskContext {
+            profile: self.profile.to_string(),
+            table: self.table.to_string(),
+            schema: encode_schema(&ctx.in_schemas[0].schema),
+            task_index: ctx.task_info.task_index as u32,
+            parallelism: ctx.task_info.parallelism as u32,
+        }))
+        .await?;
+
+        self.responses = Some(
+            client
+                .write(ReceiverStream::new(rx))
+                .await
+                .map_err(|e| UserError::new("Connector plugin failed", e.message()))?
+                .into_inner(),
+        );
+
+        Ok(())
+    }
+
+    async fn send(&self, msg: sink_req::Msg) -> Result<(), UserError> {
+        self.tx
+            .as_ref()
+            .expect("sink should be started")
+            .send(SinkReq { msg: Some(msg) })
+            .await
+            .map_err(|_| {
+                UserError::new(
+                    "Connector plugin failed",
+                    "plugin closed the write stream unexpectedly",
+                )
+            })
+    }
+
+    /// Waits for the plugin to acknowledge that everything before the checkpoint was written
+    async fn checkpoint(&mut self, epoch: u32) -> Result<(), UserError> {
+        self.send(sink_req::Msg::Checkpoint(SinkCheckpoint { epoch }))
+            .await?;
+
+        let responses = self.responses.as_mut().expect("sink should be started");
+        loop {
+            let resp = responses
+                .message()
+                .await
+                .map_err(|e| UserError::new("Connector plugin failed", e.message()))?;
+
+            match resp.and_then(|r| r.msg) {
+                Some(sink_resp::Msg::Flushed(flushed)) if flushed.epoch == epoch => {
+                    return Ok(());
+                }
+                Some(sink_resp::Msg::Flushed(flushed)) => {
+                    return Err(UserError::new(
+                        "Connector plugin failed",
+                        format!(
+                            "plugin flushed epoch {} while waiting for epoch {}",
+                            flushed.epoch, epoch
+                        ),
+                    ));
+                }
+                None => {
+                    return Err(UserError::new(
+                        "Connector plugin failed",
+                        "plugin closed the write stream unexpectedly",
+                    ));
+                }
+            }
+        }
+    }
+
+    async fn handle_result(ctx: &mut ArrowContext, result: Result<(), UserError>) {
+        if let Err(e) = result {
+            ctx.report_error(e.name.clone(), e.details.clone()).await;
+            panic!("{}: {}", e.name, e.details);
+        }
+    }
+}
+
+#[async_trait]
+impl ArrowOperator for PluginSinkFunc {
+    fn name(&self) -> String {
+        "PluginSink".to_string()
+    }
+
+    async fn on_start(&mut self, ctx: &mut ArrowContext) {
+        let result = self.start(ctx).await;
+        Self::handle_result(ctx, result).await;
+    }
+
+    async fn process_batch(
+        &mut self,
+        batch: RecordBatch,
+        ctx: &mut ArrowContext,
+    ) -> anyhow::Result<()> {
+        let result = match encode_batch(&batch) {
+            Ok(data) => self.send(sink_req::Msg::Batch(data)).await,
+            Err(e) => Err(UserError::new(
+                "Failed to encode batch for connector plugin",
+                format!("{:#}", e),
+            )),
+        };
+        Self::handle_result(ctx, result).await;
+        Ok(())
+    }
+
+    async fn handle_checkpoint(
+        &mut self,
+        b: CheckpointBarrier,
+        ctx: &mut ArrowContext,
+    ) -> anyhow::Result<()> {
+        // records are only considered written once the plugin has flushed them, so none are
+        // lost if the pipeline restarts from this checkpoint
+        let result = self.checkpoint(b.epoch).await;
+        Self::handle_result(ctx, result).await;
+        Ok(())
+    }
+
+    async fn on_close(
+        &mut self,
+        _: &Option<SignalMessage>,
+        _: &mut ArrowContext,
+    ) -> anyhow::Result<()> {
+        // closing the request stream tells the plugin that there's no more data
+        self.tx = None;
+        if let Some(mut responses) = self.responses.take() {
+            while let Ok(Some(_)) = responses.message().await {}
+        }
+
+        Ok(())
+    }
+}
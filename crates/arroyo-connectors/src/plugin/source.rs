@@ -0,0 +1,254 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use arrow::array::{RecordBatch, TimestampNanosecondArray};
+use arroyo_operator::context::ArrowContext;
+use arroyo_operator::operator::SourceOperator;
+use arroyo_operator::SourceFinishType;
+use arroyo_rpc::grpc::connector::{
+    source_req, source_resp, SourceCheckpoint, SourceReq, StartSource, StopSource, TaskContext,
+};
+use arroyo_rpc::grpc::rpc::{StopMode, TableConfig};
+use arroyo_rpc::ControlMessage;
+use arroyo_types::{to_nanos, CheckpointBarrier, UserError};
+use async_trait::async_trait;
+use bincode::{Decode, Encode};
+use serde_json::Value;
+use tokio::select;
+use tokio::sync::mpsc::Sender;
+use tokio_stream::wrappers::ReceiverStream;
+use tracing::{debug, info};
+
+use crate::plugin::{connect, decode_batches, encode_schema};
+
+#[derive(Encode, Decode, Debug, Clone, Eq, PartialEq)]
+pub struct PluginSourceState {
+    /// opaque state returned by the plugin, like the offsets it has read up to
+    pub state: Vec<u8>,
+}
+
+pub struct PluginSourceFunc {
+    endpoint: String,
+    profile: Value,
+    table: Value,
+    state: Option<PluginSourceState>,
+}
+
+impl PluginSourceFunc {
+    pub fn new(endpoint: String, profile: Value, table: Value) -> Self {
+        Self {
+            endpoint,
+            profile,
+            table,
+            state: None,
+        }
+    }
+
+    async fn send(&self, tx: &Sender<SourceReq>, msg: source_req::Msg) -> Result<(), UserError> {
+        tx.send(SourceReq { msg: Some(msg) }).await.map_err(|_| {
+            UserError::new(
+                "Connector plugin failed",
+                "plugin closed the read stream unexpectedly",
+            )
+        })
+    }
+
+    async fn collect(&self, data: &[u8], ctx: &mut ArrowContext) -> Result<(), UserError> {
+        let out_schema = ctx.out_schema.as_ref().unwrap();
+        let schema = out_schema.schema.clone();
+        let timestamp_index = out_schema.timestamp_index;
+
+        let batches = decode_batches(data).map_err(|e| {
+            UserError::new(
+                "Invalid batch from connector plugin",
+                format!("could not decode Arrow IPC data: {:#}", e),
+            )
+        })?;
+
+        for batch in batches {
+            let mut columns = batch.columns().to_vec();
+            columns.insert(
+                timestamp_index,
+                Arc::new(TimestampNanosecondArray::from(vec![
+                    to_nanos(SystemTime::now())
+                        as i64;
+                    batch.num_rows()
+                ])),
+            );
+
+            let batch = RecordBatch::try_new(schema.clone(), columns).map_err(|e| {
+                UserError::new(
+                    "Invalid batch from connector plugin",
+                    format!("batch does not match the table's schema: {}", e),
+                )
+            })?;
+
+            ctx.collect(batch).await;
+        }
+
+        Ok(())
+    }
+
+    async fn checkpoint(
+        &mut self,
+        c: CheckpointBarrier,
+        state: Vec<u8>,
+        ctx: &mut ArrowContext,
+    ) -> bool {
+        debug!("starting checkpointing {}", ctx.task_info.task_index);
+        let state = PluginSourceState { state };
+        ctx.table_manager
+            .get_global_keyed_state("p")
+            .await
+            .unwrap()
+            .insert(ctx.task_info.task_index, state.clone())
+            .await;
+        self.state = Some(state);
+
+        self.start_checkpoint(c, ctx).await
+    }
+
+    async fn run_int(&mut self, ctx: &mut ArrowContext) -> Result<SourceFinishType, UserError> {
+        let out_schema = ctx.out_schema.as_ref().unwrap();
+        // the plugin produces every column but the timestamp, which is added as rows arrive
+        let schema = out_schema
+            .schema
+            .project(
+                &(0..out_schema.schema.fields().len())
+                    .filter(|i| *i != out_schema.timestamp_index)
+                    .collect::<Vec<_>>(),
+            )
+            .unwrap();
+
+        let mut client = connect(&self.endpoint).await.map_err(|e| {
+            UserError::new("Failed to connect to connector plugin", format!("{:#}", e))
+        })?;
+
+        let (tx, rx) = tokio::sync::mpsc::channel(16);
+        self.send(
+            &tx,
+            source_req::Msg::Start(StartSource {
+                context: Some(TaskContext {
+                    profile: self.profile.to_string(),
+                    table: self.table.to_string(),
+                    schema: encode_schema(&schema),
+                    task_index: ctx.task_info.task_index as u32,
+                    parallelism: ctx.task_info.parallelism as u32,
+                }),
+                state: self.state.as_ref().map(|s| s.state.clone()),
+            }),
+        )
+        .await?;
+
+        let mut responses = client
+            .read(ReceiverStream::new(rx))
+            .await
+            .map_err(|e| UserError::new("Connector plugin failed", e.message()))?
+            .into_inner();
+
+        // the checkpoint we're waiting on the plugin's state for
+        let mut pending: Option<CheckpointBarrier> = None;
+
+        loop {
+            select! {
+                resp = responses.message() => {
+                    let resp = resp
+                        .map_err(|e| UserError::new("Connector plugin failed", e.message()))?;
+
+                    match resp.and_then(|r| r.msg) {
+                        Some(source_resp::Msg::Batch(data)) => {
+                            self.collect(&data, ctx).await?;
+                        }
+                        Some(source_resp::Msg::State(state)) => {
+                            let Some(c) = pending.filter(|c| c.epoch == state.epoch) else {
+                                return Err(UserError::new(
+                                    "Connector plugin failed",
+                                    format!("plugin sent state for unexpected epoch {}", state.epoch),
+                                ));
+                            };
+                            pending = None;
+
+                            if self.checkpoint(c, state.state, ctx).await {
+                                return Ok(SourceFinishType::Immediate);
+                            }
+                        }
+                        Some(source_resp::Msg::Finished(_)) => {
+                            info!("Connector plugin source finished");
+                            return Ok(SourceFinishType::Final);
+                        }
+                        None => {
+                            return Err(UserError::new(
+                                "Connector plugin failed",
+                                "plugin closed the read stream unexpectedly",
+                            ));
+                        }
+                    }
+                }
+                msg = ctx.control_rx.recv(), if pending.is_none() => {
+                    match msg {
+                        Some(ControlMessage::Checkpoint(c)) => {
+                            // the plugin sends its state after every batch that precedes the
+                            // checkpoint, so the barrier is emitted once that arrives
+                            self.send(&tx, source_req::Msg::Checkpoint(SourceCheckpoint {
+                                epoch: c.epoch,
+                            })).await?;
+                            pending = Some(c);
+                        }
+                        Some(ControlMessage::Stop { mode }) => {
+                            info!("Stopping connector plugin source {:?}", mode);
+                            let _ = self.send(&tx, source_req::Msg::Stop(StopSource {})).await;
+
+                            return Ok(match mode {
+                                StopMode::Graceful => SourceFinishType::Graceful,
+                                StopMode::Immediate => SourceFinishType::Immediate,
+                            });
+                        }
+                        Some(ControlMessage::Commit { .. }) => {
+                            unreachable!("sources shouldn't receive commit messages");
+                        }
+                        Some(ControlMessage::LoadCompacted { compacted }) => {
+                            ctx.load_compacted(compacted).await;
+                        }
+                        Some(ControlMessage::QueryState { query }) => {
+                            ctx.query_state(query).await;
+                        }
+                        Some(ControlMessage::NoOp) | None => {}
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl SourceOperator for PluginSourceFunc {
+    fn name(&self) -> String {
+        "plugin-source".to_string()
+    }
+
+    fn tables(&self) -> HashMap<String, TableConfig> {
+        arroyo_state::global_table_config("p", "connector plugin source state")
+    }
+
+    async fn on_start(&mut self, ctx: &mut ArrowContext) {
+        let s = ctx
+            .table_manager
+            .get_global_keyed_state::<usize, PluginSourceState>("p")
+            .await
+            .expect("should have table p in plugin source");
+
+        self.state = s.get(&ctx.task_info.task_index).cloned();
+    }
+
+    async fn run(&mut self, ctx: &mut ArrowContext) -> SourceFinishType {
+        match self.run_int(ctx).await {
+            Ok(r) => r,
+            Err(e) => {
+                ctx.report_error(e.name.clone(), e.details.clone()).await;
+
+                panic!("{}: {}", e.name, e.details);
+            }
+        }
+    }
+}
@@ -4,11 +4,12 @@ use std::collections::HashMap;
 use std::str::FromStr;
 use std::time::Duration;
 
-use anyhow::anyhow;
+use anyhow::{anyhow, bail};
 use arroyo_rpc::{var_str::VarStr, OperatorConfig};
 use arroyo_types::string_to_map;
 use reqwest::{Client, Request};
 use tokio::sync::mpsc::Sender;
+use tokio::sync::oneshot;
 use typify::import_types;
 
 use arroyo_operator::connector::Connection;
@@ -16,24 +17,73 @@ use arroyo_rpc::api_types::connections::{
     ConnectionProfile, ConnectionSchema, ConnectionType, TestSourceMessage,
 };
 use serde::{Deserialize, Serialize};
+use serde_json_path::JsonPath;
 
+use crate::oauth::{oauth_config, with_oauth_schema, OAuthOptions, TokenProvider};
 use crate::{construct_http_client, pull_opt, pull_option_to_i64, EmptyConfig};
 
-use crate::polling_http::operator::{PollingHttpSourceFunc, PollingHttpSourceState};
+use crate::polling_http::operator::{
+    IncrementalCursor, PageCursor, PollingHttpSourceFunc, PollingHttpSourceState,
+};
 use arroyo_operator::connector::Connector;
 use arroyo_operator::operator::OperatorNode;
 
 const TABLE_SCHEMA: &str = include_str!("./table.json");
 const DEFAULT_POLLING_INTERVAL: Duration = Duration::from_secs(1);
+const DEFAULT_MAX_PAGES: usize = 100;
+const DEFAULT_CURSOR_PARAM: &str = "cursor";
 
 import_types!(
     schema = "src/polling_http/table.json",
-    convert = { {type = "string", format = "var-str"} = VarStr }
+    convert = {
+        {type = "string", format = "var-str"} = VarStr,
+        {type = "object", format = "oauth"} = OAuthOptions
+    }
 );
 const ICON: &str = include_str!("./http.svg");
 
 pub struct PollingHTTPConnector {}
 
+fn parse_json_path(name: &str, path: &str) -> anyhow::Result<JsonPath> {
+    JsonPath::parse(path).map_err(|e| anyhow!("invalid JSONPath '{}' for {}: {}", path, name, e))
+}
+
+fn page_cursor(table: &PollingHttpTable) -> anyhow::Result<Option<PageCursor>> {
+    match table.pagination {
+        None | Some(Pagination::None) => {
+            if table.cursor_path.is_some() {
+                bail!("'cursor_path' requires pagination = 'body_cursor'");
+            }
+            Ok(None)
+        }
+        Some(Pagination::LinkHeader) => Ok(Some(PageCursor::LinkHeader)),
+        Some(Pagination::BodyCursor) => {
+            let path = table
+                .cursor_path
+                .as_ref()
+                .ok_or_else(|| anyhow!("'cursor_path' must be set for body_cursor pagination"))?;
+            Ok(Some(PageCursor::Body {
+                path: parse_json_path("cursor_path", path)?,
+                param: table
+                    .cursor_param
+                    .clone()
+                    .unwrap_or_else(|| DEFAULT_CURSOR_PARAM.to_string()),
+            }))
+        }
+    }
+}
+
+fn incremental_cursor(table: &PollingHttpTable) -> anyhow::Result<Option<IncrementalCursor>> {
+    match (&table.incremental_param, &table.incremental_path) {
+        (Some(param), Some(path)) => Ok(Some(IncrementalCursor {
+            param: param.clone(),
+            path: parse_json_path("incremental_path", path)?,
+        })),
+        (None, None) if table.incremental_initial_value.is_none() => Ok(None),
+        _ => bail!("'incremental_param' and 'incremental_path' must be set together"),
+    }
+}
+
 impl PollingHTTPConnector {
     fn construct_test_request(
         client: &Client,
@@ -60,10 +110,12 @@ impl PollingHTTPConnector {
         Ok(req)
     }
 
-    async fn test_int(
+    /// Builds the first request that the source makes, fetching an OAuth token for it if one is
+    /// configured
+    async fn first_request(
         config: &PollingHttpTable,
-        tx: Sender<TestSourceMessage>,
-    ) -> anyhow::Result<()> {
+        tx: Option<&Sender<TestSourceMessage>>,
+    ) -> anyhow::Result<(Client, Request)> {
         let headers = config
             .headers
             .as_ref()
@@ -71,7 +123,50 @@ impl PollingHTTPConnector {
             .transpose()?;
 
         let client = construct_http_client(&config.endpoint, headers)?;
-        let req = Self::construct_test_request(&client, config)?;
+        let mut req = Self::construct_test_request(&client, config)?;
+
+        if let Some(oauth) = oauth_config(config.oauth.as_ref())? {
+            if let Some(tx) = tx {
+                tx.send(TestSourceMessage {
+                    error: false,
+                    done: false,
+                    message: "Fetching OAuth token".to_string(),
+                })
+                .await
+                .unwrap();
+            }
+
+            let authorization = TokenProvider::new(oauth).authorization().await?;
+            req.headers_mut()
+                .insert(reqwest::header::AUTHORIZATION, authorization.try_into()?);
+        }
+
+        Ok((client, req))
+    }
+
+    /// Polls the endpoint once, returning the body of the response as a single message
+    async fn sample_int(config: &PollingHttpTable) -> anyhow::Result<Vec<Vec<u8>>> {
+        let (client, req) = Self::first_request(config, None).await?;
+
+        let resp = client
+            .execute(req)
+            .await
+            .and_then(|resp| resp.error_for_status())
+            .map_err(|e| anyhow!("HTTP request failed: {}", e))?;
+
+        let body = resp
+            .bytes()
+            .await
+            .map_err(|e| anyhow!("failed to read response body: {}", e))?;
+
+        Ok(vec![body.to_vec()])
+    }
+
+    async fn test_int(
+        config: &PollingHttpTable,
+        tx: Sender<TestSourceMessage>,
+    ) -> anyhow::Result<()> {
+        let (client, req) = Self::first_request(config, Some(&tx)).await?;
 
         tx.send(TestSourceMessage {
             error: false,
@@ -112,7 +207,7 @@ impl Connector for PollingHTTPConnector {
             hidden: false,
             custom_schemas: true,
             connection_config: None,
-            table_config: TABLE_SCHEMA.to_owned(),
+            table_config: with_oauth_schema(TABLE_SCHEMA),
         }
     }
 
@@ -120,6 +215,26 @@ impl Connector for PollingHTTPConnector {
         ConnectionType::Source
     }
 
+    fn max_source_parallelism(&self, _: Self::ProfileT, _: Self::TableT) -> Option<usize> {
+        // only the first subtask polls the endpoint
+        Some(1)
+    }
+
+    fn sample(
+        &self,
+        _: Self::ProfileT,
+        table: Self::TableT,
+        _: usize,
+    ) -> oneshot::Receiver<anyhow::Result<Vec<Vec<u8>>>> {
+        let (tx, rx) = oneshot::channel();
+
+        tokio::spawn(async move {
+            let _ = tx.send(Self::sample_int(&table).await);
+        });
+
+        rx
+    }
+
     fn test(
         &self,
         _: &str,
@@ -169,6 +284,11 @@ impl Connector for PollingHTTPConnector {
             .map(|s| s.try_into())
             .transpose()
             .map_err(|_| anyhow!("invalid value for 'emit_behavior'"))?;
+        let pagination: Option<Pagination> = options
+            .remove("pagination")
+            .map(|s| s.try_into())
+            .transpose()
+            .map_err(|_| anyhow!("invalid value for 'pagination'"))?;
 
         self.from_config(
             None,
@@ -181,6 +301,14 @@ impl Connector for PollingHTTPConnector {
                 body,
                 poll_interval_ms: interval,
                 emit_behavior,
+                pagination,
+                cursor_path: options.remove("cursor_path"),
+                cursor_param: options.remove("cursor_param"),
+                max_pages: pull_option_to_i64("max_pages", options)?,
+                incremental_param: options.remove("incremental_param"),
+                incremental_path: options.remove("incremental_path"),
+                incremental_initial_value: options.remove("incremental_initial_value"),
+                oauth: OAuthOptions::from_options(options)?,
             },
             schema,
         )
@@ -205,6 +333,14 @@ impl Connector for PollingHTTPConnector {
             })?;
         }
 
+        oauth_config(table.oauth.as_ref())?;
+        page_cursor(&table)?;
+        incremental_cursor(&table)?;
+
+        if table.max_pages.is_some_and(|m| m <= 0) {
+            bail!("'max_pages' must be positive");
+        }
+
         let schema = schema
             .map(|s| s.to_owned())
             .ok_or_else(|| anyhow!("no schema defined for polling HTTP connection"))?;
@@ -262,8 +398,12 @@ impl Connector for PollingHTTPConnector {
         })
         .collect();
 
+        let oauth = oauth_config(table.oauth.as_ref())?.map(TokenProvider::new);
+        let pagination = page_cursor(&table)?;
+        let incremental = incremental_cursor(&table)?;
+
         Ok(OperatorNode::from_source(Box::new(PollingHttpSourceFunc {
-            state: PollingHttpSourceState::default(),
+            state: PollingHttpSourceState::new(table.incremental_initial_value.clone()),
             client: reqwest::ClientBuilder::new()
                 .default_headers(headers)
                 .timeout(Duration::from_secs(5))
@@ -287,6 +427,13 @@ impl Connector for PollingHTTPConnector {
                 .expect("PollingHTTP source must have a format"),
             framing: config.framing,
             bad_data: config.bad_data,
+            oauth,
+            pagination,
+            max_pages: table
+                .max_pages
+                .map(|m| m as usize)
+                .unwrap_or(DEFAULT_MAX_PAGES),
+            incremental,
         })))
     }
 }
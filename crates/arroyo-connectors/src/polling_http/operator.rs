@@ -13,6 +13,7 @@ use arroyo_types::{ArrowMessage, SignalMessage, UserError, Watermark};
 use tokio::select;
 use tokio::time::MissedTickBehavior;
 
+use crate::oauth::TokenProvider;
 use crate::polling_http::EmitBehavior;
 use arroyo_operator::context::ArrowContext;
 use arroyo_operator::operator::SourceOperator;
@@ -20,10 +21,34 @@ use arroyo_operator::SourceFinishType;
 use arroyo_rpc::formats::{BadData, Format, Framing};
 use arroyo_rpc::grpc::rpc::{StopMode, TableConfig};
 use arroyo_state::tables::global_keyed_map::GlobalKeyedView;
+use reqwest::header::{HeaderMap, LINK};
+use serde_json_path::JsonPath;
 use tracing::{debug, info, warn};
+use url::Url;
 
 const MAX_BODY_SIZE: usize = 5 * 1024 * 1024; // 5M ought to be enough for anybody
 
+/// How the next page of a response is found
+pub enum PageCursor {
+    /// The `rel="next"` URL of the Link header
+    LinkHeader,
+    /// A value in the response body, which is either the URL of the next page or a cursor to
+    /// send as a query parameter
+    Body { path: JsonPath, param: String },
+}
+
+/// A query parameter that's sent with the last value found at `path` in the previous poll's
+/// responses, so that each poll only fetches new records
+pub struct IncrementalCursor {
+    pub param: String,
+    pub path: JsonPath,
+}
+
+struct Page {
+    body: Vec<u8>,
+    headers: HeaderMap,
+}
+
 pub struct PollingHttpSourceFunc {
     pub state: PollingHttpSourceState,
     pub client: reqwest::Client,
@@ -35,11 +60,79 @@ pub struct PollingHttpSourceFunc {
     pub format: Format,
     pub framing: Option<Framing>,
     pub bad_data: Option<BadData>,
+    pub oauth: Option<TokenProvider>,
+    pub pagination: Option<PageCursor>,
+    pub max_pages: usize,
+    pub incremental: Option<IncrementalCursor>,
 }
 
 #[derive(Clone, Debug, Encode, Decode, PartialEq, PartialOrd, Default)]
 pub struct PollingHttpSourceState {
     last_message: Option<Vec<u8>>,
+    cursor: Option<String>,
+}
+
+impl PollingHttpSourceState {
+    pub fn new(cursor: Option<String>) -> Self {
+        Self {
+            last_message: None,
+            cursor,
+        }
+    }
+}
+
+/// Returns the value of the last node that `path` selects in a JSON body, if there is one that
+/// isn't null
+fn query_last(body: &[u8], path: &JsonPath) -> Result<Option<String>, UserError> {
+    let value: serde_json::Value = serde_json::from_slice(body).map_err(|e| {
+        UserError::new(
+            "invalid response",
+            format!("response body is not valid JSON: {}", e),
+        )
+    })?;
+
+    Ok(match path.query(&value).iter().last() {
+        None | Some(serde_json::Value::Null) => None,
+        Some(serde_json::Value::String(s)) if s.is_empty() => None,
+        Some(serde_json::Value::String(s)) => Some(s.clone()),
+        Some(v) => Some(v.to_string()),
+    })
+}
+
+/// Returns `url` with the query parameter `name` set to `value`, replacing any existing values
+fn with_param(url: &Url, name: &str, value: &str) -> Url {
+    let pairs: Vec<(String, String)> = url
+        .query_pairs()
+        .filter(|(k, _)| k != name)
+        .map(|(k, v)| (k.into_owned(), v.into_owned()))
+        .collect();
+
+    let mut url = url.clone();
+    url.query_pairs_mut()
+        .clear()
+        .extend_pairs(pairs)
+        .append_pair(name, value);
+    url
+}
+
+/// Finds the `rel="next"` URL in a Link header, like `<https://example.com/items?page=2>;
+/// rel="next", <https://example.com/items?page=5>; rel="last"`
+fn next_link(link: &str) -> Option<&str> {
+    link.split(',').find_map(|link| {
+        let (target, params) = link.split_once(';')?;
+        let target = target.trim().strip_prefix('<')?.strip_suffix('>')?;
+        params
+            .split(';')
+            .filter_map(|p| p.trim().split_once('='))
+            .any(|(k, v)| {
+                k.trim().eq_ignore_ascii_case("rel")
+                    && v.trim()
+                        .trim_matches('"')
+                        .split_whitespace()
+                        .any(|rel| rel.eq_ignore_ascii_case("next"))
+            })
+            .then_some(target)
+    })
 }
 
 #[async_trait]
@@ -115,20 +208,29 @@ impl PollingHttpSourceFunc {
             ControlMessage::LoadCompacted { compacted } => {
                 ctx.load_compacted(compacted).await;
             }
+            ControlMessage::QueryState { query } => {
+                ctx.query_state(query).await;
+            }
             ControlMessage::NoOp => {}
         }
         None
     }
 
-    async fn request(&mut self) -> Result<Vec<u8>, UserError> {
-        let mut request = self
-            .client
-            .request(self.method.clone(), self.endpoint.clone());
+    async fn request(&mut self, url: Url) -> Result<Page, UserError> {
+        let mut request = self.client.request(self.method.clone(), url.clone());
 
         if let Some(body) = self.body.clone() {
             request = request.body(body);
         }
 
+        if let Some(oauth) = &self.oauth {
+            let token = oauth
+                .token()
+                .await
+                .map_err(|e| UserError::new("failed to fetch OAuth token", format!("{:#}", e)))?;
+            request = request.bearer_auth(token);
+        }
+
         let resp = self
             .client
             .execute(request.build().expect("building request failed"))
@@ -152,6 +254,7 @@ impl PollingHttpSourceFunc {
                 ));
             }
 
+            let headers = resp.headers().clone();
             let mut buf = Vec::with_capacity(content_len as usize);
 
             let mut bytes_stream = resp.bytes_stream();
@@ -171,9 +274,16 @@ impl PollingHttpSourceFunc {
                 }
             }
 
-            Ok(buf)
+            Ok(Page { body: buf, headers })
         } else {
             let status = resp.status();
+            if status == reqwest::StatusCode::UNAUTHORIZED {
+                if let Some(oauth) = &self.oauth {
+                    // the token may have been revoked; fetch a new one for the next poll
+                    oauth.invalidate().await;
+                }
+            }
+
             let bytes = resp.bytes().await;
             let error_body = bytes
                 .as_ref()
@@ -182,7 +292,7 @@ impl PollingHttpSourceFunc {
 
             warn!(
                 "HTTP request to {} failed with {}: {}",
-                self.endpoint,
+                url,
                 status.as_u16(),
                 error_body
             );
@@ -194,6 +304,64 @@ impl PollingHttpSourceFunc {
         }
     }
 
+    /// The URL of the page after `page`, which was fetched from `url`
+    fn next_page(&self, url: &Url, page: &Page) -> Result<Option<Url>, UserError> {
+        let invalid = |e: url::ParseError| {
+            UserError::new(
+                "invalid response",
+                format!("invalid URL for the next page: {}", e),
+            )
+        };
+
+        match &self.pagination {
+            None => Ok(None),
+            Some(PageCursor::LinkHeader) => page
+                .headers
+                .get_all(LINK)
+                .iter()
+                .filter_map(|v| v.to_str().ok())
+                .find_map(next_link)
+                .map(|next| url.join(next).map_err(invalid))
+                .transpose(),
+            Some(PageCursor::Body { path, param }) => Ok(match query_last(&page.body, path)? {
+                None => None,
+                Some(cursor) if cursor.starts_with("http://") || cursor.starts_with("https://") => {
+                    Some(Url::parse(&cursor).map_err(invalid)?)
+                }
+                Some(cursor) => Some(with_param(url, param, &cursor)),
+            }),
+        }
+    }
+
+    /// Fetches every page of a poll
+    async fn poll(&mut self) -> Result<Vec<Vec<u8>>, UserError> {
+        let mut url = self.endpoint.clone();
+        if let (Some(incremental), Some(cursor)) = (&self.incremental, &self.state.cursor) {
+            url = with_param(&url, &incremental.param, cursor);
+        }
+
+        let mut pages = vec![];
+        loop {
+            let page = self.request(url.clone()).await?;
+            let next = self.next_page(&url, &page)?;
+            pages.push(page.body);
+
+            match next {
+                Some(next) if pages.len() < self.max_pages => url = next,
+                Some(_) => {
+                    warn!(
+                        "stopped fetching pages from {} after reaching the limit of {}",
+                        self.endpoint, self.max_pages
+                    );
+                    break;
+                }
+                None => break,
+            }
+        }
+
+        Ok(pages)
+    }
+
     async fn run_int(&mut self, ctx: &mut ArrowContext) -> Result<SourceFinishType, UserError> {
         ctx.initialize_deserializer(
             self.format.clone(),
@@ -209,19 +377,31 @@ impl PollingHttpSourceFunc {
             loop {
                 select! {
                     _ = timer.tick()  => {
-                        match self.request().await {
-                            Ok(buf) => {
-                                if self.emit_behavior == EmitBehavior::Changed && Some(&buf) == self.state.last_message.as_ref() {
+                        match self.poll().await {
+                            Ok(pages) => {
+                                if self.emit_behavior == EmitBehavior::Changed && pages.first() == self.state.last_message.as_ref() {
                                     continue;
                                 }
 
-                                ctx.deserialize_slice(&buf, SystemTime::now(), None).await?;
+                                let mut cursor = None;
+                                for page in &pages {
+                                    ctx.deserialize_slice(page, SystemTime::now(), None).await?;
+
+                                    if let Some(incremental) = &self.incremental {
+                                        cursor = query_last(page, &incremental.path)?.or(cursor);
+                                    }
+                                }
 
                                 if ctx.should_flush() {
                                     ctx.flush_buffer().await?;
                                 }
 
-                                self.state.last_message = Some(buf);
+                                // the cursor is only advanced once the records it covers have been
+                                // emitted, so a checkpoint never includes one without the other
+                                if cursor.is_some() {
+                                    self.state.cursor = cursor;
+                                }
+                                self.state.last_message = pages.into_iter().next();
                             }
                             Err(e) => {
                                 ctx.report_user_error(e).await;
@@ -250,3 +430,48 @@ impl PollingHttpSourceFunc {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{next_link, query_last, with_param};
+    use serde_json_path::JsonPath;
+    use url::Url;
+
+    #[test]
+    fn test_next_link() {
+        assert_eq!(
+            next_link(
+                r#"<https://example.com/items?page=2>; rel="next", <https://example.com/items?page=5>; rel="last""#
+            ),
+            Some("https://example.com/items?page=2")
+        );
+        assert_eq!(
+            next_link(r#"</items?page=3>; rel="prev next""#),
+            Some("/items?page=3")
+        );
+        assert_eq!(
+            next_link(r#"<https://example.com/items?page=1>; rel="first""#),
+            None
+        );
+    }
+
+    #[test]
+    fn test_cursors() {
+        let url = Url::parse("https://example.com/items?limit=10&cursor=a").unwrap();
+        assert_eq!(
+            with_param(&url, "cursor", "b c").as_str(),
+            "https://example.com/items?limit=10&cursor=b+c"
+        );
+
+        let body = br#"{"data": [{"updated_at": "2024-01-01"}, {"updated_at": "2024-01-02"}], "next": null}"#;
+        assert_eq!(
+            query_last(body, &JsonPath::parse("$.data[*].updated_at").unwrap()).unwrap(),
+            Some("2024-01-02".to_string())
+        );
+        assert_eq!(
+            query_last(body, &JsonPath::parse("$.next").unwrap()).unwrap(),
+            None
+        );
+        assert!(query_last(b"not json", &JsonPath::parse("$.next").unwrap()).is_err());
+    }
+}
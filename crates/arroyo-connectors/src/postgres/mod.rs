@@ -0,0 +1,428 @@
+mod sink;
+
+use anyhow::{anyhow, bail};
+use arrow::datatypes::Field;
+use arroyo_operator::connector::{Connection, Connector};
+use arroyo_operator::operator::OperatorNode;
+use arroyo_rpc::api_types::connections::{
+    ConnectionProfile, ConnectionSchema, ConnectionType, TestSourceMessage,
+};
+use arroyo_rpc::formats::{Format, JsonFormat, TimestampFormat};
+use arroyo_rpc::var_str::VarStr;
+use arroyo_rpc::OperatorConfig;
+use deadpool_postgres::{ManagerConfig, Pool, PoolConfig, RecyclingMethod, Runtime};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use tokio::sync::oneshot::Receiver;
+use tokio_postgres::{Client, NoTls};
+use typify::import_types;
+
+use crate::postgres::sink::{sql_type, PostgresSinkFunc};
+use crate::sink_schema::{resolve_drift, schema_drift, Dialect, DriftMode, TableColumn};
+use crate::sql_sink::check_schema;
+use crate::{pull_opt, pull_option_to_i64};
+
+const CONFIG_SCHEMA: &str = include_str!("./profile.json");
+const TABLE_SCHEMA: &str = include_str!("./table.json");
+
+import_types!(
+    schema = "src/postgres/profile.json",
+    convert = {
+        {type = "string", format = "var-str"} = VarStr
+    }
+);
+
+import_types!(schema = "src/postgres/table.json");
+
+pub struct PostgresConnector {}
+
+/// Creates a pool of connections to the database, which are opened lazily as they are needed
+fn create_pool(config: &PostgresConfig) -> anyhow::Result<Pool> {
+    let mut cfg = deadpool_postgres::Config::new();
+    cfg.host = Some(config.host.clone());
+    cfg.port = Some(config.port as u16);
+    cfg.dbname = Some(config.database.clone());
+    cfg.user = Some(config.user.sub_env_vars()?);
+    cfg.password = config
+        .password
+        .as_ref()
+        .map(|p| p.sub_env_vars())
+        .transpose()?;
+    cfg.application_name = Some("arroyo".to_string());
+    cfg.manager = Some(ManagerConfig {
+        recycling_method: RecyclingMethod::Fast,
+    });
+    cfg.pool = Some(PoolConfig::new(config.pool_size.max(1) as usize));
+
+    cfg.create_pool(Some(Runtime::Tokio1), NoTls)
+        .map_err(|e| anyhow!("failed to create connection pool: {}", e))
+}
+
+fn quote_ident(ident: &str) -> String {
+    format!("\"{}\"", ident.replace('"', "\"\""))
+}
+
+impl From<&SchemaDrift> for DriftMode {
+    fn from(mode: &SchemaDrift) -> Self {
+        match mode {
+            SchemaDrift::Fail => DriftMode::Fail,
+            SchemaDrift::Coerce => DriftMode::Coerce,
+            SchemaDrift::Evolve => DriftMode::Evolve,
+        }
+    }
+}
+
+/// Whether values of the postgres type `from` can be assigned to a column of type `to`
+/// without loss
+fn coercible(from: &str, to: &str) -> bool {
+    // every type can be written to a text column via its text representation
+    from == to
+        || matches!(to, "text" | "varchar" | "bpchar")
+        || matches!(
+            (from, to),
+            ("int4", "int8" | "numeric" | "float8")
+                | ("int8", "numeric")
+                | ("float4", "float8" | "numeric")
+                | ("float8", "numeric")
+                | ("jsonb", "json")
+                | ("timestamp", "timestamptz")
+                | ("date", "timestamp" | "timestamptz")
+        )
+}
+
+/// Adds a column to the table; other tasks of the sink may have already added it
+fn add_column(table: &str, column: &str, type_name: &str) -> String {
+    format!(
+        "ALTER TABLE {} ADD COLUMN IF NOT EXISTS {} {}",
+        table,
+        quote_ident(column),
+        type_name
+    )
+}
+
+pub(crate) const POSTGRES: Dialect = Dialect {
+    // parameters are cast to this type before being assigned to the column
+    column_type: |field| Ok(sql_type(field)?.rsplit("::").next().unwrap().to_string()),
+    coercible,
+    add_column: Some(add_column),
+};
+
+/// The schema-qualified, quoted name of the table
+fn qualified_name(table: &PostgresTable) -> String {
+    format!(
+        "{}.{}",
+        quote_ident(&table.schema_name),
+        quote_ident(&table.table_name)
+    )
+}
+
+/// Reads the columns of the table from the database catalog, in order
+async fn table_columns(client: &Client, table: &PostgresTable) -> anyhow::Result<Vec<TableColumn>> {
+    let rows = client
+        .query(
+            "SELECT a.attname::text, t.typname::text, a.attnotnull,
+                a.atthasdef OR a.attidentity <> '' OR a.attgenerated <> ''
+            FROM pg_attribute a
+            JOIN pg_class c ON c.oid = a.attrelid
+            JOIN pg_namespace n ON n.oid = c.relnamespace
+            JOIN pg_type t ON t.oid = a.atttypid
+            WHERE n.nspname = $1 AND c.relname = $2 AND a.attnum > 0 AND NOT a.attisdropped
+            ORDER BY a.attnum",
+            &[&table.schema_name, &table.table_name],
+        )
+        .await?;
+
+    if rows.is_empty() {
+        bail!(
+            "table {}.{} does not exist",
+            table.schema_name,
+            table.table_name
+        );
+    }
+
+    Ok(rows
+        .iter()
+        .map(|row| TableColumn {
+            name: row.get(0),
+            type_name: row.get(1),
+            not_null: row.get(2),
+            has_default: row.get(3),
+        })
+        .collect())
+}
+
+/// Reads the columns of the primary key of the table from the database catalog; this is
+/// empty if the table has no primary key
+async fn primary_key_columns(
+    client: &Client,
+    table: &PostgresTable,
+) -> anyhow::Result<Vec<String>> {
+    let rows = client
+        .query(
+            "SELECT a.attname::text
+            FROM pg_index i
+            JOIN pg_class c ON c.oid = i.indrelid
+            JOIN pg_namespace n ON n.oid = c.relnamespace
+            JOIN pg_attribute a ON a.attrelid = c.oid AND a.attnum = ANY(i.indkey)
+            WHERE i.indisprimary AND n.nspname = $1 AND c.relname = $2
+            ORDER BY array_position(i.indkey::int2[], a.attnum)",
+            &[&table.schema_name, &table.table_name],
+        )
+        .await?;
+
+    Ok(rows.iter().map(|row| row.get(0)).collect())
+}
+
+async fn test_inner(
+    config: &PostgresConfig,
+    table: Option<&PostgresTable>,
+    schema: Option<&ConnectionSchema>,
+) -> anyhow::Result<String> {
+    let pool = create_pool(config)?;
+    let client = pool.get().await.map_err(|e| {
+        anyhow!(
+            "failed to connect to {}:{}: {}",
+            config.host,
+            config.port,
+            e
+        )
+    })?;
+
+    let Some(table) = table else {
+        return Ok("Successfully connected to Postgres".to_string());
+    };
+
+    let columns = table_columns(&client, table).await?;
+
+    for key in &table.key_columns {
+        if !columns.iter().any(|c| &c.name == key) {
+            bail!(
+                "key column '{}' does not exist in table {}.{}",
+                key,
+                table.schema_name,
+                table.table_name
+            );
+        }
+    }
+
+    let Some(schema) = schema else {
+        return Ok("Successfully validated connection".to_string());
+    };
+
+    let fields: Vec<Field> = schema.fields.iter().map(|f| f.clone().into()).collect();
+    let drift = schema_drift(&POSTGRES, &fields, &columns)?;
+    let evolutions = resolve_drift(
+        &POSTGRES,
+        &qualified_name(table),
+        (&table.schema_drift).into(),
+        &drift,
+    )?;
+
+    if evolutions.is_empty() {
+        Ok("Successfully validated connection".to_string())
+    } else {
+        Ok(format!(
+            "Successfully validated connection; the table will be altered when the pipeline starts:\n{}",
+            evolutions.join(";\n")
+        ))
+    }
+}
+
+impl Connector for PostgresConnector {
+    type ProfileT = PostgresConfig;
+    type TableT = PostgresTable;
+
+    fn name(&self) -> &'static str {
+        "postgres"
+    }
+
+    fn metadata(&self) -> arroyo_rpc::api_types::connections::Connector {
+        arroyo_rpc::api_types::connections::Connector {
+            id: "postgres".to_string(),
+            name: "Postgres".to_string(),
+            icon: "".to_string(),
+            description: "Write results to a Postgres table, upserting rows by key".to_string(),
+            enabled: true,
+            source: false,
+            sink: true,
+            testing: true,
+            hidden: false,
+            custom_schemas: true,
+            connection_config: Some(CONFIG_SCHEMA.to_owned()),
+            table_config: TABLE_SCHEMA.to_owned(),
+        }
+    }
+
+    fn config_description(&self, config: Self::ProfileT) -> String {
+        format!("{}:{}/{}", config.host, config.port, config.database)
+    }
+
+    fn table_type(&self, _: Self::ProfileT, _: Self::TableT) -> ConnectionType {
+        ConnectionType::Sink
+    }
+
+    fn get_schema(
+        &self,
+        _: Self::ProfileT,
+        _: Self::TableT,
+        s: Option<&ConnectionSchema>,
+    ) -> Option<ConnectionSchema> {
+        s.cloned()
+    }
+
+    fn test_profile(&self, profile: Self::ProfileT) -> Option<Receiver<TestSourceMessage>> {
+        let (tx, rx) = tokio::sync::oneshot::channel();
+
+        tokio::spawn(async move {
+            let message = match test_inner(&profile, None, None).await {
+                Ok(m) => TestSourceMessage::done(m),
+                Err(e) => TestSourceMessage::fail(format!("Failed to connect to Postgres: {}", e)),
+            };
+
+            tx.send(message).unwrap();
+        });
+
+        Some(rx)
+    }
+
+    fn test(
+        &self,
+        _: &str,
+        config: Self::ProfileT,
+        table: Self::TableT,
+        schema: Option<&ConnectionSchema>,
+        tx: tokio::sync::mpsc::Sender<TestSourceMessage>,
+    ) {
+        let schema = schema.cloned();
+        tokio::task::spawn(async move {
+            let message = match test_inner(&config, Some(&table), schema.as_ref()).await {
+                Ok(m) => TestSourceMessage::done(m),
+                Err(e) => TestSourceMessage::fail(e.to_string()),
+            };
+
+            tx.send(message).await.unwrap();
+        });
+    }
+
+    fn from_options(
+        &self,
+        name: &str,
+        options: &mut HashMap<String, String>,
+        schema: Option<&ConnectionSchema>,
+        profile: Option<&ConnectionProfile>,
+    ) -> anyhow::Result<Connection> {
+        let config = match profile {
+            Some(p) => serde_json::from_value(p.config.clone())
+                .map_err(|e| anyhow!("invalid config for profile '{}' in database: {}", p.id, e))?,
+            None => PostgresConfig {
+                host: pull_opt("host", options)?,
+                port: pull_option_to_i64("port", options)?.unwrap_or(5432),
+                database: pull_opt("database", options)?,
+                user: VarStr::new(pull_opt("user", options)?),
+                password: options.remove("password").map(VarStr::new),
+                pool_size: pull_option_to_i64("pool_size", options)?.unwrap_or(4),
+            },
+        };
+
+        let table = PostgresTable {
+            schema_name: options
+                .remove("schema_name")
+                .unwrap_or_else(|| "public".to_string()),
+            table_name: pull_opt("table_name", options)?,
+            key_columns: options
+                .remove("key_columns")
+                .map(|keys| keys.split(',').map(|k| k.trim().to_string()).collect())
+                .unwrap_or_default(),
+            schema_drift: options
+                .remove("schema_drift")
+                .map(|mode| match mode.as_str() {
+                    "fail" => Ok(SchemaDrift::Fail),
+                    "coerce" => Ok(SchemaDrift::Coerce),
+                    "evolve" => Ok(SchemaDrift::Evolve),
+                    _ => Err(anyhow!(
+                        "invalid value for 'schema_drift': '{}'; expected one of 'fail', 'coerce' or 'evolve'",
+                        mode
+                    )),
+                })
+                .transpose()?
+                .unwrap_or(SchemaDrift::Coerce),
+            batch_size: pull_option_to_i64("batch_size", options)?.unwrap_or(1000),
+            flush_interval_millis: pull_option_to_i64("flush_interval_millis", options)?
+                .unwrap_or(1000),
+        };
+
+        self.from_config(None, name, config, table, schema)
+    }
+
+    fn from_config(
+        &self,
+        id: Option<i64>,
+        name: &str,
+        config: Self::ProfileT,
+        table: Self::TableT,
+        schema: Option<&ConnectionSchema>,
+    ) -> anyhow::Result<Connection> {
+        let mut schema = schema
+            .map(|s| s.to_owned())
+            .ok_or_else(|| anyhow!("no schema defined for Postgres sink"))?;
+
+        // the sink always receives changes as debezium-style before/after rows, which lets
+        // it handle both append-only and updating queries
+        let format = schema.format.clone().unwrap_or_else(|| {
+            Format::Json(JsonFormat {
+                debezium: true,
+                timestamp_format: TimestampFormat::UnixMillis,
+                ..Default::default()
+            })
+        });
+
+        if !matches!(format, Format::Json(JsonFormat { debezium: true, .. })) {
+            bail!("Postgres sinks only support the debezium_json format");
+        }
+
+        if table.batch_size <= 0 {
+            bail!("batch_size must be positive");
+        }
+
+        if table.flush_interval_millis <= 0 {
+            bail!("flush_interval_millis must be positive");
+        }
+
+        check_schema(&schema, &table.key_columns, "Postgres")?;
+
+        schema.format = Some(format.clone());
+
+        let description = format!("PostgresSink<{}.{}>", table.schema_name, table.table_name);
+
+        let config = OperatorConfig {
+            connection: serde_json::to_value(config).unwrap(),
+            table: serde_json::to_value(table).unwrap(),
+            rate_limit: None,
+            format: Some(format),
+            bad_data: schema.bad_data.clone(),
+            framing: None,
+            metadata_fields: vec![],
+        };
+
+        Ok(Connection {
+            id,
+            connector: self.name(),
+            name: name.to_string(),
+            connection_type: ConnectionType::Sink,
+            schema,
+            config: serde_json::to_string(&config).unwrap(),
+            description,
+        })
+    }
+
+    fn make_operator(
+        &self,
+        profile: Self::ProfileT,
+        table: Self::TableT,
+        _: OperatorConfig,
+    ) -> anyhow::Result<OperatorNode> {
+        Ok(OperatorNode::from_operator(Box::new(
+            PostgresSinkFunc::new(create_pool(&profile)?, table),
+        )))
+    }
+}
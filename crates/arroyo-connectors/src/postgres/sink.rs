@@ -0,0 +1,458 @@
+use anyhow::bail;
+use arrow::array::RecordBatch;
+use arrow::datatypes::{DataType, Field};
+use arroyo_operator::context::ArrowContext;
+use arroyo_operator::operator::ArrowOperator;
+use arroyo_types::{ArroyoExtensionType, CheckpointBarrier, NonRetryableError, SignalMessage};
+use async_trait::async_trait;
+use bytes::BytesMut;
+use deadpool_postgres::{Object, Pool};
+use std::error::Error;
+use std::time::{Duration, Instant};
+use tokio_postgres::types::{to_sql_checked, IsNull, ToSql, Type};
+use tokio_postgres::Client;
+use tracing::{info, warn};
+
+use crate::postgres::{
+    primary_key_columns, qualified_name, quote_ident, table_columns, PostgresTable, POSTGRES,
+};
+use crate::sink_schema::{resolve_drift, schema_drift};
+use crate::sql_sink::{PendingWrites, SqlValue};
+
+/// The maximum number of bind parameters postgres allows in a single statement
+const MAX_PARAMS: usize = 65535;
+const MAX_WRITE_ATTEMPTS: u32 = 20;
+
+/// Parameters are always cast to the type that corresponds to the arrow type of their column
+/// (see [sql_type]), which postgres then converts to the type of the target column on
+/// assignment
+impl ToSql for SqlValue {
+    fn to_sql(&self, ty: &Type, out: &mut BytesMut) -> Result<IsNull, Box<dyn Error + Sync + Send>>
+    where
+        Self: Sized,
+    {
+        match self {
+            SqlValue::Null => Ok(IsNull::Yes),
+            SqlValue::Bool(v) => v.to_sql(ty, out),
+            SqlValue::Int4(v) => v.to_sql(ty, out),
+            SqlValue::Int8(v) => v.to_sql(ty, out),
+            SqlValue::Float4(v) => v.to_sql(ty, out),
+            SqlValue::Float8(v) => v.to_sql(ty, out),
+            SqlValue::Text(v) => v.to_sql(ty, out),
+            SqlValue::Bytes(v) => v.to_sql(ty, out),
+            SqlValue::Timestamp(v) => v.to_sql(ty, out),
+        }
+    }
+
+    fn accepts(_: &Type) -> bool
+    where
+        Self: Sized,
+    {
+        true
+    }
+
+    to_sql_checked!();
+}
+
+/// The SQL type that parameters for a field are cast to
+pub(super) fn sql_type(field: &Field) -> anyhow::Result<&'static str> {
+    Ok(match field.data_type() {
+        DataType::Boolean => "bool",
+        DataType::Int8 | DataType::Int16 | DataType::Int32 | DataType::UInt8 | DataType::UInt16 => {
+            "int4"
+        }
+        DataType::Int64 | DataType::UInt32 => "int8",
+        DataType::Float16 | DataType::Float32 => "float4",
+        DataType::Float64 => "float8",
+        DataType::UInt64 | DataType::Decimal128(_, _) | DataType::Decimal256(_, _) => {
+            "text::numeric"
+        }
+        DataType::Utf8 | DataType::LargeUtf8 => {
+            match ArroyoExtensionType::from_map(field.metadata()) {
+                Some(ArroyoExtensionType::JSON) => "text::jsonb",
+                None => "text",
+            }
+        }
+        DataType::Binary | DataType::LargeBinary => "bytea",
+        DataType::Timestamp(_, _) => "timestamp",
+        DataType::Date32 | DataType::Date64 => "text::date",
+        t => bail!(
+            "field '{}' has type {}, which cannot be written to Postgres",
+            field.name(),
+            t
+        ),
+    })
+}
+
+/// A list of `rows` parenthesized tuples of casted parameters, like `($1::int8, $2::text)`
+fn values_list(casts: &[&str], rows: usize) -> String {
+    (0..rows)
+        .map(|row| {
+            let params: Vec<_> = casts
+                .iter()
+                .enumerate()
+                .map(|(i, cast)| format!("${}::{}", row * casts.len() + i + 1, cast))
+                .collect();
+            format!("({})", params.join(", "))
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// The statements used to write to the table, which are built once the input schema is known
+struct Statements {
+    table: String,
+    columns: Vec<String>,
+    casts: Vec<&'static str>,
+    key_indices: Vec<usize>,
+}
+
+impl Statements {
+    fn keyed(&self) -> bool {
+        !self.key_indices.is_empty()
+    }
+
+    /// Inserts `rows` rows, or upserts them if the table has key columns
+    fn insert(&self, rows: usize) -> String {
+        let columns: Vec<_> = self.columns.iter().map(|c| quote_ident(c)).collect();
+        let mut sql = format!(
+            "INSERT INTO {} ({}) VALUES {}",
+            self.table,
+            columns.join(", "),
+            values_list(&self.casts, rows)
+        );
+
+        if self.keyed() {
+            let keys: Vec<_> = self
+                .key_indices
+                .iter()
+                .map(|i| columns[*i].as_str())
+                .collect();
+            let updates: Vec<_> = columns
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| !self.key_indices.contains(i))
+                .map(|(_, c)| format!("{c} = EXCLUDED.{c}"))
+                .collect();
+
+            sql.push_str(&format!(" ON CONFLICT ({}) DO ", keys.join(", ")));
+            if updates.is_empty() {
+                sql.push_str("NOTHING");
+            } else {
+                sql.push_str(&format!("UPDATE SET {}", updates.join(", ")));
+            }
+        }
+
+        sql
+    }
+
+    /// Deletes the rows with `rows` sets of key values
+    fn delete(&self, rows: usize) -> String {
+        let keys: Vec<_> = self
+            .key_indices
+            .iter()
+            .map(|i| quote_ident(&self.columns[*i]))
+            .collect();
+        let casts: Vec<_> = self.key_indices.iter().map(|i| self.casts[*i]).collect();
+
+        format!(
+            "DELETE FROM {} WHERE ({}) IN ({})",
+            self.table,
+            keys.join(", "),
+            values_list(&casts, rows)
+        )
+    }
+}
+
+pub struct PostgresSinkFunc {
+    pool: Pool,
+    table: PostgresTable,
+    fields: Vec<Field>,
+    statements: Option<Statements>,
+    pending: PendingWrites,
+    last_flushed: Instant,
+}
+
+impl PostgresSinkFunc {
+    pub fn new(pool: Pool, table: PostgresTable) -> Self {
+        Self {
+            pool,
+            table,
+            fields: vec![],
+            statements: None,
+            pending: PendingWrites::default(),
+            last_flushed: Instant::now(),
+        }
+    }
+
+    async fn connect(&self, ctx: &mut ArrowContext) -> anyhow::Result<Object> {
+        let mut attempts = 0;
+        loop {
+            match self.pool.get().await {
+                Ok(client) => return Ok(client),
+                Err(e) => {
+                    attempts += 1;
+                    if attempts >= MAX_WRITE_ATTEMPTS {
+                        bail!(
+                            "failed to connect to Postgres after {} attempts: {}",
+                            attempts,
+                            e
+                        );
+                    }
+                    ctx.report_error("Failed to connect", e.to_string()).await;
+                    tokio::time::sleep(Duration::from_millis((50 * (1 << attempts)).min(5_000)))
+                        .await;
+                }
+            }
+        }
+    }
+
+    /// Checks the fields of the sink against the table, evolving the table if that's
+    /// allowed, and returns the key columns to write with
+    async fn prepare_table(
+        &self,
+        client: &Client,
+        fields: &[Field],
+    ) -> anyhow::Result<Vec<String>> {
+        let columns = table_columns(client, &self.table).await?;
+        let table = qualified_name(&self.table);
+
+        let drift = schema_drift(&POSTGRES, fields, &columns)?;
+        for d in &drift {
+            warn!(
+                "schema of Postgres table {} differs from sink: {}",
+                table, d
+            );
+        }
+
+        // the table won't change by itself, so restarting the pipeline wouldn't help
+        let evolutions =
+            resolve_drift(&POSTGRES, &table, (&self.table.schema_drift).into(), &drift)
+                .map_err(|e| NonRetryableError(e.to_string()))?;
+
+        for statement in evolutions {
+            info!("evolving Postgres table: {}", statement);
+            client.batch_execute(&statement).await?;
+        }
+
+        if !self.table.key_columns.is_empty() {
+            return Ok(self.table.key_columns.clone());
+        }
+
+        primary_key_columns(client, &self.table).await
+    }
+
+    /// Builds the statements to write with, once the table has been checked against the
+    /// fields of the sink
+    async fn prepare(&mut self, ctx: &mut ArrowContext) -> anyhow::Result<()> {
+        if self.statements.is_none() {
+            let client = self.connect(ctx).await?;
+            let key_columns = self.prepare_table(&client, &self.fields).await?;
+
+            let columns: Vec<_> = self.fields.iter().map(|f| f.name().clone()).collect();
+            let key_indices = key_columns
+                .iter()
+                .map(|k| {
+                    columns.iter().position(|c| c == k).ok_or_else(|| {
+                        NonRetryableError(format!(
+                            "key column '{}' is not a field of the Postgres sink",
+                            k
+                        ))
+                        .into()
+                    })
+                })
+                .collect::<anyhow::Result<_>>()?;
+
+            info!(
+                "writing to Postgres table {}.{} with key columns {:?}",
+                self.table.schema_name, self.table.table_name, key_columns
+            );
+
+            self.statements = Some(Statements {
+                table: qualified_name(&self.table),
+                casts: self
+                    .fields
+                    .iter()
+                    .map(sql_type)
+                    .collect::<anyhow::Result<_>>()?,
+                columns,
+                key_indices,
+            });
+        }
+
+        Ok(())
+    }
+
+    async fn write(&self) -> anyhow::Result<()> {
+        let statements = self.statements.as_ref().unwrap();
+
+        let mut client = self.pool.get().await?;
+        let transaction = client.transaction().await?;
+
+        let (upserts, deletes) = self.pending.writes();
+
+        for chunk in deletes.chunks(MAX_PARAMS / statements.key_indices.len().max(1)) {
+            let params: Vec<_> = chunk
+                .iter()
+                .flat_map(|row| row.iter().map(|v| v as &(dyn ToSql + Sync)))
+                .collect();
+            transaction
+                .execute(&statements.delete(chunk.len()), &params)
+                .await?;
+        }
+
+        for chunk in upserts.chunks(MAX_PARAMS / statements.columns.len()) {
+            let params: Vec<_> = chunk
+                .iter()
+                .flat_map(|row| row.iter().map(|v| v as &(dyn ToSql + Sync)))
+                .collect();
+            transaction
+                .execute(&statements.insert(chunk.len()), &params)
+                .await?;
+        }
+
+        transaction.commit().await?;
+        Ok(())
+    }
+
+    async fn flush(&mut self, ctx: &mut ArrowContext) -> anyhow::Result<()> {
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+
+        let mut attempts = 0;
+        while let Err(e) = self.write().await {
+            attempts += 1;
+            if attempts >= MAX_WRITE_ATTEMPTS {
+                bail!(
+                    "failed to write to Postgres after {} attempts: {}",
+                    attempts,
+                    e
+                );
+            }
+
+            ctx.report_error("Failed to write to Postgres", e.to_string())
+                .await;
+            tokio::time::sleep(Duration::from_millis((50 * (1 << attempts)).min(5_000))).await;
+        }
+
+        self.pending.clear();
+        self.last_flushed = Instant::now();
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl ArrowOperator for PostgresSinkFunc {
+    fn name(&self) -> String {
+        "PostgresSink".to_string()
+    }
+
+    fn tick_interval(&self) -> Option<Duration> {
+        Some(Duration::from_millis(
+            self.table.flush_interval_millis as u64,
+        ))
+    }
+
+    async fn on_start(&mut self, ctx: &mut ArrowContext) {
+        // the fields of the sink are checked when the pipeline is planned
+        let schema = &ctx
+            .in_schemas
+            .first()
+            .expect("no in-schema for Postgres sink!")
+            .schema;
+        let DataType::Struct(fields) = schema
+            .field_with_name("after")
+            .expect("Postgres sink input has no 'after' field")
+            .data_type()
+        else {
+            panic!("'after' field of Postgres sink input is not a struct");
+        };
+
+        self.fields = fields.iter().map(|f| f.as_ref().clone()).collect();
+    }
+
+    async fn process_batch(
+        &mut self,
+        batch: RecordBatch,
+        ctx: &mut ArrowContext,
+    ) -> anyhow::Result<()> {
+        self.prepare(ctx).await?;
+
+        let statements = self.statements.as_ref().unwrap();
+        self.pending.add_batch(
+            &batch,
+            statements.columns.len(),
+            &statements.key_indices,
+            &statements.table,
+        )?;
+
+        if self.pending.len() >= self.table.batch_size as usize {
+            self.flush(ctx).await?;
+        }
+        Ok(())
+    }
+
+    async fn handle_checkpoint(
+        &mut self,
+        _: CheckpointBarrier,
+        ctx: &mut ArrowContext,
+    ) -> anyhow::Result<()> {
+        // rows are written idempotently, so flushing before the checkpoint completes gives
+        // at-least-once delivery without needing any state
+        self.flush(ctx).await
+    }
+
+    async fn handle_tick(&mut self, _: u64, ctx: &mut ArrowContext) -> anyhow::Result<()> {
+        if self.last_flushed.elapsed()
+            >= Duration::from_millis(self.table.flush_interval_millis as u64)
+        {
+            self.flush(ctx).await?;
+        }
+        Ok(())
+    }
+
+    async fn on_close(
+        &mut self,
+        _: &Option<SignalMessage>,
+        ctx: &mut ArrowContext,
+    ) -> anyhow::Result<()> {
+        self.flush(ctx).await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn statements(keys: Vec<usize>) -> Statements {
+        Statements {
+            table: "\"public\".\"counts\"".to_string(),
+            columns: vec!["id".to_string(), "name".to_string(), "count".to_string()],
+            casts: vec!["int8", "text", "int8"],
+            key_indices: keys,
+        }
+    }
+
+    #[test]
+    fn test_statements() {
+        assert_eq!(
+            statements(vec![]).insert(2),
+            "INSERT INTO \"public\".\"counts\" (\"id\", \"name\", \"count\") VALUES \
+            ($1::int8, $2::text, $3::int8), ($4::int8, $5::text, $6::int8)"
+        );
+
+        let keyed = statements(vec![0, 1]);
+        assert_eq!(
+            keyed.insert(1),
+            "INSERT INTO \"public\".\"counts\" (\"id\", \"name\", \"count\") VALUES \
+            ($1::int8, $2::text, $3::int8) ON CONFLICT (\"id\", \"name\") \
+            DO UPDATE SET \"count\" = EXCLUDED.\"count\""
+        );
+        assert_eq!(
+            keyed.delete(2),
+            "DELETE FROM \"public\".\"counts\" WHERE (\"id\", \"name\") IN \
+            (($1::int8, $2::text), ($3::int8, $4::text))"
+        );
+    }
+}
@@ -0,0 +1,377 @@
+mod pgoutput;
+mod source;
+
+use anyhow::{anyhow, bail};
+use arroyo_operator::connector::{Connection, Connector};
+use arroyo_operator::operator::OperatorNode;
+use arroyo_rpc::api_types::connections::{
+    ConnectionProfile, ConnectionSchema, ConnectionType, FieldType, PrimitiveType, SourceField,
+    TestSourceMessage,
+};
+use arroyo_rpc::formats::{Format, JsonFormat, TimestampFormat};
+use arroyo_rpc::var_str::VarStr;
+use arroyo_rpc::OperatorConfig;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use tokio::sync::oneshot::Receiver;
+use tokio_postgres::{Client, NoTls};
+use tracing::warn;
+use typify::import_types;
+
+use crate::postgres_cdc::source::PostgresCdcSourceFunc;
+use crate::{pull_opt, source_field};
+
+// CDC sources connect to the database with the same profile as Postgres sinks
+pub use crate::postgres::PostgresConfig;
+
+const CONFIG_SCHEMA: &str = include_str!("../postgres/profile.json");
+const TABLE_SCHEMA: &str = include_str!("./table.json");
+
+import_types!(schema = "src/postgres_cdc/table.json");
+
+// type oids from pg_type.dat
+const BOOL_OID: u32 = 16;
+const INT8_OID: u32 = 20;
+const INT2_OID: u32 = 21;
+const INT4_OID: u32 = 23;
+const OID_OID: u32 = 26;
+const JSON_OID: u32 = 114;
+const FLOAT4_OID: u32 = 700;
+const FLOAT8_OID: u32 = 701;
+const TIMESTAMP_OID: u32 = 1114;
+const TIMESTAMPTZ_OID: u32 = 1184;
+const NUMERIC_OID: u32 = 1700;
+const JSONB_OID: u32 = 3802;
+
+pub struct PostgresCdcConnector {}
+
+/// Connects to the database, spawning a task to drive the connection
+async fn connect(config: &PostgresConfig) -> anyhow::Result<Client> {
+    let mut pg_config = tokio_postgres::Config::new();
+    pg_config
+        .host(&config.host)
+        .port(config.port as u16)
+        .dbname(&config.database)
+        .user(&config.user.sub_env_vars()?)
+        .application_name("arroyo");
+    if let Some(password) = &config.password {
+        pg_config.password(password.sub_env_vars()?);
+    }
+
+    let (client, connection) = pg_config.connect(NoTls).await.map_err(|e| {
+        anyhow!(
+            "failed to connect to {}:{}: {}",
+            config.host,
+            config.port,
+            e
+        )
+    })?;
+
+    tokio::spawn(async move {
+        if let Err(e) = connection.await {
+            warn!("postgres connection closed with error: {}", e);
+        }
+    });
+
+    Ok(client)
+}
+
+fn field_type(type_oid: u32) -> PrimitiveType {
+    match type_oid {
+        BOOL_OID => PrimitiveType::Bool,
+        INT2_OID | INT4_OID => PrimitiveType::Int32,
+        INT8_OID | OID_OID => PrimitiveType::Int64,
+        FLOAT4_OID => PrimitiveType::F32,
+        FLOAT8_OID | NUMERIC_OID => PrimitiveType::F64,
+        TIMESTAMP_OID | TIMESTAMPTZ_OID => PrimitiveType::DateTime,
+        JSON_OID | JSONB_OID => PrimitiveType::Json,
+        _ => PrimitiveType::String,
+    }
+}
+
+/// Reads the columns of the replicated table from the database catalog
+pub async fn infer_fields(
+    config: &PostgresConfig,
+    table: &PostgresCdcTable,
+) -> anyhow::Result<Vec<SourceField>> {
+    let client = connect(config).await?;
+
+    let rows = client
+        .query(
+            "SELECT a.attname::text, a.atttypid::int8, a.attnotnull
+            FROM pg_attribute a
+            JOIN pg_class c ON c.oid = a.attrelid
+            JOIN pg_namespace n ON n.oid = c.relnamespace
+            WHERE n.nspname = $1 AND c.relname = $2 AND a.attnum > 0 AND NOT a.attisdropped
+            ORDER BY a.attnum",
+            &[&table.schema_name, &table.table_name],
+        )
+        .await?;
+
+    if rows.is_empty() {
+        bail!(
+            "table {}.{} does not exist or has no columns",
+            table.schema_name,
+            table.table_name
+        );
+    }
+
+    Ok(rows
+        .iter()
+        .map(|row| {
+            let name: String = row.get(0);
+            let oid: i64 = row.get(1);
+            let not_null: bool = row.get(2);
+
+            let mut field = source_field(&name, FieldType::Primitive(field_type(oid as u32)));
+            field.nullable = !not_null;
+            field
+        })
+        .collect())
+}
+
+async fn test_inner(
+    config: &PostgresConfig,
+    table: Option<&PostgresCdcTable>,
+) -> anyhow::Result<String> {
+    let client = connect(config).await?;
+
+    let wal_level: String = client.query_one("SHOW wal_level", &[]).await?.get(0);
+    if wal_level != "logical" {
+        bail!(
+            "wal_level must be set to 'logical' for logical replication, but it is '{}'",
+            wal_level
+        );
+    }
+
+    let Some(table) = table else {
+        return Ok("Successfully connected to Postgres".to_string());
+    };
+
+    let published = client
+        .query_opt(
+            "SELECT 1 FROM pg_publication_tables
+            WHERE pubname = $1 AND schemaname = $2 AND tablename = $3",
+            &[&table.publication, &table.schema_name, &table.table_name],
+        )
+        .await?;
+    if published.is_none() {
+        bail!(
+            "publication '{}' does not exist or does not include {}.{}",
+            table.publication,
+            table.schema_name,
+            table.table_name
+        );
+    }
+
+    let slot = client
+        .query_opt(
+            "SELECT plugin::text FROM pg_replication_slots WHERE slot_name = $1",
+            &[&table.slot_name],
+        )
+        .await?;
+    match slot {
+        Some(row) => {
+            let plugin: String = row.get(0);
+            if plugin != "pgoutput" {
+                bail!(
+                    "replication slot '{}' uses the '{}' plugin, but only pgoutput is supported",
+                    table.slot_name,
+                    plugin
+                );
+            }
+            Ok("Successfully validated connection".to_string())
+        }
+        None => Ok(format!(
+            "Successfully validated connection; replication slot '{}' will be created when the pipeline starts",
+            table.slot_name
+        )),
+    }
+}
+
+impl Connector for PostgresCdcConnector {
+    type ProfileT = PostgresConfig;
+    type TableT = PostgresCdcTable;
+
+    fn name(&self) -> &'static str {
+        "postgres_cdc"
+    }
+
+    fn metadata(&self) -> arroyo_rpc::api_types::connections::Connector {
+        arroyo_rpc::api_types::connections::Connector {
+            id: "postgres_cdc".to_string(),
+            name: "Postgres CDC".to_string(),
+            icon: "".to_string(),
+            description: "Read changes from a Postgres table via logical replication".to_string(),
+            enabled: true,
+            source: true,
+            sink: false,
+            testing: true,
+            hidden: false,
+            custom_schemas: true,
+            connection_config: Some(CONFIG_SCHEMA.to_owned()),
+            table_config: TABLE_SCHEMA.to_owned(),
+        }
+    }
+
+    fn config_description(&self, config: Self::ProfileT) -> String {
+        format!("{}:{}/{}", config.host, config.port, config.database)
+    }
+
+    fn table_type(&self, _: Self::ProfileT, _: Self::TableT) -> ConnectionType {
+        ConnectionType::Source
+    }
+
+    fn max_source_parallelism(&self, _: Self::ProfileT, _: Self::TableT) -> Option<usize> {
+        // a replication slot can only be consumed by one reader at a time
+        Some(1)
+    }
+
+    fn get_schema(
+        &self,
+        _: Self::ProfileT,
+        _: Self::TableT,
+        s: Option<&ConnectionSchema>,
+    ) -> Option<ConnectionSchema> {
+        s.cloned()
+    }
+
+    fn test_profile(&self, profile: Self::ProfileT) -> Option<Receiver<TestSourceMessage>> {
+        let (tx, rx) = tokio::sync::oneshot::channel();
+
+        tokio::spawn(async move {
+            let message = match test_inner(&profile, None).await {
+                Ok(m) => TestSourceMessage::done(m),
+                Err(e) => TestSourceMessage::fail(format!("Failed to connect to Postgres: {}", e)),
+            };
+
+            tx.send(message).unwrap();
+        });
+
+        Some(rx)
+    }
+
+    fn test(
+        &self,
+        _: &str,
+        config: Self::ProfileT,
+        table: Self::TableT,
+        _: Option<&ConnectionSchema>,
+        tx: tokio::sync::mpsc::Sender<TestSourceMessage>,
+    ) {
+        tokio::task::spawn(async move {
+            let message = match test_inner(&config, Some(&table)).await {
+                Ok(m) => TestSourceMessage::done(m),
+                Err(e) => TestSourceMessage::fail(e.to_string()),
+            };
+
+            tx.send(message).await.unwrap();
+        });
+    }
+
+    fn from_options(
+        &self,
+        name: &str,
+        options: &mut HashMap<String, String>,
+        schema: Option<&ConnectionSchema>,
+        profile: Option<&ConnectionProfile>,
+    ) -> anyhow::Result<Connection> {
+        let config = match profile {
+            Some(p) => serde_json::from_value(p.config.clone())
+                .map_err(|e| anyhow!("invalid config for profile '{}' in database: {}", p.id, e))?,
+            None => PostgresConfig {
+                host: pull_opt("host", options)?,
+                port: options
+                    .remove("port")
+                    .map(|p| p.parse())
+                    .transpose()
+                    .map_err(|_| anyhow!("invalid value for 'port'"))?
+                    .unwrap_or(5432),
+                database: pull_opt("database", options)?,
+                user: VarStr::new(pull_opt("user", options)?),
+                password: options.remove("password").map(VarStr::new),
+                pool_size: 4,
+            },
+        };
+
+        let table = PostgresCdcTable {
+            schema_name: options
+                .remove("schema_name")
+                .unwrap_or_else(|| "public".to_string()),
+            table_name: pull_opt("table_name", options)?,
+            slot_name: pull_opt("slot_name", options)?,
+            publication: pull_opt("publication", options)?,
+        };
+
+        self.from_config(None, name, config, table, schema)
+    }
+
+    fn from_config(
+        &self,
+        id: Option<i64>,
+        name: &str,
+        config: Self::ProfileT,
+        table: Self::TableT,
+        schema: Option<&ConnectionSchema>,
+    ) -> anyhow::Result<Connection> {
+        let schema = schema
+            .map(|s| s.to_owned())
+            .ok_or_else(|| anyhow!("no schema defined for Postgres CDC connection"))?;
+
+        let format = schema
+            .format
+            .as_ref()
+            .map(|t| t.to_owned())
+            .unwrap_or_else(|| {
+                Format::Json(JsonFormat {
+                    debezium: true,
+                    timestamp_format: TimestampFormat::UnixMillis,
+                    ..Default::default()
+                })
+            });
+
+        if !matches!(format, Format::Json(JsonFormat { debezium: true, .. })) {
+            bail!("Postgres CDC sources only support the debezium_json format");
+        }
+
+        let description = format!("PostgresCDC<{}.{}>", table.schema_name, table.table_name);
+
+        let config = OperatorConfig {
+            connection: serde_json::to_value(config).unwrap(),
+            table: serde_json::to_value(table).unwrap(),
+            rate_limit: None,
+            format: Some(format),
+            bad_data: schema.bad_data.clone(),
+            framing: None,
+            metadata_fields: vec![],
+        };
+
+        Ok(Connection {
+            id,
+            connector: self.name(),
+            name: name.to_string(),
+            connection_type: ConnectionType::Source,
+            schema,
+            config: serde_json::to_string(&config).unwrap(),
+            description,
+        })
+    }
+
+    fn make_operator(
+        &self,
+        profile: Self::ProfileT,
+        table: Self::TableT,
+        config: OperatorConfig,
+    ) -> anyhow::Result<OperatorNode> {
+        Ok(OperatorNode::from_source(Box::new(PostgresCdcSourceFunc {
+            config: profile,
+            table,
+            format: config
+                .format
+                .ok_or_else(|| anyhow!("format required for Postgres CDC source"))?,
+            bad_data: config.bad_data,
+            relations: HashMap::new(),
+            state: Default::default(),
+        })))
+    }
+}
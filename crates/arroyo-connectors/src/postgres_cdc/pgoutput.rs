@@ -0,0 +1,344 @@
+//! Decoding for the messages produced by the `pgoutput` logical decoding plugin (protocol
+//! version 1), as described in
+//! https://www.postgresql.org/docs/current/protocol-logicalrep-message-formats.html
+
+use anyhow::{anyhow, bail};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Seconds between the unix epoch and the postgres epoch (2000-01-01)
+const POSTGRES_EPOCH_OFFSET_SECS: u64 = 946_684_800;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Column {
+    pub name: String,
+    pub type_oid: u32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Relation {
+    pub id: u32,
+    pub namespace: String,
+    pub name: String,
+    pub columns: Vec<Column>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TupleValue {
+    Null,
+    /// A TOASTed value that was not changed by an update, and so is not sent
+    UnchangedToast,
+    Text(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LogicalMessage {
+    Begin {
+        final_lsn: u64,
+        commit_time: i64,
+    },
+    Commit {
+        end_lsn: u64,
+    },
+    Relation(Relation),
+    Insert {
+        relation_id: u32,
+        new: Vec<TupleValue>,
+    },
+    Update {
+        relation_id: u32,
+        old: Option<Vec<TupleValue>>,
+        new: Vec<TupleValue>,
+    },
+    Delete {
+        relation_id: u32,
+        old: Vec<TupleValue>,
+    },
+    Truncate {
+        relation_ids: Vec<u32>,
+    },
+    /// Origin and type messages, which carry nothing we need
+    Other,
+}
+
+struct Reader<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> anyhow::Result<&'a [u8]> {
+        if self.buf.len() < n {
+            bail!("unexpected end of pgoutput message");
+        }
+        let (head, tail) = self.buf.split_at(n);
+        self.buf = tail;
+        Ok(head)
+    }
+
+    fn u8(&mut self) -> anyhow::Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn i16(&mut self) -> anyhow::Result<i16> {
+        Ok(i16::from_be_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> anyhow::Result<u32> {
+        Ok(u32::from_be_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> anyhow::Result<u64> {
+        Ok(u64::from_be_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn string(&mut self) -> anyhow::Result<String> {
+        let end = self
+            .buf
+            .iter()
+            .position(|b| *b == 0)
+            .ok_or_else(|| anyhow!("unterminated string in pgoutput message"))?;
+        let s = String::from_utf8(self.take(end)?.to_vec())?;
+        self.take(1)?;
+        Ok(s)
+    }
+
+    fn tuple(&mut self) -> anyhow::Result<Vec<TupleValue>> {
+        let n = self.i16()?;
+        (0..n)
+            .map(|_| match self.u8()? {
+                b'n' => Ok(TupleValue::Null),
+                b'u' => Ok(TupleValue::UnchangedToast),
+                b't' => {
+                    let len = self.u32()? as usize;
+                    Ok(TupleValue::Text(String::from_utf8(
+                        self.take(len)?.to_vec(),
+                    )?))
+                }
+                k => bail!("unsupported tuple data kind '{}'", k as char),
+            })
+            .collect()
+    }
+}
+
+pub fn parse(data: &[u8]) -> anyhow::Result<LogicalMessage> {
+    let mut r = Reader { buf: data };
+
+    let tag = r.u8()?;
+    Ok(match tag {
+        b'B' => {
+            let final_lsn = r.u64()?;
+            let commit_time = r.u64()? as i64;
+            LogicalMessage::Begin {
+                final_lsn,
+                commit_time,
+            }
+        }
+        b'C' => {
+            let _flags = r.u8()?;
+            let _commit_lsn = r.u64()?;
+            LogicalMessage::Commit { end_lsn: r.u64()? }
+        }
+        b'R' => {
+            let id = r.u32()?;
+            let namespace = r.string()?;
+            let name = r.string()?;
+            let _replica_identity = r.u8()?;
+            let n = r.i16()?;
+            let columns = (0..n)
+                .map(|_| {
+                    let _flags = r.u8()?;
+                    let name = r.string()?;
+                    let type_oid = r.u32()?;
+                    let _type_modifier = r.u32()?;
+                    Ok(Column { name, type_oid })
+                })
+                .collect::<anyhow::Result<_>>()?;
+            // pgoutput sends an empty namespace for pg_catalog
+            let namespace = if namespace.is_empty() {
+                "pg_catalog".to_string()
+            } else {
+                namespace
+            };
+            LogicalMessage::Relation(Relation {
+                id,
+                namespace,
+                name,
+                columns,
+            })
+        }
+        b'I' => {
+            let relation_id = r.u32()?;
+            match r.u8()? {
+                b'N' => LogicalMessage::Insert {
+                    relation_id,
+                    new: r.tuple()?,
+                },
+                k => bail!("unexpected tuple type '{}' in insert", k as char),
+            }
+        }
+        b'U' => {
+            let relation_id = r.u32()?;
+            let (old, new) = match r.u8()? {
+                b'K' | b'O' => {
+                    let old = r.tuple()?;
+                    match r.u8()? {
+                        b'N' => (Some(old), r.tuple()?),
+                        k => bail!("unexpected tuple type '{}' in update", k as char),
+                    }
+                }
+                b'N' => (None, r.tuple()?),
+                k => bail!("unexpected tuple type '{}' in update", k as char),
+            };
+            LogicalMessage::Update {
+                relation_id,
+                old,
+                new,
+            }
+        }
+        b'D' => {
+            let relation_id = r.u32()?;
+            match r.u8()? {
+                b'K' | b'O' => LogicalMessage::Delete {
+                    relation_id,
+                    old: r.tuple()?,
+                },
+                k => bail!("unexpected tuple type '{}' in delete", k as char),
+            }
+        }
+        b'T' => {
+            let n = r.u32()?;
+            let _options = r.u8()?;
+            LogicalMessage::Truncate {
+                relation_ids: (0..n).map(|_| r.u32()).collect::<anyhow::Result<_>>()?,
+            }
+        }
+        b'O' | b'Y' => LogicalMessage::Other,
+        t => bail!("unknown pgoutput message type '{}'", t as char),
+    })
+}
+
+/// Converts a timestamp in microseconds since the postgres epoch to a `SystemTime`
+pub fn to_system_time(postgres_micros: i64) -> SystemTime {
+    let unix_micros = postgres_micros + (POSTGRES_EPOCH_OFFSET_SECS * 1_000_000) as i64;
+    UNIX_EPOCH + Duration::from_micros(unix_micros.max(0) as u64)
+}
+
+/// Formats an LSN in the `XXX/XXX` form that postgres uses for `pg_lsn` values
+pub fn format_lsn(lsn: u64) -> String {
+    format!("{:X}/{:X}", lsn >> 32, lsn & 0xFFFF_FFFF)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn tuple(values: &[Option<&str>]) -> Vec<u8> {
+        let mut buf = (values.len() as i16).to_be_bytes().to_vec();
+        for v in values {
+            match v {
+                Some(v) => {
+                    buf.push(b't');
+                    buf.extend_from_slice(&(v.len() as u32).to_be_bytes());
+                    buf.extend_from_slice(v.as_bytes());
+                }
+                None => buf.push(b'n'),
+            }
+        }
+        buf
+    }
+
+    #[test]
+    fn test_parse_relation() {
+        let mut msg = vec![b'R'];
+        msg.extend_from_slice(&16384u32.to_be_bytes());
+        msg.extend_from_slice(b"public\0orders\0");
+        msg.push(b'd');
+        msg.extend_from_slice(&2i16.to_be_bytes());
+        msg.push(1);
+        msg.extend_from_slice(b"id\0");
+        msg.extend_from_slice(&23u32.to_be_bytes());
+        msg.extend_from_slice(&(-1i32).to_be_bytes());
+        msg.push(0);
+        msg.extend_from_slice(b"note\0");
+        msg.extend_from_slice(&25u32.to_be_bytes());
+        msg.extend_from_slice(&(-1i32).to_be_bytes());
+
+        assert_eq!(
+            parse(&msg).unwrap(),
+            LogicalMessage::Relation(Relation {
+                id: 16384,
+                namespace: "public".to_string(),
+                name: "orders".to_string(),
+                columns: vec![
+                    Column {
+                        name: "id".to_string(),
+                        type_oid: 23,
+                    },
+                    Column {
+                        name: "note".to_string(),
+                        type_oid: 25,
+                    },
+                ],
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_changes() {
+        let mut insert = vec![b'I'];
+        insert.extend_from_slice(&16384u32.to_be_bytes());
+        insert.push(b'N');
+        insert.extend_from_slice(&tuple(&[Some("1"), None]));
+
+        assert_eq!(
+            parse(&insert).unwrap(),
+            LogicalMessage::Insert {
+                relation_id: 16384,
+                new: vec![TupleValue::Text("1".to_string()), TupleValue::Null],
+            }
+        );
+
+        let mut update = vec![b'U'];
+        update.extend_from_slice(&16384u32.to_be_bytes());
+        update.push(b'K');
+        update.extend_from_slice(&tuple(&[Some("1"), None]));
+        update.push(b'N');
+        update.extend_from_slice(&tuple(&[Some("2"), Some("hi")]));
+
+        assert_eq!(
+            parse(&update).unwrap(),
+            LogicalMessage::Update {
+                relation_id: 16384,
+                old: Some(vec![TupleValue::Text("1".to_string()), TupleValue::Null]),
+                new: vec![
+                    TupleValue::Text("2".to_string()),
+                    TupleValue::Text("hi".to_string())
+                ],
+            }
+        );
+
+        let mut delete = vec![b'D'];
+        delete.extend_from_slice(&16384u32.to_be_bytes());
+        delete.push(b'K');
+        delete.extend_from_slice(&tuple(&[Some("2"), None]));
+
+        assert_eq!(
+            parse(&delete).unwrap(),
+            LogicalMessage::Delete {
+                relation_id: 16384,
+                old: vec![TupleValue::Text("2".to_string()), TupleValue::Null],
+            }
+        );
+
+        // truncated message
+        assert!(parse(&delete[..delete.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn test_lsn_and_time() {
+        assert_eq!(format_lsn(0x16_B374_D848), "16/B374D848");
+        assert_eq!(
+            to_system_time(0),
+            UNIX_EPOCH + Duration::from_secs(POSTGRES_EPOCH_OFFSET_SECS)
+        );
+    }
+}
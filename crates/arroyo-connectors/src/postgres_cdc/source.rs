@@ -0,0 +1,506 @@
+use async_trait::async_trait;
+use bincode::{Decode, Encode};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use serde_json::{json, Map, Number, Value};
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime};
+use tokio::select;
+use tokio::time::MissedTickBehavior;
+use tokio_postgres::Client;
+use tracing::{debug, info, warn};
+
+use arroyo_operator::context::ArrowContext;
+use arroyo_operator::operator::SourceOperator;
+use arroyo_operator::SourceFinishType;
+use arroyo_rpc::formats::{BadData, Format, JsonFormat, TimestampFormat};
+use arroyo_rpc::grpc::rpc::{StopMode, TableConfig};
+use arroyo_rpc::ControlMessage;
+use arroyo_state::tables::global_keyed_map::GlobalKeyedView;
+use arroyo_types::UserError;
+
+use crate::postgres_cdc::pgoutput::{self, LogicalMessage, Relation, TupleValue};
+use crate::postgres_cdc::{
+    connect, PostgresCdcTable, PostgresConfig, BOOL_OID, FLOAT4_OID, FLOAT8_OID, INT2_OID,
+    INT4_OID, INT8_OID, NUMERIC_OID, OID_OID, TIMESTAMPTZ_OID, TIMESTAMP_OID,
+};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Number of changes to request from the cursor slot per poll
+const POLL_CHANGES: i32 = 8192;
+
+/// Reads changes to a table from a logical replication slot using the pgoutput plugin, and
+/// emits them as debezium-style changelog rows.
+///
+/// The slot is only advanced once a checkpoint that covers the changes has completed, so that
+/// changes are not lost if the pipeline is restored from an earlier checkpoint. Changes are
+/// consumed from a temporary copy of the slot, which acts as a cursor, so that each change is
+/// decoded once rather than re-read from the slot's confirmed position on every poll.
+pub struct PostgresCdcSourceFunc {
+    pub config: PostgresConfig,
+    pub table: PostgresCdcTable,
+    pub format: Format,
+    pub bad_data: Option<BadData>,
+    pub relations: HashMap<u32, Relation>,
+    pub state: PostgresCdcState,
+}
+
+#[derive(Clone, Debug, Encode, Decode, PartialEq, PartialOrd, Default)]
+pub struct PostgresCdcState {
+    /// end LSN of the commit of the last transaction that was emitted
+    lsn: u64,
+}
+
+#[async_trait]
+impl SourceOperator for PostgresCdcSourceFunc {
+    fn name(&self) -> String {
+        "PostgresCdcSource".to_string()
+    }
+
+    fn tables(&self) -> HashMap<String, TableConfig> {
+        arroyo_state::global_table_config("p", "postgres cdc source state")
+    }
+
+    async fn on_start(&mut self, ctx: &mut ArrowContext) {
+        let s: &mut GlobalKeyedView<String, PostgresCdcState> = ctx
+            .table_manager
+            .get_global_keyed_state("p")
+            .await
+            .expect("should be able to read postgres cdc state");
+
+        if let Some(state) = s.get(&self.table.slot_name) {
+            self.state = state.clone();
+        }
+    }
+
+    async fn run(&mut self, ctx: &mut ArrowContext) -> SourceFinishType {
+        match self.run_int(ctx).await {
+            Ok(r) => r,
+            Err(e) => {
+                ctx.report_error(e.name.clone(), e.details.clone()).await;
+
+                panic!("{}: {}", e.name, e.details);
+            }
+        }
+    }
+}
+
+impl PostgresCdcSourceFunc {
+    async fn run_int(&mut self, ctx: &mut ArrowContext) -> Result<SourceFinishType, UserError> {
+        ctx.initialize_deserializer(self.format.clone(), None, self.bad_data.clone());
+
+        let client = self
+            .connect()
+            .await
+            .map_err(|e| UserError::new("failed to set up replication slot", format!("{:#}", e)))?;
+
+        // the restored state comes from a completed checkpoint, so everything before it can be
+        // released from the slot
+        let mut confirmed_lsn = self.state.lsn;
+        self.advance_slot(&client, confirmed_lsn).await?;
+        self.create_cursor(&client).await?;
+
+        let mut timer = tokio::time::interval(POLL_INTERVAL);
+        timer.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        loop {
+            select! {
+                _ = timer.tick() => {
+                    let rows = self.poll(ctx, &client).await?;
+
+                    if ctx.should_flush() {
+                        ctx.flush_buffer().await?;
+                    }
+
+                    if rows >= POLL_CHANGES as usize {
+                        // there are more changes waiting in the slot
+                        timer.reset_immediately();
+                    }
+                }
+                control_message = ctx.control_rx.recv() => {
+                    let Some(msg) = control_message else {
+                        continue;
+                    };
+
+                    match msg {
+                        ControlMessage::Checkpoint(c) => {
+                            // checkpoints are taken one at a time, so the previous one has
+                            // completed and the changes it covers won't be needed again
+                            self.advance_slot(&client, confirmed_lsn).await?;
+                            confirmed_lsn = self.state.lsn;
+
+                            let s = ctx
+                                .table_manager
+                                .get_global_keyed_state("p")
+                                .await
+                                .expect("should be able to get postgres cdc state");
+                            s.insert(self.table.slot_name.clone(), self.state.clone()).await;
+
+                            if self.start_checkpoint(c, ctx).await {
+                                return Ok(SourceFinishType::Immediate);
+                            }
+                        }
+                        ControlMessage::Stop { mode } => {
+                            info!("Stopping postgres cdc source: {:?}", mode);
+
+                            match mode {
+                                StopMode::Graceful => {
+                                    return Ok(SourceFinishType::Graceful);
+                                }
+                                StopMode::Immediate => {
+                                    return Ok(SourceFinishType::Immediate);
+                                }
+                            }
+                        }
+                        ControlMessage::Commit { .. } => {
+                            unreachable!("sources shouldn't receive commit messages");
+                        }
+                        ControlMessage::LoadCompacted { compacted } => {
+                            ctx.load_compacted(compacted).await;
+                        }
+                        ControlMessage::QueryState { query } => {
+                            ctx.query_state(query).await;
+                        }
+                        ControlMessage::NoOp => {}
+                    }
+                }
+            }
+        }
+    }
+
+    /// Connects to the database and creates the replication slot if it doesn't exist yet
+    async fn connect(&self) -> anyhow::Result<Client> {
+        let client = connect(&self.config).await?;
+
+        // pgoutput formats values using the session time zone
+        client.batch_execute("SET TIME ZONE 'UTC'").await?;
+
+        let exists = client
+            .query_opt(
+                "SELECT 1 FROM pg_replication_slots WHERE slot_name = $1",
+                &[&self.table.slot_name],
+            )
+            .await?
+            .is_some();
+
+        if !exists {
+            info!(
+                "creating logical replication slot '{}'",
+                self.table.slot_name
+            );
+            client
+                .execute(
+                    "SELECT pg_create_logical_replication_slot($1, 'pgoutput')",
+                    &[&self.table.slot_name],
+                )
+                .await?;
+        }
+
+        Ok(client)
+    }
+
+    /// Confirms all changes before `lsn` to the slot, which allows postgres to discard them
+    async fn advance_slot(&self, client: &Client, lsn: u64) -> Result<(), UserError> {
+        if lsn == 0 {
+            return Ok(());
+        }
+
+        let lsn = pgoutput::format_lsn(lsn);
+        debug!("advancing slot '{}' to {}", self.table.slot_name, lsn);
+
+        // postgres refuses to move a slot backwards
+        client
+            .execute(
+                "SELECT pg_replication_slot_advance(slot_name, $2::text::pg_lsn)
+                FROM pg_replication_slots
+                WHERE slot_name = $1 AND confirmed_flush_lsn < $2::text::pg_lsn",
+                &[&self.table.slot_name, &lsn],
+            )
+            .await
+            .map_err(|e| {
+                UserError::new(
+                    "failed to advance replication slot",
+                    format!("slot '{}': {}", self.table.slot_name, e),
+                )
+            })?;
+
+        Ok(())
+    }
+
+    /// Name of the temporary slot that changes are consumed from
+    fn cursor_slot_name(&self) -> String {
+        // slot names are limited to 63 characters
+        let mut name: String = self.table.slot_name.chars().take(56).collect();
+        name.push_str("_cursor");
+        name
+    }
+
+    /// Copies the slot to a temporary slot, which is dropped when the connection closes. It
+    /// starts at the slot's confirmed position and is consumed as changes are read, so it
+    /// tracks our position without moving the slot itself.
+    async fn create_cursor(&self, client: &Client) -> Result<(), UserError> {
+        client
+            .execute(
+                "SELECT pg_copy_logical_replication_slot($1, $2, true)",
+                &[&self.table.slot_name, &self.cursor_slot_name()],
+            )
+            .await
+            .map_err(|e| {
+                UserError::new(
+                    "failed to create cursor for replication slot",
+                    format!(
+                        "slot '{}' (Postgres 12 or later is required): {}",
+                        self.table.slot_name, e
+                    ),
+                )
+            })?;
+
+        Ok(())
+    }
+
+    /// Consumes up to [`POLL_CHANGES`] changes from the cursor slot, emitting those that
+    /// haven't already been emitted. Returns the number of rows read.
+    async fn poll(&mut self, ctx: &mut ArrowContext, client: &Client) -> Result<usize, UserError> {
+        let rows = client
+            .query(
+                "SELECT data FROM pg_logical_slot_get_binary_changes(
+                    $1, NULL, $2, 'proto_version', '1', 'publication_names', $3)",
+                &[
+                    &self.cursor_slot_name(),
+                    &POLL_CHANGES,
+                    &self.table.publication,
+                ],
+            )
+            .await
+            .map_err(|e| {
+                UserError::new(
+                    "failed to read from replication slot",
+                    format!("slot '{}': {}", self.table.slot_name, e),
+                )
+            })?;
+
+        let timestamp_format = match &self.format {
+            Format::Json(JsonFormat {
+                timestamp_format, ..
+            }) => timestamp_format.clone(),
+            _ => TimestampFormat::UnixMillis,
+        };
+
+        let mut skipping = true;
+        let mut commit_time = SystemTime::now();
+
+        for row in &rows {
+            let data: Vec<u8> = row.get(0);
+            let message = pgoutput::parse(&data).map_err(|e| {
+                UserError::new("invalid message from replication slot", e.to_string())
+            })?;
+
+            let change = match message {
+                LogicalMessage::Begin {
+                    final_lsn,
+                    commit_time: t,
+                } => {
+                    // transactions before our position were emitted before the checkpoint we
+                    // restored from, but may not have been confirmed to the slot yet
+                    skipping = final_lsn < self.state.lsn;
+                    commit_time = pgoutput::to_system_time(t);
+                    continue;
+                }
+                LogicalMessage::Commit { end_lsn, .. } => {
+                    if !skipping {
+                        self.state.lsn = end_lsn;
+                    }
+                    continue;
+                }
+                LogicalMessage::Relation(relation) => {
+                    self.relations.insert(relation.id, relation);
+                    continue;
+                }
+                LogicalMessage::Truncate { relation_ids } => {
+                    if !skipping && relation_ids.iter().any(|id| self.is_source_table(*id)) {
+                        warn!(
+                            "table {}.{} was truncated; truncations are not reflected in the changelog",
+                            self.table.schema_name, self.table.table_name
+                        );
+                    }
+                    continue;
+                }
+                LogicalMessage::Other => continue,
+                LogicalMessage::Insert { relation_id, new } => (relation_id, None, Some(new), "c"),
+                LogicalMessage::Update {
+                    relation_id,
+                    old,
+                    new,
+                } => (relation_id, old, Some(new), "u"),
+                LogicalMessage::Delete { relation_id, old } => (relation_id, Some(old), None, "d"),
+            };
+
+            let (relation_id, before, after, op) = change;
+            if skipping || !self.is_source_table(relation_id) {
+                continue;
+            }
+
+            let relation = self.relations.get(&relation_id).unwrap();
+            let before_json = before
+                .as_ref()
+                .map(|t| tuple_to_json(relation, t, None, &timestamp_format));
+            let after_json = after
+                .as_ref()
+                .map(|t| tuple_to_json(relation, t, before.as_deref(), &timestamp_format));
+
+            let message = json!({
+                "before": before_json,
+                "after": after_json,
+                "op": op,
+            });
+
+            ctx.deserialize_slice(message.to_string().as_bytes(), commit_time, None)
+                .await?;
+        }
+
+        Ok(rows.len())
+    }
+
+    fn is_source_table(&self, relation_id: u32) -> bool {
+        self.relations.get(&relation_id).is_some_and(|r| {
+            r.namespace == self.table.schema_name && r.name == self.table.table_name
+        })
+    }
+}
+
+/// Converts a tuple to a JSON object keyed by column name. TOASTed values that weren't
+/// changed by an update are taken from `old` if it has them, and otherwise left out.
+fn tuple_to_json(
+    relation: &Relation,
+    values: &[TupleValue],
+    old: Option<&[TupleValue]>,
+    timestamp_format: &TimestampFormat,
+) -> Value {
+    let mut map = Map::new();
+    for (i, (column, value)) in relation.columns.iter().zip(values).enumerate() {
+        let value = match value {
+            TupleValue::UnchangedToast => match old.and_then(|o| o.get(i)) {
+                Some(v @ TupleValue::Text(_)) => v,
+                _ => continue,
+            },
+            v => v,
+        };
+
+        let json = match value {
+            TupleValue::Text(s) => text_to_json(column.type_oid, s, timestamp_format),
+            _ => Value::Null,
+        };
+        map.insert(column.name.clone(), json);
+    }
+    Value::Object(map)
+}
+
+/// Converts the text representation of a postgres value to the JSON representation that the
+/// debezium format expects for its type
+fn text_to_json(type_oid: u32, s: &str, timestamp_format: &TimestampFormat) -> Value {
+    match type_oid {
+        BOOL_OID => Value::Bool(s == "t"),
+        INT2_OID | INT4_OID | INT8_OID | OID_OID => s
+            .parse::<i64>()
+            .map(Value::from)
+            .unwrap_or_else(|_| Value::String(s.to_string())),
+        // NaN and infinite values can't be represented in JSON
+        FLOAT4_OID | FLOAT8_OID | NUMERIC_OID => s
+            .parse::<f64>()
+            .ok()
+            .and_then(Number::from_f64)
+            .map(Value::Number)
+            .unwrap_or(Value::Null),
+        TIMESTAMP_OID | TIMESTAMPTZ_OID => {
+            let parsed = if type_oid == TIMESTAMPTZ_OID {
+                DateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S%.f%#z")
+                    .map(|t| t.with_timezone(&Utc))
+            } else {
+                NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S%.f").map(|t| t.and_utc())
+            };
+
+            // infinite timestamps don't parse, and become nulls
+            match (parsed, timestamp_format) {
+                (Ok(t), TimestampFormat::UnixMillis) => Value::from(t.timestamp_millis()),
+                (Ok(t), TimestampFormat::RFC3339) => Value::String(t.to_rfc3339()),
+                (Err(_), _) => Value::Null,
+            }
+        }
+        _ => Value::String(s.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::postgres_cdc::pgoutput::Column;
+
+    #[test]
+    fn test_tuple_to_json() {
+        let relation = Relation {
+            id: 1,
+            namespace: "public".to_string(),
+            name: "orders".to_string(),
+            columns: [
+                ("id", INT8_OID),
+                ("paid", BOOL_OID),
+                ("amount", NUMERIC_OID),
+                ("created_at", TIMESTAMPTZ_OID),
+                ("note", 25),
+            ]
+            .into_iter()
+            .map(|(name, type_oid)| Column {
+                name: name.to_string(),
+                type_oid,
+            })
+            .collect(),
+        };
+
+        let old = vec![
+            TupleValue::Text("7".to_string()),
+            TupleValue::Text("f".to_string()),
+            TupleValue::Text("NaN".to_string()),
+            TupleValue::Null,
+            TupleValue::Text("a long note".to_string()),
+        ];
+        let new = vec![
+            TupleValue::Text("7".to_string()),
+            TupleValue::Text("t".to_string()),
+            TupleValue::Text("12.50".to_string()),
+            TupleValue::Text("2024-03-01 12:30:00.25+00".to_string()),
+            TupleValue::UnchangedToast,
+        ];
+
+        assert_eq!(
+            tuple_to_json(&relation, &new, Some(&old), &TimestampFormat::UnixMillis),
+            json!({
+                "id": 7,
+                "paid": true,
+                "amount": 12.5,
+                "created_at": 1709296200250i64,
+                "note": "a long note",
+            })
+        );
+
+        assert_eq!(
+            tuple_to_json(&relation, &new, None, &TimestampFormat::RFC3339),
+            json!({
+                "id": 7,
+                "paid": true,
+                "amount": 12.5,
+                "created_at": "2024-03-01T12:30:00.250+00:00",
+            })
+        );
+
+        assert_eq!(
+            tuple_to_json(&relation, &old, None, &TimestampFormat::UnixMillis),
+            json!({
+                "id": 7,
+                "paid": false,
+                "amount": null,
+                "created_at": null,
+                "note": "a long note",
+            })
+        );
+    }
+}
@@ -16,6 +16,28 @@ use tonic::transport::Channel;
 pub struct PreviewSink {
     client: Option<ControllerGrpcClient<Channel>>,
     row: usize,
+    /// set once the sink has output the maximum number of rows for a preview
+    limit_reached: bool,
+}
+
+impl PreviewSink {
+    async fn send_done(&mut self, ctx: &mut ArrowContext) {
+        self.client
+            .as_mut()
+            .unwrap()
+            .send_sink_data(SinkDataReq {
+                job_id: ctx.task_info.job_id.clone(),
+                operator_id: ctx.task_info.operator_id.clone(),
+                subtask_index: ctx.task_info.task_index as u32,
+                timestamps: vec![],
+                batch: "[]".to_string(),
+                start_id: self.row as u64,
+                done: true,
+                limit_reached: self.limit_reached,
+            })
+            .await
+            .unwrap();
+    }
 }
 
 #[async_trait::async_trait]
@@ -43,7 +65,21 @@ impl ArrowOperator for PreviewSink {
         );
     }
 
-    async fn process_batch(&mut self, mut batch: RecordBatch, ctx: &mut ArrowContext) {
+    async fn process_batch(
+        &mut self,
+        batch: RecordBatch,
+        ctx: &mut ArrowContext,
+    ) -> anyhow::Result<()> {
+        if self.limit_reached {
+            // the preview is being stopped; drop anything that arrives in the meantime
+            return Ok(());
+        }
+
+        let max_rows = config().pipeline.preview.max_rows as usize;
+        let remaining = max_rows.saturating_sub(self.row);
+        self.limit_reached = batch.num_rows() >= remaining;
+        let mut batch = batch.slice(0, batch.num_rows().min(remaining));
+
         let ts = ctx.in_schemas[0].timestamp_index;
         let timestamps: Vec<_> = batch
             .column(ts)
@@ -78,14 +114,25 @@ impl ArrowOperator for PreviewSink {
                 batch: String::from_utf8(buf).unwrap_or_else(|_| String::new()),
                 start_id: self.row as u64,
                 done: false,
+                limit_reached: false,
             })
             .await
             .unwrap();
 
         self.row += batch.num_rows();
+
+        if self.limit_reached {
+            // the controller stops the preview once it hears that we're done
+            self.send_done(ctx).await;
+        }
+        Ok(())
     }
 
-    async fn handle_checkpoint(&mut self, _: CheckpointBarrier, ctx: &mut ArrowContext) {
+    async fn handle_checkpoint(
+        &mut self,
+        _: CheckpointBarrier,
+        ctx: &mut ArrowContext,
+    ) -> anyhow::Result<()> {
         let table = ctx
             .table_manager
             .get_global_keyed_state::<usize, usize>("s")
@@ -93,22 +140,18 @@ impl ArrowOperator for PreviewSink {
             .unwrap();
 
         table.insert(ctx.task_info.task_index, self.row).await;
+        Ok(())
     }
 
-    async fn on_close(&mut self, _: &Option<SignalMessage>, ctx: &mut ArrowContext) {
-        self.client
-            .as_mut()
-            .unwrap()
-            .send_sink_data(SinkDataReq {
-                job_id: ctx.task_info.job_id.clone(),
-                operator_id: ctx.task_info.operator_id.clone(),
-                subtask_index: ctx.task_info.task_index as u32,
-                timestamps: vec![],
-                batch: "[]".to_string(),
-                start_id: self.row as u64,
-                done: true,
-            })
-            .await
-            .unwrap();
+    async fn on_close(
+        &mut self,
+        _: &Option<SignalMessage>,
+        ctx: &mut ArrowContext,
+    ) -> anyhow::Result<()> {
+        if !self.limit_reached {
+            self.send_done(ctx).await;
+        }
+
+        Ok(())
     }
 }
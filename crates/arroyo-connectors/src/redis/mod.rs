@@ -288,6 +288,32 @@ impl Connector for RedisConnector {
             Ok(column)
         }
 
+        fn validate_score_column(
+            schema: &ConnectionSchema,
+            column: String,
+            sql: &str,
+        ) -> anyhow::Result<String> {
+            if !schema.fields.iter().any(|f| {
+                f.field_name == column
+                    && matches!(
+                        f.field_type.r#type,
+                        FieldType::Primitive(
+                            PrimitiveType::Int32
+                                | PrimitiveType::Int64
+                                | PrimitiveType::UInt32
+                                | PrimitiveType::UInt64
+                                | PrimitiveType::F32
+                                | PrimitiveType::F64
+                        )
+                    )
+                    && !f.nullable
+            }) {
+                bail!("invalid value '{}' for {}, must be the name of a non-nullable numeric column on the table", column, sql);
+            };
+
+            Ok(column)
+        }
+
         let sink = match typ.as_str() {
             "sink" => TableType::Target(match pull_opt("target", options)?.as_str() {
                 "string" => Target::StringTable {
@@ -331,6 +357,43 @@ impl Connector for RedisConnector {
                         .transpose()?,
                     hash_key_prefix: pull_opt("target.key_prefix", options)?,
                 },
+                "sorted_set" => Target::SortedSetTable {
+                    sorted_set_key_prefix: pull_opt("target.key_prefix", options)?,
+                    sorted_set_key_column: options
+                        .remove("target.key_column")
+                        .map(|name| validate_column(schema, name, "target.key_column"))
+                        .transpose()?,
+                    score_column: validate_score_column(
+                        schema,
+                        pull_opt("target.score_column", options)?,
+                        "target.score_column",
+                    )?,
+                    max_length: pull_option_to_u64("target.max_length", options)?
+                        .map(|t| t.try_into())
+                        .transpose()
+                        .map_err(|_| anyhow!("target.max_length must be greater than 0"))?,
+                },
+                "stream" => Target::StreamTable {
+                    stream_key_prefix: pull_opt("target.key_prefix", options)?,
+                    stream_key_column: options
+                        .remove("target.key_column")
+                        .map(|name| validate_column(schema, name, "target.key_column"))
+                        .transpose()?,
+                    stream_field: options
+                        .remove("target.field")
+                        .unwrap_or_else(|| "value".to_string()),
+                    stream_max_length: pull_option_to_u64("target.max_length", options)?
+                        .map(|t| t.try_into())
+                        .transpose()
+                        .map_err(|_| anyhow!("target.max_length must be greater than 0"))?,
+                },
+                "pubsub" => Target::PubSubTable {
+                    channel_prefix: pull_opt("target.channel_prefix", options)?,
+                    channel_column: options
+                        .remove("target.channel_column")
+                        .map(|name| validate_column(schema, name, "target.channel_column"))
+                        .transpose()?,
+                },
                 s => {
                     bail!("'{}' is not a valid redis target", s);
                 }
@@ -414,6 +477,7 @@ impl Connector for RedisConnector {
             rx,
             key_index: None,
             hash_index: None,
+            score_index: None,
         })))
     }
 }
@@ -1,5 +1,7 @@
 use crate::redis::{ListOperation, RedisClient, RedisTable, TableType, Target};
 use arrow::array::{AsArray, RecordBatch};
+use arrow::compute::cast;
+use arrow::datatypes::{DataType, Float64Type};
 use arroyo_formats::ser::ArrowSerializer;
 use arroyo_operator::context::{ArrowContext, ErrorReporter};
 use arroyo_operator::operator::ArrowOperator;
@@ -7,6 +9,7 @@ use arroyo_types::CheckpointBarrier;
 use async_trait::async_trait;
 use redis::aio::{ConnectionLike, ConnectionManager};
 use redis::cluster_async::ClusterConnection;
+use redis::streams::StreamMaxlen;
 use redis::{Cmd, Pipeline, RedisFuture};
 use std::collections::HashSet;
 use std::time::{Duration, Instant};
@@ -28,6 +31,7 @@ pub struct RedisSinkFunc {
 
     pub key_index: Option<usize>,
     pub hash_index: Option<usize>,
+    pub score_index: Option<usize>,
 }
 
 impl RedisSinkFunc {
@@ -42,11 +46,14 @@ impl RedisSinkFunc {
     }
 }
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Clone, Debug)]
 enum RedisBehavior {
     Set { ttl: Option<usize> },
     Push { append: bool, max: Option<usize> },
     Hash,
+    SortedSet { max: Option<usize> },
+    Stream { field: String, max: Option<usize> },
+    Publish,
 }
 
 pub enum RedisCmd {
@@ -61,6 +68,12 @@ pub enum RedisCmd {
         value: Vec<u8>,
     },
 
+    ZData {
+        key: String,
+        score: f64,
+        value: Vec<u8>,
+    },
+
     Flush(u32),
 }
 
@@ -100,7 +113,7 @@ impl ConnectionLike for GeneralConnection {
 struct RedisWriter {
     rx: Receiver<RedisCmd>,
     tx: Sender<u32>,
-    max_push_keys: HashSet<String>,
+    trim_keys: HashSet<String>,
     behavior: RedisBehavior,
     connection: GeneralConnection,
     pipeline: Pipeline,
@@ -132,27 +145,42 @@ impl RedisWriter {
                             Some(RedisCmd::Data { key, value }) => {
                                 self.size_estimate += key.len() + value.len();
 
-                                match self.behavior {
-                                    RedisBehavior::Set {ttl } => {
+                                match &self.behavior {
+                                    RedisBehavior::Set { ttl } => {
                                         // TODO: resolve duplicates before sending
                                         if let Some(ttl) = ttl {
-                                            self.pipeline.set_ex(key, value, ttl as u64);
+                                            self.pipeline.set_ex(key, value, *ttl as u64);
                                         } else {
                                             self.pipeline.set(key, value);
                                         }
                                     }
                                     RedisBehavior::Push { append, max } => {
-                                        if max.is_some() && !self.max_push_keys.contains(&key) {
-                                            self.max_push_keys.insert(key.clone());
+                                        if max.is_some() && !self.trim_keys.contains(&key) {
+                                            self.trim_keys.insert(key.clone());
                                         }
 
-                                        if append {
+                                        if *append {
                                             self.pipeline.rpush(key, value);
                                         } else {
                                             self.pipeline.lpush(key, value);
                                         }
                                     }
-                                    RedisBehavior::Hash => {
+                                    RedisBehavior::Stream { field, max } => {
+                                        if let Some(max) = max {
+                                            self.pipeline.xadd_maxlen(
+                                                key,
+                                                StreamMaxlen::Approx(*max),
+                                                "*",
+                                                &[(field, value)],
+                                            );
+                                        } else {
+                                            self.pipeline.xadd(key, "*", &[(field, value)]);
+                                        }
+                                    }
+                                    RedisBehavior::Publish => {
+                                        self.pipeline.publish(key, value);
+                                    }
+                                    RedisBehavior::Hash | RedisBehavior::SortedSet { .. } => {
                                         unreachable!();
                                     }
                                 }
@@ -162,6 +190,16 @@ impl RedisWriter {
 
                                 self.pipeline.hset(key, field, value);
                             }
+                            Some(RedisCmd::ZData { key, score, value }) => {
+                                self.size_estimate += key.len() + 8 + value.len();
+
+                                if matches!(self.behavior, RedisBehavior::SortedSet { max: Some(_) })
+                                    && !self.trim_keys.contains(&key) {
+                                    self.trim_keys.insert(key.clone());
+                                }
+
+                                self.pipeline.zadd(key, value, score);
+                            }
                             Some(RedisCmd::Flush(i)) => {
                                 self.flush().await;
                                 if self.tx.send(i).await.is_err() {
@@ -182,18 +220,26 @@ impl RedisWriter {
     async fn flush(&mut self) {
         let mut attempts = 0;
 
-        if let RedisBehavior::Push {
-            max: Some(max),
-            append,
-        } = self.behavior
-        {
-            for k in self.max_push_keys.drain() {
-                if append {
-                    self.pipeline.ltrim(k, -(max as isize), -1);
-                } else {
-                    self.pipeline.ltrim(k, 0, max as isize - 1);
+        match self.behavior {
+            RedisBehavior::Push {
+                max: Some(max),
+                append,
+            } => {
+                for k in self.trim_keys.drain() {
+                    if append {
+                        self.pipeline.ltrim(k, -(max as isize), -1);
+                    } else {
+                        self.pipeline.ltrim(k, 0, max as isize - 1);
+                    }
+                }
+            }
+            RedisBehavior::SortedSet { max: Some(max) } => {
+                // keep the members with the highest scores
+                for k in self.trim_keys.drain() {
+                    self.pipeline.zremrangebyrank(k, 0, -(max as isize) - 1);
                 }
             }
+            _ => {}
         }
 
         while attempts < 20 {
@@ -241,6 +287,18 @@ impl ArrowOperator for RedisSinkFunc {
             | TableType::Target(Target::HashTable {
                 hash_key_column: Some(key),
                 ..
+            })
+            | TableType::Target(Target::SortedSetTable {
+                sorted_set_key_column: Some(key),
+                ..
+            })
+            | TableType::Target(Target::StreamTable {
+                stream_key_column: Some(key),
+                ..
+            })
+            | TableType::Target(Target::PubSubTable {
+                channel_column: Some(key),
+                ..
             }) => {
                 self.key_index = Some(
                     ctx.in_schemas
@@ -268,6 +326,15 @@ impl ArrowOperator for RedisSinkFunc {
                 .unwrap_or_else(|_| panic!("hash field column ({hash_field_column}) does not exist in input schema for redis sink")));
         }
 
+        if let TableType::Target(Target::SortedSetTable { score_column, .. }) =
+            &self.table.connector_type
+        {
+            self.score_index = Some(ctx.in_schemas.first().expect("no in-schema for redis sink!")
+                .schema
+                .index_of(score_column)
+                .unwrap_or_else(|_| panic!("score column ({score_column}) does not exist in input schema for redis sink")));
+        }
+
         let mut attempts = 0;
         while attempts < 20 {
             match self.client.get_connection().await {
@@ -281,8 +348,8 @@ impl ArrowOperator for RedisSinkFunc {
                         pipeline: redis::pipe(),
                         size_estimate: 0,
                         last_flushed: Instant::now(),
-                        max_push_keys: HashSet::new(),
-                        behavior: match self.table.connector_type {
+                        trim_keys: HashSet::new(),
+                        behavior: match &self.table.connector_type {
                             TableType::Target(Target::StringTable { ttl_secs, .. }) => {
                                 RedisBehavior::Set {
                                     ttl: ttl_secs.map(|t| t.get() as usize),
@@ -304,6 +371,20 @@ impl ArrowOperator for RedisSinkFunc {
                                 }
                             }
                             TableType::Target(Target::HashTable { .. }) => RedisBehavior::Hash,
+                            TableType::Target(Target::SortedSetTable { max_length, .. }) => {
+                                RedisBehavior::SortedSet {
+                                    max: max_length.map(|x| x.get() as usize),
+                                }
+                            }
+                            TableType::Target(Target::StreamTable {
+                                stream_field,
+                                stream_max_length,
+                                ..
+                            }) => RedisBehavior::Stream {
+                                field: stream_field.clone(),
+                                max: stream_max_length.map(|x| x.get() as usize),
+                            },
+                            TableType::Target(Target::PubSubTable { .. }) => RedisBehavior::Publish,
                         },
                     }
                     .start();
@@ -321,7 +402,15 @@ impl ArrowOperator for RedisSinkFunc {
         panic!("Failed to establish connection to redis after 20 retries");
     }
 
-    async fn process_batch(&mut self, batch: RecordBatch, _: &mut ArrowContext) {
+    async fn process_batch(
+        &mut self,
+        batch: RecordBatch,
+        _: &mut ArrowContext,
+    ) -> anyhow::Result<()> {
+        let scores = self.score_index.map(|idx| {
+            cast(batch.column(idx), &DataType::Float64).expect("score column must be numeric")
+        });
+
         for (i, value) in self.serializer.serialize(&batch).enumerate() {
             match &self.table.connector_type {
                 TableType::Target(target) => match &target {
@@ -355,12 +444,54 @@ impl ArrowOperator for RedisSinkFunc {
                             .await
                             .expect("Redis writer panicked");
                     }
+                    Target::SortedSetTable {
+                        sorted_set_key_prefix,
+                        ..
+                    } => {
+                        let key = self.make_key(sorted_set_key_prefix, &batch, i);
+                        let score = scores
+                            .as_ref()
+                            .expect("no score index")
+                            .as_primitive::<Float64Type>()
+                            .value(i);
+
+                        self.tx
+                            .send(RedisCmd::ZData { key, score, value })
+                            .await
+                            .expect("Redis writer panicked");
+                    }
+                    Target::StreamTable {
+                        stream_key_prefix, ..
+                    } => {
+                        let key = self.make_key(stream_key_prefix, &batch, i);
+
+                        self.tx
+                            .send(RedisCmd::Data { key, value })
+                            .await
+                            .expect("Redis writer panicked");
+                    }
+                    Target::PubSubTable { channel_prefix, .. } => {
+                        let channel = self.make_key(channel_prefix, &batch, i);
+
+                        self.tx
+                            .send(RedisCmd::Data {
+                                key: channel,
+                                value,
+                            })
+                            .await
+                            .expect("Redis writer panicked");
+                    }
                 },
             };
         }
+        Ok(())
     }
 
-    async fn handle_checkpoint(&mut self, checkpoint: CheckpointBarrier, _ctx: &mut ArrowContext) {
+    async fn handle_checkpoint(
+        &mut self,
+        checkpoint: CheckpointBarrier,
+        _ctx: &mut ArrowContext,
+    ) -> anyhow::Result<()> {
         self.tx
             .send(RedisCmd::Flush(checkpoint.epoch))
             .await
@@ -370,7 +501,7 @@ impl ArrowOperator for RedisSinkFunc {
             match tokio::time::timeout(Duration::from_secs(30), self.rx.recv()).await {
                 Ok(Some(epoch)) => {
                     if checkpoint.epoch == epoch {
-                        return;
+                        return Ok(());
                     }
                 }
                 Ok(None) => {
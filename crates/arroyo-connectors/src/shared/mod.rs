@@ -0,0 +1,313 @@
+mod sink;
+mod source;
+
+use anyhow::{anyhow, bail};
+use arroyo_operator::connector::{Connection, Connector};
+use arroyo_operator::operator::OperatorNode;
+use arroyo_rpc::api_types::connections::{
+    ConnectionProfile, ConnectionSchema, ConnectionType, TestSourceMessage,
+};
+use arroyo_rpc::config::config;
+use arroyo_rpc::OperatorConfig;
+use arroyo_storage::StorageProvider;
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::Duration;
+use typify::import_types;
+
+use crate::shared::sink::SharedSinkFunc;
+use crate::shared::source::SharedSourceFunc;
+use crate::{pull_opt, pull_option_to_i64, EmptyConfig};
+
+const TABLE_SCHEMA: &str = include_str!("./table.json");
+
+import_types!(schema = "src/shared/table.json");
+
+/// A shared source lets many pipelines consume a high-volume stream that is read only once.
+/// A single publishing pipeline writes what it reads into a buffer in the cluster's checkpoint
+/// storage, one Arrow file per subtask per checkpoint. Files become visible to consumers when
+/// their checkpoint commits, and each consuming pipeline tracks its position in its own state.
+pub struct SharedConnector {}
+
+/// The range of segments a publisher subtask has available, which it rewrites after each commit
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct Head {
+    /// the oldest segment that hasn't been removed by retention
+    pub first: u64,
+    /// the sequence number the next committed segment will have
+    pub next: u64,
+}
+
+fn storage_url(name: &str) -> String {
+    format!(
+        "{}/shared/{}",
+        config().checkpoint_url.trim_end_matches('/'),
+        name
+    )
+}
+
+pub(crate) async fn storage_provider(name: &str) -> anyhow::Result<StorageProvider> {
+    StorageProvider::for_url(&storage_url(name))
+        .await
+        .map_err(|e| {
+            anyhow!(
+                "failed to open storage for shared source '{}': {:?}",
+                name,
+                e
+            )
+        })
+}
+
+pub(crate) fn pending_path(subtask: usize, epoch: u32) -> String {
+    format!("pending/{}/{}.arrow", subtask, epoch)
+}
+
+pub(crate) fn segment_path(subtask: usize, seq: u64) -> String {
+    format!("segments/{}/{:012}.arrow", subtask, seq)
+}
+
+pub(crate) fn head_path(subtask: usize) -> String {
+    format!("heads/{}.json", subtask)
+}
+
+/// Reads the heads of every publisher subtask that has ever written to the shared source
+pub(crate) async fn read_heads(
+    name: &str,
+    storage: &StorageProvider,
+) -> anyhow::Result<HashMap<usize, Head>> {
+    let heads_storage = StorageProvider::for_url(&format!("{}/heads", storage_url(name)))
+        .await
+        .map_err(|e| {
+            anyhow!(
+                "failed to open storage for shared source '{}': {:?}",
+                name,
+                e
+            )
+        })?;
+
+    let mut subtasks = vec![];
+    let mut list = heads_storage.list(false).await?;
+    while let Some(path) = list.next().await {
+        if let Some(Ok(subtask)) = path?
+            .filename()
+            .and_then(|f| f.strip_suffix(".json"))
+            .map(|s| s.parse::<usize>())
+        {
+            subtasks.push(subtask);
+        }
+    }
+
+    let mut heads = HashMap::new();
+    for subtask in subtasks {
+        if let Some(bytes) = storage.get_if_present(head_path(subtask)).await? {
+            heads.insert(subtask, serde_json::from_slice(&bytes)?);
+        }
+    }
+
+    Ok(heads)
+}
+
+fn validate_table(table: &SharedTable) -> anyhow::Result<()> {
+    if table.name.is_empty()
+        || !table
+            .name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    {
+        bail!(
+            "invalid shared source name '{}'; names may only contain letters, numbers, '_' and '-'",
+            table.name
+        );
+    }
+
+    if table.retention_segments <= 0 {
+        bail!("retention_segments must be positive");
+    }
+
+    if table.poll_interval_millis <= 0 {
+        bail!("poll_interval_millis must be positive");
+    }
+
+    Ok(())
+}
+
+async fn test_inner(table: &SharedTable) -> anyhow::Result<String> {
+    validate_table(table)?;
+
+    let storage = storage_provider(&table.name).await?;
+    let heads = read_heads(&table.name, &storage).await?;
+
+    let available: u64 = heads.values().map(|h| h.next - h.first).sum();
+
+    Ok(match (&table.table_type, heads.is_empty()) {
+        (TableType::Source, true) => format!(
+            "Shared source '{}' has not been published yet; consumers will start reading once it is",
+            table.name
+        ),
+        (TableType::Sink, true) => format!("Shared source '{}' is available", table.name),
+        (_, false) => format!(
+            "Shared source '{}' is published by {} subtasks, with {} segments available",
+            table.name,
+            heads.len(),
+            available
+        ),
+    })
+}
+
+impl Connector for SharedConnector {
+    type ProfileT = EmptyConfig;
+    type TableT = SharedTable;
+
+    fn name(&self) -> &'static str {
+        "shared"
+    }
+
+    fn metadata(&self) -> arroyo_rpc::api_types::connections::Connector {
+        arroyo_rpc::api_types::connections::Connector {
+            id: "shared".to_string(),
+            name: "Shared Source".to_string(),
+            icon: "".to_string(),
+            description: "Publish a stream once and consume it from many pipelines".to_string(),
+            enabled: true,
+            source: true,
+            sink: true,
+            testing: true,
+            hidden: false,
+            custom_schemas: true,
+            connection_config: None,
+            table_config: TABLE_SCHEMA.to_owned(),
+        }
+    }
+
+    fn table_type(&self, _: Self::ProfileT, table: Self::TableT) -> ConnectionType {
+        match table.table_type {
+            TableType::Source => ConnectionType::Source,
+            TableType::Sink => ConnectionType::Sink,
+        }
+    }
+
+    fn get_schema(
+        &self,
+        _: Self::ProfileT,
+        _: Self::TableT,
+        s: Option<&ConnectionSchema>,
+    ) -> Option<ConnectionSchema> {
+        s.cloned()
+    }
+
+    fn test(
+        &self,
+        _: &str,
+        _: Self::ProfileT,
+        table: Self::TableT,
+        _: Option<&ConnectionSchema>,
+        tx: tokio::sync::mpsc::Sender<TestSourceMessage>,
+    ) {
+        tokio::task::spawn(async move {
+            let message = match test_inner(&table).await {
+                Ok(m) => TestSourceMessage::done(m),
+                Err(e) => TestSourceMessage::fail(format!("{:#}", e)),
+            };
+
+            tx.send(message).await.unwrap();
+        });
+    }
+
+    fn from_options(
+        &self,
+        name: &str,
+        options: &mut HashMap<String, String>,
+        schema: Option<&ConnectionSchema>,
+        _: Option<&ConnectionProfile>,
+    ) -> anyhow::Result<Connection> {
+        let Ok(table_type) = pull_opt("type", options)?.try_into() else {
+            bail!("'type' must be 'source' or 'sink'");
+        };
+
+        let offset = options
+            .remove("offset")
+            .map(|o| {
+                o.as_str()
+                    .try_into()
+                    .map_err(|_| anyhow!("'offset' must be 'earliest' or 'latest'"))
+            })
+            .transpose()?
+            .unwrap_or(Offset::Latest);
+
+        let table = SharedTable {
+            name: pull_opt("name", options)?,
+            table_type,
+            offset,
+            retention_segments: pull_option_to_i64("retention_segments", options)?.unwrap_or(360),
+            poll_interval_millis: pull_option_to_i64("poll_interval_millis", options)?
+                .unwrap_or(1000),
+        };
+
+        self.from_config(None, name, EmptyConfig {}, table, schema)
+    }
+
+    fn from_config(
+        &self,
+        id: Option<i64>,
+        name: &str,
+        config: Self::ProfileT,
+        table: Self::TableT,
+        schema: Option<&ConnectionSchema>,
+    ) -> anyhow::Result<Connection> {
+        validate_table(&table)?;
+
+        let schema = schema
+            .map(|s| s.to_owned())
+            .ok_or_else(|| anyhow!("no schema defined for shared source '{}'", table.name))?;
+
+        if schema.format.is_some() {
+            bail!("shared sources store data in Arrow format, so 'format' can't be set");
+        }
+
+        let connection_type = self.table_type(config.clone(), table.clone());
+        let description = match connection_type {
+            ConnectionType::Source => format!("SharedSource<{}>", table.name),
+            _ => format!("SharedSink<{}>", table.name),
+        };
+
+        let config = OperatorConfig {
+            connection: serde_json::to_value(config).unwrap(),
+            table: serde_json::to_value(table).unwrap(),
+            rate_limit: None,
+            format: None,
+            bad_data: schema.bad_data.clone(),
+            framing: None,
+            metadata_fields: vec![],
+        };
+
+        Ok(Connection {
+            id,
+            connector: self.name(),
+            name: name.to_string(),
+            connection_type,
+            schema,
+            config: serde_json::to_string(&config).unwrap(),
+            description,
+        })
+    }
+
+    fn make_operator(
+        &self,
+        _: Self::ProfileT,
+        table: Self::TableT,
+        _: OperatorConfig,
+    ) -> anyhow::Result<OperatorNode> {
+        Ok(match table.table_type {
+            TableType::Source => OperatorNode::from_source(Box::new(SharedSourceFunc::new(
+                table.name,
+                table.offset,
+                Duration::from_millis(table.poll_interval_millis as u64),
+            ))),
+            TableType::Sink => OperatorNode::from_operator(Box::new(SharedSinkFunc::new(
+                table.name,
+                table.retention_segments as u64,
+            ))),
+        })
+    }
+}
@@ -0,0 +1,315 @@
+use arrow::array::RecordBatch;
+use arrow::ipc::writer::FileWriter;
+use arroyo_operator::context::ArrowContext;
+use arroyo_operator::operator::{ArrowOperator, AsDisplayable, DisplayableOperator};
+use arroyo_rpc::grpc::rpc::{GlobalKeyedTableConfig, TableConfig, TableEnum};
+use arroyo_rpc::{CheckpointEvent, ControlMessage, ControlResp};
+use arroyo_state::tables::global_keyed_map::GlobalKeyedView;
+use arroyo_storage::StorageProvider;
+use arroyo_types::{single_item_hash_map, CheckpointBarrier, SignalMessage};
+use async_trait::async_trait;
+use bincode::{Decode, Encode};
+use prost::Message;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::time::SystemTime;
+use tracing::{info, warn};
+
+use crate::shared::{head_path, pending_path, segment_path, storage_provider, Head};
+
+/// What a publisher subtask has written, as of a checkpoint
+#[derive(Debug, Clone, Default, Encode, Decode)]
+pub struct PublisherState {
+    /// the oldest retained segment
+    first: u64,
+    /// the sequence number of the next segment to be written
+    next: u64,
+    /// segments that have been written for a checkpoint, but not yet committed, as pairs of
+    /// (epoch, sequence number)
+    pending: Vec<(u32, u64)>,
+}
+
+pub struct SharedSinkFunc {
+    name: String,
+    retention: u64,
+    subtask_index: usize,
+    storage: Option<StorageProvider>,
+    batches: Vec<RecordBatch>,
+    state: PublisherState,
+}
+
+impl SharedSinkFunc {
+    pub fn new(name: String, retention: u64) -> Self {
+        Self {
+            name,
+            retention,
+            subtask_index: 0,
+            storage: None,
+            batches: vec![],
+            state: PublisherState::default(),
+        }
+    }
+
+    fn storage(&self) -> &StorageProvider {
+        self.storage
+            .as_ref()
+            .expect("shared source storage not initialized")
+    }
+
+    /// The range of segments that consumers can read
+    fn head(&self) -> Head {
+        Head {
+            first: self.state.first,
+            next: self
+                .state
+                .pending
+                .first()
+                .map(|(_, seq)| *seq)
+                .unwrap_or(self.state.next),
+        }
+    }
+
+    async fn write_head(&self) {
+        self.storage()
+            .put(
+                head_path(self.subtask_index),
+                serde_json::to_vec(&self.head()).unwrap(),
+            )
+            .await
+            .unwrap_or_else(|e| {
+                panic!(
+                    "failed to write head of shared source '{}': {:?}",
+                    self.name, e
+                )
+            });
+    }
+}
+
+#[async_trait]
+impl ArrowOperator for SharedSinkFunc {
+    fn name(&self) -> String {
+        "SharedSink".to_string()
+    }
+
+    fn display(&self) -> DisplayableOperator {
+        DisplayableOperator {
+            name: Cow::Borrowed("SharedSink"),
+            fields: vec![
+                ("name", AsDisplayable::Str(&self.name)),
+                ("retention", AsDisplayable::Display(&self.retention)),
+            ],
+        }
+    }
+
+    fn tables(&self) -> HashMap<String, TableConfig> {
+        single_item_hash_map(
+            "s".to_string(),
+            TableConfig {
+                table_type: TableEnum::GlobalKeyValue.into(),
+                config: GlobalKeyedTableConfig {
+                    table_name: "s".to_string(),
+                    description: "segments written by each publisher subtask".to_string(),
+                    uses_two_phase_commit: true,
+                    ttl_micros: None,
+                }
+                .encode_to_vec(),
+            },
+        )
+    }
+
+    async fn on_start(&mut self, ctx: &mut ArrowContext) {
+        let storage = storage_provider(&self.name)
+            .await
+            .unwrap_or_else(|e| panic!("{:?}", e));
+
+        let task_index = ctx.task_info.task_index;
+        self.subtask_index = task_index;
+
+        let state: &mut GlobalKeyedView<usize, PublisherState> = ctx
+            .table_manager
+            .get_global_keyed_state("s")
+            .await
+            .expect("should be able to get shared source state");
+
+        self.state = match state.get(&task_index) {
+            Some(state) => state.clone(),
+            None => {
+                // continue from whatever a previous publisher with this name left behind, so
+                // that consumers' positions stay valid
+                let head: Head = storage
+                    .get_if_present(head_path(task_index))
+                    .await
+                    .unwrap_or_else(|e| panic!("failed to read shared source head: {:?}", e))
+                    .map(|bytes| serde_json::from_slice(&bytes).unwrap_or_default())
+                    .unwrap_or_default();
+
+                PublisherState {
+                    first: head.first,
+                    next: head.next,
+                    pending: vec![],
+                }
+            }
+        };
+
+        self.storage = Some(storage);
+
+        // segments that were published after the checkpoint we restored from was taken don't
+        // need to be published again
+        let mut pending = vec![];
+        for (epoch, seq) in std::mem::take(&mut self.state.pending) {
+            if self
+                .storage()
+                .exists(pending_path(task_index, epoch))
+                .await
+                .unwrap_or(true)
+            {
+                pending.push((epoch, seq));
+            }
+        }
+        self.state.pending = pending;
+
+        self.write_head().await;
+    }
+
+    async fn process_batch(
+        &mut self,
+        batch: RecordBatch,
+        _: &mut ArrowContext,
+    ) -> anyhow::Result<()> {
+        if batch.num_rows() > 0 {
+            self.batches.push(batch);
+        }
+        Ok(())
+    }
+
+    async fn handle_checkpoint(
+        &mut self,
+        barrier: CheckpointBarrier,
+        ctx: &mut ArrowContext,
+    ) -> anyhow::Result<()> {
+        let task_index = ctx.task_info.task_index;
+
+        if !self.batches.is_empty() {
+            let batches = std::mem::take(&mut self.batches);
+            let mut writer = FileWriter::try_new(vec![], &batches[0].schema())
+                .expect("failed to create Arrow writer");
+            for batch in &batches {
+                writer.write(batch).expect("failed to write Arrow batch");
+            }
+            let bytes = writer.into_inner().expect("failed to finish Arrow file");
+
+            self.storage()
+                .put(pending_path(task_index, barrier.epoch), bytes)
+                .await
+                .unwrap_or_else(|e| {
+                    panic!(
+                        "failed to write segment for shared source '{}': {:?}",
+                        self.name, e
+                    )
+                });
+
+            self.state.pending.push((barrier.epoch, self.state.next));
+            self.state.next += 1;
+        }
+
+        let state: &mut GlobalKeyedView<usize, PublisherState> = ctx
+            .table_manager
+            .get_global_keyed_state("s")
+            .await
+            .expect("should be able to get shared source state");
+        state.insert(task_index, self.state.clone()).await;
+        Ok(())
+    }
+
+    async fn handle_commit(
+        &mut self,
+        epoch: u32,
+        _commit_data: &HashMap<String, HashMap<u32, Vec<u8>>>,
+        ctx: &mut ArrowContext,
+    ) -> anyhow::Result<()> {
+        let task_index = ctx.task_info.task_index;
+
+        let committed: Vec<_> = self
+            .state
+            .pending
+            .iter()
+            .filter(|(e, _)| *e <= epoch)
+            .copied()
+            .collect();
+
+        for (e, seq) in committed {
+            // publishing a segment is a rename, so it's safe to retry if we fail part way
+            // through; if the pending file is gone, it was already published
+            let storage = self.storage();
+            if storage
+                .exists(pending_path(task_index, e))
+                .await
+                .unwrap_or(true)
+            {
+                storage
+                    .rename(pending_path(task_index, e), segment_path(task_index, seq))
+                    .await
+                    .unwrap_or_else(|err| {
+                        panic!(
+                            "failed to publish segment {} of shared source '{}': {:?}",
+                            seq, self.name, err
+                        )
+                    });
+            }
+
+            self.state.pending.retain(|(p, _)| *p != e);
+        }
+
+        let head = self.head();
+        while head.next - self.state.first > self.retention {
+            if let Err(e) = self
+                .storage()
+                .delete_if_present(segment_path(task_index, self.state.first))
+                .await
+            {
+                warn!(
+                    "failed to remove expired segment of shared source '{}': {:?}",
+                    self.name, e
+                );
+                break;
+            }
+            self.state.first += 1;
+        }
+
+        self.write_head().await;
+
+        info!(
+            "published shared source '{}' segments up to {} for epoch {}",
+            self.name,
+            self.head().next,
+            epoch
+        );
+
+        let checkpoint_event = ControlResp::CheckpointEvent(CheckpointEvent {
+            checkpoint_epoch: epoch,
+            operator_id: ctx.task_info.operator_id.clone(),
+            subtask_index: ctx.task_info.task_index as u32,
+            time: SystemTime::now(),
+            event_type: arroyo_rpc::grpc::rpc::TaskCheckpointEventType::FinishedCommit,
+        });
+        ctx.control_tx
+            .send(checkpoint_event)
+            .await
+            .expect("sent commit event");
+        Ok(())
+    }
+
+    async fn on_close(
+        &mut self,
+        _: &Option<SignalMessage>,
+        ctx: &mut ArrowContext,
+    ) -> anyhow::Result<()> {
+        if let Some(ControlMessage::Commit { epoch, commit_data }) = ctx.control_rx.recv().await {
+            self.handle_commit(epoch, &commit_data, ctx).await?;
+        } else {
+            warn!("no commit message received, not committing")
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,317 @@
+use std::collections::HashMap;
+use std::io::Cursor;
+use std::time::{Duration, SystemTime};
+
+use arrow::array::{new_null_array, RecordBatch};
+use arrow::compute::cast;
+use arrow::ipc::reader::FileReader;
+use arroyo_operator::context::ArrowContext;
+use arroyo_operator::operator::SourceOperator;
+use arroyo_operator::SourceFinishType;
+use arroyo_rpc::grpc::rpc::{StopMode, TableConfig};
+use arroyo_rpc::ControlMessage;
+use arroyo_state::global_table_config;
+use arroyo_state::tables::global_keyed_map::GlobalKeyedView;
+use arroyo_storage::StorageProvider;
+use arroyo_types::{to_nanos, UserError};
+use async_trait::async_trait;
+use datafusion::common::ScalarValue;
+use tokio::select;
+use tokio::time::MissedTickBehavior;
+use tracing::{debug, info};
+
+use crate::shared::{read_heads, segment_path, storage_provider, Head, Offset};
+
+/// Consumes the segments published to a shared source. Each publisher subtask's segments are
+/// read by a single consumer subtask, which tracks the next sequence number it will read.
+pub struct SharedSourceFunc {
+    name: String,
+    offset: Offset,
+    poll_interval: Duration,
+    storage: Option<StorageProvider>,
+    /// the next segment to read, by publisher subtask
+    positions: HashMap<usize, u64>,
+}
+
+impl SharedSourceFunc {
+    pub fn new(name: String, offset: Offset, poll_interval: Duration) -> Self {
+        Self {
+            name,
+            offset,
+            poll_interval,
+            storage: None,
+            positions: HashMap::new(),
+        }
+    }
+
+    fn storage(&self) -> &StorageProvider {
+        self.storage
+            .as_ref()
+            .expect("shared source storage not initialized")
+    }
+
+    fn is_assigned(parallelism: usize, task_index: usize, publisher: usize) -> bool {
+        publisher % parallelism == task_index
+    }
+
+    /// Converts a published batch to the output schema by column name, casting columns whose
+    /// types have changed and filling in nullable columns the publisher doesn't write
+    fn to_output(&self, ctx: &ArrowContext, batch: RecordBatch) -> Result<RecordBatch, UserError> {
+        let out_schema = ctx.out_schema.as_ref().unwrap();
+        let mismatch = |details: String| {
+            UserError::new(
+                "data does not match schema",
+                format!(
+                    "Data published to shared source '{}' does not match the table schema: {}",
+                    self.name, details
+                ),
+            )
+        };
+
+        let columns = out_schema
+            .schema
+            .fields()
+            .iter()
+            .enumerate()
+            .map(|(i, field)| match batch.column_by_name(field.name()) {
+                Some(column) if column.data_type() == field.data_type() => Ok(column.clone()),
+                Some(column) => cast(column, field.data_type())
+                    .map_err(|e| mismatch(format!("column '{}': {}", field.name(), e))),
+                None if i == out_schema.timestamp_index => Ok(ScalarValue::TimestampNanosecond(
+                    Some(to_nanos(SystemTime::now()) as i64),
+                    None,
+                )
+                .to_array_of_size(batch.num_rows())
+                .unwrap()),
+                None if field.is_nullable() => {
+                    Ok(new_null_array(field.data_type(), batch.num_rows()))
+                }
+                None => Err(mismatch(format!(
+                    "required column '{}' is not published",
+                    field.name()
+                ))),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        RecordBatch::try_new(out_schema.schema.clone(), columns)
+            .map_err(|e| mismatch(e.to_string()))
+    }
+
+    async fn read_segment(
+        &self,
+        ctx: &mut ArrowContext,
+        publisher: usize,
+        seq: u64,
+    ) -> Result<(), UserError> {
+        let bytes = self
+            .storage()
+            .get(segment_path(publisher, seq))
+            .await
+            .map_err(|e| {
+                UserError::new(
+                    "failed to read shared source",
+                    format!(
+                        "Could not read segment {} of subtask {} of shared source '{}': {:?}",
+                        seq, publisher, self.name, e
+                    ),
+                )
+            })?;
+
+        let reader = FileReader::try_new(Cursor::new(bytes), None).map_err(|e| {
+            UserError::new(
+                "invalid shared source segment",
+                format!(
+                    "Segment {} of subtask {} of shared source '{}' is not a valid Arrow file: {:?}",
+                    seq, publisher, self.name, e
+                ),
+            )
+        })?;
+
+        for batch in reader {
+            let batch = batch.map_err(|e| {
+                UserError::new(
+                    "invalid shared source segment",
+                    format!(
+                        "Failed to decode segment {} of subtask {} of shared source '{}': {:?}",
+                        seq, publisher, self.name, e
+                    ),
+                )
+            })?;
+
+            let batch = self.to_output(ctx, batch)?;
+            ctx.collect(batch).await;
+        }
+
+        Ok(())
+    }
+
+    /// Reads every segment that has been published since the last poll by the publisher
+    /// subtasks assigned to this subtask
+    async fn poll(&mut self, ctx: &mut ArrowContext) -> Result<(), UserError> {
+        let heads = match read_heads(&self.name, self.storage()).await {
+            Ok(heads) => heads,
+            Err(e) => {
+                ctx.report_error(
+                    "failed to read shared source",
+                    format!(
+                        "Could not read the heads of shared source '{}': {:?}",
+                        self.name, e
+                    ),
+                )
+                .await;
+                return Ok(());
+            }
+        };
+
+        let parallelism = ctx.task_info.parallelism;
+        let task_index = ctx.task_info.task_index;
+        let mut publishers: Vec<(usize, Head)> = heads
+            .into_iter()
+            .filter(|(publisher, _)| Self::is_assigned(parallelism, task_index, *publisher))
+            .collect();
+        publishers.sort_by_key(|(publisher, _)| *publisher);
+
+        for (publisher, head) in publishers {
+            let mut position = match (self.positions.get(&publisher), &self.offset) {
+                (Some(position), _) => *position,
+                (None, Offset::Earliest) => head.first,
+                (None, Offset::Latest) => head.next,
+            };
+
+            if position < head.first {
+                ctx.report_error(
+                    "shared source data skipped",
+                    format!(
+                        "Segments {}..{} of subtask {} of shared source '{}' were removed by retention before they were read",
+                        position, head.first, publisher, self.name
+                    ),
+                )
+                .await;
+                position = head.first;
+            }
+
+            self.positions.insert(publisher, position);
+
+            while position < head.next {
+                self.read_segment(ctx, publisher, position).await?;
+                position += 1;
+                self.positions.insert(publisher, position);
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn handle_control_message(
+        &mut self,
+        ctx: &mut ArrowContext,
+        msg: Option<ControlMessage>,
+    ) -> Option<SourceFinishType> {
+        match msg? {
+            ControlMessage::Checkpoint(c) => {
+                debug!("starting checkpointing {}", ctx.task_info.task_index);
+                let state: &mut GlobalKeyedView<usize, u64> = ctx
+                    .table_manager
+                    .get_global_keyed_state("p")
+                    .await
+                    .expect("should be able to get shared source state");
+
+                for (publisher, position) in &self.positions {
+                    state.insert(*publisher, *position).await;
+                }
+
+                if self.start_checkpoint(c, ctx).await {
+                    return Some(SourceFinishType::Immediate);
+                }
+            }
+            ControlMessage::Stop { mode } => {
+                info!("Stopping shared source '{}': {:?}", self.name, mode);
+
+                match mode {
+                    StopMode::Graceful => {
+                        return Some(SourceFinishType::Graceful);
+                    }
+                    StopMode::Immediate => {
+                        return Some(SourceFinishType::Immediate);
+                    }
+                }
+            }
+            ControlMessage::Commit { .. } => {
+                unreachable!("sources shouldn't receive commit messages");
+            }
+            ControlMessage::LoadCompacted { compacted } => {
+                ctx.load_compacted(compacted).await;
+            }
+            ControlMessage::QueryState { query } => {
+                ctx.query_state(query).await;
+            }
+            ControlMessage::NoOp => {}
+        }
+        None
+    }
+
+    async fn run_int(&mut self, ctx: &mut ArrowContext) -> Result<SourceFinishType, UserError> {
+        let mut timer = tokio::time::interval(self.poll_interval);
+        timer.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        loop {
+            select! {
+                _ = timer.tick() => {
+                    self.poll(ctx).await?;
+                }
+                control_message = ctx.control_rx.recv() => {
+                    if let Some(r) = self.handle_control_message(ctx, control_message).await {
+                        return Ok(r);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl SourceOperator for SharedSourceFunc {
+    fn name(&self) -> String {
+        "SharedSource".to_string()
+    }
+
+    fn tables(&self) -> HashMap<String, TableConfig> {
+        global_table_config("p", "position of the consumer in each publisher subtask")
+    }
+
+    async fn on_start(&mut self, ctx: &mut ArrowContext) {
+        self.storage = Some(
+            storage_provider(&self.name)
+                .await
+                .unwrap_or_else(|e| panic!("{:?}", e)),
+        );
+
+        let parallelism = ctx.task_info.parallelism;
+        let task_index = ctx.task_info.task_index;
+        let state: &mut GlobalKeyedView<usize, u64> = ctx
+            .table_manager
+            .get_global_keyed_state("p")
+            .await
+            .expect("should be able to get shared source state");
+
+        // positions are keyed by publisher subtask, so after a restore at a different
+        // parallelism each consumer subtask picks up those of the publishers assigned to it
+        self.positions = state
+            .get_all()
+            .iter()
+            .filter(|(publisher, _)| Self::is_assigned(parallelism, task_index, **publisher))
+            .map(|(publisher, position)| (*publisher, *position))
+            .collect();
+    }
+
+    async fn run(&mut self, ctx: &mut ArrowContext) -> SourceFinishType {
+        match self.run_int(ctx).await {
+            Ok(r) => r,
+            Err(e) => {
+                ctx.report_error(e.name.clone(), e.details.clone()).await;
+
+                panic!("{}: {}", e.name, e.details);
+            }
+        }
+    }
+}
@@ -0,0 +1,359 @@
+mod source;
+
+use anyhow::{anyhow, bail};
+use arroyo_operator::connector::{Connection, Connector};
+use arroyo_operator::operator::OperatorNode;
+use arroyo_rpc::api_types::connections::{
+    ConnectionProfile, ConnectionSchema, ConnectionType, FieldType, TestSourceMessage,
+};
+use arroyo_rpc::formats::{Format, JsonFormat};
+use arroyo_rpc::var_str::VarStr;
+use arroyo_rpc::OperatorConfig;
+use arroyo_storage::StorageProvider;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::time::Duration;
+use tracing::warn;
+use typify::import_types;
+
+use crate::side_input::source::SideInputSourceFunc;
+use crate::{construct_http_client, pull_opt, pull_option_to_i64, EmptyConfig};
+
+const TABLE_SCHEMA: &str = include_str!("./table.json");
+
+import_types!(schema = "src/side_input/table.json", convert = { {type = "string", format = "var-str"} = VarStr });
+
+/// A side input is reference data that is re-read in full on an interval, from an HTTP
+/// endpoint, a SQL query or an object in storage. Each read produces a new version of the
+/// table, and the differences from the previous version are emitted together as a
+/// debezium-style changelog, so that joins against it switch between versions atomically.
+pub struct SideInputConnector {}
+
+/// Parses a JSON array of objects or newline-delimited JSON objects into rows
+fn parse_rows(body: &[u8]) -> anyhow::Result<Vec<Map<String, Value>>> {
+    let text = std::str::from_utf8(body).map_err(|_| anyhow!("data is not valid UTF-8"))?;
+    let text = text.trim();
+
+    let values: Vec<Value> = if text.starts_with('[') {
+        serde_json::from_str(text).map_err(|e| anyhow!("invalid JSON array: {}", e))?
+    } else {
+        text.lines()
+            .filter(|line| !line.trim().is_empty())
+            .enumerate()
+            .map(|(i, line)| {
+                serde_json::from_str(line)
+                    .map_err(|e| anyhow!("invalid JSON on line {}: {}", i + 1, e))
+            })
+            .collect::<anyhow::Result<_>>()?
+    };
+
+    values
+        .into_iter()
+        .map(|v| match v {
+            Value::Object(row) => Ok(row),
+            other => bail!("expected each row to be a JSON object, but found {}", other),
+        })
+        .collect()
+}
+
+/// Reads the current version of the input
+pub(crate) async fn read_input(input: &Input) -> anyhow::Result<Vec<Map<String, Value>>> {
+    match input {
+        Input::HttpEndpoint { endpoint, headers } => {
+            let endpoint = endpoint.sub_env_vars()?;
+            let headers = headers.as_ref().map(|h| h.sub_env_vars()).transpose()?;
+            let client = construct_http_client(&endpoint, headers)?;
+
+            let response = client
+                .get(&endpoint)
+                .send()
+                .await
+                .map_err(|e| anyhow!("request to {} failed: {}", endpoint, e))?;
+
+            if !response.status().is_success() {
+                bail!(
+                    "request to {} failed with status {}",
+                    endpoint,
+                    response.status()
+                );
+            }
+
+            parse_rows(&response.bytes().await?)
+        }
+        Input::SqlQuery {
+            connection_string,
+            query,
+        } => {
+            let pg_config = tokio_postgres::Config::from_str(&connection_string.sub_env_vars()?)
+                .map_err(|e| anyhow!("invalid connection string: {}", e))?;
+
+            let (client, connection) = pg_config
+                .connect(tokio_postgres::NoTls)
+                .await
+                .map_err(|e| anyhow!("failed to connect to Postgres: {}", e))?;
+
+            tokio::spawn(async move {
+                if let Err(e) = connection.await {
+                    warn!("postgres connection closed with error: {}", e);
+                }
+            });
+
+            let rows = client
+                .query(
+                    &format!(
+                        "SELECT row_to_json(q)::text FROM ({}) q",
+                        query.trim().trim_end_matches(';')
+                    ),
+                    &[],
+                )
+                .await
+                .map_err(|e| anyhow!("query failed: {}", e))?;
+
+            rows.iter()
+                .map(|row| {
+                    let json: String = row.get(0);
+                    parse_rows(json.as_bytes())?
+                        .pop()
+                        .ok_or_else(|| anyhow!("query returned an empty row"))
+                })
+                .collect()
+        }
+        Input::Object { path } => {
+            let body = StorageProvider::get_url(path)
+                .await
+                .map_err(|e| anyhow!("failed to read {}: {:?}", path, e))?;
+
+            parse_rows(&body)
+        }
+    }
+}
+
+/// Returns the names of the columns of a table, looking inside the `after` struct of a
+/// debezium schema
+fn column_names(schema: &ConnectionSchema) -> Vec<&str> {
+    schema
+        .fields
+        .iter()
+        .find_map(|f| match &f.field_type.r#type {
+            FieldType::Struct(s) if f.field_name == "after" => {
+                Some(s.fields.iter().map(|f| f.field_name.as_str()).collect())
+            }
+            _ => None,
+        })
+        .unwrap_or_else(|| {
+            schema
+                .fields
+                .iter()
+                .map(|f| f.field_name.as_str())
+                .collect()
+        })
+}
+
+async fn test_inner(table: &SideInputTable) -> anyhow::Result<String> {
+    let rows = read_input(&table.input).await?;
+    Ok(format!("Successfully read {} rows", rows.len()))
+}
+
+impl Connector for SideInputConnector {
+    type ProfileT = EmptyConfig;
+    type TableT = SideInputTable;
+
+    fn name(&self) -> &'static str {
+        "side_input"
+    }
+
+    fn metadata(&self) -> arroyo_rpc::api_types::connections::Connector {
+        arroyo_rpc::api_types::connections::Connector {
+            id: "side_input".to_string(),
+            name: "Side Input".to_string(),
+            icon: "".to_string(),
+            description: "Reference data that is periodically reloaded from an HTTP endpoint, SQL query or object"
+                .to_string(),
+            enabled: true,
+            source: true,
+            sink: false,
+            testing: true,
+            hidden: false,
+            custom_schemas: true,
+            connection_config: None,
+            table_config: TABLE_SCHEMA.to_owned(),
+        }
+    }
+
+    fn table_type(&self, _: Self::ProfileT, _: Self::TableT) -> ConnectionType {
+        ConnectionType::Source
+    }
+
+    fn max_source_parallelism(&self, _: Self::ProfileT, _: Self::TableT) -> Option<usize> {
+        // each version is read in full by a single subtask
+        Some(1)
+    }
+
+    fn get_schema(
+        &self,
+        _: Self::ProfileT,
+        _: Self::TableT,
+        s: Option<&ConnectionSchema>,
+    ) -> Option<ConnectionSchema> {
+        s.cloned()
+    }
+
+    fn test(
+        &self,
+        _: &str,
+        _: Self::ProfileT,
+        table: Self::TableT,
+        _: Option<&ConnectionSchema>,
+        tx: tokio::sync::mpsc::Sender<TestSourceMessage>,
+    ) {
+        tokio::task::spawn(async move {
+            let message = match test_inner(&table).await {
+                Ok(m) => TestSourceMessage::done(m),
+                Err(e) => TestSourceMessage::fail(format!("Failed to read side input: {:#}", e)),
+            };
+
+            tx.send(message).await.unwrap();
+        });
+    }
+
+    fn from_options(
+        &self,
+        name: &str,
+        options: &mut HashMap<String, String>,
+        schema: Option<&ConnectionSchema>,
+        _: Option<&ConnectionProfile>,
+    ) -> anyhow::Result<Connection> {
+        let input = match (
+            options.remove("endpoint"),
+            options.remove("query"),
+            options.remove("path"),
+        ) {
+            (Some(endpoint), None, None) => Input::HttpEndpoint {
+                endpoint: VarStr::new(endpoint),
+                headers: options.remove("headers").map(VarStr::new),
+            },
+            (None, Some(query), None) => Input::SqlQuery {
+                connection_string: VarStr::new(pull_opt("connection_string", options)?),
+                query,
+            },
+            (None, None, Some(path)) => Input::Object { path },
+            _ => bail!("side inputs require exactly one of 'endpoint', 'query' or 'path'"),
+        };
+
+        let table = SideInputTable {
+            input,
+            refresh_interval_ms: pull_option_to_i64("refresh_interval_ms", options)?
+                .unwrap_or(60_000),
+            version_field: options.remove("version_field"),
+            refreshed_at_field: options.remove("refreshed_at_field"),
+        };
+
+        self.from_config(None, name, EmptyConfig {}, table, schema)
+    }
+
+    fn from_config(
+        &self,
+        id: Option<i64>,
+        name: &str,
+        config: Self::ProfileT,
+        table: Self::TableT,
+        schema: Option<&ConnectionSchema>,
+    ) -> anyhow::Result<Connection> {
+        if table.refresh_interval_ms <= 0 {
+            bail!("refresh_interval_ms must be positive");
+        }
+
+        let schema = schema
+            .map(|s| s.to_owned())
+            .ok_or_else(|| anyhow!("no schema defined for side input"))?;
+
+        let format = schema.format.clone().unwrap_or_else(|| {
+            Format::Json(JsonFormat {
+                debezium: true,
+                ..Default::default()
+            })
+        });
+
+        if !matches!(format, Format::Json(JsonFormat { debezium: true, .. })) {
+            bail!("side inputs only support the debezium_json format");
+        }
+
+        let columns = column_names(&schema);
+        for field in [&table.version_field, &table.refreshed_at_field]
+            .into_iter()
+            .flatten()
+        {
+            if !columns.contains(&field.as_str()) {
+                bail!("side input has no column named '{}'", field);
+            }
+        }
+
+        let description = match &table.input {
+            Input::HttpEndpoint { .. } => "SideInput<http>".to_string(),
+            Input::SqlQuery { .. } => "SideInput<sql>".to_string(),
+            Input::Object { path } => format!("SideInput<{}>", path),
+        };
+
+        let config = OperatorConfig {
+            connection: serde_json::to_value(config).unwrap(),
+            table: serde_json::to_value(table).unwrap(),
+            rate_limit: None,
+            format: Some(format),
+            bad_data: schema.bad_data.clone(),
+            framing: None,
+            metadata_fields: vec![],
+        };
+
+        Ok(Connection {
+            id,
+            connector: self.name(),
+            name: name.to_string(),
+            connection_type: ConnectionType::Source,
+            schema,
+            config: serde_json::to_string(&config).unwrap(),
+            description,
+        })
+    }
+
+    fn make_operator(
+        &self,
+        _: Self::ProfileT,
+        table: Self::TableT,
+        config: OperatorConfig,
+    ) -> anyhow::Result<OperatorNode> {
+        Ok(OperatorNode::from_source(Box::new(
+            SideInputSourceFunc::new(
+                table.input,
+                Duration::from_millis(table.refresh_interval_ms as u64),
+                table.version_field,
+                table.refreshed_at_field,
+                config
+                    .format
+                    .ok_or_else(|| anyhow!("format required for side input"))?,
+                config.bad_data,
+            ),
+        )))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::parse_rows;
+
+    #[test]
+    fn test_parse_rows() {
+        let array = parse_rows(br#"[{"id": 1, "name": "a"}, {"id": 2, "name": "b"}]"#).unwrap();
+        assert_eq!(array.len(), 2);
+        assert_eq!(array[1]["name"], "b");
+
+        let lines = parse_rows(b"{\"id\": 1}\n\n{\"id\": 2}\n").unwrap();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[1]["id"], 2);
+
+        assert!(parse_rows(b"[1, 2]").is_err());
+        assert!(parse_rows(b"{\"id\": 1}\nnot json").is_err());
+        assert!(parse_rows(b"").unwrap().is_empty());
+    }
+}
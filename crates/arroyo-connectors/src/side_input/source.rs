@@ -0,0 +1,284 @@
+use async_trait::async_trait;
+use bincode::{Decode, Encode};
+use chrono::{DateTime, Utc};
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::time::{Duration, SystemTime};
+use tokio::select;
+use tokio::time::MissedTickBehavior;
+use tracing::{debug, info};
+
+use arroyo_operator::context::ArrowContext;
+use arroyo_operator::operator::SourceOperator;
+use arroyo_operator::SourceFinishType;
+use arroyo_rpc::formats::{BadData, Format, JsonFormat, TimestampFormat};
+use arroyo_rpc::grpc::rpc::{StopMode, TableConfig};
+use arroyo_rpc::ControlMessage;
+use arroyo_state::tables::global_keyed_map::GlobalKeyedView;
+use arroyo_types::{to_millis, UserError};
+
+use crate::side_input::{read_input, Input};
+
+/// Re-reads a side input on an interval. Each read that differs from the current version
+/// becomes a new version: rows that are no longer present are retracted and new rows are
+/// added, all in a single batch with the time of the refresh.
+pub struct SideInputSourceFunc {
+    input: Input,
+    refresh_interval: Duration,
+    version_field: Option<String>,
+    refreshed_at_field: Option<String>,
+    format: Format,
+    bad_data: Option<BadData>,
+    state: SideInputState,
+}
+
+#[derive(Clone, Debug, Encode, Decode, PartialEq, Default)]
+pub struct SideInputState {
+    /// the current version, which is 0 before the input has been read
+    version: u64,
+    /// the rows of the current version, as pairs of the row as read from the input and the
+    /// row as it was emitted (including the version fields)
+    rows: Vec<(String, String)>,
+}
+
+/// The JSON of a row with its keys in a consistent order, for comparing rows across reads
+fn canonical(row: &Map<String, Value>) -> String {
+    serde_json::to_string(&row.iter().collect::<BTreeMap<_, _>>()).unwrap()
+}
+
+#[async_trait]
+impl SourceOperator for SideInputSourceFunc {
+    fn name(&self) -> String {
+        "SideInputSource".to_string()
+    }
+
+    fn tables(&self) -> HashMap<String, TableConfig> {
+        arroyo_state::global_table_config("s", "side input state")
+    }
+
+    async fn on_start(&mut self, ctx: &mut ArrowContext) {
+        let s: &mut GlobalKeyedView<(), SideInputState> = ctx
+            .table_manager
+            .get_global_keyed_state("s")
+            .await
+            .expect("should be able to read side input state");
+
+        if let Some(state) = s.get(&()) {
+            self.state = state.clone();
+        }
+    }
+
+    async fn run(&mut self, ctx: &mut ArrowContext) -> SourceFinishType {
+        match self.run_int(ctx).await {
+            Ok(r) => r,
+            Err(e) => {
+                ctx.report_error(e.name.clone(), e.details.clone()).await;
+
+                panic!("{}: {}", e.name, e.details);
+            }
+        }
+    }
+}
+
+impl SideInputSourceFunc {
+    pub fn new(
+        input: Input,
+        refresh_interval: Duration,
+        version_field: Option<String>,
+        refreshed_at_field: Option<String>,
+        format: Format,
+        bad_data: Option<BadData>,
+    ) -> Self {
+        Self {
+            input,
+            refresh_interval,
+            version_field,
+            refreshed_at_field,
+            format,
+            bad_data,
+            state: SideInputState::default(),
+        }
+    }
+
+    async fn handle_control_message(
+        &mut self,
+        ctx: &mut ArrowContext,
+        msg: Option<ControlMessage>,
+    ) -> Option<SourceFinishType> {
+        match msg? {
+            ControlMessage::Checkpoint(c) => {
+                debug!("starting checkpointing {}", ctx.task_info.task_index);
+                let state = self.state.clone();
+                let s = ctx
+                    .table_manager
+                    .get_global_keyed_state("s")
+                    .await
+                    .expect("should be able to get side input state");
+                s.insert((), state).await;
+
+                if self.start_checkpoint(c, ctx).await {
+                    return Some(SourceFinishType::Immediate);
+                }
+            }
+            ControlMessage::Stop { mode } => {
+                info!("Stopping side input source: {:?}", mode);
+
+                match mode {
+                    StopMode::Graceful => {
+                        return Some(SourceFinishType::Graceful);
+                    }
+                    StopMode::Immediate => {
+                        return Some(SourceFinishType::Immediate);
+                    }
+                }
+            }
+            ControlMessage::Commit { .. } => {
+                unreachable!("sources shouldn't receive commit messages");
+            }
+            ControlMessage::LoadCompacted { compacted } => {
+                ctx.load_compacted(compacted).await;
+            }
+            ControlMessage::QueryState { query } => {
+                ctx.query_state(query).await;
+            }
+            ControlMessage::NoOp => {}
+        }
+        None
+    }
+
+    /// Adds the version fields to a row that is loaded in a new version
+    fn with_version(
+        &self,
+        mut row: Map<String, Value>,
+        version: u64,
+        refreshed_at: SystemTime,
+    ) -> Map<String, Value> {
+        if let Some(field) = &self.version_field {
+            row.insert(field.clone(), Value::from(version));
+        }
+
+        if let Some(field) = &self.refreshed_at_field {
+            let value = match &self.format {
+                Format::Json(JsonFormat {
+                    timestamp_format: TimestampFormat::UnixMillis,
+                    ..
+                }) => Value::from(to_millis(refreshed_at)),
+                _ => Value::from(DateTime::<Utc>::from(refreshed_at).to_rfc3339()),
+            };
+            row.insert(field.clone(), value);
+        }
+
+        row
+    }
+
+    /// Reads the input and emits the changes from the current version
+    async fn refresh(&mut self, ctx: &mut ArrowContext) -> Result<(), UserError> {
+        let rows = match read_input(&self.input).await {
+            Ok(rows) => rows,
+            Err(e) => {
+                // keep serving the current version until the input can be read again
+                ctx.report_error("failed to refresh side input", format!("{:#}", e))
+                    .await;
+                return Ok(());
+            }
+        };
+
+        let version = self.state.version + 1;
+        let refreshed_at = SystemTime::now();
+
+        let mut current: HashMap<String, Vec<String>> = HashMap::new();
+        for (raw, emitted) in std::mem::take(&mut self.state.rows) {
+            current.entry(raw).or_default().push(emitted);
+        }
+
+        let mut next = Vec::with_capacity(rows.len());
+        let mut added = vec![];
+        for row in rows {
+            let raw = canonical(&row);
+            match current.get_mut(&raw).and_then(|r| r.pop()) {
+                Some(emitted) => next.push((raw, emitted)),
+                None => {
+                    let emitted =
+                        Value::Object(self.with_version(row, version, refreshed_at)).to_string();
+                    added.push(emitted.clone());
+                    next.push((raw, emitted));
+                }
+            }
+        }
+
+        let removed: Vec<String> = current.into_values().flatten().collect();
+        self.state.rows = next;
+
+        if added.is_empty() && removed.is_empty() {
+            return Ok(());
+        }
+
+        // the whole diff is flushed as one batch, so downstream operators never see a mix
+        // of two versions
+        for row in &removed {
+            let message = format!(r#"{{"before":{},"after":null,"op":"d"}}"#, row);
+            ctx.deserialize_slice(message.as_bytes(), refreshed_at, None)
+                .await?;
+        }
+
+        for row in &added {
+            let message = format!(r#"{{"before":null,"after":{},"op":"c"}}"#, row);
+            ctx.deserialize_slice(message.as_bytes(), refreshed_at, None)
+                .await?;
+        }
+
+        ctx.flush_buffer().await?;
+
+        info!(
+            "loaded version {} of side input with {} rows ({} added, {} removed)",
+            version,
+            self.state.rows.len(),
+            added.len(),
+            removed.len()
+        );
+        self.state.version = version;
+
+        Ok(())
+    }
+
+    async fn run_int(&mut self, ctx: &mut ArrowContext) -> Result<SourceFinishType, UserError> {
+        ctx.initialize_deserializer(self.format.clone(), None, self.bad_data.clone());
+
+        let mut timer = tokio::time::interval(self.refresh_interval);
+        timer.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        loop {
+            select! {
+                _ = timer.tick() => {
+                    self.refresh(ctx).await?;
+                }
+                control_message = ctx.control_rx.recv() => {
+                    if let Some(r) = self.handle_control_message(ctx, control_message).await {
+                        return Ok(r);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::canonical;
+    use serde_json::json;
+
+    #[test]
+    fn test_canonical() {
+        let a = json!({"id": 1, "name": "a"});
+        let b = json!({"name": "a", "id": 1});
+
+        assert_eq!(
+            canonical(a.as_object().unwrap()),
+            canonical(b.as_object().unwrap())
+        );
+        assert_ne!(
+            canonical(a.as_object().unwrap()),
+            canonical(json!({"id": 2, "name": "a"}).as_object().unwrap())
+        );
+    }
+}
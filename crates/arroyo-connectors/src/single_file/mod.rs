@@ -77,6 +77,15 @@ impl Connector for SingleFileConnector {
         }
     }
 
+    fn max_source_parallelism(&self, _: Self::ProfileT, _: Self::TableT) -> Option<usize> {
+        // only the first subtask reads the file
+        Some(1)
+    }
+
+    fn is_bounded(&self, _: Self::ProfileT, table: Self::TableT) -> bool {
+        matches!(table.table_type, TableType::Source)
+    }
+
     fn from_config(
         &self,
         id: Option<i64>,
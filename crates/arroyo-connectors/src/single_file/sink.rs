@@ -31,13 +31,18 @@ impl ArrowOperator for SingleFileSink {
         arroyo_state::global_table_config("f", "file_sink")
     }
 
-    async fn process_batch(&mut self, batch: RecordBatch, _ctx: &mut ArrowContext) {
+    async fn process_batch(
+        &mut self,
+        batch: RecordBatch,
+        _ctx: &mut ArrowContext,
+    ) -> anyhow::Result<()> {
         let values = self.serializer.serialize(&batch);
         let file = self.file.as_mut().unwrap();
         for value in values {
             file.write_all(&value).await.unwrap();
             file.write_all(b"\n").await.unwrap();
         }
+        Ok(())
     }
 
     async fn on_start(&mut self, ctx: &mut ArrowContext) {
@@ -72,13 +77,23 @@ impl ArrowOperator for SingleFileSink {
         self.file = Some(file);
     }
 
-    async fn on_close(&mut self, final_message: &Option<SignalMessage>, _ctx: &mut ArrowContext) {
+    async fn on_close(
+        &mut self,
+        final_message: &Option<SignalMessage>,
+        _ctx: &mut ArrowContext,
+    ) -> anyhow::Result<()> {
         if let Some(SignalMessage::EndOfData) = final_message {
-            self.file.as_mut().unwrap().flush().await.unwrap();
+            self.file.as_mut().unwrap().flush().await?;
         }
+
+        Ok(())
     }
 
-    async fn handle_checkpoint(&mut self, _b: CheckpointBarrier, ctx: &mut ArrowContext) {
+    async fn handle_checkpoint(
+        &mut self,
+        _b: CheckpointBarrier,
+        ctx: &mut ArrowContext,
+    ) -> anyhow::Result<()> {
         self.file.as_mut().unwrap().flush().await.unwrap();
         let state = ctx.table_manager.get_global_keyed_state("f").await.unwrap();
         state
@@ -87,5 +102,6 @@ impl ArrowOperator for SingleFileSink {
                 self.file.as_ref().unwrap().metadata().await.unwrap().len(),
             )
             .await;
+        Ok(())
     }
 }
@@ -0,0 +1,349 @@
+//! Detects drift between the fields that a pipeline writes to a sink and the columns of the
+//! table it writes to, and decides how to handle it according to the table's drift mode
+
+use anyhow::bail;
+use arrow::datatypes::Field;
+use std::fmt::{Display, Formatter};
+
+/// A column of the target table, as read from its catalog
+#[derive(Debug, Clone)]
+pub struct TableColumn {
+    pub name: String,
+    pub type_name: String,
+    pub not_null: bool,
+    /// Whether the column is filled in by the database if it is not written, either from a
+    /// default, as an identity or as a generated column
+    pub has_default: bool,
+}
+
+/// How to handle differences between the pipeline and the table
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DriftMode {
+    /// Fail on any difference
+    Fail,
+    /// Allow types that can be converted without loss
+    Coerce,
+    /// Also add missing columns to the table
+    Evolve,
+}
+
+impl Display for DriftMode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            DriftMode::Fail => "fail",
+            DriftMode::Coerce => "coerce",
+            DriftMode::Evolve => "evolve",
+        })
+    }
+}
+
+/// How a sink's target names the types of its columns, and how it converts between them
+pub struct Dialect {
+    /// The type of the column that a field is written as; only the part before any
+    /// parameters (like `decimal` in `decimal(38,10)`) is compared with existing columns
+    pub column_type: fn(&Field) -> anyhow::Result<String>,
+    /// Whether values of type `from` are converted to a column of type `to` without loss
+    pub coercible: fn(&str, &str) -> bool,
+    /// The statement that adds a column of the given type to the table, if the sink can
+    /// evolve its tables
+    pub add_column: Option<fn(&str, &str, &str) -> String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Drift {
+    /// A field of the pipeline has no column in the table
+    Missing { column: String, type_name: String },
+    /// A field's type differs from its column's, but can be converted to it without loss
+    Coercible {
+        column: String,
+        from: String,
+        to: String,
+    },
+    /// A field's type can't be safely converted to its column's
+    Incompatible {
+        column: String,
+        from: String,
+        to: String,
+    },
+    /// A nullable field is written to a NOT NULL column
+    Nullable { column: String },
+    /// A NOT NULL column without a default is not written by the pipeline
+    Unwritten { column: String },
+}
+
+impl Display for Drift {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Drift::Missing { column, type_name } => write!(
+                f,
+                "+ column \"{}\" ({}) is written by the pipeline but does not exist in the table",
+                column, type_name
+            ),
+            Drift::Coercible { column, from, to } => write!(
+                f,
+                "~ column \"{}\" has type {} in the table, and will be converted from {}",
+                column, to, from
+            ),
+            Drift::Incompatible { column, from, to } => write!(
+                f,
+                "! column \"{}\" has type {} in the table, but the pipeline writes {}, which cannot be converted to it",
+                column, to, from
+            ),
+            Drift::Nullable { column } => write!(
+                f,
+                "~ column \"{}\" is NOT NULL in the table, but is nullable in the pipeline",
+                column
+            ),
+            Drift::Unwritten { column } => write!(
+                f,
+                "- column \"{}\" is NOT NULL with no default in the table, but is not written by the pipeline",
+                column
+            ),
+        }
+    }
+}
+
+impl Drift {
+    /// Whether the sink can write to the table despite this difference
+    fn allowed(&self, dialect: &Dialect, mode: DriftMode) -> bool {
+        match (self, mode) {
+            (_, DriftMode::Fail) => false,
+            (Drift::Coercible { .. } | Drift::Nullable { .. }, _) => true,
+            (Drift::Missing { .. }, DriftMode::Evolve) => dialect.add_column.is_some(),
+            _ => false,
+        }
+    }
+}
+
+/// The name of a type without its parameters or modifiers
+fn base_type(type_name: &str) -> &str {
+    type_name
+        .split(['(', ' '])
+        .next()
+        .unwrap_or(type_name)
+        .trim()
+}
+
+/// Compares the fields written by the pipeline with the columns of the table
+pub fn schema_drift(
+    dialect: &Dialect,
+    fields: &[Field],
+    columns: &[TableColumn],
+) -> anyhow::Result<Vec<Drift>> {
+    let mut drift = vec![];
+
+    for field in fields {
+        let type_name = (dialect.column_type)(field)?;
+
+        let Some(column) = columns.iter().find(|c| &c.name == field.name()) else {
+            drift.push(Drift::Missing {
+                column: field.name().clone(),
+                type_name,
+            });
+            continue;
+        };
+
+        let from = base_type(&type_name);
+        let to = base_type(&column.type_name);
+        if from != to {
+            if (dialect.coercible)(from, to) {
+                drift.push(Drift::Coercible {
+                    column: column.name.clone(),
+                    from: from.to_string(),
+                    to: column.type_name.clone(),
+                });
+            } else {
+                drift.push(Drift::Incompatible {
+                    column: column.name.clone(),
+                    from: from.to_string(),
+                    to: column.type_name.clone(),
+                });
+            }
+        }
+
+        if field.is_nullable() && column.not_null {
+            drift.push(Drift::Nullable {
+                column: column.name.clone(),
+            });
+        }
+    }
+
+    for column in columns {
+        if column.not_null
+            && !column.has_default
+            && !fields.iter().any(|f| f.name() == &column.name)
+        {
+            drift.push(Drift::Unwritten {
+                column: column.name.clone(),
+            });
+        }
+    }
+
+    Ok(drift)
+}
+
+/// Checks that the sink can write to `table` in the given mode, returning a description of
+/// all of the differences if it can't, or the statements needed to evolve the table if it can
+pub fn resolve_drift(
+    dialect: &Dialect,
+    table: &str,
+    mode: DriftMode,
+    drift: &[Drift],
+) -> anyhow::Result<Vec<String>> {
+    let disallowed: Vec<_> = drift.iter().filter(|d| !d.allowed(dialect, mode)).collect();
+    if !disallowed.is_empty() {
+        bail!(
+            "the schema of the pipeline does not match table {} (schema_drift = '{}'):\n{}",
+            table,
+            mode,
+            disallowed
+                .iter()
+                .map(|d| format!("  {}", d))
+                .collect::<Vec<_>>()
+                .join("\n")
+        );
+    }
+
+    Ok(drift
+        .iter()
+        .filter_map(|d| match d {
+            Drift::Missing { column, type_name } => dialect
+                .add_column
+                .map(|add_column| add_column(table, column, type_name)),
+            _ => None,
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::mysql::MYSQL;
+    use crate::postgres::POSTGRES;
+    use arrow::datatypes::DataType;
+
+    fn column(name: &str, type_name: &str, not_null: bool) -> TableColumn {
+        TableColumn {
+            name: name.to_string(),
+            type_name: type_name.to_string(),
+            not_null,
+            has_default: false,
+        }
+    }
+
+    #[test]
+    fn test_schema_drift() {
+        let fields = vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new("count", DataType::Int32, true),
+            Field::new("name", DataType::Utf8, true),
+            Field::new("score", DataType::Float64, true),
+        ];
+
+        let columns = vec![
+            column("id", "int8", true),
+            column("count", "int8", true),
+            column("name", "int4", false),
+            column("created_at", "timestamptz", true),
+        ];
+
+        let drift = schema_drift(&POSTGRES, &fields, &columns).unwrap();
+        assert_eq!(
+            drift,
+            vec![
+                Drift::Coercible {
+                    column: "count".to_string(),
+                    from: "int4".to_string(),
+                    to: "int8".to_string()
+                },
+                Drift::Nullable {
+                    column: "count".to_string()
+                },
+                Drift::Incompatible {
+                    column: "name".to_string(),
+                    from: "text".to_string(),
+                    to: "int4".to_string()
+                },
+                Drift::Missing {
+                    column: "score".to_string(),
+                    type_name: "float8".to_string()
+                },
+                Drift::Unwritten {
+                    column: "created_at".to_string()
+                },
+            ]
+        );
+
+        let err = resolve_drift(&POSTGRES, "\"t\"", DriftMode::Evolve, &drift)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("column \"name\""));
+        assert!(err.contains("column \"created_at\""));
+        assert!(!err.contains("column \"score\""));
+    }
+
+    #[test]
+    fn test_resolve_drift() {
+        let drift = vec![
+            Drift::Coercible {
+                column: "count".to_string(),
+                from: "int4".to_string(),
+                to: "int8".to_string(),
+            },
+            Drift::Missing {
+                column: "score".to_string(),
+                type_name: "float8".to_string(),
+            },
+        ];
+
+        assert!(resolve_drift(&POSTGRES, "\"t\"", DriftMode::Fail, &drift[..1]).is_err());
+        assert_eq!(
+            resolve_drift(&POSTGRES, "\"t\"", DriftMode::Coerce, &drift[..1]).unwrap(),
+            Vec::<String>::new()
+        );
+        assert!(resolve_drift(&POSTGRES, "\"t\"", DriftMode::Coerce, &drift).is_err());
+        assert_eq!(
+            resolve_drift(&POSTGRES, "\"t\"", DriftMode::Evolve, &drift).unwrap(),
+            vec!["ALTER TABLE \"t\" ADD COLUMN IF NOT EXISTS \"score\" float8".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_mysql_schema_drift() {
+        let fields = vec![
+            Field::new("id", DataType::Int32, false),
+            Field::new("price", DataType::Decimal128(38, 10), true),
+            Field::new(
+                "at",
+                DataType::Timestamp(arrow::datatypes::TimeUnit::Millisecond, None),
+                true,
+            ),
+        ];
+
+        let columns = vec![
+            column("id", "bigint", true),
+            column("price", "decimal", false),
+        ];
+
+        let drift = schema_drift(&MYSQL, &fields, &columns).unwrap();
+        assert_eq!(
+            drift,
+            vec![
+                Drift::Coercible {
+                    column: "id".to_string(),
+                    from: "int".to_string(),
+                    to: "bigint".to_string()
+                },
+                Drift::Missing {
+                    column: "at".to_string(),
+                    type_name: "datetime(6)".to_string()
+                },
+            ]
+        );
+
+        assert_eq!(
+            resolve_drift(&MYSQL, "`t`", DriftMode::Evolve, &drift).unwrap(),
+            vec!["ALTER TABLE `t` ADD COLUMN `at` datetime(6)".to_string()]
+        );
+    }
+}
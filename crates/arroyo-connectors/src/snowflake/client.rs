@@ -0,0 +1,407 @@
+use anyhow::{anyhow, bail, Context};
+use reqwest::header::{AUTHORIZATION, CONTENT_TYPE};
+use reqwest::{Client, RequestBuilder, Response};
+use rsa::pkcs1v15::SigningKey;
+use rsa::pkcs8::{DecodePrivateKey, EncodePublicKey};
+use rsa::signature::{SignatureEncoding, Signer};
+use rsa::RsaPrivateKey;
+use serde::Deserialize;
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::snowflake::SnowflakeConfig;
+
+const TOKEN_TYPE_HEADER: &str = "X-Snowflake-Authorization-Token-Type";
+
+/// How long the JWTs we sign are valid for; Snowflake rejects anything over an hour
+const JWT_LIFETIME: Duration = Duration::from_secs(59 * 60);
+
+/// How long we use a scoped ingest token before exchanging a fresh JWT for a new one
+const INGEST_TOKEN_LIFETIME: Duration = Duration::from_secs(30 * 60);
+
+/// The offset token attached to each append, which Snowflake stores with the channel once the
+/// rows are committed. The rows for an epoch are appended in chunks, so the token records both,
+/// which lets a restored sink skip whatever part of an epoch was already committed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct OffsetToken {
+    pub epoch: u32,
+    pub chunk: usize,
+}
+
+impl Display for OffsetToken {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}-{}", self.epoch, self.chunk)
+    }
+}
+
+impl FromStr for OffsetToken {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (epoch, chunk) = s
+            .split_once('-')
+            .ok_or_else(|| anyhow!("invalid offset token '{}'", s))?;
+        Ok(Self {
+            epoch: epoch
+                .parse()
+                .map_err(|_| anyhow!("invalid epoch in offset token '{}'", s))?,
+            chunk: chunk
+                .parse()
+                .map_err(|_| anyhow!("invalid chunk in offset token '{}'", s))?,
+        })
+    }
+}
+
+/// A channel that's been opened for writing
+pub struct Channel {
+    pub name: String,
+    pub continuation_token: String,
+    pub committed: Option<OffsetToken>,
+}
+
+#[derive(Deserialize)]
+struct OpenChannelResponse {
+    next_continuation_token: String,
+    #[serde(default)]
+    channel_status: Option<ChannelStatus>,
+}
+
+#[derive(Deserialize)]
+struct AppendRowsResponse {
+    next_continuation_token: String,
+}
+
+#[derive(Deserialize)]
+struct BulkChannelStatusResponse {
+    #[serde(default)]
+    channel_statuses: std::collections::HashMap<String, ChannelStatus>,
+}
+
+#[derive(Deserialize)]
+struct ChannelStatus {
+    #[serde(default, alias = "committed_offset_token")]
+    last_committed_offset_token: Option<String>,
+}
+
+struct IngestToken {
+    host: String,
+    token: String,
+    fetched: Instant,
+}
+
+/// A client for the Snowpipe Streaming REST API, authenticated with key-pair JWTs
+pub struct SnowflakeClient {
+    client: Client,
+    account_url: String,
+    /// `ACCOUNT.USER`, as it appears in the JWT claims
+    qualified_user: String,
+    public_key_fingerprint: String,
+    signing_key: SigningKey<Sha256>,
+    role: Option<String>,
+    ingest: Option<IngestToken>,
+}
+
+/// The account name used in JWT claims is the upper-cased account identifier, without any
+/// region or cloud suffix of a legacy account locator
+fn jwt_account(account: &str) -> String {
+    account.split('.').next().unwrap_or(account).to_uppercase()
+}
+
+impl SnowflakeClient {
+    pub fn new(config: &SnowflakeConfig) -> anyhow::Result<Self> {
+        // keys passed through environment variables often have their newlines escaped
+        let pem = config.private_key.sub_env_vars()?.replace("\\n", "\n");
+        let key = RsaPrivateKey::from_pkcs8_pem(pem.trim()).map_err(|e| {
+            anyhow!(
+                "invalid private key; expected an unencrypted PKCS#8 PEM key: {}",
+                e
+            )
+        })?;
+
+        let public_key = key
+            .to_public_key()
+            .to_public_key_der()
+            .map_err(|e| anyhow!("failed to encode public key: {}", e))?;
+
+        let account = config.account.trim();
+        if account.is_empty() {
+            bail!("account must not be empty");
+        }
+
+        Ok(Self {
+            client: reqwest::ClientBuilder::new()
+                .timeout(Duration::from_secs(60))
+                .build()
+                .map_err(|e| anyhow!("could not construct HTTP client: {:?}", e))?,
+            account_url: format!(
+                "https://{}.snowflakecomputing.com",
+                account.to_lowercase().replace('_', "-")
+            ),
+            qualified_user: format!(
+                "{}.{}",
+                jwt_account(account),
+                config.user.trim().to_uppercase()
+            ),
+            public_key_fingerprint: base64::encode(Sha256::digest(public_key.as_bytes())),
+            signing_key: SigningKey::<Sha256>::new(key),
+            role: config.role.clone(),
+            ingest: None,
+        })
+    }
+
+    fn jwt(&self) -> anyhow::Result<String> {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+
+        let header = json!({ "alg": "RS256", "typ": "JWT" });
+        let claims = json!({
+            "iss": format!("{}.SHA256:{}", self.qualified_user, self.public_key_fingerprint),
+            "sub": self.qualified_user,
+            "iat": now,
+            "exp": now + JWT_LIFETIME.as_secs(),
+        });
+
+        let message = format!(
+            "{}.{}",
+            base64::encode_config(header.to_string(), base64::URL_SAFE_NO_PAD),
+            base64::encode_config(claims.to_string(), base64::URL_SAFE_NO_PAD)
+        );
+
+        let signature = self.signing_key.sign(message.as_bytes()).to_bytes();
+
+        Ok(format!(
+            "{}.{}",
+            message,
+            base64::encode_config(signature, base64::URL_SAFE_NO_PAD)
+        ))
+    }
+
+    async fn send(request: RequestBuilder, action: &str) -> anyhow::Result<Response> {
+        let resp = request
+            .send()
+            .await
+            .with_context(|| format!("failed to {}", action))?;
+
+        if !resp.status().is_success() {
+            bail!(
+                "failed to {}; Snowflake responded with {}: {}",
+                action,
+                resp.status(),
+                resp.text().await.unwrap_or_default()
+            );
+        }
+
+        Ok(resp)
+    }
+
+    /// Looks up the ingest host for the account and exchanges a JWT for a token scoped to it
+    async fn fetch_ingest_token(&self) -> anyhow::Result<IngestToken> {
+        let jwt = self.jwt()?;
+
+        let host = Self::send(
+            self.client
+                .get(format!("{}/v2/streaming/hostname", self.account_url))
+                .header(AUTHORIZATION, format!("Bearer {}", jwt))
+                .header(TOKEN_TYPE_HEADER, "KEYPAIR_JWT"),
+            "look up the Snowpipe Streaming host",
+        )
+        .await?
+        .text()
+        .await?
+        .trim()
+        .trim_matches('"')
+        .to_string();
+
+        let scope = match &self.role {
+            Some(role) => format!("session:role:{} {}", role, host),
+            None => host.clone(),
+        };
+
+        let token = Self::send(
+            self.client
+                .post(format!("{}/oauth/token", self.account_url))
+                .header(AUTHORIZATION, format!("Bearer {}", jwt))
+                .form(&[
+                    ("grant_type", "urn:ietf:params:oauth:grant-type:jwt-bearer"),
+                    ("scope", &scope),
+                ]),
+            "exchange the key-pair JWT for an ingest token",
+        )
+        .await?
+        .text()
+        .await?;
+
+        Ok(IngestToken {
+            host,
+            token,
+            fetched: Instant::now(),
+        })
+    }
+
+    /// Authenticates with the account, returning the ingest host it streams through
+    pub async fn authenticate(&mut self) -> anyhow::Result<String> {
+        if self
+            .ingest
+            .as_ref()
+            .map(|t| t.fetched.elapsed() >= INGEST_TOKEN_LIFETIME)
+            .unwrap_or(true)
+        {
+            self.ingest = Some(self.fetch_ingest_token().await?);
+        }
+
+        Ok(self.ingest.as_ref().unwrap().host.clone())
+    }
+
+    async fn ingest_request(
+        &mut self,
+        method: reqwest::Method,
+        path: &str,
+    ) -> anyhow::Result<RequestBuilder> {
+        self.authenticate().await?;
+        let ingest = self.ingest.as_ref().unwrap();
+
+        Ok(self
+            .client
+            .request(
+                method,
+                format!("https://{}/v2/streaming{}", ingest.host, path),
+            )
+            .header(AUTHORIZATION, format!("Bearer {}", ingest.token))
+            .header(TOKEN_TYPE_HEADER, "OAUTH"))
+    }
+
+    /// Opens (or reopens) a channel, which invalidates the continuation tokens of any previous
+    /// opener, and returns the offset token of the last rows Snowflake committed on it
+    pub async fn open_channel(&mut self, pipe_path: &str, name: &str) -> anyhow::Result<Channel> {
+        let request = self
+            .ingest_request(
+                reqwest::Method::PUT,
+                &format!("{}/channels/{}", pipe_path, name),
+            )
+            .await?
+            .json(&json!({}));
+
+        let resp: OpenChannelResponse = Self::send(request, &format!("open channel {}", name))
+            .await?
+            .json()
+            .await
+            .map_err(|e| anyhow!("invalid response when opening channel {}: {}", name, e))?;
+
+        Ok(Channel {
+            name: name.to_string(),
+            continuation_token: resp.next_continuation_token,
+            committed: Self::parse_committed(
+                name,
+                resp.channel_status
+                    .and_then(|s| s.last_committed_offset_token),
+            )?,
+        })
+    }
+
+    fn parse_committed(name: &str, token: Option<String>) -> anyhow::Result<Option<OffsetToken>> {
+        token
+            .map(|t| {
+                t.parse().with_context(|| {
+                    format!(
+                        "channel {} was last written by something other than this sink",
+                        name
+                    )
+                })
+            })
+            .transpose()
+    }
+
+    /// Appends a newline-delimited JSON body of rows to the channel, advancing its
+    /// continuation token
+    pub async fn append_rows(
+        &mut self,
+        pipe_path: &str,
+        channel: &mut Channel,
+        offset_token: OffsetToken,
+        rows: Vec<u8>,
+    ) -> anyhow::Result<()> {
+        let request = self
+            .ingest_request(
+                reqwest::Method::POST,
+                &format!("{}/channels/{}/rows", pipe_path, channel.name),
+            )
+            .await?
+            .query(&[
+                ("continuationToken", channel.continuation_token.clone()),
+                ("offsetToken", offset_token.to_string()),
+            ])
+            .header(CONTENT_TYPE, "application/x-ndjson")
+            .body(rows);
+
+        let resp: AppendRowsResponse =
+            Self::send(request, &format!("append rows to channel {}", channel.name))
+                .await?
+                .json()
+                .await
+                .map_err(|e| {
+                    anyhow!(
+                        "invalid response when appending to channel {}: {}",
+                        channel.name,
+                        e
+                    )
+                })?;
+
+        channel.continuation_token = resp.next_continuation_token;
+        Ok(())
+    }
+
+    /// Returns the offset token of the last rows that were committed to the table
+    pub async fn committed_offset_token(
+        &mut self,
+        pipe_path: &str,
+        channel: &str,
+    ) -> anyhow::Result<Option<OffsetToken>> {
+        let request = self
+            .ingest_request(
+                reqwest::Method::POST,
+                &format!("{}:bulk-channel-status", pipe_path),
+            )
+            .await?
+            .json(&json!({ "channel_names": [channel] }));
+
+        let mut resp: BulkChannelStatusResponse =
+            Self::send(request, &format!("get the status of channel {}", channel))
+                .await?
+                .json()
+                .await
+                .map_err(|e| anyhow!("invalid channel status response: {}", e))?;
+
+        Self::parse_committed(
+            channel,
+            resp.channel_statuses
+                .remove(channel)
+                .and_then(|s| s.last_committed_offset_token),
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_offset_token() {
+        let token = OffsetToken { epoch: 7, chunk: 2 };
+        assert_eq!(token.to_string(), "7-2");
+        assert_eq!("7-2".parse::<OffsetToken>().unwrap(), token);
+
+        assert!(token < OffsetToken { epoch: 7, chunk: 3 });
+        assert!(token < OffsetToken { epoch: 8, chunk: 0 });
+
+        assert!("7".parse::<OffsetToken>().is_err());
+        assert!("a-1".parse::<OffsetToken>().is_err());
+    }
+
+    #[test]
+    fn test_jwt_account() {
+        assert_eq!(jwt_account("myorg-myaccount"), "MYORG-MYACCOUNT");
+        assert_eq!(jwt_account("xy12345.us-east-1"), "XY12345");
+    }
+}
@@ -0,0 +1,262 @@
+mod client;
+mod sink;
+
+use anyhow::{anyhow, bail};
+use arroyo_operator::connector::{Connection, Connector};
+use arroyo_operator::operator::OperatorNode;
+use arroyo_rpc::api_types::connections::{
+    ConnectionProfile, ConnectionSchema, ConnectionType, TestSourceMessage,
+};
+use arroyo_rpc::formats::{Format, JsonFormat};
+use arroyo_rpc::var_str::VarStr;
+use arroyo_rpc::OperatorConfig;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use tokio::sync::oneshot::Receiver;
+use typify::import_types;
+
+use crate::pull_opt;
+use crate::snowflake::client::SnowflakeClient;
+use crate::snowflake::sink::SnowflakeSinkFunc;
+
+const CONFIG_SCHEMA: &str = include_str!("./profile.json");
+const TABLE_SCHEMA: &str = include_str!("./table.json");
+
+import_types!(
+    schema = "src/snowflake/profile.json",
+    convert = {
+        {type = "string", format = "var-str"} = VarStr
+    }
+);
+
+import_types!(schema = "src/snowflake/table.json");
+
+pub struct SnowflakeConnector {}
+
+async fn test_inner(
+    config: &SnowflakeConfig,
+    table: Option<&SnowflakeTable>,
+) -> anyhow::Result<String> {
+    if let Some(table) = table {
+        validate_table(table)?;
+    }
+
+    let mut client = SnowflakeClient::new(config)?;
+    let host = client.authenticate().await?;
+
+    Ok(format!(
+        "Successfully authenticated as {} with ingest host {}",
+        config.user, host
+    ))
+}
+
+/// Checks that an identifier can be used in a Snowpipe Streaming URL
+fn validate_identifier(kind: &str, value: &str) -> anyhow::Result<()> {
+    if value.is_empty() {
+        bail!("{} must not be empty", kind);
+    }
+
+    if value.contains(['/', '?', '#', ':']) || value.chars().any(char::is_whitespace) {
+        bail!("{} '{}' contains invalid characters", kind, value);
+    }
+
+    Ok(())
+}
+
+fn validate_table(table: &SnowflakeTable) -> anyhow::Result<()> {
+    validate_identifier("database", &table.database)?;
+    validate_identifier("schema", &table.schema)?;
+    validate_identifier("table", &table.table)?;
+
+    if let Some(pipe) = &table.pipe {
+        validate_identifier("pipe", pipe)?;
+    }
+
+    if let Some(prefix) = &table.channel_prefix {
+        validate_identifier("channel_prefix", prefix)?;
+    }
+
+    Ok(())
+}
+
+impl Connector for SnowflakeConnector {
+    type ProfileT = SnowflakeConfig;
+    type TableT = SnowflakeTable;
+
+    fn name(&self) -> &'static str {
+        "snowflake"
+    }
+
+    fn metadata(&self) -> arroyo_rpc::api_types::connections::Connector {
+        arroyo_rpc::api_types::connections::Connector {
+            id: "snowflake".to_string(),
+            name: "Snowflake".to_string(),
+            icon: "".to_string(),
+            description: "Write results to Snowflake tables with Snowpipe Streaming".to_string(),
+            enabled: true,
+            source: false,
+            sink: true,
+            testing: true,
+            hidden: false,
+            custom_schemas: true,
+            connection_config: Some(CONFIG_SCHEMA.to_owned()),
+            table_config: TABLE_SCHEMA.to_owned(),
+        }
+    }
+
+    fn config_description(&self, config: Self::ProfileT) -> String {
+        format!("{}@{}", config.user, config.account)
+    }
+
+    fn table_type(&self, _: Self::ProfileT, _: Self::TableT) -> ConnectionType {
+        ConnectionType::Sink
+    }
+
+    fn get_schema(
+        &self,
+        _: Self::ProfileT,
+        _: Self::TableT,
+        s: Option<&ConnectionSchema>,
+    ) -> Option<ConnectionSchema> {
+        s.cloned()
+    }
+
+    fn test_profile(&self, profile: Self::ProfileT) -> Option<Receiver<TestSourceMessage>> {
+        let (tx, rx) = tokio::sync::oneshot::channel();
+
+        tokio::spawn(async move {
+            let message = match test_inner(&profile, None).await {
+                Ok(m) => TestSourceMessage::done(m),
+                Err(e) => {
+                    TestSourceMessage::fail(format!("Failed to connect to Snowflake: {:#}", e))
+                }
+            };
+
+            tx.send(message).unwrap();
+        });
+
+        Some(rx)
+    }
+
+    fn test(
+        &self,
+        _: &str,
+        config: Self::ProfileT,
+        table: Self::TableT,
+        _: Option<&ConnectionSchema>,
+        tx: tokio::sync::mpsc::Sender<TestSourceMessage>,
+    ) {
+        tokio::task::spawn(async move {
+            let message = match test_inner(&config, Some(&table)).await {
+                Ok(m) => TestSourceMessage::done(m),
+                Err(e) => TestSourceMessage::fail(format!("{:#}", e)),
+            };
+
+            tx.send(message).await.unwrap();
+        });
+    }
+
+    fn from_options(
+        &self,
+        name: &str,
+        options: &mut HashMap<String, String>,
+        schema: Option<&ConnectionSchema>,
+        profile: Option<&ConnectionProfile>,
+    ) -> anyhow::Result<Connection> {
+        let config = match profile {
+            Some(p) => serde_json::from_value(p.config.clone())
+                .map_err(|e| anyhow!("invalid config for profile '{}' in database: {}", p.id, e))?,
+            None => SnowflakeConfig {
+                account: pull_opt("account", options)?,
+                user: pull_opt("user", options)?,
+                private_key: VarStr::new(pull_opt("private_key", options)?),
+                role: options.remove("role"),
+            },
+        };
+
+        let table = SnowflakeTable {
+            database: pull_opt("database", options)?,
+            schema: pull_opt("schema", options)?,
+            table: pull_opt("table", options)?,
+            pipe: options.remove("pipe"),
+            channel_prefix: options.remove("channel_prefix"),
+        };
+
+        self.from_config(None, name, config, table, schema)
+    }
+
+    fn from_config(
+        &self,
+        id: Option<i64>,
+        name: &str,
+        config: Self::ProfileT,
+        table: Self::TableT,
+        schema: Option<&ConnectionSchema>,
+    ) -> anyhow::Result<Connection> {
+        validate_identifier("account", &config.account)?;
+
+        let mut schema = schema
+            .map(|s| s.to_owned())
+            .ok_or_else(|| anyhow!("no schema defined for Snowflake sink"))?;
+
+        validate_table(&table)?;
+
+        let format = schema
+            .format
+            .clone()
+            .unwrap_or_else(|| Format::Json(JsonFormat::default()));
+
+        match &format {
+            Format::Json(JsonFormat {
+                debezium: false,
+                include_schema: false,
+                ..
+            }) => {}
+            _ => bail!("Snowflake sinks only support the json format"),
+        }
+
+        schema.format = Some(format.clone());
+
+        let description = format!(
+            "SnowflakeSink<{}.{}.{}>",
+            table.database, table.schema, table.table
+        );
+
+        let config = OperatorConfig {
+            connection: serde_json::to_value(config).unwrap(),
+            table: serde_json::to_value(table).unwrap(),
+            rate_limit: None,
+            format: Some(format),
+            bad_data: schema.bad_data.clone(),
+            framing: None,
+            metadata_fields: vec![],
+        };
+
+        Ok(Connection {
+            id,
+            connector: self.name(),
+            name: name.to_string(),
+            connection_type: ConnectionType::Sink,
+            schema,
+            config: serde_json::to_string(&config).unwrap(),
+            description,
+        })
+    }
+
+    fn make_operator(
+        &self,
+        profile: Self::ProfileT,
+        table: Self::TableT,
+        config: OperatorConfig,
+    ) -> anyhow::Result<OperatorNode> {
+        Ok(OperatorNode::from_operator(Box::new(
+            SnowflakeSinkFunc::new(
+                SnowflakeClient::new(&profile)?,
+                table,
+                config
+                    .format
+                    .expect("No format configured for Snowflake sink"),
+            ),
+        )))
+    }
+}
@@ -0,0 +1,525 @@
+use anyhow::{bail, Context};
+use arrow::array::RecordBatch;
+use arroyo_formats::ser::ArrowSerializer;
+use arroyo_operator::context::ArrowContext;
+use arroyo_operator::operator::{ArrowOperator, AsDisplayable, DisplayableOperator};
+use arroyo_rpc::formats::Format;
+use arroyo_rpc::grpc::rpc::{GlobalKeyedTableConfig, TableConfig, TableEnum};
+use arroyo_rpc::{CheckpointEvent, ControlMessage, ControlResp};
+use arroyo_state::tables::global_keyed_map::GlobalKeyedView;
+use arroyo_types::{single_item_hash_map, CheckpointBarrier, SignalMessage};
+use async_trait::async_trait;
+use bincode::{Decode, Encode};
+use prost::Message;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+use std::time::{Duration, Instant, SystemTime};
+use tracing::{info, warn};
+
+use crate::snowflake::client::{Channel, OffsetToken, SnowflakeClient};
+use crate::snowflake::SnowflakeTable;
+
+/// Appends are limited to 16MB, so the rows of an epoch are split into chunks well under that
+const MAX_CHUNK_BYTES: usize = 4 * 1024 * 1024;
+
+const MAX_APPEND_ATTEMPTS: u32 = 5;
+
+/// How long we wait for appended rows to be committed to the table before failing the commit
+const COMMIT_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// The rows written during a checkpoint epoch, which are appended to the channel once the
+/// checkpoint is committed
+#[derive(Debug, Clone, Encode, Decode)]
+pub struct PendingEpoch {
+    epoch: u32,
+    /// newline-delimited JSON bodies, each no larger than [MAX_CHUNK_BYTES]
+    chunks: Vec<Vec<u8>>,
+}
+
+pub struct SnowflakeSinkFunc {
+    client: SnowflakeClient,
+    table: SnowflakeTable,
+    pipe_path: String,
+    serializer: ArrowSerializer,
+    /// rows written since the last checkpoint
+    chunks: Vec<Vec<u8>>,
+    /// epochs that have been checkpointed but not committed, by channel index. Besides this
+    /// subtask's own channel, this contains the channels of subtasks that no longer exist after a
+    /// restore at lower parallelism.
+    pending: BTreeMap<usize, Vec<PendingEpoch>>,
+    channels: HashMap<usize, Channel>,
+}
+
+impl SnowflakeSinkFunc {
+    pub fn new(client: SnowflakeClient, table: SnowflakeTable, format: Format) -> Self {
+        let pipe = table
+            .pipe
+            .clone()
+            .unwrap_or_else(|| format!("{}-STREAMING", table.table.to_uppercase()));
+
+        Self {
+            client,
+            pipe_path: format!(
+                "/databases/{}/schemas/{}/pipes/{}",
+                table.database, table.schema, pipe
+            ),
+            table,
+            serializer: ArrowSerializer::new(format),
+            chunks: vec![],
+            pending: BTreeMap::new(),
+            channels: HashMap::new(),
+        }
+    }
+
+    fn channel_name(&self, ctx: &ArrowContext, index: usize) -> String {
+        match &self.table.channel_prefix {
+            Some(prefix) => format!("{}_{}", prefix, index),
+            None => format!(
+                "arroyo_{}_{}_{}",
+                ctx.task_info.job_id, ctx.task_info.operator_id, index
+            ),
+        }
+    }
+
+    async fn channel(
+        &mut self,
+        ctx: &mut ArrowContext,
+        index: usize,
+    ) -> anyhow::Result<&mut Channel> {
+        if !self.channels.contains_key(&index) {
+            let name = self.channel_name(ctx, index);
+            let channel = self
+                .client
+                .open_channel(&self.pipe_path, &name)
+                .await
+                .with_context(|| format!("failed to open Snowflake channel {}", name))?;
+
+            info!(
+                "opened Snowflake channel {} with committed offset {:?}",
+                name,
+                channel.committed.map(|t| t.to_string())
+            );
+            self.channels.insert(index, channel);
+        }
+
+        Ok(self.channels.get_mut(&index).unwrap())
+    }
+
+    async fn append(
+        &mut self,
+        ctx: &mut ArrowContext,
+        index: usize,
+        offset_token: OffsetToken,
+        rows: &[u8],
+    ) -> anyhow::Result<()> {
+        let mut attempts = 0;
+
+        loop {
+            self.channel(ctx, index).await?;
+            let channel = self.channels.get_mut(&index).unwrap();
+
+            let result = self
+                .client
+                .append_rows(&self.pipe_path, channel, offset_token, rows.to_vec())
+                .await;
+
+            let Err(e) = result else {
+                return Ok(());
+            };
+
+            attempts += 1;
+            if attempts >= MAX_APPEND_ATTEMPTS {
+                return Err(e.context(format!(
+                    "failed to append to Snowflake channel {} after {} attempts",
+                    channel.name, attempts
+                )));
+            }
+
+            ctx.report_error("Failed to append rows to Snowflake", e.to_string())
+                .await;
+            tokio::time::sleep(append_backoff(attempts)).await;
+        }
+    }
+
+    /// Waits until Snowflake has committed the rows with the given offset token
+    async fn wait_for_commit(
+        &mut self,
+        ctx: &mut ArrowContext,
+        index: usize,
+        token: OffsetToken,
+    ) -> anyhow::Result<()> {
+        let name = self.channel(ctx, index).await?.name.clone();
+        let start = Instant::now();
+
+        loop {
+            match self
+                .client
+                .committed_offset_token(&self.pipe_path, &name)
+                .await
+            {
+                Ok(Some(committed)) if committed >= token => {
+                    if let Some(channel) = self.channels.get_mut(&index) {
+                        channel.committed = Some(committed);
+                    }
+                    return Ok(());
+                }
+                Ok(_) => {}
+                Err(e) => warn!(
+                    "failed to get status of Snowflake channel {}: {:?}",
+                    name, e
+                ),
+            }
+
+            if start.elapsed() > COMMIT_TIMEOUT {
+                bail!(
+                    "rows appended to Snowflake channel {} with offset token {} were not committed within {:?}",
+                    name, token, COMMIT_TIMEOUT
+                );
+            }
+
+            tokio::time::sleep(Duration::from_millis(500)).await;
+        }
+    }
+
+    /// Appends the chunks of every pending epoch up to `epoch` that the channel hasn't already
+    /// committed, and waits for them to be committed
+    async fn commit_channel(
+        &mut self,
+        ctx: &mut ArrowContext,
+        index: usize,
+        epoch: u32,
+    ) -> anyhow::Result<()> {
+        let committed = self.channel(ctx, index).await?.committed;
+        let appends: Vec<_> = self
+            .pending
+            .get(&index)
+            .map(|p| {
+                to_append(p, epoch, committed)
+                    .map(|(token, rows)| (token, rows.to_vec()))
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let last = appends.last().map(|(token, _)| *token);
+        for (token, rows) in appends {
+            self.append(ctx, index, token, &rows).await?;
+        }
+
+        if let Some(token) = last {
+            self.wait_for_commit(ctx, index, token).await?;
+        }
+
+        if let Some(pending) = self.pending.get_mut(&index) {
+            pending.retain(|p| p.epoch > epoch);
+        }
+
+        Ok(())
+    }
+
+    fn add_batch(&mut self, batch: &RecordBatch) {
+        for row in self.serializer.serialize(batch) {
+            push_row(&mut self.chunks, row);
+        }
+    }
+}
+
+/// How long to wait before retrying an append that has failed `attempts` times
+fn append_backoff(attempts: u32) -> Duration {
+    Duration::from_millis(500 * (1 << attempts))
+}
+
+/// Adds a serialized row to the last chunk, or starts a new one if it would grow past
+/// [MAX_CHUNK_BYTES]
+fn push_row(chunks: &mut Vec<Vec<u8>>, row: Vec<u8>) {
+    match chunks.last_mut() {
+        Some(chunk) if chunk.len() + row.len() < MAX_CHUNK_BYTES => {
+            chunk.extend_from_slice(&row);
+            chunk.push(b'\n');
+        }
+        _ => {
+            let mut chunk = row;
+            chunk.push(b'\n');
+            chunks.push(chunk);
+        }
+    }
+}
+
+/// The chunks of the pending epochs up to `epoch` that still need to be appended to a channel
+/// that has committed up to `committed`, with their offset tokens
+fn to_append(
+    pending: &[PendingEpoch],
+    epoch: u32,
+    committed: Option<OffsetToken>,
+) -> impl Iterator<Item = (OffsetToken, &[u8])> {
+    pending
+        .iter()
+        .filter(move |p| p.epoch <= epoch)
+        .flat_map(|p| {
+            p.chunks.iter().enumerate().map(move |(chunk, rows)| {
+                (
+                    OffsetToken {
+                        epoch: p.epoch,
+                        chunk,
+                    },
+                    rows.as_slice(),
+                )
+            })
+        })
+        // after a restore, some or all of these may have been committed before the failure, and
+        // appending them again would duplicate them
+        .filter(move |(token, _)| !committed.is_some_and(|c| c >= *token))
+}
+
+/// The channels whose pending rows this subtask is responsible for after a restore: its own,
+/// plus any with rows left over from subtasks that no longer exist at this parallelism
+fn owned_channels(
+    state: impl IntoIterator<Item = (usize, Vec<PendingEpoch>)>,
+    task_index: usize,
+    parallelism: usize,
+) -> BTreeMap<usize, Vec<PendingEpoch>> {
+    state
+        .into_iter()
+        .filter(|(index, epochs)| {
+            *index % parallelism == task_index && (*index == task_index || !epochs.is_empty())
+        })
+        .collect()
+}
+
+#[async_trait]
+impl ArrowOperator for SnowflakeSinkFunc {
+    fn name(&self) -> String {
+        "SnowflakeSink".to_string()
+    }
+
+    fn display(&self) -> DisplayableOperator {
+        DisplayableOperator {
+            name: Cow::Borrowed("SnowflakeSink"),
+            fields: vec![
+                ("database", AsDisplayable::Str(&self.table.database)),
+                ("schema", AsDisplayable::Str(&self.table.schema)),
+                ("table", AsDisplayable::Str(&self.table.table)),
+                ("pipe_path", AsDisplayable::Str(&self.pipe_path)),
+            ],
+        }
+    }
+
+    fn tables(&self) -> HashMap<String, TableConfig> {
+        single_item_hash_map(
+            "p".to_string(),
+            TableConfig {
+                table_type: TableEnum::GlobalKeyValue.into(),
+                config: GlobalKeyedTableConfig {
+                    table_name: "p".to_string(),
+                    description: "rows waiting to be appended to each channel".to_string(),
+                    uses_two_phase_commit: true,
+                    ttl_micros: None,
+                }
+                .encode_to_vec(),
+            },
+        )
+    }
+
+    async fn on_start(&mut self, ctx: &mut ArrowContext) {
+        let state: &mut GlobalKeyedView<usize, Vec<PendingEpoch>> = ctx
+            .table_manager
+            .get_global_keyed_state("p")
+            .await
+            .expect("should be able to get table");
+
+        // each channel is owned by a single subtask; if we've been restored at a lower
+        // parallelism, the pending rows of the channels that no longer have a subtask are
+        // finished by the subtasks they map to
+        let task_index = ctx.task_info.task_index;
+        self.pending = owned_channels(
+            state
+                .get_all()
+                .iter()
+                .map(|(index, epochs)| (*index, epochs.clone())),
+            task_index,
+            ctx.task_info.parallelism,
+        );
+
+        // open our channel up front, which also fences off any writer left from a previous run;
+        // if that fails, it's opened again (or the task fails) when the first epoch is committed
+        if let Err(e) = self.channel(ctx, task_index).await {
+            warn!("{:?}", e);
+            ctx.report_error("Failed to open Snowflake channel", format!("{:?}", e))
+                .await;
+        }
+    }
+
+    async fn process_batch(
+        &mut self,
+        batch: RecordBatch,
+        _: &mut ArrowContext,
+    ) -> anyhow::Result<()> {
+        self.add_batch(&batch);
+        Ok(())
+    }
+
+    async fn handle_checkpoint(
+        &mut self,
+        barrier: CheckpointBarrier,
+        ctx: &mut ArrowContext,
+    ) -> anyhow::Result<()> {
+        let chunks = std::mem::take(&mut self.chunks);
+        let own = self.pending.entry(ctx.task_info.task_index).or_default();
+        if !chunks.is_empty() {
+            own.push(PendingEpoch {
+                epoch: barrier.epoch,
+                chunks,
+            });
+        }
+
+        let state: &mut GlobalKeyedView<usize, Vec<PendingEpoch>> = ctx
+            .table_manager
+            .get_global_keyed_state("p")
+            .await
+            .expect("should be able to get table");
+
+        for (index, epochs) in &self.pending {
+            state.insert(*index, epochs.clone()).await;
+        }
+        Ok(())
+    }
+
+    async fn handle_commit(
+        &mut self,
+        epoch: u32,
+        _commit_data: &HashMap<String, HashMap<u32, Vec<u8>>>,
+        ctx: &mut ArrowContext,
+    ) -> anyhow::Result<()> {
+        let indices: Vec<_> = self.pending.keys().copied().collect();
+        for index in indices {
+            self.commit_channel(ctx, index, epoch).await?;
+        }
+
+        // channels inherited from other subtasks aren't written to again once they're committed
+        let task_index = ctx.task_info.task_index;
+        self.pending
+            .retain(|index, epochs| *index == task_index || !epochs.is_empty());
+        self.channels.retain(|index, _| *index == task_index);
+
+        let checkpoint_event = ControlResp::CheckpointEvent(CheckpointEvent {
+            checkpoint_epoch: epoch,
+            operator_id: ctx.task_info.operator_id.clone(),
+            subtask_index: ctx.task_info.task_index as u32,
+            time: SystemTime::now(),
+            event_type: arroyo_rpc::grpc::rpc::TaskCheckpointEventType::FinishedCommit,
+        });
+        ctx.control_tx
+            .send(checkpoint_event)
+            .await
+            .expect("sent commit event");
+        Ok(())
+    }
+
+    async fn on_close(
+        &mut self,
+        _: &Option<SignalMessage>,
+        ctx: &mut ArrowContext,
+    ) -> anyhow::Result<()> {
+        if let Some(ControlMessage::Commit { epoch, commit_data }) = ctx.control_rx.recv().await {
+            self.handle_commit(epoch, &commit_data, ctx).await?;
+        } else {
+            warn!("no commit message received, not committing")
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn pending(epoch: u32, chunks: usize) -> PendingEpoch {
+        PendingEpoch {
+            epoch,
+            chunks: (0..chunks).map(|c| vec![c as u8]).collect(),
+        }
+    }
+
+    fn tokens<'a>(appends: impl Iterator<Item = (OffsetToken, &'a [u8])>) -> Vec<String> {
+        appends.map(|(token, _)| token.to_string()).collect()
+    }
+
+    #[test]
+    fn test_push_row() {
+        let mut chunks = vec![];
+        push_row(&mut chunks, b"{\"a\":1}".to_vec());
+        push_row(&mut chunks, b"{\"a\":2}".to_vec());
+        assert_eq!(chunks, vec![b"{\"a\":1}\n{\"a\":2}\n".to_vec()]);
+
+        // a row that would take the chunk over the limit starts a new one
+        push_row(&mut chunks, vec![b'x'; MAX_CHUNK_BYTES - 8]);
+        push_row(&mut chunks, b"{\"a\":3}".to_vec());
+        assert_eq!(chunks.len(), 3);
+        assert!(chunks.iter().all(|c| c.len() <= MAX_CHUNK_BYTES));
+        assert_eq!(chunks[2], b"{\"a\":3}\n".to_vec());
+    }
+
+    #[test]
+    fn test_to_append() {
+        let epochs = vec![pending(1, 2), pending(2, 3), pending(3, 1)];
+
+        assert_eq!(
+            tokens(to_append(&epochs, 2, None)),
+            vec!["1-0", "1-1", "2-0", "2-1", "2-2"]
+        );
+
+        // chunks the channel committed before a failure aren't appended again
+        assert_eq!(
+            tokens(to_append(
+                &epochs,
+                3,
+                Some(OffsetToken { epoch: 2, chunk: 1 })
+            )),
+            vec!["2-2", "3-0"]
+        );
+
+        assert!(tokens(to_append(
+            &epochs,
+            3,
+            Some(OffsetToken { epoch: 3, chunk: 0 })
+        ))
+        .is_empty());
+
+        let (token, rows) = to_append(&epochs, 1, None).nth(1).unwrap();
+        assert_eq!(token, OffsetToken { epoch: 1, chunk: 1 });
+        assert_eq!(rows, &[1]);
+    }
+
+    #[test]
+    fn test_owned_channels() {
+        let state = vec![
+            (0, vec![]),
+            (1, vec![]),
+            (2, vec![pending(4, 1)]),
+            (3, vec![]),
+            (4, vec![pending(4, 2)]),
+        ];
+
+        // subtask 0 of 2 finishes the pending rows of channels 2 and 4, but doesn't pick up
+        // channels that have nothing left to commit
+        let owned = owned_channels(state.clone(), 0, 2);
+        assert_eq!(owned.keys().copied().collect::<Vec<_>>(), vec![0, 2, 4]);
+        assert!(owned[&0].is_empty());
+
+        let owned = owned_channels(state.clone(), 1, 2);
+        assert_eq!(owned.keys().copied().collect::<Vec<_>>(), vec![1]);
+
+        // at the same parallelism, each subtask keeps just its own channel
+        let owned = owned_channels(state, 4, 5);
+        assert_eq!(owned.keys().copied().collect::<Vec<_>>(), vec![4]);
+        assert_eq!(owned[&4][0].chunks.len(), 2);
+    }
+
+    #[test]
+    fn test_append_backoff() {
+        let backoffs: Vec<_> = (1..MAX_APPEND_ATTEMPTS).map(append_backoff).collect();
+        assert_eq!(backoffs[0], Duration::from_secs(1));
+        assert!(backoffs.windows(2).all(|w| w[0] < w[1]));
+        assert!(backoffs.iter().sum::<Duration>() < COMMIT_TIMEOUT);
+    }
+}
@@ -0,0 +1,283 @@
+mod sink;
+
+use anyhow::{anyhow, bail, Context};
+use arroyo_formats::ser::ArrowSerializer;
+use arroyo_operator::connector::{Connection, Connector};
+use arroyo_operator::operator::OperatorNode;
+use arroyo_rpc::api_types::connections::{
+    ConnectionProfile, ConnectionSchema, ConnectionType, TestSourceMessage,
+};
+use arroyo_rpc::formats::{Format, JsonFormat};
+use arroyo_rpc::OperatorConfig;
+use aws_config::{from_env, Region, SdkConfig};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use typify::import_types;
+
+use crate::pull_opt;
+use crate::sns::sink::SnsSinkFunc;
+use crate::EmptyConfig;
+
+const TABLE_SCHEMA: &str = include_str!("./table.json");
+
+import_types!(schema = "src/sns/table.json");
+
+/// SNS allows at most this many attributes on a message
+const MAX_ATTRIBUTES: usize = 10;
+
+pub struct SnsConnector {}
+
+pub(crate) async fn load_aws_config(aws_region: Option<&String>) -> SdkConfig {
+    let mut loader = from_env();
+    if let Some(region) = aws_region {
+        loader = loader.region(Region::new(region.clone()));
+    }
+    loader.load().await
+}
+
+fn validate_table(table: &SnsTable, schema: Option<&ConnectionSchema>) -> anyhow::Result<()> {
+    let check_field = |option: &str, field: &String| {
+        if let Some(schema) = schema {
+            if !schema.fields.iter().any(|f| &f.field_name == field) {
+                bail!("{} '{}' is not a field of the table", option, field);
+            }
+        }
+        Ok(())
+    };
+
+    match &table.destination {
+        Destination::Topic {
+            attribute_fields,
+            message_group_id_field,
+            ..
+        } => {
+            if attribute_fields.len() > MAX_ATTRIBUTES {
+                bail!(
+                    "SNS messages can have at most {} attributes, but {} attribute_fields were given",
+                    MAX_ATTRIBUTES,
+                    attribute_fields.len()
+                );
+            }
+
+            for field in attribute_fields {
+                check_field("attribute_fields", field)?;
+
+                let lower = field.to_lowercase();
+                if lower.starts_with("aws.") || lower.starts_with("amazon.") {
+                    bail!(
+                        "attribute field '{}' can't be used, as attribute names starting with 'AWS.' or 'Amazon.' are reserved",
+                        field
+                    );
+                }
+            }
+
+            if let Some(field) = message_group_id_field {
+                check_field("message_group_id_field", field)?;
+            }
+        }
+        Destination::EventBus {
+            detail_type_field, ..
+        } => {
+            if let Some(field) = detail_type_field {
+                check_field("detail_type_field", field)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+async fn test_inner(table: &SnsTable) -> anyhow::Result<String> {
+    let config = load_aws_config(table.aws_region.as_ref()).await;
+
+    match &table.destination {
+        Destination::Topic { topic_arn, .. } => {
+            aws_sdk_sns::Client::new(&config)
+                .get_topic_attributes()
+                .topic_arn(topic_arn)
+                .send()
+                .await
+                .with_context(|| format!("failed to read attributes of topic {}", topic_arn))?;
+
+            Ok("Successfully connected to topic".to_string())
+        }
+        Destination::EventBus { event_bus_name, .. } => {
+            aws_sdk_eventbridge::Client::new(&config)
+                .describe_event_bus()
+                .name(event_bus_name)
+                .send()
+                .await
+                .with_context(|| format!("failed to describe event bus {}", event_bus_name))?;
+
+            Ok("Successfully connected to event bus".to_string())
+        }
+    }
+}
+
+impl Connector for SnsConnector {
+    type ProfileT = EmptyConfig;
+    type TableT = SnsTable;
+
+    fn name(&self) -> &'static str {
+        "sns"
+    }
+
+    fn metadata(&self) -> arroyo_rpc::api_types::connections::Connector {
+        arroyo_rpc::api_types::connections::Connector {
+            id: "sns".to_string(),
+            name: "Amazon SNS / EventBridge".to_string(),
+            icon: "".to_string(),
+            description: "Publish messages to an SNS topic or events to an EventBridge bus"
+                .to_string(),
+            enabled: true,
+            source: false,
+            sink: true,
+            testing: true,
+            hidden: false,
+            custom_schemas: true,
+            connection_config: None,
+            table_config: TABLE_SCHEMA.to_owned(),
+        }
+    }
+
+    fn table_type(&self, _: Self::ProfileT, _: Self::TableT) -> ConnectionType {
+        ConnectionType::Sink
+    }
+
+    fn get_schema(
+        &self,
+        _: Self::ProfileT,
+        _: Self::TableT,
+        s: Option<&ConnectionSchema>,
+    ) -> Option<ConnectionSchema> {
+        s.cloned()
+    }
+
+    fn test(
+        &self,
+        _: &str,
+        _: Self::ProfileT,
+        table: Self::TableT,
+        schema: Option<&ConnectionSchema>,
+        tx: tokio::sync::mpsc::Sender<TestSourceMessage>,
+    ) {
+        let schema = schema.cloned();
+        tokio::task::spawn(async move {
+            let message = match validate_table(&table, schema.as_ref()) {
+                Ok(()) => match test_inner(&table).await {
+                    Ok(m) => TestSourceMessage::done(m),
+                    Err(e) => TestSourceMessage::fail(format!("{:#}", e)),
+                },
+                Err(e) => TestSourceMessage::fail(e.to_string()),
+            };
+
+            tx.send(message).await.unwrap();
+        });
+    }
+
+    fn from_options(
+        &self,
+        name: &str,
+        options: &mut HashMap<String, String>,
+        schema: Option<&ConnectionSchema>,
+        _: Option<&ConnectionProfile>,
+    ) -> anyhow::Result<Connection> {
+        let destination = match (
+            options.remove("topic_arn"),
+            options.remove("event_bus_name"),
+        ) {
+            (Some(topic_arn), None) => Destination::Topic {
+                topic_arn,
+                attribute_fields: options
+                    .remove("attribute_fields")
+                    .map(|fields| fields.split(',').map(|f| f.trim().to_string()).collect())
+                    .unwrap_or_default(),
+                message_group_id_field: options.remove("message_group_id_field"),
+            },
+            (None, Some(event_bus_name)) => Destination::EventBus {
+                event_bus_name,
+                source: pull_opt("source", options)?,
+                detail_type: pull_opt("detail_type", options)?,
+                detail_type_field: options.remove("detail_type_field"),
+            },
+            (Some(_), Some(_)) => bail!("only one of 'topic_arn' and 'event_bus_name' may be set"),
+            (None, None) => bail!("one of 'topic_arn' or 'event_bus_name' must be set"),
+        };
+
+        let table = SnsTable {
+            aws_region: options.remove("aws_region"),
+            destination,
+        };
+
+        self.from_config(None, name, EmptyConfig {}, table, schema)
+    }
+
+    fn from_config(
+        &self,
+        id: Option<i64>,
+        name: &str,
+        config: Self::ProfileT,
+        table: Self::TableT,
+        schema: Option<&ConnectionSchema>,
+    ) -> anyhow::Result<Connection> {
+        let mut schema = schema
+            .map(|s| s.to_owned())
+            .ok_or_else(|| anyhow!("no schema defined for SNS sink"))?;
+
+        validate_table(&table, Some(&schema))?;
+
+        let format = schema
+            .format
+            .clone()
+            .unwrap_or_else(|| Format::Json(JsonFormat::default()));
+
+        let description = match &table.destination {
+            Destination::Topic { topic_arn, .. } => format!("SnsSink<{}>", topic_arn),
+            Destination::EventBus { event_bus_name, .. } => {
+                // the detail of an event must be a JSON object
+                if !matches!(format, Format::Json(_)) {
+                    bail!("EventBridge sinks only support the json format");
+                }
+                format!("EventBridgeSink<{}>", event_bus_name)
+            }
+        };
+
+        schema.format = Some(format.clone());
+
+        let config = OperatorConfig {
+            connection: serde_json::to_value(config).unwrap(),
+            table: serde_json::to_value(table).unwrap(),
+            rate_limit: None,
+            format: Some(format),
+            bad_data: schema.bad_data.clone(),
+            framing: None,
+            metadata_fields: vec![],
+        };
+
+        Ok(Connection {
+            id,
+            connector: self.name(),
+            name: name.to_string(),
+            connection_type: ConnectionType::Sink,
+            schema,
+            config: serde_json::to_string(&config).unwrap(),
+            description,
+        })
+    }
+
+    fn make_operator(
+        &self,
+        _: Self::ProfileT,
+        table: Self::TableT,
+        config: OperatorConfig,
+    ) -> anyhow::Result<OperatorNode> {
+        Ok(OperatorNode::from_operator(Box::new(SnsSinkFunc::new(
+            table,
+            ArrowSerializer::new(
+                config
+                    .format
+                    .ok_or_else(|| anyhow!("format required for SNS sink"))?,
+            ),
+        ))))
+    }
+}
@@ -0,0 +1,490 @@
+use anyhow::{anyhow, Context};
+use arrow::array::{Array, ArrayRef, AsArray, RecordBatch};
+use arrow::datatypes::TimestampNanosecondType;
+use arrow::util::display::{ArrayFormatter, FormatOptions};
+use arroyo_formats::ser::ArrowSerializer;
+use arroyo_operator::context::ArrowContext;
+use arroyo_operator::operator::ArrowOperator;
+use arroyo_types::from_nanos;
+use async_trait::async_trait;
+use aws_sdk_eventbridge::primitives::DateTime;
+use aws_sdk_eventbridge::types::PutEventsRequestEntry;
+use aws_sdk_sns::types::{MessageAttributeValue, PublishBatchRequestEntry};
+use futures::StreamExt;
+use std::time::{Duration, SystemTime};
+use tracing::warn;
+
+use crate::sns::{load_aws_config, Destination, SnsTable};
+
+/// Both SNS and EventBridge accept at most 10 entries in a batch
+const MAX_BATCH_SIZE: usize = 10;
+/// Both also limit the total size of a batch to 256 KiB
+const MAX_BATCH_BYTES: usize = 256 * 1024;
+const MAX_CONCURRENT_REQUESTS: usize = 8;
+const MAX_PUBLISH_ATTEMPTS: u32 = 20;
+
+/// EventBridge errors for an entry that may succeed if it's retried
+const RETRYABLE_EVENT_ERRORS: &[&str] = &["InternalFailure", "ThrottlingException"];
+
+#[derive(Clone)]
+struct Attribute {
+    name: String,
+    data_type: &'static str,
+    value: String,
+}
+
+/// A message or event waiting to be published
+#[derive(Clone)]
+struct Message {
+    body: String,
+    timestamp: SystemTime,
+    attributes: Vec<Attribute>,
+    message_group_id: Option<String>,
+    detail_type: Option<String>,
+}
+
+impl Message {
+    fn size(&self) -> usize {
+        self.body.len()
+            + self
+                .attributes
+                .iter()
+                .map(|a| a.name.len() + a.data_type.len() + a.value.len())
+                .sum::<usize>()
+    }
+}
+
+enum Publisher {
+    Topic {
+        client: aws_sdk_sns::Client,
+        topic_arn: String,
+    },
+    EventBus {
+        client: aws_sdk_eventbridge::Client,
+        event_bus_name: String,
+        source: String,
+        detail_type: String,
+    },
+}
+
+/// The outcome of publishing a batch of messages
+struct PublishResult {
+    /// messages that failed but may succeed if they're retried
+    retry: Vec<Message>,
+    /// why messages that will never be accepted were rejected
+    rejected: Vec<String>,
+    /// why the messages to retry failed
+    error: Option<String>,
+}
+
+impl Publisher {
+    async fn publish(&self, messages: Vec<Message>) -> PublishResult {
+        let result = match self {
+            Publisher::Topic { client, topic_arn } => {
+                Self::publish_to_topic(client, topic_arn, &messages).await
+            }
+            Publisher::EventBus {
+                client,
+                event_bus_name,
+                source,
+                detail_type,
+            } => Self::put_events(client, event_bus_name, source, detail_type, &messages).await,
+        };
+
+        match result {
+            Ok((retry, rejected)) => PublishResult {
+                error: (!retry.is_empty())
+                    .then(|| format!("{} of {} messages failed", retry.len(), messages.len())),
+                retry: retry.into_iter().map(|i| messages[i].clone()).collect(),
+                rejected,
+            },
+            Err(e) => PublishResult {
+                retry: messages,
+                rejected: vec![],
+                error: Some(format!("{:#}", e)),
+            },
+        }
+    }
+
+    /// Publishes the messages in a single request, returning the indices of those that should be
+    /// retried and the errors of those that were rejected
+    async fn publish_to_topic(
+        client: &aws_sdk_sns::Client,
+        topic_arn: &str,
+        messages: &[Message],
+    ) -> anyhow::Result<(Vec<usize>, Vec<String>)> {
+        let entries = messages
+            .iter()
+            .enumerate()
+            .map(|(i, message)| {
+                let mut entry = PublishBatchRequestEntry::builder()
+                    .id(i.to_string())
+                    .message(&message.body)
+                    .set_message_group_id(message.message_group_id.clone());
+
+                for attribute in &message.attributes {
+                    entry = entry.message_attributes(
+                        &attribute.name,
+                        MessageAttributeValue::builder()
+                            .data_type(attribute.data_type)
+                            .string_value(&attribute.value)
+                            .build()?,
+                    );
+                }
+
+                Ok(entry.build()?)
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        let output = client
+            .publish_batch()
+            .topic_arn(topic_arn)
+            .set_publish_batch_request_entries(Some(entries))
+            .send()
+            .await
+            .context("failed to publish to SNS")?;
+
+        let mut retry = vec![];
+        let mut rejected = vec![];
+        for failure in output.failed() {
+            if failure.sender_fault() {
+                rejected.push(format!(
+                    "{}: {}",
+                    failure.code(),
+                    failure.message().unwrap_or_default()
+                ));
+            } else {
+                retry.push(
+                    failure
+                        .id()
+                        .parse()
+                        .map_err(|_| anyhow!("invalid id in SNS response"))?,
+                );
+            }
+        }
+
+        Ok((retry, rejected))
+    }
+
+    /// Puts the messages on the bus in a single request, returning the indices of those that
+    /// should be retried and the errors of those that were rejected
+    async fn put_events(
+        client: &aws_sdk_eventbridge::Client,
+        event_bus_name: &str,
+        source: &str,
+        detail_type: &str,
+        messages: &[Message],
+    ) -> anyhow::Result<(Vec<usize>, Vec<String>)> {
+        let entries = messages
+            .iter()
+            .map(|message| {
+                PutEventsRequestEntry::builder()
+                    .event_bus_name(event_bus_name)
+                    .source(source)
+                    .detail_type(message.detail_type.as_deref().unwrap_or(detail_type))
+                    .detail(&message.body)
+                    .time(DateTime::from(message.timestamp))
+                    .build()
+            })
+            .collect();
+
+        let output = client
+            .put_events()
+            .set_entries(Some(entries))
+            .send()
+            .await
+            .context("failed to put events on EventBridge")?;
+
+        let mut retry = vec![];
+        let mut rejected = vec![];
+        if output.failed_entry_count() > 0 {
+            // results are in the same order as the entries of the request
+            for (i, result) in output.entries().iter().enumerate() {
+                let Some(code) = result.error_code() else {
+                    continue;
+                };
+
+                if RETRYABLE_EVENT_ERRORS.contains(&code) {
+                    retry.push(i);
+                } else {
+                    rejected.push(format!(
+                        "{}: {}",
+                        code,
+                        result.error_message().unwrap_or_default()
+                    ));
+                }
+            }
+        }
+
+        Ok((retry, rejected))
+    }
+}
+
+/// Splits the messages into batches that are within the request limits
+fn into_batches(messages: Vec<Message>) -> Vec<Vec<Message>> {
+    let mut batches = vec![];
+    let mut batch: Vec<Message> = vec![];
+    let mut batch_bytes = 0;
+
+    for message in messages {
+        let size = message.size();
+        if batch.len() == MAX_BATCH_SIZE || batch_bytes + size > MAX_BATCH_BYTES {
+            batches.push(std::mem::take(&mut batch));
+            batch_bytes = 0;
+        }
+
+        batch_bytes += size;
+        batch.push(message);
+    }
+
+    if !batch.is_empty() {
+        batches.push(batch);
+    }
+
+    batches
+}
+
+pub struct SnsSinkFunc {
+    table: SnsTable,
+    serializer: ArrowSerializer,
+    publisher: Option<Publisher>,
+}
+
+impl SnsSinkFunc {
+    pub fn new(table: SnsTable, serializer: ArrowSerializer) -> Self {
+        Self {
+            table,
+            serializer,
+            publisher: None,
+        }
+    }
+
+    /// Builds the messages for the rows of the batch; messages that are too large to ever be
+    /// accepted are returned as errors instead
+    fn messages(
+        &mut self,
+        batch: &RecordBatch,
+        timestamp_index: usize,
+    ) -> anyhow::Result<(Vec<Message>, Vec<String>)> {
+        let column = |field: &String| -> anyhow::Result<ArrayRef> {
+            batch
+                .column_by_name(field)
+                .cloned()
+                .ok_or_else(|| anyhow!("field '{}' is not in the input", field))
+        };
+
+        let (attribute_fields, group_id_field, detail_type_field) = match &self.table.destination {
+            Destination::Topic {
+                attribute_fields,
+                message_group_id_field,
+                ..
+            } => (
+                attribute_fields.as_slice(),
+                message_group_id_field.as_ref(),
+                None,
+            ),
+            Destination::EventBus {
+                detail_type_field, ..
+            } => (&[][..], None, detail_type_field.as_ref()),
+        };
+
+        let attribute_columns = attribute_fields
+            .iter()
+            .map(|field| Ok((field, column(field)?)))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let group_id_column = group_id_field.map(column).transpose()?;
+        let detail_type_column = detail_type_field.map(column).transpose()?;
+
+        let options = FormatOptions::default();
+        let attribute_formatters = attribute_columns
+            .iter()
+            .map(|(field, array)| {
+                let data_type = if array.data_type().is_numeric() {
+                    "Number"
+                } else {
+                    "String"
+                };
+                Ok((
+                    *field,
+                    data_type,
+                    array.as_ref(),
+                    ArrayFormatter::try_new(array.as_ref(), &options)?,
+                ))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let group_id_formatter = group_id_column
+            .as_ref()
+            .map(|c| ArrayFormatter::try_new(c.as_ref(), &options))
+            .transpose()?;
+        let detail_type_formatter = detail_type_column
+            .as_ref()
+            .map(|c| ArrayFormatter::try_new(c.as_ref(), &options))
+            .transpose()?;
+
+        let timestamps = batch
+            .column(timestamp_index)
+            .as_primitive::<TimestampNanosecondType>();
+
+        let value_at = |array: Option<&ArrayRef>, formatter: Option<&ArrayFormatter>, i| match (
+            array, formatter,
+        ) {
+            (Some(array), Some(formatter)) if array.is_valid(i) => {
+                Some(formatter.value(i).to_string())
+            }
+            _ => None,
+        };
+
+        let mut messages = vec![];
+        let mut rejected = vec![];
+        for (i, body) in self.serializer.serialize(batch).enumerate() {
+            let body = String::from_utf8(body)
+                .map_err(|_| anyhow!("messages must be valid UTF-8 to be published"))?;
+
+            let message = Message {
+                body,
+                timestamp: from_nanos(timestamps.value(i) as u128),
+                // attributes can't be empty, so null values are left out
+                attributes: attribute_formatters
+                    .iter()
+                    .filter(|(_, _, array, _)| array.is_valid(i))
+                    .map(|(name, data_type, _, formatter)| Attribute {
+                        name: name.to_string(),
+                        data_type: *data_type,
+                        value: formatter.value(i).to_string(),
+                    })
+                    .collect(),
+                message_group_id: value_at(
+                    group_id_column.as_ref(),
+                    group_id_formatter.as_ref(),
+                    i,
+                ),
+                detail_type: value_at(
+                    detail_type_column.as_ref(),
+                    detail_type_formatter.as_ref(),
+                    i,
+                ),
+            };
+
+            if message.size() > MAX_BATCH_BYTES {
+                rejected.push(format!(
+                    "message of {} bytes is larger than the limit of {} bytes",
+                    message.size(),
+                    MAX_BATCH_BYTES
+                ));
+            } else {
+                messages.push(message);
+            }
+        }
+
+        Ok((messages, rejected))
+    }
+
+    async fn publish(&mut self, mut messages: Vec<Message>, ctx: &mut ArrowContext) {
+        let publisher = self
+            .publisher
+            .as_ref()
+            .expect("publisher should be created on start");
+
+        let mut attempts: u32 = 0;
+        while !messages.is_empty() {
+            let results: Vec<PublishResult> = futures::stream::iter(into_batches(messages))
+                .map(|batch| publisher.publish(batch))
+                .buffer_unordered(MAX_CONCURRENT_REQUESTS)
+                .collect()
+                .await;
+
+            messages = vec![];
+            let mut error = None;
+            for result in results {
+                for rejection in result.rejected {
+                    warn!("message was rejected: {}", rejection);
+                    ctx.report_error("Message rejected", rejection).await;
+                }
+
+                messages.extend(result.retry);
+                error = error.or(result.error);
+            }
+
+            let Some(error) = error else {
+                continue;
+            };
+
+            attempts += 1;
+            if attempts >= MAX_PUBLISH_ATTEMPTS {
+                panic!(
+                    "failed to publish messages after {} attempts: {}",
+                    attempts, error
+                );
+            }
+
+            ctx.report_error("Failed to publish messages", error).await;
+            tokio::time::sleep(Duration::from_millis(
+                (50 * (1 << attempts.min(10))).min(5_000),
+            ))
+            .await;
+        }
+    }
+}
+
+#[async_trait]
+impl ArrowOperator for SnsSinkFunc {
+    fn name(&self) -> String {
+        match &self.table.destination {
+            Destination::Topic { .. } => "SnsSink".to_string(),
+            Destination::EventBus { .. } => "EventBridgeSink".to_string(),
+        }
+    }
+
+    async fn on_start(&mut self, _: &mut ArrowContext) {
+        let config = load_aws_config(self.table.aws_region.as_ref()).await;
+
+        self.publisher = Some(match &self.table.destination {
+            Destination::Topic { topic_arn, .. } => Publisher::Topic {
+                client: aws_sdk_sns::Client::new(&config),
+                topic_arn: topic_arn.clone(),
+            },
+            Destination::EventBus {
+                event_bus_name,
+                source,
+                detail_type,
+                ..
+            } => Publisher::EventBus {
+                client: aws_sdk_eventbridge::Client::new(&config),
+                event_bus_name: event_bus_name.clone(),
+                source: source.clone(),
+                detail_type: detail_type.clone(),
+            },
+        });
+    }
+
+    async fn process_batch(
+        &mut self,
+        batch: RecordBatch,
+        ctx: &mut ArrowContext,
+    ) -> anyhow::Result<()> {
+        let timestamp_index = ctx
+            .in_schemas
+            .first()
+            .expect("no in-schema for SNS sink!")
+            .timestamp_index;
+
+        let (messages, rejected) = match self.messages(&batch, timestamp_index) {
+            Ok(messages) => messages,
+            Err(e) => {
+                ctx.report_error("Failed to prepare messages", e.to_string())
+                    .await;
+                panic!("failed to prepare messages: {:?}", e);
+            }
+        };
+
+        for rejection in rejected {
+            warn!("message was rejected: {}", rejection);
+            ctx.report_error("Message rejected", rejection).await;
+        }
+
+        // messages are published as each batch arrives, so every message before a checkpoint
+        // barrier has been published by the time the checkpoint completes
+        self.publish(messages, ctx).await;
+        Ok(())
+    }
+}
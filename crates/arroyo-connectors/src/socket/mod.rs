@@ -0,0 +1,250 @@
+mod source;
+mod syslog;
+
+use anyhow::{anyhow, bail};
+use arroyo_operator::connector::{Connection, Connector};
+use arroyo_operator::operator::OperatorNode;
+use arroyo_rpc::api_types::connections::FieldType::Primitive;
+use arroyo_rpc::api_types::connections::{
+    ConnectionProfile, ConnectionSchema, ConnectionType, PrimitiveType, SourceField,
+    TestSourceMessage,
+};
+use arroyo_rpc::formats::{Format, JsonFormat};
+use arroyo_rpc::OperatorConfig;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::str::FromStr;
+use typify::import_types;
+
+use crate::socket::source::SocketSourceFunc;
+use crate::{pull_opt, pull_option_to_i64, source_field, EmptyConfig};
+
+const TABLE_SCHEMA: &str = include_str!("./table.json");
+
+import_types!(schema = "src/socket/table.json");
+
+/// Listens on a TCP or UDP port for messages sent directly by network appliances, log
+/// shippers or applications. Messages are either read with the table's format, or parsed as
+/// syslog into a fixed set of columns.
+pub struct SocketConnector {}
+
+fn field(name: &str, t: PrimitiveType, nullable: bool) -> SourceField {
+    SourceField {
+        nullable,
+        ..source_field(name, Primitive(t))
+    }
+}
+
+pub fn syslog_schema() -> ConnectionSchema {
+    ConnectionSchema {
+        format: Some(Format::Json(JsonFormat::default())),
+        framing: None,
+        bad_data: None,
+        struct_name: None,
+        fields: vec![
+            field("facility", PrimitiveType::UInt32, true),
+            field("severity", PrimitiveType::UInt32, true),
+            field("version", PrimitiveType::UInt32, true),
+            field("timestamp", PrimitiveType::DateTime, true),
+            field("hostname", PrimitiveType::String, true),
+            field("app_name", PrimitiveType::String, true),
+            field("proc_id", PrimitiveType::String, true),
+            field("msg_id", PrimitiveType::String, true),
+            field("structured_data", PrimitiveType::Json, true),
+            field("message", PrimitiveType::String, false),
+            field("source_address", PrimitiveType::String, false),
+        ],
+        definition: None,
+        inferred: None,
+    }
+}
+
+impl SocketTable {
+    fn address(&self) -> anyhow::Result<SocketAddr> {
+        let ip = IpAddr::from_str(&self.bind_address)
+            .map_err(|_| anyhow!("invalid bind_address '{}'", self.bind_address))?;
+        let port = u16::try_from(self.port)
+            .ok()
+            .filter(|p| *p != 0)
+            .ok_or_else(|| anyhow!("invalid port {}; expected 1-65535", self.port))?;
+
+        Ok(SocketAddr::new(ip, port))
+    }
+}
+
+impl Connector for SocketConnector {
+    type ProfileT = EmptyConfig;
+    type TableT = SocketTable;
+
+    fn name(&self) -> &'static str {
+        "socket"
+    }
+
+    fn metadata(&self) -> arroyo_rpc::api_types::connections::Connector {
+        arroyo_rpc::api_types::connections::Connector {
+            id: "socket".to_string(),
+            name: "Socket".to_string(),
+            icon: "".to_string(),
+            description: "Receive messages and syslog over TCP or UDP".to_string(),
+            enabled: true,
+            source: true,
+            sink: false,
+            testing: false,
+            hidden: false,
+            custom_schemas: true,
+            connection_config: None,
+            table_config: TABLE_SCHEMA.to_owned(),
+        }
+    }
+
+    fn table_type(&self, _: Self::ProfileT, _: Self::TableT) -> ConnectionType {
+        ConnectionType::Source
+    }
+
+    fn max_source_parallelism(&self, _: Self::ProfileT, _: Self::TableT) -> Option<usize> {
+        // only one subtask can listen on the port
+        Some(1)
+    }
+
+    fn get_schema(
+        &self,
+        _: Self::ProfileT,
+        table: Self::TableT,
+        s: Option<&ConnectionSchema>,
+    ) -> Option<ConnectionSchema> {
+        if table.syslog {
+            Some(syslog_schema())
+        } else {
+            s.cloned()
+        }
+    }
+
+    fn test(
+        &self,
+        _: &str,
+        _: Self::ProfileT,
+        table: Self::TableT,
+        _: Option<&ConnectionSchema>,
+        tx: tokio::sync::mpsc::Sender<TestSourceMessage>,
+    ) {
+        tokio::task::spawn(async move {
+            let message = match table.address() {
+                Ok(_) => TestSourceMessage::done("Successfully validated connection"),
+                Err(e) => TestSourceMessage::fail(format!("{:#}", e)),
+            };
+            tx.send(message).await.unwrap();
+        });
+    }
+
+    fn from_options(
+        &self,
+        name: &str,
+        options: &mut HashMap<String, String>,
+        schema: Option<&ConnectionSchema>,
+        _: Option<&ConnectionProfile>,
+    ) -> anyhow::Result<Connection> {
+        let protocol = pull_opt("protocol", options)?;
+        let protocol = Protocol::try_from(&protocol)
+            .map_err(|_| anyhow!("invalid protocol '{}'; expected 'tcp' or 'udp'", protocol))?;
+
+        let port = pull_option_to_i64("port", options)?
+            .ok_or_else(|| anyhow!("required option 'port' not set"))?;
+
+        let syslog = options
+            .remove("syslog")
+            .map(|s| {
+                s.parse::<bool>()
+                    .map_err(|_| anyhow!("invalid value for 'syslog'; expected true or false"))
+            })
+            .transpose()?
+            .unwrap_or(false);
+
+        let table = SocketTable {
+            protocol,
+            port,
+            bind_address: options
+                .remove("bind_address")
+                .unwrap_or_else(|| "0.0.0.0".to_string()),
+            syslog,
+        };
+
+        self.from_config(None, name, EmptyConfig {}, table, schema)
+    }
+
+    fn from_config(
+        &self,
+        id: Option<i64>,
+        name: &str,
+        config: Self::ProfileT,
+        table: Self::TableT,
+        schema: Option<&ConnectionSchema>,
+    ) -> anyhow::Result<Connection> {
+        let address = table.address()?;
+
+        let (schema, description) = if table.syslog {
+            // the columns are determined by the syslog format, but may be declared to
+            // document them
+            if let Some(s) = schema {
+                if !s.fields.is_empty() && s.fields != syslog_schema().fields {
+                    bail!("invalid schema for syslog source; omit the columns to use the syslog schema");
+                }
+            }
+
+            (
+                syslog_schema(),
+                format!("SyslogSource<{}, {}>", table.protocol, address),
+            )
+        } else {
+            (
+                schema
+                    .cloned()
+                    .ok_or_else(|| anyhow!("no schema defined for socket source"))?,
+                format!("SocketSource<{}, {}>", table.protocol, address),
+            )
+        };
+
+        let format = schema
+            .format
+            .clone()
+            .ok_or_else(|| anyhow!("'format' must be set for socket source"))?;
+
+        let config = OperatorConfig {
+            connection: serde_json::to_value(config).unwrap(),
+            table: serde_json::to_value(table).unwrap(),
+            rate_limit: None,
+            format: Some(format),
+            bad_data: schema.bad_data.clone(),
+            framing: schema.framing.clone(),
+            metadata_fields: vec![],
+        };
+
+        Ok(Connection {
+            id,
+            connector: self.name(),
+            name: name.to_string(),
+            connection_type: ConnectionType::Source,
+            schema,
+            config: serde_json::to_string(&config).unwrap(),
+            description,
+        })
+    }
+
+    fn make_operator(
+        &self,
+        _: Self::ProfileT,
+        table: Self::TableT,
+        config: OperatorConfig,
+    ) -> anyhow::Result<OperatorNode> {
+        Ok(OperatorNode::from_source(Box::new(SocketSourceFunc::new(
+            table.protocol,
+            table.address()?,
+            table.syslog,
+            config
+                .format
+                .ok_or_else(|| anyhow!("format required for socket source"))?,
+            config.framing,
+            config.bad_data,
+        ))))
+    }
+}
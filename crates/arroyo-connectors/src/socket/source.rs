@@ -0,0 +1,351 @@
+use std::io;
+use std::net::SocketAddr;
+use std::time::{Duration, SystemTime};
+
+use async_trait::async_trait;
+use chrono::Utc;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt, BufReader};
+use tokio::net::{TcpListener, UdpSocket};
+use tokio::select;
+use tokio::sync::{mpsc, oneshot};
+use tokio::task::JoinSet;
+use tokio::time::MissedTickBehavior;
+use tracing::{debug, info, warn};
+
+use arroyo_operator::context::ArrowContext;
+use arroyo_operator::operator::SourceOperator;
+use arroyo_operator::SourceFinishType;
+use arroyo_rpc::formats::{BadData, Format, Framing};
+use arroyo_rpc::grpc::rpc::StopMode;
+use arroyo_rpc::ControlMessage;
+use arroyo_types::UserError;
+
+use crate::socket::syslog::parse_syslog;
+use crate::socket::Protocol;
+
+/// The largest message that will be read; longer TCP lines are split
+const MAX_MESSAGE_SIZE: usize = 64 * 1024;
+
+/// A message and the address of the peer that sent it
+type Message = (Vec<u8>, SocketAddr);
+
+/// Listens for messages over TCP or UDP. Messages are read off the socket only as fast as the
+/// pipeline consumes them, which backpressures TCP senders; UDP datagrams that arrive while
+/// the pipeline is backpressured or not running are lost, as are messages received since the
+/// last checkpoint when the pipeline recovers.
+pub struct SocketSourceFunc {
+    protocol: Protocol,
+    address: SocketAddr,
+    syslog: bool,
+    format: Format,
+    framing: Option<Framing>,
+    bad_data: Option<BadData>,
+}
+
+/// Reads the next message from a TCP connection into `buf`, returning false at the end of the
+/// stream. Messages are newline-delimited, or when `octet_counting` is set, may also be framed
+/// with their length as described in RFC 6587.
+async fn read_message<R: AsyncBufRead + Unpin>(
+    reader: &mut R,
+    octet_counting: bool,
+    buf: &mut Vec<u8>,
+) -> io::Result<bool> {
+    buf.clear();
+
+    if octet_counting
+        && reader
+            .fill_buf()
+            .await?
+            .first()
+            .is_some_and(u8::is_ascii_digit)
+    {
+        let mut len = vec![];
+        (&mut *reader).take(8).read_until(b' ', &mut len).await?;
+        let len = std::str::from_utf8(&len)
+            .ok()
+            .and_then(|l| l.trim_end().parse::<usize>().ok())
+            .filter(|l| *l <= MAX_MESSAGE_SIZE)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "invalid frame length"))?;
+
+        buf.resize(len, 0);
+        reader.read_exact(buf).await?;
+        return Ok(true);
+    }
+
+    let read = (&mut *reader)
+        .take(MAX_MESSAGE_SIZE as u64)
+        .read_until(b'\n', buf)
+        .await?;
+
+    while matches!(buf.last(), Some(b'\n' | b'\r')) {
+        buf.pop();
+    }
+
+    Ok(read > 0)
+}
+
+async fn serve_tcp(listener: TcpListener, octet_counting: bool, tx: mpsc::Sender<Message>) {
+    // connections are dropped along with the listener
+    let mut connections = JoinSet::new();
+
+    loop {
+        select! {
+            accepted = listener.accept() => {
+                let (stream, peer) = match accepted {
+                    Ok(accepted) => accepted,
+                    Err(e) => {
+                        warn!("failed to accept connection: {}", e);
+                        continue;
+                    }
+                };
+
+                let tx = tx.clone();
+                connections.spawn(async move {
+                    let mut reader = BufReader::new(stream);
+                    let mut buf = vec![];
+                    loop {
+                        match read_message(&mut reader, octet_counting, &mut buf).await {
+                            Ok(true) if buf.is_empty() => {}
+                            Ok(true) => {
+                                if tx.send((std::mem::take(&mut buf), peer)).await.is_err() {
+                                    return;
+                                }
+                            }
+                            Ok(false) => return,
+                            Err(e) => {
+                                debug!("closing connection from {}: {}", peer, e);
+                                return;
+                            }
+                        }
+                    }
+                });
+            }
+            Some(_) = connections.join_next() => {}
+        }
+    }
+}
+
+async fn serve_udp(socket: UdpSocket, tx: mpsc::Sender<Message>) {
+    let mut buf = vec![0u8; u16::MAX as usize];
+
+    loop {
+        match socket.recv_from(&mut buf).await {
+            Ok((len, peer)) => {
+                let mut message = &buf[..len];
+                while let [rest @ .., b'\n' | b'\r'] = message {
+                    message = rest;
+                }
+
+                if !message.is_empty() && tx.send((message.to_vec(), peer)).await.is_err() {
+                    return;
+                }
+            }
+            Err(e) => {
+                warn!("failed to receive datagram: {}", e);
+            }
+        }
+    }
+}
+
+impl SocketSourceFunc {
+    pub fn new(
+        protocol: Protocol,
+        address: SocketAddr,
+        syslog: bool,
+        format: Format,
+        framing: Option<Framing>,
+        bad_data: Option<BadData>,
+    ) -> Self {
+        Self {
+            protocol,
+            address,
+            syslog,
+            format,
+            framing,
+            bad_data,
+        }
+    }
+
+    async fn handle_control_message(
+        &mut self,
+        ctx: &mut ArrowContext,
+        msg: Option<ControlMessage>,
+    ) -> Option<SourceFinishType> {
+        match msg? {
+            ControlMessage::Checkpoint(c) => {
+                debug!("starting checkpointing {}", ctx.task_info.task_index);
+                if self.start_checkpoint(c, ctx).await {
+                    return Some(SourceFinishType::Immediate);
+                }
+            }
+            ControlMessage::Stop { mode } => {
+                info!("Stopping socket source: {:?}", mode);
+
+                match mode {
+                    StopMode::Graceful => {
+                        return Some(SourceFinishType::Graceful);
+                    }
+                    StopMode::Immediate => {
+                        return Some(SourceFinishType::Immediate);
+                    }
+                }
+            }
+            ControlMessage::Commit { .. } => {
+                unreachable!("sources shouldn't receive commit messages");
+            }
+            ControlMessage::LoadCompacted { compacted } => {
+                ctx.load_compacted(compacted).await;
+            }
+            ControlMessage::QueryState { query } => {
+                ctx.query_state(query).await;
+            }
+            ControlMessage::NoOp => {}
+        }
+        None
+    }
+
+    async fn handle_message(
+        &mut self,
+        ctx: &mut ArrowContext,
+        (message, peer): Message,
+    ) -> Result<(), UserError> {
+        if self.syslog {
+            let row = parse_syslog(
+                &String::from_utf8_lossy(&message),
+                &peer.to_string(),
+                Utc::now(),
+            );
+            ctx.deserialize_slice(row.to_string().as_bytes(), SystemTime::now(), None)
+                .await
+        } else {
+            ctx.deserialize_slice(&message, SystemTime::now(), None)
+                .await
+        }
+    }
+
+    async fn run_int(&mut self, ctx: &mut ArrowContext) -> Result<SourceFinishType, UserError> {
+        ctx.initialize_deserializer(
+            self.format.clone(),
+            self.framing.clone(),
+            self.bad_data.clone(),
+        );
+
+        let bind_error = |e: io::Error| {
+            UserError::new(
+                "failed to start socket source",
+                format!(
+                    "Could not listen on {} {}: {}",
+                    self.protocol, self.address, e
+                ),
+            )
+        };
+
+        let (tx, mut rx) = mpsc::channel(1024);
+        let (_shutdown_tx, shutdown_rx) = oneshot::channel::<()>();
+
+        // the listener is closed when the shutdown sender is dropped, as the source finishes
+        match self.protocol {
+            Protocol::Tcp => {
+                let listener = TcpListener::bind(self.address).await.map_err(bind_error)?;
+                let octet_counting = self.syslog;
+                tokio::spawn(async move {
+                    select! {
+                        _ = serve_tcp(listener, octet_counting, tx) => {}
+                        _ = shutdown_rx => {}
+                    }
+                });
+            }
+            Protocol::Udp => {
+                let socket = UdpSocket::bind(self.address).await.map_err(bind_error)?;
+                tokio::spawn(async move {
+                    select! {
+                        _ = serve_udp(socket, tx) => {}
+                        _ = shutdown_rx => {}
+                    }
+                });
+            }
+        }
+
+        info!(
+            "listening for {} messages on {}",
+            self.protocol, self.address
+        );
+
+        let mut flush_ticker = tokio::time::interval(Duration::from_millis(50));
+        flush_ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        loop {
+            select! {
+                Some(message) = rx.recv() => {
+                    self.handle_message(ctx, message).await?;
+
+                    if ctx.should_flush() {
+                        ctx.flush_buffer().await?;
+                    }
+                }
+                _ = flush_ticker.tick() => {
+                    if ctx.should_flush() {
+                        ctx.flush_buffer().await?;
+                    }
+                }
+                control_message = ctx.control_rx.recv() => {
+                    if let Some(r) = self.handle_control_message(ctx, control_message).await {
+                        return Ok(r);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl SourceOperator for SocketSourceFunc {
+    fn name(&self) -> String {
+        "SocketSource".to_string()
+    }
+
+    async fn run(&mut self, ctx: &mut ArrowContext) -> SourceFinishType {
+        match self.run_int(ctx).await {
+            Ok(r) => r,
+            Err(e) => {
+                ctx.report_error(e.name.clone(), e.details.clone()).await;
+
+                panic!("{}: {}", e.name, e.details);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::read_message;
+
+    #[tokio::test]
+    async fn test_read_message() {
+        let mut reader: &[u8] =
+            b"first line\r\n\nsecond line\n31 <13>1 - - - - - - octet counted\nlast";
+        let mut buf = vec![];
+
+        let mut messages = vec![];
+        while read_message(&mut reader, true, &mut buf).await.unwrap() {
+            messages.push(String::from_utf8(buf.clone()).unwrap());
+        }
+
+        assert_eq!(
+            messages,
+            vec![
+                "first line",
+                "",
+                "second line",
+                "<13>1 - - - - - - octet counted",
+                "",
+                "last"
+            ]
+        );
+
+        // without octet counting, a leading number is part of the message
+        let mut reader: &[u8] = b"12 apples\n";
+        assert!(read_message(&mut reader, false, &mut buf).await.unwrap());
+        assert_eq!(buf, b"12 apples");
+    }
+}
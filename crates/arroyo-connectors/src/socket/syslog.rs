@@ -0,0 +1,282 @@
+use chrono::{DateTime, Datelike, Duration, NaiveDateTime, Utc};
+use serde_json::{Map, Value};
+
+/// Splits the PRI part (`<134>`) from the start of a message, returning the priority
+fn parse_pri(s: &str) -> Option<(u32, &str)> {
+    let rest = s.strip_prefix('<')?;
+    let end = rest.find('>')?;
+    let pri = &rest[..end];
+    if pri.is_empty() || pri.len() > 3 || !pri.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+
+    let pri: u32 = pri.parse().ok()?;
+    (pri <= 191).then_some((pri, &rest[end + 1..]))
+}
+
+/// RFC 5424 uses `-` for fields that are not present
+fn nil(field: &str) -> Value {
+    if field == "-" {
+        Value::Null
+    } else {
+        Value::String(field.to_string())
+    }
+}
+
+/// Parses the STRUCTURED-DATA of an RFC 5424 message into an object of SD-IDs to their
+/// parameters, returning the remainder of the message
+fn parse_structured_data(s: &str) -> Option<(Value, &str)> {
+    if let Some(rest) = s.strip_prefix('-') {
+        return Some((Value::Null, rest));
+    }
+
+    let mut elements = Map::new();
+    let mut rest = s;
+    while let Some(element) = rest.strip_prefix('[') {
+        let id_end = element.find(|c| c == ' ' || c == ']')?;
+        let id = &element[..id_end];
+        let mut r = &element[id_end..];
+
+        let mut params = Map::new();
+        loop {
+            if let Some(after) = r.strip_prefix(']') {
+                r = after;
+                break;
+            }
+
+            r = r.strip_prefix(' ')?;
+            let eq = r.find('=')?;
+            let name = &r[..eq];
+            r = r[eq + 1..].strip_prefix('"')?;
+
+            let mut value = String::new();
+            let mut chars = r.char_indices();
+            let end = loop {
+                match chars.next()? {
+                    (_, '\\') => {
+                        let (_, escaped) = chars.next()?;
+                        // only '"', '\' and ']' are escaped; any other backslash is literal
+                        if !matches!(escaped, '"' | '\\' | ']') {
+                            value.push('\\');
+                        }
+                        value.push(escaped);
+                    }
+                    (i, '"') => break i,
+                    (_, c) => value.push(c),
+                }
+            };
+
+            params.insert(name.to_string(), Value::String(value));
+            r = &r[end + 1..];
+        }
+
+        elements.insert(id.to_string(), Value::Object(params));
+        rest = r;
+    }
+
+    if elements.is_empty() {
+        return None;
+    }
+
+    Some((Value::String(Value::Object(elements).to_string()), rest))
+}
+
+/// Parses the part of an RFC 5424 message after the PRI, or returns None if it isn't one
+fn parse_5424(s: &str, row: &mut Map<String, Value>) -> Option<()> {
+    let (version, s) = s.split_once(' ')?;
+    if version.is_empty() || version.len() > 2 || !version.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+
+    let (timestamp, s) = s.split_once(' ')?;
+    let timestamp = match timestamp {
+        "-" => Value::Null,
+        t => Value::String(DateTime::parse_from_rfc3339(t).ok()?.to_rfc3339()),
+    };
+
+    let (hostname, s) = s.split_once(' ')?;
+    let (app_name, s) = s.split_once(' ')?;
+    let (proc_id, s) = s.split_once(' ')?;
+    let (msg_id, s) = s.split_once(' ')?;
+    let (structured_data, s) = parse_structured_data(s)?;
+
+    let message = s.strip_prefix(' ').unwrap_or(s);
+    let message = message.strip_prefix('\u{feff}').unwrap_or(message);
+
+    row.insert(
+        "version".to_string(),
+        Value::from(version.parse::<u32>().ok()?),
+    );
+    row.insert("timestamp".to_string(), timestamp);
+    row.insert("hostname".to_string(), nil(hostname));
+    row.insert("app_name".to_string(), nil(app_name));
+    row.insert("proc_id".to_string(), nil(proc_id));
+    row.insert("msg_id".to_string(), nil(msg_id));
+    row.insert("structured_data".to_string(), structured_data);
+    row.insert("message".to_string(), Value::String(message.to_string()));
+    Some(())
+}
+
+/// Parses the TAG of an RFC 3164 message (`app[pid]: `), returning the remainder
+fn parse_tag(s: &str) -> Option<(&str, Option<&str>, &str)> {
+    let end = s.find(|c| c == '[' || c == ':' || c == ' ')?;
+    let (tag, rest) = s.split_at(end);
+    if tag.is_empty() {
+        return None;
+    }
+
+    let (proc_id, rest) = match rest.strip_prefix('[') {
+        Some(rest) => {
+            let (proc_id, rest) = rest.split_once(']')?;
+            (Some(proc_id), rest)
+        }
+        None => (None, rest),
+    };
+
+    let rest = rest.strip_prefix(':')?;
+    Some((tag, proc_id, rest.strip_prefix(' ').unwrap_or(rest)))
+}
+
+/// Parses the part of an RFC 3164 message after the PRI, or returns None if it isn't one.
+/// These timestamps have no year or zone, so they're taken to be in UTC and in the year that
+/// puts them closest to `now`.
+fn parse_3164(s: &str, now: DateTime<Utc>, row: &mut Map<String, Value>) -> Option<()> {
+    let timestamp = s.get(..15)?;
+    let parse = |year: i32| {
+        NaiveDateTime::parse_from_str(&format!("{} {}", year, timestamp), "%Y %b %e %H:%M:%S")
+            .ok()
+            .map(|t| t.and_utc())
+    };
+
+    let mut timestamp = parse(now.year())?;
+    if timestamp > now + Duration::days(1) {
+        // a message from late December received in January
+        timestamp = parse(now.year() - 1)?;
+    }
+
+    let s = s[15..].strip_prefix(' ')?;
+    let (hostname, s) = s.split_once(' ').unwrap_or((s, ""));
+
+    let (app_name, proc_id, message) = match parse_tag(s) {
+        Some((tag, proc_id, message)) => (Some(tag), proc_id, message),
+        None => (None, None, s),
+    };
+
+    row.insert(
+        "timestamp".to_string(),
+        Value::String(timestamp.to_rfc3339()),
+    );
+    row.insert("hostname".to_string(), Value::String(hostname.to_string()));
+    row.insert("app_name".to_string(), app_name.into());
+    row.insert("proc_id".to_string(), proc_id.into());
+    row.insert("message".to_string(), Value::String(message.to_string()));
+    Some(())
+}
+
+/// Parses a syslog message in either RFC 5424 or RFC 3164 format into a row of the syslog
+/// schema. Like most syslog receivers, this is lenient: whatever can't be parsed is kept in
+/// the message column, so no messages are dropped.
+pub fn parse_syslog(message: &str, source_address: &str, now: DateTime<Utc>) -> Value {
+    let mut row = Map::new();
+    row.insert(
+        "source_address".to_string(),
+        Value::String(source_address.to_string()),
+    );
+
+    let Some((pri, rest)) = parse_pri(message) else {
+        row.insert("message".to_string(), Value::String(message.to_string()));
+        return Value::Object(row);
+    };
+
+    row.insert("facility".to_string(), Value::from(pri / 8));
+    row.insert("severity".to_string(), Value::from(pri % 8));
+
+    let mut parsed = row.clone();
+    if parse_5424(rest, &mut parsed).is_some() || parse_3164(rest, now, &mut parsed).is_some() {
+        return Value::Object(parsed);
+    }
+
+    row.insert("message".to_string(), Value::String(rest.to_string()));
+    Value::Object(row)
+}
+
+#[cfg(test)]
+mod test {
+    use super::parse_syslog;
+    use chrono::{TimeZone, Utc};
+    use serde_json::{json, Value};
+
+    #[test]
+    fn test_rfc_5424() {
+        let now = Utc.with_ymd_and_hms(2024, 6, 1, 0, 0, 0).unwrap();
+        let row = parse_syslog(
+            r#"<165>1 2003-10-11T22:14:15.003Z mymachine.example.com evntslog - ID47 [exampleSDID@32473 iut="3" eventSource="Application" eventID="1011"][examplePriority@32473 class="high \"a\\b\" \]"] An application event log entry..."#,
+            "10.0.0.1:514",
+            now,
+        );
+
+        assert_eq!(row["facility"], 20);
+        assert_eq!(row["severity"], 5);
+        assert_eq!(row["version"], 1);
+        assert_eq!(row["timestamp"], "2003-10-11T22:14:15.003+00:00");
+        assert_eq!(row["hostname"], "mymachine.example.com");
+        assert_eq!(row["app_name"], "evntslog");
+        assert_eq!(row["proc_id"], Value::Null);
+        assert_eq!(row["msg_id"], "ID47");
+        assert_eq!(row["message"], "An application event log entry...");
+        assert_eq!(row["source_address"], "10.0.0.1:514");
+
+        let sd: Value = serde_json::from_str(row["structured_data"].as_str().unwrap()).unwrap();
+        assert_eq!(
+            sd,
+            json!({
+                "exampleSDID@32473": {"iut": "3", "eventSource": "Application", "eventID": "1011"},
+                "examplePriority@32473": {"class": "high \"a\\b\" ]"}
+            })
+        );
+
+        let row = parse_syslog("<14>1 - - - - - -", "10.0.0.1:514", now);
+        assert_eq!(row["timestamp"], Value::Null);
+        assert_eq!(row["hostname"], Value::Null);
+        assert_eq!(row["structured_data"], Value::Null);
+        assert_eq!(row["message"], "");
+    }
+
+    #[test]
+    fn test_rfc_3164() {
+        let now = Utc.with_ymd_and_hms(2024, 6, 1, 0, 0, 0).unwrap();
+        let row = parse_syslog(
+            "<34>Oct 11 22:14:15 mymachine su[1234]: 'su root' failed for lonvick on /dev/pts/8",
+            "10.0.0.1:514",
+            now,
+        );
+
+        assert_eq!(row["facility"], 4);
+        assert_eq!(row["severity"], 2);
+        assert_eq!(row.get("version"), None);
+        assert_eq!(row["timestamp"], "2023-10-11T22:14:15+00:00");
+        assert_eq!(row["hostname"], "mymachine");
+        assert_eq!(row["app_name"], "su");
+        assert_eq!(row["proc_id"], "1234");
+        assert_eq!(row["message"], "'su root' failed for lonvick on /dev/pts/8");
+
+        let row = parse_syslog("<13>Feb  5 17:32:18 10.0.0.99 Use the BFG!", "", now);
+        assert_eq!(row["timestamp"], "2024-02-05T17:32:18+00:00");
+        assert_eq!(row["hostname"], "10.0.0.99");
+        assert_eq!(row["app_name"], Value::Null);
+        assert_eq!(row["message"], "Use the BFG!");
+    }
+
+    #[test]
+    fn test_unparseable() {
+        let now = Utc::now();
+
+        let row = parse_syslog("just some text", "10.0.0.1:514", now);
+        assert_eq!(row["message"], "just some text");
+        assert_eq!(row.get("severity"), None);
+
+        let row = parse_syslog("<13>not a timestamp", "10.0.0.1:514", now);
+        assert_eq!(row["severity"], 5);
+        assert_eq!(row["message"], "not a timestamp");
+    }
+}
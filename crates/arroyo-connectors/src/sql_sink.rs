@@ -0,0 +1,360 @@
+//! Shared support for the sinks that write to SQL databases (Postgres and MySQL), which
+//! consume debezium-style changes and turn them into rows to upsert or delete by key
+
+use anyhow::bail;
+use arrow::array::{Array, ArrayRef, AsArray, RecordBatch, StructArray};
+use arrow::compute::cast;
+use arrow::datatypes::{
+    DataType, Field, Float32Type, Float64Type, Int32Type, Int64Type, TimeUnit,
+    TimestampNanosecondType,
+};
+use arroyo_rpc::api_types::connections::ConnectionSchema;
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// A value to write to a column of the table, converted from the arrow type of its field
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum SqlValue {
+    Null,
+    Bool(bool),
+    Int4(i32),
+    Int8(i64),
+    Float4(f32),
+    Float8(f64),
+    Text(String),
+    Bytes(Vec<u8>),
+    Timestamp(SystemTime),
+}
+
+/// Checks that a field can be written by the SQL sinks; everything else is sent as text,
+/// which the database parses on assignment
+pub(crate) fn check_writable(field: &Field, database: &str) -> anyhow::Result<()> {
+    match field.data_type() {
+        DataType::Boolean
+        | DataType::Int8
+        | DataType::Int16
+        | DataType::Int32
+        | DataType::Int64
+        | DataType::UInt8
+        | DataType::UInt16
+        | DataType::UInt32
+        | DataType::UInt64
+        | DataType::Float16
+        | DataType::Float32
+        | DataType::Float64
+        | DataType::Decimal128(_, _)
+        | DataType::Decimal256(_, _)
+        | DataType::Utf8
+        | DataType::LargeUtf8
+        | DataType::Binary
+        | DataType::LargeBinary
+        | DataType::Timestamp(_, _)
+        | DataType::Date32
+        | DataType::Date64 => Ok(()),
+        t => bail!(
+            "field '{}' has type {}, which cannot be written to {}",
+            field.name(),
+            t,
+            database
+        ),
+    }
+}
+
+/// Checks at planning time that every field of the sink can be written, and that the
+/// configured key columns are fields of the sink
+pub(crate) fn check_schema(
+    schema: &ConnectionSchema,
+    key_columns: &[String],
+    database: &str,
+) -> anyhow::Result<()> {
+    let fields: Vec<Field> = schema.fields.iter().map(|f| f.clone().into()).collect();
+
+    for field in &fields {
+        check_writable(field, database)?;
+    }
+
+    for key in key_columns {
+        if !fields.iter().any(|f| f.name() == key) {
+            bail!(
+                "key column '{}' is not a field of the {} sink",
+                key,
+                database
+            );
+        }
+    }
+
+    Ok(())
+}
+
+fn collect(array: &dyn Array, f: impl Fn(usize) -> SqlValue) -> Vec<SqlValue> {
+    (0..array.len())
+        .map(|i| {
+            if array.is_null(i) {
+                SqlValue::Null
+            } else {
+                f(i)
+            }
+        })
+        .collect()
+}
+
+/// Converts an arrow array into values to write
+pub(crate) fn sql_values(array: &ArrayRef) -> anyhow::Result<Vec<SqlValue>> {
+    Ok(match array.data_type() {
+        DataType::Boolean => {
+            let a = array.as_boolean();
+            collect(a, |i| SqlValue::Bool(a.value(i)))
+        }
+        DataType::Int8 | DataType::Int16 | DataType::Int32 | DataType::UInt8 | DataType::UInt16 => {
+            let a = cast(array, &DataType::Int32)?;
+            let a = a.as_primitive::<Int32Type>();
+            collect(a, |i| SqlValue::Int4(a.value(i)))
+        }
+        DataType::Int64 | DataType::UInt32 => {
+            let a = cast(array, &DataType::Int64)?;
+            let a = a.as_primitive::<Int64Type>();
+            collect(a, |i| SqlValue::Int8(a.value(i)))
+        }
+        DataType::Float16 | DataType::Float32 => {
+            let a = cast(array, &DataType::Float32)?;
+            let a = a.as_primitive::<Float32Type>();
+            collect(a, |i| SqlValue::Float4(a.value(i)))
+        }
+        DataType::Float64 => {
+            let a = array.as_primitive::<Float64Type>();
+            collect(a, |i| SqlValue::Float8(a.value(i)))
+        }
+        DataType::Binary | DataType::LargeBinary => {
+            let a = cast(array, &DataType::Binary)?;
+            let a = a.as_binary::<i32>();
+            collect(a, |i| SqlValue::Bytes(a.value(i).to_vec()))
+        }
+        DataType::Timestamp(_, _) => {
+            let a = cast(array, &DataType::Timestamp(TimeUnit::Nanosecond, None))?;
+            let a = a.as_primitive::<TimestampNanosecondType>();
+            collect(a, |i| {
+                let nanos = a.value(i);
+                SqlValue::Timestamp(if nanos >= 0 {
+                    UNIX_EPOCH + Duration::from_nanos(nanos as u64)
+                } else {
+                    UNIX_EPOCH - Duration::from_nanos(nanos.unsigned_abs())
+                })
+            })
+        }
+        _ => {
+            // strings, and the types that are sent as text and parsed by the database
+            let a = cast(array, &DataType::Utf8)?;
+            let a = a.as_string::<i32>();
+            collect(a, |i| SqlValue::Text(a.value(i).to_string()))
+        }
+    })
+}
+
+/// Converts the given columns of a struct array into rows of values
+pub(crate) fn struct_rows(
+    array: &StructArray,
+    columns: &[usize],
+) -> anyhow::Result<Vec<Vec<SqlValue>>> {
+    let mut rows = vec![Vec::with_capacity(columns.len()); array.len()];
+    for i in columns {
+        for (row, value) in rows.iter_mut().zip(sql_values(array.column(*i))?) {
+            row.push(value);
+        }
+    }
+    Ok(rows)
+}
+
+pub(crate) enum Write {
+    Upsert(Vec<SqlValue>),
+    Delete(Vec<SqlValue>),
+}
+
+/// Rows that have been received but not yet written. For keyed tables only the last change
+/// to each key is kept, so that the order of writes within a batch doesn't matter.
+#[derive(Default)]
+pub(crate) struct PendingWrites {
+    pub inserts: Vec<Vec<SqlValue>>,
+    pub keyed: HashMap<String, Write>,
+}
+
+impl PendingWrites {
+    pub fn len(&self) -> usize {
+        self.inserts.len() + self.keyed.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn clear(&mut self) {
+        self.inserts.clear();
+        self.keyed.clear();
+    }
+
+    /// The rows to upsert and the keys to delete
+    pub fn writes(&self) -> (Vec<&Vec<SqlValue>>, Vec<&Vec<SqlValue>>) {
+        let mut upserts: Vec<_> = self.inserts.iter().collect();
+        let mut deletes = vec![];
+        for write in self.keyed.values() {
+            match write {
+                Write::Upsert(row) => upserts.push(row),
+                Write::Delete(key) => deletes.push(key),
+            }
+        }
+        (upserts, deletes)
+    }
+
+    /// Adds a batch of debezium-style changes, whose `after` and `before` structs have a
+    /// field for each of the `columns` of the table, in order
+    pub fn add_batch(
+        &mut self,
+        batch: &RecordBatch,
+        columns: usize,
+        key_indices: &[usize],
+        table: &str,
+    ) -> anyhow::Result<()> {
+        let before = batch.column_by_name("before").unwrap().as_struct();
+        let after = batch.column_by_name("after").unwrap().as_struct();
+        let ops = batch.column_by_name("op").unwrap().as_string::<i32>();
+
+        let all_columns: Vec<_> = (0..columns).collect();
+        let after_rows = struct_rows(after, &all_columns)?;
+        let before_keys = struct_rows(before, key_indices)?;
+
+        let key_of = |row: &[SqlValue]| -> Vec<SqlValue> {
+            key_indices.iter().map(|i| row[*i].clone()).collect()
+        };
+
+        for ((op, row), old_key) in ops.iter().zip(after_rows).zip(before_keys) {
+            let op = op.unwrap_or("c");
+
+            if key_indices.is_empty() {
+                if op != "c" {
+                    bail!(
+                        "received an update or delete for table {}, which has no key columns; \
+                        set key_columns or add a primary key to the table",
+                        table
+                    );
+                }
+                self.inserts.push(row);
+                continue;
+            }
+
+            match op {
+                "c" | "u" => {
+                    let key = key_of(&row);
+                    if op == "u" && old_key != key {
+                        self.keyed
+                            .insert(format!("{:?}", old_key), Write::Delete(old_key));
+                    }
+                    self.keyed.insert(format!("{:?}", key), Write::Upsert(row));
+                }
+                "d" => {
+                    self.keyed
+                        .insert(format!("{:?}", old_key), Write::Delete(old_key));
+                }
+                op => bail!("unknown debezium op '{}'", op),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use arrow::array::{Int64Array, StringArray, TimestampMillisecondArray};
+    use arrow::datatypes::Fields;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_sql_values() {
+        let ids: ArrayRef = Arc::new(Int64Array::from(vec![Some(1), None]));
+        assert_eq!(
+            sql_values(&ids).unwrap(),
+            vec![SqlValue::Int8(1), SqlValue::Null]
+        );
+
+        let names: ArrayRef = Arc::new(StringArray::from(vec!["a", "b"]));
+        assert_eq!(
+            sql_values(&names).unwrap(),
+            vec![
+                SqlValue::Text("a".to_string()),
+                SqlValue::Text("b".to_string())
+            ]
+        );
+
+        let times: ArrayRef = Arc::new(TimestampMillisecondArray::from(vec![1_000, -1_000]));
+        assert_eq!(
+            sql_values(&times).unwrap(),
+            vec![
+                SqlValue::Timestamp(UNIX_EPOCH + Duration::from_secs(1)),
+                SqlValue::Timestamp(UNIX_EPOCH - Duration::from_secs(1))
+            ]
+        );
+    }
+
+    fn changes(ops: Vec<&str>, before: Vec<Option<i64>>, after: Vec<Option<i64>>) -> RecordBatch {
+        let fields = Fields::from(vec![
+            Field::new("id", DataType::Int64, true),
+            Field::new("count", DataType::Int64, true),
+        ]);
+        let row = |ids: Vec<Option<i64>>| -> ArrayRef {
+            let counts = Int64Array::from(
+                ids.iter()
+                    .map(|id| id.map(|id| id * 10))
+                    .collect::<Vec<_>>(),
+            );
+            Arc::new(StructArray::new(
+                fields.clone(),
+                vec![Arc::new(Int64Array::from(ids)), Arc::new(counts)],
+                None,
+            ))
+        };
+
+        RecordBatch::try_from_iter(vec![
+            ("before", row(before)),
+            ("after", row(after)),
+            ("op", Arc::new(StringArray::from(ops)) as ArrayRef),
+        ])
+        .unwrap()
+    }
+
+    #[test]
+    fn test_pending_writes() {
+        let mut pending = PendingWrites::default();
+        pending
+            .add_batch(
+                &changes(
+                    vec!["c", "c", "u", "d"],
+                    vec![None, None, Some(1), Some(2)],
+                    vec![Some(1), Some(2), Some(3), None],
+                ),
+                2,
+                &[0],
+                "counts",
+            )
+            .unwrap();
+
+        // 1 was moved to 3, and 2 was deleted
+        let (upserts, mut deletes) = pending.writes();
+        assert_eq!(upserts, vec![&vec![SqlValue::Int8(3), SqlValue::Int8(30)]]);
+        deletes.sort_by_key(|k| format!("{:?}", k));
+        assert_eq!(
+            deletes,
+            vec![&vec![SqlValue::Int8(1)], &vec![SqlValue::Int8(2)]]
+        );
+
+        // without key columns, only inserts can be written
+        let mut unkeyed = PendingWrites::default();
+        assert!(unkeyed
+            .add_batch(
+                &changes(vec!["c", "d"], vec![None, Some(1)], vec![Some(1), None]),
+                2,
+                &[],
+                "counts",
+            )
+            .is_err());
+    }
+}
@@ -0,0 +1,260 @@
+mod source;
+
+use anyhow::{anyhow, bail, Context};
+use arroyo_operator::connector::{Connection, Connector};
+use arroyo_operator::operator::OperatorNode;
+use arroyo_rpc::api_types::connections::{
+    ConnectionProfile, ConnectionSchema, ConnectionType, TestSourceMessage,
+};
+use arroyo_rpc::OperatorConfig;
+use aws_config::{from_env, Region};
+use aws_sdk_sqs::types::QueueAttributeName;
+use aws_sdk_sqs::Client as SqsClient;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use typify::import_types;
+
+use crate::sqs::source::SqsSourceFunc;
+use crate::{pull_opt, pull_option_to_i64, EmptyConfig};
+
+const TABLE_SCHEMA: &str = include_str!("./table.json");
+
+import_types!(schema = "src/sqs/table.json");
+
+pub struct SqsConnector {}
+
+pub(crate) async fn create_client(aws_region: Option<&String>) -> SqsClient {
+    let mut loader = from_env();
+    if let Some(region) = aws_region {
+        loader = loader.region(Region::new(region.clone()));
+    }
+    SqsClient::new(&loader.load().await)
+}
+
+async fn test_inner(table: &SqsTable) -> anyhow::Result<String> {
+    let client = create_client(table.aws_region.as_ref()).await;
+
+    let attributes = client
+        .get_queue_attributes()
+        .queue_url(&table.queue_url)
+        .attribute_names(QueueAttributeName::ApproximateNumberOfMessages)
+        .send()
+        .await
+        .with_context(|| format!("failed to read attributes of queue {}", table.queue_url))?;
+
+    let messages = attributes
+        .attributes()
+        .and_then(|a| a.get(&QueueAttributeName::ApproximateNumberOfMessages))
+        .map(|n| n.as_str())
+        .unwrap_or("an unknown number of");
+
+    Ok(format!(
+        "Successfully connected to queue, which has approximately {} messages available",
+        messages
+    ))
+}
+
+impl Connector for SqsConnector {
+    type ProfileT = EmptyConfig;
+    type TableT = SqsTable;
+
+    fn name(&self) -> &'static str {
+        "sqs"
+    }
+
+    fn metadata(&self) -> arroyo_rpc::api_types::connections::Connector {
+        arroyo_rpc::api_types::connections::Connector {
+            id: "sqs".to_string(),
+            name: "Amazon SQS".to_string(),
+            icon: "".to_string(),
+            description: "Read messages from an Amazon SQS queue".to_string(),
+            enabled: true,
+            source: true,
+            sink: false,
+            testing: true,
+            hidden: false,
+            custom_schemas: true,
+            connection_config: None,
+            table_config: TABLE_SCHEMA.to_owned(),
+        }
+    }
+
+    fn table_type(&self, _: Self::ProfileT, _: Self::TableT) -> ConnectionType {
+        ConnectionType::Source
+    }
+
+    fn get_schema(
+        &self,
+        _: Self::ProfileT,
+        _: Self::TableT,
+        s: Option<&ConnectionSchema>,
+    ) -> Option<ConnectionSchema> {
+        s.cloned()
+    }
+
+    fn test(
+        &self,
+        _: &str,
+        _: Self::ProfileT,
+        table: Self::TableT,
+        _: Option<&ConnectionSchema>,
+        tx: tokio::sync::mpsc::Sender<TestSourceMessage>,
+    ) {
+        tokio::task::spawn(async move {
+            let message = match test_inner(&table).await {
+                Ok(m) => TestSourceMessage::done(m),
+                Err(e) => TestSourceMessage::fail(format!("{:#}", e)),
+            };
+
+            tx.send(message).await.unwrap();
+        });
+    }
+
+    fn from_options(
+        &self,
+        name: &str,
+        options: &mut HashMap<String, String>,
+        schema: Option<&ConnectionSchema>,
+        _: Option<&ConnectionProfile>,
+    ) -> anyhow::Result<Connection> {
+        let table = SqsTable {
+            queue_url: pull_opt("queue_url", options)?,
+            aws_region: options.remove("aws_region"),
+            wait_time_seconds: pull_option_to_i64("wait_time_seconds", options)?.unwrap_or(20),
+            visibility_timeout_seconds: pull_option_to_i64("visibility_timeout_seconds", options)?,
+        };
+
+        self.from_config(None, name, EmptyConfig {}, table, schema)
+    }
+
+    fn from_config(
+        &self,
+        id: Option<i64>,
+        name: &str,
+        config: Self::ProfileT,
+        table: Self::TableT,
+        schema: Option<&ConnectionSchema>,
+    ) -> anyhow::Result<Connection> {
+        if !(0..=20).contains(&table.wait_time_seconds) {
+            bail!("wait_time_seconds must be between 0 and 20");
+        }
+
+        if let Some(timeout) = table.visibility_timeout_seconds {
+            if !(0..=43_200).contains(&timeout) {
+                bail!("visibility_timeout_seconds must be between 0 and 43200 (12 hours)");
+            }
+        }
+
+        let schema = schema
+            .map(|s| s.to_owned())
+            .ok_or_else(|| anyhow!("no schema defined for SQS source"))?;
+
+        let format = schema
+            .format
+            .clone()
+            .ok_or_else(|| anyhow!("'format' must be set for SQS sources"))?;
+
+        let description = format!("SqsSource<{}>", table.queue_url);
+
+        let config = OperatorConfig {
+            connection: serde_json::to_value(config).unwrap(),
+            table: serde_json::to_value(table).unwrap(),
+            rate_limit: None,
+            format: Some(format),
+            bad_data: schema.bad_data.clone(),
+            framing: schema.framing.clone(),
+            metadata_fields: schema.metadata_fields(),
+        };
+
+        Ok(Connection {
+            id,
+            connector: self.name(),
+            name: name.to_string(),
+            connection_type: ConnectionType::Source,
+            schema,
+            config: serde_json::to_string(&config).unwrap(),
+            description,
+        })
+    }
+
+    fn make_operator(
+        &self,
+        _: Self::ProfileT,
+        table: Self::TableT,
+        config: OperatorConfig,
+    ) -> anyhow::Result<OperatorNode> {
+        Ok(OperatorNode::from_source(Box::new(SqsSourceFunc::new(
+            table,
+            config
+                .format
+                .ok_or_else(|| anyhow!("format required for SQS source"))?,
+            config.framing,
+            config.bad_data,
+        ))))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use arroyo_rpc::formats::{Format, JsonFormat};
+
+    fn options(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_from_options() {
+        let schema = ConnectionSchema {
+            format: Some(Format::Json(JsonFormat::default())),
+            bad_data: None,
+            framing: None,
+            struct_name: None,
+            fields: vec![],
+            definition: None,
+            inferred: None,
+        };
+
+        let url = "https://sqs.us-east-1.amazonaws.com/123456789012/events";
+        let connection = SqsConnector {}
+            .from_options(
+                "events",
+                &mut options(&[("queue_url", url), ("visibility_timeout_seconds", "300")]),
+                Some(&schema),
+                None,
+            )
+            .unwrap();
+
+        let config: OperatorConfig = serde_json::from_str(&connection.config).unwrap();
+        let table: SqsTable = serde_json::from_value(config.table).unwrap();
+        assert_eq!(table.queue_url, url);
+        assert_eq!(table.wait_time_seconds, 20);
+        assert_eq!(table.visibility_timeout_seconds, Some(300));
+
+        for invalid in [
+            vec![],
+            vec![("queue_url", url), ("wait_time_seconds", "21")],
+            vec![("queue_url", url), ("visibility_timeout_seconds", "-1")],
+        ] {
+            assert!(SqsConnector {}
+                .from_options("events", &mut options(&invalid), Some(&schema), None)
+                .is_err());
+        }
+
+        // a format is required to deserialize message bodies
+        assert!(SqsConnector {}
+            .from_options(
+                "events",
+                &mut options(&[("queue_url", url)]),
+                Some(&ConnectionSchema {
+                    format: None,
+                    ..schema.clone()
+                }),
+                None,
+            )
+            .is_err());
+    }
+}
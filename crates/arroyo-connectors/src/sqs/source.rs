@@ -0,0 +1,507 @@
+use std::collections::{BTreeMap, HashMap};
+use std::time::{Duration, SystemTime};
+
+use anyhow::Context as AnyhowContext;
+use arroyo_operator::context::ArrowContext;
+use arroyo_operator::operator::SourceOperator;
+use arroyo_operator::SourceFinishType;
+use arroyo_rpc::formats::{BadData, Format, Framing};
+use arroyo_rpc::grpc::rpc::{StopMode, TableConfig, TaskCheckpointEventType};
+use arroyo_rpc::{CheckpointEvent, ControlMessage, ControlResp};
+use arroyo_state::tables::global_keyed_map::GlobalKeyedView;
+use arroyo_types::{from_millis, UserError};
+use async_trait::async_trait;
+use aws_sdk_sqs::operation::receive_message::ReceiveMessageOutput;
+use aws_sdk_sqs::types::{
+    DeleteMessageBatchRequestEntry, Message as SqsMessage, MessageSystemAttributeName,
+};
+use aws_sdk_sqs::Client as SqsClient;
+use futures::future::BoxFuture;
+use tokio::select;
+use tokio::time::MissedTickBehavior;
+use tracing::{debug, info, warn};
+
+use crate::sqs::{create_client, SqsTable};
+
+/// The most messages SQS will return from a single receive, or delete in a single batch
+const MAX_BATCH_SIZE: i32 = 10;
+
+/// The longest visibility timeout SQS allows, which is how long receipt handles are kept for
+/// queues whose timeout isn't configured on the table
+const MAX_VISIBILITY_TIMEOUT: Duration = Duration::from_secs(43_200);
+
+pub struct SqsSourceFunc {
+    table: SqsTable,
+    format: Format,
+    framing: Option<Framing>,
+    bad_data: Option<BadData>,
+    /// receipt handles of the messages read since the last checkpoint
+    in_flight: Vec<String>,
+    /// receipt handles of the messages in each checkpoint that has not yet been committed; these
+    /// are deleted from the queue once their checkpoint commits
+    uncommitted: BTreeMap<u32, Vec<String>>,
+}
+
+impl SqsSourceFunc {
+    pub fn new(
+        table: SqsTable,
+        format: Format,
+        framing: Option<Framing>,
+        bad_data: Option<BadData>,
+    ) -> Self {
+        Self {
+            table,
+            format,
+            framing,
+            bad_data,
+            in_flight: vec![],
+            uncommitted: BTreeMap::new(),
+        }
+    }
+
+    /// Moves the messages read since the last checkpoint into checkpoint `epoch`, returning the
+    /// handles of every message that is not yet committed
+    fn stage(&mut self, epoch: u32) -> Vec<String> {
+        self.uncommitted
+            .insert(epoch, std::mem::take(&mut self.in_flight));
+
+        self.uncommitted.values().flatten().cloned().collect()
+    }
+
+    /// Removes and returns the handles of the messages in every checkpoint up to and including
+    /// `epoch`
+    fn take_committed(&mut self, epoch: u32) -> Vec<String> {
+        let remaining = self.uncommitted.split_off(&(epoch + 1));
+        std::mem::replace(&mut self.uncommitted, remaining)
+            .into_values()
+            .flatten()
+            .collect()
+    }
+
+    /// How long the receipt handles in the state are kept without being updated; handles can't be
+    /// used to delete their messages once the visibility timeout has passed, so there's no point
+    /// in keeping them longer, for example after the subtask that read them has gone away
+    fn receipt_handle_ttl(&self) -> Duration {
+        self.table
+            .visibility_timeout_seconds
+            .map(|t| Duration::from_secs(t as u64))
+            .unwrap_or(MAX_VISIBILITY_TIMEOUT)
+    }
+
+    /// Long-polls the queue for messages, after waiting for `delay`. The returned future owns
+    /// everything it needs so that it can be kept across iterations of the run loop; dropping
+    /// it after SQS has returned messages would leave them invisible until their visibility
+    /// timeout expires.
+    fn receive(
+        &self,
+        client: &SqsClient,
+        delay: Duration,
+    ) -> BoxFuture<'static, anyhow::Result<ReceiveMessageOutput>> {
+        let request = client
+            .receive_message()
+            .queue_url(&self.table.queue_url)
+            .max_number_of_messages(MAX_BATCH_SIZE)
+            .wait_time_seconds(self.table.wait_time_seconds as i32)
+            .set_visibility_timeout(self.table.visibility_timeout_seconds.map(|t| t as i32))
+            .message_system_attribute_names(MessageSystemAttributeName::SentTimestamp);
+
+        Box::pin(async move {
+            if !delay.is_zero() {
+                tokio::time::sleep(delay).await;
+            }
+
+            request
+                .send()
+                .await
+                .context("failed to receive messages from SQS")
+        })
+    }
+
+    async fn process_messages(
+        &mut self,
+        output: ReceiveMessageOutput,
+        ctx: &mut ArrowContext,
+    ) -> Result<(), UserError> {
+        for message in output.messages.unwrap_or_default() {
+            let Some(receipt_handle) = message.receipt_handle else {
+                continue;
+            };
+
+            let timestamp = sent_timestamp(&message).unwrap_or_else(SystemTime::now);
+
+            if let Some(body) = &message.body {
+                ctx.deserialize_slice(body.as_bytes(), timestamp, None)
+                    .await?;
+            }
+
+            self.in_flight.push(receipt_handle);
+
+            if ctx.should_flush() {
+                ctx.flush_buffer().await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Records the messages read in this epoch so that they can be deleted once the checkpoint
+    /// commits, or after restoring from it if the job fails before then
+    async fn checkpoint_messages(&mut self, epoch: u32, ctx: &mut ArrowContext) {
+        let handles = self.stage(epoch);
+
+        let state: &mut GlobalKeyedView<usize, Vec<String>> = ctx
+            .table_manager
+            .get_global_keyed_state("h")
+            .await
+            .expect("should be able to get SQS state");
+
+        state.insert(ctx.task_info.task_index, handles).await;
+
+        // the handles are kept in memory until the commit, so the commit only needs to signal
+        // that the checkpoint has completed
+        ctx.table_manager
+            .insert_committing_data("h", vec![])
+            .await
+            .expect("should be able to send committing data");
+    }
+
+    /// Deletes the messages of every checkpoint up to and including `epoch`, which has now
+    /// committed
+    async fn commit(&mut self, client: &SqsClient, epoch: u32, ctx: &mut ArrowContext) {
+        let committed = self.take_committed(epoch);
+
+        debug!(
+            "deleting {} SQS messages committed in epoch {}",
+            committed.len(),
+            epoch
+        );
+
+        if let Err(e) = delete_messages(client, &self.table.queue_url, &committed).await {
+            // the messages will be delivered again once their visibility timeout expires
+            warn!("failed to delete committed SQS messages: {:?}", e);
+            ctx.report_error("Failed to delete committed messages", format!("{:#}", e))
+                .await;
+        }
+
+        ctx.control_tx
+            .send(ControlResp::CheckpointEvent(CheckpointEvent {
+                checkpoint_epoch: epoch,
+                operator_id: ctx.task_info.operator_id.clone(),
+                subtask_index: ctx.task_info.task_index as u32,
+                time: SystemTime::now(),
+                event_type: TaskCheckpointEventType::FinishedCommit,
+            }))
+            .await
+            .expect("sent commit event");
+    }
+
+    /// Deletes the messages of the checkpoint we restored from. It completed, so its messages
+    /// have been processed, but the job may have failed before they were deleted.
+    async fn delete_restored(&mut self, client: &SqsClient, ctx: &mut ArrowContext) {
+        let state: &mut GlobalKeyedView<usize, Vec<String>> = ctx
+            .table_manager
+            .get_global_keyed_state("h")
+            .await
+            .expect("should be able to get SQS state");
+
+        let restored = restored_handles(
+            state.get_all().iter(),
+            ctx.task_info.task_index,
+            ctx.task_info.parallelism,
+        );
+
+        if restored.is_empty() {
+            return;
+        }
+
+        info!(
+            "deleting {} SQS messages from the restored checkpoint",
+            restored.len()
+        );
+
+        if let Err(e) = delete_messages(client, &self.table.queue_url, &restored).await {
+            warn!("failed to delete restored SQS messages: {:?}", e);
+            ctx.report_error("Failed to delete restored messages", format!("{:#}", e))
+                .await;
+        }
+    }
+
+    async fn run_int(&mut self, ctx: &mut ArrowContext) -> Result<SourceFinishType, UserError> {
+        let client = create_client(self.table.aws_region.as_ref()).await;
+
+        self.delete_restored(&client, ctx).await;
+
+        let mut flush_ticker = tokio::time::interval(Duration::from_millis(50));
+        flush_ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        let mut attempts: u32 = 0;
+        let mut receive = self.receive(&client, Duration::ZERO);
+
+        loop {
+            select! {
+                result = &mut receive => {
+                    let delay = match result {
+                        Ok(output) => {
+                            attempts = 0;
+                            self.process_messages(output, ctx).await?;
+                            Duration::ZERO
+                        }
+                        Err(e) => {
+                            attempts += 1;
+                            warn!("{:?}", e);
+                            ctx.report_error("Failed to receive messages", format!("{:#}", e))
+                                .await;
+                            Duration::from_millis((50 * (1 << attempts.min(10))).min(5_000))
+                        }
+                    };
+
+                    receive = self.receive(&client, delay);
+                }
+                _ = flush_ticker.tick() => {
+                    if ctx.should_flush() {
+                        ctx.flush_buffer().await?;
+                    }
+                }
+                control_message = ctx.control_rx.recv() => {
+                    match control_message {
+                        Some(ControlMessage::Checkpoint(c)) => {
+                            debug!("starting checkpointing {}", ctx.task_info.task_index);
+                            self.checkpoint_messages(c.epoch, ctx).await;
+                            if self.start_checkpoint(c, ctx).await {
+                                // wait for the final checkpoint to commit so that its messages
+                                // are deleted before we finish
+                                if let Some(ControlMessage::Commit { epoch, .. }) = ctx.control_rx.recv().await {
+                                    self.commit(&client, epoch, ctx).await;
+                                } else {
+                                    warn!("no commit message received, not deleting messages");
+                                }
+                                return Ok(SourceFinishType::Immediate);
+                            }
+                        },
+                        Some(ControlMessage::Stop { mode }) => {
+                            info!("Stopping SQS source: {:?}", mode);
+
+                            match mode {
+                                StopMode::Graceful => {
+                                    return Ok(SourceFinishType::Graceful);
+                                }
+                                StopMode::Immediate => {
+                                    return Ok(SourceFinishType::Immediate);
+                                }
+                            }
+                        }
+                        Some(ControlMessage::Commit { epoch, .. }) => {
+                            self.commit(&client, epoch, ctx).await;
+                        }
+                        Some(ControlMessage::LoadCompacted { compacted }) => {
+                            ctx.load_compacted(compacted).await;
+                        }
+                        Some(ControlMessage::QueryState { query }) => {
+                            ctx.query_state(query).await;
+                        }
+                        Some(ControlMessage::NoOp) => {}
+                        None => {}
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// The time at which SQS received the message, if it was returned with the message
+fn sent_timestamp(message: &SqsMessage) -> Option<SystemTime> {
+    message
+        .attributes
+        .as_ref()
+        .and_then(|a| a.get(&MessageSystemAttributeName::SentTimestamp))
+        .and_then(|t| t.parse().ok())
+        .map(from_millis)
+}
+
+/// The restored receipt handles that this subtask is responsible for deleting; if the job
+/// was restored at a lower parallelism, a subtask takes over the handles of several old ones
+fn restored_handles<'a>(
+    state: impl Iterator<Item = (&'a usize, &'a Vec<String>)>,
+    task_index: usize,
+    parallelism: usize,
+) -> Vec<String> {
+    state
+        .filter(|(i, _)| **i % parallelism == task_index)
+        .flat_map(|(_, handles)| handles.iter().cloned())
+        .collect()
+}
+
+/// Deletes the messages with the given receipt handles from the queue. Handles whose
+/// visibility timeout has expired can no longer be used, and are logged and skipped.
+async fn delete_messages(
+    client: &SqsClient,
+    queue_url: &str,
+    receipt_handles: &[String],
+) -> anyhow::Result<()> {
+    for chunk in receipt_handles.chunks(MAX_BATCH_SIZE as usize) {
+        let entries = chunk
+            .iter()
+            .enumerate()
+            .map(|(i, handle)| {
+                DeleteMessageBatchRequestEntry::builder()
+                    .id(i.to_string())
+                    .receipt_handle(handle)
+                    .build()
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let output = client
+            .delete_message_batch()
+            .queue_url(queue_url)
+            .set_entries(Some(entries))
+            .send()
+            .await
+            .context("failed to delete messages from SQS")?;
+
+        for failure in output.failed() {
+            warn!(
+                "failed to delete SQS message: {} ({})",
+                failure.message().unwrap_or_default(),
+                failure.code()
+            );
+        }
+    }
+
+    Ok(())
+}
+
+#[async_trait]
+impl SourceOperator for SqsSourceFunc {
+    fn name(&self) -> String {
+        "sqs".to_string()
+    }
+
+    fn tables(&self) -> HashMap<String, TableConfig> {
+        arroyo_state::global_table_config_with_ttl(
+            "h",
+            "receipt handles of uncommitted SQS messages",
+            self.receipt_handle_ttl(),
+            true,
+        )
+    }
+
+    async fn on_start(&mut self, ctx: &mut ArrowContext) {
+        ctx.initialize_deserializer(
+            self.format.clone(),
+            self.framing.clone(),
+            self.bad_data.clone(),
+        );
+    }
+
+    async fn run(&mut self, ctx: &mut ArrowContext) -> SourceFinishType {
+        match self.run_int(ctx).await {
+            Ok(r) => r,
+            Err(UserError { name, details, .. }) => {
+                ctx.report_error(name.clone(), details.clone()).await;
+                panic!("{}: {}", name, details);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use arroyo_rpc::formats::JsonFormat;
+    use arroyo_rpc::grpc::rpc::GlobalKeyedTableConfig;
+    use prost::Message;
+
+    fn source() -> SqsSourceFunc {
+        SqsSourceFunc::new(
+            SqsTable {
+                queue_url: "https://sqs.us-east-1.amazonaws.com/123456789012/events".to_string(),
+                aws_region: None,
+                wait_time_seconds: 20,
+                visibility_timeout_seconds: None,
+            },
+            Format::Json(JsonFormat::default()),
+            None,
+            None,
+        )
+    }
+
+    fn handles(names: &[&str]) -> Vec<String> {
+        names.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_commit_deletes_checkpointed_messages() {
+        let mut source = source();
+
+        source.in_flight = handles(&["a", "b"]);
+        assert_eq!(source.stage(1), handles(&["a", "b"]));
+        assert!(source.in_flight.is_empty());
+
+        source.in_flight = handles(&["c"]);
+        assert_eq!(source.stage(2), handles(&["a", "b", "c"]));
+
+        source.in_flight = handles(&["d"]);
+        assert_eq!(source.stage(3), handles(&["a", "b", "c", "d"]));
+
+        // a commit covers every earlier checkpoint, whose commits may have been skipped
+        assert_eq!(source.take_committed(2), handles(&["a", "b", "c"]));
+        assert_eq!(source.take_committed(2), Vec::<String>::new());
+
+        // messages read after the checkpoint aren't committed with it
+        source.in_flight = handles(&["e"]);
+        assert_eq!(source.take_committed(3), handles(&["d"]));
+        assert_eq!(source.in_flight, handles(&["e"]));
+        assert!(source.uncommitted.is_empty());
+    }
+
+    #[test]
+    fn test_sent_timestamp() {
+        let message = SqsMessage::builder()
+            .attributes(MessageSystemAttributeName::SentTimestamp, "1700000000123")
+            .build();
+        assert_eq!(sent_timestamp(&message), Some(from_millis(1700000000123)));
+
+        assert_eq!(sent_timestamp(&SqsMessage::builder().build()), None);
+
+        let message = SqsMessage::builder()
+            .attributes(MessageSystemAttributeName::SentTimestamp, "yesterday")
+            .build();
+        assert_eq!(sent_timestamp(&message), None);
+    }
+
+    #[test]
+    fn test_restored_handles() {
+        let state: HashMap<usize, Vec<String>> = [
+            (0, handles(&["a"])),
+            (1, handles(&["b"])),
+            (2, handles(&["c", "d"])),
+        ]
+        .into_iter()
+        .collect();
+
+        let mut restored = restored_handles(state.iter(), 0, 2);
+        restored.sort();
+        assert_eq!(restored, handles(&["a", "c", "d"]));
+        assert_eq!(restored_handles(state.iter(), 1, 2), handles(&["b"]));
+        assert_eq!(restored_handles(state.iter(), 3, 4), Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_receipt_handle_ttl() {
+        let config = |source: &SqsSourceFunc| {
+            GlobalKeyedTableConfig::decode(source.tables().remove("h").unwrap().config.as_slice())
+                .unwrap()
+        };
+
+        let mut source = source();
+        let table = config(&source);
+        assert!(table.uses_two_phase_commit);
+        assert_eq!(
+            table.ttl_micros,
+            Some(MAX_VISIBILITY_TIMEOUT.as_micros() as u64)
+        );
+
+        source.table.visibility_timeout_seconds = Some(300);
+        assert_eq!(config(&source).ttl_micros, Some(300_000_000));
+    }
+}
@@ -18,6 +18,7 @@ use arroyo_rpc::api_types::connections::{
 };
 use serde::{Deserialize, Serialize};
 
+use crate::oauth::{oauth_config, with_oauth_schema, OAuthOptions, TokenProvider};
 use crate::sse::operator::SSESourceFunc;
 use crate::{pull_opt, EmptyConfig};
 
@@ -25,7 +26,13 @@ use arroyo_operator::connector::Connector;
 
 const TABLE_SCHEMA: &str = include_str!("./table.json");
 
-import_types!(schema = "src/sse/table.json", convert = { {type = "string", format = "var-str"} = VarStr });
+import_types!(
+    schema = "src/sse/table.json",
+    convert = {
+        {type = "string", format = "var-str"} = VarStr,
+        {type = "object", format = "oauth"} = OAuthOptions
+    }
+);
 const ICON: &str = include_str!("./sse.svg");
 
 pub struct SSEConnector {}
@@ -52,7 +59,7 @@ impl Connector for SSEConnector {
             hidden: false,
             custom_schemas: true,
             connection_config: None,
-            table_config: TABLE_SCHEMA.to_owned(),
+            table_config: with_oauth_schema(TABLE_SCHEMA),
         }
     }
 
@@ -71,6 +78,11 @@ impl Connector for SSEConnector {
         ConnectionType::Source
     }
 
+    fn max_source_parallelism(&self, _: Self::ProfileT, _: Self::TableT) -> Option<usize> {
+        // only the first subtask reads from the event stream
+        Some(1)
+    }
+
     fn from_config(
         &self,
         id: Option<i64>,
@@ -90,6 +102,8 @@ impl Connector for SSEConnector {
             })?;
         }
 
+        oauth_config(table.oauth.as_ref())?;
+
         let schema = schema
             .map(|s| s.to_owned())
             .ok_or_else(|| anyhow!("no schema defined for SSE connection"))?;
@@ -140,6 +154,7 @@ impl Connector for SSEConnector {
                 endpoint,
                 events,
                 headers: headers.map(VarStr::new),
+                oauth: OAuthOptions::from_options(options)?,
             },
             schema,
         )
@@ -203,6 +218,13 @@ impl SseTester {
                 .map_err(|_| anyhow!("Invalid header '{}: {}'", k, v))?;
         }
 
+        if let Some(oauth) = oauth_config(self.config.oauth.as_ref())? {
+            let authorization = TokenProvider::new(oauth).authorization().await?;
+            client = client
+                .header("Authorization", &authorization)
+                .map_err(|_| anyhow!("OAuth token is not a valid header value"))?;
+        }
+
         let mut stream = client.build().stream();
 
         let timeout = Duration::from_secs(30);
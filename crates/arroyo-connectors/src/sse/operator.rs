@@ -1,3 +1,4 @@
+use crate::oauth::{oauth_config, TokenProvider};
 use crate::sse::SseTable;
 use arroyo_operator::context::ArrowContext;
 use arroyo_operator::operator::{OperatorNode, SourceOperator};
@@ -9,13 +10,17 @@ use arroyo_state::tables::global_keyed_map::GlobalKeyedView;
 use arroyo_types::{string_to_map, ArrowMessage, SignalMessage, UserError, Watermark};
 use async_trait::async_trait;
 use bincode::{Decode, Encode};
-use eventsource_client::{Client, Error, SSE};
+use eventsource_client::{Client, Error, ReconnectOptions, SSE};
+use futures::stream::BoxStream;
 use futures::StreamExt;
 use std::collections::{HashMap, HashSet};
-use std::time::{Duration, Instant, SystemTime};
+use std::time::{Duration, SystemTime};
 use tokio::select;
-use tokio::time::MissedTickBehavior;
-use tracing::{debug, info};
+use tokio::time::{Instant, MissedTickBehavior};
+use tracing::{debug, info, warn};
+
+/// Number of times in a row that connecting to the server may fail before the source fails
+const MAX_RECONNECT_ATTEMPTS: u32 = 10;
 
 #[derive(Clone, Debug, Encode, Decode, PartialEq, PartialOrd, Default)]
 pub struct SSESourceState {
@@ -29,6 +34,7 @@ pub struct SSESourceFunc {
     format: Format,
     framing: Option<Framing>,
     bad_data: Option<BadData>,
+    oauth: Option<TokenProvider>,
     state: SSESourceState,
 }
 
@@ -39,6 +45,8 @@ impl SSESourceFunc {
             .as_ref()
             .map(|s| s.sub_env_vars().expect("Failed to substitute env vars"));
 
+        let oauth = oauth_config(table.oauth.as_ref())?.map(TokenProvider::new);
+
         Ok(OperatorNode::from_source(Box::new(SSESourceFunc {
             url: table.endpoint,
             headers: string_to_map(&headers.unwrap_or("".to_string()), ':')
@@ -52,6 +60,7 @@ impl SSESourceFunc {
             format: config.format.expect("SSE requires a format"),
             framing: config.framing,
             bad_data: config.bad_data,
+            oauth,
             state: SSESourceState::default(),
         })))
     }
@@ -127,19 +136,23 @@ impl SSESourceFunc {
             ControlMessage::LoadCompacted { compacted } => {
                 ctx.load_compacted(compacted).await;
             }
+            ControlMessage::QueryState { query } => {
+                ctx.query_state(query).await;
+            }
             ControlMessage::NoOp => {}
         }
         None
     }
 
-    async fn run_int(&mut self, ctx: &mut ArrowContext) -> Result<SourceFinishType, UserError> {
-        ctx.initialize_deserializer(
-            self.format.clone(),
-            self.framing.clone(),
-            self.bad_data.clone(),
-        );
-
-        let mut client = eventsource_client::ClientBuilder::for_url(&self.url).unwrap();
+    /// Opens a stream of events that resumes after the last event we've received, by sending its
+    /// id as `Last-Event-ID`. We reconnect ourselves rather than letting the client do it, so that
+    /// each connection uses the id tracked in our state and a fresh OAuth token.
+    async fn connect(
+        &self,
+    ) -> Result<BoxStream<'static, eventsource_client::Result<SSE>>, UserError> {
+        let mut client = eventsource_client::ClientBuilder::for_url(&self.url)
+            .map_err(|e| UserError::new("invalid EventSource URL", format!("{:?}", e)))?
+            .reconnect(ReconnectOptions::reconnect(false).build());
 
         if let Some(id) = &self.state.last_id {
             client = client.last_event_id(id.clone());
@@ -149,23 +162,57 @@ impl SSESourceFunc {
             client = client.header(k, v).unwrap();
         }
 
-        let mut stream = client.build().stream();
+        if let Some(oauth) = &self.oauth {
+            let authorization = oauth
+                .authorization()
+                .await
+                .map_err(|e| UserError::new("failed to fetch OAuth token", format!("{:#}", e)))?;
+            client = client
+                .header("Authorization", &authorization)
+                .map_err(|_| {
+                    UserError::new(
+                        "invalid OAuth token",
+                        "token endpoint returned a token that is not a valid header value",
+                    )
+                })?;
+        }
+
+        Ok(client.build().stream())
+    }
+
+    async fn run_int(&mut self, ctx: &mut ArrowContext) -> Result<SourceFinishType, UserError> {
+        ctx.initialize_deserializer(
+            self.format.clone(),
+            self.framing.clone(),
+            self.bad_data.clone(),
+        );
+
         let events: HashSet<_> = self.events.iter().cloned().collect();
 
         let mut flush_ticker = tokio::time::interval(Duration::from_millis(50));
         flush_ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
 
+        let mut stream = None;
+        let mut reconnect_at = Instant::now();
+        let mut failures: u32 = 0;
         let mut last_eof = Instant::now();
 
         // since there's no way to partition across an event source, only read on the first task
         if ctx.task_info.task_index == 0 {
             loop {
                 select! {
-                    message = stream.next()  => {
+                    _ = tokio::time::sleep_until(reconnect_at), if stream.is_none() => {
+                        if self.state.last_id.is_some() {
+                            info!("connecting to EventSource, resuming after event {:?}", self.state.last_id);
+                        }
+                        stream = Some(self.connect().await?);
+                    }
+                    message = async { stream.as_mut().unwrap().next().await }, if stream.is_some() => {
                         match message {
                             Some(Ok(msg)) => {
                                 match msg {
                                     SSE::Event(event) => {
+                                        failures = 0;
                                         if let Some(id) = event.id {
                                             self.state.last_id = Some(id);
                                         }
@@ -185,25 +232,39 @@ impl SSESourceFunc {
                                     SSE::Connected(_) => {}
                                 }
                             }
-                            Some(Err(Error::Eof)) => {
+                            Some(Err(e)) => {
+                                stream = None;
+
                                 // Many SSE servers will periodically send an EOF; just reconnect
                                 // and continue on unless we immediately get another
-                                if last_eof.elapsed() < Duration::from_secs(5) {
-                                    ctx.report_user_error(UserError::new("Error while reading from EventSource",
-                                    "Received repeated EOF from EventSource server")).await;
-                                    panic!("Error while reading from EventSource: EOF");
+                                let repeated_eof = last_eof.elapsed() < Duration::from_secs(5);
+                                if matches!(e, Error::Eof) && !repeated_eof {
+                                    last_eof = Instant::now();
+                                    reconnect_at = Instant::now();
+                                    continue;
                                 }
-                                last_eof = Instant::now();
-                            }
-                            Some(Err(e)) => {
-                                ctx.control_tx.send(
-                                    ControlResp::Error {
-                                        operator_id: ctx.task_info.operator_id.clone(),
-                                        task_index: ctx.task_info.task_index,
-                                        message: "Error while reading from EventSource".to_string(),
-                                        details: format!("{:?}", e)}
-                                ).await.unwrap();
-                                panic!("Error while reading from EventSource: {:?}", e);
+                                if matches!(e, Error::Eof) {
+                                    last_eof = Instant::now();
+                                } else if let Some(oauth) = &self.oauth {
+                                    // the server may have rejected our token
+                                    oauth.invalidate().await;
+                                }
+
+                                failures += 1;
+                                if failures > MAX_RECONNECT_ATTEMPTS {
+                                    ctx.control_tx.send(
+                                        ControlResp::Error {
+                                            operator_id: ctx.task_info.operator_id.clone(),
+                                            task_index: ctx.task_info.task_index,
+                                            message: "Error while reading from EventSource".to_string(),
+                                            details: format!("{:?}", e)}
+                                    ).await.unwrap();
+                                    panic!("Error while reading from EventSource after {} attempts: {:?}", failures, e);
+                                }
+
+                                let backoff = Duration::from_millis((100 * (1 << failures)).min(30_000));
+                                warn!("Error while reading from EventSource, reconnecting in {:?}: {:?}", backoff, e);
+                                reconnect_at = Instant::now() + backoff;
                             }
                             None => {
                                 info!("Socket closed");
@@ -239,3 +300,135 @@ impl SSESourceFunc {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{SSESourceFunc, SSESourceState};
+    use arrow::array::{Array, StringArray};
+    use arrow::datatypes::{DataType, Field, Schema, TimeUnit};
+    use arroyo_operator::context::{batch_bounded, ArrowContext};
+    use arroyo_operator::operator::SourceOperator;
+    use arroyo_rpc::df::ArroyoSchema;
+    use arroyo_rpc::formats::{Format, RawStringFormat};
+    use arroyo_rpc::grpc::rpc::StopMode;
+    use arroyo_rpc::ControlMessage;
+    use arroyo_types::ArrowMessage;
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+    use tokio::sync::mpsc::{channel, unbounded_channel};
+    use tokio::time::timeout;
+
+    #[tokio::test]
+    async fn test_resume_after_dropped_connection() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        // the server sends two events then drops the connection, and sends a third to whoever
+        // reconnects; it reports the request headers of each connection
+        let (requests_tx, mut requests_rx) = unbounded_channel();
+        tokio::spawn(async move {
+            for events in ["id: 1\ndata: a\n\nid: 2\ndata: b\n\n", "id: 3\ndata: c\n\n"] {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let mut request = vec![];
+                let mut buf = [0; 1024];
+                while !request.ends_with(b"\r\n\r\n") {
+                    let n = socket.read(&mut buf).await.unwrap();
+                    assert!(n > 0, "client hung up before sending its request");
+                    request.extend_from_slice(&buf[..n]);
+                }
+                requests_tx
+                    .send(String::from_utf8(request).unwrap().to_lowercase())
+                    .unwrap();
+
+                socket
+                    .write_all(
+                        format!(
+                            "HTTP/1.1 200 OK\r\ncontent-type: text/event-stream\r\ncache-control: no-cache\r\nconnection: close\r\n\r\n{}",
+                            events
+                        )
+                        .as_bytes(),
+                    )
+                    .await
+                    .unwrap();
+                socket.flush().await.unwrap();
+            }
+        });
+
+        let mut source = SSESourceFunc {
+            url: format!("http://127.0.0.1:{}/events", port),
+            headers: vec![],
+            events: vec![],
+            format: Format::RawString(RawStringFormat {}),
+            framing: None,
+            bad_data: None,
+            oauth: None,
+            state: SSESourceState::default(),
+        };
+
+        let (control_tx, control_rx) = channel(128);
+        let (command_tx, _command_rx) = channel(128);
+        let (data_tx, mut data_rx) = batch_bounded(128);
+
+        let mut ctx = ArrowContext::new(
+            arroyo_types::get_test_task_info(),
+            None,
+            control_rx,
+            command_tx,
+            1,
+            vec![],
+            Some(ArroyoSchema::new_unkeyed(
+                Arc::new(Schema::new(vec![
+                    Field::new(
+                        "_timestamp",
+                        DataType::Timestamp(TimeUnit::Nanosecond, None),
+                        false,
+                    ),
+                    Field::new("value", DataType::Utf8, false),
+                ])),
+                0,
+            )),
+            None,
+            vec![vec![data_tx]],
+            source.tables(),
+        )
+        .await;
+
+        tokio::spawn(async move {
+            source.run(&mut ctx).await;
+        });
+
+        let mut values = vec![];
+        while values.len() < 3 {
+            let message = timeout(Duration::from_secs(10), data_rx.recv())
+                .await
+                .expect("timed out waiting for events")
+                .unwrap();
+            if let ArrowMessage::Data(batch) = message {
+                let column = batch
+                    .column_by_name("value")
+                    .unwrap()
+                    .as_any()
+                    .downcast_ref::<StringArray>()
+                    .unwrap();
+                values.extend(column.iter().map(|v| v.unwrap().to_string()));
+            }
+        }
+
+        // every event is read once, the source picking up where it left off
+        assert_eq!(values, vec!["a", "b", "c"]);
+
+        let first = requests_rx.recv().await.unwrap();
+        assert!(!first.contains("last-event-id"), "{}", first);
+        let second = requests_rx.recv().await.unwrap();
+        assert!(second.contains("\r\nlast-event-id: 2\r\n"), "{}", second);
+
+        control_tx
+            .send(ControlMessage::Stop {
+                mode: StopMode::Immediate,
+            })
+            .await
+            .unwrap();
+    }
+}
@@ -17,14 +17,24 @@ impl ArrowOperator for StdoutSink {
         "Stdout".to_string()
     }
 
-    async fn process_batch(&mut self, batch: RecordBatch, _: &mut ArrowContext) {
+    async fn process_batch(
+        &mut self,
+        batch: RecordBatch,
+        _: &mut ArrowContext,
+    ) -> anyhow::Result<()> {
         for value in self.serializer.serialize(&batch) {
             self.stdout.write_all(&value).await.unwrap();
             self.stdout.write_u8(b'\n').await.unwrap();
         }
         self.stdout.flush().await.unwrap();
+        Ok(())
     }
-    async fn on_close(&mut self, _: &Option<SignalMessage>, _: &mut ArrowContext) {
-        self.stdout.flush().await.unwrap();
+    async fn on_close(
+        &mut self,
+        _: &Option<SignalMessage>,
+        _: &mut ArrowContext,
+    ) -> anyhow::Result<()> {
+        self.stdout.flush().await?;
+        Ok(())
     }
 }
@@ -13,6 +13,7 @@ use arroyo_rpc::api_types::connections::{
     ConnectionProfile, ConnectionSchema, ConnectionType, TestSourceMessage,
 };
 use arroyo_rpc::var_str::VarStr;
+use reqwest::header::{HeaderValue, AUTHORIZATION};
 use reqwest::{Client, Request};
 use serde::{Deserialize, Serialize};
 use serde_json::json;
@@ -20,6 +21,7 @@ use tokio::sync::mpsc::Sender;
 use tokio::sync::{Mutex, Semaphore};
 use typify::import_types;
 
+use crate::oauth::{oauth_config, with_oauth_schema, OAuthOptions, TokenProvider};
 use crate::{construct_http_client, pull_opt, EmptyConfig};
 
 use crate::webhook::operator::WebhookSinkFunc;
@@ -28,7 +30,13 @@ use arroyo_operator::operator::OperatorNode;
 
 const TABLE_SCHEMA: &str = include_str!("./table.json");
 
-import_types!(schema = "src/webhook/table.json", convert = { {type = "string", format = "var-str"} = VarStr });
+import_types!(
+    schema = "src/webhook/table.json",
+    convert = {
+        {type = "string", format = "var-str"} = VarStr,
+        {type = "object", format = "oauth"} = OAuthOptions
+    }
+);
 const ICON: &str = include_str!("./webhook.svg");
 
 const MAX_INFLIGHT: u32 = 50;
@@ -60,7 +68,16 @@ impl WebhookConnector {
             .transpose()?;
 
         let client = construct_http_client(&config.endpoint.sub_env_vars()?, headers)?;
-        let req = Self::construct_test_request(&client, config)?;
+        let mut req = Self::construct_test_request(&client, config)?;
+
+        if let Some(oauth) = oauth_config(config.oauth.as_ref())?.map(TokenProvider::new) {
+            let authorization = oauth
+                .authorization()
+                .await
+                .map_err(|e| anyhow!("failed to fetch OAuth token: {:?}", e))?;
+            req.headers_mut()
+                .insert(AUTHORIZATION, HeaderValue::from_str(&authorization)?);
+        }
 
         tx.send(TestSourceMessage {
             error: false,
@@ -101,7 +118,7 @@ impl Connector for WebhookConnector {
             hidden: false,
             custom_schemas: true,
             connection_config: None,
-            table_config: TABLE_SCHEMA.to_owned(),
+            table_config: with_oauth_schema(TABLE_SCHEMA),
         }
     }
 
@@ -145,6 +162,8 @@ impl Connector for WebhookConnector {
     ) -> anyhow::Result<arroyo_operator::connector::Connection> {
         let description = format!("WebhookSink<{}>", table.endpoint.sub_env_vars()?);
 
+        oauth_config(table.oauth.as_ref())?;
+
         let schema = schema
             .map(|s| s.to_owned())
             .ok_or_else(|| anyhow!("no schema defined for webhook connection"))?;
@@ -190,6 +209,7 @@ impl Connector for WebhookConnector {
         let table = WebhookTable {
             endpoint: VarStr::new(endpoint),
             headers,
+            oauth: OAuthOptions::from_options(options)?,
         };
 
         let client = construct_http_client(
@@ -229,6 +249,7 @@ impl Connector for WebhookConnector {
                     .expect("No format configured for webhook sink"),
             ),
             last_reported_error_at: Arc::new(Mutex::new(SystemTime::UNIX_EPOCH)),
+            oauth: oauth_config(table.oauth.as_ref())?.map(TokenProvider::new),
         })))
     }
 }
@@ -8,9 +8,11 @@ use std::{
 
 use arroyo_types::CheckpointBarrier;
 
+use reqwest::StatusCode;
 use tokio::sync::{Mutex, Semaphore};
 use tracing::warn;
 
+use crate::oauth::TokenProvider;
 use crate::webhook::MAX_INFLIGHT;
 use arroyo_formats::ser::ArrowSerializer;
 use arroyo_operator::context::ArrowContext;
@@ -25,6 +27,41 @@ pub struct WebhookSinkFunc {
     pub client: reqwest::Client,
     pub serializer: ArrowSerializer,
     pub last_reported_error_at: Arc<Mutex<SystemTime>>,
+    pub oauth: Option<TokenProvider>,
+}
+
+/// Sends a single webhook request, returning a description of the error on failure
+async fn send_request(
+    client: &reqwest::Client,
+    url: &str,
+    body: bytes::Bytes,
+    oauth: Option<&TokenProvider>,
+) -> Result<(), String> {
+    let mut req = client.post(url).body(body);
+
+    if let Some(oauth) = oauth {
+        let token = oauth
+            .token()
+            .await
+            .map_err(|e| format!("failed to fetch OAuth token: {}", e))?;
+        req = req.bearer_auth(token);
+    }
+
+    let req = req.build().expect("failed to build request");
+
+    match client.execute(req).await {
+        Ok(resp) if resp.status() == StatusCode::UNAUTHORIZED && oauth.is_some() => {
+            // the token may have been revoked before it expired; fetch a new one on retry
+            oauth.unwrap().invalidate().await;
+            Err("server rejected OAuth token".to_string())
+        }
+        Ok(_) => Ok(()),
+        Err(e) => Err(if let Some(status) = e.status() {
+            format!("server responded with error code: {}", status.as_u16())
+        } else {
+            e.to_string()
+        }),
+    }
 }
 
 #[async_trait]
@@ -37,7 +74,11 @@ impl ArrowOperator for WebhookSinkFunc {
         global_table_config("s", "webhook sink state")
     }
 
-    async fn process_batch(&mut self, record: RecordBatch, ctx: &mut ArrowContext) {
+    async fn process_batch(
+        &mut self,
+        record: RecordBatch,
+        ctx: &mut ArrowContext,
+    ) -> anyhow::Result<()> {
         for body in self.serializer.serialize(&record) {
             let permit = self
                 .semaphore
@@ -52,6 +93,7 @@ impl ArrowOperator for WebhookSinkFunc {
             let control_tx = ctx.control_tx.clone();
             let error_lock = self.last_reported_error_at.clone();
             let url = self.url.clone();
+            let oauth = self.oauth.clone();
 
             // these are just used for (potential) error reporting and we don't need to clone them
             let operator_id = ctx.task_info.operator_id.clone();
@@ -62,61 +104,49 @@ impl ArrowOperator for WebhookSinkFunc {
                 let _permit = permit;
                 let mut retries = 0;
                 loop {
-                    let req = client
-                        .post(&*url)
-                        .body(body.clone())
-                        .build()
-                        .expect("failed to build request");
-
-                    match client.execute(req).await {
-                        Ok(_) => break,
-                        Err(e) => {
-                            if let Ok(mut last_reported) = error_lock.try_lock() {
-                                if last_reported.elapsed().unwrap_or_default()
-                                    > Duration::from_secs(1)
-                                {
-                                    warn!("websink request failed: {:?}", e);
-
-                                    let details = if let Some(status) = e.status() {
-                                        format!(
-                                            "server responded with error code: {}",
-                                            status.as_u16()
-                                        )
-                                    } else {
-                                        e.to_string()
-                                    };
-
-                                    control_tx
-                                        .send(ControlResp::Error {
-                                            operator_id: operator_id.clone(),
-                                            task_index,
-                                            message: format!("webhook failed (retry {})", retries),
-                                            details,
-                                        })
-                                        .await
-                                        .unwrap();
-
-                                    *last_reported = SystemTime::now();
-                                }
-                            }
-
-                            retries += 1;
-
-                            tokio::time::sleep(Duration::from_millis(
-                                (50 * (1 << retries)).min(5_000),
-                            ))
-                            .await
+                    let details =
+                        match send_request(&client, &url, body.clone(), oauth.as_ref()).await {
+                            Ok(_) => break,
+                            Err(details) => details,
+                        };
+
+                    if let Ok(mut last_reported) = error_lock.try_lock() {
+                        if last_reported.elapsed().unwrap_or_default() > Duration::from_secs(1) {
+                            warn!("websink request failed: {}", details);
+
+                            control_tx
+                                .send(ControlResp::Error {
+                                    operator_id: operator_id.clone(),
+                                    task_index,
+                                    message: format!("webhook failed (retry {})", retries),
+                                    details,
+                                })
+                                .await
+                                .unwrap();
+
+                            *last_reported = SystemTime::now();
                         }
                     }
+
+                    retries += 1;
+
+                    tokio::time::sleep(Duration::from_millis((50 * (1 << retries)).min(5_000)))
+                        .await
                 }
             });
         }
+        Ok(())
     }
 
-    async fn handle_checkpoint(&mut self, _: CheckpointBarrier, _ctx: &mut ArrowContext) {
+    async fn handle_checkpoint(
+        &mut self,
+        _: CheckpointBarrier,
+        _ctx: &mut ArrowContext,
+    ) -> anyhow::Result<()> {
         // wait to acquire all of the permits (effectively blocking until all inflight requests are done)
         let _permits = self.semaphore.acquire_many(MAX_INFLIGHT).await.unwrap();
 
         // TODO: instead of blocking checkpoints on in-progress (or failing) requests, we should store them to state
+        Ok(())
     }
 }
@@ -19,6 +19,7 @@ use tokio_tungstenite::{connect_async, tungstenite};
 use tungstenite::http::Request;
 use typify::import_types;
 
+use crate::oauth::{oauth_config, with_oauth_schema, OAuthOptions, TokenProvider};
 use crate::{header_map, pull_opt, EmptyConfig};
 
 use crate::websocket::operator::{WebsocketSourceFunc, WebsocketSourceState};
@@ -29,7 +30,13 @@ mod operator;
 
 const TABLE_SCHEMA: &str = include_str!("./table.json");
 
-import_types!(schema = "src/websocket/table.json", convert = { {type = "string", format = "var-str"} = VarStr });
+import_types!(
+    schema = "src/websocket/table.json",
+    convert = {
+        {type = "string", format = "var-str"} = VarStr,
+        {type = "object", format = "oauth"} = OAuthOptions
+    }
+);
 const ICON: &str = include_str!("./websocket.svg");
 
 pub struct WebsocketConnector {}
@@ -56,7 +63,7 @@ impl Connector for WebsocketConnector {
             hidden: false,
             custom_schemas: true,
             connection_config: None,
-            table_config: TABLE_SCHEMA.to_owned(),
+            table_config: with_oauth_schema(TABLE_SCHEMA),
         }
     }
 
@@ -121,6 +128,26 @@ impl Connector for WebsocketConnector {
                 request_builder = request_builder.header(k, v);
             }
 
+            let oauth = match oauth_config(table.oauth.as_ref()) {
+                Ok(oauth) => oauth.map(TokenProvider::new),
+                Err(e) => {
+                    send(true, true, format!("Invalid OAuth configuration: {:?}", e)).await;
+                    return;
+                }
+            };
+
+            if let Some(oauth) = oauth {
+                match oauth.authorization().await {
+                    Ok(authorization) => {
+                        request_builder = request_builder.header("Authorization", authorization);
+                    }
+                    Err(e) => {
+                        send(true, true, format!("Failed to fetch OAuth token: {:?}", e)).await;
+                        return;
+                    }
+                }
+            }
+
             let request = match request_builder
                 .header("Host", host)
                 .header("Sec-WebSocket-Key", generate_key())
@@ -213,6 +240,11 @@ impl Connector for WebsocketConnector {
         ConnectionType::Source
     }
 
+    fn max_source_parallelism(&self, _: Self::ProfileT, _: Self::TableT) -> Option<usize> {
+        // only the first subtask reads from the websocket
+        Some(1)
+    }
+
     fn from_config(
         &self,
         id: Option<i64>,
@@ -232,6 +264,8 @@ impl Connector for WebsocketConnector {
             })?;
         }
 
+        oauth_config(table.oauth.as_ref())?;
+
         let schema = schema
             .map(|s| s.to_owned())
             .ok_or_else(|| anyhow!("no schema defined for WebSocket connection"))?;
@@ -303,6 +337,7 @@ impl Connector for WebsocketConnector {
                 headers: headers.map(VarStr::new),
                 subscription_message: None,
                 subscription_messages,
+                oauth: OAuthOptions::from_options(options)?,
             },
             schema,
         )
@@ -314,6 +349,8 @@ impl Connector for WebsocketConnector {
         table: Self::TableT,
         config: OperatorConfig,
     ) -> anyhow::Result<OperatorNode> {
+        let oauth = oauth_config(table.oauth.as_ref())?.map(TokenProvider::new);
+
         // Include subscription_message for backwards compatibility
         let mut subscription_messages = vec![];
         if let Some(message) = table.subscription_message {
@@ -335,6 +372,7 @@ impl Connector for WebsocketConnector {
             url: table.endpoint,
             headers,
             subscription_messages,
+            oauth,
             format: config
                 .format
                 .ok_or_else(|| anyhow!("format required for websocket source"))?,
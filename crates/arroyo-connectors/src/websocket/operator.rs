@@ -21,6 +21,8 @@ use tokio_tungstenite::{connect_async, tungstenite};
 use tracing::{debug, info};
 use tungstenite::http::Request;
 
+use crate::oauth::TokenProvider;
+
 #[derive(Clone, Debug, Encode, Decode, PartialEq, PartialOrd, Default)]
 pub struct WebsocketSourceState {}
 
@@ -28,6 +30,7 @@ pub struct WebsocketSourceFunc {
     pub url: String,
     pub headers: Vec<(String, String)>,
     pub subscription_messages: Vec<String>,
+    pub oauth: Option<TokenProvider>,
     pub format: Format,
     pub framing: Option<Framing>,
     pub bad_data: Option<BadData>,
@@ -112,6 +115,9 @@ impl WebsocketSourceFunc {
             ControlMessage::LoadCompacted { compacted } => {
                 ctx.load_compacted(compacted).await;
             }
+            ControlMessage::QueryState { query } => {
+                ctx.query_state(query).await;
+            }
             ControlMessage::NoOp => {}
         }
         None
@@ -156,6 +162,14 @@ impl WebsocketSourceFunc {
             request_builder = request_builder.header(k, v);
         }
 
+        if let Some(oauth) = &self.oauth {
+            let authorization = oauth
+                .authorization_with_retry()
+                .await
+                .map_err(|e| UserError::new("Failed to fetch OAuth token", format!("{:#}", e)))?;
+            request_builder = request_builder.header("Authorization", authorization);
+        }
+
         let request = match request_builder
             .header("Host", host)
             .header("Sec-WebSocket-Key", generate_key())
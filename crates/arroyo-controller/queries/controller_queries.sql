@@ -1,4 +1,4 @@
---! all_jobs : Job(ttl_micros?, state?, start_time?, finish_time?, tasks?, failure_message?, run_id?, pipeline_path?, wasm_path?)
+--! all_jobs : Job(ttl_micros?, source_offset_overrides?, restore_savepoint_id?, state?, start_time?, finish_time?, tasks?, failure_message?, run_id?, pipeline_path?, wasm_path?)
 SELECT
     c.id as id,
     c.organization_id as org_id,
@@ -7,6 +7,8 @@ SELECT
     checkpoint_interval_micros,
     ttl_micros,
     parallelism_overrides,
+    source_offset_overrides,
+    restore_savepoint_id,
     stop,
     state,
     start_time,
@@ -37,6 +39,12 @@ SET state = :state,
     restart_nonce = :restart_nonce
 WHERE id = :job_id;
 
+--! update_parallelism_overrides
+UPDATE job_configs
+SET parallelism_overrides = :parallelism_overrides,
+    updated_at = :updated_at
+WHERE id = :job_id;
+
 --! get_program
 SELECT program, proto_version FROM pipelines WHERE id = :id;
 
@@ -81,6 +89,12 @@ SET
     state = 'failed'
 WHERE job_id = :job_id AND epoch >= :epoch;
 
+--! checkpoints_in_range
+SELECT epoch, start_time
+FROM checkpoints
+WHERE job_id = :job_id AND epoch >= :min_epoch AND epoch < :max_epoch AND state <> 'failed'
+ORDER BY epoch ASC;
+
 --! last_successful_checkpoint
 SELECT pub_id, epoch, min_epoch, state = 'committing' as needs_commits
 FROM checkpoints
@@ -88,6 +102,27 @@ WHERE job_id = :job_id AND (state = 'ready' or state = 'committing')
 ORDER BY epoch DESC
 LIMIT 1;
 
+--! get_savepoint_epoch
+SELECT epoch, state
+FROM savepoints
+WHERE pub_id = :pub_id;
+
+--! finish_savepoint
+UPDATE savepoints
+SET
+    state = 'ready',
+    epoch = :epoch,
+    updated_at = CURRENT_TIMESTAMP
+WHERE pub_id = :pub_id;
+
+--! fail_savepoint
+UPDATE savepoints
+SET
+    state = 'failed',
+    failure_message = :failure_message,
+    updated_at = CURRENT_TIMESTAMP
+WHERE pub_id = :pub_id;
+
 --! create_job_log_message
 INSERT INTO job_log_messages (pub_id, job_id, operator_id, task_index, log_level, message, details)
 VALUES (:pub_id, :job_id, :operator_id, :task_index, :log_level, :message, :details);
@@ -99,4 +134,15 @@ DELETE FROM pipelines WHERE id in (
   INNER JOIN job_statuses js ON jc.id = js.id
   WHERE (js.state = 'Finished' OR js.state = 'Stopped' OR js.state = 'Failed')
     AND jc.ttl_micros > 0
-    AND jc.created_at < :created_at);
\ No newline at end of file
+    AND jc.created_at < :created_at);
+
+--! record_job_usage
+INSERT INTO job_usage (job_id, day, cpu_seconds, max_memory_bytes, state_bytes, network_bytes)
+VALUES (:job_id, :day, :cpu_seconds, :max_memory_bytes, :state_bytes, :network_bytes)
+ON CONFLICT (job_id, day) DO UPDATE SET
+    cpu_seconds = job_usage.cpu_seconds + excluded.cpu_seconds,
+    max_memory_bytes = CASE WHEN excluded.max_memory_bytes > job_usage.max_memory_bytes
+        THEN excluded.max_memory_bytes ELSE job_usage.max_memory_bytes END,
+    state_bytes = excluded.state_bytes,
+    network_bytes = job_usage.network_bytes + excluded.network_bytes,
+    updated_at = CURRENT_TIMESTAMP;
@@ -0,0 +1,226 @@
+use arroyo_rpc::config::AutoscalingConfig;
+use arroyo_rpc::grpc::api::Autoscaling;
+use std::collections::HashMap;
+use std::time::Instant;
+
+/// The load on a running job, as seen by the autoscaler
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Load {
+    /// the highest backpressure of any operator, averaged across its subtasks
+    pub backpressure: f64,
+    /// the highest lag of any source, summed across its subtasks
+    pub lag: u64,
+}
+
+/// Decides when a job with autoscaling bounds should be rescaled, according to how long it's
+/// been overloaded or underloaded
+#[derive(Debug)]
+pub struct Autoscaler {
+    min_parallelism: usize,
+    max_parallelism: usize,
+    overloaded_since: Option<Instant>,
+    underloaded_since: Option<Instant>,
+}
+
+impl Autoscaler {
+    pub fn new(bounds: &Autoscaling) -> Self {
+        Self {
+            min_parallelism: bounds.min_parallelism as usize,
+            max_parallelism: bounds.max_parallelism as usize,
+            overloaded_since: None,
+            underloaded_since: None,
+        }
+    }
+
+    /// Records the load of the job as of `now`, returning the parallelism it should be rescaled
+    /// to if it's time to rescale it
+    pub fn observe(
+        &mut self,
+        config: &AutoscalingConfig,
+        now: Instant,
+        parallelism: usize,
+        load: Load,
+    ) -> Option<usize> {
+        let overloaded =
+            load.backpressure >= config.scale_up_backpressure || load.lag >= config.scale_up_lag;
+        let underloaded = !overloaded
+            && load.backpressure <= config.scale_down_backpressure
+            && load.lag <= config.scale_down_lag;
+
+        self.overloaded_since = overloaded.then(|| self.overloaded_since.unwrap_or(now));
+        self.underloaded_since = underloaded.then(|| self.underloaded_since.unwrap_or(now));
+
+        let target = if self
+            .overloaded_since
+            .is_some_and(|t| now.duration_since(t) >= *config.scale_up_after)
+        {
+            (parallelism * 2).min(self.max_parallelism)
+        } else if self
+            .underloaded_since
+            .is_some_and(|t| now.duration_since(t) >= *config.scale_down_after)
+        {
+            (parallelism / 2).max(self.min_parallelism)
+        } else {
+            parallelism
+        };
+
+        (target != parallelism).then_some(target)
+    }
+}
+
+/// Computes the parallelism of each operator once the job is rescaled to `target`, which is kept
+/// within the job's autoscaling bounds. Operators that run at the job's parallelism are rescaled
+/// to the target, while those that run at less (like sources that can't be read in parallel)
+/// keep their parallelism, up to the target; none are scaled past their own maximum.
+pub fn rescaled_parallelism(
+    operator_parallelism: &HashMap<String, usize>,
+    target: usize,
+    bounds: &Autoscaling,
+) -> HashMap<String, usize> {
+    let parallelism = operator_parallelism.values().copied().max().unwrap_or(0);
+    let target = target
+        .min(bounds.max_parallelism as usize)
+        .max(bounds.min_parallelism as usize);
+
+    operator_parallelism
+        .iter()
+        .map(|(op, p)| {
+            let p = if *p == parallelism { target } else { *p };
+            let max = bounds
+                .operator_max_parallelism
+                .get(op)
+                .map_or(target, |max| *max as usize);
+            (op.clone(), p.min(target).min(max).max(1))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::{rescaled_parallelism, Autoscaler, Load};
+    use arroyo_rpc::config::AutoscalingConfig;
+    use arroyo_rpc::grpc::api::Autoscaling;
+    use std::collections::HashMap;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_autoscaler() {
+        let config = AutoscalingConfig {
+            scale_up_backpressure: 0.8,
+            scale_up_lag: 1000,
+            scale_down_backpressure: 0.2,
+            scale_down_lag: 10,
+            scale_up_after: "1m".parse().unwrap(),
+            scale_down_after: "5m".parse().unwrap(),
+            min_interval: "1m".parse().unwrap(),
+        };
+
+        let mut autoscaler = Autoscaler::new(&Autoscaling {
+            min_parallelism: 2,
+            max_parallelism: 6,
+            operator_max_parallelism: HashMap::new(),
+        });
+
+        let start = Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+        let overloaded = Load {
+            backpressure: 0.1,
+            lag: 5000,
+        };
+        let underloaded = Load {
+            backpressure: 0.0,
+            lag: 0,
+        };
+
+        assert_eq!(autoscaler.observe(&config, at(0), 4, overloaded), None);
+        assert_eq!(autoscaler.observe(&config, at(30), 4, overloaded), None);
+        // the load dropping resets the time the job's been overloaded
+        assert_eq!(
+            autoscaler.observe(&config, at(40), 4, Load::default()),
+            None
+        );
+        assert_eq!(autoscaler.observe(&config, at(50), 4, overloaded), None);
+        assert_eq!(autoscaler.observe(&config, at(110), 4, overloaded), Some(6));
+
+        assert_eq!(autoscaler.observe(&config, at(200), 6, underloaded), None);
+        assert_eq!(
+            autoscaler.observe(&config, at(500), 6, underloaded),
+            Some(3)
+        );
+        assert_eq!(
+            autoscaler.observe(&config, at(900), 3, underloaded),
+            Some(2)
+        );
+        assert_eq!(autoscaler.observe(&config, at(1300), 2, underloaded), None);
+
+        let moderate = Load {
+            backpressure: 0.5,
+            lag: 100,
+        };
+        assert_eq!(autoscaler.observe(&config, at(2000), 4, moderate), None);
+    }
+
+    #[test]
+    fn test_rescaled_parallelism() {
+        let parallelism = HashMap::from([
+            ("source".to_string(), 1),
+            ("filter".to_string(), 1),
+            ("window".to_string(), 4),
+            ("sink".to_string(), 4),
+        ]);
+
+        let bounds = Autoscaling {
+            min_parallelism: 1,
+            max_parallelism: 16,
+            operator_max_parallelism: HashMap::new(),
+        };
+
+        assert_eq!(
+            rescaled_parallelism(&parallelism, 8, &bounds),
+            HashMap::from([
+                ("source".to_string(), 1),
+                ("filter".to_string(), 1),
+                ("window".to_string(), 8),
+                ("sink".to_string(), 8),
+            ])
+        );
+
+        let parallelism = HashMap::from([("source".to_string(), 3), ("sink".to_string(), 8)]);
+        assert_eq!(
+            rescaled_parallelism(&parallelism, 2, &bounds),
+            HashMap::from([("source".to_string(), 2), ("sink".to_string(), 2)])
+        );
+
+        // a Kafka source with 4 partitions, and the filter chained to it, stay at 4, while the
+        // target is kept within the job's bounds
+        let parallelism = HashMap::from([
+            ("source".to_string(), 4),
+            ("filter".to_string(), 4),
+            ("sink".to_string(), 4),
+        ]);
+        let bounds = Autoscaling {
+            min_parallelism: 2,
+            max_parallelism: 12,
+            operator_max_parallelism: HashMap::from([
+                ("source".to_string(), 4),
+                ("filter".to_string(), 4),
+            ]),
+        };
+        assert_eq!(
+            rescaled_parallelism(&parallelism, 16, &bounds),
+            HashMap::from([
+                ("source".to_string(), 4),
+                ("filter".to_string(), 4),
+                ("sink".to_string(), 12),
+            ])
+        );
+        assert_eq!(
+            rescaled_parallelism(&parallelism, 1, &bounds),
+            HashMap::from([
+                ("source".to_string(), 2),
+                ("filter".to_string(), 2),
+                ("sink".to_string(), 2),
+            ])
+        );
+    }
+}
@@ -101,6 +101,24 @@ impl JobMetrics {
         task.update_backpressure(now, backpressure);
     }
 
+    /// Returns the highest backpressure of any operator, averaged across its subtasks' most
+    /// recent values, or None if no backpressure has been collected yet
+    pub async fn max_backpressure(&self) -> Option<f64> {
+        let mut operators: HashMap<u32, (f64, usize)> = HashMap::new();
+        for (k, v) in self.tasks.read().await.iter() {
+            if let Some((_, backpressure)) = v.backpressure.last() {
+                let (sum, count) = operators.entry(k.operator_id).or_default();
+                *sum += backpressure;
+                *count += 1;
+            }
+        }
+
+        operators
+            .into_values()
+            .map(|(sum, count)| sum / count as f64)
+            .max_by(|a, b| a.total_cmp(b))
+    }
+
     pub async fn get_groups(&self) -> Vec<OperatorMetricGroup> {
         let mut metric_groups: HashMap<u32, HashMap<MetricName, Vec<SubtaskMetrics>>> =
             HashMap::new();
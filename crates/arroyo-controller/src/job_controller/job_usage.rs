@@ -0,0 +1,189 @@
+use arroyo_rpc::grpc::rpc::MetricFamily;
+use arroyo_types::{WorkerId, BYTES_SENT};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use time::OffsetDateTime;
+
+pub const RECORD_RATE: Duration = Duration::from_secs(60);
+
+const CPU_SECONDS: &str = "process_cpu_seconds_total";
+const RESIDENT_MEMORY: &str = "process_resident_memory_bytes";
+
+/// Resources consumed by a job over a single UTC day, as written to the `job_usage` table.
+/// CPU and network are deltas since the last record; memory is the peak seen and state is
+/// the size of the most recent checkpoint.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct UsageRecord {
+    pub day: String,
+    pub cpu_seconds: f64,
+    pub max_memory_bytes: u64,
+    pub state_bytes: u64,
+    pub network_bytes: u64,
+}
+
+#[derive(Default)]
+struct UsageState {
+    last_cpu_seconds: HashMap<WorkerId, f64>,
+    last_network_bytes: HashMap<WorkerId, f64>,
+    memory_bytes: HashMap<WorkerId, u64>,
+    state_bytes: u64,
+    pending: HashMap<String, UsageRecord>,
+}
+
+impl UsageState {
+    fn current(&mut self) -> &mut UsageRecord {
+        let day = OffsetDateTime::now_utc().date().to_string();
+        let state_bytes = self.state_bytes;
+        self.pending
+            .entry(day.clone())
+            .or_insert_with(|| UsageRecord {
+                day,
+                state_bytes,
+                ..Default::default()
+            })
+    }
+}
+
+/// Turns the cumulative process and network counters reported by each worker into daily
+/// usage records for a job
+#[derive(Clone, Default)]
+pub struct JobUsage {
+    state: Arc<Mutex<UsageState>>,
+}
+
+impl JobUsage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records a scrape of a worker's metrics
+    pub fn update(&self, worker_id: WorkerId, families: &[MetricFamily]) {
+        let mut cpu_seconds = None;
+        let mut memory_bytes = None;
+        let mut network_bytes = None;
+
+        for family in families {
+            let values = family.metric.iter().filter_map(|m| {
+                m.counter
+                    .as_ref()
+                    .and_then(|c| c.value)
+                    .or_else(|| m.gauge.as_ref().and_then(|g| g.value))
+            });
+
+            let Some(name) = family.name.as_deref() else {
+                continue;
+            };
+
+            if name == CPU_SECONDS {
+                cpu_seconds = Some(values.sum::<f64>());
+            } else if name == RESIDENT_MEMORY {
+                memory_bytes = Some(values.sum::<f64>() as u64);
+            } else if name == BYTES_SENT {
+                network_bytes = Some(values.sum::<f64>());
+            }
+        }
+
+        let mut state = self.state.lock().unwrap();
+
+        let cpu_delta = cpu_seconds
+            .map(|v| delta(&mut state.last_cpu_seconds, worker_id, v))
+            .unwrap_or_default();
+        let network_delta = network_bytes
+            .map(|v| delta(&mut state.last_network_bytes, worker_id, v))
+            .unwrap_or_default();
+        if let Some(memory_bytes) = memory_bytes {
+            state.memory_bytes.insert(worker_id, memory_bytes);
+        }
+        let total_memory: u64 = state.memory_bytes.values().sum();
+
+        let current = state.current();
+        current.cpu_seconds += cpu_delta;
+        current.network_bytes += network_delta as u64;
+        current.max_memory_bytes = current.max_memory_bytes.max(total_memory);
+    }
+
+    /// Records the size of a completed checkpoint
+    pub fn checkpoint_completed(&self, bytes: u64) {
+        let mut state = self.state.lock().unwrap();
+        state.state_bytes = bytes;
+        state.current().state_bytes = bytes;
+    }
+
+    /// Takes all usage accumulated since the last call
+    pub fn take(&self) -> Vec<UsageRecord> {
+        let mut state = self.state.lock().unwrap();
+        state.pending.drain().map(|(_, r)| r).collect()
+    }
+}
+
+// counters are cumulative per process, so we report the increase since the last scrape; if
+// the counter went backwards the worker restarted and the whole value is new usage
+fn delta(last: &mut HashMap<WorkerId, f64>, worker_id: WorkerId, value: f64) -> f64 {
+    match last.insert(worker_id, value) {
+        Some(prev) if prev <= value => value - prev,
+        Some(_) => value,
+        None => 0.0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arroyo_rpc::grpc::rpc::{Counter, Gauge, Metric};
+
+    fn family(name: &str, counter: Option<f64>, gauge: Option<f64>) -> MetricFamily {
+        MetricFamily {
+            name: Some(name.to_string()),
+            help: None,
+            r#type: None,
+            metric: vec![Metric {
+                label: vec![],
+                gauge: gauge.map(|v| Gauge { value: Some(v) }),
+                counter: counter.map(|v| Counter { value: Some(v) }),
+                summary: None,
+                untyped: None,
+                histogram: None,
+                timestamp_ms: None,
+            }],
+        }
+    }
+
+    fn scrape(cpu: f64, memory: f64, network: f64) -> Vec<MetricFamily> {
+        vec![
+            family(CPU_SECONDS, Some(cpu), None),
+            family(RESIDENT_MEMORY, None, Some(memory)),
+            family(BYTES_SENT, Some(network), None),
+        ]
+    }
+
+    #[test]
+    fn test_usage_deltas() {
+        let usage = JobUsage::new();
+        let w1 = WorkerId(1);
+        let w2 = WorkerId(2);
+
+        usage.update(w1, &scrape(10.0, 100.0, 1000.0));
+        usage.update(w2, &scrape(5.0, 200.0, 500.0));
+        usage.update(w1, &scrape(12.5, 150.0, 1500.0));
+        // worker 2 restarted, so its counters reset
+        usage.update(w2, &scrape(1.0, 50.0, 100.0));
+        usage.checkpoint_completed(4096);
+
+        let records = usage.take();
+        assert_eq!(records.len(), 1);
+        let record = &records[0];
+        assert_eq!(record.cpu_seconds, 3.5);
+        assert_eq!(record.network_bytes, 600);
+        assert_eq!(record.max_memory_bytes, 350);
+        assert_eq!(record.state_bytes, 4096);
+
+        assert!(usage.take().is_empty());
+
+        usage.update(w1, &scrape(13.0, 150.0, 1600.0));
+        let records = usage.take();
+        assert_eq!(records[0].cpu_seconds, 0.5);
+        assert_eq!(records[0].network_bytes, 100);
+        assert_eq!(records[0].state_bytes, 4096);
+    }
+}
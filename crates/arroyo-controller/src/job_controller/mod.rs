@@ -1,7 +1,7 @@
 use std::str::FromStr;
 use std::sync::Arc;
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     time::{Duration, Instant, SystemTime},
 };
 
@@ -9,38 +9,46 @@ use crate::types::public::StopMode as SqlStopMode;
 use anyhow::bail;
 use arroyo_rpc::grpc::rpc::{
     worker_grpc_client::WorkerGrpcClient, CheckpointReq, CommitReq, JobFinishedReq, LabelPair,
-    LoadCompactedDataReq, MetricsReq, StopExecutionReq, StopMode, TaskCheckpointEventType,
+    LoadCompactedDataReq, MetricsReq, QueryStateReq, RestartRegionReq, StateEntry,
+    StopExecutionReq, StopMode, TaskCheckpointEventType,
 };
 use arroyo_state::{BackingStore, StateBackend};
-use arroyo_types::{to_micros, WorkerId};
+use arroyo_types::{to_micros, NonRetryableError, WorkerId};
 use cornucopia_async::DatabaseSource;
 use rand::{thread_rng, Rng};
 
 use time::OffsetDateTime;
 
+use crate::job_controller::autoscaler::{rescaled_parallelism, Autoscaler, Load};
 use crate::job_controller::job_metrics::{get_metric_name, JobMetrics};
+use crate::job_controller::job_usage::JobUsage;
+use crate::job_controller::rebalancer::{Skew, SourceSplitLag};
 use crate::types::public::CheckpointState as DbCheckpointState;
 use crate::{queries::controller_queries, JobConfig, JobMessage, RunningMessage};
 use arroyo_datastream::logical::LogicalProgram;
 use arroyo_rpc::api_types::metrics::MetricName;
-use arroyo_rpc::config::config;
+use arroyo_rpc::config::{config, SourceRebalancingConfig};
 use arroyo_rpc::notify_db;
 use arroyo_rpc::public_ids::{generate_id, IdTypes};
 use arroyo_state::checkpoint_state::CheckpointState;
 use arroyo_state::committing_state::CommittingState;
 use arroyo_state::parquet::ParquetBackend;
 use tokio::{sync::mpsc::Receiver, task::JoinHandle};
-use tonic::{transport::Channel, Request};
+use tonic::{transport::Channel, Request, Status};
 use tracing::{debug, error, info, warn};
 
 use self::checkpointer::CheckpointingOrCommittingState;
 
+mod autoscaler;
 mod checkpointer;
 pub mod job_metrics;
+pub mod job_usage;
+mod rebalancer;
 
-const CHECKPOINTS_TO_KEEP: u32 = 4;
 const CHECKPOINT_ROWS_TO_KEEP: u32 = 100;
 const COMPACT_EVERY: u32 = 2;
+// the first protocol version in which workers can restart a region of a running job
+const REGION_RESTART_VERSION: u32 = 5;
 
 #[derive(Debug, PartialEq, Eq)]
 pub enum WorkerState {
@@ -89,12 +97,37 @@ pub struct RunningJobModel {
     epoch: u32,
     min_epoch: u32,
     last_checkpoint: Instant,
+    checkpoint_requested: bool,
     workers: HashMap<WorkerId, WorkerStatus>,
     tasks: HashMap<(String, u32), TaskStatus>,
     operator_parallelism: HashMap<String, usize>,
     metrics: JobMetrics,
     metric_update_task: Option<JoinHandle<()>>,
     last_updated_metrics: Instant,
+    usage: JobUsage,
+    last_recorded_usage: Instant,
+    // the most recently reported lag of each source subtask, by operator and subtask index
+    source_lag: HashMap<String, HashMap<u32, u64>>,
+    // the most recently reported lag of each split of the sources that assign splits by lag
+    split_lag: HashMap<String, SourceSplitLag>,
+    started_at: Instant,
+    // the epoch of the latest completed checkpoint, if its state hasn't been compacted yet
+    compaction_epoch: Option<u32>,
+    // the epoch at which old checkpoints were last cleaned up
+    last_cleanup_epoch: u32,
+    // savepoints that will be written from the next checkpoint
+    requested_savepoints: Vec<String>,
+    // savepoints that will be written from the checkpoint in progress
+    checkpoint_savepoints: Vec<String>,
+    // savepoints whose checkpoints have completed, with their epochs, waiting to be written
+    completed_savepoints: Vec<(String, u32)>,
+    // whether to leave the workers running once all tasks have finished, so that the job can be
+    // started on them again
+    keep_workers: bool,
+    // whether one of the workers is draining, so the job needs to be moved off of it
+    draining: bool,
+    // the reason a task failed with an error that restarting the job won't fix
+    fatal_failure: Option<String>,
 }
 
 impl std::fmt::Debug for RunningJobModel {
@@ -190,7 +223,7 @@ impl RunningJobModel {
                                 {
                                     committing_state
                                         .subtask_committed(c.operator_id.clone(), c.subtask_index);
-                                    self.compact_state().await?;
+                                    self.request_compaction();
                                 } else {
                                     warn!("unexpected checkpoint event type {:?}", c.event_type())
                                 }
@@ -252,10 +285,17 @@ impl RunningJobModel {
                 operator_id,
                 subtask_index,
                 reason,
+                fatal,
                 ..
             } => {
                 let key = (operator_id, subtask_index);
                 if let Some(status) = self.tasks.get_mut(&key) {
+                    if fatal {
+                        self.fatal_failure = Some(format!(
+                            "task {}-{} failed: {}",
+                            key.0, subtask_index, reason
+                        ));
+                    }
                     status.state = TaskState::Failed(reason);
                 } else {
                     warn!(
@@ -289,20 +329,78 @@ impl RunningJobModel {
                     );
                 }
             }
+            RunningMessage::SourceLag {
+                operator_id,
+                subtask_index,
+                lag,
+                split_lag,
+                unit,
+            } => {
+                self.split_lag
+                    .entry(operator_id.clone())
+                    .or_insert_with(|| SourceSplitLag {
+                        unit,
+                        subtasks: HashMap::new(),
+                    })
+                    .subtasks
+                    .insert(subtask_index, split_lag);
+                self.source_lag
+                    .entry(operator_id)
+                    .or_default()
+                    .insert(subtask_index, lag);
+            }
+            RunningMessage::Savepoint { savepoint_id } => {
+                self.requested_savepoints.push(savepoint_id);
+                self.checkpoint_requested = true;
+            }
+            RunningMessage::QueryState { req, tx } => {
+                if self.operator_parallelism.contains_key(&req.operator_id) {
+                    let workers = self.workers.values().map(|w| w.connect.clone()).collect();
+                    tokio::spawn(async move {
+                        let _ = tx.send(query_state(workers, req).await);
+                    });
+                } else {
+                    let _ = tx.send(Err(Status::not_found(format!(
+                        "Job has no operator {}",
+                        req.operator_id
+                    ))));
+                }
+            }
+            RunningMessage::WorkerShuttingDown { worker_id, drain } => {
+                if self.workers.contains_key(&worker_id) {
+                    // the worker is about to go away (e.g., its pod is being evicted). If it's
+                    // draining, the job is stopped with a final checkpoint and moved to new
+                    // workers; otherwise we take a checkpoint as soon as possible to minimize
+                    // the data we'll need to reprocess
+                    if drain {
+                        self.draining = true;
+                    } else {
+                        self.checkpoint_requested = true;
+                    }
+                } else {
+                    warn!(
+                        message = "Received shutdown message for unknown worker",
+                        job_id = *self.job_id,
+                        worker_id = worker_id.0
+                    );
+                }
+            }
         }
 
         if self.state == JobState::Running
             && self.all_tasks_finished()
             && self.checkpoint_state.is_none()
         {
-            for w in &mut self.workers.values_mut() {
-                if let Err(e) = w.connect.job_finished(JobFinishedReq {}).await {
-                    warn!(
-                        message = "Failed to connect to work to send job finish",
-                        job_id = *self.job_id,
-                        worker_id = w.id.0,
-                        error = format!("{:?}", e),
-                    )
+            if !self.keep_workers {
+                for w in &mut self.workers.values_mut() {
+                    if let Err(e) = w.connect.job_finished(JobFinishedReq {}).await {
+                        warn!(
+                            message = "Failed to connect to work to send job finish",
+                            job_id = *self.job_id,
+                            worker_id = w.id.0,
+                            error = format!("{:?}", e),
+                        )
+                    }
                 }
             }
             self.state = JobState::Stopped;
@@ -318,6 +416,9 @@ impl RunningJobModel {
         then_stop: bool,
     ) -> anyhow::Result<()> {
         self.epoch += 1;
+        self.checkpoint_requested = false;
+        self.checkpoint_savepoints
+            .append(&mut self.requested_savepoints);
 
         info!(
             message = "Starting checkpointing",
@@ -368,50 +469,23 @@ impl RunningJobModel {
         Ok(())
     }
 
-    async fn compact_state(&mut self) -> anyhow::Result<()> {
+    /// Marks the state of the current epoch to be compacted by the job controller, which does
+    /// so in the background
+    fn request_compaction(&mut self) {
         if !config().pipeline.compaction.enabled {
             debug!("Compaction is disabled, skipping compaction");
-            return Ok(());
+            return;
         }
 
-        info!(
-            message = "Compacting state",
-            job_id = *self.job_id,
-            epoch = self.epoch,
-        );
-
-        let mut worker_clients: Vec<WorkerGrpcClient<Channel>> =
-            self.workers.values().map(|w| w.connect.clone()).collect();
-        for operator_id in self.operator_parallelism.keys() {
-            let compacted_tables = ParquetBackend::compact_operator(
-                // compact the operator's state and notify the workers to load the new files
-                self.job_id.clone(),
-                operator_id.clone(),
-                self.epoch,
-            )
-            .await?;
-
-            if compacted_tables.is_empty() {
-                continue;
-            }
-
-            // TODO: these should be put on separate tokio tasks.
-            for worker_client in &mut worker_clients {
-                worker_client
-                    .load_compacted_data(LoadCompactedDataReq {
-                        operator_id: operator_id.clone(),
-                        compacted_metadata: compacted_tables.clone(),
-                    })
-                    .await?;
-            }
-        }
+        self.compaction_epoch = Some(self.epoch);
+    }
 
-        info!(
-            message = "Finished compaction",
-            job_id = *self.job_id,
-            epoch = self.epoch,
-        );
-        Ok(())
+    /// Queues the savepoints requested for the current checkpoint to be written, now that it's
+    /// complete
+    fn complete_savepoints(&mut self) {
+        let epoch = self.epoch;
+        self.completed_savepoints
+            .extend(self.checkpoint_savepoints.drain(..).map(|id| (id, epoch)));
     }
 
     pub async fn finish_checkpoint_if_done(&mut self, db: &DatabaseSource) -> anyhow::Result<()> {
@@ -420,6 +494,7 @@ impl RunningJobModel {
             match state {
                 CheckpointingOrCommittingState::Checkpointing(checkpointing) => {
                     checkpointing.save_state().await?;
+                    self.usage.checkpoint_completed(checkpointing.bytes());
 
                     let committing_state = checkpointing.committing_state();
                     let duration = checkpointing
@@ -433,7 +508,8 @@ impl RunningJobModel {
                             .await?;
                         self.last_checkpoint = Instant::now();
                         self.checkpoint_state = None;
-                        self.compact_state().await?;
+                        self.request_compaction();
+                        self.complete_savepoints();
 
                         info!(
                             message = "Finished checkpointing",
@@ -473,6 +549,7 @@ impl RunningJobModel {
                     Self::finish_committing(committing.checkpoint_id(), db).await?;
                     self.last_checkpoint = Instant::now();
                     self.checkpoint_state = None;
+                    self.complete_savepoints();
                     info!(
                         message = "Finished committing checkpointing",
                         job_id = *self.job_id,
@@ -486,9 +563,15 @@ impl RunningJobModel {
         Ok(())
     }
 
+    /// Returns the new min epoch if there are more checkpoints than the retention policy keeps;
+    /// checkpoints kept for their age are accounted for when cleaning up
     pub fn cleanup_needed(&self) -> Option<u32> {
-        if self.epoch - self.min_epoch > CHECKPOINTS_TO_KEEP && self.epoch % COMPACT_EVERY == 0 {
-            Some(self.epoch - CHECKPOINTS_TO_KEEP)
+        let keep_last = config().pipeline.checkpoint_retention.keep_last.max(1);
+        if self.epoch - self.min_epoch > keep_last
+            && self.epoch % COMPACT_EVERY == 0
+            && self.epoch > self.last_cleanup_epoch
+        {
+            Some(self.epoch - keep_last)
         } else {
             None
         }
@@ -522,6 +605,27 @@ impl RunningJobModel {
         false
     }
 
+    /// If every failure in the job is of tasks in a single region that doesn't span the whole
+    /// job, returns the operators of that region
+    fn failed_region(&self) -> Option<HashSet<String>> {
+        if self.workers.values().any(|w| w.heartbeat_timeout()) {
+            return None;
+        }
+
+        let mut region: Option<HashSet<String>> = None;
+        for ((operator_id, _), status) in &self.tasks {
+            if let TaskState::Failed(_) = status.state {
+                match &region {
+                    Some(region) if region.contains(operator_id) => {}
+                    Some(_) => return None,
+                    None => region = Some(self.program.region(operator_id)),
+                }
+            }
+        }
+
+        region.filter(|region| region.len() < self.program.graph.node_count())
+    }
+
     pub fn any_finished_sources(&self) -> bool {
         let source_tasks = self.program.sources();
 
@@ -530,6 +634,26 @@ impl RunningJobModel {
         })
     }
 
+    /// Returns a source whose most lagged subtask has fallen far behind its least lagged one,
+    /// and whose splits could be reassigned to narrow that gap, along with the gap before and
+    /// after reassigning them
+    fn skewed_source(&self, config: &SourceRebalancingConfig) -> Option<(&str, Skew, Skew)> {
+        self.split_lag.iter().find_map(|(operator_id, lag)| {
+            let parallelism = *self.operator_parallelism.get(operator_id)?;
+            let (current, expected) = lag.should_rebalance(config, parallelism)?;
+            Some((operator_id.as_str(), current, expected))
+        })
+    }
+
+    /// Returns the highest lag of any source, summed across its subtasks
+    fn max_source_lag(&self) -> u64 {
+        self.source_lag
+            .values()
+            .map(|lags| lags.values().sum())
+            .max()
+            .unwrap_or(0)
+    }
+
     pub fn all_tasks_finished(&self) -> bool {
         self.tasks
             .iter()
@@ -537,11 +661,44 @@ impl RunningJobModel {
     }
 }
 
+/// Looks up the operator's state in each of the workers, which answer for the subtasks they run
+async fn query_state(
+    workers: Vec<WorkerGrpcClient<Channel>>,
+    req: QueryStateReq,
+) -> Result<Vec<StateEntry>, Status> {
+    let responses = futures::future::try_join_all(workers.into_iter().map(|mut worker| {
+        let req = req.clone();
+        async move { worker.query_state(req).await }
+    }))
+    .await?;
+
+    let mut entries: Vec<_> = responses
+        .into_iter()
+        .flat_map(|resp| resp.into_inner().entries)
+        .collect();
+    entries.sort_by_key(|e| e.subtask_index);
+    entries.truncate(req.limit as usize);
+    Ok(entries)
+}
+
 pub struct JobController {
     db: DatabaseSource,
     config: JobConfig,
     model: RunningJobModel,
     cleanup_task: Option<JoinHandle<anyhow::Result<u32>>>,
+    compaction_task: Option<JoinHandle<anyhow::Result<()>>>,
+    savepoint_task: Option<JoinHandle<anyhow::Result<()>>>,
+    autoscaler: Option<Autoscaler>,
+    // the data-plane protocol version of the job's workers
+    protocol_version: u32,
+    // incremented each time a region is restarted, so that workers can tell the restarted
+    // region's tasks apart from the ones they replace
+    region_generation: u32,
+    region_restarts: i32,
+    last_region_restart: Option<Instant>,
+    // when the job was last stopped to rebalance its sources, if it has been since the
+    // controller took it over
+    last_rebalanced_at: Option<Instant>,
 }
 
 impl std::fmt::Debug for JobController {
@@ -550,6 +707,10 @@ impl std::fmt::Debug for JobController {
             .field("config", &self.config)
             .field("model", &self.model)
             .field("cleaning", &self.cleanup_task.is_some())
+            .field("compacting", &self.compaction_task.is_some())
+            .field("writing_savepoints", &self.savepoint_task.is_some())
+            .field("autoscaler", &self.autoscaler)
+            .field("region_restarts", &self.region_restarts)
             .finish()
     }
 }
@@ -557,6 +718,8 @@ impl std::fmt::Debug for JobController {
 pub enum ControllerProgress {
     Continue,
     Finishing,
+    Rebalancing,
+    Draining,
 }
 
 impl JobController {
@@ -570,7 +733,14 @@ impl JobController {
         worker_connects: HashMap<WorkerId, WorkerGrpcClient<Channel>>,
         commit_state: Option<CommittingState>,
         metrics: JobMetrics,
+        protocol_version: u32,
     ) -> Self {
+        let autoscaler = program
+            .program_config
+            .autoscaling
+            .as_ref()
+            .map(Autoscaler::new);
+
         Self {
             db,
             model: RunningJobModel {
@@ -585,6 +755,7 @@ impl JobController {
                     + Duration::from_millis(
                         thread_rng().gen_range(0..config.checkpoint_interval.as_millis() as u64),
                     ),
+                checkpoint_requested: false,
                 workers: worker_connects
                     .into_iter()
                     .map(|(id, connect)| {
@@ -617,10 +788,31 @@ impl JobController {
                 metrics,
                 metric_update_task: None,
                 last_updated_metrics: Instant::now(),
+                usage: JobUsage::new(),
+                last_recorded_usage: Instant::now(),
+                source_lag: HashMap::new(),
+                split_lag: HashMap::new(),
+                started_at: Instant::now(),
+                compaction_epoch: None,
+                last_cleanup_epoch: 0,
+                requested_savepoints: vec![],
+                checkpoint_savepoints: vec![],
+                completed_savepoints: vec![],
+                keep_workers: false,
+                draining: false,
+                fatal_failure: None,
                 program,
             },
             config,
             cleanup_task: None,
+            compaction_task: None,
+            savepoint_task: None,
+            autoscaler,
+            protocol_version,
+            region_generation: 0,
+            region_restarts: 0,
+            last_region_restart: None,
+            last_rebalanced_at: None,
         }
     }
 
@@ -628,6 +820,12 @@ impl JobController {
         self.config = config;
     }
 
+    /// Sets when the job was last stopped to rebalance its sources, which carries over from the
+    /// controller it was running under before
+    pub fn set_last_rebalanced_at(&mut self, at: Option<Instant>) {
+        self.last_rebalanced_at = at;
+    }
+
     pub async fn handle_message(&mut self, msg: RunningMessage) -> anyhow::Result<()> {
         self.model.handle_message(msg, &self.db).await
     }
@@ -645,6 +843,7 @@ impl JobController {
         }
 
         let job_metrics = self.model.metrics.clone();
+        let usage = self.model.usage.clone();
         let workers: Vec<_> = self
             .model
             .workers
@@ -674,8 +873,10 @@ impl JobController {
                     )
                 }
 
-                e.into_inner()
-                    .metrics
+                let families = e.into_inner().metrics;
+                usage.update(id, &families);
+
+                families
                     .into_iter()
                     .filter_map(|f| Some((get_metric_name(&f.name?)?, f.metric)))
                     .flat_map(|(metric, values)| {
@@ -708,6 +909,16 @@ impl JobController {
     }
 
     pub async fn progress(&mut self) -> anyhow::Result<ControllerProgress> {
+        // restarting won't recover a task that failed with a non-retryable error
+        if let Some(reason) = &self.model.fatal_failure {
+            return Err(NonRetryableError(reason.clone()).into());
+        }
+
+        // can failed tasks be recovered by restarting just their region?
+        if let Some(region) = self.region_to_restart() {
+            self.restart_region(region).await?;
+        }
+
         // have any of our workers failed?
         if self.model.failed() {
             bail!("worker failed");
@@ -718,7 +929,40 @@ impl JobController {
             return Ok(ControllerProgress::Finishing);
         }
 
-        // check on compaction
+        // are any of our workers going away?
+        if self.model.draining {
+            info!(
+                message = "moving job off of draining worker",
+                job_id = *self.config.id
+            );
+            return Ok(ControllerProgress::Draining);
+        }
+
+        // have any of our sources fallen far behind on some of their splits?
+        let rebalancing = &config().pipeline.source_rebalancing;
+        if rebalancing.enabled
+            && self.config.ttl.is_none()
+            && self.model.checkpoint_state.is_none()
+            && self.model.started_at.elapsed() > *rebalancing.min_interval
+            && self
+                .last_rebalanced_at
+                .map_or(true, |t| t.elapsed() > *rebalancing.cooldown)
+        {
+            if let Some((operator_id, current, expected)) = self.model.skewed_source(rebalancing) {
+                info!(
+                    message = "rebalancing skewed source",
+                    job_id = *self.config.id,
+                    operator_id,
+                    min_lag = current.min,
+                    max_lag = current.max,
+                    expected_min_lag = expected.min,
+                    expected_max_lag = expected.max
+                );
+                return Ok(ControllerProgress::Rebalancing);
+            }
+        }
+
+        // check on cleanup
         if self.cleanup_task.is_some() && self.cleanup_task.as_ref().unwrap().is_finished() {
             let task = self.cleanup_task.take().unwrap();
 
@@ -730,6 +974,7 @@ impl JobController {
                         job_id = *self.config.id
                     );
                     self.model.min_epoch = min_epoch;
+                    self.model.last_cleanup_epoch = self.model.epoch;
                 }
                 Ok(Err(e)) => {
                     error!(
@@ -754,8 +999,72 @@ impl JobController {
             }
         }
 
+        // check on compaction
+        if self.compaction_task.is_some() && self.compaction_task.as_ref().unwrap().is_finished() {
+            match self.compaction_task.take().unwrap().await {
+                Ok(Ok(())) => {}
+                Ok(Err(e)) => {
+                    error!(
+                        message = "compaction failed",
+                        job_id = *self.config.id,
+                        error = format!("{:?}", e)
+                    );
+                }
+                Err(e) => {
+                    error!(
+                        message = "compaction panicked",
+                        job_id = *self.config.id,
+                        error = format!("{:?}", e)
+                    );
+                }
+            }
+        }
+
+        // compaction and cleanup don't run at the same time, so that cleanup can't delete files
+        // that are being compacted
+        if self.compaction_task.is_none() && self.cleanup_task.is_none() {
+            if let Some(epoch) = self.model.compaction_epoch.take() {
+                self.compaction_task = Some(self.start_compaction(epoch));
+            }
+        }
+
+        // check on savepoints
+        if self.savepoint_task.is_some() && self.savepoint_task.as_ref().unwrap().is_finished() {
+            match self.savepoint_task.take().unwrap().await {
+                Ok(Ok(())) => {}
+                Ok(Err(e)) => {
+                    error!(
+                        message = "writing savepoints failed",
+                        job_id = *self.config.id,
+                        error = format!("{:?}", e)
+                    );
+                }
+                Err(e) => {
+                    error!(
+                        message = "writing savepoints panicked",
+                        job_id = *self.config.id,
+                        error = format!("{:?}", e)
+                    );
+                }
+            }
+        }
+
+        // savepoints are copied from their checkpoints while cleanup isn't running, so that the
+        // files being copied can't be deleted
+        if self.savepoint_task.is_none()
+            && self.cleanup_task.is_none()
+            && !self.model.completed_savepoints.is_empty()
+        {
+            let savepoints = std::mem::take(&mut self.model.completed_savepoints);
+            self.savepoint_task = Some(self.start_savepoints(savepoints));
+        }
+
         if let Some(new_epoch) = self.model.cleanup_needed() {
-            if self.cleanup_task.is_none() && self.model.checkpoint_state.is_none() {
+            if self.cleanup_task.is_none()
+                && self.compaction_task.is_none()
+                && self.savepoint_task.is_none()
+                && self.model.checkpoint_state.is_none()
+            {
                 self.cleanup_task = Some(self.start_cleanup(new_epoch));
             }
         }
@@ -763,7 +1072,8 @@ impl JobController {
         // check on checkpointing
         if self.model.checkpoint_state.is_some() {
             self.model.finish_checkpoint_if_done(&self.db).await?;
-        } else if self.model.last_checkpoint.elapsed() > self.config.checkpoint_interval
+        } else if (self.model.last_checkpoint.elapsed() > self.config.checkpoint_interval
+            || self.model.checkpoint_requested)
             && self.cleanup_task.is_none()
         {
             // or do we need to start checkpointing?
@@ -774,11 +1084,242 @@ impl JobController {
         if self.model.last_updated_metrics.elapsed() > job_metrics::COLLECTION_RATE {
             self.update_metrics().await;
             self.model.last_updated_metrics = Instant::now();
+            self.autoscale().await;
+        }
+
+        if self.model.last_recorded_usage.elapsed() > job_usage::RECORD_RATE {
+            self.record_usage().await;
+            self.model.last_recorded_usage = Instant::now();
         }
 
         Ok(ControllerProgress::Continue)
     }
 
+    /// Returns the region of the job to restart, if its failed tasks can be recovered without
+    /// restarting the whole job
+    fn region_to_restart(&mut self) -> Option<HashSet<String>> {
+        let pipeline_config = &config().pipeline;
+
+        if !pipeline_config.region_failover {
+            return None;
+        }
+
+        let region = self.model.failed_region()?;
+
+        if let Some(reason) = region_restart_refusal(
+            pipeline_config.region_failover,
+            self.protocol_version,
+            self.model.checkpoint_state.as_ref(),
+            self.compaction_task.is_some(),
+        ) {
+            info!(
+                message = "not restarting failed region on its own",
+                reason,
+                job_id = *self.config.id,
+                epoch = self.model.epoch,
+            );
+            return None;
+        }
+
+        if self
+            .last_region_restart
+            .is_some_and(|t| t.elapsed() > *pipeline_config.healthy_duration)
+        {
+            self.region_restarts = 0;
+        }
+
+        if pipeline_config.allowed_restarts != -1
+            && self.region_restarts >= pipeline_config.allowed_restarts
+        {
+            warn!(
+                message = "too many region restarts, restarting job",
+                job_id = *self.config.id,
+                region_restarts = self.region_restarts
+            );
+            return None;
+        }
+
+        Some(region)
+    }
+
+    /// Restarts the tasks of a region of the job on each worker, restoring them from the last
+    /// checkpoint while the rest of the job keeps running
+    async fn restart_region(&mut self, region: HashSet<String>) -> anyhow::Result<()> {
+        for ((operator_id, subtask), status) in &self.model.tasks {
+            if let TaskState::Failed(reason) = &status.state {
+                error!(
+                    message = "task failed",
+                    job_id = *self.config.id,
+                    operator_id,
+                    subtask,
+                    reason,
+                );
+            }
+        }
+
+        self.region_generation += 1;
+        let restore_epoch = (self.model.epoch > 0).then_some(self.model.epoch);
+
+        info!(
+            message = "restarting failed region",
+            job_id = *self.config.id,
+            operators = ?region,
+            restore_epoch,
+            generation = self.region_generation
+        );
+
+        let req = RestartRegionReq {
+            operator_ids: region.iter().cloned().collect(),
+            restore_epoch,
+            generation: self.region_generation,
+        };
+
+        futures::future::try_join_all(self.model.workers.values().map(|w| {
+            let mut connect = w.connect.clone();
+            let req = req.clone();
+            async move { connect.restart_region(Request::new(req)).await }
+        }))
+        .await?;
+
+        for ((operator_id, _), status) in &mut self.model.tasks {
+            if region.contains(operator_id) {
+                status.state = TaskState::Running;
+            }
+        }
+        self.model
+            .source_lag
+            .retain(|operator_id, _| !region.contains(operator_id));
+        self.model
+            .split_lag
+            .retain(|operator_id, _| !region.contains(operator_id));
+
+        self.region_restarts += 1;
+        self.last_region_restart = Some(Instant::now());
+
+        Ok(())
+    }
+
+    /// Feeds the job's load to its autoscaler, if it has one, and writes new parallelism
+    /// overrides when it decides the job should be rescaled. The job is then rescaled like any
+    /// other change to its overrides, once the controller sees the new config.
+    async fn autoscale(&mut self) {
+        let Some(autoscaler) = &mut self.autoscaler else {
+            return;
+        };
+
+        let Some(backpressure) = self.model.metrics.max_backpressure().await else {
+            return;
+        };
+
+        let autoscaling = &config().pipeline.autoscaling;
+        let load = Load {
+            backpressure,
+            lag: self.model.max_source_lag(),
+        };
+        let parallelism = self
+            .model
+            .operator_parallelism
+            .values()
+            .copied()
+            .max()
+            .unwrap_or(0);
+
+        let Some(target) = autoscaler.observe(autoscaling, Instant::now(), parallelism, load)
+        else {
+            return;
+        };
+
+        if self.model.started_at.elapsed() < *autoscaling.min_interval {
+            return;
+        }
+
+        // the overrides are written straight to the job's config, so they're kept within the
+        // bounds checked when the pipeline was created
+        let Some(bounds) = &self.model.program.program_config.autoscaling else {
+            return;
+        };
+        let overrides = rescaled_parallelism(&self.model.operator_parallelism, target, bounds);
+
+        // every operator that would be rescaled is already at its limit
+        if overrides == self.model.operator_parallelism {
+            return;
+        }
+
+        info!(
+            message = "autoscaling job",
+            job_id = *self.config.id,
+            from = parallelism,
+            to = target,
+            backpressure,
+            lag = load.lag
+        );
+
+        match self.update_parallelism_overrides(&overrides).await {
+            // the job gets a new autoscaler once it's been rescaled
+            Ok(()) => self.autoscaler = None,
+            Err(e) => warn!(
+                message = "failed to write autoscaled parallelism",
+                job_id = *self.config.id,
+                error = format!("{:?}", e)
+            ),
+        }
+    }
+
+    async fn update_parallelism_overrides(
+        &self,
+        overrides: &HashMap<String, usize>,
+    ) -> anyhow::Result<()> {
+        let c = self.db.client().await?;
+        controller_queries::execute_update_parallelism_overrides(
+            &c,
+            &serde_json::to_value(overrides)?,
+            &OffsetDateTime::now_utc(),
+            &*self.config.id,
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn record_usage(&mut self) {
+        let records = self.model.usage.take();
+        if records.is_empty() {
+            return;
+        }
+
+        let c = match self.db.client().await {
+            Ok(c) => c,
+            Err(e) => {
+                warn!(
+                    message = "failed to connect to database to record usage",
+                    job_id = *self.config.id,
+                    error = format!("{:?}", e)
+                );
+                return;
+            }
+        };
+
+        for record in records {
+            if let Err(e) = controller_queries::execute_record_job_usage(
+                &c,
+                &*self.config.id,
+                &record.day,
+                &record.cpu_seconds,
+                &(record.max_memory_bytes as i64),
+                &(record.state_bytes as i64),
+                &(record.network_bytes as i64),
+            )
+            .await
+            {
+                warn!(
+                    message = "failed to record job usage",
+                    job_id = *self.config.id,
+                    day = record.day,
+                    error = format!("{:?}", e)
+                );
+            }
+        }
+    }
+
     pub async fn stop_job(&mut self, stop_mode: StopMode) -> anyhow::Result<()> {
         for c in self.model.workers.values_mut() {
             c.connect
@@ -802,6 +1343,12 @@ impl JobController {
         }
     }
 
+    /// Leaves the workers running rather than shutting them down once all of the job's tasks
+    /// have finished, so that it can be restarted on them (for example, to rescale it in place)
+    pub fn keep_workers(&mut self) {
+        self.model.keep_workers = true;
+    }
+
     pub fn finished(&self) -> bool {
         self.model.all_tasks_finished()
     }
@@ -865,6 +1412,110 @@ impl JobController {
         self.model.operator_parallelism.get(op).cloned()
     }
 
+    /// Compacts the state of each operator as of the epoch, and sends the compacted tables to the
+    /// workers, which use them in place of the files they replace from their next checkpoint
+    fn start_compaction(&mut self, epoch: u32) -> JoinHandle<anyhow::Result<()>> {
+        let job_id = self.config.id.clone();
+        let operator_ids: Vec<_> = self.model.operator_parallelism.keys().cloned().collect();
+        let mut worker_clients: Vec<WorkerGrpcClient<Channel>> = self
+            .model
+            .workers
+            .values()
+            .map(|w| w.connect.clone())
+            .collect();
+
+        info!(message = "Compacting state", job_id = *job_id, epoch);
+        let start = Instant::now();
+
+        tokio::spawn(async move {
+            for operator_id in operator_ids {
+                let compacted_tables =
+                    ParquetBackend::compact_operator(job_id.clone(), operator_id.clone(), epoch)
+                        .await?;
+
+                if compacted_tables.is_empty() {
+                    continue;
+                }
+
+                for worker_client in &mut worker_clients {
+                    worker_client
+                        .load_compacted_data(LoadCompactedDataReq {
+                            operator_id: operator_id.clone(),
+                            compacted_metadata: compacted_tables.clone(),
+                        })
+                        .await?;
+                }
+            }
+
+            info!(
+                message = "Finished compaction",
+                job_id = *job_id,
+                epoch,
+                duration = start.elapsed().as_secs_f32()
+            );
+
+            Ok(())
+        })
+    }
+
+    /// Writes savepoints from the checkpoints they were taken at, recording in the database
+    /// whether each one succeeded
+    fn start_savepoints(
+        &mut self,
+        savepoints: Vec<(String, u32)>,
+    ) -> JoinHandle<anyhow::Result<()>> {
+        let job_id = self.config.id.clone();
+        let db = self.db.clone();
+
+        tokio::spawn(async move {
+            for (savepoint_id, epoch) in savepoints {
+                info!(
+                    message = "Writing savepoint",
+                    job_id = *job_id,
+                    savepoint_id,
+                    epoch
+                );
+                let start = Instant::now();
+
+                match ParquetBackend::write_savepoint(&job_id, epoch, &savepoint_id).await {
+                    Ok(()) => {
+                        controller_queries::execute_finish_savepoint(
+                            &db.client().await?,
+                            &(epoch as i32),
+                            &savepoint_id,
+                        )
+                        .await?;
+
+                        info!(
+                            message = "Finished writing savepoint",
+                            job_id = *job_id,
+                            savepoint_id,
+                            epoch,
+                            duration = start.elapsed().as_secs_f32()
+                        );
+                    }
+                    Err(e) => {
+                        error!(
+                            message = "Failed to write savepoint",
+                            job_id = *job_id,
+                            savepoint_id,
+                            error = format!("{:?}", e)
+                        );
+
+                        controller_queries::execute_fail_savepoint(
+                            &db.client().await?,
+                            &e.to_string(),
+                            &savepoint_id,
+                        )
+                        .await?;
+                    }
+                }
+            }
+
+            Ok(())
+        })
+    }
+
     fn start_cleanup(&mut self, new_min: u32) -> JoinHandle<anyhow::Result<u32>> {
         let min_epoch = self.model.min_epoch.max(1);
         let job_id = self.config.id.clone();
@@ -878,8 +1529,42 @@ impl JobController {
         );
         let start = Instant::now();
         let cur_epoch = self.model.epoch;
+        let keep_younger_than = config()
+            .pipeline
+            .checkpoint_retention
+            .keep_younger_than
+            .as_deref()
+            .copied();
 
         tokio::spawn(async move {
+            // keep any checkpoints that are younger than the retention policy requires
+            let new_min = match keep_younger_than {
+                Some(max_age) => {
+                    let checkpoints: Vec<_> = controller_queries::fetch_checkpoints_in_range(
+                        &db.client().await?,
+                        &*job_id,
+                        &(min_epoch as i32),
+                        &(new_min as i32),
+                    )
+                    .await?
+                    .into_iter()
+                    .map(|r| (r.epoch as u32, r.start_time))
+                    .collect();
+
+                    retained_min_epoch(new_min, &checkpoints, OffsetDateTime::now_utc() - max_age)
+                }
+                None => new_min,
+            };
+
+            if new_min <= min_epoch {
+                debug!(
+                    message = "No checkpoints to clean up",
+                    job_id = *job_id,
+                    min_epoch
+                );
+                return Ok(min_epoch);
+            }
+
             let checkpoint = StateBackend::load_checkpoint_metadata(&job_id, cur_epoch).await?;
 
             controller_queries::execute_mark_compacting(
@@ -920,3 +1605,127 @@ impl JobController {
         })
     }
 }
+
+/// Returns the epoch that checkpoints can be cleaned up to, which is `new_min` unless one of the
+/// `checkpoints` (as epochs and start times) before it was started at or after `cutoff`, in which
+/// case the oldest such checkpoint and everything after it are kept
+fn retained_min_epoch(
+    new_min: u32,
+    checkpoints: &[(u32, OffsetDateTime)],
+    cutoff: OffsetDateTime,
+) -> u32 {
+    checkpoints
+        .iter()
+        .filter(|(epoch, start_time)| *epoch < new_min && *start_time >= cutoff)
+        .map(|(epoch, _)| *epoch)
+        .min()
+        .unwrap_or(new_min)
+}
+
+/// Why a failed region can't be restored on its own from the last checkpoint, if it can't, in
+/// which case the whole job is restarted instead
+fn region_restart_refusal(
+    region_failover: bool,
+    protocol_version: u32,
+    checkpoint_state: Option<&CheckpointingOrCommittingState>,
+    compacting: bool,
+) -> Option<&'static str> {
+    if !region_failover {
+        return Some("region failover is disabled");
+    }
+
+    if protocol_version < REGION_RESTART_VERSION {
+        return Some("workers don't support restarting regions");
+    }
+
+    match checkpoint_state {
+        // committing lasts until every sink subtask has reported its commit finished; restoring
+        // a region's sinks before then could commit or abort their pre-commits twice
+        Some(CheckpointingOrCommittingState::Committing(_)) => {
+            return Some("a two-phase commit is in flight");
+        }
+        // the region is restored from the last checkpoint, which must be complete
+        Some(CheckpointingOrCommittingState::Checkpointing(_)) => {
+            return Some("a checkpoint is in progress");
+        }
+        None => {}
+    }
+
+    compacting.then_some("the last checkpoint is being compacted")
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        region_restart_refusal, retained_min_epoch, CheckpointingOrCommittingState,
+        REGION_RESTART_VERSION,
+    };
+    use arroyo_state::committing_state::CommittingState;
+    use std::collections::{HashMap, HashSet};
+    use time::{Duration, OffsetDateTime};
+
+    #[test]
+    fn test_region_restart_refusal() {
+        let committing = CheckpointingOrCommittingState::Committing(CommittingState::new(
+            "checkpoint".to_string(),
+            HashSet::from([("sink".to_string(), 0)]),
+            HashMap::new(),
+        ));
+
+        // with region failover on, an idle job restarts only the failed region
+        assert_eq!(
+            region_restart_refusal(true, REGION_RESTART_VERSION, None, false),
+            None
+        );
+
+        assert!(region_restart_refusal(false, REGION_RESTART_VERSION, None, false).is_some());
+        assert!(region_restart_refusal(true, REGION_RESTART_VERSION - 1, None, false).is_some());
+        assert_eq!(
+            region_restart_refusal(true, REGION_RESTART_VERSION, Some(&committing), false),
+            Some("a two-phase commit is in flight")
+        );
+        assert_eq!(
+            region_restart_refusal(true, REGION_RESTART_VERSION, None, true),
+            Some("the last checkpoint is being compacted")
+        );
+    }
+
+    #[test]
+    fn test_retained_min_epoch() {
+        let now = OffsetDateTime::now_utc();
+        let checkpoints: Vec<_> = (3..10)
+            .map(|epoch| (epoch, now - Duration::minutes(10 * (10 - epoch as i64))))
+            .collect();
+
+        // nothing is young enough to keep
+        assert_eq!(
+            retained_min_epoch(8, &checkpoints, now - Duration::minutes(5)),
+            8
+        );
+
+        // epochs 6 and 7 were started within the last 45 minutes
+        assert_eq!(
+            retained_min_epoch(8, &checkpoints, now - Duration::minutes(45)),
+            6
+        );
+
+        // a checkpoint started exactly at the cutoff is kept
+        assert_eq!(
+            retained_min_epoch(8, &checkpoints, now - Duration::minutes(40)),
+            6
+        );
+
+        // everything is kept if every checkpoint is young enough
+        assert_eq!(
+            retained_min_epoch(8, &checkpoints, now - Duration::hours(2)),
+            3
+        );
+
+        // checkpoints at or after the new min are always kept, so don't affect it
+        assert_eq!(
+            retained_min_epoch(8, &checkpoints[5..], now - Duration::hours(2)),
+            8
+        );
+        assert_eq!(retained_min_epoch(8, &[], now), 8);
+    }
+}
@@ -0,0 +1,166 @@
+use arroyo_connectors::splits::assign_splits;
+use arroyo_rpc::config::SourceRebalancingConfig;
+use arroyo_rpc::grpc::rpc::SourceLagUnit;
+use std::collections::HashMap;
+
+/// The most recently reported lag of each split of a source, by the subtask that reads it
+#[derive(Debug)]
+pub struct SourceSplitLag {
+    pub unit: SourceLagUnit,
+    pub subtasks: HashMap<u32, HashMap<String, u64>>,
+}
+
+/// The gap between the most and least lagged subtasks of a source
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Skew {
+    pub min: u64,
+    pub max: u64,
+}
+
+impl Skew {
+    fn of(lags: impl IntoIterator<Item = u64>) -> Self {
+        let (min, max) = lags.into_iter().fold((u64::MAX, 0), |(min, max), lag| {
+            (min.min(lag), max.max(lag))
+        });
+
+        Self {
+            min: min.min(max),
+            max,
+        }
+    }
+
+    pub fn gap(&self) -> u64 {
+        self.max - self.min
+    }
+}
+
+impl SourceSplitLag {
+    /// Returns the skew between the subtasks of the source as its splits are assigned now, and
+    /// as they would be if it were restarted and assigned its splits by lag; `None` until every
+    /// subtask has reported its lag
+    pub fn skew(&self, parallelism: usize) -> Option<(Skew, Skew)> {
+        if parallelism < 2 || self.subtasks.len() < parallelism {
+            return None;
+        }
+
+        let current = Skew::of(self.subtasks.values().map(|splits| splits.values().sum()));
+
+        let lag: HashMap<String, u64> = self
+            .subtasks
+            .values()
+            .flat_map(|splits| splits.iter().map(|(k, v)| (k.clone(), *v)))
+            .collect();
+
+        let mut loads = vec![0u64; parallelism];
+        for (split, subtask) in assign_splits(lag.keys().cloned(), &lag, parallelism) {
+            loads[subtask] = loads[subtask].saturating_add(lag[&split]);
+        }
+
+        Some((current, Skew::of(loads)))
+    }
+
+    /// Whether the source is skewed enough to rebalance, and rebalancing it would narrow the
+    /// gap between its most and least lagged subtasks by at least the minimum lag; otherwise
+    /// restarting the job wouldn't help (for example, if one split holds most of the backlog)
+    pub fn should_rebalance(
+        &self,
+        config: &SourceRebalancingConfig,
+        parallelism: usize,
+    ) -> Option<(Skew, Skew)> {
+        let min_lag = match self.unit {
+            SourceLagUnit::Messages => config.min_lag_messages,
+            SourceLagUnit::Milliseconds => config.min_lag_millis,
+        };
+
+        let (current, expected) = self.skew(parallelism)?;
+
+        let skewed = current.gap() >= min_lag
+            && current.max as f64 >= config.skew_ratio * current.min.max(1) as f64;
+        let improves = current.gap().saturating_sub(expected.gap()) >= min_lag;
+
+        (skewed && improves).then_some((current, expected))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Skew, SourceSplitLag};
+    use arroyo_rpc::config::SourceRebalancingConfig;
+    use arroyo_rpc::grpc::rpc::SourceLagUnit;
+    use std::collections::HashMap;
+
+    fn lag(unit: SourceLagUnit, subtasks: &[&[(&str, u64)]]) -> SourceSplitLag {
+        SourceSplitLag {
+            unit,
+            subtasks: subtasks
+                .iter()
+                .enumerate()
+                .map(|(i, splits)| {
+                    (
+                        i as u32,
+                        splits.iter().map(|(k, v)| (k.to_string(), *v)).collect(),
+                    )
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_should_rebalance() {
+        let config = SourceRebalancingConfig {
+            enabled: true,
+            min_lag_messages: 1000,
+            min_lag_millis: 60_000,
+            skew_ratio: 4.0,
+            min_interval: "10m".parse().unwrap(),
+            cooldown: "30m".parse().unwrap(),
+        };
+
+        // two lagged partitions on one subtask can be split up
+        let lagged = lag(
+            SourceLagUnit::Messages,
+            &[&[("0", 5000), ("1", 5000)], &[("2", 0), ("3", 0)]],
+        );
+        assert_eq!(
+            lagged.should_rebalance(&config, 2),
+            Some((
+                Skew { min: 0, max: 10000 },
+                Skew {
+                    min: 5000,
+                    max: 5000
+                }
+            ))
+        );
+
+        // but not until every subtask has reported its lag
+        assert_eq!(lagged.should_rebalance(&config, 3), None);
+
+        // a single lagged partition can't be, so there's no point restarting
+        let hot_partition = lag(
+            SourceLagUnit::Messages,
+            &[&[("0", 10000), ("1", 0)], &[("2", 0), ("3", 0)]],
+        );
+        assert_eq!(hot_partition.skew(2).unwrap().1.gap(), 10000);
+        assert_eq!(hot_partition.should_rebalance(&config, 2), None);
+
+        // Kinesis lag is measured in milliseconds, with its own threshold
+        let shards = lag(
+            SourceLagUnit::Milliseconds,
+            &[&[("a", 5000), ("b", 5000)], &[("c", 0), ("d", 0)]],
+        );
+        assert_eq!(shards.should_rebalance(&config, 2), None);
+
+        let shards = lag(
+            SourceLagUnit::Milliseconds,
+            &[&[("a", 100_000), ("b", 100_000)], &[("c", 0), ("d", 0)]],
+        );
+        assert!(shards.should_rebalance(&config, 2).is_some());
+
+        // sources that don't assign their splits by lag report none
+        let unsplit = SourceSplitLag {
+            unit: SourceLagUnit::Messages,
+            subtasks: (0..2).map(|i| (i, HashMap::new())).collect(),
+        };
+        assert_eq!(unsplit.should_rebalance(&config, 2), None);
+    }
+}
@@ -3,20 +3,25 @@
 #![allow(clippy::type_complexity)]
 
 use anyhow::Result;
+use arroyo_rpc::api_types::pipelines::SourceOffsetOverrides;
 use arroyo_rpc::config;
 use arroyo_rpc::config::config;
 use arroyo_rpc::grpc::rpc::controller_grpc_server::{ControllerGrpc, ControllerGrpcServer};
 use arroyo_rpc::grpc::rpc::{
-    GrpcOutputSubscription, HeartbeatNodeReq, HeartbeatNodeResp, HeartbeatReq, HeartbeatResp,
-    JobMetricsReq, JobMetricsResp, OutputData, RegisterNodeReq, RegisterNodeResp,
-    RegisterWorkerReq, RegisterWorkerResp, TaskCheckpointCompletedReq, TaskCheckpointCompletedResp,
-    TaskFailedReq, TaskFailedResp, TaskFinishedReq, TaskFinishedResp, TaskStartedReq,
-    TaskStartedResp, WorkerFinishedReq, WorkerFinishedResp,
+    task_checkpoint_message, SinkDataReq, SinkDataResp, TaskCheckpointEventReq,
+    TaskCheckpointEventResp, TaskCheckpointMessagesReq, TaskCheckpointMessagesResp, WorkerErrorReq,
+    WorkerErrorRes,
 };
 use arroyo_rpc::grpc::rpc::{
-    SinkDataReq, SinkDataResp, TaskCheckpointEventReq, TaskCheckpointEventResp, WorkerErrorReq,
-    WorkerErrorRes,
+    GrpcOutputSubscription, HeartbeatNodeReq, HeartbeatNodeResp, HeartbeatReq, HeartbeatResp,
+    JobMetricsReq, JobMetricsResp, JobsMetricsReq, JobsMetricsResp, OutputData, QueryStateReq,
+    QueryStateResp, RegisterNodeReq, RegisterNodeResp, RegisterWorkerReq, RegisterWorkerResp,
+    SourceLagReq, SourceLagResp, SourceLagUnit, StateEntry, TakeSavepointReq, TakeSavepointResp,
+    TaskCheckpointCompletedReq, TaskCheckpointCompletedResp, TaskFailedReq, TaskFailedResp,
+    TaskFinishedReq, TaskFinishedResp, TaskStartedReq, TaskStartedResp, WorkerFinishedReq,
+    WorkerFinishedResp, WorkerShuttingDownReq, WorkerShuttingDownResp,
 };
+use arroyo_rpc::protocol::negotiate_protocol_version;
 use arroyo_rpc::public_ids::{generate_id, IdTypes};
 use arroyo_server_common::shutdown::ShutdownGuard;
 use arroyo_server_common::wrap_start;
@@ -34,7 +39,7 @@ use time::OffsetDateTime;
 use tokio::net::TcpListener;
 use tokio::sync::mpsc::error::TrySendError;
 use tokio::sync::mpsc::Sender;
-use tokio::sync::RwLock;
+use tokio::sync::{oneshot, RwLock};
 use tokio_stream::wrappers::{ReceiverStream, TcpListenerStream};
 use tonic::codec::CompressionEncoding;
 use tonic::{Request, Response, Status};
@@ -84,6 +89,9 @@ pub struct JobConfig {
     checkpoint_interval: Duration,
     ttl: Option<Duration>,
     parallelism_overrides: HashMap<String, usize>,
+    source_offset_overrides: Option<SourceOffsetOverrides>,
+    // the savepoint to restore from if the job has no checkpoints of its own
+    restore_savepoint_id: Option<String>,
     restart_nonce: i32,
     restart_mode: RestartMode,
 }
@@ -154,6 +162,7 @@ pub enum RunningMessage {
         operator_id: String,
         subtask_index: u32,
         reason: String,
+        fatal: bool,
     },
     WorkerHeartbeat {
         worker_id: WorkerId,
@@ -162,6 +171,26 @@ pub enum RunningMessage {
     WorkerFinished {
         worker_id: WorkerId,
     },
+    WorkerShuttingDown {
+        worker_id: WorkerId,
+        drain: bool,
+    },
+    SourceLag {
+        operator_id: String,
+        subtask_index: u32,
+        lag: u64,
+        split_lag: HashMap<String, u64>,
+        unit: SourceLagUnit,
+    },
+    /// A user has asked for a savepoint to be written from the job's next checkpoint
+    Savepoint {
+        savepoint_id: String,
+    },
+    /// A user is looking up entries in the live state of one of the job's operators
+    QueryState {
+        req: QueryStateReq,
+        tx: oneshot::Sender<Result<Vec<StateEntry>, Status>>,
+    },
 }
 
 #[derive(Debug)]
@@ -173,6 +202,7 @@ pub enum JobMessage {
         rpc_address: String,
         data_address: String,
         slots: usize,
+        protocol_version: u32,
     },
     TaskStarted {
         worker_id: WorkerId,
@@ -180,6 +210,8 @@ pub enum JobMessage {
         operator_subtask: u64,
     },
     RunningMessage(RunningMessage),
+    /// A preview sink has output the maximum number of rows for a preview
+    PreviewLimitReached,
 }
 
 #[derive(Clone)]
@@ -205,6 +237,22 @@ impl ControllerGrpc for ControllerServer {
 
         let req = request.into_inner();
 
+        let protocol_version =
+            negotiate_protocol_version(req.protocol_version, req.min_protocol_version).map_err(
+                |e| {
+                    warn!(
+                        message = "rejecting incompatible worker",
+                        job_id = req.job_id,
+                        worker_id = req.worker_id,
+                        error = format!("{}", e)
+                    );
+                    Status::failed_precondition(format!(
+                        "worker {} cannot join job {}: {}",
+                        req.worker_id, req.job_id, e
+                    ))
+                },
+            )?;
+
         self.send_to_job_queue(
             &req.job_id,
             JobMessage::WorkerConnect {
@@ -213,11 +261,12 @@ impl ControllerGrpc for ControllerServer {
                 rpc_address: req.rpc_address,
                 data_address: req.data_address,
                 slots: req.slots as usize,
+                protocol_version,
             },
         )
         .await?;
 
-        Ok(Response::new(RegisterWorkerResp {}))
+        Ok(Response::new(RegisterWorkerResp { protocol_version }))
     }
 
     async fn heartbeat(
@@ -293,6 +342,41 @@ impl ControllerGrpc for ControllerServer {
         Ok(Response::new(TaskCheckpointCompletedResp {}))
     }
 
+    async fn task_checkpoint_messages(
+        &self,
+        request: Request<TaskCheckpointMessagesReq>,
+    ) -> Result<Response<TaskCheckpointMessagesResp>, Status> {
+        let req = request.into_inner();
+
+        debug!(
+            message = "received task checkpoint messages",
+            job_id = req.job_id,
+            worker_id = req.worker_id,
+            count = req.messages.len()
+        );
+
+        // the messages are forwarded in order, as the job controller ignores messages for an
+        // epoch other than the one it's currently checkpointing
+        for message in req.messages {
+            let message = match message.message {
+                Some(task_checkpoint_message::Message::Event(event)) => {
+                    RunningMessage::TaskCheckpointEvent(event)
+                }
+                Some(task_checkpoint_message::Message::Completed(completed)) => {
+                    RunningMessage::TaskCheckpointFinished(completed)
+                }
+                None => {
+                    return Err(Status::invalid_argument("checkpoint message is empty"));
+                }
+            };
+
+            self.send_to_job_queue(&req.job_id, JobMessage::RunningMessage(message))
+                .await?;
+        }
+
+        Ok(Response::new(TaskCheckpointMessagesResp {}))
+    }
+
     async fn task_finished(
         &self,
         request: Request<TaskFinishedReq>,
@@ -326,6 +410,7 @@ impl ControllerGrpc for ControllerServer {
                 operator_id: req.operator_id,
                 subtask_index: req.operator_subtask as u32,
                 reason: req.error,
+                fatal: req.fatal,
             }),
         )
         .await?;
@@ -364,11 +449,70 @@ impl ControllerGrpc for ControllerServer {
         Ok(Response::new(WorkerFinishedResp {}))
     }
 
+    async fn worker_shutting_down(
+        &self,
+        request: Request<WorkerShuttingDownReq>,
+    ) -> Result<Response<WorkerShuttingDownResp>, Status> {
+        let req = request.into_inner();
+        info!(
+            message = "Worker shutting down",
+            job_id = req.job_id,
+            worker_id = req.worker_id,
+            drain = req.drain
+        );
+
+        self.send_to_job_queue(
+            &req.job_id,
+            JobMessage::RunningMessage(RunningMessage::WorkerShuttingDown {
+                worker_id: WorkerId(req.worker_id),
+                drain: req.drain,
+            }),
+        )
+        .await?;
+
+        Ok(Response::new(WorkerShuttingDownResp {}))
+    }
+
+    async fn source_lag(
+        &self,
+        request: Request<SourceLagReq>,
+    ) -> Result<Response<SourceLagResp>, Status> {
+        let req = request.into_inner();
+
+        self.send_to_job_queue(
+            &req.job_id,
+            JobMessage::RunningMessage(RunningMessage::SourceLag {
+                operator_id: req.operator_id,
+                subtask_index: req.task_index,
+                lag: req.lag,
+                unit: req.unit(),
+                split_lag: req.split_lag,
+            }),
+        )
+        .await?;
+
+        Ok(Response::new(SourceLagResp {}))
+    }
+
     async fn send_sink_data(
         &self,
         request: Request<SinkDataReq>,
     ) -> Result<Response<SinkDataResp>, Status> {
         let req = request.into_inner();
+
+        if req.limit_reached {
+            info!(
+                message = "preview reached its row limit; stopping",
+                job_id = req.job_id
+            );
+            if let Err(e) = self
+                .send_to_job_queue(&req.job_id, JobMessage::PreviewLimitReached)
+                .await
+            {
+                warn!("failed to stop preview {}: {}", req.job_id, e.message());
+            }
+        }
+
         let mut data_txs = self.data_txs.lock().await;
         if let Some(v) = data_txs.get_mut(&req.job_id) {
             let output = OutputData {
@@ -484,6 +628,68 @@ impl ControllerGrpc for ControllerServer {
             metrics: serde_json::to_string(&metrics.get_groups().await).unwrap(),
         }))
     }
+
+    async fn jobs_metrics(
+        &self,
+        request: Request<JobsMetricsReq>,
+    ) -> Result<Response<JobsMetricsResp>, Status> {
+        let job_ids = request.into_inner().job_ids;
+        let all_metrics = self.metrics.read().await;
+
+        let mut metrics = HashMap::new();
+        for job_id in job_ids {
+            if let Some(job_metrics) = all_metrics.get(&job_id) {
+                let groups = serde_json::to_string(&job_metrics.get_groups().await).unwrap();
+                metrics.insert(job_id, groups);
+            }
+        }
+
+        Ok(Response::new(JobsMetricsResp { metrics }))
+    }
+
+    async fn take_savepoint(
+        &self,
+        request: Request<TakeSavepointReq>,
+    ) -> Result<Response<TakeSavepointResp>, Status> {
+        let req = request.into_inner();
+        info!(
+            message = "Savepoint requested",
+            job_id = req.job_id,
+            savepoint_id = req.savepoint_id
+        );
+
+        self.send_to_job_queue(
+            &req.job_id,
+            JobMessage::RunningMessage(RunningMessage::Savepoint {
+                savepoint_id: req.savepoint_id,
+            }),
+        )
+        .await?;
+
+        Ok(Response::new(TakeSavepointResp {}))
+    }
+
+    async fn query_state(
+        &self,
+        request: Request<QueryStateReq>,
+    ) -> Result<Response<QueryStateResp>, Status> {
+        let req = request.into_inner();
+        let job_id = req.job_id.clone();
+
+        let (tx, rx) = oneshot::channel();
+        self.send_to_job_queue(
+            &job_id,
+            JobMessage::RunningMessage(RunningMessage::QueryState { req, tx }),
+        )
+        .await?;
+
+        // the query is dropped if the job isn't running
+        let entries = rx
+            .await
+            .map_err(|_| Status::failed_precondition("Job must be running to query its state"))??;
+
+        Ok(Response::new(QueryStateResp { entries }))
+    }
 }
 
 impl ControllerServer {
@@ -571,6 +777,18 @@ impl ControllerServer {
                             .into_iter()
                             .map(|(k, v)| (k.clone(), v.as_u64().unwrap() as usize))
                             .collect(),
+                        source_offset_overrides: p.source_offset_overrides.and_then(|v| {
+                            serde_json::from_value(v)
+                                .map_err(|e| {
+                                    warn!(
+                                        message = "invalid source offset overrides",
+                                        job_id = *id,
+                                        error = format!("{:?}", e)
+                                    )
+                                })
+                                .ok()
+                        }),
+                        restore_savepoint_id: p.restore_savepoint_id,
                         restart_nonce: p.config_restart_nonce,
                         restart_mode: p.restart_mode,
                     };
@@ -653,7 +871,8 @@ impl ControllerServer {
                 .add_service(
                     ControllerGrpcServer::new(self.clone())
                         .send_compressed(CompressionEncoding::Zstd)
-                        .accept_compressed(CompressionEncoding::Zstd),
+                        .accept_compressed(CompressionEncoding::Zstd)
+                        .accept_compressed(CompressionEncoding::Gzip),
                 )
                 .add_service(reflection)
                 .serve_with_incoming(TcpListenerStream::new(listener)),
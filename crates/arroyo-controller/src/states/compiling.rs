@@ -12,6 +12,6 @@ impl State for Compiling {
     }
 
     async fn next(self: Box<Self>, _ctx: &mut JobContext) -> Result<Transition, StateError> {
-        return Ok(Transition::next(*self, Scheduling {}));
+        return Ok(Transition::next(*self, Scheduling::default()));
     }
 }
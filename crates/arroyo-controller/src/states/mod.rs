@@ -31,7 +31,7 @@ use self::finishing::Finishing;
 use self::recovering::Recovering;
 use self::rescaling::Rescaling;
 use self::running::Running;
-use self::scheduling::Scheduling;
+use self::scheduling::{RunningWorkers, Scheduling};
 use self::stopping::Stopping;
 
 mod checkpoint_stopping;
@@ -361,12 +361,25 @@ pub struct JobContext<'a> {
     rx: &'a mut Receiver<JobMessage>,
     retries_attempted: usize,
     job_controller: Option<JobController>,
+    // the workers the job was last scheduled on, while it's running on them
+    running_workers: Option<RunningWorkers>,
     last_transitioned_at: Instant,
+    // when the job was last stopped to rebalance its sources
+    last_rebalanced_at: Option<Instant>,
+    // whether the job is a preview that has produced all of the output it's allowed to, which
+    // may be reported before it has finished starting
+    preview_limit_reached: bool,
     metrics: Arc<tokio::sync::RwLock<HashMap<Arc<String>, JobMetrics>>>,
 }
 
 impl<'a> JobContext<'a> {
     pub fn handle(&mut self, msg: JobMessage) -> Result<(), StateError> {
+        if matches!(msg, JobMessage::PreviewLimitReached) && self.config.ttl.is_some() {
+            // the preview is stopped once it's running
+            self.preview_limit_reached = true;
+            return Ok(());
+        }
+
         if !matches!(
             msg,
             JobMessage::RunningMessage(RunningMessage::WorkerHeartbeat { .. })
@@ -566,7 +579,10 @@ pub async fn run_to_completion(
         rx: &mut rx,
         retries_attempted: 0,
         job_controller: None,
+        running_workers: None,
         last_transitioned_at: Instant::now(),
+        last_rebalanced_at: None,
+        preview_limit_reached: false,
         metrics,
     };
 
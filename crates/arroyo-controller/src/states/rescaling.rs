@@ -1,3 +1,5 @@
+use tracing::{info, warn};
+
 use crate::{states::stop_if_desired_non_running, JobMessage};
 
 use super::{scheduling::Scheduling, JobContext, State, StateError, Transition};
@@ -12,7 +14,16 @@ impl State for Rescaling {
     }
 
     async fn next(mut self: Box<Self>, ctx: &mut JobContext) -> Result<Transition, StateError> {
+        // if the workers the job is running on have room for the new parallelism, it's restarted
+        // on them from the final checkpoint rather than on a new set of workers
+        let mut program = ctx.program.clone();
+        program.update_parallelism(&ctx.config.parallelism_overrides);
+        let mut running_workers = ctx.running_workers.take().filter(|w| w.can_run(&program));
+
         let job_controller = ctx.job_controller.as_mut().unwrap();
+        if running_workers.is_some() {
+            job_controller.keep_workers();
+        }
 
         let mut final_checkpoint_started = false;
 
@@ -20,7 +31,28 @@ impl State for Rescaling {
             match job_controller.checkpoint_finished().await {
                 Ok(done) => {
                     if done && job_controller.finished() && final_checkpoint_started {
-                        return Ok(Transition::next(*self, Scheduling {}));
+                        let next = match running_workers.take() {
+                            Some(mut workers) => match workers.reset().await {
+                                Ok(()) => {
+                                    info!(
+                                        message = "rescaling job in place",
+                                        job_id = *ctx.config.id
+                                    );
+                                    Scheduling::on_running_workers(workers)
+                                }
+                                Err(e) => {
+                                    warn!(
+                                        message = "failed to reset workers for rescaling; rescheduling job",
+                                        job_id = *ctx.config.id,
+                                        error = format!("{:?}", e)
+                                    );
+                                    Scheduling::default()
+                                }
+                            },
+                            None => Scheduling::default(),
+                        };
+
+                        return Ok(Transition::next(*self, next));
                     }
                 }
                 Err(e) => {
@@ -61,6 +93,10 @@ impl State for Rescaling {
                 JobMessage::ConfigUpdate(c) => {
                     stop_if_desired_non_running!(self, &c);
                 }
+                JobMessage::PreviewLimitReached if ctx.config.ttl.is_some() => {
+                    // the preview is stopped once it's running again
+                    ctx.preview_limit_reached = true;
+                }
                 _ => {
                     // ignore other messages
                 }
@@ -30,7 +30,7 @@ impl State for Restarting {
                     match job_controller.checkpoint_finished().await {
                         Ok(done) => {
                             if done && job_controller.finished() {
-                                return Ok(Transition::next(*self, Scheduling {}));
+                                return Ok(Transition::next(*self, Scheduling::default()));
                             }
                         }
                         Err(e) => {
@@ -65,6 +65,10 @@ impl State for Restarting {
                             }
                             stop_if_desired_non_running!(self, &c);
                         }
+                        JobMessage::PreviewLimitReached if ctx.config.ttl.is_some() => {
+                            // the preview is stopped once it's running again
+                            ctx.preview_limit_reached = true;
+                        }
                         _ => {
                             // ignore other messages
                         }
@@ -76,7 +80,7 @@ impl State for Restarting {
                     return Err(ctx.retryable(self, "failed to tear down existing cluster", e, 10));
                 }
 
-                Ok(Transition::next(*self, Scheduling {}))
+                Ok(Transition::next(*self, Scheduling::default()))
             }
         }
     }
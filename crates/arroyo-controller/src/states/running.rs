@@ -14,6 +14,7 @@ use crate::JobMessage;
 use crate::{job_controller::ControllerProgress, states::StateError};
 use arroyo_rpc::config::config;
 use arroyo_server_common::log_event;
+use arroyo_types::NonRetryableError;
 use serde_json::json;
 
 use super::{JobContext, State, Transition};
@@ -30,6 +31,16 @@ impl State for Running {
     async fn next(mut self: Box<Self>, ctx: &mut JobContext) -> Result<Transition, StateError> {
         stop_if_desired_running!(self, ctx.config);
 
+        if ctx.preview_limit_reached {
+            // the preview produced all of the output it's allowed to while it was starting
+            return Ok(Transition::next(
+                *self,
+                Stopping {
+                    stop_mode: StopBehavior::StopJob(rpc::StopMode::Immediate),
+                },
+            ));
+        }
+
         let pipeline_config = &config().clone().pipeline;
 
         let running_start = Instant::now();
@@ -79,6 +90,15 @@ impl State for Running {
                                 return Err(ctx.retryable(self, "job encountered an error", e, 10));
                             }
                         }
+                        Some(JobMessage::PreviewLimitReached) if ctx.config.ttl.is_some() => {
+                            // the preview has produced all of the output it's allowed to
+                            return Ok(Transition::next(
+                                *self,
+                                Stopping {
+                                    stop_mode: StopBehavior::StopJob(rpc::StopMode::Immediate),
+                                },
+                            ));
+                        }
                         Some(msg) => {
                             ctx.handle(msg)?;
                         }
@@ -109,6 +129,25 @@ impl State for Running {
                                 Finishing {}
                             ))
                         },
+                        Ok(ControllerProgress::Draining) => {
+                            // the job is stopped with a final checkpoint like when it's
+                            // rescaled, but rescheduled on new workers so that the draining
+                            // worker can exit
+                            ctx.running_workers = None;
+                            return Ok(Transition::next(
+                                *self,
+                                Rescaling {}
+                            ))
+                        },
+                        Ok(ControllerProgress::Rebalancing) => {
+                            ctx.last_rebalanced_at = Some(Instant::now());
+                            // stopping with a checkpoint hands off the sources' state, and they
+                            // redistribute their splits according to their lag when restored
+                            return Ok(Transition::next(
+                                *self,
+                                Rescaling {}
+                            ))
+                        },
                         Err(err) => {
                             error!(message = "error while running", error = format!("{:?}", err), job_id = *ctx.config.id);
                             log_event("running_error", json!({
@@ -118,6 +157,10 @@ impl State for Running {
                                 "is_preview": ctx.config.ttl.is_some(),
                             }));
 
+                            if err.is::<NonRetryableError>() {
+                                return Err(fatal("Job failed with an error that restarting it won't fix", err));
+                            }
+
                             // only allow one restart for preview pipelines
                             if ctx.config.ttl.is_some() {
                                 return Err(fatal("Job encountered a fatal error; see worker logs for details", err));
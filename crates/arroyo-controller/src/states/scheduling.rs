@@ -5,9 +5,11 @@ use std::{
 };
 
 use arroyo_rpc::grpc::rpc::{
-    worker_grpc_client::WorkerGrpcClient, StartExecutionReq, TaskAssignment,
+    worker_grpc_client::WorkerGrpcClient, ResetExecutionReq, SourceOffsetOverride,
+    StartExecutionReq, TaskAssignment,
 };
 use arroyo_types::WorkerId;
+use time::OffsetDateTime;
 use tokio::{select, sync::Mutex, task::JoinHandle};
 use tonic::{transport::Channel, Request};
 use tracing::{error, info, warn};
@@ -16,8 +18,11 @@ use anyhow::anyhow;
 use arroyo_datastream::logical::LogicalProgram;
 use arroyo_rpc::config::config;
 use arroyo_rpc::grpc::api;
+use arroyo_rpc::protocol::PROTOCOL_VERSION;
+use arroyo_rpc::public_ids::{generate_id, IdTypes};
 use arroyo_state::{
     committing_state::CommittingState,
+    compatibility::check_operator_compatibility,
     tables::{global_keyed_map::GlobalKeyedTable, ErasedTable},
     BackingStore, StateBackend,
 };
@@ -40,10 +45,57 @@ struct WorkerStatus {
     id: WorkerId,
     data_address: String,
     slots: usize,
+    protocol_version: u32,
 }
 
-#[derive(Debug)]
-pub struct Scheduling {}
+// the first protocol version in which workers can be reset to run a new execution
+const RESET_EXECUTION_VERSION: u32 = 4;
+
+/// The workers a job was scheduled on, which it can be restarted on without starting new ones
+#[derive(Debug, Clone)]
+pub struct RunningWorkers {
+    workers: HashMap<WorkerId, WorkerStatus>,
+    connects: HashMap<WorkerId, WorkerGrpcClient<Channel>>,
+}
+
+impl RunningWorkers {
+    fn slots(&self) -> usize {
+        self.workers.values().map(|w| w.slots).sum()
+    }
+
+    /// Whether the program can be run on these workers once their current execution is reset
+    pub fn can_run(&self, program: &LogicalProgram) -> bool {
+        self.workers
+            .values()
+            .all(|w| w.protocol_version >= RESET_EXECUTION_VERSION)
+            && self.slots() >= slots_for_job(program)
+    }
+
+    /// Clears the finished execution from each worker so that a new one can be started on it
+    pub async fn reset(&mut self) -> anyhow::Result<()> {
+        for (id, c) in &mut self.connects {
+            c.reset_execution(Request::new(ResetExecutionReq {}))
+                .await
+                .map_err(|e| anyhow!("failed to reset worker {}: {}", id.0, e.message()))?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Scheduling {
+    // when rescaling in place, the workers to start the job on again
+    running_workers: Option<RunningWorkers>,
+}
+
+impl Scheduling {
+    pub fn on_running_workers(workers: RunningWorkers) -> Self {
+        Self {
+            running_workers: Some(workers),
+        }
+    }
+}
 
 fn slots_for_job(job: &LogicalProgram) -> usize {
     job.graph
@@ -94,6 +146,7 @@ async fn handle_worker_connect<'a>(
             rpc_address,
             data_address,
             slots,
+            protocol_version,
             ..
         } => {
             workers.insert(
@@ -102,6 +155,7 @@ async fn handle_worker_connect<'a>(
                     id: worker_id,
                     data_address,
                     slots,
+                    protocol_version,
                 },
             );
 
@@ -225,67 +279,100 @@ impl State for Scheduling {
         // to schedule
         stop_if_desired_non_running!(self, &ctx.config);
 
-        // clear out any existing workers for this job
-        if let Err(e) = ctx.scheduler.stop_workers(&ctx.config.id, None, true).await {
-            warn!(
-                message = "failed to clean cluster prior to scheduling",
-                job_id = *ctx.config.id,
-                error = format!("{:?}", e)
-            )
-        }
+        ctx.running_workers = None;
 
         ctx.program
             .update_parallelism(&ctx.config.parallelism_overrides);
 
         let slots_needed: usize = slots_for_job(&*ctx.program);
-        self = self.start_workers(ctx, slots_needed).await?;
 
-        // wait for them to connect and make outbound RPC connections
-        let mut workers = HashMap::new();
-        let worker_connects = Arc::new(Mutex::new(HashMap::new()));
-        let mut handles = vec![];
+        let running_workers = self
+            .running_workers
+            .take()
+            .filter(|w| w.slots() >= slots_needed);
+
+        if running_workers.is_none() {
+            // clear out any existing workers for this job
+            if let Err(e) = ctx.scheduler.stop_workers(&ctx.config.id, None, true).await {
+                warn!(
+                    message = "failed to clean cluster prior to scheduling",
+                    job_id = *ctx.config.id,
+                    error = format!("{:?}", e)
+                )
+            }
+        }
 
-        let config = &config().pipeline;
+        if let Err(e) = arroyo_state::register_checkpoint_storage(
+            &ctx.config.id,
+            ctx.program.program_config.checkpoint_storage.as_ref(),
+        )
+        .await
+        {
+            return Err(ctx.retryable(self, "failed to connect to checkpoint storage", e, 3));
+        }
 
-        let start = Instant::now();
-        loop {
-            let timeout = config
-                .worker_startup_time
-                .min(ctx.config.ttl.unwrap_or(*config.worker_startup_time))
-                .checked_sub(start.elapsed())
-                .unwrap_or(Duration::ZERO);
+        let config = &config().pipeline;
 
-            tokio::select! {
-                val = ctx.rx.recv() => {
-                    match val {
-                        Some(JobMessage::ConfigUpdate(c)) => {
-                            stop_if_desired_non_running!(self, &c);
-                        }
-                        Some(msg) => {
-                            handle_worker_connect(msg, &mut workers, worker_connects.clone(), &mut handles, ctx).await?;
-                        }
-                        None => {
-                            panic!("Job message channel closed: {}", ctx.config.id);
+        let (workers, worker_connects) = if let Some(running_workers) = running_workers {
+            info!(
+                message = "restarting job on its running workers",
+                job_id = *ctx.config.id,
+                workers = running_workers.workers.len()
+            );
+            (running_workers.workers, running_workers.connects)
+        } else {
+            self = self.start_workers(ctx, slots_needed).await?;
+
+            // wait for them to connect and make outbound RPC connections
+            let mut workers = HashMap::new();
+            let worker_connects = Arc::new(Mutex::new(HashMap::new()));
+            let mut handles = vec![];
+
+            let start = Instant::now();
+            loop {
+                let timeout = config
+                    .worker_startup_time
+                    .min(ctx.config.ttl.unwrap_or(*config.worker_startup_time))
+                    .checked_sub(start.elapsed())
+                    .unwrap_or(Duration::ZERO);
+
+                tokio::select! {
+                    val = ctx.rx.recv() => {
+                        match val {
+                            Some(JobMessage::ConfigUpdate(c)) => {
+                                stop_if_desired_non_running!(self, &c);
+                            }
+                            Some(msg) => {
+                                handle_worker_connect(msg, &mut workers, worker_connects.clone(), &mut handles, ctx).await?;
+                            }
+                            None => {
+                                panic!("Job message channel closed: {}", ctx.config.id);
+                            }
                         }
                     }
+                    _ = tokio::time::sleep(timeout) => {
+                        return Err(ctx.retryable(self,
+                            "timed out while waiting for workers to start",
+                            anyhow!("timed out after {:?} while waiting for worker startup", *config.worker_startup_time), 3));
+                    }
                 }
-                _ = tokio::time::sleep(timeout) => {
-                    return Err(ctx.retryable(self,
-                        "timed out while waiting for workers to start",
-                        anyhow!("timed out after {:?} while waiting for worker startup", *config.worker_startup_time), 3));
+
+                if workers.values().map(|w| w.slots).sum::<usize>() >= slots_needed {
+                    break;
                 }
             }
 
-            if workers.values().map(|w| w.slots).sum::<usize>() >= slots_needed {
-                break;
+            for h in handles {
+                if let Err(e) = h.await {
+                    return Err(fatal("Failed to start cluster for pipeline", e.into()));
+                }
             }
-        }
 
-        for h in handles {
-            if let Err(e) = h.await {
-                return Err(fatal("Failed to start cluster for pipeline", e.into()));
-            }
-        }
+            (
+                workers,
+                Arc::try_unwrap(worker_connects).unwrap().into_inner(),
+            )
+        };
 
         // Compute assignments and send to workers
 
@@ -299,7 +386,7 @@ impl State for Scheduling {
             needs_commits: bool,
         }
 
-        let checkpoint_info = controller_queries::fetch_last_successful_checkpoint(
+        let mut checkpoint_info = controller_queries::fetch_last_successful_checkpoint(
             &ctx.db.client().await.unwrap(),
             &*ctx.config.id,
         )
@@ -323,6 +410,77 @@ impl State for Scheduling {
             }
         });
 
+        // a job started from a savepoint restores from it until it has checkpoints of its own
+        let restore_savepoint_id = ctx
+            .config
+            .restore_savepoint_id
+            .clone()
+            .filter(|_| checkpoint_info.is_none());
+        if let Some(savepoint_id) = &restore_savepoint_id {
+            let Some(epoch) = controller_queries::fetch_get_savepoint_epoch(
+                &ctx.db.client().await.unwrap(),
+                savepoint_id,
+            )
+            .await
+            .unwrap()
+            .into_iter()
+            .next()
+            .filter(|s| s.state == "ready")
+            .and_then(|s| s.epoch) else {
+                return Err(fatal(
+                    format!(
+                        "Failed to restore job; savepoint {} is not ready.",
+                        savepoint_id
+                    ),
+                    anyhow!("savepoint {} not found or not ready", savepoint_id),
+                ));
+            };
+            let epoch = epoch as u32;
+
+            info!(
+                message = "restoring savepoint",
+                job_id = *ctx.config.id,
+                savepoint_id,
+                epoch
+            );
+
+            if let Err(e) =
+                StateBackend::restore_savepoint(savepoint_id, epoch, &ctx.config.id).await
+            {
+                return Err(ctx.retryable(self, "failed to restore savepoint", e, 10));
+            }
+
+            // the savepoint becomes the job's first checkpoint
+            let c = ctx.db.client().await.unwrap();
+            let checkpoint_id = generate_id(IdTypes::Checkpoint);
+            controller_queries::execute_create_checkpoint(
+                &c,
+                &checkpoint_id,
+                &ctx.config.organization_id,
+                &*ctx.config.id,
+                &StateBackend::name().to_string(),
+                &(epoch as i32),
+                &(epoch as i32),
+                &OffsetDateTime::now_utc(),
+            )
+            .await
+            .unwrap();
+            controller_queries::execute_commit_checkpoint(
+                &c,
+                &OffsetDateTime::now_utc(),
+                &checkpoint_id,
+            )
+            .await
+            .unwrap();
+
+            checkpoint_info = Some(CheckpointInfo {
+                epoch,
+                min_epoch: epoch,
+                id: checkpoint_id,
+                needs_commits: false,
+            });
+        }
+
         {
             // mark in-progress checkpoints as failed
             let last_epoch = checkpoint_info
@@ -361,48 +519,89 @@ impl State for Scheduling {
                 return Err(ctx.retryable(self, "failed to prepare checkpoint for loading", e, 10));
             }
             metadata.min_epoch = min_epoch;
+
+            let mut operator_metadata = HashMap::new();
+            for operator_id in &metadata.operator_ids {
+                let op_metadata =
+                    StateBackend::load_operator_metadata(&ctx.config.id, operator_id, epoch)
+                        .await
+                        .map_err(|err| {
+                            fatal(
+                                format!(
+                                    "Failed to restore job; operator metadata for {} not found.",
+                                    operator_id
+                                ),
+                                err,
+                            )
+                        })?;
+                let Some(op_metadata) = op_metadata else {
+                    return Err(fatal(
+                        "missing operator metadata",
+                        anyhow!(
+                            "operator metadata for {} not found for job {}",
+                            operator_id,
+                            ctx.config.id
+                        ),
+                    ));
+                };
+                operator_metadata.insert(operator_id.clone(), op_metadata);
+            }
+
+            // check that every operator can restore its state before starting any of them, so
+            // that an incompatible update fails here rather than in a loop of task failures
+            let tables = arroyo_worker::engine::operator_tables(&ctx.program)
+                .await
+                .map_err(|err| fatal("Failed to construct the operators of the pipeline", err))?;
+
+            let mut dropped_operators = vec![];
+            for (operator_id, op_metadata) in &operator_metadata {
+                match tables.get(operator_id) {
+                    Some(current) => {
+                        check_operator_compatibility(
+                            operator_id,
+                            &op_metadata.table_configs,
+                            current,
+                        )
+                        .map_err(|err| {
+                            fatal(
+                                format!(
+                                    "Failed to restore job; checkpoint {} is incompatible with the pipeline",
+                                    epoch
+                                ),
+                                err,
+                            )
+                        })?;
+                    }
+                    None => dropped_operators.push(operator_id.clone()),
+                }
+            }
+
+            // operators whose subplans changed when the query was edited get new ids, so their
+            // state in the checkpoint can't be restored
+            if !dropped_operators.is_empty() {
+                warn!(
+                    message = "checkpoint contains state for operators that are no longer in the pipeline; it will not be restored",
+                    job_id = *ctx.config.id,
+                    operators = ?dropped_operators
+                );
+            }
+
             if needs_commits {
                 let mut commit_subtasks = HashSet::new();
                 let mut committing_data: HashMap<String, HashMap<String, HashMap<u32, Vec<u8>>>> =
                     HashMap::new();
-                for operator_id in &metadata.operator_ids {
-                    let operator_metadata =
-                        StateBackend::load_operator_metadata(&ctx.config.id, operator_id, epoch)
-                            .await
-                            .map_err(|err| {
+                for (operator_id, op_metadata) in &operator_metadata {
+                    for (table_name, table_metadata) in &op_metadata.table_checkpoint_metadata {
+                        let config =
+                            op_metadata.table_configs.get(table_name).ok_or_else(|| {
                                 fatal(
                                     format!(
-                                "Failed to restore job; operator metadata for {} not found.",
-                                operator_id
-                            ),
-                                    err,
+                                        "Failed to restore job; table config for {} not found.",
+                                        table_name
+                                    ),
+                                    anyhow!("table config for {} not found", table_name),
                                 )
                             })?;
-                    let Some(operator_metadata) = operator_metadata else {
-                        return Err(fatal(
-                            "missing operator metadata",
-                            anyhow!(
-                                "operator metadata for {} not found for job {}",
-                                operator_id,
-                                ctx.config.id
-                            ),
-                        ));
-                    };
-                    for (table_name, table_metadata) in &operator_metadata.table_checkpoint_metadata
-                    {
-                        let config =
-                            operator_metadata
-                                .table_configs
-                                .get(table_name)
-                                .ok_or_else(|| {
-                                    fatal(
-                                        format!(
-                                            "Failed to restore job; table config for {} not found.",
-                                            table_name
-                                        ),
-                                        anyhow!("table config for {} not found", table_name),
-                                    )
-                                })?;
                         if let Some(commit_data) = match config.table_type() {
                             arroyo_rpc::grpc::rpc::TableEnum::MissingTableType => {
                                 return Err(fatal(
@@ -413,18 +612,31 @@ impl State for Scheduling {
                             arroyo_rpc::grpc::rpc::TableEnum::GlobalKeyValue => {
                                 GlobalKeyedTable::committing_data(config.clone(), table_metadata)
                             }
-                            arroyo_rpc::grpc::rpc::TableEnum::ExpiringKeyedTimeTable => None,
+                            arroyo_rpc::grpc::rpc::TableEnum::ExpiringKeyedTimeTable
+                            | arroyo_rpc::grpc::rpc::TableEnum::RocksDbKeyValue => None,
                         } {
-                            committing_data
-                                .entry(operator_id.clone())
-                                .or_default()
-                                .insert(table_name.to_string(), commit_data);
-                            let program_node = ctx
+                            // the data was pre-committed to an external system by an operator
+                            // that's no longer in the pipeline, so nothing would commit it
+                            let Some(program_node) = ctx
                                 .program
                                 .graph
                                 .node_weights()
                                 .find(|node| node.operator_id == *operator_id)
-                                .unwrap();
+                            else {
+                                return Err(fatal(
+                                    format!(
+                                        "Failed to restore job; checkpoint {} has uncommitted data for operator {}, which is no longer in the pipeline",
+                                        epoch, operator_id
+                                    ),
+                                    anyhow!(
+                                        "restore the pipeline unchanged so that the commit can finish, then update it"
+                                    ),
+                                ));
+                            };
+                            committing_data
+                                .entry(operator_id.clone())
+                                .or_default()
+                                .insert(table_name.to_string(), commit_data);
                             for subtask_index in 0..program_node.parallelism {
                                 commit_subtasks.insert((operator_id.clone(), subtask_index as u32));
                             }
@@ -444,16 +656,45 @@ impl State for Scheduling {
         }
 
         let assignments = compute_assignments(workers.values().collect(), &*ctx.program);
-        let worker_connects = Arc::try_unwrap(worker_connects).unwrap().into_inner();
+        // workers may be running different releases during an upgrade, so the data plane
+        // uses the newest protocol they all support
+        let protocol_version = workers
+            .values()
+            .map(|w| w.protocol_version)
+            .min()
+            .unwrap_or(PROTOCOL_VERSION);
         let program = api::ArrowProgram::from(ctx.program.clone());
+
+        let restore_epoch = checkpoint_info.as_ref().map(|info| info.epoch);
+        let source_offset_overrides: Vec<_> = match &ctx.config.source_offset_overrides {
+            Some(overrides) if overrides.epoch == restore_epoch => {
+                info!(
+                    message = "overriding source offsets",
+                    job_id = *ctx.config.id,
+                    overrides = overrides.overrides.len()
+                );
+                overrides
+                    .overrides
+                    .iter()
+                    .map(|o| SourceOffsetOverride {
+                        operator_id: o.node_id.clone(),
+                        partition: o.partition.clone(),
+                        offset: o.offset.clone(),
+                    })
+                    .collect()
+            }
+            // the job has checkpointed since the overrides were set, so the offsets in the
+            // checkpoint already reflect them
+            _ => vec![],
+        };
         let tasks: Vec<_> = worker_connects
             .into_iter()
             .map(|(id, mut c)| {
                 let assignments = assignments.clone();
 
                 let job_id = ctx.config.id.clone();
-                let restore_epoch = checkpoint_info.as_ref().map(|info| info.epoch);
                 let program = program.clone();
+                let source_offset_overrides = source_offset_overrides.clone();
                 tokio::spawn(async move {
                     info!(
                         message = "starting execution on worker",
@@ -466,6 +707,8 @@ impl State for Scheduling {
                                 restore_epoch,
                                 program: Some(program.clone()),
                                 tasks: assignments.clone(),
+                                protocol_version,
+                                source_offset_overrides: source_offset_overrides.clone(),
                             }))
                             .await
                         {
@@ -522,7 +765,11 @@ impl State for Scheduling {
                         }) => {
                             started_tasks.insert((operator_id, operator_subtask));
                         }
-                        Some(JobMessage::RunningMessage(RunningMessage::TaskFailed {worker_id, operator_id, subtask_index, reason})) => {
+                        Some(JobMessage::RunningMessage(RunningMessage::TaskFailed {worker_id, operator_id, subtask_index, reason, fatal: is_fatal})) => {
+                            if is_fatal {
+                                return Err(fatal("Job failed with an error that restarting it won't fix",
+                                    anyhow!("task failed on job startup on {:?}: {}:{}: {}", worker_id, operator_id, subtask_index, reason)));
+                            }
                             return Err(ctx.retryable(self, "task failed on startup",
                                 anyhow!("task failed on job startup on {:?}: {}:{}: {}", worker_id, operator_id, subtask_index, reason), 10));
                         }
@@ -556,6 +803,11 @@ impl State for Scheduling {
             .await
             .insert(ctx.config.id.clone(), metrics.clone());
 
+        ctx.running_workers = Some(RunningWorkers {
+            workers,
+            connects: worker_connects.clone(),
+        });
+
         let mut controller = JobController::new(
             ctx.db.clone(),
             ctx.config.clone(),
@@ -568,7 +820,9 @@ impl State for Scheduling {
             worker_connects,
             committing_state,
             metrics,
+            protocol_version,
         );
+        controller.set_last_rebalanced_at(ctx.last_rebalanced_at);
         if needs_commit {
             info!("restored checkpoint was in committing phase, sending commits");
             controller
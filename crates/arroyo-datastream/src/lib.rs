@@ -52,10 +52,24 @@ pub struct WasmUDF {
 
 #[derive(Clone, Encode, Decode, Serialize, Deserialize, PartialEq, Eq, Hash, PartialOrd)]
 pub enum WindowType {
-    Tumbling { width: Duration },
-    Sliding { width: Duration, slide: Duration },
+    Tumbling {
+        width: Duration,
+    },
+    Sliding {
+        width: Duration,
+        slide: Duration,
+    },
     Instant,
-    Session { gap: Duration },
+    Session {
+        gap: Duration,
+    },
+    /// Tumbling windows with bins computed by an expression over `_timestamp` (e.g.,
+    /// `date_trunc('month', _timestamp)`), serialized as a `LogicalExprNode`. Bins may be
+    /// irregular, but may be no wider than `max_width`.
+    Custom {
+        max_width: Duration,
+        binning_function: Vec<u8>,
+    },
 }
 
 fn format_duration(duration: Duration) -> String {
@@ -96,6 +110,9 @@ impl Debug for WindowType {
             Self::Session { gap } => {
                 write!(f, "SessionWindow({})", format_duration(*gap))
             }
+            Self::Custom { max_width, .. } => {
+                write!(f, "CustomWindow(max: {})", format_duration(*max_width))
+            }
         }
     }
 }
@@ -229,6 +246,9 @@ pub fn default_sink() -> api::ConnectorOp {
             })
             .to_string(),
             description: "PreviewSink".to_string(),
+            timestamp_field: None,
+            start_at: None,
+            end_at: None,
         },
         DefaultSink::Stdout => api::ConnectorOp {
             connector: "stdout".to_string(),
@@ -241,6 +261,9 @@ pub fn default_sink() -> api::ConnectorOp {
             })
             .to_string(),
             description: "StdoutSink".to_string(),
+            timestamp_field: None,
+            start_at: None,
+            end_at: None,
         },
     }
 }
@@ -2,13 +2,17 @@ use datafusion_proto::protobuf::ArrowType;
 
 use anyhow::anyhow;
 use arrow_schema::DataType;
-use arroyo_rpc::api_types::pipelines::{PipelineEdge, PipelineGraph, PipelineNode};
+use arroyo_rpc::api_types::pipelines::{PipelineEdge, PipelineGraph, PipelineNode, SinkLineage};
 use arroyo_rpc::df::ArroyoSchema;
 use arroyo_rpc::grpc::api;
-use arroyo_rpc::grpc::api::{ArrowProgram, ArrowProgramConfig, ConnectorOp, EdgeType};
+use arroyo_rpc::grpc::api::{
+    ArrowProgram, ArrowProgramConfig, Autoscaling, CheckpointStorage, ConnectorOp, EdgeType,
+    StateStorage,
+};
 use petgraph::dot::Dot;
-use petgraph::graph::DiGraph;
+use petgraph::graph::{DiGraph, NodeIndex};
 use petgraph::prelude::EdgeRef;
+use petgraph::visit::{Dfs, Reversed};
 use petgraph::Direction;
 use prost::Message;
 use rand::distributions::Alphanumeric;
@@ -19,6 +23,7 @@ use std::collections::{HashMap, HashSet};
 use std::fmt::{Debug, Display, Formatter};
 use std::hash::Hasher;
 use std::sync::Arc;
+use std::time::Duration;
 use strum::{Display, EnumString};
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq, EnumString, Display)]
@@ -27,12 +32,15 @@ pub enum OperatorName {
     ArrowValue,
     ArrowKey,
     AsyncUdf,
+    Delay,
+    Dedupe,
     Join,
     InstantJoin,
     WindowFunction,
     TumblingWindowAggregate,
     SlidingWindowAggregate,
     SessionWindowAggregate,
+    SessionEvents,
     UpdatingAggregate,
     ConnectorSource,
     ConnectorSink,
@@ -120,6 +128,7 @@ impl TryFrom<LogicalProgram> for PipelineGraph {
         Ok(Self {
             nodes: nodes?,
             edges,
+            lineage: value.program_config.lineage,
         })
     }
 }
@@ -197,6 +206,27 @@ pub struct PythonUdfConfig {
 pub struct ProgramConfig {
     pub udf_dylibs: HashMap<String, DylibUdfConfig>,
     pub python_udfs: HashMap<String, PythonUdfConfig>,
+    /// overrides for the pipeline configuration, set by `SET` statements in the query
+    pub parallelism: Option<usize>,
+    pub checkpoint_interval: Option<Duration>,
+    /// overrides for the worker's queue limits, set by `SET` statements in the query
+    pub queue_size: Option<u32>,
+    pub queue_max_bytes: Option<u64>,
+    /// column-level lineage for each sink, computed by the planner
+    pub lineage: Vec<SinkLineage>,
+    /// where each stateful operator keeps its state, by operator id; operators that aren't
+    /// listed keep it in memory
+    pub state_storage: HashMap<String, StateStorage>,
+    /// where the pipeline's checkpoints are written, if not to the cluster's checkpoint URL
+    pub checkpoint_storage: Option<CheckpointStorage>,
+    /// how long operators wait for checkpoint barriers to align before failing, which restores
+    /// the pipeline from its last complete checkpoint; unbounded if unset
+    pub checkpoint_alignment_timeout: Option<Duration>,
+    /// the bounds within which the controller scales the pipeline's parallelism with its load;
+    /// the pipeline isn't autoscaled if unset
+    pub autoscaling: Option<Autoscaling>,
+    /// the most memory the state of each subtask may use; unbounded if unset
+    pub max_state_bytes: Option<u64>,
 }
 
 #[derive(Clone, Debug, Default)]
@@ -245,6 +275,68 @@ impl LogicalProgram {
             .collect()
     }
 
+    /// Returns the operators in the region of the given operator: those connected to it through
+    /// edges in either direction. Data only flows between the operators of a region, so a region
+    /// can be restarted from a checkpoint while the rest of the program keeps running.
+    pub fn region(&self, operator_id: &str) -> HashSet<String> {
+        let mut region = HashSet::new();
+        let Some(idx) = self.operator_index(operator_id) else {
+            return region;
+        };
+
+        let mut stack = vec![NodeIndex::new(idx as usize)];
+        while let Some(idx) = stack.pop() {
+            if region.insert(self.graph[idx].operator_id.clone()) {
+                stack.extend(self.graph.neighbors_undirected(idx));
+            }
+        }
+
+        region
+    }
+
+    /// Returns a copy of this program containing only the given operator and the operators
+    /// upstream of it, with the operator's output written to `sink` instead of its original
+    /// consumers. This is used to preview intermediate stages of a pipeline.
+    pub fn truncate_to(&self, operator_id: &str, sink: LogicalNode) -> anyhow::Result<Self> {
+        let idx = self
+            .graph
+            .node_indices()
+            .find(|idx| self.graph[*idx].operator_id == operator_id)
+            .ok_or_else(|| anyhow!("no operator with id '{}'", operator_id))?;
+
+        let output = self
+            .graph
+            .edges_directed(idx, Direction::Outgoing)
+            .next()
+            .ok_or_else(|| anyhow!("operator '{}' does not produce any output", operator_id))?
+            .weight()
+            .clone();
+
+        let mut upstream = HashSet::new();
+        let mut dfs = Dfs::new(Reversed(&self.graph), idx);
+        while let Some(n) = dfs.next(Reversed(&self.graph)) {
+            upstream.insert(n);
+        }
+
+        let mut graph = self.graph.filter_map(
+            |i, node| upstream.contains(&i).then(|| node.clone()),
+            |_, edge| Some(edge.clone()),
+        );
+
+        let idx = graph
+            .node_indices()
+            .find(|idx| graph[*idx].operator_id == operator_id)
+            .unwrap();
+        let sink_idx = graph.add_node(sink);
+        graph.add_edge(
+            idx,
+            sink_idx,
+            LogicalEdge::new(LogicalEdgeType::Forward, output.schema, output.projection),
+        );
+
+        Ok(Self::new(graph, self.program_config.clone()))
+    }
+
     pub fn get_hash(&self) -> String {
         let mut hasher = DefaultHasher::new();
         let bs = api::ArrowProgram::from(self.clone()).encode_to_vec();
@@ -273,12 +365,34 @@ impl LogicalProgram {
         tasks_per_operator
     }
 
+    /// Returns the operators that feed into the given operator along with the edges they feed
+    /// it through, ordered by operator id
+    pub fn inputs(&self, operator_id: &str) -> Vec<(&LogicalNode, &LogicalEdge)> {
+        let Some(idx) = self
+            .graph
+            .node_indices()
+            .find(|idx| self.graph[*idx].operator_id == operator_id)
+        else {
+            return vec![];
+        };
+
+        let mut inputs: Vec<_> = self
+            .graph
+            .edges_directed(idx, Direction::Incoming)
+            .map(|e| (&self.graph[e.source()], e.weight()))
+            .collect();
+        inputs.sort_by(|(a, _), (b, _)| a.operator_id.cmp(&b.operator_id));
+        inputs
+    }
+
     pub fn features(&self) -> HashSet<String> {
         let mut s = HashSet::new();
 
         for t in self.graph.node_weights() {
             let feature = match &t.operator_name {
                 OperatorName::AsyncUdf => "async-udf".to_string(),
+                OperatorName::Delay => "sql-delay".to_string(),
+                OperatorName::Dedupe => "source-dedupe".to_string(),
                 OperatorName::ExpressionWatermark
                 | OperatorName::ArrowValue
                 | OperatorName::ArrowKey => continue,
@@ -290,6 +404,7 @@ impl LogicalProgram {
                 }
                 OperatorName::SlidingWindowAggregate => "sql-sliding-window-aggregate".to_string(),
                 OperatorName::SessionWindowAggregate => "sql-session-window-aggregate".to_string(),
+                OperatorName::SessionEvents => "sql-session-events".to_string(),
                 OperatorName::UpdatingAggregate => "sql-updating-aggregate".to_string(),
                 OperatorName::ConnectorSource => {
                     let Ok(connector_op) = ConnectorOp::decode(&t.operator_config[..]) else {
@@ -357,6 +472,16 @@ impl TryFrom<ArrowProgram> for LogicalProgram {
             .unwrap_or_else(|| ArrowProgramConfig {
                 udf_dylibs: HashMap::new(),
                 python_udfs: HashMap::new(),
+                parallelism: None,
+                checkpoint_interval_micros: None,
+                queue_size: None,
+                queue_max_bytes: None,
+                lineage: vec![],
+                state_storage: HashMap::new(),
+                checkpoint_storage: None,
+                checkpoint_alignment_timeout_micros: None,
+                autoscaling: None,
+                max_state_bytes: None,
             })
             .into();
 
@@ -467,6 +592,22 @@ impl From<ProgramConfig> for ArrowProgramConfig {
                 .into_iter()
                 .map(|(k, v)| (k, v.into()))
                 .collect(),
+            parallelism: from.parallelism.map(|p| p as u64),
+            checkpoint_interval_micros: from.checkpoint_interval.map(|d| d.as_micros() as u64),
+            queue_size: from.queue_size,
+            queue_max_bytes: from.queue_max_bytes,
+            lineage: from.lineage.into_iter().map(|l| l.into()).collect(),
+            state_storage: from
+                .state_storage
+                .into_iter()
+                .map(|(k, v)| (k, v as i32))
+                .collect(),
+            checkpoint_storage: from.checkpoint_storage,
+            checkpoint_alignment_timeout_micros: from
+                .checkpoint_alignment_timeout
+                .map(|d| d.as_micros() as u64),
+            autoscaling: from.autoscaling,
+            max_state_bytes: from.max_state_bytes,
         }
     }
 }
@@ -484,6 +625,22 @@ impl From<ArrowProgramConfig> for ProgramConfig {
                 .into_iter()
                 .map(|(k, v)| (k, v.into()))
                 .collect(),
+            parallelism: from.parallelism.map(|p| p as usize),
+            checkpoint_interval: from.checkpoint_interval_micros.map(Duration::from_micros),
+            queue_size: from.queue_size,
+            queue_max_bytes: from.queue_max_bytes,
+            lineage: from.lineage.into_iter().map(|l| l.into()).collect(),
+            state_storage: from
+                .state_storage
+                .into_iter()
+                .map(|(k, v)| (k, StateStorage::try_from(v).unwrap_or(StateStorage::Memory)))
+                .collect(),
+            checkpoint_storage: from.checkpoint_storage,
+            checkpoint_alignment_timeout: from
+                .checkpoint_alignment_timeout_micros
+                .map(Duration::from_micros),
+            autoscaling: from.autoscaling,
+            max_state_bytes: from.max_state_bytes,
         }
     }
 }
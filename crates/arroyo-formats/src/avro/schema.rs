@@ -102,7 +102,21 @@ fn arrow_to_avro(name: &str, dt: &DataType) -> serde_json::value::Value {
         DataType::Dictionary(_, _) => unimplemented!("dictionaries are not supported"),
         DataType::Decimal128(_, _) => unimplemented!("decimal128 is not supported"),
         DataType::Decimal256(_, _) => unimplemented!("decimal256 is not supported"),
-        DataType::Map(_, _) => unimplemented!("maps are not supported"),
+        DataType::Map(entries, _) => {
+            let DataType::Struct(kv) = entries.data_type() else {
+                unreachable!("map entries must be a struct");
+            };
+            let value = &kv[1];
+            let mut values = arrow_to_avro(&format!("{}_values", name), value.data_type());
+            if value.is_nullable() {
+                values = json!(["null", values]);
+            }
+
+            return json!({
+                "type": "map",
+                "values": values,
+            });
+        }
         DataType::RunEndEncoded(_, _) => unimplemented!("run end encoded is not supported"),
         DataType::BinaryView => unimplemented!("binary view is not supported"),
         DataType::Utf8View => unimplemented!("utf8 view is not suported"),
@@ -167,6 +181,22 @@ fn to_arrow_datatype(schema: &Schema) -> (DataType, bool, Option<ArroyoExtension
 
             (DataType::Struct(fields), false, None)
         }
+        Schema::Map(values) => {
+            let (dt, nullable, extension) = to_arrow_datatype(values);
+            let entries = Field::new(
+                "entries",
+                DataType::Struct(Fields::from(vec![
+                    Field::new("keys", DataType::Utf8, false),
+                    ArroyoExtensionType::add_metadata(
+                        extension,
+                        Field::new("values", dt, nullable),
+                    ),
+                ])),
+                false,
+            );
+
+            (DataType::Map(Arc::new(entries), false), false, None)
+        }
         _ => (DataType::Utf8, false, Some(ArroyoExtensionType::JSON)),
     }
 }
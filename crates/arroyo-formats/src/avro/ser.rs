@@ -9,6 +9,8 @@ use arrow_array::{Array, ArrayRef, RecordBatch};
 use arrow_schema::{DataType, TimeUnit};
 use arroyo_rpc::formats::AvroFormat;
 use arroyo_types::{from_nanos, to_micros};
+use std::collections::HashMap;
+use std::sync::Arc;
 
 trait SerializeTarget {
     fn add(&mut self, i: usize, name: &str, value: Value);
@@ -199,6 +201,62 @@ fn serialize_column<T: SerializeTarget>(
             }
         }
 
+        DataType::Map(entries, _) => {
+            let schema = get_field_schema(schema, name, nullable);
+            let Schema::Map(value_schema) = schema else {
+                panic!(
+                    "invalid avro schema -- map field {} should correspond to map schema but is {:?}",
+                    name, schema
+                );
+            };
+            let DataType::Struct(kv) = entries.data_type() else {
+                panic!("invalid map column {} -- entries must be a struct", name);
+            };
+            let value_nullable = kv[1].is_nullable();
+
+            let map = column.as_map();
+            for i in 0..map.len() {
+                if !values.is_some(i) {
+                    continue;
+                }
+
+                let v = map.is_valid(i).then(|| {
+                    let entries = map.value(i);
+                    let keys = entries.column(0).as_string::<i32>();
+                    let mut map_values = vec![];
+                    serialize_column(
+                        value_schema,
+                        &mut map_values,
+                        "",
+                        entries.column(1),
+                        value_nullable,
+                    );
+
+                    keys.iter()
+                        .map(|k| k.expect("map keys cannot be null").to_string())
+                        .zip(map_values)
+                        .collect::<HashMap<_, _>>()
+                });
+
+                if nullable {
+                    values.add(
+                        i,
+                        name,
+                        Value::Union(
+                            v.is_some() as u32,
+                            Box::new(v.map(Value::Map).unwrap_or(Value::Null)),
+                        ),
+                    );
+                } else {
+                    values.add(
+                        i,
+                        name,
+                        Value::Map(v.expect("null found in non-nullable map column")),
+                    );
+                }
+            }
+        }
+
         DataType::Struct(fields) => {
             let schema = get_field_schema(schema, name, nullable);
             if nullable {
@@ -282,15 +340,133 @@ pub fn serialize(schema: &Schema, batch: &RecordBatch) -> Vec<Value> {
     values.into_iter().flatten().map(|r| r.into()).collect()
 }
 
+/// Writes a long in Avro's binary encoding: zig-zag encoded, as a variable-length integer
+fn write_long(buf: &mut Vec<u8>, n: i64) {
+    let mut n = ((n << 1) ^ (n >> 63)) as u64;
+    while n >= 0x80 {
+        buf.push((n as u8 & 0x7f) | 0x80);
+        n >>= 7;
+    }
+    buf.push(n as u8);
+}
+
+fn write_bytes(buf: &mut Vec<u8>, bytes: &[u8]) {
+    write_long(buf, bytes.len() as i64);
+    buf.extend_from_slice(bytes);
+}
+
+/// Writes an Avro object container file as a header, containing the schema, followed by blocks
+/// of records. Each block ends with the file's sync marker, so the file is complete after any
+/// block and can be written out as it grows.
+pub struct ContainerFileWriter {
+    schema: Arc<Schema>,
+    marker: [u8; 16],
+}
+
+impl ContainerFileWriter {
+    pub fn new(schema: Arc<Schema>) -> Self {
+        Self {
+            schema,
+            marker: *uuid::Uuid::new_v4().as_bytes(),
+        }
+    }
+
+    pub fn header(&self) -> Vec<u8> {
+        let mut buf = b"Obj\x01".to_vec();
+
+        // the file metadata is a map of strings to bytes, written as a single block
+        let schema = serde_json::to_string(self.schema.as_ref()).expect("invalid avro schema");
+        write_long(&mut buf, 2);
+        write_bytes(&mut buf, b"avro.schema");
+        write_bytes(&mut buf, schema.as_bytes());
+        write_bytes(&mut buf, b"avro.codec");
+        write_bytes(&mut buf, b"null");
+        write_long(&mut buf, 0);
+
+        buf.extend_from_slice(&self.marker);
+        buf
+    }
+
+    /// Encodes the rows of `batch` (which must match the schema) as a block, or returns nothing
+    /// if it's empty
+    pub fn block(&self, batch: &RecordBatch) -> Vec<u8> {
+        if batch.num_rows() == 0 {
+            return vec![];
+        }
+
+        let mut data = vec![];
+        for value in serialize(&self.schema, batch) {
+            data.extend(
+                apache_avro::to_avro_datum(&self.schema, value).expect("avro serialization failed"),
+            );
+        }
+
+        let mut buf = Vec::with_capacity(data.len() + 36);
+        write_long(&mut buf, batch.num_rows() as i64);
+        write_bytes(&mut buf, &data);
+        buf.extend_from_slice(&self.marker);
+        buf
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::avro::schema::to_avro;
-    use crate::avro::ser::serialize;
-    use arrow_array::builder::{Int64Builder, ListBuilder, StringBuilder, StructBuilder};
-    use arrow_array::RecordBatch;
+    use crate::avro::ser::{serialize, ContainerFileWriter};
+    use arrow_array::builder::{
+        Int64Builder, ListBuilder, MapBuilder, StringBuilder, StructBuilder,
+    };
+    use arrow_array::{Array, RecordBatch};
     use arrow_schema::{DataType, Field, Schema};
+    use std::collections::HashMap;
     use std::sync::Arc;
 
+    #[test]
+    fn test_writing_maps() {
+        use apache_avro::types::Value::*;
+
+        let mut counts = MapBuilder::new(None, StringBuilder::new(), Int64Builder::new());
+        counts.keys().append_value("a");
+        counts.values().append_value(1);
+        counts.keys().append_value("b");
+        counts.values().append_null();
+        counts.append(true).unwrap();
+        counts.append(true).unwrap();
+        counts.append(false).unwrap();
+        let counts = counts.finish();
+
+        let arrow_schema = Arc::new(Schema::new(vec![Field::new(
+            "counts",
+            counts.data_type().clone(),
+            true,
+        )]));
+        let avro_schema = to_avro("Counts", &arrow_schema.fields);
+        let batch = RecordBatch::try_new(arrow_schema, vec![Arc::new(counts)]).unwrap();
+
+        let result: Vec<apache_avro::types::Value> = serialize(&avro_schema, &batch);
+
+        assert_eq!(
+            result,
+            vec![
+                Record(vec![(
+                    "counts".to_string(),
+                    Union(
+                        1,
+                        Box::new(Map(HashMap::from([
+                            ("a".to_string(), Union(1, Box::new(Long(1)))),
+                            ("b".to_string(), Union(0, Box::new(Null))),
+                        ])))
+                    )
+                )]),
+                Record(vec![(
+                    "counts".to_string(),
+                    Union(1, Box::new(Map(HashMap::new())))
+                )]),
+                Record(vec![("counts".to_string(), Union(0, Box::new(Null)))]),
+            ]
+        );
+    }
+
     #[test]
     fn test_writing() {
         use apache_avro::types::Value::*;
@@ -518,4 +694,53 @@ mod tests {
             ]
         )
     }
+
+    #[test]
+    fn test_container_file() {
+        use apache_avro::types::Value::*;
+
+        let arrow_schema = Arc::new(Schema::new(vec![
+            Field::new("name", DataType::Utf8, false),
+            Field::new("count", DataType::Int64, true),
+        ]));
+        let batch = |names: Vec<&str>, counts: Vec<Option<i64>>| {
+            RecordBatch::try_new(
+                arrow_schema.clone(),
+                vec![
+                    Arc::new(arrow_array::StringArray::from(names)),
+                    Arc::new(arrow_array::Int64Array::from(counts)),
+                ],
+            )
+            .unwrap()
+        };
+
+        let writer = ContainerFileWriter::new(Arc::new(to_avro("Row", &arrow_schema.fields)));
+        let mut file = writer.header();
+        file.extend(writer.block(&batch(vec!["a", "b"], vec![Some(1), None])));
+        file.extend(writer.block(&batch(vec![], vec![])));
+        file.extend(writer.block(&batch(vec!["c"], vec![Some(3)])));
+
+        let values: Vec<_> = apache_avro::Reader::new(file.as_slice())
+            .unwrap()
+            .map(|v| v.unwrap())
+            .collect();
+
+        assert_eq!(
+            values,
+            vec![
+                Record(vec![
+                    ("name".to_string(), String("a".to_string())),
+                    ("count".to_string(), Union(1, Box::new(Long(1)))),
+                ]),
+                Record(vec![
+                    ("name".to_string(), String("b".to_string())),
+                    ("count".to_string(), Union(0, Box::new(Null))),
+                ]),
+                Record(vec![
+                    ("name".to_string(), String("c".to_string())),
+                    ("count".to_string(), Union(1, Box::new(Long(3)))),
+                ]),
+            ]
+        );
+    }
 }
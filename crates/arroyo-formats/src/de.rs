@@ -1,22 +1,37 @@
 use crate::avro::de;
 use crate::proto::schema::get_pool;
+use crate::xml::XmlReader;
 use crate::{proto, should_flush};
-use arrow::array::{Int32Builder, Int64Builder};
-use arrow::compute::kernels;
+use arrow::array::{
+    new_null_array, ArrayRef, BooleanArray, BooleanBuilder, Int32Array, Int32Builder, Int64Array,
+    Int64Builder, StringArray,
+};
+use arrow::compute::{
+    cast, cast_with_options, concat_batches, filter_record_batch, is_not_null, kernels, CastOptions,
+};
+use arrow::ipc::reader::{FileReader, StreamReader};
 use arrow_array::builder::{
     ArrayBuilder, GenericByteBuilder, StringBuilder, TimestampNanosecondBuilder,
 };
-use arrow_array::types::GenericBinaryType;
-use arrow_array::RecordBatch;
+use arrow_array::cast::AsArray;
+use arrow_array::types::{Float64Type, GenericBinaryType, Int64Type, TimestampNanosecondType};
+use arrow_array::{Array, RecordBatch, RecordBatchOptions, TimestampNanosecondArray};
+use arrow_schema::{DataType, FieldRef, Schema, TimeUnit};
 use arroyo_rpc::df::ArroyoSchema;
 use arroyo_rpc::formats::{
-    AvroFormat, BadData, Format, Framing, FramingMethod, JsonFormat, ProtobufFormat,
+    AvroFormat, BadData, CsvFormat, Format, Framing, FramingMethod, JsonFormat, PayloadCompression,
+    ProtobufFormat, TimestampField,
 };
 use arroyo_rpc::schema_resolver::{FailingSchemaResolver, FixedSchemaResolver, SchemaResolver};
 use arroyo_types::{to_nanos, SourceError};
+use bytes::Bytes;
+use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime};
+use flate2::read::MultiGzDecoder;
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
 use prost_reflect::DescriptorPool;
 use serde_json::Value;
 use std::collections::HashMap;
+use std::io::{Cursor, Read};
 use std::sync::Arc;
 use std::time::{Instant, SystemTime};
 use tokio::sync::Mutex;
@@ -25,7 +40,9 @@ use tokio::sync::Mutex;
 pub enum FieldValueType<'a> {
     Int64(i64),
     Int32(i32),
+    Boolean(bool),
     String(&'a str),
+    OptionalString(Option<&'a str>),
     // Extend with more types as needed
 }
 
@@ -53,24 +70,19 @@ impl<'a> Iterator for FramingIterator<'a> {
             return None;
         }
 
-        match &self.framing {
-            Some(framing) => {
-                match &framing.method {
-                    FramingMethod::Newline(newline) => {
-                        let end = memchr::memchr(b'\n', &self.buf[self.offset..])
-                            .map(|i| self.offset + i)
-                            .unwrap_or(self.buf.len());
+        match self.framing.as_ref().and_then(|f| f.method.as_ref()) {
+            Some(FramingMethod::Newline(newline)) => {
+                let end = memchr::memchr(b'\n', &self.buf[self.offset..])
+                    .map(|i| self.offset + i)
+                    .unwrap_or(self.buf.len());
 
-                        let prev = self.offset;
-                        self.offset = end + 1;
+                let prev = self.offset;
+                self.offset = end + 1;
 
-                        // enforce max len if set
-                        let length =
-                            (end - prev).min(newline.max_line_length.unwrap_or(u64::MAX) as usize);
+                // enforce max len if set
+                let length = (end - prev).min(newline.max_line_length.unwrap_or(u64::MAX) as usize);
 
-                        Some(&self.buf[prev..(prev + length)])
-                    }
-                }
+                Some(&self.buf[prev..(prev + length)])
             }
             None => {
                 self.offset = self.buf.len();
@@ -92,6 +104,12 @@ pub struct ArrowDeserializer {
     proto_pool: DescriptorPool,
     schema_resolver: Arc<dyn SchemaResolver + Sync>,
     additional_fields_builder: Option<HashMap<String, Box<dyn ArrayBuilder>>>,
+    /// batches decoded from Arrow IPC, Parquet, CSV and XML messages, which are decoded a message
+    /// at a time
+    batches: Vec<RecordBatch>,
+    xml_reader: Option<XmlReader>,
+    /// the field of the data to take the event time from, instead of the time it was read
+    timestamp_field: Option<TimestampField>,
 }
 
 impl ArrowDeserializer {
@@ -132,6 +150,12 @@ impl ArrowDeserializer {
             DescriptorPool::global()
         };
 
+        let xml_reader = if let Format::Xml(xml) = &format {
+            Some(XmlReader::new(xml).expect("unable to handle XML paths"))
+        } else {
+            None
+        };
+
         Self {
             json_decoder: matches!(
                 format,
@@ -169,9 +193,17 @@ impl ArrowDeserializer {
             buffered_count: 0,
             buffered_since: Instant::now(),
             additional_fields_builder: None,
+            batches: vec![],
+            xml_reader,
+            timestamp_field: None,
         }
     }
 
+    pub fn with_timestamp_field(mut self, timestamp_field: Option<TimestampField>) -> Self {
+        self.timestamp_field = timestamp_field;
+        self
+    }
+
     pub async fn deserialize_slice(
         &mut self,
         buffer: &mut [Box<dyn ArrayBuilder>],
@@ -179,8 +211,30 @@ impl ArrowDeserializer {
         timestamp: SystemTime,
         additional_fields: Option<&HashMap<&String, FieldValueType<'_>>>,
     ) -> Vec<SourceError> {
+        let decompressed;
+        let msg = match self.framing.as_ref().and_then(|f| f.compression) {
+            Some(compression) => match decompress(compression, msg) {
+                Ok(data) => {
+                    decompressed = data;
+                    &decompressed[..]
+                }
+                Err(e) => return vec![e],
+            },
+            None => msg,
+        };
+
         match &*self.format {
             Format::Avro(_) => self.deserialize_slice_avro(buffer, msg, timestamp).await,
+            Format::ArrowIpc(_) => self
+                .deserialize_slice_arrow_ipc(msg, timestamp, additional_fields)
+                .err()
+                .into_iter()
+                .collect(),
+            Format::Parquet(_) => self
+                .deserialize_slice_parquet(msg, timestamp, additional_fields)
+                .err()
+                .into_iter()
+                .collect(),
             _ => FramingIterator::new(self.framing.clone(), msg)
                 .map(|t| self.deserialize_single(buffer, t, timestamp, additional_fields))
                 .filter_map(|t| t.err())
@@ -193,6 +247,71 @@ impl ArrowDeserializer {
     }
 
     pub fn flush_buffer(&mut self) -> Option<Result<RecordBatch, SourceError>> {
+        Some(
+            self.flush_decoded()?
+                .and_then(|batch| self.extract_timestamp(batch)),
+        )
+    }
+
+    /// Replaces the timestamps of the rows of a deserialized batch with the values of the
+    /// timestamp field, if one is configured. Rows where the field is null or can't be parsed
+    /// as a timestamp are bad data.
+    pub fn extract_timestamp(&self, mut batch: RecordBatch) -> Result<RecordBatch, SourceError> {
+        let Some(timestamp_field) = &self.timestamp_field else {
+            return Ok(batch);
+        };
+
+        let column = batch
+            .column_by_name(&timestamp_field.field)
+            .ok_or_else(|| {
+                SourceError::other(
+                    "Missing timestamp field",
+                    format!(
+                        "timestamp field '{}' is not a column of the table",
+                        timestamp_field.field
+                    ),
+                )
+            })?;
+
+        let mut timestamps = event_timestamps(timestamp_field, column)?;
+
+        if timestamps.null_count() > 0 {
+            match self.bad_data {
+                BadData::Fail { .. } => {
+                    return Err(SourceError::bad_data(format!(
+                        "timestamp field '{}' is null or not a valid timestamp in {} of {} rows",
+                        timestamp_field.field,
+                        timestamps.null_count(),
+                        batch.num_rows()
+                    )));
+                }
+                BadData::Drop { .. } => {
+                    let valid = is_not_null(&timestamps).unwrap();
+                    batch = filter_record_batch(&batch, &valid).unwrap();
+                    timestamps = kernels::filter::filter(&timestamps, &valid)
+                        .unwrap()
+                        .as_primitive::<TimestampNanosecondType>()
+                        .clone();
+                }
+            }
+        }
+
+        let mut columns = batch.columns().to_vec();
+        columns[self.schema.timestamp_index] = Arc::new(timestamps);
+        RecordBatch::try_new(batch.schema(), columns)
+            .map_err(|e| SourceError::other("Invalid timestamps", e.to_string()))
+    }
+
+    fn flush_decoded(&mut self) -> Option<Result<RecordBatch, SourceError>> {
+        if !self.batches.is_empty() {
+            self.buffered_since = Instant::now();
+            self.buffered_count = 0;
+            let batches = std::mem::take(&mut self.batches);
+            return Some(concat_batches(&self.schema.schema, &batches).map_err(|e| {
+                SourceError::other("failed to combine Arrow batches", e.to_string())
+            }));
+        }
+
         let (decoder, timestamp) = self.json_decoder.as_mut()?;
         self.buffered_since = Instant::now();
         self.buffered_count = 0;
@@ -289,7 +408,10 @@ impl ArrowDeserializer {
                             let builder: Box<dyn ArrayBuilder> = match value {
                                 FieldValueType::Int32(_) => Box::new(Int32Builder::new()),
                                 FieldValueType::Int64(_) => Box::new(Int64Builder::new()),
-                                FieldValueType::String(_) => Box::new(StringBuilder::new()),
+                                FieldValueType::Boolean(_) => Box::new(BooleanBuilder::new()),
+                                FieldValueType::String(_) | FieldValueType::OptionalString(_) => {
+                                    Box::new(StringBuilder::new())
+                                }
                             };
                             builders.insert(key, builder);
                         }
@@ -337,7 +459,16 @@ impl ArrowDeserializer {
                 }
             }
             Format::Avro(_) => unreachable!("this should not be called for avro"),
-            Format::Parquet(_) => todo!("parquet is not supported as an input format"),
+            Format::ArrowIpc(_) => unreachable!("this should not be called for arrow ipc"),
+            Format::Csv(csv) => {
+                let batches = self.csv_to_batches(csv, msg, timestamp, additional_fields)?;
+                self.buffer_batches(batches);
+            }
+            Format::Parquet(_) => unreachable!("this should not be called for parquet"),
+            Format::Xml(_) => {
+                let batch = self.xml_to_batch(msg, timestamp, additional_fields)?;
+                self.buffer_batches(vec![batch]);
+            }
         }
 
         Ok(())
@@ -421,6 +552,236 @@ impl ArrowDeserializer {
             .collect()
     }
 
+    /// Decodes a message containing an Arrow IPC stream or file, mapping the columns of its
+    /// batches onto the table schema by name
+    fn deserialize_slice_arrow_ipc(
+        &mut self,
+        msg: &[u8],
+        timestamp: SystemTime,
+        additional_fields: Option<&HashMap<&String, FieldValueType<'_>>>,
+    ) -> Result<(), SourceError> {
+        let batches: Box<dyn Iterator<Item = Result<RecordBatch, arrow::error::ArrowError>>> =
+            if msg.starts_with(ARROW_FILE_MAGIC) {
+                Box::new(
+                    FileReader::try_new(Cursor::new(msg), None).map_err(|e| {
+                        SourceError::bad_data(format!("invalid Arrow IPC file: {}", e))
+                    })?,
+                )
+            } else {
+                Box::new(StreamReader::try_new(Cursor::new(msg), None).map_err(|e| {
+                    SourceError::bad_data(format!("invalid Arrow IPC stream: {}", e))
+                })?)
+            };
+
+        let mut decoded = vec![];
+        for batch in batches {
+            let batch = batch
+                .map_err(|e| SourceError::bad_data(format!("invalid Arrow IPC batch: {}", e)))?;
+            decoded.push(self.batch_to_schema(&batch, timestamp, additional_fields)?);
+        }
+
+        self.buffer_batches(decoded);
+        Ok(())
+    }
+
+    /// Decodes a message containing a whole Parquet file, mapping the columns of its row groups
+    /// onto the table schema by name
+    fn deserialize_slice_parquet(
+        &mut self,
+        msg: &[u8],
+        timestamp: SystemTime,
+        additional_fields: Option<&HashMap<&String, FieldValueType<'_>>>,
+    ) -> Result<(), SourceError> {
+        let reader = ParquetRecordBatchReaderBuilder::try_new(Bytes::copy_from_slice(msg))
+            .and_then(|builder| builder.build())
+            .map_err(|e| SourceError::bad_data(format!("invalid Parquet file: {}", e)))?;
+
+        let mut decoded = vec![];
+        for batch in reader {
+            let batch =
+                batch.map_err(|e| SourceError::bad_data(format!("invalid Parquet data: {}", e)))?;
+            decoded.push(self.batch_to_schema(&batch, timestamp, additional_fields)?);
+        }
+
+        self.buffer_batches(decoded);
+        Ok(())
+    }
+
+    /// Buffers the batches decoded from a message; this is only done once the whole message has
+    /// been decoded, so that a bad message is dropped entirely
+    fn buffer_batches(&mut self, batches: Vec<RecordBatch>) {
+        for batch in batches {
+            self.buffered_count += batch.num_rows();
+            self.batches.push(batch);
+        }
+    }
+
+    /// Parses the rows of a CSV message as the table's columns, other than the timestamp and
+    /// those filled from the additional fields. A header row at the start of the message is
+    /// skipped if the format has one.
+    fn csv_to_batches(
+        &self,
+        csv: &CsvFormat,
+        msg: &[u8],
+        timestamp: SystemTime,
+        additional_fields: Option<&HashMap<&String, FieldValueType<'_>>>,
+    ) -> Result<Vec<RecordBatch>, SourceError> {
+        let fields = self.decoded_fields(additional_fields);
+
+        let mut msg = msg;
+        if csv.include_header {
+            let end = memchr::memchr(b'\n', msg).unwrap_or(msg.len());
+            if is_csv_header(csv, &fields, &msg[..end]) {
+                msg = &msg[(end + 1).min(msg.len())..];
+            }
+        }
+
+        let reader = arrow::csv::ReaderBuilder::new(Arc::new(Schema::new(fields)))
+            .with_header(false)
+            .with_delimiter(csv.delimiter())
+            .with_quote(csv.quote())
+            .build_buffered(Cursor::new(msg))
+            .map_err(|e| SourceError::bad_data(format!("invalid CSV: {}", e)))?;
+
+        reader
+            .map(|batch| {
+                let batch = batch.map_err(|e| {
+                    SourceError::bad_data(format!("CSV does not match schema: {}", e))
+                })?;
+                self.batch_to_schema(&batch, timestamp, additional_fields)
+            })
+            .collect()
+    }
+
+    /// Reads the records of an XML document as the table's columns, other than the timestamp and
+    /// those filled from the additional fields
+    fn xml_to_batch(
+        &self,
+        msg: &[u8],
+        timestamp: SystemTime,
+        additional_fields: Option<&HashMap<&String, FieldValueType<'_>>>,
+    ) -> Result<RecordBatch, SourceError> {
+        let fields = self.decoded_fields(additional_fields);
+        let names: Vec<&str> = fields.iter().map(|f| f.name().as_str()).collect();
+
+        let (rows, values) = self
+            .xml_reader
+            .as_ref()
+            .expect("xml reader not initialized")
+            .read(msg, &names)
+            .map_err(|e| SourceError::bad_data(format!("invalid XML: {}", e)))?;
+
+        // unlike a plain cast, values that can't be parsed are errors rather than nulls
+        let options = CastOptions {
+            safe: false,
+            ..Default::default()
+        };
+        let columns = fields
+            .iter()
+            .zip(values)
+            .map(|(field, values)| {
+                cast_with_options(&StringArray::from(values), field.data_type(), &options).map_err(
+                    |e| {
+                        SourceError::bad_data(format!(
+                            "XML value of '{}' can't be read as {}: {}",
+                            field.name(),
+                            field.data_type(),
+                            e
+                        ))
+                    },
+                )
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let batch = RecordBatch::try_new_with_options(
+            Arc::new(Schema::new(fields)),
+            columns,
+            &RecordBatchOptions::new().with_row_count(Some(rows)),
+        )
+        .map_err(|e| SourceError::bad_data(format!("XML does not match schema: {}", e)))?;
+
+        self.batch_to_schema(&batch, timestamp, additional_fields)
+    }
+
+    /// The fields of the table that are decoded from messages, which excludes the timestamp and
+    /// those filled from the additional fields
+    fn decoded_fields(
+        &self,
+        additional_fields: Option<&HashMap<&String, FieldValueType<'_>>>,
+    ) -> Vec<FieldRef> {
+        self.schema
+            .schema
+            .fields()
+            .iter()
+            .enumerate()
+            .filter(|(i, f)| {
+                *i != self.schema.timestamp_index
+                    && !additional_fields.is_some_and(|a| a.contains_key(f.name()))
+            })
+            .map(|(_, f)| f.clone())
+            .collect()
+    }
+
+    fn batch_to_schema(
+        &self,
+        batch: &RecordBatch,
+        timestamp: SystemTime,
+        additional_fields: Option<&HashMap<&String, FieldValueType<'_>>>,
+    ) -> Result<RecordBatch, SourceError> {
+        let rows = batch.num_rows();
+
+        let columns = self
+            .schema
+            .schema
+            .fields()
+            .iter()
+            .enumerate()
+            .map(|(i, field)| {
+                if i == self.schema.timestamp_index {
+                    return Ok(Arc::new(arrow_array::TimestampNanosecondArray::from(vec![
+                        to_nanos(timestamp)
+                            as i64;
+                        rows
+                    ])) as ArrayRef);
+                }
+
+                if let Some(value) = additional_fields.and_then(|f| f.get(field.name())) {
+                    return Ok(match value {
+                        FieldValueType::Int64(v) => {
+                            Arc::new(Int64Array::from(vec![*v; rows])) as ArrayRef
+                        }
+                        FieldValueType::Int32(v) => Arc::new(Int32Array::from(vec![*v; rows])),
+                        FieldValueType::Boolean(v) => Arc::new(BooleanArray::from(vec![*v; rows])),
+                        FieldValueType::String(v) => Arc::new(StringArray::from(vec![*v; rows])),
+                        FieldValueType::OptionalString(v) => {
+                            Arc::new(StringArray::from(vec![*v; rows]))
+                        }
+                    });
+                }
+
+                match batch.column_by_name(field.name()) {
+                    Some(column) if column.data_type() == field.data_type() => Ok(column.clone()),
+                    Some(column) => cast(column, field.data_type()).map_err(|e| {
+                        SourceError::bad_data(format!(
+                            "column '{}' can't be converted to {}: {}",
+                            field.name(),
+                            field.data_type(),
+                            e
+                        ))
+                    }),
+                    None if field.is_nullable() => Ok(new_null_array(field.data_type(), rows)),
+                    None => Err(SourceError::bad_data(format!(
+                        "Arrow batch is missing required column '{}'",
+                        field.name()
+                    ))),
+                }
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        RecordBatch::try_new(self.schema.schema.clone(), columns)
+            .map_err(|e| SourceError::bad_data(format!("Arrow batch does not match schema: {}", e)))
+    }
+
     fn deserialize_raw_string(&mut self, buffer: &mut [Box<dyn ArrayBuilder>], msg: &[u8]) {
         let (col, _) = self
             .schema
@@ -452,6 +813,126 @@ impl ArrowDeserializer {
     }
 }
 
+/// Converts the values of the timestamp field to event times. Values that can't be converted
+/// become nulls.
+fn event_timestamps(
+    timestamp_field: &TimestampField,
+    column: &ArrayRef,
+) -> Result<TimestampNanosecondArray, SourceError> {
+    let cast_err = |e: arrow::error::ArrowError| {
+        SourceError::bad_data(format!(
+            "timestamp field '{}' could not be converted: {}",
+            timestamp_field.field, e
+        ))
+    };
+
+    let nanos = timestamp_field.unit.nanos();
+    match column.data_type() {
+        DataType::Timestamp(..) | DataType::Date32 | DataType::Date64 => Ok(cast(
+            column,
+            &DataType::Timestamp(TimeUnit::Nanosecond, None),
+        )
+        .map_err(cast_err)?
+        .as_primitive::<TimestampNanosecondType>()
+        .clone()),
+        DataType::Utf8 | DataType::LargeUtf8 => match &timestamp_field.format {
+            Some(format) => {
+                let strings = cast(column, &DataType::Utf8).map_err(cast_err)?;
+                Ok(strings
+                    .as_string::<i32>()
+                    .iter()
+                    .map(|s| s.and_then(|s| parse_timestamp(s, format)))
+                    .collect())
+            }
+            // invalid strings are cast to nulls
+            None => Ok(
+                cast(column, &DataType::Timestamp(TimeUnit::Nanosecond, None))
+                    .map_err(cast_err)?
+                    .as_primitive::<TimestampNanosecondType>()
+                    .clone(),
+            ),
+        },
+        t if t.is_integer() => Ok(cast(column, &DataType::Int64)
+            .map_err(cast_err)?
+            .as_primitive::<Int64Type>()
+            .unary_opt::<_, TimestampNanosecondType>(|v| v.checked_mul(nanos))),
+        t if t.is_floating() => Ok(cast(column, &DataType::Float64)
+            .map_err(cast_err)?
+            .as_primitive::<Float64Type>()
+            .unary_opt::<_, TimestampNanosecondType>(|v| {
+                let v = v * nanos as f64;
+                (v.is_finite() && v.abs() < i64::MAX as f64).then_some(v as i64)
+            })),
+        t => Err(SourceError::other(
+            "Invalid timestamp field",
+            format!(
+                "timestamp field '{}' has type {}, which can't be used as a timestamp",
+                timestamp_field.field, t
+            ),
+        )),
+    }
+}
+
+/// Parses a timestamp with a strftime-style format, which may or may not include a time zone
+/// (timestamps without one are taken to be UTC) or a time
+fn parse_timestamp(s: &str, format: &str) -> Option<i64> {
+    DateTime::parse_from_str(s, format)
+        .map(|t| t.naive_utc())
+        .or_else(|_| NaiveDateTime::parse_from_str(s, format))
+        .or_else(|_| NaiveDate::parse_from_str(s, format).map(|d| d.and_time(NaiveTime::MIN)))
+        .ok()?
+        .and_utc()
+        .timestamp_nanos_opt()
+}
+
+/// Decompresses a whole message, before it's split into records
+pub(crate) fn decompress(
+    compression: PayloadCompression,
+    msg: &[u8],
+) -> Result<Vec<u8>, SourceError> {
+    let mut reader: Box<dyn Read + '_> = match compression {
+        PayloadCompression::Gzip => Box::new(MultiGzDecoder::new(msg)),
+        PayloadCompression::Zstd => Box::new(
+            zstd::Decoder::new(msg)
+                .map_err(|e| SourceError::bad_data(format!("invalid zstd data: {}", e)))?,
+        ),
+        PayloadCompression::Snappy => Box::new(snap::read::FrameDecoder::new(msg)),
+        PayloadCompression::Lz4 => Box::new(lz4_flex::frame::FrameDecoder::new(msg)),
+    };
+
+    let mut data = vec![];
+    reader.read_to_end(&mut data).map_err(|e| {
+        SourceError::bad_data(format!(
+            "failed to decompress {:?} message: {}",
+            compression, e
+        ))
+    })?;
+    Ok(data)
+}
+
+/// Arrow IPC files (also known as Feather v2) start with this, while streams don't
+const ARROW_FILE_MAGIC: &[u8] = b"ARROW1";
+
+/// Whether a line of CSV is made up of the names of the columns
+fn is_csv_header(csv: &CsvFormat, fields: &[FieldRef], line: &[u8]) -> bool {
+    let line = line.strip_suffix(b"\r").unwrap_or(line);
+    let quote = [csv.quote()];
+    let names: Vec<_> = line
+        .split(|b| *b == csv.delimiter())
+        .map(|name| {
+            name.strip_prefix(&quote[..])
+                .and_then(|n| n.strip_suffix(&quote[..]))
+                .unwrap_or(name)
+        })
+        .collect();
+
+    names.len() == fields.len()
+        && names
+            .iter()
+            .zip(fields)
+            .all(|(name, field)| *name == field.name().as_bytes())
+}
+
 pub(crate) fn add_timestamp(
     builder: &mut [Box<dyn ArrayBuilder>],
     idx: usize,
@@ -489,6 +970,13 @@ pub(crate) fn add_additional_fields(
                 .expect("additional field has incorrect type")
                 .append_value(*i);
         }
+        FieldValueType::Boolean(b) => {
+            builder[idx]
+                .as_any_mut()
+                .downcast_mut::<BooleanBuilder>()
+                .expect("additional field has incorrect type")
+                .append_value(*b);
+        }
         FieldValueType::String(s) => {
             builder[idx]
                 .as_any_mut()
@@ -496,6 +984,13 @@ pub(crate) fn add_additional_fields(
                 .expect("additional field has incorrect type")
                 .append_value(s);
         }
+        FieldValueType::OptionalString(s) => {
+            builder[idx]
+                .as_any_mut()
+                .downcast_mut::<StringBuilder>()
+                .expect("additional field has incorrect type")
+                .append_option(*s);
+        }
     }
 }
 
@@ -524,6 +1019,13 @@ pub(crate) fn add_additional_fields_using_builder(
                             .expect("additional field has incorrect type")
                             .append_value(*i);
                     }
+                    FieldValueType::Boolean(b) => {
+                        builder
+                            .as_any_mut()
+                            .downcast_mut::<BooleanBuilder>()
+                            .expect("additional field has incorrect type")
+                            .append_value(*b);
+                    }
                     FieldValueType::String(s) => {
                         builder
                             .as_any_mut()
@@ -531,6 +1033,13 @@ pub(crate) fn add_additional_fields_using_builder(
                             .expect("additional field has incorrect type")
                             .append_value(s);
                     }
+                    FieldValueType::OptionalString(s) => {
+                        builder
+                            .as_any_mut()
+                            .downcast_mut::<StringBuilder>()
+                            .expect("additional field has incorrect type")
+                            .append_option(*s);
+                    }
                 }
             }
         }
@@ -564,8 +1073,10 @@ pub(crate) fn flush_additional_fields_builders(
 
 #[cfg(test)]
 mod tests {
-    use crate::de::{ArrowDeserializer, FieldValueType, FramingIterator};
+    use crate::de::{parse_timestamp, ArrowDeserializer, FieldValueType, FramingIterator};
+    use arrow::array::{Array, ArrayRef, Int32Array, StringArray};
     use arrow::datatypes::Int32Type;
+    use arrow::ipc::writer::StreamWriter;
     use arrow_array::builder::{make_builder, ArrayBuilder};
     use arrow_array::cast::AsArray;
     use arrow_array::types::{GenericBinaryType, Int64Type, TimestampNanosecondType};
@@ -573,20 +1084,25 @@ mod tests {
     use arrow_schema::{Schema, TimeUnit};
     use arroyo_rpc::df::ArroyoSchema;
     use arroyo_rpc::formats::{
-        BadData, Format, Framing, FramingMethod, JsonFormat, NewlineDelimitedFraming,
-        RawBytesFormat,
+        ArrowIpcFormat, BadData, CsvFormat, Format, Framing, FramingMethod, JsonFormat,
+        NewlineDelimitedFraming, ParquetFormat, PayloadCompression, RawBytesFormat, TimestampField,
+        TimestampUnit, XmlFormat,
     };
     use arroyo_types::{to_nanos, SourceError};
+    use parquet::arrow::ArrowWriter;
     use serde_json::json;
+    use std::collections::BTreeMap;
+    use std::io::Write;
     use std::sync::Arc;
     use std::time::SystemTime;
 
     #[test]
     fn test_line_framing() {
         let framing = Some(Arc::new(Framing {
-            method: FramingMethod::Newline(NewlineDelimitedFraming {
+            method: Some(FramingMethod::Newline(NewlineDelimitedFraming {
                 max_line_length: None,
-            }),
+            })),
+            compression: None,
         }));
 
         let result: Vec<_> = FramingIterator::new(framing.clone(), "one block".as_bytes())
@@ -631,9 +1147,10 @@ mod tests {
     #[test]
     fn test_max_line_length() {
         let framing = Some(Arc::new(Framing {
-            method: FramingMethod::Newline(NewlineDelimitedFraming {
+            method: Some(FramingMethod::Newline(NewlineDelimitedFraming {
                 max_line_length: Some(5),
-            }),
+            })),
+            compression: None,
         }));
 
         let result: Vec<_> =
@@ -726,6 +1243,54 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn test_timestamp_field() {
+        let (mut arrays, deserializer) = setup_deserializer(BadData::Drop {});
+        let mut deserializer = deserializer.with_timestamp_field(Some(TimestampField {
+            field: "x".to_string(),
+            format: None,
+            unit: TimestampUnit::Seconds,
+        }));
+
+        for msg in [json!({ "x": 5 }), json!({ "x": null }), json!({ "x": 7 })] {
+            assert_eq!(
+                deserializer
+                    .deserialize_slice(
+                        &mut arrays[..],
+                        msg.to_string().as_bytes(),
+                        SystemTime::now(),
+                        None,
+                    )
+                    .await,
+                vec![]
+            );
+        }
+
+        let batch = deserializer.flush_buffer().unwrap().unwrap();
+        assert_eq!(batch.num_rows(), 2);
+        assert_eq!(
+            batch.columns()[1]
+                .as_primitive::<TimestampNanosecondType>()
+                .values()
+                .to_vec(),
+            vec![5_000_000_000, 7_000_000_000]
+        );
+
+        assert_eq!(
+            parse_timestamp("2024-01-02 03:04:05+0100", "%Y-%m-%d %H:%M:%S%z"),
+            Some(1704161045000000000)
+        );
+        assert_eq!(
+            parse_timestamp("2024-01-02 03:04:05", "%Y-%m-%d %H:%M:%S"),
+            Some(1704164645000000000)
+        );
+        assert_eq!(
+            parse_timestamp("2024-01-02", "%Y-%m-%d"),
+            Some(1704153600000000000)
+        );
+        assert_eq!(parse_timestamp("yesterday", "%Y-%m-%d"), None);
+    }
+
     #[tokio::test]
     async fn test_bad_data_fail() {
         let (mut arrays, mut deserializer) = setup_deserializer(BadData::Fail {});
@@ -758,6 +1323,79 @@ mod tests {
         assert!(matches!(err, SourceError::BadData { .. }));
     }
 
+    fn compress(compression: PayloadCompression, data: &[u8]) -> Vec<u8> {
+        match compression {
+            PayloadCompression::Gzip => {
+                let mut encoder =
+                    flate2::write::GzEncoder::new(vec![], flate2::Compression::default());
+                encoder.write_all(data).unwrap();
+                encoder.finish().unwrap()
+            }
+            PayloadCompression::Zstd => zstd::encode_all(data, 0).unwrap(),
+            PayloadCompression::Snappy => {
+                let mut encoder = snap::write::FrameEncoder::new(vec![]);
+                encoder.write_all(data).unwrap();
+                encoder.into_inner().unwrap()
+            }
+            PayloadCompression::Lz4 => {
+                let mut encoder = lz4_flex::frame::FrameEncoder::new(vec![]);
+                encoder.write_all(data).unwrap();
+                encoder.finish().unwrap()
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_compressed_payloads() {
+        for compression in [
+            PayloadCompression::Gzip,
+            PayloadCompression::Zstd,
+            PayloadCompression::Snappy,
+            PayloadCompression::Lz4,
+        ] {
+            let (mut arrays, deserializer) = setup_deserializer(BadData::Fail {});
+            let mut deserializer = ArrowDeserializer::new(
+                (*deserializer.format).clone(),
+                deserializer.schema.clone(),
+                Some(Framing {
+                    method: Some(FramingMethod::Newline(NewlineDelimitedFraming {
+                        max_line_length: None,
+                    })),
+                    compression: Some(compression),
+                }),
+                BadData::Fail {},
+            );
+
+            let msg = compress(compression, b"{\"x\": 1}\n{\"x\": 2}\n{\"x\": 3}");
+            assert_eq!(
+                deserializer
+                    .deserialize_slice(&mut arrays[..], &msg, SystemTime::now(), None)
+                    .await,
+                vec![]
+            );
+
+            let batch = deserializer.flush_buffer().unwrap().unwrap();
+            assert_eq!(
+                batch.columns()[0]
+                    .as_primitive::<Int64Type>()
+                    .values()
+                    .to_vec(),
+                vec![1, 2, 3],
+                "{:?}",
+                compression
+            );
+
+            let errors = deserializer
+                .deserialize_slice(&mut arrays[..], b"{\"x\": 4}", SystemTime::now(), None)
+                .await;
+            assert!(
+                matches!(&errors[..], [SourceError::BadData { .. }]),
+                "{:?}",
+                compression
+            );
+        }
+    }
+
     #[tokio::test]
     async fn test_raw_bytes() {
         let schema = Arc::new(Schema::new(vec![
@@ -808,12 +1446,248 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn test_arrow_ipc() {
+        // IPC messages are decoded directly into batches, so no builders are needed
+        let mut arrays: Vec<Box<dyn ArrayBuilder>> = vec![];
+        let schema = Arc::new(Schema::new(vec![
+            arrow_schema::Field::new("x", arrow_schema::DataType::Int64, true),
+            arrow_schema::Field::new(
+                "_timestamp",
+                arrow_schema::DataType::Timestamp(TimeUnit::Nanosecond, None),
+                false,
+            ),
+        ]));
+        let mut deserializer = ArrowDeserializer::new(
+            Format::ArrowIpc(ArrowIpcFormat {}),
+            ArroyoSchema::from_schema_unkeyed(schema).unwrap(),
+            None,
+            BadData::Fail {},
+        );
+
+        // the message has a narrower type for x and an extra column, which are cast and ignored
+        let input = RecordBatch::try_from_iter(vec![
+            ("x", Arc::new(Int32Array::from(vec![1, 2, 3])) as ArrayRef),
+            (
+                "y",
+                Arc::new(StringArray::from(vec!["a", "b", "c"])) as ArrayRef,
+            ),
+        ])
+        .unwrap();
+        let mut writer = StreamWriter::try_new(Vec::new(), &input.schema()).unwrap();
+        writer.write(&input).unwrap();
+        writer.finish().unwrap();
+        let msg = writer.into_inner().unwrap();
+
+        let time = SystemTime::now();
+        let result = deserializer
+            .deserialize_slice(&mut arrays, &msg, time, None)
+            .await;
+        assert!(result.is_empty());
+
+        let result = deserializer
+            .deserialize_slice(&mut arrays, b"not arrow", time, None)
+            .await;
+        assert!(matches!(result[0], SourceError::BadData { .. }));
+
+        let batch = deserializer.flush_buffer().unwrap().unwrap();
+        assert_eq!(batch.num_rows(), 3);
+        assert_eq!(batch.columns()[0].as_primitive::<Int64Type>().value(2), 3);
+        assert_eq!(
+            batch.columns()[1]
+                .as_primitive::<TimestampNanosecondType>()
+                .value(0),
+            to_nanos(time) as i64
+        );
+        assert!(deserializer.flush_buffer().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_parquet() {
+        // Parquet messages are decoded directly into batches, so no builders are needed
+        let mut arrays: Vec<Box<dyn ArrayBuilder>> = vec![];
+        let schema = Arc::new(Schema::new(vec![
+            arrow_schema::Field::new("x", arrow_schema::DataType::Int64, true),
+            arrow_schema::Field::new("y", arrow_schema::DataType::Utf8, true),
+            arrow_schema::Field::new(
+                "_timestamp",
+                arrow_schema::DataType::Timestamp(TimeUnit::Nanosecond, None),
+                false,
+            ),
+        ]));
+        let mut deserializer = ArrowDeserializer::new(
+            Format::Parquet(ParquetFormat {}),
+            ArroyoSchema::from_schema_unkeyed(schema).unwrap(),
+            None,
+            BadData::Fail {},
+        );
+
+        // the file's columns are in a different order and x has a narrower type
+        let input = RecordBatch::try_from_iter(vec![
+            (
+                "y",
+                Arc::new(StringArray::from(vec!["a", "b", "c"])) as ArrayRef,
+            ),
+            ("x", Arc::new(Int32Array::from(vec![1, 2, 3])) as ArrayRef),
+        ])
+        .unwrap();
+        let mut writer = ArrowWriter::try_new(Vec::new(), input.schema(), None).unwrap();
+        writer.write(&input).unwrap();
+        let msg = writer.into_inner().unwrap();
+
+        let time = SystemTime::now();
+        let result = deserializer
+            .deserialize_slice(&mut arrays, &msg, time, None)
+            .await;
+        assert!(result.is_empty());
+
+        let result = deserializer
+            .deserialize_slice(&mut arrays, b"not parquet", time, None)
+            .await;
+        assert!(matches!(result[0], SourceError::BadData { .. }));
+
+        let batch = deserializer.flush_buffer().unwrap().unwrap();
+        assert_eq!(batch.num_rows(), 3);
+        assert_eq!(batch.columns()[0].as_primitive::<Int64Type>().value(2), 3);
+        assert_eq!(batch.columns()[1].as_string::<i32>().value(0), "a");
+        assert_eq!(
+            batch.columns()[2]
+                .as_primitive::<TimestampNanosecondType>()
+                .value(0),
+            to_nanos(time) as i64
+        );
+    }
+
+    #[tokio::test]
+    async fn test_csv() {
+        // CSV messages are decoded directly into batches, so no builders are needed
+        let mut arrays: Vec<Box<dyn ArrayBuilder>> = vec![];
+        let schema = Arc::new(Schema::new(vec![
+            arrow_schema::Field::new("name", arrow_schema::DataType::Utf8, true),
+            arrow_schema::Field::new("count", arrow_schema::DataType::Int64, false),
+            arrow_schema::Field::new(
+                "_timestamp",
+                arrow_schema::DataType::Timestamp(TimeUnit::Nanosecond, None),
+                false,
+            ),
+        ]));
+        let mut deserializer = ArrowDeserializer::new(
+            Format::Csv(CsvFormat {
+                delimiter: Some(";".to_string()),
+                quote: Some("'".to_string()),
+                include_header: true,
+            }),
+            ArroyoSchema::from_schema_unkeyed(schema).unwrap(),
+            Some(Framing {
+                method: Some(FramingMethod::Newline(NewlineDelimitedFraming {
+                    max_line_length: None,
+                })),
+                compression: None,
+            }),
+            BadData::Fail {},
+        );
+
+        let time = SystemTime::now();
+        let result = deserializer
+            .deserialize_slice(
+                &mut arrays,
+                b"name;count\na;1\n'with;delimiter';2\n;3",
+                time,
+                None,
+            )
+            .await;
+        assert!(result.is_empty());
+
+        let result = deserializer
+            .deserialize_slice(&mut arrays, b"b;many", time, None)
+            .await;
+        assert!(matches!(result[0], SourceError::BadData { .. }));
+
+        let batch = deserializer.flush_buffer().unwrap().unwrap();
+        assert_eq!(batch.num_rows(), 3);
+        let names = batch.columns()[0].as_string::<i32>();
+        assert_eq!(names.value(0), "a");
+        assert_eq!(names.value(1), "with;delimiter");
+        assert!(names.is_null(2));
+        assert_eq!(batch.columns()[1].as_primitive::<Int64Type>().value(2), 3);
+        assert_eq!(
+            batch.columns()[2]
+                .as_primitive::<TimestampNanosecondType>()
+                .value(0),
+            to_nanos(time) as i64
+        );
+    }
+
+    #[tokio::test]
+    async fn test_xml() {
+        let mut arrays: Vec<Box<dyn ArrayBuilder>> = vec![];
+        let schema = Arc::new(Schema::new(vec![
+            arrow_schema::Field::new("id", arrow_schema::DataType::Int64, false),
+            arrow_schema::Field::new("title", arrow_schema::DataType::Utf8, true),
+            arrow_schema::Field::new("published", arrow_schema::DataType::Boolean, true),
+            arrow_schema::Field::new(
+                "_timestamp",
+                arrow_schema::DataType::Timestamp(TimeUnit::Nanosecond, None),
+                false,
+            ),
+        ]));
+        let mut deserializer = ArrowDeserializer::new(
+            Format::Xml(XmlFormat {
+                record_path: "/rss/channel/item".to_string(),
+                fields: BTreeMap::from([("published".to_string(), "@published".to_string())]),
+            }),
+            ArroyoSchema::from_schema_unkeyed(schema).unwrap(),
+            None,
+            BadData::Fail {},
+        );
+
+        let time = SystemTime::now();
+        let result = deserializer
+            .deserialize_slice(
+                &mut arrays,
+                br#"<rss><channel>
+                    <item published="true"><id>1</id><title>a</title></item>
+                    <item><id>2</id></item>
+                </channel></rss>"#,
+                time,
+                None,
+            )
+            .await;
+        assert!(result.is_empty());
+
+        for msg in [
+            &b"<rss><channel><item><id>x</id></item></channel></rss>"[..],
+            b"<rss><channel><item><title>no id</title></item></channel></rss>",
+            b"<rss><channel><item>",
+        ] {
+            let result = deserializer
+                .deserialize_slice(&mut arrays, msg, time, None)
+                .await;
+            assert!(matches!(result[0], SourceError::BadData { .. }));
+        }
+
+        let batch = deserializer.flush_buffer().unwrap().unwrap();
+        assert_eq!(batch.num_rows(), 2);
+        assert_eq!(batch.columns()[0].as_primitive::<Int64Type>().value(1), 2);
+        assert_eq!(batch.columns()[1].as_string::<i32>().value(0), "a");
+        assert!(batch.columns()[1].is_null(1));
+        assert!(batch.columns()[2].as_boolean().value(0));
+        assert!(batch.columns()[2].is_null(1));
+        assert_eq!(
+            batch.columns()[3]
+                .as_primitive::<TimestampNanosecondType>()
+                .value(0),
+            to_nanos(time) as i64
+        );
+    }
+
     #[tokio::test]
     async fn test_additional_fields_deserialisation() {
         let schema = Arc::new(Schema::new(vec![
             arrow_schema::Field::new("x", arrow_schema::DataType::Int64, true),
             arrow_schema::Field::new("y", arrow_schema::DataType::Int32, true),
             arrow_schema::Field::new("z", arrow_schema::DataType::Utf8, true),
+            arrow_schema::Field::new("retain", arrow_schema::DataType::Boolean, true),
             arrow_schema::Field::new(
                 "_timestamp",
                 arrow_schema::DataType::Timestamp(TimeUnit::Nanosecond, None),
@@ -850,6 +1724,8 @@ mod tests {
         let z_value = "hello".to_string();
         let binding = "z".to_string();
         additional_fields.insert(&binding, FieldValueType::String(&z_value));
+        let binding = "retain".to_string();
+        additional_fields.insert(&binding, FieldValueType::Boolean(true));
 
         let result = deserializer
             .deserialize_slice(
@@ -866,8 +1742,9 @@ mod tests {
         assert_eq!(batch.columns()[0].as_primitive::<Int64Type>().value(0), 5);
         assert_eq!(batch.columns()[1].as_primitive::<Int32Type>().value(0), 5);
         assert_eq!(batch.columns()[2].as_string::<i32>().value(0), "hello");
+        assert!(batch.columns()[3].as_boolean().value(0));
         assert_eq!(
-            batch.columns()[3]
+            batch.columns()[4]
                 .as_primitive::<TimestampNanosecondType>()
                 .value(0),
             to_nanos(time) as i64
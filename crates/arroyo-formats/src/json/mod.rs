@@ -1,10 +1,86 @@
+use crate::de::{decompress, FramingIterator};
+use anyhow::{anyhow, bail};
 use arrow::datatypes::{Field, Fields};
-use arrow_schema::DataType;
+use arrow_schema::{DataType, FieldRef, Schema};
+use arroyo_rpc::formats::{Framing, JsonFormat};
 use serde_json::{json, Value};
+use std::borrow::Cow;
 use std::collections::HashMap;
+use std::sync::Arc;
 
 pub mod schema;
 
+/// Infers the schema of a sample of JSON messages, which are split into records by the framing.
+/// Every field is nullable, and fields that are only ever null are inferred as strings.
+pub fn infer_schema(
+    format: &JsonFormat,
+    framing: Option<&Framing>,
+    messages: &[Vec<u8>],
+) -> anyhow::Result<Schema> {
+    if format.unstructured {
+        bail!("unstructured JSON is read into a single JSON field, so has no schema to infer");
+    }
+    if format.debezium {
+        bail!("schemas can't be inferred for Debezium JSON");
+    }
+
+    let framing = framing.cloned().map(Arc::new);
+    let mut records = vec![];
+    for msg in messages {
+        let msg = match framing.as_ref().and_then(|f| f.compression) {
+            Some(compression) => {
+                Cow::Owned(decompress(compression, msg).map_err(|e| anyhow!("{}", e.details()))?)
+            }
+            None => Cow::Borrowed(&msg[..]),
+        };
+
+        for record in FramingIterator::new(framing.clone(), &msg) {
+            let record = if format.confluent_schema_registry {
+                record.get(5..).unwrap_or_default()
+            } else {
+                record
+            };
+
+            if record.iter().all(u8::is_ascii_whitespace) {
+                continue;
+            }
+
+            records.push(
+                serde_json::from_slice::<Value>(record)
+                    .map_err(|e| anyhow!("sampled message is not valid JSON: {}", e))?,
+            );
+        }
+    }
+
+    if records.is_empty() {
+        bail!("no JSON records were found in the sampled messages");
+    }
+
+    let schema = arrow_json::reader::infer_json_schema_from_iterator(records.iter().map(Ok))
+        .map_err(|e| anyhow!("could not infer a schema from the sampled messages: {}", e))?;
+
+    Ok(Schema::new(
+        schema
+            .fields()
+            .iter()
+            .map(without_nulls)
+            .collect::<Vec<_>>(),
+    ))
+}
+
+fn without_nulls(field: &FieldRef) -> Field {
+    let data_type = match field.data_type() {
+        DataType::Null => DataType::Utf8,
+        DataType::List(item) => DataType::List(Arc::new(without_nulls(item))),
+        DataType::Struct(fields) => {
+            DataType::Struct(fields.iter().map(without_nulls).collect::<Vec<_>>().into())
+        }
+        t => t.clone(),
+    };
+
+    Field::new(field.name(), data_type, true)
+}
+
 pub fn field_to_json_schema(field: &Field) -> Value {
     match field.data_type() {
         arrow::datatypes::DataType::Null => {
@@ -58,7 +134,12 @@ pub fn field_to_json_schema(field: &Field) -> Value {
         arrow::datatypes::DataType::Dictionary(_, _) => todo!(),
         arrow::datatypes::DataType::Decimal128(_, _) => todo!(),
         arrow::datatypes::DataType::Decimal256(_, _) => todo!(),
-        arrow::datatypes::DataType::Map(_, _) => todo!(),
+        arrow::datatypes::DataType::Map(entries, _) => {
+            let DataType::Struct(kv) = entries.data_type() else {
+                unreachable!("map entries must be a struct");
+            };
+            json! {{ "type": "object", "additionalProperties": field_to_json_schema(&kv[1]) }}
+        }
         arrow::datatypes::DataType::RunEndEncoded(_, _) => todo!(),
         DataType::BinaryView => todo!(),
         DataType::Utf8View => todo!(),
@@ -140,7 +221,18 @@ pub fn field_to_kafka_json(field: &Field) -> Value {
         Dictionary(_, _) => todo!(),
         Decimal128(_, _) => todo!(),
         Decimal256(_, _) => todo!(),
-        Map(_, _) => todo!(),
+        Map(entries, _) => {
+            let Struct(kv) = entries.data_type() else {
+                unreachable!("map entries must be a struct");
+            };
+            return json! {{
+                "type": "map",
+                "keys": field_to_kafka_json(&kv[0]),
+                "values": field_to_kafka_json(&kv[1]),
+                "field": field.name().clone(),
+                "optional": field.is_nullable(),
+            }};
+        }
         RunEndEncoded(_, _) => todo!(),
         BinaryView => todo!(),
         Utf8View => todo!(),
@@ -166,3 +258,53 @@ pub fn arrow_to_kafka_json(name: &str, fields: &Fields) -> Value {
         "optional": false,
     }}
 }
+
+#[cfg(test)]
+mod test {
+    use super::infer_schema;
+    use arrow_schema::{DataType, Field, Fields};
+    use arroyo_rpc::formats::{Framing, FramingMethod, JsonFormat, NewlineDelimitedFraming};
+
+    #[test]
+    fn test_infer_schema() {
+        let format = JsonFormat {
+            confluent_schema_registry: false,
+            schema_id: None,
+            include_schema: false,
+            debezium: false,
+            unstructured: false,
+            timestamp_format: Default::default(),
+        };
+
+        let framing = Framing {
+            method: Some(FramingMethod::Newline(NewlineDelimitedFraming {
+                max_line_length: None,
+            })),
+            compression: None,
+        };
+
+        let messages = vec![
+            br#"{"id": 1, "name": "a", "tags": ["x"], "user": {"age": 30}}"#.to_vec(),
+            b"{\"id\": 2, \"score\": 1.5, \"note\": null}\n{\"id\": 3}".to_vec(),
+        ];
+
+        let schema = infer_schema(&format, Some(&framing), &messages).unwrap();
+
+        let field = |name| schema.field_with_name(name).unwrap().data_type().clone();
+        assert_eq!(field("id"), DataType::Int64);
+        assert_eq!(field("name"), DataType::Utf8);
+        assert_eq!(
+            field("tags"),
+            DataType::List(Field::new("item", DataType::Utf8, true).into())
+        );
+        assert_eq!(
+            field("user"),
+            DataType::Struct(Fields::from(vec![Field::new("age", DataType::Int64, true)]))
+        );
+        assert_eq!(field("score"), DataType::Float64);
+        assert_eq!(field("note"), DataType::Utf8);
+        assert!(schema.fields().iter().all(|f| f.is_nullable()));
+
+        assert!(infer_schema(&format, None, &[b"not json".to_vec()]).is_err());
+    }
+}
@@ -1,5 +1,5 @@
 use anyhow::{anyhow, bail};
-use arrow_schema::{DataType, Field, TimeUnit};
+use arrow_schema::{DataType, Field, Fields, TimeUnit};
 use arroyo_types::ArroyoExtensionType;
 use schemars::schema::{RootSchema, Schema};
 use std::sync::Arc;
@@ -123,6 +123,28 @@ fn to_arrow_datatype(
                 None,
             )
         }
+        TypeDetails::Map(k, v)
+            if matches!(
+                type_space.get_type(&k).unwrap().details(),
+                TypeDetails::String
+            ) =>
+        {
+            let v = type_space.get_type(&v).unwrap();
+            let (v, nullable, extension) = to_arrow_datatype(type_space, &v, None);
+            let entries = Field::new(
+                "entries",
+                DataType::Struct(Fields::from(vec![
+                    Field::new("keys", DataType::Utf8, false),
+                    ArroyoExtensionType::add_metadata(extension, Field::new("values", v, nullable)),
+                ])),
+                false,
+            );
+            (
+                DataType::Map(Arc::new(entries), false),
+                !required.unwrap_or(true),
+                None,
+            )
+        }
         _ => {
             warn!(
                 "Unhandled JSON schema type for field {}, converting to raw json",
@@ -136,6 +158,7 @@ fn to_arrow_datatype(
 #[cfg(test)]
 mod test {
     use super::to_arrow;
+    use arrow_schema::DataType;
 
     #[test]
     fn test() {
@@ -196,4 +219,34 @@ mod test {
 
         let _ = to_arrow("nexmark", json_schema).unwrap();
     }
+
+    #[test]
+    fn test_map() {
+        let json_schema = r##"
+{
+  "type": "object",
+  "properties": {
+    "counts": {
+      "type": "object",
+      "additionalProperties": {
+        "type": "integer"
+      }
+    }
+  },
+  "required": ["counts"]
+}"##;
+
+        let schema = to_arrow("counts", json_schema).unwrap();
+        let field = schema.field_with_name("counts").unwrap();
+        assert!(!field.is_nullable());
+
+        let DataType::Map(entries, _) = field.data_type() else {
+            panic!("expected a map, found {}", field.data_type());
+        };
+        let DataType::Struct(kv) = entries.data_type() else {
+            panic!("map entries should be a struct");
+        };
+        assert_eq!(kv[0].data_type(), &DataType::Utf8);
+        assert!(kv[1].data_type().is_integer());
+    }
 }
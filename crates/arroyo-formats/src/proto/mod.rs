@@ -1,4 +1,5 @@
 pub mod de;
 pub mod schema;
+pub(crate) mod ser;
 #[cfg(test)]
 mod test;
@@ -0,0 +1,190 @@
+use anyhow::{anyhow, bail};
+use arrow_array::RecordBatch;
+use arrow_json::writer::record_batch_to_vec;
+use base64::prelude::BASE64_STANDARD;
+use base64::Engine;
+use prost::Message;
+use prost_reflect::{DynamicMessage, FieldDescriptor, Kind, MapKey, MessageDescriptor, Value};
+use serde_json::Value as JsonValue;
+use std::collections::HashMap;
+
+/// Encodes each row of the batch as a `descriptor` message. Columns are matched to fields by
+/// name, and columns without a corresponding field are ignored. Timestamps are written as
+/// milliseconds since the epoch, and bytes fields are read from base64-encoded strings,
+/// mirroring the deserializer.
+pub(crate) fn serialize(
+    descriptor: &MessageDescriptor,
+    batch: &RecordBatch,
+) -> anyhow::Result<Vec<Vec<u8>>> {
+    let rows = record_batch_to_vec(batch, true, arrow_json::writer::TimestampFormat::UnixMillis)?;
+
+    rows.into_iter()
+        .map(|row| {
+            let JsonValue::Object(row) = serde_json::from_slice(&row)? else {
+                bail!("row was not serialized as a JSON object");
+            };
+            Ok(json_to_message(descriptor, &row)?.encode_to_vec())
+        })
+        .collect()
+}
+
+pub(crate) fn json_to_message(
+    descriptor: &MessageDescriptor,
+    object: &serde_json::Map<String, JsonValue>,
+) -> anyhow::Result<DynamicMessage> {
+    let mut message = DynamicMessage::new(descriptor.clone());
+
+    for (name, value) in object {
+        if value.is_null() {
+            continue;
+        }
+
+        let Some(field) = descriptor
+            .get_field_by_name(name)
+            .or_else(|| descriptor.get_field_by_json_name(name))
+        else {
+            continue;
+        };
+
+        let value = json_to_field_value(&field, value)
+            .map_err(|e| anyhow!("invalid value for field '{}': {}", name, e))?;
+        message.set_field(&field, value);
+    }
+
+    Ok(message)
+}
+
+fn json_to_field_value(field: &FieldDescriptor, value: &JsonValue) -> anyhow::Result<Value> {
+    if field.is_map() {
+        let JsonValue::Object(object) = value else {
+            bail!("expected an object for a map field");
+        };
+
+        let Kind::Message(entry) = field.kind() else {
+            unreachable!("map fields have message kinds");
+        };
+        let key_field = entry.map_entry_key_field();
+        let value_field = entry.map_entry_value_field();
+
+        let mut map = HashMap::new();
+        for (k, v) in object {
+            if v.is_null() {
+                continue;
+            }
+            map.insert(
+                json_to_map_key(&key_field.kind(), k)?,
+                json_to_value(&value_field.kind(), v)?,
+            );
+        }
+        return Ok(Value::Map(map));
+    }
+
+    if field.is_list() {
+        let JsonValue::Array(items) = value else {
+            bail!("expected an array for a repeated field");
+        };
+
+        return Ok(Value::List(
+            items
+                .iter()
+                .filter(|v| !v.is_null())
+                .map(|v| json_to_value(&field.kind(), v))
+                .collect::<anyhow::Result<_>>()?,
+        ));
+    }
+
+    json_to_value(&field.kind(), value)
+}
+
+fn json_to_value(kind: &Kind, value: &JsonValue) -> anyhow::Result<Value> {
+    Ok(match kind {
+        Kind::Double => Value::F64(as_f64(value)?),
+        Kind::Float => Value::F32(as_f64(value)? as f32),
+        Kind::Int32 | Kind::Sint32 | Kind::Sfixed32 => Value::I32(as_i64(value)?.try_into()?),
+        Kind::Int64 | Kind::Sint64 | Kind::Sfixed64 => Value::I64(as_i64(value)?),
+        Kind::Uint32 | Kind::Fixed32 => Value::U32(as_u64(value)?.try_into()?),
+        Kind::Uint64 | Kind::Fixed64 => Value::U64(as_u64(value)?),
+        Kind::Bool => Value::Bool(
+            value
+                .as_bool()
+                .ok_or_else(|| anyhow!("expected a boolean, found {}", value))?,
+        ),
+        Kind::String => Value::String(match value {
+            JsonValue::String(s) => s.clone(),
+            // structured values, like JSON columns, are written as their JSON encoding
+            v => v.to_string(),
+        }),
+        Kind::Bytes => {
+            let JsonValue::String(s) = value else {
+                bail!("expected a string, found {}", value);
+            };
+            Value::Bytes(
+                BASE64_STANDARD
+                    .decode(s)
+                    .unwrap_or_else(|_| s.as_bytes().to_vec())
+                    .into(),
+            )
+        }
+        Kind::Message(descriptor) => {
+            let object = match value {
+                JsonValue::Object(object) => object.clone(),
+                // JSON columns may hold messages encoded as strings
+                JsonValue::String(s) => match serde_json::from_str(s)? {
+                    JsonValue::Object(object) => object,
+                    _ => bail!("expected an object, found {}", value),
+                },
+                _ => bail!("expected an object, found {}", value),
+            };
+            Value::Message(json_to_message(descriptor, &object)?)
+        }
+        Kind::Enum(descriptor) => match value {
+            JsonValue::String(s) => Value::EnumNumber(
+                descriptor
+                    .get_value_by_name(s)
+                    .ok_or_else(|| anyhow!("'{}' is not a value of enum {}", s, descriptor.name()))?
+                    .number(),
+            ),
+            v => Value::EnumNumber(as_i64(v)?.try_into()?),
+        },
+    })
+}
+
+fn json_to_map_key(kind: &Kind, key: &str) -> anyhow::Result<MapKey> {
+    Ok(match kind {
+        Kind::Int32 | Kind::Sint32 | Kind::Sfixed32 => MapKey::I32(key.parse()?),
+        Kind::Int64 | Kind::Sint64 | Kind::Sfixed64 => MapKey::I64(key.parse()?),
+        Kind::Uint32 | Kind::Fixed32 => MapKey::U32(key.parse()?),
+        Kind::Uint64 | Kind::Fixed64 => MapKey::U64(key.parse()?),
+        Kind::Bool => MapKey::Bool(key.parse()?),
+        Kind::String => MapKey::String(key.to_string()),
+        k => bail!("{:?} is not a valid map key type", k),
+    })
+}
+
+fn as_f64(value: &JsonValue) -> anyhow::Result<f64> {
+    match value {
+        JsonValue::Number(n) => n.as_f64(),
+        JsonValue::String(s) => s.parse().ok(),
+        _ => None,
+    }
+    .ok_or_else(|| anyhow!("expected a number, found {}", value))
+}
+
+fn as_i64(value: &JsonValue) -> anyhow::Result<i64> {
+    match value {
+        JsonValue::Number(n) => n.as_i64(),
+        // 64-bit integers may be written as strings
+        JsonValue::String(s) => s.parse().ok(),
+        _ => None,
+    }
+    .ok_or_else(|| anyhow!("expected an integer, found {}", value))
+}
+
+fn as_u64(value: &JsonValue) -> anyhow::Result<u64> {
+    match value {
+        JsonValue::Number(n) => n.as_u64(),
+        JsonValue::String(s) => s.parse().ok(),
+        _ => None,
+    }
+    .ok_or_else(|| anyhow!("expected an unsigned integer, found {}", value))
+}
@@ -2,9 +2,12 @@ use crate::proto::schema::{
     protobuf_to_arrow, schema_file_to_descriptor, schema_file_to_descriptor_with_resolver,
     ProtoSchemaResolver,
 };
+use crate::ser::ArrowSerializer;
+use arrow_array::{BooleanArray, Float64Array, Int64Array, RecordBatch, StringArray};
 use arrow_schema::{DataType, Field, Schema};
+use arroyo_rpc::formats::{Format, ProtobufFormat};
 use arroyo_types::ArroyoExtensionType;
-use prost_reflect::DescriptorPool;
+use prost_reflect::{DescriptorPool, DynamicMessage};
 use std::collections::HashMap;
 use std::sync::Arc;
 
@@ -165,6 +168,66 @@ async fn test_enum_fields() {
     assert_field(&arrow_schema, "enum_field", DataType::Utf8, true);
 }
 
+#[tokio::test]
+async fn test_serialize() {
+    let bytes = schema_file_to_descriptor(
+        include_str!("protos/basic_types.proto"),
+        &HashMap::default(),
+    )
+    .await
+    .unwrap();
+
+    let mut serializer = ArrowSerializer::new(Format::Protobuf(ProtobufFormat {
+        into_unstructured_json: false,
+        message_name: Some("TestBasicTypes".to_string()),
+        compiled_schema: Some(bytes.clone()),
+        confluent_schema_registry: false,
+    }));
+
+    let batch = RecordBatch::try_new(
+        Arc::new(Schema::new(vec![
+            Field::new("bool_field", DataType::Boolean, true),
+            Field::new("int64_field", DataType::Int64, true),
+            Field::new("double_field", DataType::Float64, true),
+            Field::new("unknown_field", DataType::Utf8, true),
+        ])),
+        vec![
+            Arc::new(BooleanArray::from(vec![true, false])),
+            Arc::new(Int64Array::from(vec![Some(5), None])),
+            Arc::new(Float64Array::from(vec![1.5, 2.0])),
+            Arc::new(StringArray::from(vec!["a", "b"])),
+        ],
+    )
+    .unwrap();
+
+    let rows: Vec<_> = serializer.serialize(&batch).collect();
+    assert_eq!(rows.len(), 2);
+
+    let pool = DescriptorPool::decode(bytes.as_ref()).unwrap();
+    let descriptor = pool.get_message_by_name("TestBasicTypes").unwrap();
+
+    let first = DynamicMessage::decode(descriptor.clone(), rows[0].as_slice()).unwrap();
+    assert_eq!(
+        first.get_field_by_name("bool_field").unwrap().as_bool(),
+        Some(true)
+    );
+    assert_eq!(
+        first.get_field_by_name("int64_field").unwrap().as_i64(),
+        Some(5)
+    );
+    assert_eq!(
+        first.get_field_by_name("double_field").unwrap().as_f64(),
+        Some(1.5)
+    );
+
+    let second = DynamicMessage::decode(descriptor, rows[1].as_slice()).unwrap();
+    assert!(!second.has_field_by_name("int64_field"));
+    assert_eq!(
+        second.get_field_by_name("double_field").unwrap().as_f64(),
+        Some(2.0)
+    );
+}
+
 // Helper function to assert field properties
 fn assert_field(schema: &Schema, name: &str, data_type: DataType, nullable: bool) {
     let field = schema.field_with_name(name).unwrap();
@@ -1,20 +1,26 @@
 use crate::avro::schema;
-use crate::{avro, json};
+use crate::proto::schema::get_pool;
+use crate::{avro, json, proto};
+use arrow::csv::WriterBuilder;
+use arrow::ipc::writer::StreamWriter;
 use arrow_array::cast::AsArray;
 use arrow_array::types::GenericBinaryType;
 use arrow_array::RecordBatch;
 use arrow_json::writer::record_batch_to_vec;
 use arrow_schema::{DataType, Field};
 use arroyo_rpc::formats::{
-    AvroFormat, Format, JsonFormat, RawBytesFormat, RawStringFormat, TimestampFormat,
+    AvroFormat, CsvFormat, Format, JsonFormat, ProtobufFormat, RawBytesFormat, RawStringFormat,
+    TimestampFormat,
 };
 use arroyo_rpc::TIMESTAMP_FIELD;
+use prost_reflect::MessageDescriptor;
 use serde_json::Value;
 use std::sync::Arc;
 
 pub struct ArrowSerializer {
     kafka_schema: Option<Value>,
     avro_schema: Option<Arc<apache_avro::schema::Schema>>,
+    proto_descriptor: Option<MessageDescriptor>,
     format: Format,
     projection: Vec<usize>,
 }
@@ -24,6 +30,7 @@ impl ArrowSerializer {
         Self {
             kafka_schema: None,
             avro_schema: None,
+            proto_descriptor: None,
             format,
             projection: vec![],
         }
@@ -59,6 +66,23 @@ impl ArrowSerializer {
         json::arrow_to_kafka_json("ArroyoJson", &Self::projected_schema(schema).into())
     }
 
+    /// The row of column names that starts a CSV file, without a trailing newline
+    pub fn csv_header(csv: &CsvFormat, schema: &arrow_schema::Schema) -> Vec<u8> {
+        let schema = Arc::new(arrow_schema::Schema::new(Self::projected_schema(schema)));
+        let mut writer = WriterBuilder::new()
+            .with_header(true)
+            .with_delimiter(csv.delimiter())
+            .with_quote(csv.quote())
+            .build(Vec::new());
+        writer
+            .write(&RecordBatch::new_empty(schema))
+            .expect("failed to write CSV header");
+
+        let mut header = writer.into_inner();
+        trim_newline(&mut header);
+        header
+    }
+
     pub fn serialize(&mut self, batch: &RecordBatch) -> Box<dyn Iterator<Item = Vec<u8>> + Send> {
         if self.projection.is_empty() {
             self.projection = Self::projection(&batch.schema());
@@ -72,6 +96,12 @@ impl ArrowSerializer {
             self.avro_schema = Some(Arc::new(Self::avro_schema(&batch.schema())));
         }
 
+        if self.proto_descriptor.is_none() {
+            if let Format::Protobuf(proto) = &self.format {
+                self.proto_descriptor = Some(Self::proto_descriptor(proto));
+            }
+        }
+
         let batch = batch
             .project(&self.projection)
             .expect("batch has wrong number of columns");
@@ -80,11 +110,12 @@ impl ArrowSerializer {
             Format::Json(json) => self.serialize_json(json, &batch),
             Format::Avro(avro) => self.serialize_avro(avro, &batch),
             Format::Parquet(_) => todo!("parquet"),
+            Format::Xml(_) => unreachable!("xml can only be used for sources"),
             Format::RawString(RawStringFormat {}) => self.serialize_raw_string(&batch),
             Format::RawBytes(RawBytesFormat {}) => self.serialize_raw_bytes(&batch),
-            Format::Protobuf(_) => {
-                todo!("protobuf serializer!")
-            }
+            Format::Protobuf(_) => self.serialize_proto(&batch),
+            Format::ArrowIpc(_) => self.serialize_arrow_ipc(&batch),
+            Format::Csv(csv) => self.serialize_csv(csv, &batch),
         }
     }
 
@@ -142,6 +173,83 @@ impl ArrowSerializer {
         }))
     }
 
+    fn proto_descriptor(proto: &ProtobufFormat) -> MessageDescriptor {
+        let pool = get_pool(
+            proto
+                .compiled_schema
+                .as_ref()
+                .expect("must have a compiled schema to write protobuf"),
+        )
+        .expect("invalid protobuf schema");
+
+        let message_name = proto
+            .message_name
+            .as_ref()
+            .expect("must have a message name to write protobuf");
+
+        pool.get_message_by_name(message_name)
+            .unwrap_or_else(|| panic!("message {} not found in protobuf schema", message_name))
+    }
+
+    /// Writes the whole batch as a single message containing a complete Arrow IPC stream, so
+    /// that each message can be decoded on its own
+    fn serialize_arrow_ipc(&self, batch: &RecordBatch) -> Box<dyn Iterator<Item = Vec<u8>> + Send> {
+        if batch.num_rows() == 0 {
+            return Box::new(std::iter::empty());
+        }
+
+        let mut writer = StreamWriter::try_new(Vec::new(), &batch.schema())
+            .expect("failed to create Arrow IPC writer");
+        writer
+            .write(batch)
+            .expect("failed to write batch as Arrow IPC");
+        writer.finish().expect("failed to finish Arrow IPC stream");
+
+        Box::new(std::iter::once(
+            writer
+                .into_inner()
+                .expect("failed to finish Arrow IPC stream"),
+        ))
+    }
+
+    /// Writes each row as a line of delimited values, without a trailing newline
+    fn serialize_csv(
+        &self,
+        csv: &CsvFormat,
+        batch: &RecordBatch,
+    ) -> Box<dyn Iterator<Item = Vec<u8>> + Send> {
+        let rows: Vec<_> = (0..batch.num_rows())
+            .map(|i| {
+                let mut writer = WriterBuilder::new()
+                    .with_header(false)
+                    .with_delimiter(csv.delimiter())
+                    .with_quote(csv.quote())
+                    .build(Vec::new());
+                writer
+                    .write(&batch.slice(i, 1))
+                    .expect("failed to write row as CSV");
+
+                let mut row = writer.into_inner();
+                trim_newline(&mut row);
+                row
+            })
+            .collect();
+
+        Box::new(rows.into_iter())
+    }
+
+    fn serialize_proto(&self, batch: &RecordBatch) -> Box<dyn Iterator<Item = Vec<u8>> + Send> {
+        let descriptor = self
+            .proto_descriptor
+            .as_ref()
+            .expect("must have descriptor set for protobuf format");
+
+        let rows = proto::ser::serialize(descriptor, batch)
+            .unwrap_or_else(|e| panic!("protobuf serialization failed: {:?}", e));
+
+        Box::new(rows.into_iter())
+    }
+
     fn serialize_raw_string(
         &self,
         batch: &RecordBatch,
@@ -236,12 +344,20 @@ impl ArrowSerializer {
     }
 }
 
+fn trim_newline(line: &mut Vec<u8>) {
+    while matches!(line.last(), Some(b'\n' | b'\r')) {
+        line.pop();
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::ser::ArrowSerializer;
     use arrow_array::builder::TimestampNanosecondBuilder;
     use arrow_schema::{Schema, TimeUnit};
-    use arroyo_rpc::formats::{Format, RawBytesFormat, RawStringFormat, TimestampFormat};
+    use arroyo_rpc::formats::{
+        CsvFormat, Format, RawBytesFormat, RawStringFormat, TimestampFormat,
+    };
     use arroyo_types::to_nanos;
     use std::sync::Arc;
     use std::time::{Duration, SystemTime};
@@ -324,6 +440,48 @@ mod tests {
         assert_eq!(iter.next(), None);
     }
 
+    #[test]
+    fn test_csv() {
+        let format = CsvFormat {
+            delimiter: Some(";".to_string()),
+            quote: None,
+            include_header: true,
+        };
+        let mut serializer = ArrowSerializer::new(Format::Csv(format.clone()));
+
+        let schema = Arc::new(Schema::new(vec![
+            arrow_schema::Field::new("name", arrow_schema::DataType::Utf8, true),
+            arrow_schema::Field::new("count", arrow_schema::DataType::Int64, false),
+            arrow_schema::Field::new(
+                "_timestamp",
+                arrow_schema::DataType::Timestamp(TimeUnit::Nanosecond, None),
+                false,
+            ),
+        ]));
+
+        let batch = arrow_array::RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(arrow_array::StringArray::from(vec![
+                    Some("a"),
+                    None,
+                    Some("with;delimiter"),
+                ])),
+                Arc::new(arrow_array::Int64Array::from(vec![1, 2, 3])),
+                Arc::new(arrow_array::TimestampNanosecondArray::from(vec![0, 0, 0])),
+            ],
+        )
+        .unwrap();
+
+        assert_eq!(ArrowSerializer::csv_header(&format, &schema), b"name;count");
+
+        let mut iter = serializer.serialize(&batch);
+        assert_eq!(iter.next().unwrap(), b"a;1");
+        assert_eq!(iter.next().unwrap(), b";2");
+        assert_eq!(iter.next().unwrap(), b"\"with;delimiter\";3");
+        assert_eq!(iter.next(), None);
+    }
+
     #[test]
     fn test_json() {
         let mut serializer = ArrowSerializer::new(Format::Json(arroyo_rpc::formats::JsonFormat {
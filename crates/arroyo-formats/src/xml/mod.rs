@@ -0,0 +1,228 @@
+use arroyo_rpc::formats::{XmlFormat, XmlPath, XmlStep};
+use roxmltree::{Document, Node, ParsingOptions};
+use std::collections::HashMap;
+
+/// Reads rows out of XML documents, according to the paths of an [XmlFormat]
+pub struct XmlReader {
+    record_path: XmlPath,
+    fields: HashMap<String, XmlPath>,
+}
+
+impl XmlReader {
+    pub fn new(format: &XmlFormat) -> Result<Self, String> {
+        Ok(Self {
+            record_path: XmlPath::parse(&format.record_path)?,
+            fields: format
+                .fields
+                .iter()
+                .map(|(column, path)| Ok((column.clone(), XmlPath::parse(path)?)))
+                .collect::<Result<_, String>>()?,
+        })
+    }
+
+    /// Reads the values of the columns from each record in the document, returning the number of
+    /// records and the values of each column. Values are trimmed, and missing or empty values are
+    /// read as null.
+    pub fn read(
+        &self,
+        doc: &[u8],
+        columns: &[&str],
+    ) -> Result<(usize, Vec<Vec<Option<String>>>), String> {
+        let text = std::str::from_utf8(doc).map_err(|e| format!("XML is not UTF-8: {}", e))?;
+        let doc = Document::parse_with_options(
+            text,
+            ParsingOptions {
+                allow_dtd: true,
+                ..Default::default()
+            },
+        )
+        .map_err(|e| e.to_string())?;
+
+        let records = select(&self.record_path.steps, vec![doc.root()]);
+
+        let values = columns
+            .iter()
+            .map(|column| {
+                records
+                    .iter()
+                    .map(|record| match self.fields.get(*column) {
+                        Some(path) => value(path, *record),
+                        None => value(
+                            &XmlPath {
+                                absolute: false,
+                                steps: vec![XmlStep::Child(column.to_string())],
+                            },
+                            *record,
+                        )
+                        .or_else(|| {
+                            value(
+                                &XmlPath {
+                                    absolute: false,
+                                    steps: vec![XmlStep::Attribute(column.to_string())],
+                                },
+                                *record,
+                            )
+                        }),
+                    })
+                    .collect()
+            })
+            .collect();
+
+        Ok((records.len(), values))
+    }
+}
+
+fn matches(node: &Node, name: &str) -> bool {
+    node.is_element() && (name == "*" || node.tag_name().name() == name)
+}
+
+/// Applies element steps to a set of nodes, returning the selected elements in document order
+fn select<'a, 'i>(steps: &[XmlStep], nodes: Vec<Node<'a, 'i>>) -> Vec<Node<'a, 'i>> {
+    steps.iter().fold(nodes, |nodes, step| match step {
+        XmlStep::Child(name) => nodes
+            .iter()
+            .flat_map(|n| n.children())
+            .filter(|n| matches(n, name))
+            .collect(),
+        XmlStep::Descendant(name) => {
+            // the descendants of nested nodes overlap
+            let mut selected: Vec<_> = nodes
+                .iter()
+                .flat_map(|n| n.descendants().skip(1))
+                .filter(|n| matches(n, name))
+                .collect();
+            selected.sort_by_key(|n| n.id());
+            selected.dedup_by_key(|n| n.id());
+            selected
+        }
+        XmlStep::Current => nodes,
+        XmlStep::Attribute(_) | XmlStep::Text => {
+            unreachable!("attributes and text() can only be the last step")
+        }
+    })
+}
+
+/// Evaluates a path relative to a record, returning the value of the first node it selects
+fn value(path: &XmlPath, record: Node) -> Option<String> {
+    let context = if path.absolute {
+        record.document().root()
+    } else {
+        record
+    };
+
+    let (steps, last) = match path.steps.split_last() {
+        Some((last @ (XmlStep::Attribute(_) | XmlStep::Text), steps)) => (steps, Some(last)),
+        _ => (&path.steps[..], None),
+    };
+
+    let node = *select(steps, vec![context]).first()?;
+
+    let value = match last {
+        Some(XmlStep::Attribute(name)) => node
+            .attributes()
+            .find(|a| name == "*" || a.name() == name)?
+            .value()
+            .to_string(),
+        Some(XmlStep::Text) => node
+            .children()
+            .filter(|n| n.is_text())
+            .filter_map(|n| n.text())
+            .collect(),
+        _ => node
+            .descendants()
+            .filter(|n| n.is_text())
+            .filter_map(|n| n.text())
+            .collect(),
+    };
+
+    let value = value.trim();
+    (!value.is_empty()).then(|| value.to_string())
+}
+
+#[cfg(test)]
+mod test {
+    use super::XmlReader;
+    use arroyo_rpc::formats::XmlFormat;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_read() {
+        let doc = br#"<?xml version="1.0" encoding="UTF-8"?>
+            <feed xmlns="http://www.w3.org/2005/Atom">
+              <title>Example</title>
+              <entry id="1">
+                <title>First</title>
+                <link href="https://example.com/1"/>
+                <author><name>Ann</name></author>
+                <views> 10 </views>
+              </entry>
+              <entry id="2">
+                <title><![CDATA[Second & last]]></title>
+                <views></views>
+              </entry>
+            </feed>"#;
+
+        let reader = XmlReader::new(&XmlFormat {
+            record_path: "/atom:feed/entry".to_string(),
+            fields: BTreeMap::from([
+                ("author".to_string(), "author/name".to_string()),
+                ("url".to_string(), "link/@href".to_string()),
+                ("feed".to_string(), "/feed/title".to_string()),
+            ]),
+        })
+        .unwrap();
+
+        let (rows, columns) = reader
+            .read(doc, &["id", "title", "author", "url", "views", "feed"])
+            .unwrap();
+
+        let s = |v: &str| Some(v.to_string());
+        assert_eq!(rows, 2);
+        assert_eq!(
+            columns,
+            vec![
+                vec![s("1"), s("2")],
+                vec![s("First"), s("Second & last")],
+                vec![s("Ann"), None],
+                vec![s("https://example.com/1"), None],
+                vec![s("10"), None],
+                vec![s("Example"), s("Example")],
+            ]
+        );
+
+        let reader = XmlReader::new(&XmlFormat {
+            record_path: "//title".to_string(),
+            fields: BTreeMap::from([("text".to_string(), ".".to_string())]),
+        })
+        .unwrap();
+        let (rows, columns) = reader.read(doc, &["text"]).unwrap();
+        assert_eq!(rows, 3);
+        assert_eq!(columns[0][0], s("Example"));
+
+        assert!(reader.read(b"<feed><entry></feed>", &["text"]).is_err());
+    }
+
+    #[test]
+    fn test_invalid_paths() {
+        for path in [
+            "",
+            "/",
+            "a//",
+            "a/@b/c",
+            "a[1]",
+            "//@id",
+            "a/text()/b",
+            "a/../b",
+        ] {
+            assert!(
+                XmlReader::new(&XmlFormat {
+                    record_path: path.to_string(),
+                    fields: Default::default(),
+                })
+                .is_err(),
+                "{} should be invalid",
+                path
+            );
+        }
+    }
+}
@@ -7,8 +7,9 @@ use arroyo_types::{
 };
 use lazy_static::lazy_static;
 use prometheus::{
-    labels, register_histogram, register_int_counter_vec, register_int_gauge, Histogram,
-    HistogramOpts, IntCounter, IntCounterVec, IntGauge, Opts,
+    exponential_buckets, labels, register_histogram, register_histogram_vec,
+    register_int_counter_vec, register_int_gauge, Histogram, HistogramOpts, HistogramVec,
+    IntCounter, IntCounterVec, IntGauge, Opts,
 };
 
 pub fn gauge_for_task(
@@ -85,6 +86,38 @@ lazy_static! {
         &TASK_METRIC_LABELS
     )
     .unwrap();
+    pub static ref CHECKPOINT_ALIGNMENT_HISTOGRAM: HistogramVec = register_histogram_vec!(
+        "arroyo_worker_checkpoint_alignment_seconds",
+        "Time from the first to the last checkpoint barrier arriving at this subtask",
+        &TASK_METRIC_LABELS,
+        exponential_buckets(0.001, 4.0, 10).unwrap()
+    )
+    .unwrap();
+    pub static ref CHECKPOINT_ALIGNMENT_TIMEOUTS_COUNTER: IntCounterVec =
+        register_int_counter_vec!(
+            "arroyo_worker_checkpoint_alignment_timeouts",
+            "Count of checkpoints whose barriers didn't align at this subtask within the timeout",
+            &TASK_METRIC_LABELS
+        )
+        .unwrap();
+    pub static ref SOURCE_THROTTLED_COUNTER: IntCounterVec = register_int_counter_vec!(
+        "arroyo_worker_source_throttled_micros",
+        "Time this source subtask has spent throttled because of backpressure, in microseconds",
+        &TASK_METRIC_LABELS
+    )
+    .unwrap();
+    pub static ref TX_BACKPRESSURE_COUNTER: IntCounterVec = register_int_counter_vec!(
+        "arroyo_worker_tx_backpressure_micros",
+        "Time a tx queue has spent backpressured, in microseconds",
+        &[
+            "operator_id",
+            "subtask_idx",
+            "operator_name",
+            "next_node",
+            "next_node_idx"
+        ]
+    )
+    .unwrap();
 }
 
 #[derive(Copy, Clone, Eq, PartialEq, Hash)]
@@ -194,3 +227,33 @@ pub fn register_queue_gauge<T>(
         })
         .collect()
 }
+
+pub type QueueCounters = Vec<Vec<IntCounter>>;
+
+/// The counters for each of a task's output queues in `counter`, which is labeled by the task
+/// and then by the queue. Unlike registering a counter per queue, this doesn't fail when a task is
+/// restarted in the same process; its queues count on from where they left off.
+pub fn queue_counters<T>(
+    counter: &IntCounterVec,
+    task_info: &TaskInfo,
+    out_qs: &[Vec<T>],
+) -> QueueCounters {
+    let subtask_idx = task_info.task_index.to_string();
+    out_qs
+        .iter()
+        .enumerate()
+        .map(|(i, qs)| {
+            (0..qs.len())
+                .map(|j| {
+                    counter.with_label_values(&[
+                        &task_info.operator_id,
+                        &subtask_idx,
+                        &task_info.operator_name,
+                        &i.to_string(),
+                        &j.to_string(),
+                    ])
+                })
+                .collect()
+        })
+        .collect()
+}
@@ -57,6 +57,28 @@ pub trait Connector: Send {
 
     fn table_type(&self, config: Self::ProfileT, table: Self::TableT) -> ConnectionType;
 
+    /// The maximum number of subtasks that can usefully read from this source, for sources
+    /// that can't split their input (like a single connection or file)
+    #[allow(unused)]
+    fn max_source_parallelism(&self, config: Self::ProfileT, table: Self::TableT) -> Option<usize> {
+        None
+    }
+
+    /// Whether this source reads a finite input and finishes once it's exhausted; only
+    /// bounded sources can be used in bounded execution mode
+    #[allow(unused)]
+    fn is_bounded(&self, config: Self::ProfileT, table: Self::TableT) -> bool {
+        false
+    }
+
+    /// Whether this source can start reading at, and finish reading at, a point in time by
+    /// seeking through its input, as the stream and backfill of a hybrid table do (see
+    /// [crate::context::ArrowContext::source_start_at])
+    #[allow(unused)]
+    fn seeks_to_time(&self, config: Self::ProfileT, table: Self::TableT) -> bool {
+        false
+    }
+
     #[allow(unused)]
     fn get_schema(
         &self,
@@ -85,6 +107,38 @@ pub trait Connector: Send {
         rx
     }
 
+    /// Looks up how many partitions (or shards) this source's input is split into, which bounds
+    /// the number of subtasks that can usefully read from it; `None` for sources that don't
+    /// partition their input
+    #[allow(unused)]
+    fn source_partitions(
+        &self,
+        profile: Self::ProfileT,
+        table: Self::TableT,
+    ) -> oneshot::Receiver<anyhow::Result<Option<usize>>> {
+        let (tx, rx) = oneshot::channel();
+        tx.send(Ok(None)).unwrap();
+        rx
+    }
+
+    /// Reads up to `count` messages from the source, as they'd be passed to the deserializer,
+    /// for inferring its schema
+    #[allow(unused)]
+    fn sample(
+        &self,
+        profile: Self::ProfileT,
+        table: Self::TableT,
+        count: usize,
+    ) -> oneshot::Receiver<anyhow::Result<Vec<Vec<u8>>>> {
+        let (tx, rx) = oneshot::channel();
+        tx.send(Err(anyhow!(
+            "the {} connector does not support sampling messages",
+            self.name()
+        )))
+        .unwrap();
+        rx
+    }
+
     fn test(
         &self,
         name: &str,
@@ -138,6 +192,24 @@ pub trait ErasedConnector: Send {
         table: &serde_json::Value,
     ) -> Result<ConnectionType, serde_json::Error>;
 
+    fn max_source_parallelism(
+        &self,
+        config: &serde_json::Value,
+        table: &serde_json::Value,
+    ) -> Result<Option<usize>, serde_json::Error>;
+
+    fn is_bounded(
+        &self,
+        config: &serde_json::Value,
+        table: &serde_json::Value,
+    ) -> Result<bool, serde_json::Error>;
+
+    fn seeks_to_time(
+        &self,
+        config: &serde_json::Value,
+        table: &serde_json::Value,
+    ) -> Result<bool, serde_json::Error>;
+
     fn config_description(&self, s: &serde_json::Value) -> Result<String, serde_json::Error>;
 
     fn get_schema(
@@ -155,6 +227,19 @@ pub trait ErasedConnector: Send {
         profile: &serde_json::Value,
     ) -> Result<oneshot::Receiver<anyhow::Result<HashMap<String, Vec<String>>>>, serde_json::Error>;
 
+    fn source_partitions(
+        &self,
+        profile: &serde_json::Value,
+        table: &serde_json::Value,
+    ) -> Result<oneshot::Receiver<anyhow::Result<Option<usize>>>, serde_json::Error>;
+
+    fn sample(
+        &self,
+        profile: &serde_json::Value,
+        table: &serde_json::Value,
+        count: usize,
+    ) -> Result<oneshot::Receiver<anyhow::Result<Vec<Vec<u8>>>>, serde_json::Error>;
+
     fn test_profile(
         &self,
         profile: &serde_json::Value,
@@ -224,6 +309,30 @@ impl<C: Connector> ErasedConnector for C {
         Ok(self.table_type(self.parse_config(config)?, self.parse_table(table)?))
     }
 
+    fn max_source_parallelism(
+        &self,
+        config: &serde_json::Value,
+        table: &serde_json::Value,
+    ) -> Result<Option<usize>, serde_json::Error> {
+        Ok(self.max_source_parallelism(self.parse_config(config)?, self.parse_table(table)?))
+    }
+
+    fn is_bounded(
+        &self,
+        config: &serde_json::Value,
+        table: &serde_json::Value,
+    ) -> Result<bool, serde_json::Error> {
+        Ok(self.is_bounded(self.parse_config(config)?, self.parse_table(table)?))
+    }
+
+    fn seeks_to_time(
+        &self,
+        config: &serde_json::Value,
+        table: &serde_json::Value,
+    ) -> Result<bool, serde_json::Error> {
+        Ok(self.seeks_to_time(self.parse_config(config)?, self.parse_table(table)?))
+    }
+
     fn get_schema(
         &self,
         config: &serde_json::Value,
@@ -241,6 +350,23 @@ impl<C: Connector> ErasedConnector for C {
         Ok(self.get_autocomplete(self.parse_config(profile)?))
     }
 
+    fn source_partitions(
+        &self,
+        profile: &serde_json::Value,
+        table: &serde_json::Value,
+    ) -> Result<oneshot::Receiver<anyhow::Result<Option<usize>>>, serde_json::Error> {
+        Ok(self.source_partitions(self.parse_config(profile)?, self.parse_table(table)?))
+    }
+
+    fn sample(
+        &self,
+        profile: &serde_json::Value,
+        table: &serde_json::Value,
+        count: usize,
+    ) -> Result<oneshot::Receiver<anyhow::Result<Vec<Vec<u8>>>>, serde_json::Error> {
+        Ok(self.sample(self.parse_config(profile)?, self.parse_table(table)?, count))
+    }
+
     fn test_profile(
         &self,
         profile: &serde_json::Value,
@@ -1,28 +1,36 @@
-use crate::{server_for_hash_array, RateLimiter};
+use crate::RateLimiter;
+use anyhow::bail;
 use arrow::array::{make_builder, Array, ArrayBuilder, PrimitiveArray, RecordBatch};
-use arrow::compute::{partition, sort_to_indices, take};
+use arrow::compute::{concat_batches, partition, sort_to_indices, take};
 use arrow::datatypes::{SchemaRef, UInt64Type};
+use arrow::ipc::reader::StreamReader;
+use arrow::ipc::writer::StreamWriter;
 use arroyo_formats::de::{ArrowDeserializer, FieldValueType};
 use arroyo_formats::should_flush;
-use arroyo_metrics::{register_queue_gauge, QueueGauges, TaskCounters};
+use arroyo_metrics::{
+    queue_counters, register_queue_gauge, QueueCounters, QueueGauges, TaskCounters,
+    SOURCE_THROTTLED_COUNTER, TX_BACKPRESSURE_COUNTER,
+};
 use arroyo_rpc::config::config;
-use arroyo_rpc::df::ArroyoSchema;
-use arroyo_rpc::formats::{BadData, Format, Framing};
-use arroyo_rpc::grpc::rpc::{CheckpointMetadata, TableConfig, TaskCheckpointEventType};
+use arroyo_rpc::df::{server_for_hash_array, ArroyoSchema};
+use arroyo_rpc::formats::{BadData, Format, Framing, TimestampField};
+use arroyo_rpc::grpc::rpc::{
+    CheckpointMetadata, SourceLagUnit, StateEntry, TableConfig, TaskCheckpointEventType,
+};
 use arroyo_rpc::schema_resolver::SchemaResolver;
-use arroyo_rpc::{get_hasher, CompactionResult, ControlMessage, ControlResp};
+use arroyo_rpc::{get_hasher, CompactionResult, ControlMessage, ControlResp, StateQuery};
 use arroyo_state::tables::table_manager::TableManager;
-use arroyo_state::{BackingStore, StateBackend};
+use arroyo_state::{global_table_config, BackingStore, StateBackend};
 use arroyo_types::{
-    from_micros, ArrowMessage, CheckpointBarrier, SourceError, TaskInfo, UserError, Watermark,
+    from_micros, ArrowMessage, CheckpointBarrier, SignalMessage, SourceError, TaskInfo, UserError,
+    Watermark,
 };
 use datafusion::common::hash_utils;
 use rand::Rng;
-use std::collections::HashMap;
-use std::mem::size_of_val;
+use std::collections::{HashMap, VecDeque};
 use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
 use std::sync::Arc;
-use std::time::{Instant, SystemTime};
+use std::time::{Duration, Instant, SystemTime};
 use tokio::sync::mpsc::error::SendError;
 use tokio::sync::mpsc::{unbounded_channel, Receiver, Sender, UnboundedReceiver, UnboundedSender};
 use tokio::sync::Notify;
@@ -30,6 +38,14 @@ use tracing::warn;
 
 pub type QueueItem = ArrowMessage;
 
+/// The table in which operators checkpoint the data that the checkpoint's barriers overtook on
+/// their inputs, which is processed after the checkpoint is taken, and on restore
+pub const IN_FLIGHT_TABLE: &str = "__in_flight";
+
+// keys of the in-flight table: the parallelism of the operator, the subtask the data was sent
+// to, and its input and position among the data overtaken by the checkpoint's barriers
+type InFlightKey = (u32, u32, u32, u32);
+
 pub struct WatermarkHolder {
     // This is the last watermark with an actual value; this helps us keep track of the watermark we're at even
     // if we're currently idle
@@ -84,11 +100,26 @@ impl WatermarkHolder {
 }
 
 /// A wrapper for an UnboundedSender<QueueItem> that bounds by the number of rows within
-/// a batch rather than the number of batches
+/// a batch rather than the number of batches, and by the number of bytes queued. Only data
+/// counts against the bounds: signals are always accepted, so that an operator never blocks
+/// waiting for room to send a checkpoint barrier or stop.
+///
+/// Checkpoint barriers are sent on a separate control lane, which the receiver reads first, so
+/// that a backpressured edge doesn't hold up checkpoints: a barrier overtakes the data queued
+/// ahead of it, and the receiver is told how much it overtook so that that data can be
+/// checkpointed as in flight (see [BatchReceiver::recv]). Other signals, and the barriers of
+/// final checkpoints (which are kept aligned so that the pipeline can be rescaled from them),
+/// stay in order behind the data sent before them. Small batches that queue up are coalesced as
+/// they're received (see [BatchReceiver::recv_coalesced]).
 #[derive(Clone)]
 pub struct BatchSender {
     size: u32,
+    max_bytes: u64,
     tx: UnboundedSender<QueueItem>,
+    // barriers, each with the number of items sent on `tx` before it
+    control_tx: UnboundedSender<(u64, QueueItem)>,
+    // the number of items sent on `tx`
+    sent: Arc<AtomicU64>,
     queued_messages: Arc<AtomicU32>,
     queued_bytes: Arc<AtomicU64>,
     notify: Arc<Notify>,
@@ -98,7 +129,7 @@ pub struct BatchSender {
 fn message_count(item: &QueueItem, size: u32) -> u32 {
     match item {
         QueueItem::Data(d) => (d.num_rows() as u32).min(size),
-        QueueItem::Signal(_) => 1,
+        QueueItem::Signal(_) => 0,
     }
 }
 
@@ -106,21 +137,42 @@ fn message_count(item: &QueueItem, size: u32) -> u32 {
 fn message_bytes(item: &QueueItem) -> u64 {
     match item {
         QueueItem::Data(d) => d.get_array_memory_size() as u64,
-        QueueItem::Signal(s) => size_of_val(s) as u64,
+        QueueItem::Signal(_) => 0,
     }
 }
 
+/// Whether a message is sent on the control lane, overtaking the data queued ahead of it
+#[inline]
+fn overtakes(item: &QueueItem) -> bool {
+    matches!(item, QueueItem::Signal(SignalMessage::Barrier(barrier)) if !barrier.then_stop)
+}
+
 impl BatchSender {
     pub async fn send(&self, item: QueueItem) -> Result<(), SendError<QueueItem>> {
+        if overtakes(&item) {
+            return self.send_overtaking(item, 0);
+        }
+
+        if let QueueItem::Signal(_) = item {
+            // other signals skip the bounds, but are still queued behind the data sent before
+            // them
+            self.sent.fetch_add(1, Ordering::AcqRel);
+            return self.tx.send(item);
+        }
+
         // Ensure that every message is sendable, even if it's bigger than our max size
         let count = message_count(&item, self.size);
+        let bytes = message_bytes(&item);
         loop {
             if self.tx.is_closed() {
                 return Err(SendError(item));
             }
 
             let cur = self.queued_messages.load(Ordering::Acquire);
-            if cur as usize + count as usize <= self.size as usize {
+            // likewise, an empty queue accepts a batch of any size in bytes
+            let fits_bytes =
+                cur == 0 || self.queued_bytes.load(Ordering::Acquire) + bytes <= self.max_bytes;
+            if cur as usize + count as usize <= self.size as usize && fits_bytes {
                 match self.queued_messages.compare_exchange(
                     cur,
                     cur + count,
@@ -128,8 +180,8 @@ impl BatchSender {
                     Ordering::SeqCst,
                 ) {
                     Ok(_) => {
-                        self.queued_bytes
-                            .fetch_add(message_bytes(&item), Ordering::AcqRel);
+                        self.queued_bytes.fetch_add(bytes, Ordering::AcqRel);
+                        self.sent.fetch_add(1, Ordering::AcqRel);
                         return self.tx.send(item);
                     }
                     Err(_) => {
@@ -145,6 +197,21 @@ impl BatchSender {
         }
     }
 
+    /// Sends a barrier on the control lane, ahead of the data queued before it. Besides the
+    /// data already sent, the barrier overtakes the next `overtaken` messages, which were sent
+    /// before it by the task upstream and are still to come (as when it's forwarded from
+    /// another worker).
+    pub fn send_overtaking(
+        &self,
+        item: QueueItem,
+        overtaken: u64,
+    ) -> Result<(), SendError<QueueItem>> {
+        let sent_before = self.sent.load(Ordering::Acquire) + overtaken;
+        self.control_tx
+            .send((sent_before, item))
+            .map_err(|SendError((_, item))| SendError(item))
+    }
+
     pub fn capacity(&self) -> u32 {
         self.size
             .saturating_sub(self.queued_messages.load(Ordering::Relaxed))
@@ -157,39 +224,213 @@ impl BatchSender {
     pub fn size(&self) -> u32 {
         self.size
     }
+
+    pub fn max_bytes(&self) -> u64 {
+        self.max_bytes
+    }
+
+    /// The fraction of the queue's rows or of its bytes that are in use, whichever is greater
+    pub fn occupancy(&self) -> f64 {
+        let rows = self.queued_messages.load(Ordering::Relaxed) as f64 / self.size.max(1) as f64;
+        let bytes = self.queued_bytes() as f64 / self.max_bytes.max(1) as f64;
+        rows.max(bytes)
+    }
 }
 
+/// Batches smaller than this are coalesced with the data queued behind them by
+/// [BatchReceiver::recv_coalesced]
+pub const COALESCE_TARGET_ROWS: usize = 8192;
+
 pub struct BatchReceiver {
     size: u32,
     rx: UnboundedReceiver<QueueItem>,
+    control_rx: UnboundedReceiver<(u64, QueueItem)>,
+    // items taken from `rx` that haven't been returned yet
+    pending: VecDeque<QueueItem>,
+    // the number of items from `rx` that have been returned
+    returned: u64,
+    // a barrier taken from `control_rx` that hasn't been returned yet, with the number of items
+    // sent on `rx` before it
+    barrier: Option<(u64, QueueItem)>,
+    // the number of items still to be returned that the last barrier returned overtook
+    overtaken: u64,
     queued_messages: Arc<AtomicU32>,
     queued_bytes: Arc<AtomicU64>,
     notify: Arc<Notify>,
 }
 
 impl BatchReceiver {
+    /// Receives the next item. Barriers are returned as soon as they're sent, ahead of the data
+    /// sent before them, which is returned right after them; [BatchReceiver::overtaken] gives
+    /// how much of it there is.
     pub async fn recv(&mut self) -> Option<QueueItem> {
-        let item = self.rx.recv().await;
-        if let Some(item) = &item {
-            let count = message_count(item, self.size);
-            self.queued_messages.fetch_sub(count, Ordering::SeqCst);
-            self.queued_bytes
-                .fetch_sub(message_bytes(item), Ordering::AcqRel);
-            self.notify.notify_waiters();
+        loop {
+            if self.overtaken > 0 {
+                let item = match self.pending.pop_front() {
+                    Some(item) => item,
+                    None => {
+                        let item = self.rx.recv().await?;
+                        self.release(&item);
+                        item
+                    }
+                };
+                self.overtaken -= 1;
+                self.returned += 1;
+                return Some(item);
+            }
+
+            if self.barrier.is_none() {
+                self.barrier = self.control_rx.try_recv().ok();
+            }
+
+            if let Some((sent_before, barrier)) = self.barrier.take() {
+                self.overtaken = sent_before.saturating_sub(self.returned);
+                return Some(barrier);
+            }
+
+            if let Some(item) = self.pending.pop_front() {
+                self.returned += 1;
+                return Some(item);
+            }
+
+            tokio::select! {
+                biased;
+                Some(barrier) = self.control_rx.recv() => {
+                    self.barrier = Some(barrier);
+                }
+                item = self.rx.recv() => {
+                    // a barrier sent before this item will be found on the next pass, and
+                    // returned ahead of it
+                    let item = item?;
+                    self.release(&item);
+                    self.pending.push_back(item);
+                }
+            }
         }
-        item
+    }
+
+    /// The number of items still to be returned that were sent before the last barrier
+    /// returned by [BatchReceiver::recv], which overtook them
+    pub fn overtaken(&self) -> u64 {
+        self.overtaken
+    }
+
+    /// Receives the items that the last barrier returned by [BatchReceiver::recv] overtook
+    pub async fn recv_overtaken(&mut self) -> Vec<QueueItem> {
+        let mut items = Vec::with_capacity(self.overtaken as usize);
+        while self.overtaken > 0 {
+            let Some(item) = self.recv().await else {
+                break;
+            };
+            items.push(item);
+        }
+        items
+    }
+
+    /// Returns the next item to be received if it's already been sent and isn't behind a
+    /// barrier, without receiving it
+    fn peek(&mut self) -> Option<&QueueItem> {
+        if self.overtaken == 0 {
+            if self.barrier.is_none() {
+                self.barrier = self.control_rx.try_recv().ok();
+            }
+            if self.barrier.is_some() {
+                return None;
+            }
+        }
+
+        if self.pending.is_empty() {
+            let item = self.rx.try_recv().ok()?;
+            self.release(&item);
+            self.pending.push_back(item);
+
+            // a barrier sent before the item has to be returned ahead of it
+            if self.overtaken == 0 {
+                self.barrier = self.control_rx.try_recv().ok();
+                if self.barrier.is_some() {
+                    return None;
+                }
+            }
+        }
+
+        self.pending.front()
+    }
+
+    /// Receives the item returned by [BatchReceiver::peek]
+    fn take_peeked(&mut self) -> Option<QueueItem> {
+        let item = self.pending.pop_front()?;
+        self.returned += 1;
+        self.overtaken = self.overtaken.saturating_sub(1);
+        Some(item)
+    }
+
+    /// Receives the next item like [BatchReceiver::recv], but merges a small batch with the
+    /// batches that are already queued behind it, up to `target_rows` rows. Batches are
+    /// never merged across a signal, and receiving never waits for more data to arrive, so
+    /// this only coalesces when the receiver is behind (for example when a shuffle has split
+    /// its input into many small batches).
+    pub async fn recv_coalesced(&mut self, target_rows: usize) -> Option<QueueItem> {
+        let first = match self.recv().await? {
+            QueueItem::Data(batch) if batch.num_rows() < target_rows => batch,
+            item => return Some(item),
+        };
+
+        let mut rows = first.num_rows();
+        let mut batches = vec![first];
+        while rows < target_rows {
+            match self.peek() {
+                Some(QueueItem::Data(batch))
+                    if batch.schema() == batches[0].schema()
+                        && rows + batch.num_rows() <= target_rows =>
+                {
+                    rows += batch.num_rows();
+                }
+                _ => break,
+            }
+
+            let Some(QueueItem::Data(batch)) = self.take_peeked() else {
+                unreachable!("peeked a batch");
+            };
+            batches.push(batch);
+        }
+
+        if batches.len() == 1 {
+            return batches.pop().map(QueueItem::Data);
+        }
+
+        Some(QueueItem::Data(
+            concat_batches(&batches[0].schema(), &batches)
+                .expect("batches in a queue have the same schema"),
+        ))
+    }
+
+    fn release(&self, item: &QueueItem) {
+        let count = message_count(item, self.size);
+        self.queued_messages.fetch_sub(count, Ordering::SeqCst);
+        self.queued_bytes
+            .fetch_sub(message_bytes(item), Ordering::AcqRel);
+        self.notify.notify_waiters();
     }
 }
 
 pub fn batch_bounded(size: u32) -> (BatchSender, BatchReceiver) {
+    batch_bounded_with_max_bytes(size, u64::MAX)
+}
+
+/// Creates a queue that holds at most `size` rows and `max_bytes` bytes of data
+pub fn batch_bounded_with_max_bytes(size: u32, max_bytes: u64) -> (BatchSender, BatchReceiver) {
     let (tx, rx) = unbounded_channel();
+    let (control_tx, control_rx) = unbounded_channel();
     let notify = Arc::new(Notify::new());
     let queued_messages = Arc::new(AtomicU32::new(0));
     let queued_bytes = Arc::new(AtomicU64::new(0));
     (
         BatchSender {
             size,
+            max_bytes,
             tx,
+            control_tx,
+            sent: Arc::new(AtomicU64::new(0)),
             queued_messages: queued_messages.clone(),
             queued_bytes: queued_bytes.clone(),
             notify: notify.clone(),
@@ -197,6 +438,11 @@ pub fn batch_bounded(size: u32) -> (BatchSender, BatchReceiver) {
         BatchReceiver {
             size,
             rx,
+            control_rx,
+            pending: VecDeque::new(),
+            returned: 0,
+            barrier: None,
+            overtaken: 0,
             notify,
             queued_bytes,
             queued_messages,
@@ -256,6 +502,22 @@ pub struct ArrowContext {
     error_rate_limiter: RateLimiter,
     deserializer: Option<ArrowDeserializer>,
     pub table_manager: TableManager,
+    /// for sources, positions to start reading partitions (or shards) from, keyed by partition,
+    /// which take precedence over the ones restored from the checkpoint
+    pub source_offset_overrides: HashMap<String, String>,
+    /// for sources, the field of the data to take event times from as it's deserialized
+    pub timestamp_field: Option<TimestampField>,
+    /// for the stream of a hybrid table, the time to start reading at when there's no state
+    /// to restore, in place of the source's configured starting point
+    pub source_start_at: Option<SystemTime>,
+    /// for the backfill of a hybrid table, the time to finish reading at: the source finishes
+    /// once it has read everything before it
+    pub source_end_at: Option<SystemTime>,
+    /// how long to wait for checkpoint barriers to align across the inputs before failing
+    pub checkpoint_alignment_timeout: Option<Duration>,
+    /// the data that the barriers of the checkpoint being aligned overtook, with the inputs it
+    /// was read from, which is written to the checkpoint and processed once it's been taken
+    pub in_flight: Vec<(usize, QueueItem)>,
 }
 
 #[derive(Clone)]
@@ -278,6 +540,23 @@ impl ErrorReporter {
     }
 }
 
+/// Slows a source down once one of its output queues has stayed backpressured for `after`, by
+/// waiting `delay` before sending each batch until the backpressure clears
+#[derive(Debug, Clone, Copy)]
+pub struct SourceThrottle {
+    pub after: Duration,
+    pub delay: Duration,
+}
+
+/// Whether an output queue is backpressured (occupied past the configured threshold), and since
+/// when. Queues are only observed when data is sent to them, so the time between observations
+/// is attributed to the state seen at the first.
+#[derive(Clone, Copy)]
+struct EdgeBackpressure {
+    since: Option<Instant>,
+    observed_at: Instant,
+}
+
 #[derive(Clone)]
 pub struct ArrowCollector {
     task_info: Arc<TaskInfo>,
@@ -287,6 +566,14 @@ pub struct ArrowCollector {
     tx_queue_rem_gauges: QueueGauges,
     tx_queue_size_gauges: QueueGauges,
     tx_queue_bytes_gauges: QueueGauges,
+    backpressure_threshold: f64,
+    backpressure_sample_interval: Duration,
+    // when the output queues were last sampled for backpressure
+    backpressure_sampled_at: Option<Instant>,
+    tx_backpressure: Vec<Vec<EdgeBackpressure>>,
+    tx_backpressured_gauges: QueueGauges,
+    tx_backpressure_counters: QueueCounters,
+    source_throttle: Option<SourceThrottle>,
 }
 
 fn repartition<'a>(
@@ -346,7 +633,161 @@ fn repartition<'a>(
 }
 
 impl ArrowCollector {
+    pub fn new(
+        task_info: Arc<TaskInfo>,
+        out_schema: Option<ArroyoSchema>,
+        projection: Option<Vec<usize>>,
+        out_qs: Vec<Vec<BatchSender>>,
+    ) -> Self {
+        // queue sizes may be overridden per pipeline, so use the size of our actual queues
+        let queue_size = out_qs
+            .iter()
+            .flatten()
+            .next()
+            .map(|q| q.size())
+            .unwrap_or(config().worker.queue_size);
+
+        let tx_queue_size_gauges = register_queue_gauge(
+            "arroyo_worker_tx_queue_size",
+            "Size of a tx queue",
+            &task_info,
+            &out_qs,
+            queue_size as i64,
+        );
+
+        let tx_queue_rem_gauges = register_queue_gauge(
+            "arroyo_worker_tx_queue_rem",
+            "Remaining space in a tx queue",
+            &task_info,
+            &out_qs,
+            queue_size as i64,
+        );
+
+        let tx_queue_bytes_gauges = register_queue_gauge(
+            "arroyo_worker_tx_bytes",
+            "Number of bytes queued in a tx queue",
+            &task_info,
+            &out_qs,
+            0,
+        );
+
+        let tx_backpressured_gauges = register_queue_gauge(
+            "arroyo_worker_tx_backpressured",
+            "Whether a tx queue is currently backpressured (1) or not (0)",
+            &task_info,
+            &out_qs,
+            0,
+        );
+
+        let tx_backpressure_counters =
+            queue_counters(&TX_BACKPRESSURE_COUNTER, &task_info, &out_qs);
+
+        let now = Instant::now();
+        let tx_backpressure = out_qs
+            .iter()
+            .map(|qs| {
+                vec![
+                    EdgeBackpressure {
+                        since: None,
+                        observed_at: now,
+                    };
+                    qs.len()
+                ]
+            })
+            .collect();
+
+        Self {
+            task_info,
+            out_schema,
+            projection,
+            out_qs,
+            tx_queue_rem_gauges,
+            tx_queue_size_gauges,
+            tx_queue_bytes_gauges,
+            backpressure_threshold: config().worker.backpressure.threshold,
+            backpressure_sample_interval: *config().worker.backpressure.sample_interval,
+            backpressure_sampled_at: None,
+            tx_backpressure,
+            tx_backpressured_gauges,
+            tx_backpressure_counters,
+            source_throttle: None,
+        }
+    }
+
+    /// Throttles the data this collector sends while its output queues stay backpressured; set
+    /// by the engine for sources
+    pub fn set_source_throttle(&mut self, throttle: SourceThrottle) {
+        self.source_throttle = Some(throttle);
+    }
+
+    fn observe_backpressure(&mut self, i: usize, partition: usize, now: Instant) {
+        let backpressured = self.out_qs[i][partition].occupancy() >= self.backpressure_threshold;
+        let edge = &mut self.tx_backpressure[i][partition];
+
+        if edge.since.is_some() {
+            let elapsed = now.saturating_duration_since(edge.observed_at).as_micros() as u64;
+            self.tx_backpressure_counters[i][partition].inc_by(elapsed);
+        }
+
+        edge.since = backpressured.then(|| edge.since.unwrap_or(now));
+        edge.observed_at = now;
+
+        self.tx_backpressured_gauges[i][partition]
+            .iter()
+            .for_each(|g| g.set(backpressured as i64));
+    }
+
+    /// Samples whether each output queue is backpressured, unless they were sampled within the
+    /// sample interval; this walks every queue, so it isn't done for every batch
+    fn sample_backpressure(&mut self, now: Instant) {
+        if self
+            .backpressure_sampled_at
+            .is_some_and(|at| now.saturating_duration_since(at) < self.backpressure_sample_interval)
+        {
+            return;
+        }
+        self.backpressure_sampled_at = Some(now);
+
+        for i in 0..self.out_qs.len() {
+            for partition in 0..self.out_qs[i].len() {
+                self.observe_backpressure(i, partition, now);
+            }
+        }
+    }
+
+    /// If this is a throttled source, waits while one of its output queues has been
+    /// backpressured for longer than the throttle allows
+    async fn throttle(&mut self) {
+        let Some(throttle) = self.source_throttle else {
+            return;
+        };
+
+        let now = Instant::now();
+        self.sample_backpressure(now);
+
+        let backpressured_for = self
+            .tx_backpressure
+            .iter()
+            .flatten()
+            .filter_map(|e| e.since)
+            .map(|since| now.saturating_duration_since(since))
+            .max();
+
+        if backpressured_for.is_some_and(|d| d >= throttle.after) {
+            tokio::time::sleep(throttle.delay).await;
+            SOURCE_THROTTLED_COUNTER
+                .with_label_values(&[
+                    &self.task_info.operator_id,
+                    &self.task_info.task_index.to_string(),
+                    &self.task_info.operator_name,
+                ])
+                .inc_by(throttle.delay.as_micros() as u64);
+        }
+    }
+
     pub async fn collect(&mut self, record: RecordBatch) {
+        self.throttle().await;
+
         TaskCounters::MessagesSent
             .for_task(&self.task_info, |c| c.inc_by(record.num_rows() as u64));
         TaskCounters::BatchesSent.for_task(&self.task_info, |c| c.inc());
@@ -400,6 +841,8 @@ impl ArrowCollector {
                     .for_each(|g| g.set(out_q[partition].queued_bytes() as i64));
             }
         }
+
+        self.sample_backpressure(Instant::now());
     }
 
     pub async fn broadcast(&mut self, message: ArrowMessage) {
@@ -456,30 +899,6 @@ impl ArrowContext {
             (None, None)
         };
 
-        let tx_queue_size_gauges = register_queue_gauge(
-            "arroyo_worker_tx_queue_size",
-            "Size of a tx queue",
-            &task_info,
-            &out_qs,
-            config().worker.queue_size as i64,
-        );
-
-        let tx_queue_rem_gauges = register_queue_gauge(
-            "arroyo_worker_tx_queue_rem",
-            "Remaining space in a tx queue",
-            &task_info,
-            &out_qs,
-            config().worker.queue_size as i64,
-        );
-
-        let tx_queue_bytes_gauges = register_queue_gauge(
-            "arroyo_worker_tx_bytes",
-            "Number of bytes queued in a tx queue",
-            &task_info,
-            &out_qs,
-            0,
-        );
-
         let task_info = Arc::new(task_info);
 
         // initialize counters so that tasks that never produce data still report 0
@@ -488,10 +907,38 @@ impl ArrowContext {
             m.for_task(&task_info, |_| {});
         }
 
-        let table_manager =
-            TableManager::new(task_info.clone(), tables, control_tx.clone(), metadata)
-                .await
-                .expect("should be able to create TableManager");
+        let mut tables = tables;
+        if !in_schemas.is_empty() {
+            tables.extend(global_table_config(
+                IN_FLIGHT_TABLE,
+                "data in flight to the operator when it was checkpointed",
+            ));
+        }
+
+        let table_manager = match TableManager::new(
+            task_info.clone(),
+            tables,
+            control_tx.clone(),
+            metadata,
+        )
+        .await
+        {
+            Ok(table_manager) => table_manager,
+            Err(e) => {
+                // report the cause (for example, a query edit that changed the operator's
+                // state incompatibly) before failing the task
+                control_tx
+                    .send(ControlResp::Error {
+                        operator_id: task_info.operator_id.clone(),
+                        task_index: task_info.task_index,
+                        message: "Failed to restore operator state".to_string(),
+                        details: format!("{:?}", e),
+                    })
+                    .await
+                    .ok();
+                panic!("should be able to create TableManager: {:?}", e);
+            }
+        };
 
         Self {
             task_info: task_info.clone(),
@@ -503,15 +950,12 @@ impl ArrowContext {
             ]),
             in_schemas,
             out_schema: out_schema.clone(),
-            collector: ArrowCollector {
-                task_info: task_info.clone(),
-                out_qs,
-                tx_queue_rem_gauges,
-                tx_queue_size_gauges,
-                tx_queue_bytes_gauges,
-                out_schema: out_schema.clone(),
+            collector: ArrowCollector::new(
+                task_info.clone(),
+                out_schema.clone(),
                 projection,
-            },
+                out_qs,
+            ),
             error_reporter: ErrorReporter {
                 tx: control_tx,
                 task_info,
@@ -521,7 +965,94 @@ impl ArrowContext {
             deserializer: None,
             buffered_error: None,
             table_manager,
+            source_offset_overrides: HashMap::new(),
+            timestamp_field: None,
+            source_start_at: None,
+            source_end_at: None,
+            checkpoint_alignment_timeout: None,
+            in_flight: vec![],
+        }
+    }
+
+    /// Writes the data that the barriers of the checkpoint about to be taken overtook to its
+    /// state, to be processed by this subtask if the pipeline is restored from it
+    pub async fn checkpoint_in_flight(&mut self) -> anyhow::Result<()> {
+        if self.in_schemas.is_empty() {
+            return Ok(());
+        }
+
+        let parallelism = self.task_info.parallelism as u32;
+        let task_index = self.task_info.task_index as u32;
+        let mut batches = vec![];
+        for (position, (input, item)) in self.in_flight.iter().enumerate() {
+            // signals, like watermarks, aren't restored
+            if let ArrowMessage::Data(batch) = item {
+                let mut writer = StreamWriter::try_new(vec![], &batch.schema())?;
+                writer.write(batch)?;
+                batches.push((
+                    (parallelism, task_index, *input as u32, position as u32),
+                    writer.into_inner()?,
+                ));
+            }
+        }
+
+        let table = self
+            .table_manager
+            .get_global_keyed_state::<InFlightKey, Vec<u8>>(IN_FLIGHT_TABLE)
+            .await?;
+        // the table only holds what's inserted in each epoch, so the data of earlier
+        // checkpoints doesn't need to be kept
+        table.clear();
+        for (key, data) in batches {
+            table.insert(key, data).await;
+        }
+
+        Ok(())
+    }
+
+    /// Takes the data that was in flight to this subtask when the checkpoint it was restored
+    /// from was taken, in the order it was sent, to be processed ahead of its inputs
+    pub async fn take_restored_in_flight(&mut self) -> anyhow::Result<Vec<(usize, RecordBatch)>> {
+        if self.in_schemas.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let parallelism = self.task_info.parallelism as u32;
+        let task_index = self.task_info.task_index as u32;
+        let table = self
+            .table_manager
+            .get_global_keyed_state::<InFlightKey, Vec<u8>>(IN_FLIGHT_TABLE)
+            .await?;
+
+        if let Some((old_parallelism, ..)) =
+            table.get_all().keys().find(|(p, ..)| *p != parallelism)
+        {
+            bail!(
+                "the checkpoint has data in flight to {} subtasks of the operator, which can't be \
+                restored at parallelism {}; restore it at its original parallelism, or from a \
+                checkpoint taken when stopping the pipeline",
+                old_parallelism,
+                parallelism
+            );
         }
+
+        let mut entries: Vec<_> = table
+            .get_all()
+            .iter()
+            .filter(|((_, subtask, _, _), _)| *subtask == task_index)
+            .map(|((_, _, input, position), data)| (*position, *input as usize, data))
+            .collect();
+        entries.sort_by_key(|(position, ..)| *position);
+
+        let mut batches = vec![];
+        for (_, input, data) in entries {
+            for batch in StreamReader::try_new(data.as_slice(), None)? {
+                batches.push((input, batch?));
+            }
+        }
+
+        table.clear();
+        Ok(batches)
     }
 
     pub fn watermark(&self) -> Option<Watermark> {
@@ -540,10 +1071,17 @@ impl ArrowContext {
         if self.buffer.as_ref().unwrap().size() > 0 {
             let buffer = self.buffer.take().unwrap();
             let batch = buffer.finish();
-            self.collector.collect(batch).await;
             self.buffer = Some(ContextBuffer::new(
                 self.out_schema.as_ref().map(|t| t.schema.clone()).unwrap(),
             ));
+
+            match self.deserializer.as_ref() {
+                Some(deserializer) => match deserializer.extract_timestamp(batch) {
+                    Ok(batch) => self.collector.collect(batch).await,
+                    Err(e) => self.collect_source_errors(vec![e]).await?,
+                },
+                None => self.collector.collect(batch).await,
+            }
         }
 
         if let Some(deserializer) = self.deserializer.as_mut() {
@@ -605,6 +1143,27 @@ impl ArrowContext {
             .unwrap();
     }
 
+    /// Reports how far this source subtask is behind the head of its splits, in total and for
+    /// each split that's assigned to subtasks by lag, which the controller uses to decide when
+    /// to rebalance splits across subtasks
+    pub async fn report_source_lag(
+        &mut self,
+        lag: u64,
+        split_lag: HashMap<String, u64>,
+        unit: SourceLagUnit,
+    ) {
+        self.control_tx
+            .send(ControlResp::SourceLag {
+                operator_id: self.task_info.operator_id.clone(),
+                task_index: self.task_info.task_index,
+                lag,
+                split_lag,
+                unit,
+            })
+            .await
+            .unwrap();
+    }
+
     pub async fn send_checkpoint_event(
         &mut self,
         barrier: CheckpointBarrier,
@@ -632,6 +1191,28 @@ impl ArrowContext {
             .expect("should be able to load compacted");
     }
 
+    pub async fn query_state(&mut self, query: StateQuery) {
+        let subtask_index = self.task_info.task_index as u32;
+        let result = self
+            .table_manager
+            .query_state(&query.table, query.key.as_deref(), query.limit)
+            .map(|entries| {
+                entries
+                    .into_iter()
+                    .map(|(key, value)| StateEntry {
+                        subtask_index,
+                        key,
+                        value,
+                    })
+                    .collect()
+            })
+            .map_err(|e| e.to_string());
+
+        if query.tx.send(result).await.is_err() {
+            warn!("state query was dropped before it was answered");
+        }
+    }
+
     pub fn initialize_deserializer(
         &mut self,
         format: Format,
@@ -642,12 +1223,15 @@ impl ArrowContext {
             panic!("Deserialize already initialized");
         }
 
-        self.deserializer = Some(ArrowDeserializer::new(
-            format,
-            self.out_schema.as_ref().expect("no out schema").clone(),
-            framing,
-            bad_data.unwrap_or_default(),
-        ));
+        self.deserializer = Some(
+            ArrowDeserializer::new(
+                format,
+                self.out_schema.as_ref().expect("no out schema").clone(),
+                framing,
+                bad_data.unwrap_or_default(),
+            )
+            .with_timestamp_field(self.timestamp_field.clone()),
+        );
     }
 
     pub fn initialize_deserializer_with_resolver(
@@ -657,13 +1241,16 @@ impl ArrowContext {
         bad_data: Option<BadData>,
         schema_resolver: Arc<dyn SchemaResolver + Sync>,
     ) {
-        self.deserializer = Some(ArrowDeserializer::with_schema_resolver(
-            format,
-            framing,
-            self.out_schema.as_ref().expect("no out schema").clone(),
-            bad_data.unwrap_or_default(),
-            schema_resolver,
-        ));
+        self.deserializer = Some(
+            ArrowDeserializer::with_schema_resolver(
+                format,
+                framing,
+                self.out_schema.as_ref().expect("no out schema").clone(),
+                bad_data.unwrap_or_default(),
+                schema_resolver,
+            )
+            .with_timestamp_field(self.timestamp_field.clone()),
+        );
     }
 
     pub async fn deserialize_slice(
@@ -815,39 +1402,12 @@ mod tests {
 
         let out_qs = vec![vec![tx1, tx2]];
 
-        let tx_queue_size_gauges = register_queue_gauge(
-            "arroyo_worker_tx_queue_size",
-            "Size of a tx queue",
-            &task_info,
-            &out_qs,
-            0,
-        );
-
-        let tx_queue_rem_gauges = register_queue_gauge(
-            "arroyo_worker_tx_queue_rem",
-            "Remaining space in a tx queue",
-            &task_info,
-            &out_qs,
-            0,
-        );
-
-        let tx_queue_bytes_gauges = register_queue_gauge(
-            "arroyo_worker_tx_bytes",
-            "Number of bytes queued in a tx queue",
-            &task_info,
-            &out_qs,
-            0,
-        );
-
-        let mut collector = ArrowCollector {
+        let mut collector = ArrowCollector::new(
             task_info,
-            out_schema: Some(ArroyoSchema::new_keyed(schema, 1, vec![0])),
-            projection: None,
+            Some(ArroyoSchema::new_keyed(schema, 1, vec![0])),
+            None,
             out_qs,
-            tx_queue_rem_gauges,
-            tx_queue_size_gauges,
-            tx_queue_bytes_gauges,
-        };
+        );
 
         collector.collect(record).await;
 
@@ -895,6 +1455,233 @@ mod tests {
         assert_eq!(tx.capacity(), 8);
     }
 
+    #[tokio::test]
+    async fn test_backpressure() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("x", DataType::Int64, false),
+            Field::new(
+                "time",
+                DataType::Timestamp(TimeUnit::Nanosecond, None),
+                false,
+            ),
+        ]));
+        let batch = |rows: usize| {
+            RecordBatch::try_new(
+                schema.clone(),
+                vec![
+                    Arc::new(Int64Array::from(vec![1; rows])),
+                    Arc::new(TimestampNanosecondArray::from(vec![0; rows])),
+                ],
+            )
+            .unwrap()
+        };
+
+        let (tx, mut rx) = batch_bounded(8);
+        let task_info = Arc::new(TaskInfo {
+            job_id: "test-job".to_string(),
+            operator_name: "test-operator".to_string(),
+            operator_id: "test-backpressure-1".to_string(),
+            task_index: 0,
+            parallelism: 1,
+            key_range: 0..=1,
+        });
+
+        let mut collector = ArrowCollector::new(
+            task_info,
+            Some(ArroyoSchema::new_unkeyed(schema.clone(), 1)),
+            None,
+            vec![vec![tx]],
+        );
+        collector.backpressure_sample_interval = Duration::ZERO;
+        collector.set_source_throttle(SourceThrottle {
+            after: Duration::ZERO,
+            delay: Duration::from_millis(1),
+        });
+
+        collector.collect(batch(7)).await;
+        assert_eq!(collector.out_qs[0][0].occupancy(), 7.0 / 8.0);
+        assert!(collector.tx_backpressure[0][0].since.is_some());
+
+        rx.recv().await.unwrap();
+        collector.collect(batch(1)).await;
+        assert!(collector.tx_backpressure[0][0].since.is_none());
+
+        // queues are only sampled once per interval
+        collector.backpressure_sample_interval = Duration::from_secs(3600);
+        collector.collect(batch(6)).await;
+        assert_eq!(collector.out_qs[0][0].occupancy(), 7.0 / 8.0);
+        assert!(collector.tx_backpressure[0][0].since.is_none());
+
+        // a restarted task counts on in the same metrics
+        let (tx, _rx) = batch_bounded(8);
+        let restarted = ArrowCollector::new(
+            collector.task_info.clone(),
+            Some(ArroyoSchema::new_unkeyed(schema.clone(), 1)),
+            None,
+            vec![vec![tx]],
+        );
+        restarted.tx_backpressure_counters[0][0].inc_by(1);
+        assert_eq!(
+            collector.tx_backpressure_counters[0][0].get(),
+            restarted.tx_backpressure_counters[0][0].get()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_signals_skip_queue_bounds() {
+        let (tx, mut rx) = batch_bounded(4);
+        let msg = RecordBatch::try_new(
+            Arc::new(Schema::new(vec![Field::new("x", DataType::Int64, false)])),
+            vec![Arc::new(Int64Array::from(vec![1, 2, 3, 4]))],
+        )
+        .unwrap();
+
+        tx.send(ArrowMessage::Data(msg.clone())).await.unwrap();
+        assert_eq!(tx.capacity(), 0);
+
+        // the queue is full of data, but a barrier can still be sent without waiting
+        let barrier = ArrowMessage::Signal(SignalMessage::Barrier(CheckpointBarrier {
+            epoch: 1,
+            min_epoch: 0,
+            timestamp: SystemTime::now(),
+            then_stop: false,
+        }));
+        tokio::time::timeout(Duration::from_millis(100), tx.send(barrier.clone()))
+            .await
+            .expect("barrier should not wait for room in the queue")
+            .unwrap();
+        assert_eq!(tx.capacity(), 0);
+
+        // and it overtakes the data that was sent first, which is received right after it
+        assert_eq!(rx.recv().await.unwrap(), barrier);
+        assert_eq!(rx.overtaken(), 1);
+        assert_eq!(tx.capacity(), 0);
+        assert_eq!(
+            rx.recv_overtaken().await,
+            vec![ArrowMessage::Data(msg.clone())]
+        );
+        assert_eq!(rx.overtaken(), 0);
+        assert_eq!(tx.capacity(), 4);
+        assert_eq!(tx.queued_bytes(), 0);
+
+        // other signals, and the barriers of final checkpoints, stay behind the data sent
+        // before them
+        let watermark = ArrowMessage::Signal(SignalMessage::Watermark(Watermark::EventTime(
+            SystemTime::now(),
+        )));
+        let final_barrier = ArrowMessage::Signal(SignalMessage::Barrier(CheckpointBarrier {
+            epoch: 2,
+            min_epoch: 0,
+            timestamp: SystemTime::now(),
+            then_stop: true,
+        }));
+        tx.send(ArrowMessage::Data(msg.clone())).await.unwrap();
+        tx.send(watermark.clone()).await.unwrap();
+        tx.send(final_barrier.clone()).await.unwrap();
+
+        assert_eq!(rx.recv().await.unwrap(), ArrowMessage::Data(msg));
+        assert_eq!(rx.recv().await.unwrap(), watermark);
+        assert_eq!(rx.recv().await.unwrap(), final_barrier);
+        assert_eq!(rx.overtaken(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_queue_byte_limit() {
+        let msg = RecordBatch::try_new(
+            Arc::new(Schema::new(vec![Field::new("x", DataType::Int64, false)])),
+            vec![Arc::new(Int64Array::from(vec![1, 2, 3, 4]))],
+        )
+        .unwrap();
+        let bytes = msg.get_array_memory_size() as u64;
+
+        // room for plenty of rows, but only one batch's worth of bytes
+        let (tx, mut rx) = batch_bounded_with_max_bytes(1024, bytes);
+
+        tx.send(ArrowMessage::Data(msg.clone())).await.unwrap();
+        assert_eq!(tx.queued_bytes(), bytes);
+        assert_eq!(tx.occupancy(), 1.0);
+
+        assert!(
+            tokio::time::timeout(
+                Duration::from_millis(100),
+                tx.send(ArrowMessage::Data(msg.clone()))
+            )
+            .await
+            .is_err(),
+            "send should wait for the queued bytes to be received"
+        );
+
+        rx.recv().await.unwrap();
+        assert_eq!(tx.queued_bytes(), 0);
+
+        tokio::time::timeout(
+            Duration::from_millis(100),
+            tx.send(ArrowMessage::Data(msg.clone())),
+        )
+        .await
+        .expect("send should not wait once the queue is empty")
+        .unwrap();
+        rx.recv().await.unwrap();
+
+        // an empty queue accepts a batch that's larger than the limit
+        let (tx, mut rx) = batch_bounded_with_max_bytes(1024, 1);
+        tokio::time::timeout(
+            Duration::from_millis(100),
+            tx.send(ArrowMessage::Data(msg.clone())),
+        )
+        .await
+        .expect("an empty queue should accept any batch")
+        .unwrap();
+        assert_eq!(rx.recv().await.unwrap(), ArrowMessage::Data(msg));
+    }
+
+    #[tokio::test]
+    async fn test_batch_coalescing() {
+        let schema = Arc::new(Schema::new(vec![Field::new("x", DataType::Int64, false)]));
+        let batch = |values: Vec<i64>| {
+            ArrowMessage::Data(
+                RecordBatch::try_new(schema.clone(), vec![Arc::new(Int64Array::from(values))])
+                    .unwrap(),
+            )
+        };
+        let watermark = ArrowMessage::Signal(SignalMessage::Watermark(Watermark::Idle));
+
+        let (tx, mut rx) = batch_bounded(64);
+        tx.send(batch(vec![1, 2, 3])).await.unwrap();
+        tx.send(batch(vec![4, 5])).await.unwrap();
+        tx.send(batch(vec![6, 7, 8, 9])).await.unwrap();
+        tx.send(watermark.clone()).await.unwrap();
+        tx.send(batch(vec![10])).await.unwrap();
+
+        // batches are merged up to the target size, in order
+        assert_eq!(
+            rx.recv_coalesced(6).await.unwrap(),
+            batch(vec![1, 2, 3, 4, 5])
+        );
+
+        // but never across a signal
+        assert_eq!(rx.recv_coalesced(6).await.unwrap(), batch(vec![6, 7, 8, 9]));
+        assert_eq!(rx.recv_coalesced(6).await.unwrap(), watermark);
+        assert_eq!(rx.recv_coalesced(6).await.unwrap(), batch(vec![10]));
+
+        assert_eq!(tx.capacity(), 64);
+        assert_eq!(tx.queued_bytes(), 0);
+
+        // batches at or above the target are passed through
+        tx.send(batch(vec![1, 2])).await.unwrap();
+        tx.send(batch(vec![3])).await.unwrap();
+        assert_eq!(rx.recv_coalesced(2).await.unwrap(), batch(vec![1, 2]));
+
+        // and receiving doesn't wait for more data to arrive
+        assert_eq!(
+            tokio::time::timeout(Duration::from_millis(100), rx.recv_coalesced(8))
+                .await
+                .unwrap()
+                .unwrap(),
+            batch(vec![3])
+        );
+    }
+
     #[tokio::test]
     async fn test_panic_propagation() {
         let (tx, mut rx) = batch_bounded(8);
@@ -6,9 +6,8 @@ use std::time::{Duration, Instant};
 use std::{collections::HashSet, time::SystemTime};
 
 use crate::inq_reader::InQReader;
-use arrow::array::types::{TimestampNanosecondType, UInt64Type};
-use arrow::array::{Array, PrimitiveArray, RecordBatch, UInt64Array};
-use arrow::compute::kernels::numeric::{div, rem};
+use arrow::array::types::TimestampNanosecondType;
+use arrow::array::{Array, PrimitiveArray, RecordBatch};
 use arroyo_types::{ArrowMessage, CheckpointBarrier, Data, SignalMessage, TaskInfoRef};
 use bincode::{Decode, Encode};
 
@@ -21,25 +20,13 @@ pub mod connector;
 pub mod context;
 pub mod inq_reader;
 pub mod operator;
+pub mod two_phase_committer;
 pub mod udfs;
 
 pub trait TimerT: Data + PartialEq + Eq + 'static {}
 
 impl<T: Data + PartialEq + Eq + 'static> TimerT for T {}
 
-pub fn server_for_hash_array(
-    hash: &PrimitiveArray<UInt64Type>,
-    n: usize,
-) -> anyhow::Result<PrimitiveArray<UInt64Type>> {
-    let range_size = u64::MAX / (n as u64);
-    let range_scalar = UInt64Array::new_scalar(range_size);
-    let server_scalar = UInt64Array::new_scalar(n as u64);
-    let division = div(hash, &range_scalar)?;
-    let mod_array = rem(&division, &server_scalar)?;
-    let result: &PrimitiveArray<UInt64Type> = mod_array.as_any().downcast_ref().unwrap();
-    Ok(result.clone())
-}
-
 pub enum SourceFinishType {
     // stop messages should be propagated through the dataflow
     Graceful,
@@ -70,6 +57,8 @@ pub enum ControlOutcome {
 pub struct CheckpointCounter {
     inputs: Vec<Option<u32>>,
     counter: Option<usize>,
+    // when the first barrier of the checkpoint being aligned arrived
+    aligning_since: Option<Instant>,
 }
 
 impl CheckpointCounter {
@@ -77,6 +66,7 @@ impl CheckpointCounter {
         CheckpointCounter {
             inputs: vec![None; size],
             counter: None,
+            aligning_since: None,
         }
     }
 
@@ -88,6 +78,20 @@ impl CheckpointCounter {
         self.inputs.iter().all(|x| x.is_none())
     }
 
+    /// When the first barrier of the checkpoint currently being aligned arrived
+    pub fn aligning_since(&self) -> Option<Instant> {
+        self.aligning_since
+    }
+
+    /// When the checkpoint currently being aligned times out, if the pipeline has an alignment
+    /// `timeout`. Inputs are never unblocked before their checkpoint is taken, as data read from
+    /// them would end up in its state and be processed again on restore.
+    pub fn alignment_deadline(&self, timeout: Option<Duration>) -> Option<Instant> {
+        timeout
+            .zip(self.aligning_since)
+            .map(|(timeout, since)| since + timeout)
+    }
+
     pub fn mark(&mut self, idx: usize, checkpoint: &CheckpointBarrier) -> bool {
         assert!(self.inputs[idx].is_none());
 
@@ -97,11 +101,15 @@ impl CheckpointCounter {
 
         self.inputs[idx] = Some(checkpoint.epoch);
         self.counter = match self.counter {
-            None => Some(self.inputs.len() - 1),
+            None => {
+                self.aligning_since = Some(Instant::now());
+                Some(self.inputs.len() - 1)
+            }
             Some(1) => {
                 for v in self.inputs.iter_mut() {
                     *v = None;
                 }
+                self.aligning_since = None;
                 None
             }
             Some(n) => Some(n - 1),
@@ -188,3 +196,52 @@ impl RateLimiter {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::CheckpointCounter;
+    use arroyo_types::CheckpointBarrier;
+    use std::time::{Duration, SystemTime};
+
+    fn barrier() -> CheckpointBarrier {
+        CheckpointBarrier {
+            epoch: 1,
+            min_epoch: 0,
+            timestamp: SystemTime::now(),
+            then_stop: false,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_alignment_timeout() {
+        let timeout = Some(Duration::from_millis(20));
+        let mut counter = CheckpointCounter::new(3);
+        assert_eq!(counter.alignment_deadline(timeout), None);
+
+        assert!(!counter.mark(0, &barrier()));
+        assert!(counter.is_blocked(0));
+        assert!(!counter.is_blocked(1));
+        assert_eq!(counter.alignment_deadline(None), None);
+
+        // the deadline runs from the first barrier, and isn't pushed back by later ones
+        let deadline = counter.alignment_deadline(timeout).unwrap();
+        assert!(!counter.mark(1, &barrier()));
+        assert_eq!(counter.alignment_deadline(timeout), Some(deadline));
+
+        tokio::time::timeout(
+            Duration::from_secs(1),
+            tokio::time::sleep_until(deadline.into()),
+        )
+        .await
+        .expect("alignment timeout should fire");
+
+        // the inputs that have delivered their barriers stay blocked until the checkpoint is
+        // taken, so none of their data after the barrier reaches its state
+        assert!(counter.is_blocked(0));
+        assert!(counter.is_blocked(1));
+
+        assert!(counter.mark(2, &barrier()));
+        assert!(counter.all_clear());
+        assert_eq!(counter.alignment_deadline(timeout), None);
+    }
+}
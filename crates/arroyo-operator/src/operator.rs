@@ -1,17 +1,19 @@
-use crate::context::{ArrowContext, BatchReceiver};
+use crate::context::{ArrowContext, BatchReceiver, COALESCE_TARGET_ROWS};
 use crate::inq_reader::InQReader;
 use crate::udfs::{ArroyoUdaf, UdafArg};
 use crate::{CheckpointCounter, ControlOutcome, SourceFinishType};
-use anyhow::anyhow;
+use anyhow::{anyhow, bail};
 use arrow::array::RecordBatch;
 use arrow::datatypes::DataType;
 use arrow::datatypes::Schema;
 use arroyo_datastream::logical::{DylibUdfConfig, PythonUdfConfig};
-use arroyo_metrics::TaskCounters;
-use arroyo_rpc::grpc::rpc::{TableConfig, TaskCheckpointEventType};
+use arroyo_metrics::{
+    TaskCounters, CHECKPOINT_ALIGNMENT_HISTOGRAM, CHECKPOINT_ALIGNMENT_TIMEOUTS_COUNTER,
+};
+use arroyo_rpc::grpc::rpc::{StopMode, TableConfig, TaskCheckpointEventType};
 use arroyo_rpc::{ControlMessage, ControlResp};
 use arroyo_storage::StorageProvider;
-use arroyo_types::{ArrowMessage, CheckpointBarrier, SignalMessage, Watermark};
+use arroyo_types::{ArrowMessage, CheckpointBarrier, NonRetryableError, SignalMessage, Watermark};
 use arroyo_udf_host::parse::inner_type;
 use arroyo_udf_host::{ContainerOrLocal, LocalUdf, SyncUdfDylib, UdfDylib, UdfInterface};
 use arroyo_udf_python::PythonUDF;
@@ -31,6 +33,7 @@ use std::borrow::Cow;
 use std::collections::{HashMap, HashSet};
 use std::fmt::{Debug, Display, Formatter};
 use std::future::Future;
+use std::mem;
 use std::path::Path;
 use std::pin::Pin;
 use std::sync::Arc;
@@ -94,7 +97,7 @@ impl OperatorNode {
         ctx: &mut ArrowContext,
         in_qs: &mut [BatchReceiver],
         ready: Arc<Barrier>,
-    ) -> Option<SignalMessage> {
+    ) -> anyhow::Result<Option<SignalMessage>> {
         match self {
             OperatorNode::Source(s) => {
                 s.on_start(ctx).await;
@@ -118,7 +121,7 @@ impl OperatorNode {
 
                 s.on_close(ctx).await;
 
-                result.into()
+                Ok(result.into())
             }
             OperatorNode::Operator(o) => operator_run_behavior(o, ctx, in_qs, ready).await,
         }
@@ -135,7 +138,25 @@ impl OperatorNode {
             ctx.task_info.operator_name, ctx.task_info.task_index
         );
 
-        let final_message = self.run_behavior(&mut ctx, &mut in_qs, ready).await;
+        let final_message = match self.run_behavior(&mut ctx, &mut in_qs, ready).await {
+            Ok(final_message) => final_message,
+            Err(e) => {
+                error!(
+                    "Task {}-{} failed: {:?}",
+                    ctx.task_info.operator_name, ctx.task_info.task_index, e
+                );
+                ctx.control_tx
+                    .send(ControlResp::TaskFailed {
+                        operator_id: ctx.task_info.operator_id.clone(),
+                        task_index: ctx.task_info.task_index,
+                        error: format!("{:?}", e),
+                        fatal: e.chain().any(|e| e.is::<NonRetryableError>()),
+                    })
+                    .await
+                    .expect("control response unwrap");
+                return;
+            }
+        };
 
         if let Some(final_message) = final_message {
             ctx.broadcast(ArrowMessage::Signal(final_message)).await;
@@ -205,12 +226,23 @@ pub trait SourceOperator: Send + 'static {
     }
 }
 
+/// Non-source tasks are only sent an immediate stop when they're being torn down without
+/// finishing, as when their region of the pipeline is restarted
+fn is_abort(message: &ControlMessage) -> bool {
+    matches!(
+        message,
+        ControlMessage::Stop {
+            mode: StopMode::Immediate
+        }
+    )
+}
+
 async fn operator_run_behavior(
     this: &mut Box<dyn ArrowOperator + Send>,
     ctx: &mut ArrowContext,
     in_qs: &mut [BatchReceiver],
     ready: Arc<Barrier>,
-) -> Option<SignalMessage> {
+) -> anyhow::Result<Option<SignalMessage>> {
     this.on_start(ctx).await;
 
     ready.wait().await;
@@ -235,10 +267,19 @@ async fn operator_run_behavior(
     let mut sel = InQReader::new();
     let in_partitions = in_qs.len();
 
+    // the data that was in flight to this subtask when the checkpoint it was restored from was
+    // taken comes before anything it reads
+    for (idx, batch) in ctx.take_restored_in_flight().await? {
+        this.process_batch_index(idx, in_partitions, batch, ctx)
+            .await?;
+    }
+
     for (i, q) in in_qs.iter_mut().enumerate() {
         let stream = async_stream::stream! {
-          while let Some(item) = q.recv().await {
-            yield(i,item);
+          while let Some(item) = q.recv_coalesced(COALESCE_TARGET_ROWS).await {
+            // the data a barrier overtook is read along with it
+            let overtaken = q.recv_overtaken().await;
+            yield(i, item, overtaken);
           }
         };
         sel.push(Box::pin(stream));
@@ -252,15 +293,33 @@ async fn operator_run_behavior(
     interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
 
     loop {
+        // messages from the controller are handled ahead of any data that is ready, so that
+        // commits aren't held up behind a steady stream of input
+        while let Ok(control_message) = ctx.control_rx.try_recv() {
+            if is_abort(&control_message) {
+                this.on_abort(ctx).await?;
+                return Ok(None);
+            }
+            this.handle_controller_message(control_message, ctx).await?;
+        }
+
         let operator_future: OptionFuture<_> = this.future_to_poll().into();
+        let alignment_timeout: OptionFuture<_> = counter
+            .alignment_deadline(ctx.checkpoint_alignment_timeout)
+            .map(|deadline| tokio::time::sleep_until(deadline.into()))
+            .into();
         tokio::select! {
             Some(control_message) = ctx.control_rx.recv() => {
-                this.handle_controller_message(control_message, ctx).await;
+                if is_abort(&control_message) {
+                    this.on_abort(ctx).await?;
+                    return Ok(None);
+                }
+                this.handle_controller_message(control_message, ctx).await?;
             }
 
             p = sel.next() => {
                 match p {
-                    Some(((idx, message), s)) => {
+                    Some(((idx, message, overtaken), s)) => {
                         let local_idx = idx;
 
                         trace!("[{}] Handling message {}-{}, {:?}",
@@ -276,10 +335,30 @@ async fn operator_run_behavior(
                                         name,
                                         operator_id = task_info.operator_id,
                                         subtask_idx = task_info.task_index)
-                                ).await;
+                                ).await?;
                             }
                             ArrowMessage::Signal(signal) => {
-                                match this.handle_control_message(idx, &signal, &mut counter, &mut closed, in_partitions, ctx).await {
+                                // the data a barrier overtook was sent before it, so it's written
+                                // to the checkpoint and processed once the checkpoint is taken
+                                ctx.in_flight.extend(overtaken.into_iter().map(|item| (idx, item)));
+                                let mut outcome = this.handle_control_message(idx, &signal, &mut counter, &mut closed, in_partitions, ctx).await?;
+                                if counter.all_clear() {
+                                    for (idx, item) in mem::take(&mut ctx.in_flight) {
+                                        if !matches!(outcome, ControlOutcome::Continue) {
+                                            break;
+                                        }
+                                        outcome = match item {
+                                            ArrowMessage::Data(record) => {
+                                                this.process_batch_index(idx, in_partitions, record, ctx).await?;
+                                                ControlOutcome::Continue
+                                            }
+                                            ArrowMessage::Signal(signal) => {
+                                                this.handle_control_message(idx, &signal, &mut counter, &mut closed, in_partitions, ctx).await?
+                                            }
+                                        };
+                                    }
+                                }
+                                match outcome {
                                     ControlOutcome::Continue => {}
                                     ControlOutcome::Stop => {
                                         // just stop; the stop will have already been broadcast for example by
@@ -318,14 +397,35 @@ async fn operator_run_behavior(
             Some(val) = operator_future => {
                 this.handle_future_result(val, ctx).await;
             }
+            Some(_) = alignment_timeout => {
+                CHECKPOINT_ALIGNMENT_TIMEOUTS_COUNTER
+                    .with_label_values(&[
+                        &task_info.operator_id,
+                        &task_info.task_index.to_string(),
+                        &task_info.operator_name,
+                    ])
+                    .inc();
+                // unblocking the inputs would let data from after the barrier into the
+                // checkpoint's state, so instead the task fails and the pipeline is restored
+                // from its last complete checkpoint
+                bail!(
+                    "checkpoint barriers didn't align within {:?} (checkpoint.alignment_timeout) \
+                    in {}-{}, with {} of {} inputs blocked",
+                    ctx.checkpoint_alignment_timeout.unwrap(),
+                    ctx.task_info.operator_id,
+                    ctx.task_info.task_index,
+                    blocked.len(),
+                    in_partitions
+                );
+            }
             _ = interval.tick() => {
-                this.handle_tick(ticks, ctx).await;
+                this.handle_tick(ticks, ctx).await?;
                 ticks += 1;
             }
         }
     }
-    this.on_close(&final_message, ctx).await;
-    final_message
+    this.on_close(&final_message, ctx).await?;
+    Ok(final_message)
 }
 
 pub enum AsDisplayable<'a> {
@@ -424,7 +524,7 @@ pub trait ArrowOperator: Send + 'static {
         &mut self,
         control_message: ControlMessage,
         ctx: &mut ArrowContext,
-    ) {
+    ) -> anyhow::Result<()> {
         match control_message {
             ControlMessage::Checkpoint(_) => {
                 error!("shouldn't receive checkpoint")
@@ -433,13 +533,18 @@ pub trait ArrowOperator: Send + 'static {
                 error!("shouldn't receive stop")
             }
             ControlMessage::Commit { epoch, commit_data } => {
-                self.handle_commit(epoch, &commit_data, ctx).await;
+                self.handle_commit(epoch, &commit_data, ctx).await?;
             }
             ControlMessage::LoadCompacted { compacted } => {
                 ctx.load_compacted(compacted).await;
             }
+            ControlMessage::QueryState { query } => {
+                ctx.query_state(query).await;
+            }
             ControlMessage::NoOp => {}
         }
+
+        Ok(())
     }
 
     async fn handle_control_message(
@@ -450,7 +555,7 @@ pub trait ArrowOperator: Send + 'static {
         closed: &mut HashSet<usize>,
         in_partitions: usize,
         ctx: &mut ArrowContext,
-    ) -> ControlOutcome {
+    ) -> anyhow::Result<ControlOutcome> {
         match message {
             SignalMessage::Barrier(t) => {
                 debug!(
@@ -474,7 +579,18 @@ pub trait ArrowOperator: Send + 'static {
                         .unwrap();
                 }
 
+                let aligning_since = counter.aligning_since();
                 if counter.mark(idx, t) {
+                    if let Some(since) = aligning_since {
+                        CHECKPOINT_ALIGNMENT_HISTOGRAM
+                            .with_label_values(&[
+                                &ctx.task_info.operator_id,
+                                &ctx.task_info.task_index.to_string(),
+                                &ctx.task_info.operator_name,
+                            ])
+                            .observe(since.elapsed().as_secs_f64());
+                    }
+
                     debug!(
                         "Checkpointing {}-{}-{}",
                         self.name(),
@@ -485,13 +601,19 @@ pub trait ArrowOperator: Send + 'static {
                     ctx.send_checkpoint_event(*t, TaskCheckpointEventType::StartedCheckpointing)
                         .await;
 
-                    self.handle_checkpoint(*t, ctx).await;
+                    self.handle_checkpoint(*t, ctx).await?;
 
                     ctx.send_checkpoint_event(*t, TaskCheckpointEventType::FinishedOperatorSetup)
                         .await;
 
-                    if run_checkpoint(*t, ctx).await {
-                        return ControlOutcome::Stop;
+                    ctx.checkpoint_in_flight().await?;
+                    let stop = run_checkpoint(*t, ctx).await;
+
+                    // previews fail once their state grows beyond its limit
+                    ctx.table_manager.check_memory_limit()?;
+
+                    if stop {
+                        return Ok(ControlOutcome::Stop);
                     }
                 }
             }
@@ -519,17 +641,17 @@ pub trait ArrowOperator: Send + 'static {
             SignalMessage::Stop => {
                 closed.insert(idx);
                 if closed.len() == in_partitions {
-                    return ControlOutcome::StopAndSendStop;
+                    return Ok(ControlOutcome::StopAndSendStop);
                 }
             }
             SignalMessage::EndOfData => {
                 closed.insert(idx);
                 if closed.len() == in_partitions {
-                    return ControlOutcome::Finish;
+                    return Ok(ControlOutcome::Finish);
                 }
             }
         }
-        ControlOutcome::Continue
+        Ok(ControlOutcome::Continue)
     }
 
     fn name(&self) -> String;
@@ -558,11 +680,15 @@ pub trait ArrowOperator: Send + 'static {
         _in_partitions: usize,
         batch: RecordBatch,
         ctx: &mut ArrowContext,
-    ) {
+    ) -> anyhow::Result<()> {
         self.process_batch(batch, ctx).await
     }
 
-    async fn process_batch(&mut self, batch: RecordBatch, ctx: &mut ArrowContext);
+    async fn process_batch(
+        &mut self,
+        batch: RecordBatch,
+        ctx: &mut ArrowContext,
+    ) -> anyhow::Result<()>;
 
     #[allow(clippy::type_complexity)]
     fn future_to_poll(
@@ -586,7 +712,13 @@ pub trait ArrowOperator: Send + 'static {
     }
 
     #[allow(unused_variables)]
-    async fn handle_checkpoint(&mut self, b: CheckpointBarrier, ctx: &mut ArrowContext) {}
+    async fn handle_checkpoint(
+        &mut self,
+        b: CheckpointBarrier,
+        ctx: &mut ArrowContext,
+    ) -> anyhow::Result<()> {
+        Ok(())
+    }
 
     #[allow(unused_variables)]
     async fn handle_commit(
@@ -594,15 +726,32 @@ pub trait ArrowOperator: Send + 'static {
         epoch: u32,
         commit_data: &HashMap<String, HashMap<u32, Vec<u8>>>,
         ctx: &mut ArrowContext,
-    ) {
+    ) -> anyhow::Result<()> {
         warn!("default handling of commit with epoch {:?}", epoch);
+        Ok(())
     }
 
     #[allow(unused_variables)]
-    async fn handle_tick(&mut self, tick: u64, ctx: &mut ArrowContext) {}
+    async fn handle_tick(&mut self, tick: u64, ctx: &mut ArrowContext) -> anyhow::Result<()> {
+        Ok(())
+    }
 
     #[allow(unused_variables)]
-    async fn on_close(&mut self, final_message: &Option<SignalMessage>, ctx: &mut ArrowContext) {}
+    async fn on_close(
+        &mut self,
+        final_message: &Option<SignalMessage>,
+        ctx: &mut ArrowContext,
+    ) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    /// Called instead of `on_close` when the task is stopped before it finishes, as when its
+    /// region of the pipeline is restarted. The task is restored from the last checkpoint, so
+    /// anything it has written since then that isn't yet visible should be aborted.
+    #[allow(unused_variables)]
+    async fn on_abort(&mut self, ctx: &mut ArrowContext) -> anyhow::Result<()> {
+        Ok(())
+    }
 }
 
 #[derive(Default)]
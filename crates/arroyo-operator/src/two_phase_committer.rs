@@ -1,6 +1,7 @@
+use crate::context::ArrowContext;
+use crate::operator::{ArrowOperator, DisplayableOperator};
 use anyhow::Result;
 use arrow::record_batch::RecordBatch;
-use arroyo_operator::{context::ArrowContext, operator::ArrowOperator};
 use arroyo_rpc::{
     grpc::rpc::{GlobalKeyedTableConfig, TableConfig, TableEnum},
     CheckpointEvent, ControlMessage,
@@ -14,6 +15,9 @@ use std::fmt::Debug;
 use std::{collections::HashMap, time::SystemTime};
 use tracing::{info, warn};
 
+/// Runs a [TwoPhaseCommitter] as an operator, handling its state, the checkpoint protocol and the
+/// commit messages from the controller. Transactional sinks should implement [TwoPhaseCommitter]
+/// and be constructed wrapped in this operator.
 pub struct TwoPhaseCommitterOperator<TPC: TwoPhaseCommitter> {
     committer: TPC,
     pre_commits: Vec<TPC::PreCommit>,
@@ -25,40 +29,84 @@ pub struct TwoPhaseCommitterOperator<TPC: TwoPhaseCommitter> {
 /// records to a persistent store in a fault-tolerant manner. The two-phase commit protocol is used
 /// to ensure that all records are either committed or rolled back in the event of a failure.
 ///
-/// The trait defines methods for initializing the committer, inserting records, committing the
-/// records, and performing a checkpoint. Implementations of this trait must be `Send` and `'static`.
-///
-/// The trait is generic over two types: `K`, which represents the key type of the records being
-/// committed, and `T`, which represents the data type of the records being committed. The trait
-/// also defines two associated types: `DataRecovery`, which represents the type of data that can
-/// be recovered in the event of a failure, and `PreCommit`, which represents the type of data that
-/// is pre-committed before the final commit.
-///
+/// Records are written with `insert_batch`. On each checkpoint, `checkpoint` pre-commits the
+/// writes since the last one, returning the data needed to recover the committer and the
+/// pre-commits to be committed. Once the checkpoint is complete across the job, the controller
+/// signals the operator to `commit` them. On restore, `abort` is given the chance to roll back
+/// writes that were in progress after the restored checkpoint, before the committer is `init`ed
+/// from its recovery data.
 ///
+/// The trait defines two associated types: `DataRecovery`, which represents the type of data that
+/// can be recovered in the event of a failure, and `PreCommit`, which represents the type of data
+/// that is pre-committed before the final commit.
 #[async_trait]
 pub trait TwoPhaseCommitter: Send + 'static {
     type DataRecovery: Data;
     type PreCommit: Data;
 
     fn name(&self) -> String;
+
+    fn display(&self) -> DisplayableOperator {
+        DisplayableOperator {
+            name: self.name().into(),
+            fields: vec![],
+        }
+    }
+
+    /// Whether this committer needs its pre-commits committed. Committers that return false
+    /// (for example, sinks configured for at-least-once delivery) have no pre-commit state and
+    /// don't wait on the controller for commit messages.
+    fn uses_two_phase_commit(&self) -> bool {
+        true
+    }
+
     async fn init(
         &mut self,
-        task_info: &mut ArrowContext,
+        ctx: &mut ArrowContext,
         data_recovery: Vec<Self::DataRecovery>,
     ) -> Result<()>;
-    async fn insert_batch(&mut self, batch: RecordBatch) -> Result<()>;
+
+    /// Called on start when restoring from a checkpoint, before `init`, with the recovery data of
+    /// every subtask. Should abort any writes made after the checkpoint that may still be pending,
+    /// so that they never become visible.
+    #[allow(unused_variables)]
+    async fn abort(
+        &mut self,
+        ctx: &mut ArrowContext,
+        data_recovery: &[Self::DataRecovery],
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    async fn insert_batch(&mut self, batch: RecordBatch, ctx: &mut ArrowContext) -> Result<()>;
     // TODO: figure out how to have the relevant vectors be of pointers across async boundaries.
     async fn commit(
         &mut self,
         task_info: &TaskInfo,
+        epoch: u32,
         pre_commit: Vec<Self::PreCommit>,
     ) -> Result<()>;
     async fn checkpoint(
         &mut self,
-        task_info: &TaskInfo,
+        ctx: &mut ArrowContext,
         watermark: Option<SystemTime>,
         stopping: bool,
     ) -> Result<(Self::DataRecovery, HashMap<String, Self::PreCommit>)>;
+
+    /// Called when the operator shuts down, before waiting for the final commit
+    #[allow(unused_variables)]
+    async fn close(&mut self, ctx: &mut ArrowContext) -> Result<()> {
+        Ok(())
+    }
+
+    /// Called when the operator is stopped before it finishes, as when its region of the pipeline
+    /// is restarted. Should abort the writes made since the last checkpoint, rather than leave
+    /// them pending until the restored subtask aborts them.
+    #[allow(unused_variables)]
+    async fn abort_pending(&mut self, ctx: &mut ArrowContext) -> Result<()> {
+        Ok(())
+    }
+
     fn commit_strategy(&self) -> CommitStrategy {
         CommitStrategy::PerSubtask
     }
@@ -73,18 +121,23 @@ pub enum CommitStrategy {
 }
 
 impl<TPC: TwoPhaseCommitter> TwoPhaseCommitterOperator<TPC> {
-    pub(crate) fn new(committer: TPC) -> Self {
+    pub fn new(committer: TPC) -> Self {
         Self {
             committer,
             pre_commits: Vec::new(),
         }
     }
+
+    pub fn committer(&self) -> &TPC {
+        &self.committer
+    }
+
     async fn handle_commit(
         &mut self,
         epoch: u32,
         mut commit_data: HashMap<String, HashMap<u32, Vec<u8>>>,
         ctx: &mut ArrowContext,
-    ) {
+    ) -> Result<()> {
         info!("received commit message");
         let pre_commits = match self.committer.commit_strategy() {
             CommitStrategy::PerSubtask => std::mem::take(&mut self.pre_commits),
@@ -104,9 +157,8 @@ impl<TPC: TwoPhaseCommitter> TwoPhaseCommitterOperator<TPC> {
         };
 
         self.committer
-            .commit(&ctx.task_info, pre_commits)
-            .await
-            .expect("committer committed");
+            .commit(&ctx.task_info, epoch, pre_commits)
+            .await?;
         let checkpoint_event = arroyo_rpc::ControlResp::CheckpointEvent(CheckpointEvent {
             checkpoint_epoch: epoch,
             operator_id: ctx.task_info.operator_id.clone(),
@@ -118,6 +170,7 @@ impl<TPC: TwoPhaseCommitter> TwoPhaseCommitterOperator<TPC> {
             .send(checkpoint_event)
             .await
             .expect("sent commit event");
+        Ok(())
     }
 
     fn map_from_serialized_data(serialized_data: Vec<u8>) -> Vec<TPC::PreCommit> {
@@ -135,20 +188,27 @@ impl<TPC: TwoPhaseCommitter> ArrowOperator for TwoPhaseCommitterOperator<TPC> {
         self.committer.name()
     }
 
+    fn display(&self) -> DisplayableOperator {
+        self.committer.display()
+    }
+
     fn tables(&self) -> HashMap<String, TableConfig> {
         let mut tables = arroyo_state::global_table_config("r", "recovery data");
-        tables.insert(
-            "p".into(),
-            TableConfig {
-                table_type: TableEnum::GlobalKeyValue.into(),
-                config: GlobalKeyedTableConfig {
-                    table_name: "p".into(),
-                    description: "pre-commit data".into(),
-                    uses_two_phase_commit: true,
-                }
-                .encode_to_vec(),
-            },
-        );
+        if self.committer.uses_two_phase_commit() {
+            tables.insert(
+                "p".into(),
+                TableConfig {
+                    table_type: TableEnum::GlobalKeyValue.into(),
+                    config: GlobalKeyedTableConfig {
+                        table_name: "p".into(),
+                        description: "pre-commit data".into(),
+                        uses_two_phase_commit: true,
+                        ttl_micros: None,
+                    }
+                    .encode_to_vec(),
+                },
+            );
+        }
         tables
     }
 
@@ -159,14 +219,23 @@ impl<TPC: TwoPhaseCommitter> ArrowOperator for TwoPhaseCommitterOperator<TPC> {
             .await
             .expect("should be able to get table");
 
-        let state_vec = tracking_key_state.get_all().values().cloned().collect();
+        let state_vec: Vec<_> = tracking_key_state.get_all().values().cloned().collect();
+
+        // recovery data is only present when restoring from a checkpoint
+        if !state_vec.is_empty() {
+            self.committer
+                .abort(ctx, &state_vec)
+                .await
+                .expect("committer aborted in-progress writes");
+        }
+
         self.committer
             .init(ctx, state_vec)
             .await
             .expect("committer initialized");
 
         // subtask 0 is responsible for finishing commits if we were interrupted mid commit.
-        if ctx.task_info.task_index == 0 {
+        if ctx.task_info.task_index == 0 && self.committer.uses_two_phase_commit() {
             let pre_commit_state: &mut GlobalKeyedView<String, TPC::PreCommit> = ctx
                 .table_manager
                 .get_global_keyed_state("p")
@@ -176,19 +245,36 @@ impl<TPC: TwoPhaseCommitter> ArrowOperator for TwoPhaseCommitterOperator<TPC> {
         }
     }
 
-    async fn process_batch(&mut self, batch: RecordBatch, _ctx: &mut ArrowContext) {
-        self.committer
-            .insert_batch(batch)
-            .await
-            .expect("record inserted");
+    async fn process_batch(
+        &mut self,
+        batch: RecordBatch,
+        ctx: &mut ArrowContext,
+    ) -> anyhow::Result<()> {
+        self.committer.insert_batch(batch, ctx).await
     }
 
-    async fn on_close(&mut self, _final_mesage: &Option<SignalMessage>, ctx: &mut ArrowContext) {
+    async fn on_close(
+        &mut self,
+        _final_mesage: &Option<SignalMessage>,
+        ctx: &mut ArrowContext,
+    ) -> anyhow::Result<()> {
+        self.committer.close(ctx).await?;
+
+        if !self.committer.uses_two_phase_commit() {
+            return Ok(());
+        }
+
         if let Some(ControlMessage::Commit { epoch, commit_data }) = ctx.control_rx.recv().await {
-            self.handle_commit(epoch, commit_data, ctx).await;
+            self.handle_commit(epoch, commit_data, ctx).await?;
         } else {
             warn!("no commit message received, not committing")
         }
+
+        Ok(())
+    }
+
+    async fn on_abort(&mut self, ctx: &mut ArrowContext) -> anyhow::Result<()> {
+        self.committer.abort_pending(ctx).await
     }
 
     async fn handle_commit(
@@ -196,27 +282,28 @@ impl<TPC: TwoPhaseCommitter> ArrowOperator for TwoPhaseCommitterOperator<TPC> {
         epoch: u32,
         commit_data: &HashMap<String, HashMap<u32, Vec<u8>>>,
         ctx: &mut ArrowContext,
-    ) {
-        self.handle_commit(epoch, commit_data.clone(), ctx).await;
+    ) -> Result<()> {
+        if !self.committer.uses_two_phase_commit() {
+            warn!("received commit but {} does not commit", self.name());
+            return Ok(());
+        }
+
+        self.handle_commit(epoch, commit_data.clone(), ctx).await
     }
 
     async fn handle_checkpoint(
         &mut self,
         checkpoint_barrier: arroyo_types::CheckpointBarrier,
         ctx: &mut ArrowContext,
-    ) {
+    ) -> anyhow::Result<()> {
+        let watermark = ctx.watermark().and_then(|watermark| match watermark {
+            Watermark::EventTime(watermark) => Some(watermark),
+            arroyo_types::Watermark::Idle => None,
+        });
         let (recovery_data, pre_commits) = self
             .committer
-            .checkpoint(
-                &ctx.task_info,
-                ctx.watermark().and_then(|watermark| match watermark {
-                    Watermark::EventTime(watermark) => Some(watermark),
-                    arroyo_types::Watermark::Idle => None,
-                }),
-                checkpoint_barrier.then_stop,
-            )
-            .await
-            .unwrap();
+            .checkpoint(ctx, watermark, checkpoint_barrier.then_stop)
+            .await?;
 
         let recovery_data_state: &mut GlobalKeyedView<usize, _> = ctx
             .table_manager
@@ -227,8 +314,8 @@ impl<TPC: TwoPhaseCommitter> ArrowOperator for TwoPhaseCommitterOperator<TPC> {
             .insert(ctx.task_info.task_index, recovery_data)
             .await;
         self.pre_commits.clear();
-        if pre_commits.is_empty() {
-            return;
+        if pre_commits.is_empty() || !self.committer.uses_two_phase_commit() {
+            return Ok(());
         }
         let commit_strategy = self.committer.commit_strategy();
         match commit_strategy {
@@ -256,5 +343,6 @@ impl<TPC: TwoPhaseCommitter> ArrowOperator for TwoPhaseCommitterOperator<TPC> {
                     .expect("should be able to send committing data");
             }
         }
+        Ok(())
     }
 }
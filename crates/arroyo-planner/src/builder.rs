@@ -1,22 +1,26 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, SystemTime};
 
-use arrow::datatypes::IntervalMonthDayNanoType;
+use arrow::array::TimestampNanosecondArray;
+use arrow::datatypes::{DataType, IntervalMonthDayNanoType, Schema, TimeUnit};
+use arrow::record_batch::RecordBatch;
 
 use arroyo_datastream::logical::{LogicalEdge, LogicalGraph, LogicalNode};
 use arroyo_rpc::df::{ArroyoSchema, ArroyoSchemaRef};
+use arroyo_types::{from_nanos, print_time, to_nanos};
 
 use async_trait::async_trait;
 use datafusion::common::tree_node::{TreeNode, TreeNodeRecursion, TreeNodeVisitor};
 use datafusion::common::{
-    plan_err, DFSchema, DFSchemaRef, DataFusionError, Result, ScalarValue, TableReference,
+    internal_err, plan_err, DFSchema, DFSchemaRef, DataFusionError, Result, ScalarValue,
+    TableReference,
 };
 use datafusion::execution::context::SessionState;
 use datafusion::execution::runtime_env::{RuntimeConfig, RuntimeEnv};
 use datafusion::functions::datetime::date_bin;
-use datafusion::logical_expr::{Expr, Extension, LogicalPlan, UserDefinedLogicalNode};
+use datafusion::logical_expr::{Cast, Expr, Extension, LogicalPlan, UserDefinedLogicalNode};
 use datafusion::physical_expr::PhysicalExpr;
 use datafusion::physical_plan::ExecutionPlan;
 use datafusion::physical_planner::{DefaultPhysicalPlanner, ExtensionPlanner, PhysicalPlanner};
@@ -24,11 +28,13 @@ use datafusion_proto::protobuf::{PhysicalExprNode, PhysicalPlanNode};
 use petgraph::graph::{DiGraph, NodeIndex};
 use tokio::runtime::Builder;
 use tokio::sync::oneshot;
+use xxhash_rust::xxh3::xxh3_64;
 
 use crate::extension::debezium::{
     DebeziumUnrollingExtension, DEBEZIUM_UNROLLING_EXTENSION_NAME, TO_DEBEZIUM_EXTENSION_NAME,
 };
 use crate::extension::key_calculation::KeyCalculationExtension;
+use crate::extension::sink::SinkExtension;
 use crate::extension::{ArroyoExtension, NodeWithIncomingEdges};
 use crate::physical::{
     ArroyoMemExec, ArroyoPhysicalExtensionCodec, DebeziumUnrollingExec, DecodingContext,
@@ -51,6 +57,13 @@ pub(crate) struct PlanToGraphVisitor<'a> {
     // In post_visit each node should clean up its vec and push its index to the last vec, if present.
     traversal: Vec<Vec<NodeIndex>>,
     planner: Planner<'a>,
+    // parallelism set by the extensions that planned each node, e.g. from table options
+    parallelism_hints: HashMap<NodeIndex, usize>,
+    // the ids used to name the nodes planned so far
+    node_ids: HashSet<usize>,
+    // the identity of each extension node on the path from the root of the plan being visited,
+    // with the number of its extension inputs visited so far
+    identities: Vec<(u64, usize)>,
 }
 
 impl<'a> PlanToGraphVisitor<'a> {
@@ -60,6 +73,9 @@ impl<'a> PlanToGraphVisitor<'a> {
             output_schemas: Default::default(),
             named_nodes: Default::default(),
             traversal: vec![],
+            parallelism_hints: Default::default(),
+            node_ids: Default::default(),
+            identities: vec![],
             planner: Planner::new(schema_provider, session_state),
         }
     }
@@ -206,6 +222,85 @@ impl<'a> Planner<'a> {
         let binning_function = self.create_physical_expr(&date_bin, &input_schema)?;
         serialize_physical_expr(binning_function, &DefaultPhysicalExtensionCodec {})
     }
+
+    /// Compiles a user-provided binning function (an expression over `_timestamp`, as passed to
+    /// `tumble_by()`) against the schema, casting its result to the type of `_timestamp`
+    pub fn custom_binning_function_proto(
+        &self,
+        binning_function: &Expr,
+        input_schema: &DFSchema,
+    ) -> Result<PhysicalExprNode> {
+        let binning_function = Expr::Cast(Cast::new(
+            Box::new(binning_function.clone()),
+            DataType::Timestamp(TimeUnit::Nanosecond, None),
+        ));
+
+        let binning_function = self.create_physical_expr(&binning_function, input_schema)?;
+        serialize_physical_expr(binning_function, &DefaultPhysicalExtensionCodec {})
+    }
+
+    /// Evaluates a user-provided binning function on a few sample timestamps, so that functions
+    /// that fail, return NULL, or place timestamps in bins that don't contain them are rejected
+    /// when the query is planned rather than when the window operator evaluates them
+    pub fn validate_binning_function(
+        &self,
+        binning_function: &Expr,
+        max_width: Duration,
+    ) -> Result<()> {
+        let schema = add_timestamp_field_arrow(Arc::new(Schema::empty()));
+        let binning_function = self.create_physical_expr(
+            &Expr::Cast(Cast::new(
+                Box::new(binning_function.clone()),
+                DataType::Timestamp(TimeUnit::Nanosecond, None),
+            )),
+            &DFSchema::try_from(schema.as_ref().clone())?,
+        )?;
+
+        let samples = vec![
+            to_nanos(SystemTime::UNIX_EPOCH + Duration::from_millis(1_704_112_496_789)) as i64,
+            to_nanos(SystemTime::now()) as i64,
+        ];
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![Arc::new(TimestampNanosecondArray::from(samples.clone()))],
+        )?;
+
+        let bins = binning_function
+            .evaluate(&batch)
+            .and_then(|bins| bins.into_array(samples.len()))
+            .map_err(|e| {
+                DataFusionError::Plan(format!(
+                    "tumble_by() binning function could not be evaluated: {}",
+                    e
+                ))
+            })?;
+        let Some(bins) = bins.as_any().downcast_ref::<TimestampNanosecondArray>() else {
+            return plan_err!("tumble_by() binning function must return a timestamp");
+        };
+
+        for (timestamp, bin) in samples.into_iter().zip(bins.iter()) {
+            let timestamp_str = print_time(from_nanos(timestamp as u128));
+            let Some(bin) = bin else {
+                return plan_err!(
+                    "tumble_by() binning function returned NULL for {}",
+                    timestamp_str
+                );
+            };
+
+            if bin > timestamp || (timestamp - bin) as u128 >= max_width.as_nanos() {
+                return plan_err!(
+                    "tumble_by() binning function must place each timestamp in a bin starting at \
+                    or before it and less than the maximum width ({:?}) earlier, but placed {} \
+                    in a bin starting at {}",
+                    max_width,
+                    timestamp_str,
+                    print_time(from_nanos(bin.max(0) as u128))
+                );
+            }
+        }
+
+        Ok(())
+    }
 }
 
 #[derive(Hash, Eq, PartialEq, Clone, Debug)]
@@ -272,18 +367,67 @@ impl<'a> PlanToGraphVisitor<'a> {
 
     pub(crate) fn add_plan(&mut self, plan: LogicalPlan) -> Result<()> {
         self.traversal.clear();
+        self.identities.clear();
         plan.visit(self)?;
         Ok(())
     }
 
+    pub(crate) fn node_count(&self) -> usize {
+        self.graph.node_count()
+    }
+
+    pub(crate) fn parallelism_hints(&self) -> &HashMap<NodeIndex, usize> {
+        &self.parallelism_hints
+    }
+
     pub fn into_graph(self) -> LogicalGraph {
         self.graph
     }
 
+    /// Returns the stable identity of an extension node, from which its operator id is derived.
+    /// Sources and other named nodes are identified by their tables and sinks by the table they
+    /// write to. Every other node is identified by a hash of its expressions (see
+    /// [operator_expressions]) along with its position on the path from the sink, so that
+    /// operators keep their ids, and so can restore their state, when the query is edited in
+    /// ways that don't change them, but get new ones when what they compute changes.
+    fn node_identity(&mut self, plan: &LogicalPlan, named: Option<NamedNode>) -> Result<u64> {
+        if let Some(named) = named {
+            return Ok(xxh3_64(format!("{:?}", named).as_bytes()));
+        }
+
+        let LogicalPlan::Extension(Extension { node }) = plan else {
+            return internal_err!("expected an extension node, found {}", plan.display());
+        };
+
+        let expressions = operator_expressions(plan)?;
+        Ok(match self.identities.last_mut() {
+            Some((parent, inputs)) => {
+                *inputs += 1;
+                xxh3_64(format!("{}/{}:{}", parent, inputs, expressions).as_bytes())
+            }
+            None => match node.as_any().downcast_ref::<SinkExtension>() {
+                Some(sink) => xxh3_64(format!("sink:{}", sink.name).as_bytes()),
+                None => xxh3_64(expressions.as_bytes()),
+            },
+        })
+    }
+
+    /// Returns the id used to name the operator with the given identity; nodes with the same
+    /// identity (like the sinks of two queries writing to the same table) are numbered in the
+    /// order they're planned
+    fn node_id(&mut self, identity: u64) -> usize {
+        let mut id = identity as u32 as usize;
+        while !self.node_ids.insert(id) {
+            id += 1;
+        }
+        id
+    }
+
     pub fn build_extension(
         &mut self,
         input_nodes: Vec<NodeIndex>,
         extension: &dyn ArroyoExtension,
+        node_id: usize,
     ) -> Result<()> {
         if let Some(node_name) = extension.node_name() {
             if self.named_nodes.contains_key(&node_name) {
@@ -307,12 +451,16 @@ impl<'a> PlanToGraphVisitor<'a> {
             .collect::<Result<Vec<_>>>()?;
 
         let NodeWithIncomingEdges { node, edges } = extension
-            .plan_node(&self.planner, self.graph.node_count(), input_schemas)
+            .plan_node(&self.planner, node_id, input_schemas)
             .map_err(|e| e.context(format!("planning extension {:?}", extension)))?;
 
         let node_index = self.graph.add_node(node);
         self.add_index_to_traversal(node_index);
 
+        if let Some(parallelism) = extension.parallelism_hint() {
+            self.parallelism_hints.insert(node_index, parallelism);
+        }
+
         for (source, edge) in input_nodes.into_iter().zip(edges.into_iter()) {
             self.graph.add_edge(source, node_index, edge);
         }
@@ -330,8 +478,8 @@ impl<'a> PlanToGraphVisitor<'a> {
 impl<'a> TreeNodeVisitor<'_> for PlanToGraphVisitor<'a> {
     type Node = LogicalPlan;
 
-    fn f_down(&mut self, node: &Self::Node) -> Result<TreeNodeRecursion> {
-        let LogicalPlan::Extension(Extension { node }) = node else {
+    fn f_down(&mut self, plan: &Self::Node) -> Result<TreeNodeRecursion> {
+        let LogicalPlan::Extension(Extension { node }) = plan else {
             return Ok(TreeNodeRecursion::Continue);
         };
 
@@ -342,6 +490,9 @@ impl<'a> TreeNodeVisitor<'_> for PlanToGraphVisitor<'a> {
             return Ok(TreeNodeRecursion::Continue);
         }
 
+        let identity = self.node_identity(plan, arroyo_extension.node_name())?;
+        self.identities.push((identity, 0));
+
         if let Some(name) = arroyo_extension.node_name() {
             if let Some(node_index) = self.named_nodes.get(&name) {
                 self.add_index_to_traversal(*node_index);
@@ -357,8 +508,8 @@ impl<'a> TreeNodeVisitor<'_> for PlanToGraphVisitor<'a> {
     }
 
     // most of the work sits in post visit so that we can have the inputs of each node
-    fn f_up(&mut self, node: &Self::Node) -> Result<TreeNodeRecursion> {
-        let LogicalPlan::Extension(Extension { node }) = node else {
+    fn f_up(&mut self, plan: &Self::Node) -> Result<TreeNodeRecursion> {
+        let LogicalPlan::Extension(Extension { node }) = plan else {
             return Ok(TreeNodeRecursion::Continue);
         };
 
@@ -370,6 +521,8 @@ impl<'a> TreeNodeVisitor<'_> for PlanToGraphVisitor<'a> {
             return Ok(TreeNodeRecursion::Continue);
         }
 
+        let (identity, _) = self.identities.pop().unwrap_or_default();
+
         if let Some(name) = arroyo_extension.node_name() {
             if self.named_nodes.contains_key(&name) {
                 return Ok(TreeNodeRecursion::Continue);
@@ -384,13 +537,41 @@ impl<'a> TreeNodeVisitor<'_> for PlanToGraphVisitor<'a> {
         let arroyo_extension: &dyn ArroyoExtension = node
             .try_into()
             .map_err(|e: DataFusionError| e.context("converting extension"))?;
-        self.build_extension(input_nodes, arroyo_extension)
+        let node_id = self.node_id(identity);
+        self.build_extension(input_nodes, arroyo_extension, node_id)
             .map_err(|e| e.context("building extension"))?;
 
         Ok(TreeNodeRecursion::Continue)
     }
 }
 
+/// Describes what the operator planned for an extension node computes: the node itself, with
+/// its expressions, and the plain DataFusion nodes between it and the extensions it reads from,
+/// which are planned into the same operator
+fn operator_expressions(plan: &LogicalPlan) -> Result<String> {
+    fn visit(plan: &LogicalPlan, out: &mut Vec<String>) -> Result<()> {
+        for input in plan.inputs() {
+            if let LogicalPlan::Extension(Extension { node }) = input {
+                let extension: &dyn ArroyoExtension = node
+                    .try_into()
+                    .map_err(|e: DataFusionError| e.context("converting extension"))?;
+                if !extension.transparent() {
+                    out.push(node.name().to_string());
+                    continue;
+                }
+            }
+
+            out.push(input.display().to_string());
+            visit(input, out)?;
+        }
+        Ok(())
+    }
+
+    let mut out = vec![plan.display().to_string()];
+    visit(plan, &mut out)?;
+    Ok(out.join("\n"))
+}
+
 pub(crate) struct SplitPlanOutput {
     pub(crate) partial_aggregation_plan: PhysicalPlanNode,
     pub(crate) partial_schema: ArroyoSchema,
@@ -1,6 +1,6 @@
 use std::{fmt::Formatter, sync::Arc, time::Duration};
 
-use arrow::datatypes::IntervalMonthDayNanoType;
+use arrow::datatypes::{IntervalMonthDayNanoType, Schema};
 
 use arroyo_datastream::{
     logical::{LogicalEdge, LogicalEdgeType, LogicalNode, OperatorName},
@@ -14,6 +14,7 @@ use arroyo_rpc::{
     },
     TIMESTAMP_FIELD,
 };
+use datafusion::common::tree_node::{Transformed, TreeNode};
 use datafusion::common::{
     internal_err, plan_err, Column, DFSchema, DFSchemaRef, Result, ScalarValue,
 };
@@ -33,10 +34,14 @@ use crate::{
     builder::{NamedNode, Planner, SplitPlanOutput},
     fields_with_qualifiers,
     physical::ArroyoPhysicalExtensionCodec,
-    schema_from_df_fields, schema_from_df_fields_with_metadata, DFField, WindowBehavior,
+    schema_from_df_fields, schema_from_df_fields_with_metadata,
+    schemas::add_timestamp_field_arrow,
+    DFField, WindowBehavior,
 };
 
-use super::{ArroyoExtension, NodeWithIncomingEdges, TimestampAppendExtension};
+use super::{
+    duration_label, operator_id, ArroyoExtension, NodeWithIncomingEdges, TimestampAppendExtension,
+};
 
 pub(crate) const AGGREGATE_EXTENSION_NAME: &str = "AggregateExtension";
 
@@ -47,6 +52,8 @@ pub(crate) struct AggregateExtension {
     pub(crate) schema: DFSchemaRef,
     pub(crate) key_fields: Vec<usize>,
     pub(crate) final_calculation: LogicalPlan,
+    // for windows with custom bins, the expression computing the bin start from _timestamp
+    pub(crate) binning_function: Option<Expr>,
 }
 
 impl AggregateExtension {
@@ -54,9 +61,14 @@ impl AggregateExtension {
         window_behavior: WindowBehavior,
         aggregate: LogicalPlan,
         key_fields: Vec<usize>,
+        binning_function: Option<Expr>,
     ) -> Self {
-        let final_calculation =
-            Self::final_projection(&aggregate, window_behavior.clone()).unwrap();
+        let final_calculation = Self::final_projection(
+            &aggregate,
+            window_behavior.clone(),
+            binning_function.as_ref(),
+        )
+        .unwrap();
 
         Self {
             window_behavior,
@@ -64,6 +76,7 @@ impl AggregateExtension {
             schema: final_calculation.schema().clone(),
             key_fields,
             final_calculation,
+            binning_function,
         }
     }
 
@@ -74,7 +87,32 @@ impl AggregateExtension {
         input_schema: DFSchemaRef,
         width: Duration,
     ) -> Result<LogicalNode> {
-        let binning_function_proto = planner.binning_function_proto(width, input_schema.clone())?;
+        let (binning_function_proto, custom_binning_function) = match &self.binning_function {
+            Some(binning_function) => {
+                planner.validate_binning_function(binning_function, width)?;
+
+                // the operator also needs to compute bins for individual timestamps, so we
+                // additionally compile the function against a schema containing only _timestamp
+                let timestamp_schema = DFSchema::try_from(
+                    add_timestamp_field_arrow(Arc::new(Schema::empty()))
+                        .as_ref()
+                        .clone(),
+                )?;
+                (
+                    planner.custom_binning_function_proto(binning_function, &input_schema)?,
+                    Some(
+                        planner
+                            .custom_binning_function_proto(binning_function, &timestamp_schema)?
+                            .encode_to_vec(),
+                    ),
+                )
+            }
+            None => (
+                planner.binning_function_proto(width, input_schema.clone())?,
+                None,
+            ),
+        };
+
         let SplitPlanOutput {
             partial_aggregation_plan,
             partial_schema,
@@ -102,13 +140,15 @@ impl AggregateExtension {
             partial_aggregation_plan: partial_aggregation_plan.encode_to_vec(),
             final_aggregation_plan: finish_plan.encode_to_vec(),
             final_projection: Some(final_physical_plan_node.encode_to_vec()),
+            custom_binning_function,
         };
 
+        let width = duration_label(width);
         Ok(LogicalNode {
-            operator_id: format!("tumbling_{}", index),
+            operator_id: operator_id("tumbling", Some(&width), index),
             operator_name: OperatorName::TumblingWindowAggregate,
             operator_config: config.encode_to_vec(),
-            description: format!("TumblingWindow<{}>", config.name),
+            description: format!("TumblingWindow<{}>", width),
             parallelism: 1,
         })
     }
@@ -153,9 +193,10 @@ impl AggregateExtension {
             final_projection: final_physical_plan_node.encode_to_vec(),
             // TODO add final aggregation.
         };
+        let (width, slide) = (duration_label(width), duration_label(slide));
         Ok(LogicalNode {
-            operator_id: format!("sliding_window_{}", index),
-            description: "sliding window".to_string(),
+            operator_id: operator_id("sliding", Some(&format!("{}_{}", width, slide)), index),
+            description: format!("SlidingWindow<{}, {}>", width, slide),
             operator_name: OperatorName::SlidingWindowAggregate,
             operator_config: config.encode_to_vec(),
             parallelism: 1,
@@ -205,7 +246,7 @@ impl AggregateExtension {
         )?;
 
         let config = SessionWindowAggregateOperator {
-            name: format!("session_window_{}", index),
+            name: operator_id("session", Some(&duration_label(*gap)), index),
             gap_micros: gap.as_micros() as u64,
             window_field_name: window_field.name().to_string(),
             window_index: *window_index as u64,
@@ -217,7 +258,7 @@ impl AggregateExtension {
 
         Ok(LogicalNode {
             operator_id: config.name.clone(),
-            description: format!("SessionWindow<{:?}>", gap),
+            description: format!("SessionWindow<{}>", duration_label(*gap)),
             operator_name: OperatorName::SessionWindowAggregate,
             operator_config: config.encode_to_vec(),
             parallelism: 1,
@@ -270,6 +311,7 @@ impl AggregateExtension {
             partial_aggregation_plan: partial_aggregation_plan.encode_to_vec(),
             final_aggregation_plan: finish_plan.encode_to_vec(),
             final_projection,
+            custom_binning_function: None,
         };
 
         Ok(LogicalNode {
@@ -281,10 +323,12 @@ impl AggregateExtension {
         })
     }
 
-    // projection assuming that _timestamp has been populated with the start of the bin.
+    // projection assuming that _timestamp has been populated with the start of the bin (or, for
+    // custom bins, the end of the bin).
     pub fn final_projection(
         aggregate_plan: &LogicalPlan,
         window_behavior: WindowBehavior,
+        binning_function: Option<&Expr>,
     ) -> Result<LogicalPlan> {
         let timestamp_field: DFField = aggregate_plan.inputs()[0]
             .schema()
@@ -312,6 +356,9 @@ impl AggregateExtension {
                 WindowType::Tumbling { width, .. } | WindowType::Sliding { width, .. } => {
                     (window_field, window_index, width, is_nested)
                 }
+                WindowType::Custom { max_width, .. } => {
+                    (window_field, window_index, max_width, is_nested)
+                }
                 WindowType::Session { .. } => {
                     return Ok(LogicalPlan::Extension(Extension {
                         node: Arc::new(WindowAppendExtension::new(
@@ -324,12 +371,13 @@ impl AggregateExtension {
                 WindowType::Instant => return Ok(timestamp_append),
             },
         };
-        if is_nested {
+        if is_nested || binning_function.is_some() {
             return Self::nested_final_projection(
                 timestamp_append,
                 window_field,
                 window_index,
                 width,
+                binning_function,
             );
         }
         let timestamp_column =
@@ -378,6 +426,7 @@ impl AggregateExtension {
         window_field: DFField,
         window_index: usize,
         width: Duration,
+        binning_function: Option<&Expr>,
     ) -> Result<LogicalPlan> {
         let timestamp_field: DFField = aggregate_plan
             .schema()
@@ -393,17 +442,31 @@ impl AggregateExtension {
             .map(|field| Expr::Column(field.qualified_column()))
             .collect();
         aggregate_fields.insert(window_index, window_field.clone());
+        // calculate the start of the bin
+        let bin_start = match binning_function {
+            Some(binning_function) => {
+                binning_function
+                    .clone()
+                    .transform_up(|expr| match expr {
+                        Expr::Column(_) => {
+                            Ok(Transformed::yes(Expr::Column(timestamp_column.clone())))
+                        }
+                        expr => Ok(Transformed::no(expr)),
+                    })?
+                    .data
+            }
+            None => Expr::BinaryExpr(BinaryExpr {
+                left: Box::new(Expr::Column(timestamp_column.clone())),
+                op: logical_expr::Operator::Minus,
+                right: Box::new(Expr::Literal(ScalarValue::IntervalMonthDayNano(Some(
+                    IntervalMonthDayNanoType::make_value(0, 0, width.as_nanos() as i64 - 1),
+                )))),
+            }),
+        };
         let window_expression = Expr::ScalarFunction(ScalarFunction {
             func: Arc::new(window_scalar_function()),
             args: vec![
-                // calculate the start of the bin
-                Expr::BinaryExpr(BinaryExpr {
-                    left: Box::new(Expr::Column(timestamp_column.clone())),
-                    op: logical_expr::Operator::Minus,
-                    right: Box::new(Expr::Literal(ScalarValue::IntervalMonthDayNano(Some(
-                        IntervalMonthDayNanoType::make_value(0, 0, width.as_nanos() as i64 - 1),
-                    )))),
-                }),
+                bin_start,
                 // add 1 nanosecond to the timestamp
                 Expr::BinaryExpr(BinaryExpr {
                     left: Box::new(Expr::Column(timestamp_column.clone())),
@@ -469,6 +532,7 @@ impl UserDefinedLogicalNodeCore for AggregateExtension {
             self.window_behavior.clone(),
             inputs[0].clone(),
             self.key_fields.clone(),
+            self.binning_function.clone(),
         ))
     }
 }
@@ -504,6 +568,12 @@ impl ArroyoExtension for AggregateExtension {
                         WindowType::Tumbling { width } => {
                             self.tumbling_window_config(planner, index, input_df_schema, *width)?
                         }
+                        WindowType::Custom { max_width, .. } => self.tumbling_window_config(
+                            planner,
+                            index,
+                            input_df_schema,
+                            *max_width,
+                        )?,
                         WindowType::Sliding { width, slide } => self.sliding_window_config(
                             planner,
                             index,
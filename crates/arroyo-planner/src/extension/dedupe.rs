@@ -0,0 +1,103 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use arroyo_datastream::logical::{LogicalEdge, LogicalEdgeType, LogicalNode, OperatorName};
+use arroyo_rpc::df::{ArroyoSchema, ArroyoSchemaRef};
+use arroyo_rpc::grpc::api::DedupeOperator;
+use datafusion::common::{internal_err, plan_err, DFSchemaRef, Result};
+use datafusion::logical_expr::{Expr, LogicalPlan, UserDefinedLogicalNodeCore};
+use prost::Message;
+
+use crate::builder::{NamedNode, Planner};
+
+use super::{duration_label, operator_id, ArroyoExtension, NodeWithIncomingEdges};
+
+pub(crate) const DEDUPE_EXTENSION_NAME: &str = "DedupeExtension";
+
+/// Drops rows of its (keyed) input whose keys were already seen within `window` of them, in
+/// event time. Inserted after sources that set the `dedupe.keys` and `dedupe.window` options.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct DedupeExtension {
+    pub(crate) input: LogicalPlan,
+    pub(crate) window: Duration,
+}
+
+impl DedupeExtension {
+    pub(crate) fn new(input: LogicalPlan, window: Duration) -> Self {
+        Self { input, window }
+    }
+}
+
+impl UserDefinedLogicalNodeCore for DedupeExtension {
+    fn name(&self) -> &str {
+        DEDUPE_EXTENSION_NAME
+    }
+
+    fn inputs(&self) -> Vec<&LogicalPlan> {
+        vec![&self.input]
+    }
+
+    fn schema(&self) -> &DFSchemaRef {
+        self.input.schema()
+    }
+
+    fn expressions(&self) -> Vec<Expr> {
+        vec![]
+    }
+
+    fn fmt_for_explain(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "DedupeExtension({:?}): {}", self.window, self.schema())
+    }
+
+    fn with_exprs_and_inputs(&self, _exprs: Vec<Expr>, inputs: Vec<LogicalPlan>) -> Result<Self> {
+        if inputs.len() != 1 {
+            return internal_err!("input size inconsistent");
+        }
+
+        Ok(Self::new(inputs[0].clone(), self.window))
+    }
+}
+
+impl ArroyoExtension for DedupeExtension {
+    fn node_name(&self) -> Option<NamedNode> {
+        None
+    }
+
+    fn plan_node(
+        &self,
+        _planner: &Planner,
+        index: usize,
+        input_schemas: Vec<ArroyoSchemaRef>,
+    ) -> Result<NodeWithIncomingEdges> {
+        if input_schemas.len() != 1 {
+            return plan_err!("DedupeExtension requires exactly one input");
+        }
+        let input_schema = input_schemas[0].clone();
+
+        let window = duration_label(self.window);
+        let config = DedupeOperator {
+            name: format!("dedupe<{}>", window),
+            input_schema: Some(input_schema.as_ref().clone().into()),
+            window_micros: self.window.as_micros() as u64,
+        };
+
+        let node = LogicalNode {
+            operator_id: operator_id("dedupe", Some(&window), index),
+            description: format!("Dedupe<{}>", window),
+            operator_name: OperatorName::Dedupe,
+            operator_config: config.encode_to_vec(),
+            parallelism: 1,
+        };
+
+        let edge = LogicalEdge::project_all(LogicalEdgeType::Shuffle, (*input_schema).clone());
+
+        Ok(NodeWithIncomingEdges {
+            node,
+            edges: vec![edge],
+        })
+    }
+
+    fn output_schema(&self) -> ArroyoSchema {
+        ArroyoSchema::from_schema_unkeyed(Arc::new(self.schema().as_ref().into())).unwrap()
+    }
+}
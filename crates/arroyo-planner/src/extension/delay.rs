@@ -0,0 +1,132 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use arrow_schema::{DataType, TimeUnit};
+use arroyo_datastream::logical::{LogicalEdge, LogicalEdgeType, LogicalNode, OperatorName};
+use arroyo_rpc::df::{ArroyoSchema, ArroyoSchemaRef};
+use arroyo_rpc::grpc::api::DelayOperator;
+use datafusion::common::{internal_err, plan_err, DFSchemaRef, Result};
+use datafusion::logical_expr::{Expr, LogicalPlan, UserDefinedLogicalNodeCore};
+use prost::Message;
+
+use crate::builder::{NamedNode, Planner};
+use crate::{fields_with_qualifiers, schema_from_df_fields, DFField, DELAYED_AT_FIELD};
+
+use super::{duration_label, operator_id, ArroyoExtension, NodeWithIncomingEdges};
+
+pub(crate) const DELAY_EXTENSION_NAME: &str = "DelayExtension";
+
+/// Buffers the rows of its input and emits each of them once `delay` has passed in processing
+/// time, appending the time at which it's due to be emitted as the `DELAYED_AT_FIELD` column.
+/// Buffered rows are retained against the event-time watermark for the delay plus
+/// `allowed_lateness`, which is how far event time may run ahead of processing time.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct DelayExtension {
+    pub(crate) input: LogicalPlan,
+    pub(crate) delay: Duration,
+    pub(crate) allowed_lateness: Duration,
+    pub(crate) schema: DFSchemaRef,
+}
+
+impl DelayExtension {
+    pub(crate) fn new(
+        input: LogicalPlan,
+        delay: Duration,
+        allowed_lateness: Duration,
+    ) -> Result<Self> {
+        let mut fields = fields_with_qualifiers(input.schema());
+        fields.push(DFField::new(
+            None,
+            DELAYED_AT_FIELD,
+            DataType::Timestamp(TimeUnit::Nanosecond, None),
+            true,
+        ));
+        let schema = Arc::new(schema_from_df_fields(&fields)?);
+
+        Ok(Self {
+            input,
+            delay,
+            allowed_lateness,
+            schema,
+        })
+    }
+}
+
+impl UserDefinedLogicalNodeCore for DelayExtension {
+    fn name(&self) -> &str {
+        DELAY_EXTENSION_NAME
+    }
+
+    fn inputs(&self) -> Vec<&LogicalPlan> {
+        vec![&self.input]
+    }
+
+    fn schema(&self) -> &DFSchemaRef {
+        &self.schema
+    }
+
+    fn expressions(&self) -> Vec<Expr> {
+        vec![]
+    }
+
+    fn fmt_for_explain(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "DelayExtension({:?}, allowed_lateness={:?}): {}",
+            self.delay, self.allowed_lateness, self.schema
+        )
+    }
+
+    fn with_exprs_and_inputs(&self, _exprs: Vec<Expr>, inputs: Vec<LogicalPlan>) -> Result<Self> {
+        if inputs.len() != 1 {
+            return internal_err!("input size inconsistent");
+        }
+
+        Self::new(inputs[0].clone(), self.delay, self.allowed_lateness)
+    }
+}
+
+impl ArroyoExtension for DelayExtension {
+    fn node_name(&self) -> Option<NamedNode> {
+        None
+    }
+
+    fn plan_node(
+        &self,
+        _planner: &Planner,
+        index: usize,
+        input_schemas: Vec<ArroyoSchemaRef>,
+    ) -> Result<NodeWithIncomingEdges> {
+        if input_schemas.len() != 1 {
+            return plan_err!("DelayExtension requires exactly one input");
+        }
+
+        let config = DelayOperator {
+            name: format!("delay<{:?}>", self.delay),
+            output_schema: Some(self.output_schema().into()),
+            delay_micros: self.delay.as_micros() as u64,
+            allowed_lateness_micros: self.allowed_lateness.as_micros() as u64,
+        };
+
+        let delay = duration_label(self.delay);
+        let node = LogicalNode {
+            operator_id: operator_id("delay", Some(&delay), index),
+            description: format!("delay<{}>", delay),
+            operator_name: OperatorName::Delay,
+            operator_config: config.encode_to_vec(),
+            parallelism: 1,
+        };
+
+        let edge =
+            LogicalEdge::project_all(LogicalEdgeType::Forward, input_schemas[0].as_ref().clone());
+
+        Ok(NodeWithIncomingEdges {
+            node,
+            edges: vec![edge],
+        })
+    }
+
+    fn output_schema(&self) -> ArroyoSchema {
+        ArroyoSchema::from_schema_unkeyed(Arc::new(self.schema.as_ref().into())).unwrap()
+    }
+}
@@ -15,11 +15,18 @@ use std::time::Duration;
 
 pub(crate) const JOIN_NODE_NAME: &str = "JoinNode";
 
+/// How long each side of an updating join retains its state
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) struct JoinTtl {
+    pub(crate) left: Duration,
+    pub(crate) right: Duration,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct JoinExtension {
     pub(crate) rewritten_join: LogicalPlan,
     pub(crate) is_instant: bool,
-    pub(crate) ttl: Option<Duration>,
+    pub(crate) ttl: Option<JoinTtl>,
 }
 
 impl ArroyoExtension for JoinExtension {
@@ -57,7 +64,9 @@ impl ArroyoExtension for JoinExtension {
             right_schema: Some(right_schema.as_ref().clone().into()),
             output_schema: Some(self.output_schema().into()),
             join_plan: physical_plan_node.encode_to_vec(),
-            ttl_micros: self.ttl.map(|t| t.as_micros() as u64),
+            ttl_micros: self.ttl.map(|t| t.left.max(t.right).as_micros() as u64),
+            left_ttl_micros: self.ttl.map(|t| t.left.as_micros() as u64),
+            right_ttl_micros: self.ttl.map(|t| t.right.as_micros() as u64),
         };
 
         let logical_node = LogicalNode {
@@ -19,6 +19,9 @@ use prost::Message;
 use watermark_node::WatermarkNode;
 
 use self::debezium::{DebeziumUnrollingExtension, ToDebeziumExtension};
+use self::dedupe::DedupeExtension;
+use self::delay::DelayExtension;
+use self::session_events::SessionEventsExtension;
 use self::updating_aggregate::UpdatingAggregateExtension;
 use self::{
     aggregate::AggregateExtension, key_calculation::KeyCalculationExtension,
@@ -32,9 +35,12 @@ use join::JoinExtension;
 
 pub(crate) mod aggregate;
 pub(crate) mod debezium;
+pub(crate) mod dedupe;
+pub(crate) mod delay;
 pub(crate) mod join;
 pub(crate) mod key_calculation;
 pub(crate) mod remote_table;
+pub(crate) mod session_events;
 pub(crate) mod sink;
 pub(crate) mod table_source;
 pub(crate) mod updating_aggregate;
@@ -43,6 +49,8 @@ pub(crate) mod window_fn;
 pub(crate) trait ArroyoExtension: Debug {
     // if the extension has a name, return it so that we can memoize.
     fn node_name(&self) -> Option<NamedNode>;
+    // `index` is a stable id for the node, derived from its expressions and position in the plan,
+    // to be used in operator ids
     fn plan_node(
         &self,
         planner: &Planner,
@@ -53,6 +61,10 @@ pub(crate) trait ArroyoExtension: Debug {
     fn transparent(&self) -> bool {
         false
     }
+    // the parallelism requested for the planned node, overriding any hints from the query
+    fn parallelism_hint(&self) -> Option<usize> {
+        None
+    }
 }
 
 pub(crate) struct NodeWithIncomingEdges {
@@ -60,6 +72,57 @@ pub(crate) struct NodeWithIncomingEdges {
     pub edges: Vec<LogicalEdge>,
 }
 
+/// Builds an operator id from the kind of operator, a label describing what it does (like the
+/// table it reads from or the width of its window), and the node's stable index, e.g.
+/// `source_orders_1234` or `tumbling_1m_5678`. Labels are normalized to lowercase
+/// alphanumerics and underscores so that ids can be used in metric labels and paths.
+pub(crate) fn operator_id(kind: &str, label: Option<&str>, index: usize) -> String {
+    let label = label
+        .map(|label| {
+            label
+                .chars()
+                .map(|c| {
+                    if c.is_ascii_alphanumeric() {
+                        c.to_ascii_lowercase()
+                    } else {
+                        '_'
+                    }
+                })
+                .collect::<String>()
+                .trim_matches('_')
+                .to_string()
+        })
+        .filter(|label| !label.is_empty());
+
+    match label {
+        Some(label) => format!("{}_{}_{}", kind, label, index),
+        None => format!("{}_{}", kind, index),
+    }
+}
+
+/// Formats a duration compactly for operator names, like `1m`, `90s`, or `250ms`
+pub(crate) fn duration_label(duration: Duration) -> String {
+    const UNITS: [(u128, &str); 6] = [
+        (86_400_000_000, "d"),
+        (3_600_000_000, "h"),
+        (60_000_000, "m"),
+        (1_000_000, "s"),
+        (1_000, "ms"),
+        (1, "us"),
+    ];
+
+    let micros = duration.as_micros();
+    if micros == 0 {
+        return "0s".to_string();
+    }
+
+    UNITS
+        .iter()
+        .find(|(unit, _)| micros % unit == 0)
+        .map(|(unit, suffix)| format!("{}{}", micros / unit, suffix))
+        .unwrap_or_else(|| format!("{}us", micros))
+}
+
 fn try_from_t<T: ArroyoExtension + 'static>(
     node: &dyn UserDefinedLogicalNode,
 ) -> Result<&dyn ArroyoExtension, ()> {
@@ -82,6 +145,9 @@ impl<'a> TryFrom<&'a dyn UserDefinedLogicalNode> for &'a dyn ArroyoExtension {
             .or_else(|_| try_from_t::<JoinExtension>(node))
             .or_else(|_| try_from_t::<WindowFunctionExtension>(node))
             .or_else(|_| try_from_t::<AsyncUDFExtension>(node))
+            .or_else(|_| try_from_t::<DelayExtension>(node))
+            .or_else(|_| try_from_t::<SessionEventsExtension>(node))
+            .or_else(|_| try_from_t::<DedupeExtension>(node))
             .or_else(|_| try_from_t::<ToDebeziumExtension>(node))
             .or_else(|_| try_from_t::<DebeziumUnrollingExtension>(node))
             .or_else(|_| try_from_t::<UpdatingAggregateExtension>(node))
@@ -220,7 +286,7 @@ impl ArroyoExtension for AsyncUDFExtension {
         };
 
         let node = LogicalNode {
-            operator_id: format!("async_udf_{}", index),
+            operator_id: operator_id("async_udf", Some(&self.name), index),
             description: format!("async_udf<{}>", self.name),
             operator_name: OperatorName::AsyncUdf,
             operator_config: config.encode_to_vec(),
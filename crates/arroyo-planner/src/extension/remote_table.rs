@@ -16,7 +16,7 @@ use crate::{
     physical::ArroyoPhysicalExtensionCodec,
 };
 
-use super::{ArroyoExtension, NodeWithIncomingEdges};
+use super::{operator_id, ArroyoExtension, NodeWithIncomingEdges};
 
 pub(crate) const REMOTE_TABLE_NAME: &str = "RemoteTableExtension";
 
@@ -71,7 +71,7 @@ impl ArroyoExtension for RemoteTableExtension {
             physical_plan: physical_plan_node.encode_to_vec(),
         };
         let node = LogicalNode {
-            operator_id: format!("value_{}", index),
+            operator_id: operator_id("value", Some(self.name.table()), index),
             description: self.name.to_string(),
             operator_name: OperatorName::ArrowValue,
             parallelism: 1,
@@ -0,0 +1,119 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use arroyo_datastream::logical::{LogicalEdge, LogicalEdgeType, LogicalNode, OperatorName};
+use arroyo_rpc::df::{ArroyoSchema, ArroyoSchemaRef};
+use arroyo_rpc::grpc::api::SessionEventsOperator;
+use datafusion::common::{internal_err, plan_err, DFSchemaRef, Result};
+use datafusion::logical_expr::{Expr, LogicalPlan, UserDefinedLogicalNodeCore};
+use prost::Message;
+
+use crate::builder::{NamedNode, Planner};
+use crate::schemas::add_timestamp_field;
+
+use super::{duration_label, operator_id, ArroyoExtension, NodeWithIncomingEdges};
+
+pub(crate) const SESSION_EVENTS_EXTENSION_NAME: &str = "SessionEventsExtension";
+
+/// Tracks the sessions of each key of its (keyed) input, emitting a row when a session starts
+/// and another once it's been idle for `gap`. The output is the keys followed by the
+/// `session_events()` struct, as described by the schema of the aggregate it replaces.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct SessionEventsExtension {
+    pub(crate) input: LogicalPlan,
+    pub(crate) gap: Duration,
+    pub(crate) aggregate_schema: DFSchemaRef,
+    pub(crate) schema: DFSchemaRef,
+}
+
+impl SessionEventsExtension {
+    pub(crate) fn new(
+        input: LogicalPlan,
+        gap: Duration,
+        aggregate_schema: DFSchemaRef,
+    ) -> Result<Self> {
+        let schema = add_timestamp_field(aggregate_schema.clone(), None)?;
+
+        Ok(Self {
+            input,
+            gap,
+            aggregate_schema,
+            schema,
+        })
+    }
+}
+
+impl UserDefinedLogicalNodeCore for SessionEventsExtension {
+    fn name(&self) -> &str {
+        SESSION_EVENTS_EXTENSION_NAME
+    }
+
+    fn inputs(&self) -> Vec<&LogicalPlan> {
+        vec![&self.input]
+    }
+
+    fn schema(&self) -> &DFSchemaRef {
+        &self.schema
+    }
+
+    fn expressions(&self) -> Vec<Expr> {
+        vec![]
+    }
+
+    fn fmt_for_explain(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "SessionEventsExtension({:?}): {}", self.gap, self.schema)
+    }
+
+    fn with_exprs_and_inputs(&self, _exprs: Vec<Expr>, inputs: Vec<LogicalPlan>) -> Result<Self> {
+        if inputs.len() != 1 {
+            return internal_err!("input size inconsistent");
+        }
+
+        Self::new(inputs[0].clone(), self.gap, self.aggregate_schema.clone())
+    }
+}
+
+impl ArroyoExtension for SessionEventsExtension {
+    fn node_name(&self) -> Option<NamedNode> {
+        None
+    }
+
+    fn plan_node(
+        &self,
+        _planner: &Planner,
+        index: usize,
+        input_schemas: Vec<ArroyoSchemaRef>,
+    ) -> Result<NodeWithIncomingEdges> {
+        if input_schemas.len() != 1 {
+            return plan_err!("SessionEventsExtension requires exactly one input");
+        }
+        let input_schema = input_schemas[0].clone();
+
+        let gap = duration_label(self.gap);
+        let config = SessionEventsOperator {
+            name: format!("session_events<{}>", gap),
+            input_schema: Some(input_schema.as_ref().clone().into()),
+            output_schema: Some(self.output_schema().into()),
+            gap_micros: self.gap.as_micros() as u64,
+        };
+
+        let node = LogicalNode {
+            operator_id: operator_id("session_events", Some(&gap), index),
+            description: format!("SessionEvents<{}>", gap),
+            operator_name: OperatorName::SessionEvents,
+            operator_config: config.encode_to_vec(),
+            parallelism: 1,
+        };
+
+        let edge = LogicalEdge::project_all(LogicalEdgeType::Shuffle, (*input_schema).clone());
+
+        Ok(NodeWithIncomingEdges {
+            node,
+            edges: vec![edge],
+        })
+    }
+
+    fn output_schema(&self) -> ArroyoSchema {
+        ArroyoSchema::from_schema_unkeyed(Arc::new(self.schema.as_ref().into())).unwrap()
+    }
+}
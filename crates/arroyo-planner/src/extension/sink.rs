@@ -17,8 +17,8 @@ use crate::{
 };
 
 use super::{
-    debezium::ToDebeziumExtension, remote_table::RemoteTableExtension, ArroyoExtension,
-    NodeWithIncomingEdges,
+    debezium::ToDebeziumExtension, operator_id, remote_table::RemoteTableExtension,
+    ArroyoExtension, NodeWithIncomingEdges,
 };
 
 pub(crate) const SINK_NODE_NAME: &str = "SinkExtension";
@@ -161,7 +161,7 @@ impl ArroyoExtension for SinkExtension {
             .map_err(|e| e.context("connector op"))?)
         .encode_to_vec();
         let node = LogicalNode {
-            operator_id: format!("sink_{}_{}", self.name, index),
+            operator_id: operator_id("sink", Some(self.name.table()), index),
             description: self.table.connector_op().unwrap().description.clone(),
             operator_name: OperatorName::ConnectorSink,
             parallelism: 1,
@@ -179,4 +179,11 @@ impl ArroyoExtension for SinkExtension {
         ArroyoSchema::from_schema_keys(Arc::new(self.input.schema().as_ref().into()), vec![])
             .unwrap()
     }
+
+    fn parallelism_hint(&self) -> Option<usize> {
+        match &self.table {
+            Table::ConnectorTable(table) => table.parallelism,
+            _ => None,
+        }
+    }
 }
@@ -8,7 +8,7 @@ use datafusion::logical_expr::{Expr, LogicalPlan, UserDefinedLogicalNodeCore};
 
 use prost::Message;
 
-use super::{ArroyoExtension, DebeziumUnrollingExtension, NodeWithIncomingEdges};
+use super::{operator_id, ArroyoExtension, DebeziumUnrollingExtension, NodeWithIncomingEdges};
 use crate::tables::FieldSpec;
 use crate::{
     builder::{NamedNode, Planner},
@@ -100,7 +100,7 @@ impl ArroyoExtension for TableSourceExtension {
         }
         let sql_source = self.table.as_sql_source()?;
         let node = LogicalNode {
-            operator_id: format!("source_{}_{}", self.name, index),
+            operator_id: operator_id("source", Some(self.name.table()), index),
             description: sql_source.source.config.description.clone(),
             operator_name: OperatorName::ConnectorSource,
             operator_config: sql_source.source.config.encode_to_vec(),
@@ -115,4 +115,8 @@ impl ArroyoExtension for TableSourceExtension {
     fn output_schema(&self) -> ArroyoSchema {
         ArroyoSchema::from_schema_keys(Arc::new(self.schema.as_ref().into()), vec![]).unwrap()
     }
+
+    fn parallelism_hint(&self) -> Option<usize> {
+        self.table.parallelism
+    }
 }
@@ -20,6 +20,7 @@ use std::time::Duration;
 
 use super::{ArroyoExtension, IsRetractExtension, NodeWithIncomingEdges};
 use crate::functions::multi_hash;
+use crate::{fields_with_qualifiers, schema_from_df_fields};
 use arroyo_rpc::config::config;
 use prost::Message;
 
@@ -32,6 +33,10 @@ pub(crate) struct UpdatingAggregateExtension {
     pub(crate) final_calculation: LogicalPlan,
     pub(crate) timestamp_qualifier: Option<TableReference>,
     pub(crate) ttl: Duration,
+    // blocking aggregates emit their results once their input is exhausted, so their output
+    // is append-only and has no updating metadata
+    pub(crate) blocking: bool,
+    pub(crate) schema: DFSchemaRef,
 }
 
 impl UpdatingAggregateExtension {
@@ -40,6 +45,7 @@ impl UpdatingAggregateExtension {
         key_fields: Vec<usize>,
         timestamp_qualifier: Option<TableReference>,
         ttl: Duration,
+        blocking: bool,
     ) -> Result<Self> {
         let final_calculation = LogicalPlan::Extension(Extension {
             node: Arc::new(IsRetractExtension::new(
@@ -48,12 +54,24 @@ impl UpdatingAggregateExtension {
             )),
         });
 
+        let schema = if blocking {
+            let fields: Vec<_> = fields_with_qualifiers(final_calculation.schema())
+                .into_iter()
+                .filter(|f| f.name() != UPDATING_META_FIELD)
+                .collect();
+            Arc::new(schema_from_df_fields(&fields)?)
+        } else {
+            final_calculation.schema().clone()
+        };
+
         Ok(Self {
             aggregate,
             key_fields,
             final_calculation,
             timestamp_qualifier,
             ttl,
+            blocking,
+            schema,
         })
     }
 }
@@ -68,7 +86,7 @@ impl UserDefinedLogicalNodeCore for UpdatingAggregateExtension {
     }
 
     fn schema(&self) -> &DFSchemaRef {
-        self.final_calculation.schema()
+        &self.schema
     }
 
     fn expressions(&self) -> Vec<datafusion::prelude::Expr> {
@@ -76,7 +94,11 @@ impl UserDefinedLogicalNodeCore for UpdatingAggregateExtension {
     }
 
     fn fmt_for_explain(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(f, "UpdatingAggregateExtension")
+        if self.blocking {
+            write!(f, "UpdatingAggregateExtension(blocking)")
+        } else {
+            write!(f, "UpdatingAggregateExtension")
+        }
     }
 
     fn with_exprs_and_inputs(
@@ -89,6 +111,7 @@ impl UserDefinedLogicalNodeCore for UpdatingAggregateExtension {
             self.key_fields.clone(),
             self.timestamp_qualifier.clone(),
             self.ttl,
+            self.blocking,
         )
     }
 }
@@ -239,6 +262,7 @@ impl ArroyoExtension for UpdatingAggregateExtension {
                 .update_aggregate_flush_interval
                 .as_micros() as u64,
             ttl_micros: self.ttl.as_micros() as u64,
+            blocking: self.blocking,
         };
 
         let node = LogicalNode {
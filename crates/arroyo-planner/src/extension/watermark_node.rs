@@ -1,5 +1,5 @@
 use crate::builder::{NamedNode, Planner};
-use crate::extension::{ArroyoExtension, NodeWithIncomingEdges};
+use crate::extension::{operator_id, ArroyoExtension, NodeWithIncomingEdges};
 use crate::schemas::add_timestamp_field;
 use arroyo_datastream::logical::{LogicalEdge, LogicalEdgeType, LogicalNode, OperatorName};
 use arroyo_rpc::df::{ArroyoSchema, ArroyoSchemaRef};
@@ -12,6 +12,7 @@ use datafusion_proto::physical_plan::DefaultPhysicalExtensionCodec;
 use prost::Message;
 use std::fmt::Formatter;
 use std::sync::Arc;
+use std::time::Duration;
 
 pub(crate) const WATERMARK_NODE_NAME: &str = "WatermarkNode";
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -20,6 +21,7 @@ pub struct WatermarkNode {
     pub qualifier: TableReference,
     pub watermark_expression: Expr,
     pub schema: DFSchemaRef,
+    pub idle_time: Option<Duration>,
     timestamp_index: usize,
 }
 
@@ -62,6 +64,7 @@ impl UserDefinedLogicalNodeCore for WatermarkNode {
             qualifier: self.qualifier.clone(),
             watermark_expression: exprs.into_iter().next().unwrap(),
             schema: self.schema.clone(),
+            idle_time: self.idle_time,
             timestamp_index,
         })
     }
@@ -81,13 +84,13 @@ impl ArroyoExtension for WatermarkNode {
         let expression = planner.create_physical_expr(&self.watermark_expression, &self.schema)?;
         let expression = serialize_physical_expr(expression, &DefaultPhysicalExtensionCodec {})?;
         let node = LogicalNode {
-            operator_id: format!("watermark_{}", index),
-            description: "watermark".to_string(),
+            operator_id: operator_id("watermark", Some(self.qualifier.table()), index),
+            description: format!("watermark<{}>", self.qualifier),
             operator_name: OperatorName::ExpressionWatermark,
             parallelism: 1,
             operator_config: ExpressionWatermarkConfig {
                 period_micros: 1_000_000,
-                idle_time_micros: None,
+                idle_time_micros: self.idle_time.map(|t| t.as_micros() as u64),
                 expression: expression.encode_to_vec(),
                 input_schema: Some(self.arroyo_schema().into()),
             }
@@ -110,6 +113,7 @@ impl WatermarkNode {
         input: LogicalPlan,
         qualifier: TableReference,
         watermark_expression: Expr,
+        idle_time: Option<Duration>,
     ) -> Result<Self> {
         let schema = add_timestamp_field(input.schema().clone(), Some(qualifier.clone()))?;
         let timestamp_index = schema
@@ -120,6 +124,7 @@ impl WatermarkNode {
             qualifier,
             watermark_expression,
             schema,
+            idle_time,
             timestamp_index,
         })
     }
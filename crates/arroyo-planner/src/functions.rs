@@ -1,14 +1,20 @@
+use crate::udafs::{register_anomaly_udafs, register_sampling_udafs};
 use crate::ArroyoSchemaProvider;
+use arrow::buffer::NullBuffer;
 use arrow::row::{RowConverter, SortField};
-use arrow_array::builder::{FixedSizeBinaryBuilder, ListBuilder, StringBuilder};
+use arrow_array::builder::{
+    BooleanBuilder, FixedSizeBinaryBuilder, Float64Builder, Int64Builder, ListBuilder,
+    StringBuilder,
+};
 use arrow_array::cast::{as_string_array, AsArray};
 use arrow_array::types::{Float64Type, Int64Type};
-use arrow_array::{Array, ArrayRef, StringArray, UnionArray};
-use arrow_schema::{DataType, Field, UnionFields, UnionMode};
-use datafusion::common::{DataFusionError, ScalarValue};
+use arrow_array::{Array, ArrayRef, StringArray, StructArray, UnionArray};
+use arrow_schema::{DataType, Field, Fields, UnionFields, UnionMode};
+use datafusion::common::{plan_datafusion_err, plan_err, DataFusionError, ExprSchema, ScalarValue};
 use datafusion::common::{Result, TableReference};
 use datafusion::execution::FunctionRegistry;
 use datafusion::logical_expr::expr::{Alias, ScalarFunction};
+use datafusion::logical_expr::simplify::{ExprSimplifyResult, SimplifyInfo};
 use datafusion::logical_expr::{
     create_udf, ColumnarValue, LogicalPlan, Projection, ScalarUDFImpl, Signature, TypeSignature,
     Volatility,
@@ -49,6 +55,8 @@ macro_rules! make_udf_function {
 }
 
 make_udf_function!(MultiHashFunction, MULTI_HASH, multi_hash);
+make_udf_function!(JsonTableFunction, JSON_TABLE, json_table);
+make_udf_function!(SampleFunction, SAMPLE, sample);
 
 pub fn register_all(registry: &mut dyn FunctionRegistry) {
     registry
@@ -95,7 +103,46 @@ pub fn register_all(registry: &mut dyn FunctionRegistry) {
         )))
         .unwrap();
 
+    registry
+        .register_udf(Arc::new(create_udf(
+            "json_array_length",
+            vec![DataType::Utf8],
+            Arc::new(DataType::Int64),
+            Volatility::Immutable,
+            Arc::new(json_array_length),
+        )))
+        .unwrap();
+
+    registry
+        .register_udf(Arc::new(create_udf(
+            "json_keys",
+            vec![DataType::Utf8],
+            Arc::new(DataType::List(Arc::new(Field::new(
+                "item",
+                DataType::Utf8,
+                true,
+            )))),
+            Volatility::Immutable,
+            Arc::new(json_keys),
+        )))
+        .unwrap();
+
+    registry
+        .register_udf(Arc::new(create_udf(
+            "json_merge",
+            vec![DataType::Utf8, DataType::Utf8],
+            Arc::new(DataType::Utf8),
+            Volatility::Immutable,
+            Arc::new(json_merge),
+        )))
+        .unwrap();
+
     registry.register_udf(multi_hash()).unwrap();
+    registry.register_udf(json_table()).unwrap();
+    registry.register_udf(sample()).unwrap();
+
+    register_anomaly_udafs(registry);
+    register_sampling_udafs(registry);
 }
 
 fn parse_path(name: &str, path: &ScalarValue) -> Result<Arc<JsonPath>> {
@@ -200,6 +247,137 @@ impl ScalarUDFImpl for MultiHashFunction {
     }
 }
 
+/// `sample(fraction [, seed, key...])`: whether to keep a row in a sample of about `fraction`
+/// of the rows, for use in a WHERE clause. With just a fraction, rows are kept at random. Given
+/// a seed and one or more keys, the decision is made by hashing the keys with the seed, so the
+/// same rows are kept every time the query runs, and rows with the same keys are kept or dropped
+/// together.
+#[derive(Debug)]
+pub struct SampleFunction {
+    signature: Signature,
+}
+
+impl Default for SampleFunction {
+    fn default() -> Self {
+        Self {
+            signature: Signature::new(TypeSignature::VariadicAny, Volatility::Volatile),
+        }
+    }
+}
+
+fn sample_fraction(arg: &ColumnarValue) -> Result<f64> {
+    let fraction = match arg {
+        ColumnarValue::Scalar(s) if s.data_type().is_numeric() => s.cast_to(&DataType::Float64)?,
+        _ => {
+            return plan_err!("the fraction argument to sample must be a numeric literal");
+        }
+    };
+
+    match fraction {
+        ScalarValue::Float64(Some(f)) if (0.0..=1.0).contains(&f) => Ok(f),
+        f => plan_err!("the fraction argument to sample must be between 0 and 1, but was {f}"),
+    }
+}
+
+impl ScalarUDFImpl for SampleFunction {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        "sample"
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn return_type(&self, arg_types: &[DataType]) -> Result<DataType> {
+        if matches!(arg_types.len(), 0 | 2) {
+            return plan_err!(
+                "sample takes either a fraction, or a fraction, seed, and one or more keys"
+            );
+        }
+        Ok(DataType::Boolean)
+    }
+
+    fn simplify(&self, args: Vec<Expr>, _info: &dyn SimplifyInfo) -> Result<ExprSimplifyResult> {
+        // a random sample needs a random value for each row, which invoke can't produce when
+        // its only argument is a literal, so it's rewritten in terms of random()
+        if let [fraction] = args.as_slice() {
+            if let Expr::Literal(value) = fraction {
+                sample_fraction(&ColumnarValue::Scalar(value.clone()))?;
+            }
+            return Ok(ExprSimplifyResult::Simplified(
+                datafusion_functions::math::random()
+                    .call(vec![])
+                    .lt(fraction.clone()),
+            ));
+        }
+
+        Ok(ExprSimplifyResult::Original(args))
+    }
+
+    fn invoke(&self, args: &[ColumnarValue]) -> Result<ColumnarValue> {
+        if args.len() < 3 {
+            return plan_err!("sample with fewer than three arguments should have been simplified");
+        }
+
+        let fraction = sample_fraction(&args[0])?;
+        let seed = match &args[1] {
+            ColumnarValue::Scalar(s) if s.data_type().is_integer() => {
+                match s.cast_to(&DataType::Int64)? {
+                    ScalarValue::Int64(Some(seed)) => seed as u64,
+                    _ => return plan_err!("the seed argument to sample cannot be null"),
+                }
+            }
+            _ => return plan_err!("the seed argument to sample must be an integer literal"),
+        };
+
+        // rows are kept if their hash falls in the lowest `fraction` of the hash space
+        let threshold = (fraction * u64::MAX as f64) as u64;
+        let keep = |hash: u64| fraction >= 1.0 || hash < threshold;
+
+        let keys = &args[2..];
+        let length = keys
+            .iter()
+            .map(|t| match t {
+                ColumnarValue::Scalar(_) => 1,
+                ColumnarValue::Array(a) => a.len(),
+            })
+            .max()
+            .unwrap();
+
+        let row_builder = RowConverter::new(
+            keys.iter()
+                .map(|t| SortField::new(t.data_type().clone()))
+                .collect(),
+        )?;
+
+        let arrays = keys
+            .iter()
+            .map(|c| c.clone().into_array(length))
+            .collect::<Result<Vec<_>>>()?;
+        let rows = row_builder.convert_columns(&arrays)?;
+
+        if keys.iter().all(|a| matches!(a, ColumnarValue::Scalar(_))) {
+            let hash = xxhash_rust::xxh3::xxh3_64_with_seed(rows.row(0).as_ref(), seed);
+            Ok(ColumnarValue::Scalar(ScalarValue::Boolean(Some(keep(
+                hash,
+            )))))
+        } else {
+            let mut builder = BooleanBuilder::with_capacity(length);
+            for row in rows.iter() {
+                builder.append_value(keep(xxhash_rust::xxh3::xxh3_64_with_seed(
+                    row.as_ref(),
+                    seed,
+                )));
+            }
+            Ok(ColumnarValue::Array(Arc::new(builder.finish())))
+        }
+    }
+}
+
 fn json_function<T, ArrayT, F, ToS>(
     name: &str,
     f: F,
@@ -313,6 +491,333 @@ pub fn extract_json_string(args: &[ColumnarValue]) -> Result<ColumnarValue> {
     )
 }
 
+/// Applies `f` to each parsed JSON document in a TEXT column (or scalar), building the output
+/// with the provided Arrow builder. Values that are null or fail to parse produce nulls.
+fn json_unary<B, F>(name: &str, arg: &ColumnarValue, mut builder: B, f: F) -> Result<ColumnarValue>
+where
+    B: arrow_array::builder::ArrayBuilder,
+    F: Fn(&mut B, Option<serde_json::Value>),
+{
+    let (values, is_scalar) = match arg {
+        ColumnarValue::Array(values) => (values.clone(), false),
+        ColumnarValue::Scalar(ScalarValue::Utf8(v)) => (
+            Arc::new(StringArray::from(vec![v.clone()])) as ArrayRef,
+            true,
+        ),
+        ColumnarValue::Scalar(_) => {
+            return Err(DataFusionError::Execution(format!(
+                "The value argument to {name} must be of type TEXT"
+            )));
+        }
+    };
+
+    for v in as_string_array(&values).iter() {
+        f(&mut builder, v.and_then(|s| serde_json::from_str(s).ok()));
+    }
+
+    let result = builder.finish();
+    Ok(if is_scalar {
+        ColumnarValue::Scalar(ScalarValue::try_from_array(&result, 0)?)
+    } else {
+        ColumnarValue::Array(result)
+    })
+}
+
+pub fn json_array_length(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    assert_eq!(args.len(), 1);
+    json_unary(
+        "json_array_length",
+        &args[0],
+        Int64Builder::new(),
+        |b, v| match v {
+            Some(serde_json::Value::Array(a)) => b.append_value(a.len() as i64),
+            _ => b.append_null(),
+        },
+    )
+}
+
+pub fn json_keys(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    assert_eq!(args.len(), 1);
+    json_unary(
+        "json_keys",
+        &args[0],
+        ListBuilder::new(StringBuilder::new()),
+        |b, v| match v {
+            Some(serde_json::Value::Object(o)) => {
+                b.append_value(o.keys().map(|k| Some(k.as_str())));
+            }
+            _ => b.append_null(),
+        },
+    )
+}
+
+/// Merges `patch` into `target` following the semantics of RFC 7396 (JSON Merge Patch):
+/// objects are merged recursively, null values in the patch remove keys, and any other
+/// value replaces the target.
+fn merge_json(target: &mut serde_json::Value, patch: serde_json::Value) {
+    let serde_json::Value::Object(patch) = patch else {
+        *target = patch;
+        return;
+    };
+
+    if !target.is_object() {
+        *target = serde_json::Value::Object(serde_json::Map::new());
+    }
+
+    let target = target.as_object_mut().unwrap();
+    for (k, v) in patch {
+        if v.is_null() {
+            target.remove(&k);
+        } else {
+            merge_json(target.entry(k).or_insert(serde_json::Value::Null), v);
+        }
+    }
+}
+
+pub fn json_merge(args: &[ColumnarValue]) -> Result<ColumnarValue> {
+    assert_eq!(args.len(), 2);
+
+    let len = args
+        .iter()
+        .map(|t| match t {
+            ColumnarValue::Scalar(_) => 1,
+            ColumnarValue::Array(a) => a.len(),
+        })
+        .max()
+        .unwrap();
+
+    let all_scalar = args.iter().all(|a| matches!(a, ColumnarValue::Scalar(_)));
+
+    let arrays = args
+        .iter()
+        .map(|a| a.clone().into_array(len))
+        .collect::<Result<Vec<_>>>()?;
+
+    if arrays.iter().any(|a| a.data_type() != &DataType::Utf8) {
+        return plan_err!("The arguments to json_merge must be of type TEXT");
+    }
+
+    let result: StringArray = as_string_array(&arrays[0])
+        .iter()
+        .zip(as_string_array(&arrays[1]).iter())
+        .map(|(target, patch)| {
+            let mut target: serde_json::Value = serde_json::from_str(target?).ok()?;
+            merge_json(&mut target, serde_json::from_str(patch?).ok()?);
+            Some(target.to_string())
+        })
+        .collect();
+
+    Ok(if all_scalar {
+        ColumnarValue::Scalar(ScalarValue::try_from_array(&result, 0)?)
+    } else {
+        ColumnarValue::Array(Arc::new(result))
+    })
+}
+
+/// A single output column of `json_table`, parsed from a spec of the form
+/// `<name> <type> PATH <json path>`, e.g. `'user_id BIGINT PATH $.user.id'`
+#[derive(Debug)]
+struct JsonTableColumn {
+    name: String,
+    data_type: DataType,
+    raw_json: bool,
+    path: JsonPath,
+}
+
+impl JsonTableColumn {
+    fn parse(spec: &str) -> Result<Self> {
+        let tokens: Vec<_> = spec.split_whitespace().collect();
+        let path_idx = tokens
+            .iter()
+            .position(|t| t.eq_ignore_ascii_case("path"))
+            .ok_or_else(|| {
+                plan_datafusion_err!(
+                    "invalid json_table column '{spec}'; expected '<name> <type> PATH <json path>'"
+                )
+            })?;
+
+        if path_idx != 2 {
+            return plan_err!(
+                "invalid json_table column '{spec}'; expected '<name> <type> PATH <json path>'"
+            );
+        }
+        let (name, ty) = (tokens[0], tokens[1]);
+
+        let (data_type, raw_json) = match ty.to_ascii_uppercase().as_str() {
+            "TEXT" | "VARCHAR" | "STRING" => (DataType::Utf8, false),
+            "JSON" => (DataType::Utf8, true),
+            "BIGINT" | "INT" | "INTEGER" => (DataType::Int64, false),
+            "DOUBLE" | "FLOAT" | "REAL" => (DataType::Float64, false),
+            "BOOLEAN" | "BOOL" => (DataType::Boolean, false),
+            _ => {
+                return plan_err!(
+                    "unsupported type '{ty}' in json_table column '{name}'; supported types \
+                    are TEXT, JSON, BIGINT, DOUBLE, and BOOLEAN"
+                );
+            }
+        };
+
+        let path = tokens[path_idx + 1..].join(" ");
+        let path = JsonPath::parse(&path).map_err(|e| {
+            plan_datafusion_err!("invalid json path '{path}' in json_table column '{name}': {e:?}")
+        })?;
+
+        Ok(Self {
+            name: name.to_string(),
+            data_type,
+            raw_json,
+            path,
+        })
+    }
+
+    fn field(&self) -> Field {
+        Field::new(&self.name, self.data_type.clone(), true)
+    }
+
+    fn build(&self, docs: &[Option<serde_json::Value>]) -> ArrayRef {
+        let values = docs.iter().map(|doc| {
+            doc.as_ref()
+                .and_then(|doc| self.path.query(doc).first().cloned())
+                .filter(|v| !v.is_null())
+        });
+
+        match self.data_type {
+            DataType::Int64 => {
+                let mut b = Int64Builder::with_capacity(docs.len());
+                values.for_each(|v| b.append_option(v.and_then(|v| v.as_i64())));
+                Arc::new(b.finish())
+            }
+            DataType::Float64 => {
+                let mut b = Float64Builder::with_capacity(docs.len());
+                values.for_each(|v| b.append_option(v.and_then(|v| v.as_f64())));
+                Arc::new(b.finish())
+            }
+            DataType::Boolean => {
+                let mut b = BooleanBuilder::with_capacity(docs.len());
+                values.for_each(|v| b.append_option(v.and_then(|v| v.as_bool())));
+                Arc::new(b.finish())
+            }
+            DataType::Utf8 => {
+                let mut b = StringBuilder::with_capacity(docs.len(), docs.len() * 8);
+                values.for_each(|v| {
+                    b.append_option(v.map(|v| match v {
+                        serde_json::Value::String(s) if !self.raw_json => s,
+                        v => v.to_string(),
+                    }))
+                });
+                Arc::new(b.finish())
+            }
+            _ => unreachable!("unsupported json_table type {}", self.data_type),
+        }
+    }
+}
+
+/// `json_table(value, column_spec, ...)` projects values out of a JSON document into a struct,
+/// with one field for each column spec. Each spec is a literal of the form
+/// `<name> <type> PATH <json path>`; individual fields can then be accessed with the usual
+/// struct field syntax, e.g. `json_table(...)['name']`.
+#[derive(Debug)]
+pub struct JsonTableFunction {
+    signature: Signature,
+}
+
+impl Default for JsonTableFunction {
+    fn default() -> Self {
+        Self {
+            signature: Signature::new(TypeSignature::VariadicAny, Volatility::Immutable),
+        }
+    }
+}
+
+impl JsonTableFunction {
+    fn columns(specs: impl Iterator<Item = Option<String>>) -> Result<Vec<JsonTableColumn>> {
+        let columns = specs
+            .map(|spec| match spec {
+                Some(spec) => JsonTableColumn::parse(&spec),
+                None => plan_err!("json_table column specs must be non-null TEXT literals"),
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        if columns.is_empty() {
+            return plan_err!("json_table requires at least one column spec");
+        }
+
+        Ok(columns)
+    }
+
+    fn return_fields(columns: &[JsonTableColumn]) -> Fields {
+        columns.iter().map(|c| c.field()).collect()
+    }
+}
+
+impl ScalarUDFImpl for JsonTableFunction {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        "json_table"
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn return_type(&self, _arg_types: &[DataType]) -> Result<DataType> {
+        plan_err!("json_table column specs must be TEXT literals")
+    }
+
+    fn return_type_from_exprs(
+        &self,
+        args: &[Expr],
+        _schema: &dyn ExprSchema,
+        arg_types: &[DataType],
+    ) -> Result<DataType> {
+        if arg_types.first() != Some(&DataType::Utf8) {
+            return plan_err!("the first argument to json_table must be of type TEXT");
+        }
+
+        let columns = Self::columns(args[1..].iter().map(|arg| match arg {
+            Expr::Literal(ScalarValue::Utf8(s)) => s.clone(),
+            _ => None,
+        }))?;
+
+        Ok(DataType::Struct(Self::return_fields(&columns)))
+    }
+
+    fn invoke(&self, args: &[ColumnarValue]) -> Result<ColumnarValue> {
+        let columns = Self::columns(args[1..].iter().map(|arg| match arg {
+            ColumnarValue::Scalar(ScalarValue::Utf8(s)) => s.clone(),
+            _ => None,
+        }))?;
+
+        let (values, is_scalar) = match &args[0] {
+            ColumnarValue::Array(values) => (values.clone(), false),
+            ColumnarValue::Scalar(s) => (s.to_array_of_size(1)?, true),
+        };
+
+        let values = as_string_array(&values);
+        let docs: Vec<Option<serde_json::Value>> = values
+            .iter()
+            .map(|v| v.and_then(|s| serde_json::from_str(s).ok()))
+            .collect();
+
+        let result = StructArray::try_new(
+            Self::return_fields(&columns),
+            columns.iter().map(|c| c.build(&docs)).collect(),
+            Some(NullBuffer::from(
+                docs.iter().map(|d| d.is_some()).collect::<Vec<_>>(),
+            )),
+        )?;
+
+        Ok(if is_scalar {
+            ColumnarValue::Scalar(ScalarValue::try_from_array(&result, 0)?)
+        } else {
+            ColumnarValue::Array(Arc::new(result))
+        })
+    }
+}
+
 // This code is vendored from
 // https://github.com/datafusion-contrib/datafusion-functions-json/blob/main/src/common_union.rs
 // as the `is_json_union` function is not public. It should be kept in sync with that code so
@@ -583,4 +1088,120 @@ mod test {
             panic!("Expected scalar");
         }
     }
+
+    #[test]
+    fn test_json_array_length() {
+        let input = Arc::new(StringArray::from(vec![
+            Some(r#"[1, 2, 3]"#),
+            Some(r#"{"a": 1}"#),
+            None,
+            Some(r#"[]"#),
+        ]));
+
+        let result = super::json_array_length(&[super::ColumnarValue::Array(input)]).unwrap();
+
+        let expected = arrow_array::Int64Array::from(vec![Some(3), None, None, Some(0)]);
+
+        if let super::ColumnarValue::Array(result) = result {
+            assert_eq!(*result, expected);
+        } else {
+            panic!("Expected array, got scalar");
+        }
+    }
+
+    #[test]
+    fn test_json_keys() {
+        let input = Arc::new(StringArray::from(vec![
+            r#"{"a": 1, "b": {"c": 2}}"#,
+            r#"[1, 2]"#,
+        ]));
+
+        let result = super::json_keys(&[super::ColumnarValue::Array(input)]).unwrap();
+
+        let mut expected = ListBuilder::new(StringBuilder::new());
+        expected.append_value(vec![Some("a"), Some("b")]);
+        expected.append_null();
+
+        if let super::ColumnarValue::Array(result) = result {
+            assert_eq!(*result, expected.finish());
+        } else {
+            panic!("Expected array, got scalar");
+        }
+    }
+
+    #[test]
+    fn test_json_merge() {
+        let input = Arc::new(StringArray::from(vec![
+            r#"{"a": 1, "b": {"c": 2, "d": 3}}"#,
+            r#"{"a": 1}"#,
+        ]));
+
+        let result = super::json_merge(&[
+            super::ColumnarValue::Array(input),
+            super::ColumnarValue::Scalar(r#"{"b": {"c": 5, "d": null}, "e": [1]}"#.into()),
+        ])
+        .unwrap();
+
+        let expected = StringArray::from(vec![
+            r#"{"a":1,"b":{"c":5},"e":[1]}"#,
+            r#"{"a":1,"b":{"c":5},"e":[1]}"#,
+        ]);
+
+        if let super::ColumnarValue::Array(result) = result {
+            assert_eq!(*result, expected);
+        } else {
+            panic!("Expected array, got scalar");
+        }
+    }
+
+    #[test]
+    fn test_json_table() {
+        use arrow_array::cast::AsArray;
+        use arrow_array::types::Int64Type;
+        use datafusion::logical_expr::ScalarUDFImpl;
+
+        let input = Arc::new(StringArray::from(vec![
+            Some(r#"{"user": {"id": 5, "name": "bob"}, "tags": ["a"]}"#),
+            Some(r#"{"user": {"name": "alice"}}"#),
+            None,
+        ]));
+
+        let result = super::JsonTableFunction::default()
+            .invoke(&[
+                super::ColumnarValue::Array(input),
+                super::ColumnarValue::Scalar("id BIGINT PATH $.user.id".into()),
+                super::ColumnarValue::Scalar("name TEXT PATH $.user.name".into()),
+                super::ColumnarValue::Scalar("tags JSON PATH $.tags".into()),
+            ])
+            .unwrap();
+
+        let super::ColumnarValue::Array(result) = result else {
+            panic!("Expected array, got scalar");
+        };
+
+        let result = result.as_struct();
+        assert_eq!(result.null_count(), 1);
+        assert_eq!(
+            *result
+                .column_by_name("id")
+                .unwrap()
+                .as_primitive::<Int64Type>(),
+            arrow_array::Int64Array::from(vec![Some(5), None, None])
+        );
+        assert_eq!(
+            *result.column_by_name("name").unwrap().as_string::<i32>(),
+            StringArray::from(vec![Some("bob"), Some("alice"), None])
+        );
+        assert_eq!(
+            *result.column_by_name("tags").unwrap().as_string::<i32>(),
+            StringArray::from(vec![Some(r#"["a"]"#), None, None])
+        );
+    }
+
+    #[test]
+    fn test_json_table_invalid_spec() {
+        assert!(super::JsonTableColumn::parse("id BIGINT $.user.id").is_err());
+        assert!(super::JsonTableColumn::parse("id DATE PATH $.user.id").is_err());
+        assert!(super::JsonTableColumn::parse("PATH $.user.id").is_err());
+    }
 }
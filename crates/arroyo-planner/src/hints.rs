@@ -0,0 +1,188 @@
+use std::collections::HashMap;
+
+use arroyo_datastream::logical::{LogicalNode, OperatorName};
+use arroyo_rpc::grpc::api::StateStorage;
+use datafusion::common::{plan_err, DataFusionError, Result};
+use datafusion::sql::sqlparser::dialect::PostgreSqlDialect;
+use datafusion::sql::sqlparser::tokenizer::{Token, Tokenizer, Whitespace};
+use regex::Regex;
+
+use crate::parallelism::{parallelism_hints, ParallelismHint};
+
+pub(crate) const OPERATOR_KINDS: &[&str] = &[
+    "source",
+    "sink",
+    "watermark",
+    "projection",
+    "aggregate",
+    "join",
+    "window",
+    "udf",
+    "delay",
+    "dedupe",
+];
+
+/// The operator kinds that keep state, and so can be given a state hint
+const STATEFUL_OPERATOR_KINDS: &[&str] = &["aggregate", "join", "window", "delay", "dedupe"];
+
+pub(crate) fn operator_kind(operator_name: OperatorName) -> &'static str {
+    match operator_name {
+        OperatorName::ConnectorSource => "source",
+        OperatorName::ConnectorSink => "sink",
+        OperatorName::ExpressionWatermark => "watermark",
+        OperatorName::ArrowValue | OperatorName::ArrowKey => "projection",
+        OperatorName::TumblingWindowAggregate
+        | OperatorName::SlidingWindowAggregate
+        | OperatorName::SessionWindowAggregate
+        | OperatorName::SessionEvents
+        | OperatorName::UpdatingAggregate => "aggregate",
+        OperatorName::Join | OperatorName::InstantJoin => "join",
+        OperatorName::WindowFunction => "window",
+        OperatorName::AsyncUdf => "udf",
+        OperatorName::Delay => "delay",
+        OperatorName::Dedupe => "dedupe",
+    }
+}
+
+/// Finds the arguments of the hint called `name` (a comment like `/*+ name(args) */`) for each
+/// statement in the query, in the order the statements are parsed
+pub(crate) fn hint_arguments(query: &str, name: &str) -> Result<Vec<Option<String>>> {
+    let tokens = Tokenizer::new(&PostgreSqlDialect {}, query)
+        .tokenize()
+        .map_err(|e| DataFusionError::Plan(e.to_string()))?;
+
+    let hint_regex = Regex::new(&format!(r"\b{}\s*\(\s*([^)]*?)\s*\)", name)).unwrap();
+
+    let mut hints = vec![];
+    let mut is_statement = false;
+    let mut hint = None;
+
+    for token in tokens {
+        match token {
+            Token::SemiColon => {
+                if is_statement {
+                    hints.push(hint.take());
+                }
+                is_statement = false;
+                hint = None;
+            }
+            Token::Whitespace(Whitespace::MultiLineComment(comment)) => {
+                let Some(comment) = comment.strip_prefix('+') else {
+                    continue;
+                };
+
+                if let Some(captures) = hint_regex.captures(comment) {
+                    hint = Some(captures.get(1).unwrap().as_str().to_string());
+                }
+            }
+            Token::Whitespace(_) | Token::EOF => {}
+            _ => is_statement = true,
+        }
+    }
+
+    if is_statement {
+        hints.push(hint);
+    }
+
+    Ok(hints)
+}
+
+/// A hint for where the stateful operators planned for a statement keep their state, written as
+/// a comment like `/*+ state(rocksdb) */`, which applies to all of them, or `/*+ state(join=disk,
+/// aggregate=memory) */`, which applies to operators of particular kinds; as with parallelism
+/// hints, the per-kind values take precedence.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct StateHint {
+    all: Option<StateStorage>,
+    by_kind: HashMap<&'static str, StateStorage>,
+}
+
+fn parse_state_storage(value: &str) -> Result<StateStorage> {
+    match value.to_lowercase().as_str() {
+        "memory" => Ok(StateStorage::Memory),
+        "disk" => Ok(StateStorage::Disk),
+        "rocksdb" => Ok(StateStorage::Rocksdb),
+        _ => plan_err!(
+            "invalid state hint '{}'; expected one of memory, disk, rocksdb",
+            value
+        ),
+    }
+}
+
+impl StateHint {
+    fn parse(args: &str) -> Result<Self> {
+        let mut hint = Self::default();
+
+        for arg in args.split(',').map(|s| s.trim()) {
+            match arg.split_once('=') {
+                Some((kind, value)) => {
+                    let kind = kind.trim().to_lowercase();
+                    let Some(kind) = STATEFUL_OPERATOR_KINDS.iter().find(|k| **k == kind) else {
+                        return plan_err!(
+                            "unknown operator '{}' in state hint; expected one of {}",
+                            kind,
+                            STATEFUL_OPERATOR_KINDS.join(", ")
+                        );
+                    };
+                    hint.by_kind
+                        .insert(*kind, parse_state_storage(value.trim())?);
+                }
+                None => {
+                    hint.all = Some(parse_state_storage(arg)?);
+                }
+            }
+        }
+
+        Ok(hint)
+    }
+
+    /// The hinted state storage for the node, if it's a stateful operator; fails if the hint
+    /// applies to an operator whose state storage can't be chosen
+    pub(crate) fn for_node(&self, node: &LogicalNode) -> Result<Option<StateStorage>> {
+        let kind = operator_kind(node.operator_name);
+        if !STATEFUL_OPERATOR_KINDS.contains(&kind) {
+            return Ok(None);
+        }
+
+        let storage = self.by_kind.get(kind).copied().or(self.all);
+        if storage.is_some() && !is_stateful(node) {
+            return plan_err!(
+                "state hints are not supported for non-windowed aggregates, which always keep their state in memory"
+            );
+        }
+
+        Ok(storage)
+    }
+}
+
+/// Whether the node is an operator whose state storage can be chosen; non-windowed aggregates
+/// keep state, but only in memory
+pub(crate) fn is_stateful(node: &LogicalNode) -> bool {
+    STATEFUL_OPERATOR_KINDS.contains(&operator_kind(node.operator_name))
+        && node.operator_name != OperatorName::UpdatingAggregate
+}
+
+/// Finds the state hint (a comment like `/*+ state(rocksdb) */`) for each statement in the
+/// query, in the order the statements are parsed
+pub(crate) fn state_hints(query: &str) -> Result<Vec<Option<StateHint>>> {
+    hint_arguments(query, "state")?
+        .into_iter()
+        .map(|args| args.map(|args| StateHint::parse(&args)).transpose())
+        .collect()
+}
+
+/// The hints given for a single statement
+#[derive(Debug, Clone, Default)]
+pub(crate) struct StatementHints {
+    pub parallelism: Option<ParallelismHint>,
+    pub state: Option<StateHint>,
+}
+
+/// Finds the hints for each statement in the query, in the order the statements are parsed
+pub(crate) fn statement_hints(query: &str) -> Result<Vec<StatementHints>> {
+    Ok(parallelism_hints(query)?
+        .into_iter()
+        .zip(state_hints(query)?)
+        .map(|(parallelism, state)| StatementHints { parallelism, state })
+        .collect())
+}
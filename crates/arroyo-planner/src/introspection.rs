@@ -0,0 +1,155 @@
+use std::sync::Arc;
+
+use arrow::array::{ArrayRef, BooleanArray, StringArray};
+use arrow::record_batch::RecordBatch;
+use arrow_schema::{DataType, Field, Schema};
+use datafusion::common::{plan_err, Result};
+use datafusion::sql::sqlparser::ast::Statement;
+
+use crate::tables::Table;
+use crate::ArroyoSchemaProvider;
+
+fn result_set(columns: Vec<(&str, DataType, ArrayRef)>) -> Result<RecordBatch> {
+    let (fields, arrays): (Vec<_>, Vec<_>) = columns
+        .into_iter()
+        .map(|(name, data_type, array)| (Field::new(name, data_type, true), array))
+        .unzip();
+
+    Ok(RecordBatch::try_new(Arc::new(Schema::new(fields)), arrays)?)
+}
+
+fn show_tables(schema_provider: &ArroyoSchemaProvider) -> Result<RecordBatch> {
+    let mut tables: Vec<_> = schema_provider
+        .tables
+        .values()
+        .filter(|t| !matches!(t, Table::PreviewSink { .. }))
+        .collect();
+    tables.sort_by_key(|t| t.name().to_lowercase());
+
+    let mut names = vec![];
+    let mut kinds = vec![];
+    let mut connectors = vec![];
+    let mut connection_types = vec![];
+
+    for table in tables {
+        names.push(table.name().to_string());
+        match table {
+            Table::ConnectorTable(t) => {
+                kinds.push("connector");
+                connectors.push(Some(t.connector.clone()));
+                connection_types.push(Some(t.connection_type.to_string()));
+            }
+            Table::MemoryTable { .. } => {
+                kinds.push("memory");
+                connectors.push(None);
+                connection_types.push(None);
+            }
+            Table::TableFromQuery { .. } => {
+                kinds.push("view");
+                connectors.push(None);
+                connection_types.push(None);
+            }
+            Table::PreviewSink { .. } => unreachable!(),
+        }
+    }
+
+    result_set(vec![
+        ("name", DataType::Utf8, Arc::new(StringArray::from(names))),
+        ("kind", DataType::Utf8, Arc::new(StringArray::from(kinds))),
+        (
+            "connector",
+            DataType::Utf8,
+            Arc::new(StringArray::from(connectors)),
+        ),
+        (
+            "connection_type",
+            DataType::Utf8,
+            Arc::new(StringArray::from(connection_types)),
+        ),
+    ])
+}
+
+fn show_connections(schema_provider: &ArroyoSchemaProvider) -> Result<RecordBatch> {
+    let mut profiles: Vec<_> = schema_provider.profiles.values().collect();
+    profiles.sort_by(|a, b| a.name.cmp(&b.name));
+
+    result_set(vec![
+        (
+            "name",
+            DataType::Utf8,
+            Arc::new(StringArray::from_iter_values(
+                profiles.iter().map(|p| &p.name),
+            )),
+        ),
+        (
+            "connector",
+            DataType::Utf8,
+            Arc::new(StringArray::from_iter_values(
+                profiles.iter().map(|p| &p.connector),
+            )),
+        ),
+        (
+            "description",
+            DataType::Utf8,
+            Arc::new(StringArray::from_iter_values(
+                profiles.iter().map(|p| &p.description),
+            )),
+        ),
+    ])
+}
+
+fn describe_table(schema_provider: &ArroyoSchemaProvider, name: &str) -> Result<RecordBatch> {
+    let Some(table) = schema_provider.get_table(name) else {
+        return plan_err!("table '{}' not found", name);
+    };
+
+    let fields = table.get_fields();
+
+    result_set(vec![
+        (
+            "column_name",
+            DataType::Utf8,
+            Arc::new(StringArray::from_iter_values(
+                fields.iter().map(|f| f.name()),
+            )),
+        ),
+        (
+            "data_type",
+            DataType::Utf8,
+            Arc::new(StringArray::from_iter_values(
+                fields.iter().map(|f| f.data_type().to_string()),
+            )),
+        ),
+        (
+            "is_nullable",
+            DataType::Boolean,
+            Arc::new(BooleanArray::from_iter(
+                fields.iter().map(|f| Some(f.is_nullable())),
+            )),
+        ),
+    ])
+}
+
+/// Handles statements that describe what's registered in the schema provider (`SHOW TABLES`,
+/// `SHOW CONNECTIONS`, and `DESCRIBE <table>`), returning their result set, or `None` if the
+/// statement is not one of them
+pub(crate) fn try_handle_introspection(
+    statement: &Statement,
+    schema_provider: &ArroyoSchemaProvider,
+) -> Result<Option<RecordBatch>> {
+    match statement {
+        Statement::ShowTables { .. } => show_tables(schema_provider).map(Some),
+        Statement::ShowVariable { variable }
+            if variable.len() == 1 && variable[0].value.eq_ignore_ascii_case("connections") =>
+        {
+            show_connections(schema_provider).map(Some)
+        }
+        Statement::ExplainTable { table_name, .. } => {
+            if table_name.0.len() != 1 {
+                return plan_err!("invalid table name '{}'", table_name);
+            }
+            describe_table(schema_provider, &table_name.0[0].value).map(Some)
+        }
+        _ => Ok(None),
+    }
+}
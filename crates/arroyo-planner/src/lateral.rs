@@ -0,0 +1,224 @@
+//! Support for lateral joins against table-valued UDFs, like
+//!
+//! ```sql
+//! SELECT e.id, w.word
+//! FROM events e
+//! CROSS JOIN LATERAL split_words(e.text) AS w(word)
+//! ```
+//!
+//! A table-valued UDF is any function that returns a list; the join produces one row for each
+//! element of the list, containing the columns of the input row along with the element. These
+//! joins are rewritten before planning into a subquery that unnests the function's result, so
+//! that they're planned as a flat-map over the input rather than as a join.
+
+use std::collections::HashSet;
+use std::ops::ControlFlow;
+
+use datafusion::common::{plan_err, DataFusionError, Result};
+use datafusion::sql::sqlparser::ast::{
+    visit_expressions_mut, Expr, FunctionArg, Ident, JoinOperator, ObjectName, Query, Select,
+    SelectItem, SetExpr, Statement, TableAlias, TableFactor, TableWithJoins, VisitMut, VisitorMut,
+};
+use datafusion::sql::sqlparser::dialect::PostgreSqlDialect;
+use datafusion::sql::sqlparser::parser::Parser;
+
+use crate::parse_sql;
+
+/// Builds `(SELECT *, unnest(<function>(<args>)) AS <column> FROM <input>)`
+fn unnest_subquery(
+    input: TableWithJoins,
+    name: &ObjectName,
+    args: &[FunctionArg],
+    column: Ident,
+) -> Result<TableFactor> {
+    let Some(Statement::Query(mut subquery)) = parse_sql("SELECT * FROM input")?.pop() else {
+        unreachable!("template should parse to a query");
+    };
+    let SetExpr::Select(select) = subquery.body.as_mut() else {
+        unreachable!("template should parse to a select");
+    };
+
+    let args: Vec<_> = args.iter().map(|a| a.to_string()).collect();
+    let expr = Parser::new(&PostgreSqlDialect {})
+        .try_with_sql(&format!("unnest({}({}))", name, args.join(", ")))?
+        .parse_expr()?;
+
+    select.from = vec![input];
+    select.projection.push(SelectItem::ExprWithAlias {
+        expr,
+        alias: column,
+    });
+
+    Ok(TableFactor::Derived {
+        lateral: false,
+        subquery,
+        alias: None,
+    })
+}
+
+/// The name of the column produced by a lateral table function and the qualifier (if any)
+/// that the query may use to refer to it
+fn lateral_column(name: &ObjectName, alias: &Option<TableAlias>) -> Result<(Ident, Option<Ident>)> {
+    let Some(alias) = alias else {
+        let function = name
+            .0
+            .last()
+            .cloned()
+            .unwrap_or_else(|| Ident::new("value"));
+        return Ok((function, None));
+    };
+
+    match alias.columns.as_slice() {
+        [] => Ok((alias.name.clone(), Some(alias.name.clone()))),
+        [column] => Ok((column.clone(), Some(alias.name.clone()))),
+        _ => plan_err!(
+            "table function {} produces a single column, but {} column names were given",
+            name,
+            alias.columns.len()
+        ),
+    }
+}
+
+/// Rewrites the lateral table functions joined in a FROM item, returning the qualifiers that
+/// referred to them
+fn rewrite_table_with_joins(table: &mut TableWithJoins) -> Result<Vec<Ident>> {
+    let lateral =
+        |factor: &TableFactor| matches!(factor, TableFactor::Function { lateral: true, .. });
+
+    if lateral(&table.relation) {
+        return plan_err!("table functions must be joined to a table with CROSS JOIN LATERAL");
+    }
+
+    if !table.joins.iter().any(|j| lateral(&j.relation)) {
+        return Ok(vec![]);
+    }
+
+    let joins = std::mem::take(&mut table.joins);
+    let mut current = TableWithJoins {
+        relation: table.relation.clone(),
+        joins: vec![],
+    };
+    let mut qualifiers = vec![];
+
+    for join in joins {
+        let TableFactor::Function {
+            lateral: true,
+            name,
+            args,
+            alias,
+        } = &join.relation
+        else {
+            current.joins.push(join);
+            continue;
+        };
+
+        if !matches!(join.join_operator, JoinOperator::CrossJoin) {
+            return plan_err!(
+                "table function {} can only be joined with CROSS JOIN LATERAL",
+                name
+            );
+        }
+
+        let (column, qualifier) = lateral_column(name, alias)?;
+        qualifiers.extend(qualifier);
+
+        current = TableWithJoins {
+            relation: unnest_subquery(current, name, args, column)?,
+            joins: vec![],
+        };
+    }
+
+    *table = current;
+    Ok(qualifiers)
+}
+
+fn rewrite_select(select: &mut Select) -> Result<()> {
+    let mut qualifiers = HashSet::new();
+    for table in &mut select.from {
+        qualifiers.extend(
+            rewrite_table_with_joins(table)?
+                .into_iter()
+                .map(|q| q.value),
+        );
+    }
+
+    if qualifiers.is_empty() {
+        return Ok(());
+    }
+
+    // the generated columns are unqualified in the rewritten query, so references to them
+    // through the function's alias (like `w.word`) are replaced with bare column names
+    let _ = visit_expressions_mut(select, |expr| {
+        if let Expr::CompoundIdentifier(idents) = expr {
+            if idents.len() == 2 && qualifiers.contains(&idents[0].value) {
+                *expr = Expr::Identifier(idents[1].clone());
+            }
+        }
+        ControlFlow::<()>::Continue(())
+    });
+
+    Ok(())
+}
+
+fn rewrite_set_expr(set_expr: &mut SetExpr) -> Result<()> {
+    match set_expr {
+        SetExpr::Select(select) => rewrite_select(select),
+        SetExpr::SetOperation { left, right, .. } => {
+            rewrite_set_expr(left)?;
+            rewrite_set_expr(right)
+        }
+        _ => Ok(()),
+    }
+}
+
+struct LateralJoinRewriter;
+
+impl VisitorMut for LateralJoinRewriter {
+    type Break = DataFusionError;
+
+    fn post_visit_query(&mut self, query: &mut Query) -> ControlFlow<Self::Break> {
+        match rewrite_set_expr(&mut query.body) {
+            Ok(()) => ControlFlow::Continue(()),
+            Err(e) => ControlFlow::Break(e),
+        }
+    }
+}
+
+/// Rewrites every `CROSS JOIN LATERAL <function>(...)` in the statement into an unnest of the
+/// function's result
+pub(crate) fn rewrite_lateral_joins(mut statement: Statement) -> Result<Statement> {
+    match statement.visit(&mut LateralJoinRewriter) {
+        ControlFlow::Continue(()) => Ok(statement),
+        ControlFlow::Break(e) => Err(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rewrite(sql: &str) -> Result<String> {
+        let statement = parse_sql(sql)?.pop().unwrap();
+        Ok(rewrite_lateral_joins(statement)?.to_string())
+    }
+
+    #[test]
+    fn test_rewrite_lateral_joins() {
+        assert_eq!(
+            rewrite(
+                "SELECT e.id, w.word FROM events e \
+                CROSS JOIN LATERAL split_words(e.text) AS w(word) WHERE w.word <> 'a'"
+            )
+            .unwrap(),
+            "SELECT e.id, word FROM (SELECT *, unnest(split_words(e.text)) AS word FROM events AS e) \
+            WHERE word <> 'a'"
+        );
+
+        assert_eq!(
+            rewrite("SELECT * FROM events CROSS JOIN LATERAL split_words(text)").unwrap(),
+            "SELECT * FROM (SELECT *, unnest(split_words(text)) AS split_words FROM events)"
+        );
+
+        assert!(rewrite("SELECT * FROM events JOIN LATERAL split_words(text) ON true").is_err());
+    }
+}
@@ -4,7 +4,12 @@ pub mod builder;
 pub(crate) mod extension;
 pub mod external;
 mod functions;
+mod hints;
+mod introspection;
+mod lateral;
+mod lineage;
 pub mod logical;
+mod parallelism;
 pub mod physical;
 mod plan;
 mod rewriters;
@@ -20,10 +25,14 @@ mod utils;
 use anyhow::bail;
 use arrow::array::ArrayRef;
 use arrow::datatypes::{self, DataType};
-use arrow_schema::{Field, FieldRef, Schema};
+use arrow::record_batch::RecordBatch;
+use arrow_schema::{Field, FieldRef, Schema, TimeUnit};
 use arroyo_datastream::WindowType;
 
-use datafusion::common::{not_impl_err, plan_err, Column, DFSchema, Result, ScalarValue};
+use datafusion::common::tree_node::{Transformed, TreeNode};
+use datafusion::common::{
+    not_impl_err, plan_datafusion_err, plan_err, Column, DFSchema, Result, ScalarValue,
+};
 use datafusion::datasource::DefaultTableSource;
 #[allow(deprecated)]
 use datafusion::physical_plan::functions::make_scalar_function;
@@ -37,13 +46,13 @@ use datafusion::sql::{planner::ContextProvider, sqlparser, TableReference};
 use datafusion::logical_expr::expr::ScalarFunction;
 use datafusion::logical_expr::{
     create_udaf, Expr, Extension, LogicalPlan, ReturnTypeFunction, ScalarUDF, Signature,
-    Volatility, WindowUDF,
+    TypeSignature, Volatility, WindowUDF,
 };
 
 use datafusion::logical_expr::{AggregateUDF, TableSource};
 use logical::LogicalBatchInput;
 
-use schemas::window_arrow_struct;
+use schemas::{session_events_struct, window_arrow_struct};
 use tables::{Insert, Table};
 
 use crate::builder::PlanToGraphVisitor;
@@ -56,14 +65,24 @@ use std::collections::HashSet;
 use std::fmt::Debug;
 
 use crate::functions::{is_json_union, serialize_outgoing_json};
-use crate::rewriters::{SourceMetadataVisitor, TimeWindowUdfChecker, UnnestRewriter};
+use crate::hints::{is_stateful, statement_hints};
+use crate::introspection::try_handle_introspection;
+use crate::lateral::rewrite_lateral_joins;
+use crate::parallelism::assign_parallelism;
+pub use crate::parallelism::limit_to_source_partitions;
+use crate::rewriters::{
+    DelayRewriter, SourceMetadataVisitor, TimeWindowUdfChecker, UnnestRewriter,
+};
 
 use crate::udafs::EmptyUdaf;
 use arrow::compute::kernels::cast_utils::parse_interval_day_time;
 use arroyo_datastream::logical::LogicalProgram;
 use arroyo_operator::connector::Connection;
+use arroyo_rpc::config::{HumanReadableDuration, PreviewConfig};
 use arroyo_rpc::df::ArroyoSchema;
-use arroyo_rpc::TIMESTAMP_FIELD;
+use arroyo_rpc::grpc::api::{Autoscaling, CheckpointStorage, StateStorage};
+use arroyo_rpc::{TIMESTAMP_FIELD, TRACEPARENT_FIELD};
+use arroyo_types::KEY_GROUPS;
 use arroyo_udf_host::parse::{inner_type, UdfDef};
 use arroyo_udf_host::ParsedUdfFile;
 use arroyo_udf_python::PythonUDF;
@@ -74,7 +93,16 @@ use datafusion::logical_expr;
 use datafusion::logical_expr::expr_rewriter::FunctionRewrite;
 use datafusion::logical_expr::planner::ExprPlanner;
 use datafusion::optimizer::Analyzer;
-use datafusion::sql::sqlparser::ast::{OneOrManyWithParens, Statement};
+use datafusion::sql::sqlparser::ast::{
+    visit_expressions, Expr as SqlExpr, OneOrManyWithParens, Statement,
+};
+use datafusion_proto::logical_plan::from_proto::parse_expr;
+use datafusion_proto::logical_plan::to_proto::serialize_expr;
+use datafusion_proto::logical_plan::DefaultLogicalExtensionCodec;
+use datafusion_proto::protobuf::LogicalExprNode;
+use petgraph::graph::NodeIndex;
+use prost::Message;
+use std::ops::ControlFlow;
 use std::time::{Duration, SystemTime};
 use std::{collections::HashMap, sync::Arc};
 use syn::Item;
@@ -83,6 +111,8 @@ use unicase::UniCase;
 
 const DEFAULT_IDLE_TIME: Option<Duration> = Some(Duration::from_secs(5 * 60));
 pub const ASYNC_RESULT_FIELD: &str = "__async_result";
+/// The column holding the time at which a row passed through `delay()` is due to be emitted
+pub const DELAYED_AT_FIELD: &str = "__delayed_at";
 
 #[derive(Clone, Debug)]
 pub struct CompiledSql {
@@ -90,15 +120,56 @@ pub struct CompiledSql {
     pub connection_ids: Vec<i64>,
 }
 
+/// The result of planning a query: either a pipeline to run, or for statements that introspect
+/// the schema provider (like `SHOW TABLES`), a result set
+#[derive(Clone, Debug)]
+pub enum PlannedSql {
+    Pipeline(CompiledSql),
+    ResultSet(RecordBatch),
+}
+
+impl PlannedSql {
+    pub fn into_pipeline(self) -> Result<CompiledSql> {
+        match self {
+            PlannedSql::Pipeline(compiled) => Ok(compiled),
+            PlannedSql::ResultSet(_) => {
+                plan_err!("SHOW and DESCRIBE statements can't be run as a pipeline")
+            }
+        }
+    }
+}
+
+/// Whether a pipeline runs continuously, or runs to completion over bounded sources (for
+/// example to backfill a table with the same query that will later run as a stream)
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ExecutionMode {
+    #[default]
+    Streaming,
+    // every source must be bounded, and aggregates that aren't windowed emit their results
+    // once, when their input is exhausted, rather than updating them as data arrives
+    Bounded,
+}
+
 #[derive(Clone)]
 pub struct PlanningOptions {
     ttl: Duration,
+    // per-side overrides of `ttl` for the state retained by updating joins
+    left_join_ttl: Option<Duration>,
+    right_join_ttl: Option<Duration>,
+    // how long a source may go without data before it's marked idle, for tables that
+    // don't set their own `idle_time`
+    source_idle_time: Option<Duration>,
+    execution_mode: ExecutionMode,
 }
 
 impl Default for PlanningOptions {
     fn default() -> Self {
         Self {
             ttl: Duration::from_secs(24 * 60 * 60),
+            left_join_ttl: None,
+            right_join_ttl: None,
+            source_idle_time: DEFAULT_IDLE_TIME,
+            execution_mode: ExecutionMode::Streaming,
         }
     }
 }
@@ -118,6 +189,12 @@ pub struct ArroyoSchemaProvider {
     pub expr_planners: Vec<Arc<dyn ExprPlanner>>,
     pub planning_options: PlanningOptions,
     pub analyzer: Analyzer,
+    // whether the statement currently being planned can refer to the _timestamp column of
+    // connector tables
+    expose_timestamp: bool,
+    // whether the statement currently being planned refers to the hidden _traceparent column,
+    // which is otherwise left out of the schemas of connector tables
+    expose_traceparent: bool,
 }
 
 pub fn register_functions(registry: &mut dyn FunctionRegistry) {
@@ -160,6 +237,20 @@ impl ArroyoSchemaProvider {
                 make_scalar_function(fn_impl),
             )),
         );
+        functions.insert(
+            "tumble_by".to_string(),
+            Arc::new(create_udf(
+                "tumble_by",
+                vec![
+                    DataType::Timestamp(TimeUnit::Nanosecond, None),
+                    DataType::Interval(datatypes::IntervalUnit::MonthDayNano),
+                ],
+                window_return_type.clone(),
+                Volatility::Volatile,
+                #[allow(deprecated)]
+                make_scalar_function(fn_impl),
+            )),
+        );
         functions.insert(
             "session".to_string(),
             Arc::new(create_udf(
@@ -195,6 +286,29 @@ impl ArroyoSchemaProvider {
                 )
             }),
         );
+        functions.insert(
+            "delay".to_string(),
+            Arc::new({
+                let interval = DataType::Interval(datatypes::IntervalUnit::MonthDayNano);
+                let return_type: ReturnTypeFunction =
+                    Arc::new(|_| Ok(Arc::new(DataType::Timestamp(TimeUnit::Nanosecond, None))));
+                // delay(interval) or delay(interval, allowed_lateness)
+                #[allow(deprecated)]
+                ScalarUDF::new(
+                    "delay",
+                    &Signature::one_of(
+                        vec![
+                            TypeSignature::Exact(vec![interval.clone()]),
+                            TypeSignature::Exact(vec![interval.clone(), interval]),
+                        ],
+                        Volatility::Volatile,
+                    ),
+                    &return_type,
+                    #[allow(deprecated)]
+                    &make_scalar_function(fn_impl),
+                )
+            }),
+        );
         // Registering kafka connector metadata function
         functions.insert(
             "metadata".to_string(),
@@ -212,6 +326,19 @@ impl ArroyoSchemaProvider {
             ..Default::default()
         };
 
+        // a placeholder; aggregates over it are planned as a session events operator instead
+        registry.aggregate_functions.insert(
+            "session_events".to_string(),
+            Arc::new(create_udaf(
+                "session_events",
+                vec![DataType::Interval(datatypes::IntervalUnit::MonthDayNano)],
+                Arc::new(session_events_struct()),
+                Volatility::Volatile,
+                Arc::new(|_| Ok(Box::new(EmptyUdaf {}))),
+                Arc::new(vec![session_events_struct()]),
+            )),
+        );
+
         register_functions(&mut registry);
 
         registry
@@ -381,7 +508,17 @@ impl ContextProvider for ArroyoSchemaProvider {
             .get_table(name.to_string())
             .ok_or_else(|| DataFusionError::Plan(format!("Table {} not found", name)))?;
 
-        let fields = table.get_fields();
+        let mut fields = table.get_fields();
+        if !self.expose_traceparent && matches!(table, Table::ConnectorTable(_)) {
+            fields.retain(|f| f.name() != TRACEPARENT_FIELD);
+        }
+        if self.expose_timestamp && matches!(table, Table::ConnectorTable(_)) {
+            fields.push(Arc::new(Field::new(
+                TIMESTAMP_FIELD,
+                DataType::Timestamp(TimeUnit::Nanosecond, None),
+                false,
+            )));
+        }
         let schema = Arc::new(Schema::new_with_metadata(fields, HashMap::new()));
         Ok(create_table(name.to_string(), schema))
     }
@@ -479,12 +616,46 @@ impl FunctionRegistry for ArroyoSchemaProvider {
 #[derive(Clone, Debug)]
 pub struct SqlConfig {
     pub default_parallelism: usize,
+    // set in the query with `SET parallelism` and `SET checkpoint.interval`; these take
+    // precedence over the configuration the pipeline was submitted with
+    pub parallelism: Option<usize>,
+    pub checkpoint_interval: Option<Duration>,
+    // set in the query with `SET queue.size` and `SET queue.max_bytes`; these take precedence
+    // over the worker configuration
+    pub queue_size: Option<u32>,
+    pub queue_max_bytes: Option<u64>,
+    // set in the query with `SET state.storage`; the state storage of stateful operators that
+    // aren't given one by a hint
+    pub state_storage: Option<StateStorage>,
+    // set in the query with `SET checkpoint.url` and `SET checkpoint.storage_options`; where the
+    // pipeline's checkpoints are written, instead of the cluster's checkpoint url
+    pub checkpoint_url: Option<String>,
+    pub checkpoint_storage_options: HashMap<String, String>,
+    // set in the query with `SET checkpoint.alignment_timeout`
+    pub checkpoint_alignment_timeout: Option<Duration>,
+    // set in the query with `SET autoscaling.min_parallelism` and
+    // `SET autoscaling.max_parallelism`; the pipeline is autoscaled if a maximum is set
+    pub autoscaling_min_parallelism: Option<usize>,
+    pub autoscaling_max_parallelism: Option<usize>,
+    // when planning a preview, the limits it runs under; these can't be loosened by the query
+    pub preview: Option<PreviewConfig>,
 }
 
 impl Default for SqlConfig {
     fn default() -> Self {
         Self {
             default_parallelism: 4,
+            parallelism: None,
+            checkpoint_interval: None,
+            queue_size: None,
+            queue_max_bytes: None,
+            state_storage: None,
+            checkpoint_url: None,
+            checkpoint_storage_options: HashMap::new(),
+            checkpoint_alignment_timeout: None,
+            autoscaling_min_parallelism: None,
+            autoscaling_max_parallelism: None,
+            preview: None,
         }
     }
 }
@@ -500,7 +671,9 @@ pub async fn parse_and_get_program(
         return plan_err!("Query is empty");
     }
 
-    parse_and_get_arrow_program(query, schema_provider, config).await
+    parse_and_get_arrow_program(query, schema_provider, config)
+        .await?
+        .into_pipeline()
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -568,6 +741,56 @@ fn find_window(expression: &Expr) -> Result<Option<WindowType>> {
                 let gap = get_duration(&args[0])?;
                 Ok(Some(WindowType::Session { gap }))
             }
+            "tumble_by" => {
+                if args.len() != 2 {
+                    return plan_err!(
+                        "wrong number of arguments for tumble_by(); expected a binning function \
+                        and a maximum width"
+                    );
+                }
+                let max_width = get_duration(&args[1])?;
+                if max_width.is_zero() {
+                    return plan_err!("tumble_by() maximum width must be positive");
+                }
+
+                // the binning function is evaluated by the operator over batches containing only
+                // the timestamp, so it may not refer to any other columns
+                let mut uses_timestamp = false;
+                let binning_function = args[0]
+                    .clone()
+                    .transform_up(|expr| match expr {
+                        Expr::Column(column) if column.name == TIMESTAMP_FIELD => {
+                            uses_timestamp = true;
+                            Ok(Transformed::yes(Expr::Column(Column::new_unqualified(
+                                TIMESTAMP_FIELD,
+                            ))))
+                        }
+                        Expr::Column(column) => plan_err!(
+                            "tumble_by() binning function may only refer to {}, not {}",
+                            TIMESTAMP_FIELD,
+                            column
+                        ),
+                        expr => Ok(Transformed::no(expr)),
+                    })?
+                    .data;
+
+                if !uses_timestamp {
+                    return plan_err!(
+                        "tumble_by() binning function must be computed from {}",
+                        TIMESTAMP_FIELD
+                    );
+                }
+
+                let binning_function =
+                    serialize_expr(&binning_function, &DefaultLogicalExtensionCodec {}).map_err(
+                        |e| plan_datafusion_err!("could not serialize binning function: {}", e),
+                    )?;
+
+                Ok(Some(WindowType::Custom {
+                    max_width,
+                    binning_function: binning_function.encode_to_vec(),
+                }))
+            }
             _ => Ok(None),
         },
         Expr::Alias(logical_expr::expr::Alias {
@@ -579,6 +802,26 @@ fn find_window(expression: &Expr) -> Result<Option<WindowType>> {
     }
 }
 
+/// Deserializes the binning function of a window with custom bins
+fn custom_binning_function(
+    window: &WindowType,
+    registry: &dyn FunctionRegistry,
+) -> Result<Option<Expr>> {
+    let WindowType::Custom {
+        binning_function, ..
+    } = window
+    else {
+        return Ok(None);
+    };
+
+    let node = LogicalExprNode::decode(binning_function.as_slice())
+        .map_err(|e| plan_datafusion_err!("invalid binning function: {}", e))?;
+
+    parse_expr(&node, registry, &DefaultLogicalExtensionCodec {})
+        .map(Some)
+        .map_err(|e| plan_datafusion_err!("invalid binning function: {}", e))
+}
+
 #[allow(unused)]
 fn inspect_plan(logical_plan: LogicalPlan) -> LogicalPlan {
     info!("logical plan = {}", logical_plan.display_graphviz());
@@ -592,7 +835,9 @@ pub fn rewrite_plan(
     let rewritten_plan = plan
         .rewrite_with_subqueries(&mut ArroyoRewriter { schema_provider })?
         .data
-        .rewrite_with_subqueries(&mut UnnestRewriter {})?;
+        .rewrite_with_subqueries(&mut UnnestRewriter {})?
+        .data
+        .rewrite_with_subqueries(&mut DelayRewriter {})?;
 
     // check for window functions
     rewritten_plan
@@ -602,9 +847,176 @@ pub fn rewrite_plan(
     Ok(rewritten_plan.data)
 }
 
+const SET_OPTIONS: &[&str] = &[
+    "updating_ttl",
+    "left_join_ttl",
+    "right_join_ttl",
+    "arroyo.source.idle_time",
+    "parallelism",
+    "checkpoint.interval",
+    "execution.mode",
+    "queue.size",
+    "queue.max_bytes",
+    "state.storage",
+    "checkpoint.url",
+    "checkpoint.storage_options",
+    "checkpoint.alignment_timeout",
+    "autoscaling.min_parallelism",
+    "autoscaling.max_parallelism",
+];
+
+/// Parses a duration written as an interval string, like '30 seconds' or '1 day', or in the
+/// short form used in the config file, like '1m'
+pub(crate) fn parse_duration(s: &str) -> Option<Duration> {
+    let Ok(interval) = parse_interval_day_time(s) else {
+        return s.parse::<HumanReadableDuration>().ok().map(|d| *d);
+    };
+    if interval.days < 0 || interval.milliseconds < 0 {
+        return None;
+    }
+
+    Some(
+        Duration::from_secs(interval.days as u64 * 24 * 60 * 60)
+            + Duration::from_millis(interval.milliseconds as u64),
+    )
+}
+
+fn parse_set_duration(option: &str, value: &[sqlparser::ast::Expr]) -> Result<Duration> {
+    if value.len() != 1 {
+        return plan_err!("invalid `SET {option}` call; expected exactly one expression");
+    }
+
+    let sqlparser::ast::Expr::Value(sqlparser::ast::Value::SingleQuotedString(s)) =
+        value.first().unwrap()
+    else {
+        return plan_err!("invalid `SET {option}`; expected a singly-quoted string argument");
+    };
+
+    parse_duration(s).ok_or_else(|| {
+        DataFusionError::Plan(format!(
+            "could not parse '{}' as an interval in `SET {}` statement",
+            s, option
+        ))
+    })
+}
+
+fn parse_set_positive_int(option: &str, value: &[sqlparser::ast::Expr]) -> Result<u64> {
+    if value.len() != 1 {
+        return plan_err!("invalid `SET {option}` call; expected exactly one expression");
+    }
+
+    let n = match value.first().unwrap() {
+        sqlparser::ast::Expr::Value(sqlparser::ast::Value::Number(n, _)) => n.parse().ok(),
+        sqlparser::ast::Expr::Value(sqlparser::ast::Value::SingleQuotedString(s)) => s.parse().ok(),
+        _ => None,
+    };
+
+    match n {
+        Some(n) if n > 0 => Ok(n),
+        _ => plan_err!(
+            "invalid `SET {option}`; expected a positive integer but found {}",
+            value.first().unwrap()
+        ),
+    }
+}
+
+/// Parses a size in bytes, written as a number with an optional (binary) unit, like '65536',
+/// '512KB' or '64 MB'
+pub(crate) fn parse_bytes(s: &str) -> Option<u64> {
+    let s = s.trim();
+    let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (number, unit) = s.split_at(split);
+
+    let multiplier = match unit.trim().to_uppercase().as_str() {
+        "" | "B" => 1,
+        "KB" | "KIB" => 1 << 10,
+        "MB" | "MIB" => 1 << 20,
+        "GB" | "GIB" => 1 << 30,
+        _ => return None,
+    };
+
+    number.parse::<u64>().ok()?.checked_mul(multiplier)
+}
+
+fn parse_set_bytes(option: &str, value: &[sqlparser::ast::Expr]) -> Result<u64> {
+    if value.len() != 1 {
+        return plan_err!("invalid `SET {option}` call; expected exactly one expression");
+    }
+
+    let bytes = match value.first().unwrap() {
+        sqlparser::ast::Expr::Value(sqlparser::ast::Value::Number(n, _)) => n.parse().ok(),
+        sqlparser::ast::Expr::Value(sqlparser::ast::Value::SingleQuotedString(s)) => parse_bytes(s),
+        _ => None,
+    };
+
+    match bytes {
+        Some(b) if b > 0 => Ok(b),
+        _ => plan_err!(
+            "invalid `SET {option}`; expected a positive size in bytes (like '64MB') but found {}",
+            value.first().unwrap()
+        ),
+    }
+}
+
+fn parse_set_execution_mode(value: &[sqlparser::ast::Expr]) -> Result<ExecutionMode> {
+    if value.len() != 1 {
+        return plan_err!("invalid `SET execution.mode` call; expected exactly one expression");
+    }
+
+    match value.first().unwrap() {
+        sqlparser::ast::Expr::Value(sqlparser::ast::Value::SingleQuotedString(s)) => {
+            match s.to_lowercase().as_str() {
+                "streaming" => Ok(ExecutionMode::Streaming),
+                "bounded" => Ok(ExecutionMode::Bounded),
+                _ => plan_err!(
+                    "invalid `SET execution.mode`; expected 'streaming' or 'bounded' but found '{}'",
+                    s
+                ),
+            }
+        }
+        _ => plan_err!("invalid `SET execution.mode`; expected a singly-quoted string argument"),
+    }
+}
+
+fn parse_set_string(option: &str, value: &[sqlparser::ast::Expr]) -> Result<String> {
+    if value.len() != 1 {
+        return plan_err!("invalid `SET {option}` call; expected exactly one expression");
+    }
+
+    let sqlparser::ast::Expr::Value(sqlparser::ast::Value::SingleQuotedString(s)) =
+        value.first().unwrap()
+    else {
+        return plan_err!("invalid `SET {option}`; expected a singly-quoted string argument");
+    };
+
+    Ok(s.clone())
+}
+
+fn parse_set_state_storage(value: &[sqlparser::ast::Expr]) -> Result<StateStorage> {
+    if value.len() != 1 {
+        return plan_err!("invalid `SET state.storage` call; expected exactly one expression");
+    }
+
+    match value.first().unwrap() {
+        sqlparser::ast::Expr::Value(sqlparser::ast::Value::SingleQuotedString(s)) => {
+            match s.to_lowercase().as_str() {
+                "memory" => Ok(StateStorage::Memory),
+                "disk" => Ok(StateStorage::Disk),
+                "rocksdb" => Ok(StateStorage::Rocksdb),
+                _ => plan_err!(
+                    "invalid `SET state.storage`; expected 'memory', 'disk' or 'rocksdb' but found '{}'",
+                    s
+                ),
+            }
+        }
+        _ => plan_err!("invalid `SET state.storage`; expected a singly-quoted string argument"),
+    }
+}
+
 fn try_handle_set_variable(
     statement: &Statement,
     schema_provider: &mut ArroyoSchemaProvider,
+    config: &mut SqlConfig,
 ) -> Result<bool> {
     if let Statement::SetVariable {
         variables, value, ..
@@ -614,41 +1026,134 @@ fn try_handle_set_variable(
             return plan_err!("invalid syntax for `SET` call");
         };
 
-        if opt.to_string() != "updating_ttl" {
-            return plan_err!(
-                "invalid option '{}'; supported options are 'updating_ttl'",
-                opt
-            );
-        }
-
-        if value.len() != 1 {
-            return plan_err!("invalid `SET updating_ttl` call; expected exactly one expression");
+        let option = opt.to_string();
+        let options = &mut schema_provider.planning_options;
+        match option.as_str() {
+            "updating_ttl" => {
+                options.ttl = parse_set_duration(&option, value)?;
+            }
+            "left_join_ttl" | "right_join_ttl" => {
+                // a zero TTL would expire each row of that side of the join as soon as it
+                // arrived, so it never joins with anything
+                let ttl = parse_set_duration(&option, value)?;
+                if ttl.is_zero() {
+                    return plan_err!("`SET {}` must be greater than zero", option);
+                }
+                if option == "left_join_ttl" {
+                    options.left_join_ttl = Some(ttl);
+                } else {
+                    options.right_join_ttl = Some(ttl);
+                }
+            }
+            "arroyo.source.idle_time" => {
+                // a zero duration disables idleness detection
+                options.source_idle_time =
+                    Some(parse_set_duration(&option, value)?).filter(|d| !d.is_zero());
+            }
+            "parallelism" => {
+                config.parallelism = Some(parse_set_positive_int(&option, value)? as usize);
+            }
+            "checkpoint.interval" => {
+                let interval = parse_set_duration(&option, value)?;
+                if interval.is_zero() {
+                    return plan_err!("`SET checkpoint.interval` must be greater than zero");
+                }
+                config.checkpoint_interval = Some(interval);
+            }
+            "checkpoint.alignment_timeout" => {
+                let timeout = parse_set_duration(&option, value)?;
+                if timeout.is_zero() {
+                    return plan_err!(
+                        "`SET checkpoint.alignment_timeout` must be greater than zero"
+                    );
+                }
+                config.checkpoint_alignment_timeout = Some(timeout);
+            }
+            "autoscaling.min_parallelism" => {
+                config.autoscaling_min_parallelism =
+                    Some(parse_set_positive_int(&option, value)? as usize);
+            }
+            "autoscaling.max_parallelism" => {
+                config.autoscaling_max_parallelism =
+                    Some(parse_set_positive_int(&option, value)? as usize);
+            }
+            "execution.mode" => {
+                options.execution_mode = parse_set_execution_mode(value)?;
+            }
+            "queue.size" => {
+                let size = parse_set_positive_int(&option, value)?;
+                config.queue_size = Some(u32::try_from(size).map_err(|_| {
+                    DataFusionError::Plan(format!("`SET queue.size` must be at most {}", u32::MAX))
+                })?);
+            }
+            "queue.max_bytes" => {
+                config.queue_max_bytes = Some(parse_set_bytes(&option, value)?);
+            }
+            "state.storage" => {
+                config.state_storage = Some(parse_set_state_storage(value)?);
+            }
+            "checkpoint.url" => {
+                let url = parse_set_string(&option, value)?;
+                // values may refer to environment variables, which are only substituted where
+                // the storage is used
+                if !url.contains("{{") {
+                    arroyo_storage::BackendConfig::parse_url(&url, false).map_err(|e| {
+                        DataFusionError::Plan(format!("invalid `SET checkpoint.url`: {}", e))
+                    })?;
+                }
+                config.checkpoint_url = Some(url);
+            }
+            "checkpoint.storage_options" => {
+                let options = parse_set_string(&option, value)?;
+                config.checkpoint_storage_options =
+                    serde_json::from_str(&options).map_err(|e| {
+                        DataFusionError::Plan(format!(
+                            "invalid `SET checkpoint.storage_options`; expected a JSON object \
+                            of string values: {}",
+                            e
+                        ))
+                    })?;
+            }
+            _ => {
+                return plan_err!(
+                    "invalid option '{}'; supported options are {}",
+                    opt,
+                    SET_OPTIONS
+                        .iter()
+                        .map(|o| format!("'{o}'"))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                );
+            }
         }
 
-        let sqlparser::ast::Expr::Value(sqlparser::ast::Value::SingleQuotedString(s)) =
-            value.first().unwrap()
-        else {
-            return plan_err!(
-                "invalid `SET updating_ttl`; expected a singly-quoted string argument"
-            );
-        };
-
-        let interval = parse_interval_day_time(s).map_err(|_| {
-            DataFusionError::Plan(format!(
-                "could not parse '{}' as an interval in `SET updating_ttl` statement",
-                s
-            ))
-        })?;
-
-        schema_provider.planning_options.ttl =
-            Duration::from_secs(interval.days as u64 * 24 * 60 * 60)
-                + Duration::from_millis(interval.milliseconds as u64);
         return Ok(true);
     }
 
     Ok(false)
 }
 
+/// Whether the statement refers to the `_timestamp` column; if so, we expose it on connector
+/// tables so that it can be used in expressions (like `date_bin(INTERVAL '1 minute', _timestamp)`).
+/// It's otherwise hidden so that it isn't included in `SELECT *`.
+fn references_timestamp(statement: &Statement) -> bool {
+    references_column(statement, TIMESTAMP_FIELD)
+}
+
+/// Whether any expression in the statement is a (possibly qualified) reference to `column`
+pub(crate) fn references_column(statement: &Statement, column: &str) -> bool {
+    visit_expressions(statement, |expr| match expr {
+        SqlExpr::Identifier(ident) if ident.value == column => ControlFlow::Break(()),
+        SqlExpr::CompoundIdentifier(idents)
+            if idents.last().is_some_and(|ident| ident.value == column) =>
+        {
+            ControlFlow::Break(())
+        }
+        _ => ControlFlow::Continue(()),
+    })
+    .is_break()
+}
+
 pub(crate) fn parse_sql(sql: &str) -> Result<Vec<Statement>, ParserError> {
     let dialect = PostgreSqlDialect {};
     Parser::parse_sql(&dialect, sql)
@@ -657,9 +1162,8 @@ pub(crate) fn parse_sql(sql: &str) -> Result<Vec<Statement>, ParserError> {
 pub async fn parse_and_get_arrow_program(
     query: String,
     mut schema_provider: ArroyoSchemaProvider,
-    // TODO: use config
-    _config: SqlConfig,
-) -> Result<CompiledSql> {
+    mut sql_config: SqlConfig,
+) -> Result<PlannedSql> {
     let mut config = SessionConfig::new();
     config
         .options_mut()
@@ -671,33 +1175,59 @@ pub async fn parse_and_get_arrow_program(
     let session_state = SessionState::new_with_config_rt(config, Arc::new(RuntimeEnv::default()))
         .with_physical_optimizer_rules(vec![]);
 
+    let statements = parse_sql(&query)?;
+    let hints = statement_hints(&query)?;
+    if hints.len() != statements.len() {
+        return plan_err!("could not match hints to the statements in the query");
+    }
+
     let mut inserts = vec![];
-    for statement in parse_sql(&query)? {
-        if try_handle_set_variable(&statement, &mut schema_provider)? {
+    let mut result_set = None;
+    for (statement, hint) in statements.into_iter().zip(hints) {
+        if try_handle_set_variable(&statement, &mut schema_provider, &mut sql_config)? {
+            continue;
+        }
+
+        if let Some(result) = try_handle_introspection(&statement, &schema_provider)? {
+            if result_set.replace(result).is_some() {
+                return plan_err!("a query may only contain one SHOW or DESCRIBE statement");
+            }
             continue;
         }
 
+        let statement = rewrite_lateral_joins(statement)?;
+
+        schema_provider.expose_timestamp = references_timestamp(&statement);
+        schema_provider.expose_traceparent = references_column(&statement, TRACEPARENT_FIELD);
+
         if let Some(table) =
             Table::try_from_statement(&statement, &schema_provider, &session_state)?
         {
             schema_provider.insert_table(table);
         } else {
-            inserts.push(Insert::try_from_statement(
-                &statement,
-                &mut schema_provider,
-                &session_state,
-            )?);
+            inserts.push((
+                Insert::try_from_statement(&statement, &mut schema_provider, &session_state)?,
+                hint,
+            ));
         };
     }
 
+    if let Some(result_set) = result_set {
+        if !inserts.is_empty() {
+            return plan_err!("SHOW and DESCRIBE statements can't be combined with queries");
+        }
+        return Ok(PlannedSql::ResultSet(result_set));
+    }
+
     if inserts.is_empty() {
         return plan_err!("The provided SQL does not contain a query");
     }
 
     let mut used_connections = HashSet::new();
     let mut extensions = vec![];
+    let mut sink_lineages = vec![];
 
-    for insert in inserts {
+    for (insert, hint) in inserts {
         let (plan, sink_name) = match insert {
             Insert::InsertQuery {
                 sink_name,
@@ -706,6 +1236,10 @@ pub async fn parse_and_get_arrow_program(
             Insert::Anonymous { logical_plan } => (logical_plan, None),
         };
 
+        let sink_lineage = sink_name
+            .as_ref()
+            .map(|sink| lineage::sink_lineage(sink, &plan, &schema_provider));
+
         let mut plan_rewrite = rewrite_plan(plan, &schema_provider)?;
 
         // if any of the outgoing fields are datafusion_json_function's union JSON
@@ -733,12 +1267,15 @@ pub async fn parse_and_get_arrow_program(
                     DataFusionError::Plan(format!("Connection {} not found", sink_name))
                 })?;
                 match table {
-                    Table::ConnectorTable(_) => SinkExtension::new(
-                        TableReference::bare(sink_name),
-                        table.clone(),
-                        plan_rewrite.schema().clone(),
-                        Arc::new(plan_rewrite),
-                    ),
+                    Table::ConnectorTable(_) => {
+                        sink_lineages.extend(sink_lineage);
+                        SinkExtension::new(
+                            TableReference::bare(sink_name),
+                            table.clone(),
+                            plan_rewrite.schema().clone(),
+                            Arc::new(plan_rewrite),
+                        )
+                    }
                     Table::MemoryTable { logical_plan, .. } => {
                         if logical_plan.is_some() {
                             return plan_err!("Can only insert into a memory table once");
@@ -763,28 +1300,146 @@ pub async fn parse_and_get_arrow_program(
                 Arc::new(plan_rewrite),
             ),
         };
-        extensions.push(LogicalPlan::Extension(Extension {
-            node: Arc::new(sink?),
-        }));
+        extensions.push((
+            LogicalPlan::Extension(Extension {
+                node: Arc::new(sink?),
+            }),
+            hint,
+        ));
     }
     let mut plan_to_graph_visitor = PlanToGraphVisitor::new(&schema_provider, &session_state);
-    for extension in extensions {
+    let mut statement_hints = vec![];
+    for (extension, hint) in extensions {
+        let first_node = plan_to_graph_visitor.node_count();
         plan_to_graph_visitor.add_plan(extension)?;
+
+        // hints apply to the operators first planned for this query, which excludes any
+        // shared with earlier queries
+        for idx in first_node..plan_to_graph_visitor.node_count() {
+            statement_hints.push((NodeIndex::new(idx), hint.clone()));
+        }
+    }
+
+    // parallelism set on a table takes precedence over hints in the query
+    let mut node_hints = plan_to_graph_visitor.parallelism_hints().clone();
+    let mut graph = plan_to_graph_visitor.into_graph();
+    let mut state_storage = HashMap::new();
+    for (idx, hint) in statement_hints {
+        let node = &graph[idx];
+        if let Some(parallelism) = hint.parallelism.and_then(|h| h.for_node(node)) {
+            node_hints.entry(idx).or_insert(parallelism);
+        }
+
+        if let Some(storage) = hint.state.map(|h| h.for_node(node)).transpose()?.flatten() {
+            state_storage.insert(node.operator_id.clone(), storage);
+        }
+    }
+
+    if let Some(storage) = sql_config.state_storage {
+        for node in graph.node_weights().filter(|node| is_stateful(node)) {
+            state_storage
+                .entry(node.operator_id.clone())
+                .or_insert(storage);
+        }
+    }
+
+    if let Some(preview) = &sql_config.preview {
+        // previews always run with a single subtask per operator, regardless of hints, and
+        // with their queues bounded so that their memory use is limited
+        sql_config.parallelism = Some(1);
+        sql_config.autoscaling_max_parallelism = None;
+        sql_config.autoscaling_min_parallelism = None;
+        node_hints.clear();
+        sql_config.queue_max_bytes = Some(
+            sql_config
+                .queue_max_bytes
+                .map_or(preview.queue_max_bytes, |b| b.min(preview.queue_max_bytes)),
+        );
+    }
+
+    if sql_config.checkpoint_url.is_none() && !sql_config.checkpoint_storage_options.is_empty() {
+        return plan_err!("`SET checkpoint.storage_options` requires `SET checkpoint.url`");
+    }
+    let checkpoint_storage = sql_config.checkpoint_url.map(|url| CheckpointStorage {
+        url,
+        options: sql_config.checkpoint_storage_options,
+    });
+
+    let mut autoscaling = match (
+        sql_config.autoscaling_min_parallelism,
+        sql_config.autoscaling_max_parallelism,
+    ) {
+        (None, None) => None,
+        (Some(_), None) => {
+            return plan_err!(
+                "`SET autoscaling.min_parallelism` requires `SET autoscaling.max_parallelism`"
+            );
+        }
+        (min, Some(max)) => {
+            let min = min.unwrap_or(1);
+            if min > max || max > KEY_GROUPS {
+                return plan_err!(
+                    "autoscaling parallelism must be between 1 and {}, with \
+                    `autoscaling.min_parallelism` at most `autoscaling.max_parallelism`",
+                    KEY_GROUPS
+                );
+            }
+            if let Some(p) = sql_config.parallelism.filter(|p| *p < min || *p > max) {
+                return plan_err!(
+                    "`SET parallelism = {}` is outside of the autoscaling bounds of {} to {}",
+                    p,
+                    min,
+                    max
+                );
+            }
+            // the pipeline starts within its bounds, and is scaled from there
+            sql_config.default_parallelism = sql_config.default_parallelism.clamp(min, max);
+            Some(Autoscaling {
+                min_parallelism: min as u64,
+                max_parallelism: max as u64,
+                operator_max_parallelism: HashMap::new(),
+            })
+        }
+    };
+
+    let max_parallelism = assign_parallelism(
+        &mut graph,
+        sql_config
+            .parallelism
+            .unwrap_or(sql_config.default_parallelism),
+        &node_hints,
+    )?;
+
+    // the autoscaler keeps hinted and source-limited operators within their limits
+    if let Some(autoscaling) = &mut autoscaling {
+        autoscaling.operator_max_parallelism = max_parallelism
+            .into_iter()
+            .map(|(op, max)| (op, max as u64))
+            .collect();
     }
-    let graph = plan_to_graph_visitor.into_graph();
 
     let program = LogicalProgram::new(
         graph,
         ProgramConfig {
             udf_dylibs: schema_provider.dylib_udfs.clone(),
             python_udfs: schema_provider.python_udfs.clone(),
+            parallelism: sql_config.parallelism,
+            checkpoint_interval: sql_config.checkpoint_interval,
+            queue_size: sql_config.queue_size,
+            queue_max_bytes: sql_config.queue_max_bytes,
+            lineage: sink_lineages,
+            state_storage,
+            checkpoint_storage,
+            checkpoint_alignment_timeout: sql_config.checkpoint_alignment_timeout,
+            autoscaling,
+            max_state_bytes: sql_config.preview.as_ref().map(|p| p.max_state_bytes),
         },
     );
 
-    Ok(CompiledSql {
+    Ok(PlannedSql::Pipeline(CompiledSql {
         program,
         connection_ids: used_connections.into_iter().collect(),
-    })
+    }))
 }
 
 #[derive(Clone)]
@@ -0,0 +1,256 @@
+use std::collections::BTreeSet;
+
+use arroyo_rpc::api_types::pipelines::{ColumnLineage, SinkLineage, SourceColumn};
+use datafusion::common::tree_node::{TreeNode, TreeNodeRecursion};
+use datafusion::common::Column;
+use datafusion::logical_expr::{Expr, Extension, LogicalPlan};
+
+use crate::extension::debezium::DEBEZIUM_UNROLLING_EXTENSION_NAME;
+use crate::extension::table_source::{TableSourceExtension, TABLE_SOURCE_NAME};
+use crate::tables::Table;
+use crate::ArroyoSchemaProvider;
+
+/// The source columns that an output column of a plan is derived from, along with the
+/// expressions that were applied to them on the way
+#[derive(Clone, Debug, Default)]
+struct Lineage {
+    sources: BTreeSet<(String, String)>,
+    expressions: Vec<String>,
+}
+
+impl Lineage {
+    fn merge<'a>(lineages: impl IntoIterator<Item = &'a Lineage>) -> Lineage {
+        let mut merged = Lineage::default();
+        for lineage in lineages {
+            merged.sources.extend(lineage.sources.iter().cloned());
+            for expr in &lineage.expressions {
+                if !merged.expressions.contains(expr) {
+                    merged.expressions.push(expr.clone());
+                }
+            }
+        }
+        merged
+    }
+}
+
+/// Lineage for the extension nodes that read from sources. Views and memory tables are stored
+/// as rewritten plans, where table scans have already been replaced by these.
+fn extension_lineage(
+    node: &Extension,
+    plan: &LogicalPlan,
+    inputs: &[(&LogicalPlan, Vec<Lineage>)],
+) -> Option<Vec<Lineage>> {
+    match node.node.name() {
+        TABLE_SOURCE_NAME => {
+            let source = node.node.as_any().downcast_ref::<TableSourceExtension>()?;
+            Some(
+                plan.schema()
+                    .fields()
+                    .iter()
+                    .map(|f| Lineage {
+                        sources: [(source.table.name.clone(), f.name().clone())].into(),
+                        expressions: vec![],
+                    })
+                    .collect(),
+            )
+        }
+        DEBEZIUM_UNROLLING_EXTENSION_NAME => {
+            // the columns of an updating source are unrolled from its `before` and `after`
+            // structs, which we attribute to the source columns with the same names
+            let (input, lineage) = inputs.first()?;
+            let after = &lineage[input.schema().index_of_column_by_name(None, "after")?];
+            Some(
+                plan.schema()
+                    .fields()
+                    .iter()
+                    .map(|f| Lineage {
+                        sources: after
+                            .sources
+                            .iter()
+                            .map(|(table, _)| (table.clone(), f.name().clone()))
+                            .collect(),
+                        expressions: vec![],
+                    })
+                    .collect(),
+            )
+        }
+        _ => None,
+    }
+}
+
+/// Computes, for each column written to a sink, the source table columns it was derived from
+/// and the expressions that produced it. `plan` is the query plan before it has been rewritten
+/// for streaming execution.
+pub(crate) fn sink_lineage(
+    sink: &str,
+    plan: &LogicalPlan,
+    schema_provider: &ArroyoSchemaProvider,
+) -> SinkLineage {
+    let lineage = plan_lineage(plan, schema_provider);
+
+    SinkLineage {
+        sink: sink.to_string(),
+        columns: plan
+            .schema()
+            .fields()
+            .iter()
+            .zip(lineage)
+            .map(|(field, lineage)| ColumnLineage {
+                column: field.name().clone(),
+                sources: lineage
+                    .sources
+                    .into_iter()
+                    .map(|(table, column)| SourceColumn { table, column })
+                    .collect(),
+                expressions: lineage.expressions,
+            })
+            .collect(),
+    }
+}
+
+fn plan_lineage(plan: &LogicalPlan, schema_provider: &ArroyoSchemaProvider) -> Vec<Lineage> {
+    let inputs: Vec<_> = plan
+        .inputs()
+        .into_iter()
+        .map(|input| (input, plan_lineage(input, schema_provider)))
+        .collect();
+
+    if let LogicalPlan::Extension(node) = plan {
+        if let Some(lineage) = extension_lineage(node, plan, &inputs) {
+            return lineage;
+        }
+    }
+
+    match plan {
+        LogicalPlan::TableScan(scan) => {
+            let table_name = scan.table_name.table();
+            let query_plan = match schema_provider.get_table(table_name) {
+                Some(Table::TableFromQuery { logical_plan, .. })
+                | Some(Table::MemoryTable {
+                    logical_plan: Some(logical_plan),
+                    ..
+                }) => Some(logical_plan),
+                _ => None,
+            };
+
+            match query_plan {
+                // views and memory tables are followed through to the tables they read from
+                Some(query_plan) => {
+                    let lineage = plan_lineage(query_plan, schema_provider);
+                    scan.projected_schema
+                        .fields()
+                        .iter()
+                        .map(|f| {
+                            query_plan
+                                .schema()
+                                .index_of_column_by_name(None, f.name())
+                                .map(|i| lineage[i].clone())
+                                .unwrap_or_default()
+                        })
+                        .collect()
+                }
+                None => scan
+                    .projected_schema
+                    .fields()
+                    .iter()
+                    .map(|f| Lineage {
+                        sources: [(table_name.to_string(), f.name().clone())].into(),
+                        expressions: vec![],
+                    })
+                    .collect(),
+            }
+        }
+        LogicalPlan::Projection(projection) => {
+            let (input, lineage) = &inputs[0];
+            projection
+                .expr
+                .iter()
+                .map(|e| expr_lineage(e, input, lineage))
+                .collect()
+        }
+        LogicalPlan::Aggregate(aggregate)
+            if aggregate.group_expr.len() + aggregate.aggr_expr.len()
+                == plan.schema().fields().len() =>
+        {
+            let (input, lineage) = &inputs[0];
+            aggregate
+                .group_expr
+                .iter()
+                .chain(&aggregate.aggr_expr)
+                .map(|e| expr_lineage(e, input, lineage))
+                .collect()
+        }
+        LogicalPlan::Window(window) => {
+            let (input, lineage) = &inputs[0];
+            lineage
+                .iter()
+                .cloned()
+                .chain(
+                    window
+                        .window_expr
+                        .iter()
+                        .map(|e| expr_lineage(e, input, lineage)),
+                )
+                .collect()
+        }
+        LogicalPlan::Union(_) => (0..plan.schema().fields().len())
+            .map(|i| Lineage::merge(inputs.iter().filter_map(|(_, lineage)| lineage.get(i))))
+            .collect(),
+        _ => {
+            // for other nodes, and in particular joins, filters and aliases that pass through
+            // the columns of their inputs, if the columns line up with those of the inputs
+            // they are taken positionally
+            let input_columns: usize = inputs.iter().map(|(_, l)| l.len()).sum();
+            if input_columns == plan.schema().fields().len() {
+                return inputs.into_iter().flat_map(|(_, l)| l).collect();
+            }
+
+            // otherwise we resolve them by name, and fall back to everything in the inputs
+            (0..plan.schema().fields().len())
+                .map(|i| {
+                    let (qualifier, field) = plan.schema().qualified_field(i);
+                    inputs
+                        .iter()
+                        .find_map(|(input, lineage)| {
+                            input
+                                .schema()
+                                .index_of_column_by_name(qualifier, field.name())
+                                .or_else(|| {
+                                    input.schema().index_of_column_by_name(None, field.name())
+                                })
+                                .map(|i| lineage[i].clone())
+                        })
+                        .unwrap_or_else(|| {
+                            Lineage::merge(inputs.iter().flat_map(|(_, lineage)| lineage))
+                        })
+                })
+                .collect()
+        }
+    }
+}
+
+/// The lineage of an expression evaluated over `input`, whose columns have lineage `lineage`
+fn expr_lineage(expr: &Expr, input: &LogicalPlan, lineage: &[Lineage]) -> Lineage {
+    let mut columns: Vec<Column> = vec![];
+    expr.apply(|e| {
+        if let Expr::Column(c) = e {
+            columns.push(c.clone());
+        }
+        Ok(TreeNodeRecursion::Continue)
+    })
+    .expect("collecting columns is infallible");
+
+    let mut result = Lineage::merge(
+        columns
+            .iter()
+            .filter_map(|c| input.schema().index_of_column(c).ok())
+            .map(|i| &lineage[i]),
+    );
+
+    let expr = expr.clone().unalias();
+    if !matches!(expr, Expr::Column(_)) {
+        result.expressions.push(expr.to_string());
+    }
+
+    result
+}
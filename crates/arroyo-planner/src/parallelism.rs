@@ -0,0 +1,253 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use arroyo_connectors::connector_for_type;
+use arroyo_datastream::logical::{LogicalEdgeType, LogicalGraph, LogicalNode, OperatorName};
+use arroyo_rpc::grpc::api::ConnectorOp;
+use arroyo_rpc::OperatorConfig;
+use datafusion::common::{plan_err, DataFusionError, Result};
+use petgraph::graph::NodeIndex;
+use petgraph::unionfind::UnionFind;
+use petgraph::visit::EdgeRef;
+use prost::Message;
+use tracing::warn;
+
+use crate::hints::{hint_arguments, operator_kind, OPERATOR_KINDS};
+
+/// A parallelism hint for the operators planned for a statement, written as a comment like
+/// `/*+ parallelism(4) */`, which applies to all of them, or `/*+ parallelism(source=2,
+/// aggregate=8) */`, which applies to operators of particular kinds; the two can be combined, in
+/// which case the per-kind values take precedence.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct ParallelismHint {
+    all: Option<usize>,
+    by_kind: HashMap<&'static str, usize>,
+}
+
+fn parse_parallelism(value: &str) -> Result<usize> {
+    match value.parse::<usize>() {
+        Ok(p) if p > 0 => Ok(p),
+        _ => plan_err!(
+            "invalid parallelism hint '{}'; expected a positive integer",
+            value
+        ),
+    }
+}
+
+impl ParallelismHint {
+    fn parse(args: &str) -> Result<Self> {
+        let mut hint = Self::default();
+
+        for arg in args.split(',').map(|s| s.trim()) {
+            match arg.split_once('=') {
+                Some((kind, value)) => {
+                    let kind = kind.trim().to_lowercase();
+                    let Some(kind) = OPERATOR_KINDS.iter().find(|k| **k == kind) else {
+                        return plan_err!(
+                            "unknown operator '{}' in parallelism hint; expected one of {}",
+                            kind,
+                            OPERATOR_KINDS.join(", ")
+                        );
+                    };
+                    hint.by_kind.insert(*kind, parse_parallelism(value.trim())?);
+                }
+                None => {
+                    hint.all = Some(parse_parallelism(arg)?);
+                }
+            }
+        }
+
+        Ok(hint)
+    }
+
+    /// The hinted parallelism for the node, if any
+    pub(crate) fn for_node(&self, node: &LogicalNode) -> Option<usize> {
+        self.by_kind
+            .get(operator_kind(node.operator_name))
+            .copied()
+            .or(self.all)
+    }
+}
+
+/// Finds the parallelism hint (a comment like `/*+ parallelism(4) */`) for each statement in
+/// the query, in the order the statements are parsed
+pub(crate) fn parallelism_hints(query: &str) -> Result<Vec<Option<ParallelismHint>>> {
+    hint_arguments(query, "parallelism")?
+        .into_iter()
+        .map(|args| args.map(|args| ParallelismHint::parse(&args)).transpose())
+        .collect()
+}
+
+/// How long to wait for a source's partitions to be looked up before planning without them
+const SOURCE_PARTITIONS_TIMEOUT: Duration = Duration::from_secs(15);
+
+fn source_config(node: &LogicalNode) -> Result<Option<(ConnectorOp, OperatorConfig)>> {
+    if node.operator_name != OperatorName::ConnectorSource {
+        return Ok(None);
+    }
+
+    let op = ConnectorOp::decode(&node.operator_config[..]).map_err(|e| {
+        DataFusionError::Plan(format!(
+            "invalid connector config for {}: {:?}",
+            node.operator_id, e
+        ))
+    })?;
+
+    let config: OperatorConfig = serde_json::from_str(&op.config).map_err(|e| {
+        DataFusionError::Plan(format!(
+            "invalid connector config for {}: {:?}",
+            node.operator_id, e
+        ))
+    })?;
+
+    Ok(Some((op, config)))
+}
+
+fn max_source_parallelism(node: &LogicalNode) -> Result<Option<usize>> {
+    let Some((op, config)) = source_config(node)? else {
+        return Ok(None);
+    };
+
+    let Some(connector) = connector_for_type(&op.connector) else {
+        return Ok(None);
+    };
+
+    connector
+        .max_source_parallelism(&config.connection, &config.table)
+        .map_err(|e| {
+            DataFusionError::Plan(format!(
+                "invalid connector config for {}: {:?}",
+                node.operator_id, e
+            ))
+        })
+}
+
+async fn source_partitions(node: &LogicalNode) -> anyhow::Result<Option<usize>> {
+    let Some((op, config)) = source_config(node)? else {
+        return Ok(None);
+    };
+
+    let Some(connector) = connector_for_type(&op.connector) else {
+        return Ok(None);
+    };
+
+    let rx = connector.source_partitions(&config.connection, &config.table)?;
+
+    tokio::time::timeout(SOURCE_PARTITIONS_TIMEOUT, rx)
+        .await
+        .map_err(|_| anyhow::anyhow!("timed out"))?
+        .map_err(|_| anyhow::anyhow!("lookup was cancelled"))?
+}
+
+fn forward_chains(graph: &LogicalGraph) -> UnionFind<usize> {
+    let mut forward_chains = UnionFind::new(graph.node_count());
+    for edge in graph.edge_references() {
+        if edge.weight().edge_type == LogicalEdgeType::Forward {
+            forward_chains.union(edge.source().index(), edge.target().index());
+        }
+    }
+    forward_chains
+}
+
+/// Sets the parallelism of each operator in the graph. Operators use their hinted parallelism
+/// (from the table they read or write, or the query that created them), or the default, limited
+/// to what their source can support. Operators joined by forward edges exchange data
+/// subtask-to-subtask, so they all run at the same parallelism: the lowest hinted for any of
+/// them, or the default if none are hinted.
+///
+/// Returns the highest parallelism that each hinted or source-limited operator may be rescaled
+/// to, by operator id.
+pub(crate) fn assign_parallelism(
+    graph: &mut LogicalGraph,
+    default_parallelism: usize,
+    hints: &HashMap<NodeIndex, usize>,
+) -> Result<HashMap<String, usize>> {
+    let forward_chains = forward_chains(graph);
+
+    let mut chain_hints: HashMap<usize, usize> = HashMap::new();
+    let mut chain_limits: HashMap<usize, usize> = HashMap::new();
+    for idx in graph.node_indices() {
+        let chain = forward_chains.find(idx.index());
+
+        if let Some(hint) = hints.get(&idx) {
+            let p = chain_hints.entry(chain).or_insert(*hint);
+            *p = (*p).min(*hint);
+        }
+
+        if let Some(max) = max_source_parallelism(&graph[idx])? {
+            let p = chain_limits.entry(chain).or_insert(max);
+            *p = (*p).min(max);
+        }
+    }
+
+    let mut max_parallelism = HashMap::new();
+    for idx in graph.node_indices() {
+        let chain = forward_chains.find(idx.index());
+        let parallelism = chain_hints
+            .get(&chain)
+            .copied()
+            .unwrap_or(default_parallelism);
+
+        graph[idx].parallelism = chain_limits
+            .get(&chain)
+            .map(|max| parallelism.min(*max))
+            .unwrap_or(parallelism);
+
+        let max = [chain_hints.get(&chain), chain_limits.get(&chain)]
+            .into_iter()
+            .flatten()
+            .min();
+        if let Some(max) = max {
+            max_parallelism.insert(graph[idx].operator_id.clone(), (*max).max(1));
+        }
+    }
+
+    Ok(max_parallelism)
+}
+
+/// Caps the parallelism of each partitioned source (like a Kafka topic or Kinesis stream) at the
+/// number of partitions or shards it currently has, as subtasks beyond that would sit idle; the
+/// rest of the source's forward chain is capped with it. This needs to reach the external
+/// systems, so it's run when a pipeline is created rather than as part of planning; sources
+/// whose partitions can't be looked up keep their planned parallelism.
+///
+/// Returns the limit of each operator that was capped, by operator id.
+pub async fn limit_to_source_partitions(graph: &mut LogicalGraph) -> HashMap<String, usize> {
+    let forward_chains = forward_chains(graph);
+
+    let mut chain_limits: HashMap<usize, usize> = HashMap::new();
+    for idx in graph.node_indices() {
+        let node = &graph[idx];
+        if node.parallelism <= 1 {
+            continue;
+        }
+
+        match source_partitions(node).await {
+            Ok(Some(partitions)) => {
+                let p = chain_limits
+                    .entry(forward_chains.find(idx.index()))
+                    .or_insert(partitions);
+                *p = (*p).min(partitions);
+            }
+            Ok(None) => {}
+            Err(e) => {
+                warn!(
+                    "could not look up the partitions of source {}, so its parallelism is not \
+                    limited by them: {:?}",
+                    node.operator_id, e
+                );
+            }
+        }
+    }
+
+    let mut limits = HashMap::new();
+    for idx in graph.node_indices() {
+        if let Some(max) = chain_limits.get(&forward_chains.find(idx.index())) {
+            let max = (*max).max(1);
+            graph[idx].parallelism = graph[idx].parallelism.min(max);
+            limits.insert(graph[idx].operator_id.clone(), max);
+        }
+    }
+
+    limits
+}
@@ -1,25 +1,137 @@
 use crate::extension::aggregate::AggregateExtension;
 use crate::extension::key_calculation::KeyCalculationExtension;
+use crate::extension::session_events::SessionEventsExtension;
 use crate::extension::updating_aggregate::UpdatingAggregateExtension;
 use crate::plan::WindowDetectingVisitor;
+use crate::udafs::SINGLE_PHASE_UDAFS;
 use crate::{
-    fields_with_qualifiers, find_window, schema_from_df_fields_with_metadata, ArroyoSchemaProvider,
-    DFField, WindowBehavior,
+    custom_binning_function, fields_with_qualifiers, find_window, get_duration,
+    schema_from_df_fields_with_metadata, ArroyoSchemaProvider, DFField, ExecutionMode,
+    WindowBehavior,
 };
 use arroyo_rpc::{TIMESTAMP_FIELD, UPDATING_META_FIELD};
-use datafusion::common::tree_node::{Transformed, TreeNodeRewriter};
+use datafusion::common::tree_node::{Transformed, TreeNode, TreeNodeRecursion, TreeNodeRewriter};
 use datafusion::common::{not_impl_err, plan_err, DFSchema, DataFusionError, Result};
 use datafusion::logical_expr;
 use datafusion::logical_expr::expr::AggregateFunction;
 use datafusion::logical_expr::{aggregate_function, Aggregate, Expr, Extension, LogicalPlan};
 use std::sync::Arc;
+use std::time::Duration;
 use tracing::debug;
 
+/// Returns the gap of the `session_events()` call among the aggregates, if there is one
+fn session_events_gap(aggr_expr: &[Expr]) -> Result<Option<Duration>> {
+    let mut calls = aggr_expr.iter().filter_map(|expr| match expr {
+        Expr::AggregateFunction(AggregateFunction { func_def, args, .. })
+            if func_def.name() == "session_events" =>
+        {
+            Some(args)
+        }
+        _ => None,
+    });
+
+    let Some(args) = calls.next() else {
+        return Ok(None);
+    };
+
+    if aggr_expr.len() > 1 {
+        return plan_err!("session_events() can't be combined with other aggregates");
+    }
+
+    let [gap] = args.as_slice() else {
+        return plan_err!("session_events() takes a single argument, the session gap");
+    };
+
+    let gap = get_duration(gap)?;
+    if gap.is_zero() {
+        return plan_err!("session_events() requires a non-zero gap");
+    }
+
+    Ok(Some(gap))
+}
+
+/// Rejects aggregates that can't be split into partial and final phases, which every
+/// aggregate in a GROUP BY is
+fn check_mergeable(aggr_expr: &[Expr]) -> Result<()> {
+    for expr in aggr_expr {
+        expr.apply(|e| {
+            if let Expr::AggregateFunction(AggregateFunction { func_def, .. }) = e {
+                if SINGLE_PHASE_UDAFS.contains(&func_def.name()) {
+                    return plan_err!(
+                        "{}() depends on the order of its inputs, so can't be used in a GROUP BY; \
+                         use it as a window function, like {}(...) OVER (PARTITION BY window ORDER BY ...)",
+                        func_def.name(),
+                        func_def.name()
+                    );
+                }
+            }
+            Ok(TreeNodeRecursion::Continue)
+        })?;
+    }
+    Ok(())
+}
+
 pub struct AggregateRewriter<'a> {
     pub schema_provider: &'a ArroyoSchemaProvider,
 }
 
 impl<'a> AggregateRewriter<'a> {
+    /// Plans an aggregate over `session_events()` as a session events operator, keyed by the
+    /// GROUP BY expressions
+    fn rewrite_session_events(
+        input: Arc<LogicalPlan>,
+        mut key_fields: Vec<DFField>,
+        group_expr: Vec<Expr>,
+        gap: Duration,
+        schema: Arc<DFSchema>,
+    ) -> Result<Transformed<LogicalPlan>> {
+        if input
+            .schema()
+            .has_column_with_unqualified_name(UPDATING_META_FIELD)
+        {
+            return plan_err!("session_events() can't be computed over an updating input");
+        }
+
+        if group_expr.is_empty() {
+            return plan_err!(
+                "session_events() requires a GROUP BY with the keys to track sessions for"
+            );
+        }
+
+        let key_count = key_fields.len();
+        key_fields.extend(fields_with_qualifiers(input.schema()));
+
+        let key_schema = Arc::new(schema_from_df_fields_with_metadata(
+            &key_fields,
+            schema.metadata().clone(),
+        )?);
+
+        let mut key_projection_expressions = group_expr;
+        key_projection_expressions.extend(
+            fields_with_qualifiers(input.schema())
+                .iter()
+                .map(|field| Expr::Column(field.qualified_column())),
+        );
+
+        let key_projection =
+            LogicalPlan::Projection(logical_expr::Projection::try_new_with_schema(
+                key_projection_expressions,
+                input,
+                key_schema,
+            )?);
+
+        let key_plan = LogicalPlan::Extension(Extension {
+            node: Arc::new(KeyCalculationExtension::new(
+                key_projection,
+                (0..key_count).collect(),
+            )),
+        });
+
+        Ok(Transformed::yes(LogicalPlan::Extension(Extension {
+            node: Arc::new(SessionEventsExtension::new(key_plan, gap, schema)?),
+        })))
+    }
+
     pub fn rewrite_non_windowed_aggregate(
         input: Arc<LogicalPlan>,
         mut key_fields: Vec<DFField>,
@@ -115,6 +227,7 @@ impl<'a> AggregateRewriter<'a> {
             (0..key_count).collect(),
             column.relation,
             schema_provider.planning_options.ttl,
+            schema_provider.planning_options.execution_mode == ExecutionMode::Bounded,
         )?;
         let final_plan = LogicalPlan::Extension(Extension {
             node: Arc::new(updating_aggregate_extension),
@@ -137,6 +250,8 @@ impl<'a> TreeNodeRewriter for AggregateRewriter<'a> {
         else {
             return Ok(Transformed::no(node));
         };
+        check_mergeable(&aggr_expr)?;
+
         let mut window_group_expr: Vec<_> = group_expr
             .iter()
             .enumerate()
@@ -168,6 +283,13 @@ impl<'a> TreeNodeRewriter for AggregateRewriter<'a> {
             })
             .collect::<Vec<_>>();
 
+        if let Some(gap) = session_events_gap(&aggr_expr)? {
+            if !window_group_expr.is_empty() {
+                return plan_err!("session_events() can't be used with a window in the GROUP BY");
+            }
+            return Self::rewrite_session_events(input, key_fields, group_expr, gap, schema);
+        }
+
         let mut window_detecting_visitor = WindowDetectingVisitor::default();
         input.visit_with_subqueries(&mut window_detecting_visitor)?;
 
@@ -281,10 +403,18 @@ impl<'a> TreeNodeRewriter for AggregateRewriter<'a> {
             internal_schema,
         )?;
 
+        let binning_function = match &window_behavior {
+            WindowBehavior::FromOperator { window, .. } => {
+                custom_binning_function(window, self.schema_provider)?
+            }
+            WindowBehavior::InData => None,
+        };
+
         let aggregate_extension = AggregateExtension::new(
             window_behavior,
             LogicalPlan::Aggregate(rewritten_aggregate),
             (0..key_count).collect(),
+            binning_function,
         );
         let final_plan = LogicalPlan::Extension(Extension {
             node: Arc::new(aggregate_extension),
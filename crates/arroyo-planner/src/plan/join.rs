@@ -1,4 +1,4 @@
-use crate::extension::join::JoinExtension;
+use crate::extension::join::{JoinExtension, JoinTtl};
 use crate::extension::key_calculation::KeyCalculationExtension;
 use crate::plan::WindowDetectingVisitor;
 use crate::{fields_with_qualifiers, schema_from_df_fields_with_metadata, ArroyoSchemaProvider};
@@ -239,11 +239,15 @@ impl<'a> TreeNodeRewriter for JoinRewriter<'a> {
 
         let final_logical_plan = self.post_join_timestamp_projection(rewritten_join)?;
 
+        let options = &self.schema_provider.planning_options;
         let join_extension = JoinExtension {
             rewritten_join: final_logical_plan,
             is_instant,
             // only non-instant (updating) joins have a TTL
-            ttl: (!is_instant).then_some(self.schema_provider.planning_options.ttl),
+            ttl: (!is_instant).then_some(JoinTtl {
+                left: options.left_join_ttl.unwrap_or(options.ttl),
+                right: options.right_join_ttl.unwrap_or(options.ttl),
+            }),
         };
 
         Ok(Transformed::yes(LogicalPlan::Extension(Extension {
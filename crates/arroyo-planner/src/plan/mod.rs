@@ -9,8 +9,9 @@ use datafusion::common::{
 use std::{collections::HashSet, sync::Arc};
 
 use aggregate::AggregateRewriter;
+use arrow_schema::{DataType, TimeUnit};
 use datafusion::logical_expr::{
-    expr::Alias, Aggregate, Expr, Extension, Filter, LogicalPlan, SubqueryAlias,
+    expr::Alias, Aggregate, Cast, Expr, Extension, Filter, LogicalPlan, Projection, SubqueryAlias,
 };
 use join::JoinRewriter;
 
@@ -258,13 +259,50 @@ pub struct ArroyoRewriter<'a> {
     pub(crate) schema_provider: &'a ArroyoSchemaProvider,
 }
 
+impl<'a> ArroyoRewriter<'a> {
+    /// Queries may compute their own event time (e.g., `date_bin(INTERVAL '1 minute',
+    /// _timestamp) AS _timestamp`); the result must be a timestamp, and we cast it to the
+    /// representation used internally.
+    fn coerce_timestamp_expr(projection: &mut Projection) -> Result<()> {
+        let Some(index) = projection
+            .schema
+            .index_of_column_by_name(None, TIMESTAMP_FIELD)
+        else {
+            return Ok(());
+        };
+
+        let (qualifier, field) = projection.schema.qualified_field(index);
+        match field.data_type() {
+            DataType::Timestamp(TimeUnit::Nanosecond, None) => Ok(()),
+            DataType::Timestamp(_, _) | DataType::Date32 | DataType::Date64 => {
+                let qualifier = qualifier.cloned();
+                let mut exprs = projection.expr.clone();
+                exprs[index] = Expr::Cast(Cast::new(
+                    Box::new(exprs[index].clone().unalias()),
+                    DataType::Timestamp(TimeUnit::Nanosecond, None),
+                ))
+                .alias_qualified(qualifier, TIMESTAMP_FIELD);
+                *projection = Projection::try_new(exprs, projection.input.clone())?;
+                Ok(())
+            }
+            dt => plan_err!(
+                "{} must be a TIMESTAMP, but the query computes it as {}",
+                TIMESTAMP_FIELD,
+                dt
+            ),
+        }
+    }
+}
+
 impl<'a> TreeNodeRewriter for ArroyoRewriter<'a> {
     type Node = LogicalPlan;
 
     fn f_up(&mut self, mut node: Self::Node) -> Result<Transformed<Self::Node>> {
         match node {
             LogicalPlan::Projection(ref mut projection) => {
-                if !has_timestamp_field(&projection.schema) {
+                if has_timestamp_field(&projection.schema) {
+                    Self::coerce_timestamp_expr(projection)?;
+                } else {
                     let timestamp_field: DFField = projection
                         .input
                         .schema()
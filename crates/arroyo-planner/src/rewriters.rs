@@ -1,18 +1,23 @@
 use crate::extension::debezium::DebeziumUnrollingExtension;
+use crate::extension::dedupe::DedupeExtension;
+use crate::extension::delay::DelayExtension;
+use crate::extension::key_calculation::KeyCalculationExtension;
 use crate::extension::remote_table::RemoteTableExtension;
 use crate::extension::sink::SinkExtension;
 use crate::extension::table_source::TableSourceExtension;
 use crate::extension::watermark_node::WatermarkNode;
 use crate::schemas::add_timestamp_field;
+use crate::tables::nested_field_expr;
 use crate::tables::ConnectorTable;
+use crate::tables::Dedupe;
 use crate::tables::FieldSpec;
 use crate::tables::Table;
 use crate::{
-    fields_with_qualifiers, schema_from_df_fields, ArroyoSchemaProvider, DFField,
-    ASYNC_RESULT_FIELD,
+    fields_with_qualifiers, get_duration, schema_from_df_fields, ArroyoSchemaProvider, DFField,
+    ExecutionMode, ASYNC_RESULT_FIELD, DELAYED_AT_FIELD,
 };
 
-use arrow_schema::DataType;
+use arrow_schema::{DataType, Schema, TimeUnit};
 use arroyo_rpc::TIMESTAMP_FIELD;
 use arroyo_rpc::UPDATING_META_FIELD;
 
@@ -27,12 +32,21 @@ use datafusion::common::{
 use datafusion::logical_expr;
 use datafusion::logical_expr::expr::ScalarFunction;
 use datafusion::logical_expr::{
-    BinaryExpr, Expr, Extension, LogicalPlan, Projection, TableScan, Unnest,
+    BinaryExpr, Expr, Extension, LogicalPlan, Projection, TableScan, TypeSignature, Unnest,
 };
 use std::collections::HashSet;
 use std::sync::Arc;
 use std::time::Duration;
 
+/// Whether a function with this signature can be called with arguments of exactly these types
+fn accepts_args(signature: &TypeSignature, args: &[DataType]) -> bool {
+    match signature {
+        TypeSignature::Exact(types) => types == args,
+        TypeSignature::OneOf(signatures) => signatures.iter().any(|s| accepts_args(s, args)),
+        _ => false,
+    }
+}
+
 /// Rewrites a logical plan to move projections out of table scans
 /// and into a separate projection node which may include virtual fields,
 /// and adds a watermark node.
@@ -41,28 +55,13 @@ pub struct SourceRewriter<'a> {
 }
 
 impl<'a> SourceRewriter<'a> {
-    fn watermark_expression(table: &ConnectorTable) -> DFResult<Expr> {
-        let expr = match table.watermark_field.clone() {
-            Some(watermark_field) => table
-                .fields
-                .iter()
-                .find_map(|f| {
-                    if f.field().name() == &watermark_field {
-                        return match f {
-                            FieldSpec::Struct(field) | FieldSpec::Metadata { field, .. } => {
-                                Some(Expr::Column(Column {
-                                    relation: None,
-                                    name: field.name().to_string(),
-                                }))
-                            }
-                            FieldSpec::Virtual { expression, .. } => Some(expression.clone()),
-                        };
-                    }
-                    None
-                })
-                .ok_or_else(|| {
-                    DataFusionError::Plan(format!("Watermark field {} not found", watermark_field))
-                })?,
+    fn watermark_expression(&self, table: &ConnectorTable) -> DFResult<Expr> {
+        if let Some(udf) = &table.watermark_udf {
+            return self.watermark_udf_expression(udf);
+        }
+
+        let expr = match &table.watermark_field {
+            Some(watermark_field) => Self::time_field_expression(table, watermark_field, None)?,
             None => Expr::BinaryExpr(BinaryExpr {
                 left: Box::new(Expr::Column(Column {
                     relation: None,
@@ -77,56 +76,113 @@ impl<'a> SourceRewriter<'a> {
         Ok(expr)
     }
 
+    /// Calls the UDF designated as the table's watermark generator with the event time of each
+    /// row; the watermark of a batch is the earliest that the UDF returns for its rows, and rows
+    /// for which it returns NULL don't advance the watermark
+    fn watermark_udf_expression(&self, name: &str) -> DFResult<Expr> {
+        let Some(udf) = self.schema_provider.functions.get(name) else {
+            return plan_err!("watermark_udf '{}' is not a known UDF", name);
+        };
+
+        if let Some(UdfType::Async(_)) =
+            self.schema_provider.udf_defs.get(name).map(|d| &d.udf_type)
+        {
+            return plan_err!("async UDF '{}' cannot be used as a watermark_udf", name);
+        }
+
+        let timestamp = DataType::Timestamp(TimeUnit::Nanosecond, None);
+        if !accepts_args(&udf.signature().type_signature, &[timestamp.clone()])
+            || udf.return_type(&[timestamp.clone()]).ok() != Some(timestamp)
+        {
+            return plan_err!(
+                "watermark_udf '{}' must take a single TIMESTAMP argument and return a TIMESTAMP",
+                name
+            );
+        }
+
+        Ok(Expr::ScalarFunction(ScalarFunction::new_udf(
+            udf.clone(),
+            vec![Expr::Column(Column {
+                relation: None,
+                name: TIMESTAMP_FIELD.to_string(),
+            })],
+        )))
+    }
+
+    /// Resolves a (possibly nested) time field on the table to an expression over the scan
+    fn time_field_expression(
+        table: &ConnectorTable,
+        field_name: &str,
+        qualifier: Option<&TableReference>,
+    ) -> DFResult<Expr> {
+        let (field, path) = table.get_time_field(field_name)?;
+        let expr = match field {
+            FieldSpec::Struct(field) | FieldSpec::Metadata { field, .. } => Expr::Column(Column {
+                relation: qualifier.cloned(),
+                name: field.name().to_string(),
+            }),
+            FieldSpec::Virtual { expression, .. } => expression.clone(),
+        };
+
+        Ok(nested_field_expr(expr, &path))
+    }
+
     fn projection_expressions(
         table: &ConnectorTable,
         qualifier: &TableReference,
+        scan_schema: &Schema,
         projection: &Option<Vec<usize>>,
     ) -> DFResult<Vec<Expr>> {
-        let mut expressions = table
-            .fields
-            .iter()
-            .map(|field| match field {
-                FieldSpec::Struct(field) | FieldSpec::Metadata { field, .. } => {
-                    Expr::Column(Column {
-                        relation: Some(qualifier.clone()),
-                        name: field.name().to_string(),
-                    })
-                }
-                FieldSpec::Virtual { field, expression } => expression
-                    .clone()
-                    .alias_qualified(Some(qualifier.clone()), field.name().to_string()),
-            })
-            .collect::<Vec<_>>();
+        let field_expression = |field: &FieldSpec| match field {
+            FieldSpec::Struct(field) | FieldSpec::Metadata { field, .. } => Expr::Column(Column {
+                relation: Some(qualifier.clone()),
+                name: field.name().to_string(),
+            }),
+            FieldSpec::Virtual { field, expression } => expression
+                .clone()
+                .alias_qualified(Some(qualifier.clone()), field.name().to_string()),
+        };
+
+        let indices: Vec<usize> = match projection {
+            Some(projection) => projection.clone(),
+            None => (0..scan_schema.fields().len()).collect(),
+        };
+
+        let mut expressions = vec![];
+        for i in indices {
+            let Some(scan_field) = scan_schema.fields().get(i) else {
+                return plan_err!(
+                    "projection index {} is out of range for table {}, which has {} fields",
+                    i,
+                    table.name,
+                    scan_schema.fields().len()
+                );
+            };
+
+            // if the query refers to _timestamp, it's exposed after the table's fields; it's
+            // always added below, so we skip it here
+            if scan_field.name() == TIMESTAMP_FIELD {
+                continue;
+            }
 
-        if let Some(projection) = projection {
-            expressions = projection.iter().map(|i| expressions[*i].clone()).collect();
+            let Some(field) = table
+                .fields
+                .iter()
+                .find(|f| f.field().name() == scan_field.name())
+            else {
+                return plan_err!(
+                    "field '{}' is not a field of table {}",
+                    scan_field.name(),
+                    table.name
+                );
+            };
+            expressions.push(field_expression(field));
         }
 
         // Add event time field if present
         if let Some(event_time_field) = table.event_time_field.clone() {
-            let event_time_field = table
-                .fields
-                .iter()
-                .find_map(|f| {
-                    if f.field().name() == &event_time_field {
-                        return match f {
-                            FieldSpec::Struct(field) | FieldSpec::Metadata { field, .. } => {
-                                Some(Expr::Column(Column {
-                                    relation: Some(qualifier.clone()),
-                                    name: field.name().to_string(),
-                                }))
-                            }
-                            FieldSpec::Virtual { expression, .. } => Some(expression.clone()),
-                        };
-                    }
-                    None
-                })
-                .ok_or_else(|| {
-                    DataFusionError::Plan(format!(
-                        "Event time field {} not found",
-                        event_time_field
-                    ))
-                })?;
+            let event_time_field =
+                Self::time_field_expression(table, &event_time_field, Some(qualifier))?;
 
             let event_time_field =
                 event_time_field.alias_qualified(Some(qualifier.clone()), "_timestamp".to_string());
@@ -175,7 +231,12 @@ impl<'a> SourceRewriter<'a> {
         };
 
         Ok(LogicalPlan::Projection(Projection::try_new(
-            Self::projection_expressions(table, &qualifier, &projection)?,
+            Self::projection_expressions(
+                table,
+                &qualifier,
+                table_scan.source.schema().as_ref(),
+                &projection,
+            )?,
             Arc::new(projection_input),
         )?))
     }
@@ -185,7 +246,24 @@ impl<'a> SourceRewriter<'a> {
         table_scan: &TableScan,
         table: &ConnectorTable,
     ) -> DFResult<Transformed<LogicalPlan>> {
-        let input = self.projection(table_scan, table)?;
+        if self.schema_provider.planning_options.execution_mode == ExecutionMode::Bounded
+            && !table.is_bounded()?
+        {
+            return plan_err!(
+                "table '{}' reads from an unbounded source, so it can't be used with `SET execution.mode = 'bounded'`",
+                table.name
+            );
+        }
+
+        let input = if table.dedupe.is_some() {
+            // the dedupe keys may not be selected by the query, so every field is read and
+            // the scan's projection is applied after deduplication
+            let mut table_scan = table_scan.clone();
+            table_scan.projection = None;
+            self.projection(&table_scan, table)?
+        } else {
+            self.projection(table_scan, table)?
+        };
 
         let schema = input.schema().clone();
         let remote = LogicalPlan::Extension(Extension {
@@ -197,18 +275,96 @@ impl<'a> SourceRewriter<'a> {
             }),
         });
 
+        let idle_time = table
+            .idle_time
+            .or(self.schema_provider.planning_options.source_idle_time)
+            .filter(|t| !t.is_zero());
+
         let watermark_node = WatermarkNode::new(
             remote,
             table_scan.table_name.clone(),
-            Self::watermark_expression(table)?,
+            self.watermark_expression(table)?,
+            idle_time,
         )
         .map_err(|err| {
             DataFusionError::Internal(format!("failed to create watermark expression: {}", err))
         })?;
 
-        Ok(Transformed::yes(LogicalPlan::Extension(Extension {
+        let plan = LogicalPlan::Extension(Extension {
             node: Arc::new(watermark_node),
-        })))
+        });
+
+        match &table.dedupe {
+            Some(dedupe) => Ok(Transformed::yes(Self::dedupe(
+                plan, table_scan, table, dedupe,
+            )?)),
+            None => Ok(Transformed::yes(plan)),
+        }
+    }
+
+    /// Keys the rows read from the source by the dedupe keys and drops the duplicates, then
+    /// projects the result down to the fields the scan selects
+    fn dedupe(
+        input: LogicalPlan,
+        table_scan: &TableScan,
+        table: &ConnectorTable,
+        dedupe: &Dedupe,
+    ) -> DFResult<LogicalPlan> {
+        let fields = fields_with_qualifiers(input.schema());
+        let columns: Vec<_> = fields
+            .iter()
+            .map(|f| Expr::Column(f.qualified_column()))
+            .collect();
+
+        // the projection expressions put the table's fields first, in order
+        let keys = dedupe
+            .keys
+            .iter()
+            .map(|key| {
+                table
+                    .fields
+                    .iter()
+                    .position(|f| f.field().name() == key)
+                    .ok_or_else(|| {
+                        DataFusionError::Plan(format!(
+                            "dedupe key '{}' is not a field of table {}",
+                            key, table.name
+                        ))
+                    })
+            })
+            .collect::<DFResult<Vec<_>>>()?;
+
+        let key_plan = LogicalPlan::Extension(Extension {
+            node: Arc::new(KeyCalculationExtension::new(
+                LogicalPlan::Projection(Projection::try_new(columns, Arc::new(input))?),
+                keys,
+            )),
+        });
+
+        let dedupe_plan = LogicalPlan::Extension(Extension {
+            node: Arc::new(DedupeExtension::new(key_plan, dedupe.window)),
+        });
+
+        // the source's fields are followed by _timestamp; hidden fields the query doesn't refer
+        // to aren't among them
+        let timestamp_index = fields.len() - 1;
+        let selected: Vec<usize> = match &table_scan.projection {
+            Some(projection) => projection
+                .iter()
+                .copied()
+                .filter(|i| *i < timestamp_index)
+                .chain([timestamp_index])
+                .collect(),
+            None => (0..=timestamp_index).collect(),
+        };
+
+        Ok(LogicalPlan::Projection(Projection::try_new(
+            selected
+                .into_iter()
+                .map(|i| Expr::Column(fields[i].qualified_column()))
+                .collect(),
+            Arc::new(dedupe_plan),
+        )?))
     }
 
     fn mutate_table_from_query(
@@ -456,6 +612,120 @@ impl TreeNodeRewriter for UnnestRewriter {
     }
 }
 
+/// Plans calls to `delay(interval)` in a SELECT, which hold back each row of the query's input
+/// until the interval has passed in processing time, as a delay operator below the projection.
+/// The call itself evaluates to the time at which the row is due to be emitted.
+pub struct DelayRewriter {}
+
+impl DelayRewriter {
+    fn split_delay(expr: Expr) -> DFResult<(Expr, Option<Vec<Expr>>)> {
+        let mut delay = None;
+
+        let expr = expr.transform_up(&mut |e| {
+            if let Expr::ScalarFunction(ScalarFunction { func, args }) = &e {
+                if func.name() == "delay" {
+                    if delay.replace(args.clone()).is_some() {
+                        return plan_err!(
+                            "multiple calls to delay() in an expression, which is not allowed"
+                        );
+                    }
+                    return Ok(Transformed::yes(Expr::Column(Column::new_unqualified(
+                        DELAYED_AT_FIELD,
+                    ))));
+                }
+            }
+            Ok(Transformed::no(e))
+        })?;
+
+        Ok((expr.data, delay))
+    }
+}
+
+impl TreeNodeRewriter for DelayRewriter {
+    type Node = LogicalPlan;
+
+    fn f_up(&mut self, node: Self::Node) -> DFResult<Transformed<Self::Node>> {
+        let LogicalPlan::Projection(projection) = node else {
+            for e in node.expressions() {
+                if Self::split_delay(e)?.1.is_some() {
+                    return plan_err!("delay() is only supported in the SELECT list of a query");
+                }
+            }
+            return Ok(Transformed::no(node));
+        };
+
+        let mut delay = None;
+        let mut exprs = vec![];
+        for (i, e) in projection.expr.iter().enumerate() {
+            let (new_e, Some(args)) = Self::split_delay(e.clone())? else {
+                exprs.push(e.clone());
+                continue;
+            };
+
+            let duration = get_duration(&args[0])?;
+            let allowed_lateness = args
+                .get(1)
+                .map(get_duration)
+                .transpose()?
+                .unwrap_or(Duration::ZERO);
+            if let Some(prev) = delay.replace((duration, allowed_lateness)) {
+                if prev != (duration, allowed_lateness) {
+                    return plan_err!(
+                        "rows may only be delayed by a single interval in a SELECT, but found {:?} and {:?}",
+                        prev.0,
+                        duration
+                    );
+                }
+            }
+
+            // keep the name the call had in the original projection
+            let (qualifier, field) = projection.schema.qualified_field(i);
+            exprs.push(
+                new_e
+                    .unalias()
+                    .alias_qualified(qualifier.cloned(), field.name()),
+            );
+        }
+
+        let Some((delay, allowed_lateness)) = delay else {
+            return Ok(Transformed::no(LogicalPlan::Projection(projection)));
+        };
+
+        if delay.is_zero() {
+            return plan_err!("delay() interval must be positive");
+        }
+
+        if projection
+            .input
+            .schema()
+            .has_column_with_unqualified_name(UPDATING_META_FIELD)
+        {
+            return plan_err!("delay() is not supported for updating inputs");
+        }
+
+        let input = if matches!(*projection.input, LogicalPlan::Extension(..)) {
+            (*projection.input).clone()
+        } else {
+            LogicalPlan::Extension(Extension {
+                node: Arc::new(RemoteTableExtension {
+                    input: (*projection.input).clone(),
+                    name: TableReference::bare("delay_input"),
+                    schema: projection.input.schema().clone(),
+                    materialize: false,
+                }),
+            })
+        };
+
+        let delayed = LogicalPlan::Extension(Extension {
+            node: Arc::new(DelayExtension::new(input, delay, allowed_lateness)?),
+        });
+
+        Ok(Transformed::yes(LogicalPlan::Projection(
+            Projection::try_new(exprs, Arc::new(delayed))?,
+        )))
+    }
+}
+
 pub struct AsyncUdfRewriter<'a> {
     provider: &'a ArroyoSchemaProvider,
 }
@@ -626,7 +896,7 @@ pub struct TimeWindowUdfChecker {}
 pub fn is_time_window(expr: &Expr) -> Option<&str> {
     if let Expr::ScalarFunction(ScalarFunction { func, args: _ }) = expr {
         match func.name() {
-            "tumble" | "hop" | "session" => {
+            "tumble" | "tumble_by" | "hop" | "session" => {
                 return Some(func.name());
             }
             _ => {}
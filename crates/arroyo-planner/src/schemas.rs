@@ -22,6 +22,34 @@ pub fn window_arrow_struct() -> DataType {
     )
 }
 
+/// The `event` of the row `session_events()` emits when a key's session begins
+pub const SESSION_STARTED: &str = "session_started";
+/// The `event` of the row `session_events()` emits once a key's session has been idle for the gap
+pub const SESSION_ENDED: &str = "session_ended";
+
+/// The struct returned by `session_events()`. `end` and `duration_ms` are only set for
+/// `session_ended` rows; `end` is the time of the session's last event.
+pub fn session_events_struct() -> DataType {
+    DataType::Struct(
+        vec![
+            Arc::new(Field::new("event", DataType::Utf8, false)),
+            Arc::new(Field::new(
+                "start",
+                DataType::Timestamp(TimeUnit::Nanosecond, None),
+                false,
+            )),
+            Arc::new(Field::new(
+                "end",
+                DataType::Timestamp(TimeUnit::Nanosecond, None),
+                true,
+            )),
+            Arc::new(Field::new("duration_ms", DataType::Int64, true)),
+            Arc::new(Field::new("event_count", DataType::Int64, false)),
+        ]
+        .into(),
+    )
+}
+
 pub(crate) fn add_timestamp_field(
     schema: DFSchemaRef,
     qualifier: Option<TableReference>,
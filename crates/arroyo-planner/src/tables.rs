@@ -1,7 +1,11 @@
 use std::str::FromStr;
 use std::sync::Arc;
-use std::{collections::HashMap, time::Duration};
+use std::{
+    collections::HashMap,
+    time::{Duration, SystemTime},
+};
 
+use arrow::compute::kernels::cast_utils::string_to_timestamp_nanos;
 use arrow_schema::{DataType, Field, FieldRef, Schema};
 use arroyo_connectors::connector_for_type;
 
@@ -11,23 +15,25 @@ use crate::{
     external::{ProcessingMode, SqlSource},
     fields_with_qualifiers, parse_sql, ArroyoSchemaProvider, DFField,
 };
-use crate::{rewrite_plan, DEFAULT_IDLE_TIME};
+use crate::{parse_duration, rewrite_plan};
 use arroyo_datastream::default_sink;
 use arroyo_operator::connector::Connection;
 use arroyo_rpc::api_types::connections::{
     ConnectionProfile, ConnectionSchema, ConnectionType, SourceField,
 };
-use arroyo_rpc::formats::{BadData, Format, Framing, JsonFormat};
+use arroyo_rpc::formats::{BadData, Format, Framing, JsonFormat, TimestampField, XmlFormat};
 use arroyo_rpc::grpc::api::ConnectorOp;
-use arroyo_types::ArroyoExtensionType;
+use arroyo_rpc::{OperatorConfig, TRACEPARENT_FIELD};
+use arroyo_types::{from_nanos, to_nanos, ArroyoExtensionType};
 use datafusion::common::tree_node::{TreeNode, TreeNodeRecursion, TreeNodeVisitor};
 use datafusion::common::{config::ConfigOptions, DFSchema, Result, ScalarValue};
 use datafusion::common::{plan_err, Column, DataFusionError};
 use datafusion::execution::context::SessionState;
 use datafusion::execution::FunctionRegistry;
+use datafusion::functions::core::expr_fn::get_field;
 use datafusion::logical_expr::{
-    CreateMemoryTable, CreateView, DdlStatement, DmlStatement, Expr, Extension, LogicalPlan,
-    WriteOp,
+    CreateMemoryTable, CreateView, DdlStatement, DmlStatement, Expr, ExprSchemable, Extension,
+    LogicalPlan, WriteOp,
 };
 use datafusion::optimizer::common_subexpr_eliminate::CommonSubexprEliminate;
 use datafusion::optimizer::decorrelate_predicate_subquery::DecorrelatePredicateSubquery;
@@ -57,7 +63,7 @@ use datafusion::{
     optimizer::{optimizer::Optimizer, OptimizerContext},
     sql::{
         planner::SqlToRel,
-        sqlparser::ast::{ColumnDef, ColumnOption, Statement, Value},
+        sqlparser::ast::{ColumnDef, ColumnOption, DataType as SQLDataType, Statement, Value},
     },
 };
 
@@ -73,12 +79,35 @@ pub struct ConnectorTable {
     pub format: Option<Format>,
     pub event_time_field: Option<String>,
     pub watermark_field: Option<String>,
+    // a UDF that computes the watermark from the event time of each row, in place of the
+    // watermark field
+    pub watermark_udf: Option<String>,
+    // overrides the session's source idle time; a zero duration disables idleness
     pub idle_time: Option<Duration>,
+    // the parallelism of the operators reading from or writing to this table
+    pub parallelism: Option<usize>,
+    // drops rows read from this source with the same keys as one seen within a window
+    pub dedupe: Option<Dedupe>,
+    // the field that the event times of rows read from this source are taken from as they're
+    // deserialized
+    pub timestamp_field: Option<TimestampField>,
+    // for the stream of a hybrid table, the time to start reading at
+    pub start_at: Option<SystemTime>,
+    // for the backfill of a hybrid table, the time to finish reading at
+    pub end_at: Option<SystemTime>,
     pub primary_keys: Arc<Vec<String>>,
 
     pub inferred_fields: Option<Vec<DFField>>,
 }
 
+/// Deduplication of the rows read from a source: a row is dropped if one with the same values
+/// for `keys` was read within `window` of it, in event time
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Dedupe {
+    pub keys: Vec<String>,
+    pub window: Duration,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum FieldSpec {
     Struct(Field),
@@ -204,7 +233,13 @@ impl From<Connection> for ConnectorTable {
             format: value.schema.format.clone(),
             event_time_field: None,
             watermark_field: None,
-            idle_time: DEFAULT_IDLE_TIME,
+            watermark_udf: None,
+            idle_time: None,
+            parallelism: None,
+            dedupe: None,
+            timestamp_field: None,
+            start_at: None,
+            end_at: None,
             primary_keys: Arc::new(vec![]),
             inferred_fields: None,
         }
@@ -221,7 +256,8 @@ impl ConnectorTable {
         connection_profile: Option<&ConnectionProfile>,
     ) -> Result<Self> {
         // TODO: a more principled way of letting connectors dictate types to use
-        if "delta" == connector {
+        // Delta Lake and Iceberg store timestamps with microsecond precision
+        if "delta" == connector || "iceberg" == connector {
             fields = fields
                 .into_iter()
                 .map(|field_spec| match &field_spec {
@@ -233,12 +269,20 @@ impl ConnectorTable {
                         }
                         _ => field_spec,
                     },
-                    FieldSpec::Metadata { .. } | FieldSpec::Virtual { .. } => {
-                        unreachable!("delta lake is only a sink, can't have virtual fields")
-                    }
+                    FieldSpec::Metadata { .. } | FieldSpec::Virtual { .. } => field_spec,
                 })
                 .collect();
         }
+        // Postgres CDC sources and side inputs always produce a debezium-style changelog, and
+        // the Postgres and MySQL sinks always consume one
+        if matches!(
+            connector,
+            "postgres_cdc" | "postgres" | "mysql" | "side_input"
+        ) && !options.contains_key("format")
+        {
+            options.insert("format".to_string(), "debezium_json".to_string());
+        }
+
         let connector = connector_for_type(connector)
             .ok_or_else(|| DataFusionError::Plan(format!("Unknown connector '{}'", connector)))?;
 
@@ -248,6 +292,45 @@ impl ConnectorTable {
         let framing = Framing::from_opts(options)
             .map_err(|e| DataFusionError::Plan(format!("invalid framing: '{e}'")))?;
 
+        let timestamp_field = TimestampField::from_opts(options)
+            .map_err(|e| DataFusionError::Plan(format!("invalid timestamp field: '{e}'")))?;
+
+        // with `traceparent = 'true'`, the W3C trace context of each message is carried through
+        // the pipeline in a hidden _traceparent column, which sinks write back out as a header
+        if options
+            .remove("traceparent")
+            .filter(|t| t == "true")
+            .is_some()
+        {
+            if fields.is_empty() {
+                return plan_err!(
+                    "traceparent can only be enabled for tables that declare their columns"
+                );
+            }
+            if matches!(
+                &format,
+                Some(Format::Json(JsonFormat { debezium: true, .. }))
+            ) {
+                return plan_err!("traceparent can't be used with debezium format");
+            }
+            if fields.iter().any(|f| f.field().name() == TRACEPARENT_FIELD) {
+                return plan_err!(
+                    "tables with traceparent enabled can't declare a {} column",
+                    TRACEPARENT_FIELD
+                );
+            }
+
+            let field = Field::new(TRACEPARENT_FIELD, DataType::Utf8, true);
+            fields.push(if options.get("type").is_some_and(|t| t == "sink") {
+                FieldSpec::Struct(field)
+            } else {
+                FieldSpec::Metadata {
+                    field,
+                    key: "traceparent".to_string(),
+                }
+            });
+        }
+
         let mut input_to_schema_fields = fields.clone();
 
         if let Some(Format::Json(JsonFormat { debezium: true, .. })) = &format {
@@ -309,15 +392,57 @@ impl ConnectorTable {
 
         table.event_time_field = options.remove("event_time_field");
         table.watermark_field = options.remove("watermark_field");
+        table.watermark_udf = options.remove("watermark_udf");
 
-        table.idle_time = options
+        if table.watermark_field.is_some() && table.watermark_udf.is_some() {
+            return plan_err!("only one of watermark_field and watermark_udf may be set");
+        }
+
+        let idle_micros = options
             .remove("idle_micros")
             .map(|t| i64::from_str(&t))
             .transpose()
             .map_err(|_| DataFusionError::Plan("idle_micros must be set to a number".to_string()))?
-            .or_else(|| DEFAULT_IDLE_TIME.map(|t| t.as_micros() as i64))
-            .filter(|t| *t <= 0)
-            .map(|t| Duration::from_micros(t as u64));
+            .map(|t| Duration::from_micros(t.max(0) as u64));
+
+        table.idle_time = options
+            .remove("idle_time")
+            .map(|t| {
+                parse_duration(&t).ok_or_else(|| {
+                    DataFusionError::Plan(format!(
+                        "idle_time must be set to an interval like '30 seconds', not '{}'",
+                        t
+                    ))
+                })
+            })
+            .transpose()?
+            .or(idle_micros);
+
+        table.parallelism = options
+            .remove("parallelism")
+            .map(|p| match usize::from_str(&p) {
+                Ok(p) if p > 0 => Ok(p),
+                _ => plan_err!("parallelism must be set to a positive integer, not '{}'", p),
+            })
+            .transpose()?;
+
+        table.dedupe = match (
+            options.remove("dedupe.keys"),
+            options.remove("dedupe.window"),
+        ) {
+            (Some(keys), Some(window)) => Some(table.dedupe_from_options(&keys, &window)?),
+            (None, None) => None,
+            _ => return plan_err!("dedupe.keys and dedupe.window must be set together"),
+        };
+
+        if let Some(timestamp_field) = timestamp_field {
+            table.validate_timestamp_field(&timestamp_field)?;
+            table.timestamp_field = Some(timestamp_field);
+        }
+
+        if let Some(Format::Xml(xml)) = &table.format {
+            table.validate_xml_format(xml)?;
+        }
 
         if !options.is_empty() {
             let keys: Vec<String> = options.keys().map(|s| format!("'{}'", s)).collect();
@@ -339,6 +464,94 @@ impl ConnectorTable {
         Ok(table)
     }
 
+    fn validate_timestamp_field(&self, timestamp_field: &TimestampField) -> Result<()> {
+        if self.connection_type != ConnectionType::Source {
+            return plan_err!("timestamp.field can only be set on source tables");
+        }
+
+        if self.is_updating() {
+            return plan_err!("timestamp.field can't be used with updating sources");
+        }
+
+        if self.event_time_field.is_some() {
+            return plan_err!("only one of timestamp.field and event_time_field may be set");
+        }
+
+        if !self.fields.is_empty()
+            && !self
+                .fields
+                .iter()
+                .any(|f| !f.is_virtual() && f.field().name() == &timestamp_field.field)
+        {
+            return plan_err!(
+                "timestamp field '{}' is not a field of table {}",
+                timestamp_field.field,
+                self.name
+            );
+        }
+
+        Ok(())
+    }
+
+    fn validate_xml_format(&self, xml: &XmlFormat) -> Result<()> {
+        if self.connection_type != ConnectionType::Source {
+            return plan_err!("the xml format can only be used for sources");
+        }
+
+        if let Some(column) = xml.fields.keys().find(|column| {
+            !self
+                .fields
+                .iter()
+                .any(|f| !f.is_virtual() && f.field().name() == *column)
+        }) {
+            return plan_err!(
+                "xml.fields.{} does not refer to a field of table {}",
+                column,
+                self.name
+            );
+        }
+
+        Ok(())
+    }
+
+    fn dedupe_from_options(&self, keys: &str, window: &str) -> Result<Dedupe> {
+        if self.connection_type != ConnectionType::Source {
+            return plan_err!("dedupe options can only be set on source tables");
+        }
+
+        if self.is_updating() {
+            return plan_err!("dedupe options can't be used with updating sources");
+        }
+
+        let keys: Vec<String> = keys
+            .split(',')
+            .map(|k| k.trim().to_string())
+            .filter(|k| !k.is_empty())
+            .collect();
+
+        if keys.is_empty() {
+            return plan_err!("dedupe.keys must be set to a comma-separated list of fields");
+        }
+
+        for key in &keys {
+            if !self.fields.iter().any(|f| f.field().name() == key) {
+                return plan_err!("dedupe key '{}' is not a field of table {}", key, self.name);
+            }
+        }
+
+        let window = match parse_duration(window) {
+            Some(w) if !w.is_zero() => w,
+            _ => {
+                return plan_err!(
+                    "dedupe.window must be set to a positive interval like '10 minutes', not '{}'",
+                    window
+                )
+            }
+        };
+
+        Ok(Dedupe { keys, window })
+    }
+
     fn has_virtual_fields(&self) -> bool {
         self.fields.iter().any(|f| f.is_virtual())
     }
@@ -350,44 +563,124 @@ impl ConnectorTable {
             .unwrap_or(false)
     }
 
+    /// Whether this is a source that reads a finite input, and so can be used in bounded
+    /// execution mode
+    pub(crate) fn is_bounded(&self) -> Result<bool> {
+        let Some(connector) = connector_for_type(&self.connector) else {
+            return Ok(false);
+        };
+
+        let config: OperatorConfig = serde_json::from_str(&self.config).map_err(|e| {
+            DataFusionError::Plan(format!(
+                "invalid connector config for {}: {:?}",
+                self.name, e
+            ))
+        })?;
+
+        connector
+            .is_bounded(&config.connection, &config.table)
+            .map_err(|e| {
+                DataFusionError::Plan(format!(
+                    "invalid connector config for {}: {:?}",
+                    self.name, e
+                ))
+            })
+    }
+
+    /// Whether this is a source that can start and finish reading at a point in time, and so
+    /// can be the stream (or an unbounded backfill) of a hybrid table
+    pub(crate) fn seeks_to_time(&self) -> Result<bool> {
+        let Some(connector) = connector_for_type(&self.connector) else {
+            return Ok(false);
+        };
+
+        let config: OperatorConfig = serde_json::from_str(&self.config).map_err(|e| {
+            DataFusionError::Plan(format!(
+                "invalid connector config for {}: {:?}",
+                self.name, e
+            ))
+        })?;
+
+        connector
+            .seeks_to_time(&config.connection, &config.table)
+            .map_err(|e| {
+                DataFusionError::Plan(format!(
+                    "invalid connector config for {}: {:?}",
+                    self.name, e
+                ))
+            })
+    }
+
     fn timestamp_override(&self) -> Result<Option<Expr>> {
         if let Some(field_name) = &self.event_time_field {
             if self.is_updating() {
                 return plan_err!("can't use event_time_field with update mode.");
             }
 
-            // check that a column exists and it is a timestamp
-            let field = self.get_time_field(field_name)?;
-
-            Ok(Some(Expr::Column(Column::from_name(field.field().name()))))
+            Ok(Some(self.time_field_expr(field_name)?))
         } else {
             Ok(None)
         }
     }
 
-    fn get_time_field(&self, field_name: &String) -> Result<&FieldSpec, DataFusionError> {
+    /// Resolves a time field, which may either be a top-level column or a dot-separated path
+    /// into a struct column (like `payload.created_at`). Returns the top-level field along
+    /// with the path of nested field names beneath it.
+    pub(crate) fn get_time_field(
+        &self,
+        field_name: &str,
+    ) -> Result<(&FieldSpec, Vec<String>), DataFusionError> {
+        let not_found =
+            || DataFusionError::Plan(format!("field {} not found or not a timestamp", field_name));
+
+        if let Some(field) = self.fields.iter().find(|f| {
+            f.field().name() == field_name
+                && matches!(f.field().data_type(), DataType::Timestamp(..))
+        }) {
+            return Ok((field, vec![]));
+        }
+
+        let mut parts = field_name.split('.');
+        let root = parts.next().ok_or_else(not_found)?;
         let field = self
             .fields
             .iter()
-            .find(|f| {
-                f.field().name() == field_name
-                    && matches!(f.field().data_type(), DataType::Timestamp(..))
-            })
-            .ok_or_else(|| {
-                DataFusionError::Plan(format!("field {} not found or not a timestamp", field_name))
-            })?;
-        Ok(field)
+            .find(|f| f.field().name() == root)
+            .ok_or_else(not_found)?;
+
+        let mut data_type = field.field().data_type();
+        let mut path = vec![];
+        for part in parts {
+            let DataType::Struct(fields) = data_type else {
+                return Err(not_found());
+            };
+            let (_, nested) = fields.find(part).ok_or_else(not_found)?;
+            data_type = nested.data_type();
+            path.push(part.to_string());
+        }
+
+        if path.is_empty() || !matches!(data_type, DataType::Timestamp(..)) {
+            return Err(not_found());
+        }
+
+        Ok((field, path))
     }
 
-    fn watermark_column(&self) -> Result<Option<Expr>> {
-        if let Some(field_name) = &self.watermark_field {
-            // check that a column exists and it is a timestamp
-            let field = self.get_time_field(field_name)?;
+    fn time_field_expr(&self, field_name: &str) -> Result<Expr> {
+        // check that a column exists and it is a timestamp
+        let (field, path) = self.get_time_field(field_name)?;
 
-            Ok(Some(Expr::Column(Column::from_name(field.field().name()))))
-        } else {
-            Ok(None)
-        }
+        Ok(nested_field_expr(
+            Expr::Column(Column::from_name(field.field().name())),
+            &path,
+        ))
+    }
+
+    fn watermark_column(&self) -> Result<Option<Expr>> {
+        self.watermark_field
+            .as_ref()
+            .map(|field_name| self.time_field_expr(field_name))
+            .transpose()
     }
 
     pub fn physical_schema(&self) -> Schema {
@@ -405,6 +698,12 @@ impl ConnectorTable {
             connector: self.connector.clone(),
             config: self.config.clone(),
             description: self.description.clone(),
+            timestamp_field: self
+                .timestamp_field
+                .as_ref()
+                .map(|t| serde_json::to_string(t).unwrap()),
+            start_at: self.start_at.map(|t| to_nanos(t) as u64),
+            end_at: self.end_at.map(|t| to_nanos(t) as u64),
         }
     }
 
@@ -479,6 +778,27 @@ pub enum Table {
     },
 }
 
+fn is_nested_type_name(data_type: &SQLDataType) -> bool {
+    match data_type {
+        SQLDataType::Custom(name, modifiers) if modifiers.is_empty() => {
+            let name = name.to_string().to_uppercase();
+            name == "STRUCT" || name == "MAP"
+        }
+        _ => false,
+    }
+}
+
+/// Builds an expression accessing the nested struct field at `path` beneath `expr`
+pub(crate) fn nested_field_expr(expr: Expr, path: &[String]) -> Expr {
+    path.iter()
+        .fold(expr, |expr, name| get_field(expr, name.as_str()))
+}
+
+/// Quotes an identifier for use in generated SQL, escaping any quotes within it
+fn quote_ident(ident: &str) -> String {
+    format!("\"{}\"", ident.replace('"', "\"\""))
+}
+
 fn value_to_inner_string(value: &Value) -> Result<String> {
     match value {
         Value::SingleQuotedString(s) => Ok(s.to_string()),
@@ -588,15 +908,10 @@ impl Table {
             .iter()
             .map(|column| {
                 let name = column.name.value.to_string();
-                let (data_type, extension) = convert_data_type(&column.data_type)?;
                 let nullable = !column
                     .options
                     .iter()
                     .any(|option| matches!(option.option, ColumnOption::NotNull));
-                let struct_field = ArroyoExtensionType::add_metadata(
-                    extension,
-                    Field::new(name, data_type.clone(), nullable),
-                );
 
                 let generating_expression = column.options.iter().find_map(|option| {
                     if let ColumnOption::Generated {
@@ -608,6 +923,31 @@ impl Table {
                         None
                     }
                 });
+
+                // the element types of STRUCT and MAP columns can't be written in DDL, so for
+                // virtual fields we infer them from the generating expression
+                let (data_type, extension) = match (
+                    generating_expression.is_some(),
+                    is_nested_type_name(&column.data_type),
+                ) {
+                    (true, true) => (DataType::Null, None),
+                    (false, true) => {
+                        return plan_err!(
+                            "column {} has type {}, but STRUCT and MAP columns must be generated \
+                            (GENERATED ALWAYS AS (...) STORED) because their field types can't be \
+                            declared in SQL; to read nested data from a source, declare the column \
+                            as JSON or use a connection schema",
+                            name,
+                            column.data_type
+                        );
+                    }
+                    (_, false) => convert_data_type(&column.data_type)?,
+                };
+
+                let struct_field = ArroyoExtensionType::add_metadata(
+                    extension,
+                    Field::new(name, data_type, nullable),
+                );
                 Ok((struct_field, generating_expression))
             })
             .collect::<Result<Vec<_>>>()?;
@@ -647,6 +987,20 @@ impl Table {
                         session_state,
                     )?;
 
+                    let struct_field = if struct_field.data_type() == &DataType::Null {
+                        let data_type = df_expr.get_type(&physical_schema)?;
+                        if !matches!(data_type, DataType::Struct(_) | DataType::Map(_, _)) {
+                            return plan_err!(
+                                "virtual field {} is declared as a nested type but has type {}",
+                                struct_field.name(),
+                                data_type
+                            );
+                        }
+                        struct_field.with_data_type(data_type)
+                    } else {
+                        struct_field
+                    };
+
                     let mut metadata_finder = MetadataFinder::default();
                     df_expr.visit(&mut metadata_finder)?;
 
@@ -668,6 +1022,144 @@ impl Table {
             .collect::<Result<Vec<_>>>()
     }
 
+    /// Creates a hybrid table, which reads historical data from a `backfill` table and hands off
+    /// to a `stream` table at the `switch_at` timestamp. The stream source starts reading at the
+    /// switch point, seeking to it in place of its configured offsets, and the backfill source
+    /// finishes there: one that can seek stops at the first message at or after it, and
+    /// otherwise it must be bounded. Events are split by event time at the switch point, those
+    /// before it only being taken from the backfill and those at or after it only from the
+    /// stream, so events around the boundary that both sources read aren't duplicated.
+    ///
+    /// It's planned as a view over the `UNION ALL` of copies of the two source tables with their
+    /// start and end times set. Both sources start with the pipeline, and its watermark is held
+    /// back by the backfill until it finishes.
+    fn hybrid_from_options(
+        name: &str,
+        options: &mut HashMap<String, String>,
+        schema_provider: &ArroyoSchemaProvider,
+        session_state: &SessionState,
+    ) -> Result<Self> {
+        let mut required = |option: &str| {
+            options.remove(option).ok_or_else(|| {
+                DataFusionError::Plan(format!("hybrid tables require the '{}' option", option))
+            })
+        };
+
+        let backfill = required("backfill")?;
+        let stream = required("stream")?;
+        let switch_at = required("switch_at")?;
+
+        if !options.is_empty() {
+            let keys: Vec<String> = options.keys().map(|s| format!("'{}'", s)).collect();
+            return plan_err!(
+                "unknown options provided in WITH clause: {}",
+                keys.join(", ")
+            );
+        }
+
+        let switch_at = string_to_timestamp_nanos(&switch_at)
+            .ok()
+            .filter(|t| *t >= 0)
+            .ok_or_else(|| {
+                DataFusionError::Plan(format!(
+                    "switch_at must be a timestamp like '2024-01-01T00:00:00Z', not '{}'",
+                    switch_at
+                ))
+            })?;
+        let switch_time = from_nanos(switch_at as u128);
+
+        let source_table = |table_name: &str| match schema_provider.get_table(table_name) {
+            Some(Table::ConnectorTable(t)) if t.connection_type == ConnectionType::Source => {
+                Ok(t.clone())
+            }
+            Some(_) => plan_err!("'{}' is not a source table", table_name),
+            None => plan_err!("table '{}' not found", table_name),
+        };
+
+        let mut backfill_table = source_table(&backfill)?;
+        let mut stream_table = source_table(&stream)?;
+
+        if !stream_table.seeks_to_time()? {
+            return plan_err!(
+                "'{}' can't be the stream of a hybrid table, as {} sources can't start reading at a point in time",
+                stream,
+                stream_table.connector
+            );
+        }
+
+        let backfill_seeks = backfill_table.seeks_to_time()?;
+        if !backfill_seeks && !backfill_table.is_bounded()? {
+            return plan_err!(
+                "'{}' can't be the backfill of a hybrid table, as it's unbounded and {} sources can't finish reading at a point in time",
+                backfill,
+                backfill_table.connector
+            );
+        }
+
+        let backfill_fields = Table::ConnectorTable(backfill_table.clone()).get_fields();
+        let stream_fields = Table::ConnectorTable(stream_table.clone()).get_fields();
+
+        for field in &backfill_fields {
+            match stream_fields.iter().find(|f| f.name() == field.name()) {
+                Some(f) if f.data_type() == field.data_type() => {}
+                Some(f) => {
+                    return plan_err!(
+                        "column '{}' has type {} in '{}' but {} in '{}'",
+                        field.name(),
+                        field.data_type(),
+                        backfill,
+                        f.data_type(),
+                        stream
+                    );
+                }
+                None => {
+                    return plan_err!(
+                        "column '{}' of '{}' is missing from '{}'",
+                        field.name(),
+                        backfill,
+                        stream
+                    );
+                }
+            }
+        }
+
+        let columns = backfill_fields
+            .iter()
+            .map(|f| quote_ident(f.name()))
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        // the view reads from copies of the sources that start or finish at the switch point,
+        // which are distinct from the sources if they're also read directly
+        backfill_table.name = format!("{}__backfill", name);
+        backfill_table.end_at = backfill_seeks.then_some(switch_time);
+        stream_table.name = format!("{}__stream", name);
+        stream_table.start_at = Some(switch_time);
+
+        let sql = format!(
+            "CREATE VIEW {} AS
+            SELECT {columns} FROM {} WHERE _timestamp < to_timestamp_nanos({switch_at})
+            UNION ALL
+            SELECT {columns} FROM {} WHERE _timestamp >= to_timestamp_nanos({switch_at})",
+            quote_ident(name),
+            quote_ident(&backfill_table.name),
+            quote_ident(&stream_table.name),
+        );
+
+        let Some(statement) = parse_sql(&sql)?.pop() else {
+            return plan_err!("failed to plan hybrid table {}", name);
+        };
+
+        // the switch is made on event time, so the sources' timestamps must be visible
+        let mut schema_provider = schema_provider.clone();
+        schema_provider.expose_timestamp = true;
+        schema_provider.insert_table(Table::ConnectorTable(backfill_table));
+        schema_provider.insert_table(Table::ConnectorTable(stream_table));
+
+        Self::try_from_statement(&statement, &schema_provider, session_state)?
+            .ok_or_else(|| DataFusionError::Plan(format!("failed to plan hybrid table {}", name)))
+    }
+
     pub fn try_from_statement(
         statement: &Statement,
         schema_provider: &ArroyoSchemaProvider,
@@ -732,6 +1224,18 @@ impl Table {
                         logical_plan: None,
                     }))
                 }
+                Some("hybrid") => {
+                    if !columns.is_empty() {
+                        return plan_err!(
+                            "hybrid table {} takes its schema from its backfill and stream tables, so it can't declare columns",
+                            name
+                        );
+                    }
+
+                    Self::hybrid_from_options(&name, &mut with_map, schema_provider, session_state)
+                        .map(Some)
+                        .map_err(|e| e.context(format!("Failed to create table {}", name)))
+                }
                 Some(connector) => {
                     let connection_profile = match with_map.remove("connection_profile") {
                         Some(connection_profile_name) => Some(
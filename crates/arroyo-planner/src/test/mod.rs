@@ -1,15 +1,27 @@
 mod plan_tests;
 
+use arrow::array::{AsArray, RecordBatch};
 use arrow_schema::DataType;
 use arroyo_connectors::{
     nexmark::{NexmarkConnector, NexmarkTable},
     EmptyConfig,
 };
+use arroyo_datastream::logical::{LogicalNode, OperatorName};
 use arroyo_operator::connector::Connector;
+use arroyo_rpc::api_types::pipelines::SourceColumn;
+use arroyo_rpc::config::PreviewConfig;
+use arroyo_rpc::grpc::api::{ConnectorOp, StateStorage, UpdatingAggregateOperator};
 use arroyo_udf_host::parse::NullableType;
+use prost::Message;
+use std::time::Duration;
 use test_log::test;
 
-use crate::{parse_and_get_program, ArroyoSchemaProvider, SqlConfig};
+use crate::hints::state_hints;
+use crate::parallelism::parallelism_hints;
+use crate::{
+    parse_and_get_arrow_program, parse_and_get_program, parse_bytes, ArroyoSchemaProvider,
+    PlannedSql, SqlConfig,
+};
 
 fn get_test_schema_provider() -> ArroyoSchemaProvider {
     let mut schema_provider = ArroyoSchemaProvider::new();
@@ -22,6 +34,15 @@ fn get_test_schema_provider() -> ArroyoSchemaProvider {
             NexmarkTable {
                 event_rate: 10.0,
                 runtime: Some(10.0 * 1_000_000.0),
+                num_events: None,
+                rate_schedule: None,
+                seed: None,
+                person_proportion: None,
+                auction_proportion: None,
+                bid_proportion: None,
+                hot_auction_ratio: None,
+                hot_seller_ratio: None,
+                hot_bidder_ratio: None,
             },
             None,
         )
@@ -58,3 +79,878 @@ async fn test_udf() {
         .await
         .unwrap();
 }
+
+#[test(tokio::test)]
+async fn test_watermark_udf() {
+    let mut schema_provider = get_test_schema_provider();
+
+    schema_provider
+        .add_rust_udf(
+            "#[udf] fn market_watermark(t: SystemTime) -> Option<SystemTime> { Some(t) }",
+            "",
+        )
+        .unwrap();
+
+    schema_provider
+        .add_rust_udf("#[udf] fn my_sqr(x: i64) -> i64 { x * x }", "")
+        .unwrap();
+
+    let query = |udf: &str| {
+        format!(
+            "CREATE TABLE events (value TEXT) WITH (
+                connector = 'sse',
+                endpoint = 'http://localhost:8080/events',
+                format = 'json',
+                watermark_udf = '{udf}'
+            );
+            SELECT count(*) FROM events GROUP BY tumble(interval '1 minute')"
+        )
+    };
+
+    parse_and_get_program(
+        &query("market_watermark"),
+        schema_provider.clone(),
+        SqlConfig::default(),
+    )
+    .await
+    .unwrap();
+
+    let err = parse_and_get_program(
+        &query("my_sqr"),
+        schema_provider.clone(),
+        SqlConfig::default(),
+    )
+    .await
+    .unwrap_err();
+    assert!(err
+        .to_string()
+        .contains("must take a single TIMESTAMP argument"));
+
+    let err = parse_and_get_program(&query("unknown"), schema_provider, SqlConfig::default())
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("is not a known UDF"));
+}
+
+#[test(tokio::test)]
+async fn test_set_pipeline_config() {
+    let sql = "SET parallelism = 8;
+    SET checkpoint.interval = '1m';
+    SET queue.size = 1024;
+    SET queue.max_bytes = '16MB';
+    SET checkpoint.alignment_timeout = '30 seconds';
+    SELECT bid.auction FROM nexmark";
+
+    let compiled = parse_and_get_program(sql, get_test_schema_provider(), SqlConfig::default())
+        .await
+        .unwrap();
+
+    let config = &compiled.program.program_config;
+    assert_eq!(config.parallelism, Some(8));
+    assert_eq!(config.checkpoint_interval, Some(Duration::from_secs(60)));
+    assert_eq!(config.queue_size, Some(1024));
+    assert_eq!(config.queue_max_bytes, Some(16 * 1024 * 1024));
+    assert_eq!(
+        config.checkpoint_alignment_timeout,
+        Some(Duration::from_secs(30))
+    );
+
+    let compiled = parse_and_get_program(
+        "SELECT bid.auction FROM nexmark",
+        get_test_schema_provider(),
+        SqlConfig::default(),
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(compiled.program.program_config.parallelism, None);
+    assert_eq!(compiled.program.program_config.checkpoint_interval, None);
+    assert_eq!(compiled.program.program_config.queue_max_bytes, None);
+    assert_eq!(
+        compiled.program.program_config.checkpoint_alignment_timeout,
+        None
+    );
+}
+
+#[test]
+fn test_parse_bytes() {
+    assert_eq!(parse_bytes("65536"), Some(65536));
+    assert_eq!(parse_bytes("512KB"), Some(512 * 1024));
+    assert_eq!(parse_bytes("64 MB"), Some(64 * 1024 * 1024));
+    assert_eq!(parse_bytes(" 2gib "), Some(2 * 1024 * 1024 * 1024));
+    assert_eq!(parse_bytes("100b"), Some(100));
+
+    assert_eq!(parse_bytes(""), None);
+    assert_eq!(parse_bytes("MB"), None);
+    assert_eq!(parse_bytes("1.5MB"), None);
+    assert_eq!(parse_bytes("-1MB"), None);
+    assert_eq!(parse_bytes("10 parsecs"), None);
+    // too large to represent
+    assert_eq!(parse_bytes("99999999999999GB"), None);
+}
+
+#[test(tokio::test)]
+async fn test_set_queue_max_bytes_errors() {
+    for value in ["'10 parsecs'", "0", "'0MB'", "true"] {
+        let sql = format!("SET queue.max_bytes = {value}; SELECT bid.auction FROM nexmark");
+        let err = parse_and_get_program(&sql, get_test_schema_provider(), SqlConfig::default())
+            .await
+            .unwrap_err();
+        assert!(
+            err.to_string().contains("invalid `SET queue.max_bytes`"),
+            "{}: {}",
+            value,
+            err
+        );
+    }
+}
+
+#[test(tokio::test)]
+async fn test_set_autoscaling() {
+    let compiled = parse_and_get_program(
+        "SET autoscaling.min_parallelism = 2;
+        SET autoscaling.max_parallelism = 16;
+        SELECT bid.auction FROM nexmark",
+        get_test_schema_provider(),
+        SqlConfig {
+            default_parallelism: 1,
+            ..SqlConfig::default()
+        },
+    )
+    .await
+    .unwrap();
+
+    let autoscaling = compiled.program.program_config.autoscaling.unwrap();
+    assert_eq!(autoscaling.min_parallelism, 2);
+    assert_eq!(autoscaling.max_parallelism, 16);
+    // the default parallelism is raised to the minimum
+    assert!(compiled
+        .program
+        .graph
+        .node_weights()
+        .any(|n| n.parallelism == 2));
+    assert!(autoscaling.operator_max_parallelism.is_empty());
+
+    // sources can't be scaled beyond what they support, nor hinted operators beyond their hints
+    let compiled = parse_and_get_program(
+        "CREATE TABLE events (value TEXT) WITH (
+            connector = 'sse',
+            endpoint = 'http://localhost:8080/events',
+            format = 'json'
+        );
+        SET autoscaling.max_parallelism = 16;
+        SELECT /*+ parallelism(aggregate=4) */ count(*) FROM events GROUP BY value",
+        get_test_schema_provider(),
+        SqlConfig::default(),
+    )
+    .await
+    .unwrap();
+
+    let autoscaling = compiled.program.program_config.autoscaling.unwrap();
+    for node in compiled.program.graph.node_weights() {
+        let expected = match node.operator_name {
+            OperatorName::ConnectorSource => 1,
+            OperatorName::UpdatingAggregate | OperatorName::ConnectorSink => 4,
+            _ => continue,
+        };
+
+        assert_eq!(
+            autoscaling.operator_max_parallelism.get(&node.operator_id),
+            Some(&expected),
+            "{}",
+            node.operator_id
+        );
+    }
+
+    for sql in [
+        "SET autoscaling.min_parallelism = 2; SELECT bid.auction FROM nexmark",
+        "SET autoscaling.min_parallelism = 8; SET autoscaling.max_parallelism = 4; \
+        SELECT bid.auction FROM nexmark",
+        "SET autoscaling.max_parallelism = 4; SET parallelism = 8; \
+        SELECT bid.auction FROM nexmark",
+    ] {
+        assert!(
+            parse_and_get_program(sql, get_test_schema_provider(), SqlConfig::default())
+                .await
+                .is_err(),
+            "{} should be invalid",
+            sql
+        );
+    }
+}
+
+#[test(tokio::test)]
+async fn test_preview_limits() {
+    let config = SqlConfig {
+        default_parallelism: 4,
+        preview: Some(PreviewConfig {
+            max_rows: 100,
+            max_runtime: "1m".parse().unwrap(),
+            queue_max_bytes: 1024 * 1024,
+            max_state_bytes: 16 * 1024 * 1024,
+            allow_sinks: false,
+        }),
+        ..Default::default()
+    };
+
+    // the query can't loosen the limits of the preview, but may tighten them
+    let compiled = parse_and_get_program(
+        "SET parallelism = 8;
+        SET queue.max_bytes = '16MB';
+        SELECT /*+ parallelism(2) */ bid.auction FROM nexmark",
+        get_test_schema_provider(),
+        config.clone(),
+    )
+    .await
+    .unwrap();
+
+    assert!(compiled
+        .program
+        .graph
+        .node_weights()
+        .all(|n| n.parallelism == 1));
+    assert_eq!(
+        compiled.program.program_config.queue_max_bytes,
+        Some(1024 * 1024)
+    );
+    assert_eq!(
+        compiled.program.program_config.max_state_bytes,
+        Some(16 * 1024 * 1024)
+    );
+
+    let compiled = parse_and_get_program(
+        "SET queue.max_bytes = '64KB';
+        SELECT bid.auction FROM nexmark",
+        get_test_schema_provider(),
+        config,
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(
+        compiled.program.program_config.queue_max_bytes,
+        Some(64 * 1024)
+    );
+}
+
+#[test(tokio::test)]
+async fn test_column_lineage() {
+    let sql = "CREATE TABLE sink (
+        auction BIGINT,
+        doubled BIGINT
+    ) WITH (
+        connector = 'kafka',
+        format = 'json',
+        type = 'sink',
+        bootstrap_servers = 'localhost:9092',
+        topic = 'out'
+    );
+
+    CREATE VIEW bids AS SELECT bid.auction as auction, bid.price as price FROM nexmark;
+
+    INSERT INTO sink SELECT auction, price * 2 FROM bids;";
+
+    let compiled = parse_and_get_program(sql, get_test_schema_provider(), SqlConfig::default())
+        .await
+        .unwrap();
+
+    let lineage = &compiled.program.program_config.lineage;
+    assert_eq!(lineage.len(), 1);
+    assert_eq!(lineage[0].sink, "sink");
+
+    let columns = &lineage[0].columns;
+    assert_eq!(
+        columns
+            .iter()
+            .map(|c| c.column.as_str())
+            .collect::<Vec<_>>(),
+        vec!["auction", "doubled"]
+    );
+
+    for column in columns {
+        assert_eq!(
+            column.sources,
+            vec![SourceColumn {
+                table: "nexmark".to_string(),
+                column: "bid".to_string(),
+            }]
+        );
+    }
+
+    assert!(columns[1]
+        .expressions
+        .iter()
+        .any(|e| e.contains("Int64(2)")));
+}
+
+#[test]
+fn test_parallelism_hints() {
+    let node = |operator_name| LogicalNode {
+        operator_id: "op".to_string(),
+        description: String::new(),
+        operator_name,
+        operator_config: vec![],
+        parallelism: 1,
+    };
+
+    let source = node(OperatorName::ConnectorSource);
+    let aggregate = node(OperatorName::UpdatingAggregate);
+
+    let hints = parallelism_hints(
+        "SET parallelism = 2;
+        SELECT /*+ parallelism(4) */ * FROM a;
+        /* not a hint: parallelism(3) */ SELECT ';' FROM b;
+        ;
+        SELECT 1 /*+ parallelism( 8 ) */;
+        SELECT /*+ parallelism(source = 2, AGGREGATE=6) */ 1;
+        SELECT /*+ parallelism(5, source=3) */ 1",
+    )
+    .unwrap();
+
+    let for_node = |node: &LogicalNode| -> Vec<_> {
+        hints
+            .iter()
+            .map(|h| h.as_ref().and_then(|h| h.for_node(node)))
+            .collect()
+    };
+
+    assert_eq!(
+        for_node(&source),
+        vec![None, Some(4), None, Some(8), Some(2), Some(3)]
+    );
+    assert_eq!(
+        for_node(&aggregate),
+        vec![None, Some(4), None, Some(8), Some(6), Some(5)]
+    );
+
+    assert!(parallelism_hints("SELECT /*+ parallelism(0) */ 1").is_err());
+    assert!(parallelism_hints("SELECT /*+ parallelism(shuffle=2) */ 1").is_err());
+    assert!(parallelism_hints("SELECT /*+ parallelism(source=) */ 1").is_err());
+}
+
+#[test]
+fn test_state_hints() {
+    let node = |operator_name| LogicalNode {
+        operator_id: "op".to_string(),
+        description: String::new(),
+        operator_name,
+        operator_config: vec![],
+        parallelism: 1,
+    };
+
+    let join = node(OperatorName::Join);
+    let aggregate = node(OperatorName::TumblingWindowAggregate);
+    let source = node(OperatorName::ConnectorSource);
+
+    let hints = state_hints(
+        "SELECT /*+ state(rocksdb) */ * FROM a;
+        SELECT 1;
+        SELECT /*+ parallelism(2) state(join = disk, AGGREGATE=Memory) */ 1;
+        SELECT /*+ state(disk, join=rocksdb) */ 1",
+    )
+    .unwrap();
+
+    let for_node = |node: &LogicalNode| -> Vec<_> {
+        hints
+            .iter()
+            .map(|h| h.as_ref().and_then(|h| h.for_node(node).unwrap()))
+            .collect()
+    };
+
+    assert_eq!(
+        for_node(&join),
+        vec![
+            Some(StateStorage::Rocksdb),
+            None,
+            Some(StateStorage::Disk),
+            Some(StateStorage::Rocksdb)
+        ]
+    );
+    assert_eq!(
+        for_node(&aggregate),
+        vec![
+            Some(StateStorage::Rocksdb),
+            None,
+            Some(StateStorage::Memory),
+            Some(StateStorage::Disk)
+        ]
+    );
+    // sources don't keep state that can be moved out of memory
+    assert_eq!(for_node(&source), vec![None, None, None, None]);
+
+    // non-windowed aggregates can only keep their state in memory
+    let updating = node(OperatorName::UpdatingAggregate);
+    for (i, hint) in hints.iter().enumerate() {
+        let result = hint.as_ref().map(|h| h.for_node(&updating));
+        assert_eq!(result.is_some_and(|r| r.is_err()), i != 1);
+    }
+
+    assert!(state_hints("SELECT /*+ state(leveldb) */ 1").is_err());
+    assert!(state_hints("SELECT /*+ state(source=disk) */ 1").is_err());
+}
+
+#[test(tokio::test)]
+async fn test_set_state_storage() {
+    // the pipeline's state storage applies to the stateful operators without a hint
+    let compiled = parse_and_get_program(
+        "SET state.storage = 'rocksdb';
+        SELECT /*+ state(disk) */ count(*) FROM nexmark GROUP BY tumble(interval '1 second');
+        SELECT count(*) FROM nexmark GROUP BY bid.auction",
+        get_test_schema_provider(),
+        SqlConfig::default(),
+    )
+    .await
+    .unwrap();
+
+    let program = &compiled.program;
+    for node in program.graph.node_weights() {
+        let expected = match node.operator_name {
+            OperatorName::TumblingWindowAggregate => Some(StateStorage::Disk),
+            // non-windowed aggregates keep their state in memory
+            OperatorName::UpdatingAggregate
+            | OperatorName::ConnectorSource
+            | OperatorName::ConnectorSink => None,
+            _ => continue,
+        };
+
+        assert_eq!(
+            program
+                .program_config
+                .state_storage
+                .get(&node.operator_id)
+                .copied(),
+            expected,
+            "{}",
+            node.operator_id
+        );
+    }
+
+    let err = parse_and_get_program(
+        "SELECT /*+ state(aggregate=rocksdb) */ count(*) FROM nexmark GROUP BY bid.auction",
+        get_test_schema_provider(),
+        SqlConfig::default(),
+    )
+    .await
+    .unwrap_err();
+    assert!(err.to_string().contains("non-windowed aggregates"));
+
+    let err = parse_and_get_program(
+        "SET state.storage = 'leveldb'; SELECT 1",
+        get_test_schema_provider(),
+        SqlConfig::default(),
+    )
+    .await
+    .unwrap_err();
+    assert!(err.to_string().contains("invalid `SET state.storage`"));
+}
+
+#[test(tokio::test)]
+async fn test_set_checkpoint_storage() {
+    let compiled = parse_and_get_program(
+        "SET checkpoint.url = 's3://checkpoints/pipeline-a';
+        SET checkpoint.storage_options = '{\"aws_access_key_id\": \"{{ PIPELINE_A_KEY }}\"}';
+        SELECT 1",
+        get_test_schema_provider(),
+        SqlConfig::default(),
+    )
+    .await
+    .unwrap();
+
+    let storage = compiled
+        .program
+        .program_config
+        .checkpoint_storage
+        .expect("should have checkpoint storage");
+    assert_eq!(storage.url, "s3://checkpoints/pipeline-a");
+    assert_eq!(
+        storage.options.get("aws_access_key_id").map(|s| s.as_str()),
+        Some("{{ PIPELINE_A_KEY }}")
+    );
+
+    for (query, error) in [
+        (
+            "SET checkpoint.url = 'ftp://checkpoints'; SELECT 1",
+            "invalid `SET checkpoint.url`",
+        ),
+        (
+            "SET checkpoint.url = 's3://checkpoints'; SET checkpoint.storage_options = 'key'; SELECT 1",
+            "invalid `SET checkpoint.storage_options`",
+        ),
+        (
+            "SET checkpoint.storage_options = '{\"region\": \"us-east-1\"}'; SELECT 1",
+            "requires `SET checkpoint.url`",
+        ),
+    ] {
+        let err = parse_and_get_program(query, get_test_schema_provider(), SqlConfig::default())
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains(error), "{}: {}", query, err);
+    }
+}
+
+#[test(tokio::test)]
+async fn test_parallelism_assignment() {
+    let config = SqlConfig {
+        default_parallelism: 3,
+        ..Default::default()
+    };
+
+    let compiled = parse_and_get_program(
+        "SELECT bid.auction FROM nexmark",
+        get_test_schema_provider(),
+        config.clone(),
+    )
+    .await
+    .unwrap();
+
+    assert!(compiled
+        .program
+        .graph
+        .node_weights()
+        .all(|n| n.parallelism == 3));
+
+    let compiled = parse_and_get_program(
+        "SELECT /*+ parallelism(2) */ bid.auction FROM nexmark",
+        get_test_schema_provider(),
+        config.clone(),
+    )
+    .await
+    .unwrap();
+
+    assert!(compiled
+        .program
+        .graph
+        .node_weights()
+        .all(|n| n.parallelism == 2));
+
+    // SSE sources can only be read by a single subtask, so they and the operators chained to
+    // them run with parallelism 1, while operators after the shuffle use the default
+    let compiled = parse_and_get_program(
+        "CREATE TABLE events (value TEXT) WITH (
+            connector = 'sse',
+            endpoint = 'http://localhost:8080/events',
+            format = 'json'
+        );
+        SELECT count(*) FROM events GROUP BY value",
+        get_test_schema_provider(),
+        config.clone(),
+    )
+    .await
+    .unwrap();
+
+    let graph = &compiled.program.graph;
+    for node in graph.node_weights() {
+        let expected = if node.operator_name == OperatorName::ConnectorSource {
+            1
+        } else if node.operator_name == OperatorName::ConnectorSink {
+            3
+        } else {
+            continue;
+        };
+
+        assert_eq!(node.parallelism, expected, "{}", node.operator_id);
+    }
+
+    // hints for particular kinds of operators, and parallelism set on the table itself; the
+    // sink has no hint of its own, so it follows the aggregate it's chained to
+    let compiled = parse_and_get_program(
+        "CREATE TABLE impulse WITH (
+            connector = 'impulse',
+            event_rate = '10',
+            parallelism = '2'
+        );
+        SELECT /*+ parallelism(aggregate=5) */ count(*)
+        FROM impulse GROUP BY counter % 10",
+        get_test_schema_provider(),
+        config,
+    )
+    .await
+    .unwrap();
+
+    let graph = &compiled.program.graph;
+    for node in graph.node_weights() {
+        let expected = match node.operator_name {
+            OperatorName::ConnectorSource => 2,
+            OperatorName::UpdatingAggregate | OperatorName::ConnectorSink => 5,
+            _ => continue,
+        };
+
+        assert_eq!(node.parallelism, expected, "{}", node.operator_id);
+    }
+}
+
+#[test(tokio::test)]
+async fn test_stable_operator_ids() {
+    async fn operator_ids(query: &str) -> Vec<(OperatorName, String)> {
+        let compiled =
+            parse_and_get_program(query, get_test_schema_provider(), SqlConfig::default())
+                .await
+                .unwrap();
+
+        let mut ids: Vec<_> = compiled
+            .program
+            .graph
+            .node_weights()
+            .map(|n| (n.operator_name, n.operator_id.clone()))
+            .collect();
+        ids.sort_by(|(_, a), (_, b)| a.cmp(b));
+        ids
+    }
+
+    fn id_for(ids: &[(OperatorName, String)], name: OperatorName) -> &str {
+        &ids.iter().find(|(n, _)| *n == name).unwrap().1
+    }
+
+    let query = "SELECT count(*) FROM nexmark GROUP BY tumble(interval '1 second')";
+    let ids = operator_ids(query).await;
+
+    // recompiling the same query gives the same ids
+    assert_eq!(ids, operator_ids(query).await);
+
+    // ids describe what the operators do
+    assert!(id_for(&ids, OperatorName::ConnectorSource).starts_with("source_nexmark_"));
+    assert!(id_for(&ids, OperatorName::ExpressionWatermark).starts_with("watermark_nexmark_"));
+    assert!(id_for(&ids, OperatorName::TumblingWindowAggregate).starts_with("tumbling_1s_"));
+
+    // changing the aggregate changes its id, but not those of the operators it reads from
+    let edited =
+        operator_ids("SELECT max(bid.price) FROM nexmark GROUP BY tumble(interval '1 second')")
+            .await;
+
+    assert_ne!(
+        id_for(&ids, OperatorName::TumblingWindowAggregate),
+        id_for(&edited, OperatorName::TumblingWindowAggregate)
+    );
+    assert_eq!(
+        id_for(&ids, OperatorName::ConnectorSource),
+        id_for(&edited, OperatorName::ConnectorSource)
+    );
+
+    // and editing the query upstream of the aggregate doesn't change the ids of it or the
+    // operators downstream of it
+    let filtered = operator_ids(
+        "SELECT count(*) FROM nexmark WHERE bid IS NOT NULL GROUP BY tumble(interval '1 second')",
+    )
+    .await;
+
+    for name in [
+        OperatorName::ConnectorSource,
+        OperatorName::TumblingWindowAggregate,
+        OperatorName::ConnectorSink,
+    ] {
+        assert_eq!(id_for(&ids, name), id_for(&filtered, name), "{:?}", name);
+    }
+}
+
+#[test(tokio::test)]
+async fn test_hybrid_handoff() {
+    async fn sources(hybrid: &str) -> datafusion::common::Result<Vec<ConnectorOp>> {
+        let query = format!(
+            "CREATE TABLE history (id BIGINT, value TEXT) WITH (
+                connector = 'filesystem',
+                type = 'source',
+                path = '/data/events',
+                format = 'parquet'
+            );
+            CREATE TABLE archive (id BIGINT, value TEXT) WITH (
+                connector = 'kafka',
+                bootstrap_servers = 'localhost:9092',
+                topic = 'archive',
+                type = 'source',
+                format = 'json',
+                'source.offset' = 'earliest'
+            );
+            CREATE TABLE live (id BIGINT, value TEXT) WITH (
+                connector = 'kafka',
+                bootstrap_servers = 'localhost:9092',
+                topic = 'events',
+                type = 'source',
+                format = 'json'
+            );
+            CREATE TABLE ticks WITH (
+                connector = 'impulse',
+                event_rate = '10'
+            );
+            CREATE TABLE events WITH (
+                connector = 'hybrid',
+                {hybrid},
+                switch_at = '2024-06-01T00:00:00Z'
+            );
+            SELECT * FROM events;"
+        );
+
+        let compiled =
+            parse_and_get_program(&query, get_test_schema_provider(), SqlConfig::default()).await?;
+
+        Ok(compiled
+            .program
+            .graph
+            .node_weights()
+            .filter(|n| n.operator_name == OperatorName::ConnectorSource)
+            .map(|n| ConnectorOp::decode(&n.operator_config[..]).unwrap())
+            .collect())
+    }
+
+    fn source<'a>(sources: &'a [ConnectorOp], connector: &str, config: &str) -> &'a ConnectorOp {
+        sources
+            .iter()
+            .find(|s| s.connector == connector && s.config.contains(config))
+            .unwrap()
+    }
+
+    let switch_at = 1_717_200_000_000_000_000;
+
+    // the stream starts at the switch point, and a bounded backfill reads all of its input
+    let ops = sources("backfill = 'history', stream = 'live'")
+        .await
+        .unwrap();
+    assert_eq!(ops.len(), 2);
+    let stream = source(&ops, "kafka", "events");
+    assert_eq!((stream.start_at, stream.end_at), (Some(switch_at), None));
+    let backfill = source(&ops, "filesystem", "/data/events");
+    assert_eq!((backfill.start_at, backfill.end_at), (None, None));
+
+    // a backfill that can seek finishes at the switch point
+    let ops = sources("backfill = 'archive', stream = 'live'")
+        .await
+        .unwrap();
+    let backfill = source(&ops, "kafka", "archive");
+    assert_eq!(
+        (backfill.start_at, backfill.end_at),
+        (None, Some(switch_at))
+    );
+    let stream = source(&ops, "kafka", "events");
+    assert_eq!((stream.start_at, stream.end_at), (Some(switch_at), None));
+
+    // the stream must be able to start at the switch point...
+    let err = sources("backfill = 'archive', stream = 'history'")
+        .await
+        .unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("can't be the stream of a hybrid table"),
+        "{}",
+        err
+    );
+
+    // ...and the backfill must finish
+    let err = sources("backfill = 'ticks', stream = 'live'")
+        .await
+        .unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("can't be the backfill of a hybrid table"),
+        "{}",
+        err
+    );
+}
+
+#[test(tokio::test)]
+async fn test_bounded_execution_mode() {
+    let query = "CREATE TABLE output (auction BIGINT, bids BIGINT) WITH (
+        connector = 'blackhole'
+    );
+
+    INSERT INTO output
+    SELECT bid.auction, count(*) FROM nexmark GROUP BY bid.auction;";
+
+    // a non-windowed aggregate is updating when streaming, so can't be written to an
+    // append-only sink...
+    let err = parse_and_get_program(query, get_test_schema_provider(), SqlConfig::default())
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("input is updating"), "{}", err);
+
+    // ...but in bounded mode it's a blocking aggregate, which emits its final results once
+    let compiled = parse_and_get_program(
+        &format!("SET execution.mode = 'bounded';\n{}", query),
+        get_test_schema_provider(),
+        SqlConfig::default(),
+    )
+    .await
+    .unwrap();
+
+    let aggregate = compiled
+        .program
+        .graph
+        .node_weights()
+        .find(|n| n.operator_name == OperatorName::UpdatingAggregate)
+        .unwrap();
+    let config = UpdatingAggregateOperator::decode(&aggregate.operator_config[..]).unwrap();
+    assert!(config.blocking);
+
+    // every source must be bounded
+    let err = parse_and_get_program(
+        "SET execution.mode = 'bounded';
+        CREATE TABLE impulse WITH (
+            connector = 'impulse',
+            event_rate = '10'
+        );
+        SELECT count(*) FROM impulse",
+        get_test_schema_provider(),
+        SqlConfig::default(),
+    )
+    .await
+    .unwrap_err();
+    assert!(err.to_string().contains("unbounded source"), "{}", err);
+}
+
+#[test(tokio::test)]
+async fn test_introspection_statements() {
+    async fn result_set(query: &str) -> RecordBatch {
+        match parse_and_get_arrow_program(
+            query.to_string(),
+            get_test_schema_provider(),
+            SqlConfig::default(),
+        )
+        .await
+        .unwrap()
+        {
+            PlannedSql::ResultSet(batch) => batch,
+            PlannedSql::Pipeline(_) => panic!("expected a result set for {}", query),
+        }
+    }
+
+    fn strings(batch: &RecordBatch, column: &str) -> Vec<String> {
+        batch
+            .column_by_name(column)
+            .unwrap()
+            .as_string::<i32>()
+            .iter()
+            .map(|s| s.unwrap_or_default().to_string())
+            .collect()
+    }
+
+    let tables = result_set(
+        "CREATE TABLE impulse WITH (connector = 'impulse', event_rate = '10');
+        SHOW TABLES",
+    )
+    .await;
+    assert_eq!(strings(&tables, "name"), vec!["impulse", "nexmark"]);
+    assert_eq!(strings(&tables, "connector"), vec!["impulse", "nexmark"]);
+
+    let columns = result_set("DESCRIBE nexmark").await;
+    assert_eq!(
+        strings(&columns, "column_name"),
+        vec!["person", "auction", "bid"]
+    );
+
+    assert_eq!(result_set("SHOW CONNECTIONS").await.num_rows(), 0);
+
+    assert!(parse_and_get_arrow_program(
+        "DESCRIBE missing".to_string(),
+        get_test_schema_provider(),
+        SqlConfig::default()
+    )
+    .await
+    .is_err());
+
+    // introspection can't be mixed with pipelines
+    assert!(parse_and_get_program(
+        "SHOW TABLES; SELECT bid FROM nexmark",
+        get_test_schema_provider(),
+        SqlConfig::default()
+    )
+    .await
+    .is_err());
+}
@@ -0,0 +1,21 @@
+CREATE TABLE Nexmark WITH (
+    connector = 'nexmark',
+    event_rate = '10'
+);
+
+SELECT *, ewma_zscore(avg_price, 0.1) OVER (
+    PARTITION BY window
+    ORDER BY auction) as price_ewma_zscore
+FROM (
+    SELECT
+        bid.auction as auction,
+        tumble(INTERVAL '1' minute) as window,
+        avg(bid.price) as avg_price,
+        mad_score(bid.price) as price_mad_score
+    FROM
+        nexmark
+    where
+        bid is not null
+    GROUP BY
+        1,
+        2)
@@ -0,0 +1,24 @@
+create table orders (
+    id TEXT,
+    amount BIGINT
+) with (
+    connector = 'kafka',
+    topic = 'orders',
+    format = 'arrow_ipc',
+    bootstrap_servers = '0.0.0.0:9092',
+    type = 'source'
+);
+
+create table large_orders (
+    id TEXT,
+    amount BIGINT
+) with (
+    connector = 'filesystem',
+    type = 'sink',
+    path = 's3://my-bucket/large_orders',
+    format = 'arrow_ipc',
+    rollover_seconds = 60
+);
+
+INSERT INTO large_orders
+SELECT id, amount FROM orders WHERE amount > 1000;
@@ -0,0 +1,23 @@
+CREATE TABLE impressions (
+    id TEXT,
+    user_id TEXT
+) WITH (
+    connector = 'sse',
+    format = 'json',
+    endpoint = 'http://localhost:9091/impressions'
+);
+
+CREATE TABLE clicks (
+    impression_id TEXT
+) WITH (
+    connector = 'sse',
+    format = 'json',
+    endpoint = 'http://localhost:9091/clicks'
+);
+
+set left_join_ttl = '1 hour';
+set right_join_ttl = '10 minutes';
+
+SELECT i.user_id, c.impression_id
+FROM impressions i
+JOIN clicks c ON i.id = c.impression_id;
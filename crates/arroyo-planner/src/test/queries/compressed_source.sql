@@ -0,0 +1,15 @@
+create table events (
+    id BIGINT,
+    name TEXT
+) with (
+    connector = 'kafka',
+    bootstrap_servers = 'localhost:9092',
+    type = 'source',
+    topic = 'events',
+    format = 'json',
+    framing = 'newline',
+    compression = 'zstd'
+);
+
+select count(*) from events
+group by tumble(interval '1 minute');
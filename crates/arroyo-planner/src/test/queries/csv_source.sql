@@ -0,0 +1,33 @@
+create table orders (
+    order_id BIGINT NOT NULL,
+    customer TEXT,
+    amount DOUBLE,
+    placed_at TIMESTAMP
+) with (
+    connector = 'filesystem',
+    type = 'source',
+    path = 's3://my-bucket/orders',
+    format = 'csv',
+    'csv.delimiter' = '\t',
+    'csv.quote' = '''',
+    'csv.include_header' = 'true',
+    'source.regex-pattern' = '.*\.tsv'
+);
+
+create table kafka_orders (
+    order_id BIGINT,
+    amount DOUBLE
+) with (
+    connector = 'kafka',
+    bootstrap_servers = 'localhost:9092',
+    type = 'source',
+    topic = 'orders',
+    format = 'csv'
+);
+
+select customer, sum(amount)
+from orders
+group by customer, tumble(interval '1 minute');
+
+select count(*) from kafka_orders
+group by tumble(interval '1 minute');
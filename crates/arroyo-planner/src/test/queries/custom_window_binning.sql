@@ -0,0 +1,24 @@
+CREATE TABLE Nexmark WITH (
+    connector = 'nexmark',
+    event_rate = '10'
+);
+
+SELECT
+    count(*) as count,
+    tumble_by(date_trunc('month', _timestamp), interval '31 days') as monthly_window
+FROM
+    (
+        SELECT
+            bid.auction as auction,
+            tumble_by(date_trunc('month', _timestamp), interval '31 days') as window,
+            count(*) as count
+        FROM
+            nexmark
+        where
+            bid is not null
+        GROUP BY
+            1,
+            2
+    )
+GROUP BY
+    2
@@ -0,0 +1,25 @@
+CREATE TABLE orders (
+    id BIGINT NOT NULL,
+    customer TEXT NOT NULL,
+    status TEXT,
+    amount DOUBLE NOT NULL,
+    created_at TIMESTAMP NOT NULL
+) WITH (
+    connector = 'datagen',
+    event_rate = '1000',
+    seed = '7',
+    'fields.id.kind' = 'sequence',
+    'fields.id.start' = '1',
+    'fields.customer.kind' = 'regex',
+    'fields.customer.regex' = 'cust-[0-9]{4}',
+    'fields.status.kind' = 'enum',
+    'fields.status.values' = 'placed:8,shipped:3,cancelled:1',
+    'fields.status.null_rate' = '0.01',
+    'fields.amount.min' = '1',
+    'fields.amount.max' = '500',
+    'fields.created_at.max_past_ms' = '5000'
+);
+
+SELECT status, count(*), sum(amount)
+FROM orders
+GROUP BY status, tumble(interval '10 seconds');
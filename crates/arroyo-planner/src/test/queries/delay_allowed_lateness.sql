@@ -0,0 +1,26 @@
+CREATE TABLE orders (
+  id BIGINT,
+  customer TEXT,
+  status TEXT
+) WITH (
+  connector = 'kafka',
+  bootstrap_servers = 'localhost:9092',
+  format = 'json',
+  type = 'source',
+  topic = 'orders'
+);
+
+CREATE TABLE reminders (
+  id BIGINT,
+  reminded_at TIMESTAMP
+) WITH (
+  connector = 'kafka',
+  bootstrap_servers = 'localhost:9092',
+  format = 'json',
+  type = 'sink',
+  topic = 'reminders'
+);
+
+INSERT INTO reminders
+SELECT id, delay(INTERVAL '5 minutes', INTERVAL '1 hour') AS reminded_at
+FROM orders;
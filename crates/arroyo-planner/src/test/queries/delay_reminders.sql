@@ -0,0 +1,28 @@
+CREATE TABLE orders (
+  id BIGINT,
+  customer TEXT,
+  status TEXT
+) WITH (
+  connector = 'kafka',
+  bootstrap_servers = 'localhost:9092',
+  format = 'json',
+  type = 'source',
+  topic = 'orders'
+);
+
+CREATE TABLE reminders (
+  id BIGINT,
+  customer TEXT,
+  reminded_at TIMESTAMP
+) WITH (
+  connector = 'kafka',
+  bootstrap_servers = 'localhost:9092',
+  format = 'json',
+  type = 'sink',
+  topic = 'reminders'
+);
+
+INSERT INTO reminders
+SELECT id, customer, delay(INTERVAL '30 minutes') AS reminded_at
+FROM orders
+WHERE status = 'pending';
@@ -0,0 +1,17 @@
+CREATE TABLE impulse WITH (
+    connector = 'impulse',
+    event_rate = '10'
+);
+
+CREATE TABLE events (
+    id BIGINT,
+    subtask BIGINT
+) WITH (
+    connector = 'elasticsearch',
+    endpoint = 'http://localhost:9200',
+    index = 'events-{yyyy-MM-dd}',
+    id_field = 'id'
+);
+
+INSERT INTO events
+SELECT counter, subtask_index FROM impulse;
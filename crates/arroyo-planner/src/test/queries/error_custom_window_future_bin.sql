@@ -0,0 +1,13 @@
+--fail=tumble_by() binning function must place each timestamp in a bin starting at or before it
+CREATE TABLE Nexmark WITH (
+    connector = 'nexmark',
+    event_rate = '10'
+);
+
+SELECT
+    tumble_by(date_trunc('day', _timestamp) + interval '1 day', interval '1 day') as window,
+    count(*) as count
+FROM
+    nexmark
+GROUP BY
+    1
@@ -0,0 +1,17 @@
+--fail=tumble_by() binning function may only refer to _timestamp
+CREATE TABLE Nexmark WITH (
+    connector = 'nexmark',
+    event_rate = '10'
+);
+
+SELECT
+    bid.auction as auction,
+    tumble_by(date_trunc('day', bid.datetime), interval '1 day') as window,
+    count(*) as count
+FROM
+    nexmark
+where
+    bid is not null
+GROUP BY
+    1,
+    2
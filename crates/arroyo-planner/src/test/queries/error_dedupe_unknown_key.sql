@@ -0,0 +1,15 @@
+--fail=dedupe key 'id' is not a field of table orders
+CREATE TABLE orders (
+    order_id BIGINT,
+    amount DOUBLE
+) WITH (
+    connector = 'kafka',
+    bootstrap_servers = 'localhost:9092',
+    type = 'source',
+    topic = 'orders',
+    format = 'json',
+    'dedupe.keys' = 'id',
+    'dedupe.window' = '10m'
+);
+
+SELECT * FROM orders;
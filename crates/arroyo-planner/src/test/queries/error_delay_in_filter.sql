@@ -0,0 +1,7 @@
+--fail=delay() is only supported in the SELECT list of a query
+CREATE TABLE impulse WITH (
+    connector = 'impulse',
+    event_rate = '10'
+);
+
+SELECT counter FROM impulse WHERE delay(INTERVAL '1 minute') IS NOT NULL;
@@ -0,0 +1,18 @@
+--fail=id_field 'event_id' is not a field of the table
+CREATE TABLE impulse WITH (
+    connector = 'impulse',
+    event_rate = '10'
+);
+
+CREATE TABLE events (
+    id BIGINT,
+    subtask BIGINT
+) WITH (
+    connector = 'elasticsearch',
+    endpoint = 'http://localhost:9200',
+    index = 'events',
+    id_field = 'event_id'
+);
+
+INSERT INTO events
+SELECT counter, subtask_index FROM impulse;
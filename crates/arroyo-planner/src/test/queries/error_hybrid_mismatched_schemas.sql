@@ -0,0 +1,30 @@
+--fail=column 'value' has type Utf8 in 'history' but Int64 in 'live'
+CREATE TABLE history (
+    id BIGINT,
+    value TEXT
+) WITH (
+    connector = 'filesystem',
+    type = 'source',
+    path = '/data/events',
+    format = 'parquet'
+);
+
+CREATE TABLE live (
+    id BIGINT,
+    value BIGINT
+) WITH (
+    connector = 'kafka',
+    bootstrap_servers = 'localhost:9092',
+    topic = 'events',
+    type = 'source',
+    format = 'json'
+);
+
+CREATE TABLE events WITH (
+    connector = 'hybrid',
+    backfill = 'history',
+    stream = 'live',
+    switch_at = '2024-06-01T00:00:00Z'
+);
+
+SELECT * FROM events;
@@ -0,0 +1,14 @@
+--fail='glue' catalogs are not supported by the Iceberg connector
+CREATE TABLE events (
+    id BIGINT,
+    value TEXT
+) WITH (
+    connector = 'iceberg',
+    type = 'source',
+    'catalog.type' = 'glue',
+    'catalog.url' = 'http://localhost:8181',
+    namespace = 'db',
+    table = 'events'
+);
+
+SELECT * FROM events;
@@ -0,0 +1,8 @@
+--fail=idle_time must be set to an interval
+CREATE TABLE impulse WITH (
+    connector = 'impulse',
+    event_rate = '10',
+    idle_time = 'soon'
+);
+
+SELECT * FROM impulse;
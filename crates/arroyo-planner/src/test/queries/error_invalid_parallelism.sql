@@ -0,0 +1,9 @@
+--fail=invalid `SET parallelism`; expected a positive integer
+CREATE TABLE impulse WITH (
+    connector = 'impulse',
+    event_rate = '10'
+);
+
+SET parallelism = 0;
+
+SELECT counter FROM impulse;
@@ -0,0 +1,8 @@
+--fail=parallelism must be set to a positive integer, not 'many'
+CREATE TABLE impulse WITH (
+    connector = 'impulse',
+    event_rate = '10',
+    parallelism = 'many'
+);
+
+SELECT counter FROM impulse;
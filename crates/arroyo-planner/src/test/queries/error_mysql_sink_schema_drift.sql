@@ -0,0 +1,21 @@
+--fail=invalid value for 'schema_drift': 'add'
+CREATE TABLE impulse WITH (
+    connector = 'impulse',
+    event_rate = '10'
+);
+
+CREATE TABLE counts (
+    bucket BIGINT,
+    events BIGINT
+) WITH (
+    connector = 'mysql',
+    host = 'localhost',
+    database = 'analytics',
+    user = 'arroyo',
+    table_name = 'event_counts',
+    schema_drift = 'add'
+);
+
+INSERT INTO counts
+SELECT counter % 10, count(*) FROM impulse
+GROUP BY counter % 10;
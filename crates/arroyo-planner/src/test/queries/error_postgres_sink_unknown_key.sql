@@ -0,0 +1,21 @@
+--fail=key column 'id' is not a field of the Postgres sink
+CREATE TABLE impulse WITH (
+    connector = 'impulse',
+    event_rate = '10'
+);
+
+CREATE TABLE counts (
+    bucket BIGINT,
+    events BIGINT
+) WITH (
+    connector = 'postgres',
+    host = 'localhost',
+    database = 'analytics',
+    user = 'arroyo',
+    table_name = 'event_counts',
+    key_columns = 'id'
+);
+
+INSERT INTO counts
+SELECT counter % 10, count(*) FROM impulse
+GROUP BY counter % 10;
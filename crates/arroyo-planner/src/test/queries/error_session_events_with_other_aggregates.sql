@@ -0,0 +1,15 @@
+--fail=session_events() can't be combined with other aggregates
+CREATE TABLE page_views (
+  user_id TEXT,
+  path TEXT
+) WITH (
+  connector = 'kafka',
+  bootstrap_servers = 'localhost:9092',
+  format = 'json',
+  type = 'source',
+  topic = 'page_views'
+);
+
+SELECT user_id, session_events(INTERVAL '30 minutes') AS session, count(*)
+FROM page_views
+GROUP BY user_id;
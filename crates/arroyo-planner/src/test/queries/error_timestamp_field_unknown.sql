@@ -0,0 +1,15 @@
+--fail=timestamp field 'created_at' is not a field of table orders
+create table orders (
+    order_id BIGINT NOT NULL,
+    placed_at TIMESTAMP
+) with (
+    connector = 'kafka',
+    bootstrap_servers = 'localhost:9092',
+    type = 'source',
+    topic = 'orders',
+    format = 'json',
+    'timestamp.field' = 'created_at'
+);
+
+select count(*) from orders
+group by tumble(interval '1 minute');
@@ -0,0 +1,19 @@
+--fail=the xml format can only be used for sources
+create table impulse with (
+    connector = 'impulse',
+    event_rate = '10'
+);
+
+create table sink (
+    counter BIGINT
+) with (
+    connector = 'kafka',
+    bootstrap_servers = 'localhost:9092',
+    type = 'sink',
+    topic = 'outputs',
+    format = 'xml',
+    'xml.record_path' = '/counters/counter'
+);
+
+insert into sink
+select counter from impulse;
@@ -0,0 +1,17 @@
+--fail=zscore() depends on the order of its inputs, so can't be used in a GROUP BY
+CREATE TABLE Nexmark WITH (
+    connector = 'nexmark',
+    event_rate = '10'
+);
+
+SELECT
+    bid.auction as auction,
+    tumble(INTERVAL '1' minute) as window,
+    zscore(bid.price) as price_zscore
+FROM
+    nexmark
+where
+    bid is not null
+GROUP BY
+    1,
+    2
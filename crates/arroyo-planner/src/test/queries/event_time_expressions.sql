@@ -0,0 +1,28 @@
+CREATE TABLE impulse WITH (
+    connector = 'impulse',
+    event_rate = '10'
+);
+
+CREATE TABLE buckets (
+    bucket TIMESTAMP,
+    hour TIMESTAMP,
+    day_ago TIMESTAMP,
+    counter BIGINT UNSIGNED
+) WITH (
+    connector = 'blackhole'
+);
+
+INSERT INTO buckets
+SELECT
+    date_bin(INTERVAL '5 minutes', _timestamp) AS bucket,
+    date_trunc('hour', _timestamp) AS hour,
+    _timestamp - INTERVAL '1 day' AS day_ago,
+    counter
+FROM impulse;
+
+SELECT count(*), tumble(INTERVAL '1 hour') AS window
+FROM (
+    SELECT date_trunc('minute', _timestamp) + INTERVAL '30 seconds' AS _timestamp, counter
+    FROM impulse
+)
+GROUP BY window;
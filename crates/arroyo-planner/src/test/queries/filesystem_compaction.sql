@@ -0,0 +1,20 @@
+create table impulse with (
+    connector = 'impulse',
+    event_rate = '10'
+);
+
+create table sink (
+    counter BIGINT,
+    bucket BIGINT
+) with (
+    connector = 'filesystem',
+    type = 'sink',
+    path = 's3://my-bucket/counters',
+    format = 'parquet',
+    partition_fields = 'bucket',
+    rollover_seconds = 10,
+    compaction_target_size = 134217728
+);
+
+INSERT INTO sink
+SELECT counter, counter % 10 FROM impulse;
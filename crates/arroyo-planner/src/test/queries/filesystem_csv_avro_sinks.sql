@@ -0,0 +1,34 @@
+create table impulse with (
+    connector = 'impulse',
+    event_rate = '10'
+);
+
+create table csv_sink (
+    counter BIGINT,
+    subtask_index BIGINT
+) with (
+    connector = 'filesystem',
+    type = 'sink',
+    path = 's3://my-bucket/csv',
+    format = 'csv',
+    'csv.delimiter' = '|',
+    'csv.include_header' = 'true',
+    rollover_seconds = 60
+);
+
+create table avro_sink (
+    counter BIGINT,
+    subtask_index BIGINT
+) with (
+    connector = 'filesystem',
+    type = 'sink',
+    path = 's3://my-bucket/avro',
+    format = 'avro',
+    rollover_seconds = 60
+);
+
+INSERT INTO csv_sink
+SELECT counter, subtask_index FROM impulse;
+
+INSERT INTO avro_sink
+SELECT counter, subtask_index FROM impulse;
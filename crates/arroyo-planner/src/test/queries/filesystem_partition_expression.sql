@@ -0,0 +1,18 @@
+create table impulse with (
+    connector = 'impulse',
+    event_rate = '10'
+);
+
+create table sink (
+    counter BIGINT
+) with (
+    connector = 'filesystem',
+    type = 'sink',
+    path = 's3://my-bucket/counters',
+    format = 'parquet',
+    partition_by = 'date_trunc(''hour'', _timestamp)',
+    rollover_seconds = 60
+);
+
+INSERT INTO sink
+SELECT counter FROM impulse;
@@ -0,0 +1,15 @@
+create table events (
+    user_id TEXT,
+    action TEXT
+) with (
+    connector = 'filesystem',
+    type = 'source',
+    path = 's3://my-bucket/events',
+    format = 'json',
+    'source.regex-pattern' = '.*\.json',
+    'source.watch-interval-ms' = '30000'
+);
+
+select action, count(*)
+from events
+group by action, tumble(interval '1 minute');
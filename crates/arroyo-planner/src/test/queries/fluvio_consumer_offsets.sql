@@ -0,0 +1,25 @@
+CREATE TABLE logs (
+    level TEXT,
+    message TEXT
+) WITH (
+    connector = 'fluvio',
+    topic = 'logs',
+    type = 'source',
+    format = 'json',
+    'source.offset' = 'group',
+    'source.consumer' = 'arroyo-errors',
+    'source.smartmodule' = 'infinyon/regex-filter@0.1.0',
+    'source.smartmodule.params' = 'regex=error'
+);
+
+CREATE TABLE errors (
+    message TEXT
+) WITH (
+    connector = 'fluvio',
+    topic = 'errors',
+    type = 'sink',
+    format = 'json'
+);
+
+INSERT INTO errors
+SELECT message FROM logs WHERE level = 'error';
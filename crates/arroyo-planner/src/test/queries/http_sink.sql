@@ -0,0 +1,23 @@
+CREATE TABLE impulse WITH (
+    connector = 'impulse',
+    event_rate = '10'
+);
+
+CREATE TABLE events (
+    id BIGINT,
+    subtask BIGINT
+) WITH (
+    connector = 'http',
+    endpoint = 'https://example.com/subtasks/{subtask}/events',
+    headers = 'Content-Type: application/json,X-Subtask: {subtask}',
+    format = 'json',
+    'auth.type' = 'bearer',
+    'auth.token' = 'secret',
+    batch_size = '100',
+    batch_encoding = 'json_array',
+    flush_interval_millis = '500',
+    bad_data = 'drop'
+);
+
+INSERT INTO events
+SELECT counter, subtask_index FROM impulse;
@@ -0,0 +1,36 @@
+CREATE TABLE history (
+    id BIGINT,
+    "the ""value""" TEXT,
+    event_time TIMESTAMP
+) WITH (
+    connector = 'filesystem',
+    type = 'source',
+    path = '/data/events',
+    format = 'parquet',
+    event_time_field = 'event_time'
+);
+
+CREATE TABLE live (
+    id BIGINT,
+    "the ""value""" TEXT,
+    event_time TIMESTAMP
+) WITH (
+    connector = 'kafka',
+    bootstrap_servers = 'localhost:9092',
+    topic = 'events',
+    type = 'source',
+    format = 'json',
+    'source.offset' = 'earliest',
+    event_time_field = 'event_time'
+);
+
+CREATE TABLE events WITH (
+    connector = 'hybrid',
+    backfill = 'history',
+    stream = 'live',
+    switch_at = '2024-06-01T00:00:00Z'
+);
+
+SELECT "the ""value""", count(*)
+FROM events
+GROUP BY "the ""value""", tumble(INTERVAL '1 hour');
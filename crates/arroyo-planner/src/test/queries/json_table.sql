@@ -0,0 +1,13 @@
+CREATE TABLE events (
+    value TEXT
+) WITH (
+    connector = 'sse',
+    format = 'raw_string',
+    endpoint = 'http://localhost:9091/events'
+);
+
+SELECT json_array_length(value) as len,
+    json_keys(value) as keys,
+    json_merge(value, '{"source": "sse"}') as merged,
+    json_table(value, 'id BIGINT PATH $.user.id', 'name TEXT PATH $.user.name') as user
+FROM events;
@@ -0,0 +1,19 @@
+CREATE TABLE impulse WITH (
+    connector = 'impulse',
+    event_rate = '10'
+);
+
+CREATE TABLE sink (
+    counter BIGINT
+) WITH (
+    connector = 'kafka',
+    bootstrap_servers = 'localhost:9092',
+    type = 'sink',
+    topic = 'counts',
+    format = 'json',
+    'sink.semantics' = 'exactly_once'
+);
+
+INSERT INTO sink
+SELECT counter
+FROM impulse;
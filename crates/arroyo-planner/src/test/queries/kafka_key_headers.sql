@@ -0,0 +1,16 @@
+CREATE TABLE orders (
+    amount DOUBLE,
+    order_key TEXT GENERATED ALWAYS AS (metadata('key')) STORED,
+    headers TEXT GENERATED ALWAYS AS (metadata('headers')) STORED,
+    msg_offset BIGINT GENERATED ALWAYS AS (metadata('offset_id')) STORED
+) WITH (
+    connector = 'kafka',
+    bootstrap_servers = 'localhost:9092',
+    type = 'source',
+    topic = 'orders',
+    format = 'json'
+);
+
+SELECT order_key, msg_offset, amount
+FROM orders
+WHERE extract_json_string(headers, '$.region') = 'us-east-1';
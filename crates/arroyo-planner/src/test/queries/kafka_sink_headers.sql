@@ -0,0 +1,23 @@
+CREATE TABLE impulse WITH (
+    connector = 'impulse',
+    event_rate = '10'
+);
+
+CREATE TABLE sink (
+    customer_id TEXT,
+    region TEXT,
+    attempt BIGINT,
+    amount BIGINT
+) WITH (
+    connector = 'kafka',
+    bootstrap_servers = 'localhost:9092',
+    type = 'sink',
+    topic = 'orders',
+    format = 'json',
+    'sink.key_field' = 'customer_id',
+    'sink.header_fields' = 'region, attempt'
+);
+
+INSERT INTO sink
+SELECT cast(counter % 100 as TEXT), 'us-east-1', 1, counter
+FROM impulse;
@@ -0,0 +1,16 @@
+CREATE TABLE orders (
+    amount DOUBLE,
+    topic TEXT GENERATED ALWAYS AS (metadata('topic')) STORED
+) WITH (
+    connector = 'kafka',
+    bootstrap_servers = 'localhost:9092',
+    type = 'source',
+    topic = '^orders-.*',
+    format = 'json',
+    'source.offset' = 'earliest',
+    'source.discovery_interval_ms' = '30000'
+);
+
+SELECT topic, sum(amount)
+FROM orders
+GROUP BY topic, tumble(interval '1 minute');
@@ -0,0 +1,41 @@
+create table orders (
+    id TEXT,
+    amount BIGINT
+) with (
+    connector = 'kafka',
+    topic = 'orders',
+    format = 'json',
+    bootstrap_servers = '0.0.0.0:9092',
+    type = 'source',
+    traceparent = 'true'
+);
+
+create table large_orders (
+    id TEXT,
+    amount BIGINT
+) with (
+    connector = 'kafka',
+    topic = 'large_orders',
+    format = 'json',
+    bootstrap_servers = '0.0.0.0:9092',
+    type = 'sink',
+    traceparent = 'true'
+);
+
+create table all_orders (
+    id TEXT,
+    amount BIGINT
+) with (
+    connector = 'kafka',
+    topic = 'all_orders',
+    format = 'json',
+    bootstrap_servers = '0.0.0.0:9092',
+    type = 'sink'
+);
+
+INSERT INTO large_orders
+SELECT id, amount, _traceparent FROM orders WHERE amount > 1000;
+
+-- the trace context is hidden unless it's referred to
+INSERT INTO all_orders
+SELECT * FROM orders;
@@ -0,0 +1,27 @@
+create table orders (
+    id TEXT,
+    amount BIGINT,
+    trace TEXT GENERATED ALWAYS AS (metadata('traceparent')) STORED
+) with (
+    connector = 'kafka',
+    topic = 'orders',
+    format = 'json',
+    bootstrap_servers = '0.0.0.0:9092',
+    type = 'source'
+);
+
+create table large_orders (
+    id TEXT,
+    amount BIGINT,
+    trace TEXT
+) with (
+    connector = 'kafka',
+    topic = 'large_orders',
+    format = 'json',
+    bootstrap_servers = '0.0.0.0:9092',
+    type = 'sink',
+    'sink.traceparent_field' = 'trace'
+);
+
+INSERT INTO large_orders
+SELECT id, amount, trace FROM orders WHERE amount > 1000;
@@ -0,0 +1,13 @@
+--fail=_traceparent
+create table orders (
+    id TEXT,
+    amount BIGINT
+) with (
+    connector = 'kafka',
+    topic = 'orders',
+    format = 'json',
+    bootstrap_servers = '0.0.0.0:9092',
+    type = 'source'
+);
+
+SELECT id, _traceparent FROM orders;
@@ -0,0 +1,9 @@
+CREATE TABLE impulse WITH (
+    connector = 'impulse',
+    event_rate = '10'
+);
+
+SELECT counter, p.part
+FROM impulse
+CROSS JOIN LATERAL string_to_array(CAST(counter AS TEXT), '0') AS p(part)
+WHERE p.part <> '';
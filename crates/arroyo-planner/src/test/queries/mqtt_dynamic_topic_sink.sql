@@ -0,0 +1,20 @@
+CREATE TABLE impulse WITH (
+    connector = 'impulse',
+    event_rate = '10'
+);
+
+CREATE TABLE alerts (
+    device_id BIGINT,
+    level TEXT
+) WITH (
+    connector = 'mqtt',
+    url = 'tcp://localhost:1883',
+    type = 'sink',
+    topic = 'devices/{device_id}/alerts',
+    qos = 'AtLeastOnce',
+    'sink.retain' = 'true',
+    format = 'json'
+);
+
+INSERT INTO alerts
+SELECT counter % 100, CASE WHEN counter % 10 = 0 THEN 'high' ELSE 'low' END FROM impulse;
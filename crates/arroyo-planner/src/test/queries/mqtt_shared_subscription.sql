@@ -0,0 +1,16 @@
+CREATE TABLE sensors (
+    device_id TEXT,
+    temperature DOUBLE
+) WITH (
+    connector = 'mqtt',
+    url = 'tcp://localhost:1883',
+    type = 'source',
+    topic = 'sensors/+/temperature',
+    'source.shared_group' = 'arroyo',
+    qos = 'AtLeastOnce',
+    format = 'json'
+);
+
+SELECT device_id, avg(temperature)
+FROM sensors
+GROUP BY device_id, tumble(interval '1 minute');
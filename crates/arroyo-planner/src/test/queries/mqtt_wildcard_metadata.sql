@@ -0,0 +1,17 @@
+create table sensors (
+    value DOUBLE,
+    topic TEXT GENERATED ALWAYS AS (metadata('topic')) STORED,
+    qos INT GENERATED ALWAYS AS (metadata('qos')) STORED,
+    retained BOOLEAN GENERATED ALWAYS AS (metadata('retain')) STORED
+) with (
+    connector = 'mqtt',
+    url = 'tcp://localhost:1883',
+    topic = 'sensors/+/temp',
+    type = 'source',
+    format = 'json'
+);
+
+select topic, avg(value)
+from sensors
+where not retained and qos > 0
+group by topic, tumble(interval '1 minute');
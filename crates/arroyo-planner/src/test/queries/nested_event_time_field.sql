@@ -0,0 +1,21 @@
+CREATE TABLE events (
+  event_time TIMESTAMP,
+  user_id BIGINT,
+  event_type TEXT,
+  payload STRUCT GENERATED ALWAYS AS (named_struct(
+    'created_at', event_time,
+    'user', named_struct('id', user_id, 'kind', event_type)
+  )) STORED
+) WITH (
+  connector = 'kafka',
+  bootstrap_servers = 'localhost:9092',
+  type = 'source',
+  topic = 'events',
+  format = 'json',
+  event_time_field = 'payload.created_at',
+  watermark_field = 'payload.created_at'
+);
+
+SELECT payload.user.id, payload['user']['kind'], count(*)
+FROM events
+GROUP BY 1, 2, tumble(interval '1 minute');
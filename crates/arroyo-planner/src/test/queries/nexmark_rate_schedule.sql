@@ -0,0 +1,14 @@
+CREATE TABLE bench WITH (
+    connector = 'nexmark',
+    event_rate = '1000',
+    rate_schedule = '60:100000,300:100000,360:1000',
+    num_events = '50000000',
+    seed = '42',
+    hot_auction_ratio = '10',
+    bid_proportion = '92'
+);
+
+SELECT bid.auction, count(*) AS bids
+FROM bench
+WHERE bid IS NOT NULL
+GROUP BY bid.auction, tumble(interval '10 seconds');
@@ -0,0 +1,23 @@
+create table spans with (
+    connector = 'otlp',
+    signal = 'traces',
+    port = '4317'
+);
+
+create table slow_requests (
+    service_name TEXT,
+    name TEXT,
+    trace_id TEXT,
+    start_time TIMESTAMP
+) with (
+    connector = 'kafka',
+    topic = 'slow_requests',
+    format = 'json',
+    bootstrap_servers = '0.0.0.0:9092',
+    type = 'sink'
+);
+
+INSERT INTO slow_requests
+SELECT service_name, name, trace_id, start_time
+FROM spans
+WHERE kind = 'server' AND end_time - start_time > interval '1 second';
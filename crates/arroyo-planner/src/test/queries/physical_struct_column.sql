@@ -0,0 +1,13 @@
+--fail=STRUCT and MAP columns must be generated
+CREATE TABLE events (
+  id BIGINT,
+  payload STRUCT
+) WITH (
+  connector = 'kafka',
+  bootstrap_servers = 'localhost:9092',
+  type = 'source',
+  topic = 'events',
+  format = 'json'
+);
+
+SELECT id FROM events;
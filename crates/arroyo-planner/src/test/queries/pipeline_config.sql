@@ -0,0 +1,11 @@
+SET parallelism = 8;
+SET checkpoint.interval = '30 seconds';
+
+CREATE TABLE impulse WITH (
+    connector = 'impulse',
+    event_rate = '10'
+);
+
+SELECT count(*), tumble(INTERVAL '1 minute') AS window
+FROM impulse
+GROUP BY window;
@@ -0,0 +1,17 @@
+CREATE TABLE events (
+    value JSON
+) WITH (
+    connector = 'polling_http',
+    endpoint = 'https://example.com/api/events',
+    format = 'json',
+    'json.unstructured' = 'true',
+    pagination = 'body_cursor',
+    cursor_path = '$.meta.next_cursor',
+    cursor_param = 'page_token',
+    max_pages = '20',
+    incremental_param = 'updated_since',
+    incremental_path = '$.data[-1:].updated_at',
+    incremental_initial_value = '2024-01-01T00:00:00Z'
+);
+
+SELECT * FROM events;
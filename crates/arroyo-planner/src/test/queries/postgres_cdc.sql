@@ -0,0 +1,17 @@
+CREATE TABLE orders (
+    id BIGINT PRIMARY KEY,
+    customer_id BIGINT,
+    amount DOUBLE,
+    created_at TIMESTAMP
+) WITH (
+    connector = 'postgres_cdc',
+    host = 'localhost',
+    database = 'shop',
+    user = 'arroyo',
+    table_name = 'orders',
+    slot_name = 'arroyo_orders',
+    publication = 'arroyo_pub'
+);
+
+SELECT customer_id, sum(amount) FROM orders
+GROUP BY customer_id;
@@ -0,0 +1,54 @@
+CREATE TABLE impulse WITH (
+    connector = 'impulse',
+    event_rate = '10'
+);
+
+CREATE TABLE leaderboard (
+    player TEXT NOT NULL,
+    score BIGINT NOT NULL
+) WITH (
+    connector = 'redis',
+    type = 'sink',
+    format = 'json',
+    address = 'redis://localhost:6379',
+    target = 'sorted_set',
+    'target.key_prefix' = 'leaderboard',
+    'target.score_column' = 'score',
+    'target.max_length' = '100'
+);
+
+CREATE TABLE events (
+    player TEXT NOT NULL,
+    score BIGINT NOT NULL
+) WITH (
+    connector = 'redis',
+    type = 'sink',
+    format = 'json',
+    address = 'redis://localhost:6379',
+    target = 'stream',
+    'target.key_prefix' = 'events:',
+    'target.key_column' = 'player',
+    'target.max_length' = '1000'
+);
+
+CREATE TABLE updates (
+    player TEXT NOT NULL,
+    score BIGINT NOT NULL
+) WITH (
+    connector = 'redis',
+    type = 'sink',
+    format = 'json',
+    address = 'redis://localhost:6379',
+    target = 'pubsub',
+    'target.channel_prefix' = 'updates:',
+    'target.channel_column' = 'player'
+);
+
+INSERT INTO leaderboard
+SELECT concat('player-', CAST(counter % 10 AS TEXT)), counter FROM impulse;
+
+INSERT INTO events
+SELECT concat('player-', CAST(counter % 10 AS TEXT)), counter FROM impulse;
+
+INSERT INTO updates
+SELECT concat('player-', CAST(counter % 10 AS TEXT)), counter FROM impulse;
@@ -0,0 +1,19 @@
+CREATE TABLE Nexmark WITH (
+    connector = 'nexmark',
+    event_rate = '10'
+);
+
+SELECT
+    bid.auction as auction,
+    tumble(INTERVAL '1' minute) as window,
+    reservoir_sample(bid.price, 10, 42) as sampled_prices,
+    reservoir_sample(bid.bidder, 5) as sampled_bidders
+FROM
+    nexmark
+where
+    bid is not null
+    and sample(0.01)
+    and sample(0.5, 42, bid.bidder)
+GROUP BY
+    1,
+    2
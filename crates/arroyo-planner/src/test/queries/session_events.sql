@@ -0,0 +1,33 @@
+CREATE TABLE page_views (
+  user_id TEXT,
+  path TEXT
+) WITH (
+  connector = 'kafka',
+  bootstrap_servers = 'localhost:9092',
+  format = 'json',
+  type = 'source',
+  topic = 'page_views'
+);
+
+CREATE TABLE sessions (
+  user_id TEXT,
+  event TEXT,
+  session_start TIMESTAMP,
+  session_end TIMESTAMP,
+  duration_ms BIGINT,
+  event_count BIGINT
+) WITH (
+  connector = 'kafka',
+  bootstrap_servers = 'localhost:9092',
+  format = 'json',
+  type = 'sink',
+  topic = 'sessions'
+);
+
+INSERT INTO sessions
+SELECT user_id, session.event, session.start, session.end, session.duration_ms, session.event_count
+FROM (
+  SELECT user_id, session_events(INTERVAL '30 minutes') AS session
+  FROM page_views
+  GROUP BY user_id
+);
@@ -0,0 +1,23 @@
+CREATE TABLE orders (
+    id BIGINT,
+    customer TEXT,
+    amount DOUBLE
+) WITH (
+    connector = 'shared',
+    type = 'source',
+    name = 'orders',
+    offset = 'earliest'
+);
+
+CREATE TABLE large_orders (
+    id BIGINT,
+    amount DOUBLE
+) WITH (
+    connector = 'shared',
+    type = 'sink',
+    name = 'large_orders',
+    retention_segments = '100'
+);
+
+INSERT INTO large_orders
+SELECT id, amount FROM orders WHERE amount > 1000;
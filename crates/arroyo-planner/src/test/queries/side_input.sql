@@ -0,0 +1,17 @@
+CREATE TABLE products (
+    id BIGINT,
+    category TEXT,
+    price DOUBLE,
+    version BIGINT,
+    loaded_at TIMESTAMP
+) WITH (
+    connector = 'side_input',
+    query = 'SELECT id, category, price FROM products WHERE active',
+    connection_string = 'postgres://arroyo@localhost:5432/shop',
+    refresh_interval_ms = '30000',
+    version_field = 'version',
+    refreshed_at_field = 'loaded_at'
+);
+
+SELECT category, count(*), max(version) FROM products
+GROUP BY category;
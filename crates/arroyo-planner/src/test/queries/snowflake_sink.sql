@@ -0,0 +1,20 @@
+CREATE TABLE impulse WITH (
+    connector = 'impulse',
+    event_rate = '10'
+);
+
+CREATE TABLE events (
+    id BIGINT,
+    subtask BIGINT
+) WITH (
+    connector = 'snowflake',
+    account = 'myorg-myaccount',
+    user = 'ARROYO',
+    private_key = '{{ SNOWFLAKE_PRIVATE_KEY }}',
+    database = 'ANALYTICS',
+    schema = 'PUBLIC',
+    table = 'EVENTS'
+);
+
+INSERT INTO events
+SELECT counter, subtask_index FROM impulse;
@@ -0,0 +1,34 @@
+CREATE TABLE impulse WITH (
+    connector = 'impulse',
+    event_rate = '10'
+);
+
+CREATE TABLE alerts (
+    id BIGINT,
+    severity TEXT,
+    subtask BIGINT
+) WITH (
+    connector = 'sns',
+    topic_arn = 'arn:aws:sns:us-east-1:123456789012:alerts',
+    aws_region = 'us-east-1',
+    attribute_fields = 'severity, subtask',
+    format = 'json'
+);
+
+CREATE TABLE events (
+    id BIGINT,
+    kind TEXT
+) WITH (
+    connector = 'sns',
+    event_bus_name = 'default',
+    source = 'com.example.impulse',
+    detail_type = 'Impulse',
+    detail_type_field = 'kind',
+    format = 'json'
+);
+
+INSERT INTO alerts
+SELECT counter, CASE WHEN counter % 10 = 0 THEN 'high' ELSE 'low' END, subtask_index FROM impulse;
+
+INSERT INTO events
+SELECT counter, CASE WHEN counter % 2 = 0 THEN 'Even' ELSE 'Odd' END FROM impulse;
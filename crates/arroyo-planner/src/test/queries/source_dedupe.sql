@@ -0,0 +1,17 @@
+CREATE TABLE orders (
+    order_id BIGINT,
+    customer_id BIGINT,
+    amount DOUBLE
+) WITH (
+    connector = 'kafka',
+    bootstrap_servers = 'localhost:9092',
+    type = 'source',
+    topic = 'orders',
+    format = 'json',
+    'dedupe.keys' = 'order_id',
+    'dedupe.window' = '10m'
+);
+
+SELECT customer_id, sum(amount), tumble(INTERVAL '1 minute') AS window
+FROM orders
+GROUP BY customer_id, window;
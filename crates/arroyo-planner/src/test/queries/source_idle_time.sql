@@ -0,0 +1,20 @@
+CREATE TABLE impulse WITH (
+    connector = 'impulse',
+    event_rate = '10'
+);
+
+CREATE TABLE slow_impulse WITH (
+    connector = 'impulse',
+    event_rate = '1',
+    idle_time = '10 minutes'
+);
+
+SET arroyo.source.idle_time = '30 seconds';
+
+SELECT count(*), tumble(INTERVAL '1 minute') AS window
+FROM (
+    SELECT counter FROM impulse
+    UNION ALL
+    SELECT counter FROM slow_impulse
+)
+GROUP BY window;
@@ -0,0 +1,14 @@
+CREATE TABLE orders (
+    order_id BIGINT,
+    customer_id BIGINT,
+    amount DOUBLE
+) WITH (
+    connector = 'sqs',
+    queue_url = 'https://sqs.us-east-1.amazonaws.com/123456789012/orders',
+    aws_region = 'us-east-1',
+    format = 'json'
+);
+
+SELECT customer_id, sum(amount)
+FROM orders
+GROUP BY customer_id, tumble(interval '1 minute');
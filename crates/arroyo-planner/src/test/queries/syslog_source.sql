@@ -0,0 +1,23 @@
+create table firewall_logs with (
+    connector = 'socket',
+    protocol = 'udp',
+    port = '5514',
+    syslog = 'true'
+);
+
+create table denied (
+    hostname TEXT,
+    message TEXT,
+    source_address TEXT
+) with (
+    connector = 'kafka',
+    topic = 'denied',
+    format = 'json',
+    bootstrap_servers = '0.0.0.0:9092',
+    type = 'sink'
+);
+
+INSERT INTO denied
+SELECT hostname, message, source_address
+FROM firewall_logs
+WHERE severity <= 4 AND message LIKE '%DENY%';
@@ -0,0 +1,34 @@
+create table orders (
+    order_id BIGINT NOT NULL,
+    customer TEXT,
+    amount DOUBLE,
+    placed_at TEXT
+) with (
+    connector = 'kafka',
+    bootstrap_servers = 'localhost:9092',
+    type = 'source',
+    topic = 'orders',
+    format = 'json',
+    'timestamp.field' = 'placed_at',
+    'timestamp.format' = '%Y-%m-%d %H:%M:%S%z'
+);
+
+create table payments (
+    payment_id BIGINT NOT NULL,
+    paid_at BIGINT
+) with (
+    connector = 'kafka',
+    bootstrap_servers = 'localhost:9092',
+    type = 'source',
+    topic = 'payments',
+    format = 'csv',
+    'timestamp.field' = 'paid_at',
+    'timestamp.unit' = 'seconds'
+);
+
+select customer, sum(amount)
+from orders
+group by customer, tumble(interval '1 minute');
+
+select count(*) from payments
+group by tumble(interval '1 minute');
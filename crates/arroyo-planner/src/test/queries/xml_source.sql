@@ -0,0 +1,37 @@
+create table posts (
+    guid TEXT NOT NULL,
+    title TEXT,
+    link TEXT,
+    category TEXT,
+    published TEXT
+) with (
+    connector = 'polling_http',
+    endpoint = 'https://example.com/feed.rss',
+    poll_interval_ms = '60000',
+    format = 'xml',
+    'xml.record_path' = '/rss/channel/item',
+    'xml.fields.category' = 'category/@domain',
+    'xml.fields.published' = 'pubDate'
+);
+
+create table entries (
+    id TEXT,
+    title TEXT,
+    author TEXT
+) with (
+    connector = 'filesystem',
+    type = 'source',
+    path = 's3://my-bucket/feeds',
+    format = 'xml',
+    'xml.record_path' = '//atom:entry',
+    'xml.fields.author' = 'author/name',
+    'source.regex-pattern' = '.*\.atom'
+);
+
+select category, count(*)
+from posts
+group by category, tumble(interval '1 hour');
+
+select author, count(*)
+from entries
+group by author, tumble(interval '1 hour');
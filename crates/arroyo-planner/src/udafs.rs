@@ -1,7 +1,21 @@
-use datafusion::arrow::array::ArrayRef;
+use arrow::array::{new_empty_array, Array, ArrayRef, Float64Array, ListArray, UInt64Array};
+use arrow::buffer::OffsetBuffer;
+use arrow::compute::cast;
+use arrow::datatypes::{DataType, Field, Float64Type, Int64Type};
+use arrow_array::cast::AsArray;
+use datafusion::common::{exec_err, not_impl_err, plan_err};
+use datafusion::execution::FunctionRegistry;
+use datafusion::logical_expr::function::{AccumulatorArgs, StateFieldsArgs};
+use datafusion::logical_expr::{
+    create_udaf, AggregateUDF, AggregateUDFImpl, Signature, TypeSignature, Volatility,
+};
 use datafusion::scalar::ScalarValue;
 use datafusion::{error::Result, physical_plan::Accumulator};
+use std::any::Any;
+use std::collections::VecDeque;
 use std::fmt::Debug;
+use std::sync::Arc;
+use xxhash_rust::xxh3::xxh3_64_with_seed;
 
 // Fake UDAF used just for plan-time
 #[derive(Debug)]
@@ -27,3 +41,734 @@ impl Accumulator for EmptyUdaf {
         unreachable!()
     }
 }
+
+/// Number of recent values that `mad_score` computes its median and deviation over
+pub const MAD_WINDOW_SIZE: usize = 1024;
+
+/// Constant that makes the median absolute deviation a consistent estimator of the standard
+/// deviation for normally-distributed data
+const MAD_SCALE: f64 = 0.6745;
+
+/// Anomaly scores that depend on the order of their inputs, so can't be computed as partial
+/// aggregates and combined. They are only supported as window functions, which run in a
+/// single phase over ordered rows.
+pub const SINGLE_PHASE_UDAFS: &[&str] = &["zscore", "ewma_zscore"];
+
+/// Registers the built-in anomaly scoring aggregates. Each returns the score of the most recent
+/// value against the statistics of the values aggregated so far, or NULL if there isn't enough
+/// data to compute one.
+pub fn register_anomaly_udafs(registry: &mut dyn FunctionRegistry) {
+    registry
+        .register_udaf(Arc::new(create_udaf(
+            "zscore",
+            vec![DataType::Float64],
+            Arc::new(DataType::Float64),
+            Volatility::Immutable,
+            Arc::new(|_| Ok(Box::<ZScoreAccumulator>::default())),
+            Arc::new(vec![
+                DataType::UInt64,
+                DataType::Float64,
+                DataType::Float64,
+                DataType::Float64,
+            ]),
+        )))
+        .unwrap();
+
+    registry
+        .register_udaf(Arc::new(create_udaf(
+            "ewma_zscore",
+            vec![DataType::Float64, DataType::Float64],
+            Arc::new(DataType::Float64),
+            Volatility::Immutable,
+            Arc::new(|_| Ok(Box::<EwmaZScoreAccumulator>::default())),
+            Arc::new(vec![
+                DataType::UInt64,
+                DataType::Float64,
+                DataType::Float64,
+                DataType::Float64,
+            ]),
+        )))
+        .unwrap();
+
+    registry
+        .register_udaf(Arc::new(create_udaf(
+            "mad_score",
+            vec![DataType::Float64],
+            Arc::new(DataType::Float64),
+            Volatility::Immutable,
+            Arc::new(|_| Ok(Box::<MadScoreAccumulator>::default())),
+            Arc::new(vec![DataType::List(Arc::new(Field::new(
+                "item",
+                DataType::Float64,
+                true,
+            )))]),
+        )))
+        .unwrap();
+}
+
+/// The largest sample that `reservoir_sample` will keep, which bounds the size of its state
+pub const MAX_RESERVOIR_SIZE: u64 = 100_000;
+
+/// Registers the sampling aggregates for building approximate pipelines
+pub fn register_sampling_udafs(registry: &mut dyn FunctionRegistry) {
+    registry
+        .register_udaf(Arc::new(AggregateUDF::new_from_impl(
+            ReservoirSampleFunction::default(),
+        )))
+        .unwrap();
+}
+
+/// `zscore(value) OVER (...)`: the number of standard deviations the latest value is from the mean of
+/// all values, with the mean and variance tracked using Welford's algorithm
+#[derive(Debug, Default)]
+pub struct ZScoreAccumulator {
+    count: u64,
+    mean: f64,
+    m2: f64,
+    last: Option<f64>,
+}
+
+impl ZScoreAccumulator {
+    fn update(&mut self, value: f64) {
+        self.count += 1;
+        let delta = value - self.mean;
+        self.mean += delta / self.count as f64;
+        self.m2 += delta * (value - self.mean);
+        self.last = Some(value);
+    }
+}
+
+impl Accumulator for ZScoreAccumulator {
+    fn update_batch(&mut self, values: &[ArrayRef]) -> Result<()> {
+        let values: &Float64Array = values[0].as_primitive();
+        for value in values.iter().flatten() {
+            self.update(value);
+        }
+        Ok(())
+    }
+
+    fn evaluate(&mut self) -> Result<ScalarValue> {
+        let Some(last) = self.last else {
+            return Ok(ScalarValue::Float64(None));
+        };
+        if self.count < 2 {
+            return Ok(ScalarValue::Float64(None));
+        }
+
+        let stddev = (self.m2 / (self.count - 1) as f64).sqrt();
+        Ok(ScalarValue::Float64(
+            (stddev > 0.0).then(|| (last - self.mean) / stddev),
+        ))
+    }
+
+    fn size(&self) -> usize {
+        std::mem::size_of_val(self)
+    }
+
+    fn state(&mut self) -> Result<Vec<ScalarValue>> {
+        Ok(vec![
+            ScalarValue::UInt64(Some(self.count)),
+            ScalarValue::Float64(Some(self.mean)),
+            ScalarValue::Float64(Some(self.m2)),
+            ScalarValue::Float64(self.last),
+        ])
+    }
+
+    fn merge_batch(&mut self, _: &[ArrayRef]) -> Result<()> {
+        // which value is the latest can't be recovered from partial states
+        not_impl_err!("zscore can't combine partial aggregates; use it as a window function")
+    }
+}
+
+/// `ewma_zscore(value, alpha) OVER (...)`: the score of the latest value against an exponentially-weighted
+/// moving mean and variance of the values before it, where `alpha` in (0, 1] is the weight
+/// given to each new value
+#[derive(Debug, Default)]
+pub struct EwmaZScoreAccumulator {
+    count: u64,
+    mean: f64,
+    variance: f64,
+    score: Option<f64>,
+}
+
+impl EwmaZScoreAccumulator {
+    fn update(&mut self, value: f64, alpha: f64) -> Result<()> {
+        if !(alpha > 0.0 && alpha <= 1.0) {
+            return exec_err!("ewma_zscore alpha must be in (0, 1], but was {alpha}");
+        }
+
+        if self.count == 0 {
+            self.mean = value;
+            self.variance = 0.0;
+            self.score = None;
+        } else {
+            let delta = value - self.mean;
+            self.score = (self.variance > 0.0).then(|| delta / self.variance.sqrt());
+            let increment = alpha * delta;
+            self.mean += increment;
+            self.variance = (1.0 - alpha) * (self.variance + delta * increment);
+        }
+        self.count += 1;
+        Ok(())
+    }
+}
+
+impl Accumulator for EwmaZScoreAccumulator {
+    fn update_batch(&mut self, values: &[ArrayRef]) -> Result<()> {
+        let alphas: &Float64Array = values[1].as_primitive();
+        for (value, alpha) in values[0].as_primitive::<Float64Type>().iter().zip(alphas) {
+            let (Some(value), Some(alpha)) = (value, alpha) else {
+                continue;
+            };
+            self.update(value, alpha)?;
+        }
+        Ok(())
+    }
+
+    fn evaluate(&mut self) -> Result<ScalarValue> {
+        Ok(ScalarValue::Float64(self.score))
+    }
+
+    fn size(&self) -> usize {
+        std::mem::size_of_val(self)
+    }
+
+    fn state(&mut self) -> Result<Vec<ScalarValue>> {
+        Ok(vec![
+            ScalarValue::UInt64(Some(self.count)),
+            ScalarValue::Float64(Some(self.mean)),
+            ScalarValue::Float64(Some(self.variance)),
+            ScalarValue::Float64(self.score),
+        ])
+    }
+
+    fn merge_batch(&mut self, _: &[ArrayRef]) -> Result<()> {
+        // a moving average depends on the order of the values, which is lost across partial
+        // states
+        not_impl_err!("ewma_zscore can't combine partial aggregates; use it as a window function")
+    }
+}
+
+/// `mad_score(value)`: the modified z-score of the latest value, based on the median and the
+/// median absolute deviation of the most recent [`MAD_WINDOW_SIZE`] values. Unlike `zscore`,
+/// this is robust to the outliers it's looking for skewing the statistics.
+#[derive(Debug, Default)]
+pub struct MadScoreAccumulator {
+    values: VecDeque<f64>,
+}
+
+impl MadScoreAccumulator {
+    fn push(&mut self, value: f64) {
+        if self.values.len() == MAD_WINDOW_SIZE {
+            self.values.pop_front();
+        }
+        self.values.push_back(value);
+    }
+}
+
+fn median(values: &mut [f64]) -> f64 {
+    values.sort_by(|a, b| a.total_cmp(b));
+    let mid = values.len() / 2;
+    if values.len() % 2 == 0 {
+        (values[mid - 1] + values[mid]) / 2.0
+    } else {
+        values[mid]
+    }
+}
+
+impl Accumulator for MadScoreAccumulator {
+    fn update_batch(&mut self, values: &[ArrayRef]) -> Result<()> {
+        let values: &Float64Array = values[0].as_primitive();
+        for value in values.iter().flatten() {
+            self.push(value);
+        }
+        Ok(())
+    }
+
+    fn evaluate(&mut self) -> Result<ScalarValue> {
+        let Some(last) = self.values.back().copied() else {
+            return Ok(ScalarValue::Float64(None));
+        };
+
+        let mut values: Vec<f64> = self.values.iter().copied().collect();
+        let median = median(&mut values);
+        let mut deviations: Vec<f64> = values.iter().map(|v| (v - median).abs()).collect();
+        let mad = self::median(&mut deviations);
+
+        Ok(ScalarValue::Float64(
+            (mad > 0.0).then(|| MAD_SCALE * (last - median) / mad),
+        ))
+    }
+
+    fn size(&self) -> usize {
+        std::mem::size_of_val(self) + self.values.capacity() * std::mem::size_of::<f64>()
+    }
+
+    fn state(&mut self) -> Result<Vec<ScalarValue>> {
+        let list = ListArray::from_iter_primitive::<Float64Type, _, _>(vec![Some(
+            self.values.iter().map(|v| Some(*v)),
+        )]);
+        Ok(vec![ScalarValue::List(Arc::new(list))])
+    }
+
+    fn merge_batch(&mut self, states: &[ArrayRef]) -> Result<()> {
+        let Some(lists) = states[0].as_list_opt::<i32>() else {
+            return exec_err!("invalid state for mad_score: {:?}", states[0].data_type());
+        };
+        for values in lists.iter().flatten() {
+            for value in values.as_primitive::<Float64Type>().iter().flatten() {
+                self.push(value);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// `reservoir_sample(value, k [, seed])`: a uniform random sample of up to `k` of the non-null
+/// values aggregated, as a list
+#[derive(Debug)]
+pub struct ReservoirSampleFunction {
+    signature: Signature,
+}
+
+impl Default for ReservoirSampleFunction {
+    fn default() -> Self {
+        Self {
+            signature: Signature::one_of(
+                vec![TypeSignature::Any(2), TypeSignature::Any(3)],
+                Volatility::Volatile,
+            ),
+        }
+    }
+}
+
+fn item_type(list: &DataType) -> Result<DataType> {
+    match list {
+        DataType::List(field) => Ok(field.data_type().clone()),
+        t => plan_err!("reservoir_sample must return a list, not {t}"),
+    }
+}
+
+impl AggregateUDFImpl for ReservoirSampleFunction {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        "reservoir_sample"
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn return_type(&self, arg_types: &[DataType]) -> Result<DataType> {
+        for t in &arg_types[1..] {
+            if !t.is_integer() {
+                return plan_err!(
+                    "the size and seed arguments to reservoir_sample must be integers, not {t}"
+                );
+            }
+        }
+        Ok(DataType::List(Arc::new(Field::new(
+            "item",
+            arg_types[0].clone(),
+            true,
+        ))))
+    }
+
+    fn accumulator(&self, acc_args: AccumulatorArgs) -> Result<Box<dyn Accumulator>> {
+        Ok(Box::new(ReservoirSampleAccumulator::new(item_type(
+            acc_args.data_type,
+        )?)))
+    }
+
+    fn state_fields(&self, args: StateFieldsArgs) -> Result<Vec<Field>> {
+        Ok(vec![
+            Field::new(format!("{}[count]", args.name), DataType::UInt64, true),
+            Field::new(format!("{}[k]", args.name), DataType::UInt64, true),
+            Field::new(format!("{}[seed]", args.name), DataType::UInt64, true),
+            Field::new(
+                format!("{}[reservoir]", args.name),
+                args.return_type.clone(),
+                true,
+            ),
+        ])
+    }
+}
+
+/// Keeps a sample of the values it's seen using Algorithm R. The random choices it makes are
+/// derived from the seed and the number of values seen, so that given the same seed and the same
+/// input in the same order, it always produces the same sample.
+#[derive(Debug)]
+pub struct ReservoirSampleAccumulator {
+    item_type: DataType,
+    count: u64,
+    k: Option<u64>,
+    seed: Option<u64>,
+    reservoir: Vec<ScalarValue>,
+}
+
+impl ReservoirSampleAccumulator {
+    pub fn new(item_type: DataType) -> Self {
+        Self {
+            item_type,
+            count: 0,
+            k: None,
+            seed: None,
+            reservoir: vec![],
+        }
+    }
+
+    /// A random number in `0..n` for the `step`th choice made after seeing `count` values
+    fn random(&mut self, count: u64, step: u64, n: u64) -> u64 {
+        let seed = *self.seed.get_or_insert_with(rand::random);
+        let mut bytes = [0; 16];
+        bytes[..8].copy_from_slice(&count.to_le_bytes());
+        bytes[8..].copy_from_slice(&step.to_le_bytes());
+        xxh3_64_with_seed(&bytes, seed) % n
+    }
+
+    fn set_k(&mut self, k: u64) -> Result<()> {
+        if k == 0 || k > MAX_RESERVOIR_SIZE {
+            return exec_err!(
+                "reservoir_sample size must be between 1 and {MAX_RESERVOIR_SIZE}, but was {k}"
+            );
+        }
+        match self.k {
+            Some(current) if current != k => {
+                exec_err!("reservoir_sample size must be constant, but was both {current} and {k}")
+            }
+            _ => {
+                self.k = Some(k);
+                Ok(())
+            }
+        }
+    }
+
+    fn push(&mut self, value: ScalarValue, k: u64) {
+        self.count += 1;
+        if (self.reservoir.len() as u64) < k {
+            self.reservoir.push(value);
+        } else {
+            let i = self.random(self.count, 0, self.count);
+            if i < k {
+                self.reservoir[i as usize] = value;
+            }
+        }
+    }
+
+    /// Merges in the sample of another accumulator that saw `count` values. Each value in a
+    /// sample stands in for `count / sample.len()` of the values it was drawn from, so the merged
+    /// sample draws from each side in proportion to the number of values it saw.
+    fn merge(&mut self, count: u64, mut other: Vec<ScalarValue>, k: u64) {
+        let mut mine = std::mem::take(&mut self.reservoir);
+        let per_value =
+            |count: u64, sample: &Vec<ScalarValue>| count as f64 / sample.len().max(1) as f64;
+        let (mine_per_value, other_per_value) =
+            (per_value(self.count, &mine), per_value(count, &other));
+        let (mut mine_weight, mut other_weight) = (self.count as f64, count as f64);
+
+        let merged_count = self.count + count;
+        let mut step = 0;
+        while (self.reservoir.len() as u64) < k && !(mine.is_empty() && other.is_empty()) {
+            let total = mine_weight + other_weight;
+            let from_mine = other.is_empty()
+                || (!mine.is_empty()
+                    && (self.random(merged_count, step, u64::MAX) as f64 / u64::MAX as f64)
+                        * total
+                        < mine_weight);
+
+            let (sample, weight, per_value) = if from_mine {
+                (&mut mine, &mut mine_weight, mine_per_value)
+            } else {
+                (&mut other, &mut other_weight, other_per_value)
+            };
+            let i = self.random(merged_count, step + 1, sample.len() as u64);
+            self.reservoir.push(sample.swap_remove(i as usize));
+            *weight = (*weight - per_value).max(0.0);
+            step += 2;
+        }
+
+        self.count = merged_count;
+    }
+}
+
+impl Accumulator for ReservoirSampleAccumulator {
+    fn update_batch(&mut self, values: &[ArrayRef]) -> Result<()> {
+        let ks = cast(&values[1], &DataType::Int64)?;
+        let ks = ks.as_primitive::<Int64Type>();
+        if let Some(seeds) = values.get(2) {
+            let seeds = cast(seeds, &DataType::Int64)?;
+            if let Some(seed) = seeds.as_primitive::<Int64Type>().iter().flatten().next() {
+                self.seed.get_or_insert(seed as u64);
+            }
+        }
+
+        for i in 0..values[0].len() {
+            if values[0].is_null(i) || ks.is_null(i) {
+                continue;
+            }
+            let k = ks.value(i);
+            let Ok(k) = u64::try_from(k) else {
+                return exec_err!("reservoir_sample size must be positive, but was {k}");
+            };
+            self.set_k(k)?;
+            self.push(ScalarValue::try_from_array(&values[0], i)?, k);
+        }
+        Ok(())
+    }
+
+    fn evaluate(&mut self) -> Result<ScalarValue> {
+        let values = if self.reservoir.is_empty() {
+            new_empty_array(&self.item_type)
+        } else {
+            ScalarValue::iter_to_array(self.reservoir.clone())?
+        };
+
+        Ok(ScalarValue::List(Arc::new(ListArray::new(
+            Arc::new(Field::new("item", self.item_type.clone(), true)),
+            OffsetBuffer::from_lengths([values.len()]),
+            values,
+            None,
+        ))))
+    }
+
+    fn size(&self) -> usize {
+        std::mem::size_of_val(self) + self.reservoir.iter().map(ScalarValue::size).sum::<usize>()
+    }
+
+    fn state(&mut self) -> Result<Vec<ScalarValue>> {
+        Ok(vec![
+            ScalarValue::UInt64(Some(self.count)),
+            ScalarValue::UInt64(self.k),
+            ScalarValue::UInt64(self.seed),
+            self.evaluate()?,
+        ])
+    }
+
+    fn merge_batch(&mut self, states: &[ArrayRef]) -> Result<()> {
+        let counts: &UInt64Array = states[0].as_primitive();
+        let ks: &UInt64Array = states[1].as_primitive();
+        let seeds: &UInt64Array = states[2].as_primitive();
+        let Some(samples) = states[3].as_list_opt::<i32>() else {
+            return exec_err!(
+                "invalid state for reservoir_sample: {:?}",
+                states[3].data_type()
+            );
+        };
+
+        for i in 0..counts.len() {
+            if counts.is_null(i) || counts.value(i) == 0 || ks.is_null(i) {
+                continue;
+            }
+            let k = ks.value(i);
+            self.set_k(k)?;
+            if seeds.is_valid(i) {
+                self.seed.get_or_insert(seeds.value(i));
+            }
+
+            let values = samples.value(i);
+            let sample = (0..values.len())
+                .map(|j| ScalarValue::try_from_array(&values, j))
+                .collect::<Result<Vec<_>>>()?;
+            self.merge(counts.value(i), sample, k);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use arrow::array::Int64Array;
+
+    fn update_and_evaluate(accumulator: &mut dyn Accumulator, args: Vec<ArrayRef>) -> Option<f64> {
+        accumulator.update_batch(&args).unwrap();
+        match accumulator.evaluate().unwrap() {
+            ScalarValue::Float64(v) => v,
+            v => panic!("unexpected result {:?}", v),
+        }
+    }
+
+    #[test]
+    fn test_zscore() {
+        let mut acc = ZScoreAccumulator::default();
+        assert_eq!(
+            update_and_evaluate(&mut acc, vec![Arc::new(Float64Array::from(vec![5.0]))]),
+            None
+        );
+
+        let score = update_and_evaluate(
+            &mut acc,
+            vec![Arc::new(Float64Array::from(vec![5.0, 7.0, 3.0, 5.0, 15.0]))],
+        )
+        .unwrap();
+        // mean 6.67, sample stddev 4.27
+        assert!((score - 1.950).abs() < 0.001, "{}", score);
+
+        // partial states can't be combined, as which value is the latest is lost
+        let state: Vec<ArrayRef> = acc
+            .state()
+            .unwrap()
+            .into_iter()
+            .map(|s| s.to_array().unwrap())
+            .collect();
+        assert!(ZScoreAccumulator::default().merge_batch(&state).is_err());
+    }
+
+    #[test]
+    fn test_ewma_zscore() {
+        let mut acc = EwmaZScoreAccumulator::default();
+        let alpha = |n| Arc::new(Float64Array::from(vec![0.5; n])) as ArrayRef;
+
+        assert_eq!(
+            update_and_evaluate(
+                &mut acc,
+                vec![Arc::new(Float64Array::from(vec![10.0, 10.0])), alpha(2)]
+            ),
+            None
+        );
+
+        let normal = update_and_evaluate(
+            &mut acc,
+            vec![
+                Arc::new(Float64Array::from(vec![11.0, 9.0, 10.0])),
+                alpha(3),
+            ],
+        )
+        .unwrap();
+        let anomaly = update_and_evaluate(
+            &mut acc,
+            vec![Arc::new(Float64Array::from(vec![30.0])), alpha(1)],
+        )
+        .unwrap();
+        assert!(normal.abs() < 1.0, "{}", normal);
+        assert!(anomaly > 10.0, "{}", anomaly);
+
+        let state: Vec<ArrayRef> = acc
+            .state()
+            .unwrap()
+            .into_iter()
+            .map(|s| s.to_array().unwrap())
+            .collect();
+        assert!(EwmaZScoreAccumulator::default()
+            .merge_batch(&state)
+            .is_err());
+
+        assert!(acc
+            .update_batch(&[
+                Arc::new(Float64Array::from(vec![1.0])),
+                Arc::new(Float64Array::from(vec![2.0]))
+            ])
+            .is_err());
+    }
+
+    #[test]
+    fn test_mad_score() {
+        let mut acc = MadScoreAccumulator::default();
+        let score = update_and_evaluate(
+            &mut acc,
+            vec![Arc::new(Float64Array::from(vec![
+                Some(1.0),
+                Some(2.0),
+                None,
+                Some(3.0),
+                Some(4.0),
+                Some(100.0),
+            ]))],
+        )
+        .unwrap();
+        // median 3, MAD 1
+        assert!((score - 65.4265).abs() < 0.0001, "{}", score);
+
+        let state: Vec<ArrayRef> = acc
+            .state()
+            .unwrap()
+            .into_iter()
+            .map(|s| s.to_array().unwrap())
+            .collect();
+        let mut merged = MadScoreAccumulator::default();
+        merged.merge_batch(&state).unwrap();
+        assert_eq!(
+            merged.evaluate().unwrap(),
+            ScalarValue::Float64(Some(score))
+        );
+
+        for i in 0..2 * MAD_WINDOW_SIZE {
+            merged.push(i as f64);
+        }
+        assert_eq!(merged.values.len(), MAD_WINDOW_SIZE);
+    }
+
+    fn sample(acc: &mut ReservoirSampleAccumulator) -> Vec<i64> {
+        let ScalarValue::List(list) = acc.evaluate().unwrap() else {
+            panic!("reservoir_sample should return a list");
+        };
+        list.value(0).as_primitive::<Int64Type>().values().to_vec()
+    }
+
+    fn update(acc: &mut ReservoirSampleAccumulator, values: Vec<i64>, k: i64, seed: i64) {
+        let n = values.len();
+        acc.update_batch(&[
+            Arc::new(Int64Array::from(values)),
+            Arc::new(Int64Array::from(vec![k; n])),
+            Arc::new(Int64Array::from(vec![seed; n])),
+        ])
+        .unwrap();
+    }
+
+    #[test]
+    fn test_reservoir_sample() {
+        let mut acc = ReservoirSampleAccumulator::new(DataType::Int64);
+        update(&mut acc, (0..5).collect(), 10, 42);
+        assert_eq!(sample(&mut acc), vec![0, 1, 2, 3, 4]);
+
+        update(&mut acc, (5..1000).collect(), 10, 42);
+        let first = sample(&mut acc);
+        assert_eq!(first.len(), 10);
+        assert!(first.iter().all(|v| (0..1000).contains(v)));
+        // with reservoir sampling, later values are as likely to be kept as earlier ones
+        assert!(first.iter().any(|v| *v >= 10));
+
+        // the same seed and input always produce the same sample
+        let mut again = ReservoirSampleAccumulator::new(DataType::Int64);
+        update(&mut again, (0..1000).collect(), 10, 42);
+        assert_eq!(sample(&mut again), first);
+
+        // merging partial samples gives a sample of all of their values
+        let mut halves: Vec<_> = [(0..500), (500..1000)]
+            .into_iter()
+            .map(|values| {
+                let mut acc = ReservoirSampleAccumulator::new(DataType::Int64);
+                update(&mut acc, values.collect(), 10, 7);
+                acc
+            })
+            .collect();
+
+        let mut merged = ReservoirSampleAccumulator::new(DataType::Int64);
+        for acc in &mut halves {
+            let state: Vec<ArrayRef> = acc
+                .state()
+                .unwrap()
+                .into_iter()
+                .map(|s| s.to_array().unwrap())
+                .collect();
+            merged.merge_batch(&state).unwrap();
+        }
+        assert_eq!(merged.count, 1000);
+        let merged_sample = sample(&mut merged);
+        assert_eq!(merged_sample.len(), 10);
+        let mut distinct = merged_sample.clone();
+        distinct.sort();
+        distinct.dedup();
+        assert_eq!(distinct.len(), 10);
+
+        assert!(acc
+            .update_batch(&[
+                Arc::new(Int64Array::from(vec![1])),
+                Arc::new(Int64Array::from(vec![20])),
+            ])
+            .is_err());
+    }
+}
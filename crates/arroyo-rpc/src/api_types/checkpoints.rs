@@ -1,5 +1,5 @@
 use serde::{Deserialize, Serialize};
-use utoipa::ToSchema;
+use utoipa::{IntoParams, ToSchema};
 
 #[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
 #[serde(rename_all = "camelCase")]
@@ -43,3 +43,57 @@ pub struct OperatorCheckpointGroup {
     pub bytes: u64,
     pub subtasks: Vec<SubtaskCheckpointGroup>,
 }
+
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct SavepointPost {
+    pub name: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, ToSchema, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SavepointState {
+    Pending,
+    Ready,
+    Failed,
+}
+
+/// A named snapshot of the state of a pipeline's job, which is kept independently of the job's
+/// checkpoints and can be used to start new pipelines
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Savepoint {
+    pub id: String,
+    pub name: String,
+    pub pipeline_id: String,
+    pub job_id: String,
+    pub state: SavepointState,
+    /// the epoch of the checkpoint the savepoint was written from, once it's ready
+    pub epoch: Option<u32>,
+    pub created_at: u64,
+    pub failure_message: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, IntoParams, ToSchema)]
+#[into_params(parameter_in = Query)]
+#[serde(rename_all = "snake_case")]
+pub struct StateQueryParams {
+    /// The operator whose state to query
+    pub operator_id: String,
+    /// The name of the operator's state table
+    pub table: String,
+    /// Only return the entries for this key
+    pub key: Option<String>,
+    /// The maximum number of entries to return (defaults to 100)
+    pub limit: Option<u32>,
+}
+
+/// An entry of an operator's live state. For keyed tables, the key and value are debug-formatted;
+/// for windowed tables the key is the row's key columns and the value is the row as JSON.
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct StateEntry {
+    pub subtask_index: u32,
+    pub key: String,
+    pub value: String,
+}
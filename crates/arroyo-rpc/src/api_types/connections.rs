@@ -105,6 +105,8 @@ pub enum FieldType {
     Primitive(PrimitiveType),
     Struct(StructType),
     List(Box<SourceField>),
+    /// A map with string keys; the boxed field describes the values
+    Map(Box<SourceField>),
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, ToSchema, PartialEq, Eq)]
@@ -157,6 +159,23 @@ impl From<SourceField> for Field {
                 None,
             ),
             FieldType::List(t) => (DataType::List(Arc::new((*t).into())), None),
+            FieldType::Map(v) => {
+                let value = Field::from(*v).with_name("values");
+                (
+                    DataType::Map(
+                        Arc::new(Field::new(
+                            "entries",
+                            DataType::Struct(Fields::from(vec![
+                                Field::new("keys", DataType::Utf8, false),
+                                value,
+                            ])),
+                            false,
+                        )),
+                        false,
+                    ),
+                    None,
+                )
+            }
         };
 
         ArroyoExtensionType::add_metadata(ext, Field::new(f.field_name, t, f.nullable))
@@ -205,6 +224,17 @@ impl TryFrom<Field> for SourceField {
                 FieldType::Struct(st)
             }
             (DataType::List(item), None) => FieldType::List(Box::new((**item).clone().try_into()?)),
+            (DataType::Map(entries, _), None) => match entries.data_type() {
+                DataType::Struct(kv)
+                    if kv.len() == 2
+                        && matches!(kv[0].data_type(), DataType::Utf8 | DataType::LargeUtf8) =>
+                {
+                    FieldType::Map(Box::new((*kv[1]).clone().try_into()?))
+                }
+                dt => {
+                    return Err(format!("Unsupported map entry type {:?}", dt));
+                }
+            },
             dt => {
                 return Err(format!("Unsupported data type {:?}", dt));
             }
@@ -361,6 +391,14 @@ pub struct ConnectionTablePost {
     pub schema: Option<ConnectionSchema>,
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug, IntoParams, ToSchema)]
+#[into_params(parameter_in = Query)]
+#[serde(rename_all = "snake_case")]
+pub struct SchemaInferenceQueryParams {
+    /// The number of messages to sample from the source (defaults to 100)
+    pub sample_size: Option<u32>,
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct ConnectionAutocompleteResp {
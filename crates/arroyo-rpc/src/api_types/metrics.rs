@@ -1,9 +1,20 @@
 use serde::{Deserialize, Serialize};
-use strum_macros::{EnumCount, EnumString};
+use strum_macros::{AsRefStr, EnumCount, EnumString};
 use utoipa::ToSchema;
 
 #[derive(
-    Serialize, Deserialize, Copy, Clone, Debug, ToSchema, Hash, PartialEq, Eq, EnumCount, EnumString,
+    Serialize,
+    Deserialize,
+    Copy,
+    Clone,
+    Debug,
+    ToSchema,
+    Hash,
+    PartialEq,
+    Eq,
+    EnumCount,
+    EnumString,
+    AsRefStr,
 )]
 #[serde(rename_all = "snake_case")]
 #[strum(serialize_all = "snake_case")]
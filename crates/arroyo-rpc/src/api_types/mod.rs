@@ -29,12 +29,15 @@ pub struct PaginatedCollection<T> {
 #[serde(rename_all = "camelCase")]
 #[aliases(
     JobCollection = NonPaginatedCollection<Job>,
+    PipelineUsageCollection = NonPaginatedCollection<PipelineUsage>,
     OperatorCheckpointGroupCollection = NonPaginatedCollection<OperatorCheckpointGroup>,
     CheckpointCollection = NonPaginatedCollection<Checkpoint>,
     OperatorMetricGroupCollection = NonPaginatedCollection<OperatorMetricGroup>,
     ConnectorCollection = NonPaginatedCollection<Connector>,
     ConnectionProfileCollection = NonPaginatedCollection<ConnectionProfile>,
     GlobalUdfCollection = NonPaginatedCollection<GlobalUdf>,
+    SavepointCollection = NonPaginatedCollection<Savepoint>,
+    StateEntryCollection = NonPaginatedCollection<StateEntry>,
 )]
 pub struct NonPaginatedCollection<T> {
     pub data: Vec<T>,
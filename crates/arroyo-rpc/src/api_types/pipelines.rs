@@ -1,3 +1,4 @@
+use crate::api_types::checkpoints::Checkpoint;
 use crate::api_types::udfs::Udf;
 use crate::grpc as grpc_proto;
 use serde::{Deserialize, Serialize};
@@ -15,6 +16,23 @@ pub struct ValidateQueryPost {
 pub struct QueryValidationResult {
     pub graph: Option<PipelineGraph>,
     pub errors: Vec<String>,
+    /// The output of a `SHOW` or `DESCRIBE` statement, which is answered when the query is
+    /// validated rather than run as a pipeline
+    pub result: Option<SystemQueryResult>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct SystemQueryPost {
+    pub query: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct SystemQueryResult {
+    pub columns: Vec<String>,
+    /// Each row as a JSON object keyed by column name
+    pub rows: Vec<serde_json::Value>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
@@ -25,6 +43,8 @@ pub struct PipelinePost {
     pub udfs: Option<Vec<Udf>>,
     pub parallelism: u64,
     pub checkpoint_interval_micros: Option<u64>,
+    /// start the pipeline from the state in this savepoint
+    pub savepoint_id: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
@@ -34,6 +54,8 @@ pub struct PreviewPost {
     pub udfs: Option<Vec<Udf>>,
     #[serde(default)]
     pub enable_sinks: bool,
+    /// preview the output of this operator from the query's graph instead of the sinks
+    pub node_id: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
@@ -44,6 +66,59 @@ pub struct PipelinePatch {
     pub stop: Option<StopType>,
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct PipelineDiffPost {
+    pub query: String,
+    pub udfs: Option<Vec<Udf>>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum OperatorChange {
+    Added,
+    Removed,
+    Changed,
+    Unchanged,
+}
+
+/// Whether an operator's state in the pipeline's last checkpoint can be restored after the
+/// update
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum StateCompatibility {
+    /// the operator doesn't keep any state
+    Stateless,
+    Compatible,
+    /// the operator's configuration changed in a way that can only be checked against its
+    /// tables, which happens when the pipeline is restored
+    Unknown,
+    Incompatible,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct OperatorDiff {
+    pub node_id: String,
+    pub operator: String,
+    pub change: OperatorChange,
+    pub old_description: Option<String>,
+    pub new_description: Option<String>,
+    /// what changed about the operator, for changed operators
+    pub changes: Vec<String>,
+    pub state: StateCompatibility,
+    /// why the state is (or may be) incompatible
+    pub reasons: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct PipelineDiff {
+    pub operators: Vec<OperatorDiff>,
+    /// false if the state of any operator is known to be incompatible
+    pub state_compatible: bool,
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct PipelineRestart {
@@ -72,6 +147,8 @@ pub struct Pipeline {
 pub struct PipelineGraph {
     pub nodes: Vec<PipelineNode>,
     pub edges: Vec<PipelineEdge>,
+    /// column-level lineage for each of the pipeline's sinks
+    pub lineage: Vec<SinkLineage>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
@@ -93,6 +170,80 @@ pub struct PipelineEdge {
     pub edge_type: String,
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct SourceColumn {
+    pub table: String,
+    pub column: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ColumnLineage {
+    /// the sink column
+    pub column: String,
+    /// the columns of source tables that the column is derived from
+    pub sources: Vec<SourceColumn>,
+    /// the expressions applied to produce the column, from the innermost outwards; each is in
+    /// terms of the columns of its input
+    pub expressions: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct SinkLineage {
+    pub sink: String,
+    pub columns: Vec<ColumnLineage>,
+}
+
+impl From<grpc_proto::api::SinkLineage> for SinkLineage {
+    fn from(value: grpc_proto::api::SinkLineage) -> Self {
+        SinkLineage {
+            sink: value.sink,
+            columns: value
+                .columns
+                .into_iter()
+                .map(|c| ColumnLineage {
+                    column: c.column,
+                    sources: c
+                        .sources
+                        .into_iter()
+                        .map(|s| SourceColumn {
+                            table: s.table,
+                            column: s.column,
+                        })
+                        .collect(),
+                    expressions: c.expressions,
+                })
+                .collect(),
+        }
+    }
+}
+
+impl From<SinkLineage> for grpc_proto::api::SinkLineage {
+    fn from(value: SinkLineage) -> Self {
+        grpc_proto::api::SinkLineage {
+            sink: value.sink,
+            columns: value
+                .columns
+                .into_iter()
+                .map(|c| grpc_proto::api::ColumnLineage {
+                    column: c.column,
+                    sources: c
+                        .sources
+                        .into_iter()
+                        .map(|s| grpc_proto::api::SourceColumn {
+                            table: s.table,
+                            column: s.column,
+                        })
+                        .collect(),
+                    expressions: c.expressions,
+                })
+                .collect(),
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
 #[serde(rename_all = "camelCase")]
 pub enum StopType {
@@ -117,6 +268,47 @@ pub struct Job {
     pub created_at: u64,
 }
 
+/// Where a source should start reading one of its partitions when a stopped job is restarted
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct SourceOffsetOverride {
+    /// the id of the source node in the pipeline graph
+    pub node_id: String,
+    /// the Kafka partition or Kinesis shard id
+    pub partition: String,
+    /// the Kafka offset or Kinesis sequence number to start reading at, or `earliest` or `latest`
+    pub offset: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct SourceOffsetsPost {
+    /// replaces any existing overrides for the job; an empty list clears them
+    pub overrides: Vec<SourceOffsetOverride>,
+}
+
+/// Source offset overrides for a job. They are only applied when the job restores from `epoch`,
+/// the checkpoint that was current when they were set, so they have no effect once the job has
+/// checkpointed again.
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct SourceOffsetOverrides {
+    pub epoch: Option<u32>,
+    pub overrides: Vec<SourceOffsetOverride>,
+}
+
+/// Resources consumed by a pipeline's jobs over a single UTC day
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct PipelineUsage {
+    /// the day in YYYY-MM-DD format
+    pub day: String,
+    pub cpu_seconds: f64,
+    pub max_memory_bytes: u64,
+    pub state_bytes: u64,
+    pub network_bytes: u64,
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
 #[serde(rename_all = "camelCase")]
 pub enum JobLogLevel {
@@ -147,6 +339,28 @@ pub struct OutputData {
     pub batch: String,
 }
 
+/// An event in the life of a job, as streamed by the watch endpoint
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum JobEvent {
+    /// The job's state changed; also sent with the current state when the stream is opened
+    StateChanged { job: Job },
+    /// A checkpoint finished
+    CheckpointCompleted { checkpoint: Checkpoint },
+    /// An operator reported an error
+    Error { error: JobLogMessage },
+}
+
+impl JobEvent {
+    pub fn name(&self) -> &'static str {
+        match self {
+            JobEvent::StateChanged { .. } => "stateChanged",
+            JobEvent::CheckpointCompleted { .. } => "checkpointCompleted",
+            JobEvent::Error { .. } => "error",
+        }
+    }
+}
+
 impl From<grpc_proto::rpc::OutputData> for OutputData {
     fn from(value: grpc_proto::rpc::OutputData) -> Self {
         OutputData {
@@ -8,6 +8,10 @@ pub struct Udf {
     pub definition: String,
     #[serde(default)]
     pub language: UdfLanguage,
+    /// The Cargo.lock a Rust UDF is built against; recorded when the pipeline is created so
+    /// that the same artifact can be rebuilt later
+    #[serde(default)]
+    pub cargo_lock: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
@@ -53,6 +57,10 @@ pub struct UdfPost {
     pub language: UdfLanguage,
     pub definition: String,
     pub description: Option<String>,
+    /// The Cargo.lock to build a Rust UDF against; if not set, dependencies are resolved when
+    /// the UDF is created, and the result is recorded with it
+    #[serde(default)]
+    pub cargo_lock: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
@@ -67,4 +75,6 @@ pub struct GlobalUdf {
     pub definition: String,
     pub description: Option<String>,
     pub dylib_url: Option<String>,
+    /// The Cargo.lock the UDF was built against
+    pub cargo_lock: Option<String>,
 }
@@ -229,6 +229,10 @@ pub struct Config {
     /// Default interval for checkpointing
     pub default_checkpoint_interval: HumanReadableDuration,
 
+    /// How calls to external services, like the checkpoint store and schema registries, are
+    /// retried when they fail
+    pub resilience: ResilienceConfig,
+
     /// The endpoint of the controller, used by other services to connect to it. This must be set
     /// if running the controller on a separate machine from the other services or on a separate
     /// process with a non-standard port.
@@ -249,6 +253,11 @@ pub struct Config {
     /// Telemetry config
     #[serde(default)]
     pub disable_telemetry: bool,
+
+    /// Out-of-tree connectors, served by plugin processes, that are registered alongside the
+    /// built-in connectors
+    #[serde(default)]
+    pub connector_plugins: Vec<ConnectorPluginConfig>,
 }
 
 impl Config {
@@ -301,6 +310,105 @@ pub struct CompactionConfig {
 
     /// The number of outstanding checkpoints that will trigger compaction
     pub checkpoints_to_compact: u32,
+
+    /// The size in bytes that compaction aims for in the files it writes; files at least this
+    /// large aren't compacted again
+    pub target_file_size: u64,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct CheckpointRetentionConfig {
+    /// The number of most recent checkpoints to keep; the data of older checkpoints is deleted
+    /// from the checkpoint storage, unless it's still needed by a retained checkpoint
+    pub keep_last: u32,
+
+    /// If set, checkpoints started within this long are also kept, even if they aren't among the
+    /// `keep-last` most recent
+    pub keep_younger_than: Option<HumanReadableDuration>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct SourceRebalancingConfig {
+    /// Whether to rebalance the splits (e.g., Kafka partitions) of a source across its subtasks
+    /// when some subtasks fall far behind the others
+    pub enabled: bool,
+
+    /// The minimum difference in lag, in messages, between the most and least lagged subtasks
+    /// of a source that measures lag in messages (like Kafka) before it is rebalanced; it's only
+    /// rebalanced if that would narrow the difference by at least as much
+    pub min_lag_messages: u64,
+
+    /// The same as `min-lag-messages`, for sources that measure lag in milliseconds behind the
+    /// head of the stream (like Kinesis)
+    pub min_lag_millis: u64,
+
+    /// How many times greater the lag of the most lagged subtask must be than the least lagged
+    /// subtask before the source is rebalanced
+    pub skew_ratio: f64,
+
+    /// How long a job must run before it's rebalanced
+    pub min_interval: HumanReadableDuration,
+
+    /// How long to wait after rebalancing a job before rebalancing it again
+    pub cooldown: HumanReadableDuration,
+}
+
+/// How the controller scales the parallelism of pipelines that set autoscaling bounds (with
+/// `SET autoscaling.max_parallelism`) according to their load. A pipeline is scaled up, doubling
+/// its parallelism, once it's been overloaded for `scale-up-after`, and scaled down, halving it,
+/// once it's been underloaded for `scale-down-after`.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct AutoscalingConfig {
+    /// A pipeline is overloaded while the backpressure of any of its operators, from 0 to 1, is
+    /// at least this
+    pub scale_up_backpressure: f64,
+
+    /// A pipeline is also overloaded while the lag of any of its sources is at least this, in
+    /// source-specific units (messages for Kafka, milliseconds for Kinesis)
+    pub scale_up_lag: u64,
+
+    /// A pipeline is underloaded while the backpressure of all of its operators is at most this
+    /// and the lag of all of its sources is at most `scale-down-lag`
+    pub scale_down_backpressure: f64,
+
+    /// The lag below which a pipeline may be underloaded, in the same units as `scale-up-lag`
+    pub scale_down_lag: u64,
+
+    /// How long a pipeline must be continuously overloaded before it's scaled up
+    pub scale_up_after: HumanReadableDuration,
+
+    /// How long a pipeline must be continuously underloaded before it's scaled down
+    pub scale_down_after: HumanReadableDuration,
+
+    /// How long a pipeline must run before (and between) being rescaled
+    pub min_interval: HumanReadableDuration,
+}
+
+/// Limits that preview pipelines run under, so that exploratory queries can't consume the
+/// resources of the whole cluster
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct PreviewConfig {
+    /// Number of rows a preview outputs before it is stopped
+    pub max_rows: u64,
+
+    /// How long a preview may run before it is stopped
+    pub max_runtime: HumanReadableDuration,
+
+    /// Maximum number of bytes queued between each pair of operators in a preview; this takes
+    /// precedence over `SET queue.max_bytes` in the query if it is lower
+    pub queue_max_bytes: u64,
+
+    /// Maximum number of bytes of memory the state of each subtask of a preview may use, as
+    /// measured at each checkpoint; previews whose state grows beyond this fail
+    pub max_state_bytes: u64,
+
+    /// Whether previews may write to the sinks of the query when requested; otherwise their
+    /// output only goes to the preview
+    pub allow_sinks: bool,
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
@@ -352,8 +460,158 @@ pub struct WorkerConfig {
     /// Name to identify this worker (e.g., e.g., its hostname or a pod name)
     pub name: Option<String>,
 
-    /// Size of the queues between nodes in the dataflow graph
+    /// Size of the queues between nodes in the dataflow graph, in rows
     pub queue_size: u32,
+
+    /// Maximum number of bytes of data buffered in each queue between nodes in the dataflow
+    /// graph; an empty queue will always accept a batch, even if it's larger than this
+    pub queue_max_bytes: u64,
+
+    /// How long a worker that receives SIGTERM will wait for a final checkpoint of its tasks
+    /// to complete and for them to stop before exiting; should be less than the termination
+    /// grace period
+    pub shutdown_checkpoint_timeout: HumanReadableDuration,
+
+    /// URL the worker polls for notice that its instance is about to be reclaimed, like
+    /// `http://169.254.169.254/latest/meta-data/spot/instance-action` for AWS spot instances. A
+    /// successful response drains the worker as if it had received SIGTERM.
+    #[serde(default)]
+    pub interruption_notice_url: Option<String>,
+
+    /// How long checkpoint events and completions are buffered before being sent to the
+    /// controller in a single request
+    pub control_batch_interval: HumanReadableDuration,
+
+    /// Maximum number of checkpoint events and completions sent to the controller in a single
+    /// request
+    pub control_batch_size: usize,
+
+    pub checkpoint_storage: CheckpointStorageConfig,
+
+    pub local_state: LocalStateConfig,
+
+    pub backpressure: BackpressureConfig,
+
+    pub chaos: ChaosConfig,
+}
+
+/// Controls how workers transfer checkpoint files to and from the checkpoint store
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct CheckpointStorageConfig {
+    /// Maximum number of parts of each checkpoint file that are uploaded concurrently
+    pub upload_concurrency: usize,
+
+    /// Size in bytes of the parts that checkpoint files are uploaded in; must be at least 5MiB,
+    /// the smallest part S3 accepts
+    #[serde(deserialize_with = "deserialize_upload_part_size")]
+    pub upload_part_size: usize,
+
+    /// Maximum number of checkpoint files each table reads concurrently when restoring
+    pub restore_concurrency: usize,
+
+    /// Limit on the bandwidth each worker uses to upload and download checkpoint files, in
+    /// bytes per second, if set
+    pub max_bytes_per_second: Option<u64>,
+}
+
+/// The smallest part that S3 accepts in a multipart upload, other than the last
+pub const MIN_UPLOAD_PART_SIZE: usize = 5 * 1024 * 1024;
+
+fn deserialize_upload_part_size<'de, D>(deserializer: D) -> Result<usize, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let size = usize::deserialize(deserializer)?;
+    if size < MIN_UPLOAD_PART_SIZE {
+        return Err(de::Error::custom(format!(
+            "upload-part-size must be at least {} bytes (5MiB), but is {}",
+            MIN_UPLOAD_PART_SIZE, size
+        )));
+    }
+    Ok(size)
+}
+
+/// Controls how failed calls to external services are retried. Failures are retried with
+/// exponential backoff; once enough calls to a service have failed in a row, its circuit breaker
+/// opens and further calls wait for it to close, rather than adding load to a struggling
+/// service.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct ResilienceConfig {
+    /// Number of times a failed call is retried before its error is returned
+    pub max_retries: u32,
+
+    /// Delay before the first retry; this doubles with each subsequent retry
+    pub base_delay: HumanReadableDuration,
+
+    /// Maximum delay between retries
+    pub max_delay: HumanReadableDuration,
+
+    /// Fraction of each delay, between 0 and 1, that is randomized so that callers that failed
+    /// together don't retry together
+    pub jitter: f64,
+
+    /// Number of consecutive failures of a service after which its circuit breaker opens
+    pub failure_threshold: u32,
+
+    /// How long the circuit breaker stays open before calls are let through again
+    pub reset_timeout: HumanReadableDuration,
+}
+
+/// Controls how operators that keep their state on disk or in RocksDB (as chosen by
+/// `/*+ state(...) */` hints) store it locally; this is a cache of the checkpointed state, and
+/// is rebuilt from the checkpoint store when tasks restart
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct LocalStateConfig {
+    /// Directory that local state is kept in; defaults to a directory under the system's
+    /// temporary directory
+    #[serde(default)]
+    pub dir: Option<PathBuf>,
+
+    /// Number of bytes of state each table of an operator using disk storage holds in memory
+    /// before spilling it to disk
+    pub spill_threshold: u64,
+}
+
+/// Controls how workers detect backpressure from the occupancy of the queues between nodes, and
+/// whether sources slow down while it lasts
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct BackpressureConfig {
+    /// Fraction of a queue's rows or bytes that must be in use for it to count as backpressured
+    pub threshold: f64,
+
+    /// How often a node samples the occupancy of its output queues as it sends data to them
+    pub sample_interval: HumanReadableDuration,
+
+    /// Whether sources are throttled when one of their output queues stays backpressured, rather
+    /// than only blocking once it's full
+    pub throttle_sources: bool,
+
+    /// How long an output queue of a source must stay backpressured before the source is
+    /// throttled
+    pub throttle_after: HumanReadableDuration,
+
+    /// How long a throttled source waits before sending each batch
+    pub throttle_delay: HumanReadableDuration,
+}
+
+/// Faults injected into workers to test that pipelines recover from them correctly (as by
+/// `arroyo chaos-test`); this should never be enabled outside of test clusters
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct ChaosConfig {
+    /// Whether to inject faults
+    pub enabled: bool,
+
+    /// Average time between workers being killed, if set; `0s` disables kills
+    pub kill_interval: Option<HumanReadableDuration>,
+
+    /// Average time between network faults, in which one of a worker's outgoing data
+    /// connections is dropped, if set; `0s` disables network faults
+    pub network_fault_interval: Option<HumanReadableDuration>,
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
@@ -423,11 +681,30 @@ pub struct PipelineConfig {
     /// Amount of time to wait for tasks to startup before considering it failed
     pub task_startup_time: HumanReadableDuration,
 
+    /// Whether a failed task restarts only its region of the pipeline (the operators connected to
+    /// it) from the last checkpoint, rather than the whole pipeline. Region restarts count
+    /// towards `allowed-restarts` separately from full restarts.
+    ///
+    /// Off by default; enable it with `pipeline.region-failover = true` (or
+    /// `ARROYO__PIPELINE__REGION_FAILOVER=true`). The whole pipeline is still restarted when
+    /// a worker is lost, when the failed tasks span more than one region or the entire
+    /// pipeline, while a checkpoint or two-phase commit is in flight, and when any worker
+    /// predates region restarts.
+    pub region_failover: bool,
+
     /// Default sink, for when none is specified
     #[serde(default)]
     pub default_sink: DefaultSink,
 
     pub compaction: CompactionConfig,
+
+    pub checkpoint_retention: CheckpointRetentionConfig,
+
+    pub source_rebalancing: SourceRebalancingConfig,
+
+    pub autoscaling: AutoscalingConfig,
+
+    pub preview: PreviewConfig,
 }
 
 #[derive(Debug, Deserialize, Serialize, Eq, PartialEq, Clone)]
@@ -584,17 +861,14 @@ impl Serialize for HumanReadableDuration {
     }
 }
 
-impl<'de> Deserialize<'de> for HumanReadableDuration {
-    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
-    where
-        D: Deserializer<'de>,
-    {
-        let str = String::deserialize(deserializer)?;
+impl FromStr for HumanReadableDuration {
+    type Err = String;
 
+    fn from_str(str: &str) -> Result<Self, Self::Err> {
         let r = Regex::new(r"^(\d+)\s*([a-zA-Zµ]+)$").unwrap();
-        let captures = r.captures(&str).ok_or_else(|| {
-            de::Error::custom(format!("invalid duration specification '{}'", str))
-        })?;
+        let captures = r
+            .captures(str)
+            .ok_or_else(|| format!("invalid duration specification '{}'", str))?;
         let mut capture = captures.iter();
 
         capture.next();
@@ -609,16 +883,26 @@ impl<'de> Deserialize<'de> for HumanReadableDuration {
             "s" | "secs" | "seconds" => Duration::from_secs(n),
             "m" | "mins" | "minutes" => Duration::from_secs(n * 60),
             "h" | "hrs" | "hours" => Duration::from_secs(n * 60 * 60),
-            x => return Err(de::Error::custom(format!("unknown time unit '{}'", x))),
+            x => return Err(format!("unknown time unit '{}'", x)),
         };
 
         Ok(HumanReadableDuration {
             duration,
-            original: str,
+            original: str.to_string(),
         })
     }
 }
 
+impl<'de> Deserialize<'de> for HumanReadableDuration {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let str = String::deserialize(deserializer)?;
+        str.parse().map_err(de::Error::custom)
+    }
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
 #[serde(rename_all = "kebab-case", deny_unknown_fields)]
 pub struct LogConfig {
@@ -663,6 +947,13 @@ pub struct RunConfig {
     pub state_dir: Option<String>,
 }
 
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct ConnectorPluginConfig {
+    /// The gRPC endpoint of the plugin process, like `http://localhost:9190`
+    pub endpoint: Url,
+}
+
 #[derive(Debug, Clone)]
 pub struct Sensitive<T: Serialize + DeserializeOwned + Debug + Clone>(T);
 
@@ -694,7 +985,9 @@ impl<T: Serialize + DeserializeOwned + Debug + Clone> Deref for Sensitive<T> {
 
 #[cfg(test)]
 mod tests {
-    use crate::config::{load_config, Config, DatabaseType, Scheduler, SqliteConfig};
+    use crate::config::{
+        load_config, Config, DatabaseType, Scheduler, SqliteConfig, MIN_UPLOAD_PART_SIZE,
+    };
     use url::Url;
 
     #[test]
@@ -748,6 +1041,29 @@ mod tests {
         });
     }
 
+    #[test]
+    fn test_upload_part_size() {
+        figment::Jail::expect_with(|jail| {
+            jail.set_env(
+                "ARROYO__WORKER__CHECKPOINT_STORAGE__UPLOAD_PART_SIZE",
+                1024 * 1024,
+            );
+            let err = load_config(&[]).extract::<Config>().unwrap_err();
+            assert!(err.to_string().contains("must be at least"));
+
+            jail.set_env(
+                "ARROYO__WORKER__CHECKPOINT_STORAGE__UPLOAD_PART_SIZE",
+                MIN_UPLOAD_PART_SIZE,
+            );
+            let config: Config = load_config(&[]).extract().unwrap();
+            assert_eq!(
+                config.worker.checkpoint_storage.upload_part_size,
+                MIN_UPLOAD_PART_SIZE
+            );
+            Ok(())
+        });
+    }
+
     #[test]
     fn test_sensitive_config() {
         figment::Jail::expect_with(|jail| {
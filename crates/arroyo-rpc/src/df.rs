@@ -1,7 +1,7 @@
 use crate::grpc::api;
 use crate::{grpc, Converter, TIMESTAMP_FIELD};
 use anyhow::{anyhow, Result};
-use arrow::compute::kernels::numeric::div;
+use arrow::compute::kernels::numeric::{div, mul};
 use arrow::compute::{filter_record_batch, take};
 use arrow::datatypes::{DataType, Field, Schema, SchemaBuilder, TimeUnit};
 use arrow::row::SortField;
@@ -11,7 +11,7 @@ use arrow_array::{Array, PrimitiveArray, RecordBatch, TimestampNanosecondArray,
 use arrow_ord::cmp::gt_eq;
 use arrow_ord::partition::partition;
 use arrow_ord::sort::{lexsort_to_indices, SortColumn};
-use arroyo_types::to_nanos;
+use arroyo_types::{to_nanos, KEY_GROUPS, KEY_GROUP_SIZE};
 use datafusion_common::DataFusionError;
 use std::ops::Range;
 use std::sync::Arc;
@@ -390,9 +390,10 @@ pub fn server_for_hash_array(
     hash: &PrimitiveArray<UInt64Type>,
     n: usize,
 ) -> anyhow::Result<PrimitiveArray<UInt64Type>> {
-    let range_size = u64::MAX / (n as u64) + 1;
-    let range_scalar = UInt64Array::new_scalar(range_size);
-    let division = div(hash, &range_scalar)?;
+    // consistent with `arroyo_types::server_for_hash`
+    let key_groups = div(hash, &UInt64Array::new_scalar(KEY_GROUP_SIZE))?;
+    let scaled = mul(&key_groups, &UInt64Array::new_scalar(n as u64))?;
+    let division = div(&scaled, &UInt64Array::new_scalar(KEY_GROUPS as u64))?;
     let result: &PrimitiveArray<UInt64Type> = division.as_any().downcast_ref().unwrap();
     Ok(result.clone())
 }
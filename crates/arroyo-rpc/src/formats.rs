@@ -1,7 +1,7 @@
 use regex::Regex;
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::cmp::Ordering;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::hash::{Hash, Hasher};
 use std::str::FromStr;
 use std::sync::OnceLock;
@@ -107,6 +107,203 @@ pub struct RawStringFormat {}
 #[serde(rename_all = "camelCase")]
 pub struct RawBytesFormat {}
 
+/// Arrow IPC encoded record batches, either in the streaming format or the file (Feather v2)
+/// format; batches are written in the streaming format
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq, Hash, PartialOrd, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ArrowIpcFormat {}
+
+/// Delimited text with one row per line. When reading, the values of each row are matched to
+/// the columns of the table by position and parsed as the column's type.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq, Hash, PartialOrd, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct CsvFormat {
+    /// The single character that separates fields (a comma by default)
+    #[serde(default)]
+    pub delimiter: Option<String>,
+    /// The single character that quotes fields containing the delimiter (a double quote by
+    /// default)
+    #[serde(default)]
+    pub quote: Option<String>,
+    /// Whether each file starts with a row of the column names; when reading, a row of the
+    /// column names at the start of a file or message is skipped
+    #[serde(default)]
+    pub include_header: bool,
+}
+
+impl CsvFormat {
+    fn from_opts(opts: &mut HashMap<String, String>) -> Result<Self, String> {
+        // SQL string literals don't have escapes, so a tab (for TSV) can be written as '\t'
+        if opts.get("csv.delimiter").is_some_and(|d| d == "\\t") {
+            opts.insert("csv.delimiter".to_string(), "\t".to_string());
+        }
+        let delimiter = Self::char_opt(opts, "csv.delimiter")?;
+        let quote = Self::char_opt(opts, "csv.quote")?;
+
+        let include_header = opts
+            .remove("csv.include_header")
+            .filter(|t| t == "true")
+            .is_some();
+
+        Ok(Self {
+            delimiter,
+            quote,
+            include_header,
+        })
+    }
+
+    fn char_opt(opts: &mut HashMap<String, String>, name: &str) -> Result<Option<String>, String> {
+        let value = opts.remove(name);
+        if let Some(value) = &value {
+            if value.len() != 1 || !value.is_ascii() {
+                return Err(format!(
+                    "{} must be a single ASCII character, not '{}'",
+                    name, value
+                ));
+            }
+        }
+        Ok(value)
+    }
+
+    pub fn delimiter(&self) -> u8 {
+        self.delimiter
+            .as_ref()
+            .and_then(|d| d.bytes().next())
+            .unwrap_or(b',')
+    }
+
+    pub fn quote(&self) -> u8 {
+        self.quote
+            .as_ref()
+            .and_then(|q| q.bytes().next())
+            .unwrap_or(b'"')
+    }
+}
+
+/// XML documents, from which each element matched by the record path is read as a row. By
+/// default a column is read from the text of the record's child element of the same name, or
+/// else from its attribute of that name; other paths can be given for columns in `fields`. Values
+/// are parsed as the type of their column.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq, Hash, PartialOrd, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct XmlFormat {
+    /// The path of the elements to read as records, like `/rss/channel/item` or `//entry`
+    pub record_path: String,
+    /// Paths relative to the record to read columns from, by column name, like `author/name`,
+    /// `link/@href` or `.` (the record's own text)
+    #[serde(default)]
+    pub fields: BTreeMap<String, String>,
+}
+
+impl XmlFormat {
+    fn from_opts(opts: &mut HashMap<String, String>) -> Result<Self, String> {
+        let record_path = opts
+            .remove("xml.record_path")
+            .ok_or_else(|| "xml.record_path must be set for the xml format".to_string())?;
+        XmlPath::parse(&record_path)?;
+
+        let field_opts: Vec<String> = opts
+            .keys()
+            .filter(|k| k.starts_with("xml.fields."))
+            .cloned()
+            .collect();
+
+        let mut fields = BTreeMap::new();
+        for opt in field_opts {
+            let path = opts.remove(&opt).unwrap();
+            XmlPath::parse(&path)?;
+            fields.insert(opt["xml.fields.".len()..].to_string(), path);
+        }
+
+        Ok(Self {
+            record_path,
+            fields,
+        })
+    }
+}
+
+/// A path through an XML document, written in the subset of XPath made up of element names
+/// (or `*`), `.`, `//` for descendants and a final `@attribute` or `text()` step. Names are
+/// compared without their namespace, so `atom:entry` and `entry` match the same elements.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct XmlPath {
+    /// whether the path starts at the root of the document rather than the current element
+    pub absolute: bool,
+    pub steps: Vec<XmlStep>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum XmlStep {
+    /// the child elements with a name, or any name for `*`
+    Child(String),
+    /// the descendant elements with a name, or any name for `*`
+    Descendant(String),
+    Current,
+    Attribute(String),
+    Text,
+}
+
+impl XmlPath {
+    pub fn parse(path: &str) -> Result<Self, String> {
+        let invalid = |reason: String| format!("invalid XML path '{}': {}", path, reason);
+
+        let absolute = path.starts_with('/');
+        let mut rest = path;
+        let mut steps = vec![];
+
+        loop {
+            let descendant = if let Some(r) = rest.strip_prefix("//") {
+                rest = r;
+                true
+            } else {
+                rest = rest.strip_prefix('/').unwrap_or(rest);
+                false
+            };
+
+            let end = rest.find('/').unwrap_or(rest.len());
+            let (segment, r) = rest.split_at(end);
+            rest = r;
+
+            if matches!(steps.last(), Some(XmlStep::Attribute(_) | XmlStep::Text)) {
+                return Err(invalid(
+                    "attributes and text() can only be the last step".to_string(),
+                ));
+            }
+
+            steps.push(match segment {
+                "" => return Err(invalid("path has an empty step".to_string())),
+                "." if !descendant => XmlStep::Current,
+                "text()" if !descendant => XmlStep::Text,
+                s if !descendant && s.starts_with('@') && Self::is_name(&s[1..]) => {
+                    XmlStep::Attribute(Self::local_name(&s[1..]).to_string())
+                }
+                s if Self::is_name(s) && descendant => {
+                    XmlStep::Descendant(Self::local_name(s).to_string())
+                }
+                s if Self::is_name(s) => XmlStep::Child(Self::local_name(s).to_string()),
+                s => return Err(invalid(format!("unsupported step '{}'", s))),
+            });
+
+            if rest.is_empty() {
+                return Ok(Self { absolute, steps });
+            }
+        }
+    }
+
+    fn is_name(name: &str) -> bool {
+        name == "*"
+            || (!name.is_empty()
+                && !name.starts_with(|c: char| c.is_ascii_digit() || c == '-' || c == '.')
+                && name
+                    .chars()
+                    .all(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | '.' | ':')))
+    }
+
+    fn local_name(name: &str) -> &str {
+        name.rsplit(':').next().unwrap()
+    }
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq, Hash, PartialOrd, ToSchema)]
 pub struct ConfluentSchemaRegistryConfig {
     endpoint: String,
@@ -226,6 +423,8 @@ impl AvroFormat {
     }
 }
 
+/// Parquet files; when reading, each message must contain a whole file, whose columns are
+/// matched to the columns of the table by name
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq, Hash, PartialOrd, ToSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct ParquetFormat {}
@@ -261,6 +460,9 @@ pub enum Format {
     Parquet(ParquetFormat),
     RawString(RawStringFormat),
     RawBytes(RawBytesFormat),
+    ArrowIpc(ArrowIpcFormat),
+    Csv(CsvFormat),
+    Xml(XmlFormat),
 }
 
 impl Format {
@@ -277,6 +479,9 @@ impl Format {
             "raw_string" => Format::RawString(RawStringFormat {}),
             "raw_bytes" => Format::RawBytes(RawBytesFormat {}),
             "parquet" => Format::Parquet(ParquetFormat {}),
+            "arrow_ipc" => Format::ArrowIpc(ArrowIpcFormat {}),
+            "csv" => Format::Csv(CsvFormat::from_opts(opts)?),
+            "xml" => Format::Xml(XmlFormat::from_opts(opts)?),
             f => return Err(format!("Unknown format '{}'", f)),
         }))
     }
@@ -289,7 +494,7 @@ impl Format {
             | Format::Parquet(_)
             | Format::RawString(_)
             | Format::Protobuf(_) => false,
-            Format::RawBytes(_) => false,
+            Format::RawBytes(_) | Format::ArrowIpc(_) | Format::Csv(_) | Format::Xml(_) => false,
         }
     }
 }
@@ -323,27 +528,141 @@ impl BadData {
     }
 }
 
+/// The unit of a numeric timestamp, which counts from the Unix epoch
+#[derive(
+    Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, Hash, PartialOrd, ToSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum TimestampUnit {
+    Seconds,
+    #[default]
+    Millis,
+    Micros,
+    Nanos,
+}
+
+impl TimestampUnit {
+    pub fn nanos(&self) -> i64 {
+        match self {
+            TimestampUnit::Seconds => 1_000_000_000,
+            TimestampUnit::Millis => 1_000_000,
+            TimestampUnit::Micros => 1_000,
+            TimestampUnit::Nanos => 1,
+        }
+    }
+}
+
+/// Takes the event time of each row from a field of the data as it's deserialized, rather than
+/// from the time it was read
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, ToSchema)]
 #[serde(rename_all = "camelCase")]
-pub struct Framing {
-    pub method: FramingMethod,
+pub struct TimestampField {
+    pub field: String,
+    /// The strftime-style format of string timestamps; if unset, RFC 3339 and similar formats
+    /// are accepted
+    #[serde(default)]
+    pub format: Option<String>,
+    /// The unit of numeric timestamps
+    #[serde(default)]
+    pub unit: TimestampUnit,
 }
 
-impl Framing {
+impl TimestampField {
     pub fn from_opts(opts: &mut HashMap<String, String>) -> Result<Option<Self>, String> {
-        let Some(method) = opts.remove("framing") else {
+        let format = opts.remove("timestamp.format");
+        let unit = opts.remove("timestamp.unit");
+
+        let Some(field) = opts.remove("timestamp.field") else {
+            if format.is_some() || unit.is_some() {
+                return Err(
+                    "timestamp.format and timestamp.unit can only be set with timestamp.field"
+                        .to_string(),
+                );
+            }
             return Ok(None);
         };
 
-        let method = match method.as_str() {
-            "newline" => FramingMethod::Newline(NewlineDelimitedFraming::from_opts(opts)?),
-            f => return Err(format!("Unknown framing method '{}'", f)),
+        let unit = match unit.as_deref() {
+            None => TimestampUnit::default(),
+            Some("seconds") => TimestampUnit::Seconds,
+            Some("millis") => TimestampUnit::Millis,
+            Some("micros") => TimestampUnit::Micros,
+            Some("nanos") => TimestampUnit::Nanos,
+            Some(u) => {
+                return Err(format!(
+                    "unknown timestamp.unit '{}'; expected one of 'seconds', 'millis', 'micros' or 'nanos'",
+                    u
+                ))
+            }
         };
 
-        Ok(Some(Framing { method }))
+        Ok(Some(Self {
+            field,
+            format,
+            unit,
+        }))
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Framing {
+    /// How records are split within a message; if unset, each message is a single record
+    #[serde(default)]
+    pub method: Option<FramingMethod>,
+    /// The compression of each message, which is decompressed before it's split into records
+    #[serde(default)]
+    pub compression: Option<PayloadCompression>,
+}
+
+impl Framing {
+    pub fn from_opts(opts: &mut HashMap<String, String>) -> Result<Option<Self>, String> {
+        let method = opts
+            .remove("framing")
+            .map(|method| match method.as_str() {
+                "newline" => Ok(FramingMethod::Newline(NewlineDelimitedFraming::from_opts(
+                    opts,
+                )?)),
+                f => Err(format!("Unknown framing method '{}'", f)),
+            })
+            .transpose()?;
+
+        let compression = opts
+            .remove("compression")
+            .map(|c| match c.as_str() {
+                "gzip" => Ok(PayloadCompression::Gzip),
+                "zstd" => Ok(PayloadCompression::Zstd),
+                "snappy" => Ok(PayloadCompression::Snappy),
+                "lz4" => Ok(PayloadCompression::Lz4),
+                c => Err(format!(
+                    "Unknown compression '{}'; expected one of 'gzip', 'zstd', 'snappy' or 'lz4'",
+                    c
+                )),
+            })
+            .transpose()?;
+
+        if method.is_none() && compression.is_none() {
+            return Ok(None);
+        }
+
+        Ok(Some(Framing {
+            method,
+            compression,
+        }))
     }
 }
 
+/// A compression codec applied to whole messages, using the streaming (framed) format of
+/// snappy and lz4
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum PayloadCompression {
+    Gzip,
+    Zstd,
+    Snappy,
+    Lz4,
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, ToSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct NewlineDelimitedFraming {
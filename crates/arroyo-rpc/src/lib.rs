@@ -1,6 +1,8 @@
 pub mod api_types;
 pub mod formats;
+pub mod protocol;
 pub mod public_ids;
+pub mod resilience;
 pub mod schema_resolver;
 pub mod var_str;
 
@@ -10,13 +12,13 @@ use std::{fs, time::SystemTime};
 
 use crate::api_types::connections::PrimitiveType;
 use crate::formats::{BadData, Format, Framing};
-use crate::grpc::rpc::{LoadCompactedDataReq, SubtaskCheckpointMetadata};
+use crate::grpc::rpc::{LoadCompactedDataReq, StateEntry, SubtaskCheckpointMetadata};
 use anyhow::Result;
 use arrow::row::{OwnedRow, RowConverter, Rows, SortField};
 use arrow_array::{Array, ArrayRef, BooleanArray};
 use arrow_schema::{DataType, Field, Fields};
 use arroyo_types::{CheckpointBarrier, HASH_SEEDS};
-use grpc::rpc::{StopMode, TableCheckpointMetadata, TaskCheckpointEventType};
+use grpc::rpc::{SourceLagUnit, StopMode, TableCheckpointMetadata, TaskCheckpointEventType};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use tokio::sync::mpsc::{channel, Receiver, Sender};
@@ -39,6 +41,11 @@ pub mod grpc {
         tonic::include_proto!("api");
     }
 
+    pub mod connector {
+        #![allow(clippy::derive_partial_eq_without_eq)]
+        tonic::include_proto!("arroyo_connector");
+    }
+
     pub const API_FILE_DESCRIPTOR_SET: &[u8] =
         tonic::include_file_descriptor_set!("api_descriptor");
 }
@@ -70,9 +77,22 @@ pub enum ControlMessage {
     LoadCompacted {
         compacted: CompactionResult,
     },
+    QueryState {
+        query: StateQuery,
+    },
     NoOp,
 }
 
+/// A lookup of entries in one of an operator's tables, answered by each subtask that receives it
+/// with the matching entries of its state
+#[derive(Debug, Clone)]
+pub struct StateQuery {
+    pub table: String,
+    pub key: Option<String>,
+    pub limit: usize,
+    pub tx: Sender<Result<Vec<StateEntry>, String>>,
+}
+
 #[derive(Debug, Clone)]
 pub struct CompactionResult {
     pub operator_id: String,
@@ -121,6 +141,8 @@ pub enum ControlResp {
         operator_id: String,
         task_index: usize,
         error: String,
+        /// whether the task failed with a [arroyo_types::NonRetryableError]
+        fatal: bool,
     },
     Error {
         operator_id: String,
@@ -128,6 +150,29 @@ pub enum ControlResp {
         message: String,
         details: String,
     },
+    SourceLag {
+        operator_id: String,
+        task_index: usize,
+        lag: u64,
+        // the lag of each split the subtask assigns by lag, which is empty for sources that
+        // can't rebalance their splits
+        split_lag: HashMap<String, u64>,
+        unit: SourceLagUnit,
+    },
+}
+
+impl ControlResp {
+    pub fn operator_id(&self) -> &str {
+        match self {
+            ControlResp::CheckpointEvent(c) => &c.operator_id,
+            ControlResp::CheckpointCompleted(c) => &c.operator_id,
+            ControlResp::TaskStarted { operator_id, .. }
+            | ControlResp::TaskFinished { operator_id, .. }
+            | ControlResp::TaskFailed { operator_id, .. }
+            | ControlResp::Error { operator_id, .. }
+            | ControlResp::SourceLag { operator_id, .. } => operator_id,
+        }
+    }
 }
 
 pub struct FileAuthInterceptor {
@@ -224,6 +269,8 @@ pub fn error_chain(e: anyhow::Error) -> String {
 
 pub const TIMESTAMP_FIELD: &str = "_timestamp";
 pub const UPDATING_META_FIELD: &str = "_updating_meta";
+/// Hidden column carrying the W3C trace context of each row, for tables with `traceparent = 'true'`
+pub const TRACEPARENT_FIELD: &str = "_traceparent";
 
 pub fn updating_meta_fields() -> Fields {
     static UPDATING_META_FIELDS: OnceLock<Fields> = OnceLock::new();
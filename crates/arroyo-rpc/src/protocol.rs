@@ -0,0 +1,75 @@
+//! Versioning for the protocol spoken between the controller and workers, and between workers
+//! on the data plane.
+//!
+//! Each process advertises the newest version it speaks along with the oldest it can still
+//! fall back to, and the two sides settle on the newest version they have in common. This lets
+//! a cluster run a mix of releases during a rolling upgrade. Processes from before versioning
+//! was introduced don't send a version, which is treated as version 1.
+//!
+//! Versions:
+//! * 1: the original, unversioned protocol
+//! * 2: data-plane connections start with a handshake carrying the sender's versions
+//! * 3: workers batch checkpoint events and completions into `TaskCheckpointMessages` requests,
+//!   and gzip-compress their requests to the controller
+//! * 4: workers can reset a finished execution and start a new one, which lets the controller
+//!   rescale a job on the workers it's already running on
+//! * 5: workers can restart a region of a running job from a checkpoint, and data-plane
+//!   handshakes carry the generation of the region's tasks
+//! * 6: checkpoint barriers are sent over data-plane connections ahead of the data queued
+//!   before them, followed by the number of data messages they overtook
+
+use anyhow::bail;
+
+/// The newest protocol version spoken by this build
+pub const PROTOCOL_VERSION: u32 = 6;
+
+/// The oldest protocol version this build can fall back to; increase this when removing
+/// support for an older version
+pub const MIN_PROTOCOL_VERSION: u32 = 1;
+
+/// Returns the newest protocol version supported by both this process and a peer that speaks
+/// versions `peer_min_version` through `peer_version`
+pub fn negotiate_protocol_version(peer_version: u32, peer_min_version: u32) -> anyhow::Result<u32> {
+    let peer_version = peer_version.max(1);
+    let peer_min_version = peer_min_version.clamp(1, peer_version);
+
+    let version = PROTOCOL_VERSION.min(peer_version);
+
+    if version < MIN_PROTOCOL_VERSION || version < peer_min_version {
+        bail!(
+            "incompatible protocol versions: this process supports versions {}-{}, but the \
+            peer supports versions {}-{}; upgrade the older of the two to a compatible release",
+            MIN_PROTOCOL_VERSION,
+            PROTOCOL_VERSION,
+            peer_min_version,
+            peer_version
+        );
+    }
+
+    Ok(version)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_negotiate_protocol_version() {
+        // peers that predate versioning
+        assert_eq!(negotiate_protocol_version(0, 0).unwrap(), 1);
+
+        assert_eq!(
+            negotiate_protocol_version(PROTOCOL_VERSION, MIN_PROTOCOL_VERSION).unwrap(),
+            PROTOCOL_VERSION
+        );
+
+        // newer peers fall back to our version if they still support it
+        assert_eq!(
+            negotiate_protocol_version(PROTOCOL_VERSION + 5, PROTOCOL_VERSION).unwrap(),
+            PROTOCOL_VERSION
+        );
+
+        // but not if they've dropped support for it
+        assert!(negotiate_protocol_version(PROTOCOL_VERSION + 5, PROTOCOL_VERSION + 1).is_err());
+    }
+}
@@ -0,0 +1,295 @@
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::future::Future;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use prometheus::{register_int_counter_vec, register_int_gauge_vec, IntCounterVec, IntGaugeVec};
+use rand::Rng;
+use tracing::{error, warn};
+
+use crate::config::{config, ResilienceConfig};
+
+fn retries_counter() -> &'static IntCounterVec {
+    static COUNTER: OnceLock<IntCounterVec> = OnceLock::new();
+    COUNTER.get_or_init(|| {
+        register_int_counter_vec!(
+            "arroyo_external_call_retries",
+            "number of failed calls to external services that were retried",
+            &["service"]
+        )
+        .unwrap()
+    })
+}
+
+fn failures_counter() -> &'static IntCounterVec {
+    static COUNTER: OnceLock<IntCounterVec> = OnceLock::new();
+    COUNTER.get_or_init(|| {
+        register_int_counter_vec!(
+            "arroyo_external_call_failures",
+            "number of calls to external services that failed after exhausting their retries",
+            &["service"]
+        )
+        .unwrap()
+    })
+}
+
+fn circuit_open_gauge() -> &'static IntGaugeVec {
+    static GAUGE: OnceLock<IntGaugeVec> = OnceLock::new();
+    GAUGE.get_or_init(|| {
+        register_int_gauge_vec!(
+            "arroyo_external_circuit_open",
+            "whether the circuit breaker for an external service is open",
+            &["service", "target"]
+        )
+        .unwrap()
+    })
+}
+
+#[derive(Debug, Default)]
+struct Breaker {
+    consecutive_failures: u32,
+    open_until: Option<Instant>,
+}
+
+/// Makes calls to an external service, like an object store or schema registry, retrying
+/// those that fail with retryable errors. All clients for the same target share a circuit
+/// breaker (see [ResilientClient::shared]), so that while a service is down callers back off
+/// together instead of each retrying against it; once the breaker's reset timeout passes, calls
+/// are let through again, and the first failure re-opens it.
+#[derive(Debug)]
+pub struct ResilientClient {
+    service: &'static str,
+    target: String,
+    config: ResilienceConfig,
+    breaker: Mutex<Breaker>,
+}
+
+impl ResilientClient {
+    pub fn new(service: &'static str, target: impl Into<String>, config: ResilienceConfig) -> Self {
+        Self {
+            service,
+            target: target.into(),
+            config,
+            breaker: Mutex::new(Breaker::default()),
+        }
+    }
+
+    /// Returns the client for `target` (like a bucket URL or registry endpoint) of `service`,
+    /// creating it with the configured retry policy if this is the first
+    pub fn shared(service: &'static str, target: &str) -> Arc<Self> {
+        static CLIENTS: OnceLock<Mutex<HashMap<(&'static str, String), Arc<ResilientClient>>>> =
+            OnceLock::new();
+
+        CLIENTS
+            .get_or_init(Default::default)
+            .lock()
+            .unwrap()
+            .entry((service, target.to_string()))
+            .or_insert_with(|| Arc::new(Self::new(service, target, config().resilience.clone())))
+            .clone()
+    }
+
+    /// How long to wait before the `retry`th retry (starting from 1)
+    fn backoff(&self, retry: u32) -> Duration {
+        let delay = (*self.config.max_delay).min(
+            self.config
+                .base_delay
+                .saturating_mul(2u32.saturating_pow(retry)),
+        );
+
+        let jitter = self.config.jitter.clamp(0.0, 1.0);
+        delay.mul_f64(1.0 - jitter) + delay.mul_f64(jitter * rand::thread_rng().gen::<f64>())
+    }
+
+    /// If the circuit breaker is open, how long until it lets calls through
+    fn open_for(&self) -> Option<Duration> {
+        let breaker = self.breaker.lock().unwrap();
+        let remaining = breaker.open_until?.checked_duration_since(Instant::now())?;
+        (!remaining.is_zero()).then_some(remaining)
+    }
+
+    fn record_success(&self) {
+        let mut breaker = self.breaker.lock().unwrap();
+        if breaker.open_until.take().is_some() {
+            circuit_open_gauge()
+                .with_label_values(&[self.service, &self.target])
+                .set(0);
+        }
+        breaker.consecutive_failures = 0;
+    }
+
+    fn record_failure(&self) {
+        let mut breaker = self.breaker.lock().unwrap();
+        breaker.consecutive_failures += 1;
+        if breaker.consecutive_failures >= self.config.failure_threshold.max(1) {
+            if breaker.open_until.is_none() {
+                warn!(
+                    "{} at {} has failed {} times in a row; backing off for {:?}",
+                    self.service,
+                    self.target,
+                    breaker.consecutive_failures,
+                    *self.config.reset_timeout
+                );
+                circuit_open_gauge()
+                    .with_label_values(&[self.service, &self.target])
+                    .set(1);
+            }
+            breaker.open_until = Some(Instant::now() + *self.config.reset_timeout);
+        }
+    }
+
+    /// Calls `f` until it succeeds, fails with an error that `is_retryable` rejects, or has been
+    /// retried the configured number of times, returning its last result. Errors that aren't
+    /// retryable (like a missing object) show that the service is up, so they don't count
+    /// towards opening the circuit breaker.
+    pub async fn call<T, E, F, Fut>(
+        &self,
+        operation: &str,
+        is_retryable: impl Fn(&E) -> bool,
+        mut f: F,
+    ) -> Result<T, E>
+    where
+        E: Display,
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+    {
+        let mut retries = 0;
+        loop {
+            if let Some(wait) = self.open_for() {
+                tokio::time::sleep(wait).await;
+            }
+
+            match f().await {
+                Ok(value) => {
+                    self.record_success();
+                    return Ok(value);
+                }
+                Err(e) if !is_retryable(&e) => {
+                    self.record_success();
+                    return Err(e);
+                }
+                Err(e) => {
+                    self.record_failure();
+
+                    if retries >= self.config.max_retries {
+                        failures_counter().with_label_values(&[self.service]).inc();
+                        error!(
+                            "{} on {} at {} failed after {} retries: {}",
+                            operation, self.service, self.target, retries, e
+                        );
+                        return Err(e);
+                    }
+
+                    retries += 1;
+                    retries_counter().with_label_values(&[self.service]).inc();
+                    warn!(
+                        "{} on {} at {} failed: {}; retrying ({}/{})",
+                        operation, self.service, self.target, e, retries, self.config.max_retries
+                    );
+
+                    tokio::time::sleep(self.backoff(retries)).await;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ResilientClient;
+    use crate::config::ResilienceConfig;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::time::Duration;
+
+    fn client(max_retries: u32, failure_threshold: u32) -> ResilientClient {
+        ResilientClient::new(
+            "test",
+            "target",
+            ResilienceConfig {
+                max_retries,
+                base_delay: "1ms".parse().unwrap(),
+                max_delay: "4ms".parse().unwrap(),
+                jitter: 0.5,
+                failure_threshold,
+                reset_timeout: "50ms".parse().unwrap(),
+            },
+        )
+    }
+
+    #[test]
+    fn test_backoff() {
+        let client = client(10, 100);
+        for retry in 1..10 {
+            let delay = client.backoff(retry);
+            let max = Duration::from_millis(4).min(Duration::from_millis(1 << retry));
+            assert!(delay >= max / 2 && delay <= max, "{:?}", delay);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_retries() {
+        let client = client(3, 100);
+        let calls = AtomicU32::new(0);
+
+        let result: Result<u32, String> = client
+            .call(
+                "get",
+                |_| true,
+                || async {
+                    match calls.fetch_add(1, Ordering::SeqCst) {
+                        0 | 1 => Err("unavailable".to_string()),
+                        n => Ok(n),
+                    }
+                },
+            )
+            .await;
+        assert_eq!(result, Ok(2));
+
+        calls.store(0, Ordering::SeqCst);
+        let result: Result<(), String> = client
+            .call(
+                "get",
+                |_| true,
+                || async {
+                    calls.fetch_add(1, Ordering::SeqCst);
+                    Err("unavailable".to_string())
+                },
+            )
+            .await;
+        assert_eq!(result, Err("unavailable".to_string()));
+        assert_eq!(calls.load(Ordering::SeqCst), 4);
+
+        // errors that aren't retryable are returned immediately
+        calls.store(0, Ordering::SeqCst);
+        let result: Result<(), String> = client
+            .call(
+                "get",
+                |e: &String| e != "not found",
+                || async {
+                    calls.fetch_add(1, Ordering::SeqCst);
+                    Err("not found".to_string())
+                },
+            )
+            .await;
+        assert_eq!(result, Err("not found".to_string()));
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_circuit_breaker() {
+        let client = client(0, 2);
+
+        for _ in 0..2 {
+            let _: Result<(), &str> = client.call("get", |_| true, || async { Err("down") }).await;
+        }
+        assert!(client.open_for().is_some());
+
+        // calls wait for the breaker to close before they're made
+        let start = tokio::time::Instant::now();
+        let result: Result<(), &str> = client.call("get", |_| true, || async { Ok(()) }).await;
+        assert_eq!(result, Ok(()));
+        assert!(start.elapsed() >= Duration::from_millis(40));
+        assert!(client.open_for().is_none());
+    }
+}
@@ -1,3 +1,4 @@
+use crate::resilience::ResilientClient;
 use crate::var_str::VarStr;
 use ahash::{HashSet, HashSetExt};
 use anyhow::{anyhow, bail, Context};
@@ -10,10 +11,11 @@ use futures::stream::FuturesUnordered;
 use futures::StreamExt;
 use percent_encoding::{percent_encode, NON_ALPHANUMERIC};
 use reqwest::header::{HeaderMap, HeaderValue};
-use reqwest::{Client, StatusCode, Url};
+use reqwest::{Client, RequestBuilder, Response, StatusCode, Url};
 use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use std::io::Write;
+use std::sync::Arc;
 use std::time::Duration;
 use tracing::warn;
 
@@ -132,9 +134,19 @@ struct RegistryErrorResponse {
     message: String,
 }
 
+/// Whether a failed request to the schema registry may succeed if retried
+fn is_retryable(e: &reqwest::Error) -> bool {
+    e.is_connect()
+        || e.is_timeout()
+        || e.status().is_some_and(|status| {
+            status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS
+        })
+}
+
 pub struct ConfluentSchemaRegistryClient {
     endpoint: Url,
     client: Client,
+    resilience: Arc<ResilientClient>,
 }
 
 impl ConfluentSchemaRegistryClient {
@@ -171,31 +183,54 @@ impl ConfluentSchemaRegistryClient {
             .map_err(|_| anyhow!("{} is not a valid url", endpoint))?;
 
         Ok(Self {
+            resilience: ResilientClient::shared("schema_registry", endpoint.as_str()),
             endpoint,
             client: client.build()?,
         })
     }
 
-    async fn get_schema_for_url<T: DeserializeOwned>(&self, url: Url) -> anyhow::Result<Option<T>> {
-        let resp = self.client.get(url.clone()).send().await.map_err(|e| {
-            warn!("Got error response from schema registry: {:?}", e);
-            match e.status() {
-                Some(StatusCode::NOT_FOUND) => {
-                    anyhow!("schema not found")
+    /// Sends a request built by `request`, retrying it if the registry can't be reached or
+    /// responds with a server error
+    async fn send(
+        &self,
+        operation: &str,
+        request: impl Fn() -> RequestBuilder,
+    ) -> reqwest::Result<Response> {
+        self.resilience
+            .call(operation, is_retryable, || async {
+                let resp = request().send().await?;
+                let status = resp.status();
+                if status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS {
+                    return Err(resp.error_for_status().unwrap_err());
                 }
-                Some(code) => anyhow!("schema registry returned error: {}", code),
-                None => {
-                    warn!(
-                        "unknown error connecting to schema registry {}: {:?}",
-                        self.endpoint, e
-                    );
-                    anyhow!(
-                        "could not connect to Schema Registry at {}: unknown error",
-                        self.endpoint
-                    )
+                Ok(resp)
+            })
+            .await
+    }
+
+    async fn get_schema_for_url<T: DeserializeOwned>(&self, url: Url) -> anyhow::Result<Option<T>> {
+        let resp = self
+            .send("fetch schema", || self.client.get(url.clone()))
+            .await
+            .map_err(|e| {
+                warn!("Got error response from schema registry: {:?}", e);
+                match e.status() {
+                    Some(StatusCode::NOT_FOUND) => {
+                        anyhow!("schema not found")
+                    }
+                    Some(code) => anyhow!("schema registry returned error: {}", code),
+                    None => {
+                        warn!(
+                            "unknown error connecting to schema registry {}: {:?}",
+                            self.endpoint, e
+                        );
+                        anyhow!(
+                            "could not connect to Schema Registry at {}: unknown error",
+                            self.endpoint
+                        )
+                    }
                 }
-            }
-        })?;
+            })?;
 
         let status = resp.status();
         if !status.is_success() {
@@ -238,13 +273,21 @@ impl ConfluentSchemaRegistryClient {
             schema_type,
         };
 
-        let resp = self.client.post(url).json(&req).send().await.map_err(|e| {
-            warn!("Got error response writing to schema registry: {:?}", e);
-            anyhow!(
-                "Could not connect to Schema Registry at {}: unknown error",
-                self.endpoint
-            )
-        })?;
+        let resp = self
+            .send("register schema", || {
+                self.client.post(url.clone()).json(&req)
+            })
+            .await
+            .map_err(|e| {
+                warn!("Got error response writing to schema registry: {:?}", e);
+                match e.status() {
+                    Some(code) => anyhow!("schema registry returned error: {}", code),
+                    None => anyhow!(
+                        "Could not connect to Schema Registry at {}: unknown error",
+                        self.endpoint
+                    ),
+                }
+            })?;
 
         if !resp.status().is_success() {
             let status = resp.status();
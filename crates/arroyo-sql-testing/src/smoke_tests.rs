@@ -280,6 +280,7 @@ async fn run_and_checkpoint(job_id: Arc<String>, program: Program, checkpoint_in
     let (running_engine, mut control_rx) = engine
         .start(StreamConfig {
             restore_epoch: None,
+            source_offset_overrides: HashMap::new(),
         })
         .await;
     info!("Smoke test checkpointing enabled");
@@ -324,6 +325,7 @@ async fn finish_from_checkpoint(job_id: &str, program: Program) {
     let (running_engine, mut control_rx) = engine
         .start(StreamConfig {
             restore_epoch: Some(3),
+            source_offset_overrides: HashMap::new(),
         })
         .await;
 
@@ -396,6 +398,7 @@ async fn run_completely(
     let (running_engine, mut control_rx) = engine
         .start(StreamConfig {
             restore_epoch: None,
+            source_offset_overrides: HashMap::new(),
         })
         .await;
 
@@ -649,9 +652,11 @@ async fn get_graph(query_string: String, udfs: &[LocalUdf]) -> Result<LogicalPro
         schema_provider,
         SqlConfig {
             default_parallelism: 1,
+            ..Default::default()
         },
     )
     .await?
+    .into_pipeline()?
     .program;
     Ok(program)
 }
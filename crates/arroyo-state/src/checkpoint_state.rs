@@ -15,14 +15,14 @@ use arroyo_rpc::grpc::{
         TableSubtaskCheckpointMetadata, TaskCheckpointCompletedReq, TaskCheckpointEventReq,
     },
 };
-use arroyo_types::{from_micros, to_micros};
+use arroyo_types::{from_micros, to_micros, KEY_GROUPS};
 use tracing::{debug, warn};
 
 use crate::{
     committing_state::CommittingState,
     tables::{
         expiring_time_key_map::ExpiringTimeKeyTable, global_keyed_map::GlobalKeyedTable,
-        ErasedTable,
+        rocksdb_keyed_map::RocksDbKeyedTable, ErasedTable,
     },
     BackingStore, StateBackend,
 };
@@ -141,6 +141,11 @@ impl TableState {
                 self.subtask_tables,
             )
             .expect("should be able to merge checkpoint metadatas"),
+            TableEnum::RocksDbKeyValue => RocksDbKeyedTable::merge_checkpoint_metadata(
+                self.table_config.clone(),
+                self.subtask_tables,
+            )
+            .expect("should be able to merge checkpoint metadatas"),
         }
         .map(|metadata| (self.table_config, metadata))
     }
@@ -314,6 +319,9 @@ impl CheckpointState {
                     TableEnum::ExpiringKeyedTimeTable => {
                         ExpiringTimeKeyTable::committing_data(config.clone(), checkpoint_metadata)
                     }
+                    TableEnum::RocksDbKeyValue => {
+                        RocksDbKeyedTable::committing_data(config.clone(), checkpoint_metadata)
+                    }
                 } {
                     for i in 0..operator_state.subtasks_checkpointed {
                         self.subtasks_to_commit
@@ -337,6 +345,7 @@ impl CheckpointState {
                     min_watermark,
                     max_watermark,
                     parallelism: operator_state.subtasks_checkpointed as u64,
+                    key_groups: KEY_GROUPS as u32,
                 }),
             })
             .await
@@ -349,6 +358,15 @@ impl CheckpointState {
         self.operators == self.operators_checkpointed
     }
 
+    /// Total bytes written by all subtasks that have completed this checkpoint
+    pub fn bytes(&self) -> u64 {
+        self.operator_details
+            .values()
+            .flat_map(|op| op.tasks.values())
+            .filter_map(|t| t.bytes)
+            .sum()
+    }
+
     pub fn committing_state(&self) -> CommittingState {
         CommittingState::new(
             self.checkpoint_id.clone(),
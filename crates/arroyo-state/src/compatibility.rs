@@ -0,0 +1,614 @@
+//! Checks whether the state in a checkpoint can be restored by the current version of an
+//! operator, whose tables may have changed since the checkpoint was taken (for example because
+//! the query was edited).
+//!
+//! Columns may be added to a table as long as they are nullable; they will be filled with
+//! nulls for data restored from the checkpoint. Any other change to a table's schema is
+//! incompatible, and is reported precisely so that users can tell what part of their edit
+//! prevents the pipeline from being restored.
+//!
+//! Before an update is applied, [`diff_programs`] compares the old and new plans operator by
+//! operator, giving a verdict for each based on what can be determined without constructing
+//! the operators; changes that can only be checked against their tables are reported as
+//! unknown, and are checked when the updated pipeline is restored.
+
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+
+use anyhow::{anyhow, bail, Result};
+use arrow::array::new_null_array;
+use arrow_array::RecordBatch;
+use arrow_schema::{DataType, Schema, SchemaRef};
+use arroyo_datastream::logical::{LogicalEdge, LogicalNode, LogicalProgram, OperatorName};
+use arroyo_rpc::api_types::pipelines::{
+    OperatorChange, OperatorDiff, PipelineDiff, StateCompatibility,
+};
+use arroyo_rpc::df::ArroyoSchema;
+use arroyo_rpc::grpc::api::ConnectorOp;
+use arroyo_rpc::grpc::rpc::{ExpiringKeyedTimeTableConfig, TableConfig, TableEnum};
+use prost::Message;
+
+/// A change to a table that prevents its checkpointed state from being restored
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Incompatibility {
+    TableRemoved,
+    TableTypeChanged {
+        old: TableEnum,
+        new: TableEnum,
+    },
+    GenerationalChanged,
+    ColumnRemoved(String),
+    ColumnTypeChanged {
+        column: String,
+        old: DataType,
+        new: DataType,
+    },
+    ColumnNoLongerNullable(String),
+    NonNullableColumnAdded(String),
+    KeysChanged {
+        old: Vec<String>,
+        new: Vec<String>,
+    },
+    TimestampChanged {
+        old: String,
+        new: String,
+    },
+}
+
+impl Display for Incompatibility {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Incompatibility::TableRemoved => write!(f, "table is no longer used by the operator"),
+            Incompatibility::TableTypeChanged { old, new } => write!(
+                f,
+                "table type changed from {} to {}",
+                old.as_str_name(),
+                new.as_str_name()
+            ),
+            Incompatibility::GenerationalChanged => {
+                write!(f, "table changed whether it stores generations")
+            }
+            Incompatibility::ColumnRemoved(column) => write!(f, "column '{}' was removed", column),
+            Incompatibility::ColumnTypeChanged { column, old, new } => write!(
+                f,
+                "column '{}' changed type from {} to {}",
+                column, old, new
+            ),
+            Incompatibility::ColumnNoLongerNullable(column) => {
+                write!(f, "column '{}' is no longer nullable", column)
+            }
+            Incompatibility::NonNullableColumnAdded(column) => write!(
+                f,
+                "column '{}' was added but is not nullable, so it can't be filled for existing data",
+                column
+            ),
+            Incompatibility::KeysChanged { old, new } => write!(
+                f,
+                "key columns changed from ({}) to ({})",
+                old.join(", "),
+                new.join(", ")
+            ),
+            Incompatibility::TimestampChanged { old, new } => write!(
+                f,
+                "timestamp column changed from '{}' to '{}'",
+                old, new
+            ),
+        }
+    }
+}
+
+/// Compares the columns of two schemas, matching them by name
+pub fn check_schema_compatibility(old: &Schema, new: &Schema) -> Vec<Incompatibility> {
+    let mut incompatibilities = vec![];
+
+    for old_field in old.fields() {
+        match new.field_with_name(old_field.name()) {
+            Ok(new_field) => {
+                if old_field.data_type() != new_field.data_type() {
+                    incompatibilities.push(Incompatibility::ColumnTypeChanged {
+                        column: old_field.name().clone(),
+                        old: old_field.data_type().clone(),
+                        new: new_field.data_type().clone(),
+                    });
+                } else if old_field.is_nullable() && !new_field.is_nullable() {
+                    incompatibilities.push(Incompatibility::ColumnNoLongerNullable(
+                        old_field.name().clone(),
+                    ));
+                }
+            }
+            Err(_) => {
+                incompatibilities.push(Incompatibility::ColumnRemoved(old_field.name().clone()))
+            }
+        }
+    }
+
+    for new_field in new.fields() {
+        if old.field_with_name(new_field.name()).is_err() && !new_field.is_nullable() {
+            incompatibilities.push(Incompatibility::NonNullableColumnAdded(
+                new_field.name().clone(),
+            ));
+        }
+    }
+
+    incompatibilities
+}
+
+fn key_names(schema: &ArroyoSchema) -> Vec<String> {
+    schema
+        .key_indices
+        .iter()
+        .flatten()
+        .map(|i| schema.schema.field(*i).name().clone())
+        .collect()
+}
+
+fn expiring_table_schema(config: &ExpiringKeyedTimeTableConfig) -> Result<ArroyoSchema> {
+    config
+        .schema
+        .clone()
+        .ok_or_else(|| anyhow!("table {} is missing its schema", config.table_name))?
+        .try_into()
+}
+
+/// Compares the config of a table in a checkpoint with its current config
+pub fn check_table_compatibility(
+    old: &TableConfig,
+    new: &TableConfig,
+) -> Result<Vec<Incompatibility>> {
+    if old.table_type() != new.table_type() {
+        return Ok(vec![Incompatibility::TableTypeChanged {
+            old: old.table_type(),
+            new: new.table_type(),
+        }]);
+    }
+
+    match new.table_type() {
+        TableEnum::MissingTableType => bail!("should have table type"),
+        // keyed tables store opaque serialized values, and are checked by the operators that
+        // deserialize them
+        TableEnum::GlobalKeyValue | TableEnum::RocksDbKeyValue => Ok(vec![]),
+        TableEnum::ExpiringKeyedTimeTable => {
+            let old = ExpiringKeyedTimeTableConfig::decode(&old.config[..])?;
+            let new = ExpiringKeyedTimeTableConfig::decode(&new.config[..])?;
+
+            let mut incompatibilities = vec![];
+            if old.generational != new.generational {
+                incompatibilities.push(Incompatibility::GenerationalChanged);
+            }
+
+            let old = expiring_table_schema(&old)?;
+            let new = expiring_table_schema(&new)?;
+
+            incompatibilities.extend(check_schema_compatibility(&old.schema, &new.schema));
+
+            let (old_keys, new_keys) = (key_names(&old), key_names(&new));
+            if old_keys != new_keys {
+                incompatibilities.push(Incompatibility::KeysChanged {
+                    old: old_keys,
+                    new: new_keys,
+                });
+            }
+
+            let old_timestamp = old.schema.field(old.timestamp_index).name();
+            let new_timestamp = new.schema.field(new.timestamp_index).name();
+            if old_timestamp != new_timestamp {
+                incompatibilities.push(Incompatibility::TimestampChanged {
+                    old: old_timestamp.clone(),
+                    new: new_timestamp.clone(),
+                });
+            }
+
+            Ok(incompatibilities)
+        }
+    }
+}
+
+/// Checks that the tables an operator has in a checkpoint can be restored into its current
+/// tables, returning an error describing every incompatibility if they can't
+pub fn check_operator_compatibility(
+    operator_id: &str,
+    checkpointed: &HashMap<String, TableConfig>,
+    current: &HashMap<String, TableConfig>,
+) -> Result<()> {
+    let mut tables: Vec<_> = checkpointed.iter().collect();
+    tables.sort_by_key(|(name, _)| *name);
+
+    let mut errors = vec![];
+    for (table_name, old) in tables {
+        let incompatibilities = match current.get(table_name) {
+            Some(new) => check_table_compatibility(old, new)?,
+            None => vec![Incompatibility::TableRemoved],
+        };
+
+        errors.extend(
+            incompatibilities
+                .into_iter()
+                .map(|i| format!("  * table '{}': {}", table_name, i)),
+        );
+    }
+
+    if !errors.is_empty() {
+        bail!(
+            "state for operator {} in the checkpoint is incompatible with the current pipeline:\n{}",
+            operator_id,
+            errors.join("\n")
+        );
+    }
+
+    Ok(())
+}
+
+fn is_stateless(operator: OperatorName) -> bool {
+    matches!(operator, OperatorName::ArrowValue | OperatorName::ArrowKey)
+}
+
+fn operator_label(node: &LogicalNode) -> String {
+    match node.operator_name {
+        OperatorName::ConnectorSource | OperatorName::ConnectorSink => {
+            ConnectorOp::decode(&node.operator_config[..])
+                .map(|op| op.connector)
+                .unwrap_or_else(|_| node.operator_name.to_string())
+        }
+        op => op.to_string(),
+    }
+}
+
+fn added_or_removed(node: &LogicalNode, change: OperatorChange) -> OperatorDiff {
+    let stateless = is_stateless(node.operator_name);
+    let (old_description, new_description, reason) = match change {
+        OperatorChange::Added => (
+            None,
+            Some(node.description.clone()),
+            "operator starts with empty state",
+        ),
+        _ => (
+            Some(node.description.clone()),
+            None,
+            "operator's state in the checkpoint will be discarded",
+        ),
+    };
+
+    OperatorDiff {
+        node_id: node.operator_id.clone(),
+        operator: operator_label(node),
+        change,
+        old_description,
+        new_description,
+        changes: vec![],
+        state: if stateless {
+            StateCompatibility::Stateless
+        } else {
+            StateCompatibility::Compatible
+        },
+        reasons: if stateless {
+            vec![]
+        } else {
+            vec![reason.to_string()]
+        },
+    }
+}
+
+fn input_ids(inputs: &[(&LogicalNode, &LogicalEdge)]) -> Vec<String> {
+    inputs.iter().map(|(n, _)| n.operator_id.clone()).collect()
+}
+
+fn diff_operator(
+    old_program: &LogicalProgram,
+    old: &LogicalNode,
+    new_program: &LogicalProgram,
+    new: &LogicalNode,
+) -> OperatorDiff {
+    let mut changes = vec![];
+    let mut incompatible = vec![];
+    let mut unknown = vec![];
+
+    let (old_label, new_label) = (operator_label(old), operator_label(new));
+    if old.operator_name != new.operator_name || old_label != new_label {
+        let change = format!("operator changed from {} to {}", old_label, new_label);
+        changes.push(change.clone());
+        incompatible.push(change);
+    }
+
+    if old.description != new.description {
+        changes.push(format!(
+            "description changed from '{}' to '{}'",
+            old.description, new.description
+        ));
+    }
+
+    if old.parallelism != new.parallelism {
+        changes.push(format!(
+            "parallelism changed from {} to {}",
+            old.parallelism, new.parallelism
+        ));
+    }
+
+    let old_inputs = old_program.inputs(&old.operator_id);
+    let new_inputs = new_program.inputs(&new.operator_id);
+    let (old_input_ids, new_input_ids) = (input_ids(&old_inputs), input_ids(&new_inputs));
+    if old_input_ids != new_input_ids {
+        let change = format!(
+            "inputs changed from ({}) to ({})",
+            old_input_ids.join(", "),
+            new_input_ids.join(", ")
+        );
+        changes.push(change.clone());
+        unknown.push(change);
+    }
+
+    for (old_input, old_edge) in &old_inputs {
+        let Some((_, new_edge)) = new_inputs
+            .iter()
+            .find(|(n, _)| n.operator_id == old_input.operator_id)
+        else {
+            continue;
+        };
+
+        if old_edge.schema.schema != new_edge.schema.schema {
+            changes.push(format!("input from {} changed", old_input.operator_id));
+        }
+
+        incompatible.extend(
+            check_schema_compatibility(&old_edge.schema.schema, &new_edge.schema.schema)
+                .into_iter()
+                .map(|i| format!("input from {}: {}", old_input.operator_id, i)),
+        );
+
+        let (old_keys, new_keys) = (key_names(&old_edge.schema), key_names(&new_edge.schema));
+        if old_keys != new_keys {
+            incompatible.push(format!(
+                "input from {}: {}",
+                old_input.operator_id,
+                Incompatibility::KeysChanged {
+                    old: old_keys,
+                    new: new_keys
+                }
+            ));
+        }
+    }
+
+    if old.operator_config != new.operator_config {
+        changes.push("configuration changed".to_string());
+        unknown.push(
+            "configuration changed; its tables will be checked when the pipeline is restored"
+                .to_string(),
+        );
+    }
+
+    let (state, reasons) = if is_stateless(old.operator_name) && is_stateless(new.operator_name) {
+        (StateCompatibility::Stateless, vec![])
+    } else if !incompatible.is_empty() {
+        (StateCompatibility::Incompatible, incompatible)
+    } else if !unknown.is_empty() {
+        (StateCompatibility::Unknown, unknown)
+    } else {
+        (StateCompatibility::Compatible, vec![])
+    };
+
+    OperatorDiff {
+        node_id: new.operator_id.clone(),
+        operator: new_label,
+        change: if changes.is_empty() {
+            OperatorChange::Unchanged
+        } else {
+            OperatorChange::Changed
+        },
+        old_description: Some(old.description.clone()),
+        new_description: Some(new.description.clone()),
+        changes,
+        state,
+        reasons,
+    }
+}
+
+/// Compares the plan a pipeline is running with the plan of an update to it, matching
+/// operators by id
+pub fn diff_programs(old: &LogicalProgram, new: &LogicalProgram) -> PipelineDiff {
+    let old_nodes: HashMap<&str, &LogicalNode> = old
+        .graph
+        .node_weights()
+        .map(|n| (n.operator_id.as_str(), n))
+        .collect();
+    let new_nodes: HashMap<&str, &LogicalNode> = new
+        .graph
+        .node_weights()
+        .map(|n| (n.operator_id.as_str(), n))
+        .collect();
+
+    let mut operators: Vec<_> = new_nodes
+        .values()
+        .map(|node| match old_nodes.get(node.operator_id.as_str()) {
+            Some(old_node) => diff_operator(old, old_node, new, node),
+            None => added_or_removed(node, OperatorChange::Added),
+        })
+        .chain(
+            old_nodes
+                .values()
+                .filter(|node| !new_nodes.contains_key(node.operator_id.as_str()))
+                .map(|node| added_or_removed(node, OperatorChange::Removed)),
+        )
+        .collect();
+    operators.sort_by(|a, b| a.node_id.cmp(&b.node_id));
+
+    PipelineDiff {
+        state_compatible: operators
+            .iter()
+            .all(|o| o.state != StateCompatibility::Incompatible),
+        operators,
+    }
+}
+
+/// Converts a batch restored from a checkpoint to the current schema of its table, filling
+/// columns that have since been added with nulls. The schemas must have been checked for
+/// compatibility.
+pub(crate) fn evolve_batch(batch: RecordBatch, schema: &SchemaRef) -> Result<RecordBatch> {
+    if batch.schema().fields() == schema.fields() {
+        return Ok(batch);
+    }
+
+    let columns = schema
+        .fields()
+        .iter()
+        .map(|field| match batch.column_by_name(field.name()) {
+            Some(column) if column.data_type() == field.data_type() => Ok(column.clone()),
+            Some(column) => bail!(
+                "restored column '{}' has type {}, but expected {}",
+                field.name(),
+                column.data_type(),
+                field.data_type()
+            ),
+            None => Ok(new_null_array(field.data_type(), batch.num_rows())),
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(RecordBatch::try_new(schema.clone(), columns)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow_array::{Array, Int64Array};
+    use arrow_schema::{Field, TimeUnit};
+    use arroyo_datastream::logical::{LogicalEdgeType, LogicalGraph, ProgramConfig};
+    use std::sync::Arc;
+
+    #[test]
+    fn test_additive_schema_changes() {
+        let old = Arc::new(Schema::new(vec![
+            Field::new("a", DataType::Int64, false),
+            Field::new("b", DataType::Utf8, true),
+        ]));
+
+        let added = Arc::new(Schema::new(vec![
+            Field::new("a", DataType::Int64, true),
+            Field::new("c", DataType::Float64, true),
+            Field::new("b", DataType::Utf8, true),
+        ]));
+        assert_eq!(check_schema_compatibility(&old, &added), vec![]);
+
+        let batch = RecordBatch::try_new(
+            old.clone(),
+            vec![
+                Arc::new(Int64Array::from(vec![1, 2])),
+                Arc::new(arrow_array::StringArray::from(vec![Some("x"), None])),
+            ],
+        )
+        .unwrap();
+        let evolved = evolve_batch(batch, &added).unwrap();
+        assert_eq!(evolved.schema(), added);
+        assert_eq!(evolved.column(1).null_count(), 2);
+        assert_eq!(evolved.column(2).null_count(), 1);
+
+        let changed = Schema::new(vec![
+            Field::new("a", DataType::Utf8, false),
+            Field::new("d", DataType::Int64, false),
+        ]);
+        assert_eq!(
+            check_schema_compatibility(&old, &changed),
+            vec![
+                Incompatibility::ColumnTypeChanged {
+                    column: "a".to_string(),
+                    old: DataType::Int64,
+                    new: DataType::Utf8,
+                },
+                Incompatibility::ColumnRemoved("b".to_string()),
+                Incompatibility::NonNullableColumnAdded("d".to_string()),
+            ]
+        );
+    }
+
+    fn node(id: &str, operator_name: OperatorName, config: &[u8]) -> LogicalNode {
+        LogicalNode {
+            operator_id: id.to_string(),
+            description: id.to_string(),
+            operator_name,
+            operator_config: config.to_vec(),
+            parallelism: 1,
+        }
+    }
+
+    fn edge(value_type: DataType) -> LogicalEdge {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("value", value_type, false),
+            Field::new(
+                "_timestamp",
+                DataType::Timestamp(TimeUnit::Nanosecond, None),
+                false,
+            ),
+        ]));
+        LogicalEdge::new(
+            LogicalEdgeType::Forward,
+            ArroyoSchema::from_schema_unkeyed(schema).unwrap(),
+            None,
+        )
+    }
+
+    fn program(nodes: Vec<LogicalNode>, edges: Vec<(usize, usize, LogicalEdge)>) -> LogicalProgram {
+        let mut graph = LogicalGraph::new();
+        let indices: Vec<_> = nodes.into_iter().map(|n| graph.add_node(n)).collect();
+        for (from, to, edge) in edges {
+            graph.add_edge(indices[from], indices[to], edge);
+        }
+        LogicalProgram::new(graph, ProgramConfig::default())
+    }
+
+    #[test]
+    fn test_diff_programs() {
+        let source = ConnectorOp {
+            connector: "kafka".to_string(),
+            ..Default::default()
+        }
+        .encode_to_vec();
+
+        let old = program(
+            vec![
+                node("source", OperatorName::ConnectorSource, &source),
+                node("value", OperatorName::ArrowValue, b"a"),
+                node("tumbling", OperatorName::TumblingWindowAggregate, b"a"),
+                node("join", OperatorName::Join, b"a"),
+            ],
+            vec![
+                (0, 1, edge(DataType::Int64)),
+                (1, 2, edge(DataType::Int64)),
+                (1, 3, edge(DataType::Int64)),
+            ],
+        );
+
+        let new = program(
+            vec![
+                node("source", OperatorName::ConnectorSource, &source),
+                node("value", OperatorName::ArrowValue, b"b"),
+                node("tumbling", OperatorName::TumblingWindowAggregate, b"b"),
+                node("window_function", OperatorName::WindowFunction, b"a"),
+            ],
+            vec![
+                (0, 1, edge(DataType::Int64)),
+                (1, 2, edge(DataType::Utf8)),
+                (1, 3, edge(DataType::Int64)),
+            ],
+        );
+
+        let diff = diff_programs(&old, &new);
+        let get = |id: &str| diff.operators.iter().find(|o| o.node_id == id).unwrap();
+
+        assert_eq!(diff.operators.len(), 5);
+        assert!(!diff.state_compatible);
+
+        assert_eq!(get("source").change, OperatorChange::Unchanged);
+        assert_eq!(get("source").operator, "kafka");
+        assert_eq!(get("source").state, StateCompatibility::Compatible);
+
+        assert_eq!(get("value").change, OperatorChange::Changed);
+        assert_eq!(get("value").state, StateCompatibility::Stateless);
+
+        assert_eq!(get("tumbling").state, StateCompatibility::Incompatible);
+        assert_eq!(
+            get("tumbling").reasons,
+            vec!["input from value: column 'value' changed type from Int64 to Utf8"]
+        );
+
+        assert_eq!(get("join").change, OperatorChange::Removed);
+        assert_eq!(get("window_function").change, OperatorChange::Added);
+        assert_eq!(get("window_function").state, StateCompatibility::Compatible);
+    }
+}
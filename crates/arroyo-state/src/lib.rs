@@ -2,7 +2,8 @@ use anyhow::{Context, Result};
 use arrow_array::RecordBatch;
 use arroyo_rpc::grpc::rpc::{
     CheckpointMetadata, ExpiringKeyedTimeTableConfig, GlobalKeyedTableConfig,
-    OperatorCheckpointMetadata, TableCheckpointMetadata, TableConfig, TableEnum,
+    OperatorCheckpointMetadata, RocksDbKeyedTableConfig, TableCheckpointMetadata, TableConfig,
+    TableEnum,
 };
 use arroyo_types::single_item_hash_map;
 use async_trait::async_trait;
@@ -11,17 +12,21 @@ use bincode::{Decode, Encode};
 
 use arroyo_rpc::config::config;
 use arroyo_rpc::df::ArroyoSchema;
-use arroyo_storage::StorageProvider;
+use arroyo_rpc::grpc::api::CheckpointStorage;
+use arroyo_rpc::var_str::VarStr;
+use arroyo_storage::{StorageProvider, TransferOptions};
+use once_cell::sync::Lazy;
 use prost::Message;
 use std::collections::hash_map::DefaultHasher;
 use std::collections::HashMap;
 use std::hash::{Hash, Hasher};
 use std::ops::RangeInclusive;
-use std::sync::Arc;
+use std::sync::{Arc, RwLock};
 use std::time::{Duration, SystemTime};
 
 pub mod checkpoint_state;
 pub mod committing_state;
+pub mod compatibility;
 mod metrics;
 pub mod parquet;
 pub(crate) mod schemas;
@@ -49,6 +54,7 @@ pub enum TableData {
     RecordBatch(RecordBatch),
     CommitData { data: Vec<u8> },
     KeyedData { key: Vec<u8>, value: Vec<u8> },
+    DeletedKey { key: Vec<u8> },
 }
 
 pub type StateBackend = parquet::ParquetBackend;
@@ -66,6 +72,33 @@ pub fn global_table_config(
                 table_name: name,
                 description: description.into(),
                 uses_two_phase_commit: false,
+                ttl_micros: None,
+            }
+            .encode_to_vec(),
+        },
+    )
+}
+
+/// Config for a global table whose entries are dropped once they haven't been inserted for
+/// `ttl`. Unlike other global tables, the live entries are written on every checkpoint along with
+/// when they were last inserted, so operators only need to insert the entries that change, and
+/// restored entries expire when they would have without the restore.
+pub fn global_table_config_with_ttl(
+    name: impl Into<String>,
+    description: impl Into<String>,
+    ttl: Duration,
+    uses_two_phase_commit: bool,
+) -> HashMap<String, TableConfig> {
+    let name = name.into();
+    single_item_hash_map(
+        name.clone(),
+        TableConfig {
+            table_type: TableEnum::GlobalKeyValue.into(),
+            config: GlobalKeyedTableConfig {
+                table_name: name,
+                description: description.into(),
+                uses_two_phase_commit,
+                ttl_micros: Some(ttl.as_micros() as u64),
             }
             .encode_to_vec(),
         },
@@ -92,6 +125,22 @@ pub fn timestamp_table_config(
     }
 }
 
+/// Config for a keyed table whose values are kept in a local RocksDB instance, for keyed state
+/// too large to hold in memory
+pub fn rocksdb_table_config(
+    name: impl Into<String>,
+    description: impl Into<String>,
+) -> TableConfig {
+    TableConfig {
+        table_type: TableEnum::RocksDbKeyValue.into(),
+        config: RocksDbKeyedTableConfig {
+            table_name: name.into(),
+            description: description.into(),
+        }
+        .encode_to_vec(),
+    }
+}
+
 #[derive(Debug, Encode, Decode, PartialEq, Eq, Clone)]
 pub struct DeleteTimeKeyOperation {
     pub timestamp: SystemTime,
@@ -164,24 +213,80 @@ pub fn hash_key<K: Hash>(key: &K) -> u64 {
     hasher.finish()
 }
 
-static STORAGE_PROVIDER: tokio::sync::OnceCell<Arc<StorageProvider>> =
+static DEFAULT_STORAGE_PROVIDER: tokio::sync::OnceCell<Arc<StorageProvider>> =
     tokio::sync::OnceCell::const_new();
 
-pub(crate) async fn get_storage_provider() -> Result<&'static Arc<StorageProvider>> {
-    // TODO: this should be encoded in the config so that the controller doesn't need
-    // to be synchronized with the workers
+/// The checkpoint storage of the jobs that configure their own, by job id
+static JOB_STORAGE_PROVIDERS: Lazy<RwLock<HashMap<String, Arc<StorageProvider>>>> =
+    Lazy::new(Default::default);
+
+async fn construct_storage_provider(
+    url: &str,
+    options: HashMap<String, String>,
+) -> Result<Arc<StorageProvider>> {
+    StorageProvider::for_url_with_options(url, options)
+        .await
+        .context(format!(
+            "failed to construct checkpoint backend for URL {}",
+            url
+        ))
+        .map(|provider| {
+            Arc::new(
+                provider.with_transfer_options(TransferOptions::from(
+                    &config().worker.checkpoint_storage,
+                )),
+            )
+        })
+}
+
+/// Sets the storage that a job's checkpoints are written to and read from, replacing any that
+/// was registered before; jobs without their own storage use the cluster's checkpoint URL.
+///
+/// The URL and options may refer to environment variables (like `{{ AWS_SECRET_ACCESS_KEY }}`),
+/// which are substituted in the registering process, so that the controller and the workers
+/// can be given their own credentials.
+pub async fn register_checkpoint_storage(
+    job_id: &str,
+    storage: Option<&CheckpointStorage>,
+) -> Result<()> {
+    let Some(storage) = storage else {
+        JOB_STORAGE_PROVIDERS.write().unwrap().remove(job_id);
+        return Ok(());
+    };
+
+    let url = VarStr::new(storage.url.clone())
+        .sub_env_vars()
+        .context("invalid checkpoint URL")?;
+    let options = storage
+        .options
+        .iter()
+        .map(|(key, value)| {
+            let value = VarStr::new(value.clone())
+                .sub_env_vars()
+                .context(format!("invalid checkpoint storage option {}", key))?;
+            Ok((key.clone(), value))
+        })
+        .collect::<Result<_>>()?;
+
+    let provider = construct_storage_provider(&url, options).await?;
+    JOB_STORAGE_PROVIDERS
+        .write()
+        .unwrap()
+        .insert(job_id.to_string(), provider);
+    Ok(())
+}
+
+/// The storage of a job's checkpoints (or of a savepoint, which are always in the default storage)
+pub(crate) async fn get_storage_provider(job_id: &str) -> Result<Arc<StorageProvider>> {
+    let provider = JOB_STORAGE_PROVIDERS.read().unwrap().get(job_id).cloned();
+    if let Some(provider) = provider {
+        return Ok(provider);
+    }
 
-    STORAGE_PROVIDER
+    DEFAULT_STORAGE_PROVIDER
         .get_or_try_init(|| async {
-            let storage_url = &config().checkpoint_url;
-
-            StorageProvider::for_url(storage_url)
-                .await
-                .context(format!(
-                    "failed to construct checkpoint backend for URL {}",
-                    storage_url
-                ))
-                .map(Arc::new)
+            construct_storage_provider(&config().checkpoint_url, HashMap::new()).await
         })
         .await
+        .cloned()
 }
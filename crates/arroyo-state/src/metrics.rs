@@ -9,11 +9,34 @@ lazy_static! {
         &WORKER_LABELS_NAMES
     )
     .unwrap();
-    pub static ref TABLE_LABELS_NAMES: Vec<&'static str> =
-        vec!["operator_id", "task_id", "table_char"];
+    pub static ref TABLE_LABELS_NAMES: Vec<&'static str> = vec!["operator_id", "task_id", "table"];
     pub static ref TABLE_SIZE_GAUGE: GaugeVec = register_gauge_vec!(
         "arroyo_worker_table_size_keys",
-        "Number of keys in the table",
+        "Number of rows (or keys) in the table",
+        &TABLE_LABELS_NAMES
+    )
+    .unwrap();
+    pub static ref TABLE_MEMORY_GAUGE: GaugeVec = register_gauge_vec!(
+        "arroyo_worker_table_memory_bytes",
+        "Bytes of memory taken up by the table",
+        &TABLE_LABELS_NAMES
+    )
+    .unwrap();
+    pub static ref TABLE_DISK_GAUGE: GaugeVec = register_gauge_vec!(
+        "arroyo_worker_table_disk_bytes",
+        "Bytes of local disk taken up by the table",
+        &TABLE_LABELS_NAMES
+    )
+    .unwrap();
+    pub static ref TABLE_CHECKPOINT_GAUGE: GaugeVec = register_gauge_vec!(
+        "arroyo_worker_table_checkpoint_bytes",
+        "Bytes written for the table by the latest checkpoint",
+        &TABLE_LABELS_NAMES
+    )
+    .unwrap();
+    pub static ref TABLE_OLDEST_TIMESTAMP_GAUGE: GaugeVec = register_gauge_vec!(
+        "arroyo_worker_table_oldest_timestamp_seconds",
+        "Event time of the oldest data retained by the table, in seconds since the epoch",
         &TABLE_LABELS_NAMES
     )
     .unwrap();
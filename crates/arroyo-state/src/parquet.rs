@@ -1,16 +1,18 @@
 use crate::tables::expiring_time_key_map::ExpiringTimeKeyTable;
 use crate::tables::global_keyed_map::GlobalKeyedTable;
+use crate::tables::rocksdb_keyed_map::RocksDbKeyedTable;
 use crate::tables::{CompactionConfig, ErasedTable};
 use crate::{get_storage_provider, BackingStore};
 use anyhow::{bail, Result};
 use arroyo_rpc::grpc::rpc::{
-    CheckpointMetadata, OperatorCheckpointMetadata, TableCheckpointMetadata,
+    CheckpointMetadata, OperatorCheckpointMetadata, TableCheckpointMetadata, TableConfig,
 };
 use futures::stream::FuturesUnordered;
 use futures::StreamExt;
 
 use arroyo_rpc::config::config;
 use arroyo_rpc::grpc::rpc;
+use arroyo_storage::StorageProvider;
 use prost::Message;
 use std::collections::{HashMap, HashSet};
 use std::ops::RangeInclusive;
@@ -35,6 +37,53 @@ fn operator_path(job_id: &str, epoch: u32, operator: &str) -> String {
     format!("{}/operator-{}", base_path(job_id, epoch), operator)
 }
 
+/// The root of a savepoint's state, which is laid out like the checkpoints of a job
+pub fn savepoint_root(savepoint_id: &str) -> String {
+    format!("savepoints/{}", savepoint_id)
+}
+
+fn table_files(config: TableConfig, metadata: TableCheckpointMetadata) -> Result<HashSet<String>> {
+    match config.table_type() {
+        rpc::TableEnum::MissingTableType => bail!("missing table type"),
+        rpc::TableEnum::GlobalKeyValue => GlobalKeyedTable::files_to_keep(config, metadata),
+        rpc::TableEnum::ExpiringKeyedTimeTable => {
+            ExpiringTimeKeyTable::files_to_keep(config, metadata)
+        }
+        rpc::TableEnum::RocksDbKeyValue => RocksDbKeyedTable::files_to_keep(config, metadata),
+    }
+}
+
+fn rename_table_files(
+    config: &TableConfig,
+    metadata: TableCheckpointMetadata,
+    rename: &dyn Fn(&str) -> String,
+) -> Result<TableCheckpointMetadata> {
+    match config.table_type() {
+        rpc::TableEnum::MissingTableType => bail!("missing table type"),
+        rpc::TableEnum::GlobalKeyValue => GlobalKeyedTable::rename_files(metadata, rename),
+        rpc::TableEnum::ExpiringKeyedTimeTable => {
+            ExpiringTimeKeyTable::rename_files(metadata, rename)
+        }
+        rpc::TableEnum::RocksDbKeyValue => RocksDbKeyedTable::rename_files(metadata, rename),
+    }
+}
+
+/// Copies a file between checkpoint storages, which may be the same
+async fn copy_file(
+    from: &Arc<StorageProvider>,
+    from_path: &str,
+    to: &Arc<StorageProvider>,
+    to_path: &str,
+) -> Result<()> {
+    if Arc::ptr_eq(from, to) {
+        from.copy(from_path, to_path).await?;
+    } else {
+        let data = from.get(from_path).await?;
+        to.put(to_path, data.to_vec()).await?;
+    }
+    Ok(())
+}
+
 #[async_trait::async_trait]
 impl BackingStore for ParquetBackend {
     fn name() -> &'static str {
@@ -42,7 +91,7 @@ impl BackingStore for ParquetBackend {
     }
 
     async fn load_checkpoint_metadata(job_id: &str, epoch: u32) -> Result<CheckpointMetadata> {
-        let storage_client = get_storage_provider().await?;
+        let storage_client = get_storage_provider(job_id).await?;
         let data = storage_client
             .get(metadata_path(&base_path(job_id, epoch)).as_str())
             .await?;
@@ -55,7 +104,7 @@ impl BackingStore for ParquetBackend {
         operator_id: &str,
         epoch: u32,
     ) -> Result<Option<OperatorCheckpointMetadata>> {
-        let storage_client = get_storage_provider().await?;
+        let storage_client = get_storage_provider(job_id).await?;
         storage_client
             .get_if_present(metadata_path(&operator_path(job_id, epoch, operator_id)).as_str())
             .await?
@@ -66,11 +115,11 @@ impl BackingStore for ParquetBackend {
     async fn write_operator_checkpoint_metadata(
         metadata: OperatorCheckpointMetadata,
     ) -> Result<()> {
-        let storage_client = get_storage_provider().await?;
         let operator_metadata = metadata
             .operator_metadata
             .as_ref()
             .ok_or_else(|| anyhow::anyhow!("missing operator metadata"))?;
+        let storage_client = get_storage_provider(&operator_metadata.job_id).await?;
         let path = metadata_path(&operator_path(
             &operator_metadata.job_id,
             operator_metadata.epoch,
@@ -85,7 +134,7 @@ impl BackingStore for ParquetBackend {
 
     async fn write_checkpoint_metadata(metadata: CheckpointMetadata) -> Result<()> {
         debug!("writing checkpoint {:?}", metadata);
-        let storage_client = get_storage_provider().await?;
+        let storage_client = get_storage_provider(&metadata.job_id).await?;
         let path = metadata_path(&base_path(&metadata.job_id, metadata.epoch));
         storage_client
             .put(path.as_str(), metadata.encode_to_vec())
@@ -121,20 +170,14 @@ impl BackingStore for ParquetBackend {
             })
             .collect();
 
-        let storage_client = get_storage_provider().await?;
+        let storage_client = get_storage_provider(&metadata.job_id).await?;
 
         // wait for all of the futures to complete
+        let mut paths_to_keep = HashSet::new();
         while let Some(result) = futures.next().await {
-            let operator_id = result?;
-
-            for epoch_to_remove in old_min_epoch..min_epoch {
-                let path = metadata_path(&operator_path(
-                    &metadata.job_id,
-                    epoch_to_remove,
-                    &operator_id,
-                ));
-                storage_client.delete_if_present(path).await?;
-            }
+            let (operator_id, operator_paths) = result?;
+            paths_to_keep.extend(operator_paths);
+
             debug!(
                 message = "Finished cleaning operator",
                 job_id = metadata.job_id,
@@ -143,10 +186,17 @@ impl BackingStore for ParquetBackend {
             );
         }
 
+        // delete everything else under the removed epochs, including their metadata and any files
+        // left behind by failed checkpoints or operators that are no longer in the pipeline
         for epoch_to_remove in old_min_epoch..min_epoch {
-            storage_client
-                .delete_if_present(metadata_path(&base_path(&metadata.job_id, epoch_to_remove)))
-                .await?;
+            for path in storage_client
+                .list_prefix(base_path(&metadata.job_id, epoch_to_remove))
+                .await?
+            {
+                if !paths_to_keep.contains(path.as_ref()) {
+                    storage_client.delete_if_present(path).await?;
+                }
+            }
         }
         metadata.min_epoch = min_epoch;
         Self::write_checkpoint_metadata(metadata).await?;
@@ -161,17 +211,16 @@ impl ParquetBackend {
         operator_id: String,
         epoch: u32,
     ) -> Result<HashMap<String, TableCheckpointMetadata>> {
-        let min_files_to_compact = config().pipeline.compaction.checkpoints_to_compact as usize;
+        let compaction = &config().pipeline.compaction;
 
         let operator_checkpoint_metadata =
             Self::load_operator_metadata(&job_id, &operator_id, epoch)
                 .await?
                 .expect("expect operator metadata to still be present");
-        let storage_provider = get_storage_provider().await?;
         let compaction_config = CompactionConfig {
-            compact_generations: vec![0].into_iter().collect(),
-            min_compaction_epochs: min_files_to_compact,
-            storage_provider: Arc::clone(storage_provider),
+            min_compaction_epochs: compaction.checkpoints_to_compact as usize,
+            target_file_size: compaction.target_file_size,
+            storage_provider: get_storage_provider(&job_id).await?,
         };
         let operator_metadata = operator_checkpoint_metadata.operator_metadata.unwrap();
 
@@ -203,6 +252,15 @@ impl ParquetBackend {
                     )
                     .await?
                 }
+                rpc::TableEnum::RocksDbKeyValue => {
+                    RocksDbKeyedTable::compact_data(
+                        table_config,
+                        &compaction_config,
+                        &operator_metadata,
+                        table_metadata,
+                    )
+                    .await?
+                }
             } {
                 result.insert(table, compacted_metadata);
             }
@@ -210,13 +268,13 @@ impl ParquetBackend {
         Ok(result)
     }
 
-    /// Delete files no longer referenced by the new min epoch
+    /// Delete files no longer referenced by the new min epoch, returning the files that still are
     pub async fn cleanup_operator(
         job_id: String,
         operator_id: String,
         old_min_epoch: u32,
         new_min_epoch: u32,
-    ) -> Result<String> {
+    ) -> Result<(String, HashSet<String>)> {
         let operator_metadata = Self::load_operator_metadata(&job_id, &operator_id, new_min_epoch)
             .await?
             .expect("expect new_min_epoch metadata to still be present");
@@ -238,12 +296,15 @@ impl ParquetBackend {
                     rpc::TableEnum::ExpiringKeyedTimeTable => {
                         ExpiringTimeKeyTable::files_to_keep(table_config, metadata.clone()).unwrap()
                     }
+                    rpc::TableEnum::RocksDbKeyValue => {
+                        RocksDbKeyedTable::files_to_keep(table_config, metadata.clone()).unwrap()
+                    }
                 }
             })
             .collect();
 
         let mut deleted_paths = HashSet::new();
-        let storage_client = get_storage_provider().await?;
+        let storage_client = get_storage_provider(&job_id).await?;
 
         for epoch_to_remove in old_min_epoch..new_min_epoch {
             let Some(operator_metadata) =
@@ -274,17 +335,122 @@ impl ParquetBackend {
                             ExpiringTimeKeyTable::files_to_keep(table_config, metadata.clone())
                                 .unwrap()
                         }
+                        rpc::TableEnum::RocksDbKeyValue => {
+                            RocksDbKeyedTable::files_to_keep(table_config, metadata.clone())
+                                .unwrap()
+                        }
                     }
                 })
             {
-                if !paths_to_keep.contains(&file) && !deleted_paths.contains(&file) {
+                // files outside of the job's directory belong to the savepoint it was started from
+                if !paths_to_keep.contains(&file)
+                    && !deleted_paths.contains(&file)
+                    && file.starts_with(&format!("{}/", job_id))
+                {
                     deleted_paths.insert(file.clone());
                     storage_client.delete_if_present(file).await?;
                 }
             }
         }
 
-        Ok(operator_id)
+        Ok((operator_id, paths_to_keep))
+    }
+
+    /// Copies the state of a job's checkpoint into a savepoint, rewriting its metadata to refer to
+    /// the copies so that the savepoint doesn't depend on the job's checkpoints. Savepoints are
+    /// kept in the default checkpoint storage, even for jobs that have their own.
+    pub async fn write_savepoint(job_id: &str, epoch: u32, savepoint_id: &str) -> Result<()> {
+        let root = savepoint_root(savepoint_id);
+        let from = get_storage_provider(job_id).await?;
+        let to = get_storage_provider(&root).await?;
+        let mut metadata = Self::load_checkpoint_metadata(job_id, epoch).await?;
+
+        // files keep their paths relative to the root of the job (or savepoint) that wrote them
+        let rename = |file: &str| match file.find("/checkpoints/") {
+            Some(i) => format!("{}{}", root, &file[i..]),
+            None => format!("{}/{}", root, file),
+        };
+
+        for operator_id in &metadata.operator_ids {
+            let Some(mut operator_metadata) =
+                Self::load_operator_metadata(job_id, operator_id, epoch).await?
+            else {
+                bail!(
+                    "missing metadata for operator {} in checkpoint {}",
+                    operator_id,
+                    epoch
+                );
+            };
+
+            for (table_name, table_metadata) in &mut operator_metadata.table_checkpoint_metadata {
+                let table_config = operator_metadata
+                    .table_configs
+                    .get(table_name)
+                    .ok_or_else(|| anyhow::anyhow!("missing table config for {}", table_name))?;
+
+                for file in table_files(table_config.clone(), table_metadata.clone())? {
+                    copy_file(&from, &file, &to, &rename(&file)).await?;
+                }
+
+                *table_metadata =
+                    rename_table_files(table_config, table_metadata.clone(), &rename)?;
+            }
+
+            if let Some(operator_metadata) = &mut operator_metadata.operator_metadata {
+                operator_metadata.job_id.clone_from(&root);
+            }
+            Self::write_operator_checkpoint_metadata(operator_metadata).await?;
+        }
+
+        metadata.job_id = root;
+        metadata.min_epoch = epoch;
+        Self::write_checkpoint_metadata(metadata).await
+    }
+
+    /// Makes a savepoint the checkpoint that a job restores from, at the savepoint's epoch. The
+    /// job reads the savepoint's files where they are, and never deletes them; if the job has its
+    /// own checkpoint storage, they're first copied into it at the same paths.
+    pub async fn restore_savepoint(savepoint_id: &str, epoch: u32, job_id: &str) -> Result<()> {
+        let root = savepoint_root(savepoint_id);
+        let from = get_storage_provider(&root).await?;
+        let to = get_storage_provider(job_id).await?;
+        let mut metadata = Self::load_checkpoint_metadata(&root, epoch).await?;
+
+        for operator_id in &metadata.operator_ids {
+            let Some(mut operator_metadata) =
+                Self::load_operator_metadata(&root, operator_id, epoch).await?
+            else {
+                bail!(
+                    "missing metadata for operator {} in savepoint {}",
+                    operator_id,
+                    savepoint_id
+                );
+            };
+
+            if !Arc::ptr_eq(&from, &to) {
+                for (table_name, table_metadata) in &operator_metadata.table_checkpoint_metadata {
+                    let table_config =
+                        operator_metadata
+                            .table_configs
+                            .get(table_name)
+                            .ok_or_else(|| {
+                                anyhow::anyhow!("missing table config for {}", table_name)
+                            })?;
+
+                    for file in table_files(table_config.clone(), table_metadata.clone())? {
+                        copy_file(&from, &file, &to, &file).await?;
+                    }
+                }
+            }
+
+            if let Some(operator_metadata) = &mut operator_metadata.operator_metadata {
+                operator_metadata.job_id = job_id.to_string();
+            }
+            Self::write_operator_checkpoint_metadata(operator_metadata).await?;
+        }
+
+        metadata.job_id = job_id.to_string();
+        Self::write_checkpoint_metadata(metadata).await
     }
 }
 
@@ -0,0 +1,707 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::{self, File, OpenOptions};
+use std::io::{Cursor, Read, Seek, SeekFrom, Write};
+use std::ops::Bound;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, bail, Context, Result};
+use arrow::compute::concat_batches;
+use arrow::ipc::reader::StreamReader;
+use arrow::ipc::writer::StreamWriter;
+use arrow_array::RecordBatch;
+use arroyo_rpc::config::config;
+use arroyo_rpc::grpc::api::StateStorage;
+use arroyo_types::TaskInfo;
+use rocksdb::{properties, Direction, IteratorMode, Options, WriteBatch, WriteOptions, DB};
+
+use super::TableStats;
+
+/// Holds the record batches of a table's view by key, in key order. Batches added under the same
+/// key are returned in the order they were added.
+pub(crate) trait BatchStore: std::fmt::Debug + Send {
+    /// Adds a batch under the key, after those already stored there
+    fn append(&mut self, key: &[u8], batch: RecordBatch) -> Result<()>;
+
+    /// All of the batches stored under the key, concatenated
+    fn get(&mut self, key: &[u8]) -> Result<Option<RecordBatch>>;
+
+    /// Removes and returns the batches stored under the key
+    fn remove(&mut self, key: &[u8]) -> Result<Vec<RecordBatch>>;
+
+    /// The batches stored under each key that is at least `start`, in key order
+    fn range_from(&mut self, start: &[u8]) -> Result<Vec<(Vec<u8>, Vec<RecordBatch>)>>;
+
+    /// Removes the batches stored under every key before `end`
+    fn remove_before(&mut self, end: &[u8]) -> Result<()>;
+
+    /// The smallest key that has batches stored under it
+    fn first_key(&mut self) -> Result<Option<Vec<u8>>>;
+
+    /// How many rows the store holds, and the memory and disk they take up
+    fn stats(&self) -> Result<TableStats>;
+}
+
+/// Opens a store for a table of the task, using the storage that was chosen for its operator.
+/// Stores are rebuilt from the checkpoint when a task starts, so anything left on disk by
+/// earlier runs of the task is discarded.
+pub(crate) fn open_store(
+    storage: StateStorage,
+    task_info: &TaskInfo,
+    table: &str,
+) -> Result<Box<dyn BatchStore>> {
+    Ok(match storage {
+        StateStorage::Memory => Box::<MemoryStore>::default(),
+        StateStorage::Disk => Box::new(DiskStore::open(
+            store_dir(task_info, table)?,
+            config().worker.local_state.spill_threshold,
+        )?),
+        StateStorage::Rocksdb => Box::new(RocksDbStore::open(store_dir(task_info, table)?)?),
+    })
+}
+
+pub(crate) fn store_dir(task_info: &TaskInfo, table: &str) -> Result<PathBuf> {
+    let dir = config()
+        .worker
+        .local_state
+        .dir
+        .clone()
+        .unwrap_or_else(|| std::env::temp_dir().join("arroyo-state"))
+        .join(&task_info.job_id)
+        .join(&task_info.operator_id)
+        .join(task_info.task_index.to_string())
+        .join(table);
+
+    match fs::remove_dir_all(&dir) {
+        Ok(()) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => {
+            return Err(e).with_context(|| format!("failed to clear state directory {:?}", dir))
+        }
+    }
+
+    fs::create_dir_all(&dir)
+        .with_context(|| format!("failed to create state directory {:?}", dir))?;
+
+    Ok(dir)
+}
+
+fn concat(mut batches: Vec<RecordBatch>) -> Result<Option<RecordBatch>> {
+    Ok(match batches.len() {
+        0 => None,
+        1 => batches.pop(),
+        _ => Some(concat_batches(&batches[0].schema(), &batches)?),
+    })
+}
+
+/// The number of rows in the batches, and the memory they take up
+fn batch_sizes<'a>(batches: impl Iterator<Item = &'a RecordBatch>) -> (u64, u64) {
+    batches.fold((0, 0), |(rows, bytes), batch| {
+        (
+            rows + batch.num_rows() as u64,
+            bytes + batch.get_array_memory_size() as u64,
+        )
+    })
+}
+
+/// The number of keys in a local RocksDB instance, and the space it takes up in memory and on
+/// disk. The number of keys is only an estimate, as RocksDB doesn't track it exactly.
+pub(crate) fn rocksdb_stats(db: &DB) -> Result<TableStats> {
+    Ok(TableStats {
+        rows: db.property_int_value(properties::ESTIMATE_NUM_KEYS)?,
+        memory_bytes: db
+            .property_int_value(properties::CUR_SIZE_ALL_MEM_TABLES)?
+            .unwrap_or_default(),
+        disk_bytes: db
+            .property_int_value(properties::TOTAL_SST_FILES_SIZE)?
+            .unwrap_or_default(),
+        oldest_timestamp: None,
+    })
+}
+
+fn encode_batch(batch: &RecordBatch) -> Result<Vec<u8>> {
+    let mut buf = vec![];
+    let mut writer = StreamWriter::try_new(&mut buf, &batch.schema())?;
+    writer.write(batch)?;
+    writer.finish()?;
+    drop(writer);
+    Ok(buf)
+}
+
+fn decode_batch(bytes: &[u8]) -> Result<RecordBatch> {
+    Ok(StreamReader::try_new(Cursor::new(bytes), None)?
+        .next()
+        .ok_or_else(|| anyhow!("stored state is missing its record batch"))??)
+}
+
+/// Keeps all batches in memory
+#[derive(Debug, Default)]
+pub(crate) struct MemoryStore {
+    batches: BTreeMap<Vec<u8>, Vec<RecordBatch>>,
+}
+
+impl BatchStore for MemoryStore {
+    fn append(&mut self, key: &[u8], batch: RecordBatch) -> Result<()> {
+        self.batches.entry(key.to_vec()).or_default().push(batch);
+        Ok(())
+    }
+
+    fn get(&mut self, key: &[u8]) -> Result<Option<RecordBatch>> {
+        let Some(batches) = self.batches.get_mut(key) else {
+            return Ok(None);
+        };
+
+        // keys are typically read many times, so coalesce their batches once rather than on
+        // every read
+        if batches.len() > 1 {
+            *batches = vec![concat_batches(&batches[0].schema(), batches.iter())?];
+        }
+
+        Ok(batches.first().cloned())
+    }
+
+    fn remove(&mut self, key: &[u8]) -> Result<Vec<RecordBatch>> {
+        Ok(self.batches.remove(key).unwrap_or_default())
+    }
+
+    fn range_from(&mut self, start: &[u8]) -> Result<Vec<(Vec<u8>, Vec<RecordBatch>)>> {
+        Ok(self
+            .batches
+            .range::<[u8], _>((Bound::Included(start), Bound::Unbounded))
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect())
+    }
+
+    fn remove_before(&mut self, end: &[u8]) -> Result<()> {
+        self.batches = self.batches.split_off(end);
+        Ok(())
+    }
+
+    fn first_key(&mut self) -> Result<Option<Vec<u8>>> {
+        Ok(self.batches.keys().next().cloned())
+    }
+
+    fn stats(&self) -> Result<TableStats> {
+        let (rows, memory_bytes) = batch_sizes(self.batches.values().flatten());
+        Ok(TableStats {
+            rows: Some(rows),
+            memory_bytes,
+            ..Default::default()
+        })
+    }
+}
+
+/// Where a spilled batch was written in the spill file
+#[derive(Debug, Clone, Copy)]
+struct Spilled {
+    offset: u64,
+    len: u64,
+    rows: u64,
+}
+
+/// Keeps batches in memory until they take up more than the spill threshold, then appends them
+/// to a file on local disk. The space taken by removed batches is reclaimed by rewriting the
+/// file once it holds more removed data than live data.
+#[derive(Debug)]
+pub(crate) struct DiskStore {
+    dir: PathBuf,
+    file: File,
+    file_len: u64,
+    spill_threshold: u64,
+    memory: BTreeMap<Vec<u8>, Vec<RecordBatch>>,
+    memory_bytes: u64,
+    // the location in the file of each batch spilled under a key
+    spilled: BTreeMap<Vec<u8>, Vec<Spilled>>,
+    spilled_rows: u64,
+    live_bytes: u64,
+    dead_bytes: u64,
+}
+
+impl DiskStore {
+    pub(crate) fn open(dir: PathBuf, spill_threshold: u64) -> Result<Self> {
+        Ok(Self {
+            file: Self::create_file(&dir.join("spill"))?,
+            dir,
+            file_len: 0,
+            spill_threshold,
+            memory: BTreeMap::new(),
+            memory_bytes: 0,
+            spilled: BTreeMap::new(),
+            spilled_rows: 0,
+            live_bytes: 0,
+            dead_bytes: 0,
+        })
+    }
+
+    fn create_file(path: &Path) -> Result<File> {
+        OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)
+            .with_context(|| format!("failed to create spill file {:?}", path))
+    }
+
+    fn spill(&mut self) -> Result<()> {
+        self.file.seek(SeekFrom::Start(self.file_len))?;
+
+        for (key, batches) in std::mem::take(&mut self.memory) {
+            let locations = self.spilled.entry(key).or_default();
+            for batch in batches {
+                let bytes = encode_batch(&batch)?;
+                self.file.write_all(&bytes)?;
+                locations.push(Spilled {
+                    offset: self.file_len,
+                    len: bytes.len() as u64,
+                    rows: batch.num_rows() as u64,
+                });
+                self.file_len += bytes.len() as u64;
+                self.live_bytes += bytes.len() as u64;
+                self.spilled_rows += batch.num_rows() as u64;
+            }
+        }
+
+        self.memory_bytes = 0;
+        Ok(())
+    }
+
+    fn read(&mut self, location: Spilled) -> Result<Vec<u8>> {
+        let mut buf = vec![0; location.len as usize];
+        self.file.seek(SeekFrom::Start(location.offset))?;
+        self.file.read_exact(&mut buf)?;
+        Ok(buf)
+    }
+
+    fn batches(&mut self, key: &[u8]) -> Result<Vec<RecordBatch>> {
+        let mut batches = vec![];
+
+        // batches are spilled in the order they were added, so those on disk come first
+        for location in self.spilled.get(key).cloned().unwrap_or_default() {
+            batches.push(decode_batch(&self.read(location)?)?);
+        }
+
+        batches.extend(self.memory.get(key).into_iter().flatten().cloned());
+        Ok(batches)
+    }
+
+    fn discard_memory(&mut self, batches: &[RecordBatch]) {
+        let size: usize = batches.iter().map(|b| b.get_array_memory_size()).sum();
+        self.memory_bytes = self.memory_bytes.saturating_sub(size as u64);
+    }
+
+    fn discard_spilled(&mut self, locations: &[Spilled]) {
+        let size: u64 = locations.iter().map(|l| l.len).sum();
+        self.live_bytes -= size;
+        self.dead_bytes += size;
+        self.spilled_rows -= locations.iter().map(|l| l.rows).sum::<u64>();
+    }
+
+    fn maybe_compact(&mut self) -> Result<()> {
+        if self.live_bytes == 0 {
+            self.file.set_len(0)?;
+            self.file_len = 0;
+            self.dead_bytes = 0;
+            return Ok(());
+        }
+
+        if self.dead_bytes <= self.live_bytes.max(self.spill_threshold) {
+            return Ok(());
+        }
+
+        let path = self.dir.join("spill");
+        let compacting_path = self.dir.join("spill.compacting");
+        let mut compacted = Self::create_file(&compacting_path)?;
+        let mut compacted_len = 0;
+
+        let mut spilled = std::mem::take(&mut self.spilled);
+        for locations in spilled.values_mut() {
+            for location in locations.iter_mut() {
+                let bytes = self.read(*location)?;
+                compacted.write_all(&bytes)?;
+                location.offset = compacted_len;
+                compacted_len += location.len;
+            }
+        }
+
+        fs::rename(&compacting_path, &path)
+            .with_context(|| format!("failed to replace spill file {:?}", path))?;
+
+        self.spilled = spilled;
+        self.file = compacted;
+        self.file_len = compacted_len;
+        self.dead_bytes = 0;
+        Ok(())
+    }
+}
+
+impl BatchStore for DiskStore {
+    fn append(&mut self, key: &[u8], batch: RecordBatch) -> Result<()> {
+        self.memory_bytes += batch.get_array_memory_size() as u64;
+        self.memory.entry(key.to_vec()).or_default().push(batch);
+
+        if self.memory_bytes > self.spill_threshold {
+            self.spill()?;
+        }
+
+        Ok(())
+    }
+
+    fn get(&mut self, key: &[u8]) -> Result<Option<RecordBatch>> {
+        concat(self.batches(key)?)
+    }
+
+    fn remove(&mut self, key: &[u8]) -> Result<Vec<RecordBatch>> {
+        let batches = self.batches(key)?;
+
+        if let Some(removed) = self.memory.remove(key) {
+            self.discard_memory(&removed);
+        }
+
+        if let Some(removed) = self.spilled.remove(key) {
+            self.discard_spilled(&removed);
+            self.maybe_compact()?;
+        }
+
+        Ok(batches)
+    }
+
+    fn range_from(&mut self, start: &[u8]) -> Result<Vec<(Vec<u8>, Vec<RecordBatch>)>> {
+        let range = (Bound::Included(start), Bound::Unbounded);
+        let keys: BTreeSet<_> = self
+            .memory
+            .range::<[u8], _>(range)
+            .map(|(k, _)| k.clone())
+            .chain(self.spilled.range::<[u8], _>(range).map(|(k, _)| k.clone()))
+            .collect();
+
+        keys.into_iter()
+            .map(|key| {
+                let batches = self.batches(&key)?;
+                Ok((key, batches))
+            })
+            .collect()
+    }
+
+    fn remove_before(&mut self, end: &[u8]) -> Result<()> {
+        let memory = self.memory.split_off(end);
+        for removed in std::mem::replace(&mut self.memory, memory).into_values() {
+            self.discard_memory(&removed);
+        }
+
+        let spilled = self.spilled.split_off(end);
+        let removed = std::mem::replace(&mut self.spilled, spilled);
+        if !removed.is_empty() {
+            for locations in removed.into_values() {
+                self.discard_spilled(&locations);
+            }
+            self.maybe_compact()?;
+        }
+
+        Ok(())
+    }
+
+    fn first_key(&mut self) -> Result<Option<Vec<u8>>> {
+        Ok(self
+            .memory
+            .keys()
+            .next()
+            .into_iter()
+            .chain(self.spilled.keys().next())
+            .min()
+            .cloned())
+    }
+
+    fn stats(&self) -> Result<TableStats> {
+        let (rows, _) = batch_sizes(self.memory.values().flatten());
+        Ok(TableStats {
+            rows: Some(rows + self.spilled_rows),
+            memory_bytes: self.memory_bytes,
+            disk_bytes: self.file_len,
+            oldest_timestamp: None,
+        })
+    }
+}
+
+impl Drop for DiskStore {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// Keeps batches in a local RocksDB instance, encoded as Arrow IPC
+pub(crate) struct RocksDbStore {
+    dir: PathBuf,
+    // only taken when the store is dropped, so that the database is closed before its files are
+    // removed
+    db: Option<DB>,
+    next_seq: u64,
+}
+
+impl std::fmt::Debug for RocksDbStore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RocksDbStore")
+            .field("dir", &self.dir)
+            .field("next_seq", &self.next_seq)
+            .finish()
+    }
+}
+
+/// Encodes a key so that encoded keys sort in the same order as the keys themselves, and no
+/// encoded key is a prefix of another; this lets a sequence number be appended to order the
+/// batches stored under the same key.
+fn encode_key(key: &[u8]) -> Vec<u8> {
+    let mut encoded = Vec::with_capacity(key.len() + 2);
+    for b in key {
+        encoded.push(*b);
+        if *b == 0 {
+            encoded.push(0xff);
+        }
+    }
+    encoded.extend_from_slice(&[0, 1]);
+    encoded
+}
+
+fn decode_key(encoded: &[u8]) -> Result<Vec<u8>> {
+    let mut key = vec![];
+    let mut bytes = encoded.iter();
+    while let Some(b) = bytes.next() {
+        if *b != 0 {
+            key.push(*b);
+            continue;
+        }
+
+        match bytes.next() {
+            Some(0xff) => key.push(0),
+            Some(1) => return Ok(key),
+            _ => break,
+        }
+    }
+
+    bail!("invalid key in state store")
+}
+
+pub(crate) fn write_options() -> WriteOptions {
+    let mut options = WriteOptions::default();
+    // the store is rebuilt from the checkpoint on restart, so its writes don't need to survive
+    // a crash
+    options.disable_wal(true);
+    options
+}
+
+impl RocksDbStore {
+    pub(crate) fn open(dir: PathBuf) -> Result<Self> {
+        let mut options = Options::default();
+        options.create_if_missing(true);
+
+        let db = DB::open(&options, dir.join("db"))
+            .with_context(|| format!("failed to open RocksDB state store in {:?}", dir))?;
+
+        Ok(Self {
+            dir,
+            db: Some(db),
+            next_seq: 0,
+        })
+    }
+
+    fn db(&self) -> &DB {
+        self.db.as_ref().expect("state store is open until dropped")
+    }
+
+    fn batches(&self, key: &[u8]) -> Result<Vec<RecordBatch>> {
+        let prefix = encode_key(key);
+        let mut batches = vec![];
+        for item in self
+            .db()
+            .iterator(IteratorMode::From(&prefix, Direction::Forward))
+        {
+            let (k, v) = item?;
+            if !k.starts_with(&prefix) {
+                break;
+            }
+            batches.push(decode_batch(&v)?);
+        }
+        Ok(batches)
+    }
+
+    fn delete_range(&self, from: &[u8], to: &[u8]) -> Result<()> {
+        let mut batch = WriteBatch::default();
+        batch.delete_range(from, to);
+        self.db().write_opt(batch, &write_options())?;
+        Ok(())
+    }
+}
+
+impl BatchStore for RocksDbStore {
+    fn append(&mut self, key: &[u8], batch: RecordBatch) -> Result<()> {
+        let mut k = encode_key(key);
+        k.extend_from_slice(&self.next_seq.to_be_bytes());
+        self.next_seq += 1;
+
+        self.db()
+            .put_opt(k, encode_batch(&batch)?, &write_options())?;
+        Ok(())
+    }
+
+    fn get(&mut self, key: &[u8]) -> Result<Option<RecordBatch>> {
+        concat(self.batches(key)?)
+    }
+
+    fn remove(&mut self, key: &[u8]) -> Result<Vec<RecordBatch>> {
+        let batches = self.batches(key)?;
+
+        // every entry for the key sorts between its encoding and the encoding with its
+        // terminator bumped
+        let start = encode_key(key);
+        let mut end = start.clone();
+        *end.last_mut().unwrap() += 1;
+        self.delete_range(&start, &end)?;
+
+        Ok(batches)
+    }
+
+    fn range_from(&mut self, start: &[u8]) -> Result<Vec<(Vec<u8>, Vec<RecordBatch>)>> {
+        let start = encode_key(start);
+        let mut result: Vec<(Vec<u8>, Vec<RecordBatch>)> = vec![];
+
+        for item in self
+            .db()
+            .iterator(IteratorMode::From(&start, Direction::Forward))
+        {
+            let (k, v) = item?;
+            let key = decode_key(&k)?;
+            let batch = decode_batch(&v)?;
+
+            match result.last_mut() {
+                Some((last, batches)) if *last == key => batches.push(batch),
+                _ => result.push((key, vec![batch])),
+            }
+        }
+
+        Ok(result)
+    }
+
+    fn remove_before(&mut self, end: &[u8]) -> Result<()> {
+        self.delete_range(&[], &encode_key(end))
+    }
+
+    fn first_key(&mut self) -> Result<Option<Vec<u8>>> {
+        self.db()
+            .iterator(IteratorMode::Start)
+            .next()
+            .transpose()?
+            .map(|(k, _)| decode_key(&k))
+            .transpose()
+    }
+
+    fn stats(&self) -> Result<TableStats> {
+        // keys are batches rather than rows, so the number of rows isn't known
+        Ok(TableStats {
+            rows: None,
+            ..rocksdb_stats(self.db())?
+        })
+    }
+}
+
+impl Drop for RocksDbStore {
+    fn drop(&mut self) {
+        drop(self.db.take());
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow_array::Int64Array;
+    use arrow_schema::{DataType, Field, Schema};
+    use std::sync::Arc;
+
+    fn batch(values: &[i64]) -> RecordBatch {
+        RecordBatch::try_new(
+            Arc::new(Schema::new(vec![Field::new("v", DataType::Int64, false)])),
+            vec![Arc::new(Int64Array::from(values.to_vec()))],
+        )
+        .unwrap()
+    }
+
+    fn test_dir() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("arroyo-state-test-{}", rand::random::<u64>()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn check_store(mut store: impl BatchStore) {
+        store.append(b"b", batch(&[1])).unwrap();
+        store.append(b"a", batch(&[2])).unwrap();
+        store.append(b"a\0", batch(&[3])).unwrap();
+        store.append(b"a", batch(&[4, 5])).unwrap();
+        if let Some(rows) = store.stats().unwrap().rows {
+            assert_eq!(rows, 5);
+        }
+
+        assert_eq!(store.get(b"a").unwrap(), Some(batch(&[2, 4, 5])));
+        assert_eq!(store.get(b"a\0").unwrap(), Some(batch(&[3])));
+        assert_eq!(store.get(b"c").unwrap(), None);
+        assert_eq!(store.first_key().unwrap(), Some(b"a".to_vec()));
+
+        assert_eq!(
+            store.range_from(b"a\0").unwrap(),
+            vec![
+                (b"a\0".to_vec(), vec![batch(&[3])]),
+                (b"b".to_vec(), vec![batch(&[1])])
+            ]
+        );
+
+        let removed = store.remove(b"a").unwrap();
+        assert_eq!(concat(removed).unwrap(), Some(batch(&[2, 4, 5])));
+        assert_eq!(store.get(b"a").unwrap(), None);
+        assert_eq!(store.first_key().unwrap(), Some(b"a\0".to_vec()));
+
+        store.remove_before(b"b").unwrap();
+        store.append(b"b", batch(&[6])).unwrap();
+        assert_eq!(
+            store.range_from(b"").unwrap(),
+            vec![(b"b".to_vec(), vec![batch(&[1]), batch(&[6])])]
+        );
+
+        store.remove_before(b"c").unwrap();
+        assert_eq!(store.first_key().unwrap(), None);
+        if let Some(rows) = store.stats().unwrap().rows {
+            assert_eq!(rows, 0);
+        }
+    }
+
+    #[test]
+    fn test_memory_store() {
+        check_store(MemoryStore::default());
+    }
+
+    #[test]
+    fn test_disk_store() {
+        // spill everything, to exercise reading from and compacting the file
+        let dir = test_dir();
+        check_store(DiskStore::open(dir.clone(), 0).unwrap());
+        assert!(!dir.exists());
+
+        // and spill nothing
+        check_store(DiskStore::open(test_dir(), u64::MAX).unwrap());
+    }
+
+    #[test]
+    fn test_rocksdb_store() {
+        let dir = test_dir();
+        check_store(RocksDbStore::open(dir.clone()).unwrap());
+        assert!(!dir.exists());
+    }
+
+    #[test]
+    fn test_key_encoding() {
+        let keys: Vec<&[u8]> = vec![b"", b"\0", b"\0\0", b"\0\x01", b"a", b"a\0", b"a\xff", b"b"];
+        for pair in keys.windows(2) {
+            assert!(encode_key(pair[0]) < encode_key(pair[1]));
+        }
+        for key in keys {
+            assert_eq!(decode_key(&encode_key(key)).unwrap(), key);
+        }
+    }
+}
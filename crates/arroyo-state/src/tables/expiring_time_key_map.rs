@@ -6,29 +6,33 @@ use std::{
 };
 
 use anyhow::{anyhow, bail, Ok, Result};
-use arrow::compute::{concat_batches, filter_record_batch, kernels::aggregate, take};
+use arrow::compute::{filter_record_batch, kernels::aggregate, take};
+use arrow::json::LineDelimitedWriter;
 use arrow::row::OwnedRow;
+use arrow::util::display::{ArrayFormatter, FormatOptions};
 use arrow_array::{
     cast::AsArray,
     types::{TimestampNanosecondType, UInt64Type},
     BooleanArray, PrimitiveArray, RecordBatch, TimestampNanosecondArray, UInt64Array,
 };
 use arrow_ord::{partition::partition, sort::sort_to_indices};
+use arrow_schema::SchemaRef;
 use arroyo_rpc::{
     df::server_for_hash_array,
+    grpc::api::StateStorage,
     grpc::rpc::{
         ExpiringKeyedTimeSubtaskCheckpointMetadata, ExpiringKeyedTimeTableCheckpointMetadata,
         ExpiringKeyedTimeTableConfig, OperatorMetadata, ParquetTimeFile, TableEnum,
     },
     Converter,
 };
-use arroyo_storage::StorageProviderRef;
+use arroyo_storage::{StorageProviderRef, ThrottledWriter};
 use arroyo_types::{
     from_micros, from_nanos, print_time, server_for_hash, to_micros, to_nanos, TaskInfoRef,
 };
+use object_store::buffered::BufWriter;
 
 use futures::{StreamExt, TryStreamExt};
-use object_store::buffered::BufWriter;
 use parquet::{
     arrow::{async_reader::ParquetObjectReader, AsyncArrowWriter, ParquetRecordBatchStreamBuilder},
     basic::{Compression, ZstdLevel},
@@ -37,13 +41,17 @@ use parquet::{
 use tokio::sync::mpsc::Sender;
 
 use crate::{
-    parquet::ParquetStats, schemas::SchemaWithHashAndOperation, CheckpointMessage, StateMessage,
-    TableData,
+    compatibility::evolve_batch, parquet::ParquetStats, schemas::SchemaWithHashAndOperation,
+    CheckpointMessage, StateMessage, TableData,
 };
 use arroyo_rpc::df::{ArroyoSchema, ArroyoSchemaRef};
 use tracing::debug;
 
-use super::{table_checkpoint_path, CompactionConfig, Table, TableEpochCheckpointer};
+use super::batch_store::{open_store, BatchStore};
+use super::{
+    key_range_overlap, table_checkpoint_path, CompactionConfig, Table, TableEpochCheckpointer,
+    TableStats, ViewStats,
+};
 
 #[derive(Debug, Clone)]
 pub struct ExpiringTimeKeyTable {
@@ -60,11 +68,12 @@ impl ExpiringTimeKeyTable {
         &self,
         state_tx: Sender<StateMessage>,
         watermark: Option<SystemTime>,
+        storage: StateStorage,
     ) -> Result<ExpiringTimeKeyView> {
         let cutoff = self.get_cutoff(watermark);
         let files = self.get_files_with_filtering(cutoff);
 
-        let mut data: BTreeMap<SystemTime, Vec<RecordBatch>> = BTreeMap::new();
+        let mut data = open_store(storage, &self.task_info, &self.table_name)?;
         let timestamp_index = self.schema.timestamp_index();
         let batches_by_timestamp = self
             .call_on_filtered_batches(files, |batch| {
@@ -103,7 +112,7 @@ impl ExpiringTimeKeyTable {
 
         for (timestamp, batch) in batches_by_timestamp {
             if cutoff <= timestamp {
-                data.entry(timestamp).or_default().push(batch)
+                data.append(&time_key(timestamp), batch)?;
             }
         }
 
@@ -122,34 +131,53 @@ impl ExpiringTimeKeyTable {
     where
         F: Fn(RecordBatch) -> Result<Vec<T>> + Send + Sync, // Ensure `F` is a closure that can be sent and synced between threads
     {
-        let mut result = vec![];
-        for (file, needs_filtering) in files {
-            let object_meta = self.storage_provider.head(file.as_str()).await?;
-            let object_reader =
-                ParquetObjectReader::new(self.storage_provider.get_backing_store(), object_meta);
-            let reader_builder = ParquetRecordBatchStreamBuilder::new(object_reader).await?;
-            let mut stream = reader_builder.build()?;
-            // projection to trim the metadata fields. Should probably be factored out.
-            let projection: Vec<_> = (0..(stream.schema().fields().len() - 2)).collect();
-            while let Some(batch_result) = stream.next().await {
-                let mut batch = batch_result?;
-                if needs_filtering {
-                    match self
-                        .schema
-                        .filter_by_hash_index(batch, &self.task_info.key_range)?
-                    {
-                        None => continue,
-                        Some(filtered_batch) => batch = filtered_batch,
-                    };
-                }
-                if batch.num_rows() == 0 {
-                    continue;
+        let state_schema = self.schema.state_schema().schema.clone();
+        // projection to trim the metadata fields. Should probably be factored out.
+        let projection: Vec<_> = (0..(state_schema.fields().len() - 2)).collect();
+        let batch_processor = &batch_processor;
+
+        // files are read concurrently, and their results combined in order
+        let results: Vec<Vec<T>> = futures::stream::iter(files)
+            .map(|(file, needs_filtering)| {
+                let state_schema = state_schema.clone();
+                let projection = &projection;
+                async move {
+                    let object_meta = self.storage_provider.head(file.as_str()).await?;
+                    self.storage_provider.throttle(object_meta.size).await;
+                    let object_reader = ParquetObjectReader::new(
+                        self.storage_provider.get_backing_store(),
+                        object_meta,
+                    );
+                    let reader_builder =
+                        ParquetRecordBatchStreamBuilder::new(object_reader).await?;
+                    let mut stream = reader_builder.build()?;
+                    let mut result = vec![];
+                    while let Some(batch_result) = stream.next().await {
+                        // the file may have been written by an earlier version of the table
+                        let mut batch = evolve_batch(batch_result?, &state_schema)?;
+                        if needs_filtering {
+                            match self
+                                .schema
+                                .filter_by_hash_index(batch, &self.task_info.key_range)?
+                            {
+                                None => continue,
+                                Some(filtered_batch) => batch = filtered_batch,
+                            };
+                        }
+                        if batch.num_rows() == 0 {
+                            continue;
+                        }
+                        batch = batch.project(projection)?;
+                        result.extend(batch_processor(batch)?)
+                    }
+                    Ok(result)
                 }
-                batch = batch.project(&projection)?;
-                result.extend(batch_processor(batch)?)
-            }
-        }
-        Ok(result)
+            })
+            .buffered(self.storage_provider.transfer_options().read_concurrency)
+            .try_collect()
+            .await?;
+
+        Ok(results.into_iter().flatten().collect())
     }
 
     fn get_cutoff(&self, watermark: Option<SystemTime>) -> SystemTime {
@@ -163,17 +191,15 @@ impl ExpiringTimeKeyTable {
             .iter()
             .filter_map(|file| {
                 // file must have some data greater than the cutoff and routing keys within the range.
-                if cutoff <= from_micros(file.max_timestamp_micros)
-                    && (file.max_routing_key >= *self.task_info.key_range.start()
-                        && *self.task_info.key_range.end() >= file.min_routing_key)
-                {
-                    let needs_hash_filtering = *self.task_info.key_range.end()
-                        < file.max_routing_key
-                        || *self.task_info.key_range.start() > file.min_routing_key;
-                    Some((file.file.clone(), needs_hash_filtering))
-                } else {
-                    None
+                if cutoff > from_micros(file.max_timestamp_micros) {
+                    return None;
                 }
+                let needs_hash_filtering = key_range_overlap(
+                    &self.task_info.key_range,
+                    file.min_routing_key,
+                    file.max_routing_key,
+                )?;
+                Some((file.file.clone(), needs_hash_filtering))
             })
             .collect()
     }
@@ -182,11 +208,13 @@ impl ExpiringTimeKeyTable {
         &self,
         state_tx: Sender<StateMessage>,
         watermark: Option<SystemTime>,
+        storage: StateStorage,
     ) -> Result<KeyTimeView> {
         let cutoff = self.get_cutoff(watermark);
         let files = self.get_files_with_filtering(cutoff);
 
-        let mut view = KeyTimeView::new(self.clone(), state_tx)?;
+        let keyed_data = open_store(storage, &self.task_info, &self.table_name)?;
+        let mut view = KeyTimeView::new(self.clone(), state_tx, keyed_data)?;
         let batches_to_add = self
             .call_on_filtered_batches(files, |batch| {
                 let timestamp_array: &PrimitiveArray<TimestampNanosecondType> = batch
@@ -365,23 +393,44 @@ impl Table for ExpiringTimeKeyTable {
             .map(|file: ParquetTimeFile| file.file)
             .collect())
     }
+
+    fn rename_files(
+        mut checkpoint: Self::TableCheckpointMessage,
+        rename: &dyn Fn(&str) -> String,
+    ) -> Self::TableCheckpointMessage {
+        for file in &mut checkpoint.files {
+            file.file = rename(&file.file);
+        }
+        checkpoint
+    }
+
     fn apply_compacted_checkpoint(
         &self,
-        epoch: u32,
+        _epoch: u32,
         compacted_checkpoint: Self::TableSubtaskCheckpointMetadata,
         subtask_metadata: Self::TableSubtaskCheckpointMetadata,
     ) -> Result<Self::TableSubtaskCheckpointMetadata> {
-        let mut current_epoch_files: Vec<_> = subtask_metadata
+        // compaction runs in the background, so the subtask may have checkpointed since the
+        // epoch that was compacted; the files it wrote after that epoch are kept
+        let compacted_epoch = compacted_checkpoint
             .files
-            .into_iter()
-            .filter(|file| file.epoch == epoch)
-            .collect();
-        current_epoch_files.extend_from_slice(&compacted_checkpoint.files);
+            .iter()
+            .map(|file| file.epoch)
+            .max()
+            .unwrap_or_default();
+
+        let mut files = compacted_checkpoint.files;
+        files.extend(
+            subtask_metadata
+                .files
+                .into_iter()
+                .filter(|file| file.epoch > compacted_epoch),
+        );
 
         Ok(Self::TableSubtaskCheckpointMetadata {
             subtask_index: subtask_metadata.subtask_index,
             watermark: subtask_metadata.watermark,
-            files: current_epoch_files,
+            files,
         })
     }
 
@@ -391,62 +440,63 @@ impl Table for ExpiringTimeKeyTable {
         operator_metadata: &OperatorMetadata,
         current_metadata: Self::TableCheckpointMessage,
     ) -> Result<Option<Self::TableCheckpointMessage>> {
-        let mut epochs_in_generation: HashMap<u64, HashSet<u32>> = HashMap::new();
-        let mut files_by_generation: BTreeMap<u64, HashMap<String, ParquetTimeFile>> =
-            BTreeMap::new();
-        for file in current_metadata.files {
-            if compaction_config
-                .compact_generations
-                .contains(&file.generation)
-            {
-                epochs_in_generation
-                    .entry(file.generation)
-                    .or_default()
-                    .insert(file.epoch);
-            }
-            files_by_generation
-                .entry(file.generation)
-                .or_default()
-                .insert(file.file.to_string(), file);
+        let storage_provider = &compaction_config.storage_provider;
+        let mut files = current_metadata.files;
+        files.sort_by_key(|file| file.epoch);
+
+        let sizes: Vec<_> = futures::stream::iter(&files)
+            .map(|file| storage_provider.head(file.file.as_str()))
+            .buffered(storage_provider.transfer_options().read_concurrency)
+            .map_ok(|meta| meta.size as u64)
+            .try_collect()
+            .await?;
+
+        // views read files in epoch order, so that later rows win; to keep that order, only the
+        // files after the last one to reach the target size are merged
+        let first_small = sizes
+            .iter()
+            .rposition(|size| *size >= compaction_config.target_file_size)
+            .map_or(0, |i| i + 1);
+        let small_files = files.split_off(first_small);
+
+        let epochs: HashSet<_> = small_files.iter().map(|file| file.epoch).collect();
+        if epochs.len() < compaction_config.min_compaction_epochs.max(2) {
+            return Ok(None);
         }
+
         let schema: ArroyoSchema = config
             .schema
             .ok_or_else(|| anyhow!("expect schema"))?
             .try_into()?;
         let state_schema = SchemaWithHashAndOperation::new(Arc::new(schema), config.generational);
 
-        for (generation, epochs) in epochs_in_generation {
-            if epochs.len() < compaction_config.min_compaction_epochs {
-                continue;
-            }
-            let mut files = TimeTableCompactor::compact_files(
-                config.table_name,
-                epochs.into_iter().max().unwrap(),
-                generation + 1,
-                compaction_config.storage_provider.clone(),
-                state_schema,
-                Duration::from_micros(config.retention_micros),
-                operator_metadata,
-                files_by_generation
-                    .remove(&generation)
-                    .expect("will have been populated"),
-            )
-            .await?;
-            files.extend(
-                files_by_generation
-                    .into_values()
-                    .flat_map(|files| files.into_values()),
-            );
-            return Ok(Some(ExpiringKeyedTimeTableCheckpointMetadata { files }));
-        }
-        Ok(None)
+        let compacted = TimeTableCompactor::compact_files(
+            config.table_name,
+            epochs.into_iter().max().unwrap(),
+            small_files
+                .iter()
+                .map(|file| file.generation)
+                .max()
+                .unwrap()
+                + 1,
+            compaction_config.target_file_size,
+            storage_provider.clone(),
+            state_schema,
+            Duration::from_micros(config.retention_micros),
+            operator_metadata,
+            small_files,
+        )
+        .await?;
+
+        files.extend(compacted);
+        Ok(Some(ExpiringKeyedTimeTableCheckpointMetadata { files }))
     }
 }
 
 struct CompactedFileWriter {
     file_name: String,
     schema: SchemaWithHashAndOperation,
-    writer: Option<AsyncArrowWriter<BufWriter>>,
+    writer: Option<AsyncArrowWriter<ThrottledWriter<BufWriter>>>,
     parquet_stats: Option<ParquetStats>,
 }
 
@@ -455,32 +505,46 @@ struct TimeTableCompactor {
     schema: SchemaWithHashAndOperation,
     operator_metadata: OperatorMetadata,
     table: String,
+    epoch: u32,
+    generation: u64,
+    target_file_size: u64,
     writers: HashMap<usize, CompactedFileWriter>,
+    // the number of files started for each partition
+    parts: HashMap<usize, usize>,
+    finished: Vec<ParquetTimeFile>,
 }
 
 impl TimeTableCompactor {
+    /// Merges the files, which must be in epoch order, into files for each partition of the
+    /// operator, starting a new file for a partition once its current one reaches the target size
     #[allow(clippy::too_many_arguments)]
     async fn compact_files(
         table: String,
         epoch: u32,
         generation: u64,
+        target_file_size: u64,
         storage_provider: StorageProviderRef,
         schema: SchemaWithHashAndOperation,
         retention: Duration,
         operator_metadata: &OperatorMetadata,
-        files: HashMap<String, ParquetTimeFile>,
+        files: Vec<ParquetTimeFile>,
     ) -> Result<Vec<ParquetTimeFile>> {
         let mut compactor = Self {
             table,
             storage_provider,
             schema: schema.clone(),
             operator_metadata: operator_metadata.clone(),
+            epoch,
+            generation,
+            target_file_size,
             writers: HashMap::new(),
+            parts: HashMap::new(),
+            finished: vec![],
         };
         let cutoff = operator_metadata
             .min_watermark
             .map(|min_micros| from_micros(min_micros) - retention);
-        for (file_name, file) in files {
+        for file in files {
             let max_file_timestamp = from_micros(file.max_timestamp_micros);
             if cutoff
                 .map(|cutoff| max_file_timestamp < cutoff)
@@ -490,7 +554,7 @@ impl TimeTableCompactor {
             }
             let reader = ParquetObjectReader::new(
                 compactor.storage_provider.get_backing_store(),
-                compactor.storage_provider.head(file_name.as_str()).await?,
+                compactor.storage_provider.head(file.file.as_str()).await?,
             );
             let first_partition =
                 server_for_hash(file.min_routing_key, operator_metadata.parallelism as usize);
@@ -501,6 +565,7 @@ impl TimeTableCompactor {
             let mut stream = reader_builder.build()?;
             // projection to trim the metadata fields. Should probably be factored out.
             while let Some(batch) = stream.try_next().await? {
+                let batch = evolve_batch(batch, &schema.state_schema().schema)?;
                 // Filter by _timestamp field
                 let time_filtered = schema.state_schema().filter_by_time(batch, cutoff)?;
                 if time_filtered.num_rows() == 0 {
@@ -545,12 +610,13 @@ impl TimeTableCompactor {
                 }
             }
         }
-        compactor.finish(epoch, generation).await
+        compactor.finish().await
     }
 
     async fn write_batch(&mut self, partition: usize, record_batch: RecordBatch) -> Result<()> {
         if let std::collections::hash_map::Entry::Vacant(e) = self.writers.entry(partition) {
-            let file_name = table_checkpoint_path(
+            let part = self.parts.entry(partition).or_default();
+            let mut file_name = table_checkpoint_path(
                 &self.operator_metadata.job_id,
                 &self.operator_metadata.operator_id,
                 &self.table,
@@ -558,6 +624,11 @@ impl TimeTableCompactor {
                 self.operator_metadata.epoch,
                 true,
             );
+            if *part > 0 {
+                file_name = format!("{}-{}", file_name, part);
+            }
+            *part += 1;
+
             let buf_writer = self.storage_provider.buf_writer(file_name.as_str());
 
             let writer = Some(AsyncArrowWriter::try_new(
@@ -576,19 +647,33 @@ impl TimeTableCompactor {
 
         writer.write_batch(record_batch).await?;
 
+        if writer.size() >= self.target_file_size {
+            let writer = self.writers.remove(&partition).unwrap();
+            self.finished
+                .push(writer.finish(self.epoch, self.generation).await?);
+        }
+
         Ok(())
     }
 
-    async fn finish(self, epoch: u32, generation: u64) -> Result<Vec<ParquetTimeFile>> {
-        let mut results = vec![];
+    async fn finish(mut self) -> Result<Vec<ParquetTimeFile>> {
         for writer in self.writers.into_values() {
-            results.push(writer.finish(epoch, generation).await?);
+            self.finished
+                .push(writer.finish(self.epoch, self.generation).await?);
         }
-        Ok(results)
+        Ok(self.finished)
     }
 }
 
 impl CompactedFileWriter {
+    /// The number of bytes written so far, including those buffered for the current row group
+    fn size(&self) -> u64 {
+        self.writer
+            .as_ref()
+            .map(|w| (w.bytes_written() + w.in_progress_size()) as u64)
+            .unwrap_or_default()
+    }
+
     async fn write_batch(&mut self, record_batch: RecordBatch) -> Result<()> {
         let mut parquet_stats = self.schema.batch_stats_from_state_batch(&record_batch)?;
         if let Some(other) = self.parquet_stats.take() {
@@ -624,7 +709,7 @@ pub struct ExpiringTimeKeyTableCheckpointer {
     file_name: String,
     parent: ExpiringTimeKeyTable,
     epoch: u32,
-    writer: Option<AsyncArrowWriter<BufWriter>>,
+    writer: Option<AsyncArrowWriter<ThrottledWriter<BufWriter>>>,
     parquet_stats: Option<ParquetStats>,
     prior_files: Vec<ParquetTimeFile>,
 }
@@ -704,8 +789,12 @@ impl TableEpochCheckpointer for ExpiringTimeKeyTableCheckpointer {
             .filter(|file| {
                 // file must have some data greater than the cutoff and routing keys within the range.
                 cutoff <= file.max_timestamp_micros
-                    && (file.max_routing_key >= *self.parent.task_info.key_range.start()
-                        && *self.parent.task_info.key_range.end() >= file.min_routing_key)
+                    && key_range_overlap(
+                        &self.parent.task_info.key_range,
+                        file.min_routing_key,
+                        file.max_routing_key,
+                    )
+                    .is_some()
             })
             .collect();
         let mut bytes = 0;
@@ -772,40 +861,51 @@ impl ExpiringTimeKeyTableCheckpointer {
     }
 }
 
+/// Keys batches by their max timestamp, as the big-endian nanoseconds since the epoch so that
+/// stores order them by time
+fn time_key(time: SystemTime) -> [u8; 16] {
+    to_nanos(time).to_be_bytes()
+}
+
+fn key_time(key: &[u8]) -> Result<SystemTime> {
+    Ok(from_nanos(u128::from_be_bytes(key.try_into().map_err(
+        |_| anyhow!("invalid timestamp key in state store"),
+    )?)))
+}
+
 #[derive(Debug)]
 pub struct ExpiringTimeKeyView {
     parent: ExpiringTimeKeyTable,
-    flushed_batches_by_max_timestamp: BTreeMap<SystemTime, Vec<RecordBatch>>,
+    // batches that have been written to the checkpoint, kept in the operator's chosen storage
+    flushed_batches_by_max_timestamp: Box<dyn BatchStore>,
     batches_to_flush: BTreeMap<SystemTime, Vec<RecordBatch>>,
     state_tx: Sender<StateMessage>,
 }
 
 impl ExpiringTimeKeyView {
     pub async fn flush(&mut self, watermark: Option<SystemTime>) -> Result<()> {
-        while let Some((max_timestamp, mut batches)) = self.batches_to_flush.pop_first() {
+        while let Some((max_timestamp, batches)) = self.batches_to_flush.pop_first() {
             if watermark
                 .map(|watermark| max_timestamp < watermark - self.parent.retention)
                 .unwrap_or(false)
             {
                 continue;
             }
-            for batch in &batches {
+            for batch in batches {
                 self.state_tx
                     .send(StateMessage::TableData {
                         table: self.parent.table_name.to_string(),
                         data: TableData::RecordBatch(batch.clone()),
                     })
                     .await?;
+                self.flushed_batches_by_max_timestamp
+                    .append(&time_key(max_timestamp), batch)?;
             }
-            self.flushed_batches_by_max_timestamp
-                .entry(max_timestamp)
-                .or_default()
-                .append(&mut batches);
         }
         if let Some(watermark) = watermark {
             let cutoff = watermark - self.parent.retention;
-            self.flushed_batches_by_max_timestamp =
-                self.flushed_batches_by_max_timestamp.split_off(&cutoff);
+            self.flushed_batches_by_max_timestamp
+                .remove_before(&time_key(cutoff))?;
         }
         Ok(())
     }
@@ -818,43 +918,46 @@ impl ExpiringTimeKeyView {
     }
 
     pub fn all_batches_for_watermark(
-        &self,
+        &mut self,
         watermark: Option<SystemTime>,
-    ) -> impl Iterator<Item = (&SystemTime, &Vec<RecordBatch>)> {
+    ) -> Result<Vec<(SystemTime, Vec<RecordBatch>)>> {
         // TODO: decide how to manage hash range ownership. Previously this was done by iterating over the contents of the record batch.
         // Should we use statistics?
         let cutoff = watermark
             .map(|watermark| watermark - self.parent.retention)
             .unwrap_or_else(|| SystemTime::UNIX_EPOCH);
         debug!("CUTOFF IS {}", print_time(cutoff));
-        let flushed_range = self.flushed_batches_by_max_timestamp.range(cutoff..);
-        let buffered_range = self.batches_to_flush.range(cutoff..);
-        flushed_range.chain(buffered_range)
+        let mut batches = self
+            .flushed_batches_by_max_timestamp
+            .range_from(&time_key(cutoff))?
+            .into_iter()
+            .map(|(key, batches)| Ok((key_time(&key)?, batches)))
+            .collect::<Result<Vec<_>>>()?;
+        batches.extend(
+            self.batches_to_flush
+                .range(cutoff..)
+                .map(|(time, batches)| (*time, batches.clone())),
+        );
+        Ok(batches)
     }
 
-    pub fn expire_timestamp(&mut self, timestamp: SystemTime) -> Vec<RecordBatch> {
-        let flushed_batches = self.flushed_batches_by_max_timestamp.remove(&timestamp);
-        let buffered_batches = self.batches_to_flush.remove(&timestamp);
-        match (flushed_batches, buffered_batches) {
-            (None, None) => vec![],
-            (None, Some(batches)) | (Some(batches), None) => batches,
-            (Some(mut flushed_batches), Some(mut buffered_batches)) => {
-                flushed_batches.append(&mut buffered_batches);
-                flushed_batches
-            }
+    pub fn expire_timestamp(&mut self, timestamp: SystemTime) -> Result<Vec<RecordBatch>> {
+        let mut batches = self
+            .flushed_batches_by_max_timestamp
+            .remove(&time_key(timestamp))?;
+        if let Some(mut buffered_batches) = self.batches_to_flush.remove(&timestamp) {
+            batches.append(&mut buffered_batches);
         }
+        Ok(batches)
     }
 
     pub async fn flush_timestamp(&mut self, bin_start: SystemTime) -> Result<()> {
         let Some(batches_to_flush) = self.batches_to_flush.remove(&bin_start) else {
             return Ok(());
         };
-        let flushed_vec = self
-            .flushed_batches_by_max_timestamp
-            .entry(bin_start)
-            .or_default();
         for batch in batches_to_flush {
-            flushed_vec.push(batch.clone());
+            self.flushed_batches_by_max_timestamp
+                .append(&time_key(bin_start), batch.clone())?;
             self.state_tx
                 .send(StateMessage::TableData {
                     table: self.parent.table_name.to_string(),
@@ -865,15 +968,74 @@ impl ExpiringTimeKeyView {
         Ok(())
     }
 
-    pub fn get_min_time(&self) -> Option<SystemTime> {
-        match (
-            self.batches_to_flush.keys().next(),
-            self.flushed_batches_by_max_timestamp.keys().next(),
-        ) {
+    /// Returns the rows in the view whose key columns are formatted (comma-separated) as `key`, or
+    /// all of them, up to `limit`. Each row is returned with its formatted key and as JSON.
+    pub fn query(&mut self, key: Option<&str>, limit: usize) -> Result<Vec<(String, String)>> {
+        let key_indices = self
+            .parent
+            .schema
+            .memory_schema()
+            .key_indices
+            .clone()
+            .unwrap_or_default();
+        let format_options = FormatOptions::default();
+
+        let mut entries = vec![];
+        for (_, batches) in self.all_batches_for_watermark(None)? {
+            for batch in batches {
+                let key_formatters = key_indices
+                    .iter()
+                    .map(|i| ArrayFormatter::try_new(batch.column(*i), &format_options))
+                    .collect::<Result<Vec<_>, _>>()?;
+
+                let mut buf = vec![];
+                let mut writer = LineDelimitedWriter::new(&mut buf);
+                writer.write(&batch)?;
+                writer.finish()?;
+                let rows = String::from_utf8(buf)?;
+
+                for (i, row) in rows.lines().enumerate() {
+                    let row_key = key_formatters
+                        .iter()
+                        .map(|f| f.value(i).to_string())
+                        .collect::<Vec<_>>()
+                        .join(",");
+                    if key.is_none() || key == Some(row_key.as_str()) {
+                        entries.push((row_key, row.to_string()));
+                        if entries.len() >= limit {
+                            return Ok(entries);
+                        }
+                    }
+                }
+            }
+        }
+        Ok(entries)
+    }
+
+    pub fn get_min_time(&mut self) -> Result<Option<SystemTime>> {
+        let flushed_time = self
+            .flushed_batches_by_max_timestamp
+            .first_key()?
+            .map(|key| key_time(&key))
+            .transpose()?;
+        Ok(match (self.batches_to_flush.keys().next(), flushed_time) {
             (None, None) => None,
-            (None, Some(time)) | (Some(time), None) => Some(*time),
-            (Some(buffered_time), Some(flushed_time)) => Some(*buffered_time.min(flushed_time)),
+            (None, Some(time)) => Some(time),
+            (Some(time), None) => Some(*time),
+            (Some(buffered_time), Some(flushed_time)) => Some((*buffered_time).min(flushed_time)),
+        })
+    }
+}
+
+impl ViewStats for ExpiringTimeKeyView {
+    fn stats(&mut self) -> Result<TableStats> {
+        let mut stats = self.flushed_batches_by_max_timestamp.stats()?;
+        for batch in self.batches_to_flush.values().flatten() {
+            stats.rows = stats.rows.map(|rows| rows + batch.num_rows() as u64);
+            stats.memory_bytes += batch.get_array_memory_size() as u64;
         }
+        stats.oldest_timestamp = self.get_min_time()?;
+        Ok(stats)
     }
 }
 
@@ -881,52 +1043,35 @@ impl ExpiringTimeKeyView {
 pub struct KeyTimeView {
     key_converter: Converter,
     parent: ExpiringTimeKeyTable,
-    keyed_data: HashMap<Vec<u8>, BatchData>,
+    // the value batches for each key, kept in the operator's chosen storage
+    keyed_data: Box<dyn BatchStore>,
     schema: ArroyoSchemaRef,
-    value_schema: ArroyoSchemaRef,
     // indices of schema that aren't keys, used for projection
     value_indices: Vec<usize>,
     state_tx: Sender<StateMessage>,
 }
 
-#[derive(Debug)]
-enum BatchData {
-    SingleBatch(RecordBatch),
-    BatchVec(Vec<RecordBatch>),
-}
-
 impl KeyTimeView {
-    fn new(parent: ExpiringTimeKeyTable, state_tx: Sender<StateMessage>) -> Result<Self> {
+    fn new(
+        parent: ExpiringTimeKeyTable,
+        state_tx: Sender<StateMessage>,
+        keyed_data: Box<dyn BatchStore>,
+    ) -> Result<Self> {
         let schema = parent.schema.memory_schema();
         let key_converter = schema.converter(false)?;
-        let value_schema = Arc::new(schema.schema_without_keys()?);
         let value_indices = schema.value_indices(true);
         Ok(Self {
             key_converter,
             parent,
-            keyed_data: HashMap::new(),
+            keyed_data,
             schema,
             value_indices,
-            value_schema,
             state_tx,
         })
     }
 
-    pub fn get_batch(&mut self, row: &[u8]) -> Result<Option<&RecordBatch>> {
-        if !self.keyed_data.contains_key(row) {
-            return Ok(None);
-        }
-        let Some(value) = self.keyed_data.get_mut(row) else {
-            unreachable!("just checked")
-        };
-        if let BatchData::BatchVec(batches) = value {
-            let coalesced_batches = concat_batches(&self.value_schema.schema, batches.iter())?;
-            *value = BatchData::SingleBatch(coalesced_batches);
-        }
-        let Some(BatchData::SingleBatch(single_batch)) = self.keyed_data.get(row) else {
-            unreachable!("just inserted")
-        };
-        Ok(Some(single_batch))
+    pub fn get_batch(&mut self, row: &[u8]) -> Result<Option<RecordBatch>> {
+        self.keyed_data.get(row)
     }
 
     pub async fn write_batch_to_state(&mut self, batch: RecordBatch) -> Result<()> {
@@ -965,31 +1110,19 @@ impl KeyTimeView {
                     .to_vec()
             };
             let key_row = self.key_converter.convert_columns(&key_columns)?;
-            let contents = self.keyed_data.get_mut(key_row.as_ref());
-            rows.push(key_row.clone());
-            let batch = match contents {
-                Some(BatchData::BatchVec(vec)) => {
-                    vec.push(value_batch);
-                    continue;
-                }
-                None => {
-                    self.keyed_data.insert(
-                        key_row.as_ref().to_vec(),
-                        BatchData::SingleBatch(value_batch),
-                    );
-                    continue;
-                }
-                Some(BatchData::SingleBatch(single_batch)) => single_batch.clone(),
-            };
-            self.keyed_data.insert(
-                key_row.as_ref().to_vec(),
-                BatchData::BatchVec(vec![batch, value_batch]),
-            );
+            self.keyed_data.append(key_row.as_ref(), value_batch)?;
+            rows.push(key_row);
         }
         Ok(rows)
     }
 }
 
+impl ViewStats for KeyTimeView {
+    fn stats(&mut self) -> Result<TableStats> {
+        self.keyed_data.stats()
+    }
+}
+
 #[derive(Debug)]
 pub struct LastKeyValueView {
     parent: ExpiringTimeKeyTable,
@@ -1086,6 +1219,28 @@ impl LastKeyValueView {
         )))
     }
 
+    /// Returns every value in the table, in the layout of the batches that were inserted
+    pub fn get_all_values(&self, schema: SchemaRef) -> Result<Option<RecordBatch>> {
+        if self.backing_map.is_empty() {
+            return Ok(None);
+        }
+
+        let mut key_rows = Vec::with_capacity(self.backing_map.len());
+        let mut value_rows = Vec::with_capacity(self.backing_map.len());
+        let mut timestamp_builder = TimestampNanosecondArray::builder(self.backing_map.len());
+        for (key, value) in &self.backing_map {
+            key_rows.push(key.as_slice());
+            value_rows.push(value.value_row_bytes.as_slice());
+            timestamp_builder.append_value(to_nanos(value.timestamp) as i64);
+        }
+
+        let mut columns = self.key_converter.convert_raw_rows(key_rows)?;
+        columns.extend(self.value_converter.convert_raw_rows(value_rows)?);
+        columns.push(Arc::new(timestamp_builder.finish()));
+
+        Ok(Some(RecordBatch::try_new(schema, columns)?))
+    }
+
     async fn insert_batch_internal(&mut self, batch: RecordBatch, is_backfill: bool) -> Result<()> {
         if batch.num_rows() == 0 {
             return Ok(());
@@ -1238,3 +1393,24 @@ impl LastKeyValueView {
         Ok(())
     }
 }
+
+impl ViewStats for LastKeyValueView {
+    fn stats(&mut self) -> Result<TableStats> {
+        Ok(TableStats {
+            rows: Some(self.backing_map.len() as u64),
+            memory_bytes: self
+                .backing_map
+                .iter()
+                .map(|(key, value)| (key.len() + value.value_row_bytes.len()) as u64)
+                .sum(),
+            disk_bytes: 0,
+            // keys are moved out of their old expiration when they're updated, which can leave it
+            // empty
+            oldest_timestamp: self
+                .expirations
+                .iter()
+                .find(|(_, keys)| !keys.is_empty())
+                .map(|(time, _)| *time),
+        })
+    }
+}
@@ -7,8 +7,9 @@ use arroyo_rpc::grpc::rpc::{
     OperatorMetadata, TableEnum,
 };
 use arroyo_storage::StorageProviderRef;
-use arroyo_types::{to_micros, Data, Key, TaskInfoRef};
+use arroyo_types::{from_micros, to_micros, Data, Key, TaskInfoRef};
 use bincode::config;
+use futures::StreamExt;
 
 use once_cell::sync::Lazy;
 use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
@@ -22,14 +23,16 @@ use tracing::info;
 use std::iter::Zip;
 
 use arroyo_rpc::grpc::rpc::GlobalKeyedTableConfig;
-use std::time::SystemTime;
+use std::time::{Duration, SystemTime};
 use std::{
     collections::{BTreeMap, HashMap},
     sync::Arc,
 };
 use tokio::sync::mpsc::Sender;
 
-use super::{table_checkpoint_path, CompactionConfig, Table, TableEpochCheckpointer};
+use super::{
+    table_checkpoint_path, CompactionConfig, Table, TableEpochCheckpointer, TableStats, ViewStats,
+};
 static GLOBAL_KEY_VALUE_SCHEMA: Lazy<Arc<Schema>> = Lazy::new(|| {
     let fields = vec![
         Field::new("key", DataType::Binary, false), // non-nullable BinaryArray for 'key'
@@ -44,6 +47,7 @@ pub struct GlobalKeyedTable {
     pub task_info: TaskInfoRef,
     storage_provider: StorageProviderRef,
     pub files: Vec<String>,
+    pub(crate) ttl: Option<Duration>,
 }
 
 impl GlobalKeyedTable {
@@ -73,8 +77,13 @@ impl GlobalKeyedTable {
         state_tx: Sender<StateMessage>,
     ) -> anyhow::Result<GlobalKeyedView<K, V>> {
         let mut data = HashMap::new();
-        for file in &self.files {
-            let contents = self.storage_provider.get(file.as_str()).await?;
+        let mut updated_at = HashMap::new();
+        // files are fetched concurrently, but applied in order so that later values win
+        let mut files = futures::stream::iter(&self.files)
+            .map(|file| self.storage_provider.get(file.as_str()))
+            .buffered(self.storage_provider.transfer_options().read_concurrency);
+        while let Some(contents) = files.next().await {
+            let contents = contents?;
             let reader = ParquetRecordBatchReaderBuilder::try_new(contents)?.build()?;
             for batch in reader {
                 for (key, value) in self.get_key_value_iterator(&batch?)? {
@@ -82,17 +91,46 @@ impl GlobalKeyedTable {
                         key.ok_or_else(|| anyhow!("unexpected null key from record batch"))?;
                     let value =
                         value.ok_or_else(|| anyhow!("unexpected null value from record batch"))?;
-                    data.insert(
-                        bincode::decode_from_slice(key, config::standard())?.0,
-                        bincode::decode_from_slice(value, config::standard())?.0,
-                    );
+                    let key: K = bincode::decode_from_slice(key, config::standard())?.0;
+
+                    if self.ttl.is_none() {
+                        data.insert(
+                            key,
+                            bincode::decode_from_slice(value, config::standard())?.0,
+                        );
+                        continue;
+                    }
+
+                    // values of tables with a ttl are written with when they were last inserted,
+                    // except by checkpoints taken before the table had a ttl
+                    let (value, inserted_at) = match bincode::decode_from_slice::<(V, u64), _>(
+                        value,
+                        config::standard(),
+                    ) {
+                        Ok(((value, inserted_at), _)) => (value, from_micros(inserted_at)),
+                        Err(_) => (
+                            bincode::decode_from_slice(value, config::standard())?.0,
+                            SystemTime::now(),
+                        ),
+                    };
+
+                    // every subtask writes all of the live entries of its view, so an entry may
+                    // have been written by several; the most recently inserted value wins
+                    if updated_at.get(&key).is_some_and(|t| *t > inserted_at) {
+                        continue;
+                    }
+                    updated_at.insert(key.clone(), inserted_at);
+                    data.insert(key, value);
                 }
             }
         }
+
         Ok(GlobalKeyedView {
             table_name: self.table_name.to_string(),
             data,
             state_tx,
+            ttl: self.ttl,
+            updated_at,
         })
     }
 }
@@ -135,6 +173,7 @@ impl Table for GlobalKeyedTable {
             files: checkpoint_message
                 .map(|checkpoint| checkpoint.files)
                 .unwrap_or_default(),
+            ttl: config.ttl_micros.map(Duration::from_micros),
         })
     }
 
@@ -193,6 +232,17 @@ impl Table for GlobalKeyedTable {
     ) -> Result<std::collections::HashSet<String>> {
         Ok(checkpoint.files.into_iter().collect())
     }
+
+    fn rename_files(
+        mut checkpoint: Self::TableCheckpointMessage,
+        rename: &dyn Fn(&str) -> String,
+    ) -> Self::TableCheckpointMessage {
+        for file in &mut checkpoint.files {
+            *file = rename(file);
+        }
+        checkpoint
+    }
+
     fn committing_data(
         config: Self::ConfigMessage,
         table_metadata: Self::TableCheckpointMessage,
@@ -252,6 +302,10 @@ impl TableEpochCheckpointer for GlobalKeyedCheckpointer {
             TableData::KeyedData { key, value } => {
                 self.latest_values.insert(key, value);
             }
+            TableData::DeletedKey { key } => {
+                // only expired entries of tables with a ttl are deleted
+                self.latest_values.remove(&key);
+            }
         }
         Ok(())
     }
@@ -318,6 +372,9 @@ pub struct GlobalKeyedView<K: Key, V: Data> {
     table_name: String,
     data: HashMap<K, V>,
     state_tx: Sender<StateMessage>,
+    ttl: Option<Duration>,
+    // when each entry was last inserted, if the table has a ttl
+    updated_at: HashMap<K, SystemTime>,
 }
 
 impl<K: Key, V: Data> GlobalKeyedView<K, V> {
@@ -326,15 +383,21 @@ impl<K: Key, V: Data> GlobalKeyedView<K, V> {
             table_name,
             data,
             state_tx,
+            ttl: None,
+            updated_at: HashMap::new(),
         }
     }
+
     pub async fn insert(&mut self, key: K, value: V) {
+        if self.ttl.is_some() {
+            self.updated_at.insert(key.clone(), SystemTime::now());
+        }
         self.state_tx
             .send(StateMessage::TableData {
                 table: self.table_name.clone(),
                 data: TableData::KeyedData {
                     key: bincode::encode_to_vec(&key, config::standard()).unwrap(),
-                    value: bincode::encode_to_vec(&value, config::standard()).unwrap(),
+                    value: self.encode_value(&key, &value),
                 },
             })
             .await
@@ -342,11 +405,179 @@ impl<K: Key, V: Data> GlobalKeyedView<K, V> {
         self.data.insert(key, value);
     }
 
+    /// Encodes a value for the checkpoint, along with when it was last inserted if the table has
+    /// a ttl
+    fn encode_value(&self, key: &K, value: &V) -> Vec<u8> {
+        match self.updated_at.get(key) {
+            Some(updated_at) if self.ttl.is_some() => {
+                bincode::encode_to_vec((value, to_micros(*updated_at)), config::standard()).unwrap()
+            }
+            _ => bincode::encode_to_vec(value, config::standard()).unwrap(),
+        }
+    }
+
+    /// Drops the entries that haven't been inserted within the table's ttl as of `now`, and writes
+    /// the rest to the checkpoint, returning the number of entries dropped. Called by the
+    /// [TableManager](super::table_manager::TableManager) before each checkpoint.
+    pub(crate) async fn expire(&mut self, now: SystemTime) -> usize {
+        let Some(ttl) = self.ttl else {
+            return 0;
+        };
+
+        let expired: Vec<K> = self
+            .updated_at
+            .iter()
+            .filter(|(_, updated_at)| **updated_at + ttl <= now)
+            .map(|(k, _)| k.clone())
+            .collect();
+
+        let mut messages = Vec::with_capacity(expired.len() + self.data.len());
+        for key in &expired {
+            self.updated_at.remove(key);
+            self.data.remove(key);
+            // it may have been inserted earlier in this epoch
+            messages.push(TableData::DeletedKey {
+                key: bincode::encode_to_vec(key, config::standard()).unwrap(),
+            });
+        }
+
+        messages.extend(self.data.iter().map(|(key, value)| TableData::KeyedData {
+            key: bincode::encode_to_vec(key, config::standard()).unwrap(),
+            value: self.encode_value(key, value),
+        }));
+
+        for data in messages {
+            self.state_tx
+                .send(StateMessage::TableData {
+                    table: self.table_name.clone(),
+                    data,
+                })
+                .await
+                .unwrap();
+        }
+
+        expired.len()
+    }
+
     pub fn get_all(&self) -> &HashMap<K, V> {
         &self.data
     }
 
+    /// Drops every entry from the view. The checkpoints of tables without a ttl only hold what
+    /// was inserted in their epoch, so this doesn't affect entries already inserted in this one.
+    pub fn clear(&mut self) {
+        self.data.clear();
+        self.updated_at.clear();
+    }
+
     pub fn get(&self, key: &K) -> Option<&V> {
         self.data.get(key)
     }
 }
+
+impl<K: Key, V: Data> ViewStats for GlobalKeyedView<K, V> {
+    fn stats(&mut self) -> Result<TableStats> {
+        // only the map's own allocation is counted, not memory owned by the keys and values
+        Ok(TableStats {
+            rows: Some(self.data.len() as u64),
+            memory_bytes: (self.data.capacity() * std::mem::size_of::<(K, V)>()) as u64,
+            oldest_timestamp: self.updated_at.values().min().copied(),
+            ..Default::default()
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{GlobalKeyedTable, GlobalKeyedView};
+    use crate::tables::{Table, TableEpochCheckpointer};
+    use crate::{CheckpointMessage, StateMessage, TableData};
+    use arroyo_storage::StorageProvider;
+    use arroyo_types::get_test_task_info;
+    use std::collections::HashMap;
+    use std::sync::Arc;
+    use std::time::{Duration, SystemTime};
+
+    #[tokio::test]
+    async fn test_expire() {
+        let (tx, mut rx) = tokio::sync::mpsc::channel(16);
+        let mut view = GlobalKeyedView::<u64, String>::new("t".to_string(), HashMap::new(), tx);
+        view.ttl = Some(Duration::from_secs(60));
+
+        view.insert(1, "a".to_string()).await;
+        view.insert(2, "b".to_string()).await;
+        let now = SystemTime::now();
+        view.updated_at.insert(1, now - Duration::from_secs(120));
+        while rx.try_recv().is_ok() {}
+
+        assert_eq!(view.expire(now).await, 1);
+        assert_eq!(view.get_all().keys().copied().collect::<Vec<_>>(), vec![2]);
+
+        // the expired entry is deleted from the checkpoint, and the live one written to it
+        let mut deleted = 0;
+        let mut written = 0;
+        while let Ok(StateMessage::TableData { data, .. }) = rx.try_recv() {
+            match data {
+                TableData::DeletedKey { .. } => deleted += 1,
+                TableData::KeyedData { .. } => written += 1,
+                _ => panic!("unexpected table data"),
+            }
+        }
+        assert_eq!((deleted, written), (1, 1));
+    }
+
+    #[tokio::test]
+    async fn test_expiry_after_restore() {
+        let storage = StorageProvider::for_url(&format!(
+            "file:///tmp/arroyo-testing/global-ttl-{}",
+            rand::random::<u64>()
+        ))
+        .await
+        .unwrap();
+        let table = GlobalKeyedTable {
+            table_name: "t".to_string(),
+            task_info: Arc::new(get_test_task_info()),
+            storage_provider: Arc::new(storage),
+            files: vec![],
+            ttl: Some(Duration::from_secs(60)),
+        };
+
+        let (tx, mut rx) = tokio::sync::mpsc::channel(16);
+        let mut view = table.memory_view::<u64, String>(tx).await.unwrap();
+        view.insert(1, "a".to_string()).await;
+        view.insert(2, "b".to_string()).await;
+        let now = SystemTime::now();
+        view.updated_at.insert(1, now - Duration::from_secs(50));
+
+        // checkpoint the view, as the table manager does
+        assert_eq!(view.expire(now).await, 0);
+        let mut checkpointer = table.epoch_checkpointer(1, None).unwrap();
+        while let Ok(StateMessage::TableData { data, .. }) = rx.try_recv() {
+            checkpointer.insert_data(data).await.unwrap();
+        }
+        let (metadata, _) = checkpointer
+            .finish(&CheckpointMessage {
+                epoch: 1,
+                time: now,
+                watermark: None,
+                then_stop: false,
+            })
+            .await
+            .unwrap()
+            .unwrap();
+
+        let restored = GlobalKeyedTable {
+            files: vec![metadata.file.unwrap()],
+            ..table
+        };
+        let (tx, _rx) = tokio::sync::mpsc::channel(16);
+        let mut view = restored.memory_view::<u64, String>(tx).await.unwrap();
+        assert_eq!(view.get_all().len(), 2);
+
+        // restored entries keep when they were inserted, so they expire on schedule
+        assert_eq!(view.expire(now + Duration::from_secs(20)).await, 1);
+        assert_eq!(view.get_all().keys().copied().collect::<Vec<_>>(), vec![2]);
+        assert_eq!(view.expire(now + Duration::from_secs(60)).await, 1);
+        assert!(view.get_all().is_empty());
+    }
+}
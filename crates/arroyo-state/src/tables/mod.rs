@@ -9,11 +9,14 @@ use arroyo_types::TaskInfoRef;
 use prost::Message;
 use std::any::Any;
 use std::collections::{HashMap, HashSet};
+use std::ops::RangeInclusive;
 use std::time::SystemTime;
 use tracing::debug;
 
+pub(crate) mod batch_store;
 pub mod expiring_time_key_map;
 pub mod global_keyed_map;
+pub mod rocksdb_keyed_map;
 pub mod table_manager;
 
 pub(crate) fn table_checkpoint_path(
@@ -41,6 +44,35 @@ fn base_path(job_id: &str, epoch: u32) -> String {
     format!("{}/checkpoints/checkpoint-{:0>7}", job_id, epoch)
 }
 
+/// Checks whether a file with state for the key hashes `min_routing_key..=max_routing_key` has
+/// state for any of the key-groups of a subtask's `key_range`, returning whether it also has state
+/// for other key-groups that has to be filtered out when it's read. That's the case when the file
+/// was written at a different parallelism, or by compaction.
+pub(crate) fn key_range_overlap(
+    key_range: &RangeInclusive<u64>,
+    min_routing_key: u64,
+    max_routing_key: u64,
+) -> Option<bool> {
+    (max_routing_key >= *key_range.start() && *key_range.end() >= min_routing_key)
+        .then(|| *key_range.start() > min_routing_key || *key_range.end() < max_routing_key)
+}
+
+/// How much state a subtask's view of a table holds, reported as metrics on each checkpoint
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct TableStats {
+    /// the number of rows (or keys) in the view, if its storage keeps track of it
+    pub rows: Option<u64>,
+    pub memory_bytes: u64,
+    /// the space taken on local disk, for views that spill or are backed by RocksDB
+    pub disk_bytes: u64,
+    /// the timestamp of the oldest data the view retains, for views that expire data by time
+    pub oldest_timestamp: Option<SystemTime>,
+}
+
+pub(crate) trait ViewStats {
+    fn stats(&mut self) -> Result<TableStats>;
+}
+
 pub struct DataTuple<K, V> {
     pub timestamp: SystemTime,
     pub key: K,
@@ -124,6 +156,13 @@ pub(crate) trait Table: Send + Sync + 'static + Clone {
         checkpoint: Self::TableCheckpointMessage,
     ) -> Result<HashSet<String>>;
 
+    // Rewrites the paths of the files referenced by the checkpoint, for when they've been copied
+    // to another location (like a savepoint).
+    fn rename_files(
+        checkpoint: Self::TableCheckpointMessage,
+        rename: &dyn Fn(&str) -> String,
+    ) -> Self::TableCheckpointMessage;
+
     async fn compact_data(
         config: Self::ConfigMessage,
         compaction_config: &CompactionConfig,
@@ -144,8 +183,9 @@ pub(crate) trait Table: Send + Sync + 'static + Clone {
 
 pub struct CompactionConfig {
     pub storage_provider: StorageProviderRef,
-    pub compact_generations: HashSet<u64>,
     pub min_compaction_epochs: usize,
+    // files at least this large are left as they are, and compacted files are split at this size
+    pub target_file_size: u64,
 }
 
 pub trait ErasedTable: Send + Sync + 'static {
@@ -211,6 +251,13 @@ pub trait ErasedTable: Send + Sync + 'static {
     where
         Self: Sized;
 
+    fn rename_files(
+        checkpoint: TableCheckpointMetadata,
+        rename: &dyn Fn(&str) -> String,
+    ) -> Result<TableCheckpointMetadata>
+    where
+        Self: Sized;
+
     fn as_any(&self) -> &dyn Any;
 
     #[allow(async_fn_in_trait)]
@@ -353,6 +400,24 @@ impl<T: Table + Sized + 'static> ErasedTable for T {
             Self::checked_proto_decode(T::table_type(), checkpoint.data)?,
         )
     }
+
+    fn rename_files(
+        checkpoint: TableCheckpointMetadata,
+        rename: &dyn Fn(&str) -> String,
+    ) -> Result<TableCheckpointMetadata>
+    where
+        Self: Sized,
+    {
+        let checkpoint = T::rename_files(
+            Self::checked_proto_decode(T::table_type(), checkpoint.data)?,
+            rename,
+        );
+        Ok(TableCheckpointMetadata {
+            table_type: T::table_type().into(),
+            data: checkpoint.encode_to_vec(),
+        })
+    }
+
     fn committing_data(
         config: TableConfig,
         table_metadata: &TableCheckpointMetadata,
@@ -0,0 +1,636 @@
+use crate::tables::batch_store::{rocksdb_stats, store_dir, write_options};
+use crate::{hash_key, CheckpointMessage, StateMessage, TableData};
+use anyhow::{anyhow, bail, Context, Result};
+use arroyo_rpc::grpc::rpc::{
+    OperatorMetadata, RocksDbKeyedTableCheckpointMetadata, RocksDbKeyedTableConfig,
+    RocksDbKeyedTableSubtaskCheckpointMetadata, RocksDbSstFile, TableEnum,
+};
+use arroyo_storage::StorageProviderRef;
+use arroyo_types::{Data, Key, TaskInfoRef};
+use bincode::config;
+use futures::StreamExt;
+use rocksdb::{IteratorMode, Options, SstFileWriter, WriteBatch, DB};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::marker::PhantomData;
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+use tokio::sync::mpsc::Sender;
+use tracing::info;
+
+use super::{
+    key_range_overlap, table_checkpoint_path, CompactionConfig, Table, TableEpochCheckpointer,
+    TableStats, ViewStats,
+};
+
+/// A keyed table whose values are kept in a local RocksDB instance rather than in memory, for
+/// keyed state that's too large to fit in RAM.
+///
+/// Each checkpoint uploads the keys that were written or deleted during its epoch as an SST
+/// file. Keys are prefixed by their routing hash, so a subtask restores its view by ingesting the
+/// files that overlap its key range, oldest first, then dropping the keys outside of it.
+#[derive(Debug, Clone)]
+pub struct RocksDbKeyedTable {
+    table_name: String,
+    task_info: TaskInfoRef,
+    storage_provider: StorageProviderRef,
+    files: Vec<RocksDbSstFile>,
+}
+
+/// The key a value is stored under: the routing hash of the key, big-endian so that keys sort by
+/// it, followed by the encoded key
+fn db_key(routing_key: u64, key: &[u8]) -> Vec<u8> {
+    let mut db_key = Vec::with_capacity(8 + key.len());
+    db_key.extend_from_slice(&routing_key.to_be_bytes());
+    db_key.extend_from_slice(key);
+    db_key
+}
+
+fn routing_key(db_key: &[u8]) -> Result<u64> {
+    Ok(u64::from_be_bytes(
+        db_key
+            .get(..8)
+            .ok_or_else(|| anyhow!("invalid key in RocksDB table"))?
+            .try_into()?,
+    ))
+}
+
+fn overlaps(file: &RocksDbSstFile, range: &RangeInclusive<u64>) -> bool {
+    key_range_overlap(range, file.min_routing_key, file.max_routing_key).is_some()
+}
+
+fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("arroyo-{}-{}", name, rand::random::<u64>()))
+}
+
+/// Writes the changes to an SST file; deleted keys are written as tombstones, so that they
+/// shadow the values in the files ingested before it
+fn write_sst(path: &Path, changes: &BTreeMap<Vec<u8>, Option<Vec<u8>>>) -> Result<()> {
+    let options = Options::default();
+    let mut writer = SstFileWriter::create(&options);
+    writer.open(path)?;
+    for (key, value) in changes {
+        match value {
+            Some(value) => writer.put(key, value)?,
+            None => writer.delete(key)?,
+        }
+    }
+    writer.finish()?;
+    Ok(())
+}
+
+/// A RocksDB instance in a local directory, which is removed when it's dropped
+struct LocalDb {
+    dir: PathBuf,
+    // only taken when dropped, so that the database is closed before its files are removed
+    db: Option<DB>,
+}
+
+impl LocalDb {
+    fn open(dir: PathBuf) -> Result<Self> {
+        let mut options = Options::default();
+        options.create_if_missing(true);
+
+        let db = DB::open(&options, dir.join("db"))
+            .with_context(|| format!("failed to open RocksDB table in {:?}", dir))?;
+
+        Ok(Self { dir, db: Some(db) })
+    }
+
+    fn db(&self) -> &DB {
+        self.db.as_ref().expect("database is open until dropped")
+    }
+
+    /// Ingests SST files, which must be given in the order they were written so that later
+    /// values win
+    async fn ingest(
+        &self,
+        storage_provider: &StorageProviderRef,
+        files: &[RocksDbSstFile],
+    ) -> Result<()> {
+        // files are fetched concurrently, but ingested in order
+        let mut contents = futures::stream::iter(files)
+            .map(|file| storage_provider.get(file.file.as_str()))
+            .buffered(storage_provider.transfer_options().read_concurrency);
+
+        let path = self.dir.join("ingest.sst");
+        while let Some(contents) = contents.next().await {
+            tokio::fs::write(&path, contents?).await?;
+            self.db().ingest_external_file(vec![&path])?;
+        }
+        let _ = tokio::fs::remove_file(&path).await;
+
+        Ok(())
+    }
+
+    /// Deletes every key whose routing hash is outside of the range
+    fn retain_range(&self, range: &RangeInclusive<u64>) -> Result<()> {
+        let mut batch = WriteBatch::default();
+        if *range.start() > 0 {
+            batch.delete_range(vec![], range.start().to_be_bytes().to_vec());
+        }
+        if *range.end() < u64::MAX {
+            let max = u64::MAX.to_be_bytes();
+            batch.delete_range((range.end() + 1).to_be_bytes().to_vec(), max.to_vec());
+            // no key sorts after every key with the largest routing hash, so those are deleted
+            // one by one
+            for item in self.db().prefix_iterator(max) {
+                let (key, _) = item?;
+                if !key.starts_with(&max) {
+                    break;
+                }
+                batch.delete(key);
+            }
+        }
+        self.db().write_opt(batch, &write_options())?;
+        Ok(())
+    }
+}
+
+impl Drop for LocalDb {
+    fn drop(&mut self) {
+        drop(self.db.take());
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+impl RocksDbKeyedTable {
+    pub(crate) async fn view<K: Key, V: Data>(
+        &self,
+        state_tx: Sender<StateMessage>,
+    ) -> Result<RocksDbKeyedView<K, V>> {
+        let db = LocalDb::open(store_dir(&self.task_info, &self.table_name)?)?;
+
+        let mut files: Vec<_> = self
+            .files
+            .iter()
+            .filter(|file| overlaps(file, &self.task_info.key_range))
+            .cloned()
+            .collect();
+        files.sort_by_key(|file| file.epoch);
+
+        db.ingest(&self.storage_provider, &files).await?;
+        db.retain_range(&self.task_info.key_range)?;
+
+        info!(
+            "restored RocksDB table {} from {} files",
+            self.table_name,
+            files.len()
+        );
+
+        Ok(RocksDbKeyedView {
+            table_name: self.table_name.clone(),
+            db,
+            state_tx,
+            _types: PhantomData,
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl Table for RocksDbKeyedTable {
+    type Checkpointer = RocksDbKeyedCheckpointer;
+
+    type ConfigMessage = RocksDbKeyedTableConfig;
+
+    type TableSubtaskCheckpointMetadata = RocksDbKeyedTableSubtaskCheckpointMetadata;
+
+    type TableCheckpointMessage = RocksDbKeyedTableCheckpointMetadata;
+
+    fn epoch_checkpointer(
+        &self,
+        epoch: u32,
+        previous_metadata: Option<Self::TableSubtaskCheckpointMetadata>,
+    ) -> Result<Self::Checkpointer> {
+        Ok(Self::Checkpointer {
+            table_name: self.table_name.clone(),
+            epoch,
+            task_info: self.task_info.clone(),
+            storage_provider: self.storage_provider.clone(),
+            files: previous_metadata
+                .map(|metadata| metadata.files)
+                .unwrap_or_default(),
+            changes: BTreeMap::new(),
+        })
+    }
+
+    fn from_config(
+        config: Self::ConfigMessage,
+        task_info: TaskInfoRef,
+        storage_provider: StorageProviderRef,
+        checkpoint_message: Option<Self::TableCheckpointMessage>,
+    ) -> Result<Self> {
+        Ok(Self {
+            table_name: config.table_name,
+            task_info,
+            storage_provider,
+            files: checkpoint_message
+                .map(|checkpoint| checkpoint.files)
+                .unwrap_or_default(),
+        })
+    }
+
+    fn merge_checkpoint_metadata(
+        _config: Self::ConfigMessage,
+        subtask_metadata: HashMap<u32, Self::TableSubtaskCheckpointMetadata>,
+    ) -> Result<Option<Self::TableCheckpointMessage>> {
+        if subtask_metadata.is_empty() {
+            return Ok(None);
+        }
+
+        // after rescaling, subtasks carry forward the files they inherited from the same
+        // subtask of the previous run
+        let mut seen_files = HashSet::new();
+        let mut files: Vec<_> = subtask_metadata
+            .into_values()
+            .flat_map(|metadata| metadata.files)
+            .filter(|file| seen_files.insert(file.file.clone()))
+            .collect();
+        files.sort_by_key(|file| file.epoch);
+
+        Ok(Some(RocksDbKeyedTableCheckpointMetadata { files }))
+    }
+
+    fn subtask_metadata_from_table(
+        &self,
+        table_metadata: Self::TableCheckpointMessage,
+    ) -> Result<Option<Self::TableSubtaskCheckpointMetadata>> {
+        Ok(Some(RocksDbKeyedTableSubtaskCheckpointMetadata {
+            subtask_index: self.task_info.task_index as u32,
+            files: table_metadata
+                .files
+                .into_iter()
+                .filter(|file| overlaps(file, &self.task_info.key_range))
+                .collect(),
+        }))
+    }
+
+    fn table_type() -> TableEnum {
+        TableEnum::RocksDbKeyValue
+    }
+
+    fn task_info(&self) -> TaskInfoRef {
+        self.task_info.clone()
+    }
+
+    fn files_to_keep(
+        _config: Self::ConfigMessage,
+        checkpoint: Self::TableCheckpointMessage,
+    ) -> Result<HashSet<String>> {
+        Ok(checkpoint.files.into_iter().map(|file| file.file).collect())
+    }
+
+    fn rename_files(
+        mut checkpoint: Self::TableCheckpointMessage,
+        rename: &dyn Fn(&str) -> String,
+    ) -> Self::TableCheckpointMessage {
+        for file in &mut checkpoint.files {
+            file.file = rename(&file.file);
+        }
+        checkpoint
+    }
+
+    /// Merges every file into a single SST holding just the live keys, once the table has files
+    /// from enough epochs
+    async fn compact_data(
+        config: Self::ConfigMessage,
+        compaction_config: &CompactionConfig,
+        operator_metadata: &OperatorMetadata,
+        current_metadata: Self::TableCheckpointMessage,
+    ) -> Result<Option<Self::TableCheckpointMessage>> {
+        let epochs: HashSet<_> = current_metadata.files.iter().map(|f| f.epoch).collect();
+        // a single file has nothing to be merged with
+        if epochs.len() < compaction_config.min_compaction_epochs.max(2) {
+            return Ok(None);
+        }
+        let epoch = *epochs.iter().max().unwrap();
+
+        let mut files = current_metadata.files;
+        files.sort_by_key(|file| file.epoch);
+
+        let dir = temp_path("rocksdb-compaction");
+        fs::create_dir_all(&dir)?;
+        let db = LocalDb::open(dir)?;
+        db.ingest(&compaction_config.storage_provider, &files)
+            .await?;
+
+        // the live keys are streamed out of the merged database, so they needn't fit in memory
+        let sst = db.dir.join("compacted.sst");
+        let options = Options::default();
+        let mut writer = SstFileWriter::create(&options);
+        writer.open(&sst)?;
+        let mut routing_keys = None;
+        for item in db.db().iterator(IteratorMode::Start) {
+            let (key, value) = item?;
+            let routing_key = routing_key(&key)?;
+            let min = routing_keys.map_or(routing_key, |(min, _)| min);
+            routing_keys = Some((min, routing_key));
+            writer.put(key, value)?;
+        }
+
+        let Some((min_routing_key, max_routing_key)) = routing_keys else {
+            // every key has been deleted; SST files can't be empty, so the files are left as is
+            return Ok(None);
+        };
+        writer.finish()?;
+
+        let path = table_checkpoint_path(
+            &operator_metadata.job_id,
+            &operator_metadata.operator_id,
+            &config.table_name,
+            0,
+            epoch,
+            true,
+        );
+        compaction_config
+            .storage_provider
+            .put(path.as_str(), tokio::fs::read(&sst).await?)
+            .await?;
+
+        info!(
+            "compacted {} files of RocksDB table {} into {}",
+            files.len(),
+            config.table_name,
+            path
+        );
+
+        Ok(Some(RocksDbKeyedTableCheckpointMetadata {
+            files: vec![RocksDbSstFile {
+                epoch,
+                file: path,
+                min_routing_key,
+                max_routing_key,
+            }],
+        }))
+    }
+
+    fn apply_compacted_checkpoint(
+        &self,
+        _epoch: u32,
+        compacted_checkpoint: Self::TableSubtaskCheckpointMetadata,
+        subtask_metadata: Self::TableSubtaskCheckpointMetadata,
+    ) -> Result<Self::TableSubtaskCheckpointMetadata> {
+        // the compacted file replaces every file up to the epoch it was compacted at
+        let compacted_epoch = compacted_checkpoint
+            .files
+            .iter()
+            .map(|file| file.epoch)
+            .max()
+            .unwrap_or_default();
+
+        let mut files = compacted_checkpoint.files;
+        files.extend(
+            subtask_metadata
+                .files
+                .into_iter()
+                .filter(|file| file.epoch > compacted_epoch),
+        );
+
+        Ok(Self::TableSubtaskCheckpointMetadata {
+            subtask_index: subtask_metadata.subtask_index,
+            files,
+        })
+    }
+}
+
+pub struct RocksDbKeyedCheckpointer {
+    table_name: String,
+    epoch: u32,
+    task_info: TaskInfoRef,
+    storage_provider: StorageProviderRef,
+    // the files written by previous epochs
+    files: Vec<RocksDbSstFile>,
+    // the latest value of each key changed this epoch, or None if it was deleted
+    changes: BTreeMap<Vec<u8>, Option<Vec<u8>>>,
+}
+
+#[async_trait::async_trait]
+impl TableEpochCheckpointer for RocksDbKeyedCheckpointer {
+    type SubTableCheckpointMessage = RocksDbKeyedTableSubtaskCheckpointMetadata;
+
+    async fn insert_data(&mut self, data: TableData) -> Result<()> {
+        match data {
+            TableData::KeyedData { key, value } => {
+                self.changes.insert(key, Some(value));
+            }
+            TableData::DeletedKey { key } => {
+                self.changes.insert(key, None);
+            }
+            TableData::RecordBatch(_) | TableData::CommitData { .. } => {
+                bail!("RocksDB tables only expect keyed data")
+            }
+        }
+        Ok(())
+    }
+
+    async fn finish(
+        mut self,
+        _checkpoint: &CheckpointMessage,
+    ) -> Result<Option<(Self::SubTableCheckpointMessage, usize)>> {
+        let mut bytes = 0;
+
+        if let (Some(min), Some(max)) =
+            (self.changes.keys().next(), self.changes.keys().next_back())
+        {
+            let (min_routing_key, max_routing_key) = (routing_key(min)?, routing_key(max)?);
+
+            let sst = temp_path("sst");
+            write_sst(&sst, &self.changes)?;
+            let contents = tokio::fs::read(&sst).await;
+            let _ = tokio::fs::remove_file(&sst).await;
+            let contents = contents?;
+            bytes = contents.len();
+
+            let path = table_checkpoint_path(
+                &self.task_info.job_id,
+                &self.task_info.operator_id,
+                &self.table_name,
+                self.task_info.task_index,
+                self.epoch,
+                false,
+            );
+            self.storage_provider.put(path.as_str(), contents).await?;
+
+            self.files.push(RocksDbSstFile {
+                epoch: self.epoch,
+                file: path,
+                min_routing_key,
+                max_routing_key,
+            });
+        }
+
+        Ok(Some((
+            RocksDbKeyedTableSubtaskCheckpointMetadata {
+                subtask_index: self.task_info.task_index as u32,
+                files: self.files,
+            },
+            bytes,
+        )))
+    }
+
+    fn table_type() -> TableEnum {
+        TableEnum::RocksDbKeyValue
+    }
+
+    fn subtask_index(&self) -> u32 {
+        self.task_info.task_index as u32
+    }
+}
+
+/// A keyed view whose values are read from and written to the subtask's local RocksDB instance;
+/// writes are also sent to the table's checkpointer, to be uploaded with the next checkpoint
+pub struct RocksDbKeyedView<K: Key, V: Data> {
+    table_name: String,
+    db: LocalDb,
+    state_tx: Sender<StateMessage>,
+    _types: PhantomData<(K, V)>,
+}
+
+impl<K: Key, V: Data> RocksDbKeyedView<K, V> {
+    fn db_key(key: &K) -> Vec<u8> {
+        db_key(
+            hash_key(key),
+            &bincode::encode_to_vec(key, config::standard()).unwrap(),
+        )
+    }
+
+    async fn send(&self, data: TableData) -> Result<()> {
+        self.state_tx
+            .send(StateMessage::TableData {
+                table: self.table_name.clone(),
+                data,
+            })
+            .await
+            .map_err(|_| anyhow!("state flusher for table {} closed", self.table_name))
+    }
+
+    pub fn get(&self, key: &K) -> Result<Option<V>> {
+        self.db
+            .db()
+            .get(Self::db_key(key))?
+            .map(|value| -> Result<V> {
+                Ok(bincode::decode_from_slice(&value, config::standard())?.0)
+            })
+            .transpose()
+    }
+
+    pub async fn insert(&mut self, key: K, value: V) -> Result<()> {
+        let key = Self::db_key(&key);
+        let value = bincode::encode_to_vec(&value, config::standard())?;
+
+        self.db.db().put_opt(&key, &value, &write_options())?;
+        self.send(TableData::KeyedData { key, value }).await
+    }
+
+    pub async fn remove(&mut self, key: &K) -> Result<()> {
+        let key = Self::db_key(key);
+
+        self.db.db().delete_opt(&key, &write_options())?;
+        self.send(TableData::DeletedKey { key }).await
+    }
+
+    /// Iterates over every key and value, in the order of their routing hashes
+    pub fn iter(&self) -> impl Iterator<Item = Result<(K, V)>> + '_ {
+        self.db
+            .db()
+            .iterator(IteratorMode::Start)
+            .map(|item| -> Result<(K, V)> {
+                let (key, value) = item?;
+                Ok((
+                    bincode::decode_from_slice(&key[8..], config::standard())?.0,
+                    bincode::decode_from_slice(&value, config::standard())?.0,
+                ))
+            })
+    }
+}
+
+impl<K: Key, V: Data> ViewStats for RocksDbKeyedView<K, V> {
+    fn stats(&mut self) -> Result<TableStats> {
+        rocksdb_stats(self.db.db())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_db() -> LocalDb {
+        let dir = temp_path("rocksdb-table-test");
+        fs::create_dir_all(&dir).unwrap();
+        LocalDb::open(dir).unwrap()
+    }
+
+    fn ingest_sst(db: &LocalDb, changes: &[(u64, &str, Option<&str>)]) {
+        let changes: BTreeMap<_, _> = changes
+            .iter()
+            .map(|(routing, key, value)| {
+                (
+                    db_key(*routing, key.as_bytes()),
+                    value.map(|v| v.as_bytes().to_vec()),
+                )
+            })
+            .collect();
+
+        let path = db.dir.join("test.sst");
+        write_sst(&path, &changes).unwrap();
+        db.db().ingest_external_file(vec![&path]).unwrap();
+    }
+
+    fn contents(db: &LocalDb) -> Vec<(u64, String, String)> {
+        db.db()
+            .iterator(IteratorMode::Start)
+            .map(|item| {
+                let (key, value) = item.unwrap();
+                (
+                    routing_key(&key).unwrap(),
+                    String::from_utf8(key[8..].to_vec()).unwrap(),
+                    String::from_utf8(value.to_vec()).unwrap(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_restore_from_ssts() {
+        let db = test_db();
+        let dir = db.dir.clone();
+
+        ingest_sst(
+            &db,
+            &[
+                (1, "a", Some("1")),
+                (5, "b", Some("2")),
+                (u64::MAX, "c", Some("3")),
+            ],
+        );
+        // later files overwrite and delete the values of earlier ones
+        ingest_sst(
+            &db,
+            &[(1, "a", Some("4")), (5, "b", None), (6, "d", Some("5"))],
+        );
+
+        assert_eq!(
+            contents(&db),
+            vec![
+                (1, "a".to_string(), "4".to_string()),
+                (6, "d".to_string(), "5".to_string()),
+                (u64::MAX, "c".to_string(), "3".to_string()),
+            ]
+        );
+
+        db.retain_range(&(2..=u64::MAX)).unwrap();
+        assert_eq!(
+            contents(&db),
+            vec![
+                (6, "d".to_string(), "5".to_string()),
+                (u64::MAX, "c".to_string(), "3".to_string()),
+            ]
+        );
+
+        db.retain_range(&(0..=6)).unwrap();
+        assert_eq!(contents(&db), vec![(6, "d".to_string(), "5".to_string())]);
+
+        drop(db);
+        assert!(!dir.exists());
+    }
+}
@@ -3,6 +3,7 @@ use std::any::Any;
 use std::{collections::HashMap, sync::Arc, time::SystemTime};
 
 use anyhow::{anyhow, bail, Result};
+use arroyo_rpc::grpc::api::StateStorage;
 use arroyo_rpc::CompactionResult;
 use arroyo_rpc::{
     grpc::rpc::{
@@ -12,7 +13,10 @@ use arroyo_rpc::{
     CheckpointCompleted, ControlResp,
 };
 use arroyo_storage::StorageProviderRef;
-use arroyo_types::{to_micros, CheckpointBarrier, Data, Key, TaskInfoRef};
+use arroyo_types::{
+    key_groups_for_server, to_micros, CheckpointBarrier, Data, Key, NonRetryableError, TaskInfoRef,
+};
+use futures::future::BoxFuture;
 use tokio::sync::{
     mpsc::{self, Receiver, Sender},
     oneshot,
@@ -20,6 +24,11 @@ use tokio::sync::{
 
 use tracing::{debug, error, info, warn};
 
+use crate::compatibility::check_operator_compatibility;
+use crate::metrics::{
+    TABLE_CHECKPOINT_GAUGE, TABLE_DISK_GAUGE, TABLE_MEMORY_GAUGE, TABLE_OLDEST_TIMESTAMP_GAUGE,
+    TABLE_SIZE_GAUGE,
+};
 use crate::{get_storage_provider, tables::global_keyed_map::GlobalKeyedTable, StateMessage};
 use crate::{CheckpointMessage, TableData};
 
@@ -27,7 +36,8 @@ use super::expiring_time_key_map::{
     ExpiringTimeKeyTable, ExpiringTimeKeyView, KeyTimeView, LastKeyValueView,
 };
 use super::global_keyed_map::GlobalKeyedView;
-use super::{ErasedCheckpointer, ErasedTable};
+use super::rocksdb_keyed_map::{RocksDbKeyedTable, RocksDbKeyedView};
+use super::{ErasedCheckpointer, ErasedTable, TableStats, ViewStats};
 
 #[allow(unused)]
 pub struct TableManager {
@@ -39,12 +49,23 @@ pub struct TableManager {
     task_info: TaskInfoRef,
     storage: StorageProviderRef,
     caches: HashMap<String, Box<dyn Any + Send>>,
+    // how the cached views of queryable tables are queried, as their types are erased
+    queries: HashMap<String, QueryFn>,
+    // how the size of each cached view is measured
+    stats: HashMap<String, StatsFn>,
+    // how the cached views of tables with a ttl are expired before each checkpoint
+    expirations: HashMap<String, ExpireFn>,
+    // where the operator's views keep their state
+    state_storage: StateStorage,
+    // the memory used by the operator's views when they were last measured, and the most they
+    // may use
+    memory_bytes: u64,
+    max_memory_bytes: Option<u64>,
 }
 
 pub struct BackendWriter {
     sender: Sender<StateMessage>,
     finish_rx: Option<oneshot::Receiver<()>>,
-    // TODO: compaction
 }
 
 #[allow(unused)]
@@ -78,6 +99,7 @@ impl BackendFlusher {
                                 operator_id: self.task_info.operator_id.clone(),
                                 task_index: self.task_info.task_index,
                                 error: err.to_string(),
+                                fatal: false,
                             })
                             .await
                             .unwrap();
@@ -131,11 +153,19 @@ impl BackendFlusher {
         };
         let mut metadatas = HashMap::new();
         let mut bytes = 0;
+        let task_index = self.task_info.task_index.to_string();
         for (table_name, checkpointer) in self.table_checkpointers.drain() {
-            if let Some((subtask_checkpoint_data, size)) = checkpointer.finish(&cp).await? {
-                metadatas.insert(table_name.clone(), subtask_checkpoint_data);
-                bytes += size;
-            }
+            let size = match checkpointer.finish(&cp).await? {
+                Some((subtask_checkpoint_data, size)) => {
+                    metadatas.insert(table_name.clone(), subtask_checkpoint_data);
+                    size
+                }
+                None => 0,
+            };
+            bytes += size;
+            TABLE_CHECKPOINT_GAUGE
+                .with_label_values(&[&self.task_info.operator_id, &task_index, &table_name])
+                .set(size as f64);
         }
 
         if let Some(compaction_metas) = compacted_tables {
@@ -224,6 +254,79 @@ impl BackendWriter {
     }
 }
 
+type QueryFn = fn(&mut (dyn Any + Send), Option<&str>, usize) -> Result<Vec<(String, String)>>;
+
+type StatsFn = fn(&mut (dyn Any + Send)) -> Result<TableStats>;
+
+fn view_stats<T: ViewStats + 'static>(view: &mut (dyn Any + Send)) -> Result<TableStats> {
+    view.downcast_mut::<T>()
+        .ok_or_else(|| anyhow!("unexpected view type"))?
+        .stats()
+}
+
+type ExpireFn = for<'a> fn(&'a mut (dyn Any + Send), SystemTime) -> BoxFuture<'a, usize>;
+
+fn expire_global_keyed_view<K: Key, V: Data>(
+    view: &mut (dyn Any + Send),
+    now: SystemTime,
+) -> BoxFuture<'_, usize> {
+    Box::pin(async move {
+        match view.downcast_mut::<GlobalKeyedView<K, V>>() {
+            Some(view) => view.expire(now).await,
+            None => 0,
+        }
+    })
+}
+
+fn query_global_keyed_view<K: Key, V: Data>(
+    view: &mut (dyn Any + Send),
+    key: Option<&str>,
+    limit: usize,
+) -> Result<Vec<(String, String)>> {
+    let view: &GlobalKeyedView<K, V> = view
+        .downcast_ref()
+        .ok_or_else(|| anyhow!("unexpected view type"))?;
+    Ok(view
+        .get_all()
+        .iter()
+        .map(|(k, v)| (format!("{:?}", k), format!("{:?}", v)))
+        .filter(|(k, _)| key.is_none() || key == Some(k.as_str()))
+        .take(limit)
+        .collect())
+}
+
+fn query_rocksdb_keyed_view<K: Key, V: Data>(
+    view: &mut (dyn Any + Send),
+    key: Option<&str>,
+    limit: usize,
+) -> Result<Vec<(String, String)>> {
+    let view: &RocksDbKeyedView<K, V> = view
+        .downcast_ref()
+        .ok_or_else(|| anyhow!("unexpected view type"))?;
+    let mut entries = vec![];
+    for entry in view.iter() {
+        let (k, v) = entry?;
+        let k = format!("{:?}", k);
+        if key.is_none() || key == Some(k.as_str()) {
+            entries.push((k, format!("{:?}", v)));
+            if entries.len() >= limit {
+                break;
+            }
+        }
+    }
+    Ok(entries)
+}
+
+fn query_expiring_time_key_view(
+    view: &mut (dyn Any + Send),
+    key: Option<&str>,
+    limit: usize,
+) -> Result<Vec<(String, String)>> {
+    view.downcast_mut::<ExpiringTimeKeyView>()
+        .ok_or_else(|| anyhow!("unexpected view type"))?
+        .query(key, limit)
+}
+
 impl TableManager {
     pub async fn new(
         task_info: TaskInfoRef,
@@ -231,7 +334,15 @@ impl TableManager {
         tx: Sender<ControlResp>,
         checkpoint_metadata: Option<OperatorCheckpointMetadata>,
     ) -> Result<Self> {
-        let storage = get_storage_provider().await?;
+        let storage = get_storage_provider(&task_info.job_id).await?;
+
+        if let Some(metadata) = &checkpoint_metadata {
+            check_operator_compatibility(
+                &task_info.operator_id,
+                &metadata.table_configs,
+                &table_configs,
+            )?;
+        }
 
         let tables = table_configs
             .iter()
@@ -257,6 +368,14 @@ impl TableManager {
                             table_restore_from,
                         )?) as Box<dyn ErasedTable>
                     }
+                    TableEnum::RocksDbKeyValue => {
+                        Box::new(<RocksDbKeyedTable as ErasedTable>::from_config(
+                            table_config.clone(),
+                            task_info.clone(),
+                            storage.clone(),
+                            table_restore_from,
+                        )?) as Box<dyn ErasedTable>
+                    }
                 };
                 Ok((table_name.to_string(), Arc::new(erased_table)))
             })
@@ -271,6 +390,17 @@ impl TableManager {
                 let Some(operator_metadata) = metadata.operator_metadata else {
                     bail!("missing operator metadata");
                 };
+                if operator_metadata.parallelism != task_info.parallelism as u64 {
+                    // each table only restores the state for the subtask's key-groups
+                    info!(
+                        "rescaling operator {} from parallelism {} to {}; subtask {} restores key-groups {:?}",
+                        task_info.operator_id,
+                        operator_metadata.parallelism,
+                        task_info.parallelism,
+                        task_info.task_index,
+                        key_groups_for_server(task_info.task_index, task_info.parallelism)
+                    );
+                }
                 epoch = operator_metadata.epoch + 1;
                 min_epoch = operator_metadata.epoch;
                 for (table, table_metadata) in metadata.table_checkpoint_metadata.clone() {
@@ -305,12 +435,55 @@ impl TableManager {
             tables,
             writer,
             task_info,
-            storage: Arc::clone(storage),
+            storage,
             caches: HashMap::new(),
+            queries: HashMap::new(),
+            stats: HashMap::new(),
+            expirations: HashMap::new(),
+            state_storage: StateStorage::Memory,
+            memory_bytes: 0,
+            max_memory_bytes: None,
         })
     }
 
+    /// Sets where the views of the operator's time-keyed tables keep their state, which must be
+    /// done before they're first used. Last-value views, used by updating aggregates, are
+    /// always kept in memory.
+    pub fn set_state_storage(&mut self, state_storage: StateStorage) {
+        self.state_storage = state_storage;
+    }
+
+    /// Limits the memory that the operator's views may use, which is checked against their size
+    /// as measured at each checkpoint
+    pub fn set_max_memory_bytes(&mut self, max_memory_bytes: Option<u64>) {
+        self.max_memory_bytes = max_memory_bytes;
+    }
+
+    /// Fails if the operator's views used more memory than they're allowed to when they were
+    /// last measured
+    pub fn check_memory_limit(&self) -> Result<()> {
+        match self.max_memory_bytes {
+            Some(max) if self.memory_bytes > max => Err(NonRetryableError(format!(
+                "the state of {}-{} uses {} bytes of memory, more than its limit of {} bytes",
+                self.task_info.operator_name, self.task_info.task_index, self.memory_bytes, max
+            ))
+            .into()),
+            _ => Ok(()),
+        }
+    }
+
     pub async fn checkpoint(&mut self, barrier: CheckpointBarrier, watermark: Option<SystemTime>) {
+        for (table_name, expire) in &self.expirations {
+            if let Some(view) = self.caches.get_mut(table_name) {
+                let expired = expire(view.as_mut(), barrier.timestamp).await;
+                if expired > 0 {
+                    debug!("expired {} entries of table {}", expired, table_name);
+                }
+            }
+        }
+
+        self.record_stats();
+
         self.writer
             .sender
             .send(StateMessage::Checkpoint(CheckpointMessage {
@@ -330,6 +503,45 @@ impl TableManager {
         }
     }
 
+    /// Updates the metrics for the size of the tables the operator has read
+    fn record_stats(&mut self) {
+        let task_index = self.task_info.task_index.to_string();
+        self.memory_bytes = 0;
+        for (table_name, measure) in &self.stats {
+            let Some(view) = self.caches.get_mut(table_name) else {
+                continue;
+            };
+            let stats = match measure(view.as_mut()) {
+                Ok(stats) => stats,
+                Err(e) => {
+                    warn!("failed to measure table {}: {:?}", table_name, e);
+                    continue;
+                }
+            };
+
+            let labels = [
+                self.task_info.operator_id.as_str(),
+                task_index.as_str(),
+                table_name.as_str(),
+            ];
+            if let Some(rows) = stats.rows {
+                TABLE_SIZE_GAUGE.with_label_values(&labels).set(rows as f64);
+            }
+            TABLE_MEMORY_GAUGE
+                .with_label_values(&labels)
+                .set(stats.memory_bytes as f64);
+            self.memory_bytes += stats.memory_bytes;
+            TABLE_DISK_GAUGE
+                .with_label_values(&labels)
+                .set(stats.disk_bytes as f64);
+            if let Some(oldest) = stats.oldest_timestamp {
+                TABLE_OLDEST_TIMESTAMP_GAUGE
+                    .with_label_values(&labels)
+                    .set(to_micros(oldest) as f64 / 1_000_000.0);
+            }
+        }
+    }
+
     pub async fn load_compacted(&mut self, compacted: CompactionResult) -> Result<()> {
         if compacted.operator_id != self.task_info.operator_id {
             bail!("shouldn't be loading compaction for other operator");
@@ -352,6 +564,31 @@ impl TableManager {
         Ok(())
     }
 
+    /// Looks up entries in the live state of one of the operator's tables, for inspecting it while
+    /// the job is running. Entries are returned with their keys and values formatted as strings,
+    /// and only match `key` if it's equal to the formatted key. Tables can be queried once the
+    /// operator has read them.
+    pub fn query_state(
+        &mut self,
+        table_name: &str,
+        key: Option<&str>,
+        limit: usize,
+    ) -> Result<Vec<(String, String)>> {
+        if !self.tables.contains_key(table_name) {
+            bail!("no registered table {}", table_name);
+        }
+        let (Some(cache), Some(query)) = (
+            self.caches.get_mut(table_name),
+            self.queries.get(table_name),
+        ) else {
+            bail!(
+                "table {} can't be queried, or hasn't been read by the operator yet",
+                table_name
+            );
+        };
+        query(cache.as_mut(), key, limit)
+    }
+
     pub async fn get_global_keyed_state<K: Key, V: Data>(
         &mut self,
         table_name: &str,
@@ -373,6 +610,14 @@ impl TableManager {
                 .await?;
             let cache: Box<dyn Any + Send> = Box::new(saved_data);
             e.insert(cache);
+            self.queries
+                .insert(table_name.to_string(), query_global_keyed_view::<K, V>);
+            self.stats
+                .insert(table_name.to_string(), view_stats::<GlobalKeyedView<K, V>>);
+            if global_keyed_table.ttl.is_some() {
+                self.expirations
+                    .insert(table_name.to_string(), expire_global_keyed_view::<K, V>);
+            }
         }
 
         let cache = self.caches.get_mut(table_name).unwrap();
@@ -387,6 +632,46 @@ impl TableManager {
         Ok(cache)
     }
 
+    /// Gets the view of a table configured with `rocksdb_table_config`, restoring it into a local
+    /// RocksDB instance from the checkpoint the first time it's used
+    pub async fn get_rocksdb_keyed_state<K: Key, V: Data>(
+        &mut self,
+        table_name: &str,
+    ) -> Result<&mut RocksDbKeyedView<K, V>> {
+        if let std::collections::hash_map::Entry::Vacant(e) =
+            self.caches.entry(table_name.to_string())
+        {
+            let table_implementation = self
+                .tables
+                .get(table_name)
+                .ok_or_else(|| anyhow!("no registered table {}", table_name))?;
+            let rocksdb_table = table_implementation
+                .as_any()
+                .downcast_ref::<RocksDbKeyedTable>()
+                .ok_or_else(|| anyhow!("wrong table type for table {}", table_name))?;
+            let saved_data = rocksdb_table
+                .view::<K, V>(self.writer.sender.clone())
+                .await?;
+            let cache: Box<dyn Any + Send> = Box::new(saved_data);
+            e.insert(cache);
+            self.queries
+                .insert(table_name.to_string(), query_rocksdb_keyed_view::<K, V>);
+            self.stats
+                .insert(table_name.to_string(), view_stats::<RocksDbKeyedView<K, V>>);
+        }
+
+        let cache = self.caches.get_mut(table_name).unwrap();
+        let cache: &mut RocksDbKeyedView<K, V> = cache.downcast_mut().ok_or_else(|| {
+            anyhow!(
+                "Failed to downcast table {} to key type {} and value type {}",
+                table_name,
+                std::any::type_name::<K>(),
+                std::any::type_name::<V>()
+            )
+        })?;
+        Ok(cache)
+    }
+
     pub async fn get_expiring_time_key_table(
         &mut self,
         table_name: &str,
@@ -404,10 +689,14 @@ impl TableManager {
                 .downcast_ref::<ExpiringTimeKeyTable>()
                 .ok_or_else(|| anyhow!("wrong table type for table {}", table_name))?;
             let saved_data = expiring_time_key_table
-                .get_view(self.writer.sender.clone(), watermark)
+                .get_view(self.writer.sender.clone(), watermark, self.state_storage)
                 .await?;
             let cache: Box<dyn Any + Send> = Box::new(saved_data);
             e.insert(cache);
+            self.queries
+                .insert(table_name.to_string(), query_expiring_time_key_view);
+            self.stats
+                .insert(table_name.to_string(), view_stats::<ExpiringTimeKeyView>);
         }
         let cache = self.caches.get_mut(table_name).unwrap();
         let cache: &mut ExpiringTimeKeyView = cache
@@ -433,10 +722,12 @@ impl TableManager {
                 .downcast_ref::<ExpiringTimeKeyTable>()
                 .ok_or_else(|| anyhow!("wrong table type for table {}", table_name))?;
             let saved_data = expiring_time_key_table
-                .get_key_time_view(self.writer.sender.clone(), watermark)
+                .get_key_time_view(self.writer.sender.clone(), watermark, self.state_storage)
                 .await?;
             let cache: Box<dyn Any + Send> = Box::new(saved_data);
             e.insert(cache);
+            self.stats
+                .insert(table_name.to_string(), view_stats::<KeyTimeView>);
         }
         let cache = self.caches.get_mut(table_name).unwrap();
         let cache: &mut KeyTimeView = cache
@@ -466,6 +757,8 @@ impl TableManager {
                 .await?;
             let cache: Box<dyn Any + Send> = Box::new(saved_data);
             e.insert(cache);
+            self.stats
+                .insert(table_name.to_string(), view_stats::<LastKeyValueView>);
         }
         let cache = self.caches.get_mut(table_name).unwrap();
         let cache: &mut LastKeyValueView = cache
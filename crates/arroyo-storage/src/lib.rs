@@ -1,7 +1,7 @@
-use arroyo_rpc::retry;
+use arroyo_rpc::resilience::ResilientClient;
 use aws::ArroyoCredentialProvider;
 use bytes::Bytes;
-use futures::{Stream, StreamExt};
+use futures::{Stream, StreamExt, TryStreamExt};
 use object_store::aws::{AmazonS3ConfigKey, AwsCredential};
 use object_store::buffered::BufWriter;
 use object_store::gcp::GoogleCloudStorageBuilder;
@@ -14,7 +14,7 @@ use object_store::{CredentialProvider, ObjectMeta};
 use regex::{Captures, Regex};
 use std::borrow::Cow;
 use std::fmt::{Debug, Formatter};
-use std::future::ready;
+use std::future::{ready, Future};
 use std::path::PathBuf;
 use std::str::FromStr;
 use std::{
@@ -22,9 +22,12 @@ use std::{
     sync::{Arc, OnceLock},
 };
 use thiserror::Error;
-use tracing::{debug, error};
+use tracing::{debug, warn};
 
 mod aws;
+mod transfer;
+
+pub use transfer::{RateLimiter, ThrottledWriter, TransferOptions};
 
 /// A reference-counted reference to a [StorageProvider].
 pub type StorageProviderRef = Arc<StorageProvider>;
@@ -39,6 +42,8 @@ pub struct StorageProvider {
     // May require storage_options to properly instantiate
     object_store_base_url: String,
     storage_options: HashMap<String, String>,
+    transfer: TransferOptions,
+    resilience: Arc<ResilientClient>,
 }
 
 impl Debug for StorageProvider {
@@ -63,6 +68,9 @@ pub enum StorageError {
 
     #[error("failed to load credentials: {0}")]
     CredentialsError(String),
+
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
 }
 
 // https://s3.us-west-2.amazonaws.com/DOC-EXAMPLE-BUCKET1/puppy.jpg
@@ -136,16 +144,20 @@ fn matchers() -> &'static HashMap<Backend, Vec<Regex>> {
     })
 }
 
-macro_rules! storage_retry {
-    ($e: expr) => {
-        retry!(
-            $e,
-            10,
-            Duration::from_millis(100),
-            Duration::from_secs(10),
-            |e| error!("Error: {}. Retrying...", e)
-        )
-    };
+/// Whether an object store error may be resolved by retrying the operation, rather than being
+/// a problem with the request itself
+fn is_retryable(e: &object_store::Error) -> bool {
+    !matches!(
+        e,
+        object_store::Error::NotFound { .. }
+            | object_store::Error::InvalidPath { .. }
+            | object_store::Error::NotSupported { .. }
+            | object_store::Error::AlreadyExists { .. }
+            | object_store::Error::Precondition { .. }
+            | object_store::Error::NotModified { .. }
+            | object_store::Error::NotImplemented
+            | object_store::Error::UnknownConfigurationKey { .. }
+    )
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -428,6 +440,7 @@ impl StorageProvider {
 
         let object_store = Arc::new(builder.build().map_err(Into::<StorageError>::into)?);
 
+        let resilience = ResilientClient::shared("object_store", &object_store_base_url);
         Ok(Self {
             config: BackendConfig::S3(config),
             object_store: object_store.clone(),
@@ -438,6 +451,8 @@ impl StorageProvider {
                 .into_iter()
                 .map(|(k, v)| (k.as_ref().to_string(), v))
                 .collect(),
+            transfer: TransferOptions::default(),
+            resilience,
         })
     }
 
@@ -458,6 +473,7 @@ impl StorageProvider {
 
         let object_store = Arc::new(builder.build()?);
 
+        let resilience = ResilientClient::shared("object_store", &object_store_base_url);
         Ok(Self {
             config: BackendConfig::GCS(config),
             object_store: object_store.clone(),
@@ -465,6 +481,8 @@ impl StorageProvider {
             object_store_base_url,
             canonical_url,
             storage_options: HashMap::new(),
+            transfer: TransferOptions::default(),
+            resilience,
         })
     }
 
@@ -482,6 +500,7 @@ impl StorageProvider {
 
         let canonical_url = format!("file://{}", config.path);
         let object_store_base_url = canonical_url.clone();
+        let resilience = ResilientClient::shared("object_store", &object_store_base_url);
         Ok(Self {
             config: BackendConfig::Local(config),
             object_store,
@@ -489,6 +508,8 @@ impl StorageProvider {
             canonical_url,
             object_store_base_url,
             storage_options: HashMap::new(),
+            transfer: TransferOptions::default(),
+            resilience,
         })
     }
 
@@ -525,16 +546,50 @@ impl StorageProvider {
         Ok(list)
     }
 
+    /// Lists the paths of all objects under `prefix`, relative to the key of this provider
+    pub async fn list_prefix(&self, prefix: impl Into<Path>) -> Result<Vec<Path>, StorageError> {
+        let prefix = prefix.into();
+        let prefix = self.qualify_path(&prefix);
+        let key_part_count = self
+            .config
+            .key()
+            .map(|key| key.parts().count())
+            .unwrap_or_default();
+
+        self.retry("list", || async {
+            self.object_store
+                .list(Some(prefix.as_ref()))
+                .map_ok(|meta| meta.location.parts().skip(key_part_count).collect())
+                .try_collect()
+                .await
+        })
+        .await
+    }
+
+    /// Runs an operation against the object store, retrying it if it fails with a transient
+    /// error, as configured by the `resilience` section of the config
+    async fn retry<T, F, Fut>(&self, operation: &str, f: F) -> Result<T, StorageError>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, object_store::Error>>,
+    {
+        Ok(self.resilience.call(operation, is_retryable, f).await?)
+    }
+
     pub async fn get(&self, path: impl Into<Path>) -> Result<Bytes, StorageError> {
         let path = path.into();
+        let path = self.qualify_path(&path);
+        // a read that fails partway through is started again from the beginning
         let bytes = self
-            .object_store
-            .get(&self.qualify_path(&path))
-            .await
-            .map_err(Into::<StorageError>::into)?
-            .bytes()
+            .retry("get", || async {
+                self.object_store.get(&path).await?.bytes().await
+            })
             .await?;
 
+        // the size isn't known until the object has been read, so this delays the transfers
+        // that come after it
+        self.throttle(bytes.len()).await;
+
         Ok(bytes)
     }
 
@@ -543,29 +598,28 @@ impl StorageProvider {
         path: impl Into<Path>,
     ) -> Result<Option<Bytes>, StorageError> {
         let path: Path = path.into();
-        match self.object_store.get(&self.qualify_path(&path)).await {
-            Ok(obj) => {
-                let bytes = obj.bytes().await?;
-                Ok(Some(bytes))
-            }
-            Err(err) => {
-                if let object_store::Error::NotFound { .. } = &err {
-                    return Ok(None);
-                }
-                Err(err.into())
+        let path = self.qualify_path(&path);
+        self.retry("get", || async {
+            match self.object_store.get(&path).await {
+                Ok(obj) => Ok(Some(obj.bytes().await?)),
+                Err(object_store::Error::NotFound { .. }) => Ok(None),
+                Err(err) => Err(err),
             }
-        }
+        })
+        .await
     }
 
     pub async fn exists<P: Into<Path>>(&self, path: P) -> Result<bool, StorageError> {
         let path: Path = path.into();
-        let exists = self.object_store.head(&self.qualify_path(&path)).await;
-
-        match exists {
-            Ok(_) => Ok(true),
-            Err(object_store::Error::NotFound { .. }) => Ok(false),
-            Err(e) => Err(e.into()),
-        }
+        let path = self.qualify_path(&path);
+        self.retry("head", || async {
+            match self.object_store.head(&path).await {
+                Ok(_) => Ok(true),
+                Err(object_store::Error::NotFound { .. }) => Ok(false),
+                Err(e) => Err(e),
+            }
+        })
+        .await
     }
 
     pub async fn get_as_stream(
@@ -574,22 +628,120 @@ impl StorageProvider {
     ) -> Result<impl tokio::io::AsyncRead, StorageError> {
         let path = path.into();
         let path = self.qualify_path(&path);
-        let bytes = storage_retry!(self.object_store.get(&path).await)
-            .map_err(Into::<StorageError>::into)?
+        let bytes = self
+            .retry("get", || self.object_store.get(&path))
+            .await?
             .into_stream();
 
         Ok(tokio_util::io::StreamReader::new(bytes))
     }
 
     pub async fn put(&self, path: impl Into<Path>, bytes: Vec<u8>) -> Result<(), StorageError> {
-        let bytes = PutPayload::from(Bytes::from(bytes));
         let path = path.into();
+
+        // large objects are uploaded as multiple parts in parallel
+        if self.multipart_store.is_some() && bytes.len() > self.transfer.upload_part_size {
+            return self.put_multipart(&path, Bytes::from(bytes)).await;
+        }
+
+        self.throttle(bytes.len()).await;
+        let bytes = PutPayload::from(Bytes::from(bytes));
         let path = self.qualify_path(&path);
-        storage_retry!(self.object_store.put(&path, bytes.clone()).await)?;
+        self.retry("put", || self.object_store.put(&path, bytes.clone()))
+            .await?;
+
+        Ok(())
+    }
+
+    /// Uploads the object in parts of the configured size, up to the configured number of parts
+    /// at a time. Each part is retried on its own, so a transient failure doesn't start the
+    /// whole upload again; if a part still fails, the upload is aborted.
+    async fn put_multipart(&self, path: &Path, bytes: Bytes) -> Result<(), StorageError> {
+        let path = self.qualify_path(path);
+        let path = path.as_ref();
+        let multipart_id = self.start_multipart(path).await?;
+        let multipart_id = &multipart_id;
+
+        let part_size = self.transfer.upload_part_size;
+        let parts = futures::stream::iter((0..bytes.len()).step_by(part_size).enumerate())
+            .map(|(part_number, start)| {
+                let part = bytes.slice(start..(start + part_size).min(bytes.len()));
+                async move {
+                    self.throttle(part.len()).await;
+                    self.add_multipart(path, multipart_id, part_number, part)
+                        .await
+                }
+            })
+            .buffered(self.transfer.upload_concurrency)
+            .try_collect::<Vec<_>>()
+            .await;
+
+        match parts {
+            Ok(parts) => self.close_multipart(path, multipart_id, parts).await,
+            Err(e) => {
+                if let Err(abort_error) = self
+                    .get_multipart()
+                    .abort_multipart(path, multipart_id)
+                    .await
+                {
+                    warn!(
+                        "failed to abort multipart upload of {}: {}",
+                        path, abort_error
+                    );
+                }
+                Err(e)
+            }
+        }
+    }
+
+    /// Moves the object at `from` to `to`, replacing any object already there. Stores without a
+    /// native rename, like S3, implement this as a copy followed by a delete.
+    pub async fn rename(
+        &self,
+        from: impl Into<Path>,
+        to: impl Into<Path>,
+    ) -> Result<(), StorageError> {
+        let from = from.into();
+        let to = to.into();
+        let (from, to) = (self.qualify_path(&from), self.qualify_path(&to));
+        self.retry("rename", || self.object_store.rename(&from, &to))
+            .await?;
 
         Ok(())
     }
 
+    /// Copies the object at `from` to `to`, replacing any object already there
+    pub async fn copy(
+        &self,
+        from: impl Into<Path>,
+        to: impl Into<Path>,
+    ) -> Result<(), StorageError> {
+        let from = from.into();
+        let to = to.into();
+        let (from, to) = (self.qualify_path(&from), self.qualify_path(&to));
+        self.retry("copy", || self.object_store.copy(&from, &to))
+            .await?;
+
+        Ok(())
+    }
+
+    /// Waits until `bytes` can be transferred without exceeding the bandwidth limit of the
+    /// provider, if it has one
+    pub async fn throttle(&self, bytes: usize) {
+        if let Some(rate_limiter) = &self.transfer.rate_limiter {
+            rate_limiter.acquire(bytes).await;
+        }
+    }
+
+    pub fn with_transfer_options(mut self, transfer: TransferOptions) -> Self {
+        self.transfer = transfer;
+        self
+    }
+
+    pub fn transfer_options(&self) -> &TransferOptions {
+        &self.transfer
+    }
+
     pub fn qualify_path<'a>(&self, path: &'a Path) -> Cow<'a, Path> {
         match self.config.key() {
             Some(prefix) => Cow::Owned(prefix.parts().chain(path.parts()).collect()),
@@ -600,11 +752,13 @@ impl StorageProvider {
     pub async fn delete_if_present(&self, path: impl Into<Path>) -> Result<(), StorageError> {
         let path = path.into();
         let path = self.qualify_path(&path);
-        match self.object_store.delete(&path).await {
-            Ok(_) => Ok(()),
-            Err(object_store::Error::NotFound { .. }) => Ok(()),
-            Err(e) => Err(e.into()),
-        }
+        self.retry("delete", || async {
+            match self.object_store.delete(&path).await {
+                Ok(_) | Err(object_store::Error::NotFound { .. }) => Ok(()),
+                Err(e) => Err(e),
+            }
+        })
+        .await
     }
 
     pub fn as_multipart(&self) -> Option<Arc<dyn MultipartStore>> {
@@ -655,23 +809,29 @@ impl StorageProvider {
 
     pub async fn head(&self, path: impl Into<Path>) -> Result<ObjectMeta, StorageError> {
         let path = path.into();
-        self.object_store
-            .head(&self.qualify_path(&path))
-            .await
-            .map_err(Into::<StorageError>::into)
+        let path = self.qualify_path(&path);
+        self.retry("head", || self.object_store.head(&path)).await
     }
 
-    pub fn buf_writer(&self, path: impl Into<Path>) -> BufWriter {
+    /// Returns a writer that uploads the object in parts of the configured size, uploading up
+    /// to the configured number of parts concurrently
+    pub fn buf_writer(&self, path: impl Into<Path>) -> ThrottledWriter<BufWriter> {
         let path = path.into();
-        BufWriter::new(
+        let writer = BufWriter::with_capacity(
             self.object_store.clone(),
             self.qualify_path(&path).into_owned(),
+            self.transfer.upload_part_size,
         )
+        .with_max_concurrency(self.transfer.upload_concurrency);
+
+        ThrottledWriter::new(writer, self.transfer.rate_limiter.clone())
     }
 
     pub async fn start_multipart(&self, path: &Path) -> Result<MultipartId, StorageError> {
-        storage_retry!(self.get_multipart().create_multipart(path).await)
-            .map_err(Into::<StorageError>::into)
+        self.retry("create multipart upload", || {
+            self.get_multipart().create_multipart(path)
+        })
+        .await
     }
 
     pub async fn add_multipart(
@@ -681,12 +841,11 @@ impl StorageProvider {
         part_number: usize,
         bytes: Bytes,
     ) -> Result<PartId, StorageError> {
-        storage_retry!(
+        self.retry("upload part", || {
             self.get_multipart()
                 .put_part(path, multipart_id, part_number, bytes.clone().into())
-                .await
-        )
-        .map_err(Into::<StorageError>::into)
+        })
+        .await
     }
 
     pub async fn close_multipart(
@@ -695,12 +854,11 @@ impl StorageProvider {
         multipart_id: &MultipartId,
         parts: Vec<PartId>,
     ) -> Result<(), StorageError> {
-        storage_retry!(
+        self.retry("complete multipart upload", || {
             self.get_multipart()
                 .complete_multipart(path, multipart_id, parts.clone())
-                .await
-        )
-        .map_err(Into::<StorageError>::into)?;
+        })
+        .await?;
 
         Ok(())
     }
@@ -708,11 +866,15 @@ impl StorageProvider {
 
 #[cfg(test)]
 mod tests {
+    use arroyo_rpc::resilience::ResilientClient;
     use arroyo_types::to_nanos;
+    use object_store::memory::InMemory;
     use object_store::path::Path;
+    use std::collections::HashMap;
+    use std::sync::Arc;
     use std::time::SystemTime;
 
-    use crate::{matchers, BackendConfig, StorageProvider};
+    use crate::{matchers, BackendConfig, LocalConfig, StorageProvider, TransferOptions};
 
     #[test]
     fn test_regex_compilation() {
@@ -848,4 +1010,62 @@ mod tests {
                 .unwrap()
         );
     }
+
+    #[tokio::test]
+    async fn test_list_prefix() {
+        let storage = StorageProvider::for_url("file:///tmp/arroyo-testing/storage-tests")
+            .await
+            .unwrap();
+
+        let prefix = format!("list-test/{}", to_nanos(SystemTime::now()));
+        let keys: Vec<_> = ["a/1", "a/2", "b/1"]
+            .iter()
+            .map(|k| Path::parse(format!("{}/{}", prefix, k)).unwrap())
+            .collect();
+        for key in &keys {
+            storage.put(key.clone(), vec![1]).await.unwrap();
+        }
+
+        let mut listed = storage.list_prefix(format!("{}/a", prefix)).await.unwrap();
+        listed.sort();
+        assert_eq!(listed, keys[..2]);
+
+        for key in keys {
+            storage.delete_if_present(key).await.unwrap();
+        }
+
+        assert!(storage
+            .list_prefix(format!("{}/a", prefix))
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_multipart_put() {
+        let object_store = Arc::new(InMemory::new());
+        let storage = StorageProvider {
+            config: BackendConfig::Local(LocalConfig {
+                path: "/".to_string(),
+                key: Some(Path::parse("prefix").unwrap()),
+            }),
+            object_store: object_store.clone(),
+            multipart_store: Some(object_store),
+            canonical_url: "memory:///".to_string(),
+            object_store_base_url: "memory:///".to_string(),
+            storage_options: HashMap::new(),
+            transfer: TransferOptions::default(),
+            resilience: ResilientClient::shared("object_store", "memory:///"),
+        }
+        .with_transfer_options(TransferOptions {
+            upload_concurrency: 2,
+            upload_part_size: 4,
+            ..Default::default()
+        });
+
+        // uploaded as parts of 4, 4 and 2 bytes, which are reassembled in order
+        let data: Vec<u8> = (0..10).collect();
+        storage.put("multipart", data.clone()).await.unwrap();
+        assert_eq!(storage.get("multipart").await.unwrap(), data);
+    }
 }
@@ -0,0 +1,152 @@
+use arroyo_rpc::config::CheckpointStorageConfig;
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{ready, Context, Poll};
+use std::time::{Duration, Instant};
+use tokio::io::AsyncWrite;
+use tokio::time::Sleep;
+
+/// Options that control how a [StorageProvider](crate::StorageProvider) transfers objects
+#[derive(Debug, Clone)]
+pub struct TransferOptions {
+    /// Maximum number of parts of a single object that are uploaded concurrently
+    pub upload_concurrency: usize,
+    /// Size of the parts that large objects are uploaded in
+    pub upload_part_size: usize,
+    /// Maximum number of objects that readers should fetch concurrently
+    pub read_concurrency: usize,
+    /// Limit on the bandwidth of all transfers through the provider
+    pub rate_limiter: Option<Arc<RateLimiter>>,
+}
+
+impl Default for TransferOptions {
+    fn default() -> Self {
+        Self {
+            upload_concurrency: 8,
+            upload_part_size: 10 * 1024 * 1024,
+            read_concurrency: 8,
+            rate_limiter: None,
+        }
+    }
+}
+
+impl From<&CheckpointStorageConfig> for TransferOptions {
+    fn from(config: &CheckpointStorageConfig) -> Self {
+        Self {
+            upload_concurrency: config.upload_concurrency.max(1),
+            upload_part_size: config.upload_part_size,
+            read_concurrency: config.restore_concurrency.max(1),
+            rate_limiter: config
+                .max_bytes_per_second
+                .map(|rate| Arc::new(RateLimiter::new(rate))),
+        }
+    }
+}
+
+/// Limits the rate at which bytes are transferred. Each transfer reserves time for its bytes
+/// after those of the transfers before it, and waits until that time has come.
+#[derive(Debug)]
+pub struct RateLimiter {
+    bytes_per_second: u64,
+    next_free: Mutex<Option<Instant>>,
+}
+
+impl RateLimiter {
+    pub fn new(bytes_per_second: u64) -> Self {
+        Self {
+            bytes_per_second: bytes_per_second.max(1),
+            next_free: Mutex::new(None),
+        }
+    }
+
+    /// Reserves capacity to transfer `bytes`, returning how long the caller must wait before
+    /// starting the transfer
+    pub fn reserve(&self, bytes: usize) -> Duration {
+        let now = Instant::now();
+        let mut next_free = self.next_free.lock().unwrap();
+        let start = next_free.map(|t| t.max(now)).unwrap_or(now);
+        *next_free =
+            Some(start + Duration::from_secs_f64(bytes as f64 / self.bytes_per_second as f64));
+        start - now
+    }
+
+    pub async fn acquire(&self, bytes: usize) {
+        let wait = self.reserve(bytes);
+        if !wait.is_zero() {
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+/// Wraps a writer so that its writes are limited by a [RateLimiter]
+pub struct ThrottledWriter<W> {
+    inner: W,
+    rate_limiter: Option<Arc<RateLimiter>>,
+    delay: Option<Pin<Box<Sleep>>>,
+}
+
+impl<W> ThrottledWriter<W> {
+    pub fn new(inner: W, rate_limiter: Option<Arc<RateLimiter>>) -> Self {
+        Self {
+            inner,
+            rate_limiter,
+            delay: None,
+        }
+    }
+}
+
+impl<W: AsyncWrite + Unpin> AsyncWrite for ThrottledWriter<W> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        // wait out the time reserved by the previous write before accepting more data
+        if let Some(delay) = self.delay.as_mut() {
+            ready!(delay.as_mut().poll(cx));
+            self.delay = None;
+        }
+
+        let n = ready!(Pin::new(&mut self.inner).poll_write(cx, buf))?;
+
+        if let Some(rate_limiter) = &self.rate_limiter {
+            let wait = rate_limiter.reserve(n);
+            if !wait.is_zero() {
+                self.delay = Some(Box::pin(tokio::time::sleep(wait)));
+            }
+        }
+
+        Poll::Ready(Ok(n))
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RateLimiter;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_rate_limiter() {
+        let limiter = RateLimiter::new(1000);
+
+        // the first transfer can start immediately, and later ones wait for the earlier
+        // ones' bytes to have been sent
+        assert_eq!(limiter.reserve(500), Duration::ZERO);
+
+        let wait = limiter.reserve(1000);
+        assert!(wait > Duration::from_millis(400) && wait <= Duration::from_millis(500));
+
+        let wait = limiter.reserve(10);
+        assert!(wait > Duration::from_millis(1400) && wait <= Duration::from_millis(1500));
+    }
+}
@@ -204,6 +204,19 @@ impl UserError {
     }
 }
 
+/// An error that restarting the pipeline won't fix, like a sink table whose schema doesn't
+/// match the pipeline's; a task that fails with it fails the job rather than restarting it
+#[derive(Debug, Clone)]
+pub struct NonRetryableError(pub String);
+
+impl Display for NonRetryableError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for NonRetryableError {}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum SourceError {
     BadData { details: String },
@@ -589,29 +602,43 @@ impl TryFrom<&str> for DateTruncPrecision {
     }
 }
 
-pub fn server_for_hash(x: u64, n: usize) -> usize {
-    if n == 1 {
-        0
-    } else {
-        let range_size = (u64::MAX / (n as u64)) + 1;
-        (x / range_size) as usize
-    }
+/// The number of key-groups that key hashes are divided into. Keyed state is partitioned by
+/// key-group, with each subtask owning a contiguous range of them, so that state checkpointed at
+/// one parallelism can be reassigned to the subtasks of another. This is also the maximum
+/// parallelism of an operator.
+pub const KEY_GROUPS: usize = 1 << 15;
+
+/// The number of key hashes in each key-group
+pub const KEY_GROUP_SIZE: u64 = 1 << (64 - KEY_GROUPS.trailing_zeros());
+
+pub fn key_group_for_hash(x: u64) -> usize {
+    (x / KEY_GROUP_SIZE) as usize
 }
 
-pub fn range_for_server(i: usize, n: usize) -> RangeInclusive<u64> {
-    if n == 1 {
-        return 0..=u64::MAX;
-    }
-    let range_size = (u64::MAX / (n as u64)) + 1;
-    let start = range_size * (i as u64);
-    let end = if i + 1 == n {
+/// The key-groups owned by subtask `i` of `n`
+pub fn key_groups_for_server(i: usize, n: usize) -> Range<usize> {
+    (i * KEY_GROUPS).div_ceil(n)..((i + 1) * KEY_GROUPS).div_ceil(n)
+}
+
+/// The key hashes that belong to a (non-empty) range of key-groups
+pub fn range_for_key_groups(key_groups: &Range<usize>) -> RangeInclusive<u64> {
+    let start = key_groups.start as u64 * KEY_GROUP_SIZE;
+    let end = if key_groups.end == KEY_GROUPS {
         u64::MAX
     } else {
-        start + range_size - 1
+        key_groups.end as u64 * KEY_GROUP_SIZE - 1
     };
     start..=end
 }
 
+pub fn server_for_hash(x: u64, n: usize) -> usize {
+    key_group_for_hash(x) * n / KEY_GROUPS
+}
+
+pub fn range_for_server(i: usize, n: usize) -> RangeInclusive<u64> {
+    range_for_key_groups(&key_groups_for_server(i, n))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -650,6 +677,32 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_key_groups_for_server() {
+        for n in [1, 3, 7, 128, KEY_GROUPS] {
+            let mut next = 0;
+            for i in 0..n {
+                let key_groups = key_groups_for_server(i, n);
+                assert_eq!(key_groups.start, next, "key-groups not adjacent");
+                assert!(
+                    !key_groups.is_empty(),
+                    "subtask {} of {} has no key-groups",
+                    i,
+                    n
+                );
+                for key_group in [key_groups.start, key_groups.end - 1] {
+                    let range = range_for_key_groups(&(key_group..key_group + 1));
+                    assert_eq!(key_group_for_hash(*range.start()), key_group);
+                    assert_eq!(key_group_for_hash(*range.end()), key_group);
+                    assert_eq!(server_for_hash(*range.start(), n), i);
+                    assert_eq!(server_for_hash(*range.end(), n), i);
+                }
+                next = key_groups.end;
+            }
+            assert_eq!(next, KEY_GROUPS);
+        }
+    }
+
     #[test]
     fn test_server_for_hash() {
         let n = 2;
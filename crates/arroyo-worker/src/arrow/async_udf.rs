@@ -290,7 +290,11 @@ impl ArrowOperator for AsyncUdfOperator {
         Some(Duration::from_millis(50))
     }
 
-    async fn process_batch(&mut self, batch: RecordBatch, _: &mut ArrowContext) {
+    async fn process_batch(
+        &mut self,
+        batch: RecordBatch,
+        _: &mut ArrowContext,
+    ) -> anyhow::Result<()> {
         let arg_batch: Vec<_> = self
             .input_exprs
             .iter()
@@ -323,15 +327,16 @@ impl ArrowOperator for AsyncUdfOperator {
                 .expect("failed to send data to async UDF runtime");
             self.next_id += 1;
         }
+        Ok(())
     }
 
-    async fn handle_tick(&mut self, _: u64, ctx: &mut ArrowContext) {
+    async fn handle_tick(&mut self, _: u64, ctx: &mut ArrowContext) -> anyhow::Result<()> {
         let Some((ids, results)) = self
             .udf
             .drain_results()
             .expect("failed to get results from async UDF executor")
         else {
-            return;
+            return Ok(());
         };
 
         let mut rows = vec![];
@@ -369,6 +374,7 @@ impl ArrowOperator for AsyncUdfOperator {
         }
 
         self.flush_output(ctx).await;
+        Ok(())
     }
 
     async fn handle_watermark(
@@ -380,7 +386,11 @@ impl ArrowOperator for AsyncUdfOperator {
         None
     }
 
-    async fn handle_checkpoint(&mut self, _: CheckpointBarrier, ctx: &mut ArrowContext) {
+    async fn handle_checkpoint(
+        &mut self,
+        _: CheckpointBarrier,
+        ctx: &mut ArrowContext,
+    ) -> anyhow::Result<()> {
         let gs = ctx.table_manager.get_global_keyed_state("a").await.unwrap();
 
         let state = AsyncUdfState {
@@ -398,15 +408,22 @@ impl ArrowOperator for AsyncUdfOperator {
         };
 
         gs.insert(ctx.task_info.task_index, state).await;
+        Ok(())
     }
 
-    async fn on_close(&mut self, final_message: &Option<SignalMessage>, ctx: &mut ArrowContext) {
+    async fn on_close(
+        &mut self,
+        final_message: &Option<SignalMessage>,
+        ctx: &mut ArrowContext,
+    ) -> anyhow::Result<()> {
         if let Some(SignalMessage::EndOfData) = final_message {
             while !self.inputs.is_empty() && !self.outputs.is_empty() {
-                self.handle_tick(0, ctx).await;
+                self.handle_tick(0, ctx).await?;
                 tokio::time::sleep(Duration::from_millis(50)).await;
             }
         }
+
+        Ok(())
     }
 }
 
@@ -0,0 +1,293 @@
+use std::borrow::Cow;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use anyhow::{anyhow, Result};
+use arrow::compute::filter_record_batch;
+use arrow::row::{OwnedRow, RowConverter, SortField};
+use arrow_array::{ArrayRef, BooleanArray, RecordBatch, TimestampNanosecondArray};
+use arrow_schema::{DataType, Field, Schema, TimeUnit};
+use arroyo_operator::context::ArrowContext;
+use arroyo_operator::operator::{
+    ArrowOperator, AsDisplayable, DisplayableOperator, OperatorConstructor, OperatorNode, Registry,
+};
+use arroyo_rpc::df::{ArroyoSchema, ArroyoSchemaRef};
+use arroyo_rpc::grpc::{api, rpc::TableConfig};
+use arroyo_rpc::TIMESTAMP_FIELD;
+use arroyo_state::timestamp_table_config;
+use arroyo_types::{from_nanos, to_nanos, CheckpointBarrier, Watermark};
+use tracing::debug;
+
+/// Drops rows whose keys were seen within the window of them, in event time. The time each key
+/// was last seen is kept until the watermark passes it plus the window, so memory is bounded by
+/// the number of distinct keys seen per window.
+///
+/// The seen keys are written to state on checkpoint, one row per key that changed since the
+/// previous checkpoint, and the latest row for each key is restored.
+pub struct DedupeOperator {
+    name: String,
+    window: Duration,
+    input_schema: ArroyoSchemaRef,
+    state_schema: ArroyoSchemaRef,
+    converter: RowConverter,
+    seen: HashMap<OwnedRow, SystemTime>,
+    /// the seen keys, ordered by the time they expire
+    seen_by_expiry: BTreeSet<(SystemTime, OwnedRow)>,
+    /// the keys that have been seen since the last checkpoint
+    changed: HashSet<OwnedRow>,
+}
+
+impl DedupeOperator {
+    fn key_columns(&self, batch: &RecordBatch) -> Vec<ArrayRef> {
+        self.input_schema
+            .key_indices
+            .as_ref()
+            .map(|indices| indices.iter().map(|i| batch.column(*i).clone()).collect())
+            .unwrap_or_default()
+    }
+
+    /// Records that `key` was seen at `time`, returning whether it's a duplicate of a row seen
+    /// within the window
+    fn observe(&mut self, key: OwnedRow, time: SystemTime) -> bool {
+        if let Some(seen) = self.seen.get(&key) {
+            if time < *seen + self.window && *seen < time + self.window {
+                return true;
+            }
+        }
+
+        self.insert(key.clone(), time);
+        self.changed.insert(key);
+        false
+    }
+
+    fn insert(&mut self, key: OwnedRow, time: SystemTime) {
+        if let Some(seen) = self.seen.get(&key).copied() {
+            if seen >= time {
+                return;
+            }
+            self.seen_by_expiry
+                .remove(&(seen + self.window, key.clone()));
+        }
+
+        self.seen_by_expiry
+            .insert((time + self.window, key.clone()));
+        self.seen.insert(key, time);
+    }
+
+    /// Forgets the keys that can no longer have duplicates at or after `watermark`
+    fn expire(&mut self, watermark: SystemTime) {
+        while let Some((expiry, _)) = self.seen_by_expiry.first() {
+            if *expiry > watermark {
+                break;
+            }
+
+            let (_, key) = self.seen_by_expiry.pop_first().unwrap();
+            self.seen.remove(&key);
+            self.changed.remove(&key);
+        }
+    }
+
+    fn state_batch(&self, keys: &[OwnedRow]) -> Result<RecordBatch> {
+        let mut columns = self.converter.convert_rows(keys.iter().map(|k| k.row()))?;
+        columns.push(Arc::new(TimestampNanosecondArray::from_iter_values(
+            keys.iter().map(|k| to_nanos(self.seen[k]) as i64),
+        )));
+
+        Ok(RecordBatch::try_new(
+            self.state_schema.schema.clone(),
+            columns,
+        )?)
+    }
+
+    /// Restores the keys in a state batch that haven't expired as of `watermark`
+    fn restore(&mut self, batch: &RecordBatch, watermark: Option<SystemTime>) -> Result<()> {
+        let key_count = self.state_schema.key_indices.as_ref().unwrap().len();
+        let keys = self
+            .converter
+            .convert_columns(&batch.columns()[..key_count])?;
+        let times = self.state_schema.timestamp_column(batch);
+
+        for i in 0..batch.num_rows() {
+            let time = from_nanos(times.value(i) as u128);
+            if watermark.is_some_and(|w| time + self.window <= w) {
+                continue;
+            }
+
+            self.insert(keys.row(i).owned(), time);
+        }
+
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl ArrowOperator for DedupeOperator {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn display(&self) -> DisplayableOperator {
+        DisplayableOperator {
+            name: Cow::Borrowed("DedupeOperator"),
+            fields: vec![("window", AsDisplayable::Debug(&self.window))],
+        }
+    }
+
+    fn tables(&self) -> HashMap<String, TableConfig> {
+        vec![(
+            "d".to_string(),
+            timestamp_table_config(
+                "d",
+                "seen keys",
+                self.window,
+                false,
+                self.state_schema.as_ref().clone(),
+            ),
+        )]
+        .into_iter()
+        .collect()
+    }
+
+    async fn on_start(&mut self, ctx: &mut ArrowContext) {
+        let watermark = ctx.last_present_watermark();
+        let table = ctx
+            .table_manager
+            .get_expiring_time_key_table("d", watermark)
+            .await
+            .expect("should have seen keys table");
+
+        for (_, batches) in table
+            .all_batches_for_watermark(watermark)
+            .expect("should be able to read seen keys")
+        {
+            for batch in batches {
+                self.restore(&batch, watermark)
+                    .expect("should be able to restore seen keys");
+            }
+        }
+
+        debug!("restored {} seen keys", self.seen.len());
+    }
+
+    async fn process_batch(
+        &mut self,
+        batch: RecordBatch,
+        ctx: &mut ArrowContext,
+    ) -> anyhow::Result<()> {
+        let keys = self
+            .converter
+            .convert_columns(&self.key_columns(&batch))
+            .expect("should be able to convert keys");
+        let timestamps = self.input_schema.timestamp_column(&batch);
+
+        let keep: BooleanArray = (0..batch.num_rows())
+            .map(|i| {
+                Some(!self.observe(keys.row(i).owned(), from_nanos(timestamps.value(i) as u128)))
+            })
+            .collect();
+
+        let batch = filter_record_batch(&batch, &keep).unwrap();
+        if batch.num_rows() > 0 {
+            ctx.collect(batch).await;
+        }
+        Ok(())
+    }
+
+    async fn handle_watermark(
+        &mut self,
+        watermark: Watermark,
+        _ctx: &mut ArrowContext,
+    ) -> Option<Watermark> {
+        if let Watermark::EventTime(t) = watermark {
+            self.expire(t);
+        }
+
+        Some(watermark)
+    }
+
+    async fn handle_checkpoint(
+        &mut self,
+        _: CheckpointBarrier,
+        ctx: &mut ArrowContext,
+    ) -> anyhow::Result<()> {
+        let watermark = ctx.last_present_watermark();
+        let changed: Vec<_> = self.changed.drain().collect();
+
+        let table = ctx
+            .table_manager
+            .get_expiring_time_key_table("d", watermark)
+            .await
+            .expect("should have seen keys table");
+
+        if let Some(latest) = changed.iter().map(|k| self.seen[k]).max() {
+            let batch = self
+                .state_batch(&changed)
+                .expect("should be able to build seen keys state");
+            table.insert(latest, batch);
+        }
+
+        table
+            .flush(watermark)
+            .await
+            .expect("should flush seen keys");
+        Ok(())
+    }
+}
+
+pub struct DedupeConstructor;
+
+impl OperatorConstructor for DedupeConstructor {
+    type ConfigT = api::DedupeOperator;
+
+    fn with_config(
+        &self,
+        config: Self::ConfigT,
+        _registry: Arc<Registry>,
+    ) -> anyhow::Result<OperatorNode> {
+        let input_schema: ArroyoSchema = config
+            .input_schema
+            .ok_or_else(|| anyhow!("missing input schema"))?
+            .try_into()?;
+
+        let key_fields: Vec<_> = input_schema
+            .key_indices
+            .as_ref()
+            .ok_or_else(|| anyhow!("dedupe input must be keyed"))?
+            .iter()
+            .map(|i| input_schema.schema.field(*i).clone())
+            .collect();
+
+        let converter = RowConverter::new(
+            key_fields
+                .iter()
+                .map(|f| SortField::new(f.data_type().clone()))
+                .collect(),
+        )?;
+
+        // each seen key is stored as its keys, timestamped by when it was last seen
+        let key_count = key_fields.len();
+        let mut fields = key_fields;
+        fields.push(Field::new(
+            TIMESTAMP_FIELD,
+            DataType::Timestamp(TimeUnit::Nanosecond, None),
+            false,
+        ));
+        let state_schema = ArroyoSchema::new_keyed(
+            Arc::new(Schema::new(fields)),
+            key_count,
+            (0..key_count).collect(),
+        );
+
+        Ok(OperatorNode::from_operator(Box::new(DedupeOperator {
+            name: config.name,
+            window: Duration::from_micros(config.window_micros),
+            input_schema: Arc::new(input_schema),
+            state_schema: Arc::new(state_schema),
+            converter,
+            seen: HashMap::new(),
+            seen_by_expiry: BTreeSet::new(),
+            changed: HashSet::new(),
+        })))
+    }
+}
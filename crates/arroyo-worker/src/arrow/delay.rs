@@ -0,0 +1,575 @@
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use anyhow::{anyhow, Result};
+use arrow::compute::{filter_record_batch, min};
+use arrow_array::cast::AsArray;
+use arrow_array::types::UInt64Type;
+use arrow_array::{BooleanArray, RecordBatch, TimestampNanosecondArray, UInt64Array};
+use arrow_schema::{DataType, Field, Schema};
+use arroyo_df::DELAYED_AT_FIELD;
+use arroyo_operator::context::ArrowContext;
+use arroyo_operator::operator::{
+    ArrowOperator, AsDisplayable, DisplayableOperator, OperatorConstructor, OperatorNode, Registry,
+};
+use arroyo_rpc::df::{ArroyoSchema, ArroyoSchemaRef};
+use arroyo_rpc::grpc::{api, rpc::TableConfig};
+use arroyo_state::{global_table_config, timestamp_table_config};
+use arroyo_types::{
+    from_nanos, to_nanos, ArrowMessage, CheckpointBarrier, SignalMessage, Watermark,
+};
+use tracing::debug;
+
+/// Records the subtask that delayed a row, so that on restore we know which subtask's progress
+/// applies to it
+const SUBTASK_FIELD: &str = "_delay_subtask";
+
+/// How often we check for rows that are due to be emitted
+const TICK_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Buffers rows and re-emits them once a fixed delay has passed in processing time. Rows are
+/// kept in an expiring time-key table keyed by the time they're due, and the time up to which
+/// each subtask has emitted its rows is checkpointed alongside, so that rows emitted since the
+/// table was last written aren't emitted again on restore.
+///
+/// Since rows are emitted later than they arrive, the operator holds back the watermark to the
+/// earliest event time of the rows it's buffering, so that they aren't late downstream.
+pub struct DelayOperator {
+    name: String,
+    delay: Duration,
+    /// how far event time may run ahead of processing time. Checkpointed rows are expired
+    /// based on the event-time watermark, so they're retained for this long beyond the delay;
+    /// rows that have already been emitted are skipped on restore regardless.
+    allowed_lateness: Duration,
+    output_schema: ArroyoSchemaRef,
+    state_schema: ArroyoSchemaRef,
+    /// the due times of the buffered rows, along with the earliest event time among them
+    pending: BTreeMap<SystemTime, SystemTime>,
+    /// the time up to which all due rows have been emitted since the operator started
+    emitted_through: Option<SystemTime>,
+    /// the checkpointed progress of the subtasks that delayed the rows we restored
+    restored: HashMap<u64, SystemTime>,
+    /// the subtasks whose rows we're responsible for
+    origins: HashSet<u64>,
+    input_watermark: Option<Watermark>,
+    last_watermark: Option<Watermark>,
+}
+
+impl DelayOperator {
+    fn new(config: api::DelayOperator) -> Result<Self> {
+        let output_schema = Arc::new(ArroyoSchema::try_from(
+            config
+                .output_schema
+                .ok_or_else(|| anyhow!("missing output schema"))?,
+        )?);
+
+        // rows are stored with the subtask that delayed them, and keyed by their due time
+        let due_index = output_schema.schema.index_of(DELAYED_AT_FIELD)?;
+        let mut fields = output_schema.schema.fields().to_vec();
+        fields.push(Arc::new(Field::new(SUBTASK_FIELD, DataType::UInt64, false)));
+        let subtask_index = fields.len() - 1;
+        let state_schema = Arc::new(ArroyoSchema::new_keyed(
+            Arc::new(Schema::new_with_metadata(
+                fields,
+                output_schema.schema.metadata().clone(),
+            )),
+            due_index,
+            vec![subtask_index],
+        ));
+
+        Ok(Self {
+            name: config.name,
+            delay: Duration::from_micros(config.delay_micros),
+            allowed_lateness: Duration::from_micros(config.allowed_lateness_micros),
+            output_schema,
+            state_schema,
+            pending: BTreeMap::new(),
+            emitted_through: None,
+            restored: HashMap::new(),
+            origins: HashSet::new(),
+            input_watermark: None,
+            last_watermark: None,
+        })
+    }
+
+    fn subtask_column<'a>(&self, batch: &'a RecordBatch) -> &'a UInt64Array {
+        batch
+            .column(self.state_schema.schema.fields().len() - 1)
+            .as_primitive::<UInt64Type>()
+    }
+
+    /// Removes rows that were emitted before the state they were restored from was written
+    fn unemitted_rows(&self, due: SystemTime, batch: RecordBatch) -> Result<RecordBatch> {
+        if self.restored.is_empty() {
+            return Ok(batch);
+        }
+
+        let keep: BooleanArray = self
+            .subtask_column(&batch)
+            .iter()
+            .map(|subtask| {
+                let emitted = subtask
+                    .and_then(|s| self.restored.get(&s))
+                    .is_some_and(|emitted_through| due <= *emitted_through);
+                Some(!emitted)
+            })
+            .collect();
+
+        Ok(filter_record_batch(&batch, &keep)?)
+    }
+
+    fn earliest_event_time(&self, batch: &RecordBatch) -> Option<SystemTime> {
+        min(self.output_schema.timestamp_column(batch)).map(|t| from_nanos(t as u128))
+    }
+
+    fn track_pending(&mut self, due: SystemTime, earliest: SystemTime) {
+        self.pending
+            .entry(due)
+            .and_modify(|t| *t = (*t).min(earliest))
+            .or_insert(earliest);
+    }
+
+    /// Emits all rows that are due at `now`
+    async fn emit_due(&mut self, now: SystemTime, ctx: &mut ArrowContext) {
+        let output_columns: Vec<_> = (0..self.output_schema.schema.fields().len()).collect();
+
+        while let Some((&due, _)) = self.pending.first_key_value() {
+            if due > now {
+                break;
+            }
+            self.pending.pop_first();
+
+            let batches = ctx
+                .table_manager
+                .get_expiring_time_key_table("delayed", None)
+                .await
+                .expect("should have delayed table")
+                .expire_timestamp(due)
+                .expect("should be able to expire delayed rows");
+
+            for batch in batches {
+                let batch = self
+                    .unemitted_rows(due, batch)
+                    .expect("should be able to filter delayed rows");
+                if batch.num_rows() > 0 {
+                    ctx.collect(batch.project(&output_columns).unwrap()).await;
+                }
+            }
+        }
+
+        self.emitted_through = Some(self.emitted_through.map_or(now, |t| t.max(now)));
+
+        if let Some(watermark) = self.next_watermark() {
+            ctx.broadcast(ArrowMessage::Signal(SignalMessage::Watermark(watermark)))
+                .await;
+        }
+    }
+
+    /// The watermark to send downstream, if it has changed: the input watermark, held back to
+    /// the earliest event time of the rows still buffered
+    fn next_watermark(&mut self) -> Option<Watermark> {
+        let input = self.input_watermark?;
+        let watermark = match (input, self.pending.values().min()) {
+            (input, None) => input,
+            (Watermark::EventTime(t), Some(earliest)) => Watermark::EventTime(t.min(*earliest)),
+            (Watermark::Idle, Some(earliest)) => Watermark::EventTime(*earliest),
+        };
+
+        match (self.last_watermark, watermark) {
+            (Some(last), watermark) if last == watermark => None,
+            (Some(Watermark::EventTime(last)), Watermark::EventTime(t)) if t < last => None,
+            _ => {
+                self.last_watermark = Some(watermark);
+                Some(watermark)
+            }
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl ArrowOperator for DelayOperator {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn display(&self) -> DisplayableOperator {
+        DisplayableOperator {
+            name: Cow::Borrowed("DelayOperator"),
+            fields: vec![
+                ("delay", AsDisplayable::Debug(&self.delay)),
+                (
+                    "allowed_lateness",
+                    AsDisplayable::Debug(&self.allowed_lateness),
+                ),
+            ],
+        }
+    }
+
+    fn tables(&self) -> HashMap<String, TableConfig> {
+        let mut tables = global_table_config("emitted", "delay emission progress");
+        tables.insert(
+            "delayed".to_string(),
+            timestamp_table_config(
+                "delayed",
+                "delayed rows",
+                self.delay + self.allowed_lateness,
+                false,
+                self.state_schema.as_ref().clone(),
+            ),
+        );
+        tables
+    }
+
+    fn tick_interval(&self) -> Option<Duration> {
+        Some(TICK_INTERVAL)
+    }
+
+    async fn on_start(&mut self, ctx: &mut ArrowContext) {
+        self.origins.insert(ctx.task_info.task_index as u64);
+
+        self.restored = ctx
+            .table_manager
+            .get_global_keyed_state::<u64, SystemTime>("emitted")
+            .await
+            .expect("should have emitted table")
+            .get_all()
+            .clone();
+
+        let table = ctx
+            .table_manager
+            .get_expiring_time_key_table("delayed", None)
+            .await
+            .expect("should have delayed table");
+
+        for (due, batches) in table
+            .all_batches_for_watermark(None)
+            .expect("should be able to read delayed rows")
+        {
+            let mut earliest = None;
+            for batch in batches {
+                let origins: Vec<u64> = self.subtask_column(&batch).iter().flatten().collect();
+                self.origins.extend(origins);
+
+                let batch = self
+                    .unemitted_rows(due, batch)
+                    .expect("should be able to filter delayed rows");
+                if let Some(t) = self.earliest_event_time(&batch) {
+                    earliest = Some(earliest.map_or(t, |e: SystemTime| e.min(t)));
+                }
+            }
+
+            match earliest {
+                Some(earliest) => self.track_pending(due, earliest),
+                None => {
+                    // everything due at this time has already been emitted
+                    table
+                        .expire_timestamp(due)
+                        .expect("should be able to expire delayed rows");
+                }
+            }
+        }
+
+        debug!("restored {} pending delayed timestamps", self.pending.len());
+    }
+
+    async fn process_batch(
+        &mut self,
+        batch: RecordBatch,
+        ctx: &mut ArrowContext,
+    ) -> anyhow::Result<()> {
+        if batch.num_rows() == 0 {
+            return Ok(());
+        }
+
+        // rows must become due after any that we (or the subtask we took over from) have
+        // already emitted, even if the clock has moved backwards
+        let floor = self
+            .restored
+            .values()
+            .chain(self.emitted_through.iter())
+            .max()
+            .map(|t| *t + Duration::from_nanos(1))
+            .unwrap_or(SystemTime::UNIX_EPOCH);
+        let due = (SystemTime::now() + self.delay).max(floor);
+
+        let mut columns = batch.columns().to_vec();
+        columns.push(Arc::new(TimestampNanosecondArray::from(vec![
+            to_nanos(due)
+                as i64;
+            batch
+                .num_rows(
+                )
+        ])));
+        columns.push(Arc::new(UInt64Array::from(vec![
+            ctx.task_info.task_index
+                as u64;
+            batch.num_rows()
+        ])));
+
+        let batch = RecordBatch::try_new(self.state_schema.schema.clone(), columns)
+            .expect("should be able to build delayed batch");
+
+        let earliest = self
+            .earliest_event_time(&batch)
+            .expect("batch should have rows");
+        self.track_pending(due, earliest);
+
+        ctx.table_manager
+            .get_expiring_time_key_table("delayed", None)
+            .await
+            .expect("should have delayed table")
+            .insert(due, batch);
+        Ok(())
+    }
+
+    async fn handle_tick(&mut self, _: u64, ctx: &mut ArrowContext) -> anyhow::Result<()> {
+        self.emit_due(SystemTime::now(), ctx).await;
+        Ok(())
+    }
+
+    async fn handle_watermark(
+        &mut self,
+        watermark: Watermark,
+        _ctx: &mut ArrowContext,
+    ) -> Option<Watermark> {
+        self.input_watermark = Some(watermark);
+        self.next_watermark()
+    }
+
+    async fn handle_checkpoint(
+        &mut self,
+        _: CheckpointBarrier,
+        ctx: &mut ArrowContext,
+    ) -> anyhow::Result<()> {
+        ctx.table_manager
+            .get_expiring_time_key_table("delayed", None)
+            .await
+            .expect("should have delayed table")
+            .flush(None)
+            .await
+            .expect("should flush delayed rows");
+
+        let emitted = ctx
+            .table_manager
+            .get_global_keyed_state::<u64, SystemTime>("emitted")
+            .await
+            .expect("should have emitted table");
+
+        for origin in &self.origins {
+            let emitted_through = match (self.restored.get(origin), self.emitted_through) {
+                (Some(restored), Some(emitted)) => (*restored).max(emitted),
+                (Some(restored), None) => *restored,
+                (None, Some(emitted)) => emitted,
+                (None, None) => continue,
+            };
+            emitted.insert(*origin, emitted_through).await;
+        }
+        Ok(())
+    }
+
+    async fn on_close(
+        &mut self,
+        final_message: &Option<SignalMessage>,
+        ctx: &mut ArrowContext,
+    ) -> anyhow::Result<()> {
+        if let Some(SignalMessage::EndOfData) = final_message {
+            // the input is finished, but rows are still emitted no earlier than they're due
+            while let Some((&due, _)) = self.pending.first_key_value() {
+                if let Ok(wait) = due.duration_since(SystemTime::now()) {
+                    tokio::time::sleep(wait).await;
+                }
+                self.emit_due(due, ctx).await;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+pub struct DelayConstructor;
+
+impl OperatorConstructor for DelayConstructor {
+    type ConfigT = api::DelayOperator;
+
+    fn with_config(
+        &self,
+        config: Self::ConfigT,
+        _registry: Arc<Registry>,
+    ) -> anyhow::Result<OperatorNode> {
+        Ok(OperatorNode::from_operator(Box::new(DelayOperator::new(
+            config,
+        )?)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use arrow_array::types::TimestampNanosecondType;
+    use arrow_array::Int64Array;
+    use arrow_schema::TimeUnit;
+    use arroyo_operator::context::{batch_bounded, BatchReceiver};
+    use arroyo_rpc::grpc::rpc::ExpiringKeyedTimeTableConfig;
+    use arroyo_types::get_test_task_info;
+    use prost::Message;
+    use tokio::sync::mpsc::channel;
+    use tokio::time::timeout;
+
+    fn input_schema() -> Arc<Schema> {
+        Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new(
+                "_timestamp",
+                DataType::Timestamp(TimeUnit::Nanosecond, None),
+                false,
+            ),
+        ]))
+    }
+
+    fn delay_operator(delay: Duration, allowed_lateness: Duration) -> DelayOperator {
+        let mut fields = input_schema().fields().to_vec();
+        fields.push(Arc::new(Field::new(
+            DELAYED_AT_FIELD,
+            DataType::Timestamp(TimeUnit::Nanosecond, None),
+            true,
+        )));
+        let output_schema =
+            ArroyoSchema::from_schema_unkeyed(Arc::new(Schema::new(fields))).unwrap();
+
+        DelayOperator::new(api::DelayOperator {
+            name: "delay".to_string(),
+            output_schema: Some(output_schema.into()),
+            delay_micros: delay.as_micros() as u64,
+            allowed_lateness_micros: allowed_lateness.as_micros() as u64,
+        })
+        .unwrap()
+    }
+
+    fn batch(ids: Vec<i64>, event_time: SystemTime) -> RecordBatch {
+        let timestamps = vec![to_nanos(event_time) as i64; ids.len()];
+        RecordBatch::try_new(
+            input_schema(),
+            vec![
+                Arc::new(Int64Array::from(ids)),
+                Arc::new(TimestampNanosecondArray::from(timestamps)),
+            ],
+        )
+        .unwrap()
+    }
+
+    async fn context(operator: &DelayOperator) -> (ArrowContext, BatchReceiver) {
+        let (_, control_rx) = channel(128);
+        let (command_tx, _) = channel(128);
+        let (data_tx, data_rx) = batch_bounded(128);
+
+        let mut task_info = get_test_task_info();
+        task_info.job_id = format!("delay-test-{}", to_nanos(SystemTime::now()));
+
+        let ctx = ArrowContext::new(
+            task_info,
+            None,
+            control_rx,
+            command_tx,
+            1,
+            vec![ArroyoSchema::from_schema_unkeyed(input_schema()).unwrap()],
+            Some(operator.output_schema.as_ref().clone()),
+            None,
+            vec![vec![data_tx]],
+            operator.tables(),
+        )
+        .await;
+
+        (ctx, data_rx)
+    }
+
+    #[test]
+    fn test_retention() {
+        let operator = delay_operator(Duration::from_secs(30), Duration::from_secs(5));
+
+        let config = ExpiringKeyedTimeTableConfig::decode(
+            &operator.tables().get("delayed").unwrap().config[..],
+        )
+        .unwrap();
+
+        assert_eq!(
+            Duration::from_micros(config.retention_micros),
+            Duration::from_secs(35)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_emits_rows_once_due() {
+        let delay = Duration::from_secs(60);
+        let mut operator = delay_operator(delay, Duration::ZERO);
+        let (mut ctx, mut rx) = context(&operator).await;
+        operator.on_start(&mut ctx).await;
+
+        let start = SystemTime::now();
+        operator
+            .process_batch(batch(vec![1, 2, 3], start), &mut ctx)
+            .await
+            .unwrap();
+
+        // nothing is emitted before the delay has passed
+        operator.emit_due(SystemTime::now(), &mut ctx).await;
+        assert!(timeout(Duration::from_millis(50), rx.recv()).await.is_err());
+
+        operator.emit_due(start + delay * 2, &mut ctx).await;
+        let Some(ArrowMessage::Data(emitted)) = rx.recv().await else {
+            panic!("expected the delayed rows");
+        };
+
+        assert_eq!(emitted.num_rows(), 3);
+        assert_eq!(
+            emitted.schema().fields().len(),
+            operator.output_schema.schema.fields().len()
+        );
+
+        let due_at = emitted
+            .column_by_name(DELAYED_AT_FIELD)
+            .unwrap()
+            .as_primitive::<TimestampNanosecondType>()
+            .value(0);
+        assert!(from_nanos(due_at as u128) >= start + delay);
+
+        // rows are only emitted once
+        operator.emit_due(start + delay * 3, &mut ctx).await;
+        assert!(timeout(Duration::from_millis(50), rx.recv()).await.is_err());
+        assert!(operator.pending.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_holds_back_watermark() {
+        let delay = Duration::from_secs(60);
+        let mut operator = delay_operator(delay, Duration::ZERO);
+        let (mut ctx, mut rx) = context(&operator).await;
+        operator.on_start(&mut ctx).await;
+
+        let now = SystemTime::now();
+        let event_time = now - Duration::from_secs(10);
+        operator
+            .process_batch(batch(vec![1], event_time), &mut ctx)
+            .await
+            .unwrap();
+
+        // the watermark can't pass the buffered row, or it would be late once it's emitted
+        assert_eq!(
+            operator
+                .handle_watermark(Watermark::EventTime(now), &mut ctx)
+                .await,
+            Some(Watermark::EventTime(event_time))
+        );
+
+        // once the row has been emitted, the input watermark is passed on
+        operator.emit_due(now + delay * 2, &mut ctx).await;
+        assert!(matches!(rx.recv().await, Some(ArrowMessage::Data(_))));
+        assert_eq!(
+            rx.recv().await,
+            Some(ArrowMessage::Signal(SignalMessage::Watermark(
+                Watermark::EventTime(now)
+            )))
+        );
+    }
+}
@@ -209,7 +209,9 @@ impl ArrowOperator for InstantJoin {
             .expect("should have left table");
         let left_batches: Vec<_> = left_table
             .all_batches_for_watermark(watermark)
-            .flat_map(|(_time, batches)| batches.clone())
+            .expect("should be able to read left table")
+            .into_iter()
+            .flat_map(|(_time, batches)| batches)
             .collect();
         for batch in left_batches {
             self.process_left(batch.clone(), ctx)
@@ -223,7 +225,9 @@ impl ArrowOperator for InstantJoin {
             .expect("should have right table");
         let right_batches: Vec<_> = right_table
             .all_batches_for_watermark(watermark)
-            .flat_map(|(_time, batches)| batches.clone())
+            .expect("should be able to read right table")
+            .into_iter()
+            .flat_map(|(_time, batches)| batches)
             .collect();
         for batch in right_batches {
             self.process_right(batch.clone(), ctx)
@@ -232,7 +236,11 @@ impl ArrowOperator for InstantJoin {
         }
     }
 
-    async fn process_batch(&mut self, _record_batch: RecordBatch, _ctx: &mut ArrowContext) {
+    async fn process_batch(
+        &mut self,
+        _record_batch: RecordBatch,
+        _ctx: &mut ArrowContext,
+    ) -> anyhow::Result<()> {
         unreachable!();
     }
     async fn process_batch_index(
@@ -241,7 +249,7 @@ impl ArrowOperator for InstantJoin {
         total_inputs: usize,
         record_batch: RecordBatch,
         ctx: &mut ArrowContext,
-    ) {
+    ) -> anyhow::Result<()> {
         match index / (total_inputs / 2) {
             0 => self
                 .process_left(record_batch, ctx)
@@ -253,6 +261,7 @@ impl ArrowOperator for InstantJoin {
                 .expect("should process right"),
             _ => unreachable!(),
         }
+        Ok(())
     }
     async fn handle_watermark(
         &mut self,
@@ -290,7 +299,11 @@ impl ArrowOperator for InstantJoin {
         Some(int_watermark)
     }
 
-    async fn handle_checkpoint(&mut self, _b: CheckpointBarrier, ctx: &mut ArrowContext) {
+    async fn handle_checkpoint(
+        &mut self,
+        _b: CheckpointBarrier,
+        ctx: &mut ArrowContext,
+    ) -> anyhow::Result<()> {
         let watermark = ctx.last_present_watermark();
         ctx.table_manager
             .get_expiring_time_key_table("left", watermark)
@@ -306,6 +319,7 @@ impl ArrowOperator for InstantJoin {
             .flush(watermark)
             .await
             .expect("should flush");
+        Ok(())
     }
 
     fn tables(&self) -> HashMap<String, TableConfig> {
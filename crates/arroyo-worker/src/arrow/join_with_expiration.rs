@@ -63,7 +63,7 @@ impl JoinWithExpiration {
                 .get_batch(row.as_ref())
                 .expect("shouldn't error getting batch")
             {
-                right_batches.push(batch.clone());
+                right_batches.push(batch);
             }
         }
         let right_batch = concat_batches(&self.right_schema.schema, right_batches.iter()).unwrap();
@@ -101,7 +101,7 @@ impl JoinWithExpiration {
                 .get_batch(row.as_ref())
                 .expect("shouldn't error getting batch")
             {
-                left_batches.push(batch.clone());
+                left_batches.push(batch);
             }
         }
         let left_batch = concat_batches(&self.left_schema.schema, left_batches.iter()).unwrap();
@@ -162,7 +162,11 @@ impl ArrowOperator for JoinWithExpiration {
         }
     }
 
-    async fn process_batch(&mut self, _record_batch: RecordBatch, _ctx: &mut ArrowContext) {
+    async fn process_batch(
+        &mut self,
+        _record_batch: RecordBatch,
+        _ctx: &mut ArrowContext,
+    ) -> anyhow::Result<()> {
         unreachable!();
     }
     async fn process_batch_index(
@@ -171,7 +175,7 @@ impl ArrowOperator for JoinWithExpiration {
         total_inputs: usize,
         record_batch: RecordBatch,
         ctx: &mut ArrowContext,
-    ) {
+    ) -> anyhow::Result<()> {
         match index / (total_inputs / 2) {
             0 => self
                 .process_left(record_batch, ctx)
@@ -183,6 +187,7 @@ impl ArrowOperator for JoinWithExpiration {
                 .expect("should process right"),
             _ => unreachable!(),
         }
+        Ok(())
     }
 
     fn tables(&self) -> HashMap<String, TableConfig> {
@@ -251,9 +256,22 @@ impl OperatorConstructor for JoinWithExpirationConstructor {
             ttl = Duration::from_secs(24 * 60 * 60);
         }
 
+        // per-side TTLs fall back to the join's TTL if unset, or zero in plans from before they
+        // were validated
+        let left_expiration = config
+            .left_ttl_micros
+            .filter(|micros| *micros > 0)
+            .map(Duration::from_micros)
+            .unwrap_or(ttl);
+        let right_expiration = config
+            .right_ttl_micros
+            .filter(|micros| *micros > 0)
+            .map(Duration::from_micros)
+            .unwrap_or(ttl);
+
         Ok(OperatorNode::from_operator(Box::new(JoinWithExpiration {
-            left_expiration: ttl,
-            right_expiration: ttl,
+            left_expiration,
+            right_expiration,
             left_input_schema,
             right_input_schema,
             left_schema,
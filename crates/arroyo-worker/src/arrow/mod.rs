@@ -25,9 +25,12 @@ use std::sync::Arc;
 use std::sync::RwLock;
 
 pub mod async_udf;
+pub mod dedupe;
+pub mod delay;
 pub mod instant_join;
 pub mod join_with_expiration;
 pub mod session_aggregating_window;
+pub mod session_events;
 pub mod sliding_aggregating_window;
 pub(crate) mod sync;
 pub mod tumbling_aggregating_window;
@@ -71,12 +74,17 @@ impl ArrowOperator for ValueExecutionOperator {
         }
     }
 
-    async fn process_batch(&mut self, record_batch: RecordBatch, ctx: &mut ArrowContext) {
+    async fn process_batch(
+        &mut self,
+        record_batch: RecordBatch,
+        ctx: &mut ArrowContext,
+    ) -> anyhow::Result<()> {
         let mut records = self.executor.process_batch(record_batch).await;
         while let Some(batch) = records.next().await {
             let batch = batch.expect("should be able to compute batch");
             ctx.collect(batch).await;
         }
+        Ok(())
     }
 }
 
@@ -196,7 +204,11 @@ impl ArrowOperator for KeyExecutionOperator {
         }
     }
 
-    async fn process_batch(&mut self, batch: RecordBatch, ctx: &mut ArrowContext) {
+    async fn process_batch(
+        &mut self,
+        batch: RecordBatch,
+        ctx: &mut ArrowContext,
+    ) -> anyhow::Result<()> {
         let mut records = self.executor.process_batch(batch).await;
         while let Some(batch) = records.next().await {
             let batch = batch.expect("should be able to compute batch");
@@ -204,6 +216,7 @@ impl ArrowOperator for KeyExecutionOperator {
             //info!("batch {:?}", batch);
             ctx.collect(batch).await;
         }
+        Ok(())
     }
 }
 
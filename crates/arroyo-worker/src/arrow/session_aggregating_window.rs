@@ -774,11 +774,13 @@ impl ArrowOperator for SessionAggregatingWindowFunc {
             .get_expiring_time_key_table("s", start_time)
             .await
             .expect("should be able to load table");
-        let all_batches = table.all_batches_for_watermark(start_time);
+        let all_batches = table
+            .all_batches_for_watermark(start_time)
+            .expect("should be able to read table");
         for (_max_timestamp, batches) in all_batches {
             for batch in batches {
                 let batch = self
-                    .filter_batch_by_time(batch.clone(), start_time)
+                    .filter_batch_by_time(batch, start_time)
                     .expect("should be able to filter");
                 if batch.num_rows() == 0 {
                     continue;
@@ -809,7 +811,11 @@ impl ArrowOperator for SessionAggregatingWindowFunc {
     }
 
     // TODO: filter out late data
-    async fn process_batch(&mut self, batch: RecordBatch, ctx: &mut ArrowContext) {
+    async fn process_batch(
+        &mut self,
+        batch: RecordBatch,
+        ctx: &mut ArrowContext,
+    ) -> anyhow::Result<()> {
         debug!("received batch {:?}", batch);
         let current_watermark = ctx.last_present_watermark();
         let batch = if let Some(watermark) = current_watermark {
@@ -827,7 +833,7 @@ impl ArrowOperator for SessionAggregatingWindowFunc {
         };
         if batch.num_rows() == 0 {
             warn!("fully filtered out a batch");
-            return;
+            return Ok(());
         }
         let sorted = self
             .sort_batch(&batch)
@@ -852,6 +858,7 @@ impl ArrowOperator for SessionAggregatingWindowFunc {
         self.add_at_watermark(sorted, current_watermark)
             .await
             .expect("should be able to add batch");
+        Ok(())
     }
 
     async fn handle_watermark(
@@ -863,7 +870,11 @@ impl ArrowOperator for SessionAggregatingWindowFunc {
         Some(watermark)
     }
 
-    async fn handle_checkpoint(&mut self, _b: CheckpointBarrier, ctx: &mut ArrowContext) {
+    async fn handle_checkpoint(
+        &mut self,
+        _b: CheckpointBarrier,
+        ctx: &mut ArrowContext,
+    ) -> anyhow::Result<()> {
         let watermark = ctx.last_present_watermark();
         let table = ctx
             .table_manager
@@ -877,6 +888,7 @@ impl ArrowOperator for SessionAggregatingWindowFunc {
             .unwrap()
             .insert(ctx.task_info.task_index, self.earliest_batch_time())
             .await;
+        Ok(())
     }
 
     fn tables(&self) -> HashMap<String, TableConfig> {
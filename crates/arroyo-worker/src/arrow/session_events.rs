@@ -0,0 +1,669 @@
+use std::borrow::Cow;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use anyhow::{anyhow, bail, Result};
+use arrow::compute::{filter_record_batch, kernels::cmp::gt_eq, sort_to_indices};
+use arrow::row::{OwnedRow, RowConverter, SortField};
+use arrow_array::cast::AsArray;
+use arrow_array::types::{Int64Type, TimestampNanosecondType};
+use arrow_array::{
+    ArrayRef, Int64Array, RecordBatch, StringArray, StructArray, TimestampNanosecondArray,
+};
+use arrow_schema::{DataType, Field, Fields, Schema, TimeUnit};
+use arroyo_df::schemas::{SESSION_ENDED, SESSION_STARTED};
+use arroyo_operator::context::ArrowContext;
+use arroyo_operator::operator::{
+    ArrowOperator, AsDisplayable, DisplayableOperator, OperatorConstructor, OperatorNode, Registry,
+};
+use arroyo_rpc::df::{ArroyoSchema, ArroyoSchemaRef};
+use arroyo_rpc::grpc::{api, rpc::TableConfig};
+use arroyo_rpc::TIMESTAMP_FIELD;
+use arroyo_state::timestamp_table_config;
+use arroyo_types::{from_nanos, to_nanos, CheckpointBarrier, SignalMessage, Watermark};
+use tracing::debug;
+
+#[derive(Debug, Clone, Copy)]
+struct Session {
+    start: SystemTime,
+    last: SystemTime,
+    count: i64,
+}
+
+struct SessionEvent {
+    key: OwnedRow,
+    event: &'static str,
+    session: Session,
+    timestamp: SystemTime,
+}
+
+/// Tracks a session for each key, emitting a `session_started` row when the first event of a
+/// session arrives and a `session_ended` row once the watermark passes the session's last event
+/// plus the gap. An event that arrives more than the gap after the last one ends the session
+/// immediately and starts a new one; events that arrive out of order are folded into the active
+/// session.
+///
+/// The active sessions are written to state on checkpoint, one row per key that changed since
+/// the previous checkpoint, and the latest row for each key is restored.
+pub struct SessionEventsOperator {
+    name: String,
+    gap: Duration,
+    input_schema: ArroyoSchemaRef,
+    output_schema: ArroyoSchemaRef,
+    state_schema: ArroyoSchemaRef,
+    event_fields: Fields,
+    converter: RowConverter,
+    sessions: HashMap<OwnedRow, Session>,
+    /// the active sessions, ordered by the time they end if they see no further events
+    sessions_by_end: BTreeSet<(SystemTime, OwnedRow)>,
+    /// the keys whose sessions have changed since the last checkpoint
+    changed: HashSet<OwnedRow>,
+}
+
+impl SessionEventsOperator {
+    fn new(config: api::SessionEventsOperator) -> Result<Self> {
+        let input_schema: ArroyoSchema = config
+            .input_schema
+            .ok_or_else(|| anyhow!("missing input schema"))?
+            .try_into()?;
+        let output_schema: ArroyoSchema = config
+            .output_schema
+            .ok_or_else(|| anyhow!("missing output schema"))?
+            .try_into()?;
+
+        let key_fields: Vec<_> = input_schema
+            .key_indices
+            .as_ref()
+            .ok_or_else(|| anyhow!("session events input must be keyed"))?
+            .iter()
+            .map(|i| input_schema.schema.field(*i).clone())
+            .collect();
+
+        let DataType::Struct(event_fields) =
+            output_schema.schema.field(key_fields.len()).data_type()
+        else {
+            bail!("session events output is missing the event struct");
+        };
+        let event_fields = event_fields.clone();
+
+        let converter = RowConverter::new(
+            key_fields
+                .iter()
+                .map(|f| SortField::new(f.data_type().clone()))
+                .collect(),
+        )?;
+
+        // each active session is stored as its keys, start and count, timestamped by its last event
+        let key_count = key_fields.len();
+        let mut fields = key_fields;
+        fields.push(Field::new(
+            "session_start",
+            DataType::Timestamp(TimeUnit::Nanosecond, None),
+            false,
+        ));
+        fields.push(Field::new("event_count", DataType::Int64, false));
+        fields.push(Field::new(
+            TIMESTAMP_FIELD,
+            DataType::Timestamp(TimeUnit::Nanosecond, None),
+            false,
+        ));
+        let state_schema = ArroyoSchema::new_keyed(
+            Arc::new(Schema::new(fields)),
+            key_count + 2,
+            (0..key_count).collect(),
+        );
+
+        Ok(Self {
+            name: config.name,
+            gap: Duration::from_micros(config.gap_micros),
+            input_schema: Arc::new(input_schema),
+            output_schema: Arc::new(output_schema),
+            state_schema: Arc::new(state_schema),
+            event_fields,
+            converter,
+            sessions: HashMap::new(),
+            sessions_by_end: BTreeSet::new(),
+            changed: HashSet::new(),
+        })
+    }
+
+    fn key_columns(&self, batch: &RecordBatch) -> Vec<ArrayRef> {
+        self.input_schema
+            .key_indices
+            .as_ref()
+            .map(|indices| indices.iter().map(|i| batch.column(*i).clone()).collect())
+            .unwrap_or_default()
+    }
+
+    fn add_event(&mut self, key: OwnedRow, time: SystemTime, events: &mut Vec<SessionEvent>) {
+        let gap = self.gap;
+
+        match self.sessions.get_mut(&key) {
+            Some(session) if time < session.last + gap => {
+                self.sessions_by_end
+                    .remove(&(session.last + gap, key.clone()));
+                session.start = session.start.min(time);
+                session.last = session.last.max(time);
+                session.count += 1;
+                self.sessions_by_end
+                    .insert((session.last + gap, key.clone()));
+            }
+            _ => {
+                if let Some(ended) = self.sessions.remove(&key) {
+                    self.sessions_by_end
+                        .remove(&(ended.last + gap, key.clone()));
+                    events.push(self.ended(key.clone(), ended));
+                }
+
+                let session = Session {
+                    start: time,
+                    last: time,
+                    count: 1,
+                };
+                events.push(SessionEvent {
+                    key: key.clone(),
+                    event: SESSION_STARTED,
+                    session,
+                    timestamp: time,
+                });
+                self.sessions_by_end.insert((time + gap, key.clone()));
+                self.sessions.insert(key.clone(), session);
+            }
+        }
+
+        self.changed.insert(key);
+    }
+
+    fn ended(&self, key: OwnedRow, session: Session) -> SessionEvent {
+        SessionEvent {
+            key,
+            event: SESSION_ENDED,
+            session,
+            // the time at which the session was known to have ended
+            timestamp: session.last + self.gap - Duration::from_nanos(1),
+        }
+    }
+
+    /// Ends the sessions that have been idle for the gap as of `time`
+    fn end_sessions(&mut self, time: Option<SystemTime>) -> Vec<SessionEvent> {
+        let mut events = vec![];
+        while let Some((end, _)) = self.sessions_by_end.first() {
+            if time.is_some_and(|t| *end > t) {
+                break;
+            }
+
+            let (_, key) = self.sessions_by_end.pop_first().unwrap();
+            let session = self
+                .sessions
+                .remove(&key)
+                .expect("active session should be tracked");
+            self.changed.remove(&key);
+            events.push(self.ended(key, session));
+        }
+        events
+    }
+
+    fn to_record_batch(&self, events: Vec<SessionEvent>) -> Result<RecordBatch> {
+        let mut columns = self
+            .converter
+            .convert_rows(events.iter().map(|e| e.key.row()))?;
+
+        let event_columns: Vec<ArrayRef> = vec![
+            Arc::new(StringArray::from_iter_values(
+                events.iter().map(|e| e.event),
+            )),
+            Arc::new(TimestampNanosecondArray::from_iter_values(
+                events.iter().map(|e| to_nanos(e.session.start) as i64),
+            )),
+            Arc::new(TimestampNanosecondArray::from_iter(events.iter().map(
+                |e| (e.event == SESSION_ENDED).then(|| to_nanos(e.session.last) as i64),
+            ))),
+            Arc::new(Int64Array::from_iter(events.iter().map(|e| {
+                (e.event == SESSION_ENDED).then(|| {
+                    e.session
+                        .last
+                        .duration_since(e.session.start)
+                        .unwrap_or_default()
+                        .as_millis() as i64
+                })
+            }))),
+            Arc::new(Int64Array::from_iter_values(
+                events.iter().map(|e| e.session.count),
+            )),
+        ];
+        columns.push(Arc::new(StructArray::try_new(
+            self.event_fields.clone(),
+            event_columns,
+            None,
+        )?));
+
+        columns.push(Arc::new(TimestampNanosecondArray::from_iter_values(
+            events.iter().map(|e| to_nanos(e.timestamp) as i64),
+        )));
+
+        Ok(RecordBatch::try_new(
+            self.output_schema.schema.clone(),
+            columns,
+        )?)
+    }
+
+    async fn emit(&self, events: Vec<SessionEvent>, ctx: &mut ArrowContext) {
+        if events.is_empty() {
+            return;
+        }
+
+        let batch = self
+            .to_record_batch(events)
+            .expect("should be able to build session events");
+        ctx.collect(batch).await;
+    }
+
+    fn state_batch(&self, keys: &[OwnedRow]) -> Result<RecordBatch> {
+        let sessions: Vec<_> = keys.iter().map(|k| self.sessions[k]).collect();
+
+        let mut columns = self.converter.convert_rows(keys.iter().map(|k| k.row()))?;
+        columns.push(Arc::new(TimestampNanosecondArray::from_iter_values(
+            sessions.iter().map(|s| to_nanos(s.start) as i64),
+        )));
+        columns.push(Arc::new(Int64Array::from_iter_values(
+            sessions.iter().map(|s| s.count),
+        )));
+        columns.push(Arc::new(TimestampNanosecondArray::from_iter_values(
+            sessions.iter().map(|s| to_nanos(s.last) as i64),
+        )));
+
+        Ok(RecordBatch::try_new(
+            self.state_schema.schema.clone(),
+            columns,
+        )?)
+    }
+
+    /// Restores the sessions in a state batch that are still active at `watermark`, keeping the
+    /// most recent state for each key
+    fn restore(&mut self, batch: &RecordBatch, watermark: Option<SystemTime>) -> Result<()> {
+        let key_count = self.state_schema.key_indices.as_ref().unwrap().len();
+        let keys = self
+            .converter
+            .convert_columns(&batch.columns()[..key_count])?;
+        let starts = batch
+            .column(key_count)
+            .as_primitive::<TimestampNanosecondType>();
+        let counts = batch.column(key_count + 1).as_primitive::<Int64Type>();
+        let lasts = self.state_schema.timestamp_column(batch);
+
+        for i in 0..batch.num_rows() {
+            let session = Session {
+                start: from_nanos(starts.value(i) as u128),
+                last: from_nanos(lasts.value(i) as u128),
+                count: counts.value(i),
+            };
+
+            if watermark.is_some_and(|w| session.last + self.gap <= w) {
+                continue;
+            }
+
+            let key = keys.row(i).owned();
+            match self.sessions.get(&key) {
+                Some(existing)
+                    if (existing.start, existing.count) >= (session.start, session.count) => {}
+                _ => {
+                    self.sessions.insert(key, session);
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl ArrowOperator for SessionEventsOperator {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn display(&self) -> DisplayableOperator {
+        DisplayableOperator {
+            name: Cow::Borrowed("SessionEventsOperator"),
+            fields: vec![("gap", AsDisplayable::Debug(&self.gap))],
+        }
+    }
+
+    fn tables(&self) -> HashMap<String, TableConfig> {
+        vec![(
+            "s".to_string(),
+            timestamp_table_config(
+                "s",
+                "active sessions",
+                self.gap,
+                false,
+                self.state_schema.as_ref().clone(),
+            ),
+        )]
+        .into_iter()
+        .collect()
+    }
+
+    async fn on_start(&mut self, ctx: &mut ArrowContext) {
+        let watermark = ctx.last_present_watermark();
+        let table = ctx
+            .table_manager
+            .get_expiring_time_key_table("s", watermark)
+            .await
+            .expect("should have sessions table");
+
+        for (_, batches) in table
+            .all_batches_for_watermark(watermark)
+            .expect("should be able to read sessions")
+        {
+            for batch in batches {
+                self.restore(&batch, watermark)
+                    .expect("should be able to restore sessions");
+            }
+        }
+
+        let gap = self.gap;
+        self.sessions_by_end.extend(
+            self.sessions
+                .iter()
+                .map(|(key, session)| (session.last + gap, key.clone())),
+        );
+
+        debug!("restored {} active sessions", self.sessions.len());
+    }
+
+    async fn process_batch(
+        &mut self,
+        batch: RecordBatch,
+        ctx: &mut ArrowContext,
+    ) -> anyhow::Result<()> {
+        let batch = match ctx.last_present_watermark() {
+            Some(watermark) => {
+                // filter out late data
+                let watermark_scalar =
+                    TimestampNanosecondArray::new_scalar(to_nanos(watermark) as i64);
+                let on_time = gt_eq(
+                    self.input_schema.timestamp_column(&batch),
+                    &watermark_scalar,
+                )
+                .unwrap();
+                filter_record_batch(&batch, &on_time).unwrap()
+            }
+            None => batch,
+        };
+
+        if batch.num_rows() == 0 {
+            return Ok(());
+        }
+
+        let keys = self
+            .converter
+            .convert_columns(&self.key_columns(&batch))
+            .expect("should be able to convert keys");
+        let timestamps = self.input_schema.timestamp_column(&batch);
+
+        let mut events = vec![];
+        for i in sort_to_indices(timestamps, None, None).unwrap().values() {
+            let i = *i as usize;
+            self.add_event(
+                keys.row(i).owned(),
+                from_nanos(timestamps.value(i) as u128),
+                &mut events,
+            );
+        }
+
+        self.emit(events, ctx).await;
+        Ok(())
+    }
+
+    async fn handle_watermark(
+        &mut self,
+        watermark: Watermark,
+        ctx: &mut ArrowContext,
+    ) -> Option<Watermark> {
+        if let Watermark::EventTime(t) = watermark {
+            let events = self.end_sessions(Some(t));
+            self.emit(events, ctx).await;
+        }
+
+        Some(watermark)
+    }
+
+    async fn handle_checkpoint(
+        &mut self,
+        _: CheckpointBarrier,
+        ctx: &mut ArrowContext,
+    ) -> anyhow::Result<()> {
+        let watermark = ctx.last_present_watermark();
+        let changed: Vec<_> = self.changed.drain().collect();
+
+        let table = ctx
+            .table_manager
+            .get_expiring_time_key_table("s", watermark)
+            .await
+            .expect("should have sessions table");
+
+        if let Some(latest) = changed.iter().map(|k| self.sessions[k].last).max() {
+            let batch = self
+                .state_batch(&changed)
+                .expect("should be able to build session state");
+            table.insert(latest, batch);
+        }
+
+        table.flush(watermark).await.expect("should flush sessions");
+        Ok(())
+    }
+
+    async fn on_close(
+        &mut self,
+        final_message: &Option<SignalMessage>,
+        ctx: &mut ArrowContext,
+    ) -> anyhow::Result<()> {
+        if let Some(SignalMessage::EndOfData) = final_message {
+            // no more events will arrive, so every session has ended
+            let events = self.end_sessions(None);
+            self.emit(events, ctx).await;
+        }
+
+        Ok(())
+    }
+}
+
+pub struct SessionEventsConstructor;
+
+impl OperatorConstructor for SessionEventsConstructor {
+    type ConfigT = api::SessionEventsOperator;
+
+    fn with_config(
+        &self,
+        config: Self::ConfigT,
+        _registry: Arc<Registry>,
+    ) -> anyhow::Result<OperatorNode> {
+        Ok(OperatorNode::from_operator(Box::new(
+            SessionEventsOperator::new(config)?,
+        )))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use arroyo_df::schemas::session_events_struct;
+
+    const GAP: Duration = Duration::from_secs(10);
+
+    fn timestamp_field() -> Field {
+        Field::new(
+            TIMESTAMP_FIELD,
+            DataType::Timestamp(TimeUnit::Nanosecond, None),
+            false,
+        )
+    }
+
+    fn operator() -> SessionEventsOperator {
+        let user = Field::new("user", DataType::Utf8, false);
+        let input_schema = ArroyoSchema::new_keyed(
+            Arc::new(Schema::new(vec![user.clone(), timestamp_field()])),
+            1,
+            vec![0],
+        );
+        let output_schema = ArroyoSchema::new_keyed(
+            Arc::new(Schema::new(vec![
+                user,
+                Field::new("session", session_events_struct(), false),
+                timestamp_field(),
+            ])),
+            2,
+            vec![0],
+        );
+
+        SessionEventsOperator::new(api::SessionEventsOperator {
+            name: "session_events".to_string(),
+            input_schema: Some(input_schema.into()),
+            output_schema: Some(output_schema.into()),
+            gap_micros: GAP.as_micros() as u64,
+        })
+        .unwrap()
+    }
+
+    fn key(operator: &SessionEventsOperator, user: &str) -> OwnedRow {
+        let users: ArrayRef = Arc::new(StringArray::from(vec![user]));
+        operator
+            .converter
+            .convert_columns(&[users])
+            .unwrap()
+            .row(0)
+            .owned()
+    }
+
+    fn at(secs: u64) -> SystemTime {
+        SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000 + secs)
+    }
+
+    #[test]
+    fn test_session_open() {
+        let mut operator = operator();
+        let a = key(&operator, "a");
+        let b = key(&operator, "b");
+
+        let mut events = vec![];
+        operator.add_event(a.clone(), at(0), &mut events);
+        operator.add_event(a.clone(), at(5), &mut events);
+        // out of order, but within the session
+        operator.add_event(a.clone(), at(3), &mut events);
+        operator.add_event(b.clone(), at(4), &mut events);
+
+        // a session start is only emitted for the first event of each session
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].key, a);
+        assert_eq!(events[0].event, SESSION_STARTED);
+        assert_eq!(events[0].timestamp, at(0));
+        assert_eq!(events[1].key, b);
+
+        let session = operator.sessions[&a];
+        assert_eq!(
+            (session.start, session.last, session.count),
+            (at(0), at(5), 3)
+        );
+        assert_eq!(operator.changed.len(), 2);
+    }
+
+    #[test]
+    fn test_session_close() {
+        let mut operator = operator();
+        let a = key(&operator, "a");
+
+        let mut events = vec![];
+        operator.add_event(a.clone(), at(0), &mut events);
+        operator.add_event(a.clone(), at(9), &mut events);
+        // arrives a full gap after the last event, so it starts a new session
+        operator.add_event(a.clone(), at(19), &mut events);
+
+        let kinds: Vec<_> = events.iter().map(|e| e.event).collect();
+        assert_eq!(kinds, vec![SESSION_STARTED, SESSION_ENDED, SESSION_STARTED]);
+
+        let ended = &events[1];
+        assert_eq!(
+            (ended.session.start, ended.session.last, ended.session.count),
+            (at(0), at(9), 2)
+        );
+        assert_eq!(ended.timestamp, at(19) - Duration::from_nanos(1));
+
+        assert_eq!(operator.sessions[&a].start, at(19));
+        assert_eq!(operator.sessions_by_end.len(), 1);
+
+        let batch = operator.to_record_batch(events).unwrap();
+        assert_eq!(batch.num_rows(), 3);
+        let session = batch.column(1).as_struct();
+        let ends = session
+            .column_by_name("end")
+            .unwrap()
+            .as_primitive::<TimestampNanosecondType>();
+        let durations = session
+            .column_by_name("duration_ms")
+            .unwrap()
+            .as_primitive::<Int64Type>();
+
+        // only ended sessions have an end and a duration
+        assert!(ends.is_null(0) && durations.is_null(0));
+        assert_eq!(ends.value(1), to_nanos(at(9)) as i64);
+        assert_eq!(durations.value(1), 9_000);
+        assert!(ends.is_null(2) && durations.is_null(2));
+    }
+
+    #[test]
+    fn test_session_timeout() {
+        let mut operator = operator();
+        let a = key(&operator, "a");
+        let b = key(&operator, "b");
+
+        let mut events = vec![];
+        operator.add_event(a.clone(), at(0), &mut events);
+        operator.add_event(b.clone(), at(5), &mut events);
+
+        // neither session has been idle for the gap yet
+        assert!(operator.end_sessions(Some(at(9))).is_empty());
+
+        let ended = operator.end_sessions(Some(at(10)));
+        assert_eq!(ended.len(), 1);
+        assert_eq!(ended[0].key, a);
+        assert_eq!(ended[0].event, SESSION_ENDED);
+        assert!(!operator.sessions.contains_key(&a));
+        assert!(!operator.changed.contains(&a));
+
+        // at the end of the input, every remaining session ends
+        let ended = operator.end_sessions(None);
+        assert_eq!(ended.len(), 1);
+        assert_eq!(ended[0].key, b);
+        assert!(operator.sessions.is_empty());
+        assert!(operator.sessions_by_end.is_empty());
+    }
+
+    #[test]
+    fn test_restore_sessions() {
+        let mut operator = operator();
+        let a = key(&operator, "a");
+        let b = key(&operator, "b");
+
+        let mut events = vec![];
+        operator.add_event(a.clone(), at(0), &mut events);
+        operator.add_event(a.clone(), at(2), &mut events);
+        operator.add_event(b.clone(), at(20), &mut events);
+        let state = operator.state_batch(&[a.clone(), b.clone()]).unwrap();
+
+        // sessions that had already ended by the watermark aren't restored
+        let mut restored = self::operator();
+        restored.restore(&state, Some(at(15))).unwrap();
+        assert!(!restored.sessions.contains_key(&a));
+        assert_eq!(restored.sessions[&b].start, at(20));
+
+        let mut restored = self::operator();
+        restored.restore(&state, None).unwrap();
+        let session = restored.sessions[&a];
+        assert_eq!(
+            (session.start, session.last, session.count),
+            (at(0), at(2), 2)
+        );
+    }
+}
@@ -155,7 +155,7 @@ impl SlidingAggregatingWindowFunc<SystemTime> {
             }
         }
         partial_table.flush_timestamp(bin_end).await?;
-        partial_table.expire_timestamp(bin_end - self.width + self.slide);
+        partial_table.expire_timestamp(bin_end - self.width + self.slide)?;
         let interval_start = bin_end - self.width;
         let interval_end = bin_end;
         {
@@ -173,7 +173,7 @@ impl SlidingAggregatingWindowFunc<SystemTime> {
             .delete_before(bin_end + self.slide - self.width)?;
 
         self.state = if self.tiered_record_batches.is_empty() {
-            match partial_table.get_min_time() {
+            match partial_table.get_min_time()? {
                 Some(min_time) => SlidingWindowState::OnlyBufferedData {
                     earliest_bin_time: self.bin_start(min_time),
                 },
@@ -563,24 +563,23 @@ impl ArrowOperator for SlidingAggregatingWindowFunc<SystemTime> {
             .expect("should be able to load table");
         // bins before the watermark should be put into the TieredRecordBatchHolder, those after in the exec.
         let watermark_bin = self.bin_start(watermark.unwrap_or(SystemTime::UNIX_EPOCH));
-        for (timestamp, batches) in table.all_batches_for_watermark(watermark) {
-            let bin = self.bin_start(*timestamp);
+        for (timestamp, batches) in table
+            .all_batches_for_watermark(watermark)
+            .expect("should be able to read table")
+        {
+            let bin = self.bin_start(timestamp);
             if bin < watermark_bin {
                 for batch in batches {
-                    self.tiered_record_batches
-                        .insert(batch.clone(), bin)
-                        .unwrap();
+                    self.tiered_record_batches.insert(batch, bin).unwrap();
                 }
                 continue;
             }
             let holder = self.execs.entry(bin).or_default();
-            batches
-                .iter()
-                .for_each(|batch| holder.finished_batches.push(batch.clone()));
+            holder.finished_batches.extend(batches);
         }
 
         if self.tiered_record_batches.is_empty() {
-            match table.get_min_time() {
+            match table.get_min_time().expect("should be able to read table") {
                 Some(min_time) => {
                     self.state = SlidingWindowState::OnlyBufferedData {
                         earliest_bin_time: self.bin_start(min_time),
@@ -596,7 +595,11 @@ impl ArrowOperator for SlidingAggregatingWindowFunc<SystemTime> {
     }
 
     // TODO: filter out late data
-    async fn process_batch(&mut self, batch: RecordBatch, ctx: &mut ArrowContext) {
+    async fn process_batch(
+        &mut self,
+        batch: RecordBatch,
+        ctx: &mut ArrowContext,
+    ) -> anyhow::Result<()> {
         let bin = self
             .binning_function
             .evaluate(&batch)
@@ -625,7 +628,7 @@ impl ArrowOperator for SlidingAggregatingWindowFunc<SystemTime> {
             let watermark = ctx.last_present_watermark();
 
             if watermark.is_some() && bin_start < self.bin_start(watermark.unwrap()) {
-                return;
+                return Ok(());
             }
 
             self.state = match self.state {
@@ -666,6 +669,7 @@ impl ArrowOperator for SlidingAggregatingWindowFunc<SystemTime> {
                 .send(bin_batch)
                 .unwrap();
         }
+        Ok(())
     }
 
     async fn handle_watermark(
@@ -682,7 +686,11 @@ impl ArrowOperator for SlidingAggregatingWindowFunc<SystemTime> {
         Some(watermark)
     }
 
-    async fn handle_checkpoint(&mut self, _b: CheckpointBarrier, ctx: &mut ArrowContext) {
+    async fn handle_checkpoint(
+        &mut self,
+        _b: CheckpointBarrier,
+        ctx: &mut ArrowContext,
+    ) -> anyhow::Result<()> {
         let watermark = ctx
             .watermark()
             .and_then(|watermark: Watermark| match watermark {
@@ -721,6 +729,7 @@ impl ArrowOperator for SlidingAggregatingWindowFunc<SystemTime> {
             }
         }
         table.flush(watermark).await.unwrap();
+        Ok(())
     }
 
     fn tables(&self) -> HashMap<String, TableConfig> {
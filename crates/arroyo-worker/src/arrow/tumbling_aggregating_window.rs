@@ -1,7 +1,9 @@
 use anyhow::{anyhow, Result};
 use arrow::compute::{partition, sort_to_indices, take};
-use arrow_array::{types::TimestampNanosecondType, Array, PrimitiveArray, RecordBatch};
-use arrow_schema::SchemaRef;
+use arrow_array::{
+    types::TimestampNanosecondType, Array, PrimitiveArray, RecordBatch, TimestampNanosecondArray,
+};
+use arrow_schema::{Schema, SchemaRef};
 use arroyo_df::schemas::add_timestamp_field_arrow;
 use arroyo_operator::context::ArrowContext;
 use arroyo_operator::operator::{
@@ -46,8 +48,11 @@ use super::sync::streams::KeyedCloneableStreamFuture;
 type NextBatchFuture<K> = KeyedCloneableStreamFuture<K, SendableRecordBatchStream>;
 
 pub struct TumblingAggregatingWindowFunc<K: Copy> {
+    // for windows with custom bins, this is the maximum width of a bin
     width: Duration,
     binning_function: Arc<dyn PhysicalExpr>,
+    // for windows with custom bins, computes the bin start for a batch containing only _timestamp
+    custom_binning_function: Option<Arc<dyn PhysicalExpr>>,
     partial_aggregation_plan: Arc<dyn ExecutionPlan>,
     partial_schema: ArroyoSchema,
     finish_execution_plan: Arc<dyn ExecutionPlan>,
@@ -63,6 +68,9 @@ pub struct TumblingAggregatingWindowFunc<K: Copy> {
 
 impl<K: Copy> TumblingAggregatingWindowFunc<K> {
     fn bin_start(&self, timestamp: SystemTime) -> SystemTime {
+        if let Some(binning_function) = &self.custom_binning_function {
+            return Self::evaluate_binning_function(binning_function.as_ref(), timestamp);
+        }
         if self.width == Duration::ZERO {
             return timestamp;
         }
@@ -71,6 +79,66 @@ impl<K: Copy> TumblingAggregatingWindowFunc<K> {
 
         from_nanos(nanos)
     }
+
+    /// The end (exclusive) of the bin starting at `bin_start`. For custom bins this is the first
+    /// timestamp that the binning function places in a later bin, found by binary search; bins
+    /// that appear to be wider than the maximum width are cut off at it.
+    fn bin_end(&self, bin_start: SystemTime) -> SystemTime {
+        let Some(binning_function) = &self.custom_binning_function else {
+            return bin_start + self.width;
+        };
+
+        let mut low = to_nanos(bin_start);
+        let mut high = low + self.width.as_nanos();
+        if Self::evaluate_binning_function(binning_function.as_ref(), from_nanos(high)) == bin_start
+        {
+            warn!(
+                "bin starting at {} is wider than the maximum width {:?}",
+                print_time(bin_start),
+                self.width
+            );
+            return from_nanos(high);
+        }
+
+        // invariant: low is in the bin, high is not
+        while high - low > 1 {
+            let mid = low + (high - low) / 2;
+            if Self::evaluate_binning_function(binning_function.as_ref(), from_nanos(mid))
+                == bin_start
+            {
+                low = mid;
+            } else {
+                high = mid;
+            }
+        }
+
+        from_nanos(high)
+    }
+
+    fn evaluate_binning_function(
+        binning_function: &dyn PhysicalExpr,
+        timestamp: SystemTime,
+    ) -> SystemTime {
+        let batch = RecordBatch::try_new(
+            add_timestamp_field_arrow(Arc::new(Schema::empty())),
+            vec![Arc::new(TimestampNanosecondArray::from(vec![
+                to_nanos(timestamp) as i64,
+            ]))],
+        )
+        .unwrap();
+
+        let bin = binning_function
+            .evaluate(&batch)
+            .and_then(|bin| bin.into_array(1))
+            .expect("should be able to evaluate binning function");
+
+        from_nanos(
+            bin.as_any()
+                .downcast_ref::<PrimitiveArray<TimestampNanosecondType>>()
+                .expect("binning function should return a timestamp")
+                .value(0) as u128,
+        )
+    }
 }
 
 struct BinComputingHolder<K: Copy> {
@@ -128,6 +196,19 @@ impl OperatorConstructor for TumblingAggregateWindowConstructor {
             &DefaultPhysicalExtensionCodec {},
         )?;
 
+        let custom_binning_function = config
+            .custom_binning_function
+            .map(|proto| {
+                let binning_function = PhysicalExprNode::decode(&mut proto.as_slice())?;
+                Ok::<_, anyhow::Error>(parse_physical_expr(
+                    &binning_function,
+                    registry.as_ref(),
+                    &add_timestamp_field_arrow(Arc::new(Schema::empty())),
+                    &DefaultPhysicalExtensionCodec {},
+                )?)
+            })
+            .transpose()?;
+
         let receiver = Arc::new(RwLock::new(None));
         let final_batches_passer = Arc::new(RwLock::new(Vec::new()));
 
@@ -186,6 +267,7 @@ impl OperatorConstructor for TumblingAggregateWindowConstructor {
             TumblingAggregatingWindowFunc {
                 width,
                 binning_function,
+                custom_binning_function,
                 partial_aggregation_plan,
                 partial_schema,
                 finish_execution_plan,
@@ -237,16 +319,21 @@ impl ArrowOperator for TumblingAggregatingWindowFunc<SystemTime> {
             .get_expiring_time_key_table("t", watermark)
             .await
             .expect("should be able to load table");
-        for (timestamp, batch) in table.all_batches_for_watermark(watermark) {
-            let bin = self.bin_start(*timestamp);
+        for (timestamp, batches) in table
+            .all_batches_for_watermark(watermark)
+            .expect("should be able to read table")
+        {
+            let bin = self.bin_start(timestamp);
             let holder = self.execs.entry(bin).or_default();
-            batch
-                .iter()
-                .for_each(|batch| holder.finished_batches.push(batch.clone()));
+            holder.finished_batches.extend(batches);
         }
     }
 
-    async fn process_batch(&mut self, batch: RecordBatch, ctx: &mut ArrowContext) {
+    async fn process_batch(
+        &mut self,
+        batch: RecordBatch,
+        ctx: &mut ArrowContext,
+    ) -> anyhow::Result<()> {
         let bin = self
             .binning_function
             .evaluate(&batch)
@@ -307,6 +394,7 @@ impl ArrowOperator for TumblingAggregatingWindowFunc<SystemTime> {
                 .send(bin_batch)
                 .unwrap();
         }
+        Ok(())
     }
 
     async fn handle_watermark(
@@ -347,12 +435,20 @@ impl ArrowOperator for TumblingAggregatingWindowFunc<SystemTime> {
                         .finish_execution_plan
                         .execute(0, SessionContext::new().task_ctx())
                         .unwrap();
+                    // custom bins can't be derived from their start by a fixed width, so for
+                    // them we emit the last timestamp of the bin, from which the final
+                    // projection can recover both ends
+                    let output_timestamp = if self.custom_binning_function.is_some() {
+                        self.bin_end(popped_bin) - Duration::from_nanos(1)
+                    } else {
+                        popped_bin
+                    };
                     let mut aggregate_results = vec![];
                     while let Some(batch) = final_exec.next().await {
                         let batch = batch.expect("should be able to compute batch");
                         let with_timestamp = Self::add_bin_start_as_timestamp(
                             &batch,
-                            popped_bin,
+                            output_timestamp,
                             self.aggregate_with_timestamp_schema.clone(),
                         )
                         .expect("should be able to add timestamp");
@@ -420,7 +516,11 @@ impl ArrowOperator for TumblingAggregatingWindowFunc<SystemTime> {
         }
     }
 
-    async fn handle_checkpoint(&mut self, _b: CheckpointBarrier, ctx: &mut ArrowContext) {
+    async fn handle_checkpoint(
+        &mut self,
+        _b: CheckpointBarrier,
+        ctx: &mut ArrowContext,
+    ) -> anyhow::Result<()> {
         let watermark = ctx
             .watermark()
             .and_then(|watermark: Watermark| match watermark {
@@ -453,6 +553,7 @@ impl ArrowOperator for TumblingAggregatingWindowFunc<SystemTime> {
             }
         }
         table.flush(watermark).await.unwrap();
+        Ok(())
     }
 
     fn tables(&self) -> HashMap<String, TableConfig> {
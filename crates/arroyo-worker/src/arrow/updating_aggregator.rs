@@ -24,7 +24,7 @@ use arroyo_rpc::df::ArroyoSchemaRef;
 use arroyo_rpc::grpc::{api::UpdatingAggregateOperator, rpc::TableConfig};
 use arroyo_rpc::{updating_meta_fields, UPDATING_META_FIELD};
 use arroyo_state::timestamp_table_config;
-use arroyo_types::{CheckpointBarrier, SignalMessage, Watermark};
+use arroyo_types::{from_nanos, ArrowMessage, CheckpointBarrier, SignalMessage, Watermark};
 use datafusion::execution::{
     runtime_env::{RuntimeConfig, RuntimeEnv},
     SendableRecordBatchStream,
@@ -54,6 +54,9 @@ pub struct UpdatingAggregatingFunc {
     // while if it does have group by keys it will emit a record batch with 0 rows.
     exec: Arc<Mutex<Option<SendableRecordBatchStream>>>,
     ttl: Duration,
+    // blocking aggregates (used in bounded execution mode) only emit their results once their
+    // input is exhausted
+    blocking: bool,
 }
 
 impl UpdatingAggregatingFunc {
@@ -138,6 +141,11 @@ impl UpdatingAggregatingFunc {
                 results.columns().to_vec(),
             )?;
 
+            if self.blocking {
+                final_output_table.insert_batch(renamed_results).await?;
+                continue;
+            }
+
             if let Some((prior_batch, _filter)) =
                 final_output_table.get_current_matching_values(&renamed_results)?
             {
@@ -168,6 +176,31 @@ impl UpdatingAggregatingFunc {
         Ok(())
     }
 
+    /// Emits the final result for every key once the input of a blocking aggregate has been
+    /// exhausted
+    async fn emit_final_results(&mut self, ctx: &mut ArrowContext) -> Result<()> {
+        let Some(mut results) = ctx
+            .table_manager
+            .get_last_key_value_table("f", ctx.last_present_watermark())
+            .await?
+            .get_all_values(self.state_final_schema.schema.clone())?
+        else {
+            return Ok(());
+        };
+
+        // the results are final, so they're emitted without updating metadata
+        results.remove_column(results.schema().index_of(UPDATING_META_FIELD)?);
+
+        let out_schema = ctx.out_schema.as_ref().unwrap().schema.clone();
+        ctx.collect(RecordBatch::try_new(
+            out_schema,
+            results.columns().to_vec(),
+        )?)
+        .await;
+
+        Ok(())
+    }
+
     fn set_retract_metadata(
         out_schema: SchemaRef,
         mut batch: RecordBatch,
@@ -217,6 +250,7 @@ impl ArrowOperator for UpdatingAggregatingFunc {
             fields: vec![
                 ("flush_interval", AsDisplayable::Debug(&self.flush_interval)),
                 ("ttl", AsDisplayable::Debug(&self.ttl)),
+                ("blocking", AsDisplayable::Debug(&self.blocking)),
                 (
                     "partial_aggregation_schema",
                     (&*self.partial_schema.schema).into(),
@@ -242,15 +276,25 @@ impl ArrowOperator for UpdatingAggregatingFunc {
         }
     }
 
-    async fn process_batch(&mut self, batch: RecordBatch, _ctx: &mut ArrowContext) {
+    async fn process_batch(
+        &mut self,
+        batch: RecordBatch,
+        _ctx: &mut ArrowContext,
+    ) -> anyhow::Result<()> {
         if self.sender.is_none() {
             self.init_exec();
         }
         self.sender.as_ref().unwrap().send(batch).unwrap();
+        Ok(())
     }
 
-    async fn handle_checkpoint(&mut self, _b: CheckpointBarrier, ctx: &mut ArrowContext) {
+    async fn handle_checkpoint(
+        &mut self,
+        _b: CheckpointBarrier,
+        ctx: &mut ArrowContext,
+    ) -> anyhow::Result<()> {
         self.flush(ctx).await.unwrap();
+        Ok(())
     }
 
     fn tables(&self) -> HashMap<String, TableConfig> {
@@ -283,8 +327,9 @@ impl ArrowOperator for UpdatingAggregatingFunc {
         Some(self.flush_interval)
     }
 
-    async fn handle_tick(&mut self, _tick: u64, ctx: &mut ArrowContext) {
+    async fn handle_tick(&mut self, _tick: u64, ctx: &mut ArrowContext) -> anyhow::Result<()> {
         self.flush(ctx).await.unwrap();
+        Ok(())
     }
 
     async fn handle_watermark(
@@ -292,6 +337,12 @@ impl ArrowOperator for UpdatingAggregatingFunc {
         watermark: Watermark,
         ctx: &mut ArrowContext,
     ) -> Option<Watermark> {
+        if self.blocking {
+            // state is kept until the input is exhausted, and watermarks are held back until
+            // the results are emitted so that downstream operators don't consider them late
+            return None;
+        }
+
         let last_watermark = ctx.last_present_watermark();
         let partial_table = ctx
             .table_manager
@@ -335,10 +386,24 @@ impl ArrowOperator for UpdatingAggregatingFunc {
         //unreachable!("should not have future result")
     }
 
-    async fn on_close(&mut self, final_mesage: &Option<SignalMessage>, ctx: &mut ArrowContext) {
+    async fn on_close(
+        &mut self,
+        final_mesage: &Option<SignalMessage>,
+        ctx: &mut ArrowContext,
+    ) -> anyhow::Result<()> {
         if let Some(SignalMessage::EndOfData) = final_mesage {
-            self.flush(ctx).await.unwrap();
+            self.flush(ctx).await?;
+
+            if self.blocking {
+                self.emit_final_results(ctx).await?;
+                ctx.broadcast(ArrowMessage::Signal(SignalMessage::Watermark(
+                    Watermark::EventTime(from_nanos(u64::MAX as u128)),
+                )))
+                .await;
+            }
         }
+
+        Ok(())
     }
 
     async fn on_start(&mut self, ctx: &mut ArrowContext) {
@@ -430,6 +495,7 @@ impl OperatorConstructor for UpdatingAggregatingConstructor {
                 sender: None,
                 exec: Arc::new(Mutex::new(None)),
                 ttl: Duration::from_micros(ttl),
+                blocking: config.blocking,
             },
         )))
     }
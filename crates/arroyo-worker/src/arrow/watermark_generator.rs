@@ -131,7 +131,11 @@ impl ArrowOperator for WatermarkGenerator {
         self.state_cache = state;
     }
 
-    async fn on_close(&mut self, final_message: &Option<SignalMessage>, ctx: &mut ArrowContext) {
+    async fn on_close(
+        &mut self,
+        final_message: &Option<SignalMessage>,
+        ctx: &mut ArrowContext,
+    ) -> anyhow::Result<()> {
         if let Some(SignalMessage::EndOfData) = final_message {
             // send final watermark on close
             ctx.collector
@@ -142,15 +146,21 @@ impl ArrowOperator for WatermarkGenerator {
                 )))
                 .await;
         }
+
+        Ok(())
     }
 
-    async fn process_batch(&mut self, record: RecordBatch, ctx: &mut ArrowContext) {
+    async fn process_batch(
+        &mut self,
+        record: RecordBatch,
+        ctx: &mut ArrowContext,
+    ) -> anyhow::Result<()> {
         ctx.collector.collect(record.clone()).await;
         self.last_event = SystemTime::now();
 
         let timestamp_column = get_timestamp_col(&record, ctx);
         let Some(max_timestamp) = kernels::aggregate::max(timestamp_column) else {
-            return;
+            return Ok(());
         };
         let max_timestamp = from_nanos(max_timestamp as u128);
 
@@ -167,7 +177,12 @@ impl ArrowOperator for WatermarkGenerator {
             .downcast_ref::<arrow::array::TimestampNanosecondArray>()
             .unwrap();
 
-        let watermark = from_nanos(kernels::aggregate::min(watermark).unwrap() as u128);
+        // rows for which the expression is null (like those for which a watermark UDF doesn't
+        // advance the watermark) are ignored; if all of them are, there's nothing to emit
+        let Some(watermark) = kernels::aggregate::min(watermark) else {
+            return Ok(());
+        };
+        let watermark = from_nanos(watermark as u128);
 
         self.state_cache.max_watermark = self.state_cache.max_watermark.max(watermark);
         if self.idle
@@ -189,9 +204,14 @@ impl ArrowOperator for WatermarkGenerator {
             self.state_cache.last_watermark_emitted_at = max_timestamp;
             self.idle = false;
         }
+        Ok(())
     }
 
-    async fn handle_checkpoint(&mut self, _: CheckpointBarrier, ctx: &mut ArrowContext) {
+    async fn handle_checkpoint(
+        &mut self,
+        _: CheckpointBarrier,
+        ctx: &mut ArrowContext,
+    ) -> anyhow::Result<()> {
         let gs = ctx
             .table_manager
             .get_global_keyed_state("s")
@@ -199,9 +219,10 @@ impl ArrowOperator for WatermarkGenerator {
             .expect("state");
 
         gs.insert(ctx.task_info.task_index, self.state_cache).await;
+        Ok(())
     }
 
-    async fn handle_tick(&mut self, _: u64, ctx: &mut ArrowContext) {
+    async fn handle_tick(&mut self, _: u64, ctx: &mut ArrowContext) -> anyhow::Result<()> {
         if let Some(idle_time) = self.idle_time {
             if self.last_event.elapsed().unwrap_or(Duration::ZERO) > idle_time && !self.idle {
                 info!(
@@ -215,5 +236,6 @@ impl ArrowOperator for WatermarkGenerator {
                 self.idle = true;
             }
         }
+        Ok(())
     }
 }
@@ -130,14 +130,18 @@ impl ArrowOperator for WindowFunctionOperator {
             .get_expiring_time_key_table("input", watermark)
             .await
             .unwrap();
-        for (timestamp, batches) in table.all_batches_for_watermark(watermark) {
-            let exec = self.get_or_insert_exec(*timestamp).await;
+        for (timestamp, batches) in table.all_batches_for_watermark(watermark).unwrap() {
+            let exec = self.get_or_insert_exec(timestamp).await;
             for batch in batches {
-                exec.sender.send(batch.clone()).unwrap();
+                exec.sender.send(batch).unwrap();
             }
         }
     }
-    async fn process_batch(&mut self, batch: RecordBatch, ctx: &mut ArrowContext) {
+    async fn process_batch(
+        &mut self,
+        batch: RecordBatch,
+        ctx: &mut ArrowContext,
+    ) -> anyhow::Result<()> {
         let current_watermark = ctx.last_present_watermark();
         let table = ctx
             .table_manager
@@ -152,6 +156,7 @@ impl ArrowOperator for WindowFunctionOperator {
             let bin_exec = self.get_or_insert_exec(timestamp).await;
             bin_exec.sender.send(batch).unwrap();
         }
+        Ok(())
     }
 
     async fn handle_watermark(
@@ -186,7 +191,11 @@ impl ArrowOperator for WindowFunctionOperator {
         Some(watermark_message)
     }
 
-    async fn handle_checkpoint(&mut self, _cb: CheckpointBarrier, ctx: &mut ArrowContext) {
+    async fn handle_checkpoint(
+        &mut self,
+        _cb: CheckpointBarrier,
+        ctx: &mut ArrowContext,
+    ) -> anyhow::Result<()> {
         let watermark = ctx.last_present_watermark();
         ctx.table_manager
             .get_expiring_time_key_table("input", watermark)
@@ -195,6 +204,7 @@ impl ArrowOperator for WindowFunctionOperator {
             .flush(watermark)
             .await
             .expect("should flush");
+        Ok(())
     }
 
     fn tables(&self) -> HashMap<String, TableConfig> {
@@ -0,0 +1,88 @@
+//! Fault injection for testing that pipelines recover correctly from failures, configured by
+//! the `worker.chaos` config section (see `arroyo chaos-test`).
+//!
+//! Faults are injected at random times, with exponentially-distributed intervals between them.
+//! An interval of zero disables that kind of fault.
+
+use arroyo_rpc::config::{config, ChaosConfig, HumanReadableDuration};
+use rand::random;
+use std::process::exit;
+use std::time::Duration;
+use tracing::error;
+
+/// The exit code used by workers that have been killed by fault injection
+const CHAOS_KILL_EXIT_CODE: i32 = 137;
+
+fn chaos_config() -> Option<ChaosConfig> {
+    let chaos = config().worker.chaos.clone();
+    chaos.enabled.then_some(chaos)
+}
+
+/// The average interval between faults of a kind, if they're enabled
+fn fault_interval(interval: &Option<HumanReadableDuration>) -> Option<Duration> {
+    interval.as_ref().map(|i| **i).filter(|i| !i.is_zero())
+}
+
+fn sample_interval(mean: Duration) -> Duration {
+    // inverse transform sampling from the exponential distribution
+    let u: f64 = random();
+    Duration::from_secs_f64(mean.as_secs_f64() * -(1.0 - u).ln())
+}
+
+/// If worker kills are enabled, starts a task that will kill this worker process at a random
+/// time, without shutting down cleanly
+pub(crate) fn start_worker_kills() {
+    let Some(interval) = chaos_config().and_then(|c| fault_interval(&c.kill_interval)) else {
+        return;
+    };
+
+    let delay = sample_interval(interval);
+    tokio::spawn(async move {
+        tokio::time::sleep(delay).await;
+        error!(
+            message = "chaos: killing worker",
+            after = format!("{:?}", delay)
+        );
+        exit(CHAOS_KILL_EXIT_CODE);
+    });
+}
+
+/// Resolves when a data connection should be dropped. If network faults are not enabled, this
+/// never resolves.
+pub(crate) async fn next_network_fault() {
+    let Some(interval) = chaos_config().and_then(|c| fault_interval(&c.network_fault_interval))
+    else {
+        return futures::future::pending().await;
+    };
+
+    tokio::time::sleep(sample_interval(interval)).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{fault_interval, sample_interval};
+    use std::time::Duration;
+
+    #[test]
+    fn test_fault_interval() {
+        assert_eq!(fault_interval(&None), None);
+        assert_eq!(fault_interval(&Some("0s".parse().unwrap())), None);
+        assert_eq!(
+            fault_interval(&Some("30s".parse().unwrap())),
+            Some(Duration::from_secs(30))
+        );
+    }
+
+    #[test]
+    fn test_sample_interval() {
+        let mean = Duration::from_secs(10);
+        let samples: Vec<_> = (0..10_000).map(|_| sample_interval(mean)).collect();
+
+        let average = samples.iter().sum::<Duration>() / samples.len() as u32;
+        assert!(
+            average > Duration::from_secs(9) && average < Duration::from_secs(11),
+            "{:?}",
+            average
+        );
+    }
+}
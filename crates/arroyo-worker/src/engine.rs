@@ -1,10 +1,11 @@
-use std::collections::{BTreeMap, HashMap};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fmt::{Debug, Formatter};
 use std::mem;
 use std::sync::{Arc, RwLock};
 
-use std::time::SystemTime;
+use std::time::{Duration, SystemTime};
 
+use anyhow::anyhow;
 use arroyo_connectors::connectors;
 use arroyo_rpc::df::ArroyoSchema;
 use bincode::{Decode, Encode};
@@ -13,9 +14,12 @@ use futures::StreamExt;
 use tracing::{info, warn};
 
 use crate::arrow::async_udf::AsyncUdfConstructor;
+use crate::arrow::dedupe::DedupeConstructor;
+use crate::arrow::delay::DelayConstructor;
 use crate::arrow::instant_join::InstantJoinConstructor;
 use crate::arrow::join_with_expiration::JoinWithExpirationConstructor;
 use crate::arrow::session_aggregating_window::SessionAggregatingWindowConstructor;
+use crate::arrow::session_events::SessionEventsConstructor;
 use crate::arrow::sliding_aggregating_window::SlidingAggregatingWindowConstructor;
 use crate::arrow::tumbling_aggregating_window::TumblingAggregateWindowConstructor;
 use crate::arrow::updating_aggregator::UpdatingAggregatingConstructor;
@@ -24,27 +28,32 @@ use crate::arrow::window_fn::WindowFunctionConstructor;
 use crate::arrow::{KeyExecutionConstructor, ValueExecutionConstructor};
 use crate::network_manager::{NetworkManager, Quad, Senders};
 use arroyo_datastream::logical::{
-    LogicalEdge, LogicalEdgeType, LogicalGraph, LogicalNode, OperatorName,
+    LogicalEdge, LogicalEdgeType, LogicalGraph, LogicalNode, LogicalProgram, OperatorName,
+    ProgramConfig,
 };
 use arroyo_df::physical::new_registry;
-use arroyo_operator::context::{batch_bounded, ArrowContext, BatchReceiver, BatchSender};
+use arroyo_operator::context::{
+    batch_bounded_with_max_bytes, ArrowContext, BatchReceiver, BatchSender, SourceThrottle,
+};
 use arroyo_operator::operator::OperatorNode;
 use arroyo_operator::operator::Registry;
 use arroyo_operator::ErasedConstructor;
 use arroyo_rpc::config::config;
+use arroyo_rpc::formats::TimestampField;
 use arroyo_rpc::grpc::{
     api,
-    rpc::{CheckpointMetadata, TaskAssignment},
+    rpc::{CheckpointMetadata, TableConfig, TaskAssignment},
 };
 use arroyo_rpc::{ControlMessage, ControlResp};
 use arroyo_state::{BackingStore, StateBackend};
-use arroyo_types::{range_for_server, Key, TaskInfo, WorkerId};
+use arroyo_types::{from_nanos, range_for_server, Key, TaskInfo, WorkerId};
 use arroyo_udf_host::LocalUdf;
 use petgraph::graph::{DiGraph, NodeIndex};
 use petgraph::visit::EdgeRef;
 use petgraph::Direction;
 use tokio::sync::mpsc::{channel, Receiver, Sender};
 use tokio::sync::Barrier;
+use tokio::task::{AbortHandle, JoinHandle};
 
 #[derive(Encode, Decode, Clone, Debug, PartialEq, Eq)]
 pub struct TimerValue<K: Key, T: Decode + Encode + Clone + PartialEq + Eq> {
@@ -61,6 +70,11 @@ pub struct SubtaskNode {
     pub out_schema: Option<ArroyoSchema>,
     pub projection: Option<Vec<usize>>,
     pub node: OperatorNode,
+    pub state_storage: api::StateStorage,
+    pub timestamp_field: Option<TimestampField>,
+    pub source_bounds: (Option<SystemTime>, Option<SystemTime>),
+    pub checkpoint_alignment_timeout: Option<Duration>,
+    pub max_state_bytes: Option<u64>,
 }
 
 impl Debug for SubtaskNode {
@@ -198,25 +212,68 @@ impl Program {
         for udf in udfs {
             registry.add_local_udf(udf);
         }
-        Self::from_logical(name, logical, &assignments, registry)
+        Self::from_logical(
+            name,
+            logical,
+            &ProgramConfig::default(),
+            &assignments,
+            Arc::new(registry),
+        )
     }
 
     pub fn from_logical(
         name: String,
         logical: &LogicalGraph,
+        program_config: &ProgramConfig,
+        assignments: &Vec<TaskAssignment>,
+        registry: Arc<Registry>,
+    ) -> Program {
+        Self::build(name, logical, program_config, assignments, registry, None)
+    }
+
+    /// Builds the tasks of a region of the logical graph (see [LogicalProgram::region]), to
+    /// restart them on their own
+    ///
+    /// [LogicalProgram::region]: arroyo_datastream::logical::LogicalProgram::region
+    pub fn region_from_logical(
+        name: String,
+        logical: &LogicalGraph,
+        program_config: &ProgramConfig,
+        assignments: &Vec<TaskAssignment>,
+        registry: Arc<Registry>,
+        operators: &HashSet<String>,
+    ) -> Program {
+        Self::build(
+            name,
+            logical,
+            program_config,
+            assignments,
+            registry,
+            Some(operators),
+        )
+    }
+
+    fn build(
+        name: String,
+        logical: &LogicalGraph,
+        program_config: &ProgramConfig,
         assignments: &Vec<TaskAssignment>,
-        registry: Registry,
+        registry: Arc<Registry>,
+        operators: Option<&HashSet<String>>,
     ) -> Program {
         let mut physical = DiGraph::new();
 
-        let registry = Arc::new(registry);
+        // the physical graph keeps the indices of the full logical graph, which identify the
+        // data connections between workers
+        let included =
+            |idx: NodeIndex| operators.map_or(true, |ops| ops.contains(&logical[idx].operator_id));
 
         let mut parallelism_map = HashMap::new();
         for task in assignments {
             *(parallelism_map.entry(&task.operator_id).or_insert(0usize)) += 1;
         }
 
-        for idx in logical.node_indices() {
+        for idx in logical.node_indices().filter(|idx| included(*idx)) {
             let in_schemas: Vec<_> = logical
                 .edges_directed(idx, Direction::Incoming)
                 .map(|edge| edge.weight().schema.clone())
@@ -238,6 +295,11 @@ impl Program {
                 warn!("no assignments for operator {}", node.operator_id);
                 &node.parallelism
             });
+            let state_storage = program_config
+                .state_storage
+                .get(&node.operator_id)
+                .copied()
+                .unwrap_or_default();
             for i in 0..parallelism {
                 physical.add_node(SubtaskOrQueueNode::SubtaskNode(SubtaskNode {
                     id: node.operator_id.clone(),
@@ -251,15 +313,29 @@ impl Program {
                         registry.clone(),
                     ),
                     projection: projection.clone(),
+                    state_storage,
+                    timestamp_field: timestamp_field(node.operator_name, &node.operator_config),
+                    source_bounds: source_bounds(node.operator_name, &node.operator_config),
+                    checkpoint_alignment_timeout: program_config.checkpoint_alignment_timeout,
+                    max_state_bytes: program_config.max_state_bytes,
                 }));
             }
         }
 
-        let queue_size = config().worker.queue_size;
+        // the pipeline's `SET queue.*` overrides take precedence over the worker's config
+        let queue_size = program_config
+            .queue_size
+            .unwrap_or(config().worker.queue_size);
+        let queue_max_bytes = program_config
+            .queue_max_bytes
+            .unwrap_or(config().worker.queue_max_bytes);
 
         for idx in logical.edge_indices() {
             let edge = logical.edge_weight(idx).unwrap();
             let (logical_in_node_idx, logical_out_node_idx) = logical.edge_endpoints(idx).unwrap();
+            if !included(logical_in_node_idx) || !included(logical_out_node_idx) {
+                continue;
+            }
             let logical_in_node = logical.node_weight(logical_in_node_idx).unwrap();
             let logical_out_node = logical.node_weight(logical_out_node_idx).unwrap();
 
@@ -280,7 +356,7 @@ impl Program {
                         panic!("cannot create a forward connection between nodes of different parallelism");
                     }
                     for (f, t) in from_nodes.iter().zip(&to_nodes) {
-                        let (tx, rx) = batch_bounded(queue_size);
+                        let (tx, rx) = batch_bounded_with_max_bytes(queue_size, queue_max_bytes);
                         let edge = PhysicalGraphEdge {
                             edge_idx: 0,
                             in_logical_idx: logical_in_node_idx.index(),
@@ -298,7 +374,8 @@ impl Program {
                 | LogicalEdgeType::RightJoin => {
                     for f in &from_nodes {
                         for (idx, t) in to_nodes.iter().enumerate() {
-                            let (tx, rx) = batch_bounded(queue_size);
+                            let (tx, rx) =
+                                batch_bounded_with_max_bytes(queue_size, queue_max_bytes);
                             let edge = PhysicalGraphEdge {
                                 edge_idx: idx,
                                 in_logical_idx: logical_in_node_idx.index(),
@@ -343,15 +420,60 @@ pub struct Engine {
 
 pub struct StreamConfig {
     pub restore_epoch: Option<u32>,
+    /// source operator id -> partition -> offset to start reading the partition from
+    pub source_offset_overrides: HashMap<String, HashMap<String, String>>,
 }
 
 pub struct RunningEngine {
     program: Program,
     assignments: HashMap<(String, usize), TaskAssignment>,
     worker_id: WorkerId,
+    tasks: HashMap<(String, usize), TaskHandle>,
+}
+
+/// A handle on a task running on this worker, used to stop it when its region is restarted or
+/// to wait for it to stop when the worker is drained
+pub struct TaskHandle {
+    abort: AbortHandle,
+    finished: JoinHandle<()>,
+}
+
+impl TaskHandle {
+    /// Aborts the task, returning once it has stopped
+    pub async fn abort(self) {
+        self.abort.abort();
+        let _ = self.finished.await;
+    }
+
+    /// Returns once the task has stopped
+    pub async fn stopped(self) {
+        let _ = self.finished.await;
+    }
+
+    /// Waits up to `timeout` for the task to stop on its own, aborting it if it doesn't
+    pub async fn stop(mut self, timeout: Duration) {
+        if tokio::time::timeout(timeout, &mut self.finished)
+            .await
+            .is_err()
+        {
+            self.abort().await;
+        }
+    }
 }
 
 impl RunningEngine {
+    /// Takes the handles of the tasks running on this worker, by operator id and subtask index
+    pub fn take_tasks(&mut self) -> HashMap<(String, usize), TaskHandle> {
+        mem::take(&mut self.tasks)
+    }
+
+    pub fn local_task_count(&self) -> usize {
+        self.assignments
+            .values()
+            .filter(|a| a.worker_id == self.worker_id.0)
+            .count()
+    }
+
     pub fn source_controls(&self) -> Vec<Sender<ControlMessage>> {
         let graph = self.program.graph.read().unwrap();
         graph
@@ -498,6 +620,7 @@ impl Engine {
         let worker_id = self.worker_id;
 
         let mut senders = Senders::new();
+        let mut tasks = HashMap::new();
 
         let ready = Arc::new(Barrier::new(self.local_task_count()));
         {
@@ -506,14 +629,16 @@ impl Engine {
             for idx in node_indexes {
                 futures.push(self.schedule_node(
                     &checkpoint_metadata,
+                    &config.source_offset_overrides,
                     &control_tx,
                     idx,
                     ready.clone(),
                 ));
             }
 
-            while let Some(result) = futures.next().await {
+            while let Some((result, task)) = futures.next().await {
                 senders.merge(result);
+                tasks.extend(task);
             }
         }
 
@@ -529,6 +654,7 @@ impl Engine {
                 program: self.program,
                 assignments: self.assignments,
                 worker_id,
+                tasks,
             },
             control_rx,
         )
@@ -537,10 +663,11 @@ impl Engine {
     async fn schedule_node(
         &self,
         checkpoint_metadata: &Option<CheckpointMetadata>,
+        source_offset_overrides: &HashMap<String, HashMap<String, String>>,
         control_tx: &Sender<ControlResp>,
         idx: NodeIndex,
         ready: Arc<Barrier>,
-    ) -> Senders {
+    ) -> (Senders, Option<((String, usize), TaskHandle)>) {
         let (node, control_rx) = self
             .program
             .graph
@@ -563,17 +690,22 @@ impl Engine {
             });
 
         let mut senders = Senders::new();
+        let mut task = None;
 
         if assignment.worker_id == self.worker_id.0 {
-            self.run_locally(
-                checkpoint_metadata,
-                control_tx,
-                idx,
-                node,
-                control_rx,
-                ready,
-            )
-            .await;
+            let key = (node.id.clone(), node.subtask_idx);
+            let handle = self
+                .run_locally(
+                    checkpoint_metadata,
+                    source_offset_overrides,
+                    control_tx,
+                    idx,
+                    node,
+                    control_rx,
+                    ready,
+                )
+                .await;
+            task = Some((key, handle));
         } else {
             self.connect_to_remote_task(
                 &mut senders,
@@ -585,7 +717,7 @@ impl Engine {
             .await;
         }
 
-        senders
+        (senders, task)
     }
 
     async fn connect_to_remote_task(
@@ -654,15 +786,17 @@ impl Engine {
         );
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub async fn run_locally(
         &self,
         checkpoint_metadata: &Option<CheckpointMetadata>,
+        source_offset_overrides: &HashMap<String, HashMap<String, String>>,
         control_tx: &Sender<ControlResp>,
         idx: NodeIndex,
         node: SubtaskNode,
         control_rx: Receiver<ControlMessage>,
         ready: Arc<Barrier>,
-    ) {
+    ) -> TaskHandle {
         info!(
             "[{:?}] Scheduling {}-{}-{} ({}/{})",
             self.worker_id,
@@ -714,7 +848,7 @@ impl Engine {
         let tables = node.node.tables();
         let in_qs: Vec<_> = in_qs_map.into_values().flatten().collect();
 
-        let ctx = ArrowContext::new(
+        let mut ctx = ArrowContext::new(
             task_info,
             checkpoint_metadata.clone(),
             control_rx,
@@ -731,63 +865,174 @@ impl Engine {
         )
         .await;
 
+        ctx.table_manager.set_state_storage(node.state_storage);
+        ctx.table_manager.set_max_memory_bytes(node.max_state_bytes);
+
+        if let Some(overrides) = source_offset_overrides.get(&operator_id) {
+            ctx.source_offset_overrides = overrides.clone();
+        }
+        ctx.timestamp_field = node.timestamp_field;
+        (ctx.source_start_at, ctx.source_end_at) = node.source_bounds;
+        ctx.checkpoint_alignment_timeout = node.checkpoint_alignment_timeout;
+
+        // sources are throttled while downstream queues stay backpressured, so that they read
+        // at the pace the pipeline can process instead of filling every queue before blocking
+        let backpressure = &config().worker.backpressure;
+        if backpressure.throttle_sources && matches!(node.node, OperatorNode::Source(_)) {
+            ctx.collector.set_source_throttle(SourceThrottle {
+                after: *backpressure.throttle_after,
+                delay: *backpressure.throttle_delay,
+            });
+        }
+
         let operator = Box::new(node.node);
         let join_task = tokio::spawn(async move {
             operator.start(ctx, in_qs, ready).await;
         });
 
+        let abort = join_task.abort_handle();
         let send_copy = control_tx.clone();
-        tokio::spawn(async move {
+        let finished = tokio::spawn(async move {
             if let Err(error) = join_task.await {
+                // tasks are aborted when their region is restarted, which isn't a failure
+                if error.is_cancelled() {
+                    return;
+                }
                 send_copy
                     .send(ControlResp::TaskFailed {
                         operator_id,
                         task_index,
                         error: error.to_string(),
+                        fatal: false,
                     })
                     .await
                     .ok();
             };
         });
+
+        TaskHandle { abort, finished }
     }
 }
 
+/// Creates a registry containing the UDFs the program uses
+pub async fn load_registry(program_config: &ProgramConfig) -> anyhow::Result<Registry> {
+    let mut registry = new_registry();
+
+    for (udf_name, dylib_config) in &program_config.udf_dylibs {
+        info!("Loading UDF {}", udf_name);
+        registry
+            .load_dylib(udf_name, dylib_config)
+            .await
+            .map_err(|e| e.context(format!("loading UDF {udf_name}")))?;
+    }
+
+    for (udf_name, python_udf) in &program_config.python_udfs {
+        info!("Loading Python UDF {}", udf_name);
+        registry
+            .add_python_udf(python_udf)
+            .await
+            .map_err(|e| e.context(format!("loading Python UDF {udf_name}")))?;
+    }
+
+    Ok(registry)
+}
+
+/// The tables each operator of the program keeps its state in, by operator id; used to check
+/// that a checkpoint can be restored before the program is started
+pub async fn operator_tables(
+    program: &LogicalProgram,
+) -> anyhow::Result<HashMap<String, HashMap<String, TableConfig>>> {
+    let registry = Arc::new(load_registry(&program.program_config).await?);
+
+    program
+        .graph
+        .node_weights()
+        .map(|node| {
+            let operator = try_construct_operator(
+                node.operator_name,
+                node.operator_config.clone(),
+                registry.clone(),
+            )
+            .map_err(|e| e.context(format!("constructing operator {}", node.operator_id)))?;
+            Ok((node.operator_id.clone(), operator.tables()))
+        })
+        .collect()
+}
+
 pub fn construct_operator(
     operator: OperatorName,
     config: Vec<u8>,
     registry: Arc<Registry>,
 ) -> OperatorNode {
+    try_construct_operator(operator, config, registry).unwrap_or_else(|e| {
+        panic!(
+            "Failed to construct operator {:?}, with error:\n{:?}",
+            operator, e
+        )
+    })
+}
+
+fn try_construct_operator(
+    operator: OperatorName,
+    config: Vec<u8>,
+    registry: Arc<Registry>,
+) -> anyhow::Result<OperatorNode> {
     let ctor: Box<dyn ErasedConstructor> = match operator {
         OperatorName::ArrowValue => Box::new(ValueExecutionConstructor),
         OperatorName::ArrowKey => Box::new(KeyExecutionConstructor),
         OperatorName::AsyncUdf => Box::new(AsyncUdfConstructor),
+        OperatorName::Delay => Box::new(DelayConstructor),
+        OperatorName::Dedupe => Box::new(DedupeConstructor),
         OperatorName::TumblingWindowAggregate => Box::new(TumblingAggregateWindowConstructor),
         OperatorName::SlidingWindowAggregate => Box::new(SlidingAggregatingWindowConstructor),
         OperatorName::SessionWindowAggregate => Box::new(SessionAggregatingWindowConstructor),
+        OperatorName::SessionEvents => Box::new(SessionEventsConstructor),
         OperatorName::UpdatingAggregate => Box::new(UpdatingAggregatingConstructor),
         OperatorName::ExpressionWatermark => Box::new(WatermarkGeneratorConstructor),
         OperatorName::Join => Box::new(JoinWithExpirationConstructor),
         OperatorName::InstantJoin => Box::new(InstantJoinConstructor),
         OperatorName::WindowFunction => Box::new(WindowFunctionConstructor),
         OperatorName::ConnectorSource | OperatorName::ConnectorSink => {
-            let op: api::ConnectorOp = prost::Message::decode(&mut config.as_slice()).unwrap();
+            let op: api::ConnectorOp = prost::Message::decode(&mut config.as_slice())?;
             return connectors()
                 .get(op.connector.as_str())
-                .unwrap_or_else(|| panic!("No connector with name '{}'", op.connector))
+                .ok_or_else(|| anyhow!("No connector with name '{}'", op.connector))?
                 .make_operator(
                     serde_json::from_str(&op.config)
-                        .unwrap_or_else(|e| panic!("invalid operator config: {:?}, {:?}", op, e)),
+                        .map_err(|e| anyhow!("invalid operator config: {:?}, {:?}", op, e))?,
                 )
-                .unwrap_or_else(|e| {
-                    panic!("Failed to construct connector {}: {:?}", op.connector, e)
-                });
+                .map_err(|e| anyhow!("Failed to construct connector {}: {:?}", op.connector, e));
         }
     };
 
-    ctor.with_config(config, registry).unwrap_or_else(|e| {
-        panic!(
-            "Failed to construct operator {:?}, with error:\n{:?}",
-            operator, e
-        )
+    ctor.with_config(config, registry)
+}
+
+/// The field that a source takes the event times of its rows from, if it's configured with one
+fn timestamp_field(operator: OperatorName, config: &[u8]) -> Option<TimestampField> {
+    if operator != OperatorName::ConnectorSource {
+        return None;
+    }
+
+    let op: api::ConnectorOp = prost::Message::decode(config).unwrap();
+    op.timestamp_field.map(|field| {
+        serde_json::from_str(&field)
+            .unwrap_or_else(|e| panic!("invalid timestamp field: {:?}, {:?}", field, e))
     })
 }
+
+/// The times that a source starts and finishes reading at, if it's part of a hybrid table
+fn source_bounds(
+    operator: OperatorName,
+    config: &[u8],
+) -> (Option<SystemTime>, Option<SystemTime>) {
+    if operator != OperatorName::ConnectorSource {
+        return (None, None);
+    }
+
+    let op: api::ConnectorOp = prost::Message::decode(config).unwrap();
+    (
+        op.start_at.map(|t| from_nanos(t as u128)),
+        op.end_at.map(|t| from_nanos(t as u128)),
+    )
+}
@@ -1,18 +1,22 @@
 // TODO: factor out complex types
 #![allow(clippy::type_complexity)]
 
-use crate::engine::{Engine, Program, StreamConfig, SubtaskNode};
+use crate::engine::{load_registry, Engine, Program, StreamConfig, SubtaskNode, TaskHandle};
 use crate::network_manager::NetworkManager;
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 
 use arroyo_rpc::grpc::rpc::controller_grpc_client::ControllerGrpcClient;
+use arroyo_rpc::grpc::rpc::task_checkpoint_message;
 use arroyo_rpc::grpc::rpc::worker_grpc_server::{WorkerGrpc, WorkerGrpcServer};
 use arroyo_rpc::grpc::rpc::{
     CheckpointReq, CheckpointResp, CommitReq, CommitResp, HeartbeatReq, JobFinishedReq,
     JobFinishedResp, LoadCompactedDataReq, LoadCompactedDataRes, MetricFamily, MetricsReq,
-    MetricsResp, RegisterWorkerReq, StartExecutionReq, StartExecutionResp, StopExecutionReq,
-    StopExecutionResp, TaskCheckpointCompletedReq, TaskCheckpointEventReq, TaskFailedReq,
-    TaskFinishedReq, TaskStartedReq, WorkerErrorReq, WorkerResources,
+    MetricsResp, QueryStateReq, QueryStateResp, RegisterWorkerReq, ResetExecutionReq,
+    ResetExecutionResp, RestartRegionReq, RestartRegionResp, SourceLagReq, StartExecutionReq,
+    StartExecutionResp, StopExecutionReq, StopExecutionResp, StopMode, TaskAssignment,
+    TaskCheckpointCompletedReq, TaskCheckpointEventReq, TaskCheckpointMessage,
+    TaskCheckpointMessagesReq, TaskFailedReq, TaskFinishedReq, TaskStartedReq, WorkerErrorReq,
+    WorkerResources, WorkerShuttingDownReq,
 };
 use arroyo_types::{
     from_millis, to_micros, CheckpointBarrier, NodeId, WorkerId, JOB_ID_ENV, RUN_ID_ENV,
@@ -24,28 +28,35 @@ use std::collections::{HashMap, HashSet};
 use std::fmt::{Debug, Display, Formatter};
 use std::future::Future;
 use std::net::SocketAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
 use std::time::{Duration, SystemTime};
 use tokio::net::TcpListener;
 use tokio::select;
-use tokio::sync::mpsc::{Receiver, Sender};
+use tokio::sync::mpsc::{channel, Receiver, Sender};
+use tokio::sync::watch;
 use tokio_stream::wrappers::TcpListenerStream;
+use tonic::codec::CompressionEncoding;
+use tonic::transport::Channel;
 use tonic::{Request, Response, Status};
 use tracing::{debug, error, info, warn};
 
-use arroyo_rpc::{retry, CompactionResult, ControlMessage, ControlResp};
+use arroyo_rpc::{retry, CompactionResult, ControlMessage, ControlResp, StateQuery};
+use async_trait::async_trait;
 pub use ordered_float::OrderedFloat;
 use prometheus::{Encoder, ProtobufEncoder};
 use prost::Message;
 
 use crate::utils::to_d2;
 use arroyo_datastream::logical::LogicalProgram;
-use arroyo_df::physical::new_registry;
+use arroyo_operator::operator::Registry;
 use arroyo_rpc::config::config;
-use arroyo_server_common::shutdown::ShutdownGuard;
+use arroyo_rpc::protocol::{negotiate_protocol_version, MIN_PROTOCOL_VERSION, PROTOCOL_VERSION};
+use arroyo_server_common::shutdown::{ShutdownGuard, ShutdownHandler};
 use arroyo_server_common::wrap_start;
 
 pub mod arrow;
+mod chaos;
 
 pub mod engine;
 mod network_manager;
@@ -94,9 +105,19 @@ struct EngineState {
     sources: Vec<Sender<ControlMessage>>,
     sinks: Vec<Sender<ControlMessage>>,
     operator_controls: HashMap<String, Vec<Sender<ControlMessage>>>, // operator_id -> vec of control tx
+    tasks: HashMap<(String, usize), TaskHandle>,
+    execution: Arc<Execution>,
     shutdown_guard: ShutdownGuard,
 }
 
+/// What a worker needs to restart the tasks of a region of the execution it's running
+struct Execution {
+    logical: LogicalProgram,
+    registry: Arc<Registry>,
+    assignments: Vec<TaskAssignment>,
+    network: NetworkManager,
+}
+
 pub struct LocalRunner {
     program: Program,
 }
@@ -113,6 +134,7 @@ impl LocalRunner {
         let (_running_engine, mut control_rx) = engine
             .start(StreamConfig {
                 restore_epoch: None,
+                source_offset_overrides: HashMap::new(),
             })
             .await;
 
@@ -144,9 +166,26 @@ pub struct WorkerServer {
     controller_addr: String,
     state: Arc<Mutex<Option<EngineState>>>,
     network: Arc<Mutex<Option<NetworkManager>>>,
+    // relays control messages to the control thread, which is started with the first execution
+    // and outlives it if the worker is reset to run another
+    control_tx: Arc<Mutex<Option<Sender<ControlResp>>>>,
+    // the number of tasks of the current execution that are running on this worker
+    local_tasks: Arc<AtomicUsize>,
+    // the generation of each operator whose region has been restarted in the current execution
+    region_generations: Arc<Mutex<HashMap<String, u32>>>,
+    // the most recent epoch for which all of the tasks on this worker have checkpointed
+    checkpointed_epoch: Arc<watch::Sender<u32>>,
+    // set once the worker has been drained, whether on SIGTERM or an interruption notice
+    drained: Arc<tokio::sync::OnceCell<()>>,
     shutdown_guard: ShutdownGuard,
 }
 
+// how often the worker polls for an interruption notice, if configured to
+const INTERRUPTION_POLL_INTERVAL: Duration = Duration::from_secs(5);
+/// How long the tasks of a restarted region are given to abort their pending writes before
+/// they're aborted themselves
+const REGION_STOP_TIMEOUT: Duration = Duration::from_secs(10);
+
 impl WorkerServer {
     pub fn from_config(shutdown_guard: ShutdownGuard) -> Result<Self> {
         let id = WorkerId(config().worker.id.unwrap_or_else(random));
@@ -182,10 +221,59 @@ impl WorkerServer {
             controller_addr,
             state: Arc::new(Mutex::new(None)),
             network: Arc::new(Mutex::new(None)),
+            control_tx: Arc::new(Mutex::new(None)),
+            local_tasks: Arc::new(AtomicUsize::new(0)),
+            region_generations: Arc::new(Mutex::new(HashMap::new())),
+            checkpointed_epoch: Arc::new(watch::channel(0).0),
+            drained: Arc::new(tokio::sync::OnceCell::new()),
             shutdown_guard,
         }
     }
 
+    /// Returns a handler that, when the worker is asked to shut down, drains it: the controller
+    /// moves the job off of this worker with a final checkpoint, and the handler waits for this
+    /// worker's tasks to complete it and stop
+    pub fn shutdown_handler(&self) -> WorkerShutdownHandler {
+        WorkerShutdownHandler {
+            worker_id: self.id,
+            job_id: self.job_id.clone(),
+            controller_addr: self.controller_addr.clone(),
+            state: self.state.clone(),
+            checkpointed_epoch: self.checkpointed_epoch.subscribe(),
+            drained: self.drained.clone(),
+        }
+    }
+
+    /// Polls for notice that the worker's instance is about to be reclaimed, draining the worker
+    /// and shutting it down once there is one
+    fn watch_for_interruption(&self, url: String) {
+        let handler = self.shutdown_handler();
+        let guard = self.shutdown_guard.clone_temporary();
+
+        self.shutdown_guard.spawn_temporary(async move {
+            let client = reqwest::Client::new();
+            let mut interval = tokio::time::interval(INTERRUPTION_POLL_INTERVAL);
+            loop {
+                interval.tick().await;
+                match client.get(&url).send().await {
+                    Ok(resp) if resp.status().is_success() => {
+                        warn!(
+                            message = "Received interruption notice, draining worker",
+                            notice = resp.text().await.unwrap_or_default()
+                        );
+                        handler.drain().await;
+                        guard.cancel();
+                        return anyhow::Ok(());
+                    }
+                    Ok(_) => {}
+                    Err(e) => {
+                        debug!("Failed to poll for interruption notice: {:?}", e);
+                    }
+                }
+            }
+        });
+    }
+
     pub fn id(&self) -> WorkerId {
         self.id
     }
@@ -221,6 +309,10 @@ impl WorkerServer {
 
         *self.network.lock().unwrap() = Some(network);
 
+        if let Some(url) = config.worker.interruption_notice_url.clone() {
+            self.watch_for_interruption(url);
+        }
+
         info!(
             "Started worker data for {} on 0.0.0.0:{}",
             self.name, data_port
@@ -246,7 +338,7 @@ impl WorkerServer {
         // ideally, get a signal when the server is started...
         tokio::time::sleep(Duration::from_millis(50)).await;
 
-        client
+        let resp = client
             .register_worker(Request::new(RegisterWorkerReq {
                 worker_id: id.0,
                 node_id: node_id.0,
@@ -257,9 +349,19 @@ impl WorkerServer {
                     slots: std::thread::available_parallelism().unwrap().get() as u64,
                 }),
                 slots: config.worker.task_slots as u64,
+                protocol_version: PROTOCOL_VERSION,
+                min_protocol_version: MIN_PROTOCOL_VERSION,
             }))
             .await
-            .unwrap();
+            .map_err(|e| anyhow!("failed to register with controller: {}", e.message()))?
+            .into_inner();
+
+        // older controllers don't negotiate a version, so make sure we can speak theirs
+        let protocol_version =
+            negotiate_protocol_version(resp.protocol_version, resp.protocol_version)?;
+        info!(message = "registered with controller", protocol_version);
+
+        chaos::start_worker_kills();
 
         Ok(())
     }
@@ -269,29 +371,84 @@ impl WorkerServer {
         self.start_async().await
     }
 
+    /// Relays the control messages of an engine's tasks to the control thread, dropping those
+    /// from tasks whose region has since been restarted
+    fn relay_control_messages(&self, mut control_rx: Receiver<ControlResp>, generation: u32) {
+        let control_tx = self
+            .control_tx
+            .lock()
+            .unwrap()
+            .clone()
+            .expect("control thread should have been started");
+        let generations = self.region_generations.clone();
+
+        self.shutdown_guard.spawn_temporary(async move {
+            while let Some(msg) = control_rx.recv().await {
+                let current = generations
+                    .lock()
+                    .unwrap()
+                    .get(msg.operator_id())
+                    .copied()
+                    .unwrap_or(0);
+                if current != generation {
+                    continue;
+                }
+
+                if control_tx.send(msg).await.is_err() {
+                    break;
+                }
+            }
+            anyhow::Ok(())
+        });
+    }
+
     fn start_control_thread(
         &self,
         mut control_rx: Receiver<ControlResp>,
         worker_id: WorkerId,
         job_id: String,
+        local_tasks: Arc<AtomicUsize>,
+        protocol_version: u32,
     ) -> impl Future<Output = Result<()>> {
         let addr = self.controller_addr.clone();
+        let checkpointed_epoch = self.checkpointed_epoch.clone();
+        let mut completed_tasks: HashMap<u32, usize> = HashMap::new();
 
         let cancel_token = self.shutdown_guard.token();
 
+        // controllers that speak version 3 accept compressed requests and batches of
+        // checkpoint messages
+        let batching = protocol_version >= 3;
+        let batch_interval = *config().worker.control_batch_interval;
+        let batch_size = config().worker.control_batch_size.max(1);
+
         async move {
             let mut controller = ControllerGrpcClient::connect(addr.clone())
                 .await
                 .expect("Unable to connect to controller");
+            if batching {
+                controller = controller.send_compressed(CompressionEncoding::Gzip);
+            }
+            let mut batch = CheckpointMessageBatch::default();
             let mut tick = tokio::time::interval(Duration::from_secs(5));
             tick.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+            let mut flush = tokio::time::interval(batch_interval);
+            flush.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
             loop {
                 select! {
                     msg = control_rx.recv() => {
-                        let err = match msg {
-                            Some(ControlResp::CheckpointEvent(c)) => {
-                                controller.task_checkpoint_event(Request::new(
-                                    TaskCheckpointEventReq {
+                        // other messages may depend on the checkpoint messages before them (for
+                        // example, a task finishing after its final checkpoint), so any that are
+                        // buffered are sent first
+                        let mut err = None;
+                        if !matches!(msg, Some(ControlResp::CheckpointEvent(_) | ControlResp::CheckpointCompleted(_))) {
+                            err = batch.flush(&mut controller, worker_id, &job_id).await.err();
+                        }
+
+                        if err.is_none() {
+                            err = match msg {
+                                Some(ControlResp::CheckpointEvent(c)) => {
+                                    let req = TaskCheckpointEventReq {
                                         worker_id: worker_id.0,
                                         time: to_micros(c.time),
                                         job_id: job_id.clone(),
@@ -299,12 +456,30 @@ impl WorkerServer {
                                         subtask_index: c.subtask_index,
                                         epoch: c.checkpoint_epoch,
                                         event_type: c.event_type as i32,
+                                    };
+
+                                    if batching {
+                                        batch.push(task_checkpoint_message::Message::Event(req));
+                                        if batch.len() >= batch_size {
+                                            batch.flush(&mut controller, worker_id, &job_id).await.err()
+                                        } else {
+                                            None
+                                        }
+                                    } else {
+                                        controller.task_checkpoint_event(Request::new(req)).await.err()
+                                    }
+                                }
+                                Some(ControlResp::CheckpointCompleted(c)) => {
+                                    let epoch = c.checkpoint_epoch;
+                                    let completed = completed_tasks.entry(epoch).or_default();
+                                    *completed += 1;
+                                    let epoch_completed = *completed == local_tasks.load(Ordering::SeqCst);
+                                    if epoch_completed {
+                                        completed_tasks.retain(|e, _| *e > epoch);
+                                        checkpointed_epoch.send_replace(epoch);
                                     }
-                                )).await.err()
-                            }
-                            Some(ControlResp::CheckpointCompleted(c)) => {
-                                controller.task_checkpoint_completed(Request::new(
-                                    TaskCheckpointCompletedReq {
+
+                                    let req = TaskCheckpointCompletedReq {
                                         worker_id: worker_id.0,
                                         time: c.subtask_metadata.finish_time,
                                         job_id: job_id.clone(),
@@ -312,66 +487,104 @@ impl WorkerServer {
                                         epoch: c.checkpoint_epoch,
                                         needs_commit: false,
                                         metadata: Some(c.subtask_metadata),
+                                    };
+
+                                    if batching {
+                                        batch.push(task_checkpoint_message::Message::Completed(req));
+                                        // once all of our tasks have finished the checkpoint there's
+                                        // nothing more to wait for, and a worker that's shutting down
+                                        // may exit as soon as it sees the completed epoch
+                                        if batch.len() >= batch_size || epoch_completed {
+                                            batch.flush(&mut controller, worker_id, &job_id).await.err()
+                                        } else {
+                                            None
+                                        }
+                                    } else {
+                                        controller.task_checkpoint_completed(Request::new(req)).await.err()
                                     }
-                                )).await.err()
-                            }
-                            Some(ControlResp::TaskFinished { operator_id, task_index }) => {
-                                info!(message = "Task finished", operator_id, task_index);
-                                controller.task_finished(Request::new(
-                                    TaskFinishedReq {
-                                        worker_id: worker_id.0,
-                                        job_id: job_id.clone(),
-                                        time: to_micros(SystemTime::now()),
-                                        operator_id: operator_id.to_string(),
-                                        operator_subtask: task_index as u64,
-                                    }
-                                )).await.err()
-                            }
-                            Some(ControlResp::TaskFailed { operator_id, task_index, error }) => {
-                                controller.task_failed(Request::new(
-                                    TaskFailedReq {
-                                        worker_id: worker_id.0,
-                                        job_id: job_id.clone(),
-                                        time: to_micros(SystemTime::now()),
-                                        operator_id: operator_id.to_string(),
-                                        operator_subtask: task_index as u64,
-                                        error,
-                                    }
-                                )).await.err()
-                            }
-                            Some(ControlResp::Error { operator_id, task_index, message, details}) => {
-                                controller.worker_error(Request::new(
-                                    WorkerErrorReq {
-                                        job_id: job_id.clone(),
-                                        operator_id,
-                                        task_index: task_index as u32,
-                                        message,
-                                        details
-                                    }
-                                )).await.err()
-                            }
-                            Some(ControlResp::TaskStarted {operator_id, task_index, start_time}) => {
-                                controller.task_started(Request::new(
-                                    TaskStartedReq {
-                                        worker_id: worker_id.0,
-                                        job_id: job_id.clone(),
-                                        time: to_micros(start_time),
-                                        operator_id: operator_id.to_string(),
-                                        operator_subtask: task_index as u64,
+                                }
+                                Some(ControlResp::TaskFinished { operator_id, task_index }) => {
+                                    info!(message = "Task finished", operator_id, task_index);
+                                    controller.task_finished(Request::new(
+                                        TaskFinishedReq {
+                                            worker_id: worker_id.0,
+                                            job_id: job_id.clone(),
+                                            time: to_micros(SystemTime::now()),
+                                            operator_id: operator_id.to_string(),
+                                            operator_subtask: task_index as u64,
+                                        }
+                                    )).await.err()
+                                }
+                                Some(ControlResp::TaskFailed { operator_id, task_index, error, fatal }) => {
+                                    controller.task_failed(Request::new(
+                                        TaskFailedReq {
+                                            worker_id: worker_id.0,
+                                            job_id: job_id.clone(),
+                                            time: to_micros(SystemTime::now()),
+                                            operator_id: operator_id.to_string(),
+                                            operator_subtask: task_index as u64,
+                                            error,
+                                            fatal,
+                                        }
+                                    )).await.err()
+                                }
+                                Some(ControlResp::Error { operator_id, task_index, message, details}) => {
+                                    controller.worker_error(Request::new(
+                                        WorkerErrorReq {
+                                            job_id: job_id.clone(),
+                                            operator_id,
+                                            task_index: task_index as u32,
+                                            message,
+                                            details
+                                        }
+                                    )).await.err()
+                                }
+                                Some(ControlResp::SourceLag { operator_id, task_index, lag, split_lag, unit }) => {
+                                    // lag reports are only used for rebalancing, so failing to deliver
+                                    // one shouldn't take down the worker
+                                    if let Err(e) = controller.source_lag(Request::new(
+                                        SourceLagReq {
+                                            job_id: job_id.clone(),
+                                            operator_id,
+                                            task_index: task_index as u32,
+                                            lag,
+                                            split_lag,
+                                            unit: unit as i32,
+                                        }
+                                    )).await {
+                                        warn!("failed to report source lag to controller: {:?}", e);
                                     }
-                                )).await.err()
-                            }
-                            None => {
-                                // TODO: remove the control queue from the select at this point
-                                tokio::time::sleep(Duration::from_millis(50)).await;
-                                None
-                            }
-                        };
+                                    None
+                                }
+                                Some(ControlResp::TaskStarted {operator_id, task_index, start_time}) => {
+                                    controller.task_started(Request::new(
+                                        TaskStartedReq {
+                                            worker_id: worker_id.0,
+                                            job_id: job_id.clone(),
+                                            time: to_micros(start_time),
+                                            operator_id: operator_id.to_string(),
+                                            operator_subtask: task_index as u64,
+                                        }
+                                    )).await.err()
+                                }
+                                None => {
+                                    // TODO: remove the control queue from the select at this point
+                                    tokio::time::sleep(Duration::from_millis(50)).await;
+                                    None
+                                }
+                            };
+                        }
                         if let Some(err) = err {
                             error!("encountered control message failure {}", err);
                             cancel_token.cancel();
                         }
                     }
+                    _ = flush.tick(), if !batch.is_empty() => {
+                        if let Err(err) = batch.flush(&mut controller, worker_id, &job_id).await {
+                            error!("encountered control message failure {}", err);
+                            cancel_token.cancel();
+                        }
+                    }
                     _ = tick.tick() => {
                         let result = controller.heartbeat(Request::new(HeartbeatReq {
                             job_id: job_id.clone(),
@@ -390,6 +603,165 @@ impl WorkerServer {
     }
 }
 
+/// Checkpoint events and completions waiting to be sent to the controller in a single request
+#[derive(Default)]
+struct CheckpointMessageBatch {
+    messages: Vec<TaskCheckpointMessage>,
+}
+
+impl CheckpointMessageBatch {
+    fn push(&mut self, message: task_checkpoint_message::Message) {
+        self.messages.push(TaskCheckpointMessage {
+            message: Some(message),
+        });
+    }
+
+    fn len(&self) -> usize {
+        self.messages.len()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.messages.is_empty()
+    }
+
+    async fn flush(
+        &mut self,
+        controller: &mut ControllerGrpcClient<Channel>,
+        worker_id: WorkerId,
+        job_id: &str,
+    ) -> Result<(), Status> {
+        if self.is_empty() {
+            return Ok(());
+        }
+
+        controller
+            .task_checkpoint_messages(Request::new(TaskCheckpointMessagesReq {
+                worker_id: worker_id.0,
+                job_id: job_id.to_string(),
+                messages: std::mem::take(&mut self.messages),
+            }))
+            .await?;
+
+        Ok(())
+    }
+}
+
+pub struct WorkerShutdownHandler {
+    worker_id: WorkerId,
+    job_id: String,
+    controller_addr: String,
+    state: Arc<Mutex<Option<EngineState>>>,
+    checkpointed_epoch: watch::Receiver<u32>,
+    drained: Arc<tokio::sync::OnceCell<()>>,
+}
+
+#[async_trait]
+impl ShutdownHandler for WorkerShutdownHandler {
+    async fn shutdown(&self) {
+        self.drain().await;
+    }
+}
+
+impl WorkerShutdownHandler {
+    /// Drains the worker; only the first call does so, while later ones wait for it to finish
+    pub async fn drain(&self) {
+        self.drained.get_or_init(|| self.drain_tasks()).await;
+    }
+
+    async fn drain_tasks(&self) {
+        if self.state.lock().unwrap().is_none() {
+            // nothing is running on this worker, so there's nothing to checkpoint
+            return;
+        }
+
+        let mut checkpointed_epoch = self.checkpointed_epoch.clone();
+        let start_epoch = *checkpointed_epoch.borrow_and_update();
+
+        info!(
+            message = "Requesting final checkpoint before shutting down",
+            job_id = self.job_id,
+            worker_id = self.worker_id.0
+        );
+
+        let result = async {
+            ControllerGrpcClient::connect(self.controller_addr.clone())
+                .await?
+                .worker_shutting_down(Request::new(WorkerShuttingDownReq {
+                    worker_id: self.worker_id.0,
+                    job_id: self.job_id.clone(),
+                    drain: true,
+                }))
+                .await?;
+            anyhow::Ok(())
+        }
+        .await;
+
+        if let Err(e) = result {
+            warn!(
+                "Failed to request final checkpoint from controller: {:?}",
+                e
+            );
+            return;
+        }
+
+        let timeout = *config().worker.shutdown_checkpoint_timeout;
+        let deadline = tokio::time::Instant::now() + timeout;
+        match tokio::time::timeout_at(
+            deadline,
+            checkpointed_epoch.wait_for(|epoch| *epoch > start_epoch),
+        )
+        .await
+        {
+            Ok(Ok(epoch)) => {
+                info!(
+                    message = "Final checkpoint completed",
+                    job_id = self.job_id,
+                    epoch = *epoch
+                );
+            }
+            Ok(Err(_)) => {
+                warn!("Worker stopped before final checkpoint completed");
+                return;
+            }
+            Err(_) => {
+                warn!(
+                    "Final checkpoint did not complete within {:?}, shutting down anyways",
+                    timeout
+                );
+                return;
+            }
+        }
+
+        // the final checkpoint stops the tasks, which may still be finishing up (e.g., sinks
+        // committing their writes)
+        let tasks: Vec<_> = self
+            .state
+            .lock()
+            .unwrap()
+            .as_mut()
+            .map(|state| {
+                state
+                    .tasks
+                    .drain()
+                    .map(|(_, task)| task.stopped())
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        if tokio::time::timeout_at(deadline, futures::future::join_all(tasks))
+            .await
+            .is_err()
+        {
+            warn!(
+                "Tasks did not stop within {:?}, shutting down anyways",
+                timeout
+            );
+        } else {
+            info!(message = "Worker drained", job_id = self.job_id);
+        }
+    }
+}
+
 #[tonic::async_trait]
 impl WorkerGrpc for WorkerServer {
     async fn start_execution(
@@ -407,7 +779,9 @@ impl WorkerGrpc for WorkerServer {
         }
 
         let req = request.into_inner();
-        let mut registry = new_registry();
+        let protocol_version =
+            negotiate_protocol_version(req.protocol_version, req.protocol_version)
+                .map_err(|e| Status::failed_precondition(e.to_string()))?;
 
         let logical = LogicalProgram::try_from(req.program.expect("Program is None"))
             .expect("Failed to create LogicalProgram");
@@ -416,55 +790,73 @@ impl WorkerGrpc for WorkerServer {
             debug!("Starting execution for graph\n{}", v);
         }
 
-        for (udf_name, dylib_config) in &logical.program_config.udf_dylibs {
-            info!("Loading UDF {}", udf_name);
-            registry
-                .load_dylib(udf_name, dylib_config)
-                .await
-                .map_err(|e| {
-                    Status::failed_precondition(
-                        e.context(format!("loading UDF {udf_name}")).to_string(),
-                    )
-                })?;
-            if dylib_config.is_async {
-                continue;
-            }
-        }
+        let registry = load_registry(&logical.program_config)
+            .await
+            .map_err(|e| Status::failed_precondition(e.to_string()))?;
+
+        let registry = Arc::new(registry);
+
+        arroyo_state::register_checkpoint_storage(
+            &self.job_id,
+            logical.program_config.checkpoint_storage.as_ref(),
+        )
+        .await
+        .map_err(|e| {
+            Status::failed_precondition(e.context("connecting to checkpoint storage").to_string())
+        })?;
 
-        for (udf_name, python_udf) in &logical.program_config.python_udfs {
-            info!("Loading Python UDF {}", udf_name);
-            registry.add_python_udf(python_udf).await.map_err(|e| {
-                Status::failed_precondition(
-                    e.context(format!("loading Python UDF {udf_name}"))
-                        .to_string(),
-                )
-            })?;
+        let mut source_offset_overrides: HashMap<String, HashMap<String, String>> = HashMap::new();
+        for o in req.source_offset_overrides {
+            source_offset_overrides
+                .entry(o.operator_id)
+                .or_default()
+                .insert(o.partition, o.offset);
         }
 
-        let (engine, control_rx) = {
-            let network = { self.network.lock().unwrap().take().unwrap() };
+        let mut network = { self.network.lock().unwrap().clone().unwrap() };
+        network.set_protocol_version(protocol_version);
 
-            let program =
-                Program::from_logical(self.name.to_string(), &logical.graph, &req.tasks, registry);
+        let (mut engine, control_rx) = {
+            let program = Program::from_logical(
+                self.name.to_string(),
+                &logical.graph,
+                &logical.program_config,
+                &req.tasks,
+                registry.clone(),
+            );
 
             let engine = Engine::new(
                 program,
                 self.id,
                 self.job_id.clone(),
                 self.run_id.clone(),
-                network,
-                req.tasks,
+                network.clone(),
+                req.tasks.clone(),
             );
             engine
                 .start(StreamConfig {
                     restore_epoch: req.restore_epoch,
+                    source_offset_overrides,
                 })
                 .await
         };
 
-        self.shutdown_guard
-            .child("control-thread")
-            .into_spawn_task(self.start_control_thread(control_rx, self.id, self.job_id.clone()));
+        self.local_tasks
+            .store(engine.local_task_count(), Ordering::SeqCst);
+        self.control_tx.lock().unwrap().get_or_insert_with(|| {
+            let (tx, rx) = channel(128);
+            self.shutdown_guard
+                .child("control-thread")
+                .into_spawn_task(self.start_control_thread(
+                    rx,
+                    self.id,
+                    self.job_id.clone(),
+                    self.local_tasks.clone(),
+                    protocol_version,
+                ));
+            tx
+        });
+        self.relay_control_messages(control_rx, 0);
 
         let sources = engine.source_controls();
         let sinks = engine.sink_controls();
@@ -475,7 +867,15 @@ impl WorkerGrpc for WorkerServer {
             sources,
             sinks,
             operator_controls,
-            shutdown_guard: self.shutdown_guard.child("engine-state"),
+            tasks: engine.take_tasks(),
+            execution: Arc::new(Execution {
+                logical,
+                registry,
+                assignments: req.tasks,
+                network,
+            }),
+            // the engine state is dropped when the worker is reset, which shouldn't shut it down
+            shutdown_guard: self.shutdown_guard.clone_temporary(),
         });
 
         info!("[{:?}] Started execution", self.id);
@@ -612,7 +1012,12 @@ impl WorkerGrpc for WorkerServer {
     ) -> Result<Response<StopExecutionResp>, Status> {
         let sources = {
             let state = self.state.lock().unwrap();
-            state.as_ref().unwrap().sources.clone()
+            let Some(state) = state.as_ref() else {
+                return Err(Status::failed_precondition(
+                    "Worker has not yet started execution",
+                ));
+            };
+            state.sources.clone()
         };
 
         let req = request.into_inner();
@@ -645,6 +1050,148 @@ impl WorkerGrpc for WorkerServer {
         Ok(Response::new(JobFinishedResp {}))
     }
 
+    async fn reset_execution(
+        &self,
+        _request: Request<ResetExecutionReq>,
+    ) -> Result<Response<ResetExecutionResp>, Status> {
+        if self.state.lock().unwrap().take().is_none() {
+            return Err(Status::failed_precondition(
+                "Worker has not yet started execution",
+            ));
+        }
+
+        self.region_generations.lock().unwrap().clear();
+
+        let network = self.network.lock().unwrap().clone();
+        if let Some(network) = network {
+            network.reset().await;
+        }
+
+        info!("[{:?}] Reset execution", self.id);
+
+        Ok(Response::new(ResetExecutionResp {}))
+    }
+
+    async fn restart_region(
+        &self,
+        request: Request<RestartRegionReq>,
+    ) -> Result<Response<RestartRegionResp>, Status> {
+        let req = request.into_inner();
+        let operators: HashSet<String> = req.operator_ids.into_iter().collect();
+
+        let (execution, old_controls, old_tasks) = {
+            let mut state = self.state.lock().unwrap();
+            let Some(state) = state.as_mut() else {
+                return Err(Status::failed_precondition(
+                    "Worker has not yet started execution",
+                ));
+            };
+
+            // from here on, messages from the region's old tasks aren't relayed to the controller
+            let mut generations = self.region_generations.lock().unwrap();
+            for op in &operators {
+                generations.insert(op.clone(), req.generation);
+            }
+
+            let mut old_controls = vec![];
+            for op in &operators {
+                if let Some(controls) = state.operator_controls.remove(op) {
+                    state
+                        .sources
+                        .retain(|s| !controls.iter().any(|c| c.same_channel(s)));
+                    state
+                        .sinks
+                        .retain(|s| !controls.iter().any(|c| c.same_channel(s)));
+                    old_controls.extend(controls);
+                }
+            }
+
+            let keys: Vec<_> = state
+                .tasks
+                .keys()
+                .filter(|(op, _)| operators.contains(op))
+                .cloned()
+                .collect();
+            let old_tasks: Vec<_> = keys
+                .into_iter()
+                .filter_map(|k| state.tasks.remove(&k))
+                .collect();
+
+            (state.execution.clone(), old_controls, old_tasks)
+        };
+
+        // stop the old tasks immediately, which lets sinks abort their open transactions before
+        // the region is restored; tasks that are blocked or have already failed are aborted
+        for control in old_controls {
+            let _ = control.try_send(ControlMessage::Stop {
+                mode: StopMode::Immediate,
+            });
+        }
+
+        futures::future::join_all(
+            old_tasks
+                .into_iter()
+                .map(|task| task.stop(REGION_STOP_TIMEOUT)),
+        )
+        .await;
+
+        let assignments: Vec<_> = execution
+            .assignments
+            .iter()
+            .filter(|a| operators.contains(&a.operator_id))
+            .cloned()
+            .collect();
+
+        let program = Program::region_from_logical(
+            self.name.to_string(),
+            &execution.logical.graph,
+            &execution.logical.program_config,
+            &assignments,
+            execution.registry.clone(),
+            &operators,
+        );
+
+        let mut network = execution.network.clone();
+        network.set_generation(req.generation);
+
+        let (mut engine, control_rx) = Engine::new(
+            program,
+            self.id,
+            self.job_id.clone(),
+            self.run_id.clone(),
+            network,
+            assignments,
+        )
+        .start(StreamConfig {
+            restore_epoch: req.restore_epoch,
+            source_offset_overrides: HashMap::new(),
+        })
+        .await;
+
+        self.relay_control_messages(control_rx, req.generation);
+
+        let mut state = self.state.lock().unwrap();
+        let Some(state) = state.as_mut() else {
+            return Err(Status::failed_precondition(
+                "Worker was reset while restarting a region",
+            ));
+        };
+        state.sources.extend(engine.source_controls());
+        state.sinks.extend(engine.sink_controls());
+        state.operator_controls.extend(engine.operator_controls());
+        state.tasks.extend(engine.take_tasks());
+
+        info!(
+            message = "Restarted region",
+            worker_id = self.id.0,
+            operators = ?operators,
+            restore_epoch = req.restore_epoch,
+            generation = req.generation
+        );
+
+        Ok(Response::new(RestartRegionResp {}))
+    }
+
     async fn get_metrics(
         &self,
         _req: Request<MetricsReq>,
@@ -673,4 +1220,63 @@ impl WorkerGrpc for WorkerServer {
 
         Ok(Response::new(MetricsResp { metrics }))
     }
+
+    async fn query_state(
+        &self,
+        request: Request<QueryStateReq>,
+    ) -> Result<Response<QueryStateResp>, Status> {
+        let req = request.into_inner();
+
+        let nodes = {
+            let state = self.state.lock().unwrap();
+            let Some(state) = state.as_ref() else {
+                return Err(Status::failed_precondition(
+                    "Worker has not yet started execution",
+                ));
+            };
+            state
+                .operator_controls
+                .get(&req.operator_id)
+                .cloned()
+                .unwrap_or_default()
+        };
+
+        let (tx, mut rx) = tokio::sync::mpsc::channel(nodes.len().max(1));
+        let query = StateQuery {
+            table: req.table,
+            key: req.key,
+            limit: req.limit as usize,
+            tx,
+        };
+
+        for s in nodes {
+            if let Err(e) = s
+                .send(ControlMessage::QueryState {
+                    query: query.clone(),
+                })
+                .await
+            {
+                warn!(
+                    "Failed to send QueryState message to operator {}: {}",
+                    req.operator_id, e
+                );
+            }
+        }
+        // the channel closes once every subtask has answered or dropped the query
+        drop(query);
+
+        let mut entries = vec![];
+        let collect = async {
+            while let Some(result) = rx.recv().await {
+                entries.extend(result.map_err(Status::invalid_argument)?);
+            }
+            Ok::<_, Status>(())
+        };
+
+        tokio::time::timeout(Duration::from_secs(10), collect)
+            .await
+            .map_err(|_| Status::deadline_exceeded("Timed out waiting for operators' state"))??;
+
+        Ok(Response::new(QueryStateResp { entries }))
+    }
 }
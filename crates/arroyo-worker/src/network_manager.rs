@@ -7,13 +7,15 @@ use arrow_array::RecordBatch;
 use arrow_schema::{ArrowError, SchemaRef};
 use arroyo_types::ArrowMessage;
 use bincode::config;
-use std::{collections::HashMap, mem::size_of, pin::Pin, sync::Arc, time::Duration};
+use std::{
+    collections::HashMap, future::Future, mem::size_of, pin::Pin, sync::Arc, time::Duration,
+};
 use tokio::{
     io::{self, BufReader, BufWriter},
     select,
-    sync::Mutex,
+    sync::{Mutex, Notify},
 };
-use tracing::warn;
+use tracing::{error, warn};
 
 use bytes::{Buf, BufMut};
 use rand::rngs::StdRng;
@@ -28,12 +30,17 @@ use tokio::time::{interval, Interval};
 use tokio_stream::StreamExt;
 
 use arroyo_operator::inq_reader::InQReader;
+use arroyo_rpc::protocol::{negotiate_protocol_version, MIN_PROTOCOL_VERSION, PROTOCOL_VERSION};
 use arroyo_server_common::shutdown::ShutdownGuard;
 
+use crate::chaos;
+
 #[derive(Clone)]
 struct NetworkSender {
     tx: BatchSender,
     schema: SchemaRef,
+    // the generation of the task the sender delivers to
+    generation: u32,
 }
 
 #[derive(Clone)]
@@ -59,26 +66,87 @@ impl Senders {
     }
 
     pub fn add(&mut self, quad: Quad, schema: SchemaRef, tx: BatchSender) {
-        self.senders.insert(quad, NetworkSender { tx, schema });
+        self.senders.insert(
+            quad,
+            NetworkSender {
+                tx,
+                schema,
+                generation: 0,
+            },
+        );
     }
 
-    async fn send(&mut self, header: Header, data: Vec<u8>) {
-        let sender = self.senders.get(&header.as_quad()).unwrap();
+    fn set_generation(&mut self, generation: u32) {
+        for sender in self.senders.values_mut() {
+            sender.generation = generation;
+        }
+    }
+}
 
-        let message = match header.message_type {
-            MessageType::Data => ArrowMessage::Data(
-                read_message(sender.schema.clone(), data).expect("failed to read message"),
-            ),
-            MessageType::Signal => ArrowMessage::Signal(
-                bincode::decode_from_slice(&data, config::standard())
-                    .expect("couldn't decode signal message, probably a record.")
-                    .0,
-            ),
+/// The senders for the data arriving over the network, shared by every incoming link. When a
+/// region of the job is restarted, the senders of its new tasks replace those of the old ones.
+#[derive(Default)]
+struct SharedSenders {
+    senders: std::sync::RwLock<HashMap<Quad, NetworkSender>>,
+    registered: Notify,
+}
+
+impl SharedSenders {
+    fn register(&self, senders: Senders) {
+        self.senders.write().unwrap().extend(senders.senders);
+        self.registered.notify_waiters();
+    }
+
+    /// Delivers a message received over a link opened by tasks of the given generation
+    async fn send(&self, generation: u32, header: Header, data: Vec<u8>) {
+        let quad = header.as_quad();
+
+        let sender = loop {
+            let registered = self.registered.notified();
+            let sender = self.senders.read().unwrap().get(&quad).cloned();
+            match sender {
+                Some(sender) if sender.generation == generation => break sender,
+                // the region has been restarted since the link was opened, so this is stale data
+                // from its old tasks
+                Some(sender) if sender.generation > generation => return,
+                // the region is being restarted and its new tasks haven't been registered yet
+                _ => registered.await,
+            }
         };
 
-        if let Err(send_error) = sender.tx.send(message).await {
+        let result = match header.message_type {
+            MessageType::Data => {
+                sender
+                    .tx
+                    .send(ArrowMessage::Data(
+                        read_message(sender.schema.clone(), data).expect("failed to read message"),
+                    ))
+                    .await
+            }
+            MessageType::Signal => {
+                sender
+                    .tx
+                    .send(ArrowMessage::Signal(
+                        bincode::decode_from_slice(&data, config::standard())
+                            .expect("couldn't decode signal message, probably a record.")
+                            .0,
+                    ))
+                    .await
+            }
+            MessageType::Overtaking => {
+                let (signal, overtaken) = bincode::decode_from_slice(&data, config::standard())
+                    .expect("couldn't decode overtaking signal message")
+                    .0;
+                sender
+                    .tx
+                    .send_overtaking(ArrowMessage::Signal(signal), overtaken)
+            }
+        };
+
+        if let Err(send_error) = result {
             if !send_error.0.is_end() {
-                panic!("{:?} not sent", send_error.0);
+                // the task has stopped, which happens when its region fails
+                warn!("dropping message for stopped task {:?}", quad);
             } else {
                 warn!("couldn't send end message");
             }
@@ -86,16 +154,35 @@ impl Senders {
     }
 }
 
+// Connections from workers speaking protocol version 2 or later start with these bytes, followed
+// by the sender's newest and oldest supported protocol versions. Older workers start sending
+// headers immediately; as the first field of a header is an operator index, it can't collide.
+const HANDSHAKE_MAGIC: [u8; 4] = *b"ARRO";
+const HANDSHAKE_VERSION: u32 = 2;
+
+// Connections from workers speaking protocol version 5 or later start with these bytes instead,
+// and follow the versions with the generation of the tasks sending over the connection
+const GENERATION_HANDSHAKE_MAGIC: [u8; 4] = *b"ARRG";
+const GENERATION_HANDSHAKE_VERSION: u32 = 5;
+
+// Workers speaking protocol version 6 or later send checkpoint barriers ahead of the data queued
+// before them, along with the number of data messages they overtook (see [BatchSender]); older
+// workers are sent each edge's messages in order
+const OVERTAKING_VERSION: u32 = 6;
+
 pub struct InNetworkLink {
-    _source: String,
+    source: String,
     stream: BufReader<TcpStream>,
-    senders: Senders,
+    senders: Arc<SharedSenders>,
+    generation: u32,
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum MessageType {
     Data,
     Signal,
+    // a signal that was sent ahead of data messages on the same edge, followed by their count
+    Overtaking,
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
@@ -139,6 +226,7 @@ impl Header {
             message_type: match bytes.get_u32_le() {
                 0 => MessageType::Data,
                 1 => MessageType::Signal,
+                2 => MessageType::Overtaking,
                 b => panic!("invalid message type: {}", b),
             },
         }
@@ -155,6 +243,7 @@ impl Header {
         buf.put_u32_le(match self.message_type {
             MessageType::Data => 0,
             MessageType::Signal => 1,
+            MessageType::Overtaking => 2,
         });
 
         writer.write_all(&bytes).await.unwrap();
@@ -162,33 +251,70 @@ impl Header {
 }
 
 impl InNetworkLink {
-    pub fn new(source: String, stream: TcpStream, senders: Senders) -> Self {
+    fn new(source: String, stream: TcpStream, senders: Arc<SharedSenders>) -> Self {
         InNetworkLink {
-            _source: source,
+            source,
             stream: BufReader::new(stream),
             senders,
+            generation: 0,
+        }
+    }
+
+    /// Reads the handshake sent by the other side of the connection, if any. Connections
+    /// without a handshake begin with a header, and the number of its bytes that were read
+    /// into `header_buf` is returned.
+    async fn read_handshake(&mut self, header_buf: &mut [u8]) -> anyhow::Result<usize> {
+        self.stream
+            .read_exact(&mut header_buf[..HANDSHAKE_MAGIC.len()])
+            .await?;
+
+        let magic = &header_buf[..HANDSHAKE_MAGIC.len()];
+        let with_generation = magic == GENERATION_HANDSHAKE_MAGIC;
+        if !with_generation && magic != HANDSHAKE_MAGIC {
+            return Ok(HANDSHAKE_MAGIC.len());
         }
+
+        let version = self.stream.read_u32_le().await?;
+        let min_version = self.stream.read_u32_le().await?;
+        negotiate_protocol_version(version, min_version)?;
+
+        if with_generation {
+            self.generation = self.stream.read_u32_le().await?;
+        }
+
+        Ok(0)
     }
 
-    async fn next(&mut self, header_buf: &mut [u8]) -> Result<(), io::Error> {
-        self.stream.read_exact(header_buf).await?;
+    async fn next(&mut self, header_buf: &mut [u8], prefix_len: usize) -> Result<(), io::Error> {
+        self.stream
+            .read_exact(&mut header_buf[prefix_len..])
+            .await?;
         let header = Header::from_bytes(&header_buf[..]);
 
         let mut buf = vec![0; header.len];
         self.stream.read_exact(&mut buf).await?;
 
-        self.senders.send(header, buf).await;
+        self.senders.send(self.generation, header, buf).await;
         Ok(())
     }
 
     pub fn start(mut self) {
         tokio::spawn(async move {
             let mut header_buf = vec![0u8; size_of::<Header>()];
+            let mut prefix_len = match self.read_handshake(&mut header_buf).await {
+                Ok(prefix_len) => prefix_len,
+                Err(e) => {
+                    error!("Rejecting data connection on {}: {:?}", self.source, e);
+                    return;
+                }
+            };
+
             loop {
-                if let Err(e) = self.next(&mut header_buf).await {
+                if let Err(e) = self.next(&mut header_buf, prefix_len).await {
                     warn!("Socket hung up: {:?}", e);
                     break;
                 };
+                prefix_len = 0;
             }
         });
     }
@@ -209,22 +335,40 @@ struct NetworkReceiver {
 }
 
 struct OutNetworkLink {
-    _dest: String,
+    dest: String,
+    protocol_version: u32,
     stream: BufWriter<TcpStream>,
     receivers: Vec<NetworkReceiver>,
 }
 
 impl OutNetworkLink {
-    pub async fn connect(dest: String) -> Self {
+    pub async fn connect(dest: String, protocol_version: u32, generation: u32) -> Self {
         let mut rand = StdRng::from_entropy();
         for i in 0..10 {
             match TcpStream::connect(&dest).await {
                 Ok(stream) => {
+                    let mut stream = BufWriter::new(stream);
+
+                    // workers that predate the handshake would treat it as a header
+                    if protocol_version >= GENERATION_HANDSHAKE_VERSION {
+                        let mut handshake = GENERATION_HANDSHAKE_MAGIC.to_vec();
+                        handshake.put_u32_le(PROTOCOL_VERSION);
+                        handshake.put_u32_le(MIN_PROTOCOL_VERSION);
+                        handshake.put_u32_le(generation);
+                        stream.write_all(&handshake).await.unwrap();
+                    } else if protocol_version >= HANDSHAKE_VERSION {
+                        let mut handshake = HANDSHAKE_MAGIC.to_vec();
+                        handshake.put_u32_le(PROTOCOL_VERSION);
+                        handshake.put_u32_le(MIN_PROTOCOL_VERSION);
+                        stream.write_all(&handshake).await.unwrap();
+                    }
+
                     return Self {
-                        _dest: dest,
-                        stream: BufWriter::new(stream),
+                        dest,
+                        protocol_version,
+                        stream,
                         receivers: vec![],
-                    }
+                    };
                 }
                 Err(e) => {
                     warn!("Failed to connect to {dest}: {:?}", e);
@@ -246,9 +390,16 @@ impl OutNetworkLink {
         });
     }
 
-    pub fn start(mut self) {
+    pub fn start(self) {
+        self.start_with_fault(chaos::next_network_fault());
+    }
+
+    /// Starts sending the messages of the link's receivers until they all finish, or `fault`
+    /// resolves, at which point the connection is dropped as if the network had failed
+    fn start_with_fault(mut self, fault: impl Future<Output = ()> + Send + 'static) {
         tokio::spawn(async move {
             let mut sel = InQReader::new();
+            let overtaking = self.protocol_version >= OVERTAKING_VERSION;
             for NetworkReceiver {
                 quad,
                 mut rx,
@@ -256,25 +407,57 @@ impl OutNetworkLink {
             } in self.receivers
             {
                 let stream = async_stream::stream! {
-                    while let Some(item) = rx.recv().await {
-                        yield (quad, dictionary_tracker.clone(), item);
+                    loop {
+                        // only a barrier read when nothing it overtook is left to send can
+                        // overtake more
+                        let ahead = rx.overtaken();
+                        let Some(item) = rx.recv().await else {
+                            break;
+                        };
+                        let mut overtaken = if ahead == 0 { rx.overtaken() } else { 0 };
+                        if overtaken > 0 && !overtaking {
+                            // the data the barrier overtook is sent ahead of it to older workers
+                            for data in rx.recv_overtaken().await {
+                                yield (quad, dictionary_tracker.clone(), data, 0);
+                            }
+                            overtaken = 0;
+                        }
+                        yield (quad, dictionary_tracker.clone(), item, overtaken);
                     }
                 };
                 sel.push(Box::pin(stream));
             }
             let mut flush_interval: Interval = interval(Duration::from_millis(100));
+            let mut network_fault = Box::pin(fault);
 
             let write_options = IpcWriteOptions::default();
 
             loop {
                 select! {
-                    Some(((quad, dictionary_tracker, msg), s)) = sel.next() => {
+                    next = sel.next() => {
+                        // once every task sending over this link has finished, the connection is
+                        // closed so that the other side can hang up too
+                        let Some(((quad, dictionary_tracker, msg, overtaken), s)) = next else {
+                            let _ = self.stream.flush().await;
+                            break;
+                        };
+
                         match msg {
                             ArrowMessage::Signal(signal) => {
-                                let data = bincode::encode_to_vec(&signal, config::standard()).unwrap();
-                                let header = Header::from_quad(quad, data.len(), MessageType::Signal);
+                                // a barrier that overtook data in the local queue is sent ahead of
+                                // it, with a count of the data messages that follow it but were
+                                // sent before it
+                                let (data, message_type) = if overtaken > 0 {
+                                    (bincode::encode_to_vec((&signal, overtaken), config::standard()).unwrap(), MessageType::Overtaking)
+                                } else {
+                                    (bincode::encode_to_vec(&signal, config::standard()).unwrap(), MessageType::Signal)
+                                };
+                                let header = Header::from_quad(quad, data.len(), message_type);
                                 header.write(&mut Pin::new(&mut self.stream)).await;
                                 self.stream.write_all(&data).await.unwrap();
+                                // signals are sent right away rather than waiting for the next
+                                // flush, as checkpoints and stops are waiting on them
+                                self.stream.flush().await.unwrap();
                             }
                             ArrowMessage::Data(data) => {
                                 let (_, encoded_message) = {
@@ -291,6 +474,13 @@ impl OutNetworkLink {
                     _ = flush_interval.tick() => {
                         self.stream.flush().await.unwrap();
                     }
+                    _ = &mut network_fault => {
+                        // dropping the receivers along with the connection fails the tasks
+                        // sending over it, so the job recovers from its last checkpoint
+                        warn!("chaos: dropping data connection to {}", self.dest);
+                        let _ = self.stream.get_mut().shutdown().await;
+                        break;
+                    }
                 }
             }
         });
@@ -299,11 +489,16 @@ impl OutNetworkLink {
 
 enum InStreamsOrSenders {
     InStreams(Vec<TcpStream>),
-    Senders(Senders),
+    Senders(Arc<SharedSenders>),
 }
 
+#[derive(Clone)]
 pub struct NetworkManager {
     port: u16,
+    // the protocol version negotiated for the job, which determines how we talk to other workers
+    protocol_version: u32,
+    // the generation of the tasks being started, which increases each time their region restarts
+    generation: u32,
     in_streams: Arc<Mutex<InStreamsOrSenders>>,
     out_streams: Arc<Mutex<HashMap<Quad, OutNetworkLink>>>,
 }
@@ -312,11 +507,21 @@ impl NetworkManager {
     pub fn new(port: u16) -> Self {
         NetworkManager {
             port,
+            protocol_version: PROTOCOL_VERSION,
+            generation: 0,
             in_streams: Arc::new(Mutex::new(InStreamsOrSenders::InStreams(vec![]))),
             out_streams: Arc::new(Mutex::new(HashMap::new())),
         }
     }
 
+    pub fn set_protocol_version(&mut self, protocol_version: u32) {
+        self.protocol_version = protocol_version;
+    }
+
+    pub fn set_generation(&mut self, generation: u32) {
+        self.generation = generation;
+    }
+
     pub async fn open_listener(&mut self, shutdown_guard: ShutdownGuard) -> u16 {
         let port = self.port;
         let listener = TcpListener::bind(format!("0.0.0.0:{}", port))
@@ -353,25 +558,32 @@ impl NetworkManager {
         port
     }
 
-    pub async fn start(&mut self, senders: Senders) {
+    /// Starts the links of newly started tasks. The first start of an execution also handles the
+    /// connections held until then; later starts are for restarted regions, whose senders
+    /// replace those of their old tasks.
+    pub async fn start(&mut self, mut senders: Senders) {
+        senders.set_generation(self.generation);
         let mut sockets = self.in_streams.lock().await;
 
-        match &mut *sockets {
+        let shared = match &mut *sockets {
             InStreamsOrSenders::InStreams(ref mut in_streams) => {
+                let shared = Arc::new(SharedSenders::default());
+                shared.register(senders);
                 for s in in_streams.drain(..) {
-                    let senders = senders.clone();
+                    let shared = shared.clone();
                     tokio::spawn(async move {
-                        InNetworkLink::new(s.local_addr().unwrap().to_string(), s, senders.clone())
-                            .start();
+                        InNetworkLink::new(s.local_addr().unwrap().to_string(), s, shared).start();
                     });
                 }
+                shared
             }
-            InStreamsOrSenders::Senders(_) => {
-                panic!("already started!");
+            InStreamsOrSenders::Senders(shared) => {
+                shared.register(senders);
+                shared.clone()
             }
-        }
+        };
 
-        *sockets = InStreamsOrSenders::Senders(senders.clone());
+        *sockets = InStreamsOrSenders::Senders(shared);
 
         let mut out_streams = self.out_streams.lock().await;
         for (_, s) in out_streams.drain() {
@@ -379,8 +591,16 @@ impl NetworkManager {
         }
     }
 
+    /// Prepares for a new execution on this worker once the previous one has finished; incoming
+    /// connections are held until the new execution starts and provides its senders
+    pub async fn reset(&self) {
+        *self.in_streams.lock().await = InStreamsOrSenders::InStreams(vec![]);
+        self.out_streams.lock().await.clear();
+    }
+
     pub async fn connect(&self, addr: String, quad: Quad, rx: BatchReceiver) {
-        let link = OutNetworkLink::connect(addr.clone()).await;
+        let link =
+            OutNetworkLink::connect(addr.clone(), self.protocol_version, self.generation).await;
         let mut ins = self.out_streams.lock().await;
         if let std::collections::hash_map::Entry::Vacant(e) = ins.entry(quad) {
             e.insert(link);
@@ -503,7 +723,8 @@ mod test {
 
     use crate::network_manager::{MessageType, Quad};
 
-    use super::{Header, NetworkManager, Senders};
+    use super::{Header, NetworkManager, OutNetworkLink, Senders};
+    use arroyo_rpc::protocol::PROTOCOL_VERSION;
 
     #[tokio::test]
     async fn test_header_serdes() {
@@ -604,4 +825,238 @@ mod test {
 
         assert_eq!(result, message);
     }
+    #[tokio::test]
+    async fn test_barriers_overtake_queued_data() {
+        for protocol_version in [PROTOCOL_VERSION, 5] {
+            let quad = Quad {
+                src_id: 5,
+                src_idx: 0,
+                dst_id: 6,
+                dst_idx: 0,
+            };
+
+            let schema = Arc::new(Schema::new(vec![Field::new(
+                "id",
+                arrow_schema::DataType::UInt64,
+                false,
+            )]));
+            let batch = |start: u64| {
+                RecordBatch::try_new(
+                    schema.clone(),
+                    vec![
+                        Arc::new(UInt64Array::from((start..start + 10).collect::<Vec<_>>()))
+                            as ArrayRef,
+                    ],
+                )
+                .unwrap()
+            };
+            let barrier = ArrowMessage::Signal(SignalMessage::Barrier(CheckpointBarrier {
+                epoch: 1,
+                min_epoch: 0,
+                timestamp: SystemTime::now(),
+                then_stop: false,
+            }));
+
+            let (server_tx, mut server_rx) = batch_bounded(100);
+            let mut senders = Senders::new();
+            senders.add(quad, schema.clone(), server_tx);
+
+            let shutdown = Shutdown::new("test", SignalBehavior::None);
+            let mut nm = NetworkManager::new(0);
+            nm.set_protocol_version(protocol_version);
+            let port = nm.open_listener(shutdown.guard("test")).await;
+
+            let (client_tx, client_rx) = batch_bounded(100);
+            nm.connect(format!("localhost:{}", port), quad, client_rx)
+                .await;
+
+            // the data and the barrier are queued before the link starts sending
+            client_tx.send(ArrowMessage::Data(batch(0))).await.unwrap();
+            client_tx.send(ArrowMessage::Data(batch(10))).await.unwrap();
+            client_tx.send(barrier.clone()).await.unwrap();
+
+            nm.start(senders).await;
+
+            // the data is received in order, and each batch either before the barrier or
+            // after it as data the barrier overtook
+            let mut received = vec![];
+            let mut overtaken = None;
+            while received.len() < 2 || overtaken.is_none() {
+                let result = timeout(Duration::from_secs(1), server_rx.recv())
+                    .await
+                    .unwrap()
+                    .unwrap();
+                if result == barrier {
+                    overtaken = Some(server_rx.overtaken() as usize);
+                    if protocol_version >= super::OVERTAKING_VERSION {
+                        // the barrier is sent ahead of the data queued before it, along with
+                        // how much of it there is
+                        assert!(received.is_empty());
+                        assert_eq!(server_rx.overtaken(), 2);
+                    }
+                } else {
+                    received.push(result);
+                }
+            }
+
+            assert_eq!(
+                received,
+                vec![ArrowMessage::Data(batch(0)), ArrowMessage::Data(batch(10))]
+            );
+            assert!(overtaken.unwrap() <= 2);
+            assert_eq!(server_rx.overtaken(), 0);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_restarted_region() {
+        let quad = Quad {
+            src_id: 1,
+            src_idx: 0,
+            dst_id: 2,
+            dst_idx: 0,
+        };
+        let time = SystemTime::now();
+        let barrier = |epoch| {
+            ArrowMessage::Signal(SignalMessage::Barrier(CheckpointBarrier {
+                epoch,
+                min_epoch: 0,
+                timestamp: time,
+                then_stop: false,
+            }))
+        };
+
+        let shutdown = Shutdown::new("test", SignalBehavior::None);
+        let mut receiver = NetworkManager::new(0);
+        let port = receiver.open_listener(shutdown.guard("test")).await;
+        let mut sender = NetworkManager::new(0);
+
+        let (old_server_tx, mut old_server_rx) = batch_bounded(10);
+        let (old_client_tx, old_client_rx) = batch_bounded(10);
+        sender
+            .connect(format!("localhost:{}", port), quad, old_client_rx)
+            .await;
+        let mut senders = Senders::new();
+        senders.add(quad, Arc::new(Schema::empty()), old_server_tx);
+        receiver.start(senders).await;
+        sender.start(Senders::new()).await;
+
+        old_client_tx.send(barrier(1)).await.unwrap();
+        let result = timeout(Duration::from_secs(1), old_server_rx.recv())
+            .await
+            .unwrap();
+        assert_eq!(result, Some(barrier(1)));
+
+        // the sending side of the region restarts first, and its data waits for the receiving side
+        let (new_client_tx, new_client_rx) = batch_bounded(10);
+        let mut sender = sender.clone();
+        sender.set_generation(1);
+        sender
+            .connect(format!("localhost:{}", port), quad, new_client_rx)
+            .await;
+        sender.start(Senders::new()).await;
+        new_client_tx.send(barrier(2)).await.unwrap();
+
+        assert!(
+            timeout(Duration::from_millis(200), old_server_rx.recv())
+                .await
+                .is_err(),
+            "data from the new generation was sent to the old task"
+        );
+
+        let (new_server_tx, mut new_server_rx) = batch_bounded(10);
+        let mut receiver = receiver.clone();
+        receiver.set_generation(1);
+        let mut senders = Senders::new();
+        senders.add(quad, Arc::new(Schema::empty()), new_server_tx);
+        receiver.start(senders).await;
+
+        let result = timeout(Duration::from_secs(1), new_server_rx.recv())
+            .await
+            .unwrap();
+        assert_eq!(result, Some(barrier(2)));
+
+        // data still arriving from the old generation is dropped
+        old_client_tx.send(barrier(3)).await.unwrap();
+        new_client_tx.send(barrier(4)).await.unwrap();
+        let result = timeout(Duration::from_secs(1), new_server_rx.recv())
+            .await
+            .unwrap();
+        assert_eq!(result, Some(barrier(4)));
+        assert!(timeout(Duration::from_millis(200), new_server_rx.recv())
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_client_without_handshake() {
+        // workers from before protocol version 2 don't send a handshake
+        let (server_tx, mut server_rx) = batch_bounded(10);
+
+        let quad = Quad {
+            src_id: 3,
+            src_idx: 0,
+            dst_id: 4,
+            dst_idx: 1,
+        };
+
+        let mut senders = Senders::new();
+        senders.add(quad, Arc::new(Schema::empty()), server_tx);
+
+        let shutdown = Shutdown::new("test", SignalBehavior::None);
+        let mut nm = NetworkManager::new(0);
+        nm.set_protocol_version(1);
+        let port = nm.open_listener(shutdown.guard("test")).await;
+
+        let (client_tx, client_rx) = batch_bounded(10);
+        nm.connect(format!("localhost:{}", port), quad, client_rx)
+            .await;
+
+        nm.start(senders).await;
+
+        let message = ArrowMessage::Signal(SignalMessage::Stop);
+        client_tx.send(message.clone()).await.unwrap();
+
+        let result = timeout(Duration::from_secs(1), server_rx.recv())
+            .await
+            .unwrap()
+            .expect("timed out");
+
+        assert_eq!(result, message);
+    }
+
+    #[tokio::test]
+    async fn test_network_fault() {
+        let quad = Quad {
+            src_id: 1,
+            src_idx: 0,
+            dst_id: 2,
+            dst_idx: 0,
+        };
+
+        let shutdown = Shutdown::new("test", SignalBehavior::None);
+        let mut receiver = NetworkManager::new(0);
+        let port = receiver.open_listener(shutdown.guard("test")).await;
+
+        let (server_tx, _server_rx) = batch_bounded(10);
+        let mut senders = Senders::new();
+        senders.add(quad, Arc::new(Schema::empty()), server_tx);
+        receiver.start(senders).await;
+
+        let (client_tx, client_rx) = batch_bounded(10);
+        let mut link =
+            OutNetworkLink::connect(format!("localhost:{}", port), PROTOCOL_VERSION, 0).await;
+        link.add_receiver(quad, client_rx).await;
+        link.start_with_fault(futures::future::ready(()));
+
+        // once the connection is dropped, the task sending over it fails to send
+        let message = ArrowMessage::Signal(SignalMessage::Stop);
+        timeout(Duration::from_secs(1), async {
+            while client_tx.send(message.clone()).await.is_ok() {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("sending should fail after the connection is dropped");
+    }
 }
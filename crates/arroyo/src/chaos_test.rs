@@ -0,0 +1,94 @@
+use crate::{db_source, ChaosTestArgs};
+use anyhow::bail;
+use arroyo_chaos::{run_chaos_test, ChaosTestConfig};
+use arroyo_openapi::Client;
+use arroyo_rpc::config;
+use arroyo_rpc::config::{DatabaseType, Scheduler};
+use arroyo_server_common::shutdown::{Shutdown, SignalBehavior};
+use rand::random;
+use std::env::{set_var, temp_dir};
+use std::process::exit;
+use std::time::Duration;
+use tracing::{error, info};
+
+async fn wait_for_connect(client: &Client) -> anyhow::Result<()> {
+    for _ in 0..50 {
+        if client.ping().send().await.is_ok() {
+            return Ok(());
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+
+    bail!("API server did not start up successfully; see logs for more details");
+}
+
+pub async fn chaos_test(args: ChaosTestArgs) {
+    let _guard = arroyo_server_common::init_logging("chaos-test");
+
+    // workers are started as child processes, which pick up their fault injection config from
+    // the environment
+    set_var("ARROYO__WORKER__CHAOS__ENABLED", "true");
+    set_var("ARROYO__WORKER__CHAOS__KILL_INTERVAL", &args.kill_interval);
+    set_var(
+        "ARROYO__WORKER__CHAOS__NETWORK_FAULT_INTERVAL",
+        &args.network_fault_interval,
+    );
+
+    let dir = temp_dir().join(format!("arroyo-chaos-test-{}", random::<u32>()));
+    std::fs::create_dir_all(&dir).unwrap();
+    info!("Writing chaos test state to {}", dir.to_string_lossy());
+
+    config::update(|c| {
+        c.database.r#type = DatabaseType::Sqlite;
+        c.database.sqlite.path = dir.join("state.sqlite");
+        c.checkpoint_url = dir.join("checkpoints").to_string_lossy().to_string();
+        c.api.http_port = 0;
+        c.controller.rpc_port = 0;
+        c.controller.scheduler = Scheduler::Process;
+    });
+
+    let db = db_source().await;
+
+    let shutdown = Shutdown::new("chaos-test", SignalBehavior::Handle);
+
+    let controller_port = arroyo_controller::ControllerServer::new(db.clone())
+        .await
+        .start(shutdown.guard("controller"))
+        .await
+        .expect("could not start system");
+
+    config::update(|c| c.controller.rpc_port = controller_port);
+
+    let http_port = arroyo_api::start_server(db, shutdown.guard("api")).unwrap();
+
+    let client = Client::new_with_client(
+        &format!("http://localhost:{http_port}/api",),
+        reqwest::ClientBuilder::new()
+            .timeout(Duration::from_secs(60))
+            .build()
+            .unwrap(),
+    );
+
+    if let Err(e) = wait_for_connect(&client).await {
+        error!("{}", e);
+        exit(1);
+    }
+
+    let mut test_config = ChaosTestConfig::new(dir.join("output"));
+    test_config.events = args.events;
+    test_config.event_rate = args.event_rate;
+    test_config.parallelism = args.parallelism;
+    test_config.restart_interval = Some(*args.restart_interval).filter(|d| !d.is_zero());
+    test_config.timeout = *args.timeout;
+
+    match run_chaos_test(&client, &test_config).await {
+        Ok(report) => {
+            println!("{}", report);
+            exit(if report.is_exactly_once() { 0 } else { 1 });
+        }
+        Err(e) => {
+            error!("Chaos test failed to run: {:?}", e);
+            exit(1);
+        }
+    }
+}
@@ -1,9 +1,11 @@
+mod chaos_test;
+mod offsets;
 mod run;
 
 use anyhow::{anyhow, bail};
 use arroyo_df::{ArroyoSchemaProvider, SqlConfig};
 use arroyo_rpc::config;
-use arroyo_rpc::config::{config, DatabaseType};
+use arroyo_rpc::config::{config, DatabaseType, HumanReadableDuration};
 use arroyo_server_common::shutdown::{Shutdown, SignalBehavior};
 use arroyo_server_common::{log_event, start_admin_server};
 use arroyo_worker::{utils, WorkerServer};
@@ -15,6 +17,7 @@ use serde_json::json;
 use std::env::temp_dir;
 use std::path::PathBuf;
 use std::process::exit;
+use std::str::FromStr;
 use std::sync::Arc;
 use std::time::Duration;
 use std::{env, fs};
@@ -80,6 +83,61 @@ struct RunArgs {
     query: Input,
 }
 
+fn duration_arg(s: &str) -> Result<String, String> {
+    HumanReadableDuration::from_str(s).map(|_| s.to_string())
+}
+
+#[derive(Args)]
+struct ChaosTestArgs {
+    /// Number of events produced by each subtask of the source
+    #[arg(short, long, default_value = "100000")]
+    events: u64,
+
+    /// Number of events produced by the source per second
+    #[arg(long, default_value = "1000")]
+    event_rate: u64,
+
+    /// Number of parallel subtasks to run
+    #[arg(short, long, default_value = "2")]
+    parallelism: u32,
+
+    /// Average time between workers being killed; `0s` disables kills
+    #[arg(long, default_value = "60s", value_parser = duration_arg)]
+    kill_interval: String,
+
+    /// Average time between workers' data connections being dropped; `0s` disables them
+    #[arg(long, default_value = "30s", value_parser = duration_arg)]
+    network_fault_interval: String,
+
+    /// Average time between forced restarts of the pipeline through the API; `0s` disables them
+    #[arg(long, default_value = "30s")]
+    restart_interval: HumanReadableDuration,
+
+    /// How long to wait for the pipeline to finish before failing
+    #[arg(long, default_value = "30m")]
+    timeout: HumanReadableDuration,
+}
+
+#[derive(Args)]
+struct SetOffsetsArgs {
+    /// URL of the Arroyo API
+    #[arg(long, default_value = "http://localhost:5115/api")]
+    endpoint: String,
+
+    /// Id of the pipeline
+    pipeline_id: String,
+
+    /// Id of the stopped job to override offsets for; defaults to the pipeline's job
+    #[arg(long)]
+    job_id: Option<String>,
+
+    /// Where to start reading a Kafka partition or Kinesis shard, as
+    /// `<node_id>:<partition>=<offset>`, where the offset is a Kafka offset or Kinesis sequence
+    /// number, or `earliest` or `latest`; with none, clears existing overrides
+    #[arg(short, long = "offset")]
+    overrides: Vec<String>,
+}
+
 #[derive(Subcommand)]
 enum Commands {
     /// Run a query as a local pipeline cluster
@@ -110,6 +168,12 @@ enum Commands {
         wait: Option<u32>,
     },
 
+    /// Runs a pipeline with injected failures and verifies that its output is exactly-once
+    ChaosTest(ChaosTestArgs),
+
+    /// Overrides where a stopped pipeline's sources start reading when it's restarted
+    SetOffsets(SetOffsetsArgs),
+
     /// Visualizes a query plan
     Visualize {
         /// Open the visualization in the browser
@@ -178,6 +242,12 @@ async fn main() {
         Commands::Run(args) => {
             run::run(args).await;
         }
+        Commands::ChaosTest(args) => {
+            chaos_test::chaos_test(args).await;
+        }
+        Commands::SetOffsets(args) => {
+            offsets::set_offsets(args).await;
+        }
         Commands::Visualize { query, open } => {
             visualize(query, open).await;
         }
@@ -398,6 +468,8 @@ async fn migrate(wait: Option<u32>) -> anyhow::Result<()> {
 async fn start_control_plane(service: CPService) {
     let _guard = arroyo_server_common::init_logging(service.name());
 
+    arroyo_connectors::plugin::register_plugins().await;
+
     let config = config::config();
 
     let db = db_source().await;
@@ -434,7 +506,7 @@ async fn start_control_plane(service: CPService) {
 }
 
 async fn start_worker() {
-    let shutdown = Shutdown::new(
+    let mut shutdown = Shutdown::new(
         "worker",
         if env::var("UNDER_PROCESS_SCHEDULER").is_ok() {
             SignalBehavior::Ignore
@@ -444,6 +516,7 @@ async fn start_worker() {
     );
     let server =
         WorkerServer::from_config(shutdown.guard("worker")).expect("Could not start worker");
+    shutdown.set_handler(Box::new(server.shutdown_handler()));
 
     let _guard = arroyo_server_common::init_logging(&format!(
         "worker-{}-{}",
@@ -451,6 +524,8 @@ async fn start_worker() {
         server.job_id()
     ));
 
+    arroyo_connectors::plugin::register_plugins().await;
+
     shutdown.spawn_task("admin", start_admin_server("worker"));
     let token = shutdown.token();
     tokio::spawn(async move {
@@ -0,0 +1,94 @@
+use crate::SetOffsetsArgs;
+use anyhow::{anyhow, bail};
+use arroyo_openapi::types::{SourceOffsetOverride, SourceOffsetsPost};
+use arroyo_openapi::Client;
+use std::process::exit;
+
+/// Parses an override of the form `<node_id>:<partition>=<offset>`
+fn parse_override(s: &str) -> anyhow::Result<SourceOffsetOverride> {
+    let (target, offset) = s.split_once('=').ok_or_else(|| {
+        anyhow!(
+            "invalid offset override '{}'; expected <node_id>:<partition>=<offset>",
+            s
+        )
+    })?;
+    let (node_id, partition) = target.rsplit_once(':').ok_or_else(|| {
+        anyhow!(
+            "invalid offset override '{}'; expected <node_id>:<partition>=<offset>",
+            s
+        )
+    })?;
+
+    if node_id.is_empty() || partition.is_empty() || offset.is_empty() {
+        bail!(
+            "invalid offset override '{}'; expected <node_id>:<partition>=<offset>",
+            s
+        );
+    }
+
+    Ok(SourceOffsetOverride {
+        node_id: node_id.to_string(),
+        partition: partition.to_string(),
+        offset: offset.to_string(),
+    })
+}
+
+async fn set_offsets_int(args: SetOffsetsArgs) -> anyhow::Result<()> {
+    let overrides = args
+        .overrides
+        .iter()
+        .map(|o| parse_override(o))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    let client = Client::new(&args.endpoint);
+
+    let job_id = match args.job_id {
+        Some(job_id) => job_id,
+        None => {
+            client
+                .get_pipeline_jobs()
+                .id(&args.pipeline_id)
+                .send()
+                .await?
+                .into_inner()
+                .data
+                .into_iter()
+                .next()
+                .ok_or_else(|| anyhow!("pipeline {} has no jobs", args.pipeline_id))?
+                .id
+        }
+    };
+
+    let result = client
+        .set_source_offsets()
+        .pipeline_id(&args.pipeline_id)
+        .job_id(&job_id)
+        .body(SourceOffsetsPost { overrides })
+        .send()
+        .await?
+        .into_inner();
+
+    if result.overrides.is_empty() {
+        println!("Cleared source offset overrides for job {}", job_id);
+    } else {
+        for o in &result.overrides {
+            println!("{}:{} => {}", o.node_id, o.partition, o.offset);
+        }
+        match result.epoch {
+            Some(epoch) => println!(
+                "Overrides will apply when job {} restores from checkpoint {}",
+                job_id, epoch
+            ),
+            None => println!("Overrides will apply when job {} is restarted", job_id),
+        }
+    }
+
+    Ok(())
+}
+
+pub async fn set_offsets(args: SetOffsetsArgs) {
+    if let Err(e) = set_offsets_int(args).await {
+        eprintln!("Failed to set source offsets: {}", e);
+        exit(1);
+    }
+}
@@ -1,6 +1,8 @@
 use crate::{db_source, RunArgs};
 use anyhow::{anyhow, bail};
-use arroyo_openapi::types::{Pipeline, PipelinePatch, PipelinePost, StopType, ValidateQueryPost};
+use arroyo_openapi::types::{
+    Job, Pipeline, PipelinePatch, PipelinePost, StopType, SystemQueryResult, ValidateQueryPost,
+};
 use arroyo_openapi::Client;
 use arroyo_rpc::config::{config, DatabaseType, DefaultSink, Scheduler};
 use arroyo_rpc::{config, init_db_notifier, notify_db, retry};
@@ -9,6 +11,7 @@ use arroyo_server_common::shutdown::{Shutdown, ShutdownHandler, SignalBehavior};
 use arroyo_storage::StorageProvider;
 use arroyo_types::to_millis;
 use async_trait::async_trait;
+use futures::StreamExt;
 use rand::random;
 use rusqlite::{Connection, DatabaseName, OpenFlags};
 use serde_json::json;
@@ -26,7 +29,7 @@ use tracing::level_filters::LevelFilter;
 use tracing::{error, info, warn};
 use tracing_subscriber::EnvFilter;
 
-async fn get_state(client: &Client, pipeline_id: &str) -> String {
+async fn get_job(client: &Client, pipeline_id: &str) -> Job {
     let jobs = retry!(
         client.get_pipeline_jobs().id(pipeline_id).send().await,
         10,
@@ -37,7 +40,66 @@ async fn get_state(client: &Client, pipeline_id: &str) -> String {
     .unwrap()
     .into_inner();
 
-    jobs.data.into_iter().next().unwrap().state
+    jobs.data.into_iter().next().unwrap()
+}
+
+/// Removes the first complete event from the buffer. Events end with a blank line, and servers
+/// may end lines with `\r\n`, `\n` or `\r`.
+fn next_event(buf: &mut Vec<u8>) -> Option<Vec<u8>> {
+    let (end, len) = (0..buf.len()).find_map(|i| {
+        [&b"\r\n\r\n"[..], b"\n\n", b"\r\r"]
+            .iter()
+            .find(|separator| buf[i..].starts_with(separator))
+            .map(|separator| (i, separator.len()))
+    })?;
+
+    Some(buf.drain(..end + len).collect())
+}
+
+/// Returns the job state carried by a server-sent event from the watch endpoint, if it's a
+/// state change
+fn state_from_event(event: &[u8]) -> Option<String> {
+    let event = std::str::from_utf8(event).ok()?;
+    let data: Vec<_> = event
+        .lines()
+        .filter_map(|line| line.strip_prefix("data:"))
+        .map(|data| data.strip_prefix(' ').unwrap_or(data))
+        .collect();
+
+    let event: serde_json::Value = serde_json::from_str(&data.join("\n")).ok()?;
+    if event["type"] != "stateChanged" {
+        return None;
+    }
+
+    Some(event["job"]["state"].as_str()?.to_string())
+}
+
+struct StateWaiter<'a> {
+    expected_states: &'a [&'a str],
+    last_state: Option<String>,
+}
+
+impl StateWaiter<'_> {
+    /// Records the job's current state, returning whether it's one we're waiting for
+    fn update(&mut self, state: String) -> anyhow::Result<bool> {
+        if self.last_state.as_ref() != Some(&state) {
+            if self.last_state.is_some() {
+                info!("Job transitioned to {}", state);
+            }
+            self.last_state = Some(state);
+        }
+
+        let state = self.last_state.as_deref().unwrap();
+        if self.expected_states.contains(&state) {
+            return Ok(true);
+        }
+
+        if state == "Failed" {
+            bail!("Job transitioned to failed");
+        }
+
+        Ok(false)
+    }
 }
 
 async fn wait_for_state(
@@ -45,22 +107,54 @@ async fn wait_for_state(
     pipeline_id: &str,
     expected_states: &[&str],
 ) -> anyhow::Result<()> {
-    let mut last_state: String = get_state(client, pipeline_id).await;
-    while !expected_states.contains(&last_state.as_str()) {
-        let state = get_state(client, pipeline_id).await;
-        if last_state != state {
-            info!("Job transitioned to {}", state);
-            last_state = state;
+    let mut waiter = StateWaiter {
+        expected_states,
+        last_state: None,
+    };
+
+    let job = get_job(client, pipeline_id).await;
+    if waiter.update(job.state)? {
+        return Ok(());
+    }
+
+    // follow the job's events as they happen, falling back to polling if the stream fails
+    match client
+        .watch_job()
+        .pipeline_id(pipeline_id)
+        .job_id(&job.id)
+        .send()
+        .await
+    {
+        Ok(resp) => {
+            let mut stream = resp.into_inner().into_inner();
+            let mut buf = vec![];
+
+            while let Some(Ok(chunk)) = stream.next().await {
+                buf.extend_from_slice(&chunk);
+
+                while let Some(event) = next_event(&mut buf) {
+                    if let Some(state) = state_from_event(&event) {
+                        if waiter.update(state)? {
+                            return Ok(());
+                        }
+                    }
+                }
+            }
+
+            warn!("Job event stream closed; polling for job state");
         }
+        Err(e) => {
+            warn!("Failed to watch job events ({}); polling for job state", e);
+        }
+    }
 
-        if last_state == "Failed" {
-            bail!("Job transitioned to failed");
+    loop {
+        if waiter.update(get_job(client, pipeline_id).await.state)? {
+            return Ok(());
         }
 
         tokio::time::sleep(Duration::from_millis(100)).await;
     }
-
-    Ok(())
 }
 
 async fn wait_for_connect(client: &Client) -> anyhow::Result<()> {
@@ -142,6 +236,53 @@ async fn get_pipelines(client: &Client) -> anyhow::Result<Vec<Pipeline>> {
     Ok(result)
 }
 
+/// Prints the result of a `SHOW` or `DESCRIBE` statement as a table
+fn print_result(result: &SystemQueryResult) {
+    let rows: Vec<Vec<String>> = result
+        .rows
+        .iter()
+        .map(|row| {
+            result
+                .columns
+                .iter()
+                .map(|column| match &row[column] {
+                    serde_json::Value::Null => String::new(),
+                    serde_json::Value::String(s) => s.clone(),
+                    v => v.to_string(),
+                })
+                .collect()
+        })
+        .collect();
+
+    let widths: Vec<_> = result
+        .columns
+        .iter()
+        .enumerate()
+        .map(|(i, column)| {
+            rows.iter()
+                .map(|row| row[i].len())
+                .chain([column.len()])
+                .max()
+                .unwrap()
+        })
+        .collect();
+
+    let format_row = |row: &[String]| {
+        row.iter()
+            .zip(&widths)
+            .map(|(value, width)| format!("{value:width$}"))
+            .collect::<Vec<_>>()
+            .join("  ")
+            .trim_end()
+            .to_string()
+    };
+
+    println!("{}", format_row(&result.columns));
+    for row in &rows {
+        println!("{}", format_row(row));
+    }
+}
+
 async fn run_pipeline(
     client: Arc<Client>,
     name: Option<String>,
@@ -155,21 +296,27 @@ async fn run_pipeline(
     wait_for_connect(&client).await.unwrap();
 
     // validate the pipeline
-    let errors = client
+    let validation = client
         .validate_query()
         .body(ValidateQueryPost::builder().query(&query))
         .send()
         .await?
         .into_inner();
 
-    if !errors.errors.is_empty() {
+    if !validation.errors.is_empty() {
         eprintln!("There were some issues with the provided query");
-        for error in errors.errors {
+        for error in validation.errors {
             eprintln!("  * {error}");
         }
         exit(1);
     }
 
+    // SHOW and DESCRIBE statements are answered by validation, and don't start a pipeline
+    if let Some(result) = validation.result {
+        print_result(&result);
+        exit(0);
+    }
+
     // see if our current pipeline is in the existing pipelines
     let id = match get_pipelines(&client)
         .await?
@@ -359,6 +506,8 @@ pub async fn run(args: RunArgs) {
         },
     );
 
+    arroyo_connectors::plugin::register_plugins().await;
+
     let query = match config().run.query.clone() {
         Some(query) => query,
         None => std::io::read_to_string(args.query).unwrap(),
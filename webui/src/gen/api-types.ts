@@ -34,6 +34,10 @@ export interface paths {
     /** Create a new connection table */
     post: operations["create_connection_table"];
   };
+  "/v1/connection_tables/schemas/infer": {
+    /** Infer the schema of a JSON source by sampling messages from it */
+    post: operations["infer_schema"];
+  };
   "/v1/connection_tables/schemas/test": {
     /** Test a Connection Schema */
     post: operations["test_schema"];
@@ -87,10 +91,33 @@ export interface paths {
     /** List a pipeline's jobs */
     get: operations["get_pipeline_jobs"];
   };
+  "/v1/pipelines/{id}/savepoints": {
+    /** List the savepoints taken of a pipeline */
+    get: operations["get_savepoints"];
+    /**
+     * Take a savepoint of a pipeline's running job
+     * @description The savepoint is written from the job's next checkpoint, after which its state becomes
+     * `ready` and new pipelines can be started from it.
+     */
+    post: operations["create_savepoint"];
+  };
+  "/v1/pipelines/{id}/diff": {
+    /**
+     * Compare a pipeline with an updated query
+     * @description Compiles the query and compares its plan with the plan the pipeline is running, reporting
+     * which operators would be added, removed or changed and whether the state of each can be
+     * restored from the pipeline's checkpoints.
+     */
+    post: operations["diff_pipeline"];
+  };
   "/v1/pipelines/{id}/restart": {
     /** Restart a pipeline */
     post: operations["restart_pipeline"];
   };
+  "/v1/pipelines/{id}/usage": {
+    /** Get a pipeline's daily resource usage */
+    get: operations["get_pipeline_usage"];
+  };
   "/v1/pipelines/{pipeline_id}/jobs/{job_id}/checkpoints": {
     /** List a job's checkpoints */
     get: operations["get_job_checkpoints"];
@@ -111,6 +138,18 @@ export interface paths {
     /** Subscribe to a job's output */
     get: operations["get_job_output"];
   };
+  "/v1/pipelines/{pipeline_id}/jobs/{job_id}/state": {
+    /**
+     * Query the live state of one of a running job's operators
+     * @description Returns the entries of the operator's state table across all of its subtasks, optionally
+     * filtered to a single key. Tables are only queryable once the operator has read them.
+     */
+    get: operations["query_job_state"];
+  };
+  "/v1/pipelines/{pipeline_id}/jobs/{job_id}/watch": {
+    /** Watch a job's state transitions, completed checkpoints and errors */
+    get: operations["watch_job"];
+  };
   "/v1/udfs": {
     /** Get Global UDFs */
     get: operations["get_udfs"];
@@ -131,6 +170,7 @@ export type webhooks = Record<string, never>;
 
 export interface components {
   schemas: {
+    ArrowIpcFormat: Record<string, never>;
     AvroFormat: {
       confluentSchemaRegistry?: boolean;
       intoUnstructuredJson?: boolean;
@@ -166,6 +206,15 @@ export interface components {
     };
     /** @enum {string} */
     CheckpointSpanType: "alignment" | "sync" | "async" | "committing";
+    ColumnLineage: {
+      /** @description the sink column */
+      column: string;
+      /** @description the expressions applied to produce the column, from the innermost outwards; each is in
+       * terms of the columns of its input */
+      expressions: (string)[];
+      /** @description the columns of source tables that the column is derived from */
+      sources: (components["schemas"]["SourceColumn"])[];
+    };
     ConnectionAutocompleteResp: {
       values: {
         [key: string]: (string)[] | undefined;
@@ -238,6 +287,11 @@ export interface components {
     ConnectorCollection: {
       data: (components["schemas"]["Connector"])[];
     };
+    CsvFormat: {
+      delimiter?: string | null;
+      includeHeader?: boolean;
+      quote?: string | null;
+    };
     ErrorResp: {
       error: string;
     };
@@ -247,6 +301,8 @@ export interface components {
       struct: components["schemas"]["StructType"];
     }, {
       list: components["schemas"]["SourceField"];
+    }, {
+      map: components["schemas"]["SourceField"];
     }]>;
     Format: OneOf<[{
       json: components["schemas"]["JsonFormat"];
@@ -260,14 +316,25 @@ export interface components {
       raw_string: components["schemas"]["RawStringFormat"];
     }, {
       raw_bytes: components["schemas"]["RawBytesFormat"];
+    }, {
+      arrow_ipc: components["schemas"]["ArrowIpcFormat"];
+    }, {
+      csv: components["schemas"]["CsvFormat"];
+    }, {
+      xml: components["schemas"]["XmlFormat"];
     }]>;
     Framing: {
-      method: components["schemas"]["FramingMethod"];
+      /** @description The compression of each message, which is decompressed before it's split into records */
+      compression?: components["schemas"]["PayloadCompression"] | null;
+      /** @description How records are split within a message; if unset, each message is a single record */
+      method?: components["schemas"]["FramingMethod"] | null;
     };
     FramingMethod: {
       newline: components["schemas"]["NewlineDelimitedFraming"];
     };
     GlobalUdf: {
+      /** @description The Cargo.lock the UDF was built against */
+      cargoLock?: string | null;
       /** Format: int64 */
       createdAt: number;
       definition: string;
@@ -302,6 +369,20 @@ export interface components {
     JobCollection: {
       data: (components["schemas"]["Job"])[];
     };
+    /** @description An event in the life of a job, as streamed by the watch endpoint */
+    JobEvent: OneOf<[{
+      job: components["schemas"]["Job"];
+      /** @enum {string} */
+      type: "stateChanged";
+    }, {
+      checkpoint: components["schemas"]["Checkpoint"];
+      /** @enum {string} */
+      type: "checkpointCompleted";
+    }, {
+      error: components["schemas"]["JobLogMessage"];
+      /** @enum {string} */
+      type: "error";
+    }]>;
     /** @enum {string} */
     JobLogLevel: "info" | "warn" | "error";
     JobLogMessage: {
@@ -353,6 +434,20 @@ export interface components {
     OperatorCheckpointGroupCollection: {
       data: (components["schemas"]["OperatorCheckpointGroup"])[];
     };
+    /** @enum {string} */
+    OperatorChange: "added" | "removed" | "changed" | "unchanged";
+    OperatorDiff: {
+      change: components["schemas"]["OperatorChange"];
+      /** @description what changed about the operator, for changed operators */
+      changes: (string)[];
+      newDescription?: string | null;
+      nodeId: string;
+      oldDescription?: string | null;
+      operator: string;
+      /** @description why the state is (or may be) incompatible */
+      reasons: (string)[];
+      state: components["schemas"]["StateCompatibility"];
+    };
     OperatorMetricGroup: {
       metricGroups: (components["schemas"]["MetricGroup"])[];
       operatorId: string;
@@ -375,6 +470,12 @@ export interface components {
       starting_after?: string | null;
     };
     ParquetFormat: Record<string, never>;
+    /**
+     * @description A compression codec applied to whole messages, using the streaming (framed) format of
+     * snappy and lz4
+     * @enum {string}
+     */
+    PayloadCompression: "gzip" | "zstd" | "snappy" | "lz4";
     Pipeline: {
       action?: components["schemas"]["StopType"] | null;
       actionInProgress: boolean;
@@ -395,6 +496,15 @@ export interface components {
       data: (components["schemas"]["Pipeline"])[];
       hasMore: boolean;
     };
+    PipelineDiff: {
+      operators: (components["schemas"]["OperatorDiff"])[];
+      /** @description false if the state of any operator is known to be incompatible */
+      stateCompatible: boolean;
+    };
+    PipelineDiffPost: {
+      query: string;
+      udfs?: (components["schemas"]["Udf"])[] | null;
+    };
     PipelineEdge: {
       destId: string;
       edgeType: string;
@@ -404,6 +514,8 @@ export interface components {
     };
     PipelineGraph: {
       edges: (components["schemas"]["PipelineEdge"])[];
+      /** @description column-level lineage for each of the pipeline's sinks */
+      lineage: (components["schemas"]["SinkLineage"])[];
       nodes: (components["schemas"]["PipelineNode"])[];
     };
     PipelineNode: {
@@ -427,13 +539,33 @@ export interface components {
       /** Format: int64 */
       parallelism: number;
       query: string;
+      /** @description start the pipeline from the state in this savepoint */
+      savepointId?: string | null;
       udfs?: (components["schemas"]["Udf"])[] | null;
     };
     PipelineRestart: {
       force?: boolean | null;
     };
+    /** @description Resources consumed by a pipeline's jobs over a single UTC day */
+    PipelineUsage: {
+      /** Format: double */
+      cpuSeconds: number;
+      /** @description the day in YYYY-MM-DD format */
+      day: string;
+      /** Format: int64 */
+      maxMemoryBytes: number;
+      /** Format: int64 */
+      networkBytes: number;
+      /** Format: int64 */
+      stateBytes: number;
+    };
+    PipelineUsageCollection: {
+      data: (components["schemas"]["PipelineUsage"])[];
+    };
     PreviewPost: {
       enableSinks?: boolean;
+      /** @description preview the output of this operator from the query's graph instead of the sinks */
+      nodeId?: string | null;
       query: string;
       udfs?: (components["schemas"]["Udf"])[] | null;
     };
@@ -452,6 +584,33 @@ export interface components {
     };
     RawBytesFormat: Record<string, never>;
     RawStringFormat: Record<string, never>;
+    /**
+     * @description A named snapshot of the state of a pipeline's job, which is kept independently of the job's
+     * checkpoints and can be used to start new pipelines
+     */
+    Savepoint: {
+      /** Format: int64 */
+      createdAt: number;
+      /**
+       * Format: int32
+       * @description the epoch of the checkpoint the savepoint was written from, once it's ready
+       */
+      epoch?: number | null;
+      failureMessage?: string | null;
+      id: string;
+      jobId: string;
+      name: string;
+      pipelineId: string;
+      state: components["schemas"]["SavepointState"];
+    };
+    SavepointCollection: {
+      data: (components["schemas"]["Savepoint"])[];
+    };
+    SavepointPost: {
+      name: string;
+    };
+    /** @enum {string} */
+    SavepointState: "pending" | "ready" | "failed";
     SchemaDefinition: OneOf<[{
       json_schema: string;
     }, {
@@ -466,6 +625,21 @@ export interface components {
     }, {
       raw_schema: string;
     }]>;
+    SchemaInferenceQueryParams: {
+      /**
+       * Format: int32
+       * @description The number of messages to sample from the source (defaults to 100)
+       */
+      sample_size?: number | null;
+    };
+    SinkLineage: {
+      columns: (components["schemas"]["ColumnLineage"])[];
+      sink: string;
+    };
+    SourceColumn: {
+      column: string;
+      table: string;
+    };
     SourceField: {
       fieldName: string;
       fieldType: components["schemas"]["SourceFieldType"];
@@ -475,6 +649,25 @@ export interface components {
       sqlName?: string | null;
       type: components["schemas"]["FieldType"];
     };
+    /**
+     * @description Whether an operator's state in the pipeline's last checkpoint can be restored after the
+     * update
+     * @enum {string}
+     */
+    StateCompatibility: "stateless" | "compatible" | "unknown" | "incompatible";
+    /**
+     * @description An entry of an operator's live state. For keyed tables, the key and value are debug-formatted;
+     * for windowed tables the key is the row's key columns and the value is the row as JSON.
+     */
+    StateEntry: {
+      key: string;
+      /** Format: int32 */
+      subtaskIndex: number;
+      value: string;
+    };
+    StateEntryCollection: {
+      data: (components["schemas"]["StateEntry"])[];
+    };
     /** @enum {string} */
     StopType: "none" | "checkpoint" | "graceful" | "immediate" | "force";
     StructType: {
@@ -501,12 +694,18 @@ export interface components {
     /** @enum {string} */
     TimestampFormat: "rfc3339" | "unix_millis";
     Udf: {
+      cargoLock?: string | null;
       definition: string;
       language?: components["schemas"]["UdfLanguage"];
     };
     /** @enum {string} */
     UdfLanguage: "python" | "rust";
     UdfPost: {
+      /**
+       * @description The Cargo.lock to build a Rust UDF against; if not set, dependencies are resolved when
+       * the UDF is created, and the result is recorded with it
+       */
+      cargoLock?: string | null;
       definition: string;
       description?: string | null;
       language?: components["schemas"]["UdfLanguage"];
@@ -524,6 +723,23 @@ export interface components {
       definition: string;
       language?: components["schemas"]["UdfLanguage"];
     };
+    /**
+     * @description XML documents, from which each element matched by the record path is read as a row. By
+     * default a column is read from the text of the record's child element of the same name, or
+     * else from its attribute of that name; other paths can be given for columns in `fields`. Values
+     * are parsed as the type of their column.
+     */
+    XmlFormat: {
+      /**
+       * @description Paths relative to the record to read columns from, by column name, like `author/name`,
+       * `link/@href` or `.` (the record's own text)
+       */
+      fields?: {
+        [key: string]: string | undefined;
+      };
+      /** @description The path of the elements to read as records, like `/rss/channel/item` or `//entry` */
+      recordPath: string;
+    };
   };
   responses: never;
   parameters: never;
@@ -654,6 +870,28 @@ export interface operations {
       200: never;
     };
   };
+  /** Infer the schema of a JSON source by sampling messages from it */
+  infer_schema: {
+    parameters: {
+      query?: {
+        /** @description The number of messages to sample from the source (defaults to 100) */
+        sample_size?: number | null;
+      };
+    };
+    requestBody: {
+      content: {
+        "application/json": components["schemas"]["ConnectionTablePost"];
+      };
+    };
+    responses: {
+      /** @description Inferred schema */
+      200: {
+        content: {
+          "application/json": components["schemas"]["ConnectionSchema"];
+        };
+      };
+    };
+  };
   /** Test a Connection Table */
   test_connection_table: {
     requestBody: {
@@ -857,6 +1095,55 @@ export interface operations {
       };
     };
   };
+  /** List the savepoints taken of a pipeline */
+  get_savepoints: {
+    parameters: {
+      path: {
+        /** @description Pipeline id */
+        id: string;
+      };
+    };
+    responses: {
+      /** @description The pipeline's savepoints */
+      200: {
+        content: {
+          "application/json": components["schemas"]["SavepointCollection"];
+        };
+      };
+    };
+  };
+  /**
+   * Take a savepoint of a pipeline's running job
+   * @description The savepoint is written from the job's next checkpoint, after which its state becomes
+   * `ready` and new pipelines can be started from it.
+   */
+  create_savepoint: {
+    parameters: {
+      path: {
+        /** @description Pipeline id */
+        id: string;
+      };
+    };
+    requestBody: {
+      content: {
+        "application/json": components["schemas"]["SavepointPost"];
+      };
+    };
+    responses: {
+      /** @description Created savepoint */
+      200: {
+        content: {
+          "application/json": components["schemas"]["Savepoint"];
+        };
+      };
+      /** @description Bad request */
+      400: {
+        content: {
+          "application/json": components["schemas"]["ErrorResp"];
+        };
+      };
+    };
+  };
   /** Restart a pipeline */
   restart_pipeline: {
     parameters: {
@@ -879,6 +1166,56 @@ export interface operations {
       };
     };
   };
+  /**
+   * Compare a pipeline with an updated query
+   * @description Compiles the query and compares its plan with the plan the pipeline is running, reporting
+   * which operators would be added, removed or changed and whether the state of each can be
+   * restored from the pipeline's checkpoints.
+   */
+  diff_pipeline: {
+    parameters: {
+      path: {
+        /** @description Pipeline id */
+        id: string;
+      };
+    };
+    requestBody: {
+      content: {
+        "application/json": components["schemas"]["PipelineDiffPost"];
+      };
+    };
+    responses: {
+      /** @description Differences between the plans */
+      200: {
+        content: {
+          "application/json": components["schemas"]["PipelineDiff"];
+        };
+      };
+      /** @description Bad request */
+      400: {
+        content: {
+          "application/json": components["schemas"]["ErrorResp"];
+        };
+      };
+    };
+  };
+  /** Get a pipeline's daily resource usage */
+  get_pipeline_usage: {
+    parameters: {
+      path: {
+        /** @description Pipeline id */
+        id: string;
+      };
+    };
+    responses: {
+      /** @description Got pipeline usage */
+      200: {
+        content: {
+          "application/json": components["schemas"]["PipelineUsageCollection"];
+        };
+      };
+    };
+  };
   /** List a job's checkpoints */
   get_job_checkpoints: {
     parameters: {
@@ -963,6 +1300,45 @@ export interface operations {
       };
     };
   };
+  /**
+   * Query the live state of one of a running job's operators
+   * @description Returns the entries of the operator's state table across all of its subtasks, optionally
+   * filtered to a single key. Tables are only queryable once the operator has read them.
+   */
+  query_job_state: {
+    parameters: {
+      query: {
+        /** @description The operator whose state to query */
+        operator_id: string;
+        /** @description The name of the operator's state table */
+        table: string;
+        /** @description Only return the entries for this key */
+        key?: string | null;
+        /** @description The maximum number of entries to return (defaults to 100) */
+        limit?: number | null;
+      };
+      path: {
+        /** @description Pipeline id */
+        pipeline_id: string;
+        /** @description Job id */
+        job_id: string;
+      };
+    };
+    responses: {
+      /** @description Got the operator's state */
+      200: {
+        content: {
+          "application/json": components["schemas"]["StateEntryCollection"];
+        };
+      };
+      /** @description Bad request */
+      400: {
+        content: {
+          "application/json": components["schemas"]["ErrorResp"];
+        };
+      };
+    };
+  };
   /** Subscribe to a job's output */
   get_job_output: {
     parameters: {
@@ -978,6 +1354,21 @@ export interface operations {
       200: never;
     };
   };
+  /** Watch a job's state transitions, completed checkpoints and errors */
+  watch_job: {
+    parameters: {
+      path: {
+        /** @description Pipeline id */
+        pipeline_id: string;
+        /** @description Job id */
+        job_id: string;
+      };
+    };
+    responses: {
+      /** @description Job events as 'text/event-stream'; each event's data is a JobEvent */
+      200: never;
+    };
+  };
   /** Get Global UDFs */
   get_udfs: {
     responses: {
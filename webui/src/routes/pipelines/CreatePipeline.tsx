@@ -67,6 +67,8 @@ function useQuery() {
 
 export interface PreviewOptions {
   enableSinks: boolean;
+  // if set, preview the output of this operator instead of the sinks
+  nodeId?: string;
 }
 
 export function CreatePipeline() {
@@ -307,6 +309,7 @@ export function CreatePipeline() {
         query: queryInput,
         udfs,
         enableSinks: previewOptions.enableSinks,
+        nodeId: previewOptions.nodeId,
       },
     });
 
@@ -388,7 +391,16 @@ export function CreatePipeline() {
           }}
           overflow="auto"
         >
-          <PipelineGraphViewer graph={queryValidation.graph} setActiveOperator={() => {}} />
+          <PipelineGraphViewer
+            graph={queryValidation.graph}
+            activeOperator={previewOptions.nodeId}
+            setActiveOperator={op =>
+              setPreviewOptions({
+                ...previewOptions,
+                nodeId: previewOptions.nodeId == op ? undefined : op,
+              })
+            }
+          />
         </Box>
       </TabPanel>
     );
@@ -243,6 +243,19 @@ const PipelineEditorTabs: React.FC<PipelineEditorTabsProps> = ({
                   By default, sinks are disabled in preview mode
                 </FormHelperText>
               </FormControl>
+              <FormControl>
+                <Checkbox
+                  isChecked={previewOptions.nodeId != undefined}
+                  isDisabled={previewOptions.nodeId == undefined}
+                  onChange={() => setPreviewOptions({ ...previewOptions, nodeId: undefined })}
+                >
+                  Preview operator {previewOptions.nodeId ?? ''}
+                </Checkbox>
+                <FormHelperText fontSize={'xs'}>
+                  Select an operator in the pipeline graph to preview its output instead of the
+                  sinks
+                </FormHelperText>
+              </FormControl>
             </Stack>
           </PopoverBody>
         </PopoverContent>